@@ -18,7 +18,8 @@ pub fn run() {
     // Add plugins
     let builder = builder
         .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_notification::init());
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_deep_link::init());
 
     // Add barcode scanner plugin on mobile platforms
     #[cfg(any(target_os = "android", target_os = "ios"))]
@@ -26,6 +27,14 @@ pub fn run() {
 
     builder
         .setup(|app| {
+            // Register the remoshell:// scheme with the OS in development
+            // builds; installed builds register it from tauri.conf.json
+            #[cfg(all(debug_assertions, any(target_os = "linux", target_os = "windows")))]
+            {
+                use tauri_plugin_deep_link::DeepLinkExt;
+                app.deep_link().register_all()?;
+            }
+
             #[cfg(all(debug_assertions, not(any(target_os = "android", target_os = "ios"))))]
             {
                 if let Some(window) = app.get_webview_window("main") {
@@ -10,7 +10,8 @@ import { OfflineIndicator } from './components/offline';
 import { ToastContainer } from './components/notifications';
 import { getOrchestrator } from './lib/orchestration/ConnectionOrchestrator';
 import { initializeAppLifecycle } from './lib/lifecycle/AppLifecycle';
-import { parsePairingData, isPairingExpired, isShortPairingCode, lookupPairingCode, type PairingData } from './lib/scanner/BarcodeScanner';
+import { parsePairingData, isPairingExpired, clockSkewWarning, isShortPairingCode, lookupPairingCode, type PairingData } from './lib/scanner/BarcodeScanner';
+import { parseDeviceId, fingerprintWords, randomart } from './lib/fingerprint';
 import { setSignalingUrl } from './config';
 
 // Wrap lazy() with chunk-load-failure recovery: on stale chunk 404,
@@ -316,9 +317,18 @@ const DevicesView: Component = () => {
   const [pairingError, setPairingError] = createSignal<string | null>(null);
   const [selectedDeviceId, setSelectedDeviceId] = createSignal<string | null>(null);
   const [scannerKey, setScannerKey] = createSignal(0);
+  // Numeric codes (`remoshell pair --numeric`) for devices without a camera
+  const [numericCode, setNumericCode] = createSignal(false);
   const [customDeviceName, setCustomDeviceName] = createSignal('');
   // Connection progress feedback: null | 'looking-up' | 'connecting' | 'waiting'
   const [connectingStatus, setConnectingStatus] = createSignal<string | null>(null);
+  // Fingerprint of the device being paired, for out-of-band comparison
+  const [pairingFingerprint, setPairingFingerprint] = createSignal<{
+    words: string[];
+    art: string;
+  } | null>(null);
+  // Warning when this device's clock disagrees with the daemon's
+  const [clockSkew, setClockSkew] = createSignal<string | null>(null);
   let connectionTimeoutId: ReturnType<typeof setTimeout> | undefined;
 
   // Listen for peer:connected to clear the waiting state and timeout
@@ -373,6 +383,8 @@ const DevicesView: Component = () => {
     try {
       // Clear any previous error and start progress
       setPairingError(null);
+      setPairingFingerprint(null);
+      setClockSkew(null);
       setConnectingStatus('looking-up');
 
       let pairingData: PairingData;
@@ -410,7 +422,7 @@ const DevicesView: Component = () => {
         return;
       }
 
-      // Validate expiry
+      // Validate expiry, judged against the daemon's clock when it sent one
       if (isPairingExpired(pairingData)) {
         setPairingError('Pairing code has expired');
         setConnectingStatus(null);
@@ -418,6 +430,21 @@ const DevicesView: Component = () => {
         return;
       }
 
+      const skewWarning = clockSkewWarning(pairingData);
+      if (skewWarning) {
+        setClockSkew(skewWarning);
+        console.warn('[Pairing] Clock skew:', pairingData.clock_offset, 'seconds');
+      }
+
+      // Show the daemon's fingerprint so it can be compared with the TUI
+      const deviceIdBytes = parseDeviceId(pairingData.device_id);
+      if (deviceIdBytes) {
+        setPairingFingerprint({
+          words: await fingerprintWords(deviceIdBytes),
+          art: randomart(deviceIdBytes),
+        });
+      }
+
       // Update signaling URL from pairing data
       if (pairingData.relay_url) {
         setSignalingUrl(pairingData.relay_url);
@@ -546,6 +573,7 @@ const DevicesView: Component = () => {
           <ErrorBoundary fallback={(err, reset) => <ErrorFallback error={err} reset={reset} />}>
             <Suspense fallback={<LoadingFallback />}>
               <PairingCodeInput
+                segmentLengths={numericCode() ? [3, 3, 4] : undefined}
                 autoFocus={false}
                 onComplete={handlePairingComplete}
                 error={!!pairingError()}
@@ -553,6 +581,16 @@ const DevicesView: Component = () => {
               />
             </Suspense>
           </ErrorBoundary>
+          <button
+            class="pairing-code-toggle"
+            data-testid="pairing-code-toggle"
+            onClick={() => {
+              setPairingError(null);
+              setNumericCode(n => !n);
+            }}
+          >
+            {numericCode() ? 'Use a letter code instead' : 'Use a numeric code instead'}
+          </button>
         </div>
       </Show>
 
@@ -565,6 +603,22 @@ const DevicesView: Component = () => {
             {connectingStatus() === 'connecting' && 'Connecting to signaling server...'}
             {connectingStatus() === 'waiting' && 'Waiting for device to respond...'}
           </span>
+          <Show when={clockSkew()}>
+            <span class="devices-clock-skew" data-testid="pairing-clock-skew">
+              {clockSkew()}
+            </span>
+          </Show>
+          <Show when={connectingStatus() === 'waiting' && pairingFingerprint()}>
+            {(fingerprint) => (
+              <div class="devices-fingerprint" data-testid="pairing-fingerprint">
+                <span class="devices-fingerprint__hint">
+                  Check that these match the pairing screen on the device:
+                </span>
+                <span class="devices-fingerprint__words">{fingerprint().words.join(' ')}</span>
+                <pre class="devices-fingerprint__art">{fingerprint().art}</pre>
+              </div>
+            )}
+          </Show>
         </div>
       </Show>
 
@@ -11,6 +11,9 @@ import {
   isPairingExpired,
   secondsUntilExpiry,
   createPairingQRContent,
+  measureClockOffset,
+  clockSkewWarning,
+  CLOCK_SKEW_WARNING_SECONDS,
   isShortPairingCode,
   lookupPairingCode,
   type PairingData,
@@ -188,6 +191,69 @@ describe('Pairing Flow Integration', () => {
     });
   });
 
+  describe('Clock Skew', () => {
+    const payload = (issuedAt: number, lifetime = 300): PairingData => ({
+      device_id: 'test',
+      public_key: 'test',
+      relay_url: 'wss://test.com',
+      issued_at: issuedAt,
+      expires: issuedAt + lifetime,
+    });
+
+    it('should treat time on screen as agreeing clocks', () => {
+      const now = 1_700_000_000;
+      expect(measureClockOffset(payload(now - 120), now)).toBe(0);
+      expect(measureClockOffset(payload(now), now)).toBe(0);
+    });
+
+    it('should measure skew outside the validity window', () => {
+      const now = 1_700_000_000;
+      // Daemon clock 10 minutes ahead: the code looks issued in the future
+      expect(measureClockOffset(payload(now + 600), now)).toBe(-600);
+      // Daemon clock an hour behind: the code looks long expired
+      expect(measureClockOffset(payload(now - 3600), now)).toBe(3600);
+    });
+
+    it('should ignore payloads without an issue time', () => {
+      const legacy: PairingData = {
+        device_id: 'test',
+        public_key: 'test',
+        relay_url: 'wss://test.com',
+        expires: 12345,
+      };
+      expect(measureClockOffset(legacy)).toBe(0);
+      expect(clockSkewWarning(legacy)).toBeNull();
+    });
+
+    it('should judge expiry against the daemon clock', () => {
+      // Daemon clock an hour behind ours
+      const now = Math.floor(Date.now() / 1000);
+      const result = parsePairingData(JSON.stringify(payload(now - 3600)));
+
+      expect(result.success).toBe(true);
+      if (result.success) {
+        expect(result.data.clock_offset).toBe(3600);
+        expect(isPairingExpired(result.data)).toBe(false);
+        expect(secondsUntilExpiry(result.data)).toBeGreaterThan(290);
+        expect(clockSkewWarning(result.data)).toContain('60 minutes ahead of');
+      }
+    });
+
+    it('should warn only beyond the threshold', () => {
+      const within = { ...payload(0), clock_offset: -CLOCK_SKEW_WARNING_SECONDS };
+      expect(clockSkewWarning(within)).toBeNull();
+
+      const behind = { ...payload(0), clock_offset: -(CLOCK_SKEW_WARNING_SECONDS + 60) };
+      expect(clockSkewWarning(behind)).toContain('2 minutes behind');
+    });
+
+    it('should leave the offset out of QR content', () => {
+      const content = createPairingQRContent({ ...payload(1000), clock_offset: 42 });
+      expect(JSON.parse(content)).not.toHaveProperty('clock_offset');
+      expect(JSON.parse(content).issued_at).toBe(1000);
+    });
+  });
+
   describe('Complete Pairing to Connection Flow', () => {
     it('should update config with relay URL from pairing data', async () => {
       const pairingData: PairingData = {
@@ -917,6 +983,42 @@ describe('Pairing Flow Integration', () => {
       });
     });
 
+    describe('parsePairingData with numeric code', () => {
+      it('should normalize a numeric code with a valid check digit', () => {
+        const result = parsePairingData('7992739875');
+        expect(result.success).toBe(false);
+        if (!result.success) {
+          expect(result.shortCode).toBe('799-273-9875');
+          expect(result.error).toBe('SHORT_CODE');
+        }
+      });
+
+      it('should accept spaces and hyphens', () => {
+        const result = parsePairingData('799 273-9875');
+        expect(result.success).toBe(false);
+        if (!result.success) {
+          expect(result.shortCode).toBe('799-273-9875');
+        }
+      });
+
+      it('should reject a mistyped digit', () => {
+        const result = parsePairingData('799-273-9876');
+        expect(result.success).toBe(false);
+        if (!result.success) {
+          expect(result.shortCode).toBeUndefined();
+          expect(result.error).toContain('check digit');
+        }
+      });
+
+      it('should extract a numeric code from web app URL', () => {
+        const result = parsePairingData('https://example.com/?peer=799-273-9875');
+        expect(result.success).toBe(false);
+        if (!result.success) {
+          expect(result.shortCode).toBe('799-273-9875');
+        }
+      });
+    });
+
     describe('parsePairingData legacy format still works', () => {
       it('should still parse valid JSON pairing data', () => {
         const data: PairingData = {
@@ -511,6 +511,38 @@ describe('Terminal Data Flow Integration', () => {
       expect(outputEvent?.[0].data).toEqual({ output: errorText });
     });
 
+    it('should mark skipped output in the terminal', () => {
+      const sessionStore = getSessionStore();
+
+      const sessionId = sessionStore.createSession({
+        peerId: 'remote-peer',
+        title: 'Test Terminal',
+      });
+
+      orchestrator.setSessionPeer(sessionId, 'remote-peer');
+      sessionStore.setSessionStatus(sessionId, 'connected');
+
+      const message = Msg.SessionOutputTruncated({
+        session_id: sessionId,
+        dropped_chunks: 3,
+      });
+
+      const envelope = createEnvelope(1, message);
+      const encoded = encodeEnvelope(envelope);
+
+      const eventHandler = vi.fn();
+      sessionStore.subscribe(eventHandler);
+
+      mockWebRTC._simulateData('remote-peer', encoded, 'terminal');
+
+      const outputEvent = eventHandler.mock.calls.find(
+        (call) => call[0].type === 'session:output'
+      );
+
+      expect(outputEvent).toBeDefined();
+      expect(outputEvent?.[0].data.output).toContain('[output truncated: 3 chunks skipped]');
+    });
+
     it('should handle incoming SessionClosed from remote peer', () => {
       const consoleLog = vi.spyOn(console, 'log').mockImplementation(() => {});
       const sessionStore = getSessionStore();
@@ -529,6 +561,7 @@ describe('Terminal Data Flow Integration', () => {
         exit_code: 0,
         signal: null,
         reason: 'Process exited',
+        close_reason: 'Exited',
       });
 
       const envelope = createEnvelope(1, message);
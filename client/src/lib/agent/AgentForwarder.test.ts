@@ -0,0 +1,60 @@
+import { describe, it, expect, vi } from 'vitest';
+import { AgentForwarder } from './AgentForwarder';
+import { Msg } from '../protocol';
+
+function createForwarder(accept: boolean) {
+  const bridge = {
+    agentConnect: vi.fn().mockResolvedValue(undefined),
+    agentWrite: vi.fn().mockResolvedValue(undefined),
+    agentClose: vi.fn().mockResolvedValue(undefined),
+  };
+  const send = vi.fn();
+  const confirm = vi.fn().mockResolvedValue(accept);
+  const forwarder = new AgentForwarder({ bridge, send, confirm });
+  return { forwarder, bridge, send, confirm };
+}
+
+describe('AgentForwarder', () => {
+  it('relays a confirmed channel in both directions', async () => {
+    const { forwarder, bridge, send, confirm } = createForwarder(true);
+
+    await forwarder.handleMessage(Msg.AgentOpen({ session_id: 'sess-1', channel_id: 1 }));
+    expect(confirm).toHaveBeenCalledWith({ session_id: 'sess-1', channel_id: 1 });
+    expect(bridge.agentConnect).toHaveBeenCalledWith('sess-1', 1);
+
+    const request = new Uint8Array([0, 0, 0, 1, 11]);
+    await forwarder.handleMessage(
+      Msg.AgentData({ session_id: 'sess-1', channel_id: 1, data: request })
+    );
+    expect(bridge.agentWrite).toHaveBeenCalledWith('sess-1', 1, request);
+
+    forwarder.handleAgentEvent({
+      type: 'Data',
+      payload: { session_id: 'sess-1', channel_id: 1, data: 'AAAAAQU=' },
+    });
+    expect(send).toHaveBeenCalledWith(
+      Msg.AgentData({ session_id: 'sess-1', channel_id: 1, data: new Uint8Array([0, 0, 0, 1, 5]) })
+    );
+
+    forwarder.handleAgentEvent({ type: 'Closed', payload: { session_id: 'sess-1', channel_id: 1 } });
+    expect(send).toHaveBeenLastCalledWith(Msg.AgentClose({ session_id: 'sess-1', channel_id: 1 }));
+  });
+
+  it('closes a declined channel without touching the local agent', async () => {
+    const { forwarder, bridge, send } = createForwarder(false);
+
+    await forwarder.handleMessage(Msg.AgentOpen({ session_id: 'sess-1', channel_id: 2 }));
+    expect(bridge.agentConnect).not.toHaveBeenCalled();
+    expect(send).toHaveBeenCalledWith(Msg.AgentClose({ session_id: 'sess-1', channel_id: 2 }));
+
+    await forwarder.handleMessage(
+      Msg.AgentData({ session_id: 'sess-1', channel_id: 2, data: new Uint8Array([1]) })
+    );
+    expect(bridge.agentWrite).not.toHaveBeenCalled();
+  });
+
+  it('ignores other messages', async () => {
+    const { forwarder } = createForwarder(true);
+    expect(await forwarder.handleMessage(Msg.Ping({ timestamp: 1, payload: new Uint8Array(0) }))).toBe(false);
+  });
+});
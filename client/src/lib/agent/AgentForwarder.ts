@@ -0,0 +1,126 @@
+/**
+ * AgentForwarder - Relays forwarded SSH agent channels to the local agent
+ * Every AgentOpen from the daemon is confirmed by the user before any
+ * request reaches the local agent; declined channels are closed
+ */
+
+import { Msg, type AgentOpen, type Message } from '../protocol';
+import type { AgentEvent, TauriIPCBridge } from '../tauri';
+import { base64ToUint8Array } from '../tauri';
+
+/**
+ * Asks the user whether a remote process may use the local agent
+ */
+export type AgentConfirmFn = (request: AgentOpen) => boolean | Promise<boolean>;
+
+/**
+ * Sends a protocol message to the daemon
+ */
+export type AgentSendFn = (message: Message) => void;
+
+/**
+ * Options for creating an agent forwarder
+ */
+export interface AgentForwarderOptions {
+  /** Bridge to the local agent */
+  bridge: Pick<TauriIPCBridge, 'agentConnect' | 'agentWrite' | 'agentClose'>;
+  /** Sends messages to the daemon */
+  send: AgentSendFn;
+  /** Confirmation prompt shown for each new channel */
+  confirm: AgentConfirmFn;
+}
+
+function channelKey(sessionId: string, channelId: number): string {
+  return `${sessionId}:${channelId}`;
+}
+
+/**
+ * Relays agent messages between the daemon and the local agent
+ */
+export class AgentForwarder {
+  private readonly bridge: AgentForwarderOptions['bridge'];
+  private readonly send: AgentSendFn;
+  private readonly confirm: AgentConfirmFn;
+  private readonly channels = new Set<string>();
+
+  constructor(options: AgentForwarderOptions) {
+    this.bridge = options.bridge;
+    this.send = options.send;
+    this.confirm = options.confirm;
+  }
+
+  /**
+   * Handle a message from the daemon
+   *
+   * Returns true if the message was an agent message.
+   */
+  async handleMessage(message: Message): Promise<boolean> {
+    switch (message.type) {
+      case 'AgentOpen':
+        await this.open(message.data);
+        return true;
+      case 'AgentData': {
+        const { session_id, channel_id, data } = message.data;
+        if (this.channels.has(channelKey(session_id, channel_id))) {
+          await this.bridge.agentWrite(session_id, channel_id, data);
+        }
+        return true;
+      }
+      case 'AgentClose': {
+        const { session_id, channel_id } = message.data;
+        if (this.channels.delete(channelKey(session_id, channel_id))) {
+          await this.bridge.agentClose(session_id, channel_id);
+        }
+        return true;
+      }
+      default:
+        return false;
+    }
+  }
+
+  /**
+   * Handle activity reported by the local agent
+   */
+  handleAgentEvent(event: AgentEvent): void {
+    const { session_id, channel_id } = event.payload;
+    const key = channelKey(session_id, channel_id);
+    if (!this.channels.has(key)) {
+      return;
+    }
+
+    if (event.type === 'Data') {
+      this.send(
+        Msg.AgentData({ session_id, channel_id, data: base64ToUint8Array(event.payload.data) })
+      );
+    } else {
+      this.channels.delete(key);
+      this.send(Msg.AgentClose({ session_id, channel_id }));
+    }
+  }
+
+  /**
+   * Forget every channel, e.g. on disconnect
+   */
+  reset(): void {
+    this.channels.clear();
+  }
+
+  private async open(request: AgentOpen): Promise<void> {
+    const { session_id, channel_id } = request;
+    let accepted = false;
+    try {
+      accepted = await this.confirm(request);
+      if (accepted) {
+        await this.bridge.agentConnect(session_id, channel_id);
+        this.channels.add(channelKey(session_id, channel_id));
+      }
+    } catch (error) {
+      console.error('Failed to open SSH agent channel:', error);
+      accepted = false;
+    }
+
+    if (!accepted) {
+      this.send(Msg.AgentClose({ session_id, channel_id }));
+    }
+  }
+}
@@ -0,0 +1,11 @@
+/**
+ * Agent module exports
+ * Provides SSH agent forwarding with per-use confirmation
+ */
+
+export {
+  AgentForwarder,
+  type AgentConfirmFn,
+  type AgentSendFn,
+  type AgentForwarderOptions,
+} from './AgentForwarder';
@@ -0,0 +1,38 @@
+import { describe, it, expect } from 'vitest';
+import { fingerprintWords, parseDeviceId, randomart } from './Fingerprint';
+import { encodeBase58 } from '../scanner';
+
+describe('Fingerprint', () => {
+  it('matches the BIP39 test vectors', async () => {
+    const cases: Array<[number, string]> = [
+      [0x00, 'abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about'],
+      [0x7f, 'legal winner thank year wave sausage worth useful legal winner thank yellow'],
+      [0x80, 'letter advice cage absurd amount doctor acoustic avoid letter advice cage above'],
+      [0xff, 'zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong'],
+    ];
+
+    for (const [byte, expected] of cases) {
+      const words = await fingerprintWords(new Uint8Array(16).fill(byte));
+      expect(words.join(' ')).toBe(expected);
+    }
+  });
+
+  it('renders the same randomart as the daemon', () => {
+    const lines = randomart(new Uint8Array(16)).split('\n');
+
+    expect(lines).toHaveLength(11);
+    expect(lines[0]).toBe('+---[RemoShell]---+');
+    expect(lines[1]).toBe('|E....            |');
+    expect(lines[2]).toBe('|     .           |');
+    expect(lines[5]).toBe('|        S        |');
+    expect(lines[10]).toBe('+-----------------+');
+  });
+
+  it('parses hex fingerprints and base58 device IDs', () => {
+    const bytes = new Uint8Array(16).fill(0x7f);
+
+    expect(parseDeviceId('7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f')).toEqual(bytes);
+    expect(parseDeviceId(encodeBase58(bytes))).toEqual(bytes);
+    expect(parseDeviceId('not-a-device')).toBeNull();
+  });
+});
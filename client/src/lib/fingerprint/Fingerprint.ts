@@ -0,0 +1,118 @@
+/**
+ * Fingerprint - Human-comparable fingerprints of device IDs
+ * Mirrors `DeviceId::randomart` and `DeviceId::fingerprint_words` in the
+ * protocol crate so both ends show the same picture and words
+ */
+
+import { decodeBase58, isValidBase58 } from '../scanner';
+import { BIP39_ENGLISH } from './wordlist';
+
+/** Length of a device ID in bytes */
+export const DEVICE_ID_LENGTH = 16;
+
+/** Number of words in a word fingerprint */
+export const FINGERPRINT_WORD_COUNT = 12;
+
+/** Bits encoded by each BIP39 word */
+const BITS_PER_WORD = 11;
+
+/** Randomart board size, as in OpenSSH */
+const RANDOMART_WIDTH = 17;
+const RANDOMART_HEIGHT = 9;
+
+/** Randomart symbols for increasing visit counts, as in OpenSSH */
+const RANDOMART_SYMBOLS = ' .o+=*BOX@%&#/^';
+
+/** Title shown in the top border of the randomart */
+const RANDOMART_TITLE = '[RemoShell]';
+
+/**
+ * Parse a device ID given as base58 (as in pairing data) or as a hex
+ * fingerprint (`a1b2:c3d4:...`)
+ *
+ * Returns null if the string is not a device ID.
+ */
+export function parseDeviceId(id: string): Uint8Array | null {
+  const hex = id.replace(/:/g, '');
+  if (hex.length === DEVICE_ID_LENGTH * 2 && /^[0-9a-fA-F]+$/.test(hex)) {
+    return Uint8Array.from(hex.match(/../g)!, (byte) => parseInt(byte, 16));
+  }
+
+  if (isValidBase58(id)) {
+    const bytes = decodeBase58(id);
+    if (bytes.length === DEVICE_ID_LENGTH) {
+      return bytes;
+    }
+  }
+  return null;
+}
+
+/**
+ * Render a device ID as OpenSSH-style randomart
+ *
+ * Returns 11 lines joined with newlines, without a trailing newline.
+ */
+export function randomart(deviceId: Uint8Array): string {
+  const field = Array.from({ length: RANDOMART_HEIGHT }, () =>
+    new Array<number>(RANDOMART_WIDTH).fill(0)
+  );
+  const startX = Math.floor(RANDOMART_WIDTH / 2);
+  const startY = Math.floor(RANDOMART_HEIGHT / 2);
+  let x = startX;
+  let y = startY;
+
+  for (const byte of deviceId) {
+    // Each byte gives four moves, least significant bit pair first
+    let bits = byte;
+    for (let i = 0; i < 4; i++) {
+      x = bits & 0x1 ? Math.min(x + 1, RANDOMART_WIDTH - 1) : Math.max(x - 1, 0);
+      y = bits & 0x2 ? Math.min(y + 1, RANDOMART_HEIGHT - 1) : Math.max(y - 1, 0);
+      field[y][x]++;
+      bits >>= 2;
+    }
+  }
+
+  const padding = RANDOMART_WIDTH - RANDOMART_TITLE.length;
+  const left = Math.floor(padding / 2);
+  const lines = [`+${'-'.repeat(left)}${RANDOMART_TITLE}${'-'.repeat(padding - left)}+`];
+  field.forEach((counts, row) => {
+    const cells = counts.map((count, col) => {
+      if (col === x && row === y) return 'E';
+      if (col === startX && row === startY) return 'S';
+      return RANDOMART_SYMBOLS[Math.min(count, RANDOMART_SYMBOLS.length - 1)];
+    });
+    lines.push(`|${cells.join('')}|`);
+  });
+  lines.push(`+${'-'.repeat(RANDOMART_WIDTH)}+`);
+  return lines.join('\n');
+}
+
+/**
+ * Encode a device ID as BIP39 English words
+ *
+ * The ID is encoded exactly like 128 bits of BIP39 entropy, including the
+ * 4-bit SHA-256 checksum.
+ */
+export async function fingerprintWords(deviceId: Uint8Array): Promise<string[]> {
+  const digest = new Uint8Array(await crypto.subtle.digest('SHA-256', deviceId));
+
+  const bits: number[] = [];
+  for (const byte of deviceId) {
+    for (let i = 7; i >= 0; i--) {
+      bits.push((byte >> i) & 1);
+    }
+  }
+  // The checksum is the first 4 bits of the digest
+  for (let i = 7; i >= 4; i--) {
+    bits.push((digest[0] >> i) & 1);
+  }
+
+  const words: string[] = [];
+  for (let i = 0; i < FINGERPRINT_WORD_COUNT; i++) {
+    const index = bits
+      .slice(i * BITS_PER_WORD, (i + 1) * BITS_PER_WORD)
+      .reduce((acc, bit) => (acc << 1) | bit, 0);
+    words.push(BIP39_ENGLISH[index]);
+  }
+  return words;
+}
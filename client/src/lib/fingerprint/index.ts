@@ -0,0 +1,12 @@
+/**
+ * Fingerprint module exports
+ * Provides randomart and word fingerprints for comparing device identities
+ */
+
+export {
+  parseDeviceId,
+  randomart,
+  fingerprintWords,
+  DEVICE_ID_LENGTH,
+  FINGERPRINT_WORD_COUNT,
+} from './Fingerprint';
@@ -0,0 +1,82 @@
+import { describe, it, expect, vi, afterEach } from 'vitest';
+import { applyMeshHosts, fetchMeshHosts, meshGroupId, type MeshHost } from './MeshStatus';
+import { createDeviceStore } from '../../stores/devices';
+
+const GROUP = '0123456789abcdef0123456789abcdef';
+const NOW = 1_700_000_000;
+
+function host(overrides: Partial<MeshHost> = {}): MeshHost {
+  return {
+    group: GROUP,
+    device_id: '7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f',
+    hostname: 'nas',
+    online: true,
+    sessions: 1,
+    version: '0.1.0',
+    timestamp: NOW - 30,
+    ttl_secs: 180,
+    ...overrides,
+  };
+}
+
+function signed(record: MeshHost) {
+  return { payload: JSON.stringify(record), public_key: 'a2V5', signature: 'c2ln' };
+}
+
+describe('MeshStatus', () => {
+  const originalFetch = globalThis.fetch;
+
+  afterEach(() => {
+    globalThis.fetch = originalFetch;
+  });
+
+  it('derives a stable group from the admin key', async () => {
+    const key = new Uint8Array(32).fill(1);
+    const group = await meshGroupId(key);
+
+    expect(group).toMatch(/^[0-9a-f]{32}$/);
+    expect(await meshGroupId(key)).toBe(group);
+    expect(await meshGroupId(new Uint8Array(32).fill(2))).not.toBe(group);
+  });
+
+  it('reads current records of the group', async () => {
+    globalThis.fetch = vi.fn().mockResolvedValue({
+      ok: true,
+      status: 200,
+      json: () =>
+        Promise.resolve({
+          hosts: [
+            signed(host({ hostname: 'web' })),
+            signed(host({ hostname: 'nas' })),
+            signed(host({ hostname: 'old', timestamp: NOW - 3600 })),
+            signed(host({ hostname: 'other', group: 'ffffffffffffffffffffffffffffffff' })),
+            { payload: 'not json' },
+          ],
+        }),
+    });
+
+    const hosts = await fetchMeshHosts('wss://signal.example.com/', GROUP, NOW);
+
+    expect(globalThis.fetch).toHaveBeenCalledWith(`https://signal.example.com/mesh/${GROUP}`);
+    expect(hosts.map((h) => h.hostname)).toEqual(['nas', 'web']);
+  });
+
+  it('rejects failed lookups', async () => {
+    globalThis.fetch = vi.fn().mockResolvedValue({ ok: false, status: 500 });
+
+    await expect(fetchMeshHosts('wss://signal.example.com', GROUP, NOW)).rejects.toThrow('500');
+  });
+
+  it('updates the status of paired devices', () => {
+    const store = createDeviceStore();
+    store.addDevice({ id: '7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f', name: 'NAS', platform: 'linux' });
+    store.addDevice({ id: '0101:0101:0101:0101:0101:0101:0101:0101', name: 'Web', platform: 'linux' });
+
+    expect(applyMeshHosts(store, [host()])).toBe(1);
+    expect(store.getDevice('7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f')?.status).toBe('online');
+    expect(store.getDevice('0101:0101:0101:0101:0101:0101:0101:0101')?.status).toBe('offline');
+
+    expect(applyMeshHosts(store, [host({ online: false })])).toBe(1);
+    expect(store.getDevice('7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f:7f7f')?.status).toBe('offline');
+  });
+});
@@ -0,0 +1,96 @@
+/**
+ * MeshStatus - Which of the admin's hosts are online
+ * Daemons configured with `mesh.admin_device` publish signed presence
+ * records to the signaling server under a group named by the admin device's
+ * public key. The signaling server checks each record's signature before
+ * storing it, so the client only has to read the group.
+ */
+
+import { parseDeviceId } from '../fingerprint';
+import type { DeviceStore } from '../../stores/devices';
+
+/** Domain separator hashed with the admin device's key, as in the daemon */
+const GROUP_CONTEXT = 'remoshell-mesh-v1';
+
+/** A daemon's presence and status, as published to the mesh */
+export interface MeshHost {
+  group: string;
+  device_id: string;
+  hostname: string;
+  online: boolean;
+  sessions: number;
+  version: string;
+  /** Unix timestamp of the record */
+  timestamp: number;
+  /** Seconds after `timestamp` until the record is stale */
+  ttl_secs: number;
+}
+
+/**
+ * Name of the mesh group of the daemons that trust the given admin device
+ */
+export async function meshGroupId(adminPublicKey: Uint8Array): Promise<string> {
+  const context = new TextEncoder().encode(GROUP_CONTEXT);
+  const data = new Uint8Array(context.length + adminPublicKey.length);
+  data.set(context);
+  data.set(adminPublicKey, context.length);
+  const digest = new Uint8Array(await crypto.subtle.digest('SHA-256', data));
+  return [...digest.slice(0, 16)].map((b) => b.toString(16).padStart(2, '0')).join('');
+}
+
+/**
+ * Read the current records of a mesh group from the signaling server
+ *
+ * Stale and malformed records are left out.
+ */
+export async function fetchMeshHosts(
+  signalingUrl: string,
+  group: string,
+  now: number = Math.floor(Date.now() / 1000)
+): Promise<MeshHost[]> {
+  const httpUrl = signalingUrl.replace('wss://', 'https://').replace('ws://', 'http://');
+  const resp = await fetch(`${httpUrl.replace(/\/$/, '')}/mesh/${group}`);
+  if (!resp.ok) {
+    throw new Error(`Mesh lookup failed (${resp.status})`);
+  }
+
+  const body = (await resp.json()) as { hosts?: Array<{ payload?: unknown }> };
+  const hosts: MeshHost[] = [];
+  for (const signed of body.hosts ?? []) {
+    if (typeof signed.payload !== 'string') continue;
+    try {
+      const host = JSON.parse(signed.payload) as MeshHost;
+      if (host.group === group && now <= host.timestamp + host.ttl_secs) {
+        hosts.push(host);
+      }
+    } catch {
+      // Skip malformed records
+    }
+  }
+  return hosts.sort((a, b) => a.hostname.localeCompare(b.hostname));
+}
+
+/**
+ * Mark paired devices online or offline from mesh records
+ *
+ * Paired devices without a record are left as they are. Returns the number
+ * of devices updated.
+ */
+export function applyMeshHosts(
+  store: Pick<DeviceStore, 'getAllDevices' | 'setDeviceStatus'>,
+  hosts: MeshHost[]
+): number {
+  const key = (id: string) => parseDeviceId(id)?.join(',') ?? id;
+  const byId = new Map(hosts.map((host) => [key(host.device_id), host]));
+
+  let updated = 0;
+  for (const device of store.getAllDevices()) {
+    const host = byId.get(key(device.id));
+    if (!host) continue;
+    const status = host.online ? 'online' : 'offline';
+    if (device.status !== status && store.setDeviceStatus(device.id, status)) {
+      updated++;
+    }
+  }
+  return updated;
+}
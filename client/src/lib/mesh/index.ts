@@ -0,0 +1,7 @@
+/**
+ * Mesh module exports
+ * Provides the online state of the admin's hosts from mesh status records
+ */
+
+export { meshGroupId, fetchMeshHosts, applyMeshHosts } from './MeshStatus';
+export type { MeshHost } from './MeshStatus';
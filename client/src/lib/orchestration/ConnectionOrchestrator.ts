@@ -15,6 +15,7 @@ import {
   createEnvelope,
   Msg,
   type SessionData,
+  type SessionOutputTruncated,
   type FileEntry as ProtocolFileEntry,
 } from '../protocol';
 
@@ -400,6 +401,14 @@ export class ConnectionOrchestrator {
             this.sessionStore?.writeOutput(sessionData.session_id, text);
           }
         }
+        // The daemon skipped output this client was too slow to receive
+        if (message.type === 'SessionOutputTruncated') {
+          const truncated = message.data as SessionOutputTruncated;
+          this.sessionStore?.writeOutput(
+            truncated.session_id,
+            `\r\n\x1b[2m[output truncated: ${truncated.dropped_chunks} chunks skipped]\x1b[0m\r\n`
+          );
+        }
         // SessionClosed would also come through here
         if (message.type === 'SessionClosed') {
           const closedData = message.data as { session_id: string; reason?: string | null };
@@ -669,6 +678,7 @@ export class ConnectionOrchestrator {
     const message = Msg.FileListRequest({
       path,
       include_hidden: this.fileStore?.state.showHidden ?? false,
+      detail: 'Basic',
     });
 
     const envelope = createEnvelope(++this.messageSequence, message);
@@ -691,6 +701,8 @@ export class ConnectionOrchestrator {
       path,
       offset,
       chunk_size: 65536, // 64KB chunks
+      verify: false,
+      window: 0, // one request per chunk
     });
 
     const envelope = createEnvelope(++this.messageSequence, message);
@@ -738,6 +750,7 @@ export class ConnectionOrchestrator {
       path,
       offset,
       data,
+      hash: null,
     });
 
     const envelope = createEnvelope(++this.messageSequence, message);
@@ -759,6 +772,7 @@ export class ConnectionOrchestrator {
     const message = Msg.FileUploadComplete({
       path,
       checksum,
+      report: false,
     });
 
     const envelope = createEnvelope(++this.messageSequence, message);
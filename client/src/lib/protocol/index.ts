@@ -17,35 +17,104 @@ export {
   type MessageType,
   // Session messages
   type SessionCreate,
+  type HostMultiplexer,
+  type HostSession,
   type SessionCreated,
   type SessionAttach,
   type SessionDetach,
   type SessionKill,
   type SessionResize,
+  type SessionSignal,
+  type UpdateSessionEnv,
+  type ProcessSignal,
   type SessionData,
   type DataStream,
+  type SessionOutputTruncated,
   type SessionClosed,
+  type SessionCloseReason,
+  type SessionMetadataRequest,
+  type SessionMetadata,
+  type SessionSearchRequest,
+  type SessionSearchResult,
+  type SearchMatch,
+  type ListTemplates,
+  type TemplateList,
+  type TemplateInfo,
+  // Agent forwarding messages
+  type AgentOpen,
+  type AgentData,
+  type AgentClose,
+  // Display forwarding messages
+  type DisplayOpen,
+  type DisplayData,
+  type DisplayClose,
+  type DisplayProtocol,
   // File messages
   type FileListRequest,
   type FileListResponse,
   type FileEntry,
   type FileEntryType,
+  type FileEntryDetails,
+  type FileXattr,
+  type FileListDetail,
   type FileDownloadRequest,
   type FileDownloadChunk,
+  type FileDownloadAck,
   type FileUploadStart,
   type FileUploadChunk,
   type FileUploadComplete,
+  type FileTransferReport,
+  type FileCopyGrantRequest,
+  type FileCopyGrant,
+  type FileCopyRequest,
+  type FileReadText,
+  type FileText,
+  type FileWriteText,
+  type FileTextWritten,
+  type FileRootsRequest,
+  type FileRoots,
+  type FileRoot,
+  type FileAccess,
+  type FileThumbnailRequest,
+  type FileThumbnail,
+  type FileTailRequest,
+  type FileTailData,
+  type FileTailStop,
   // Device messages
   type DeviceInfo,
   type DeviceApprovalRequest,
   type DeviceApproved,
   type DeviceRejected,
+  type SecurityKeyChallenge,
+  type SecurityKeyAssertion,
+  type SecurityKeyVerified,
+  // Host messages
+  type SystemInfoRequest,
+  type SystemInfo,
+  type DiskUsage,
+  type ProcessListRequest,
+  type ProcessSort,
+  type ProcessList,
+  type ProcessInfo,
+  type ProcessKill,
+  type PowerAction,
+  type PowerActionRequest,
+  type PowerActionChallenge,
+  type PowerActionConfirm,
+  type PowerActionStarted,
+  type ConfigRequest,
+  type ConfigView,
+  type ConfigPatch,
   // Control messages
   type Ping,
   type Pong,
   type ErrorMessage,
   type ErrorCode,
   type Capabilities,
+  type Notification,
+  type NotificationLevel,
+  type NotificationCategory,
+  type ServerShutdownPending,
 } from './messages';
 
 // Re-export serialization utilities
@@ -86,6 +86,9 @@ describe('Rust Interop: Decode Rust bytes', () => {
       expect(envelope.payload.data.shell).toBeNull();
       expect(envelope.payload.data.env).toEqual([]);
       expect(envelope.payload.data.cwd).toBeNull();
+      expect(envelope.payload.data.forward_agent).toBe(false);
+      expect(envelope.payload.data.timezone).toBeNull();
+      expect(envelope.payload.data.login_shell).toBe(false);
     }
   });
 
@@ -113,6 +116,8 @@ describe('Rust Interop: Decode Rust bytes', () => {
       expect(envelope.payload.data.message).toBe('Not found');
       expect(envelope.payload.data.context).toBe('test');
       expect(envelope.payload.data.recoverable).toBe(false);
+      // Encoded before retry delays existed
+      expect(envelope.payload.data.retry_after_ms).toBeNull();
     }
   });
 
@@ -293,6 +298,7 @@ describe('Rust Interop: Enum handling', () => {
               size: 0,
               mode: 0o644,
               modified: 0,
+              details: null,
             },
           ],
         })
@@ -318,6 +324,12 @@ describe('Rust Interop: Enum handling', () => {
       'AlreadyExists',
       'PermissionDenied',
       'VersionMismatch',
+      'InsufficientSpace',
+      'Conflict',
+      'Unsupported',
+      'QuotaExceeded',
+      'NotConnected',
+      'Busy',
     ] as const;
 
     for (const code of codes) {
@@ -328,6 +340,7 @@ describe('Rust Interop: Enum handling', () => {
           message: 'test',
           context: null,
           recoverable: false,
+          retry_after_ms: null,
         })
       );
       const bytes = encodeEnvelope(envelope);
@@ -507,26 +520,82 @@ describe('All Message Types Coverage', () => {
     'SessionDetach',
     'SessionKill',
     'SessionResize',
+    'SessionSignal',
+    'UpdateSessionEnv',
     'SessionData',
+    'SessionOutputTruncated',
     'SessionClosed',
+    'SessionMetadataRequest',
+    'SessionMetadata',
+    'SessionSearchRequest',
+    'SessionSearchResult',
+    'ListTemplates',
+    'TemplateList',
+    'AgentOpen',
+    'AgentData',
+    'AgentClose',
+    'DisplayOpen',
+    'DisplayData',
+    'DisplayClose',
     'FileListRequest',
     'FileListResponse',
     'FileDownloadRequest',
     'FileDownloadChunk',
+    'FileDownloadAck',
     'FileUploadStart',
     'FileUploadChunk',
     'FileUploadComplete',
+    'FileTransferReport',
+    'FileCopyGrantRequest',
+    'FileCopyGrant',
+    'FileCopyRequest',
+    'FileReadText',
+    'FileText',
+    'FileWriteText',
+    'FileTextWritten',
+    'FileRootsRequest',
+    'FileRoots',
+    'FileThumbnailRequest',
+    'FileThumbnail',
+    'FileTailRequest',
+    'FileTailData',
+    'FileTailStop',
     'DeviceInfo',
     'DeviceApprovalRequest',
     'DeviceApproved',
     'DeviceRejected',
+    'SecurityKeyChallenge',
+    'SecurityKeyAssertion',
+    'SecurityKeyVerified',
+    'SystemInfoRequest',
+    'SystemInfo',
+    'ProcessListRequest',
+    'ProcessList',
+    'ProcessKill',
+    'PowerActionRequest',
+    'PowerActionChallenge',
+    'PowerActionConfirm',
+    'PowerActionStarted',
+    'ConfigRequest',
+    'ConfigView',
+    'ConfigPatch',
     'Ping',
     'Pong',
     'Error',
     'Capabilities',
+    'Notification',
+    'ServerShutdownPending',
   ];
 
   // Create test messages for each type
+  const copyGrant = {
+    path: '/srv/copy.txt',
+    token: new Uint8Array(32).fill(0x5a),
+    addresses: ['192.168.1.20:7677'],
+    public_key: new Uint8Array(32).fill(0x11),
+    expires_in_secs: 60,
+  };
+
   const testMessages: Record<string, Message> = {
     SessionCreate: Msg.SessionCreate(defaultSessionCreate()),
     SessionCreated: Msg.SessionCreated({ session_id: 'test-sess', pid: 1234 }),
@@ -534,23 +603,86 @@ describe('All Message Types Coverage', () => {
     SessionDetach: Msg.SessionDetach({ session_id: 'test-sess' }),
     SessionKill: Msg.SessionKill({ session_id: 'test-sess', signal: null }),
     SessionResize: Msg.SessionResize({ session_id: 'test-sess', cols: 80, rows: 24 }),
+    SessionSignal: Msg.SessionSignal({ session_id: 'test-sess', signal: 'Interrupt' }),
+    UpdateSessionEnv: Msg.UpdateSessionEnv({
+      session_id: 'test-sess',
+      set: [['DISPLAY', 'localhost:10.0']],
+      unset: ['TZ'],
+    }),
     SessionData: Msg.SessionData({
       session_id: 'test-sess',
       stream: 'Stdout',
       data: new TextEncoder().encode('test'),
     }),
+    SessionOutputTruncated: Msg.SessionOutputTruncated({
+      session_id: 'test-sess',
+      dropped_chunks: 3,
+    }),
     SessionClosed: Msg.SessionClosed({
       session_id: 'test-sess',
       exit_code: 0,
       signal: null,
       reason: null,
+      close_reason: 'Exited',
+    }),
+    SessionMetadataRequest: Msg.SessionMetadataRequest({ session_id: 'test-sess' }),
+    SessionMetadata: Msg.SessionMetadata({
+      session_id: 'test-sess',
+      cwd: '/home/user',
+      last_exit_code: 0,
+      last_command: null,
+      command_running: false,
+    }),
+    SessionSearchRequest: Msg.SessionSearchRequest({
+      session_id: 'test-sess',
+      pattern: 'error',
+      regex: false,
+      case_sensitive: false,
+      max_results: 0,
+    }),
+    SessionSearchResult: Msg.SessionSearchResult({
+      session_id: 'test-sess',
+      matches: [{ line: 12, start: 0, end: 5, text: 'error: build failed' }],
+      truncated: false,
+      first_line: 0,
+      last_line: 14,
     }),
-    FileListRequest: Msg.FileListRequest({ path: '/home', include_hidden: false }),
+    ListTemplates: Msg.ListTemplates({}),
+    TemplateList: Msg.TemplateList({
+      templates: [
+        {
+          name: 'logs',
+          description: 'Follow the application log',
+          shell: null,
+          cwd: '/var/log',
+          command: 'tail -f app.log',
+          cols: 200,
+          rows: null,
+        },
+      ],
+    }),
+    AgentOpen: Msg.AgentOpen({ session_id: 'test-sess', channel_id: 1 }),
+    AgentData: Msg.AgentData({
+      session_id: 'test-sess',
+      channel_id: 1,
+      data: new Uint8Array([0, 0, 0, 1, 11]),
+    }),
+    AgentClose: Msg.AgentClose({ session_id: 'test-sess', channel_id: 1 }),
+    DisplayOpen: Msg.DisplayOpen({ session_id: 'test-sess', channel_id: 1 }),
+    DisplayData: Msg.DisplayData({
+      session_id: 'test-sess',
+      channel_id: 1,
+      data: new Uint8Array([108, 0, 11, 0]),
+    }),
+    DisplayClose: Msg.DisplayClose({ session_id: 'test-sess', channel_id: 1 }),
+    FileListRequest: Msg.FileListRequest({ path: '/home', include_hidden: false, detail: 'Basic' }),
     FileListResponse: Msg.FileListResponse({ path: '/home', entries: [] }),
     FileDownloadRequest: Msg.FileDownloadRequest({
       path: '/test.txt',
       offset: 0,
       chunk_size: 65536,
+      verify: false,
+      window: 0,
     }),
     FileDownloadChunk: Msg.FileDownloadChunk({
       path: '/test.txt',
@@ -558,7 +690,10 @@ describe('All Message Types Coverage', () => {
       total_size: 100,
       data: new Uint8Array([1, 2, 3]),
       is_last: true,
+      hash: new Uint8Array(32).fill(0x5a),
+      file_hash: new Uint8Array(32).fill(0xa5),
     }),
+    FileDownloadAck: Msg.FileDownloadAck({ path: '/test.txt', offset: 65536 }),
     FileUploadStart: Msg.FileUploadStart({
       path: '/upload.txt',
       size: 100,
@@ -569,11 +704,73 @@ describe('All Message Types Coverage', () => {
       path: '/upload.txt',
       offset: 0,
       data: new Uint8Array([1, 2, 3]),
+      hash: null,
     }),
     FileUploadComplete: Msg.FileUploadComplete({
       path: '/upload.txt',
       checksum: new Uint8Array(32),
+      report: true,
+    }),
+    FileTransferReport: Msg.FileTransferReport({
+      path: '/upload.txt',
+      size: 3,
+      hash: new Uint8Array(32),
+      chunks: 1,
+      verified_chunks: 0,
+      duration_ms: 42,
+    }),
+    FileCopyGrantRequest: Msg.FileCopyGrantRequest({
+      path: '/srv/copy.txt',
+      overwrite: true,
+    }),
+    FileCopyGrant: Msg.FileCopyGrant(copyGrant),
+    FileCopyRequest: Msg.FileCopyRequest({
+      source_path: '/upload.txt',
+      grant: copyGrant,
+    }),
+    FileReadText: Msg.FileReadText({ path: '/etc/app.conf' }),
+    FileText: Msg.FileText({
+      path: '/etc/app.conf',
+      content: 'port = 8080\n',
+      hash: new Uint8Array(32).fill(0x2b),
+    }),
+    FileWriteText: Msg.FileWriteText({
+      path: '/etc/app.conf',
+      content: 'port = 9090\n',
+      expected_hash: new Uint8Array(32).fill(0x2b),
+    }),
+    FileTextWritten: Msg.FileTextWritten({
+      path: '/etc/app.conf',
+      hash: new Uint8Array(32).fill(0x3c),
+    }),
+    FileRootsRequest: Msg.FileRootsRequest({}),
+    FileRoots: Msg.FileRoots({
+      roots: [
+        {
+          path: '/home/user',
+          access: 'Full',
+          total_bytes: 512_000_000_000,
+          available_bytes: 128_000_000_000,
+        },
+        { path: '/var/log', access: 'Read', total_bytes: null, available_bytes: null },
+      ],
+    }),
+    FileThumbnailRequest: Msg.FileThumbnailRequest({ path: '/home/user/photo.jpg', max_size: 128 }),
+    FileThumbnail: Msg.FileThumbnail({
+      path: '/home/user/photo.jpg',
+      mime_type: 'image/jpeg',
+      jpeg: new Uint8Array([0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10]),
+      width: 128,
+      height: 96,
+    }),
+    FileTailRequest: Msg.FileTailRequest({ path: '/var/log/syslog', lines: 100, follow: true }),
+    FileTailData: Msg.FileTailData({
+      path: '/var/log/syslog',
+      offset: 4096,
+      data: new TextEncoder().encode('started\n'),
+      reset: false,
     }),
+    FileTailStop: Msg.FileTailStop({ path: '/var/log/syslog' }),
     DeviceInfo: Msg.DeviceInfo({
       device_id: 'test-device',
       name: 'Test Device',
@@ -587,6 +784,8 @@ describe('All Message Types Coverage', () => {
       name: 'Test Device',
       public_key: new Uint8Array(32),
       reason: null,
+      reinvite_token: null,
+      pairing_code: null,
     }),
     DeviceApproved: Msg.DeviceApproved({
       device_id: 'test-device',
@@ -598,6 +797,61 @@ describe('All Message Types Coverage', () => {
       reason: 'Rejected',
       retry_allowed: false,
     }),
+    SecurityKeyChallenge: Msg.SecurityKeyChallenge({
+      challenge_id: 'chal-1',
+      challenge: new Uint8Array(32),
+      rp_id: 'remoshell',
+      allowed_credentials: ['Y3JlZC0x'],
+      action: 'session_create',
+      user_verification: false,
+      expires_at: 1700000060,
+    }),
+    SecurityKeyAssertion: Msg.SecurityKeyAssertion({
+      challenge_id: 'chal-1',
+      credential_id: new Uint8Array([1, 2, 3]),
+      authenticator_data: new Uint8Array(37),
+      client_data_json: new TextEncoder().encode('{"type":"webauthn.get"}'),
+      signature: new Uint8Array([0x30, 0x44]),
+    }),
+    SecurityKeyVerified: Msg.SecurityKeyVerified({
+      challenge_id: 'chal-1',
+      valid_until: 1700000300,
+    }),
+    SystemInfoRequest: Msg.SystemInfoRequest({}),
+    SystemInfo: Msg.SystemInfo({
+      hostname: 'build-01',
+      os_name: 'Ubuntu',
+      os_version: '24.04',
+      kernel_version: '6.8.0',
+      arch: 'x86_64',
+      cpu_count: 8,
+      load_average: [125, 98, 70],
+      memory_total: 16_000_000_000,
+      memory_used: 4_000_000_000,
+      swap_total: 0,
+      swap_used: 0,
+      disks: [{ path: '/home/user', total: 500_000_000_000, available: 200_000_000_000 }],
+      uptime_secs: 86400,
+    }),
+    ProcessListRequest: Msg.ProcessListRequest({ sort: 'Cpu', limit: 20 }),
+    ProcessList: Msg.ProcessList({
+      processes: [
+        { pid: 1, user: 'root', cpu_usage: 0, memory: 12_582_912, command: '/sbin/init' },
+        { pid: 4242, user: null, cpu_usage: 1250, memory: 134_217_728, command: 'postgres' },
+      ],
+    }),
+    ProcessKill: Msg.ProcessKill({ pid: 4242, signal: 'Terminate' }),
+    PowerActionRequest: Msg.PowerActionRequest({ action: 'Reboot' }),
+    PowerActionChallenge: Msg.PowerActionChallenge({
+      token: 'power-1',
+      action: 'Reboot',
+      expires_at: 1704067230,
+    }),
+    PowerActionConfirm: Msg.PowerActionConfirm({ token: 'power-1' }),
+    PowerActionStarted: Msg.PowerActionStarted({ action: 'Reboot' }),
+    ConfigRequest: Msg.ConfigRequest({}),
+    ConfigView: Msg.ConfigView({ toml: '[session]\nidle_timeout = 3600\n' }),
+    ConfigPatch: Msg.ConfigPatch({ idle_timeout: 900, file_exclude: null, log_level: 'debug' }),
     Ping: Msg.Ping({ timestamp: Date.now(), payload: new Uint8Array() }),
     Pong: Msg.Pong({ timestamp: Date.now(), payload: new Uint8Array() }),
     Error: Msg.Error({
@@ -605,8 +859,20 @@ describe('All Message Types Coverage', () => {
       message: 'Test error',
       context: null,
       recoverable: true,
+      retry_after_ms: null,
     }),
     Capabilities: Msg.Capabilities(defaultCapabilities()),
+    Notification: Msg.Notification({
+      level: 'Warning',
+      title: 'Daemon stopping',
+      body: 'The daemon is shutting down',
+      category: 'Shutdown',
+      session_id: null,
+    }),
+    ServerShutdownPending: Msg.ServerShutdownPending({
+      reason: 'The daemon is stopping',
+      grace_ms: 10000,
+    }),
   };
 
   it.each(allMessageTypes)('should round-trip %s message type', (msgType) => {
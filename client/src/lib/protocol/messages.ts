@@ -56,26 +56,75 @@ export type Message =
   | { type: 'SessionDetach'; data: SessionDetach }
   | { type: 'SessionKill'; data: SessionKill }
   | { type: 'SessionResize'; data: SessionResize }
+  | { type: 'SessionSignal'; data: SessionSignal }
+  | { type: 'UpdateSessionEnv'; data: UpdateSessionEnv }
   | { type: 'SessionData'; data: SessionData }
+  | { type: 'SessionOutputTruncated'; data: SessionOutputTruncated }
   | { type: 'SessionClosed'; data: SessionClosed }
+  | { type: 'SessionMetadataRequest'; data: SessionMetadataRequest }
+  | { type: 'SessionMetadata'; data: SessionMetadata }
+  | { type: 'SessionSearchRequest'; data: SessionSearchRequest }
+  | { type: 'SessionSearchResult'; data: SessionSearchResult }
+  | { type: 'ListTemplates'; data: ListTemplates }
+  | { type: 'TemplateList'; data: TemplateList }
+  | { type: 'AgentOpen'; data: AgentOpen }
+  | { type: 'AgentData'; data: AgentData }
+  | { type: 'AgentClose'; data: AgentClose }
+  | { type: 'DisplayOpen'; data: DisplayOpen }
+  | { type: 'DisplayData'; data: DisplayData }
+  | { type: 'DisplayClose'; data: DisplayClose }
   // File messages
   | { type: 'FileListRequest'; data: FileListRequest }
   | { type: 'FileListResponse'; data: FileListResponse }
   | { type: 'FileDownloadRequest'; data: FileDownloadRequest }
   | { type: 'FileDownloadChunk'; data: FileDownloadChunk }
+  | { type: 'FileDownloadAck'; data: FileDownloadAck }
   | { type: 'FileUploadStart'; data: FileUploadStart }
   | { type: 'FileUploadChunk'; data: FileUploadChunk }
   | { type: 'FileUploadComplete'; data: FileUploadComplete }
+  | { type: 'FileTransferReport'; data: FileTransferReport }
+  | { type: 'FileCopyGrantRequest'; data: FileCopyGrantRequest }
+  | { type: 'FileCopyGrant'; data: FileCopyGrant }
+  | { type: 'FileCopyRequest'; data: FileCopyRequest }
+  | { type: 'FileReadText'; data: FileReadText }
+  | { type: 'FileText'; data: FileText }
+  | { type: 'FileWriteText'; data: FileWriteText }
+  | { type: 'FileTextWritten'; data: FileTextWritten }
+  | { type: 'FileRootsRequest'; data: FileRootsRequest }
+  | { type: 'FileRoots'; data: FileRoots }
+  | { type: 'FileThumbnailRequest'; data: FileThumbnailRequest }
+  | { type: 'FileThumbnail'; data: FileThumbnail }
+  | { type: 'FileTailRequest'; data: FileTailRequest }
+  | { type: 'FileTailData'; data: FileTailData }
+  | { type: 'FileTailStop'; data: FileTailStop }
   // Device messages
   | { type: 'DeviceInfo'; data: DeviceInfo }
   | { type: 'DeviceApprovalRequest'; data: DeviceApprovalRequest }
   | { type: 'DeviceApproved'; data: DeviceApproved }
   | { type: 'DeviceRejected'; data: DeviceRejected }
+  | { type: 'SecurityKeyChallenge'; data: SecurityKeyChallenge }
+  | { type: 'SecurityKeyAssertion'; data: SecurityKeyAssertion }
+  | { type: 'SecurityKeyVerified'; data: SecurityKeyVerified }
+  // Host messages
+  | { type: 'SystemInfoRequest'; data: SystemInfoRequest }
+  | { type: 'SystemInfo'; data: SystemInfo }
+  | { type: 'ProcessListRequest'; data: ProcessListRequest }
+  | { type: 'ProcessList'; data: ProcessList }
+  | { type: 'ProcessKill'; data: ProcessKill }
+  | { type: 'PowerActionRequest'; data: PowerActionRequest }
+  | { type: 'PowerActionChallenge'; data: PowerActionChallenge }
+  | { type: 'PowerActionConfirm'; data: PowerActionConfirm }
+  | { type: 'PowerActionStarted'; data: PowerActionStarted }
+  | { type: 'ConfigRequest'; data: ConfigRequest }
+  | { type: 'ConfigView'; data: ConfigView }
+  | { type: 'ConfigPatch'; data: ConfigPatch }
   // Control messages
   | { type: 'Ping'; data: Ping }
   | { type: 'Pong'; data: Pong }
   | { type: 'Error'; data: ErrorMessage }
-  | { type: 'Capabilities'; data: Capabilities };
+  | { type: 'Capabilities'; data: Capabilities }
+  | { type: 'Notification'; data: Notification }
+  | { type: 'ServerShutdownPending'; data: ServerShutdownPending };
 
 // ============================================================================
 // Message Type Helpers
@@ -92,23 +141,77 @@ export const Msg = {
   SessionDetach: (data: SessionDetach): Message => ({ type: 'SessionDetach', data }),
   SessionKill: (data: SessionKill): Message => ({ type: 'SessionKill', data }),
   SessionResize: (data: SessionResize): Message => ({ type: 'SessionResize', data }),
+  SessionSignal: (data: SessionSignal): Message => ({ type: 'SessionSignal', data }),
+  UpdateSessionEnv: (data: UpdateSessionEnv): Message => ({ type: 'UpdateSessionEnv', data }),
   SessionData: (data: SessionData): Message => ({ type: 'SessionData', data }),
+  SessionOutputTruncated: (data: SessionOutputTruncated): Message => ({
+    type: 'SessionOutputTruncated',
+    data,
+  }),
   SessionClosed: (data: SessionClosed): Message => ({ type: 'SessionClosed', data }),
+  SessionMetadataRequest: (data: SessionMetadataRequest): Message => ({ type: 'SessionMetadataRequest', data }),
+  SessionMetadata: (data: SessionMetadata): Message => ({ type: 'SessionMetadata', data }),
+  SessionSearchRequest: (data: SessionSearchRequest): Message => ({ type: 'SessionSearchRequest', data }),
+  SessionSearchResult: (data: SessionSearchResult): Message => ({ type: 'SessionSearchResult', data }),
+  ListTemplates: (data: ListTemplates): Message => ({ type: 'ListTemplates', data }),
+  TemplateList: (data: TemplateList): Message => ({ type: 'TemplateList', data }),
+  AgentOpen: (data: AgentOpen): Message => ({ type: 'AgentOpen', data }),
+  AgentData: (data: AgentData): Message => ({ type: 'AgentData', data }),
+  AgentClose: (data: AgentClose): Message => ({ type: 'AgentClose', data }),
+  DisplayOpen: (data: DisplayOpen): Message => ({ type: 'DisplayOpen', data }),
+  DisplayData: (data: DisplayData): Message => ({ type: 'DisplayData', data }),
+  DisplayClose: (data: DisplayClose): Message => ({ type: 'DisplayClose', data }),
   FileListRequest: (data: FileListRequest): Message => ({ type: 'FileListRequest', data }),
   FileListResponse: (data: FileListResponse): Message => ({ type: 'FileListResponse', data }),
   FileDownloadRequest: (data: FileDownloadRequest): Message => ({ type: 'FileDownloadRequest', data }),
   FileDownloadChunk: (data: FileDownloadChunk): Message => ({ type: 'FileDownloadChunk', data }),
+  FileDownloadAck: (data: FileDownloadAck): Message => ({ type: 'FileDownloadAck', data }),
   FileUploadStart: (data: FileUploadStart): Message => ({ type: 'FileUploadStart', data }),
   FileUploadChunk: (data: FileUploadChunk): Message => ({ type: 'FileUploadChunk', data }),
   FileUploadComplete: (data: FileUploadComplete): Message => ({ type: 'FileUploadComplete', data }),
+  FileTransferReport: (data: FileTransferReport): Message => ({ type: 'FileTransferReport', data }),
+  FileCopyGrantRequest: (data: FileCopyGrantRequest): Message => ({ type: 'FileCopyGrantRequest', data }),
+  FileCopyGrant: (data: FileCopyGrant): Message => ({ type: 'FileCopyGrant', data }),
+  FileCopyRequest: (data: FileCopyRequest): Message => ({ type: 'FileCopyRequest', data }),
+  FileReadText: (data: FileReadText): Message => ({ type: 'FileReadText', data }),
+  FileText: (data: FileText): Message => ({ type: 'FileText', data }),
+  FileWriteText: (data: FileWriteText): Message => ({ type: 'FileWriteText', data }),
+  FileTextWritten: (data: FileTextWritten): Message => ({ type: 'FileTextWritten', data }),
+  FileRootsRequest: (data: FileRootsRequest): Message => ({ type: 'FileRootsRequest', data }),
+  FileRoots: (data: FileRoots): Message => ({ type: 'FileRoots', data }),
+  FileThumbnailRequest: (data: FileThumbnailRequest): Message => ({ type: 'FileThumbnailRequest', data }),
+  FileThumbnail: (data: FileThumbnail): Message => ({ type: 'FileThumbnail', data }),
+  FileTailRequest: (data: FileTailRequest): Message => ({ type: 'FileTailRequest', data }),
+  FileTailData: (data: FileTailData): Message => ({ type: 'FileTailData', data }),
+  FileTailStop: (data: FileTailStop): Message => ({ type: 'FileTailStop', data }),
   DeviceInfo: (data: DeviceInfo): Message => ({ type: 'DeviceInfo', data }),
   DeviceApprovalRequest: (data: DeviceApprovalRequest): Message => ({ type: 'DeviceApprovalRequest', data }),
   DeviceApproved: (data: DeviceApproved): Message => ({ type: 'DeviceApproved', data }),
   DeviceRejected: (data: DeviceRejected): Message => ({ type: 'DeviceRejected', data }),
+  SecurityKeyChallenge: (data: SecurityKeyChallenge): Message => ({ type: 'SecurityKeyChallenge', data }),
+  SecurityKeyAssertion: (data: SecurityKeyAssertion): Message => ({ type: 'SecurityKeyAssertion', data }),
+  SecurityKeyVerified: (data: SecurityKeyVerified): Message => ({ type: 'SecurityKeyVerified', data }),
+  SystemInfoRequest: (data: SystemInfoRequest): Message => ({ type: 'SystemInfoRequest', data }),
+  SystemInfo: (data: SystemInfo): Message => ({ type: 'SystemInfo', data }),
+  ProcessListRequest: (data: ProcessListRequest): Message => ({ type: 'ProcessListRequest', data }),
+  ProcessList: (data: ProcessList): Message => ({ type: 'ProcessList', data }),
+  ProcessKill: (data: ProcessKill): Message => ({ type: 'ProcessKill', data }),
+  PowerActionRequest: (data: PowerActionRequest): Message => ({ type: 'PowerActionRequest', data }),
+  PowerActionChallenge: (data: PowerActionChallenge): Message => ({ type: 'PowerActionChallenge', data }),
+  PowerActionConfirm: (data: PowerActionConfirm): Message => ({ type: 'PowerActionConfirm', data }),
+  PowerActionStarted: (data: PowerActionStarted): Message => ({ type: 'PowerActionStarted', data }),
+  ConfigRequest: (data: ConfigRequest): Message => ({ type: 'ConfigRequest', data }),
+  ConfigView: (data: ConfigView): Message => ({ type: 'ConfigView', data }),
+  ConfigPatch: (data: ConfigPatch): Message => ({ type: 'ConfigPatch', data }),
   Ping: (data: Ping): Message => ({ type: 'Ping', data }),
   Pong: (data: Pong): Message => ({ type: 'Pong', data }),
   Error: (data: ErrorMessage): Message => ({ type: 'Error', data }),
   Capabilities: (data: Capabilities): Message => ({ type: 'Capabilities', data }),
+  Notification: (data: Notification): Message => ({ type: 'Notification', data }),
+  ServerShutdownPending: (data: ServerShutdownPending): Message => ({
+    type: 'ServerShutdownPending',
+    data,
+  }),
 } as const;
 
 // ============================================================================
@@ -125,8 +228,40 @@ export interface SessionCreate {
   shell: string | null;
   /** Environment variables to set. */
   env: Array<[string, string]>;
-  /** Working directory for the session. */
+  /** Working directory for the session; `~` is the user's home directory. */
   cwd: string | null;
+  /** Forward the client's SSH agent into the session. */
+  forward_agent: boolean;
+  /** Name of a daemon session template to start from. */
+  template: string | null;
+  /** Client's IANA timezone (e.g. `Europe/Paris`), exported as `TZ`. */
+  timezone: string | null;
+  /** Client's locale (e.g. `fr-FR`), exported as `LANG`. */
+  locale: string | null;
+  /** Client terminal type (e.g. `xterm-256color`), exported as `TERM`. */
+  term: string | null;
+  /** Forward GUI applications of the session to the client's display. */
+  forward_display: DisplayProtocol | null;
+  /** Attach the session to a tmux or screen session on the host. */
+  host_session: HostSession | null;
+  /** Run the shell as a login shell, which reads the user's profile. */
+  login_shell: boolean;
+}
+
+/** Display protocol forwarded with `SessionCreate.forward_display`. */
+export type DisplayProtocol = 'X11' | 'Wayland';
+
+/** Terminal multiplexer running on the daemon's host. */
+export type HostMultiplexer = 'Tmux' | 'Screen';
+
+/** Multiplexer session on the host that `SessionCreate.host_session` attaches to. */
+export interface HostSession {
+  /** Multiplexer the session belongs to. */
+  multiplexer: HostMultiplexer;
+  /** Session name, as in `tmux attach -t <name>` or `screen -x <name>`. */
+  name: string;
+  /** Create the session if it does not exist (tmux only). */
+  create: boolean;
 }
 
 /** Default SessionCreate values */
@@ -137,6 +272,14 @@ export function defaultSessionCreate(): SessionCreate {
     shell: null,
     env: [],
     cwd: null,
+    forward_agent: false,
+    template: null,
+    timezone: null,
+    locale: null,
+    term: null,
+    forward_display: null,
+    host_session: null,
+    login_shell: false,
   };
 }
 
@@ -178,6 +321,39 @@ export interface SessionResize {
   rows: number;
 }
 
+/** Request to deliver a signal to a session's foreground process group. */
+export interface SessionSignal {
+  /** Session ID to signal. */
+  session_id: string;
+  /** The signal to deliver. */
+  signal: ProcessSignal;
+}
+
+/**
+ * Environment variables for commands a session starts from now on.
+ *
+ * The shell picks them up before each prompt once the hook printed by
+ * `remoshell-daemon shell-hook` is installed. Updates accumulate.
+ */
+export interface UpdateSessionEnv {
+  /** Session ID to update. */
+  session_id: string;
+  /** Variables to set, such as `DISPLAY` or `TZ`. */
+  set: Array<[string, string]>;
+  /** Variables to remove. */
+  unset: string[];
+}
+
+/** Signals that clients may deliver to a session. */
+export type ProcessSignal =
+  | 'Interrupt'
+  | 'Quit'
+  | 'Suspend'
+  | 'Continue'
+  | 'Hangup'
+  | 'Terminate'
+  | 'Kill';
+
 /** Session data (input or output). */
 export interface SessionData {
   /** Session ID this data belongs to. */
@@ -191,6 +367,17 @@ export interface SessionData {
 /** Data stream type. */
 export type DataStream = 'Stdin' | 'Stdout' | 'Stderr';
 
+/**
+ * Notification that the client fell behind a session's output and some of it
+ * was skipped, so the screen may be out of date.
+ */
+export interface SessionOutputTruncated {
+  /** Session whose output was skipped. */
+  session_id: string;
+  /** Number of output chunks skipped. */
+  dropped_chunks: number;
+}
+
 /** Session closed notification. */
 export interface SessionClosed {
   /** Session ID that was closed. */
@@ -201,6 +388,161 @@ export interface SessionClosed {
   signal: number | null;
   /** Human-readable reason for closure. */
   reason: string | null;
+  /** Why the session ended. `null` from daemons that predate it. */
+  close_reason: SessionCloseReason | null;
+}
+
+/** Why a session ended. */
+export type SessionCloseReason =
+  | 'Exited'
+  | 'Killed'
+  | 'DaemonShutdown'
+  | 'IdleTimeout'
+  | 'OwnerRevoked';
+
+/** Request for a session's shell metadata. */
+export interface SessionMetadataRequest {
+  /** Session ID to query. */
+  session_id: string;
+}
+
+/** Shell metadata for a session, tracked from shell integration sequences. */
+export interface SessionMetadata {
+  /** Session ID this metadata belongs to. */
+  session_id: string;
+  /** Current working directory of the shell. */
+  cwd: string | null;
+  /** Exit status of the most recently finished command. */
+  last_exit_code: number | null;
+  /** Command line of the most recently started command. */
+  last_command: string | null;
+  /** Whether a command is currently executing. */
+  command_running: boolean;
+}
+
+/** Request to search the output a session has kept on the daemon. */
+export interface SessionSearchRequest {
+  /** Session ID to search. */
+  session_id: string;
+  /** Text or regular expression to find. */
+  pattern: string;
+  /** Whether `pattern` is a regular expression. */
+  regex: boolean;
+  /** Whether matching is case sensitive. */
+  case_sensitive: boolean;
+  /** Maximum number of matches to return, or 0 for the daemon's default. */
+  max_results: number;
+}
+
+/** Matches found in a session's output, newest first. */
+export interface SessionSearchResult {
+  /** Session ID that was searched. */
+  session_id: string;
+  /** The matches, newest first. */
+  matches: SearchMatch[];
+  /** Whether more matches were found than returned. */
+  truncated: boolean;
+  /** Number of the oldest line the daemon still holds. */
+  first_line: number;
+  /** Number of the line currently being written. */
+  last_line: number;
+}
+
+/** A match in a session's output. */
+export interface SearchMatch {
+  /** Line number, counted from the start of the session. */
+  line: number;
+  /** Offset of the start of the match in `text`, in Unicode code points. */
+  start: number;
+  /** Offset just past the end of the match in `text`, in Unicode code points. */
+  end: number;
+  /** The line, with escape sequences removed. */
+  text: string;
+}
+
+/** Request for the session templates configured on the daemon. */
+export type ListTemplates = Record<string, never>;
+
+/** Session templates configured on the daemon, sorted by name. */
+export interface TemplateList {
+  /** Available templates. */
+  templates: TemplateInfo[];
+}
+
+/** A session template, as shown in launcher menus. Environment variables are not included. */
+export interface TemplateInfo {
+  /** Name to pass in SessionCreate.template. */
+  name: string;
+  /** Short description of the template. */
+  description: string | null;
+  /** Shell the session runs. */
+  shell: string | null;
+  /** Working directory of the session. */
+  cwd: string | null;
+  /** Command typed into the shell once the session starts. */
+  command: string | null;
+  /** Terminal columns, replacing the client's size. */
+  cols: number | null;
+  /** Terminal rows, replacing the client's size. */
+  rows: number | null;
+}
+
+/**
+ * Notification that a process in a session connected to the forwarded SSH
+ * agent socket. Answer with AgentClose to decline.
+ */
+export interface AgentOpen {
+  /** Session whose agent socket was connected to. */
+  session_id: string;
+  /** Channel identifier, unique within the session. */
+  channel_id: number;
+}
+
+/** SSH agent protocol data on a forwarded agent channel, in either direction. */
+export interface AgentData {
+  /** Session the channel belongs to. */
+  session_id: string;
+  /** Channel identifier. */
+  channel_id: number;
+  /** Raw agent protocol bytes. */
+  data: Uint8Array;
+}
+
+/** Closes a forwarded agent channel, in either direction. */
+export interface AgentClose {
+  /** Session the channel belongs to. */
+  session_id: string;
+  /** Channel identifier. */
+  channel_id: number;
+}
+
+/**
+ * Notification that a GUI application in a session connected to the
+ * forwarded display. Answer with DisplayClose to decline.
+ */
+export interface DisplayOpen {
+  /** Session whose display was connected to. */
+  session_id: string;
+  /** Channel identifier, unique within the session. */
+  channel_id: number;
+}
+
+/** X11 or waypipe protocol data on a forwarded display channel, in either direction. */
+export interface DisplayData {
+  /** Session the channel belongs to. */
+  session_id: string;
+  /** Channel identifier. */
+  channel_id: number;
+  /** Raw display protocol bytes. */
+  data: Uint8Array;
+}
+
+/** Closes a forwarded display channel, in either direction. */
+export interface DisplayClose {
+  /** Session the channel belongs to. */
+  session_id: string;
+  /** Channel identifier. */
+  channel_id: number;
 }
 
 // ============================================================================
@@ -213,8 +555,17 @@ export interface FileListRequest {
   path: string;
   /** Include hidden files. */
   include_hidden: boolean;
+  /** How much to report about each entry. */
+  detail: FileListDetail;
 }
 
+/**
+ * Level of detail of listed entries: `Basic` is name, type, size, mode and
+ * modification time; `Extended` adds ownership and symlink targets; `Full`
+ * also adds extended attributes.
+ */
+export type FileListDetail = 'Basic' | 'Extended' | 'Full';
+
 /** Response with directory listing. */
 export interface FileListResponse {
   /** Path that was listed. */
@@ -235,6 +586,32 @@ export interface FileEntry {
   mode: number;
   /** Last modified timestamp (Unix epoch seconds). */
   modified: number;
+  /** Extra information, present when the listing asked for more than `Basic`. */
+  details: FileEntryDetails | null;
+}
+
+/** Extra information about a FileEntry. */
+export interface FileEntryDetails {
+  /** Numeric owner ID. */
+  uid: number;
+  /** Numeric group ID. */
+  gid: number;
+  /** Owner name, if the ID maps to a user. */
+  owner: string | null;
+  /** Group name, if the ID maps to a group. */
+  group: string | null;
+  /** Where the entry points to, if it is a symlink. */
+  symlink_target: string | null;
+  /** Extended attributes; empty unless `Full` detail was asked. */
+  xattrs: FileXattr[];
+}
+
+/** An extended attribute of a file. */
+export interface FileXattr {
+  /** Attribute name, including its namespace (e.g. `user.comment`). */
+  name: string;
+  /** Attribute value. */
+  value: Uint8Array;
 }
 
 /** Type of file entry. */
@@ -248,6 +625,10 @@ export interface FileDownloadRequest {
   offset: number;
   /** Maximum chunk size. */
   chunk_size: number;
+  /** Ask for a BLAKE3 hash of each chunk, and of the whole file with the last chunk. */
+  verify: boolean;
+  /** Chunks the daemon may stream ahead of acks; 0 requests each chunk in turn. */
+  window: number;
 }
 
 /** Chunk of downloaded file data. */
@@ -262,6 +643,18 @@ export interface FileDownloadChunk {
   data: Uint8Array;
   /** Whether this is the last chunk. */
   is_last: boolean;
+  /** BLAKE3 hash of `data`, if the download was requested with `verify`. */
+  hash: Uint8Array | null;
+  /** BLAKE3 hash of the whole file, sent with the last chunk of a verified download. */
+  file_hash: Uint8Array | null;
+}
+
+/** Acknowledge chunks of a streamed download. */
+export interface FileDownloadAck {
+  /** Path being downloaded. */
+  path: string;
+  /** Bytes received so far, counted from the start of the file. */
+  offset: number;
 }
 
 /** Start a file upload. */
@@ -284,6 +677,8 @@ export interface FileUploadChunk {
   offset: number;
   /** The chunk data. */
   data: Uint8Array;
+  /** BLAKE3 hash of `data`. The daemon rejects the chunk on mismatch. */
+  hash: Uint8Array | null;
 }
 
 /** Complete a file upload. */
@@ -292,6 +687,168 @@ export interface FileUploadComplete {
   path: string;
   /** SHA-256 hash of the complete file for verification. */
   checksum: Uint8Array;
+  /** Ask for a FileTransferReport in reply. */
+  report: boolean;
+}
+
+/** Integrity report for a completed upload, sent when `report` was set. */
+export interface FileTransferReport {
+  /** Destination path. */
+  path: string;
+  /** Size of the file in bytes. */
+  size: number;
+  /** BLAKE3 hash of the file as written. */
+  hash: Uint8Array;
+  /** Number of chunks received. */
+  chunks: number;
+  /** Number of chunks that carried a hash and matched it. */
+  verified_chunks: number;
+  /** Time from the start of the upload to its completion, in milliseconds. */
+  duration_ms: number;
+}
+
+/** Ask the destination daemon of a direct copy for a grant. */
+export interface FileCopyGrantRequest {
+  /** Destination path. */
+  path: string;
+  /** Replace the file if it exists. */
+  overwrite: boolean;
+}
+
+/** Single-use permission for another daemon to write one file. */
+export interface FileCopyGrant {
+  /** Destination path. */
+  path: string;
+  /** Token the source daemon presents to the destination. */
+  token: Uint8Array;
+  /** Addresses the destination accepts copies on, as `host:port`. */
+  addresses: string[];
+  /** Ed25519 public key of the destination daemon. */
+  public_key: Uint8Array;
+  /** Seconds until the grant expires. */
+  expires_in_secs: number;
+}
+
+/**
+ * Ask the source daemon to copy a file to the daemon that issued `grant`.
+ * The outcome arrives later as a FileTransferReport or an Error.
+ */
+export interface FileCopyRequest {
+  /** Source path. */
+  source_path: string;
+  /** Grant from the destination daemon. */
+  grant: FileCopyGrant;
+}
+
+/** Read a small text file in one message, e.g. to edit a config file. */
+export interface FileReadText {
+  /** Path of the file. */
+  path: string;
+}
+
+/** Contents of a text file. */
+export interface FileText {
+  /** Path of the file. */
+  path: string;
+  /** Contents of the file. */
+  content: string;
+  /** BLAKE3 hash of the contents, to pass back in FileWriteText. */
+  hash: Uint8Array;
+}
+
+/**
+ * Replace a text file, only if its hash is still `expected_hash` (or, with
+ * none, if it does not exist). Otherwise the daemon answers with a
+ * `Conflict` error and leaves the file alone.
+ */
+export interface FileWriteText {
+  /** Path of the file. */
+  path: string;
+  /** New contents of the file. */
+  content: string;
+  /** Hash from the FileText the edit started from, or null to create. */
+  expected_hash: Uint8Array | null;
+}
+
+/** Confirms a FileWriteText. */
+export interface FileTextWritten {
+  /** Path of the file. */
+  path: string;
+  /** BLAKE3 hash of the new contents, for the next write. */
+  hash: Uint8Array;
+}
+
+/** Asks the daemon which paths this device may browse. */
+export type FileRootsRequest = Record<string, never>;
+
+/** Paths the device may browse, with its access to each. */
+export interface FileRoots {
+  roots: FileRoot[];
+}
+
+/** One allowed path with the device's access level and free space. */
+export interface FileRoot {
+  /** Canonical path of the root. */
+  path: string;
+  /** What the device may do under this path. */
+  access: FileAccess;
+  /** Size of the filesystem holding the path, if known. */
+  total_bytes: number | null;
+  /** Bytes available to the daemon on that filesystem, if known. */
+  available_bytes: number | null;
+}
+
+/** Access level on a file root: read-only, read-write, or read-write-delete. */
+export type FileAccess = 'Read' | 'ReadWrite' | 'Full';
+
+/** Ask for a preview of a file, without downloading it. */
+export interface FileThumbnailRequest {
+  /** Path of the file. */
+  path: string;
+  /** Longest edge of the thumbnail in pixels; 0 lets the daemon choose. */
+  max_size: number;
+}
+
+/** A file's MIME type and, for images, a small preview. */
+export interface FileThumbnail {
+  /** Path of the file. */
+  path: string;
+  /** MIME type of the file, for picking an icon. */
+  mime_type: string;
+  /** JPEG thumbnail, null when the file is not an image within the daemon's limits. */
+  jpeg: Uint8Array | null;
+  /** Width of the thumbnail in pixels, 0 without one. */
+  width: number;
+  /** Height of the thumbnail in pixels, 0 without one. */
+  height: number;
+}
+
+/** Stream the last lines of a text file and, if following, data appended to it. */
+export interface FileTailRequest {
+  /** Path of the file. */
+  path: string;
+  /** Number of lines from the end of the file to send first. */
+  lines: number;
+  /** Keep sending appended data until a FileTailStop. */
+  follow: boolean;
+}
+
+/** Data from a file being tailed. */
+export interface FileTailData {
+  /** Path of the file, as requested. */
+  path: string;
+  /** Offset of `data` in the file. */
+  offset: number;
+  /** Bytes of the file, which may end within a line or character. */
+  data: Uint8Array;
+  /** The file was truncated or replaced, so `data` starts the file anew. */
+  reset: boolean;
+}
+
+/** Stop following a file. */
+export interface FileTailStop {
+  /** Path of the file, as requested. */
+  path: string;
 }
 
 // ============================================================================
@@ -324,6 +881,10 @@ export interface DeviceApprovalRequest {
   public_key: Uint8Array;
   /** Human-readable reason for connection. */
   reason: string | null;
+  /** One-time token allowing a revoked device to be re-approved. */
+  reinvite_token: string | null;
+  /** Pairing code the client paired with, bound to the first device to use it. */
+  pairing_code: string | null;
 }
 
 /** Device connection approved. */
@@ -346,6 +907,191 @@ export interface DeviceRejected {
   retry_allowed: boolean;
 }
 
+/**
+ * Challenge for a security key (FIDO2) assertion, sent in place of the
+ * response to a request that daemon policy gates on a security key.
+ */
+export interface SecurityKeyChallenge {
+  /** Identifier to echo back in the assertion. */
+  challenge_id: string;
+  /** Random challenge to be signed by the authenticator. */
+  challenge: Uint8Array;
+  /** Relying party ID the credential is scoped to. */
+  rp_id: string;
+  /** Base64url-encoded IDs of the credentials the daemon accepts. */
+  allowed_credentials: string[];
+  /** The gated action (`session_create` or `file_write`). */
+  action: string;
+  /** Whether the authenticator must verify the user (PIN or biometrics). */
+  user_verification: boolean;
+  /** When the challenge expires (Unix timestamp). */
+  expires_at: number;
+}
+
+/** Security key assertion answering a challenge. */
+export interface SecurityKeyAssertion {
+  /** Challenge being answered. */
+  challenge_id: string;
+  /** ID of the credential that produced the assertion. */
+  credential_id: Uint8Array;
+  /** Authenticator data. */
+  authenticator_data: Uint8Array;
+  /** Client data JSON. */
+  client_data_json: Uint8Array;
+  /** DER-encoded ECDSA P-256 signature. */
+  signature: Uint8Array;
+}
+
+/** Security key assertion accepted. */
+export interface SecurityKeyVerified {
+  /** Challenge that was answered. */
+  challenge_id: string;
+  /** Until when protected file writes are allowed (Unix timestamp). */
+  valid_until: number;
+}
+
+// ============================================================================
+// Host Messages
+// ============================================================================
+
+/** Request for information about the daemon's host. */
+export type SystemInfoRequest = Record<string, never>;
+
+/** Snapshot of the daemon's host. */
+export interface SystemInfo {
+  /** Host name. */
+  hostname: string;
+  /** Operating system name (e.g. "Ubuntu"). */
+  os_name: string | null;
+  /** Operating system version (e.g. "24.04"). */
+  os_version: string | null;
+  /** Kernel version. */
+  kernel_version: string | null;
+  /** CPU architecture (e.g. "x86_64"). */
+  arch: string;
+  /** Number of logical CPUs. */
+  cpu_count: number;
+  /** Load averages over 1, 5 and 15 minutes, multiplied by 100. */
+  load_average: [number, number, number];
+  /** Total memory in bytes. */
+  memory_total: number;
+  /** Used memory in bytes. */
+  memory_used: number;
+  /** Total swap in bytes. */
+  swap_total: number;
+  /** Used swap in bytes. */
+  swap_used: number;
+  /** Usage of the filesystems holding the paths the daemon allows file access to. */
+  disks: DiskUsage[];
+  /** Seconds since the host booted. */
+  uptime_secs: number;
+}
+
+/** Usage of the filesystem holding a path. */
+export interface DiskUsage {
+  /** The path, as configured on the daemon. */
+  path: string;
+  /** Size of the filesystem in bytes. */
+  total: number;
+  /** Bytes available to unprivileged users. */
+  available: number;
+}
+
+/** Request for the processes running on the daemon host. */
+export interface ProcessListRequest {
+  /** Order of the returned processes (by PID if null). */
+  sort: ProcessSort | null;
+  /** Maximum number of processes to return, after sorting. */
+  limit: number | null;
+}
+
+/** Order of a process list. Cpu and Memory put the highest usage first. */
+export type ProcessSort = 'Pid' | 'Cpu' | 'Memory' | 'Command';
+
+/** Processes running on the daemon host. */
+export interface ProcessList {
+  /** The processes, in the requested order. */
+  processes: ProcessInfo[];
+}
+
+/** A process running on the daemon host. */
+export interface ProcessInfo {
+  /** Process ID. */
+  pid: number;
+  /** Name of the user running the process, if known. */
+  user: string | null;
+  /** CPU usage in hundredths of a percent of one CPU. */
+  cpu_usage: number;
+  /** Resident memory in bytes. */
+  memory: number;
+  /** Command line, or the process name if the command line is not readable. */
+  command: string;
+}
+
+/** Signal a process on the daemon host. Requires the `process-kill` capability. */
+export interface ProcessKill {
+  /** Process to signal. */
+  pid: number;
+  /** The signal to deliver. */
+  signal: ProcessSignal;
+}
+
+/** Power actions a client can request on the daemon host. */
+export type PowerAction = 'Reboot' | 'Shutdown' | 'Suspend';
+
+/**
+ * Request to reboot, shut down or suspend the daemon host. Requires the
+ * `power` capability; the daemon answers with a PowerActionChallenge.
+ */
+export interface PowerActionRequest {
+  /** The action to perform. */
+  action: PowerAction;
+}
+
+/** Token to confirm a requested power action with. */
+export interface PowerActionChallenge {
+  /** One-time token to send back in PowerActionConfirm. */
+  token: string;
+  /** The requested action. */
+  action: PowerAction;
+  /** Until when the action can be confirmed (Unix timestamp). */
+  expires_at: number;
+}
+
+/** Confirmation of a requested power action, sent once the user agreed. */
+export interface PowerActionConfirm {
+  /** Token from the PowerActionChallenge. */
+  token: string;
+}
+
+/** A confirmed power action is about to run. */
+export interface PowerActionStarted {
+  /** The action being performed. */
+  action: PowerAction;
+}
+
+/** Request for the daemon's effective configuration. Requires a management role. */
+export type ConfigRequest = Record<string, never>;
+
+/** The daemon's effective configuration, with secrets redacted. */
+export interface ConfigView {
+  /** The configuration in the daemon's TOML format. */
+  toml: string;
+}
+
+/**
+ * Change to the settings that can be changed remotely; null fields are kept.
+ * Requires the `owner` role; the daemon answers with the new ConfigView.
+ */
+export interface ConfigPatch {
+  /** Seconds a session may go without input or output before it is closed (0 = never). */
+  idle_timeout: number | null;
+  /** Glob patterns of names left out of directory listings. */
+  file_exclude: string[] | null;
+  /** Log level: trace, debug, info, warn or error. */
+  log_level: string | null;
+}
+
 // ============================================================================
 // Control Messages
 // ============================================================================
@@ -374,8 +1120,10 @@ export interface ErrorMessage {
   message: string;
   /** Optional context (e.g., session_id, path). */
   context: string | null;
-  /** Whether the error is recoverable. */
+  /** Whether the request may succeed if sent again; see `isRetryable`. */
   recoverable: boolean;
+  /** How long to wait before retrying, in milliseconds, when the daemon knows. */
+  retry_after_ms: number | null;
 }
 
 /** Error codes for common error conditions. */
@@ -389,7 +1137,31 @@ export type ErrorCode =
   | 'RateLimited'
   | 'AlreadyExists'
   | 'PermissionDenied'
-  | 'VersionMismatch';
+  | 'VersionMismatch'
+  | 'InsufficientSpace'
+  | 'Conflict'
+  | 'Unsupported'
+  | 'QuotaExceeded'
+  | 'NotConnected'
+  | 'Busy';
+
+/**
+ * Whether a request that failed with `code` may succeed if sent again
+ * unchanged, possibly after a delay. Mirrors `ErrorCode::is_retryable` in the
+ * protocol crate.
+ */
+export function isRetryable(code: ErrorCode): boolean {
+  switch (code) {
+    case 'InternalError':
+    case 'Timeout':
+    case 'RateLimited':
+    case 'NotConnected':
+    case 'Busy':
+      return true;
+    default:
+      return false;
+  }
+}
 
 /** Capabilities announcement. */
 export interface Capabilities {
@@ -415,3 +1187,34 @@ export function defaultCapabilities(): Capabilities {
     compression: ['lz4'],
   };
 }
+
+/** Severity of a Notification. */
+export type NotificationLevel = 'Info' | 'Warning' | 'Error';
+
+/** What a Notification is about. */
+export type NotificationCategory = 'General' | 'Approval' | 'Shutdown' | 'Quota' | 'Terminal';
+
+/** Notice pushed by the daemon for the user, without a preceding request. */
+export interface Notification {
+  /** How urgent the notice is. */
+  level: NotificationLevel;
+  /** Short summary, suitable as the title of an OS notification. */
+  title: string;
+  /** Details for the user. */
+  body: string;
+  /** What the notice is about. */
+  category: NotificationCategory;
+  /** Session the notice came from, for `Terminal` notifications. */
+  session_id: string | null;
+}
+
+/**
+ * The daemon is stopping and will close the connection, sent to every client
+ * before it closes sessions and drains file transfers.
+ */
+export interface ServerShutdownPending {
+  /** Why the daemon is stopping. */
+  reason: string;
+  /** Longest time, in milliseconds, before the connection is closed. */
+  grace_ms: number;
+}
@@ -20,6 +20,7 @@ import {
   defaultSessionCreate,
   defaultCapabilities,
   Msg,
+  isRetryable,
   PROTOCOL_VERSION,
   type Message,
 } from './messages';
@@ -125,6 +126,14 @@ describe('Session Message Roundtrip', () => {
           ['LANG', 'en_US.UTF-8'],
         ],
         cwd: '/home/user',
+        forward_agent: true,
+        template: 'logs',
+        timezone: 'Europe/Paris',
+        locale: 'fr-FR',
+        term: 'xterm-256color',
+        forward_display: 'X11',
+        host_session: { multiplexer: 'Tmux', name: 'work', create: true },
+        login_shell: true,
       })
     );
   });
@@ -177,6 +186,25 @@ describe('Session Message Roundtrip', () => {
     );
   });
 
+  it('should roundtrip SessionSignal', () => {
+    roundtripEnvelope(
+      Msg.SessionSignal({
+        session_id: 'sess-abc123',
+        signal: 'Interrupt',
+      })
+    );
+  });
+
+  it('should roundtrip UpdateSessionEnv', () => {
+    roundtripEnvelope(
+      Msg.UpdateSessionEnv({
+        session_id: 'sess-abc123',
+        set: [['TZ', 'Europe/Paris']],
+        unset: ['DISPLAY'],
+      })
+    );
+  });
+
   it('should roundtrip SessionData with Stdin', () => {
     roundtripEnvelope(
       Msg.SessionData({
@@ -187,6 +215,15 @@ describe('Session Message Roundtrip', () => {
     );
   });
 
+  it('should roundtrip SessionOutputTruncated', () => {
+    roundtripEnvelope(
+      Msg.SessionOutputTruncated({
+        session_id: 'sess-abc123',
+        dropped_chunks: 42,
+      })
+    );
+  });
+
   it('should roundtrip SessionData with Stdout', () => {
     roundtripEnvelope(
       Msg.SessionData({
@@ -214,6 +251,7 @@ describe('Session Message Roundtrip', () => {
         exit_code: 0,
         signal: null,
         reason: 'Process exited normally',
+        close_reason: 'Exited',
       })
     );
   });
@@ -225,6 +263,7 @@ describe('Session Message Roundtrip', () => {
         exit_code: null,
         signal: 9,
         reason: 'Killed by SIGKILL',
+        close_reason: 'Killed',
       })
     );
   });
@@ -236,6 +275,7 @@ describe('File Message Roundtrip', () => {
       Msg.FileListRequest({
         path: '/home/user/documents',
         include_hidden: true,
+        detail: 'Extended',
       })
     );
   });
@@ -251,6 +291,7 @@ describe('File Message Roundtrip', () => {
             size: 1024,
             mode: 0o644,
             modified: 1704067200,
+            details: null,
           },
           {
             name: 'docs',
@@ -258,6 +299,7 @@ describe('File Message Roundtrip', () => {
             size: 0,
             mode: 0o755,
             modified: 1704067200,
+            details: null,
           },
           {
             name: 'link',
@@ -265,6 +307,14 @@ describe('File Message Roundtrip', () => {
             size: 0,
             mode: 0o777,
             modified: 1704067200,
+            details: {
+              uid: 1000,
+              gid: 100,
+              owner: 'user',
+              group: null,
+              symlink_target: '../target',
+              xattrs: [{ name: 'user.comment', value: new Uint8Array([1, 2, 3]) }],
+            },
           },
         ],
       })
@@ -277,6 +327,8 @@ describe('File Message Roundtrip', () => {
         path: '/home/user/large-file.bin',
         offset: 1024,
         chunk_size: 65536,
+        verify: false,
+        window: 8,
       })
     );
   });
@@ -345,6 +397,8 @@ describe('Device Message Roundtrip', () => {
         name: 'Work Computer',
         public_key: new Uint8Array(32).fill(0x04),
         reason: 'Need to access project files',
+        reinvite_token: null,
+        pairing_code: null,
       })
     );
   });
@@ -396,6 +450,7 @@ describe('Control Message Roundtrip', () => {
         message: 'Session not found',
         context: 'sess-unknown',
         recoverable: false,
+        retry_after_ms: null,
       })
     );
   });
@@ -415,6 +470,27 @@ describe('Control Message Roundtrip', () => {
       })
     );
   });
+
+  it('should roundtrip terminal Notification', () => {
+    roundtripEnvelope(
+      Msg.Notification({
+        level: 'Info',
+        title: 'make',
+        body: 'Build finished',
+        category: 'Terminal',
+        session_id: 'sess-abc123',
+      })
+    );
+  });
+
+  it('should roundtrip ServerShutdownPending', () => {
+    roundtripEnvelope(
+      Msg.ServerShutdownPending({
+        reason: 'The daemon is stopping',
+        grace_ms: 10000,
+      })
+    );
+  });
 });
 
 describe('Error Code Roundtrip', () => {
@@ -429,6 +505,12 @@ describe('Error Code Roundtrip', () => {
     'AlreadyExists',
     'PermissionDenied',
     'VersionMismatch',
+    'InsufficientSpace',
+    'Conflict',
+    'Unsupported',
+    'QuotaExceeded',
+    'NotConnected',
+    'Busy',
   ] as const;
 
   for (const code of errorCodes) {
@@ -438,7 +520,8 @@ describe('Error Code Roundtrip', () => {
           code,
           message: `Test error: ${code}`,
           context: null,
-          recoverable: true,
+          recoverable: isRetryable(code),
+          retry_after_ms: code === 'RateLimited' ? 1500 : null,
         })
       );
     });
@@ -463,6 +546,14 @@ describe('Standalone Message Serialization', () => {
         shell: '/bin/bash',
         env: [['TERM', 'xterm-256color']],
         cwd: '/home/user',
+        forward_agent: false,
+        template: null,
+        timezone: null,
+        locale: null,
+        term: null,
+        forward_display: null,
+        host_session: null,
+        login_shell: false,
       })
     );
   });
@@ -501,6 +592,14 @@ describe('Edge Cases', () => {
           ['GREETING', 'Hello!'],
         ],
         cwd: '/home/user/documents',
+        forward_agent: false,
+        template: null,
+        timezone: null,
+        locale: null,
+        term: null,
+        forward_display: null,
+        host_session: null,
+        login_shell: false,
       })
     );
   });
@@ -510,6 +609,7 @@ describe('Edge Cases', () => {
       Msg.FileListRequest({
         path: '/home/user/My Documents/file (1).txt',
         include_hidden: false,
+        detail: 'Basic',
       })
     );
   });
@@ -536,6 +636,14 @@ describe('Edge Cases', () => {
         shell: null,
         env: [],
         cwd: null,
+        forward_agent: false,
+        template: null,
+        timezone: null,
+        locale: null,
+        term: null,
+        forward_display: null,
+        host_session: null,
+        login_shell: false,
       })
     );
   });
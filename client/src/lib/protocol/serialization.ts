@@ -19,26 +19,86 @@ import type {
   SessionDetach,
   SessionKill,
   SessionResize,
+  SessionSignal,
+  UpdateSessionEnv,
+  ProcessSignal,
   SessionData,
   DataStream,
+  SessionOutputTruncated,
   SessionClosed,
+  SessionCloseReason,
+  SessionMetadataRequest,
+  SessionMetadata,
+  SessionSearchRequest,
+  SessionSearchResult,
+  SearchMatch,
+  TemplateList,
+  TemplateInfo,
+  AgentOpen,
+  AgentData,
+  AgentClose,
+  DisplayOpen,
+  DisplayData,
+  DisplayClose,
+  DisplayProtocol,
+  HostMultiplexer,
+  HostSession,
   FileListRequest,
   FileListResponse,
   FileEntryType,
+  FileEntryDetails,
+  FileListDetail,
   FileDownloadRequest,
   FileDownloadChunk,
+  FileDownloadAck,
   FileUploadStart,
   FileUploadChunk,
   FileUploadComplete,
+  FileTransferReport,
+  FileCopyGrantRequest,
+  FileCopyGrant,
+  FileCopyRequest,
+  FileReadText,
+  FileText,
+  FileWriteText,
+  FileTextWritten,
+  FileRoots,
+  FileAccess,
+  FileThumbnailRequest,
+  FileThumbnail,
+  FileTailRequest,
+  FileTailData,
+  FileTailStop,
   DeviceInfo,
   DeviceApprovalRequest,
   DeviceApproved,
   DeviceRejected,
+  SecurityKeyChallenge,
+  SecurityKeyAssertion,
+  SecurityKeyVerified,
+  SystemInfo,
+  DiskUsage,
+  ProcessListRequest,
+  ProcessSort,
+  ProcessList,
+  ProcessInfo,
+  ProcessKill,
+  PowerAction,
+  PowerActionRequest,
+  PowerActionChallenge,
+  PowerActionConfirm,
+  PowerActionStarted,
+  ConfigView,
+  ConfigPatch,
   Ping,
   Pong,
   ErrorMessage,
   ErrorCode,
   Capabilities,
+  Notification,
+  NotificationLevel,
+  NotificationCategory,
+  ServerShutdownPending,
 } from './messages';
 
 // ============================================================================
@@ -111,8 +171,23 @@ function serializeMessageData(type: string, data: unknown): unknown[] {
     // Session messages
     case 'SessionCreate': {
       const d = data as SessionCreate;
-      // Rust order: cols, rows, shell, env, cwd
-      return [d.cols, d.rows, d.shell, d.env, d.cwd];
+      // Rust order: cols, rows, shell, env, cwd, forward_agent, template, timezone, locale, term,
+      // forward_display, host_session, login_shell
+      return [
+        d.cols,
+        d.rows,
+        d.shell,
+        d.env,
+        d.cwd,
+        d.forward_agent ?? false,
+        d.template ?? null,
+        d.timezone ?? null,
+        d.locale ?? null,
+        d.term ?? null,
+        d.forward_display ?? null,
+        d.host_session ? serializeHostSession(d.host_session) : null,
+        d.login_shell ?? false,
+      ];
     }
     case 'SessionCreated': {
       const d = data as SessionCreated;
@@ -135,33 +210,112 @@ function serializeMessageData(type: string, data: unknown): unknown[] {
       const d = data as SessionResize;
       return [d.session_id, d.cols, d.rows];
     }
+    case 'SessionSignal': {
+      const d = data as SessionSignal;
+      return [d.session_id, d.signal];
+    }
+    case 'UpdateSessionEnv': {
+      const d = data as UpdateSessionEnv;
+      return [d.session_id, d.set, d.unset];
+    }
     case 'SessionData': {
       const d = data as SessionData;
       // Rust order: session_id, stream, data
       return [d.session_id, d.stream, d.data];
     }
+    case 'SessionOutputTruncated': {
+      const d = data as SessionOutputTruncated;
+      return [d.session_id, d.dropped_chunks];
+    }
     case 'SessionClosed': {
       const d = data as SessionClosed;
-      return [d.session_id, d.exit_code, d.signal, d.reason];
+      return [d.session_id, d.exit_code, d.signal, d.reason, d.close_reason ?? null];
+    }
+    case 'SessionMetadataRequest': {
+      const d = data as SessionMetadataRequest;
+      return [d.session_id];
+    }
+    case 'SessionMetadata': {
+      const d = data as SessionMetadata;
+      return [d.session_id, d.cwd, d.last_exit_code, d.last_command, d.command_running];
+    }
+    case 'SessionSearchRequest': {
+      const d = data as SessionSearchRequest;
+      return [d.session_id, d.pattern, d.regex, d.case_sensitive, d.max_results];
+    }
+    case 'SessionSearchResult': {
+      const d = data as SessionSearchResult;
+      // Rust order per match: line, start, end, text
+      return [
+        d.session_id,
+        d.matches.map((m) => [m.line, m.start, m.end, m.text]),
+        d.truncated,
+        d.first_line,
+        d.last_line,
+      ];
+    }
+    case 'ListTemplates':
+      return [];
+    case 'TemplateList': {
+      const d = data as TemplateList;
+      // Rust order per template: name, description, shell, cwd, command, cols, rows
+      return [
+        d.templates.map((t) => [t.name, t.description, t.shell, t.cwd, t.command, t.cols, t.rows]),
+      ];
+    }
+    case 'AgentOpen': {
+      const d = data as AgentOpen;
+      return [d.session_id, d.channel_id];
+    }
+    case 'AgentData': {
+      const d = data as AgentData;
+      return [d.session_id, d.channel_id, d.data];
+    }
+    case 'AgentClose': {
+      const d = data as AgentClose;
+      return [d.session_id, d.channel_id];
+    }
+    case 'DisplayOpen': {
+      const d = data as DisplayOpen;
+      return [d.session_id, d.channel_id];
+    }
+    case 'DisplayData': {
+      const d = data as DisplayData;
+      return [d.session_id, d.channel_id, d.data];
+    }
+    case 'DisplayClose': {
+      const d = data as DisplayClose;
+      return [d.session_id, d.channel_id];
     }
 
     // File messages
     case 'FileListRequest': {
       const d = data as FileListRequest;
-      return [d.path, d.include_hidden];
+      return [d.path, d.include_hidden, d.detail];
     }
     case 'FileListResponse': {
       const d = data as FileListResponse;
-      const entries = d.entries.map((e) => [e.name, e.entry_type, e.size, e.mode, e.modified]);
+      const entries = d.entries.map((e) => [
+        e.name,
+        e.entry_type,
+        e.size,
+        e.mode,
+        e.modified,
+        e.details && serializeEntryDetails(e.details),
+      ]);
       return [d.path, entries];
     }
     case 'FileDownloadRequest': {
       const d = data as FileDownloadRequest;
-      return [d.path, d.offset, d.chunk_size];
+      return [d.path, d.offset, d.chunk_size, d.verify, d.window];
     }
     case 'FileDownloadChunk': {
       const d = data as FileDownloadChunk;
-      return [d.path, d.offset, d.total_size, d.data, d.is_last];
+      return [d.path, d.offset, d.total_size, d.data, d.is_last, d.hash, d.file_hash];
+    }
+    case 'FileDownloadAck': {
+      const d = data as FileDownloadAck;
+      return [d.path, d.offset];
     }
     case 'FileUploadStart': {
       const d = data as FileUploadStart;
@@ -169,11 +323,67 @@ function serializeMessageData(type: string, data: unknown): unknown[] {
     }
     case 'FileUploadChunk': {
       const d = data as FileUploadChunk;
-      return [d.path, d.offset, d.data];
+      return [d.path, d.offset, d.data, d.hash];
     }
     case 'FileUploadComplete': {
       const d = data as FileUploadComplete;
-      return [d.path, d.checksum];
+      return [d.path, d.checksum, d.report];
+    }
+    case 'FileTransferReport': {
+      const d = data as FileTransferReport;
+      return [d.path, d.size, d.hash, d.chunks, d.verified_chunks, d.duration_ms];
+    }
+    case 'FileCopyGrantRequest': {
+      const d = data as FileCopyGrantRequest;
+      return [d.path, d.overwrite];
+    }
+    case 'FileCopyGrant':
+      return serializeCopyGrant(data as FileCopyGrant);
+    case 'FileCopyRequest': {
+      const d = data as FileCopyRequest;
+      return [d.source_path, serializeCopyGrant(d.grant)];
+    }
+    case 'FileReadText': {
+      const d = data as FileReadText;
+      return [d.path];
+    }
+    case 'FileText': {
+      const d = data as FileText;
+      return [d.path, d.content, d.hash];
+    }
+    case 'FileWriteText': {
+      const d = data as FileWriteText;
+      return [d.path, d.content, d.expected_hash];
+    }
+    case 'FileTextWritten': {
+      const d = data as FileTextWritten;
+      return [d.path, d.hash];
+    }
+    case 'FileRootsRequest':
+      return [];
+    case 'FileRoots': {
+      const d = data as FileRoots;
+      return [d.roots.map((r) => [r.path, r.access, r.total_bytes, r.available_bytes])];
+    }
+    case 'FileThumbnailRequest': {
+      const d = data as FileThumbnailRequest;
+      return [d.path, d.max_size];
+    }
+    case 'FileThumbnail': {
+      const d = data as FileThumbnail;
+      return [d.path, d.mime_type, d.jpeg, d.width, d.height];
+    }
+    case 'FileTailRequest': {
+      const d = data as FileTailRequest;
+      return [d.path, d.lines, d.follow];
+    }
+    case 'FileTailData': {
+      const d = data as FileTailData;
+      return [d.path, d.offset, d.data, d.reset];
+    }
+    case 'FileTailStop': {
+      const d = data as FileTailStop;
+      return [d.path];
     }
 
     // Device messages
@@ -183,7 +393,7 @@ function serializeMessageData(type: string, data: unknown): unknown[] {
     }
     case 'DeviceApprovalRequest': {
       const d = data as DeviceApprovalRequest;
-      return [d.device_id, d.name, d.public_key, d.reason];
+      return [d.device_id, d.name, d.public_key, d.reason, d.reinvite_token, d.pairing_code];
     }
     case 'DeviceApproved': {
       const d = data as DeviceApproved;
@@ -193,6 +403,92 @@ function serializeMessageData(type: string, data: unknown): unknown[] {
       const d = data as DeviceRejected;
       return [d.device_id, d.reason, d.retry_allowed];
     }
+    case 'SecurityKeyChallenge': {
+      const d = data as SecurityKeyChallenge;
+      return [
+        d.challenge_id,
+        d.challenge,
+        d.rp_id,
+        d.allowed_credentials,
+        d.action,
+        d.user_verification,
+        d.expires_at,
+      ];
+    }
+    case 'SecurityKeyAssertion': {
+      const d = data as SecurityKeyAssertion;
+      return [
+        d.challenge_id,
+        d.credential_id,
+        d.authenticator_data,
+        d.client_data_json,
+        d.signature,
+      ];
+    }
+    case 'SecurityKeyVerified': {
+      const d = data as SecurityKeyVerified;
+      return [d.challenge_id, d.valid_until];
+    }
+
+    // Host messages
+    case 'SystemInfoRequest':
+      return [];
+    case 'SystemInfo': {
+      const d = data as SystemInfo;
+      return [
+        d.hostname,
+        d.os_name,
+        d.os_version,
+        d.kernel_version,
+        d.arch,
+        d.cpu_count,
+        d.load_average,
+        d.memory_total,
+        d.memory_used,
+        d.swap_total,
+        d.swap_used,
+        d.disks.map((disk) => [disk.path, disk.total, disk.available]),
+        d.uptime_secs,
+      ];
+    }
+    case 'ProcessListRequest': {
+      const d = data as ProcessListRequest;
+      return [d.sort, d.limit];
+    }
+    case 'ProcessList': {
+      const d = data as ProcessList;
+      return [d.processes.map((p) => [p.pid, p.user, p.cpu_usage, p.memory, p.command])];
+    }
+    case 'ProcessKill': {
+      const d = data as ProcessKill;
+      return [d.pid, d.signal];
+    }
+    case 'PowerActionRequest': {
+      const d = data as PowerActionRequest;
+      return [d.action];
+    }
+    case 'PowerActionChallenge': {
+      const d = data as PowerActionChallenge;
+      return [d.token, d.action, d.expires_at];
+    }
+    case 'PowerActionConfirm': {
+      const d = data as PowerActionConfirm;
+      return [d.token];
+    }
+    case 'PowerActionStarted': {
+      const d = data as PowerActionStarted;
+      return [d.action];
+    }
+    case 'ConfigRequest':
+      return [];
+    case 'ConfigView': {
+      const d = data as ConfigView;
+      return [d.toml];
+    }
+    case 'ConfigPatch': {
+      const d = data as ConfigPatch;
+      return [d.idle_timeout, d.file_exclude, d.log_level];
+    }
 
     // Control messages
     case 'Ping': {
@@ -205,12 +501,20 @@ function serializeMessageData(type: string, data: unknown): unknown[] {
     }
     case 'Error': {
       const d = data as ErrorMessage;
-      return [d.code, d.message, d.context, d.recoverable];
+      return [d.code, d.message, d.context, d.recoverable, d.retry_after_ms ?? null];
     }
     case 'Capabilities': {
       const d = data as Capabilities;
       return [d.protocol_versions, d.features, d.max_message_size, d.max_sessions, d.compression];
     }
+    case 'Notification': {
+      const d = data as Notification;
+      return [d.level, d.title, d.body, d.category, d.session_id ?? null];
+    }
+    case 'ServerShutdownPending': {
+      const d = data as ServerShutdownPending;
+      return [d.reason, d.grace_ms];
+    }
 
     default:
       throw new Error(`Unknown message type: ${type}`);
@@ -239,6 +543,15 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         shell: arr[2] as string | null,
         env: arr[3] as Array<[string, string]>,
         cwd: arr[4] as string | null,
+        // Absent in messages from older clients
+        forward_agent: (arr[5] as boolean | undefined) ?? false,
+        template: (arr[6] as string | null | undefined) ?? null,
+        timezone: (arr[7] as string | null | undefined) ?? null,
+        locale: (arr[8] as string | null | undefined) ?? null,
+        term: (arr[9] as string | null | undefined) ?? null,
+        forward_display: (arr[10] as DisplayProtocol | null | undefined) ?? null,
+        host_session: arr[11] ? deserializeHostSession(arr[11] as unknown[]) : null,
+        login_shell: (arr[12] as boolean | undefined) ?? false,
       } satisfies SessionCreate;
 
     case 'SessionCreated':
@@ -270,6 +583,19 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         rows: arr[2] as number,
       } satisfies SessionResize;
 
+    case 'SessionSignal':
+      return {
+        session_id: arr[0] as string,
+        signal: arr[1] as ProcessSignal,
+      } satisfies SessionSignal;
+
+    case 'UpdateSessionEnv':
+      return {
+        session_id: arr[0] as string,
+        set: arr[1] as Array<[string, string]>,
+        unset: arr[2] as string[],
+      } satisfies UpdateSessionEnv;
+
     case 'SessionData':
       return {
         session_id: arr[0] as string,
@@ -277,19 +603,124 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         data: ensureUint8Array(arr[2]),
       } satisfies SessionData;
 
+    case 'SessionOutputTruncated':
+      return {
+        session_id: arr[0] as string,
+        dropped_chunks: arr[1] as number,
+      } satisfies SessionOutputTruncated;
+
     case 'SessionClosed':
       return {
         session_id: arr[0] as string,
         exit_code: arr[1] as number | null,
         signal: arr[2] as number | null,
         reason: arr[3] as string | null,
+        close_reason: (arr[4] as SessionCloseReason | null | undefined) ?? null,
       } satisfies SessionClosed;
 
+    case 'SessionMetadataRequest':
+      return {
+        session_id: arr[0] as string,
+      } satisfies SessionMetadataRequest;
+
+    case 'SessionMetadata':
+      return {
+        session_id: arr[0] as string,
+        cwd: arr[1] as string | null,
+        last_exit_code: arr[2] as number | null,
+        last_command: arr[3] as string | null,
+        command_running: arr[4] as boolean,
+      } satisfies SessionMetadata;
+
+    case 'SessionSearchRequest':
+      return {
+        session_id: arr[0] as string,
+        pattern: arr[1] as string,
+        regex: arr[2] as boolean,
+        case_sensitive: arr[3] as boolean,
+        max_results: arr[4] as number,
+      } satisfies SessionSearchRequest;
+
+    case 'SessionSearchResult':
+      return {
+        session_id: arr[0] as string,
+        matches: (arr[1] as unknown[][]).map(
+          (m) =>
+            ({
+              line: m[0] as number,
+              start: m[1] as number,
+              end: m[2] as number,
+              text: m[3] as string,
+            }) satisfies SearchMatch
+        ),
+        truncated: arr[2] as boolean,
+        first_line: arr[3] as number,
+        last_line: arr[4] as number,
+      } satisfies SessionSearchResult;
+
+    case 'ListTemplates':
+      return {};
+
+    case 'TemplateList':
+      return {
+        templates: (arr[0] as unknown[][]).map(
+          (t) =>
+            ({
+              name: t[0] as string,
+              description: t[1] as string | null,
+              shell: t[2] as string | null,
+              cwd: t[3] as string | null,
+              command: t[4] as string | null,
+              cols: t[5] as number | null,
+              rows: t[6] as number | null,
+            }) satisfies TemplateInfo
+        ),
+      } satisfies TemplateList;
+
+    case 'AgentOpen':
+      return {
+        session_id: arr[0] as string,
+        channel_id: arr[1] as number,
+      } satisfies AgentOpen;
+
+    case 'AgentData':
+      return {
+        session_id: arr[0] as string,
+        channel_id: arr[1] as number,
+        data: ensureUint8Array(arr[2]),
+      } satisfies AgentData;
+
+    case 'AgentClose':
+      return {
+        session_id: arr[0] as string,
+        channel_id: arr[1] as number,
+      } satisfies AgentClose;
+
+    case 'DisplayOpen':
+      return {
+        session_id: arr[0] as string,
+        channel_id: arr[1] as number,
+      } satisfies DisplayOpen;
+
+    case 'DisplayData':
+      return {
+        session_id: arr[0] as string,
+        channel_id: arr[1] as number,
+        data: ensureUint8Array(arr[2]),
+      } satisfies DisplayData;
+
+    case 'DisplayClose':
+      return {
+        session_id: arr[0] as string,
+        channel_id: arr[1] as number,
+      } satisfies DisplayClose;
+
     // File messages
     case 'FileListRequest':
       return {
         path: arr[0] as string,
         include_hidden: arr[1] as boolean,
+        detail: (arr[2] as FileListDetail | undefined) ?? 'Basic',
       } satisfies FileListRequest;
 
     case 'FileListResponse': {
@@ -299,6 +730,7 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         size: e[2] as number,
         mode: e[3] as number,
         modified: e[4] as number,
+        details: e[5] == null ? null : deserializeEntryDetails(e[5] as unknown[]),
       }));
       return {
         path: arr[0] as string,
@@ -311,6 +743,8 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         path: arr[0] as string,
         offset: arr[1] as number,
         chunk_size: arr[2] as number,
+        verify: (arr[3] as boolean | undefined) ?? false,
+        window: (arr[4] as number | undefined) ?? 0,
       } satisfies FileDownloadRequest;
 
     case 'FileDownloadChunk':
@@ -320,8 +754,16 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         total_size: arr[2] as number,
         data: ensureUint8Array(arr[3]),
         is_last: arr[4] as boolean,
+        hash: arr[5] == null ? null : ensureUint8Array(arr[5]),
+        file_hash: arr[6] == null ? null : ensureUint8Array(arr[6]),
       } satisfies FileDownloadChunk;
 
+    case 'FileDownloadAck':
+      return {
+        path: arr[0] as string,
+        offset: arr[1] as number,
+      } satisfies FileDownloadAck;
+
     case 'FileUploadStart':
       return {
         path: arr[0] as string,
@@ -335,14 +777,114 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         path: arr[0] as string,
         offset: arr[1] as number,
         data: ensureUint8Array(arr[2]),
+        hash: arr[3] == null ? null : ensureUint8Array(arr[3]),
       } satisfies FileUploadChunk;
 
     case 'FileUploadComplete':
       return {
         path: arr[0] as string,
         checksum: ensureUint8Array(arr[1]),
+        report: (arr[2] as boolean | undefined) ?? false,
       } satisfies FileUploadComplete;
 
+    case 'FileTransferReport':
+      return {
+        path: arr[0] as string,
+        size: arr[1] as number,
+        hash: ensureUint8Array(arr[2]),
+        chunks: arr[3] as number,
+        verified_chunks: arr[4] as number,
+        duration_ms: arr[5] as number,
+      } satisfies FileTransferReport;
+
+    case 'FileCopyGrantRequest':
+      return {
+        path: arr[0] as string,
+        overwrite: arr[1] as boolean,
+      } satisfies FileCopyGrantRequest;
+
+    case 'FileCopyGrant':
+      return deserializeCopyGrant(arr);
+
+    case 'FileCopyRequest':
+      return {
+        source_path: arr[0] as string,
+        grant: deserializeCopyGrant(arr[1] as unknown[]),
+      } satisfies FileCopyRequest;
+
+    case 'FileReadText':
+      return {
+        path: arr[0] as string,
+      } satisfies FileReadText;
+
+    case 'FileText':
+      return {
+        path: arr[0] as string,
+        content: arr[1] as string,
+        hash: ensureUint8Array(arr[2]),
+      } satisfies FileText;
+
+    case 'FileWriteText':
+      return {
+        path: arr[0] as string,
+        content: arr[1] as string,
+        expected_hash: arr[2] == null ? null : ensureUint8Array(arr[2]),
+      } satisfies FileWriteText;
+
+    case 'FileTextWritten':
+      return {
+        path: arr[0] as string,
+        hash: ensureUint8Array(arr[1]),
+      } satisfies FileTextWritten;
+
+    case 'FileRootsRequest':
+      return {};
+
+    case 'FileRoots': {
+      const roots = (arr[0] as unknown[][]).map((r) => ({
+        path: r[0] as string,
+        access: r[1] as FileAccess,
+        total_bytes: (r[2] as number | null) ?? null,
+        available_bytes: (r[3] as number | null) ?? null,
+      }));
+      return { roots } satisfies FileRoots;
+    }
+
+    case 'FileThumbnailRequest':
+      return {
+        path: arr[0] as string,
+        max_size: arr[1] as number,
+      } satisfies FileThumbnailRequest;
+
+    case 'FileThumbnail':
+      return {
+        path: arr[0] as string,
+        mime_type: arr[1] as string,
+        jpeg: arr[2] == null ? null : ensureUint8Array(arr[2]),
+        width: arr[3] as number,
+        height: arr[4] as number,
+      } satisfies FileThumbnail;
+
+    case 'FileTailRequest':
+      return {
+        path: arr[0] as string,
+        lines: arr[1] as number,
+        follow: arr[2] as boolean,
+      } satisfies FileTailRequest;
+
+    case 'FileTailData':
+      return {
+        path: arr[0] as string,
+        offset: arr[1] as number,
+        data: ensureUint8Array(arr[2]),
+        reset: arr[3] as boolean,
+      } satisfies FileTailData;
+
+    case 'FileTailStop':
+      return {
+        path: arr[0] as string,
+      } satisfies FileTailStop;
+
     // Device messages
     case 'DeviceInfo':
       return {
@@ -360,6 +902,9 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         name: arr[1] as string,
         public_key: ensureUint8Array(arr[2]),
         reason: arr[3] as string | null,
+        // Absent when encoded by daemons that predate re-invites
+        reinvite_token: (arr[4] as string | null | undefined) ?? null,
+        pairing_code: (arr[5] as string | null | undefined) ?? null,
       } satisfies DeviceApprovalRequest;
 
     case 'DeviceApproved':
@@ -376,6 +921,123 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         retry_allowed: arr[2] as boolean,
       } satisfies DeviceRejected;
 
+    case 'SecurityKeyChallenge':
+      return {
+        challenge_id: arr[0] as string,
+        challenge: ensureUint8Array(arr[1]),
+        rp_id: arr[2] as string,
+        allowed_credentials: arr[3] as string[],
+        action: arr[4] as string,
+        user_verification: arr[5] as boolean,
+        expires_at: arr[6] as number,
+      } satisfies SecurityKeyChallenge;
+
+    case 'SecurityKeyAssertion':
+      return {
+        challenge_id: arr[0] as string,
+        credential_id: ensureUint8Array(arr[1]),
+        authenticator_data: ensureUint8Array(arr[2]),
+        client_data_json: ensureUint8Array(arr[3]),
+        signature: ensureUint8Array(arr[4]),
+      } satisfies SecurityKeyAssertion;
+
+    case 'SecurityKeyVerified':
+      return {
+        challenge_id: arr[0] as string,
+        valid_until: arr[1] as number,
+      } satisfies SecurityKeyVerified;
+
+    // Host messages
+    case 'SystemInfoRequest':
+      return {};
+
+    case 'SystemInfo':
+      return {
+        hostname: arr[0] as string,
+        os_name: arr[1] as string | null,
+        os_version: arr[2] as string | null,
+        kernel_version: arr[3] as string | null,
+        arch: arr[4] as string,
+        cpu_count: arr[5] as number,
+        load_average: arr[6] as [number, number, number],
+        memory_total: arr[7] as number,
+        memory_used: arr[8] as number,
+        swap_total: arr[9] as number,
+        swap_used: arr[10] as number,
+        disks: (arr[11] as unknown[][]).map(
+          (disk) =>
+            ({
+              path: disk[0] as string,
+              total: disk[1] as number,
+              available: disk[2] as number,
+            }) satisfies DiskUsage
+        ),
+        uptime_secs: arr[12] as number,
+      } satisfies SystemInfo;
+
+    case 'ProcessListRequest':
+      return {
+        sort: arr[0] as ProcessSort | null,
+        limit: arr[1] as number | null,
+      } satisfies ProcessListRequest;
+
+    case 'ProcessList':
+      return {
+        processes: (arr[0] as unknown[][]).map(
+          (p) =>
+            ({
+              pid: p[0] as number,
+              user: p[1] as string | null,
+              cpu_usage: p[2] as number,
+              memory: p[3] as number,
+              command: p[4] as string,
+            }) satisfies ProcessInfo
+        ),
+      } satisfies ProcessList;
+
+    case 'ProcessKill':
+      return {
+        pid: arr[0] as number,
+        signal: arr[1] as ProcessSignal,
+      } satisfies ProcessKill;
+
+    case 'PowerActionRequest':
+      return {
+        action: arr[0] as PowerAction,
+      } satisfies PowerActionRequest;
+
+    case 'PowerActionChallenge':
+      return {
+        token: arr[0] as string,
+        action: arr[1] as PowerAction,
+        expires_at: arr[2] as number,
+      } satisfies PowerActionChallenge;
+
+    case 'PowerActionConfirm':
+      return {
+        token: arr[0] as string,
+      } satisfies PowerActionConfirm;
+
+    case 'PowerActionStarted':
+      return {
+        action: arr[0] as PowerAction,
+      } satisfies PowerActionStarted;
+
+    case 'ConfigRequest':
+      return {};
+
+    case 'ConfigView':
+      return {
+        toml: arr[0] as string,
+      } satisfies ConfigView;
+
+    case 'ConfigPatch':
+      return {
+        idle_timeout: arr[0] as number | null,
+        file_exclude: arr[1] as string[] | null,
+        log_level: arr[2] as string | null,
+      } satisfies ConfigPatch;
+
     // Control messages
     case 'Ping':
       return {
@@ -395,6 +1057,7 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         message: arr[1] as string,
         context: arr[2] as string | null,
         recoverable: arr[3] as boolean,
+        retry_after_ms: (arr[4] as number | null | undefined) ?? null,
       } satisfies ErrorMessage;
 
     case 'Capabilities':
@@ -406,11 +1069,95 @@ function deserializeMessageData(type: string, arr: unknown[]): unknown {
         compression: arr[4] as string[],
       } satisfies Capabilities;
 
+    case 'Notification':
+      return {
+        level: arr[0] as NotificationLevel,
+        title: arr[1] as string,
+        body: arr[2] as string,
+        category: arr[3] as NotificationCategory,
+        session_id: (arr[4] as string | null | undefined) ?? null,
+      } satisfies Notification;
+
+    case 'ServerShutdownPending':
+      return {
+        reason: arr[0] as string,
+        grace_ms: arr[1] as number,
+      } satisfies ServerShutdownPending;
+
     default:
       throw new Error(`Unknown message type: ${type}`);
   }
 }
 
+/**
+ * Serialize the host multiplexer session of a SessionCreate.
+ */
+function serializeHostSession(session: HostSession): unknown[] {
+  return [session.multiplexer, session.name, session.create];
+}
+
+/**
+ * Deserialize the host multiplexer session of a SessionCreate.
+ */
+function deserializeHostSession(arr: unknown[]): HostSession {
+  return {
+    multiplexer: arr[0] as HostMultiplexer,
+    name: arr[1] as string,
+    create: arr[2] as boolean,
+  };
+}
+
+/**
+ * Serialize a copy grant, on its own or inside a FileCopyRequest.
+ */
+function serializeCopyGrant(grant: FileCopyGrant): unknown[] {
+  return [grant.path, grant.token, grant.addresses, grant.public_key, grant.expires_in_secs];
+}
+
+/**
+ * Deserialize a copy grant, on its own or inside a FileCopyRequest.
+ */
+function deserializeCopyGrant(arr: unknown[]): FileCopyGrant {
+  return {
+    path: arr[0] as string,
+    token: ensureUint8Array(arr[1]),
+    addresses: arr[2] as string[],
+    public_key: ensureUint8Array(arr[3]),
+    expires_in_secs: arr[4] as number,
+  };
+}
+
+/**
+ * Serialize the details of a listed FileEntry.
+ */
+function serializeEntryDetails(details: FileEntryDetails): unknown[] {
+  return [
+    details.uid,
+    details.gid,
+    details.owner,
+    details.group,
+    details.symlink_target,
+    details.xattrs.map((x) => [x.name, x.value]),
+  ];
+}
+
+/**
+ * Deserialize the details of a listed FileEntry.
+ */
+function deserializeEntryDetails(arr: unknown[]): FileEntryDetails {
+  return {
+    uid: arr[0] as number,
+    gid: arr[1] as number,
+    owner: (arr[2] as string | null) ?? null,
+    group: (arr[3] as string | null) ?? null,
+    symlink_target: (arr[4] as string | null) ?? null,
+    xattrs: (arr[5] as unknown[][]).map((x) => ({
+      name: x[0] as string,
+      value: ensureUint8Array(x[1]),
+    })),
+  };
+}
+
 /**
  * Ensure a value is a Uint8Array.
  */
@@ -504,23 +1251,71 @@ const VALID_MESSAGE_TYPES = [
   'SessionDetach',
   'SessionKill',
   'SessionResize',
+  'SessionSignal',
+  'UpdateSessionEnv',
   'SessionData',
+  'SessionOutputTruncated',
   'SessionClosed',
+  'SessionMetadataRequest',
+  'SessionMetadata',
+  'SessionSearchRequest',
+  'SessionSearchResult',
+  'ListTemplates',
+  'TemplateList',
+  'AgentOpen',
+  'AgentData',
+  'AgentClose',
+  'DisplayOpen',
+  'DisplayData',
+  'DisplayClose',
   'FileListRequest',
   'FileListResponse',
   'FileDownloadRequest',
   'FileDownloadChunk',
+  'FileDownloadAck',
   'FileUploadStart',
   'FileUploadChunk',
   'FileUploadComplete',
+  'FileTransferReport',
+  'FileCopyGrantRequest',
+  'FileCopyGrant',
+  'FileCopyRequest',
+  'FileReadText',
+  'FileText',
+  'FileWriteText',
+  'FileTextWritten',
+  'FileRootsRequest',
+  'FileRoots',
+  'FileThumbnailRequest',
+  'FileThumbnail',
+  'FileTailRequest',
+  'FileTailData',
+  'FileTailStop',
   'DeviceInfo',
   'DeviceApprovalRequest',
   'DeviceApproved',
   'DeviceRejected',
+  'SecurityKeyChallenge',
+  'SecurityKeyAssertion',
+  'SecurityKeyVerified',
+  'SystemInfoRequest',
+  'SystemInfo',
+  'ProcessListRequest',
+  'ProcessList',
+  'ProcessKill',
+  'PowerActionRequest',
+  'PowerActionChallenge',
+  'PowerActionConfirm',
+  'PowerActionStarted',
+  'ConfigRequest',
+  'ConfigView',
+  'ConfigPatch',
   'Ping',
   'Pong',
   'Error',
   'Capabilities',
+  'Notification',
+  'ServerShutdownPending',
 ];
 
 /**
@@ -102,8 +102,18 @@ export interface PairingData {
   public_key: string;
   /** Relay/signaling server WebSocket URL */
   relay_url: string;
+  /**
+   * Unix timestamp (seconds) on the daemon's clock when this pairing code was
+   * generated. Absent or 0 from daemons that predate it.
+   */
+  issued_at?: number;
   /** Unix timestamp (seconds) when this pairing code expires */
   expires: number;
+  /**
+   * Seconds the local clock is ahead of the daemon's (negative if behind),
+   * measured when the payload was read. Not part of the daemon's payload.
+   */
+  clock_offset?: number;
 }
 
 /**
@@ -125,12 +135,13 @@ export type PairingParseResult =
  * - Raw base58 encoded data
  * - Web app URL with ?peer=XXXX-XXXX parameter
  * - Short pairing code XXXX-XXXX
+ * - Numeric pairing code DDD-DDD-DDDD (9 digits + Luhn check digit)
  */
 const REMOSHELL_URL_PREFIX = 'remoshell://connect/';
 const SHORT_URL_PREFIX = 'rs://';
 
 /** Pattern to extract peer code from web app URL */
-const WEB_APP_URL_PATTERN = /[?&]peer=([A-Z0-9]+-[A-Z0-9]+)/i;
+const WEB_APP_URL_PATTERN = /[?&]peer=([A-Z0-9]+(?:-[A-Z0-9]+)+)/i;
 
 /**
  * Check if a string is a short pairing code (XXXX-XXXX format)
@@ -139,6 +150,36 @@ export function isShortPairingCode(s: string): boolean {
   return /^[A-Z0-9]{4}-[A-Z0-9]{4}$/i.test(s.trim());
 }
 
+/**
+ * Compute the Luhn check digit for a string of digits
+ */
+function luhnCheckDigit(digits: string): number {
+  let sum = 0;
+  for (let i = 0; i < digits.length; i++) {
+    let d = Number(digits[digits.length - 1 - i]);
+    if (i % 2 === 0) {
+      d *= 2;
+      if (d > 9) d -= 9;
+    }
+    sum += d;
+  }
+  return (10 - (sum % 10)) % 10;
+}
+
+/**
+ * Normalize a numeric pairing code (9 digits + Luhn check digit) to the
+ * DDD-DDD-DDDD form the daemon registers.
+ *
+ * Spaces and hyphens are ignored. Returns null if the input is not a
+ * 10-digit code or its check digit does not match.
+ */
+export function normalizeNumericPairingCode(s: string): string | null {
+  const digits = s.replace(/[-\s]/g, '');
+  if (!/^\d{10}$/.test(digits)) return null;
+  if (luhnCheckDigit(digits.slice(0, 9)) !== Number(digits[9])) return null;
+  return `${digits.slice(0, 3)}-${digits.slice(3, 6)}-${digits.slice(6)}`;
+}
+
 /**
  * Look up a short pairing code from the signaling server
  */
@@ -163,12 +204,13 @@ export async function lookupPairingCode(code: string): Promise<PairingParseResul
 
     return {
       success: true,
-      data: {
+      data: withClockOffset({
         device_id: data.device_id,
         public_key: data.public_key,
         relay_url: data.relay_url,
+        issued_at: typeof data.issued_at === 'number' ? data.issued_at : undefined,
         expires: data.expires,
-      },
+      }),
     };
   } catch (error) {
     return {
@@ -287,6 +329,15 @@ export function parsePairingData(qrContent: string): PairingParseResult {
     return { success: false, error: 'SHORT_CODE', shortCode: trimmed.toUpperCase() };
   }
 
+  // Numeric pairing code format: DDD-DDD-DDDD entered manually
+  if (/^[\d\s-]+$/.test(trimmed) && trimmed.replace(/[-\s]/g, '').length === 10) {
+    const numericCode = normalizeNumericPairingCode(trimmed);
+    if (!numericCode) {
+      return { success: false, error: 'Invalid numeric pairing code (check digit mismatch)' };
+    }
+    return { success: false, error: 'SHORT_CODE', shortCode: numericCode };
+  }
+
   // Try to determine the format
   if (trimmed.startsWith(REMOSHELL_URL_PREFIX)) {
     // Legacy format: remoshell://connect/<base58-data>
@@ -349,12 +400,13 @@ export function parsePairingData(qrContent: string): PairingParseResult {
     }
 
     // Build pairing data
-    const pairingData: PairingData = {
+    const pairingData: PairingData = withClockOffset({
       device_id: data.device_id,
       public_key: data.public_key,
       relay_url: data.relay_url,
+      issued_at: typeof data.issued_at === 'number' ? data.issued_at : undefined,
       expires: data.expires,
-    };
+    });
 
     return { success: true, data: pairingData };
   } catch (error) {
@@ -373,7 +425,49 @@ export function parsePairingData(qrContent: string): PairingParseResult {
  * Uses the same format as the daemon (raw JSON)
  */
 export function createPairingQRContent(data: PairingData): string {
-  return JSON.stringify(data);
+  const payload: PairingData = { ...data };
+  delete payload.clock_offset;
+  return JSON.stringify(payload);
+}
+
+/**
+ * Clock drift (seconds) beyond which pairing warns about a skewed clock
+ */
+export const CLOCK_SKEW_WARNING_SECONDS = 60;
+
+/**
+ * Measure how far the local clock is from the daemon's, in seconds.
+ *
+ * A payload read between its issue time and its expiry is taken to come from
+ * a daemon whose clock agrees with ours, the gap being the time the code was
+ * on screen. Outside that window, the difference from the issue time is
+ * counted as skew: a code that looks issued in the future means our clock is
+ * behind, and one that looks already expired is assumed fresh, since the
+ * daemon still rejects codes that really expired.
+ */
+export function measureClockOffset(
+  pairingData: PairingData,
+  now: number = Math.floor(Date.now() / 1000)
+): number {
+  const issuedAt = pairingData.issued_at;
+  if (!issuedAt) return 0;
+  if (now >= issuedAt && now <= pairingData.expires) return 0;
+  return now - issuedAt;
+}
+
+/**
+ * Record the clock offset of freshly read pairing data
+ */
+function withClockOffset(pairingData: PairingData): PairingData {
+  if (!pairingData.issued_at) return pairingData;
+  return { ...pairingData, clock_offset: measureClockOffset(pairingData) };
+}
+
+/**
+ * Expiry of a pairing code on the local clock
+ */
+function localExpiry(pairingData: PairingData): number {
+  return pairingData.expires + (pairingData.clock_offset ?? 0);
 }
 
 /**
@@ -381,7 +475,7 @@ export function createPairingQRContent(data: PairingData): string {
  */
 export function isPairingExpired(pairingData: PairingData): boolean {
   const now = Math.floor(Date.now() / 1000);
-  return now > pairingData.expires;
+  return now > localExpiry(pairingData);
 }
 
 /**
@@ -389,7 +483,19 @@ export function isPairingExpired(pairingData: PairingData): boolean {
  */
 export function secondsUntilExpiry(pairingData: PairingData): number {
   const now = Math.floor(Date.now() / 1000);
-  return Math.max(0, pairingData.expires - now);
+  return Math.max(0, localExpiry(pairingData) - now);
+}
+
+/**
+ * Describe a clock skew large enough to confuse pairing, or null if none
+ */
+export function clockSkewWarning(pairingData: PairingData): string | null {
+  const offset = pairingData.clock_offset ?? 0;
+  if (Math.abs(offset) <= CLOCK_SKEW_WARNING_SECONDS) return null;
+  const minutes = Math.round(Math.abs(offset) / 60);
+  const amount = minutes >= 1 ? `${minutes} minute${minutes === 1 ? '' : 's'}` : `${Math.abs(offset)} seconds`;
+  return `This device's clock is ${amount} ${offset > 0 ? 'ahead of' : 'behind'} the daemon's. ` +
+    'Pairing will continue, but check the date and time settings if it fails.';
 }
 
 // ============================================================================
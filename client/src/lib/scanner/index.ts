@@ -35,6 +35,7 @@ export {
   type ScannerPlatform,
   // Pairing data
   parsePairingData,
+  normalizeNumericPairingCode,
   createPairingQRContent,
   isValidBase58,
   decodeBase58,
@@ -0,0 +1,68 @@
+import { describe, it, expect, vi, afterEach } from 'vitest';
+import { answerSecurityKeyChallenge, fromBase64Url, toBase64Url } from './SecurityKey';
+import type { SecurityKeyChallenge } from '../protocol';
+
+describe('SecurityKey', () => {
+  afterEach(() => {
+    vi.unstubAllGlobals();
+  });
+
+  it('round-trips base64url', () => {
+    const bytes = new Uint8Array([0xfb, 0xff, 0x00, 0x10, 0x3e]);
+    const encoded = toBase64Url(bytes);
+    expect(encoded).not.toMatch(/[+/=]/);
+    expect(fromBase64Url(encoded)).toEqual(bytes);
+  });
+
+  it('answers a challenge with a WebAuthn assertion', async () => {
+    const get = vi.fn().mockResolvedValue({
+      rawId: new Uint8Array([1, 2, 3]).buffer,
+      response: {
+        authenticatorData: new Uint8Array([4]).buffer,
+        clientDataJSON: new Uint8Array([5]).buffer,
+        signature: new Uint8Array([6]).buffer,
+      },
+    });
+    vi.stubGlobal('navigator', { credentials: { get } });
+
+    const challenge: SecurityKeyChallenge = {
+      challenge_id: 'challenge-1',
+      challenge: new Uint8Array([9, 9, 9]),
+      rp_id: 'remoshell',
+      allowed_credentials: [toBase64Url(new Uint8Array([1, 2, 3]))],
+      action: 'session_create',
+      user_verification: true,
+      expires_at: Math.floor(Date.now() / 1000) + 60,
+    };
+
+    const assertion = await answerSecurityKeyChallenge(challenge);
+
+    const options = get.mock.calls[0][0].publicKey;
+    expect(options.rpId).toBe('remoshell');
+    expect(options.userVerification).toBe('required');
+    expect(options.allowCredentials[0].id).toEqual(new Uint8Array([1, 2, 3]));
+    expect(assertion).toEqual({
+      challenge_id: 'challenge-1',
+      credential_id: new Uint8Array([1, 2, 3]),
+      authenticator_data: new Uint8Array([4]),
+      client_data_json: new Uint8Array([5]),
+      signature: new Uint8Array([6]),
+    });
+  });
+
+  it('fails when no credential is returned', async () => {
+    vi.stubGlobal('navigator', { credentials: { get: vi.fn().mockResolvedValue(null) } });
+
+    await expect(
+      answerSecurityKeyChallenge({
+        challenge_id: 'challenge-1',
+        challenge: new Uint8Array([1]),
+        rp_id: 'remoshell',
+        allowed_credentials: [],
+        action: 'file_write',
+        user_verification: false,
+        expires_at: 0,
+      })
+    ).rejects.toThrow();
+  });
+});
@@ -0,0 +1,147 @@
+/**
+ * SecurityKey - FIDO2 security key assertions for gated daemon actions
+ * Uses the WebAuthn API (navigator.credentials), which talks CTAP2 to the
+ * authenticator, to answer SecurityKeyChallenge messages from the daemon
+ */
+
+import type { SecurityKeyAssertion, SecurityKeyChallenge } from '../protocol';
+
+/** COSE algorithm identifier for ES256 (ECDSA P-256 with SHA-256) */
+const COSE_ALG_ES256 = -7;
+
+/** Length of an uncompressed SEC1 P-256 public key */
+const SEC1_P256_LENGTH = 65;
+
+/**
+ * A newly registered credential, in the form expected by the daemon's
+ * `[[security.fido2.credentials]]` configuration
+ */
+export interface RegisteredSecurityKey {
+  /** Base64url-encoded credential ID */
+  id: string;
+  /** Base64-encoded SEC1 P-256 public key */
+  publicKey: string;
+}
+
+/**
+ * Options for registering a security key
+ */
+export interface RegisterSecurityKeyOptions {
+  /** Relying party ID configured on the daemon (`security.fido2.rp_id`) */
+  rpId: string;
+  /** Name of the user the key is registered for */
+  userName: string;
+  /** Require user verification (PIN or biometrics) */
+  userVerification?: boolean;
+}
+
+/**
+ * Encode bytes as unpadded base64url
+ */
+export function toBase64Url(bytes: Uint8Array): string {
+  let binary = '';
+  for (const byte of bytes) {
+    binary += String.fromCharCode(byte);
+  }
+  return btoa(binary).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+}
+
+/**
+ * Decode base64url, with or without padding
+ */
+export function fromBase64Url(value: string): Uint8Array {
+  const base64 = value.replace(/-/g, '+').replace(/_/g, '/');
+  const padded = base64 + '='.repeat((4 - (base64.length % 4)) % 4);
+  return Uint8Array.from(atob(padded), (c) => c.charCodeAt(0));
+}
+
+/**
+ * Check whether WebAuthn is available in this environment
+ */
+export function isSecurityKeySupported(): boolean {
+  return (
+    typeof navigator !== 'undefined' &&
+    typeof navigator.credentials?.get === 'function' &&
+    typeof PublicKeyCredential !== 'undefined'
+  );
+}
+
+/**
+ * Ask the user to touch their security key and answer a daemon challenge
+ */
+export async function answerSecurityKeyChallenge(
+  challenge: SecurityKeyChallenge
+): Promise<SecurityKeyAssertion> {
+  const timeout = Math.max(challenge.expires_at * 1000 - Date.now(), 0);
+  const credential = (await navigator.credentials.get({
+    publicKey: {
+      challenge: challenge.challenge,
+      rpId: challenge.rp_id,
+      allowCredentials: challenge.allowed_credentials.map((id) => ({
+        type: 'public-key',
+        id: fromBase64Url(id),
+      })),
+      userVerification: challenge.user_verification ? 'required' : 'discouraged',
+      timeout,
+    },
+  })) as PublicKeyCredential | null;
+
+  if (!credential) {
+    throw new Error('No security key assertion was produced');
+  }
+
+  const response = credential.response as AuthenticatorAssertionResponse;
+  return {
+    challenge_id: challenge.challenge_id,
+    credential_id: new Uint8Array(credential.rawId),
+    authenticator_data: new Uint8Array(response.authenticatorData),
+    client_data_json: new Uint8Array(response.clientDataJSON),
+    signature: new Uint8Array(response.signature),
+  };
+}
+
+/**
+ * Register a new security key and return the values to add to the daemon
+ * configuration
+ */
+export async function registerSecurityKey(
+  options: RegisterSecurityKeyOptions
+): Promise<RegisteredSecurityKey> {
+  const credential = (await navigator.credentials.create({
+    publicKey: {
+      challenge: crypto.getRandomValues(new Uint8Array(32)),
+      rp: { id: options.rpId, name: 'RemoShell' },
+      user: {
+        id: crypto.getRandomValues(new Uint8Array(16)),
+        name: options.userName,
+        displayName: options.userName,
+      },
+      pubKeyCredParams: [{ type: 'public-key', alg: COSE_ALG_ES256 }],
+      authenticatorSelection: {
+        authenticatorAttachment: 'cross-platform',
+        userVerification: options.userVerification ? 'required' : 'discouraged',
+      },
+      attestation: 'none',
+    },
+  })) as PublicKeyCredential | null;
+
+  if (!credential) {
+    throw new Error('No security key was registered');
+  }
+
+  const response = credential.response as AuthenticatorAttestationResponse;
+  if (response.getPublicKeyAlgorithm() !== COSE_ALG_ES256) {
+    throw new Error('Security key does not support ES256');
+  }
+  const spki = response.getPublicKey();
+  if (!spki) {
+    throw new Error('Security key did not return a public key');
+  }
+
+  // The SPKI encoding of a P-256 key ends with the uncompressed SEC1 point
+  const publicKey = new Uint8Array(spki).slice(-SEC1_P256_LENGTH);
+  return {
+    id: toBase64Url(new Uint8Array(credential.rawId)),
+    publicKey: btoa(String.fromCharCode(...publicKey)),
+  };
+}
@@ -0,0 +1,14 @@
+/**
+ * Security module exports
+ * Provides FIDO2 security key support for daemon actions gated on an assertion
+ */
+
+export {
+  answerSecurityKeyChallenge,
+  registerSecurityKey,
+  isSecurityKeySupported,
+  toBase64Url,
+  fromBase64Url,
+  type RegisteredSecurityKey,
+  type RegisterSecurityKeyOptions,
+} from './SecurityKey';
@@ -160,6 +160,7 @@ describe('TauriIPCBridge', () => {
       const mockResponse = {
         initialized: true,
         node_id: 'node-123',
+        profile: 'default',
       };
       mockTauriAPI.core.invoke.mockResolvedValue(mockResponse);
 
@@ -413,6 +414,181 @@ describe('TauriIPCBridge', () => {
     });
   });
 
+  // ==========================================================================
+  // Connection History Commands
+  // ==========================================================================
+
+  describe('getConnectionAnalytics', () => {
+    it('should call get_connection_analytics command', async () => {
+      const response = { days: 7, since: 1000, devices: [] };
+      mockTauriAPI.core.invoke.mockResolvedValue(response);
+
+      const result = await bridge.getConnectionAnalytics(7);
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('get_connection_analytics', {
+        days: 7,
+      });
+      expect(result).toEqual(response);
+    });
+  });
+
+  describe('setConnectionRetention', () => {
+    it('should call set_connection_retention command', async () => {
+      mockTauriAPI.core.invoke.mockResolvedValue(3);
+
+      const policy = { max_rows: 100, max_age_days: 30 };
+      const result = await bridge.setConnectionRetention(policy);
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('set_connection_retention', {
+        policy,
+      });
+      expect(result).toBe(3);
+    });
+  });
+
+  // ==========================================================================
+  // Identity Backup Commands
+  // ==========================================================================
+
+  describe('exportIdentity', () => {
+    it('should call export_identity command', async () => {
+      mockTauriAPI.core.invoke.mockResolvedValue({ path: '/tmp/backup.json', device_count: 2 });
+
+      const request = { path: '/tmp/backup.json', passphrase: 'correct horse' };
+      const result = await bridge.exportIdentity(request);
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('export_identity', { request });
+      expect(result.device_count).toBe(2);
+    });
+  });
+
+  describe('importIdentity', () => {
+    it('should call import_identity command', async () => {
+      const response = { imported_devices: 2, skipped_devices: 0, identity_restored: true };
+      mockTauriAPI.core.invoke.mockResolvedValue(response);
+
+      const request = { path: '/tmp/backup.json', passphrase: 'correct horse' };
+      const result = await bridge.importIdentity(request);
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('import_identity', { request });
+      expect(result).toEqual(response);
+    });
+  });
+
+  // ==========================================================================
+  // Profile Commands
+  // ==========================================================================
+
+  describe('listProfiles', () => {
+    it('should call list_profiles command', async () => {
+      const profiles = [
+        { name: 'default', active: true, has_device_keys: true },
+        { name: 'work', active: false, has_device_keys: true },
+      ];
+      mockTauriAPI.core.invoke.mockResolvedValue(profiles);
+
+      const result = await bridge.listProfiles();
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('list_profiles', undefined);
+      expect(result).toEqual(profiles);
+    });
+  });
+
+  describe('switchProfile', () => {
+    it('should call switch_profile command', async () => {
+      const profile = { name: 'work', active: true, has_device_keys: true };
+      mockTauriAPI.core.invoke.mockResolvedValue(profile);
+
+      const result = await bridge.switchProfile('work');
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('switch_profile', { name: 'work' });
+      expect(result).toEqual(profile);
+    });
+  });
+
+  // ==========================================================================
+  // SSH Agent Forwarding Commands
+  // ==========================================================================
+
+  describe('agentWrite', () => {
+    it('should call agent_write with base64-encoded data', async () => {
+      mockTauriAPI.core.invoke.mockResolvedValue(undefined);
+
+      await bridge.agentWrite('sess-1', 2, new Uint8Array([0, 0, 0, 1, 11]));
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('agent_write', {
+        request: { session_id: 'sess-1', channel_id: 2, data: 'AAAAAQs=' },
+      });
+    });
+  });
+
+  // ==========================================================================
+  // Session Recording Commands
+  // ==========================================================================
+
+  describe('startRecording', () => {
+    it('should call start_recording command', async () => {
+      const entry = {
+        id: 'rec-1',
+        session_id: 'sess-1',
+        format: 'asciicast',
+        path: '/data/recordings/rec-1.cast',
+        started_at: 1000,
+        stopped_at: null,
+        size_bytes: 0,
+      };
+      mockTauriAPI.core.invoke.mockResolvedValue(entry);
+
+      const request = { session_id: 'sess-1', format: 'asciicast' as const, cols: 120, rows: 40 };
+      const result = await bridge.startRecording(request);
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('start_recording', { request });
+      expect(result).toEqual(entry);
+    });
+  });
+
+  describe('exportRecording', () => {
+    it('should call export_recording command', async () => {
+      mockTauriAPI.core.invoke.mockResolvedValue({ path: '/tmp/session.log', size_bytes: 42 });
+
+      const request = { id: 'rec-1', path: '/tmp/session.log', format: 'text' as const };
+      const result = await bridge.exportRecording(request);
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('export_recording', { request });
+      expect(result.size_bytes).toBe(42);
+    });
+  });
+
+  describe('setRecordingRedactions', () => {
+    it('should call set_recording_redactions command', async () => {
+      mockTauriAPI.core.invoke.mockResolvedValue(undefined);
+
+      await bridge.setRecordingRedactions(['hunter\\d']);
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('set_recording_redactions', {
+        patterns: ['hunter\\d'],
+      });
+    });
+  });
+
+  // ==========================================================================
+  // Deep Link Commands
+  // ==========================================================================
+
+  describe('handleDeepLink', () => {
+    it('should call handle_deep_link command', async () => {
+      const link = { action: 'pair_code', code: 'AXBK-7392' };
+      mockTauriAPI.core.invoke.mockResolvedValue(link);
+
+      const result = await bridge.handleDeepLink('remoshell://pair?code=AXBK-7392');
+
+      expect(mockTauriAPI.core.invoke).toHaveBeenCalledWith('handle_deep_link', {
+        url: 'remoshell://pair?code=AXBK-7392',
+      });
+      expect(result).toEqual(link);
+    });
+  });
+
   // ==========================================================================
   // Notification Commands
   // ==========================================================================
@@ -459,6 +635,10 @@ describe('TauriIPCBridge', () => {
       expect(mockTauriAPI.event.listen).toHaveBeenCalledWith('quic_data_received', expect.any(Function));
       expect(mockTauriAPI.event.listen).toHaveBeenCalledWith('quic_error', expect.any(Function));
       expect(mockTauriAPI.event.listen).toHaveBeenCalledWith('quic_peer_info', expect.any(Function));
+      expect(mockTauriAPI.event.listen).toHaveBeenCalledWith('agent_event', expect.any(Function));
+      expect(mockTauriAPI.event.listen).toHaveBeenCalledWith('daemon_notification', expect.any(Function));
+      expect(mockTauriAPI.event.listen).toHaveBeenCalledWith('deep_link', expect.any(Function));
+      expect(mockTauriAPI.event.listen).toHaveBeenCalledWith('deep_link_error', expect.any(Function));
     });
 
     it('should emit StateChanged event to subscribers', async () => {
@@ -516,13 +696,52 @@ describe('TauriIPCBridge', () => {
       });
     });
 
+    it('should emit Agent event to subscribers', async () => {
+      const subscriber = vi.fn();
+      bridge.subscribe(subscriber);
+      await bridge.startListening();
+
+      const payload = { type: 'Closed', payload: { session_id: 'sess-1', channel_id: 1 } };
+      mockTauriAPI._emitEvent('agent_event', payload);
+
+      expect(subscriber).toHaveBeenCalledWith({ type: 'Agent', payload });
+    });
+
+    it('should emit Notification event to subscribers', async () => {
+      const subscriber = vi.fn();
+      bridge.subscribe(subscriber);
+      await bridge.startListening();
+
+      const payload = {
+        level: 'Warning',
+        title: 'Daemon stopping',
+        body: 'The daemon is shutting down',
+        category: 'Shutdown',
+        session_id: null,
+      };
+      mockTauriAPI._emitEvent('daemon_notification', payload);
+
+      expect(subscriber).toHaveBeenCalledWith({ type: 'Notification', payload });
+    });
+
+    it('should emit DeepLink event to subscribers', async () => {
+      const subscriber = vi.fn();
+      bridge.subscribe(subscriber);
+      await bridge.startListening();
+
+      const payload = { action: 'connect', device_id: 'a1b2c3d4:e5f67890' };
+      mockTauriAPI._emitEvent('deep_link', payload);
+
+      expect(subscriber).toHaveBeenCalledWith({ type: 'DeepLink', payload });
+    });
+
     it('should not call startListening multiple times', async () => {
       await bridge.startListening();
       await bridge.startListening();
       await bridge.startListening();
 
       // Should only register listeners once
-      expect(mockTauriAPI.event.listen).toHaveBeenCalledTimes(4);
+      expect(mockTauriAPI.event.listen).toHaveBeenCalledTimes(8);
     });
 
     it('should stop listening and clean up unlisten functions', async () => {
@@ -543,7 +762,7 @@ describe('TauriIPCBridge', () => {
       bridge.stopListening();
       await bridge.startListening();
 
-      expect(mockTauriAPI.event.listen).toHaveBeenCalledTimes(8); // 4 + 4
+      expect(mockTauriAPI.event.listen).toHaveBeenCalledTimes(16); // 8 + 8
     });
   });
 
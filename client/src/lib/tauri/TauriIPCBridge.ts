@@ -8,6 +8,8 @@
  * and provides typed methods matching the Rust commands.
  */
 
+import type { Notification, SystemInfo } from '../protocol';
+
 // ============================================================================
 // Type Definitions
 // ============================================================================
@@ -123,6 +125,8 @@ export interface PairedDevice {
   created_at: number;
   /** Unix timestamp when the device was last seen (connected). */
   last_seen?: number;
+  /** MAC address of the device's host for Wake-on-LAN, if known. */
+  mac_address?: string;
 }
 
 /**
@@ -137,6 +141,26 @@ export interface StorePairedDeviceRequest {
   public_key: string;
 }
 
+/**
+ * Request payload for storing a device's MAC address.
+ */
+export interface SetMacAddressRequest {
+  /** The paired device. */
+  device_id: string;
+  /** MAC address of the device's host, or null to clear it. */
+  mac_address: string | null;
+}
+
+/**
+ * Request payload for waking a device.
+ */
+export interface WakeDeviceRequest {
+  /** The paired device to wake. */
+  device_id: string;
+  /** Address to send the magic packet to (defaults to 255.255.255.255:9). */
+  broadcast_address: string | null;
+}
+
 /**
  * Response from removing a paired device.
  */
@@ -145,6 +169,222 @@ export interface RemoveDeviceResponse {
   removed: boolean;
 }
 
+/**
+ * Status of a database schema migration.
+ */
+export interface MigrationInfo {
+  /** Schema version after the migration is applied. */
+  version: number;
+  /** Short descriptive name. */
+  name: string;
+  /** Unix timestamp when the migration was applied, if it was. */
+  applied_at?: number;
+}
+
+/**
+ * Response with the schema migration status of the database.
+ */
+export interface MigrationStatusResponse {
+  /** The schema version of the open database. */
+  current_version: number;
+  /** The schema version this build migrates to. */
+  latest_version: number;
+  /** Migrations that have not been applied. */
+  pending: MigrationInfo[];
+}
+
+/**
+ * Retention policy for the connection history.
+ */
+export interface RetentionPolicy {
+  /** Maximum number of entries to keep (oldest are removed first). */
+  max_rows?: number;
+  /** Maximum age of entries in days. */
+  max_age_days?: number;
+}
+
+/**
+ * Connection statistics for one paired device.
+ */
+export interface DeviceConnectionStats {
+  /** Device ID. */
+  device_id: string;
+  /** Human-readable name of the device. */
+  device_name: string;
+  /** Number of connection attempts in the window. */
+  total_connections: number;
+  /** Number of failed connection attempts in the window. */
+  failed_connections: number;
+  /** Fraction of failed attempts (0 when there were none). */
+  failure_rate: number;
+  /** Total seconds connected within the window. */
+  uptime_secs: number;
+  /** Unix timestamp of the most recent connection attempt. */
+  last_connected_at?: number;
+}
+
+/**
+ * Response with per-device connection statistics.
+ */
+export interface ConnectionAnalyticsResponse {
+  /** Length of the analytics window in days. */
+  days: number;
+  /** Unix timestamp of the start of the window. */
+  since: number;
+  /** Statistics for each paired device. */
+  devices: DeviceConnectionStats[];
+}
+
+/**
+ * Request payload for exporting the client identity.
+ */
+export interface ExportIdentityRequest {
+  /** Path of the backup file to write. */
+  path: string;
+  /** Passphrase used to encrypt the backup. */
+  passphrase: string;
+}
+
+/**
+ * Response from exporting the client identity.
+ */
+export interface ExportIdentityResponse {
+  /** Path of the written backup file. */
+  path: string;
+  /** Number of paired devices included in the backup. */
+  device_count: number;
+}
+
+/**
+ * Request payload for importing a client identity backup.
+ */
+export interface ImportIdentityRequest {
+  /** Path of the backup file to read. */
+  path: string;
+  /** Passphrase the backup was encrypted with. */
+  passphrase: string;
+  /** Replace an existing, different device key in the keychain. */
+  replace_identity?: boolean;
+}
+
+/**
+ * Response from importing a client identity backup.
+ */
+export interface ImportIdentityResponse {
+  /** Number of paired devices added to the database. */
+  imported_devices: number;
+  /** Number of paired devices skipped because they were already present. */
+  skipped_devices: number;
+  /** Whether the device key in the keychain was written or replaced. */
+  identity_restored: boolean;
+}
+
+/**
+ * Information about an identity profile.
+ */
+export interface ProfileInfo {
+  /** The profile name. */
+  name: string;
+  /** Whether this is the active profile. */
+  active: boolean;
+  /** Whether the profile has a device key in the keychain. */
+  has_device_keys: boolean;
+}
+
+/**
+ * Identifies a forwarded SSH agent channel.
+ */
+export interface AgentChannelRequest {
+  /** Session the channel belongs to. */
+  session_id: string;
+  /** Channel identifier from the daemon's `AgentOpen` message. */
+  channel_id: number;
+}
+
+/**
+ * Request payload for sending data to the local SSH agent.
+ */
+export interface AgentWriteRequest extends AgentChannelRequest {
+  /** Base64-encoded agent protocol data. */
+  data: string;
+}
+
+/**
+ * Activity on a forwarded agent channel, reported by the local agent.
+ */
+export type AgentEvent =
+  | { type: 'Data'; payload: { session_id: string; channel_id: number; data: string } }
+  | { type: 'Closed'; payload: { session_id: string; channel_id: number } };
+
+/**
+ * File format of a session recording.
+ */
+export type RecordingFormat = 'asciicast' | 'text';
+
+/**
+ * A session recording in the local index.
+ */
+export interface RecordingEntry {
+  /** Unique identifier of the recording. */
+  id: string;
+  /** The recorded session. */
+  session_id: string;
+  /** File format of the recording. */
+  format: RecordingFormat;
+  /** Path of the recording file under the app data directory. */
+  path: string;
+  /** Unix timestamp when recording started. */
+  started_at: number;
+  /** Unix timestamp when recording stopped, or null while recording. */
+  stopped_at: number | null;
+  /** Size of the recording file in bytes, once stopped. */
+  size_bytes: number;
+}
+
+/**
+ * Request payload for starting a recording.
+ */
+export interface StartRecordingRequest {
+  /** The session to record. */
+  session_id: string;
+  /** The recording format. */
+  format: RecordingFormat;
+  /** Current terminal columns. */
+  cols?: number;
+  /** Current terminal rows. */
+  rows?: number;
+}
+
+/**
+ * Request payload for exporting a recording.
+ */
+export interface ExportRecordingRequest {
+  /** ID of the recording. */
+  id: string;
+  /** Path to write the export to. */
+  path: string;
+  /** Format to export as (defaults to the recording's format). */
+  format?: RecordingFormat;
+}
+
+/**
+ * Response from exporting a recording.
+ */
+export interface ExportRecordingResponse {
+  /** Path the export was written to. */
+  path: string;
+  /** Size of the export in bytes. */
+  size_bytes: number;
+}
+
+/**
+ * Action requested through a `remoshell://` link.
+ */
+export type DeepLink =
+  | { action: 'pair_code'; code: string }
+  | { action: 'pair_data'; data: string }
+  | { action: 'connect'; device_id: string };
+
 /**
  * Request payload for showing a notification.
  */
@@ -173,6 +413,13 @@ export interface InitRequest {
   database_path: string;
   /** Optional relay URL for QUIC connections. */
   relay_url?: string;
+  /** Identity profile to activate (defaults to the default profile). */
+  profile?: string;
+  /**
+   * Encrypt the database at rest, migrating an unencrypted database.
+   * Requires a client built with the `sqlcipher` feature.
+   */
+  encrypt_database?: boolean;
 }
 
 /**
@@ -183,6 +430,39 @@ export interface InitResponse {
   initialized: boolean;
   /** The local node ID for QUIC connections. */
   node_id: string;
+  /** The active identity profile. */
+  profile: string;
+}
+
+/**
+ * One step of a connection attempt.
+ */
+export interface DialStep {
+  /** What the step did. */
+  kind: 'relay_lookup' | 'hole_punch' | 'direct' | 'transport' | 'handshake' | 'streams';
+  /** What the step targeted: a relay URL, socket address or transport. */
+  target?: string;
+  /** When the step started, in milliseconds from the start of the attempt. */
+  started_ms?: number;
+  /** How long the step took, in milliseconds. */
+  duration_ms?: number;
+  /** How the step ended. */
+  outcome: 'succeeded' | 'failed' | 'skipped';
+  /** Why the step failed or was skipped, or what it measured. */
+  detail?: string;
+}
+
+/**
+ * The steps of a connection attempt and how it ended.
+ */
+export interface DialReport {
+  /** The node ID that was dialed. */
+  target: string;
+  steps: DialStep[];
+  /** How long the attempt took, in milliseconds. */
+  elapsed_ms: number;
+  /** Why the attempt failed; absent if it connected. */
+  error?: string;
 }
 
 /**
@@ -192,7 +472,12 @@ export type ConnectionEvent =
   | { type: 'StateChanged'; payload: ConnectionState }
   | { type: 'DataReceived'; payload: { channel: ChannelType; data: string } }
   | { type: 'Error'; payload: string }
-  | { type: 'PeerInfo'; payload: { node_id: string } };
+  | { type: 'Failed'; payload: DialReport }
+  | { type: 'PeerInfo'; payload: { node_id: string } }
+  | { type: 'Agent'; payload: AgentEvent }
+  | { type: 'Notification'; payload: Notification }
+  | { type: 'DeepLink'; payload: DeepLink }
+  | { type: 'DeepLinkError'; payload: string };
 
 /**
  * Event subscriber callback type.
@@ -363,6 +648,35 @@ export class TauriIPCBridge {
     );
     this.unlistenFns.push(unlistenPeer);
 
+    // Listen for forwarded SSH agent activity
+    const unlistenAgent = await tauri.event.listen<AgentEvent>('agent_event', (event) => {
+      this.emit({ type: 'Agent', payload: event.payload });
+    });
+    this.unlistenFns.push(unlistenAgent);
+
+    // Listen for notifications pushed by the daemon
+    const unlistenNotification = await tauri.event.listen<Notification>(
+      'daemon_notification',
+      (event) => {
+        this.emit({ type: 'Notification', payload: event.payload });
+      }
+    );
+    this.unlistenFns.push(unlistenNotification);
+
+    // Listen for remoshell:// links opened by the OS
+    const unlistenDeepLink = await tauri.event.listen<DeepLink>('deep_link', (event) => {
+      this.emit({ type: 'DeepLink', payload: event.payload });
+    });
+    this.unlistenFns.push(unlistenDeepLink);
+
+    const unlistenDeepLinkError = await tauri.event.listen<string>(
+      'deep_link_error',
+      (event) => {
+        this.emit({ type: 'DeepLinkError', payload: event.payload });
+      }
+    );
+    this.unlistenFns.push(unlistenDeepLinkError);
+
     this.initialized = true;
   }
 
@@ -503,6 +817,257 @@ export class TauriIPCBridge {
     return this.invoke<boolean>('update_device_last_seen', { device_id: deviceId });
   }
 
+  // ==========================================================================
+  // Wake-on-LAN Commands
+  // ==========================================================================
+
+  /**
+   * Store the MAC address used to wake a paired device's host, or clear it
+   * with null. Rejects with code `INVALID_MAC_ADDRESS` for malformed input.
+   */
+  async setDeviceMacAddress(deviceId: string, macAddress: string | null): Promise<boolean> {
+    const request: SetMacAddressRequest = { device_id: deviceId, mac_address: macAddress };
+    return this.invoke<boolean>('set_device_mac_address', { request });
+  }
+
+  /**
+   * Wake a paired device's host with a Wake-on-LAN magic packet.
+   *
+   * @param broadcastAddress - Where to send the packet, e.g. `192.168.1.255:9`
+   *   (defaults to `255.255.255.255:9`)
+   */
+  async wakeDevice(deviceId: string, broadcastAddress?: string): Promise<void> {
+    const request: WakeDeviceRequest = {
+      device_id: deviceId,
+      broadcast_address: broadcastAddress ?? null,
+    };
+    return this.invoke<void>('wake_device', { request });
+  }
+
+  // ==========================================================================
+  // Database Commands
+  // ==========================================================================
+
+  /**
+   * Get the schema migrations that have not been applied to the database.
+   */
+  async getPendingMigrations(): Promise<MigrationStatusResponse> {
+    return this.invoke<MigrationStatusResponse>('get_pending_migrations');
+  }
+
+  // ==========================================================================
+  // Connection History Commands
+  // ==========================================================================
+
+  /**
+   * Get per-device connection statistics for the last `days` days
+   * (30 by default).
+   */
+  async getConnectionAnalytics(days?: number): Promise<ConnectionAnalyticsResponse> {
+    return this.invoke<ConnectionAnalyticsResponse>('get_connection_analytics', { days });
+  }
+
+  /**
+   * Get the connection history retention policy.
+   */
+  async getConnectionRetention(): Promise<RetentionPolicy> {
+    return this.invoke<RetentionPolicy>('get_connection_retention');
+  }
+
+  /**
+   * Set the connection history retention policy.
+   *
+   * Returns the number of history entries removed.
+   */
+  async setConnectionRetention(policy: RetentionPolicy): Promise<number> {
+    return this.invoke<number>('set_connection_retention', { policy });
+  }
+
+  // ==========================================================================
+  // Identity Backup Commands
+  // ==========================================================================
+
+  /**
+   * Export the device key and paired devices to an encrypted backup file.
+   */
+  async exportIdentity(request: ExportIdentityRequest): Promise<ExportIdentityResponse> {
+    return this.invoke<ExportIdentityResponse>('export_identity', { request });
+  }
+
+  /**
+   * Restore the device key and paired devices from an encrypted backup file.
+   *
+   * Fails with `IDENTITY_EXISTS` if a different key is already stored,
+   * unless `replace_identity` is set.
+   */
+  async importIdentity(request: ImportIdentityRequest): Promise<ImportIdentityResponse> {
+    return this.invoke<ImportIdentityResponse>('import_identity', { request });
+  }
+
+  // ==========================================================================
+  // Profile Commands
+  // ==========================================================================
+
+  /**
+   * List all identity profiles.
+   */
+  async listProfiles(): Promise<ProfileInfo[]> {
+    return this.invoke<ProfileInfo[]>('list_profiles');
+  }
+
+  /**
+   * Create a new identity profile with its own device key and database.
+   */
+  async createProfile(name: string): Promise<ProfileInfo> {
+    return this.invoke<ProfileInfo>('create_profile', { name });
+  }
+
+  /**
+   * Switch to another identity profile.
+   *
+   * Any open QUIC connection is closed first.
+   */
+  async switchProfile(name: string): Promise<ProfileInfo> {
+    return this.invoke<ProfileInfo>('switch_profile', { name });
+  }
+
+  /**
+   * Delete an identity profile, its database and its device key.
+   */
+  async deleteProfile(name: string): Promise<boolean> {
+    return this.invoke<boolean>('delete_profile', { name });
+  }
+
+  // ==========================================================================
+  // SSH Agent Forwarding Commands
+  // ==========================================================================
+
+  /**
+   * Check whether a local SSH agent is available for forwarding.
+   */
+  async hasSshAgent(): Promise<boolean> {
+    return this.invoke<boolean>('has_ssh_agent');
+  }
+
+  /**
+   * Open a channel to the local SSH agent for a daemon `AgentOpen`.
+   *
+   * Replies from the agent are delivered as `Agent` connection events.
+   */
+  async agentConnect(sessionId: string, channelId: number): Promise<void> {
+    const request: AgentChannelRequest = { session_id: sessionId, channel_id: channelId };
+    return this.invoke<void>('agent_connect', { request });
+  }
+
+  /**
+   * Send agent protocol data from the daemon to the local SSH agent.
+   */
+  async agentWrite(sessionId: string, channelId: number, data: Uint8Array): Promise<void> {
+    const request: AgentWriteRequest = {
+      session_id: sessionId,
+      channel_id: channelId,
+      data: uint8ArrayToBase64(data),
+    };
+    return this.invoke<void>('agent_write', { request });
+  }
+
+  /**
+   * Close a channel to the local SSH agent.
+   */
+  async agentClose(sessionId: string, channelId: number): Promise<void> {
+    const request: AgentChannelRequest = { session_id: sessionId, channel_id: channelId };
+    return this.invoke<void>('agent_close', { request });
+  }
+
+  // ==========================================================================
+  // Remote Host Commands
+  // ==========================================================================
+
+  /**
+   * Get host name, load, memory and disk usage of the connected daemon's host.
+   *
+   * Rejects with code `TIMEOUT` if the daemon does not answer within 10 seconds.
+   */
+  async getRemoteSystemInfo(): Promise<SystemInfo> {
+    return this.invoke<SystemInfo>('get_remote_system_info');
+  }
+
+  // ==========================================================================
+  // Session Recording Commands
+  // ==========================================================================
+
+  /**
+   * Start recording the output of an attached session.
+   *
+   * Recording stops on `stopRecording` or when the session closes. Output
+   * matching the configured redaction patterns is never written to disk.
+   */
+  async startRecording(request: StartRecordingRequest): Promise<RecordingEntry> {
+    return this.invoke<RecordingEntry>('start_recording', { request });
+  }
+
+  /**
+   * Stop a recording, resolving once its file is closed.
+   */
+  async stopRecording(id: string): Promise<RecordingEntry> {
+    return this.invoke<RecordingEntry>('stop_recording', { id });
+  }
+
+  /**
+   * List recordings, newest first.
+   */
+  async listRecordings(): Promise<RecordingEntry[]> {
+    return this.invoke<RecordingEntry[]>('list_recordings');
+  }
+
+  /**
+   * Copy a stopped recording to the given path.
+   *
+   * asciicast recordings can also be exported as `text`. Rejects with code
+   * `RECORDING_ACTIVE` while the recording is running.
+   */
+  async exportRecording(request: ExportRecordingRequest): Promise<ExportRecordingResponse> {
+    return this.invoke<ExportRecordingResponse>('export_recording', { request });
+  }
+
+  /**
+   * Delete a stopped recording and its file.
+   */
+  async deleteRecording(id: string): Promise<boolean> {
+    return this.invoke<boolean>('delete_recording', { id });
+  }
+
+  /**
+   * Get the secret patterns (regular expressions) redacted from recordings.
+   */
+  async getRecordingRedactions(): Promise<string[]> {
+    return this.invoke<string[]>('get_recording_redactions');
+  }
+
+  /**
+   * Set the secret patterns redacted from new recordings.
+   *
+   * Rejects with code `INVALID_REDACTION_PATTERN` if a pattern is not a
+   * valid regular expression.
+   */
+  async setRecordingRedactions(patterns: string[]): Promise<void> {
+    return this.invoke<void>('set_recording_redactions', { patterns });
+  }
+
+  // ==========================================================================
+  // Deep Link Commands
+  // ==========================================================================
+
+  /**
+   * Open a `remoshell://` link received by the frontend, e.g. from the QR
+   * scanner. The link is also emitted as a `DeepLink` event.
+   *
+   * Rejects with code `INVALID_DEEP_LINK` for malformed links.
+   */
+  async handleDeepLink(url: string): Promise<DeepLink> {
+    return this.invoke<DeepLink>('handle_deep_link', { url });
+  }
+
   // ==========================================================================
   // Notification Commands
   // ==========================================================================
@@ -23,10 +23,31 @@ export {
   type PairedDevice,
   type StorePairedDeviceRequest,
   type RemoveDeviceResponse,
+  type MigrationInfo,
+  type MigrationStatusResponse,
+  type RetentionPolicy,
+  type DeviceConnectionStats,
+  type ConnectionAnalyticsResponse,
+  type ExportIdentityRequest,
+  type ExportIdentityResponse,
+  type ImportIdentityRequest,
+  type ImportIdentityResponse,
+  type ProfileInfo,
+  type AgentChannelRequest,
+  type AgentWriteRequest,
+  type AgentEvent,
+  type RecordingFormat,
+  type RecordingEntry,
+  type StartRecordingRequest,
+  type ExportRecordingRequest,
+  type ExportRecordingResponse,
+  type DeepLink,
   type NotificationRequest,
   type NotificationResponse,
   type InitRequest,
   type InitResponse,
+  type DialStep,
+  type DialReport,
   type ConnectionEvent,
   type TauriEventSubscriber,
 } from './TauriIPCBridge';
@@ -0,0 +1,37 @@
+//! Regenerate the stored vectors of the current tree.
+//!
+//! Run with: cargo run --package conformance --example generate_vectors
+
+use std::fs;
+
+use conformance::vectors::{current_frame_vectors, current_message_vectors, vectors_dir};
+
+fn main() -> conformance::Result<()> {
+    let dir = vectors_dir();
+
+    let messages = current_message_vectors()?;
+    let path = dir.join("messages").join("current.json");
+    fs::write(&path, to_json(&messages))?;
+    println!(
+        "Wrote {} message vectors to {}",
+        messages.vectors.len(),
+        path.display()
+    );
+
+    let frames = current_frame_vectors()?;
+    let path = dir.join("frames.json");
+    fs::write(&path, to_json(&frames))?;
+    println!(
+        "Wrote {} frame vectors to {}",
+        frames.vectors.len(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    let mut json = serde_json::to_string_pretty(value).expect("vectors serialize to JSON");
+    json.push('\n');
+    json
+}
@@ -0,0 +1,35 @@
+//! Error types for the conformance suite.
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors raised while loading or producing vectors.
+#[derive(Debug, Error)]
+pub enum ConformanceError {
+    /// Reading a vector file failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A vector file is not valid JSON.
+    #[error("invalid vector file {path}: {source}")]
+    Json {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// A vector holds invalid hex.
+    #[error("invalid hex: {0}")]
+    Hex(String),
+
+    /// Encoding a message failed.
+    #[error("encoding failed: {0}")]
+    Encode(String),
+
+    /// The protocol crate rejected the input.
+    #[error(transparent)]
+    Protocol(#[from] protocol::ProtocolError),
+}
+
+/// Result type for conformance operations.
+pub type Result<T> = std::result::Result<T, ConformanceError>;
@@ -0,0 +1,42 @@
+//! # RemoShell Protocol Conformance
+//!
+//! This crate checks that the protocol's wire format stays compatible across
+//! releases. It is not published; it exists for its tests.
+//!
+//! ## Overview
+//!
+//! - **Round trips**: property tests in `tests/roundtrip.rs` encode and decode
+//!   arbitrary values of every [`Message`](protocol::Message) variant
+//! - **Golden frames**: `tests/framing.rs` checks the frame codec, with and
+//!   without compression, against stored frames
+//! - **Compatibility matrix**: `tests/compatibility.rs` runs every stored
+//!   encoding, including those from earlier releases, through the current
+//!   decoder and encoder
+//!
+//! ## Updating vectors
+//!
+//! A deliberate change to the wire format fails the suite until the stored
+//! snapshot of the tree is regenerated:
+//!
+//! ```text
+//! cargo run -p conformance --example generate_vectors
+//! ```
+//!
+//! At release time, copy `vectors/messages/current.json` to a file named after
+//! the release and set its `release` field, so the encodings keep being
+//! checked against later encoders.
+//!
+//! ## Modules
+//!
+//! - [`samples`]: One representative message per variant
+//! - [`vectors`]: Loading and producing stored vectors
+//! - [`matrix`]: The compatibility matrix runner
+//! - [`error`]: Error types
+
+pub mod error;
+pub mod matrix;
+pub mod samples;
+pub mod vectors;
+
+pub use error::{ConformanceError, Result};
+pub use matrix::{Matrix, Outcome};
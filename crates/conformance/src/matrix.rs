@@ -0,0 +1,215 @@
+//! Compatibility matrix of stored encodings against the current codec.
+//!
+//! Every stored vector is decoded with the current decoder and re-encoded
+//! with the current encoder. Encodings from earlier releases must decode to
+//! the same message type and survive the round trip; they may re-encode to
+//! different bytes when a later release added a field. Encodings in the
+//! [`CURRENT`] snapshot must re-encode byte for byte, so any change to the
+//! wire format shows up as a failure until the snapshot is regenerated.
+
+use std::fmt::Write as _;
+
+use protocol::messages::Envelope;
+
+use crate::samples::variant_name;
+use crate::vectors::{decode_hex, MessageVector, MessageVectors, CURRENT};
+
+/// Result of checking one stored encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The current encoder produces the stored bytes.
+    Identical,
+    /// The stored bytes decode, and the current encoding of the decoded
+    /// message differs but decodes to the same message.
+    Compatible,
+    /// The stored bytes are not accepted.
+    Failed(String),
+}
+
+impl Outcome {
+    /// Short label used in the rendered matrix.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Identical => "identical",
+            Self::Compatible => "compatible",
+            Self::Failed(_) => "FAILED",
+        }
+    }
+}
+
+/// One cell of the matrix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    /// Release the encoding came from.
+    pub release: String,
+    /// Variant name of the encoded message.
+    pub message_type: String,
+    /// Outcome of the check.
+    pub outcome: Outcome,
+}
+
+/// Outcomes for every stored encoding.
+#[derive(Debug, Clone, Default)]
+pub struct Matrix {
+    releases: Vec<String>,
+    cells: Vec<Cell>,
+}
+
+impl Matrix {
+    /// Checks every vector of every snapshot.
+    pub fn run(snapshots: &[MessageVectors]) -> Self {
+        let mut matrix = Self::default();
+        for snapshot in snapshots {
+            matrix.releases.push(snapshot.release.clone());
+            let strict = snapshot.release == CURRENT;
+            for vector in &snapshot.vectors {
+                matrix.cells.push(Cell {
+                    release: snapshot.release.clone(),
+                    message_type: vector.message_type.clone(),
+                    outcome: check(snapshot.protocol_version, vector, strict),
+                });
+            }
+        }
+        matrix
+    }
+
+    /// Returns every cell.
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    /// Returns the cells that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &Cell> {
+        self.cells
+            .iter()
+            .filter(|cell| matches!(cell.outcome, Outcome::Failed(_)))
+    }
+
+    /// Renders the matrix as a table with one row per message type and one
+    /// column per release.
+    pub fn render(&self) -> String {
+        let mut rows: Vec<&str> = Vec::new();
+        for cell in &self.cells {
+            if !rows.contains(&cell.message_type.as_str()) {
+                rows.push(&cell.message_type);
+            }
+        }
+
+        let name_width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        let col_width = self
+            .releases
+            .iter()
+            .map(|r| r.len())
+            .chain([Outcome::Compatible.label().len()])
+            .max()
+            .unwrap_or(0);
+
+        let mut out = format!("{:name_width$}", "");
+        for release in &self.releases {
+            let _ = write!(out, "  {:col_width$}", release);
+        }
+        out.push('\n');
+
+        for row in rows {
+            let _ = write!(out, "{:name_width$}", row);
+            for release in &self.releases {
+                let label = self
+                    .cells
+                    .iter()
+                    .find(|c| c.release == *release && c.message_type == row)
+                    .map_or("-", |c| c.outcome.label());
+                let _ = write!(out, "  {:col_width$}", label);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn check(protocol_version: u8, vector: &MessageVector, strict: bool) -> Outcome {
+    let bytes = match decode_hex(&vector.bytes_hex) {
+        Ok(bytes) => bytes,
+        Err(e) => return Outcome::Failed(e.to_string()),
+    };
+    let envelope = match Envelope::from_msgpack(&bytes) {
+        Ok(envelope) => envelope,
+        Err(e) => return Outcome::Failed(format!("decode failed: {}", e)),
+    };
+
+    let decoded_type = variant_name(&envelope.payload);
+    if decoded_type != vector.message_type {
+        return Outcome::Failed(format!("decoded as {}", decoded_type));
+    }
+    if envelope.version != protocol_version || envelope.sequence != vector.sequence {
+        return Outcome::Failed(format!(
+            "envelope header changed: version {}, sequence {}",
+            envelope.version, envelope.sequence
+        ));
+    }
+
+    let reencoded = match envelope.to_msgpack() {
+        Ok(reencoded) => reencoded,
+        Err(e) => return Outcome::Failed(format!("encode failed: {}", e)),
+    };
+    if reencoded == bytes {
+        return Outcome::Identical;
+    }
+    if strict {
+        return Outcome::Failed("current encoder output differs from snapshot".to_string());
+    }
+    match Envelope::from_msgpack(&reencoded) {
+        Ok(again) if again == envelope => Outcome::Compatible,
+        Ok(_) => Outcome::Failed("message changed on re-encoding".to_string()),
+        Err(e) => Outcome::Failed(format!("re-encoded bytes do not decode: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectors::current_message_vectors;
+
+    #[test]
+    fn test_current_encodings_are_identical() {
+        let matrix = Matrix::run(&[current_message_vectors().unwrap()]);
+        assert!(matrix
+            .cells()
+            .iter()
+            .all(|cell| cell.outcome == Outcome::Identical));
+    }
+
+    #[test]
+    fn test_detects_changed_encodings() {
+        let mut snapshot = current_message_vectors().unwrap();
+        snapshot.vectors.truncate(1);
+        // SessionCreate as encoded before forward_agent existed
+        snapshot.vectors[0].bytes_hex =
+            "93010192ad53657373696f6e437265617465955018c090c0".to_string();
+
+        let mut old = snapshot.clone();
+        old.release = "0.0.0".to_string();
+        let matrix = Matrix::run(&[old, snapshot]);
+
+        assert_eq!(matrix.cells()[0].outcome, Outcome::Compatible);
+        assert!(matches!(matrix.cells()[1].outcome, Outcome::Failed(_)));
+        assert_eq!(matrix.failures().count(), 1);
+
+        let rendered = matrix.render();
+        assert!(rendered.contains("SessionCreate"));
+        assert!(rendered.contains("compatible"));
+        assert!(rendered.contains("FAILED"));
+    }
+
+    #[test]
+    fn test_rejects_mislabelled_vectors() {
+        let mut snapshot = current_message_vectors().unwrap();
+        snapshot.vectors.truncate(1);
+        snapshot.vectors[0].message_type = "Ping".to_string();
+
+        let matrix = Matrix::run(&[snapshot]);
+        assert_eq!(
+            matrix.cells()[0].outcome,
+            Outcome::Failed("decoded as SessionCreate".to_string())
+        );
+    }
+}
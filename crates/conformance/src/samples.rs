@@ -0,0 +1,645 @@
+//! Representative messages, one per [`Message`] variant.
+//!
+//! These are the messages captured in the stored vectors. Field values are
+//! chosen to exercise every encoding path a variant has: optional fields are
+//! set, byte fields are non-empty, and collections hold more than one entry.
+
+use protocol::messages::*;
+
+/// Names of every [`Message`] variant, in declaration order.
+pub const VARIANTS: &[&str] = &[
+    "SessionCreate",
+    "SessionCreated",
+    "SessionAttach",
+    "SessionDetach",
+    "SessionKill",
+    "SessionResize",
+    "SessionSignal",
+    "UpdateSessionEnv",
+    "SessionData",
+    "SessionOutputTruncated",
+    "SessionClosed",
+    "SessionMetadataRequest",
+    "SessionMetadata",
+    "SessionSearchRequest",
+    "SessionSearchResult",
+    "ListTemplates",
+    "TemplateList",
+    "AgentOpen",
+    "AgentData",
+    "AgentClose",
+    "DisplayOpen",
+    "DisplayData",
+    "DisplayClose",
+    "FileListRequest",
+    "FileListResponse",
+    "FileDownloadRequest",
+    "FileDownloadChunk",
+    "FileDownloadAck",
+    "FileUploadStart",
+    "FileUploadChunk",
+    "FileUploadComplete",
+    "FileTransferReport",
+    "FileCopyGrantRequest",
+    "FileCopyGrant",
+    "FileCopyRequest",
+    "FileReadText",
+    "FileText",
+    "FileWriteText",
+    "FileTextWritten",
+    "FileRootsRequest",
+    "FileRoots",
+    "FileThumbnailRequest",
+    "FileThumbnail",
+    "FileTailRequest",
+    "FileTailData",
+    "FileTailStop",
+    "DeviceInfo",
+    "DeviceApprovalRequest",
+    "DeviceApproved",
+    "DeviceRejected",
+    "SecurityKeyChallenge",
+    "SecurityKeyAssertion",
+    "SecurityKeyVerified",
+    "SystemInfoRequest",
+    "SystemInfo",
+    "ProcessListRequest",
+    "ProcessList",
+    "ProcessKill",
+    "PowerActionRequest",
+    "PowerActionChallenge",
+    "PowerActionConfirm",
+    "PowerActionStarted",
+    "ConfigRequest",
+    "ConfigView",
+    "ConfigPatch",
+    "Ping",
+    "Pong",
+    "Error",
+    "Capabilities",
+    "Notification",
+    "ServerShutdownPending",
+];
+
+/// Returns the wire name of a message's variant.
+///
+/// The match is exhaustive on purpose: adding a variant fails to compile
+/// here until it is added to [`VARIANTS`] and [`sample_messages`].
+pub fn variant_name(message: &Message) -> &'static str {
+    match message {
+        Message::SessionCreate(_) => "SessionCreate",
+        Message::SessionCreated(_) => "SessionCreated",
+        Message::SessionAttach(_) => "SessionAttach",
+        Message::SessionDetach(_) => "SessionDetach",
+        Message::SessionKill(_) => "SessionKill",
+        Message::SessionResize(_) => "SessionResize",
+        Message::SessionSignal(_) => "SessionSignal",
+        Message::UpdateSessionEnv(_) => "UpdateSessionEnv",
+        Message::SessionData(_) => "SessionData",
+        Message::SessionOutputTruncated(_) => "SessionOutputTruncated",
+        Message::SessionClosed(_) => "SessionClosed",
+        Message::SessionMetadataRequest(_) => "SessionMetadataRequest",
+        Message::SessionMetadata(_) => "SessionMetadata",
+        Message::SessionSearchRequest(_) => "SessionSearchRequest",
+        Message::SessionSearchResult(_) => "SessionSearchResult",
+        Message::ListTemplates(_) => "ListTemplates",
+        Message::TemplateList(_) => "TemplateList",
+        Message::AgentOpen(_) => "AgentOpen",
+        Message::AgentData(_) => "AgentData",
+        Message::AgentClose(_) => "AgentClose",
+        Message::DisplayOpen(_) => "DisplayOpen",
+        Message::DisplayData(_) => "DisplayData",
+        Message::DisplayClose(_) => "DisplayClose",
+        Message::FileListRequest(_) => "FileListRequest",
+        Message::FileListResponse(_) => "FileListResponse",
+        Message::FileDownloadRequest(_) => "FileDownloadRequest",
+        Message::FileDownloadChunk(_) => "FileDownloadChunk",
+        Message::FileDownloadAck(_) => "FileDownloadAck",
+        Message::FileUploadStart(_) => "FileUploadStart",
+        Message::FileUploadChunk(_) => "FileUploadChunk",
+        Message::FileUploadComplete(_) => "FileUploadComplete",
+        Message::FileTransferReport(_) => "FileTransferReport",
+        Message::FileCopyGrantRequest(_) => "FileCopyGrantRequest",
+        Message::FileCopyGrant(_) => "FileCopyGrant",
+        Message::FileCopyRequest(_) => "FileCopyRequest",
+        Message::FileReadText(_) => "FileReadText",
+        Message::FileText(_) => "FileText",
+        Message::FileWriteText(_) => "FileWriteText",
+        Message::FileTextWritten(_) => "FileTextWritten",
+        Message::FileRootsRequest(_) => "FileRootsRequest",
+        Message::FileRoots(_) => "FileRoots",
+        Message::FileThumbnailRequest(_) => "FileThumbnailRequest",
+        Message::FileThumbnail(_) => "FileThumbnail",
+        Message::FileTailRequest(_) => "FileTailRequest",
+        Message::FileTailData(_) => "FileTailData",
+        Message::FileTailStop(_) => "FileTailStop",
+        Message::DeviceInfo(_) => "DeviceInfo",
+        Message::DeviceApprovalRequest(_) => "DeviceApprovalRequest",
+        Message::DeviceApproved(_) => "DeviceApproved",
+        Message::DeviceRejected(_) => "DeviceRejected",
+        Message::SecurityKeyChallenge(_) => "SecurityKeyChallenge",
+        Message::SecurityKeyAssertion(_) => "SecurityKeyAssertion",
+        Message::SecurityKeyVerified(_) => "SecurityKeyVerified",
+        Message::SystemInfoRequest(_) => "SystemInfoRequest",
+        Message::SystemInfo(_) => "SystemInfo",
+        Message::ProcessListRequest(_) => "ProcessListRequest",
+        Message::ProcessList(_) => "ProcessList",
+        Message::ProcessKill(_) => "ProcessKill",
+        Message::PowerActionRequest(_) => "PowerActionRequest",
+        Message::PowerActionChallenge(_) => "PowerActionChallenge",
+        Message::PowerActionConfirm(_) => "PowerActionConfirm",
+        Message::PowerActionStarted(_) => "PowerActionStarted",
+        Message::ConfigRequest(_) => "ConfigRequest",
+        Message::ConfigView(_) => "ConfigView",
+        Message::ConfigPatch(_) => "ConfigPatch",
+        Message::Ping(_) => "Ping",
+        Message::Pong(_) => "Pong",
+        Message::Error(_) => "Error",
+        Message::Capabilities(_) => "Capabilities",
+        Message::Notification(_) => "Notification",
+        Message::ServerShutdownPending(_) => "ServerShutdownPending",
+        // Stands in for types from newer releases, so it has no sample
+        Message::Unknown { .. } => "Unknown",
+    }
+}
+
+/// Returns one representative message per variant, in [`VARIANTS`] order.
+pub fn sample_messages() -> Vec<Message> {
+    let session_id = || "sess-1".to_string();
+    vec![
+        Message::SessionCreate(SessionCreate {
+            cols: 120,
+            rows: 40,
+            shell: Some("/bin/zsh".to_string()),
+            env: vec![
+                ("TERM".to_string(), "xterm-256color".to_string()),
+                ("LANG".to_string(), "en_US.UTF-8".to_string()),
+            ],
+            cwd: Some("/home/user".to_string()),
+            forward_agent: true,
+            template: Some("logs".to_string()),
+            timezone: Some("Europe/Paris".to_string()),
+            locale: Some("fr-FR".to_string()),
+            term: Some("xterm-256color".to_string()),
+            forward_display: Some(DisplayProtocol::X11),
+            host_session: Some(HostSession {
+                multiplexer: HostMultiplexer::Tmux,
+                name: "work".to_string(),
+                create: true,
+            }),
+            login_shell: true,
+        }),
+        Message::SessionCreated(SessionCreated {
+            session_id: session_id(),
+            pid: 4242,
+        }),
+        Message::SessionAttach(SessionAttach {
+            session_id: session_id(),
+        }),
+        Message::SessionDetach(SessionDetach {
+            session_id: session_id(),
+        }),
+        Message::SessionKill(SessionKill {
+            session_id: session_id(),
+            signal: Some(15),
+        }),
+        Message::SessionResize(SessionResize {
+            session_id: session_id(),
+            cols: 200,
+            rows: 50,
+        }),
+        Message::SessionSignal(SessionSignal {
+            session_id: session_id(),
+            signal: ProcessSignal::Interrupt,
+        }),
+        Message::UpdateSessionEnv(UpdateSessionEnv {
+            session_id: session_id(),
+            set: vec![
+                ("DISPLAY".to_string(), "localhost:10.0".to_string()),
+                ("TZ".to_string(), "Europe/Paris".to_string()),
+            ],
+            unset: vec!["SSH_AUTH_SOCK".to_string(), "LANG".to_string()],
+        }),
+        Message::SessionData(SessionData {
+            session_id: session_id(),
+            stream: DataStream::Stdout,
+            data: b"hello\r\n".to_vec(),
+        }),
+        Message::SessionOutputTruncated(SessionOutputTruncated {
+            session_id: session_id(),
+            dropped_chunks: 3,
+        }),
+        Message::SessionClosed(SessionClosed {
+            session_id: session_id(),
+            exit_code: Some(1),
+            signal: Some(9),
+            reason: Some("killed".to_string()),
+            close_reason: Some(SessionCloseReason::Killed),
+        }),
+        Message::SessionMetadataRequest(SessionMetadataRequest {
+            session_id: session_id(),
+        }),
+        Message::SessionMetadata(SessionMetadata {
+            session_id: session_id(),
+            cwd: Some("/srv/app".to_string()),
+            last_exit_code: Some(0),
+            last_command: Some("make test".to_string()),
+            command_running: false,
+        }),
+        Message::SessionSearchRequest(SessionSearchRequest {
+            session_id: session_id(),
+            pattern: "error\\[E\\d+\\]".to_string(),
+            regex: true,
+            case_sensitive: true,
+            max_results: 20,
+        }),
+        Message::SessionSearchResult(SessionSearchResult {
+            session_id: session_id(),
+            matches: vec![
+                SearchMatch {
+                    line: 1042,
+                    start: 0,
+                    end: 12,
+                    text: "error[E0308]: mismatched types".to_string(),
+                },
+                SearchMatch {
+                    line: 998,
+                    start: 4,
+                    end: 16,
+                    text: "  | error[E0425]: cannot find value".to_string(),
+                },
+            ],
+            truncated: true,
+            first_line: 40,
+            last_line: 1050,
+        }),
+        Message::ListTemplates(ListTemplates {}),
+        Message::TemplateList(TemplateList {
+            templates: vec![
+                TemplateInfo {
+                    name: "logs".to_string(),
+                    description: Some("Follow the application log".to_string()),
+                    shell: Some("/bin/bash".to_string()),
+                    cwd: Some("/var/log".to_string()),
+                    command: Some("tail -f app.log".to_string()),
+                    cols: Some(200),
+                    rows: Some(50),
+                },
+                TemplateInfo {
+                    name: "scratch".to_string(),
+                    description: None,
+                    shell: None,
+                    cwd: None,
+                    command: None,
+                    cols: None,
+                    rows: None,
+                },
+            ],
+        }),
+        Message::AgentOpen(AgentOpen {
+            session_id: session_id(),
+            channel_id: 1,
+        }),
+        Message::AgentData(AgentData {
+            session_id: session_id(),
+            channel_id: 1,
+            data: vec![0, 0, 0, 1, 11],
+        }),
+        Message::AgentClose(AgentClose {
+            session_id: session_id(),
+            channel_id: 1,
+        }),
+        Message::DisplayOpen(DisplayOpen {
+            session_id: session_id(),
+            channel_id: 1,
+        }),
+        Message::DisplayData(DisplayData {
+            session_id: session_id(),
+            channel_id: 1,
+            data: vec![b'l', 0, 11, 0],
+        }),
+        Message::DisplayClose(DisplayClose {
+            session_id: session_id(),
+            channel_id: 1,
+        }),
+        Message::FileListRequest(FileListRequest {
+            path: "/home/user".to_string(),
+            include_hidden: true,
+            detail: FileListDetail::Full,
+        }),
+        Message::FileListResponse(FileListResponse {
+            path: "/home/user".to_string(),
+            entries: vec![
+                FileEntry {
+                    name: "notes.txt".to_string(),
+                    entry_type: FileEntryType::File,
+                    size: 1024,
+                    mode: 0o644,
+                    modified: 1_704_067_200,
+                    details: Some(FileEntryDetails {
+                        uid: 1000,
+                        gid: 1000,
+                        owner: Some("user".to_string()),
+                        group: Some("user".to_string()),
+                        symlink_target: None,
+                        xattrs: vec![FileXattr {
+                            name: "user.comment".to_string(),
+                            value: b"draft".to_vec(),
+                        }],
+                    }),
+                },
+                FileEntry {
+                    name: "src".to_string(),
+                    entry_type: FileEntryType::Directory,
+                    size: 4096,
+                    mode: 0o755,
+                    modified: 1_704_067_260,
+                    details: None,
+                },
+            ],
+        }),
+        Message::FileDownloadRequest(FileDownloadRequest {
+            path: "/home/user/notes.txt".to_string(),
+            offset: 65_536,
+            chunk_size: 65_536,
+            verify: true,
+            window: 8,
+        }),
+        Message::FileDownloadChunk(FileDownloadChunk {
+            path: "/home/user/notes.txt".to_string(),
+            offset: 0,
+            total_size: 5,
+            data: b"notes".to_vec(),
+            is_last: true,
+            hash: Some(vec![0x5a; 32]),
+            file_hash: Some(vec![0xa5; 32]),
+        }),
+        Message::FileDownloadAck(FileDownloadAck {
+            path: "/home/user/notes.txt".to_string(),
+            offset: 131_072,
+        }),
+        Message::FileUploadStart(FileUploadStart {
+            path: "/tmp/upload.bin".to_string(),
+            size: 3,
+            mode: 0o600,
+            overwrite: false,
+        }),
+        Message::FileUploadChunk(FileUploadChunk {
+            path: "/tmp/upload.bin".to_string(),
+            offset: 0,
+            data: vec![1, 2, 3],
+            hash: None,
+        }),
+        Message::FileUploadComplete(FileUploadComplete {
+            path: "/tmp/upload.bin".to_string(),
+            checksum: vec![0xab; 32],
+            report: true,
+        }),
+        Message::FileTransferReport(FileTransferReport {
+            path: "/tmp/upload.bin".to_string(),
+            size: 3,
+            hash: vec![0xcd; 32],
+            chunks: 1,
+            verified_chunks: 0,
+            duration_ms: 42,
+        }),
+        Message::FileCopyGrantRequest(FileCopyGrantRequest {
+            path: "/srv/backup/upload.bin".to_string(),
+            overwrite: true,
+        }),
+        Message::FileCopyGrant(copy_grant()),
+        Message::FileCopyRequest(FileCopyRequest {
+            source_path: "/tmp/upload.bin".to_string(),
+            grant: copy_grant(),
+        }),
+        Message::FileReadText(FileReadText {
+            path: "/etc/app.conf".to_string(),
+        }),
+        Message::FileText(FileText {
+            path: "/etc/app.conf".to_string(),
+            content: "port = 8080\nname = \"héllo\"\n".to_string(),
+            hash: vec![0x2b; 32],
+        }),
+        Message::FileWriteText(FileWriteText {
+            path: "/etc/app.conf".to_string(),
+            content: "port = 9090\n".to_string(),
+            expected_hash: Some(vec![0x2b; 32]),
+        }),
+        Message::FileTextWritten(FileTextWritten {
+            path: "/etc/app.conf".to_string(),
+            hash: vec![0x3c; 32],
+        }),
+        Message::FileRootsRequest(FileRootsRequest {}),
+        Message::FileRoots(FileRoots {
+            roots: vec![
+                FileRoot {
+                    path: "/home/user".to_string(),
+                    access: FileAccess::Full,
+                    total_bytes: Some(512_000_000_000),
+                    available_bytes: Some(128_000_000_000),
+                },
+                FileRoot {
+                    path: "/var/log".to_string(),
+                    access: FileAccess::Read,
+                    total_bytes: None,
+                    available_bytes: None,
+                },
+            ],
+        }),
+        Message::FileThumbnailRequest(FileThumbnailRequest {
+            path: "/home/user/photo.jpg".to_string(),
+            max_size: 128,
+        }),
+        Message::FileThumbnail(FileThumbnail {
+            path: "/home/user/photo.jpg".to_string(),
+            mime_type: "image/jpeg".to_string(),
+            jpeg: Some(vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10]),
+            width: 128,
+            height: 96,
+        }),
+        Message::FileTailRequest(FileTailRequest {
+            path: "/var/log/syslog".to_string(),
+            lines: 100,
+            follow: true,
+        }),
+        Message::FileTailData(FileTailData {
+            path: "/var/log/syslog".to_string(),
+            offset: 4096,
+            data: b"started\n".to_vec(),
+            reset: false,
+        }),
+        Message::FileTailStop(FileTailStop {
+            path: "/var/log/syslog".to_string(),
+        }),
+        Message::DeviceInfo(DeviceInfo {
+            device_id: "device-1".to_string(),
+            name: "Laptop".to_string(),
+            os: "linux".to_string(),
+            os_version: "6.8".to_string(),
+            arch: "x86_64".to_string(),
+            protocol_version: PROTOCOL_VERSION,
+        }),
+        Message::DeviceApprovalRequest(DeviceApprovalRequest {
+            device_id: "device-1".to_string(),
+            name: "Laptop".to_string(),
+            public_key: vec![7; 32],
+            reason: Some("first connection".to_string()),
+            reinvite_token: Some("reinvite-token".to_string()),
+            pairing_code: Some("ABCD-1234".to_string()),
+        }),
+        Message::DeviceApproved(DeviceApproved {
+            device_id: "device-1".to_string(),
+            expires_at: Some(1_735_689_600),
+            allowed_capabilities: vec!["shell".to_string(), "file-transfer".to_string()],
+        }),
+        Message::DeviceRejected(DeviceRejected {
+            device_id: "device-1".to_string(),
+            reason: "denied by user".to_string(),
+            retry_allowed: true,
+        }),
+        Message::SecurityKeyChallenge(SecurityKeyChallenge {
+            challenge_id: "challenge-1".to_string(),
+            challenge: vec![0x5a; 32],
+            rp_id: "remoshell".to_string(),
+            allowed_credentials: vec!["Y3JlZGVudGlhbA".to_string()],
+            action: "session".to_string(),
+            user_verification: true,
+            expires_at: 1_704_067_500,
+        }),
+        Message::SecurityKeyAssertion(SecurityKeyAssertion {
+            challenge_id: "challenge-1".to_string(),
+            credential_id: b"credential".to_vec(),
+            authenticator_data: vec![0x01; 37],
+            client_data_json: br#"{"type":"webauthn.get"}"#.to_vec(),
+            signature: vec![0x30, 0x44, 0x02, 0x20],
+        }),
+        Message::SecurityKeyVerified(SecurityKeyVerified {
+            challenge_id: "challenge-1".to_string(),
+            valid_until: 1_704_068_100,
+        }),
+        Message::SystemInfoRequest(SystemInfoRequest {}),
+        Message::SystemInfo(SystemInfo {
+            hostname: "build-box".to_string(),
+            os_name: Some("Debian GNU/Linux".to_string()),
+            os_version: Some("12".to_string()),
+            kernel_version: Some("6.1.0-18-amd64".to_string()),
+            arch: "x86_64".to_string(),
+            cpu_count: 8,
+            load_average: [125, 80, 42],
+            memory_total: 16 << 30,
+            memory_used: 5 << 30,
+            swap_total: 2 << 30,
+            swap_used: 1 << 20,
+            disks: vec![
+                DiskUsage {
+                    path: "/home".to_string(),
+                    total: 500 << 30,
+                    available: 120 << 30,
+                },
+                DiskUsage {
+                    path: "/srv".to_string(),
+                    total: 2 << 40,
+                    available: 1 << 40,
+                },
+            ],
+            uptime_secs: 864_000,
+        }),
+        Message::ProcessListRequest(ProcessListRequest {
+            sort: Some(ProcessSort::Memory),
+            limit: Some(50),
+        }),
+        Message::ProcessList(ProcessList {
+            processes: vec![
+                ProcessInfo {
+                    pid: 1,
+                    user: Some("root".to_string()),
+                    cpu_usage: 0,
+                    memory: 12 << 20,
+                    command: "/sbin/init".to_string(),
+                },
+                ProcessInfo {
+                    pid: 4242,
+                    user: None,
+                    cpu_usage: 15_075,
+                    memory: 1 << 30,
+                    command: "postgres: checkpointer".to_string(),
+                },
+            ],
+        }),
+        Message::ProcessKill(ProcessKill {
+            pid: 4242,
+            signal: ProcessSignal::Terminate,
+        }),
+        Message::PowerActionRequest(PowerActionRequest {
+            action: PowerAction::Reboot,
+        }),
+        Message::PowerActionChallenge(PowerActionChallenge {
+            token: "power-1".to_string(),
+            action: PowerAction::Reboot,
+            expires_at: 1_704_067_230,
+        }),
+        Message::PowerActionConfirm(PowerActionConfirm {
+            token: "power-1".to_string(),
+        }),
+        Message::PowerActionStarted(PowerActionStarted {
+            action: PowerAction::Suspend,
+        }),
+        Message::ConfigRequest(ConfigRequest {}),
+        Message::ConfigView(ConfigView {
+            toml: "[session]\nidle_timeout = 3600\n".to_string(),
+        }),
+        Message::ConfigPatch(ConfigPatch {
+            idle_timeout: Some(900),
+            file_exclude: None,
+            log_level: Some("debug".to_string()),
+        }),
+        Message::Ping(Ping {
+            timestamp: 12_345,
+            payload: vec![1, 2, 3, 4],
+        }),
+        Message::Pong(Pong {
+            timestamp: 12_345,
+            payload: vec![1, 2, 3, 4],
+        }),
+        Message::Error(ErrorMessage {
+            code: ErrorCode::NotFound,
+            message: "Not found".to_string(),
+            context: Some("/missing".to_string()),
+            recoverable: false,
+            retry_after_ms: None,
+        }),
+        Message::Capabilities(Capabilities::default()),
+        Message::Notification(Notification {
+            level: NotificationLevel::Warning,
+            title: "Daemon stopping".to_string(),
+            body: "The daemon is shutting down".to_string(),
+            category: NotificationCategory::Shutdown,
+            session_id: None,
+        }),
+        Message::ServerShutdownPending(ServerShutdownPending {
+            reason: "stop requested".to_string(),
+            grace_ms: 10_000,
+        }),
+    ]
+}
+
+fn copy_grant() -> FileCopyGrant {
+    FileCopyGrant {
+        path: "/srv/backup/upload.bin".to_string(),
+        token: vec![0x5a; 32],
+        addresses: vec!["192.168.1.20:7677".to_string(), "nas.lan:7677".to_string()],
+        public_key: vec![0x11; 32],
+        expires_in_secs: 60,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_cover_every_variant_in_order() {
+        let names: Vec<_> = sample_messages().iter().map(variant_name).collect();
+        assert_eq!(names, VARIANTS);
+    }
+}
@@ -0,0 +1,217 @@
+//! Stored encodings checked by the conformance suite.
+//!
+//! Vectors live in the crate's `vectors/` directory as JSON:
+//!
+//! - `messages/<release>.json` holds one MessagePack-encoded [`Envelope`] per
+//!   message variant, as encoded by that release. `current.json` is the
+//!   snapshot of the tree itself; at release time it is copied to a file
+//!   named after the release so later encoders keep being checked against it.
+//! - `frames.json` holds framed payloads, compressed and not.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use protocol::framing::{Frame, FrameCodec, FrameFlags, FRAME_HEADER_SIZE};
+use protocol::messages::{DataStream, Envelope, Message, SessionData};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ConformanceError, Result};
+use crate::samples::{sample_messages, variant_name};
+
+/// Name of the snapshot holding the tree's own encodings.
+pub const CURRENT: &str = "current";
+
+/// Stored message encodings from one release.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageVectors {
+    /// Release that produced the encodings, or [`CURRENT`].
+    pub release: String,
+    /// Protocol version carried in the envelopes.
+    pub protocol_version: u8,
+    /// The encodings.
+    pub vectors: Vec<MessageVector>,
+}
+
+/// One stored envelope encoding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageVector {
+    /// Variant name of the payload.
+    pub message_type: String,
+    /// Sequence number of the envelope.
+    pub sequence: u64,
+    /// Hex-encoded MessagePack bytes.
+    pub bytes_hex: String,
+}
+
+/// Golden framing vectors.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameVectors {
+    /// The vectors.
+    pub vectors: Vec<FrameVector>,
+}
+
+/// One framed payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameVector {
+    /// Short description of what the vector covers.
+    pub name: String,
+    /// Whether the frame carries an LZ4-compressed payload.
+    pub compressed: bool,
+    /// Hex-encoded payload before framing.
+    pub payload_hex: String,
+    /// Hex-encoded frame, as produced by a codec with compression enabled.
+    pub frame_hex: String,
+}
+
+/// Returns the directory holding the stored vectors.
+pub fn vectors_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("vectors")
+}
+
+/// Loads every stored message snapshot, ordered by file name.
+pub fn load_message_vectors(dir: &Path) -> Result<Vec<MessageVectors>> {
+    let mut paths = fs::read_dir(dir.join("messages"))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+
+    paths.iter().map(|path| read_json(path)).collect()
+}
+
+/// Loads the golden framing vectors.
+pub fn load_frame_vectors(dir: &Path) -> Result<FrameVectors> {
+    read_json(&dir.join("frames.json"))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let text = fs::read_to_string(path)?;
+    serde_json::from_str(&text).map_err(|source| ConformanceError::Json {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Encodes every sample message with the current encoder.
+pub fn current_message_vectors() -> Result<MessageVectors> {
+    let vectors = sample_messages()
+        .into_iter()
+        .zip(1..)
+        .map(|(message, sequence)| encode_vector(sequence, message))
+        .collect::<Result<_>>()?;
+
+    Ok(MessageVectors {
+        release: CURRENT.to_string(),
+        protocol_version: protocol::PROTOCOL_VERSION,
+        vectors,
+    })
+}
+
+fn encode_vector(sequence: u64, message: Message) -> Result<MessageVector> {
+    let message_type = variant_name(&message).to_string();
+    let bytes = Envelope::new(sequence, message)
+        .to_msgpack()
+        .map_err(|e| ConformanceError::Encode(e.to_string()))?;
+
+    Ok(MessageVector {
+        message_type,
+        sequence,
+        bytes_hex: encode_hex(&bytes),
+    })
+}
+
+/// Frames a fixed set of payloads with the current codec.
+pub fn current_frame_vectors() -> Result<FrameVectors> {
+    let session_data = |data: Vec<u8>| {
+        Envelope::new(
+            1,
+            Message::SessionData(SessionData {
+                session_id: "sess-1".to_string(),
+                stream: DataStream::Stdout,
+                data,
+            }),
+        )
+        .to_msgpack()
+        .map_err(|e| ConformanceError::Encode(e.to_string()))
+    };
+    // Deterministic bytes that LZ4 cannot shrink
+    let mut state = 0x2545_f491_u32;
+    let noise: Vec<u8> = (0..1500)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state >> 24) as u8
+        })
+        .collect();
+
+    let payloads = [
+        ("empty", Vec::new()),
+        ("small_envelope", session_data(b"ls\r".to_vec())?),
+        ("at_compression_threshold", vec![b'x'; 1024]),
+        (
+            "compressed_terminal_output",
+            session_data(b"drwxr-xr-x  user  staff  src\r\n".repeat(64))?,
+        ),
+        ("incompressible_above_threshold", noise),
+    ];
+
+    let codec = FrameCodec::new();
+    let vectors = payloads
+        .into_iter()
+        .map(|(name, payload)| {
+            let frame = codec.encode(&Frame::new(payload.clone()))?;
+            let (decoded, _) = codec.decode(&frame)?;
+            Ok(FrameVector {
+                name: name.to_string(),
+                compressed: FrameFlags::from_byte(frame[FRAME_HEADER_SIZE - 1]).is_compressed(),
+                payload_hex: encode_hex(&decoded.payload),
+                frame_hex: encode_hex(&frame),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(FrameVectors { vectors })
+}
+
+/// Encodes bytes as lowercase hex.
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes lowercase or uppercase hex.
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return Err(ConformanceError::Hex("odd number of digits".to_string()));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| ConformanceError::Hex(format!("invalid digits at offset {}", i)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_round_trip() {
+        let bytes = [0x00, 0x7f, 0x80, 0xff];
+        assert_eq!(encode_hex(&bytes), "007f80ff");
+        assert_eq!(decode_hex("007F80ff").unwrap(), bytes);
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_current_vectors_are_sequenced() {
+        let current = current_message_vectors().unwrap();
+        for (vector, sequence) in current.vectors.iter().zip(1..) {
+            assert_eq!(vector.sequence, sequence);
+        }
+    }
+}
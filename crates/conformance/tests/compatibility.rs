@@ -0,0 +1,70 @@
+//! Compatibility matrix of stored encodings against the current codec.
+
+use conformance::matrix::{Matrix, Outcome};
+use conformance::samples::VARIANTS;
+use conformance::vectors::{current_message_vectors, load_message_vectors, vectors_dir, CURRENT};
+
+#[test]
+fn compatibility_matrix() {
+    let snapshots = load_message_vectors(&vectors_dir()).unwrap();
+    let matrix = Matrix::run(&snapshots);
+    // Shown with --nocapture
+    println!("{}", matrix.render());
+
+    let failures: Vec<_> = matrix
+        .failures()
+        .map(|cell| format!("{} {}: {:?}", cell.release, cell.message_type, cell.outcome))
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "incompatible encodings:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn earlier_releases_are_stored() {
+    let snapshots = load_message_vectors(&vectors_dir()).unwrap();
+    assert!(snapshots.iter().any(|s| s.release == CURRENT));
+    assert!(snapshots.iter().any(|s| s.release != CURRENT));
+}
+
+#[test]
+fn current_snapshot_is_up_to_date() {
+    let snapshots = load_message_vectors(&vectors_dir()).unwrap();
+    let stored = snapshots
+        .into_iter()
+        .find(|s| s.release == CURRENT)
+        .expect("vectors/messages/current.json is missing");
+
+    let types: Vec<_> = stored
+        .vectors
+        .iter()
+        .map(|v| v.message_type.as_str())
+        .collect();
+    assert_eq!(
+        types, VARIANTS,
+        "current snapshot does not cover every variant; regenerate the vectors"
+    );
+    assert_eq!(
+        stored,
+        current_message_vectors().unwrap(),
+        "current snapshot is stale; regenerate the vectors"
+    );
+}
+
+#[test]
+fn added_fields_decode_with_defaults() {
+    // 0.6.3 predates SessionCreate.forward_agent and .template, and
+    // DeviceApprovalRequest.reinvite_token and .pairing_code
+    let snapshots = load_message_vectors(&vectors_dir()).unwrap();
+    let matrix = Matrix::run(&snapshots);
+    for message_type in ["SessionCreate", "DeviceApprovalRequest"] {
+        let cell = matrix
+            .cells()
+            .iter()
+            .find(|c| c.release == "0.6.3" && c.message_type == message_type)
+            .unwrap();
+        assert_eq!(cell.outcome, Outcome::Compatible, "{}", message_type);
+    }
+}
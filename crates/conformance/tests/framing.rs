@@ -0,0 +1,82 @@
+//! Golden-vector tests for framing and compression.
+
+use conformance::vectors::{
+    current_frame_vectors, decode_hex, encode_hex, load_frame_vectors, vectors_dir,
+};
+use protocol::framing::{Frame, FrameCodec, COMPRESSION_THRESHOLD};
+
+#[test]
+fn golden_frames_decode() {
+    let golden = load_frame_vectors(&vectors_dir()).unwrap();
+    let codec = FrameCodec::new();
+
+    for vector in &golden.vectors {
+        let frame = decode_hex(&vector.frame_hex).unwrap();
+        let (decoded, consumed) = codec
+            .decode(&frame)
+            .unwrap_or_else(|e| panic!("{}: {}", vector.name, e));
+
+        assert_eq!(consumed, frame.len(), "{}", vector.name);
+        assert_eq!(
+            encode_hex(&decoded.payload),
+            vector.payload_hex,
+            "{}",
+            vector.name
+        );
+    }
+}
+
+#[test]
+fn golden_frames_encode() {
+    let golden = load_frame_vectors(&vectors_dir()).unwrap();
+    let codec = FrameCodec::new();
+
+    for vector in &golden.vectors {
+        let payload = decode_hex(&vector.payload_hex).unwrap();
+        let frame = codec.encode(&Frame::new(payload)).unwrap();
+        assert_eq!(
+            encode_hex(&frame),
+            vector.frame_hex,
+            "{}: encoding changed; if intended, regenerate the vectors",
+            vector.name
+        );
+    }
+}
+
+#[test]
+fn golden_frames_are_current() {
+    // Catches a vector set edited by hand or regenerated from older code
+    let golden = load_frame_vectors(&vectors_dir()).unwrap();
+    assert_eq!(golden, current_frame_vectors().unwrap());
+}
+
+#[test]
+fn golden_frames_cover_compression() {
+    let golden = load_frame_vectors(&vectors_dir()).unwrap();
+    let payload_len = |hex: &str| hex.len() / 2;
+
+    // Compressed frames, and large frames LZ4 could not shrink
+    assert!(golden.vectors.iter().any(|v| v.compressed));
+    assert!(golden
+        .vectors
+        .iter()
+        .any(|v| !v.compressed && payload_len(&v.payload_hex) > COMPRESSION_THRESHOLD));
+    // A payload exactly at the threshold is never compressed
+    assert!(golden
+        .vectors
+        .iter()
+        .any(|v| !v.compressed && payload_len(&v.payload_hex) == COMPRESSION_THRESHOLD));
+}
+
+#[test]
+fn uncompressing_decoder_accepts_golden_frames() {
+    // Compression is a sender choice; receivers always honour the flag
+    let golden = load_frame_vectors(&vectors_dir()).unwrap();
+    let codec = FrameCodec::without_compression();
+
+    for vector in &golden.vectors {
+        let frame = decode_hex(&vector.frame_hex).unwrap();
+        let (decoded, _) = codec.decode(&frame).unwrap();
+        assert_eq!(encode_hex(&decoded.payload), vector.payload_hex);
+    }
+}
@@ -0,0 +1,952 @@
+//! Property-based round trips for every message variant.
+
+use conformance::samples::{variant_name, VARIANTS};
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+use protocol::framing::{Frame, FrameCodec};
+use protocol::messages::*;
+
+fn text() -> impl Strategy<Value = String> {
+    "\\PC{0,24}"
+}
+
+fn bytes(max: usize) -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..max)
+}
+
+fn process_signal() -> impl Strategy<Value = ProcessSignal> {
+    prop_oneof![
+        Just(ProcessSignal::Interrupt),
+        Just(ProcessSignal::Quit),
+        Just(ProcessSignal::Suspend),
+        Just(ProcessSignal::Continue),
+        Just(ProcessSignal::Hangup),
+        Just(ProcessSignal::Terminate),
+        Just(ProcessSignal::Kill),
+    ]
+}
+
+fn display_protocol() -> impl Strategy<Value = DisplayProtocol> {
+    prop_oneof![Just(DisplayProtocol::X11), Just(DisplayProtocol::Wayland)]
+}
+
+fn host_session() -> impl Strategy<Value = HostSession> {
+    (
+        prop_oneof![Just(HostMultiplexer::Tmux), Just(HostMultiplexer::Screen)],
+        text(),
+        any::<bool>(),
+    )
+        .prop_map(|(multiplexer, name, create)| HostSession {
+            multiplexer,
+            name,
+            create,
+        })
+}
+
+fn process_sort() -> impl Strategy<Value = ProcessSort> {
+    prop_oneof![
+        Just(ProcessSort::Pid),
+        Just(ProcessSort::Cpu),
+        Just(ProcessSort::Memory),
+        Just(ProcessSort::Command),
+    ]
+}
+
+fn power_action() -> impl Strategy<Value = PowerAction> {
+    prop_oneof![
+        Just(PowerAction::Reboot),
+        Just(PowerAction::Shutdown),
+        Just(PowerAction::Suspend),
+    ]
+}
+
+fn notification_level() -> impl Strategy<Value = NotificationLevel> {
+    prop_oneof![
+        Just(NotificationLevel::Info),
+        Just(NotificationLevel::Warning),
+        Just(NotificationLevel::Error),
+    ]
+}
+
+fn notification_category() -> impl Strategy<Value = NotificationCategory> {
+    prop_oneof![
+        Just(NotificationCategory::General),
+        Just(NotificationCategory::Approval),
+        Just(NotificationCategory::Shutdown),
+        Just(NotificationCategory::Quota),
+        Just(NotificationCategory::Terminal),
+    ]
+}
+
+fn session_close_reason() -> impl Strategy<Value = SessionCloseReason> {
+    prop_oneof![
+        Just(SessionCloseReason::Exited),
+        Just(SessionCloseReason::Killed),
+        Just(SessionCloseReason::DaemonShutdown),
+        Just(SessionCloseReason::IdleTimeout),
+        Just(SessionCloseReason::OwnerRevoked),
+    ]
+}
+
+fn data_stream() -> impl Strategy<Value = DataStream> {
+    prop_oneof![
+        Just(DataStream::Stdin),
+        Just(DataStream::Stdout),
+        Just(DataStream::Stderr),
+    ]
+}
+
+fn file_entry() -> impl Strategy<Value = FileEntry> {
+    let entry_type = prop_oneof![
+        Just(FileEntryType::File),
+        Just(FileEntryType::Directory),
+        Just(FileEntryType::Symlink),
+        Just(FileEntryType::Other),
+    ];
+    (
+        text(),
+        entry_type,
+        any::<u64>(),
+        any::<u32>(),
+        any::<u64>(),
+        option::of(file_entry_details()),
+    )
+        .prop_map(
+            |(name, entry_type, size, mode, modified, details)| FileEntry {
+                name,
+                entry_type,
+                size,
+                mode,
+                modified,
+                details,
+            },
+        )
+}
+
+fn file_entry_details() -> impl Strategy<Value = FileEntryDetails> {
+    let xattr = (text(), bytes(64)).prop_map(|(name, value)| FileXattr { name, value });
+    (
+        any::<u32>(),
+        any::<u32>(),
+        option::of(text()),
+        option::of(text()),
+        option::of(text()),
+        vec(xattr, 0..3),
+    )
+        .prop_map(
+            |(uid, gid, owner, group, symlink_target, xattrs)| FileEntryDetails {
+                uid,
+                gid,
+                owner,
+                group,
+                symlink_target,
+                xattrs,
+            },
+        )
+}
+
+fn file_list_detail() -> impl Strategy<Value = FileListDetail> {
+    prop_oneof![
+        Just(FileListDetail::Basic),
+        Just(FileListDetail::Extended),
+        Just(FileListDetail::Full),
+    ]
+}
+
+fn template_info() -> impl Strategy<Value = TemplateInfo> {
+    (
+        text(),
+        option::of(text()),
+        option::of(text()),
+        option::of(text()),
+        option::of(text()),
+        option::of(any::<u16>()),
+        option::of(any::<u16>()),
+    )
+        .prop_map(
+            |(name, description, shell, cwd, command, cols, rows)| TemplateInfo {
+                name,
+                description,
+                shell,
+                cwd,
+                command,
+                cols,
+                rows,
+            },
+        )
+}
+
+fn error_code() -> impl Strategy<Value = ErrorCode> {
+    prop_oneof![
+        Just(ErrorCode::Unknown),
+        Just(ErrorCode::Unauthorized),
+        Just(ErrorCode::NotFound),
+        Just(ErrorCode::InvalidRequest),
+        Just(ErrorCode::InternalError),
+        Just(ErrorCode::Timeout),
+        Just(ErrorCode::RateLimited),
+        Just(ErrorCode::AlreadyExists),
+        Just(ErrorCode::PermissionDenied),
+        Just(ErrorCode::VersionMismatch),
+        Just(ErrorCode::InsufficientSpace),
+        Just(ErrorCode::Conflict),
+        Just(ErrorCode::Unsupported),
+        Just(ErrorCode::QuotaExceeded),
+        Just(ErrorCode::NotConnected),
+        Just(ErrorCode::Busy),
+    ]
+}
+
+fn search_match() -> impl Strategy<Value = SearchMatch> {
+    (any::<u64>(), any::<u32>(), any::<u32>(), text()).prop_map(|(line, start, end, text)| {
+        SearchMatch {
+            line,
+            start,
+            end,
+            text,
+        }
+    })
+}
+
+fn session_message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        (
+            any::<u16>(),
+            any::<u16>(),
+            option::of(text()),
+            vec((text(), text()), 0..4),
+            option::of(text()),
+            (any::<bool>(), any::<bool>()),
+            option::of(text()),
+            option::of(text()),
+            option::of(text()),
+            option::of(text()),
+            option::of(display_protocol()),
+            option::of(host_session()),
+        )
+            .prop_map(
+                |(
+                    cols,
+                    rows,
+                    shell,
+                    env,
+                    cwd,
+                    (login_shell, forward_agent),
+                    template,
+                    timezone,
+                    locale,
+                    term,
+                    forward_display,
+                    host_session,
+                )| {
+                    Message::SessionCreate(SessionCreate {
+                        cols,
+                        rows,
+                        shell,
+                        env,
+                        cwd,
+                        forward_agent,
+                        template,
+                        timezone,
+                        locale,
+                        term,
+                        forward_display,
+                        host_session,
+                        login_shell,
+                    })
+                }
+            ),
+        (text(), any::<u32>()).prop_map(|(session_id, pid)| {
+            Message::SessionCreated(SessionCreated { session_id, pid })
+        }),
+        text().prop_map(|session_id| Message::SessionAttach(SessionAttach { session_id })),
+        text().prop_map(|session_id| Message::SessionDetach(SessionDetach { session_id })),
+        (text(), option::of(any::<i32>())).prop_map(|(session_id, signal)| {
+            Message::SessionKill(SessionKill { session_id, signal })
+        }),
+        (text(), any::<u16>(), any::<u16>()).prop_map(|(session_id, cols, rows)| {
+            Message::SessionResize(SessionResize {
+                session_id,
+                cols,
+                rows,
+            })
+        }),
+        (text(), process_signal()).prop_map(|(session_id, signal)| {
+            Message::SessionSignal(SessionSignal { session_id, signal })
+        }),
+        (text(), vec((text(), text()), 0..4), vec(text(), 0..4)).prop_map(
+            |(session_id, set, unset)| {
+                Message::UpdateSessionEnv(UpdateSessionEnv {
+                    session_id,
+                    set,
+                    unset,
+                })
+            }
+        ),
+        (text(), data_stream(), bytes(256)).prop_map(|(session_id, stream, data)| {
+            Message::SessionData(SessionData {
+                session_id,
+                stream,
+                data,
+            })
+        }),
+        (text(), any::<u64>()).prop_map(|(session_id, dropped_chunks)| {
+            Message::SessionOutputTruncated(SessionOutputTruncated {
+                session_id,
+                dropped_chunks,
+            })
+        }),
+        (
+            text(),
+            option::of(any::<i32>()),
+            option::of(any::<i32>()),
+            option::of(text()),
+            option::of(session_close_reason()),
+        )
+            .prop_map(|(session_id, exit_code, signal, reason, close_reason)| {
+                Message::SessionClosed(SessionClosed {
+                    session_id,
+                    exit_code,
+                    signal,
+                    reason,
+                    close_reason,
+                })
+            }),
+        text().prop_map(|session_id| {
+            Message::SessionMetadataRequest(SessionMetadataRequest { session_id })
+        }),
+        (
+            text(),
+            option::of(text()),
+            option::of(any::<i32>()),
+            option::of(text()),
+            any::<bool>(),
+        )
+            .prop_map(
+                |(session_id, cwd, last_exit_code, last_command, command_running)| {
+                    Message::SessionMetadata(SessionMetadata {
+                        session_id,
+                        cwd,
+                        last_exit_code,
+                        last_command,
+                        command_running,
+                    })
+                }
+            ),
+        (text(), text(), any::<bool>(), any::<bool>(), any::<u32>(),).prop_map(
+            |(session_id, pattern, regex, case_sensitive, max_results)| {
+                Message::SessionSearchRequest(SessionSearchRequest {
+                    session_id,
+                    pattern,
+                    regex,
+                    case_sensitive,
+                    max_results,
+                })
+            }
+        ),
+        (
+            text(),
+            vec(search_match(), 0..4),
+            any::<bool>(),
+            any::<u64>(),
+            any::<u64>(),
+        )
+            .prop_map(|(session_id, matches, truncated, first_line, last_line)| {
+                Message::SessionSearchResult(SessionSearchResult {
+                    session_id,
+                    matches,
+                    truncated,
+                    first_line,
+                    last_line,
+                })
+            }),
+        Just(Message::ListTemplates(ListTemplates {})),
+        vec(template_info(), 0..4)
+            .prop_map(|templates| Message::TemplateList(TemplateList { templates })),
+        (text(), any::<u32>()).prop_map(|(session_id, channel_id)| {
+            Message::AgentOpen(AgentOpen {
+                session_id,
+                channel_id,
+            })
+        }),
+        (text(), any::<u32>(), bytes(256)).prop_map(|(session_id, channel_id, data)| {
+            Message::AgentData(AgentData {
+                session_id,
+                channel_id,
+                data,
+            })
+        }),
+        (text(), any::<u32>()).prop_map(|(session_id, channel_id)| {
+            Message::AgentClose(AgentClose {
+                session_id,
+                channel_id,
+            })
+        }),
+        (text(), any::<u32>()).prop_map(|(session_id, channel_id)| {
+            Message::DisplayOpen(DisplayOpen {
+                session_id,
+                channel_id,
+            })
+        }),
+        (text(), any::<u32>(), bytes(256)).prop_map(|(session_id, channel_id, data)| {
+            Message::DisplayData(DisplayData {
+                session_id,
+                channel_id,
+                data,
+            })
+        }),
+        (text(), any::<u32>()).prop_map(|(session_id, channel_id)| {
+            Message::DisplayClose(DisplayClose {
+                session_id,
+                channel_id,
+            })
+        }),
+    ]
+}
+
+fn file_message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        (text(), any::<bool>(), file_list_detail()).prop_map(|(path, include_hidden, detail)| {
+            Message::FileListRequest(FileListRequest {
+                path,
+                include_hidden,
+                detail,
+            })
+        }),
+        (text(), vec(file_entry(), 0..4)).prop_map(|(path, entries)| {
+            Message::FileListResponse(FileListResponse { path, entries })
+        }),
+        (
+            text(),
+            any::<u64>(),
+            any::<u32>(),
+            any::<bool>(),
+            any::<u32>()
+        )
+            .prop_map(|(path, offset, chunk_size, verify, window)| {
+                Message::FileDownloadRequest(FileDownloadRequest {
+                    path,
+                    offset,
+                    chunk_size,
+                    verify,
+                    window,
+                })
+            }),
+        (
+            text(),
+            any::<u64>(),
+            any::<u64>(),
+            bytes(256),
+            any::<bool>(),
+            option::of(bytes(32)),
+            option::of(bytes(32))
+        )
+            .prop_map(
+                |(path, offset, total_size, data, is_last, hash, file_hash)| {
+                    Message::FileDownloadChunk(FileDownloadChunk {
+                        path,
+                        offset,
+                        total_size,
+                        data,
+                        is_last,
+                        hash,
+                        file_hash,
+                    })
+                }
+            ),
+        (text(), any::<u64>()).prop_map(|(path, offset)| {
+            Message::FileDownloadAck(FileDownloadAck { path, offset })
+        }),
+        (text(), any::<u64>(), any::<u32>(), any::<bool>()).prop_map(
+            |(path, size, mode, overwrite)| {
+                Message::FileUploadStart(FileUploadStart {
+                    path,
+                    size,
+                    mode,
+                    overwrite,
+                })
+            }
+        ),
+        (text(), any::<u64>(), bytes(256), option::of(bytes(32))).prop_map(
+            |(path, offset, data, hash)| {
+                Message::FileUploadChunk(FileUploadChunk {
+                    path,
+                    offset,
+                    data,
+                    hash,
+                })
+            }
+        ),
+        (text(), bytes(64), any::<bool>()).prop_map(|(path, checksum, report)| {
+            Message::FileUploadComplete(FileUploadComplete {
+                path,
+                checksum,
+                report,
+            })
+        }),
+        (
+            text(),
+            any::<u64>(),
+            bytes(32),
+            any::<u32>(),
+            any::<u32>(),
+            any::<u64>()
+        )
+            .prop_map(|(path, size, hash, chunks, verified_chunks, duration_ms)| {
+                Message::FileTransferReport(FileTransferReport {
+                    path,
+                    size,
+                    hash,
+                    chunks,
+                    verified_chunks,
+                    duration_ms,
+                })
+            }),
+        (text(), any::<bool>()).prop_map(|(path, overwrite)| {
+            Message::FileCopyGrantRequest(FileCopyGrantRequest { path, overwrite })
+        }),
+        copy_grant().prop_map(Message::FileCopyGrant),
+        (text(), copy_grant()).prop_map(|(source_path, grant)| {
+            Message::FileCopyRequest(FileCopyRequest { source_path, grant })
+        }),
+        text().prop_map(|path| Message::FileReadText(FileReadText { path })),
+        (text(), text(), bytes(32)).prop_map(|(path, content, hash)| {
+            Message::FileText(FileText {
+                path,
+                content,
+                hash,
+            })
+        }),
+        (text(), text(), option::of(bytes(32))).prop_map(|(path, content, expected_hash)| {
+            Message::FileWriteText(FileWriteText {
+                path,
+                content,
+                expected_hash,
+            })
+        }),
+        (text(), bytes(32))
+            .prop_map(|(path, hash)| Message::FileTextWritten(FileTextWritten { path, hash })),
+        Just(Message::FileRootsRequest(FileRootsRequest {})),
+        vec(file_root(), 0..4).prop_map(|roots| Message::FileRoots(FileRoots { roots })),
+        (text(), any::<u32>()).prop_map(|(path, max_size)| {
+            Message::FileThumbnailRequest(FileThumbnailRequest { path, max_size })
+        }),
+        (
+            text(),
+            text(),
+            option::of(bytes(256)),
+            any::<u32>(),
+            any::<u32>()
+        )
+            .prop_map(|(path, mime_type, jpeg, width, height)| {
+                Message::FileThumbnail(FileThumbnail {
+                    path,
+                    mime_type,
+                    jpeg,
+                    width,
+                    height,
+                })
+            }),
+        (text(), any::<u32>(), any::<bool>()).prop_map(|(path, lines, follow)| {
+            Message::FileTailRequest(FileTailRequest {
+                path,
+                lines,
+                follow,
+            })
+        }),
+        (text(), any::<u64>(), bytes(256), any::<bool>()).prop_map(
+            |(path, offset, data, reset)| {
+                Message::FileTailData(FileTailData {
+                    path,
+                    offset,
+                    data,
+                    reset,
+                })
+            }
+        ),
+        text().prop_map(|path| Message::FileTailStop(FileTailStop { path })),
+    ]
+}
+
+fn file_root() -> impl Strategy<Value = FileRoot> {
+    (
+        text(),
+        prop_oneof![
+            Just(FileAccess::Read),
+            Just(FileAccess::ReadWrite),
+            Just(FileAccess::Full),
+        ],
+        option::of(any::<u64>()),
+        option::of(any::<u64>()),
+    )
+        .prop_map(|(path, access, total_bytes, available_bytes)| FileRoot {
+            path,
+            access,
+            total_bytes,
+            available_bytes,
+        })
+}
+
+fn copy_grant() -> impl Strategy<Value = FileCopyGrant> {
+    (
+        text(),
+        bytes(32),
+        vec(text(), 0..3),
+        bytes(32),
+        any::<u32>(),
+    )
+        .prop_map(
+            |(path, token, addresses, public_key, expires_in_secs)| FileCopyGrant {
+                path,
+                token,
+                addresses,
+                public_key,
+                expires_in_secs,
+            },
+        )
+}
+
+fn device_message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        (text(), text(), text(), text(), text(), any::<u8>()).prop_map(
+            |(device_id, name, os, os_version, arch, protocol_version)| {
+                Message::DeviceInfo(DeviceInfo {
+                    device_id,
+                    name,
+                    os,
+                    os_version,
+                    arch,
+                    protocol_version,
+                })
+            }
+        ),
+        (
+            text(),
+            text(),
+            bytes(64),
+            option::of(text()),
+            option::of(text()),
+            option::of(text()),
+        )
+            .prop_map(
+                |(device_id, name, public_key, reason, reinvite_token, pairing_code)| {
+                    Message::DeviceApprovalRequest(DeviceApprovalRequest {
+                        device_id,
+                        name,
+                        public_key,
+                        reason,
+                        reinvite_token,
+                        pairing_code,
+                    })
+                },
+            ),
+        (text(), option::of(any::<u64>()), vec(text(), 0..4)).prop_map(
+            |(device_id, expires_at, allowed_capabilities)| {
+                Message::DeviceApproved(DeviceApproved {
+                    device_id,
+                    expires_at,
+                    allowed_capabilities,
+                })
+            }
+        ),
+        (text(), text(), any::<bool>()).prop_map(|(device_id, reason, retry_allowed)| {
+            Message::DeviceRejected(DeviceRejected {
+                device_id,
+                reason,
+                retry_allowed,
+            })
+        }),
+        (
+            text(),
+            bytes(64),
+            text(),
+            vec(text(), 0..4),
+            text(),
+            any::<bool>(),
+            any::<u64>(),
+        )
+            .prop_map(
+                |(
+                    challenge_id,
+                    challenge,
+                    rp_id,
+                    allowed_credentials,
+                    action,
+                    user_verification,
+                    expires_at,
+                )| {
+                    Message::SecurityKeyChallenge(SecurityKeyChallenge {
+                        challenge_id,
+                        challenge,
+                        rp_id,
+                        allowed_credentials,
+                        action,
+                        user_verification,
+                        expires_at,
+                    })
+                }
+            ),
+        (text(), bytes(64), bytes(64), bytes(128), bytes(72)).prop_map(
+            |(challenge_id, credential_id, authenticator_data, client_data_json, signature)| {
+                Message::SecurityKeyAssertion(SecurityKeyAssertion {
+                    challenge_id,
+                    credential_id,
+                    authenticator_data,
+                    client_data_json,
+                    signature,
+                })
+            }
+        ),
+        (text(), any::<u64>()).prop_map(|(challenge_id, valid_until)| {
+            Message::SecurityKeyVerified(SecurityKeyVerified {
+                challenge_id,
+                valid_until,
+            })
+        }),
+    ]
+}
+
+fn control_message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        (any::<u64>(), bytes(64))
+            .prop_map(|(timestamp, payload)| Message::Ping(Ping { timestamp, payload })),
+        (any::<u64>(), bytes(64))
+            .prop_map(|(timestamp, payload)| Message::Pong(Pong { timestamp, payload })),
+        (
+            error_code(),
+            text(),
+            option::of(text()),
+            any::<bool>(),
+            option::of(any::<u64>()),
+        )
+            .prop_map(|(code, message, context, recoverable, retry_after_ms)| {
+                Message::Error(ErrorMessage {
+                    code,
+                    message,
+                    context,
+                    recoverable,
+                    retry_after_ms,
+                })
+            }),
+        (
+            vec(any::<u8>(), 0..4),
+            vec(text(), 0..4),
+            any::<u32>(),
+            any::<u32>(),
+            vec(text(), 0..4),
+        )
+            .prop_map(
+                |(protocol_versions, features, max_message_size, max_sessions, compression)| {
+                    Message::Capabilities(Capabilities {
+                        protocol_versions,
+                        features,
+                        max_message_size,
+                        max_sessions,
+                        compression,
+                    })
+                }
+            ),
+        (
+            notification_level(),
+            text(),
+            text(),
+            notification_category(),
+            option::of(text())
+        )
+            .prop_map(|(level, title, body, category, session_id)| {
+                Message::Notification(Notification {
+                    level,
+                    title,
+                    body,
+                    category,
+                    session_id,
+                })
+            }),
+        (text(), any::<u64>()).prop_map(|(reason, grace_ms)| {
+            Message::ServerShutdownPending(ServerShutdownPending { reason, grace_ms })
+        }),
+    ]
+}
+
+fn host_message() -> impl Strategy<Value = Message> {
+    let disk =
+        (text(), any::<u64>(), any::<u64>()).prop_map(|(path, total, available)| DiskUsage {
+            path,
+            total,
+            available,
+        });
+    let process = (
+        any::<u32>(),
+        option::of(text()),
+        any::<u32>(),
+        any::<u64>(),
+        text(),
+    )
+        .prop_map(|(pid, user, cpu_usage, memory, command)| ProcessInfo {
+            pid,
+            user,
+            cpu_usage,
+            memory,
+            command,
+        });
+    let host = (
+        text(),
+        option::of(text()),
+        option::of(text()),
+        option::of(text()),
+        text(),
+        any::<u32>(),
+        any::<[u32; 3]>(),
+    );
+    let resources = (
+        any::<u64>(),
+        any::<u64>(),
+        any::<u64>(),
+        any::<u64>(),
+        vec(disk, 0..4),
+        any::<u64>(),
+    );
+    prop_oneof![
+        Just(Message::SystemInfoRequest(SystemInfoRequest {})),
+        (host, resources).prop_map(
+            |(
+                (hostname, os_name, os_version, kernel_version, arch, cpu_count, load_average),
+                (memory_total, memory_used, swap_total, swap_used, disks, uptime_secs),
+            )| {
+                Message::SystemInfo(SystemInfo {
+                    hostname,
+                    os_name,
+                    os_version,
+                    kernel_version,
+                    arch,
+                    cpu_count,
+                    load_average,
+                    memory_total,
+                    memory_used,
+                    swap_total,
+                    swap_used,
+                    disks,
+                    uptime_secs,
+                })
+            }
+        ),
+        (option::of(process_sort()), option::of(any::<u32>())).prop_map(|(sort, limit)| {
+            Message::ProcessListRequest(ProcessListRequest { sort, limit })
+        }),
+        vec(process, 0..4).prop_map(|processes| Message::ProcessList(ProcessList { processes })),
+        (any::<u32>(), process_signal())
+            .prop_map(|(pid, signal)| Message::ProcessKill(ProcessKill { pid, signal })),
+        power_action()
+            .prop_map(|action| Message::PowerActionRequest(PowerActionRequest { action })),
+        (text(), power_action(), any::<u64>()).prop_map(|(token, action, expires_at)| {
+            Message::PowerActionChallenge(PowerActionChallenge {
+                token,
+                action,
+                expires_at,
+            })
+        }),
+        text().prop_map(|token| Message::PowerActionConfirm(PowerActionConfirm { token })),
+        power_action()
+            .prop_map(|action| Message::PowerActionStarted(PowerActionStarted { action })),
+    ]
+}
+
+fn config_message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        Just(Message::ConfigRequest(ConfigRequest {})),
+        text().prop_map(|toml| Message::ConfigView(ConfigView { toml })),
+        (
+            option::of(any::<u64>()),
+            option::of(vec(text(), 0..4)),
+            option::of(text()),
+        )
+            .prop_map(|(idle_timeout, file_exclude, log_level)| {
+                Message::ConfigPatch(ConfigPatch {
+                    idle_timeout,
+                    file_exclude,
+                    log_level,
+                })
+            }),
+    ]
+}
+
+fn message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        session_message(),
+        file_message(),
+        device_message(),
+        host_message(),
+        config_message(),
+        control_message(),
+    ]
+}
+
+fn envelope() -> impl Strategy<Value = Envelope> {
+    (any::<u64>(), message()).prop_map(|(sequence, payload)| Envelope::new(sequence, payload))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn envelope_round_trips(envelope in envelope()) {
+        let bytes = envelope.to_msgpack().unwrap();
+        let decoded = Envelope::from_msgpack(&bytes).unwrap();
+        prop_assert!(VARIANTS.contains(&variant_name(&decoded.payload)));
+        prop_assert_eq!(decoded, envelope);
+    }
+
+    #[test]
+    fn encoding_is_deterministic(envelope in envelope()) {
+        prop_assert_eq!(envelope.to_msgpack().unwrap(), envelope.clone().to_msgpack().unwrap());
+    }
+
+    #[test]
+    fn truncated_envelopes_are_rejected(envelope in envelope(), cut in any::<prop::sample::Index>()) {
+        let bytes = envelope.to_msgpack().unwrap();
+        let len = cut.index(bytes.len());
+        prop_assert!(Envelope::from_msgpack(&bytes[..len]).is_err());
+    }
+
+    #[test]
+    fn framed_envelopes_round_trip(envelope in envelope(), compress in any::<bool>()) {
+        let mut codec = FrameCodec::new();
+        codec.set_compression(compress);
+        let frame = codec.encode(&Frame::new(envelope.to_msgpack().unwrap())).unwrap();
+
+        let (decoded, consumed) = codec.decode(&frame).unwrap();
+        prop_assert_eq!(consumed, frame.len());
+        prop_assert_eq!(Envelope::from_msgpack(&decoded.payload).unwrap(), envelope);
+    }
+
+    #[test]
+    fn frames_round_trip(payload in bytes(8192), compress in any::<bool>()) {
+        let mut codec = FrameCodec::new();
+        codec.set_compression(compress);
+        let frame = codec.encode(&Frame::new(payload.clone())).unwrap();
+
+        let (decoded, consumed) = codec.decode(&frame).unwrap();
+        prop_assert_eq!(consumed, frame.len());
+        prop_assert_eq!(decoded.payload, payload);
+    }
+}
+
+#[test]
+fn strategies_cover_every_variant() {
+    use proptest::strategy::ValueTree;
+    use proptest::test_runner::TestRunner;
+
+    let strategy = message();
+    let mut runner = TestRunner::deterministic();
+    let mut seen = std::collections::HashSet::new();
+    for _ in 0..10_000 {
+        let message = strategy.new_tree(&mut runner).unwrap().current();
+        seen.insert(variant_name(&message));
+        if seen.len() == VARIANTS.len() {
+            return;
+        }
+    }
+    let missing: Vec<_> = VARIANTS.iter().filter(|v| !seen.contains(*v)).collect();
+    panic!("strategies never produced {:?}", missing);
+}
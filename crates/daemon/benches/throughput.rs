@@ -0,0 +1,593 @@
+//! Performance benchmarks for the daemon's throughput-critical paths.
+//!
+//! These benchmarks measure the paths that bulk data takes:
+//! - PTY output fan-out to the clients attached to a session
+//! - A session's output pump, from a program writing to the terminal to
+//!   its subscribers
+//! - File transfer chunk pipelines, from disk to frame and back
+//! - Download reads from cached file handles against reopening the file for
+//!   each chunk, and whole-file hashing by read buffer size
+//! - File downloads over a simulated high-latency link, one request per
+//!   chunk against a streamed window
+//! - The trust check made for every routed message, against the trust store
+//!   and through its cache
+//! - Terminal round trips over a localhost QUIC connection, idle and while a
+//!   file transfer fills the link
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use daemon::devices::{TrustCache, TrustStore, TrustedDevice};
+use daemon::files::{
+    DirectoryBrowser, DownloadStream, DownloadStreams, FileTransfer, ReaderConfig,
+};
+use daemon::network::loopback::{self, LoopbackConnection, SimulatedLink};
+use daemon::network::{ChannelType, Connection};
+#[cfg(feature = "quic")]
+use daemon::network::{QuicConfig, QuicConnectionHandler};
+use daemon::session::{Session, SessionOutputBroadcaster};
+use protocol::framing::{Frame, FrameCodec};
+use protocol::messages::{
+    Envelope, FileDownloadAck, FileDownloadChunk, FileDownloadRequest, FileUploadChunk, Message,
+};
+use protocol::DeviceId;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use tokio::sync::{broadcast, mpsc};
+
+/// Size of a PTY read.
+const PTY_CHUNK_SIZE: usize = 4096;
+
+/// Output written to the terminal by the PTY output benchmark.
+const PTY_OUTPUT_SIZE: usize = 64 * 1024 * 1024;
+
+/// Size of the file transferred by the chunk benchmarks.
+const FILE_SIZE: usize = 1024 * 1024;
+
+/// Chunk sizes clients request.
+const CHUNK_SIZES: &[(&str, u32)] = &[("16KB", 16 * 1024), ("64KB", 64 * 1024)];
+
+/// One-way latency of the simulated link, for a 100ms round trip.
+const LINK_LATENCY: Duration = Duration::from_millis(50);
+
+/// Download windows compared over the simulated link; 0 is one request per
+/// chunk.
+const DOWNLOAD_WINDOWS: &[(&str, u32)] =
+    &[("request_response", 0), ("window_8", 8), ("window_32", 32)];
+
+/// Devices in the trust store of the trust check benchmark.
+const TRUSTED_DEVICES: u8 = 32;
+
+/// Terminal input messages checked per iteration: one second of typing at
+/// 10k messages per second.
+const TERMINAL_INPUT_MESSAGES: u64 = 10_000;
+
+/// Size of the messages of the file transfer loading the QUIC link.
+#[cfg(feature = "quic")]
+const FILE_MESSAGE_SIZE: usize = 256 * 1024;
+
+/// Size of a terminal message: a keystroke with its framing.
+#[cfg(feature = "quic")]
+const KEYSTROKE_SIZE: usize = 64;
+
+/// Benchmark PTY output fan-out in the multiplexer.
+fn bench_pty_fanout(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("pty_fanout");
+    let chunk = vec![b'x'; PTY_CHUNK_SIZE];
+
+    for clients in [1usize, 4, 16] {
+        let broadcaster = SessionOutputBroadcaster::new();
+        let mut receivers = runtime.block_on(async {
+            let mut receivers = Vec::new();
+            for i in 0..clients {
+                receivers.push(broadcaster.add_client(format!("client-{}", i)).await);
+            }
+            receivers
+        });
+
+        group.throughput(Throughput::Bytes((PTY_CHUNK_SIZE * clients) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("broadcast_4KB", clients),
+            &chunk,
+            |b, chunk| {
+                b.iter(|| {
+                    runtime.block_on(async {
+                        let delivered = broadcaster.broadcast(black_box(chunk.clone())).await;
+                        // Drain so no client falls behind and starts dropping
+                        for receiver in &mut receivers {
+                            black_box(receiver.try_recv().unwrap());
+                        }
+                        delivered
+                    })
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Benchmark a session's output pump.
+///
+/// A program writes a burst of output to the terminal, as `cat` of a large
+/// file does, and a subscriber receives it.
+fn bench_pty_output(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("pty_output");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(PTY_OUTPUT_SIZE as u64));
+
+    group.bench_function("burst_64MB", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let (session, mut output) =
+                    Session::spawn(Some("/bin/sh".to_string()), 80, 24, vec![], None).unwrap();
+                session.start_read_loop();
+                let command = format!(
+                    "yes 'The quick brown fox jumps over the lazy dog' | head -c {}\n",
+                    PTY_OUTPUT_SIZE
+                );
+                session.write(command.as_bytes()).await.unwrap();
+
+                let mut received = 0;
+                while received < PTY_OUTPUT_SIZE {
+                    match output.recv().await {
+                        Ok(chunk) => received += black_box(chunk).len(),
+                        Err(broadcast::error::RecvError::Lagged(_)) => {
+                            panic!("subscriber fell behind the session output")
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+                session.kill(Some(9)).await.unwrap();
+                received
+            })
+        })
+    });
+
+    group.finish();
+}
+
+/// Creates a file transfer handler rooted in a temporary directory.
+fn file_transfer(root: &Path) -> FileTransfer {
+    FileTransfer::new(
+        DirectoryBrowser::new(vec![root.to_path_buf()]),
+        FILE_SIZE as u64 * 4,
+    )
+    .with_temp_dir(root.join("tmp"))
+}
+
+/// Benchmark file chunk pipelines.
+///
+/// A download reads each chunk from disk, wraps it in a `FileDownloadChunk`
+/// envelope and frames it; an upload decodes each frame and writes the
+/// chunk to disk, then verifies the checksum.
+fn bench_file_chunks(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let transfer = file_transfer(&root);
+    let codec = FrameCodec::new();
+
+    let content: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    let source = root.join("source.bin");
+    std::fs::write(&source, &content).unwrap();
+    let checksum = Sha256::digest(&content).to_vec();
+
+    let mut group = c.benchmark_group("file_chunks");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(20);
+
+    for &(size_name, chunk_size) in CHUNK_SIZES {
+        group.bench_function(BenchmarkId::new("download_1MB", size_name), |b| {
+            b.iter(|| {
+                let mut offset = 0;
+                let mut sequence = 0;
+                loop {
+                    let (data, total_size, is_last) = transfer
+                        .download_chunk(&source, offset, chunk_size)
+                        .unwrap();
+                    let len = data.len() as u64;
+                    let message = Message::FileDownloadChunk(FileDownloadChunk {
+                        path: source.display().to_string(),
+                        offset,
+                        total_size,
+                        data,
+                        is_last,
+                        hash: None,
+                        file_hash: None,
+                    });
+                    let payload = Envelope::new(sequence, message).to_msgpack().unwrap();
+                    black_box(codec.encode(&Frame::new(payload)).unwrap());
+
+                    offset += len;
+                    sequence += 1;
+                    if is_last {
+                        break;
+                    }
+                }
+            })
+        });
+
+        // Frames as they arrive from the client
+        let frames: Vec<Vec<u8>> = content
+            .chunks(chunk_size as usize)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let message = Message::FileUploadChunk(FileUploadChunk {
+                    path: String::new(),
+                    offset: (i * chunk_size as usize) as u64,
+                    data: chunk.to_vec(),
+                    hash: None,
+                });
+                let payload = Envelope::new(i as u64, message).to_msgpack().unwrap();
+                codec.encode(&Frame::new(payload)).unwrap()
+            })
+            .collect();
+        let destination = root.join("upload.bin");
+
+        group.bench_function(BenchmarkId::new("upload_1MB", size_name), |b| {
+            b.iter(|| {
+                transfer
+                    .start_upload(&destination, FILE_SIZE as u64, 0o644, true)
+                    .unwrap();
+                for frame in &frames {
+                    let (frame, _) = codec.decode(frame).unwrap();
+                    let envelope = Envelope::from_msgpack(&frame.payload).unwrap();
+                    let Message::FileUploadChunk(chunk) = envelope.payload else {
+                        unreachable!("only upload chunks are framed");
+                    };
+                    transfer
+                        .write_chunk(&destination, chunk.offset, &chunk.data)
+                        .unwrap();
+                }
+                transfer.complete_upload(&destination, &checksum).unwrap();
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Benchmark how downloads read files.
+///
+/// Chunks are read with the file kept open between them, and with the
+/// file cache disabled so that every chunk opens it again. Whole-file
+/// hashing is measured with a small buffer, as a Raspberry Pi might be
+/// configured, and with the default.
+fn bench_download_reads(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let cached = file_transfer(&root);
+    let uncached = file_transfer(&root).with_reader_config(ReaderConfig {
+        open_files: 0,
+        ..ReaderConfig::default()
+    });
+
+    let content: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    let source = root.join("source.bin");
+    std::fs::write(&source, &content).unwrap();
+
+    let mut group = c.benchmark_group("download_reads");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(20);
+
+    for &(size_name, chunk_size) in CHUNK_SIZES {
+        for (name, transfer) in [("reopen_1MB", &uncached), ("cached_1MB", &cached)] {
+            group.bench_function(BenchmarkId::new(name, size_name), |b| {
+                b.iter(|| {
+                    let mut offset = 0;
+                    loop {
+                        let (data, _, is_last) = transfer
+                            .download_chunk(&source, offset, chunk_size)
+                            .unwrap();
+                        offset += black_box(data).len() as u64;
+                        if is_last {
+                            break;
+                        }
+                    }
+                })
+            });
+        }
+    }
+
+    for (buffer_name, buffer_size) in [("16KB", 16 * 1024), ("256KB", 256 * 1024)] {
+        let transfer = file_transfer(&root).with_reader_config(ReaderConfig {
+            buffer_size,
+            ..ReaderConfig::default()
+        });
+        group.bench_function(BenchmarkId::new("hash_1MB", buffer_name), |b| {
+            b.iter(|| black_box(transfer.download_hash(&source).unwrap()))
+        });
+    }
+
+    group.finish();
+}
+
+/// Encodes a message for the files channel.
+fn encode(message: Message) -> Vec<u8> {
+    Envelope::new(0, message).to_msgpack().unwrap()
+}
+
+/// Decodes a message from the files channel.
+fn decode(data: &[u8]) -> Message {
+    Envelope::from_msgpack(data).unwrap().payload
+}
+
+/// Answers download requests and acks from `conn` the way the daemon does,
+/// until the client hangs up.
+async fn serve_downloads(mut conn: LoopbackConnection, transfer: Arc<FileTransfer>) {
+    let device_id = DeviceId::from_bytes([0u8; 16]);
+    let (tx, mut streamed) = mpsc::unbounded_channel();
+    let streams = Arc::new(DownloadStreams::new(tx));
+
+    loop {
+        let message = tokio::select! {
+            data = conn.recv(ChannelType::Files) => match data {
+                Ok(data) => decode(&data),
+                Err(_) => break,
+            },
+            Some((_, message)) = streamed.recv() => message,
+        };
+
+        let reply = match message {
+            Message::FileDownloadRequest(req) => {
+                let chunk = transfer
+                    .download_chunk_message(&req.path, req.offset, req.chunk_size, req.verify)
+                    .unwrap();
+                let reply = encode(Message::FileDownloadChunk(chunk.clone()));
+                if req.window > 0 {
+                    let stream = DownloadStream {
+                        path: req.path,
+                        offset: req.offset,
+                        chunk_size: req.chunk_size,
+                        window: req.window,
+                        verify: req.verify,
+                    };
+                    streams.start(Arc::clone(&transfer), device_id, stream, &chunk);
+                }
+                reply
+            }
+            Message::FileDownloadAck(ack) => {
+                streams.ack(&device_id, &ack.path, ack.offset);
+                continue;
+            }
+            message => encode(message),
+        };
+        if conn.send(ChannelType::Files, &reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Downloads `path` over `conn`, acking streamed chunks or requesting each
+/// chunk in turn.
+async fn download(conn: &mut LoopbackConnection, path: &str, chunk_size: u32, window: u32) {
+    let request = |offset| {
+        encode(Message::FileDownloadRequest(FileDownloadRequest {
+            path: path.to_string(),
+            offset,
+            chunk_size,
+            verify: false,
+            window,
+        }))
+    };
+    conn.send(ChannelType::Files, &request(0)).await.unwrap();
+
+    let mut received = 0;
+    loop {
+        let Message::FileDownloadChunk(chunk) =
+            decode(&conn.recv(ChannelType::Files).await.unwrap())
+        else {
+            unreachable!("only download chunks are served");
+        };
+        received += chunk.data.len() as u64;
+        if chunk.is_last {
+            break;
+        }
+
+        let next = if window > 0 {
+            encode(Message::FileDownloadAck(FileDownloadAck {
+                path: path.to_string(),
+                offset: received,
+            }))
+        } else {
+            request(received)
+        };
+        conn.send(ChannelType::Files, &next).await.unwrap();
+    }
+    black_box(received);
+}
+
+/// Benchmark file downloads over a link with a 100ms round trip.
+///
+/// Time is simulated, so the reported throughput is what the link allows
+/// rather than what the host can read from disk.
+fn bench_download_rtt(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap();
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let transfer = Arc::new(file_transfer(&root));
+
+    let content: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    let source = root.join("source.bin");
+    std::fs::write(&source, &content).unwrap();
+    let path = source.display().to_string();
+    let chunk_size = 64 * 1024;
+
+    let mut group = c.benchmark_group("download_rtt_100ms");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(10);
+
+    for &(name, window) in DOWNLOAD_WINDOWS {
+        group.bench_function(BenchmarkId::new("download_1MB", name), |b| {
+            b.iter_custom(|iters| {
+                runtime.block_on(async {
+                    let start = tokio::time::Instant::now();
+                    for _ in 0..iters {
+                        let (mut client, server) =
+                            loopback::pair(SimulatedLink::default().with_latency(LINK_LATENCY));
+                        let server = tokio::spawn(serve_downloads(server, Arc::clone(&transfer)));
+                        download(&mut client, &path, chunk_size, window).await;
+                        drop(client);
+                        server.await.unwrap();
+                    }
+                    start.elapsed()
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Benchmark the trust check made for each routed message under a 10k msg/s
+/// terminal input load, reading the trust store directly against reading it
+/// through a [`TrustCache`].
+fn bench_trust_checks(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let store = Arc::new(TrustStore::new(
+        temp_dir.path().join("trusted_devices.json"),
+    ));
+    for seed in 0..TRUSTED_DEVICES {
+        let name = format!("Device {}", seed);
+        let mut device = TrustedDevice::new(DeviceId::from_bytes([seed; 16]), name, [seed; 32]);
+        device.capabilities.insert("power".to_string());
+        store.add_device(device).unwrap();
+    }
+    let cache = TrustCache::new(store.clone());
+    let device_id = DeviceId::from_bytes([0; 16]);
+
+    let mut group = c.benchmark_group("trust_checks");
+    group.throughput(Throughput::Elements(TERMINAL_INPUT_MESSAGES));
+    group.bench_function("store_10k", |b| {
+        b.iter(|| {
+            for _ in 0..TERMINAL_INPUT_MESSAGES {
+                let device = store.get_device(black_box(&device_id)).unwrap().unwrap();
+                assert!(device.is_trusted_at(SystemTime::now()));
+            }
+        });
+    });
+    group.bench_function("cached_10k", |b| {
+        b.iter(|| {
+            // The store changes once a second, such as a device being seen
+            // for the first time, so the first check misses
+            store.set_role(&device_id, None).unwrap();
+            for _ in 0..TERMINAL_INPUT_MESSAGES {
+                let device = cache.get(black_box(&device_id)).unwrap().unwrap();
+                assert!(device.is_trusted_at(SystemTime::now()));
+            }
+        });
+    });
+    group.finish();
+}
+
+/// Connects two QUIC handlers over localhost. The server echoes terminal
+/// messages and discards file messages.
+#[cfg(feature = "quic")]
+async fn quic_echo_pair() -> (Arc<QuicConnectionHandler>, Arc<QuicConnectionHandler>) {
+    let config = || QuicConfig::default().without_relay();
+    let server = Arc::new(QuicConnectionHandler::new(config()).await.unwrap());
+    let client = Arc::new(QuicConnectionHandler::new(config()).await.unwrap());
+
+    let (v4, v6) = server.endpoint().bound_sockets();
+    let addrs = std::iter::once(v4).chain(v6);
+    let server_addr = iroh::NodeAddr::from_parts(server.node_id(), None, addrs);
+
+    let accepted = {
+        let server = Arc::clone(&server);
+        tokio::spawn(async move {
+            server.accept().await.unwrap();
+            server.accept_streams().await.unwrap();
+            server.spawn_stream_readers();
+        })
+    };
+    client.connect(server_addr).await.unwrap();
+    client.create_streams().await.unwrap();
+    client.spawn_stream_readers();
+    accepted.await.unwrap();
+
+    let echo = Arc::clone(&server);
+    tokio::spawn(async move {
+        while let Ok(data) = echo.recv(ChannelType::Terminal).await {
+            if echo.send(ChannelType::Terminal, &data).await.is_err() {
+                break;
+            }
+        }
+    });
+    let sink = Arc::clone(&server);
+    tokio::spawn(async move { while sink.recv(ChannelType::Files).await.is_ok() {} });
+
+    (client, server)
+}
+
+/// Benchmark the round trip of a keystroke over QUIC on an idle connection
+/// against one where a file upload keeps the link full. Terminal streams
+/// are sent before file streams, so the second should stay close to the
+/// first.
+#[cfg(feature = "quic")]
+fn bench_terminal_latency(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (client, server) = runtime.block_on(quic_echo_pair());
+    let keystroke = vec![b'x'; KEYSTROKE_SIZE];
+    let round_trip = |client: &QuicConnectionHandler| {
+        runtime.block_on(async {
+            client
+                .send(ChannelType::Terminal, &keystroke)
+                .await
+                .unwrap();
+            client.recv(ChannelType::Terminal).await.unwrap()
+        })
+    };
+
+    let mut group = c.benchmark_group("terminal_latency");
+    group.bench_function("idle", |b| b.iter(|| round_trip(&client)));
+
+    let upload = {
+        let client = Arc::clone(&client);
+        runtime.spawn(async move {
+            let chunk = vec![0u8; FILE_MESSAGE_SIZE];
+            while client.send(ChannelType::Files, &chunk).await.is_ok() {}
+        })
+    };
+    group.bench_function("files_saturating", |b| b.iter(|| round_trip(&client)));
+    group.finish();
+
+    upload.abort();
+    runtime.block_on(async {
+        client.close().await.ok();
+        server.close().await.ok();
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_pty_fanout,
+    bench_pty_output,
+    bench_file_chunks,
+    bench_download_reads,
+    bench_trust_checks
+);
+
+criterion_group! {
+    name = link_benches;
+    // Simulated time gives identical samples, which the plots cannot scale
+    config = Criterion::default().without_plots();
+    targets = bench_download_rtt
+}
+
+#[cfg(feature = "quic")]
+criterion_group!(quic_benches, bench_terminal_latency);
+
+#[cfg(feature = "quic")]
+criterion_main!(benches, link_benches, quic_benches);
+#[cfg(not(feature = "quic"))]
+criterion_main!(benches, link_benches);
@@ -0,0 +1,523 @@
+//! Session records for the Linux audit system.
+//!
+//! When `[auditd]` is enabled, RemoShell sessions are reported to the kernel
+//! audit subsystem the way `sshd` and `sudo` report theirs, so they show up
+//! in `ausearch`, `aureport` and the compliance tooling built on them:
+//!
+//! - `USER_START` when a device opens a session
+//! - `USER_END` when the session ends
+//! - `USER_CMD` for each command run in it, if shell integration reports the
+//!   command line
+//!
+//! All records carry `terminal=remoshell`, the session ID and the device
+//! fingerprint. Sending records to the kernel needs `CAP_AUDIT_WRITE`; with
+//! `log_file` set, they are appended to that file in the raw audit log format
+//! instead, which `ausearch -if <file>` reads.
+
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use dashmap::DashMap;
+use protocol::DeviceId;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::config::AuditdConfig;
+use crate::session::FinishedCommand;
+
+/// Value of the `terminal` field of every record.
+pub const TERMINAL: &str = "remoshell";
+
+/// Audit record types, as defined in `linux/audit.h`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    /// A user session started.
+    UserStart,
+    /// A user session ended.
+    UserEnd,
+    /// A user ran a command.
+    UserCmd,
+}
+
+impl RecordType {
+    /// Returns the numeric message type.
+    pub fn code(self) -> u16 {
+        match self {
+            Self::UserStart => 1105,
+            Self::UserEnd => 1106,
+            Self::UserCmd => 1123,
+        }
+    }
+
+    /// Returns the name `ausearch -m` knows the type by.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UserStart => "USER_START",
+            Self::UserEnd => "USER_END",
+            Self::UserCmd => "USER_CMD",
+        }
+    }
+}
+
+/// A record waiting to be written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// Record type.
+    pub kind: RecordType,
+    /// `key=value` fields of the record.
+    pub message: String,
+}
+
+/// The device a session belongs to.
+#[derive(Debug, Clone)]
+struct SessionOwner {
+    device_id: DeviceId,
+    device_name: String,
+}
+
+/// Reports session activity to the audit writer.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    records: mpsc::UnboundedSender<AuditRecord>,
+    sessions: Arc<DashMap<String, SessionOwner>>,
+    /// Fields naming the daemon's user and executable, shared by all records.
+    subject: Arc<str>,
+}
+
+impl AuditLog {
+    /// Creates a handle and the receiver its records are written from.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<AuditRecord>) {
+        let (records, rx) = mpsc::unbounded_channel();
+        let acct = nix::unistd::User::from_uid(nix::unistd::getuid())
+            .ok()
+            .flatten()
+            .map(|user| user.name)
+            .unwrap_or_else(|| nix::unistd::getuid().to_string());
+        let exe = std::env::current_exe()
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let subject = format!("acct={} exe={}", encode_value(&acct), encode_value(&exe));
+        let log = Self {
+            records,
+            sessions: Arc::new(DashMap::new()),
+            subject: subject.into(),
+        };
+        (log, rx)
+    }
+
+    /// Records that `device_id` opened session `session_id`.
+    pub fn session_started(
+        &self,
+        session_id: &str,
+        pid: u32,
+        device_id: &DeviceId,
+        device_name: &str,
+    ) {
+        let owner = SessionOwner {
+            device_id: *device_id,
+            device_name: device_name.to_string(),
+        };
+        let message = format!(
+            "op=remoshell:session_open {} pid={} res=success",
+            self.session_fields(session_id, &owner),
+            pid
+        );
+        self.sessions.insert(session_id.to_string(), owner);
+        self.send(RecordType::UserStart, message);
+    }
+
+    /// Records a command that finished in `session_id`.
+    ///
+    /// Commands whose command line the shell did not report are skipped.
+    pub fn command(&self, session_id: &str, command: &FinishedCommand) {
+        let Some(line) = &command.command else {
+            return;
+        };
+        let Some(owner) = self.sessions.get(session_id).map(|o| o.clone()) else {
+            return;
+        };
+        let exit = command
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "?".to_string());
+        let res = if command.exit_code.unwrap_or(0) == 0 {
+            "success"
+        } else {
+            "failed"
+        };
+        let message = format!(
+            "cwd={} cmd={} exit={} {} res={}",
+            encode_value(command.cwd.as_deref().unwrap_or("?")),
+            encode_value(line),
+            exit,
+            self.session_fields(session_id, &owner),
+            res
+        );
+        self.send(RecordType::UserCmd, message);
+    }
+
+    /// Records that session `session_id` ended.
+    pub fn session_ended(&self, session_id: &str) {
+        let Some((_, owner)) = self.sessions.remove(session_id) else {
+            return;
+        };
+        let message = format!(
+            "op=remoshell:session_close {} res=success",
+            self.session_fields(session_id, &owner)
+        );
+        self.send(RecordType::UserEnd, message);
+    }
+
+    fn session_fields(&self, session_id: &str, owner: &SessionOwner) -> String {
+        format!(
+            "{} hostname=? addr=? terminal={} session={} device={} device_name={}",
+            self.subject,
+            TERMINAL,
+            encode_value(session_id),
+            owner.device_id.fingerprint(),
+            encode_value(&owner.device_name)
+        )
+    }
+
+    fn send(&self, kind: RecordType, message: String) {
+        // The receiver only goes away when the daemon shuts down
+        let _ = self.records.send(AuditRecord { kind, message });
+    }
+}
+
+/// Encodes a field value the way libaudit does: quoted if it is plain
+/// printable ASCII, otherwise as unquoted uppercase hex.
+pub fn encode_value(value: &str) -> String {
+    let needs_encoding = value
+        .bytes()
+        .any(|b| b == b'"' || !(0x21..=0x7e).contains(&b));
+    if needs_encoding {
+        hex::encode_upper(value)
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
+/// Where records are written.
+#[derive(Debug)]
+enum Target {
+    /// The kernel audit subsystem, over netlink.
+    #[cfg(target_os = "linux")]
+    Kernel(netlink::AuditSocket),
+    /// A file in the raw audit log format.
+    File(PathBuf),
+}
+
+/// Writes audit records.
+#[derive(Debug)]
+pub struct AuditWriter {
+    target: Target,
+    serial: AtomicU64,
+}
+
+impl AuditWriter {
+    /// Creates a writer for `config`.
+    ///
+    /// Fails if the audit netlink socket cannot be opened, or on platforms
+    /// other than Linux when no log file is configured.
+    pub fn from_config(config: &AuditdConfig) -> anyhow::Result<Self> {
+        let target = match &config.log_file {
+            Some(path) => Target::File(path.clone()),
+            #[cfg(target_os = "linux")]
+            None => Target::Kernel(netlink::AuditSocket::open()?),
+            #[cfg(not(target_os = "linux"))]
+            None => anyhow::bail!("the kernel audit subsystem is only available on Linux"),
+        };
+        Ok(Self {
+            target,
+            serial: AtomicU64::new(1),
+        })
+    }
+
+    /// Writes records from `records` until shutdown.
+    ///
+    /// Records already queued at shutdown, such as the ends of sessions
+    /// closed while stopping, are still written.
+    pub async fn run(
+        self,
+        mut records: mpsc::UnboundedReceiver<AuditRecord>,
+        shutdown_token: CancellationToken,
+    ) {
+        let mut failing = false;
+        loop {
+            let record = tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                next = records.recv() => match next {
+                    Some(record) => record,
+                    None => return,
+                },
+            };
+            self.write_logged(&record, &mut failing);
+        }
+        while let Ok(record) = records.try_recv() {
+            self.write_logged(&record, &mut failing);
+        }
+    }
+
+    fn write_logged(&self, record: &AuditRecord, failing: &mut bool) {
+        match self.write(record) {
+            Ok(()) => *failing = false,
+            // Only the first of a series of failures is worth a warning
+            Err(e) if !*failing => {
+                warn!(
+                    record = record.kind.name(),
+                    error = %e,
+                    "Failed to write audit record (writing to the kernel needs CAP_AUDIT_WRITE)"
+                );
+                *failing = true;
+            }
+            Err(e) => {
+                debug!(record = record.kind.name(), error = %e, "Failed to write audit record")
+            }
+        }
+    }
+
+    /// Writes one record.
+    pub fn write(&self, record: &AuditRecord) -> std::io::Result<()> {
+        match &self.target {
+            #[cfg(target_os = "linux")]
+            Target::Kernel(socket) => socket.send(record.kind.code(), &record.message),
+            Target::File(path) => {
+                let line = self.format_line(record);
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .mode(0o600)
+                    .open(path)?;
+                writeln!(file, "{}", line)
+            }
+        }
+    }
+
+    /// Formats a record the way auditd writes user records to its log.
+    fn format_line(&self, record: &AuditRecord) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        // The login UID and session are unset: the user did not log in locally
+        format!(
+            "type={} msg=audit({}.{:03}:{}): pid={} uid={} auid=4294967295 ses=4294967295 msg='{}'",
+            record.kind.name(),
+            now.as_secs(),
+            now.subsec_millis(),
+            self.serial.fetch_add(1, Ordering::Relaxed),
+            std::process::id(),
+            nix::unistd::getuid(),
+            record.message
+        )
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod netlink {
+    use std::os::fd::{AsRawFd, OwnedFd};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use nix::sys::socket::{
+        recv, sendto, setsockopt, socket, sockopt, AddressFamily, MsgFlags, NetlinkAddr, SockFlag,
+        SockProtocol, SockType,
+    };
+    use nix::sys::time::TimeVal;
+
+    const NLMSG_HDRLEN: usize = 16;
+    const NLMSG_ERROR: u16 = 2;
+    const NLM_F_REQUEST: u16 = 0x1;
+    const NLM_F_ACK: u16 = 0x4;
+
+    /// Netlink socket to the kernel audit subsystem.
+    #[derive(Debug)]
+    pub(super) struct AuditSocket {
+        fd: OwnedFd,
+        sequence: AtomicU32,
+    }
+
+    impl AuditSocket {
+        pub(super) fn open() -> std::io::Result<Self> {
+            let fd = socket(
+                AddressFamily::Netlink,
+                SockType::Raw,
+                SockFlag::SOCK_CLOEXEC,
+                SockProtocol::NetlinkAudit,
+            )?;
+            // Do not hang if the kernel never acknowledges
+            setsockopt(&fd, sockopt::ReceiveTimeout, &TimeVal::new(1, 0))?;
+            Ok(Self {
+                fd,
+                sequence: AtomicU32::new(1),
+            })
+        }
+
+        /// Sends a user message and waits for the kernel to accept it.
+        pub(super) fn send(&self, kind: u16, message: &str) -> std::io::Result<()> {
+            let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+            let packet = encode(kind, sequence, message);
+            sendto(
+                self.fd.as_raw_fd(),
+                &packet,
+                &NetlinkAddr::new(0, 0),
+                MsgFlags::empty(),
+            )?;
+
+            let mut reply = [0u8; 256];
+            let len = recv(self.fd.as_raw_fd(), &mut reply, MsgFlags::empty())?;
+            match parse_ack(&reply[..len]) {
+                Some(0) | None => Ok(()),
+                Some(errno) => Err(std::io::Error::from_raw_os_error(-errno)),
+            }
+        }
+    }
+
+    /// Builds a netlink message carrying `message`, padded to 4 bytes.
+    pub(super) fn encode(kind: u16, sequence: u32, message: &str) -> Vec<u8> {
+        let len = NLMSG_HDRLEN + message.len() + 1;
+        let mut packet = Vec::with_capacity((len + 3) & !3);
+        packet.extend_from_slice(&(len as u32).to_ne_bytes());
+        packet.extend_from_slice(&kind.to_ne_bytes());
+        packet.extend_from_slice(&(NLM_F_REQUEST | NLM_F_ACK).to_ne_bytes());
+        packet.extend_from_slice(&sequence.to_ne_bytes());
+        // Addressed to the kernel
+        packet.extend_from_slice(&0u32.to_ne_bytes());
+        packet.extend_from_slice(message.as_bytes());
+        packet.push(0);
+        packet.resize((len + 3) & !3, 0);
+        packet
+    }
+
+    /// Returns the error code of an acknowledgement, 0 meaning success.
+    pub(super) fn parse_ack(reply: &[u8]) -> Option<i32> {
+        if reply.len() < NLMSG_HDRLEN + 4 {
+            return None;
+        }
+        let kind = u16::from_ne_bytes([reply[4], reply[5]]);
+        if kind != NLMSG_ERROR {
+            return None;
+        }
+        let errno = &reply[NLMSG_HDRLEN..NLMSG_HDRLEN + 4];
+        Some(i32::from_ne_bytes([errno[0], errno[1], errno[2], errno[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([9u8; 16])
+    }
+
+    #[test]
+    fn test_encode_value() {
+        assert_eq!(encode_value("alice"), "\"alice\"");
+        assert_eq!(encode_value("ls -l"), "6C73202D6C");
+        assert_eq!(encode_value("say \"hi\""), hex::encode_upper("say \"hi\""));
+        assert_eq!(encode_value("caf\u{e9}"), "636166C3A9");
+    }
+
+    #[test]
+    fn test_session_records() {
+        let (audit, mut rx) = AuditLog::channel();
+        let command = FinishedCommand {
+            command: Some("rm -rf build".to_string()),
+            cwd: Some("/srv".to_string()),
+            exit_code: Some(1),
+        };
+
+        // Sessions not opened through the log are ignored
+        audit.command("other", &command);
+        audit.session_ended("other");
+        assert!(rx.try_recv().is_err());
+
+        audit.session_started("s1", 4242, &device(), "laptop");
+        let start = rx.try_recv().unwrap();
+        assert_eq!(start.kind, RecordType::UserStart);
+        assert!(start.message.starts_with("op=remoshell:session_open acct="));
+        assert!(start
+            .message
+            .contains(" terminal=remoshell session=\"s1\" "));
+        assert!(start
+            .message
+            .contains(&format!(" device={} ", device().fingerprint())));
+        assert!(start
+            .message
+            .ends_with(" device_name=\"laptop\" pid=4242 res=success"));
+
+        audit.command("s1", &command);
+        let cmd = rx.try_recv().unwrap();
+        assert_eq!(cmd.kind, RecordType::UserCmd);
+        assert!(cmd.message.starts_with(&format!(
+            "cwd=\"/srv\" cmd={} exit=1 ",
+            hex::encode_upper("rm -rf build")
+        )));
+        assert!(cmd.message.ends_with(" res=failed"));
+
+        // Without a command line there is nothing to record
+        audit.command(
+            "s1",
+            &FinishedCommand {
+                command: None,
+                ..command
+            },
+        );
+        audit.session_ended("s1");
+        let end = rx.try_recv().unwrap();
+        assert_eq!(end.kind, RecordType::UserEnd);
+        assert!(end.message.starts_with("op=remoshell:session_close "));
+
+        // Each session ends once
+        audit.session_ended("s1");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_writes_log_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let writer = AuditWriter::from_config(&AuditdConfig {
+            enabled: true,
+            log_file: Some(path.clone()),
+        })
+        .unwrap();
+
+        let (audit, rx) = AuditLog::channel();
+        audit.session_started("s1", 1, &device(), "laptop");
+        audit.session_ended("s1");
+        drop(audit);
+        writer.run(rx, CancellationToken::new()).await;
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("type=USER_START msg=audit("));
+        assert!(lines[0].contains(":1): pid="));
+        assert!(lines[0].ends_with(" res=success'"));
+        assert!(lines[1].starts_with("type=USER_END msg=audit("));
+        assert!(lines[1].contains(":2): pid="));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_netlink_encoding() {
+        let packet = netlink::encode(RecordType::UserCmd.code(), 7, "op=x");
+        assert_eq!(packet.len(), 24);
+        assert_eq!(u32::from_ne_bytes(packet[0..4].try_into().unwrap()), 21);
+        assert_eq!(u16::from_ne_bytes([packet[4], packet[5]]), 1123);
+        assert_eq!(u32::from_ne_bytes(packet[8..12].try_into().unwrap()), 7);
+        assert_eq!(&packet[16..21], b"op=x\0");
+
+        let mut ack = vec![0u8; 36];
+        ack[4..6].copy_from_slice(&2u16.to_ne_bytes());
+        ack[16..20].copy_from_slice(&(-1i32).to_ne_bytes());
+        assert_eq!(netlink::parse_ack(&ack), Some(-1));
+        assert_eq!(netlink::parse_ack(&ack[..8]), None);
+    }
+}
@@ -0,0 +1,390 @@
+//! Roles and authorization of management operations.
+//!
+//! Management operations (reading status, deciding which devices get in,
+//! changing the daemon's configuration) are allowed by role rather than by
+//! trust alone. A trusted device may hold one of three roles, and any number
+//! of devices may share one:
+//!
+//! | Role | Allows |
+//! |------|--------|
+//! | `viewer` | Reading status, sessions, logs and pending requests |
+//! | `admin` | The above, approving devices and sessions, killing sessions |
+//! | `owner` | Everything, including changing the configuration and stopping the daemon |
+//!
+//! Devices without a role have no management access. The local operator,
+//! talking to the daemon over the IPC socket as the daemon's user or root,
+//! is an owner; other local users have no management access. Every management request goes through
+//! [`authorize`], which logs denials under the `audit` tracing target: IPC
+//! requests in the IPC server, and device messages in the router's
+//! [`RoleCheck`](crate::router::middleware::RoleCheck) interceptor.
+
+use std::fmt;
+use std::time::SystemTime;
+
+use protocol::messages::Message;
+use protocol::DeviceId;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::devices::{DeviceTrust, TrustedDevice};
+use crate::ipc::IpcRequest;
+
+/// A management role, ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// May read the daemon's state.
+    Viewer,
+    /// May also approve devices and sessions and kill sessions.
+    Admin,
+    /// May also change the configuration and stop the daemon.
+    Owner,
+}
+
+impl Role {
+    /// Returns the role's name, as stored in the trust store.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Admin => "admin",
+            Self::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A management operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Read status, sessions, logs, network information and pending requests.
+    ReadStatus,
+    /// Approve or reject devices and sessions, or pick up trust changes.
+    ApproveDevice,
+    /// Kill sessions.
+    ManageSessions,
+    /// Change the daemon's configuration.
+    ChangeConfig,
+    /// Stop the daemon.
+    StopDaemon,
+}
+
+impl Operation {
+    /// Returns the least privileged role allowed to perform the operation.
+    pub fn required_role(self) -> Role {
+        match self {
+            Self::ReadStatus => Role::Viewer,
+            Self::ApproveDevice | Self::ManageSessions => Role::Admin,
+            Self::ChangeConfig | Self::StopDaemon => Role::Owner,
+        }
+    }
+
+    /// Returns the operation an IPC request performs.
+    pub fn of_ipc_request(request: &IpcRequest) -> Self {
+        match request {
+            IpcRequest::Ping
+            | IpcRequest::Status
+            | IpcRequest::ListSessions
+            | IpcRequest::LogsSubscribe { .. }
+            | IpcRequest::PairingStatus { .. }
+            | IpcRequest::NetworkInfo
+            | IpcRequest::MeshStatus
+            | IpcRequest::ListSessionConfirmations => Self::ReadStatus,
+            IpcRequest::ReloadTrustStore | IpcRequest::AnswerSessionConfirmation { .. } => {
+                Self::ApproveDevice
+            }
+            IpcRequest::KillSession { .. } => Self::ManageSessions,
+            IpcRequest::Stop => Self::StopDaemon,
+            IpcRequest::SetMaintenance { .. } => Self::ChangeConfig,
+        }
+    }
+
+    /// Returns the management operation a device message performs, or
+    /// `None` if the message is not a management request.
+    pub fn of_message(message: &Message) -> Option<Self> {
+        match message {
+            Message::ConfigRequest(_) => Some(Self::ReadStatus),
+            Message::ConfigPatch(_) => Some(Self::ChangeConfig),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ReadStatus => "read status",
+            Self::ApproveDevice => "approve devices",
+            Self::ManageSessions => "manage sessions",
+            Self::ChangeConfig => "change the configuration",
+            Self::StopDaemon => "stop the daemon",
+        })
+    }
+}
+
+/// Whoever asks for a management operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    /// The operator on the daemon's host, over the IPC socket.
+    LocalOperator,
+    /// Another user on the daemon's host, over the IPC socket.
+    LocalUser {
+        /// The user's ID.
+        uid: u32,
+    },
+    /// A client device, with its role if it is trusted and has one.
+    Device {
+        /// The device.
+        device_id: DeviceId,
+        /// The device's role.
+        role: Option<Role>,
+    },
+}
+
+impl Principal {
+    /// Returns the principal for an IPC client running as `peer_uid`.
+    ///
+    /// The daemon's own user and root are the local operator. If the
+    /// platform does not report the client's user, the socket's permissions
+    /// are relied on to keep other users out.
+    pub fn ipc_peer(peer_uid: Option<u32>) -> Self {
+        let daemon_uid = nix::unistd::geteuid().as_raw();
+        match peer_uid {
+            Some(uid) if uid != daemon_uid && uid != 0 => Self::LocalUser { uid },
+            _ => Self::LocalOperator,
+        }
+    }
+
+    /// Returns the principal for a device from the trust store. Only devices
+    /// trusted at `now` keep their role.
+    pub fn device(device: &TrustedDevice, now: SystemTime) -> Self {
+        Self::from_trust(device.device_id, Some(&DeviceTrust::from(device)), now)
+    }
+
+    /// Returns the principal for a device from its cached trust, or `None`
+    /// if the device is not in the trust store. Only devices trusted at
+    /// `now` keep their role.
+    pub fn from_trust(device_id: DeviceId, trust: Option<&DeviceTrust>, now: SystemTime) -> Self {
+        Self::Device {
+            device_id,
+            role: trust
+                .filter(|trust| trust.is_trusted_at(now))
+                .and_then(|trust| trust.role),
+        }
+    }
+
+    /// Returns the principal's role, if it has one.
+    pub fn role(&self) -> Option<Role> {
+        match self {
+            Self::LocalOperator => Some(Role::Owner),
+            Self::LocalUser { .. } => None,
+            Self::Device { role, .. } => *role,
+        }
+    }
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LocalOperator => f.write_str("local operator"),
+            Self::LocalUser { uid } => write!(f, "local user {}", uid),
+            Self::Device { device_id, .. } => write!(f, "device {}", device_id),
+        }
+    }
+}
+
+/// Errors from authorizing a management operation.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AuthzError {
+    #[error("{principal} has no management role and may not {operation}")]
+    NoRole {
+        principal: String,
+        operation: Operation,
+    },
+
+    #[error("{principal} is {role} and may not {operation}, which requires {required}")]
+    InsufficientRole {
+        principal: String,
+        role: Role,
+        required: Role,
+        operation: Operation,
+    },
+}
+
+/// Checks that `principal` may perform `operation`.
+///
+/// Denials are logged under the `audit` target.
+pub fn authorize(principal: &Principal, operation: Operation) -> Result<(), AuthzError> {
+    let required = operation.required_role();
+    let result = match principal.role() {
+        Some(role) if role >= required => return Ok(()),
+        Some(role) => Err(AuthzError::InsufficientRole {
+            principal: principal.to_string(),
+            role,
+            required,
+            operation,
+        }),
+        None => Err(AuthzError::NoRole {
+            principal: principal.to_string(),
+            operation,
+        }),
+    };
+
+    warn!(
+        target: "audit",
+        principal = %principal,
+        role = ?principal.role(),
+        operation = %operation,
+        "Management operation denied"
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn device(role: Option<Role>) -> Principal {
+        Principal::Device {
+            device_id: DeviceId::from_bytes([7; 16]),
+            role,
+        }
+    }
+
+    #[test]
+    fn test_role_order() {
+        assert!(Role::Viewer < Role::Admin);
+        assert!(Role::Admin < Role::Owner);
+        assert_eq!(serde_json::to_string(&Role::Admin).unwrap(), "\"admin\"");
+        assert_eq!(
+            serde_json::from_str::<Role>("\"owner\"").unwrap(),
+            Role::Owner
+        );
+    }
+
+    #[test]
+    fn test_authorize_by_role() {
+        let operations = [
+            Operation::ReadStatus,
+            Operation::ApproveDevice,
+            Operation::ManageSessions,
+            Operation::ChangeConfig,
+            Operation::StopDaemon,
+        ];
+        let allowed = |principal: &Principal| {
+            operations
+                .iter()
+                .map(|op| authorize(principal, *op).is_ok())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(allowed(&device(None)), [false; 5]);
+        assert_eq!(
+            allowed(&device(Some(Role::Viewer))),
+            [true, false, false, false, false]
+        );
+        assert_eq!(
+            allowed(&device(Some(Role::Admin))),
+            [true, true, true, false, false]
+        );
+        assert_eq!(allowed(&device(Some(Role::Owner))), [true; 5]);
+        assert_eq!(allowed(&Principal::LocalOperator), [true; 5]);
+
+        assert!(matches!(
+            authorize(&device(Some(Role::Admin)), Operation::ChangeConfig),
+            Err(AuthzError::InsufficientRole {
+                required: Role::Owner,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_ipc_operations() {
+        assert_eq!(
+            Operation::of_ipc_request(&IpcRequest::Status),
+            Operation::ReadStatus
+        );
+        assert_eq!(
+            Operation::of_ipc_request(&IpcRequest::AnswerSessionConfirmation {
+                id: 1,
+                allow: true
+            }),
+            Operation::ApproveDevice
+        );
+        assert_eq!(
+            Operation::of_ipc_request(&IpcRequest::Stop),
+            Operation::StopDaemon
+        );
+    }
+
+    #[test]
+    fn test_message_operations() {
+        assert_eq!(
+            Operation::of_message(&Message::ConfigRequest(Default::default())),
+            Some(Operation::ReadStatus)
+        );
+        assert_eq!(
+            Operation::of_message(&Message::ConfigPatch(Default::default())),
+            Some(Operation::ChangeConfig)
+        );
+        assert_eq!(
+            Operation::of_message(&Message::Ping(protocol::messages::Ping {
+                timestamp: 0,
+                payload: vec![],
+            })),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ipc_peer() {
+        let daemon_uid = nix::unistd::geteuid().as_raw();
+        assert_eq!(
+            Principal::ipc_peer(Some(daemon_uid)),
+            Principal::LocalOperator
+        );
+        assert_eq!(Principal::ipc_peer(Some(0)), Principal::LocalOperator);
+        assert_eq!(Principal::ipc_peer(None), Principal::LocalOperator);
+
+        let other = Principal::ipc_peer(Some(daemon_uid.max(1) + 1000));
+        assert!(matches!(other, Principal::LocalUser { .. }));
+        assert!(matches!(
+            authorize(&other, Operation::ReadStatus),
+            Err(AuthzError::NoRole { .. })
+        ));
+    }
+
+    #[test]
+    fn test_only_trusted_devices_keep_their_role() {
+        let identity = protocol::DeviceIdentity::generate();
+        let mut device = TrustedDevice::new(
+            *identity.device_id(),
+            "Laptop".to_string(),
+            identity.public_key_bytes(),
+        );
+        device.role = Some(Role::Admin);
+        let now = SystemTime::now();
+        assert_eq!(Principal::device(&device, now).role(), Some(Role::Admin));
+
+        // Temporary grants keep it only while they last
+        device.grant = Some(crate::devices::TemporaryGrant {
+            expires_at: now + Duration::from_secs(60),
+            capabilities: Default::default(),
+            previous_trust_level: crate::devices::TrustLevel::Unknown,
+        });
+        assert_eq!(Principal::device(&device, now).role(), Some(Role::Admin));
+        let later = now + Duration::from_secs(61);
+        assert_eq!(Principal::device(&device, later).role(), None);
+
+        device.grant = None;
+        device.trust_level = crate::devices::TrustLevel::Revoked;
+        assert_eq!(Principal::device(&device, now).role(), None);
+    }
+}
@@ -0,0 +1,269 @@
+//! Panic capture and crash reporting.
+//!
+//! This module installs a process-wide panic hook that writes a structured
+//! crash report (including a backtrace) to the daemon's data directory, and
+//! provides a task-local name so reports from spawned tasks identify which
+//! subsystem panicked. A [`CrashHandler`] can also pass each report on, such
+//! as to the `crash` webhook.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Name of the crash report directory inside the data directory.
+pub const CRASH_DIR_NAME: &str = "crashes";
+
+tokio::task_local! {
+    static CURRENT_TASK: &'static str;
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// A structured report describing a captured panic.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CrashReport {
+    /// Unix timestamp in milliseconds when the panic occurred.
+    pub timestamp_ms: u64,
+    /// Name of the task that panicked, if known.
+    pub task: Option<String>,
+    /// Name of the OS thread that panicked, if any.
+    pub thread: Option<String>,
+    /// The panic message.
+    pub message: String,
+    /// Source location of the panic (`file:line:column`).
+    pub location: Option<String>,
+    /// Captured backtrace.
+    pub backtrace: String,
+    /// Daemon version that produced the report.
+    pub version: String,
+}
+
+impl CrashReport {
+    /// Writes the report as JSON into `dir`, creating the directory if needed.
+    ///
+    /// Returns the path of the written file.
+    pub fn write_to(&self, dir: &Path) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let task = self.task.as_deref().unwrap_or("unknown");
+        let path = dir.join(format!("crash-{}-{}.json", self.timestamp_ms, task));
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        std::fs::write(&path, json)?;
+        Ok(path)
+    }
+}
+
+/// Returns the crash report directory for the given data directory.
+pub fn crash_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(CRASH_DIR_NAME)
+}
+
+/// Extracts a human-readable message from a panic payload.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+/// Returns the name of the current supervised task, if any.
+pub fn current_task_name() -> Option<&'static str> {
+    CURRENT_TASK.try_with(|name| *name).ok()
+}
+
+/// Runs a future with the given task name attached for crash reports.
+pub async fn run_named<F: Future>(name: &'static str, future: F) -> F::Output {
+    CURRENT_TASK.scope(name, future).await
+}
+
+/// Called with each crash report and the path it was written to, if
+/// writing it succeeded.
+pub type CrashNotifier = Box<dyn Fn(&CrashReport, Option<&Path>) + Send + Sync>;
+
+/// Writes crash reports and passes them on to an optional notifier.
+pub struct CrashHandler {
+    crash_dir: PathBuf,
+    notify: Option<CrashNotifier>,
+}
+
+impl CrashHandler {
+    /// Creates a handler writing reports into `crash_dir`.
+    pub fn new(crash_dir: PathBuf) -> Self {
+        Self {
+            crash_dir,
+            notify: None,
+        }
+    }
+
+    /// Passes each report to `notify` once it is written.
+    ///
+    /// `notify` runs inside the panic hook, so it must not block or panic.
+    pub fn with_notifier(
+        mut self,
+        notify: impl Fn(&CrashReport, Option<&Path>) + Send + Sync + 'static,
+    ) -> Self {
+        self.notify = Some(Box::new(notify));
+        self
+    }
+
+    /// Writes a report, logs it, and notifies.
+    pub fn handle(&self, report: &CrashReport) {
+        let path = match report.write_to(&self.crash_dir) {
+            Ok(path) => {
+                tracing::error!(
+                    task = report.task.as_deref().unwrap_or("unknown"),
+                    "Panic captured: {} (crash report written to {})",
+                    report.message,
+                    path.display()
+                );
+                Some(path)
+            }
+            Err(e) => {
+                tracing::error!(
+                    task = report.task.as_deref().unwrap_or("unknown"),
+                    "Panic captured: {} (failed to write crash report: {})",
+                    report.message,
+                    e
+                );
+                None
+            }
+        };
+
+        if let Some(notify) = &self.notify {
+            notify(report, path.as_deref());
+        }
+    }
+}
+
+/// Installs the crash-reporting panic hook.
+///
+/// The hook builds a [`CrashReport`], hands it to `handler` and then
+/// delegates to the previously installed hook. Only the first call has any
+/// effect.
+pub fn install_panic_hook(handler: CrashHandler) {
+    INSTALL_HOOK.call_once(move || {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let report = CrashReport {
+                timestamp_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0),
+                task: current_task_name().map(str::to_string),
+                thread: std::thread::current().name().map(str::to_string),
+                message: panic_message(info.payload()),
+                location: info
+                    .location()
+                    .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+                backtrace: Backtrace::force_capture().to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            };
+            handler.handle(&report);
+
+            previous(info);
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_panic_message_str() {
+        let payload: Box<dyn Any + Send> = Box::new("boom");
+        assert_eq!(panic_message(payload.as_ref()), "boom");
+    }
+
+    #[test]
+    fn test_panic_message_string() {
+        let payload: Box<dyn Any + Send> = Box::new(String::from("formatted boom"));
+        assert_eq!(panic_message(payload.as_ref()), "formatted boom");
+    }
+
+    #[test]
+    fn test_panic_message_other() {
+        let payload: Box<dyn Any + Send> = Box::new(42u32);
+        assert_eq!(
+            panic_message(payload.as_ref()),
+            "<non-string panic payload>"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_named_sets_task_name() {
+        assert_eq!(current_task_name(), None);
+        let name = run_named("signaling", async { current_task_name() }).await;
+        assert_eq!(name, Some("signaling"));
+    }
+
+    #[test]
+    fn test_crash_report_write_to() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = crash_dir(temp_dir.path());
+        let report = CrashReport {
+            timestamp_ms: 1700000000000,
+            task: Some("approval-cleanup".to_string()),
+            thread: Some("tokio-runtime-worker".to_string()),
+            message: "boom".to_string(),
+            location: Some("src/orchestrator.rs:1:1".to_string()),
+            backtrace: String::new(),
+            version: "0.0.0".to_string(),
+        };
+
+        let path = report.write_to(&dir).unwrap();
+        assert!(path.starts_with(&dir));
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .contains("approval-cleanup"));
+
+        let loaded: CrashReport =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(loaded, report);
+    }
+
+    #[test]
+    fn test_crash_handler_notifies() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = crash_dir(temp_dir.path());
+        let notified = Arc::new(Mutex::new(Vec::new()));
+        let handler = CrashHandler::new(dir.clone()).with_notifier({
+            let notified = Arc::clone(&notified);
+            move |report, path| {
+                notified
+                    .lock()
+                    .unwrap()
+                    .push((report.message.clone(), path.map(Path::to_path_buf)));
+            }
+        });
+        let report = CrashReport {
+            timestamp_ms: 1700000000000,
+            task: Some("router".to_string()),
+            thread: None,
+            message: "boom".to_string(),
+            location: None,
+            backtrace: String::new(),
+            version: "0.0.0".to_string(),
+        };
+
+        handler.handle(&report);
+        let notified = notified.lock().unwrap();
+        assert_eq!(notified.len(), 1);
+        assert_eq!(notified[0].0, "boom");
+        let path = notified[0].1.as_ref().unwrap();
+        assert!(path.starts_with(&dir) && path.exists());
+    }
+}
@@ -0,0 +1,439 @@
+//! Signed trust store export and import.
+//!
+//! `remoshell devices export` writes the trust store as a JSON or TOML
+//! document signed with the daemon's identity key. `remoshell devices import`
+//! verifies the signature and applies the devices to another daemon's trust
+//! store, so a fleet of daemons can be provisioned with the same set of
+//! client devices through configuration management.
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use protocol::{DeviceId, DeviceIdentity, PeerIdentity, Signature};
+use serde::{Deserialize, Serialize};
+
+use super::trust_store::{TrustLevel, TrustStore, TrustedDevice};
+
+/// Current version of the export format.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// File format of a trust store export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Pretty-printed JSON.
+    Json,
+    /// TOML.
+    Toml,
+}
+
+impl ExportFormat {
+    /// Infers the format from a file extension (`.json` or `.toml`).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// How an import is combined with the devices already in the trust store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Add new devices and update existing ones; keep devices not in the export.
+    Merge,
+    /// Make the trust store match the export exactly.
+    Replace,
+}
+
+/// A device entry in an export.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportedDevice {
+    /// Device fingerprint (colon-separated hex).
+    pub device_id: String,
+    /// Human-readable name for the device.
+    pub name: String,
+    /// Base64-encoded Ed25519 public key.
+    pub public_key: String,
+    /// The trust level of this device.
+    pub trust_level: TrustLevel,
+    /// Optional features granted to this device.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub capabilities: BTreeSet<String>,
+}
+
+impl ExportedDevice {
+    /// Decodes the entry, checking that the device ID matches the public key.
+    fn to_trusted_device(&self) -> Result<TrustedDevice> {
+        let (device_id, public_key) = decode_device_key(&self.device_id, &self.public_key)?;
+        let mut device = TrustedDevice::new(device_id, self.name.clone(), public_key);
+        device.trust_level = self.trust_level;
+        device.capabilities = self.capabilities.clone();
+        Ok(device)
+    }
+}
+
+/// Decodes a base64 public key and checks that `device_id` (a fingerprint)
+/// was derived from it.
+pub(super) fn decode_device_key(device_id: &str, public_key: &str) -> Result<(DeviceId, [u8; 32])> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(public_key)
+        .with_context(|| format!("Invalid public key for device {}", device_id))?;
+    let key: [u8; 32] = bytes.as_slice().try_into().map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid public key for device {}: expected 32 bytes, got {}",
+            device_id,
+            bytes.len()
+        )
+    })?;
+    let peer = PeerIdentity::from_public_key_bytes(&key)
+        .with_context(|| format!("Invalid public key for device {}", device_id))?;
+    if peer.fingerprint() != device_id {
+        anyhow::bail!(
+            "Device ID {} does not match its public key ({})",
+            device_id,
+            peer.fingerprint()
+        );
+    }
+    Ok((*peer.device_id(), key))
+}
+
+/// Summary of changes made by an import.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Devices that were not in the trust store.
+    pub added: usize,
+    /// Existing devices whose name, trust level or capabilities changed.
+    pub updated: usize,
+    /// Existing devices that already matched the export.
+    pub unchanged: usize,
+    /// Devices removed because they were not in the export (replace mode).
+    pub removed: usize,
+}
+
+/// A signed snapshot of a trust store.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrustExport {
+    /// Export format version.
+    pub version: u32,
+    /// Unix timestamp (seconds) when the export was created.
+    pub exported_at: u64,
+    /// Base64-encoded public key of the daemon that signed the export.
+    pub signer: String,
+    /// Base64-encoded Ed25519 signature over the other fields.
+    pub signature: String,
+    /// The exported devices.
+    pub devices: Vec<ExportedDevice>,
+}
+
+impl TrustExport {
+    /// Creates a signed export of every device in the trust store.
+    pub fn from_store(store: &TrustStore, identity: &DeviceIdentity) -> Result<Self> {
+        let mut devices: Vec<ExportedDevice> = store
+            .list_devices()?
+            .into_iter()
+            // Temporary grants are not meant to outlive this daemon
+            .filter(|d| d.grant.is_none())
+            .map(|d| ExportedDevice {
+                device_id: d.device_id.fingerprint(),
+                name: d.name,
+                public_key: base64::engine::general_purpose::STANDARD.encode(d.public_key),
+                trust_level: d.trust_level,
+                capabilities: d.capabilities,
+            })
+            .collect();
+        // Stable order so repeated exports of the same store are identical
+        devices.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+
+        let mut export = Self {
+            version: EXPORT_VERSION,
+            exported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            signer: base64::engine::general_purpose::STANDARD.encode(identity.public_key_bytes()),
+            signature: String::new(),
+            devices,
+        };
+        let signature = identity.sign(&export.signed_bytes()?);
+        export.signature = base64::engine::general_purpose::STANDARD.encode(signature.as_bytes());
+        Ok(export)
+    }
+
+    /// Parses an export from a string.
+    pub fn parse(contents: &str, format: ExportFormat) -> Result<Self> {
+        match format {
+            ExportFormat::Json => {
+                serde_json::from_str(contents).context("Failed to parse JSON trust store export")
+            }
+            ExportFormat::Toml => {
+                toml::from_str(contents).context("Failed to parse TOML trust store export")
+            }
+        }
+    }
+
+    /// Serializes the export in the given format.
+    pub fn to_string(&self, format: ExportFormat) -> Result<String> {
+        match format {
+            ExportFormat::Json => {
+                serde_json::to_string_pretty(self).context("Failed to serialize export as JSON")
+            }
+            ExportFormat::Toml => {
+                toml::to_string_pretty(self).context("Failed to serialize export as TOML")
+            }
+        }
+    }
+
+    /// Verifies the signature and returns the identity of the signer.
+    pub fn verify(&self) -> Result<PeerIdentity> {
+        if self.version != EXPORT_VERSION {
+            anyhow::bail!("Unsupported trust store export version: {}", self.version);
+        }
+
+        let engine = base64::engine::general_purpose::STANDARD;
+        let signer_bytes: [u8; 32] = engine
+            .decode(&self.signer)
+            .context("Invalid signer key encoding")?
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid signer key: expected 32 bytes"))?;
+        let signer =
+            PeerIdentity::from_public_key_bytes(&signer_bytes).context("Invalid signer key")?;
+
+        let signature_bytes: [u8; 64] = engine
+            .decode(&self.signature)
+            .context("Invalid signature encoding")?
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid signature: expected 64 bytes"))?;
+        signer
+            .verify(
+                &self.signed_bytes()?,
+                &Signature::from_bytes(signature_bytes),
+            )
+            .context("Trust store export signature verification failed")?;
+
+        Ok(signer)
+    }
+
+    /// Verifies the export and applies it to a trust store.
+    ///
+    /// Existing devices keep their `first_seen` and `last_seen` timestamps.
+    /// Does not save the store; call `save()` afterwards.
+    pub fn apply(&self, store: &TrustStore, mode: ImportMode) -> Result<ImportSummary> {
+        self.verify()?;
+
+        // Decode everything up front so a bad entry leaves the store untouched
+        let devices = self
+            .devices
+            .iter()
+            .map(ExportedDevice::to_trusted_device)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut summary = ImportSummary::default();
+        let imported: HashSet<DeviceId> = devices.iter().map(|d| d.device_id).collect();
+
+        if mode == ImportMode::Replace {
+            for existing in store.list_devices()? {
+                if !imported.contains(&existing.device_id) {
+                    store.remove_device(&existing.device_id)?;
+                    summary.removed += 1;
+                }
+            }
+        }
+
+        for mut device in devices {
+            match store.get_device(&device.device_id)? {
+                Some(existing) => {
+                    if existing.name == device.name
+                        && existing.trust_level == device.trust_level
+                        && existing.capabilities == device.capabilities
+                    {
+                        summary.unchanged += 1;
+                        continue;
+                    }
+                    device.first_seen = existing.first_seen;
+                    device.last_seen = existing.last_seen;
+                    summary.updated += 1;
+                }
+                None => summary.added += 1,
+            }
+            store.add_device(device)?;
+        }
+
+        Ok(summary)
+    }
+
+    /// Returns the bytes covered by the signature.
+    ///
+    /// The signature is computed over a JSON encoding of every field except
+    /// the signature itself, so it holds regardless of the file format.
+    fn signed_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(&(self.version, self.exported_at, &self.signer, &self.devices))
+            .context("Failed to encode export for signing")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_device(name: &str, trust_level: TrustLevel) -> TrustedDevice {
+        let identity = DeviceIdentity::generate();
+        let mut device = TrustedDevice::new(
+            *identity.device_id(),
+            name.to_string(),
+            identity.public_key_bytes(),
+        );
+        device.trust_level = trust_level;
+        device
+    }
+
+    fn create_store(temp_dir: &TempDir, name: &str) -> TrustStore {
+        TrustStore::new(temp_dir.path().join(name))
+    }
+
+    #[test]
+    fn test_export_roundtrip_json_and_toml() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = create_store(&temp_dir, "source.json");
+        store
+            .add_device(create_device("laptop", TrustLevel::Trusted))
+            .unwrap();
+        store
+            .add_device(create_device("old phone", TrustLevel::Revoked))
+            .unwrap();
+
+        let identity = DeviceIdentity::generate();
+        let export = TrustExport::from_store(&store, &identity).unwrap();
+        assert_eq!(export.devices.len(), 2);
+
+        for format in [ExportFormat::Json, ExportFormat::Toml] {
+            let contents = export.to_string(format).unwrap();
+            let parsed = TrustExport::parse(&contents, format).unwrap();
+            assert_eq!(parsed, export);
+            let signer = parsed.verify().unwrap();
+            assert_eq!(signer.device_id(), identity.device_id());
+        }
+    }
+
+    #[test]
+    fn test_tampered_export_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = create_store(&temp_dir, "source.json");
+        store
+            .add_device(create_device("laptop", TrustLevel::Revoked))
+            .unwrap();
+
+        let mut export = TrustExport::from_store(&store, &DeviceIdentity::generate()).unwrap();
+        export.devices[0].trust_level = TrustLevel::Trusted;
+
+        assert!(export.verify().is_err());
+        let target = create_store(&temp_dir, "target.json");
+        assert!(export.apply(&target, ImportMode::Merge).is_err());
+        assert!(target.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_import_merge() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = create_store(&temp_dir, "source.json");
+        let shared = create_device("shared", TrustLevel::Trusted);
+        let new = create_device("new", TrustLevel::Trusted);
+        source.add_device(shared.clone()).unwrap();
+        source.add_device(new.clone()).unwrap();
+
+        let target = create_store(&temp_dir, "target.json");
+        let mut local_shared = shared.clone();
+        local_shared.trust_level = TrustLevel::Unknown;
+        target.add_device(local_shared.clone()).unwrap();
+        let local_only = create_device("local", TrustLevel::Trusted);
+        target.add_device(local_only.clone()).unwrap();
+
+        let export = TrustExport::from_store(&source, &DeviceIdentity::generate()).unwrap();
+        let summary = export.apply(&target, ImportMode::Merge).unwrap();
+
+        assert_eq!(
+            summary,
+            ImportSummary {
+                added: 1,
+                updated: 1,
+                unchanged: 0,
+                removed: 0,
+            }
+        );
+        assert_eq!(target.len().unwrap(), 3);
+        assert!(target.is_trusted(&shared.device_id).unwrap());
+        assert!(target.is_trusted(&new.device_id).unwrap());
+        assert!(target.is_trusted(&local_only.device_id).unwrap());
+
+        // Timestamps of existing devices are preserved
+        let merged = target.get_device(&shared.device_id).unwrap().unwrap();
+        assert_eq!(merged.first_seen, local_shared.first_seen);
+    }
+
+    #[test]
+    fn test_import_replace() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = create_store(&temp_dir, "source.json");
+        let kept = create_device("kept", TrustLevel::Trusted);
+        source.add_device(kept.clone()).unwrap();
+
+        let target = create_store(&temp_dir, "target.json");
+        target.add_device(kept.clone()).unwrap();
+        target
+            .add_device(create_device("stale", TrustLevel::Trusted))
+            .unwrap();
+
+        let export = TrustExport::from_store(&source, &DeviceIdentity::generate()).unwrap();
+        let summary = export.apply(&target, ImportMode::Replace).unwrap();
+
+        assert_eq!(summary.removed, 1);
+        assert_eq!(summary.unchanged, 1);
+        assert_eq!(target.len().unwrap(), 1);
+        assert!(target.get_device(&kept.device_id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_import_rejects_mismatched_device_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let identity = DeviceIdentity::generate();
+        let mut export =
+            TrustExport::from_store(&create_store(&temp_dir, "empty.json"), &identity).unwrap();
+
+        // A correctly signed export whose device ID was not derived from its key
+        let other = create_device("other", TrustLevel::Trusted);
+        export.devices.push(ExportedDevice {
+            device_id: create_device("spoofed", TrustLevel::Trusted)
+                .device_id
+                .fingerprint(),
+            name: other.name,
+            public_key: base64::engine::general_purpose::STANDARD.encode(other.public_key),
+            trust_level: TrustLevel::Trusted,
+            capabilities: BTreeSet::new(),
+        });
+        let signature = identity.sign(&export.signed_bytes().unwrap());
+        export.signature = base64::engine::general_purpose::STANDARD.encode(signature.as_bytes());
+
+        let target = create_store(&temp_dir, "target.json");
+        let err = export.apply(&target, ImportMode::Merge).unwrap_err();
+        assert!(err.to_string().contains("does not match its public key"));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            ExportFormat::from_path(Path::new("fleet.json")),
+            Some(ExportFormat::Json)
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("fleet.TOML")),
+            Some(ExportFormat::Toml)
+        );
+        assert_eq!(ExportFormat::from_path(Path::new("fleet")), None);
+    }
+}
@@ -3,8 +3,27 @@
 //! This module provides functionality for managing trusted devices,
 //! including persistence and trust level management.
 
+pub mod export;
+pub mod names;
+pub mod pairing;
+pub mod provisioning;
+pub mod reinvite;
+pub mod security_key;
+pub mod session_confirm;
+pub mod trust_cache;
 pub mod trust_store;
 
+pub use export::{ExportFormat, ImportMode, ImportSummary, TrustExport};
+pub use names::{DeviceName, MAX_DEVICE_NAME_CHARS};
+pub use pairing::{
+    ConsumeOutcome, PairingCode, PairingCodeStatus, PairingCodeStore, PAIRING_CODE_FILE_NAME,
+};
+pub use provisioning::{Drift, ProvisionedDevice, ProvisioningFile, ReconcileReport};
+pub use reinvite::{RedeemOutcome, Reinvite, ReinviteStore, DEFAULT_REINVITE_TTL};
+pub use security_key::{GatedAction, SecurityKeyError, SecurityKeyGate};
+pub use session_confirm::{ConfirmOutcome, PendingSession, SessionConfirmations};
+pub use trust_cache::{DeviceTrust, GrantRights, TrustCache};
 pub use trust_store::{
-    default_trust_store_path, PendingApproval, TrustLevel, TrustStore, TrustedDevice,
+    default_trust_store_path, PendingApproval, TemporaryGrant, TrustLevel, TrustStore,
+    TrustedDevice, FILES_ACCESS, SHELL_ACCESS, TRUST_STORE_FILE_NAME,
 };
@@ -0,0 +1,188 @@
+//! Device names sent by clients.
+//!
+//! Names come from untrusted peers and end up in the TUI, CLI output, logs
+//! and approval prompts, so the router normalizes them before anything else
+//! sees them. Normalization strips control and invisible formatting
+//! characters (which could rewrite the terminal or reorder text), collapses
+//! whitespace, NFC-normalizes and caps the length. The name as sent is kept
+//! in the trust store when it differs, for forensics.
+//!
+//! Names mixing Latin, Greek, Cyrillic, Armenian or Cherokee letters are
+//! flagged: those scripts share look-alike letters, so `Аlice's laptop` with
+//! a Cyrillic `А` can pass for a device the user already trusts.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest device name kept, in characters.
+pub const MAX_DEVICE_NAME_CHARS: usize = 64;
+
+/// Name given to devices whose name is empty after normalization.
+pub const UNNAMED_DEVICE: &str = "Unnamed device";
+
+/// A device name normalized for display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceName {
+    /// The normalized name.
+    pub name: String,
+    /// The name as sent by the device.
+    pub raw: String,
+    /// The name mixes scripts with look-alike letters.
+    pub mixed_script: bool,
+}
+
+impl DeviceName {
+    /// Normalizes a name sent by a device.
+    pub fn normalize(raw: &str) -> Self {
+        let mut name = String::with_capacity(raw.len());
+        let mut pending_space = false;
+        for c in raw.nfc().filter(|c| c.is_whitespace() || !is_invisible(*c)) {
+            if c.is_whitespace() {
+                pending_space = !name.is_empty();
+                continue;
+            }
+            if pending_space {
+                name.push(' ');
+                pending_space = false;
+            }
+            name.push(c);
+        }
+        if let Some((end, _)) = name.char_indices().nth(MAX_DEVICE_NAME_CHARS) {
+            name.truncate(end);
+            name.truncate(name.trim_end().len());
+        }
+        if name.is_empty() {
+            name = UNNAMED_DEVICE.to_string();
+        }
+
+        Self {
+            mixed_script: is_mixed_script(&name),
+            name,
+            raw: raw.to_string(),
+        }
+    }
+
+    /// Returns the name as sent, if normalization changed it.
+    pub fn raw_if_changed(&self) -> Option<String> {
+        (self.raw != self.name).then(|| self.raw.clone())
+    }
+}
+
+/// Returns true if `name` has letters from more than one of the scripts
+/// with look-alike letters.
+pub fn is_mixed_script(name: &str) -> bool {
+    let mut seen = None;
+    for script in name.chars().filter_map(confusable_script) {
+        match seen {
+            None => seen = Some(script),
+            Some(first) if first != script => return true,
+            Some(_) => {}
+        }
+    }
+    false
+}
+
+/// Scripts whose letters are commonly confused with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Cherokee,
+}
+
+/// Returns the script of a letter, if it is one of the confusable ones.
+fn confusable_script(c: char) -> Option<Script> {
+    if !c.is_alphabetic() {
+        return None;
+    }
+    match c as u32 {
+        0x0041..=0x024F | 0x1E00..=0x1EFF | 0x2C60..=0x2C7F | 0xA720..=0xA7FF | 0xFF21..=0xFF5A => {
+            Some(Script::Latin)
+        }
+        0x0370..=0x03FF | 0x1F00..=0x1FFF => Some(Script::Greek),
+        0x0400..=0x052F | 0x1C80..=0x1C8F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => {
+            Some(Script::Cyrillic)
+        }
+        0x0530..=0x058F => Some(Script::Armenian),
+        0x13A0..=0x13FF | 0xAB70..=0xABBF => Some(Script::Cherokee),
+        _ => None,
+    }
+}
+
+/// Returns true for characters that change how text is displayed without
+/// being visible: control characters, bidirectional overrides and
+/// zero-width characters.
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{061C}'
+                | '\u{180E}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{206F}'
+                | '\u{FEFF}'
+                | '\u{FFF9}'..='\u{FFFB}'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_plain_name() {
+        let name = DeviceName::normalize("Alice's laptop");
+        assert_eq!(name.name, "Alice's laptop");
+        assert!(!name.mixed_script);
+        assert_eq!(name.raw_if_changed(), None);
+    }
+
+    #[test]
+    fn test_normalize_strips_control_and_invisible_characters() {
+        let name = DeviceName::normalize("evil\x1b[2J\u{202E}pot\u{200B}\r\nphone\x07");
+        assert_eq!(name.name, "evil[2Jpot phone");
+        assert_eq!(
+            name.raw_if_changed().as_deref(),
+            Some("evil\x1b[2J\u{202E}pot\u{200B}\r\nphone\x07")
+        );
+    }
+
+    #[test]
+    fn test_normalize_collapses_whitespace() {
+        assert_eq!(DeviceName::normalize("  my \t  phone  ").name, "my phone");
+        assert_eq!(DeviceName::normalize("\u{200B} \n").name, UNNAMED_DEVICE);
+    }
+
+    #[test]
+    fn test_normalize_nfc() {
+        // "é" as "e" and a combining acute accent
+        let name = DeviceName::normalize("Cafe\u{0301}");
+        assert_eq!(name.name, "Caf\u{00E9}");
+        assert!(!name.mixed_script);
+    }
+
+    #[test]
+    fn test_normalize_limits_length() {
+        let name = DeviceName::normalize(&"é".repeat(100));
+        assert_eq!(name.name.chars().count(), MAX_DEVICE_NAME_CHARS);
+
+        let name = DeviceName::normalize(&format!("{} tail", "a".repeat(63)));
+        assert_eq!(name.name, "a".repeat(63));
+    }
+
+    #[test]
+    fn test_mixed_script() {
+        // Cyrillic "А" and "о" among Latin letters
+        assert!(DeviceName::normalize("\u{0410}lice's lapt\u{043E}p").mixed_script);
+        // Greek omicron
+        assert!(is_mixed_script("r\u{03BF}uter"));
+        assert!(!is_mixed_script("Ноутбук Алисы"));
+        assert!(!is_mixed_script("Ελένη 2"));
+        // Scripts without Latin look-alikes may be mixed with Latin freely
+        assert!(!is_mixed_script("MacBook 太郎"));
+        assert!(!is_mixed_script("Pixel 8 (テスト)"));
+    }
+}
@@ -0,0 +1,389 @@
+//! Single-use pairing codes.
+//!
+//! `remoshell pair` and the TUI register each code they show before
+//! publishing it on the signaling server. A client that paired with a code
+//! presents it in its `DeviceApprovalRequest`: the first device to do so
+//! consumes the code and is bound to it, and any other device presenting it
+//! later is rejected. Only a SHA-256 hash of each code is stored, in
+//! `pairing_codes.json` next to the trust store, so the CLI and a running
+//! daemon share codes through the filesystem.
+//!
+//! While any code is outstanding, that is registered and neither consumed
+//! nor expired, new devices must present one: see
+//! [`PairingCodeStore::has_outstanding`].
+
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use protocol::DeviceId;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::util::file_store::JsonFileStore;
+
+/// File name of the pairing code store, placed next to the trust store.
+pub const PAIRING_CODE_FILE_NAME: &str = "pairing_codes.json";
+
+/// How long codes are kept after they expire, so that their status can
+/// still be queried.
+pub const PAIRING_CODE_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A registered pairing code.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PairingCode {
+    /// Hex-encoded SHA-256 hash of the normalized code.
+    pub code_hash: String,
+    /// When the code was registered.
+    pub issued_at: SystemTime,
+    /// When the code stops being accepted.
+    pub expires_at: SystemTime,
+    /// The device that consumed the code, if any.
+    pub consumed_by: Option<DeviceId>,
+    /// When the code was consumed.
+    pub consumed_at: Option<SystemTime>,
+}
+
+impl PairingCode {
+    /// Returns true if the code has expired.
+    pub fn is_expired(&self) -> bool {
+        SystemTime::now() >= self.expires_at
+    }
+
+    fn is_stale(&self) -> bool {
+        SystemTime::now() >= self.expires_at + PAIRING_CODE_RETENTION
+    }
+}
+
+/// Result of presenting a pairing code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsumeOutcome {
+    /// The code was unused and is now bound to the device.
+    Accepted,
+    /// The code is already bound to the same device.
+    AlreadyBound,
+    /// The code was consumed by another device.
+    Reused,
+    /// The code expired before it was used.
+    Expired,
+    /// No such code was registered.
+    Unknown,
+}
+
+/// State of a pairing code, as reported to the CLI.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum PairingCodeStatus {
+    /// No such code was registered, or it was removed after expiring.
+    Unknown,
+    /// The code has not been used yet.
+    Pending {
+        /// Unix timestamp (seconds) when the code expires.
+        expires_at: u64,
+    },
+    /// The code was consumed.
+    Consumed {
+        /// Fingerprint of the device the code is bound to.
+        device_id: String,
+        /// Unix timestamp (seconds) when the code was consumed.
+        consumed_at: u64,
+    },
+    /// The code expired unused.
+    Expired,
+}
+
+/// Serialized form of the pairing code store.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PairingCodeData {
+    codes: Vec<PairingCode>,
+}
+
+/// File-backed store of pairing codes.
+///
+/// Every operation reads the file directly so that codes registered by the
+/// CLI are visible to a running daemon without a restart, and changes it
+/// under a lock so that a code cannot be consumed twice.
+#[derive(Debug, Clone)]
+pub struct PairingCodeStore {
+    file: JsonFileStore<PairingCodeData>,
+}
+
+impl PairingCodeStore {
+    /// Creates a store backed by the given file.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            file: JsonFileStore::new(path, "pairing code store"),
+        }
+    }
+
+    /// Creates a store next to the given trust store file.
+    pub fn for_trust_store(trust_store_path: &Path) -> Self {
+        let dir = trust_store_path.parent().unwrap_or_else(|| Path::new("."));
+        Self::new(dir.join(PAIRING_CODE_FILE_NAME))
+    }
+
+    /// Returns the path to the store file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Registers a code that stays valid for `ttl`.
+    ///
+    /// Codes that expired more than [`PAIRING_CODE_RETENTION`] ago are
+    /// removed.
+    pub fn register(&self, code: &str, ttl: Duration) -> Result<()> {
+        let code_hash = hash_code(code);
+        let now = SystemTime::now();
+        self.file.update(|data| {
+            data.codes
+                .retain(|c| c.code_hash != code_hash && !c.is_stale());
+            data.codes.push(PairingCode {
+                code_hash,
+                issued_at: now,
+                expires_at: now + ttl,
+                consumed_by: None,
+                consumed_at: None,
+            });
+        })
+    }
+
+    /// Returns true if a registered code is neither consumed nor expired.
+    ///
+    /// A device that presents no code is then refused, since otherwise it
+    /// could pair without the code the operator is showing.
+    pub fn has_outstanding(&self) -> Result<bool> {
+        Ok(self
+            .file
+            .read()?
+            .codes
+            .iter()
+            .any(|c| c.consumed_by.is_none() && !c.is_expired()))
+    }
+
+    /// Returns the state of a code.
+    pub fn status(&self, code: &str) -> Result<PairingCodeStatus> {
+        let code_hash = hash_code(code);
+        let data = self.file.read()?;
+        let Some(entry) = data.codes.iter().find(|c| c.code_hash == code_hash) else {
+            return Ok(PairingCodeStatus::Unknown);
+        };
+
+        Ok(match (&entry.consumed_by, entry.consumed_at) {
+            (Some(device_id), Some(consumed_at)) => PairingCodeStatus::Consumed {
+                device_id: device_id.fingerprint(),
+                consumed_at: unix_secs(consumed_at),
+            },
+            _ if entry.is_expired() => PairingCodeStatus::Expired,
+            _ => PairingCodeStatus::Pending {
+                expires_at: unix_secs(entry.expires_at),
+            },
+        })
+    }
+
+    /// Presents a code on behalf of a device, binding the code to it if the
+    /// code is unused.
+    pub fn consume(&self, code: &str, device_id: &DeviceId) -> Result<ConsumeOutcome> {
+        let code_hash = hash_code(code);
+        self.file.update(|data| {
+            let Some(entry) = data.codes.iter_mut().find(|c| c.code_hash == code_hash) else {
+                return ConsumeOutcome::Unknown;
+            };
+
+            match &entry.consumed_by {
+                Some(bound) if bound == device_id => return ConsumeOutcome::AlreadyBound,
+                Some(_) => return ConsumeOutcome::Reused,
+                None if entry.is_expired() => return ConsumeOutcome::Expired,
+                None => {}
+            }
+
+            entry.consumed_by = Some(*device_id);
+            entry.consumed_at = Some(SystemTime::now());
+            ConsumeOutcome::Accepted
+        })
+    }
+}
+
+/// Returns the hex-encoded SHA-256 hash of a code.
+///
+/// Codes are compared case-insensitively, as users may type them by hand.
+fn hash_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.trim().to_ascii_uppercase().as_bytes()))
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::{Arc, Barrier};
+    use tempfile::TempDir;
+
+    fn device_id() -> DeviceId {
+        *protocol::DeviceIdentity::generate().device_id()
+    }
+
+    fn store(temp_dir: &TempDir) -> PairingCodeStore {
+        PairingCodeStore::new(temp_dir.path().join(PAIRING_CODE_FILE_NAME))
+    }
+
+    #[test]
+    fn test_consume_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = store(&temp_dir);
+        let first = device_id();
+
+        store
+            .register("ABCD-1234", Duration::from_secs(300))
+            .unwrap();
+        assert!(matches!(
+            store.status("ABCD-1234").unwrap(),
+            PairingCodeStatus::Pending { .. }
+        ));
+
+        // The plaintext code is never written to disk
+        let contents = fs::read_to_string(store.path()).unwrap();
+        assert!(!contents.contains("ABCD-1234"));
+
+        assert_eq!(
+            store.consume("abcd-1234", &first).unwrap(),
+            ConsumeOutcome::Accepted
+        );
+        // The bound device may present the code again, others may not
+        assert_eq!(
+            store.consume("ABCD-1234", &first).unwrap(),
+            ConsumeOutcome::AlreadyBound
+        );
+        assert_eq!(
+            store.consume("ABCD-1234", &device_id()).unwrap(),
+            ConsumeOutcome::Reused
+        );
+
+        match store.status("ABCD-1234").unwrap() {
+            PairingCodeStatus::Consumed { device_id, .. } => {
+                assert_eq!(device_id, first.fingerprint())
+            }
+            other => panic!("Expected consumed code, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_consume_expired() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = store(&temp_dir);
+
+        store.register("EXPR-0000", Duration::ZERO).unwrap();
+        assert_eq!(
+            store.consume("EXPR-0000", &device_id()).unwrap(),
+            ConsumeOutcome::Expired
+        );
+        assert_eq!(
+            store.status("EXPR-0000").unwrap(),
+            PairingCodeStatus::Expired
+        );
+    }
+
+    #[test]
+    fn test_unknown_code() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = store(&temp_dir);
+
+        assert_eq!(
+            store.consume("NONE-0000", &device_id()).unwrap(),
+            ConsumeOutcome::Unknown
+        );
+        assert_eq!(
+            store.status("NONE-0000").unwrap(),
+            PairingCodeStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_register_prunes_stale_codes() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = store(&temp_dir);
+
+        let stale = SystemTime::now() - PAIRING_CODE_RETENTION - Duration::from_secs(1);
+        store
+            .file
+            .update(|data| {
+                data.codes.push(PairingCode {
+                    code_hash: hash_code("OLD0-0000"),
+                    issued_at: stale,
+                    expires_at: stale,
+                    consumed_by: None,
+                    consumed_at: None,
+                })
+            })
+            .unwrap();
+
+        store
+            .register("NEW0-0000", Duration::from_secs(300))
+            .unwrap();
+        assert_eq!(
+            store.status("OLD0-0000").unwrap(),
+            PairingCodeStatus::Unknown
+        );
+        assert_eq!(store.file.read().unwrap().codes.len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_consume_accepts_one() {
+        let temp_dir = TempDir::new().unwrap();
+        store(&temp_dir)
+            .register("RACE-0000", Duration::from_secs(300))
+            .unwrap();
+
+        let threads = 8;
+        let barrier = Arc::new(Barrier::new(threads));
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let store = store(&temp_dir);
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    store.consume("RACE-0000", &device_id()).unwrap()
+                })
+            })
+            .collect();
+        let outcomes: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let accepted = outcomes
+            .iter()
+            .filter(|o| **o == ConsumeOutcome::Accepted)
+            .count();
+        assert_eq!(accepted, 1, "outcomes: {:?}", outcomes);
+        assert!(outcomes
+            .iter()
+            .all(|o| matches!(o, ConsumeOutcome::Accepted | ConsumeOutcome::Reused)));
+    }
+
+    #[test]
+    fn test_has_outstanding() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = store(&temp_dir);
+        assert!(!store.has_outstanding().unwrap());
+
+        store.register("EXPR-0000", Duration::ZERO).unwrap();
+        assert!(!store.has_outstanding().unwrap());
+
+        store
+            .register("OPEN-0000", Duration::from_secs(300))
+            .unwrap();
+        assert!(store.has_outstanding().unwrap());
+
+        store.consume("OPEN-0000", &device_id()).unwrap();
+        assert!(!store.has_outstanding().unwrap());
+    }
+
+    #[test]
+    fn test_for_trust_store_path() {
+        let store =
+            PairingCodeStore::for_trust_store(Path::new("/etc/remoshell/trusted_devices.json"));
+        assert_eq!(store.path(), Path::new("/etc/remoshell/pairing_codes.json"));
+    }
+}
@@ -0,0 +1,427 @@
+//! Declarative device provisioning.
+//!
+//! A provisioning file (referenced by `security.provisioning_file`) lists the
+//! devices a daemon should trust together with their capabilities and path
+//! permissions. The daemon reconciles its trust store and permission store
+//! against the file on startup and whenever the file changes, and reports any
+//! drift it corrects.
+//!
+//! ```toml
+//! # Remove devices that are not listed below
+//! prune = false
+//!
+//! [[devices]]
+//! device_id = "a1:b2:..."
+//! name = "ops-laptop"
+//! public_key = "base64..."
+//! trust_level = "trusted"
+//! capabilities = ["agent-forwarding"]
+//! default_level = "none"
+//! paths = [{ path = "/srv/app", level = "readwrite" }]
+//! ```
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use protocol::DeviceId;
+use serde::{Deserialize, Serialize};
+
+use super::export::decode_device_key;
+use super::trust_store::{TrustLevel, TrustStore, TrustedDevice};
+use crate::files::permissions::{PathPermission, PermissionLevel};
+use crate::files::{DevicePermissions, PathPermissions};
+
+/// Contents of a provisioning file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProvisioningFile {
+    /// Remove devices (and their permissions) that are not listed.
+    #[serde(default)]
+    pub prune: bool,
+    /// The devices this daemon should know about.
+    #[serde(default)]
+    pub devices: Vec<ProvisionedDevice>,
+}
+
+/// A device entry in a provisioning file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionedDevice {
+    /// Device fingerprint (colon-separated hex).
+    pub device_id: String,
+    /// Human-readable name for the device.
+    pub name: String,
+    /// Base64-encoded Ed25519 public key.
+    pub public_key: String,
+    /// Trust level to enforce (default: trusted).
+    #[serde(default = "default_trust_level")]
+    pub trust_level: TrustLevel,
+    /// Optional features granted to the device.
+    #[serde(default)]
+    pub capabilities: BTreeSet<String>,
+    /// Permission level for paths not listed in `paths`.
+    #[serde(default)]
+    pub default_level: PermissionLevel,
+    /// Per-path permissions.
+    #[serde(default)]
+    pub paths: Vec<PathPermission>,
+}
+
+fn default_trust_level() -> TrustLevel {
+    TrustLevel::Trusted
+}
+
+/// A difference between the provisioning file and the daemon's state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    /// A listed device was missing from the trust store and has been added.
+    Missing { device_id: DeviceId, name: String },
+    /// A listed device differed from the file and has been updated.
+    Changed {
+        device_id: DeviceId,
+        fields: Vec<&'static str>,
+    },
+    /// A listed device's path permissions differed and have been replaced.
+    Permissions { device_id: DeviceId },
+    /// A device in the trust store is not listed in the file.
+    Unmanaged {
+        device_id: DeviceId,
+        name: String,
+        /// Whether the device was removed (`prune = true`).
+        removed: bool,
+    },
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { device_id, name } => {
+                write!(f, "device {} ({}) was missing; added", device_id, name)
+            }
+            Self::Changed { device_id, fields } => write!(
+                f,
+                "device {} differed in {}; updated",
+                device_id,
+                fields.join(", ")
+            ),
+            Self::Permissions { device_id } => {
+                write!(
+                    f,
+                    "device {} had different path permissions; replaced",
+                    device_id
+                )
+            }
+            Self::Unmanaged {
+                device_id,
+                name,
+                removed: true,
+            } => write!(
+                f,
+                "device {} ({}) is not provisioned; removed",
+                device_id, name
+            ),
+            Self::Unmanaged {
+                device_id,
+                name,
+                removed: false,
+            } => write!(
+                f,
+                "device {} ({}) is not provisioned; kept",
+                device_id, name
+            ),
+        }
+    }
+}
+
+/// Result of reconciling against a provisioning file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReconcileReport {
+    /// Differences found, in the order they were handled.
+    pub drift: Vec<Drift>,
+}
+
+impl ReconcileReport {
+    /// Returns true if the daemon already matched the file.
+    pub fn is_clean(&self) -> bool {
+        self.drift.is_empty()
+    }
+
+    /// Returns true if any store was modified.
+    pub fn changed(&self) -> bool {
+        self.drift
+            .iter()
+            .any(|d| !matches!(d, Drift::Unmanaged { removed: false, .. }))
+    }
+}
+
+impl ProvisioningFile {
+    /// Loads a provisioning file from disk.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read provisioning file: {}", path.display()))?;
+        Self::from_toml(&contents)
+            .with_context(|| format!("Failed to parse provisioning file: {}", path.display()))
+    }
+
+    /// Parses a provisioning file from a TOML string.
+    pub fn from_toml(toml_str: &str) -> Result<Self> {
+        toml::from_str(toml_str).context("Invalid provisioning file")
+    }
+
+    /// Makes the trust store and path permissions match this file.
+    ///
+    /// Every entry is validated before anything is changed, so an invalid
+    /// file leaves both stores untouched. Existing devices keep their
+    /// `first_seen` and `last_seen` timestamps. Does not save either store.
+    pub fn reconcile(
+        &self,
+        trust_store: &TrustStore,
+        permissions: &PathPermissions,
+    ) -> Result<ReconcileReport> {
+        let mut wanted = Vec::with_capacity(self.devices.len());
+        let mut listed = HashSet::new();
+        for entry in &self.devices {
+            let (device_id, public_key) = decode_device_key(&entry.device_id, &entry.public_key)?;
+            if !listed.insert(device_id) {
+                anyhow::bail!("Device {} is listed more than once", entry.device_id);
+            }
+            wanted.push((entry, device_id, public_key));
+        }
+
+        let mut report = ReconcileReport::default();
+
+        for existing in trust_store.list_devices()? {
+            if listed.contains(&existing.device_id) {
+                continue;
+            }
+            if self.prune {
+                trust_store.remove_device(&existing.device_id)?;
+                permissions.remove_device_permissions(&existing.device_id)?;
+            }
+            report.drift.push(Drift::Unmanaged {
+                device_id: existing.device_id,
+                name: existing.name,
+                removed: self.prune,
+            });
+        }
+
+        for (entry, device_id, public_key) in wanted {
+            let mut device = TrustedDevice::new(device_id, entry.name.clone(), public_key);
+            device.trust_level = entry.trust_level;
+            device.capabilities = entry.capabilities.clone();
+
+            match trust_store.get_device(&device_id)? {
+                Some(existing) => {
+                    let mut fields = Vec::new();
+                    if existing.name != device.name {
+                        fields.push("name");
+                    }
+                    if existing.trust_level != device.trust_level {
+                        fields.push("trust_level");
+                    }
+                    if existing.capabilities != device.capabilities {
+                        fields.push("capabilities");
+                    }
+                    if !fields.is_empty() {
+                        device.first_seen = existing.first_seen;
+                        device.last_seen = existing.last_seen;
+                        trust_store.add_device(device)?;
+                        report.drift.push(Drift::Changed { device_id, fields });
+                    }
+                }
+                None => {
+                    trust_store.add_device(device)?;
+                    report.drift.push(Drift::Missing {
+                        device_id,
+                        name: entry.name.clone(),
+                    });
+                }
+            }
+
+            let mut device_permissions = DevicePermissions::new(device_id);
+            device_permissions.set_default_level(entry.default_level);
+            for path in &entry.paths {
+                device_permissions.add_path(path.clone());
+            }
+            if permissions.get_device_permissions(&device_id)?.as_ref() != Some(&device_permissions)
+            {
+                permissions.set_device_permissions(device_permissions)?;
+                report.drift.push(Drift::Permissions { device_id });
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use protocol::DeviceIdentity;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn provisioned(identity: &DeviceIdentity, name: &str) -> ProvisionedDevice {
+        ProvisionedDevice {
+            device_id: identity.fingerprint(),
+            name: name.to_string(),
+            public_key: base64::engine::general_purpose::STANDARD
+                .encode(identity.public_key_bytes()),
+            trust_level: TrustLevel::Trusted,
+            capabilities: BTreeSet::new(),
+            default_level: PermissionLevel::None,
+            paths: Vec::new(),
+        }
+    }
+
+    fn create_stores(temp_dir: &TempDir) -> (TrustStore, PathPermissions) {
+        (
+            TrustStore::new(temp_dir.path().join("trusted_devices.json")),
+            PathPermissions::new(
+                temp_dir.path().join("permissions.json"),
+                vec![temp_dir.path().to_path_buf()],
+            ),
+        )
+    }
+
+    #[test]
+    fn test_parse_provisioning_file() {
+        let identity = DeviceIdentity::generate();
+        let toml_str = format!(
+            r#"
+prune = true
+
+[[devices]]
+device_id = "{}"
+name = "ops-laptop"
+public_key = "{}"
+capabilities = ["agent-forwarding"]
+paths = [{{ path = "/srv/app", level = "readwrite" }}]
+"#,
+            identity.fingerprint(),
+            base64::engine::general_purpose::STANDARD.encode(identity.public_key_bytes())
+        );
+
+        let file = ProvisioningFile::from_toml(&toml_str).unwrap();
+        assert!(file.prune);
+        let device = &file.devices[0];
+        assert_eq!(device.trust_level, TrustLevel::Trusted);
+        assert!(device.capabilities.contains("agent-forwarding"));
+        assert_eq!(device.default_level, PermissionLevel::None);
+        assert_eq!(
+            device.paths,
+            vec![PathPermission::new(
+                PathBuf::from("/srv/app"),
+                PermissionLevel::ReadWrite,
+                true
+            )]
+        );
+    }
+
+    #[test]
+    fn test_reconcile_adds_devices_and_permissions() {
+        let temp_dir = TempDir::new().unwrap();
+        let (trust_store, permissions) = create_stores(&temp_dir);
+        let identity = DeviceIdentity::generate();
+        let mut device = provisioned(&identity, "ops-laptop");
+        device.capabilities.insert("agent-forwarding".to_string());
+        device
+            .paths
+            .push(PathPermission::read_only(temp_dir.path().to_path_buf()));
+
+        let file = ProvisioningFile {
+            prune: false,
+            devices: vec![device],
+        };
+        let report = file.reconcile(&trust_store, &permissions).unwrap();
+
+        assert_eq!(report.drift.len(), 2);
+        assert!(report.changed());
+        let stored = trust_store
+            .get_device(identity.device_id())
+            .unwrap()
+            .unwrap();
+        assert!(stored.has_capability("agent-forwarding"));
+        assert!(permissions
+            .can_device_read(identity.device_id(), temp_dir.path())
+            .unwrap());
+        assert!(!permissions
+            .can_device_write(identity.device_id(), temp_dir.path())
+            .unwrap());
+
+        // A second pass finds nothing to do
+        let report = file.reconcile(&trust_store, &permissions).unwrap();
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_reconcile_reports_and_corrects_drift() {
+        let temp_dir = TempDir::new().unwrap();
+        let (trust_store, permissions) = create_stores(&temp_dir);
+        let identity = DeviceIdentity::generate();
+        let file = ProvisioningFile {
+            prune: false,
+            devices: vec![provisioned(&identity, "ops-laptop")],
+        };
+        file.reconcile(&trust_store, &permissions).unwrap();
+
+        // Someone revokes the device by hand
+        trust_store
+            .set_trust_level(identity.device_id(), TrustLevel::Revoked)
+            .unwrap();
+
+        let report = file.reconcile(&trust_store, &permissions).unwrap();
+        assert_eq!(
+            report.drift,
+            vec![Drift::Changed {
+                device_id: *identity.device_id(),
+                fields: vec!["trust_level"],
+            }]
+        );
+        assert!(trust_store.is_trusted(identity.device_id()).unwrap());
+    }
+
+    #[test]
+    fn test_reconcile_unmanaged_devices() {
+        let temp_dir = TempDir::new().unwrap();
+        let (trust_store, permissions) = create_stores(&temp_dir);
+        let stray = DeviceIdentity::generate();
+        trust_store
+            .add_device(TrustedDevice::new(
+                *stray.device_id(),
+                "stray".to_string(),
+                stray.public_key_bytes(),
+            ))
+            .unwrap();
+
+        let mut file = ProvisioningFile::default();
+        let report = file.reconcile(&trust_store, &permissions).unwrap();
+        assert!(!report.is_clean());
+        assert!(!report.changed());
+        assert_eq!(trust_store.len().unwrap(), 1);
+
+        file.prune = true;
+        let report = file.reconcile(&trust_store, &permissions).unwrap();
+        assert!(report.changed());
+        assert!(trust_store.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_reconcile_invalid_entry_changes_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let (trust_store, permissions) = create_stores(&temp_dir);
+        let good = DeviceIdentity::generate();
+        let mut bad = provisioned(&DeviceIdentity::generate(), "bad");
+        bad.device_id = good.fingerprint();
+
+        let file = ProvisioningFile {
+            prune: false,
+            devices: vec![provisioned(&good, "good"), bad],
+        };
+        assert!(file.reconcile(&trust_store, &permissions).is_err());
+        assert!(trust_store.is_empty().unwrap());
+        assert!(permissions.list_devices().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+//! One-time re-invite tokens for revoked devices.
+//!
+//! A revoked device is normally rejected with `retry_allowed: false`. An
+//! operator can issue a re-invite token with `remoshell devices reinvite`;
+//! a client that presents the token in its `DeviceApprovalRequest` is
+//! re-approved once. Only a SHA-256 hash of each token is stored, in
+//! `reinvites.json` next to the trust store, so the CLI and a running daemon
+//! share tokens through the filesystem. Redeeming a token happens under the
+//! store's lock, so two connections presenting the same token cannot both
+//! be accepted.
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use protocol::DeviceId;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::util::file_store::JsonFileStore;
+
+/// File name of the re-invite store, placed next to the trust store.
+pub const REINVITE_FILE_NAME: &str = "reinvites.json";
+
+/// Default lifetime of a re-invite token (24 hours).
+pub const DEFAULT_REINVITE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Number of random bytes in a re-invite token.
+const TOKEN_BYTES: usize = 32;
+
+/// A stored re-invite for a single device.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Reinvite {
+    /// The device this re-invite applies to.
+    pub device_id: DeviceId,
+    /// Hex-encoded SHA-256 hash of the token.
+    pub token_hash: String,
+    /// When the re-invite was issued.
+    pub issued_at: SystemTime,
+    /// When the re-invite stops being valid.
+    pub expires_at: SystemTime,
+}
+
+impl Reinvite {
+    /// Returns true if the re-invite has expired.
+    pub fn is_expired(&self) -> bool {
+        SystemTime::now() >= self.expires_at
+    }
+}
+
+/// Result of presenting a re-invite token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedeemOutcome {
+    /// The token was valid and has been consumed.
+    Accepted,
+    /// No re-invite exists for the device.
+    NotIssued,
+    /// A re-invite exists but the token does not match.
+    InvalidToken,
+    /// The re-invite expired before it was used.
+    Expired,
+}
+
+/// Serialized form of the re-invite store.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ReinviteData {
+    reinvites: Vec<Reinvite>,
+}
+
+/// File-backed store of pending re-invites.
+///
+/// Every operation reads and writes the file directly so that tokens issued
+/// by the CLI are visible to a running daemon without a restart.
+#[derive(Debug, Clone)]
+pub struct ReinviteStore {
+    file: JsonFileStore<ReinviteData>,
+}
+
+impl ReinviteStore {
+    /// Creates a store backed by the given file.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            file: JsonFileStore::new(path, "re-invite store"),
+        }
+    }
+
+    /// Creates a store next to the given trust store file.
+    pub fn for_trust_store(trust_store_path: &Path) -> Self {
+        let dir = trust_store_path.parent().unwrap_or_else(|| Path::new("."));
+        Self::new(dir.join(REINVITE_FILE_NAME))
+    }
+
+    /// Returns the path to the store file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Issues a new token for a device, replacing any earlier one.
+    ///
+    /// Returns the plaintext token; it is not stored and cannot be recovered.
+    pub fn issue(&self, device_id: DeviceId, ttl: Duration) -> Result<String> {
+        let mut bytes = [0u8; TOKEN_BYTES];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+
+        let now = SystemTime::now();
+        self.file.update(|data| {
+            data.reinvites
+                .retain(|r| r.device_id != device_id && !r.is_expired());
+            data.reinvites.push(Reinvite {
+                device_id,
+                token_hash: hash_token(&token),
+                issued_at: now,
+                expires_at: now + ttl,
+            });
+        })?;
+
+        Ok(token)
+    }
+
+    /// Returns the pending re-invite for a device, if any.
+    pub fn get(&self, device_id: &DeviceId) -> Result<Option<Reinvite>> {
+        Ok(self
+            .file
+            .read()?
+            .reinvites
+            .into_iter()
+            .find(|r| &r.device_id == device_id))
+    }
+
+    /// Checks a presented token and consumes the re-invite if it is valid.
+    ///
+    /// Expired re-invites are removed whether or not the token matches.
+    pub fn redeem(&self, device_id: &DeviceId, token: &str) -> Result<RedeemOutcome> {
+        self.file.update(|data| {
+            let Some(pos) = data
+                .reinvites
+                .iter()
+                .position(|r| &r.device_id == device_id)
+            else {
+                return RedeemOutcome::NotIssued;
+            };
+
+            if data.reinvites[pos].is_expired() {
+                data.reinvites.remove(pos);
+                return RedeemOutcome::Expired;
+            }
+
+            if !constant_time_eq(
+                data.reinvites[pos].token_hash.as_bytes(),
+                hash_token(token).as_bytes(),
+            ) {
+                return RedeemOutcome::InvalidToken;
+            }
+
+            data.reinvites.remove(pos);
+            RedeemOutcome::Accepted
+        })
+    }
+}
+
+/// Returns the hex-encoded SHA-256 hash of a token.
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().as_bytes()))
+}
+
+/// Compares two byte strings without short-circuiting on the first mismatch.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::{Arc, Barrier};
+    use tempfile::TempDir;
+
+    fn device_id() -> DeviceId {
+        *protocol::DeviceIdentity::generate().device_id()
+    }
+
+    #[test]
+    fn test_issue_and_redeem() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ReinviteStore::new(temp_dir.path().join(REINVITE_FILE_NAME));
+        let id = device_id();
+
+        let token = store.issue(id, DEFAULT_REINVITE_TTL).unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+
+        // The plaintext token is never written to disk
+        let contents = fs::read_to_string(store.path()).unwrap();
+        assert!(!contents.contains(&token));
+
+        assert_eq!(store.redeem(&id, &token).unwrap(), RedeemOutcome::Accepted);
+        // Tokens are single use
+        assert_eq!(store.redeem(&id, &token).unwrap(), RedeemOutcome::NotIssued);
+    }
+
+    #[test]
+    fn test_redeem_wrong_token() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ReinviteStore::new(temp_dir.path().join(REINVITE_FILE_NAME));
+        let id = device_id();
+
+        let token = store.issue(id, DEFAULT_REINVITE_TTL).unwrap();
+        assert_eq!(
+            store.redeem(&id, "not-the-token").unwrap(),
+            RedeemOutcome::InvalidToken
+        );
+        // A failed attempt does not consume the re-invite
+        assert_eq!(store.redeem(&id, &token).unwrap(), RedeemOutcome::Accepted);
+    }
+
+    #[test]
+    fn test_redeem_other_device() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ReinviteStore::new(temp_dir.path().join(REINVITE_FILE_NAME));
+
+        let token = store.issue(device_id(), DEFAULT_REINVITE_TTL).unwrap();
+        assert_eq!(
+            store.redeem(&device_id(), &token).unwrap(),
+            RedeemOutcome::NotIssued
+        );
+    }
+
+    #[test]
+    fn test_redeem_expired() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ReinviteStore::new(temp_dir.path().join(REINVITE_FILE_NAME));
+        let id = device_id();
+
+        let token = store.issue(id, Duration::ZERO).unwrap();
+        assert_eq!(store.redeem(&id, &token).unwrap(), RedeemOutcome::Expired);
+        assert!(store.get(&id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reissue_replaces_previous_token() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ReinviteStore::new(temp_dir.path().join(REINVITE_FILE_NAME));
+        let id = device_id();
+
+        let first = store.issue(id, DEFAULT_REINVITE_TTL).unwrap();
+        let second = store.issue(id, DEFAULT_REINVITE_TTL).unwrap();
+
+        assert_eq!(
+            store.redeem(&id, &first).unwrap(),
+            RedeemOutcome::InvalidToken
+        );
+        assert_eq!(store.redeem(&id, &second).unwrap(), RedeemOutcome::Accepted);
+    }
+
+    #[test]
+    fn test_concurrent_redeem_accepts_one() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(REINVITE_FILE_NAME);
+        let id = device_id();
+        let token = ReinviteStore::new(&path)
+            .issue(id, DEFAULT_REINVITE_TTL)
+            .unwrap();
+
+        let threads = 8;
+        let barrier = Arc::new(Barrier::new(threads));
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let store = ReinviteStore::new(&path);
+                let barrier = Arc::clone(&barrier);
+                let token = token.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    store.redeem(&id, &token).unwrap()
+                })
+            })
+            .collect();
+        let outcomes: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let accepted = outcomes
+            .iter()
+            .filter(|o| **o == RedeemOutcome::Accepted)
+            .count();
+        assert_eq!(accepted, 1, "outcomes: {:?}", outcomes);
+    }
+
+    #[test]
+    fn test_for_trust_store_path() {
+        let store =
+            ReinviteStore::for_trust_store(Path::new("/etc/remoshell/trusted_devices.json"));
+        assert_eq!(store.path(), Path::new("/etc/remoshell/reinvites.json"));
+    }
+}
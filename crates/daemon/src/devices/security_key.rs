@@ -0,0 +1,591 @@
+//! Security key (FIDO2) gating of high-privilege actions.
+//!
+//! When `security.fido2` is enabled, the router answers a gated request with
+//! a `SecurityKeyChallenge` instead of performing it. The client signs the
+//! challenge with a registered security key (a WebAuthn assertion, which the
+//! authenticator produces over CTAP2) and sends a `SecurityKeyAssertion`;
+//! once it verifies, the client repeats the original request.
+//!
+//! Two actions can be gated:
+//! - a device's first session of the (UTC) day
+//! - file writes under protected paths, allowed for `assertion_ttl` seconds
+//!   after each assertion
+//!
+//! Credentials are registered in the configuration as a base64url
+//! credential ID and a base64 SEC1-encoded P-256 public key (ES256).
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use protocol::messages::{SecurityKeyAssertion, SecurityKeyChallenge, SecurityKeyVerified};
+use protocol::DeviceId;
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::config::Fido2Config;
+
+/// How long a challenge may be answered, in seconds.
+const CHALLENGE_TTL: u64 = 60;
+
+/// Number of random bytes in a challenge.
+const CHALLENGE_BYTES: usize = 32;
+
+/// Seconds in a day, used to detect the first session of the day.
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Minimum length of authenticator data: RP ID hash, flags and counter.
+const AUTHENTICATOR_DATA_MIN_LEN: usize = 37;
+
+/// Authenticator data flag: user present.
+const FLAG_USER_PRESENT: u8 = 0x01;
+
+/// Authenticator data flag: user verified.
+const FLAG_USER_VERIFIED: u8 = 0x04;
+
+/// An action that can require a security key assertion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GatedAction {
+    /// Creating a shell session.
+    SessionCreate,
+    /// Writing a file.
+    FileWrite,
+}
+
+impl GatedAction {
+    /// Returns the name of the action used in challenges.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GatedAction::SessionCreate => "session_create",
+            GatedAction::FileWrite => "file_write",
+        }
+    }
+}
+
+/// Errors from verifying a security key assertion.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SecurityKeyError {
+    #[error("unknown or already answered challenge")]
+    UnknownChallenge,
+
+    #[error("challenge has expired")]
+    ChallengeExpired,
+
+    #[error("challenge was issued to another device")]
+    WrongDevice,
+
+    #[error("credential is not registered")]
+    UnknownCredential,
+
+    #[error("invalid client data: {0}")]
+    InvalidClientData(String),
+
+    #[error("invalid authenticator data: {0}")]
+    InvalidAuthenticatorData(String),
+
+    #[error("user presence was not confirmed")]
+    UserNotPresent,
+
+    #[error("user verification is required")]
+    UserNotVerified,
+
+    #[error("signature counter did not increase; the security key may be cloned")]
+    CounterRegression,
+
+    #[error("invalid assertion signature")]
+    InvalidSignature,
+}
+
+/// A registered security key credential.
+#[derive(Debug, Clone)]
+struct Credential {
+    name: String,
+    id: Vec<u8>,
+    public_key: VerifyingKey,
+}
+
+/// A challenge waiting for an assertion.
+#[derive(Debug, Clone)]
+struct PendingChallenge {
+    device_id: DeviceId,
+    challenge: Vec<u8>,
+    expires_at: u64,
+}
+
+/// The subset of WebAuthn client data that is checked.
+#[derive(Debug, Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+}
+
+/// Enforces the security key policy and verifies assertions.
+#[derive(Debug)]
+pub struct SecurityKeyGate {
+    rp_id: String,
+    require_for_first_session: bool,
+    protected_paths: Vec<PathBuf>,
+    assertion_ttl: u64,
+    user_verification: bool,
+    credentials: Vec<Credential>,
+    pending: Mutex<HashMap<String, PendingChallenge>>,
+    /// Time of each device's most recent accepted assertion.
+    verified: Mutex<HashMap<DeviceId, u64>>,
+    /// Highest signature counter seen for each credential.
+    counters: Mutex<HashMap<Vec<u8>, u32>>,
+}
+
+impl SecurityKeyGate {
+    /// Creates a gate from the `security.fido2` configuration.
+    pub fn from_config(config: &Fido2Config) -> Result<Self> {
+        let credentials = config
+            .credentials
+            .iter()
+            .map(|c| {
+                let id = URL_SAFE_NO_PAD
+                    .decode(c.id.trim_end_matches('='))
+                    .with_context(|| format!("Invalid ID for security key {:?}", c.name))?;
+                let key_bytes = STANDARD
+                    .decode(&c.public_key)
+                    .with_context(|| format!("Invalid public key for security key {:?}", c.name))?;
+                let public_key = VerifyingKey::from_sec1_bytes(&key_bytes).map_err(|_| {
+                    anyhow::anyhow!("Security key {:?} is not a P-256 public key", c.name)
+                })?;
+                Ok(Credential {
+                    name: c.name.clone(),
+                    id,
+                    public_key,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if credentials.is_empty() {
+            anyhow::bail!("security.fido2 is enabled but no credentials are registered");
+        }
+
+        Ok(Self {
+            rp_id: config.rp_id.clone(),
+            require_for_first_session: config.require_for_first_session,
+            protected_paths: config
+                .protected_paths
+                .iter()
+                .map(|p| resolve_path(p))
+                .collect(),
+            assertion_ttl: config.assertion_ttl,
+            user_verification: config.user_verification,
+            credentials,
+            pending: Mutex::new(HashMap::new()),
+            verified: Mutex::new(HashMap::new()),
+            counters: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns true if `path` is under one of the protected paths.
+    pub fn is_protected(&self, path: &Path) -> bool {
+        let path = resolve_path(path);
+        self.protected_paths.iter().any(|p| path.starts_with(p))
+    }
+
+    /// Returns true if the device must present an assertion before the
+    /// action is performed.
+    ///
+    /// `path` is the target of a file write.
+    pub fn requires_assertion(
+        &self,
+        device_id: &DeviceId,
+        action: GatedAction,
+        path: Option<&Path>,
+        now: u64,
+    ) -> bool {
+        let last = self
+            .verified
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(device_id)
+            .copied();
+
+        match action {
+            GatedAction::SessionCreate => {
+                self.require_for_first_session
+                    && last.is_none_or(|t| t / SECONDS_PER_DAY != now / SECONDS_PER_DAY)
+            }
+            GatedAction::FileWrite => {
+                path.is_some_and(|p| self.is_protected(p))
+                    && last.is_none_or(|t| now >= t.saturating_add(self.assertion_ttl))
+            }
+        }
+    }
+
+    /// Issues a challenge for a gated action.
+    pub fn issue_challenge(
+        &self,
+        device_id: &DeviceId,
+        action: GatedAction,
+        now: u64,
+    ) -> SecurityKeyChallenge {
+        let mut challenge = vec![0u8; CHALLENGE_BYTES];
+        rand::thread_rng().fill_bytes(&mut challenge);
+        let challenge_id = uuid::Uuid::new_v4().to_string();
+        let expires_at = now + CHALLENGE_TTL;
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, p| p.expires_at > now);
+        pending.insert(
+            challenge_id.clone(),
+            PendingChallenge {
+                device_id: *device_id,
+                challenge: challenge.clone(),
+                expires_at,
+            },
+        );
+
+        SecurityKeyChallenge {
+            challenge_id,
+            challenge,
+            rp_id: self.rp_id.clone(),
+            allowed_credentials: self
+                .credentials
+                .iter()
+                .map(|c| URL_SAFE_NO_PAD.encode(&c.id))
+                .collect(),
+            action: action.as_str().to_string(),
+            user_verification: self.user_verification,
+            expires_at,
+        }
+    }
+
+    /// Verifies an assertion from a device.
+    ///
+    /// A challenge can be answered once, whether or not the assertion is
+    /// valid.
+    pub fn verify(
+        &self,
+        device_id: &DeviceId,
+        assertion: &SecurityKeyAssertion,
+        now: u64,
+    ) -> Result<SecurityKeyVerified, SecurityKeyError> {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&assertion.challenge_id)
+            .ok_or(SecurityKeyError::UnknownChallenge)?;
+        if pending.device_id != *device_id {
+            return Err(SecurityKeyError::WrongDevice);
+        }
+        if now >= pending.expires_at {
+            return Err(SecurityKeyError::ChallengeExpired);
+        }
+
+        let credential = self
+            .credentials
+            .iter()
+            .find(|c| c.id == assertion.credential_id)
+            .ok_or(SecurityKeyError::UnknownCredential)?;
+
+        let client_data: ClientData = serde_json::from_slice(&assertion.client_data_json)
+            .map_err(|e| SecurityKeyError::InvalidClientData(e.to_string()))?;
+        if client_data.kind != "webauthn.get" {
+            return Err(SecurityKeyError::InvalidClientData(format!(
+                "unexpected type {:?}",
+                client_data.kind
+            )));
+        }
+        if client_data.challenge.trim_end_matches('=') != URL_SAFE_NO_PAD.encode(&pending.challenge)
+        {
+            return Err(SecurityKeyError::InvalidClientData(
+                "challenge does not match".to_string(),
+            ));
+        }
+
+        let auth_data = &assertion.authenticator_data;
+        if auth_data.len() < AUTHENTICATOR_DATA_MIN_LEN {
+            return Err(SecurityKeyError::InvalidAuthenticatorData(
+                "too short".to_string(),
+            ));
+        }
+        if auth_data[..32] != Sha256::digest(self.rp_id.as_bytes())[..] {
+            return Err(SecurityKeyError::InvalidAuthenticatorData(
+                "relying party ID does not match".to_string(),
+            ));
+        }
+        let flags = auth_data[32];
+        if flags & FLAG_USER_PRESENT == 0 {
+            return Err(SecurityKeyError::UserNotPresent);
+        }
+        if self.user_verification && flags & FLAG_USER_VERIFIED == 0 {
+            return Err(SecurityKeyError::UserNotVerified);
+        }
+
+        let signature = Signature::from_der(&assertion.signature)
+            .map_err(|_| SecurityKeyError::InvalidSignature)?;
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&assertion.client_data_json));
+        credential
+            .public_key
+            .verify(&signed, &signature)
+            .map_err(|_| SecurityKeyError::InvalidSignature)?;
+
+        // Authenticators that keep a counter must increase it on every
+        // assertion; a counter of zero means the authenticator has none
+        let counter =
+            u32::from_be_bytes([auth_data[33], auth_data[34], auth_data[35], auth_data[36]]);
+        {
+            let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+            let previous = counters.get(&credential.id).copied().unwrap_or(0);
+            if counter != 0 || previous != 0 {
+                if counter <= previous {
+                    return Err(SecurityKeyError::CounterRegression);
+                }
+                counters.insert(credential.id.clone(), counter);
+            }
+        }
+
+        self.verified
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(*device_id, now);
+        tracing::info!(
+            target: "audit",
+            device_id = %device_id,
+            credential = %credential.name,
+            "Security key assertion accepted"
+        );
+
+        Ok(SecurityKeyVerified {
+            challenge_id: assertion.challenge_id.clone(),
+            valid_until: now + self.assertion_ttl,
+        })
+    }
+}
+
+/// Resolves `..` and `.` components and symlinks in the existing part of a
+/// path, so a protected path cannot be reached through another name.
+fn resolve_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+
+    // Canonicalize the longest existing ancestor
+    let mut existing = normalized.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest.iter().rev().fold(canonical, |acc, c| acc.join(c));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Fido2Credential;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::SigningKey;
+
+    const RP_ID: &str = "remoshell";
+    const CREDENTIAL_ID: &[u8] = b"credential-1";
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([1u8; 16])
+    }
+
+    fn gate(key: &SigningKey, protected: &Path) -> SecurityKeyGate {
+        let public_key = key.verifying_key().to_encoded_point(false);
+        SecurityKeyGate::from_config(&Fido2Config {
+            enabled: true,
+            rp_id: RP_ID.to_string(),
+            require_for_first_session: true,
+            protected_paths: vec![protected.to_path_buf()],
+            assertion_ttl: 300,
+            user_verification: false,
+            credentials: vec![Fido2Credential {
+                name: "test key".to_string(),
+                id: URL_SAFE_NO_PAD.encode(CREDENTIAL_ID),
+                public_key: STANDARD.encode(public_key.as_bytes()),
+            }],
+        })
+        .unwrap()
+    }
+
+    /// Produces an assertion the way an authenticator would.
+    fn assert_challenge(
+        key: &SigningKey,
+        challenge: &SecurityKeyChallenge,
+        counter: u32,
+    ) -> SecurityKeyAssertion {
+        let client_data_json = format!(
+            r#"{{"type":"webauthn.get","challenge":"{}","origin":"tauri://localhost"}}"#,
+            URL_SAFE_NO_PAD.encode(&challenge.challenge)
+        )
+        .into_bytes();
+
+        let mut authenticator_data = Sha256::digest(challenge.rp_id.as_bytes()).to_vec();
+        authenticator_data.push(FLAG_USER_PRESENT);
+        authenticator_data.extend_from_slice(&counter.to_be_bytes());
+
+        let mut signed = authenticator_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data_json));
+        let signature: Signature = key.sign(&signed);
+
+        SecurityKeyAssertion {
+            challenge_id: challenge.challenge_id.clone(),
+            credential_id: CREDENTIAL_ID.to_vec(),
+            authenticator_data,
+            client_data_json,
+            signature: signature.to_der().as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_first_session_of_day_requires_assertion() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let gate = gate(&key, temp_dir.path());
+        let now = 10 * SECONDS_PER_DAY + 100;
+
+        assert!(gate.requires_assertion(&device(), GatedAction::SessionCreate, None, now));
+
+        let challenge = gate.issue_challenge(&device(), GatedAction::SessionCreate, now);
+        assert_eq!(challenge.action, "session_create");
+        let verified = gate
+            .verify(&device(), &assert_challenge(&key, &challenge, 1), now)
+            .unwrap();
+        assert_eq!(verified.valid_until, now + 300);
+
+        // Later the same day no assertion is needed, the next day it is
+        assert!(!gate.requires_assertion(&device(), GatedAction::SessionCreate, None, now + 3600));
+        assert!(gate.requires_assertion(
+            &device(),
+            GatedAction::SessionCreate,
+            None,
+            now + SECONDS_PER_DAY
+        ));
+    }
+
+    #[test]
+    fn test_protected_writes() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let protected = temp_dir.path().join("protected");
+        std::fs::create_dir(&protected).unwrap();
+        let gate = gate(&key, &protected);
+        let now = 1_000_000;
+
+        let inside = protected.join("file.txt");
+        let sneaky = temp_dir.path().join("other/../protected/file.txt");
+        let outside = temp_dir.path().join("file.txt");
+        assert!(gate.requires_assertion(&device(), GatedAction::FileWrite, Some(&inside), now));
+        assert!(gate.requires_assertion(&device(), GatedAction::FileWrite, Some(&sneaky), now));
+        assert!(!gate.requires_assertion(&device(), GatedAction::FileWrite, Some(&outside), now));
+
+        let challenge = gate.issue_challenge(&device(), GatedAction::FileWrite, now);
+        gate.verify(&device(), &assert_challenge(&key, &challenge, 0), now)
+            .unwrap();
+        assert!(!gate.requires_assertion(
+            &device(),
+            GatedAction::FileWrite,
+            Some(&inside),
+            now + 299
+        ));
+        assert!(gate.requires_assertion(
+            &device(),
+            GatedAction::FileWrite,
+            Some(&inside),
+            now + 300
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_assertions() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let gate = gate(&key, temp_dir.path());
+        let now = 1_000_000;
+
+        // Signed by an unregistered key
+        let other = SigningKey::random(&mut rand::rngs::OsRng);
+        let challenge = gate.issue_challenge(&device(), GatedAction::SessionCreate, now);
+        assert_eq!(
+            gate.verify(&device(), &assert_challenge(&other, &challenge, 1), now),
+            Err(SecurityKeyError::InvalidSignature)
+        );
+
+        // Challenges are single use
+        assert_eq!(
+            gate.verify(&device(), &assert_challenge(&key, &challenge, 1), now),
+            Err(SecurityKeyError::UnknownChallenge)
+        );
+
+        // Answered by another device
+        let challenge = gate.issue_challenge(&device(), GatedAction::SessionCreate, now);
+        let other_device = DeviceId::from_bytes([2u8; 16]);
+        assert_eq!(
+            gate.verify(&other_device, &assert_challenge(&key, &challenge, 1), now),
+            Err(SecurityKeyError::WrongDevice)
+        );
+
+        // Answered too late
+        let challenge = gate.issue_challenge(&device(), GatedAction::SessionCreate, now);
+        assert_eq!(
+            gate.verify(
+                &device(),
+                &assert_challenge(&key, &challenge, 1),
+                now + CHALLENGE_TTL
+            ),
+            Err(SecurityKeyError::ChallengeExpired)
+        );
+
+        // Signature over a different challenge
+        let challenge = gate.issue_challenge(&device(), GatedAction::SessionCreate, now);
+        let mut stale = challenge.clone();
+        stale.challenge = vec![0u8; CHALLENGE_BYTES];
+        let mut assertion = assert_challenge(&key, &stale, 1);
+        assertion.challenge_id = challenge.challenge_id.clone();
+        assert!(matches!(
+            gate.verify(&device(), &assertion, now),
+            Err(SecurityKeyError::InvalidClientData(_))
+        ));
+
+        // Counter going backwards
+        let challenge = gate.issue_challenge(&device(), GatedAction::SessionCreate, now);
+        gate.verify(&device(), &assert_challenge(&key, &challenge, 5), now)
+            .unwrap();
+        let challenge = gate.issue_challenge(&device(), GatedAction::SessionCreate, now);
+        assert_eq!(
+            gate.verify(&device(), &assert_challenge(&key, &challenge, 5), now),
+            Err(SecurityKeyError::CounterRegression)
+        );
+    }
+
+    #[test]
+    fn test_requires_credentials() {
+        assert!(SecurityKeyGate::from_config(&Fido2Config {
+            enabled: true,
+            ..Fido2Config::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Operator confirmation of new sessions.
+//!
+//! When `security.session_confirmation` is enabled, trust alone is not
+//! enough to open a shell: every `SessionCreate` waits until the operator
+//! allows it in the TUI, at the `start --plain` prompt or with
+//! `remoshell sessions allow`. A request nobody answers within the timeout
+//! is denied.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use protocol::DeviceId;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+/// What became of a session waiting for confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmOutcome {
+    /// The operator allowed the session.
+    Allowed,
+    /// The operator denied the session.
+    Denied,
+    /// Nobody answered before the timeout.
+    TimedOut,
+}
+
+/// A session waiting for the operator's answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingSession {
+    /// Number identifying the request, unique for the daemon's lifetime.
+    pub id: u64,
+    /// The device asking for a session.
+    pub device_id: DeviceId,
+    /// The device's name in the trust store.
+    pub device_name: String,
+    /// Seconds until the request is denied.
+    pub expires_in_secs: u64,
+}
+
+struct Waiting {
+    device_id: DeviceId,
+    device_name: String,
+    deadline: Instant,
+    answer: oneshot::Sender<bool>,
+}
+
+/// Sessions waiting for the operator's confirmation.
+pub struct SessionConfirmations {
+    timeout: Duration,
+    next_id: AtomicU64,
+    waiting: Mutex<BTreeMap<u64, Waiting>>,
+}
+
+impl SessionConfirmations {
+    /// Creates an empty queue whose requests are denied after `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            next_id: AtomicU64::new(1),
+            waiting: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns how long a request waits for an answer.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Waits until the operator answers a session request from a device, or
+    /// the timeout passes.
+    pub async fn confirm(&self, device_id: DeviceId, device_name: String) -> ConfirmOutcome {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (answer, answered) = oneshot::channel();
+        self.waiting.lock().unwrap().insert(
+            id,
+            Waiting {
+                device_id,
+                device_name,
+                deadline: Instant::now() + self.timeout,
+                answer,
+            },
+        );
+
+        // Also drops the request if the device goes away while it waits
+        let _remove = RemoveOnDrop { queue: self, id };
+        match tokio::time::timeout(self.timeout, answered).await {
+            Ok(Ok(true)) => ConfirmOutcome::Allowed,
+            Ok(Ok(false)) => ConfirmOutcome::Denied,
+            // The sender is only dropped along with the queue
+            Ok(Err(_)) => ConfirmOutcome::Denied,
+            Err(_) => ConfirmOutcome::TimedOut,
+        }
+    }
+
+    /// Answers a pending request. Returns `false` if there is no such
+    /// request, e.g. because it timed out.
+    pub fn answer(&self, id: u64, allow: bool) -> bool {
+        match self.waiting.lock().unwrap().remove(&id) {
+            Some(waiting) => waiting.answer.send(allow).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Denies a device's pending requests, e.g. when it disconnects.
+    pub fn cancel_device(&self, device_id: &DeviceId) {
+        self.waiting
+            .lock()
+            .unwrap()
+            .retain(|_, waiting| waiting.device_id != *device_id);
+    }
+
+    /// Lists the pending requests, oldest first.
+    pub fn pending(&self) -> Vec<PendingSession> {
+        let now = Instant::now();
+        self.waiting
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, waiting)| PendingSession {
+                id: *id,
+                device_id: waiting.device_id,
+                device_name: waiting.device_name.clone(),
+                expires_in_secs: waiting.deadline.saturating_duration_since(now).as_secs(),
+            })
+            .collect()
+    }
+}
+
+/// Removes a request from the queue when its waiter finishes.
+struct RemoveOnDrop<'a> {
+    queue: &'a SessionConfirmations,
+    id: u64,
+}
+
+impl Drop for RemoveOnDrop<'_> {
+    fn drop(&mut self) {
+        self.queue.waiting.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn device_id() -> DeviceId {
+        *protocol::DeviceIdentity::generate().device_id()
+    }
+
+    /// Waits until the queue holds a request and returns it.
+    async fn next_pending(confirmations: &SessionConfirmations) -> PendingSession {
+        loop {
+            if let Some(pending) = confirmations.pending().into_iter().next() {
+                return pending;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_answered_requests() {
+        let confirmations = Arc::new(SessionConfirmations::new(Duration::from_secs(30)));
+        let device = device_id();
+
+        for allow in [true, false] {
+            let waiter = tokio::spawn({
+                let confirmations = Arc::clone(&confirmations);
+                async move { confirmations.confirm(device, "Phone".to_string()).await }
+            });
+            let pending = next_pending(&confirmations).await;
+            assert_eq!(pending.device_id, device);
+            assert_eq!(pending.device_name, "Phone");
+            assert!(pending.expires_in_secs <= 30);
+
+            assert!(confirmations.answer(pending.id, allow));
+            let expected = if allow {
+                ConfirmOutcome::Allowed
+            } else {
+                ConfirmOutcome::Denied
+            };
+            assert_eq!(waiter.await.unwrap(), expected);
+            assert!(confirmations.pending().is_empty());
+            // A request is answered once
+            assert!(!confirmations.answer(pending.id, true));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_request_is_removed() {
+        let confirmations = Arc::new(SessionConfirmations::new(Duration::from_secs(30)));
+        let waiter = tokio::spawn({
+            let confirmations = Arc::clone(&confirmations);
+            async move {
+                confirmations
+                    .confirm(device_id(), "Phone".to_string())
+                    .await
+            }
+        });
+        let pending = next_pending(&confirmations).await;
+
+        waiter.abort();
+        let _ = waiter.await;
+        assert!(confirmations.pending().is_empty());
+        assert!(!confirmations.answer(pending.id, true));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_device_is_denied() {
+        let confirmations = Arc::new(SessionConfirmations::new(Duration::from_secs(30)));
+        let device = device_id();
+        let waiter = tokio::spawn({
+            let confirmations = Arc::clone(&confirmations);
+            async move { confirmations.confirm(device, "Phone".to_string()).await }
+        });
+        next_pending(&confirmations).await;
+
+        // Other devices keep their requests
+        confirmations.cancel_device(&device_id());
+        assert_eq!(confirmations.pending().len(), 1);
+
+        confirmations.cancel_device(&device);
+        assert_eq!(waiter.await.unwrap(), ConfirmOutcome::Denied);
+        assert!(confirmations.pending().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unanswered_request_times_out() {
+        let confirmations = SessionConfirmations::new(Duration::from_secs(30));
+        assert_eq!(
+            confirmations
+                .confirm(device_id(), "Phone".to_string())
+                .await,
+            ConfirmOutcome::TimedOut
+        );
+        assert!(confirmations.pending().is_empty());
+        assert!(!confirmations.answer(1, true));
+    }
+}
@@ -0,0 +1,262 @@
+//! Cached trust checks for routed messages.
+//!
+//! Every message a device sends is checked against the trust store, which
+//! copies the whole device entry for each lookup. A [`TrustCache`] keeps just
+//! what the check needs, per device, and reads through to the store on a
+//! miss. Entries are tagged with the store's [generation], so any change to
+//! the store, including a reload from disk, invalidates them.
+//!
+//! [generation]: TrustStore::generation
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use anyhow::Result;
+use protocol::DeviceId;
+
+use super::trust_store::{TrustLevel, TrustStore, TrustedDevice, FILES_ACCESS, SHELL_ACCESS};
+use crate::authz::Role;
+
+/// What a trust check needs to know about a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceTrust {
+    /// The device's trust level.
+    pub trust_level: TrustLevel,
+    /// When the device's temporary grant expires, if it has one.
+    pub grant_expires_at: Option<SystemTime>,
+    /// The device's management role, if it has one.
+    pub role: Option<Role>,
+    /// The base rights the device's temporary grant covers, if it has one.
+    pub grant_rights: Option<GrantRights>,
+}
+
+/// The base rights a temporary grant covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrantRights {
+    /// Whether the grant includes [`SHELL_ACCESS`].
+    pub shell: bool,
+    /// Whether the grant includes [`FILES_ACCESS`].
+    pub files: bool,
+}
+
+impl DeviceTrust {
+    /// Returns true if the device is trusted at `now`, as
+    /// [`TrustedDevice::is_trusted_at`] does.
+    pub fn is_trusted_at(&self, now: SystemTime) -> bool {
+        self.trust_level == TrustLevel::Trusted
+            && self
+                .grant_expires_at
+                .is_none_or(|expires_at| now < expires_at)
+    }
+
+    /// Returns true if the device may use a base right, as
+    /// [`TrustedDevice::has_access`] does.
+    pub fn has_access(&self, access: &str) -> bool {
+        self.grant_rights.is_none_or(|rights| match access {
+            SHELL_ACCESS => rights.shell,
+            FILES_ACCESS => rights.files,
+            _ => false,
+        })
+    }
+}
+
+impl From<&TrustedDevice> for DeviceTrust {
+    fn from(device: &TrustedDevice) -> Self {
+        Self {
+            trust_level: device.trust_level,
+            grant_expires_at: device.grant.as_ref().map(|grant| grant.expires_at),
+            role: device.role,
+            grant_rights: device.grant.as_ref().map(|_| GrantRights {
+                shell: device.has_access(SHELL_ACCESS),
+                files: device.has_access(FILES_ACCESS),
+            }),
+        }
+    }
+}
+
+/// A cached lookup, with the store generation it was made at.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    generation: u64,
+    /// `None` if the device is not in the store.
+    trust: Option<DeviceTrust>,
+}
+
+/// Read-through cache of the trust of devices in a [`TrustStore`].
+pub struct TrustCache {
+    store: Arc<TrustStore>,
+    entries: RwLock<HashMap<DeviceId, Entry>>,
+}
+
+impl TrustCache {
+    /// Creates an empty cache over `store`.
+    pub fn new(store: Arc<TrustStore>) -> Self {
+        Self {
+            store,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the trust of a device, or `None` if it is not in the store.
+    pub fn get(&self, device_id: &DeviceId) -> Result<Option<DeviceTrust>> {
+        // Read before the store, so a change made during the lookup leaves
+        // the entry already outdated rather than wrongly current
+        let generation = self.store.generation();
+        if let Ok(entries) = self.entries.read() {
+            if let Some(entry) = entries.get(device_id) {
+                if entry.generation == generation {
+                    return Ok(entry.trust);
+                }
+            }
+        }
+
+        let trust = self
+            .store
+            .get_device(device_id)?
+            .as_ref()
+            .map(DeviceTrust::from);
+        if let Ok(mut entries) = self.entries.write() {
+            // Entries from older generations can never be used again
+            entries.retain(|_, entry| entry.generation == generation);
+            entries.insert(*device_id, Entry { generation, trust });
+        }
+        Ok(trust)
+    }
+
+    /// Returns the number of cached devices, including outdated entries.
+    pub fn len(&self) -> usize {
+        self.entries
+            .read()
+            .map(|entries| entries.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns true if no devices are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl std::fmt::Debug for TrustCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrustCache")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn device(seed: u8) -> TrustedDevice {
+        TrustedDevice::new(
+            DeviceId::from_bytes([seed; 16]),
+            "Phone".to_string(),
+            [seed; 32],
+        )
+    }
+
+    fn cache() -> (TempDir, Arc<TrustStore>, TrustCache) {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Arc::new(TrustStore::new(
+            temp_dir.path().join("trusted_devices.json"),
+        ));
+        let cache = TrustCache::new(store.clone());
+        (temp_dir, store, cache)
+    }
+
+    #[test]
+    fn test_get_reads_through() {
+        let (_temp_dir, store, cache) = cache();
+        let device = device(1);
+        store.add_device(device.clone()).unwrap();
+
+        let trust = cache.get(&device.device_id).unwrap().unwrap();
+        assert_eq!(trust.trust_level, TrustLevel::Trusted);
+        assert!(trust.is_trusted_at(SystemTime::now()));
+        assert_eq!(cache.len(), 1);
+
+        // Unknown devices are cached too
+        assert!(cache.get(&DeviceId::from_bytes([2; 16])).unwrap().is_none());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_mutations_invalidate() {
+        let (_temp_dir, store, cache) = cache();
+        let device = device(1);
+        assert!(cache.get(&device.device_id).unwrap().is_none());
+
+        store.add_device(device.clone()).unwrap();
+        assert!(cache.get(&device.device_id).unwrap().is_some());
+
+        store
+            .set_trust_level(&device.device_id, TrustLevel::Revoked)
+            .unwrap();
+        let trust = cache.get(&device.device_id).unwrap().unwrap();
+        assert_eq!(trust.trust_level, TrustLevel::Revoked);
+
+        let trust = cache.get(&device.device_id).unwrap().unwrap();
+        assert!(trust.has_access(FILES_ACCESS));
+
+        store
+            .grant_temporary(
+                &device.device_id,
+                Duration::from_secs(60),
+                BTreeSet::from([SHELL_ACCESS.to_string()]),
+            )
+            .unwrap();
+        let trust = cache.get(&device.device_id).unwrap().unwrap();
+        assert!(trust.is_trusted_at(SystemTime::now()));
+        assert!(trust.has_access(SHELL_ACCESS));
+        assert!(!trust.has_access(FILES_ACCESS));
+        assert!(!trust.is_trusted_at(SystemTime::now() + Duration::from_secs(120)));
+
+        store.remove_device(&device.device_id).unwrap();
+        assert!(cache.get(&device.device_id).unwrap().is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_reload_invalidates() {
+        let (_temp_dir, store, cache) = cache();
+        let device = device(1);
+        store.add_device(device.clone()).unwrap();
+        store.save().unwrap();
+        assert!(cache.get(&device.device_id).unwrap().is_some());
+
+        // Revoked on disk by another process, such as `devices revoke`
+        let other = TrustStore::new(store.path());
+        other.load().unwrap();
+        other
+            .set_trust_level(&device.device_id, TrustLevel::Revoked)
+            .unwrap();
+        other.save().unwrap();
+        assert!(cache
+            .get(&device.device_id)
+            .unwrap()
+            .unwrap()
+            .is_trusted_at(SystemTime::now()));
+
+        store.load().unwrap();
+        let trust = cache.get(&device.device_id).unwrap().unwrap();
+        assert_eq!(trust.trust_level, TrustLevel::Revoked);
+    }
+
+    #[test]
+    fn test_last_seen_keeps_entries() {
+        let (_temp_dir, store, cache) = cache();
+        let device = device(1);
+        store.add_device(device.clone()).unwrap();
+        cache.get(&device.device_id).unwrap();
+
+        let generation = store.generation();
+        store.update_last_seen(&device.device_id).unwrap();
+        assert_eq!(store.generation(), generation);
+    }
+}
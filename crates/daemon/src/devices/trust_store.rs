@@ -4,17 +4,31 @@
 //! Devices can be added, removed, and queried. The store persists to JSON
 //! at `~/.config/remoshell/trusted_devices.json`.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::net::SocketAddr;
+use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
-use std::time::{Instant, SystemTime};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockWriteGuard};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result};
-use protocol::DeviceId;
+use protocol::{DeviceId, PeerIdentity};
 use serde::{Deserialize, Serialize};
 
+use crate::authz::Role;
+
+/// File name of the trust store inside the daemon's data directory.
+pub const TRUST_STORE_FILE_NAME: &str = "trusted_devices.json";
+
+/// Right to open shell sessions. Permanently trusted devices always have it.
+pub const SHELL_ACCESS: &str = "shell";
+
+/// Right to browse and transfer files. Permanently trusted devices always
+/// have it.
+pub const FILES_ACCESS: &str = "files";
+
 /// Trust level for a device.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -33,8 +47,11 @@ pub enum TrustLevel {
 pub struct TrustedDevice {
     /// The unique device identifier.
     pub device_id: DeviceId,
-    /// Human-readable name for the device.
+    /// Human-readable name for the device, normalized for display.
     pub name: String,
+    /// The name as the device sent it, when normalization changed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<String>,
     /// The device's public key (32 bytes, Ed25519).
     #[serde(with = "public_key_serde")]
     pub public_key: [u8; 32],
@@ -44,6 +61,49 @@ pub struct TrustedDevice {
     pub first_seen: SystemTime,
     /// Timestamp when the device was last seen.
     pub last_seen: SystemTime,
+    /// Optional features granted to this device beyond shell and file access.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub capabilities: BTreeSet<String>,
+    /// Temporary access from `devices grant-once`, if that is what trusts
+    /// the device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant: Option<TemporaryGrant>,
+    /// Management role, see [`crate::authz`]. Devices without one have no
+    /// management access.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+}
+
+/// Access granted for a limited time with `devices grant-once`.
+///
+/// The grant ends when it expires or when the device first disconnects;
+/// the device then goes back to its previous trust level, and an entry
+/// that was only known is removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemporaryGrant {
+    /// When the grant expires.
+    pub expires_at: SystemTime,
+    /// Everything the grant allows: [`SHELL_ACCESS`], [`FILES_ACCESS`] and
+    /// optional capabilities. Replaces the device's own capabilities while
+    /// the grant lasts.
+    pub capabilities: BTreeSet<String>,
+    /// The trust level the device had before the grant.
+    pub previous_trust_level: TrustLevel,
+}
+
+impl TemporaryGrant {
+    /// Returns true if the grant has expired at `now`.
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Returns the seconds left at `now`.
+    pub fn remaining_secs_at(&self, now: SystemTime) -> u64 {
+        self.expires_at
+            .duration_since(now)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
 }
 
 impl TrustedDevice {
@@ -53,10 +113,14 @@ impl TrustedDevice {
         Self {
             device_id,
             name,
+            raw_name: None,
             public_key,
             trust_level: TrustLevel::Trusted,
             first_seen: now,
             last_seen: now,
+            capabilities: BTreeSet::new(),
+            grant: None,
+            role: None,
         }
     }
 
@@ -66,12 +130,48 @@ impl TrustedDevice {
         Self {
             device_id,
             name,
+            raw_name: None,
             public_key,
             trust_level: TrustLevel::Unknown,
             first_seen: now,
             last_seen: now,
+            capabilities: BTreeSet::new(),
+            grant: None,
+            role: None,
         }
     }
+
+    /// Records the name as the device sent it.
+    pub fn with_raw_name(mut self, raw_name: Option<String>) -> Self {
+        self.raw_name = raw_name;
+        self
+    }
+
+    /// Returns true if the device has been granted the given capability.
+    pub fn has_capability(&self, capability: &str) -> bool {
+        match &self.grant {
+            Some(grant) => grant.capabilities.contains(capability),
+            None => self.capabilities.contains(capability),
+        }
+    }
+
+    /// Returns true if the device may use a base right, [`SHELL_ACCESS`] or
+    /// [`FILES_ACCESS`]. Only temporary grants restrict them.
+    pub fn has_access(&self, access: &str) -> bool {
+        self.grant
+            .as_ref()
+            .is_none_or(|grant| grant.capabilities.contains(access))
+    }
+
+    /// Returns true if the device is trusted at `now`: permanently, or by a
+    /// grant that has not expired.
+    pub fn is_trusted_at(&self, now: SystemTime) -> bool {
+        self.trust_level == TrustLevel::Trusted
+            && self
+                .grant
+                .as_ref()
+                .is_none_or(|grant| !grant.is_expired_at(now))
+    }
 }
 
 /// A device pending manual approval.
@@ -83,8 +183,10 @@ impl TrustedDevice {
 pub struct PendingApproval {
     /// The unique device identifier.
     pub device_id: DeviceId,
-    /// Human-readable name for the device.
+    /// Human-readable name for the device, normalized for display.
     pub device_name: String,
+    /// The name as the device sent it, when normalization changed it.
+    pub raw_name: Option<String>,
     /// The device's public key (32 bytes, Ed25519).
     pub public_key: [u8; 32],
     /// When the approval request was created.
@@ -104,12 +206,19 @@ impl PendingApproval {
         Self {
             device_id,
             device_name,
+            raw_name: None,
             public_key,
             requested_at: Instant::now(),
             remote_addr,
         }
     }
 
+    /// Records the name as the device sent it.
+    pub fn with_raw_name(mut self, raw_name: Option<String>) -> Self {
+        self.raw_name = raw_name;
+        self
+    }
+
     /// Returns how many seconds have elapsed since the approval was requested.
     pub fn age_secs(&self) -> u64 {
         self.requested_at.elapsed().as_secs()
@@ -181,6 +290,36 @@ pub struct TrustStore {
     pending_approvals: RwLock<HashMap<DeviceId, PendingApproval>>,
     /// Whether manual approval is required for new devices.
     require_approval: bool,
+    /// Counts changes to the devices, for caches of their trust.
+    generation: AtomicU64,
+}
+
+/// Write access to the devices that counts a change when dropped.
+struct DevicesMut<'a> {
+    devices: RwLockWriteGuard<'a, HashMap<DeviceId, TrustedDevice>>,
+    generation: &'a AtomicU64,
+}
+
+impl Deref for DevicesMut<'_> {
+    type Target = HashMap<DeviceId, TrustedDevice>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.devices
+    }
+}
+
+impl DerefMut for DevicesMut<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.devices
+    }
+}
+
+impl Drop for DevicesMut<'_> {
+    fn drop(&mut self) {
+        // Counted before the lock is released, so a reader that sees the new
+        // generation also sees the change
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
 }
 
 impl TrustStore {
@@ -194,6 +333,7 @@ impl TrustStore {
             devices: RwLock::new(HashMap::new()),
             pending_approvals: RwLock::new(HashMap::new()),
             require_approval: false,
+            generation: AtomicU64::new(0),
         }
     }
 
@@ -219,6 +359,23 @@ impl TrustStore {
         &self.path
     }
 
+    /// Returns a number that changes whenever a device is added, removed or
+    /// changed, or the store is reloaded. Last-seen times do not count.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn devices_mut(&self) -> Result<DevicesMut<'_>> {
+        let devices = self
+            .devices
+            .write()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on trust store"))?;
+        Ok(DevicesMut {
+            devices,
+            generation: &self.generation,
+        })
+    }
+
     /// Loads the trust store from the JSON file.
     ///
     /// If the file does not exist, the store will be empty.
@@ -238,10 +395,7 @@ impl TrustStore {
         let data: TrustStoreData = serde_json::from_str(&contents)
             .with_context(|| format!("Failed to parse trust store: {}", self.path.display()))?;
 
-        let mut devices = self
-            .devices
-            .write()
-            .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on trust store"))?;
+        let mut devices = self.devices_mut()?;
 
         devices.clear();
         for device in data.devices {
@@ -307,10 +461,7 @@ impl TrustStore {
     /// If the device already exists, it will be updated.
     /// Does not automatically save; call `save()` after making changes.
     pub fn add_device(&self, device: TrustedDevice) -> Result<()> {
-        let mut devices = self
-            .devices
-            .write()
-            .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on trust store"))?;
+        let mut devices = self.devices_mut()?;
 
         tracing::info!(
             "Adding device {} ({}) with trust level {:?}",
@@ -323,15 +474,46 @@ impl TrustStore {
         Ok(())
     }
 
+    /// Trusts a device directly from its public key, without pairing.
+    ///
+    /// Adds the device if it is unknown; otherwise marks it trusted and
+    /// renames it. Returns the device ID and whether the store changed.
+    /// Does not automatically save; call `save()` after making changes.
+    pub fn preauthorize(&self, public_key: [u8; 32], name: &str) -> Result<(DeviceId, bool)> {
+        let peer = PeerIdentity::from_public_key_bytes(&public_key)
+            .context("Invalid device public key")?;
+        let device_id = *peer.device_id();
+
+        let mut devices = self.devices_mut()?;
+
+        let changed = match devices.get_mut(&device_id) {
+            Some(device) => {
+                let changed = device.trust_level != TrustLevel::Trusted || device.name != name;
+                device.trust_level = TrustLevel::Trusted;
+                device.name = name.to_string();
+                changed
+            }
+            None => {
+                devices.insert(
+                    device_id,
+                    TrustedDevice::new(device_id, name.to_string(), public_key),
+                );
+                true
+            }
+        };
+
+        if changed {
+            tracing::info!("Preauthorized device {} ({})", device_id, name);
+        }
+        Ok((device_id, changed))
+    }
+
     /// Removes a device from the trust store.
     ///
     /// Returns the removed device if it existed.
     /// Does not automatically save; call `save()` after making changes.
     pub fn remove_device(&self, device_id: &DeviceId) -> Result<Option<TrustedDevice>> {
-        let mut devices = self
-            .devices
-            .write()
-            .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on trust store"))?;
+        let mut devices = self.devices_mut()?;
 
         let removed = devices.remove(device_id);
         if let Some(ref device) = removed {
@@ -343,16 +525,17 @@ impl TrustStore {
     /// Checks if a device is trusted.
     ///
     /// Returns `true` only if the device exists and has `TrustLevel::Trusted`.
-    /// Revoked and unknown devices return `false`.
+    /// Revoked and unknown devices, and expired grants, return `false`.
     pub fn is_trusted(&self, device_id: &DeviceId) -> Result<bool> {
         let devices = self
             .devices
             .read()
             .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on trust store"))?;
 
+        let now = SystemTime::now();
         Ok(devices
             .get(device_id)
-            .map(|d| d.trust_level == TrustLevel::Trusted)
+            .map(|d| d.is_trusted_at(now))
             .unwrap_or(false))
     }
 
@@ -372,10 +555,7 @@ impl TrustStore {
     ///
     /// Returns an error if the device doesn't exist.
     pub fn set_trust_level(&self, device_id: &DeviceId, level: TrustLevel) -> Result<()> {
-        let mut devices = self
-            .devices
-            .write()
-            .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on trust store"))?;
+        let mut devices = self.devices_mut()?;
 
         let device = devices
             .get_mut(device_id)
@@ -393,6 +573,125 @@ impl TrustStore {
         Ok(())
     }
 
+    /// Sets or clears the management role of a device.
+    ///
+    /// Returns an error if the device doesn't exist.
+    /// Does not automatically save; call `save()` after making changes.
+    pub fn set_role(&self, device_id: &DeviceId, role: Option<Role>) -> Result<()> {
+        let mut devices = self.devices_mut()?;
+
+        let device = devices
+            .get_mut(device_id)
+            .ok_or_else(|| anyhow::anyhow!("Device {} not found in trust store", device_id))?;
+
+        tracing::info!(
+            "Changing role of device {} ({}) from {:?} to {:?}",
+            device.device_id,
+            device.name,
+            device.role,
+            role
+        );
+
+        device.role = role;
+        Ok(())
+    }
+
+    /// Trusts a known device for `duration`, with only the given
+    /// capabilities.
+    ///
+    /// Applies to unknown and revoked devices, and renews an existing grant.
+    /// Returns an error for devices that are missing or permanently trusted.
+    /// Does not automatically save; call `save()` after making changes.
+    pub fn grant_temporary(
+        &self,
+        device_id: &DeviceId,
+        duration: Duration,
+        capabilities: BTreeSet<String>,
+    ) -> Result<TrustedDevice> {
+        let mut devices = self.devices_mut()?;
+
+        let device = devices
+            .get_mut(device_id)
+            .ok_or_else(|| anyhow::anyhow!("Device {} not found in trust store", device_id))?;
+
+        let previous_trust_level = match &device.grant {
+            Some(grant) => grant.previous_trust_level,
+            None if device.trust_level == TrustLevel::Trusted => {
+                anyhow::bail!("Device {} is already trusted", device_id)
+            }
+            None => device.trust_level,
+        };
+
+        tracing::info!(
+            "Granting device {} ({}) temporary access for {:?}",
+            device.device_id,
+            device.name,
+            duration
+        );
+
+        device.trust_level = TrustLevel::Trusted;
+        device.grant = Some(TemporaryGrant {
+            expires_at: SystemTime::now() + duration,
+            capabilities,
+            previous_trust_level,
+        });
+        Ok(device.clone())
+    }
+
+    /// Ends a device's temporary grant.
+    ///
+    /// A device that was revoked before the grant is revoked again; any other
+    /// device is removed. Returns the device as it was during the grant, or
+    /// `None` if it had no grant.
+    /// Does not automatically save; call `save()` after making changes.
+    pub fn end_grant(&self, device_id: &DeviceId) -> Result<Option<TrustedDevice>> {
+        let mut devices = self.devices_mut()?;
+
+        let Some(previous) = devices
+            .get(device_id)
+            .and_then(|device| device.grant.as_ref())
+            .map(|grant| grant.previous_trust_level)
+        else {
+            return Ok(None);
+        };
+
+        let ended = if previous == TrustLevel::Revoked {
+            let device = devices.get_mut(device_id).expect("device checked above");
+            let ended = device.clone();
+            device.trust_level = TrustLevel::Revoked;
+            device.grant = None;
+            ended
+        } else {
+            devices.remove(device_id).expect("device checked above")
+        };
+
+        tracing::info!(
+            "Ended temporary access of device {} ({})",
+            ended.device_id,
+            ended.name
+        );
+        Ok(Some(ended))
+    }
+
+    /// Returns the devices whose temporary grant has expired at `now`.
+    pub fn expired_grants(&self, now: SystemTime) -> Result<Vec<DeviceId>> {
+        let devices = self
+            .devices
+            .read()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on trust store"))?;
+
+        Ok(devices
+            .values()
+            .filter(|device| {
+                device
+                    .grant
+                    .as_ref()
+                    .is_some_and(|grant| grant.is_expired_at(now))
+            })
+            .map(|device| device.device_id)
+            .collect())
+    }
+
     /// Updates the last seen timestamp for a device.
     ///
     /// Returns an error if the device doesn't exist.
@@ -499,7 +798,8 @@ impl TrustStore {
             pending_device.device_id,
             pending_device.device_name.clone(),
             pending_device.public_key,
-        );
+        )
+        .with_raw_name(pending_device.raw_name.clone());
 
         // Add to trusted devices
         self.add_device(trusted_device)?;
@@ -675,6 +975,21 @@ mod tests {
         assert_eq!(device.trust_level, restored.trust_level);
     }
 
+    #[test]
+    fn test_trusted_device_capabilities() {
+        let mut device = create_test_device("Capabilities Test");
+        assert!(!device.has_capability("agent-forwarding"));
+
+        // Devices stored before capabilities existed load with none
+        let json = serde_json::to_string(&device).unwrap();
+        assert!(!json.contains("capabilities"));
+
+        device.capabilities.insert("agent-forwarding".to_string());
+        let json = serde_json::to_string(&device).unwrap();
+        let restored: TrustedDevice = serde_json::from_str(&json).unwrap();
+        assert!(restored.has_capability("agent-forwarding"));
+    }
+
     #[test]
     fn test_trust_store_new() {
         let temp_dir = TempDir::new().unwrap();
@@ -823,6 +1138,39 @@ mod tests {
         assert!(store2.is_trusted(&device2_id).unwrap());
     }
 
+    #[test]
+    fn test_approved_device_keeps_raw_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("trusted_devices.json");
+        let identity = protocol::DeviceIdentity::generate();
+        let device_id = *identity.device_id();
+
+        let store = TrustStore::new(&path);
+        store
+            .add_pending(
+                PendingApproval::new(
+                    device_id,
+                    "phone".to_string(),
+                    identity.public_key_bytes(),
+                    None,
+                )
+                .with_raw_name(Some("phone\x1b[2J".to_string())),
+            )
+            .unwrap();
+        store.approve_pending(&device_id).unwrap();
+        store.save().unwrap();
+
+        let reloaded = TrustStore::new(&path);
+        reloaded.load().unwrap();
+        let device = reloaded.get_device(&device_id).unwrap().unwrap();
+        assert_eq!(device.name, "phone");
+        assert_eq!(device.raw_name.as_deref(), Some("phone\x1b[2J"));
+
+        // Unchanged names are not stored twice
+        let json = serde_json::to_string(&create_test_device("laptop")).unwrap();
+        assert!(!json.contains("raw_name"));
+    }
+
     #[test]
     fn test_trust_store_persistence_across_restarts() {
         let temp_dir = TempDir::new().unwrap();
@@ -935,6 +1283,138 @@ mod tests {
         assert!(names.contains(&"Device C"));
     }
 
+    #[test]
+    fn test_trust_store_preauthorize() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = create_test_store(&temp_dir);
+        let identity = protocol::DeviceIdentity::generate();
+
+        let (device_id, changed) = store
+            .preauthorize(identity.public_key_bytes(), "ci-runner")
+            .unwrap();
+        assert_eq!(&device_id, identity.device_id());
+        assert!(changed);
+        assert!(store.is_trusted(&device_id).unwrap());
+
+        // Re-running with the same input is a no-op
+        let (_, changed) = store
+            .preauthorize(identity.public_key_bytes(), "ci-runner")
+            .unwrap();
+        assert!(!changed);
+
+        // A revoked device is trusted again
+        store
+            .set_trust_level(&device_id, TrustLevel::Revoked)
+            .unwrap();
+        let (_, changed) = store
+            .preauthorize(identity.public_key_bytes(), "ci-runner")
+            .unwrap();
+        assert!(changed);
+        assert!(store.is_trusted(&device_id).unwrap());
+    }
+
+    #[test]
+    fn test_trust_store_set_role() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = create_test_store(&temp_dir);
+        let device = create_test_device("Admin Phone");
+        let device_id = device.device_id;
+
+        // Devices stored before roles existed load without one
+        let json = serde_json::to_string(&device).unwrap();
+        assert!(!json.contains("role"));
+
+        store.add_device(device).unwrap();
+        store.set_role(&device_id, Some(Role::Admin)).unwrap();
+        store.save().unwrap();
+        store.load().unwrap();
+        let stored = store.get_device(&device_id).unwrap().unwrap();
+        assert_eq!(stored.role, Some(Role::Admin));
+
+        store.set_role(&device_id, None).unwrap();
+        assert_eq!(store.get_device(&device_id).unwrap().unwrap().role, None);
+
+        let missing = create_test_device("Missing");
+        assert!(store
+            .set_role(&missing.device_id, Some(Role::Viewer))
+            .is_err());
+    }
+
+    #[test]
+    fn test_trust_store_temporary_grant() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = create_test_store(&temp_dir);
+        let identity = protocol::DeviceIdentity::generate();
+        let device_id = *identity.device_id();
+        store
+            .add_device(TrustedDevice::new_unknown(
+                device_id,
+                "Guest".to_string(),
+                identity.public_key_bytes(),
+            ))
+            .unwrap();
+
+        let granted = store
+            .grant_temporary(
+                &device_id,
+                Duration::from_secs(1800),
+                BTreeSet::from([SHELL_ACCESS.to_string()]),
+            )
+            .unwrap();
+        assert!(store.is_trusted(&device_id).unwrap());
+        assert!(granted.has_access(SHELL_ACCESS));
+        assert!(!granted.has_access(FILES_ACCESS));
+        let grant = granted.grant.as_ref().unwrap();
+        assert!(grant.remaining_secs_at(SystemTime::now()) <= 1800);
+
+        // The grant survives a reload
+        store.save().unwrap();
+        store.load().unwrap();
+        let stored = store.get_device(&device_id).unwrap().unwrap();
+        assert_eq!(stored.grant.as_ref(), Some(grant));
+
+        // Expired grants no longer trust the device
+        let later = grant.expires_at + Duration::from_secs(1);
+        assert!(!stored.is_trusted_at(later));
+        assert!(store.expired_grants(SystemTime::now()).unwrap().is_empty());
+        assert_eq!(store.expired_grants(later).unwrap(), vec![device_id]);
+
+        // Ending the grant removes a device that was only known
+        assert!(store.end_grant(&device_id).unwrap().is_some());
+        assert!(store.get_device(&device_id).unwrap().is_none());
+        assert!(store.end_grant(&device_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_trust_store_temporary_grant_of_revoked_device() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = create_test_store(&temp_dir);
+        let mut device = create_test_device("Old Laptop");
+        device.capabilities.insert("agent-forwarding".to_string());
+        let device_id = device.device_id;
+        store.add_device(device).unwrap();
+
+        // Permanently trusted devices need no grant
+        assert!(store
+            .grant_temporary(&device_id, Duration::from_secs(60), BTreeSet::new())
+            .is_err());
+
+        store
+            .set_trust_level(&device_id, TrustLevel::Revoked)
+            .unwrap();
+        let granted = store
+            .grant_temporary(&device_id, Duration::from_secs(60), BTreeSet::new())
+            .unwrap();
+        // The grant's capabilities replace the device's own
+        assert!(!granted.has_capability("agent-forwarding"));
+
+        store.end_grant(&device_id).unwrap();
+        let restored = store.get_device(&device_id).unwrap().unwrap();
+        assert_eq!(restored.trust_level, TrustLevel::Revoked);
+        assert!(restored.grant.is_none());
+        assert!(restored.has_capability("agent-forwarding"));
+    }
+
     #[test]
     fn test_trust_store_update_existing_device() {
         let temp_dir = TempDir::new().unwrap();
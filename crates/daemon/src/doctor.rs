@@ -0,0 +1,774 @@
+//! Self-checks for `remoshell doctor`.
+//!
+//! Each check inspects one thing the daemon depends on, such as the
+//! configuration file, the files in the data directory, the IPC socket
+//! location or the network services used to reach clients, and reports
+//! whether it is usable. Failed and suspicious checks carry a suggested fix.
+//! Checks only read state; the only thing they create is a probe file, which
+//! is removed straight away.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rand::RngCore;
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+use crate::config::Config;
+use crate::devices::{TrustStore, TRUST_STORE_FILE_NAME};
+use crate::ipc::{get_socket_path, is_daemon_running};
+use crate::network::{dial, ProxySettings};
+use crate::orchestrator::IDENTITY_FILE_NAME;
+
+/// How long a network check waits for an answer.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default STUN port when a server URL does not name one.
+const DEFAULT_STUN_PORT: u16 = 3478;
+
+/// Magic cookie carried in every STUN message (RFC 5389).
+const STUN_MAGIC_COOKIE: u32 = 0x2112_a442;
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    /// Everything is in order.
+    Ok,
+    /// The daemon works, but something is likely to cause trouble.
+    Warn,
+    /// The daemon will not work correctly until this is fixed.
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok => write!(f, " ok "),
+            Self::Warn => write!(f, "warn"),
+            Self::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// Result of a single check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    /// Short name of what was checked.
+    pub name: &'static str,
+    /// Outcome of the check.
+    pub status: CheckStatus,
+    /// What was found.
+    pub detail: String,
+    /// What to do about it, for warnings and failures.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Results of every check, in the order they ran.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DoctorReport {
+    /// The checks.
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Returns true if any check failed.
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == CheckStatus::Fail)
+    }
+
+    /// Renders the report for the terminal.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for check in &self.checks {
+            out.push_str(&format!(
+                "[{}] {}: {}\n",
+                check.status, check.name, check.detail
+            ));
+            if let Some(fix) = &check.fix {
+                out.push_str(&format!("       fix: {}\n", fix));
+            }
+        }
+
+        let count = |status| self.checks.iter().filter(|c| c.status == status).count();
+        out.push_str(&format!(
+            "\n{} ok, {} warnings, {} failures\n",
+            count(CheckStatus::Ok),
+            count(CheckStatus::Warn),
+            count(CheckStatus::Fail)
+        ));
+        out
+    }
+}
+
+/// Runs every check.
+///
+/// `config_path` is the configuration file the daemon would load for
+/// `profile` and `instance`.
+pub async fn run(
+    config_path: &Path,
+    profile: Option<&str>,
+    instance: Option<&str>,
+) -> DoctorReport {
+    let (config_check, config) = check_config(config_path, profile, instance);
+    let data_dir = &config.daemon.data_dir;
+    let socket_path = get_socket_path(instance);
+    let daemon_running = is_daemon_running(instance);
+
+    let mut checks = vec![
+        config_check,
+        check_data_dir(data_dir),
+        check_identity(&data_dir.join(IDENTITY_FILE_NAME)),
+        check_trust_store(&data_dir.join(TRUST_STORE_FILE_NAME)),
+        check_socket_path(&socket_path, daemon_running),
+        check_systemd(),
+    ];
+    // Signaling and STUN are only used for WebRTC
+    if config.network.webrtc {
+        checks.push(
+            check_signaling(
+                &config.network.signaling_url,
+                &ProxySettings::from_config(&config.network.proxy),
+            )
+            .await,
+        );
+        checks.extend(check_stun_servers(&config.network.stun_servers).await);
+    }
+
+    DoctorReport { checks }
+}
+
+/// Loads and validates the configuration.
+///
+/// Returns the configuration the remaining checks should use: the loaded
+/// one when it parses, and the defaults otherwise.
+pub fn check_config(path: &Path, profile: Option<&str>, instance: Option<&str>) -> (Check, Config) {
+    const NAME: &str = "config";
+
+    let mut config = match Config::load_scoped(path, profile, instance) {
+        Ok(config) => config,
+        Err(e) => {
+            return (
+                Check::fail(
+                    NAME,
+                    format!("{:#}", e),
+                    format!(
+                        "Fix the TOML in {} or move it aside to use defaults",
+                        path.display()
+                    ),
+                ),
+                Config::default(),
+            );
+        }
+    };
+    config.apply_env_overrides();
+
+    let check = match config.validate() {
+        Ok(()) if path.exists() => Check::ok(NAME, format!("{} is valid", path.display())),
+        Ok(()) => Check::ok(
+            NAME,
+            format!("{} not found, using defaults", path.display()),
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            e.to_string(),
+            format!(
+                "Correct the value in {} (or the REMOSHELL_* environment variable overriding it)",
+                path.display()
+            ),
+        ),
+    };
+    (check, config)
+}
+
+/// Checks that the data directory is private and writable.
+pub fn check_data_dir(dir: &Path) -> Check {
+    const NAME: &str = "data directory";
+
+    let metadata = match fs::metadata(dir) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return match writable_ancestor(dir) {
+                Ok(()) => Check::ok(
+                    NAME,
+                    format!("{} will be created on first start", dir.display()),
+                ),
+                Err(e) => Check::fail(
+                    NAME,
+                    format!(
+                        "{} does not exist and cannot be created: {}",
+                        dir.display(),
+                        e
+                    ),
+                    format!(
+                        "mkdir -m 700 -p {} as a user that can write there, or set daemon.data_dir",
+                        dir.display()
+                    ),
+                ),
+            };
+        }
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("cannot read {}: {}", dir.display(), e),
+                format!("Check the permissions of {} and its parents", dir.display()),
+            );
+        }
+    };
+
+    if !metadata.is_dir() {
+        return Check::fail(
+            NAME,
+            format!("{} is not a directory", dir.display()),
+            "Move the file aside or set daemon.data_dir to a directory",
+        );
+    }
+
+    if let Err(e) = probe_write(dir) {
+        return Check::fail(
+            NAME,
+            format!("{} is not writable: {}", dir.display(), e),
+            format!(
+                "chown $USER {} && chmod 700 {}",
+                dir.display(),
+                dir.display()
+            ),
+        );
+    }
+
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o022 != 0 {
+        Check::fail(
+            NAME,
+            format!(
+                "{} is writable by other users (mode {:o})",
+                dir.display(),
+                mode
+            ),
+            format!("chmod 700 {}", dir.display()),
+        )
+    } else if mode & 0o077 != 0 {
+        Check::warn(
+            NAME,
+            format!(
+                "{} is readable by other users (mode {:o})",
+                dir.display(),
+                mode
+            ),
+            format!("chmod 700 {}", dir.display()),
+        )
+    } else {
+        Check::ok(NAME, format!("{} (mode {:o})", dir.display(), mode))
+    }
+}
+
+/// Checks that the identity key exists, is well formed and is private.
+pub fn check_identity(path: &Path) -> Check {
+    const NAME: &str = "identity key";
+
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Check::warn(
+                NAME,
+                format!("{} does not exist", path.display()),
+                "Start the daemon once to generate it; clients paired with a previous key must pair again",
+            );
+        }
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("cannot read {}: {}", path.display(), e),
+                format!(
+                    "Check the permissions of {} and its parents",
+                    path.display()
+                ),
+            );
+        }
+    };
+
+    if metadata.len() != 32 {
+        return Check::fail(
+            NAME,
+            format!(
+                "{} is {} bytes, expected 32",
+                path.display(),
+                metadata.len()
+            ),
+            "Restore it from a backup, or move it aside to generate a new identity and pair all clients again",
+        );
+    }
+
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        return Check::fail(
+            NAME,
+            format!(
+                "{} is accessible by other users (mode {:o})",
+                path.display(),
+                mode
+            ),
+            format!("chmod 600 {}", path.display()),
+        );
+    }
+
+    if let Err(e) = fs::File::open(path) {
+        return Check::fail(
+            NAME,
+            format!("cannot read {}: {}", path.display(), e),
+            format!("chown $USER {}", path.display()),
+        );
+    }
+
+    Check::ok(NAME, format!("{} (mode {:o})", path.display(), mode))
+}
+
+/// Checks that the trust store can be read.
+pub fn check_trust_store(path: &Path) -> Check {
+    const NAME: &str = "trust store";
+
+    if !path.exists() {
+        return Check::ok(NAME, "no devices paired yet");
+    }
+
+    let store = TrustStore::new(path);
+    match store.load().and_then(|()| store.list_devices()) {
+        Ok(devices) => Check::ok(
+            NAME,
+            format!("{} ({} devices)", path.display(), devices.len()),
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            format!("{:#}", e),
+            format!(
+                "Restore {} from a backup or import a `remoshell-daemon devices export`, or move it aside and pair devices again",
+                path.display()
+            ),
+        ),
+    }
+}
+
+/// Checks that the daemon can create its IPC socket.
+pub fn check_socket_path(path: &Path, daemon_running: bool) -> Check {
+    const NAME: &str = "socket";
+
+    if path.exists() {
+        return if daemon_running {
+            Check::ok(NAME, format!("{} (daemon running)", path.display()))
+        } else {
+            Check::warn(
+                NAME,
+                format!("{} exists but no daemon is running", path.display()),
+                format!("rm {}", path.display()),
+            )
+        };
+    }
+
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    let result = if dir.exists() {
+        probe_write(dir)
+    } else {
+        writable_ancestor(dir)
+    };
+    match result {
+        Ok(()) => Check::ok(NAME, format!("{} can be created", path.display())),
+        Err(e) => Check::fail(
+            NAME,
+            format!("cannot create {}: {}", path.display(), e),
+            "Set XDG_RUNTIME_DIR to a directory you own (usually /run/user/$UID)",
+        ),
+    }
+}
+
+/// Reports whether the daemon can run as a systemd service.
+pub fn check_systemd() -> Check {
+    const NAME: &str = "systemd";
+
+    if !cfg!(target_os = "linux") {
+        return Check::ok(NAME, "not applicable on this platform");
+    }
+    if std::env::var_os("NOTIFY_SOCKET").is_some() {
+        return Check::ok(NAME, "running under systemd");
+    }
+    if Path::new("/run/systemd/system").exists() {
+        Check::ok(
+            NAME,
+            "available; run the daemon as a service with `remoshell-daemon start --systemd`",
+        )
+    } else {
+        Check::warn(
+            NAME,
+            "not the init system; the daemon will not restart on failure or boot",
+            "Run `remoshell-daemon start` from your init system or a process supervisor",
+        )
+    }
+}
+
+/// Checks that the signaling server accepts TCP connections, through the
+/// proxy if one applies.
+pub async fn check_signaling(signaling_url: &str, proxies: &ProxySettings) -> Check {
+    const NAME: &str = "signaling server";
+
+    let parsed = url::Url::parse(signaling_url).ok();
+    let address = parsed.as_ref().and_then(|url| {
+        let host = dial::unbracket(url.host_str()?).to_string();
+        let port = url.port_or_known_default()?;
+        Some((url, host, port))
+    });
+    let Some((url, host, port)) = address else {
+        return Check::fail(
+            NAME,
+            format!("cannot parse {}", signaling_url),
+            "Set network.signaling_url to a ws:// or wss:// URL",
+        );
+    };
+
+    let mut address = dial::join_host_port(&host, port);
+    if let Some(proxy) = proxies.proxy_for(url) {
+        address = format!("{} via {}", address, crate::network::proxy::redact(proxy));
+    }
+    match tokio::time::timeout(NETWORK_TIMEOUT, proxies.connect(url)).await {
+        Ok(Ok(_)) => Check::ok(NAME, format!("{} is reachable", address)),
+        Ok(Err(e)) => Check::fail(
+            NAME,
+            format!("cannot connect to {}: {}", address, e),
+            format!(
+                "Check DNS, proxies and firewalls for outbound TCP port {}, or set network.signaling_url",
+                port
+            ),
+        ),
+        Err(_) => Check::fail(
+            NAME,
+            format!("no answer from {} within {:?}", address, NETWORK_TIMEOUT),
+            format!("Allow outbound TCP port {} through the firewall", port),
+        ),
+    }
+}
+
+/// Checks that each STUN server answers a binding request.
+///
+/// Without a reachable STUN server peers can only connect when they are on
+/// the same network, so failures are reported as warnings.
+pub async fn check_stun_servers(servers: &[String]) -> Vec<Check> {
+    const NAME: &str = "STUN server";
+
+    if servers.is_empty() {
+        return vec![Check::warn(
+            NAME,
+            "none configured; clients outside this network cannot connect directly",
+            "Add a server to network.stun_servers, e.g. \"stun:stun.l.google.com:19302\"",
+        )];
+    }
+
+    let mut checks = Vec::with_capacity(servers.len());
+    for server in servers {
+        let check = match stun_binding(server).await {
+            Ok(()) => Check::ok(NAME, format!("{} answered", server)),
+            Err(e) => Check::warn(
+                NAME,
+                format!("{}: {}", server, e),
+                "Allow outbound UDP to the server's port, or replace it in network.stun_servers",
+            ),
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+/// Sends a STUN binding request to each address of the server, racing
+/// IPv4 and IPv6, until one answers.
+async fn stun_binding(server: &str) -> io::Result<()> {
+    let address = server.strip_prefix("stun:").unwrap_or(server);
+    let (host, port) = dial::split_host_port(address).unwrap_or((address, DEFAULT_STUN_PORT));
+    let addresses = dial::resolve(host, port).await?;
+    dial::race(&addresses, dial::CONNECTION_ATTEMPT_DELAY, stun_binding_to).await
+}
+
+/// Sends a STUN binding request to one address and waits for the matching
+/// response.
+async fn stun_binding_to(address: std::net::SocketAddr) -> io::Result<()> {
+    let mut transaction_id = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut transaction_id);
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&0x0001u16.to_be_bytes()); // Binding request
+    request.extend_from_slice(&0u16.to_be_bytes()); // No attributes
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction_id);
+
+    let socket = if address.is_ipv6() {
+        UdpSocket::bind("[::]:0").await?
+    } else {
+        UdpSocket::bind("0.0.0.0:0").await?
+    };
+    socket.connect(address).await?;
+    socket.send(&request).await?;
+
+    let mut response = [0u8; 512];
+    loop {
+        let len = tokio::time::timeout(NETWORK_TIMEOUT, socket.recv(&mut response))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no response"))??;
+        // Binding success response for our transaction
+        if len >= 20 && response[..2] == [0x01, 0x01] && response[8..20] == transaction_id {
+            return Ok(());
+        }
+    }
+}
+
+/// Creates and removes a file in `dir` to prove it is writable.
+fn probe_write(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".remoshell-doctor-{}", std::process::id()));
+    fs::File::create(&probe)?;
+    fs::remove_file(&probe)
+}
+
+/// Checks that the closest existing ancestor of `path` is writable, so the
+/// missing directories can be created.
+fn writable_ancestor(path: &Path) -> io::Result<()> {
+    let existing: PathBuf = path
+        .ancestors()
+        .find(|p| p.exists())
+        .unwrap_or(Path::new("/"))
+        .to_path_buf();
+    probe_write(&existing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn set_mode(path: &Path, mode: u32) {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn test_report_render_and_failures() {
+        let report = DoctorReport {
+            checks: vec![
+                Check::ok("config", "valid"),
+                Check::fail("identity key", "mode 644", "chmod 600 key"),
+            ],
+        };
+        assert!(report.has_failures());
+
+        let rendered = report.render();
+        assert!(rendered.contains("[ ok ] config: valid"));
+        assert!(rendered.contains("[FAIL] identity key: mode 644"));
+        assert!(rendered.contains("fix: chmod 600 key"));
+        assert!(rendered.contains("1 ok, 0 warnings, 1 failures"));
+    }
+
+    #[test]
+    fn test_check_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+
+        let (check, _) = check_config(&path, None, None);
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert!(check.detail.contains("using defaults"));
+
+        fs::write(&path, "[session]\nmax_sessions = 0\n").unwrap();
+        let (check, config) = check_config(&path, None, None);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.contains("max_sessions"));
+        assert_eq!(config.session.max_sessions, 0);
+
+        fs::write(&path, "[session\n").unwrap();
+        let (check, config) = check_config(&path, None, None);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_check_config_profile() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("work.toml");
+
+        let (check, config) = check_config(&path, Some("work"), None);
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert_eq!(
+            config.daemon.data_dir,
+            crate::config::profile_data_dir("work")
+        );
+    }
+
+    #[test]
+    fn test_check_data_dir_permissions() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("data");
+
+        assert_eq!(check_data_dir(&dir).status, CheckStatus::Ok);
+
+        fs::create_dir(&dir).unwrap();
+        set_mode(&dir, 0o700);
+        assert_eq!(check_data_dir(&dir).status, CheckStatus::Ok);
+
+        set_mode(&dir, 0o755);
+        assert_eq!(check_data_dir(&dir).status, CheckStatus::Warn);
+
+        set_mode(&dir, 0o777);
+        let check = check_data_dir(&dir);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.fix, Some(format!("chmod 700 {}", dir.display())));
+    }
+
+    #[test]
+    fn test_check_identity() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(IDENTITY_FILE_NAME);
+
+        assert_eq!(check_identity(&path).status, CheckStatus::Warn);
+
+        fs::write(&path, [7u8; 32]).unwrap();
+        set_mode(&path, 0o600);
+        assert_eq!(check_identity(&path).status, CheckStatus::Ok);
+
+        set_mode(&path, 0o644);
+        let check = check_identity(&path);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.fix, Some(format!("chmod 600 {}", path.display())));
+
+        fs::write(&path, [7u8; 16]).unwrap();
+        set_mode(&path, 0o600);
+        assert_eq!(check_identity(&path).status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_check_trust_store() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(TRUST_STORE_FILE_NAME);
+
+        assert_eq!(check_trust_store(&path).status, CheckStatus::Ok);
+
+        TrustStore::new(&path).save().unwrap();
+        let check = check_trust_store(&path);
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert!(check.detail.contains("0 devices"));
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(check_trust_store(&path).status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_check_socket_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("remoshell").join("daemon.sock");
+
+        assert_eq!(check_socket_path(&path, false).status, CheckStatus::Ok);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "").unwrap();
+        assert_eq!(check_socket_path(&path, true).status, CheckStatus::Ok);
+        assert_eq!(check_socket_path(&path, false).status, CheckStatus::Warn);
+    }
+
+    #[tokio::test]
+    async fn test_check_signaling() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let check = check_signaling(
+            &format!("ws://127.0.0.1:{}", port),
+            &ProxySettings::default(),
+        )
+        .await;
+        assert_eq!(check.status, CheckStatus::Ok);
+
+        drop(listener);
+        let check = check_signaling(
+            &format!("ws://127.0.0.1:{}", port),
+            &ProxySettings::default(),
+        )
+        .await;
+        assert_eq!(check.status, CheckStatus::Fail);
+
+        let check = check_signaling("not a url", &ProxySettings::default()).await;
+        assert_eq!(check.status, CheckStatus::Fail);
+
+        // IPv6 literals keep their brackets in URLs
+        if let Ok(listener) = tokio::net::TcpListener::bind("[::1]:0").await {
+            let port = listener.local_addr().unwrap().port();
+            let check = check_signaling(
+                &format!("wss://[::1]:{}/signal", port),
+                &ProxySettings::default(),
+            )
+            .await;
+            assert_eq!(check.status, CheckStatus::Ok);
+            assert_eq!(check.detail, format!("[::1]:{} is reachable", port));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_stun_servers() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (len, peer) = server.recv_from(&mut buf).await.unwrap();
+            assert_eq!(len, 20);
+            let mut response = buf[..20].to_vec();
+            response[..2].copy_from_slice(&[0x01, 0x01]);
+            server.send_to(&response, peer).await.unwrap();
+        });
+
+        let checks = check_stun_servers(&[format!("stun:{}", address)]).await;
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].status, CheckStatus::Ok);
+
+        let checks = check_stun_servers(&[]).await;
+        assert_eq!(checks[0].status, CheckStatus::Warn);
+    }
+
+    #[tokio::test]
+    async fn test_check_stun_servers_ipv6() {
+        let Ok(server) = UdpSocket::bind("[::1]:0").await else {
+            // No IPv6 on this host
+            return;
+        };
+        let address = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (len, peer) = server.recv_from(&mut buf).await.unwrap();
+            let mut response = buf[..len].to_vec();
+            response[..2].copy_from_slice(&[0x01, 0x01]);
+            server.send_to(&response, peer).await.unwrap();
+        });
+
+        let checks = check_stun_servers(&[format!("stun:{}", address)]).await;
+        assert_eq!(checks[0].status, CheckStatus::Ok);
+    }
+}
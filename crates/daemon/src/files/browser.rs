@@ -3,13 +3,19 @@
 //! This module provides secure directory listing functionality that validates
 //! all paths against allowed boundaries and prevents path traversal attacks.
 
+use std::collections::HashMap;
 use std::fs;
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 use std::time::SystemTime;
 
-use protocol::messages::{FileEntry, FileEntryType};
+use glob::Pattern;
+use protocol::messages::{
+    ErrorCode, FileEntry, FileEntryDetails, FileEntryType, FileListDetail, FileXattr,
+};
+use protocol::DeviceId;
 use thiserror::Error;
 
 /// Get the Unix mode of a file. Returns 0 on non-Unix platforms.
@@ -23,6 +29,159 @@ fn get_mode(_metadata: &std::fs::Metadata) -> u32 {
     0
 }
 
+/// Get the owner and group IDs of a file. Returns 0 on non-Unix platforms.
+#[cfg(unix)]
+fn get_ids(metadata: &std::fs::Metadata) -> (u32, u32) {
+    (metadata.uid(), metadata.gid())
+}
+
+#[cfg(not(unix))]
+fn get_ids(_metadata: &std::fs::Metadata) -> (u32, u32) {
+    (0, 0)
+}
+
+/// Extended attribute values larger than this are left out of listings.
+pub const MAX_XATTR_VALUE_LEN: usize = 4096;
+
+/// Read the extended attributes of a file, without following symlinks.
+///
+/// Attributes that cannot be read, or whose values are longer than
+/// [`MAX_XATTR_VALUE_LEN`], are skipped.
+#[cfg(target_os = "linux")]
+fn read_xattrs(path: &Path) -> Vec<FileXattr> {
+    use nix::libc;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return Vec::new();
+    };
+
+    // SAFETY: `c_path` is a valid C string and a null buffer of length 0
+    // only asks for the size of the name list.
+    let len = unsafe { libc::llistxattr(c_path.as_ptr(), std::ptr::null_mut(), 0) };
+    if len <= 0 {
+        return Vec::new();
+    }
+    let mut names = vec![0u8; len as usize];
+    // SAFETY: the buffer is valid for writes of `names.len()` bytes.
+    let len = unsafe { libc::llistxattr(c_path.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+    if len <= 0 {
+        return Vec::new();
+    }
+    names.truncate(len as usize);
+
+    names
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            let c_name = CString::new(name).ok()?;
+            let mut value = vec![0u8; MAX_XATTR_VALUE_LEN];
+            // SAFETY: both strings are valid C strings and the buffer is
+            // valid for writes of `value.len()` bytes. Longer values fail
+            // with ERANGE instead of being truncated.
+            let len = unsafe {
+                libc::lgetxattr(
+                    c_path.as_ptr(),
+                    c_name.as_ptr(),
+                    value.as_mut_ptr().cast(),
+                    value.len(),
+                )
+            };
+            if len < 0 {
+                return None;
+            }
+            value.truncate(len as usize);
+            Some(FileXattr {
+                name: String::from_utf8_lossy(name).into_owned(),
+                value,
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_xattrs(_path: &Path) -> Vec<FileXattr> {
+    Vec::new()
+}
+
+/// User and group names, looked up once per ID while converting a listing.
+#[derive(Default)]
+struct OwnerNames {
+    users: HashMap<u32, Option<String>>,
+    groups: HashMap<u32, Option<String>>,
+}
+
+impl OwnerNames {
+    #[cfg(unix)]
+    fn user(&mut self, uid: u32) -> Option<String> {
+        use nix::unistd::{Uid, User};
+        self.users
+            .entry(uid)
+            .or_insert_with(|| {
+                User::from_uid(Uid::from_raw(uid))
+                    .ok()
+                    .flatten()
+                    .map(|u| u.name)
+            })
+            .clone()
+    }
+
+    #[cfg(unix)]
+    fn group(&mut self, gid: u32) -> Option<String> {
+        use nix::unistd::{Gid, Group};
+        self.groups
+            .entry(gid)
+            .or_insert_with(|| {
+                Group::from_gid(Gid::from_raw(gid))
+                    .ok()
+                    .flatten()
+                    .map(|g| g.name)
+            })
+            .clone()
+    }
+
+    #[cfg(not(unix))]
+    fn user(&mut self, _uid: u32) -> Option<String> {
+        None
+    }
+
+    #[cfg(not(unix))]
+    fn group(&mut self, _gid: u32) -> Option<String> {
+        None
+    }
+}
+
+/// Convert directory entries to protocol entries with the given level of
+/// detail.
+pub fn protocol_entries(entries: &[DirectoryEntry], detail: FileListDetail) -> Vec<FileEntry> {
+    let mut names = OwnerNames::default();
+    entries
+        .iter()
+        .map(|entry| {
+            let mut proto = entry.to_protocol();
+            if detail != FileListDetail::Basic {
+                proto.details = Some(FileEntryDetails {
+                    uid: entry.uid,
+                    gid: entry.gid,
+                    owner: names.user(entry.uid),
+                    group: names.group(entry.gid),
+                    symlink_target: entry
+                        .symlink_target
+                        .as_ref()
+                        .map(|target| target.to_string_lossy().into_owned()),
+                    xattrs: if detail == FileListDetail::Full {
+                        read_xattrs(&entry.path)
+                    } else {
+                        Vec::new()
+                    },
+                });
+            }
+            proto
+        })
+        .collect()
+}
+
 /// Errors that can occur during directory browsing.
 #[derive(Debug, Error)]
 pub enum BrowserError {
@@ -55,6 +214,21 @@ pub enum BrowserError {
     Io(#[from] std::io::Error),
 }
 
+impl BrowserError {
+    /// Returns the protocol error code to report the error with.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            BrowserError::PathOutsideBoundary(_)
+            | BrowserError::PathTraversal(_)
+            | BrowserError::SymlinkOutsideBoundary(_)
+            | BrowserError::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            BrowserError::PathNotFound(_) => ErrorCode::NotFound,
+            BrowserError::NotADirectory(_) => ErrorCode::InvalidRequest,
+            BrowserError::Io(e) => super::io_error_code(e),
+        }
+    }
+}
+
 /// A directory entry with metadata.
 #[derive(Debug, Clone)]
 pub struct DirectoryEntry {
@@ -68,6 +242,10 @@ pub struct DirectoryEntry {
     pub size: u64,
     /// Unix permissions mode.
     pub mode: u32,
+    /// Owner user ID.
+    pub uid: u32,
+    /// Owner group ID.
+    pub gid: u32,
     /// Last modified timestamp.
     pub modified: SystemTime,
     /// Whether this is a symbolic link.
@@ -91,6 +269,7 @@ impl DirectoryEntry {
             size: self.size,
             mode: self.mode,
             modified,
+            details: None,
         }
     }
 }
@@ -100,11 +279,18 @@ impl DirectoryEntry {
 /// The browser validates all paths against a set of allowed paths before
 /// performing any operations. Path traversal attacks are prevented by
 /// canonicalizing all paths.
+///
+/// Listings can also leave out entries whose names match exclusion patterns,
+/// and hide or show hidden entries for a device regardless of its request.
 pub struct DirectoryBrowser {
     /// Allowed paths for browsing. Empty means all paths allowed.
     allowed_paths: Vec<PathBuf>,
     /// Whether to follow symlinks (default: false for security).
     follow_symlinks: bool,
+    /// Patterns of entry names left out of listings.
+    exclude: RwLock<Vec<Pattern>>,
+    /// Per-device override of whether hidden entries are listed.
+    show_hidden: HashMap<DeviceId, bool>,
 }
 
 impl DirectoryBrowser {
@@ -115,15 +301,19 @@ impl DirectoryBrowser {
         Self {
             allowed_paths,
             follow_symlinks: false,
+            exclude: RwLock::new(Vec::new()),
+            show_hidden: HashMap::new(),
         }
     }
 
     /// Create a browser that allows all paths.
     pub fn allow_all() -> Self {
-        Self {
-            allowed_paths: Vec::new(),
-            follow_symlinks: false,
-        }
+        Self::new(Vec::new())
+    }
+
+    /// The allowed paths, as configured. Empty means all paths are allowed.
+    pub fn allowed_paths(&self) -> &[PathBuf] {
+        &self.allowed_paths
     }
 
     /// Set whether to follow symlinks.
@@ -135,6 +325,46 @@ impl DirectoryBrowser {
         self
     }
 
+    /// Leave entries whose names match any of `patterns` out of listings.
+    ///
+    /// This only filters listings; excluded paths can still be opened by
+    /// name if the device is allowed to.
+    pub fn with_exclude(self, patterns: Vec<Pattern>) -> Self {
+        self.set_exclude(patterns);
+        self
+    }
+
+    /// Replace the patterns of names left out of listings, for listings
+    /// made from now on.
+    pub fn set_exclude(&self, patterns: Vec<Pattern>) {
+        *self.exclude.write().unwrap_or_else(|e| e.into_inner()) = patterns;
+    }
+
+    /// Set, per device, whether hidden entries are listed whatever the
+    /// client asks for.
+    pub fn with_show_hidden(mut self, overrides: HashMap<DeviceId, bool>) -> Self {
+        self.show_hidden = overrides;
+        self
+    }
+
+    /// Whether hidden entries should be listed for `device_id`, given
+    /// what its client requested.
+    pub fn show_hidden(&self, device_id: &DeviceId, requested: bool) -> bool {
+        self.show_hidden
+            .get(device_id)
+            .copied()
+            .unwrap_or(requested)
+    }
+
+    /// Whether `name` matches one of the exclusion patterns.
+    fn is_excluded(&self, name: &str) -> bool {
+        self.exclude
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|pattern| pattern.matches(name))
+    }
+
     /// Validate that a path is within allowed boundaries.
     ///
     /// This canonicalizes the path and checks it against all allowed paths.
@@ -245,7 +475,7 @@ impl DirectoryBrowser {
     /// List contents of a directory.
     ///
     /// Returns a list of entries in the directory. Hidden files (starting with '.')
-    /// are included if `include_hidden` is true.
+    /// are included if `include_hidden` is true; excluded names never are.
     pub fn list_directory(
         &self,
         path: &Path,
@@ -277,6 +507,9 @@ impl DirectoryBrowser {
             if !include_hidden && name.starts_with('.') {
                 continue;
             }
+            if self.is_excluded(&name) {
+                continue;
+            }
 
             // Get entry metadata (don't follow symlinks)
             let metadata = match entry.metadata() {
@@ -333,6 +566,7 @@ impl DirectoryBrowser {
             };
 
             let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let (uid, gid) = get_ids(&metadata);
 
             results.push(DirectoryEntry {
                 name,
@@ -340,6 +574,8 @@ impl DirectoryBrowser {
                 entry_type,
                 size,
                 mode: get_mode(&metadata),
+                uid,
+                gid,
                 modified,
                 is_symlink,
                 symlink_target,
@@ -398,12 +634,16 @@ impl DirectoryBrowser {
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| "/".to_string());
 
+        let (uid, gid) = get_ids(&metadata);
+
         Ok(DirectoryEntry {
             name,
             path: canonical,
             entry_type,
             size,
             mode: get_mode(&metadata),
+            uid,
+            gid,
             modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
             is_symlink,
             symlink_target,
@@ -462,6 +702,38 @@ mod tests {
         assert!(names.contains(&".hidden_dir"));
     }
 
+    #[test]
+    fn test_list_directory_excludes() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_structure(temp_dir.path());
+        fs::create_dir(temp_dir.path().join("node_modules")).unwrap();
+        fs::write(temp_dir.path().join("agent.sock"), "").unwrap();
+
+        let browser = DirectoryBrowser::allow_all().with_exclude(vec![
+            Pattern::new("node_modules").unwrap(),
+            Pattern::new("*.sock").unwrap(),
+            Pattern::new(".hidden_dir").unwrap(),
+        ]);
+        let entries = browser.list_directory(temp_dir.path(), true).unwrap();
+
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["subdir", ".hidden", "file.txt"]);
+    }
+
+    #[test]
+    fn test_show_hidden_override() {
+        let hidden = DeviceId::from_bytes([1; 16]);
+        let shown = DeviceId::from_bytes([2; 16]);
+        let other = DeviceId::from_bytes([3; 16]);
+        let browser = DirectoryBrowser::allow_all()
+            .with_show_hidden(HashMap::from([(hidden, false), (shown, true)]));
+
+        assert!(!browser.show_hidden(&hidden, true));
+        assert!(browser.show_hidden(&shown, false));
+        assert!(browser.show_hidden(&other, true));
+        assert!(!browser.show_hidden(&other, false));
+    }
+
     #[test]
     fn test_validate_path_allowed() {
         let temp_dir = TempDir::new().unwrap();
@@ -629,6 +901,8 @@ mod tests {
             entry_type: FileEntryType::File,
             size: 1024,
             mode: 0o644,
+            uid: 1000,
+            gid: 1000,
             modified: SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1704067200),
             is_symlink: false,
             symlink_target: None,
@@ -640,6 +914,68 @@ mod tests {
         assert_eq!(proto.size, 1024);
         assert_eq!(proto.mode, 0o644);
         assert_eq!(proto.modified, 1704067200);
+        assert_eq!(proto.details, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_protocol_entries_detail() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("file.txt"), "Hello").unwrap();
+        std::os::unix::fs::symlink("file.txt", temp_dir.path().join("link")).unwrap();
+
+        let browser = DirectoryBrowser::allow_all();
+        let entries = browser.list_directory(temp_dir.path(), false).unwrap();
+
+        let basic = protocol_entries(&entries, FileListDetail::Basic);
+        assert!(basic.iter().all(|e| e.details.is_none()));
+
+        let extended = protocol_entries(&entries, FileListDetail::Extended);
+        let file = extended[0].details.as_ref().unwrap();
+        assert_eq!(file.uid, nix::unistd::getuid().as_raw());
+        assert_eq!(file.gid, nix::unistd::getgid().as_raw());
+        assert_eq!(file.symlink_target, None);
+        assert!(file.xattrs.is_empty());
+        let link = extended[1].details.as_ref().unwrap();
+        assert_eq!(link.symlink_target.as_deref(), Some("file.txt"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_protocol_entries_xattrs() {
+        use nix::libc;
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file.txt");
+        fs::write(&path, "Hello").unwrap();
+
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let name = CString::new("user.remoshell").unwrap();
+        // SAFETY: valid C strings and a value buffer of the given length.
+        let set = unsafe {
+            libc::lsetxattr(c_path.as_ptr(), name.as_ptr(), b"tag".as_ptr().cast(), 3, 0)
+        };
+        if set != 0 {
+            // The filesystem holding the temp dir has no user xattrs
+            return;
+        }
+
+        let browser = DirectoryBrowser::allow_all();
+        let entries = browser.list_directory(temp_dir.path(), false).unwrap();
+
+        let extended = protocol_entries(&entries, FileListDetail::Extended);
+        assert!(extended[0].details.as_ref().unwrap().xattrs.is_empty());
+
+        let full = protocol_entries(&entries, FileListDetail::Full);
+        assert_eq!(
+            full[0].details.as_ref().unwrap().xattrs,
+            vec![FileXattr {
+                name: "user.remoshell".to_string(),
+                value: b"tag".to_vec(),
+            }]
+        );
     }
 
     #[test]
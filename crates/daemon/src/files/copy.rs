@@ -0,0 +1,728 @@
+//! Direct daemon-to-daemon file copies.
+//!
+//! A client connected to two daemons can copy a file from one to the other
+//! without relaying the data itself:
+//!
+//! 1. The client asks the destination daemon for a [`FileCopyGrant`]: a
+//!    short-lived, single-use token for one destination path, together with
+//!    the addresses and public key of the destination.
+//! 2. The client hands the grant to the source daemon in a
+//!    [`FileCopyRequest`](protocol::messages::FileCopyRequest).
+//! 3. The source daemon connects to the destination over TCP and performs a
+//!    Noise XX handshake, sending the token in its final handshake message.
+//!    The destination then signs the handshake hash with its identity key,
+//!    so the source knows it reached the daemon that issued the grant.
+//! 4. The source uploads the file with the usual upload messages, with a
+//!    BLAKE3 hash on every chunk, and relays the destination's
+//!    [`FileTransferReport`] (or error) to the client.
+//!
+//! The client checks permissions on both ends: reading the source path on
+//! the source daemon, and writing the destination path when the grant is
+//! issued.
+
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use protocol::crypto::{DeviceIdentity, PeerIdentity, Signature};
+use protocol::error::ProtocolError;
+use protocol::framing::{Frame, FrameCodec};
+use protocol::messages::{
+    Envelope, ErrorCode, ErrorMessage, FileCopyGrant, FileTransferReport, FileUploadChunk,
+    FileUploadComplete, FileUploadStart, Message,
+};
+use protocol::noise::NoiseSession;
+use protocol::DeviceId;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::transfer::{FileMessage, FileTransfer, TransferError};
+use crate::network::dial;
+
+/// How long a copy grant stays valid.
+pub const COPY_GRANT_TTL: Duration = Duration::from_secs(60);
+
+/// Size of the chunks copied between daemons.
+///
+/// Each chunk is encrypted as a single Noise message, which is limited to
+/// 64KB.
+pub const COPY_CHUNK_SIZE: u32 = 32 * 1024;
+
+/// Length of a copy grant token.
+const TOKEN_LENGTH: usize = 32;
+
+/// How long to wait to connect to a destination address.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long either daemon waits for the other to send something.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Prefix of the handshake hash signed by the destination.
+const SIGNATURE_CONTEXT: &[u8] = b"remoshell file copy v1";
+
+/// Errors of a direct copy.
+#[derive(Debug, Error)]
+pub enum CopyError {
+    /// The grant is malformed.
+    #[error("invalid copy grant: {0}")]
+    InvalidGrant(String),
+
+    /// No destination address could be reached.
+    #[error("could not connect to the destination: {0}")]
+    Connect(String),
+
+    /// The destination could not prove it issued the grant.
+    #[error("destination did not prove its identity")]
+    UntrustedDestination,
+
+    /// The destination refused the copy.
+    #[error("destination refused the copy: {}", .0.message)]
+    Rejected(ErrorMessage),
+
+    /// The other daemon sent something unexpected.
+    #[error("unexpected message: {0}")]
+    UnexpectedMessage(String),
+
+    /// The other daemon closed the connection.
+    #[error("connection closed")]
+    Closed,
+
+    /// The other daemon stopped responding.
+    #[error("timed out waiting for the other daemon")]
+    Timeout,
+
+    /// Reading or writing a file failed.
+    #[error(transparent)]
+    Transfer(#[from] TransferError),
+
+    /// Encryption, framing or encoding failed.
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+
+    /// The connection failed.
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl CopyError {
+    /// Converts the error into a message for the client that asked for the
+    /// copy of `source_path`.
+    pub fn to_error_message(&self, source_path: &str) -> ErrorMessage {
+        let code = match self {
+            CopyError::Rejected(error) => error.code,
+            CopyError::InvalidGrant(_) => ErrorCode::InvalidRequest,
+            CopyError::UntrustedDestination => ErrorCode::Unauthorized,
+            CopyError::Connect(_) | CopyError::Closed => ErrorCode::NotConnected,
+            CopyError::Timeout => ErrorCode::Timeout,
+            CopyError::Transfer(e) => e.error_code(),
+            _ => ErrorCode::InternalError,
+        };
+        ErrorMessage::new(code, format!("file copy failed: {}", self)).with_context(source_path)
+    }
+}
+
+/// An issued grant, until it is redeemed or expires.
+struct Grant {
+    device_id: DeviceId,
+    path: String,
+    overwrite: bool,
+    expires_at: Instant,
+}
+
+/// Issues copy grants, accepts copies made with them, and makes copies to
+/// other daemons.
+pub struct FileCopy {
+    identity: DeviceIdentity,
+    transfer: Arc<FileTransfer>,
+    /// Addresses advertised in grants.
+    addresses: Vec<String>,
+    /// Outstanding grants, by token.
+    grants: Mutex<HashMap<Vec<u8>, Grant>>,
+    /// Results of copies for the clients that requested them.
+    outbound: mpsc::UnboundedSender<FileMessage>,
+}
+
+impl FileCopy {
+    /// Creates a copier for the daemon with `identity`, reachable on
+    /// `addresses`, that reports results on `outbound`.
+    pub fn new(
+        identity: DeviceIdentity,
+        transfer: Arc<FileTransfer>,
+        addresses: Vec<String>,
+        outbound: mpsc::UnboundedSender<FileMessage>,
+    ) -> Self {
+        Self {
+            identity,
+            transfer,
+            addresses,
+            grants: Mutex::new(HashMap::new()),
+            outbound,
+        }
+    }
+
+    /// Issues a grant for a device to have a file copied to `path`.
+    ///
+    /// The caller checks that the device may write `path`.
+    pub fn grant(&self, device_id: DeviceId, path: &str, overwrite: bool) -> FileCopyGrant {
+        let token: [u8; TOKEN_LENGTH] = rand::random();
+        let now = Instant::now();
+
+        let mut grants = self.lock_grants();
+        grants.retain(|_, grant| grant.expires_at > now);
+        grants.insert(
+            token.to_vec(),
+            Grant {
+                device_id,
+                path: path.to_string(),
+                overwrite,
+                expires_at: now + COPY_GRANT_TTL,
+            },
+        );
+
+        FileCopyGrant {
+            path: path.to_string(),
+            token: token.to_vec(),
+            addresses: self.addresses.clone(),
+            public_key: self.identity.public_key_bytes().to_vec(),
+            expires_in_secs: COPY_GRANT_TTL.as_secs() as u32,
+        }
+    }
+
+    /// Returns the number of outstanding grants.
+    pub fn pending_grants(&self) -> usize {
+        self.lock_grants().len()
+    }
+
+    /// Copies `source_path` to the daemon that issued `grant`, in the
+    /// background, and sends the outcome to the device.
+    ///
+    /// The caller checks that the device may read `source_path`.
+    pub fn start(self: &Arc<Self>, device_id: DeviceId, source_path: String, grant: FileCopyGrant) {
+        let copier = Arc::clone(self);
+        tokio::spawn(async move {
+            let message = match copier.push(&source_path, &grant).await {
+                Ok(report) => {
+                    info!(
+                        source = %source_path,
+                        destination = %report.path,
+                        size = report.size,
+                        "Copied file to another daemon"
+                    );
+                    Message::FileTransferReport(report)
+                }
+                Err(e) => {
+                    warn!(source = %source_path, error = %e, "File copy failed");
+                    Message::Error(e.to_error_message(&source_path))
+                }
+            };
+            let _ = copier.outbound.send((device_id, message));
+        });
+    }
+
+    /// Accepts copies from other daemons on `listener` until `shutdown`.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener, shutdown: CancellationToken) {
+        loop {
+            let (stream, peer) = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!(error = %e, "Failed to accept file copy connection");
+                        continue;
+                    }
+                },
+            };
+
+            let copier = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = copier.receive(stream).await {
+                    warn!(peer = %peer, error = %e, "Incoming file copy failed");
+                }
+            });
+        }
+    }
+
+    /// Sends `source_path` to the daemon that issued `grant`.
+    async fn push(
+        &self,
+        source_path: &str,
+        grant: &FileCopyGrant,
+    ) -> Result<FileTransferReport, CopyError> {
+        let public_key: [u8; 32] = grant
+            .public_key
+            .as_slice()
+            .try_into()
+            .map_err(|_| CopyError::InvalidGrant("public key must be 32 bytes".to_string()))?;
+        let destination = PeerIdentity::from_public_key_bytes(&public_key)
+            .map_err(|e| CopyError::InvalidGrant(e.to_string()))?;
+        if grant.token.len() != TOKEN_LENGTH {
+            return Err(CopyError::InvalidGrant(
+                "token must be 32 bytes".to_string(),
+            ));
+        }
+
+        // Read the first chunk before connecting, so that a missing file
+        // fails without touching the destination
+        let mut chunk =
+            self.transfer
+                .download_chunk_message(source_path, 0, COPY_CHUNK_SIZE, false)?;
+        let mode = std::fs::metadata(source_path)?.permissions().mode() & 0o777;
+
+        let stream = connect(&grant.addresses).await?;
+        let mut link = Link::new(stream, NoiseSession::new_initiator(&self.identity)?);
+
+        // -> e
+        let message = link.noise.write_handshake_message(&[])?;
+        link.write_frame(message).await?;
+        // <- e, ee, s, es
+        let message = link.read_frame().await?;
+        link.noise.read_handshake_message(&message)?;
+        // -> s, se, with the token
+        let message = link.noise.write_handshake_message(&grant.token)?;
+        link.write_frame(message).await?;
+        link.noise.into_transport()?;
+
+        // The destination signs the handshake hash with its identity key
+        let signed = signed_hash(&link.noise)?;
+        let signature = link.recv_raw().await?;
+        let signature =
+            Signature::from_slice(&signature).map_err(|_| CopyError::UntrustedDestination)?;
+        destination
+            .verify(&signed, &signature)
+            .map_err(|_| CopyError::UntrustedDestination)?;
+
+        link.send(Message::FileUploadStart(FileUploadStart {
+            path: grant.path.clone(),
+            size: chunk.total_size,
+            mode,
+            overwrite: false,
+        }))
+        .await?;
+
+        let mut checksum = Sha256::new();
+        loop {
+            checksum.update(&chunk.data);
+            let offset = chunk.offset + chunk.data.len() as u64;
+            let is_last = chunk.is_last;
+            link.send(Message::FileUploadChunk(FileUploadChunk {
+                path: grant.path.clone(),
+                offset: chunk.offset,
+                hash: Some(blake3::hash(&chunk.data).as_bytes().to_vec()),
+                data: chunk.data,
+            }))
+            .await?;
+            // Stop early if the destination gave up
+            if let Some(reply) = link.try_recv()? {
+                return Err(unexpected_reply(reply));
+            }
+            if is_last {
+                break;
+            }
+            chunk = self.transfer.download_chunk_message(
+                source_path,
+                offset,
+                COPY_CHUNK_SIZE,
+                false,
+            )?;
+        }
+
+        link.send(Message::FileUploadComplete(FileUploadComplete {
+            path: grant.path.clone(),
+            checksum: checksum.finalize().to_vec(),
+            report: true,
+        }))
+        .await?;
+
+        match link.recv().await? {
+            Message::FileTransferReport(report) => Ok(report),
+            reply => Err(unexpected_reply(reply)),
+        }
+    }
+
+    /// Receives a file from another daemon on `stream`.
+    async fn receive(&self, stream: TcpStream) -> Result<(), CopyError> {
+        let mut link = Link::new(stream, NoiseSession::new_responder(&self.identity)?);
+
+        // -> e
+        let message = link.read_frame().await?;
+        link.noise.read_handshake_message(&message)?;
+        // <- e, ee, s, es
+        let message = link.noise.write_handshake_message(&[])?;
+        link.write_frame(message).await?;
+        // -> s, se, with the token
+        let message = link.read_frame().await?;
+        let token = link.noise.read_handshake_message(&message)?;
+        link.noise.into_transport()?;
+
+        let signature = self.identity.sign(&signed_hash(&link.noise)?);
+        link.send_raw(signature.as_bytes()).await?;
+
+        let Some(grant) = self.redeem(&token) else {
+            return link
+                .reject(ErrorMessage::new(
+                    ErrorCode::Unauthorized,
+                    "invalid or expired copy grant",
+                ))
+                .await;
+        };
+
+        debug!(path = %grant.path, device_id = %grant.device_id, "Receiving file copy");
+        let result = self.receive_file(&mut link, &grant).await;
+        if result.is_err() {
+            let _ = self.transfer.cancel_upload(Path::new(&grant.path));
+        }
+        result
+    }
+
+    /// Writes the upload the source sends on `link` to the grant's path.
+    async fn receive_file(&self, link: &mut Link, grant: &Grant) -> Result<(), CopyError> {
+        let path = Path::new(&grant.path);
+        let mut started = false;
+
+        loop {
+            let result = match link.recv().await? {
+                Message::FileUploadStart(start) if !started => {
+                    started = true;
+                    self.transfer
+                        .start_upload(path, start.size, start.mode, grant.overwrite)
+                }
+                Message::FileUploadChunk(chunk) if started => self.transfer.write_verified_chunk(
+                    path,
+                    chunk.offset,
+                    &chunk.data,
+                    chunk.hash.as_deref(),
+                ),
+                Message::FileUploadComplete(complete) if started => {
+                    match self.transfer.complete_upload(path, &complete.checksum) {
+                        Ok(summary) => {
+                            info!(path = %grant.path, size = summary.size, "Received file copy");
+                            let report = FileTransferReport {
+                                path: grant.path.clone(),
+                                size: summary.size,
+                                hash: summary.hash.to_vec(),
+                                chunks: summary.chunks,
+                                verified_chunks: summary.verified_chunks,
+                                duration_ms: summary.duration.as_millis() as u64,
+                            };
+                            link.send(Message::FileTransferReport(report)).await?;
+                            return Ok(());
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                other => {
+                    let name = other.name().to_string();
+                    link.reject(
+                        ErrorMessage::new(
+                            ErrorCode::InvalidRequest,
+                            format!("unexpected {} during file copy", name),
+                        )
+                        .with_context(grant.path.clone()),
+                    )
+                    .await?;
+                    return Err(CopyError::UnexpectedMessage(name));
+                }
+            };
+
+            if let Err(e) = result {
+                link.reject(
+                    ErrorMessage::new(e.error_code(), format!("file error: {}", e))
+                        .with_context(grant.path.clone()),
+                )
+                .await?;
+                return Err(e.into());
+            }
+        }
+    }
+
+    /// Takes the unexpired grant for `token`, if any.
+    fn redeem(&self, token: &[u8]) -> Option<Grant> {
+        let grant = self.lock_grants().remove(token)?;
+        (grant.expires_at > Instant::now()).then_some(grant)
+    }
+
+    fn lock_grants(&self) -> std::sync::MutexGuard<'_, HashMap<Vec<u8>, Grant>> {
+        self.grants.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns what the destination signs to prove its identity.
+fn signed_hash(noise: &NoiseSession) -> Result<Vec<u8>, CopyError> {
+    let hash = noise
+        .handshake_hash()
+        .ok_or(ProtocolError::HandshakeIncomplete)?;
+    Ok([SIGNATURE_CONTEXT, hash].concat())
+}
+
+/// Converts a reply other than the final report into an error.
+fn unexpected_reply(reply: Message) -> CopyError {
+    match reply {
+        Message::Error(error) => CopyError::Rejected(error),
+        other => CopyError::UnexpectedMessage(other.name().to_string()),
+    }
+}
+
+/// Connects to the first reachable address.
+async fn connect(addresses: &[String]) -> Result<TcpStream, CopyError> {
+    let mut last_error = "no addresses in grant".to_string();
+    for address in addresses {
+        match tokio::time::timeout(CONNECT_TIMEOUT, dial::connect_tcp_address(address)).await {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => last_error = format!("{}: {}", address, e),
+            Err(_) => last_error = format!("{}: timed out", address),
+        }
+    }
+    Err(CopyError::Connect(last_error))
+}
+
+/// A framed, encrypted connection between two daemons.
+struct Link {
+    stream: TcpStream,
+    noise: NoiseSession,
+    codec: FrameCodec,
+    buffer: Vec<u8>,
+    sequence: u64,
+}
+
+impl Link {
+    fn new(stream: TcpStream, noise: NoiseSession) -> Self {
+        Self {
+            stream,
+            noise,
+            // Encrypted payloads do not compress
+            codec: FrameCodec::without_compression(),
+            buffer: Vec::new(),
+            sequence: 0,
+        }
+    }
+
+    async fn write_frame(&mut self, payload: Vec<u8>) -> Result<(), CopyError> {
+        let frame = self.codec.encode(&Frame::new(payload))?;
+        self.stream.write_all(&frame).await?;
+        Ok(())
+    }
+
+    async fn read_frame(&mut self) -> Result<Vec<u8>, CopyError> {
+        loop {
+            if let Some((frame, used)) = self.codec.try_decode(&self.buffer)? {
+                self.buffer.drain(..used);
+                return Ok(frame.payload);
+            }
+
+            let mut data = [0u8; 16 * 1024];
+            let read = tokio::time::timeout(IDLE_TIMEOUT, self.stream.read(&mut data))
+                .await
+                .map_err(|_| CopyError::Timeout)??;
+            if read == 0 {
+                return Err(CopyError::Closed);
+            }
+            self.buffer.extend_from_slice(&data[..read]);
+        }
+    }
+
+    async fn send_raw(&mut self, plaintext: &[u8]) -> Result<(), CopyError> {
+        let ciphertext = self.noise.encrypt(plaintext)?;
+        self.write_frame(ciphertext).await
+    }
+
+    async fn recv_raw(&mut self) -> Result<Vec<u8>, CopyError> {
+        let ciphertext = self.read_frame().await?;
+        Ok(self.noise.decrypt(&ciphertext)?)
+    }
+
+    async fn send(&mut self, message: Message) -> Result<(), CopyError> {
+        self.sequence += 1;
+        let payload = Envelope::new(self.sequence, message)
+            .to_msgpack()
+            .map_err(|e| ProtocolError::Serialization(e.to_string()))?;
+        self.send_raw(&payload).await
+    }
+
+    async fn recv(&mut self) -> Result<Message, CopyError> {
+        let payload = self.recv_raw().await?;
+        Ok(Envelope::from_msgpack(&payload)?.payload)
+    }
+
+    /// Returns a message if one has already arrived, without waiting.
+    fn try_recv(&mut self) -> Result<Option<Message>, CopyError> {
+        let mut data = [0u8; 16 * 1024];
+        loop {
+            match self.stream.try_read(&mut data) {
+                Ok(0) => break,
+                Ok(read) => self.buffer.extend_from_slice(&data[..read]),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let Some((frame, used)) = self.codec.try_decode(&self.buffer)? else {
+            return Ok(None);
+        };
+        self.buffer.drain(..used);
+        let payload = self.noise.decrypt(&frame.payload)?;
+        Ok(Some(Envelope::from_msgpack(&payload)?.payload))
+    }
+
+    /// Sends `error` and waits for the other daemon to hang up.
+    ///
+    /// Closing with unread data would reset the connection, and the other
+    /// daemon could lose the error before reading it.
+    async fn reject(&mut self, error: ErrorMessage) -> Result<(), CopyError> {
+        self.send(Message::Error(error)).await?;
+        while self.read_frame().await.is_ok() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::DirectoryBrowser;
+    use tempfile::TempDir;
+
+    struct Daemon {
+        copier: Arc<FileCopy>,
+        results: mpsc::UnboundedReceiver<FileMessage>,
+        _dir: TempDir,
+        root: std::path::PathBuf,
+    }
+
+    /// Starts a daemon's copier, listening on a local port.
+    async fn daemon(shutdown: &CancellationToken) -> Daemon {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let transfer = Arc::new(
+            FileTransfer::new(
+                DirectoryBrowser::new(vec![root.clone()]),
+                1024 * 1024 * 1024,
+            )
+            .with_temp_dir(root.join("tmp")),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, results) = mpsc::unbounded_channel();
+        let copier = Arc::new(FileCopy::new(
+            DeviceIdentity::generate(),
+            transfer,
+            vec![address],
+            tx,
+        ));
+        tokio::spawn(Arc::clone(&copier).serve(listener, shutdown.clone()));
+        Daemon {
+            copier,
+            results,
+            _dir: dir,
+            root,
+        }
+    }
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([7u8; 16])
+    }
+
+    #[tokio::test]
+    async fn test_copy_between_daemons() {
+        let shutdown = CancellationToken::new();
+        let mut source = daemon(&shutdown).await;
+        let destination = daemon(&shutdown).await;
+
+        // Several chunks, the last one partial
+        let content: Vec<u8> = (0..COPY_CHUNK_SIZE as usize * 3 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let source_path = source.root.join("data.bin");
+        std::fs::write(&source_path, &content).unwrap();
+        let destination_path = destination.root.join("copy.bin");
+
+        let grant = destination
+            .copier
+            .grant(device(), &destination_path.to_string_lossy(), false);
+        source
+            .copier
+            .start(device(), source_path.to_string_lossy().to_string(), grant);
+
+        let (device_id, message) = source.results.recv().await.unwrap();
+        assert_eq!(device_id, device());
+        match message {
+            Message::FileTransferReport(report) => {
+                assert_eq!(report.path, destination_path.to_string_lossy());
+                assert_eq!(report.size, content.len() as u64);
+                assert_eq!(report.hash, blake3::hash(&content).as_bytes());
+                assert_eq!(report.chunks, 4);
+                assert_eq!(report.verified_chunks, 4);
+            }
+            other => panic!("Expected FileTransferReport, got {:?}", other),
+        }
+        assert_eq!(std::fs::read(&destination_path).unwrap(), content);
+
+        // Grants are single-use
+        assert_eq!(destination.copier.pending_grants(), 0);
+        shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_copy_rejects_reused_grant() {
+        let shutdown = CancellationToken::new();
+        let mut source = daemon(&shutdown).await;
+        let destination = daemon(&shutdown).await;
+
+        let source_path = source.root.join("data.bin");
+        std::fs::write(&source_path, b"hello").unwrap();
+        let source_path = source_path.to_string_lossy().to_string();
+        let destination_path = destination.root.join("copy.bin");
+        let grant = destination
+            .copier
+            .grant(device(), &destination_path.to_string_lossy(), true);
+
+        source
+            .copier
+            .start(device(), source_path.clone(), grant.clone());
+        let (_, message) = source.results.recv().await.unwrap();
+        assert!(matches!(message, Message::FileTransferReport(_)));
+
+        source.copier.start(device(), source_path.clone(), grant);
+        match source.results.recv().await.unwrap().1 {
+            Message::Error(error) => {
+                assert_eq!(error.code, ErrorCode::Unauthorized);
+                assert_eq!(error.context, Some(source_path));
+            }
+            other => panic!("Expected Error, got {:?}", other),
+        }
+        shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_copy_rejects_impostor_destination() {
+        let shutdown = CancellationToken::new();
+        let mut source = daemon(&shutdown).await;
+        let destination = daemon(&shutdown).await;
+
+        let source_path = source.root.join("data.bin");
+        std::fs::write(&source_path, b"secret").unwrap();
+        let destination_path = destination.root.join("copy.bin");
+
+        // A grant naming another daemon's key, pointing at this one
+        let mut grant =
+            destination
+                .copier
+                .grant(device(), &destination_path.to_string_lossy(), false);
+        grant.public_key = DeviceIdentity::generate().public_key_bytes().to_vec();
+
+        source
+            .copier
+            .start(device(), source_path.to_string_lossy().to_string(), grant);
+        match source.results.recv().await.unwrap().1 {
+            Message::Error(error) => assert_eq!(error.code, ErrorCode::Unauthorized),
+            other => panic!("Expected Error, got {:?}", other),
+        }
+        assert!(!destination_path.exists());
+        shutdown.cancel();
+    }
+}
@@ -3,6 +3,10 @@
 //! This module provides secure file operations including:
 //! - Directory listing with path validation
 //! - Chunked file downloads and uploads
+//! - Direct file copies between daemons
+//! - Positional reads from cached file handles for downloads
+//! - File type detection and image thumbnails
+//! - Following text files like `tail -f`
 //! - Atomic file writes using temp files
 //! - Per-device path permission enforcement
 //!
@@ -13,9 +17,32 @@
 //! that point outside allowed boundaries.
 
 pub mod browser;
+pub mod copy;
 pub mod permissions;
+pub mod reader;
+pub mod tail;
+pub mod thumbnail;
 pub mod transfer;
 
-pub use browser::{DirectoryBrowser, DirectoryEntry};
-pub use permissions::{DevicePermissions, PathPermissions};
-pub use transfer::{FileTransfer, TransferError, UploadState};
+pub use browser::{protocol_entries, DirectoryBrowser, DirectoryEntry};
+pub use copy::{CopyError, FileCopy, COPY_GRANT_TTL};
+pub use permissions::{DevicePermissions, PathPermissions, PermissionLevel};
+pub use reader::{FileReader, ReaderConfig};
+pub use tail::{FileTails, TailError, TailLimits};
+pub use thumbnail::{ThumbnailError, Thumbnailer};
+pub use transfer::{
+    DownloadStream, DownloadStreams, DrainedTransfers, FileMessage, FileTransfer, TransferError,
+    UploadState, UploadSummary, MAX_DOWNLOAD_WINDOW,
+};
+
+use protocol::messages::ErrorCode;
+
+/// Returns the protocol error code for a filesystem error.
+fn io_error_code(error: &std::io::Error) -> ErrorCode {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+        std::io::ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+        std::io::ErrorKind::AlreadyExists => ErrorCode::AlreadyExists,
+        _ => ErrorCode::InternalError,
+    }
+}
@@ -46,16 +46,22 @@ impl PermissionLevel {
 }
 
 /// Path permission configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PathPermission {
     /// The path this permission applies to.
     pub path: PathBuf,
     /// The permission level.
     pub level: PermissionLevel,
     /// Whether this permission applies recursively to subdirectories.
+    #[serde(default = "default_recursive")]
     pub recursive: bool,
 }
 
+/// Path permissions apply to subdirectories unless stated otherwise.
+fn default_recursive() -> bool {
+    true
+}
+
 impl PathPermission {
     /// Create a new path permission.
     pub fn new(path: PathBuf, level: PermissionLevel, recursive: bool) -> Self {
@@ -83,7 +89,7 @@ impl PathPermission {
 }
 
 /// Permissions for a specific device.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DevicePermissions {
     /// The device ID.
     pub device_id: DeviceId,
@@ -510,6 +516,40 @@ impl PathPermissions {
         false
     }
 
+    /// Returns the paths a device may access, each with the device's
+    /// permission level there, in path order.
+    ///
+    /// These are the device's own permission paths and the global allowed
+    /// paths (or `/` if there are none), keeping those that exist, lie within
+    /// the global allowed paths, and that the device may at least read.
+    pub fn device_roots(&self, device_id: &DeviceId) -> Result<Vec<(PathBuf, PermissionLevel)>> {
+        let Some(permissions) = self.get_device_permissions(device_id)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut candidates: Vec<PathBuf> =
+            permissions.paths.iter().map(|p| p.path.clone()).collect();
+        if self.global_allowed_paths.is_empty() {
+            candidates.push(PathBuf::from("/"));
+        } else {
+            candidates.extend(self.global_allowed_paths.iter().cloned());
+        }
+
+        let mut roots: Vec<(PathBuf, PermissionLevel)> = candidates
+            .into_iter()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .filter(|path| self.is_within_global_paths(path))
+            .map(|path| {
+                let level = permissions.get_permission(&path);
+                (path, level)
+            })
+            .filter(|(_, level)| level.can_read())
+            .collect();
+        roots.sort_by(|a, b| a.0.cmp(&b.0));
+        roots.dedup_by(|a, b| a.0 == b.0);
+        Ok(roots)
+    }
+
     /// List all devices with permissions.
     pub fn list_devices(&self) -> Result<Vec<DeviceId>> {
         let devices = self
@@ -715,6 +755,55 @@ mod tests {
         assert!(!store.can_device_read(&device_id, &forbidden_file).unwrap());
     }
 
+    #[test]
+    fn test_device_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let shared = root.join("shared");
+        let logs = root.join("shared/logs");
+        let home = root.join("home");
+        let outside = TempDir::new().unwrap();
+        for dir in [&shared, &logs, &home] {
+            fs::create_dir_all(dir).unwrap();
+        }
+
+        let device_id = create_test_device_id();
+        let mut device_perms = DevicePermissions::new(device_id);
+        device_perms.add_path(PathPermission::full_access(shared.clone()));
+        device_perms.add_path(PathPermission::read_only(logs.clone()));
+        // Outside the global paths
+        device_perms.add_path(PathPermission::full_access(outside.path().to_path_buf()));
+        // Does not exist
+        device_perms.add_path(PathPermission::read_only(root.join("missing")));
+
+        let store =
+            PathPermissions::new(root.join("perms.json"), vec![shared.clone(), home.clone()]);
+        store.set_device_permissions(device_perms).unwrap();
+
+        // The home global path is not accessible to the device
+        assert_eq!(
+            store.device_roots(&device_id).unwrap(),
+            vec![
+                (shared.clone(), PermissionLevel::Full),
+                (logs, PermissionLevel::Read),
+            ]
+        );
+
+        // A default level opens the global paths
+        let mut device_perms = store.get_device_permissions(&device_id).unwrap().unwrap();
+        device_perms.set_default_level(PermissionLevel::Read);
+        store.set_device_permissions(device_perms).unwrap();
+        let roots = store.device_roots(&device_id).unwrap();
+        assert_eq!(roots[0], (home, PermissionLevel::Read));
+        assert_eq!(roots[1], (shared, PermissionLevel::Full));
+
+        // Unknown devices have no roots
+        assert!(store
+            .device_roots(&create_test_device_id())
+            .unwrap()
+            .is_empty());
+    }
+
     #[test]
     fn test_path_permissions_remove_device() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,290 @@
+//! Positional reads for downloads.
+//!
+//! A download reads a file chunk by chunk, usually asking for each chunk
+//! right after the previous one. Instead of opening the file and seeking
+//! for every chunk, [`FileReader`] keeps the most recently read files open
+//! and reads at an offset with `pread`. Reads that stream a whole file, such
+//! as hashing it, borrow their buffer from a small pool instead of
+//! allocating one each time.
+//!
+//! Both caches are bounded by [`ReaderConfig`], so devices short on memory
+//! or file descriptors can shrink them.
+
+use std::collections::VecDeque;
+use std::fs::{File, Metadata};
+use std::io;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Sizes of the reader's caches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderConfig {
+    /// Size of the buffers used to stream whole files.
+    pub buffer_size: usize,
+    /// Number of idle buffers kept for reuse.
+    pub pooled_buffers: usize,
+    /// Number of files kept open between reads; 0 opens a file per read.
+    pub open_files: usize,
+}
+
+impl Default for ReaderConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: 256 * 1024,
+            pooled_buffers: 4,
+            open_files: 8,
+        }
+    }
+}
+
+/// A file kept open between reads.
+struct OpenFile {
+    path: PathBuf,
+    dev: u64,
+    ino: u64,
+    file: Arc<File>,
+}
+
+/// Reads files at an offset, keeping recently read files open.
+pub struct FileReader {
+    config: ReaderConfig,
+    /// Idle buffers of `config.buffer_size` bytes.
+    buffers: Mutex<Vec<Vec<u8>>>,
+    /// Open files, most recently read first.
+    open_files: Mutex<VecDeque<OpenFile>>,
+}
+
+impl FileReader {
+    /// Creates a reader with the given cache sizes.
+    pub fn new(config: ReaderConfig) -> Self {
+        Self {
+            config,
+            buffers: Mutex::new(Vec::new()),
+            open_files: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns the reader's cache sizes.
+    pub fn config(&self) -> ReaderConfig {
+        self.config
+    }
+
+    /// Reads up to `len` bytes at `offset` of the file at `path`, whose
+    /// current metadata is `metadata`.
+    ///
+    /// Returns fewer bytes only if the file ends first.
+    pub fn read_at(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+        offset: u64,
+        len: usize,
+    ) -> io::Result<Vec<u8>> {
+        let file = self.open(path, metadata)?;
+        let mut buffer = vec![0u8; len];
+        let read = fill_at(&file, &mut buffer, offset)?;
+        buffer.truncate(read);
+        Ok(buffer)
+    }
+
+    /// Streams the whole file at `path` to `f`, one buffer at a time.
+    pub fn for_each_block(&self, path: &Path, mut f: impl FnMut(&[u8])) -> io::Result<()> {
+        let metadata = std::fs::metadata(path)?;
+        let file = self.open(path, &metadata)?;
+        let mut buffer = self.take_buffer();
+
+        let mut offset = 0;
+        let result = loop {
+            match fill_at(&file, &mut buffer, offset) {
+                Ok(0) => break Ok(()),
+                Ok(read) => {
+                    f(&buffer[..read]);
+                    offset += read as u64;
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.recycle_buffer(buffer);
+        result
+    }
+
+    /// Returns the number of files kept open.
+    pub fn open_file_count(&self) -> usize {
+        self.lock_open_files().len()
+    }
+
+    /// Returns the number of idle buffers in the pool.
+    pub fn pooled_buffer_count(&self) -> usize {
+        self.lock_buffers().len()
+    }
+
+    /// Returns an open handle to `path`, reusing a cached one if it still
+    /// refers to the same file.
+    fn open(&self, path: &Path, metadata: &Metadata) -> io::Result<Arc<File>> {
+        if self.config.open_files == 0 {
+            return File::open(path).map(Arc::new);
+        }
+
+        let mut open_files = self.lock_open_files();
+        if let Some(index) = open_files.iter().position(|open| open.path == path) {
+            let open = open_files.remove(index).expect("index is in bounds");
+            // A file replaced since it was opened has a new inode
+            if open.dev == metadata.dev() && open.ino == metadata.ino() {
+                let file = Arc::clone(&open.file);
+                open_files.push_front(open);
+                return Ok(file);
+            }
+        }
+
+        let file = Arc::new(File::open(path)?);
+        // Describe the file actually opened, which may differ from
+        // `metadata` if it was replaced in between
+        let opened = file.metadata()?;
+        open_files.push_front(OpenFile {
+            path: path.to_path_buf(),
+            dev: opened.dev(),
+            ino: opened.ino(),
+            file: Arc::clone(&file),
+        });
+        open_files.truncate(self.config.open_files);
+        Ok(file)
+    }
+
+    fn take_buffer(&self) -> Vec<u8> {
+        self.lock_buffers()
+            .pop()
+            .unwrap_or_else(|| vec![0u8; self.config.buffer_size.max(1)])
+    }
+
+    fn recycle_buffer(&self, buffer: Vec<u8>) {
+        let mut buffers = self.lock_buffers();
+        if buffers.len() < self.config.pooled_buffers {
+            buffers.push(buffer);
+        }
+    }
+
+    fn lock_buffers(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_open_files(&self) -> std::sync::MutexGuard<'_, VecDeque<OpenFile>> {
+        self.open_files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for FileReader {
+    fn default() -> Self {
+        Self::new(ReaderConfig::default())
+    }
+}
+
+/// Reads into `buffer` at `offset` until it is full or the file ends,
+/// returning the number of bytes read.
+fn fill_at(file: &File, buffer: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read_at(&mut buffer[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn read(reader: &FileReader, path: &Path, offset: u64, len: usize) -> Vec<u8> {
+        let metadata = std::fs::metadata(path).unwrap();
+        reader.read_at(path, &metadata, offset, len).unwrap()
+    }
+
+    #[test]
+    fn test_read_at_keeps_file_open() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data.bin");
+        std::fs::write(&path, b"0123456789").unwrap();
+
+        let reader = FileReader::default();
+        assert_eq!(read(&reader, &path, 2, 4), b"2345");
+        assert_eq!(read(&reader, &path, 8, 4), b"89");
+        assert_eq!(read(&reader, &path, 10, 4), b"");
+        assert_eq!(reader.open_file_count(), 1);
+    }
+
+    #[test]
+    fn test_read_at_reopens_replaced_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data.bin");
+        std::fs::write(&path, b"old contents").unwrap();
+
+        let reader = FileReader::default();
+        assert_eq!(read(&reader, &path, 0, 3), b"old");
+
+        // Replace the file the way editors and uploads do
+        let replacement = temp_dir.path().join("data.bin.tmp");
+        std::fs::write(&replacement, b"new contents").unwrap();
+        std::fs::rename(&replacement, &path).unwrap();
+
+        assert_eq!(read(&reader, &path, 0, 3), b"new");
+        assert_eq!(reader.open_file_count(), 1);
+    }
+
+    #[test]
+    fn test_open_files_bounded() {
+        let temp_dir = TempDir::new().unwrap();
+        let reader = FileReader::new(ReaderConfig {
+            open_files: 2,
+            ..ReaderConfig::default()
+        });
+
+        for i in 0..4 {
+            let path = temp_dir.path().join(format!("file{}.bin", i));
+            std::fs::write(&path, [i as u8; 4]).unwrap();
+            assert_eq!(read(&reader, &path, 0, 4), [i as u8; 4]);
+        }
+        assert_eq!(reader.open_file_count(), 2);
+
+        let uncached = FileReader::new(ReaderConfig {
+            open_files: 0,
+            ..ReaderConfig::default()
+        });
+        let path = temp_dir.path().join("file0.bin");
+        assert_eq!(read(&uncached, &path, 0, 4), [0u8; 4]);
+        assert_eq!(uncached.open_file_count(), 0);
+    }
+
+    #[test]
+    fn test_for_each_block_reuses_buffer() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data.bin");
+        let content: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let reader = FileReader::new(ReaderConfig {
+            buffer_size: 64,
+            pooled_buffers: 1,
+            open_files: 8,
+        });
+
+        for _ in 0..2 {
+            let mut blocks = 0;
+            let mut streamed = Vec::new();
+            reader
+                .for_each_block(&path, |block| {
+                    blocks += 1;
+                    streamed.extend_from_slice(block);
+                })
+                .unwrap();
+            assert_eq!(streamed, content);
+            assert_eq!(blocks, 1000_usize.div_ceil(64));
+            assert_eq!(reader.pooled_buffer_count(), 1);
+        }
+    }
+}
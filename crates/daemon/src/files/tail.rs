@@ -0,0 +1,489 @@
+//! Streaming the end of text files, like `tail -f`.
+//!
+//! A tail first sends the last lines of a file, then, if the client asked
+//! to follow it, polls the file and sends whatever is appended until the
+//! client stops it or disconnects. A file that shrinks or is replaced, as
+//! when a log is rotated, is sent again from its start. Each device may
+//! follow a few files at once, and the data sent for each is rate limited
+//! so that a busy log cannot flood the connection.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use protocol::messages::{ErrorCode, ErrorMessage, FileTailData, Message};
+use protocol::DeviceId;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, warn};
+
+use super::transfer::FileMessage;
+
+/// Most bytes read from the end of a file for its last lines.
+pub const MAX_TAIL_BACKLOG: u64 = 256 * 1024;
+
+/// Size of the blocks read backwards while looking for line breaks.
+const SCAN_BLOCK: usize = 8192;
+
+/// Limits on tails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TailLimits {
+    /// Most lines sent from the end of a file.
+    pub max_lines: u32,
+    /// Most files a device may follow at once.
+    pub max_streams: usize,
+    /// How often followed files are checked for new data.
+    pub poll_interval: Duration,
+    /// Most bytes per second sent for each followed file.
+    pub max_rate: u64,
+}
+
+impl Default for TailLimits {
+    fn default() -> Self {
+        Self {
+            max_lines: 1000,
+            max_streams: 4,
+            poll_interval: Duration::from_millis(500),
+            max_rate: 64 * 1024,
+        }
+    }
+}
+
+/// Errors that can occur while tailing a file.
+#[derive(Debug, Error)]
+pub enum TailError {
+    /// The path is not a regular file.
+    #[error("Not a file: {0}")]
+    NotAFile(PathBuf),
+
+    /// The device already follows as many files as it may.
+    #[error("Too many followed files (limit {0})")]
+    TooManyTails(usize),
+
+    /// IO error.
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl TailError {
+    /// Returns the protocol error code to report the error with.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            TailError::NotAFile(_) => ErrorCode::InvalidRequest,
+            TailError::TooManyTails(_) => ErrorCode::QuotaExceeded,
+            TailError::Io(e) => super::io_error_code(e),
+        }
+    }
+}
+
+/// Stop channel of a followed file.
+struct TailHandle {
+    id: u64,
+    _stop: oneshot::Sender<()>,
+}
+
+/// Where a followed file has been sent up to.
+struct Position {
+    dev: u64,
+    ino: u64,
+    offset: u64,
+}
+
+/// Files being followed.
+///
+/// Each followed file is polled in its own task, which queues new data on
+/// `outbound`. Tails are keyed by device and path: tailing the same path
+/// again replaces the running tail, and dropping a tail's handle stops it.
+pub struct FileTails {
+    outbound: mpsc::UnboundedSender<FileMessage>,
+    limits: TailLimits,
+    tails: Mutex<HashMap<(DeviceId, String), TailHandle>>,
+    next_id: AtomicU64,
+}
+
+impl FileTails {
+    /// Creates an empty set of tails that queue data on `outbound`.
+    pub fn new(outbound: mpsc::UnboundedSender<FileMessage>, limits: TailLimits) -> Self {
+        Self {
+            outbound,
+            limits,
+            tails: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Reads the last `lines` lines of the file at `canonical`, which must
+    /// already be validated, and if `follow` is set keeps sending what is
+    /// appended to it.
+    ///
+    /// `path` is the path as requested, which the data is tagged with.
+    pub fn start(
+        self: &Arc<Self>,
+        device_id: DeviceId,
+        path: String,
+        canonical: PathBuf,
+        lines: u32,
+        follow: bool,
+    ) -> Result<FileTailData, TailError> {
+        let key = (device_id, path.clone());
+        if follow {
+            let tails = self.lock();
+            let running = tails
+                .keys()
+                .filter(|(id, tailed)| *id == device_id && *tailed != path)
+                .count();
+            if running >= self.limits.max_streams {
+                return Err(TailError::TooManyTails(self.limits.max_streams));
+            }
+        }
+
+        let mut file = File::open(&canonical)?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(TailError::NotAFile(canonical));
+        }
+        let end = metadata.len();
+        let lines = lines.min(self.limits.max_lines);
+        let start = last_lines_start(&mut file, end, lines)?;
+        let data = read_range(&mut file, start, end - start)?;
+
+        if follow {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let (stop, stopped) = oneshot::channel();
+            // Dropping the previous handle stops a tail of the same path
+            self.lock()
+                .insert(key.clone(), TailHandle { id, _stop: stop });
+
+            let position = Position {
+                dev: metadata.dev(),
+                ino: metadata.ino(),
+                offset: end,
+            };
+            let tails = Arc::clone(self);
+            tokio::spawn(async move {
+                tails.follow(&key, &canonical, position, stopped).await;
+                let mut running = tails.lock();
+                if running.get(&key).is_some_and(|handle| handle.id == id) {
+                    running.remove(&key);
+                }
+            });
+        }
+
+        Ok(FileTailData {
+            path,
+            offset: start,
+            data,
+            reset: false,
+        })
+    }
+
+    /// Stops following a file.
+    ///
+    /// Returns false if the device was not following it.
+    pub fn stop(&self, device_id: &DeviceId, path: &str) -> bool {
+        self.lock()
+            .remove(&(*device_id, path.to_string()))
+            .is_some()
+    }
+
+    /// Stops the tails of a device, when it disconnects.
+    pub fn close_device(&self, device_id: &DeviceId) {
+        self.lock().retain(|(id, _), _| id != device_id);
+    }
+
+    /// Returns the number of files being followed.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no files are being followed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(DeviceId, String), TailHandle>> {
+        self.tails.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn follow(
+        &self,
+        key: &(DeviceId, String),
+        canonical: &Path,
+        mut position: Position,
+        mut stopped: oneshot::Receiver<()>,
+    ) {
+        let (device_id, path) = key;
+        let interval = self.limits.poll_interval;
+        let budget = (u128::from(self.limits.max_rate) * interval.as_millis() / 1000).max(1) as u64;
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticks.tick().await;
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = &mut stopped => {
+                    debug!(path = %path, "Tail stopped");
+                    return;
+                }
+                _ = ticks.tick() => {}
+            }
+
+            let (offset, data, reset) = match poll(canonical, &mut position, budget) {
+                Ok(Some(read)) => read,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(path = %path, error = %e, "Tail failed");
+                    let error =
+                        ErrorMessage::new(super::io_error_code(&e), format!("file error: {}", e))
+                            .with_context(path.clone());
+                    let _ = self.outbound.send((*device_id, Message::Error(error)));
+                    return;
+                }
+            };
+
+            let data = FileTailData {
+                path: path.clone(),
+                offset,
+                data,
+                reset,
+            };
+            if self
+                .outbound
+                .send((*device_id, Message::FileTailData(data)))
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Reads up to `budget` bytes appended to a followed file since
+/// `position`, starting over if the file was truncated or replaced.
+///
+/// Returns the offset of the data read, the data, and whether the file
+/// started over, or `None` if there is nothing new, including while a
+/// rotated file has not been recreated yet.
+fn poll(
+    canonical: &Path,
+    position: &mut Position,
+    budget: u64,
+) -> io::Result<Option<(u64, Vec<u8>, bool)>> {
+    let metadata = match fs::metadata(canonical) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let replaced = metadata.dev() != position.dev || metadata.ino() != position.ino;
+    let reset = replaced || metadata.len() < position.offset;
+    if reset {
+        *position = Position {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            offset: 0,
+        };
+    }
+    if metadata.len() == position.offset && !reset {
+        return Ok(None);
+    }
+
+    let mut file = File::open(canonical)?;
+    let len = (metadata.len() - position.offset).min(budget);
+    let offset = position.offset;
+    let data = read_range(&mut file, offset, len)?;
+    position.offset += data.len() as u64;
+
+    Ok(Some((offset, data, reset)))
+}
+
+/// Finds the offset where the last `lines` lines of a file of `len` bytes
+/// start, looking back at most [`MAX_TAIL_BACKLOG`] bytes.
+///
+/// A line break at the very end of the file ends the last line rather
+/// than starting an empty one.
+fn last_lines_start(file: &mut File, len: u64, lines: u32) -> io::Result<u64> {
+    if lines == 0 {
+        return Ok(len);
+    }
+
+    let floor = len.saturating_sub(MAX_TAIL_BACKLOG);
+    let mut block = vec![0u8; SCAN_BLOCK];
+    let mut position = len;
+    let mut breaks = 0;
+    while position > floor {
+        let size = (position - floor).min(SCAN_BLOCK as u64) as usize;
+        position -= size as u64;
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut block[..size])?;
+
+        for (i, _) in block[..size]
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, &byte)| byte == b'\n')
+        {
+            let next = position + i as u64 + 1;
+            if next == len {
+                continue;
+            }
+            breaks += 1;
+            if breaks == lines {
+                return Ok(next);
+            }
+        }
+    }
+    Ok(floor)
+}
+
+/// Reads up to `len` bytes of a file from `offset`.
+fn read_range(file: &mut File, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(len as usize);
+    file.seek(SeekFrom::Start(offset))?;
+    file.take(len).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([1u8; 16])
+    }
+
+    fn limits() -> TailLimits {
+        TailLimits {
+            max_lines: 100,
+            max_streams: 1,
+            poll_interval: Duration::from_millis(10),
+            max_rate: 1000,
+        }
+    }
+
+    #[test]
+    fn test_last_lines_start() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.log");
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let mut file = File::open(&path).unwrap();
+
+        assert_eq!(last_lines_start(&mut file, 14, 0).unwrap(), 14);
+        assert_eq!(last_lines_start(&mut file, 14, 1).unwrap(), 8);
+        assert_eq!(last_lines_start(&mut file, 14, 2).unwrap(), 4);
+        assert_eq!(last_lines_start(&mut file, 14, 10).unwrap(), 0);
+
+        // An unterminated last line counts as a line
+        assert_eq!(last_lines_start(&mut file, 12, 1).unwrap(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_tail_follows_appends() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.log");
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let tails = Arc::new(FileTails::new(tx, limits()));
+        let first = tails
+            .start(device(), "app.log".to_string(), path.clone(), 2, true)
+            .unwrap();
+        assert_eq!(first.data, b"two\nthree\n");
+        assert_eq!(first.offset, 4);
+        assert_eq!(tails.len(), 1);
+
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"four\n")
+            .unwrap();
+        match rx.recv().await {
+            Some((_, Message::FileTailData(data))) => {
+                assert_eq!(data.path, "app.log");
+                assert_eq!(data.offset, 14);
+                assert_eq!(data.data, b"four\n");
+                assert!(!data.reset);
+            }
+            other => panic!("Expected FileTailData, got {:?}", other),
+        }
+
+        // A rotated file is sent again from its start
+        let rotated = temp_dir.path().join("app.log.new");
+        fs::write(&rotated, "new\n").unwrap();
+        fs::rename(&rotated, &path).unwrap();
+        match rx.recv().await {
+            Some((_, Message::FileTailData(data))) => {
+                assert_eq!(data.offset, 0);
+                assert_eq!(data.data, b"new\n");
+                assert!(data.reset);
+            }
+            other => panic!("Expected FileTailData, got {:?}", other),
+        }
+
+        assert!(tails.stop(&device(), "app.log"));
+        assert!(!tails.stop(&device(), "app.log"));
+        assert!(tails.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tail_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.log");
+        fs::write(&path, "").unwrap();
+        let other = temp_dir.path().join("other.log");
+        fs::write(&other, "").unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let tails = Arc::new(FileTails::new(tx, limits()));
+        tails
+            .start(device(), "app.log".to_string(), path.clone(), 10, true)
+            .unwrap();
+
+        // One followed file per device, but the same one can be restarted
+        assert!(matches!(
+            tails.start(device(), "other.log".to_string(), other.clone(), 10, true),
+            Err(TailError::TooManyTails(1))
+        ));
+        tails
+            .start(device(), "app.log".to_string(), path.clone(), 10, true)
+            .unwrap();
+        assert!(tails
+            .start(device(), "other.log".to_string(), other, 10, false)
+            .is_ok());
+        assert_eq!(tails.len(), 1);
+
+        // Appends are sent at most 10 bytes per 10ms poll
+        fs::write(&path, "x".repeat(25)).unwrap();
+        let mut sizes = Vec::new();
+        while sizes.iter().sum::<usize>() < 25 {
+            match rx.recv().await {
+                Some((_, Message::FileTailData(data))) => sizes.push(data.data.len()),
+                other => panic!("Expected FileTailData, got {:?}", other),
+            }
+        }
+        assert_eq!(sizes, vec![10, 10, 5]);
+
+        tails.close_device(&device());
+        assert!(tails.is_empty());
+        assert!(matches!(
+            tails.start(
+                device(),
+                "dir".to_string(),
+                temp_dir.path().to_path_buf(),
+                1,
+                false
+            ),
+            Err(TailError::NotAFile(_))
+        ));
+    }
+}
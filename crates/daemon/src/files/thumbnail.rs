@@ -0,0 +1,315 @@
+//! File type detection and image thumbnails for file browsers.
+//!
+//! A [`FileThumbnail`] tells a client the MIME type of a file, so it can
+//! pick an icon, and for images carries a small JPEG preview so it does not
+//! have to download the file. Decoding untrusted images is expensive, so
+//! thumbnails are only made for files below a size limit, with bounded
+//! image dimensions and decoder memory, a few at a time, and within a time
+//! limit. A file over any limit gets its MIME type only.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::{ImageReader, Limits};
+use protocol::messages::{ErrorCode, FileThumbnail};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::debug;
+
+/// Longest edge of a thumbnail when the client lets the daemon choose.
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 128;
+
+/// Largest thumbnail edge a client can ask for.
+pub const MAX_THUMBNAIL_SIZE: u32 = 512;
+
+/// Largest width or height of an image that is decoded.
+const MAX_SOURCE_DIMENSION: u32 = 16_384;
+
+/// Most memory the image decoder may allocate.
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+
+/// JPEG quality of thumbnails.
+const JPEG_QUALITY: u8 = 80;
+
+/// Bytes read from the start of a file to detect its type.
+const SNIFF_LEN: usize = 8192;
+
+/// MIME types of common files that have no magic number, by extension.
+const MIME_BY_EXTENSION: &[(&str, &str)] = &[
+    ("txt", "text/plain"),
+    ("log", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("ts", "text/x-typescript"),
+    ("rs", "text/x-rust"),
+    ("py", "text/x-python"),
+    ("sh", "application/x-sh"),
+    ("json", "application/json"),
+    ("toml", "application/toml"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("xml", "application/xml"),
+    ("svg", "image/svg+xml"),
+];
+
+/// Errors that can occur while making a thumbnail.
+#[derive(Debug, Error)]
+pub enum ThumbnailError {
+    /// The path is not a regular file.
+    #[error("Not a file: {0}")]
+    NotAFile(PathBuf),
+
+    /// IO error.
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl ThumbnailError {
+    /// Returns the protocol error code to report the error with.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            ThumbnailError::NotAFile(_) => ErrorCode::InvalidRequest,
+            ThumbnailError::Io(e) => super::io_error_code(e),
+        }
+    }
+}
+
+/// Detect the MIME type of a file from the first bytes of its contents,
+/// falling back to its extension.
+///
+/// Files that are neither recognized nor valid UTF-8 text are
+/// `application/octet-stream`.
+pub fn detect_mime_type(path: &Path, header: &[u8]) -> String {
+    if let Some(kind) = infer::get(header) {
+        return kind.mime_type().to_string();
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    if let Some((_, mime)) =
+        extension.and_then(|ext| MIME_BY_EXTENSION.iter().find(|(known, _)| *known == ext))
+    {
+        return mime.to_string();
+    }
+
+    // A multi-byte character may be cut off at the end of the header
+    let text = match std::str::from_utf8(header) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    if text && !header.contains(&0) {
+        "text/plain".to_string()
+    } else {
+        "application/octet-stream".to_string()
+    }
+}
+
+/// Makes thumbnails within size, time and concurrency limits.
+pub struct Thumbnailer {
+    /// Largest file, in bytes, a thumbnail is made of.
+    max_source_size: u64,
+    /// Longest a thumbnail may take, including waiting for a permit.
+    timeout: Duration,
+    /// Permits for thumbnails being made.
+    permits: Arc<Semaphore>,
+}
+
+impl Thumbnailer {
+    /// Create a thumbnailer for files up to `max_source_size` bytes, taking
+    /// at most `timeout` each and making at most `max_concurrent` at once.
+    pub fn new(max_source_size: u64, timeout: Duration, max_concurrent: usize) -> Self {
+        Self {
+            max_source_size,
+            timeout,
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+        }
+    }
+
+    /// Describe the file at `path`, which must already be validated, with a
+    /// thumbnail whose longest edge is at most `max_size` pixels if it is an
+    /// image.
+    pub async fn thumbnail(
+        &self,
+        path: &Path,
+        max_size: u32,
+    ) -> Result<FileThumbnail, ThumbnailError> {
+        let (size, mime_type) = {
+            let path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || sniff(&path))
+                .await
+                .map_err(io::Error::other)??
+        };
+
+        let mut thumbnail = FileThumbnail {
+            path: path.to_string_lossy().into_owned(),
+            mime_type,
+            jpeg: None,
+            width: 0,
+            height: 0,
+        };
+        if !thumbnail.mime_type.starts_with("image/") || size > self.max_source_size {
+            return Ok(thumbnail);
+        }
+
+        let max_size = match max_size {
+            0 => DEFAULT_THUMBNAIL_SIZE,
+            size => size.min(MAX_THUMBNAIL_SIZE),
+        };
+        let permits = Arc::clone(&self.permits);
+        let source = path.to_path_buf();
+        let render = async move {
+            let permit = permits.acquire_owned().await.map_err(io::Error::other)?;
+            // The permit moves into the task, so a timed out render still
+            // counts against the limit until it finishes
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                render(&source, max_size)
+            })
+            .await
+            .map_err(io::Error::other)?
+        };
+
+        match tokio::time::timeout(self.timeout, render).await {
+            Ok(Ok((jpeg, width, height))) => {
+                thumbnail.jpeg = Some(jpeg);
+                thumbnail.width = width;
+                thumbnail.height = height;
+            }
+            Ok(Err(e)) => debug!(path = %path.display(), error = %e, "No thumbnail"),
+            Err(_) => debug!(path = %path.display(), "Thumbnail timed out"),
+        }
+        Ok(thumbnail)
+    }
+}
+
+/// Read the size and MIME type of a regular file.
+fn sniff(path: &Path) -> Result<(u64, String), ThumbnailError> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Err(ThumbnailError::NotAFile(path.to_path_buf()));
+    }
+
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok((metadata.len(), detect_mime_type(path, &header)))
+}
+
+/// Decode the image at `path` and encode a JPEG thumbnail of it.
+fn render(path: &Path, max_size: u32) -> io::Result<(Vec<u8>, u32, u32)> {
+    let mut reader = ImageReader::open(path)?.with_guessed_format()?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    reader.limits(limits);
+
+    let image = reader.decode().map_err(io::Error::other)?;
+    // Small images are kept at their size rather than scaled up
+    let thumbnail = if image.width() <= max_size && image.height() <= max_size {
+        image.to_rgb8()
+    } else {
+        image.thumbnail(max_size, max_size).to_rgb8()
+    };
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+        .encode_image(&thumbnail)
+        .map_err(io::Error::other)?;
+    Ok((jpeg, thumbnail.width(), thumbnail.height()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbImage};
+    use tempfile::TempDir;
+
+    fn thumbnailer() -> Thumbnailer {
+        Thumbnailer::new(1024 * 1024, Duration::from_secs(10), 2)
+    }
+
+    #[test]
+    fn test_detect_mime_type() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(detect_mime_type(Path::new("a.bin"), png), "image/png");
+        assert_eq!(
+            detect_mime_type(Path::new("Cargo.TOML"), b"[package]"),
+            "application/toml"
+        );
+        assert_eq!(
+            detect_mime_type(Path::new("README"), "héllo".as_bytes()),
+            "text/plain"
+        );
+        assert_eq!(
+            detect_mime_type(Path::new("blob"), &[0, 1, 2, 0xff]),
+            "application/octet-stream"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_image_thumbnail() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("photo.png");
+        RgbImage::from_pixel(300, 200, image::Rgb([200, 40, 40]))
+            .save_with_format(&path, ImageFormat::Png)
+            .unwrap();
+
+        let thumbnail = thumbnailer().thumbnail(&path, 64).await.unwrap();
+        assert_eq!(thumbnail.mime_type, "image/png");
+        assert_eq!((thumbnail.width, thumbnail.height), (64, 43));
+
+        let jpeg = thumbnail.jpeg.unwrap();
+        let decoded = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 43));
+
+        // Requests above the maximum are capped
+        let thumbnail = thumbnailer().thumbnail(&path, 4096).await.unwrap();
+        assert_eq!(thumbnail.width, 300);
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let image = temp_dir.path().join("photo.png");
+        RgbImage::new(32, 32)
+            .save_with_format(&image, ImageFormat::Png)
+            .unwrap();
+        let text = temp_dir.path().join("notes.txt");
+        fs::write(&text, "hello").unwrap();
+
+        // Not an image: type only
+        let thumbnail = thumbnailer().thumbnail(&text, 0).await.unwrap();
+        assert_eq!(thumbnail.mime_type, "text/plain");
+        assert_eq!(thumbnail.jpeg, None);
+
+        // Over the source size limit: type only
+        let small = Thumbnailer::new(16, Duration::from_secs(10), 2);
+        let thumbnail = small.thumbnail(&image, 0).await.unwrap();
+        assert_eq!(thumbnail.mime_type, "image/png");
+        assert_eq!(thumbnail.jpeg, None);
+
+        // A corrupt image gets no thumbnail rather than an error
+        let corrupt = temp_dir.path().join("broken.png");
+        fs::write(&corrupt, b"\x89PNG\r\n\x1a\ngarbage").unwrap();
+        let thumbnail = thumbnailer().thumbnail(&corrupt, 0).await.unwrap();
+        assert_eq!(thumbnail.mime_type, "image/png");
+        assert_eq!(thumbnail.jpeg, None);
+
+        assert!(matches!(
+            thumbnailer().thumbnail(temp_dir.path(), 0).await,
+            Err(ThumbnailError::NotAFile(_))
+        ));
+    }
+}
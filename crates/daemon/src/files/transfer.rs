@@ -1,21 +1,38 @@
 //! File transfer with chunked download/upload and atomic writes.
 //!
 //! This module provides secure file transfer operations including:
-//! - Chunked file downloads with offset support for resuming
-//! - Chunked file uploads to temporary files
+//! - Chunked file downloads with offset support for resuming, read with
+//!   `pread` from files kept open between chunks
+//! - Chunked file uploads to temporary files, after checking that the
+//!   filesystem has room for them
 //! - Atomic file finalization using rename
+//! - Optional BLAKE3 hashes of each chunk and of the whole file, so clients
+//!   can verify transfers as they go
+//! - Streamed downloads, where the daemon sends a window of chunks ahead of
+//!   the client's acks instead of waiting for a request per chunk, so that
+//!   throughput does not collapse on high-latency links
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs::{self, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Read, Write};
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
+use nix::sys::statvfs::statvfs;
+use protocol::messages::{ErrorCode, ErrorMessage, FileDownloadChunk, Message};
+use protocol::DeviceId;
 use sha2::{Digest, Sha256};
 use thiserror::Error;
-use tracing::warn;
+use tokio::sync::{mpsc, watch};
+use tokio_util::task::TaskTracker;
+use tracing::{debug, warn};
 
 use super::browser::DirectoryBrowser;
+use super::reader::{FileReader, ReaderConfig};
+use crate::util::format::format_size;
 
 /// Default chunk size for transfers (64KB).
 pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
@@ -23,6 +40,23 @@ pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
 /// Maximum chunk size (1MB).
 pub const MAX_CHUNK_SIZE: u32 = 1024 * 1024;
 
+/// Free space an upload must leave on its filesystem, on top of its own
+/// size (16MB).
+pub const UPLOAD_SPACE_RESERVE: u64 = 16 * 1024 * 1024;
+
+/// Default size limit of text files read and written in one message
+/// (256KB), which is also the most a protocol string may hold.
+pub const DEFAULT_MAX_TEXT_SIZE: u64 = 256 * 1024;
+
+/// Most chunks a streamed download may have in flight.
+pub const MAX_DOWNLOAD_WINDOW: u32 = 32;
+
+/// How long a streamed download waits for an ack before giving up.
+pub const DOWNLOAD_ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A message for a connected device, with the device it is for.
+pub type FileMessage = (DeviceId, Message);
+
 /// Errors that can occur during file transfer.
 #[derive(Debug, Error)]
 pub enum TransferError {
@@ -54,6 +88,10 @@ pub enum TransferError {
     #[error("expected chunk at offset {expected}, got {received}")]
     ChunkOutOfOrder { expected: u64, received: u64 },
 
+    /// A chunk did not match the hash sent with it.
+    #[error("hash mismatch for chunk at offset {offset}")]
+    ChunkHashMismatch { offset: u64 },
+
     /// Checksum mismatch.
     #[error("checksum mismatch: expected {expected}, got {actual}")]
     ChecksumMismatch { expected: String, actual: String },
@@ -71,14 +109,57 @@ pub enum TransferError {
     PathValidation(String),
 
     /// File too large.
-    #[error("file too large: {size} bytes exceeds limit of {limit} bytes")]
+    #[error("file too large: {} exceeds limit of {}", format_size(*.size), format_size(*.limit))]
     FileTooLarge { size: u64, limit: u64 },
 
+    /// Not enough free disk space for an upload.
+    #[error(
+        "insufficient disk space: {} required, {} available",
+        format_size(*.required),
+        format_size(*.available)
+    )]
+    InsufficientSpace { required: u64, available: u64 },
+
+    /// The file is not valid UTF-8 text.
+    #[error("not a UTF-8 text file: {0}")]
+    NotText(PathBuf),
+
+    /// The file changed since the client read it.
+    #[error("file changed since it was read: {0}")]
+    Conflict(PathBuf),
+
     /// Lock poisoned during operation.
     #[error("lock poisoned: {context}")]
     LockPoisoned { context: String },
 }
 
+impl TransferError {
+    /// Returns the protocol error code to report the error with.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            TransferError::PathOutsideBoundary(_) => ErrorCode::PermissionDenied,
+            TransferError::FileNotFound(_) | TransferError::UploadNotFound(_) => {
+                ErrorCode::NotFound
+            }
+            TransferError::FileExists(_) => ErrorCode::AlreadyExists,
+            TransferError::IsADirectory(_)
+            | TransferError::InvalidOffset { .. }
+            | TransferError::ChunkOutOfOrder { .. }
+            | TransferError::PathValidation(_)
+            | TransferError::NotText(_) => ErrorCode::InvalidRequest,
+            TransferError::FileTooLarge { .. } => ErrorCode::QuotaExceeded,
+            TransferError::InsufficientSpace { .. } => ErrorCode::InsufficientSpace,
+            TransferError::Conflict(_) => ErrorCode::Conflict,
+            TransferError::Io(e) => super::io_error_code(e),
+            // Corrupted chunks can be sent again
+            TransferError::ChunkHashMismatch { .. }
+            | TransferError::ChecksumMismatch { .. }
+            | TransferError::SizeMismatch { .. }
+            | TransferError::LockPoisoned { .. } => ErrorCode::InternalError,
+        }
+    }
+}
+
 /// State for an in-progress upload.
 #[derive(Debug)]
 pub struct UploadState {
@@ -98,6 +179,14 @@ pub struct UploadState {
     file: Option<File>,
     /// SHA-256 hasher for checksum verification.
     hasher: Sha256,
+    /// BLAKE3 hasher for the transfer report.
+    blake3: blake3::Hasher,
+    /// Number of chunks written.
+    chunks: u32,
+    /// Number of chunks whose hash was checked.
+    verified_chunks: u32,
+    /// When the upload was started.
+    started_at: Instant,
 }
 
 impl UploadState {
@@ -118,10 +207,29 @@ impl UploadState {
             overwrite,
             file: None,
             hasher: Sha256::new(),
+            blake3: blake3::Hasher::new(),
+            chunks: 0,
+            verified_chunks: 0,
+            started_at: Instant::now(),
         }
     }
 }
 
+/// Summary of a completed upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadSummary {
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// BLAKE3 hash of the file.
+    pub hash: [u8; 32],
+    /// Number of chunks written.
+    pub chunks: u32,
+    /// Number of chunks whose hash was checked.
+    pub verified_chunks: u32,
+    /// Time from the start of the upload to its completion.
+    pub duration: Duration,
+}
+
 /// File transfer handler.
 ///
 /// Manages file downloads and uploads with path validation and atomic writes.
@@ -134,6 +242,12 @@ pub struct FileTransfer {
     max_file_size: u64,
     /// Temporary directory for uploads.
     temp_dir: PathBuf,
+    /// Reads files for downloads.
+    reader: FileReader,
+    /// Size limit of text files read and written in one message.
+    max_text_size: u64,
+    /// Held while a text write compares and replaces a file.
+    text_writes: Mutex<()>,
 }
 
 impl FileTransfer {
@@ -145,6 +259,9 @@ impl FileTransfer {
             uploads: RwLock::new(HashMap::new()),
             max_file_size,
             temp_dir,
+            reader: FileReader::default(),
+            max_text_size: DEFAULT_MAX_TEXT_SIZE,
+            text_writes: Mutex::new(()),
         }
     }
 
@@ -154,6 +271,18 @@ impl FileTransfer {
         self
     }
 
+    /// Set the sizes of the buffers and open files kept for downloads.
+    pub fn with_reader_config(mut self, config: ReaderConfig) -> Self {
+        self.reader = FileReader::new(config);
+        self
+    }
+
+    /// Set the size limit of text files read and written in one message.
+    pub fn with_max_text_size(mut self, max_text_size: u64) -> Self {
+        self.max_text_size = max_text_size;
+        self
+    }
+
     /// Download a chunk of a file.
     ///
     /// Returns the chunk data and whether this is the last chunk.
@@ -189,22 +318,54 @@ impl FileTransfer {
             return Err(TransferError::InvalidOffset { offset, file_size });
         }
 
-        // Open file and seek to offset
-        let mut file = File::open(&canonical)?;
-        file.seek(SeekFrom::Start(offset))?;
-
         // Read chunk
         let chunk_size = (chunk_size.min(MAX_CHUNK_SIZE) as u64).min(file_size - offset) as usize;
-        let mut buffer = vec![0u8; chunk_size];
-        let bytes_read = file.read(&mut buffer)?;
-        buffer.truncate(bytes_read);
+        let buffer = self
+            .reader
+            .read_at(&canonical, &metadata, offset, chunk_size)?;
 
-        let new_offset = offset + bytes_read as u64;
+        let new_offset = offset + buffer.len() as u64;
         let is_last = new_offset >= file_size;
 
         Ok((buffer, file_size, is_last))
     }
 
+    /// Download a chunk of a file as a protocol message.
+    ///
+    /// With `verify`, the chunk carries a BLAKE3 hash of its data, and the
+    /// last chunk also carries one of the whole file.
+    pub fn download_chunk_message(
+        &self,
+        path: &str,
+        offset: u64,
+        chunk_size: u32,
+        verify: bool,
+    ) -> Result<FileDownloadChunk, TransferError> {
+        let (data, total_size, is_last) =
+            self.download_chunk(Path::new(path), offset, chunk_size)?;
+
+        let (hash, file_hash) = if verify {
+            let file_hash = if is_last {
+                Some(self.download_hash(Path::new(path))?.to_vec())
+            } else {
+                None
+            };
+            (Some(blake3::hash(&data).as_bytes().to_vec()), file_hash)
+        } else {
+            (None, None)
+        };
+
+        Ok(FileDownloadChunk {
+            path: path.to_string(),
+            offset,
+            total_size,
+            data,
+            is_last,
+            hash,
+            file_hash,
+        })
+    }
+
     /// Start a new file upload.
     ///
     /// Creates a temporary file and prepares for receiving chunks.
@@ -234,19 +395,20 @@ impl FileTransfer {
             return Err(TransferError::FileExists(path.to_path_buf()));
         }
 
-        // Create temp directory if it doesn't exist
-        fs::create_dir_all(&self.temp_dir)?;
+        let (temp_dir, temp_prefix) = self.upload_temp_dir(&destination)?;
+        self.check_upload_space(&temp_dir, size)?;
 
         // Create temporary file with unique name
         let temp_filename = format!(
-            "upload_{:x}_{}.tmp",
+            "{}upload_{:x}_{}.tmp",
+            temp_prefix,
             std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_nanos(),
             rand::random::<u32>()
         );
-        let temp_path = self.temp_dir.join(temp_filename);
+        let temp_path = temp_dir.join(temp_filename);
 
         // Create the upload state
         let mut state = UploadState::new(
@@ -280,8 +442,85 @@ impl FileTransfer {
         Ok(())
     }
 
+    /// Returns the directory to write an upload to `destination` in, and a
+    /// prefix for the temp file's name.
+    ///
+    /// That is the configured temp directory if it is on the same filesystem
+    /// as the destination, so that finishing the upload is an atomic rename.
+    /// Otherwise it is the destination's directory, with a hidden temp file.
+    fn upload_temp_dir(
+        &self,
+        destination: &Path,
+    ) -> Result<(PathBuf, &'static str), TransferError> {
+        // Create temp directory if it doesn't exist
+        fs::create_dir_all(&self.temp_dir)?;
+
+        let parent = destination.parent().unwrap_or(Path::new("/"));
+        let temp_dev = fs::metadata(&self.temp_dir)?.dev();
+        match fs::metadata(parent) {
+            Ok(metadata) if metadata.dev() != temp_dev => {
+                debug!(
+                    temp_dir = ?self.temp_dir,
+                    destination = ?destination,
+                    "Temp directory is on another filesystem, uploading next to the destination"
+                );
+                Ok((parent.to_path_buf(), ".remoshell-"))
+            }
+            _ => Ok((self.temp_dir.clone(), "")),
+        }
+    }
+
+    /// Checks that the filesystem holding `temp_dir` has room for an upload
+    /// of `size` bytes, the reserve, and what in-progress uploads to the same
+    /// filesystem have yet to write.
+    fn check_upload_space(&self, temp_dir: &Path, size: u64) -> Result<(), TransferError> {
+        let stats = statvfs(temp_dir).map_err(std::io::Error::from)?;
+        let available =
+            (stats.blocks_available() as u64).saturating_mul(stats.fragment_size() as u64);
+
+        let temp_dev = fs::metadata(temp_dir)?.dev();
+        let pending: u64 = self
+            .uploads
+            .read()
+            .map_err(|_| TransferError::LockPoisoned {
+                context: "uploads lock during start_upload".to_string(),
+            })?
+            .values()
+            .filter(|state| {
+                fs::metadata(&state.temp_path).is_ok_and(|metadata| metadata.dev() == temp_dev)
+            })
+            .map(|state| state.total_size.saturating_sub(state.current_offset))
+            .sum();
+
+        let required = size
+            .saturating_add(UPLOAD_SPACE_RESERVE)
+            .saturating_add(pending);
+        if required > available {
+            return Err(TransferError::InsufficientSpace {
+                required,
+                available,
+            });
+        }
+        Ok(())
+    }
+
     /// Write a chunk of data to an in-progress upload.
     pub fn write_chunk(&self, path: &Path, offset: u64, data: &[u8]) -> Result<(), TransferError> {
+        self.write_verified_chunk(path, offset, data, None)
+    }
+
+    /// Write a chunk of data to an in-progress upload, checking it against
+    /// its BLAKE3 hash if one is given.
+    ///
+    /// A chunk that does not match its hash is not written, so the client
+    /// can send it again at the same offset.
+    pub fn write_verified_chunk(
+        &self,
+        path: &Path,
+        offset: u64,
+        data: &[u8],
+        hash: Option<&[u8]>,
+    ) -> Result<(), TransferError> {
         let key = path.to_string_lossy().to_string();
 
         let mut uploads = self
@@ -303,6 +542,13 @@ impl FileTransfer {
             });
         }
 
+        if let Some(expected) = hash {
+            if blake3::hash(data).as_bytes().as_slice() != expected {
+                return Err(TransferError::ChunkHashMismatch { offset });
+            }
+            state.verified_chunks += 1;
+        }
+
         // Write data to temp file
         if let Some(ref mut file) = state.file {
             file.write_all(data)?;
@@ -312,8 +558,10 @@ impl FileTransfer {
             )));
         }
 
-        // Update hasher with chunk data
+        // Update hashers with chunk data
         state.hasher.update(data);
+        state.blake3.update(data);
+        state.chunks += 1;
 
         // Update offset
         state.current_offset += data.len() as u64;
@@ -322,7 +570,11 @@ impl FileTransfer {
     }
 
     /// Complete an upload by verifying checksum and atomically moving to destination.
-    pub fn complete_upload(&self, path: &Path, checksum: &[u8]) -> Result<(), TransferError> {
+    pub fn complete_upload(
+        &self,
+        path: &Path,
+        checksum: &[u8],
+    ) -> Result<UploadSummary, TransferError> {
         let key = path.to_string_lossy().to_string();
 
         // Remove the upload state
@@ -379,7 +631,152 @@ impl FileTransfer {
         // Atomic rename to destination
         fs::rename(&state.temp_path, &state.destination)?;
 
-        Ok(())
+        Ok(UploadSummary {
+            size: state.total_size,
+            hash: *state.blake3.finalize().as_bytes(),
+            chunks: state.chunks,
+            verified_chunks: state.verified_chunks,
+            duration: state.started_at.elapsed(),
+        })
+    }
+
+    /// Compute the BLAKE3 hash of a file that may be downloaded.
+    pub fn download_hash(&self, path: &Path) -> Result<[u8; 32], TransferError> {
+        let canonical = self
+            .browser
+            .validate_path(path)
+            .map_err(|e| TransferError::PathValidation(e.to_string()))?;
+
+        let mut hasher = blake3::Hasher::new();
+        self.reader.for_each_block(&canonical, |block| {
+            hasher.update(block);
+        })?;
+        Ok(*hasher.finalize().as_bytes())
+    }
+
+    /// Read a whole text file.
+    ///
+    /// Returns its contents and their BLAKE3 hash, to pass back to
+    /// [`Self::write_text`].
+    pub fn read_text(&self, path: &Path) -> Result<(String, [u8; 32]), TransferError> {
+        let canonical = self
+            .browser
+            .validate_path(path)
+            .map_err(|e| TransferError::PathValidation(e.to_string()))?;
+
+        let bytes = match self.read_text_bytes(&canonical)? {
+            Some(bytes) => bytes,
+            None => return Err(TransferError::FileNotFound(path.to_path_buf())),
+        };
+        let hash = *blake3::hash(&bytes).as_bytes();
+        let content =
+            String::from_utf8(bytes).map_err(|_| TransferError::NotText(path.to_path_buf()))?;
+        Ok((content, hash))
+    }
+
+    /// Replace the contents of a text file, if its current contents hash to
+    /// `expected_hash`, or if it does not exist and there is no
+    /// `expected_hash`.
+    ///
+    /// The file keeps its permissions and is replaced atomically. A symlink
+    /// is written through: the file it points to is compared and replaced,
+    /// and must itself be allowed, so the link stays a link. Links that
+    /// point nowhere are refused. Returns the hash of the new contents.
+    pub fn write_text(
+        &self,
+        path: &Path,
+        content: &str,
+        expected_hash: Option<&[u8]>,
+    ) -> Result<[u8; 32], TransferError> {
+        if content.len() as u64 > self.max_text_size {
+            return Err(TransferError::FileTooLarge {
+                size: content.len() as u64,
+                limit: self.max_text_size,
+            });
+        }
+
+        let destination = self
+            .browser
+            .validate_path_for_creation(path)
+            .map_err(|e| TransferError::PathValidation(e.to_string()))?;
+        let destination = match fs::symlink_metadata(&destination) {
+            Ok(metadata) if metadata.file_type().is_symlink() => self
+                .browser
+                .validate_path(&destination)
+                .map_err(|e| TransferError::PathValidation(e.to_string()))?,
+            _ => destination,
+        };
+
+        // Writes from other clients of this daemon wait until this one has
+        // replaced the file, so they compare against its new contents
+        let _guard = self.text_writes.lock().unwrap_or_else(|e| e.into_inner());
+
+        let current = self.read_text_bytes(&destination)?;
+        let current_hash = current.map(|bytes| *blake3::hash(&bytes).as_bytes());
+        if current_hash.as_ref().map(|hash| hash.as_slice()) != expected_hash {
+            return Err(TransferError::Conflict(path.to_path_buf()));
+        }
+
+        // Write next to the destination so the rename stays on one
+        // filesystem
+        let parent = destination.parent().unwrap_or(Path::new("/"));
+        let temp_path = parent.join(format!(".remoshell-edit_{:x}.tmp", rand::random::<u64>()));
+        let mode = match fs::metadata(&destination) {
+            Ok(metadata) => metadata.mode() & 0o7777,
+            Err(_) => 0o644,
+        };
+        let result = (|| {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&temp_path)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&temp_path, fs::Permissions::from_mode(mode))?;
+            }
+            fs::rename(&temp_path, &destination)
+        })();
+        if let Err(e) = result {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+
+        Ok(*blake3::hash(content.as_bytes()).as_bytes())
+    }
+
+    /// Reads a file for a text read or write, or `None` if it does not
+    /// exist.
+    fn read_text_bytes(&self, path: &Path) -> Result<Option<Vec<u8>>, TransferError> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if metadata.is_dir() {
+            return Err(TransferError::IsADirectory(path.to_path_buf()));
+        }
+        if metadata.len() > self.max_text_size {
+            return Err(TransferError::FileTooLarge {
+                size: metadata.len(),
+                limit: self.max_text_size,
+            });
+        }
+
+        // Read through the handle, limited, in case the file grew since
+        let mut bytes = Vec::with_capacity(metadata.len() as usize);
+        File::open(path)?
+            .take(self.max_text_size + 1)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > self.max_text_size {
+            return Err(TransferError::FileTooLarge {
+                size: bytes.len() as u64,
+                limit: self.max_text_size,
+            });
+        }
+        Ok(Some(bytes))
     }
 
     /// Cancel an in-progress upload.
@@ -407,6 +804,34 @@ impl FileTransfer {
         Ok(())
     }
 
+    /// Flushes in-progress uploads to disk.
+    ///
+    /// Waits for a chunk being written to complete, so the flushed uploads
+    /// end on a chunk boundary. Returns the number of uploads in progress.
+    pub fn sync_uploads(&self) -> Result<usize, TransferError> {
+        let uploads = self
+            .uploads
+            .write()
+            .map_err(|_| TransferError::LockPoisoned {
+                context: "uploads lock during sync_uploads".to_string(),
+            })?;
+
+        for state in uploads.values() {
+            if let Some(file) = &state.file {
+                file.sync_data()?;
+            }
+        }
+        Ok(uploads.len())
+    }
+
+    /// Returns the number of uploads in progress.
+    pub fn upload_count(&self) -> usize {
+        self.uploads
+            .read()
+            .map(|uploads| uploads.len())
+            .unwrap_or(0)
+    }
+
     /// Get the status of an in-progress upload.
     pub fn get_upload_status(&self, path: &Path) -> Option<(u64, u64)> {
         let key = path.to_string_lossy().to_string();
@@ -446,6 +871,222 @@ impl FileTransfer {
     }
 }
 
+/// File transfers brought to a chunk boundary when the daemon stops.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrainedTransfers {
+    /// Number of streamed downloads stopped.
+    pub downloads: usize,
+    /// Number of uploads in progress, flushed to disk.
+    pub uploads: usize,
+    /// Whether some downloads did not stop in time.
+    pub timed_out: bool,
+}
+
+/// Parameters of a streamed download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadStream {
+    /// Path being downloaded, as requested.
+    pub path: String,
+    /// Offset the client requested the download from.
+    pub offset: u64,
+    /// Maximum chunk size.
+    pub chunk_size: u32,
+    /// Number of chunks that may be sent ahead of the client's acks.
+    pub window: u32,
+    /// Whether chunks carry BLAKE3 hashes.
+    pub verify: bool,
+}
+
+/// Ack channel of a running streamed download.
+struct StreamHandle {
+    id: u64,
+    acks: watch::Sender<u64>,
+}
+
+/// Streamed downloads in progress.
+///
+/// Each stream runs in its own task, reading chunks and queueing them on
+/// `outbound` while fewer than its window of chunks are unacknowledged.
+/// Streams are keyed by device and path: requesting the same path again
+/// replaces the running stream, and a stream that receives no ack for
+/// [`DOWNLOAD_ACK_TIMEOUT`] stops. A replaced or closed stream stops after
+/// the chunk it is reading.
+pub struct DownloadStreams {
+    outbound: mpsc::UnboundedSender<FileMessage>,
+    streams: Mutex<HashMap<(DeviceId, String), StreamHandle>>,
+    next_id: AtomicU64,
+    tasks: TaskTracker,
+}
+
+impl DownloadStreams {
+    /// Creates an empty set of streams that queue chunks on `outbound`.
+    pub fn new(outbound: mpsc::UnboundedSender<FileMessage>) -> Self {
+        Self {
+            outbound,
+            streams: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            tasks: TaskTracker::new(),
+        }
+    }
+
+    /// Streams the rest of a download whose first chunk was already sent.
+    ///
+    /// Does nothing if `first` is the last chunk.
+    pub fn start(
+        self: &Arc<Self>,
+        transfer: Arc<FileTransfer>,
+        device_id: DeviceId,
+        stream: DownloadStream,
+        first: &FileDownloadChunk,
+    ) {
+        if first.is_last {
+            return;
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let key = (device_id, stream.path.clone());
+        let (acks, ack_rx) = watch::channel(stream.offset);
+        // Dropping the previous sender stops a stream for the same path
+        self.lock().insert(key.clone(), StreamHandle { id, acks });
+
+        let streams = Arc::clone(self);
+        let next = first.offset + first.data.len() as u64;
+        self.tasks.spawn(async move {
+            streams.run(transfer, &key, stream, next, ack_rx).await;
+            let mut running = streams.lock();
+            if running.get(&key).is_some_and(|handle| handle.id == id) {
+                running.remove(&key);
+            }
+        });
+    }
+
+    /// Records that a device has received a download up to `offset`.
+    ///
+    /// Returns false if no such download is streaming.
+    pub fn ack(&self, device_id: &DeviceId, path: &str, offset: u64) -> bool {
+        let streams = self.lock();
+        let Some(handle) = streams.get(&(*device_id, path.to_string())) else {
+            return false;
+        };
+        handle.acks.send_if_modified(|acked| {
+            let advanced = offset > *acked;
+            if advanced {
+                *acked = offset;
+            }
+            advanced
+        });
+        true
+    }
+
+    /// Stops the streams of a device, when it disconnects.
+    pub fn close_device(&self, device_id: &DeviceId) {
+        self.lock().retain(|(id, _), _| id != device_id);
+    }
+
+    /// Stops every stream, when the daemon stops, and waits up to `timeout`
+    /// for them to finish the chunk they are reading.
+    ///
+    /// Returns the number of streams stopped, or `None` if some did not
+    /// finish in time.
+    pub async fn drain(&self, timeout: Duration) -> Option<usize> {
+        let stopped = {
+            let mut streams = self.lock();
+            let stopped = streams.len();
+            streams.clear();
+            stopped
+        };
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .ok()
+            .map(|()| stopped)
+    }
+
+    /// Returns the number of streams running.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no streams are running.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(DeviceId, String), StreamHandle>> {
+        self.streams.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn run(
+        &self,
+        transfer: Arc<FileTransfer>,
+        key: &(DeviceId, String),
+        stream: DownloadStream,
+        mut offset: u64,
+        mut acks: watch::Receiver<u64>,
+    ) {
+        let (device_id, path) = key;
+        let window = stream.window.clamp(1, MAX_DOWNLOAD_WINDOW) as usize;
+        let chunk_size = if stream.chunk_size == 0 {
+            DEFAULT_CHUNK_SIZE
+        } else {
+            stream.chunk_size
+        };
+        // End offsets of the chunks sent but not acknowledged
+        let mut in_flight = VecDeque::from([offset]);
+
+        loop {
+            if acks.has_changed().is_err() {
+                debug!(path = %path, offset, "Streamed download replaced or closed");
+                return;
+            }
+            loop {
+                let acked = *acks.borrow_and_update();
+                while in_flight.front().is_some_and(|&end| end <= acked) {
+                    in_flight.pop_front();
+                }
+                if in_flight.len() < window {
+                    break;
+                }
+                match tokio::time::timeout(DOWNLOAD_ACK_TIMEOUT, acks.changed()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(_)) => {
+                        debug!(path = %path, "Streamed download replaced or closed");
+                        return;
+                    }
+                    Err(_) => {
+                        warn!(path = %path, offset, "Streamed download timed out waiting for an ack");
+                        return;
+                    }
+                }
+            }
+
+            let chunk =
+                match transfer.download_chunk_message(path, offset, chunk_size, stream.verify) {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        warn!(path = %path, offset, error = %e, "Streamed download failed");
+                        let error = ErrorMessage::new(e.error_code(), format!("file error: {}", e))
+                            .with_context(path.clone());
+                        let _ = self.outbound.send((*device_id, Message::Error(error)));
+                        return;
+                    }
+                };
+
+            let is_last = chunk.is_last;
+            offset += chunk.data.len() as u64;
+            if self
+                .outbound
+                .send((*device_id, Message::FileDownloadChunk(chunk)))
+                .is_err()
+                || is_last
+            {
+                return;
+            }
+            in_flight.push_back(offset);
+        }
+    }
+}
+
 /// Helper function to compute SHA-256 hash of a file.
 pub fn hash_file(path: &Path) -> Result<Vec<u8>, std::io::Error> {
     let mut file = File::open(path)?;
@@ -660,6 +1301,60 @@ mod tests {
         assert!(matches!(result, Err(TransferError::ChunkOutOfOrder { .. })));
     }
 
+    #[test]
+    fn test_upload_verified_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let upload_dir = temp_dir.path().join("uploads");
+        fs::create_dir_all(&upload_dir).unwrap();
+
+        let content = b"Verified chunks, one hash each";
+        let checksum = Sha256::digest(content).to_vec();
+
+        let browser = DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]);
+        let transfer = FileTransfer::new(browser, 100 * 1024 * 1024)
+            .with_temp_dir(temp_dir.path().join("tmp"));
+
+        let dest_path = upload_dir.join("verified.txt");
+
+        transfer
+            .start_upload(&dest_path, content.len() as u64, 0o644, false)
+            .unwrap();
+
+        // A corrupted chunk is rejected and can be sent again
+        let first = &content[..10];
+        let result = transfer.write_verified_chunk(
+            &dest_path,
+            0,
+            b"corrupted!",
+            Some(blake3::hash(first).as_bytes()),
+        );
+        assert!(matches!(
+            result,
+            Err(TransferError::ChunkHashMismatch { offset: 0 })
+        ));
+        assert_eq!(transfer.get_upload_status(&dest_path).unwrap().0, 0);
+
+        transfer
+            .write_verified_chunk(&dest_path, 0, first, Some(blake3::hash(first).as_bytes()))
+            .unwrap();
+        // Chunks without a hash are still accepted
+        transfer
+            .write_chunk(&dest_path, 10, &content[10..])
+            .unwrap();
+
+        let summary = transfer.complete_upload(&dest_path, &checksum).unwrap();
+        assert_eq!(summary.size, content.len() as u64);
+        assert_eq!(summary.hash, *blake3::hash(content).as_bytes());
+        assert_eq!(summary.chunks, 2);
+        assert_eq!(summary.verified_chunks, 1);
+        assert_eq!(fs::read(&dest_path).unwrap(), content);
+
+        assert_eq!(
+            transfer.download_hash(&dest_path).unwrap(),
+            *blake3::hash(content).as_bytes()
+        );
+    }
+
     #[test]
     fn test_upload_file_exists_no_overwrite() {
         let temp_dir = TempDir::new().unwrap();
@@ -710,6 +1405,102 @@ mod tests {
         assert!(matches!(result, Err(TransferError::FileTooLarge { .. })));
     }
 
+    #[test]
+    fn test_upload_insufficient_space() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let browser = DirectoryBrowser::allow_all();
+        let transfer =
+            FileTransfer::new(browser, u64::MAX).with_temp_dir(temp_dir.path().join("tmp"));
+
+        let dest_path = temp_dir.path().join("huge.bin");
+        let result = transfer.start_upload(&dest_path, u64::MAX / 2, 0o644, false);
+        assert!(matches!(
+            result,
+            Err(TransferError::InsufficientSpace { required, available })
+                if required > available
+        ));
+        assert!(transfer.get_upload_status(&dest_path).is_none());
+    }
+
+    #[test]
+    fn test_upload_space_counts_pending_uploads() {
+        let temp_dir = TempDir::new().unwrap();
+        let stats = statvfs(temp_dir.path()).unwrap();
+        let available = stats.blocks_available() as u64 * stats.fragment_size() as u64;
+        let slack = 32 * 1024 * 1024;
+        if available < UPLOAD_SPACE_RESERVE + 2 * slack {
+            return;
+        }
+
+        let browser = DirectoryBrowser::allow_all();
+        let transfer =
+            FileTransfer::new(browser, u64::MAX).with_temp_dir(temp_dir.path().join("tmp"));
+
+        // Each upload fits on its own, but not both
+        let first = temp_dir.path().join("first.bin");
+        transfer
+            .start_upload(
+                &first,
+                available - UPLOAD_SPACE_RESERVE - slack,
+                0o644,
+                false,
+            )
+            .unwrap();
+        let second = temp_dir.path().join("second.bin");
+        let result = transfer.start_upload(&second, slack + slack / 2, 0o644, false);
+        assert!(matches!(
+            result,
+            Err(TransferError::InsufficientSpace { .. })
+        ));
+
+        transfer.cancel_upload(&first).unwrap();
+        transfer
+            .start_upload(&second, slack + slack / 2, 0o644, false)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_upload_temp_dir_on_other_filesystem() {
+        let shm = Path::new("/dev/shm");
+        let temp_dir = TempDir::new().unwrap();
+        let Ok(other_fs) = TempDir::new_in(shm) else {
+            return;
+        };
+        if fs::metadata(other_fs.path()).unwrap().dev()
+            == fs::metadata(temp_dir.path()).unwrap().dev()
+        {
+            return;
+        }
+
+        let browser = DirectoryBrowser::allow_all();
+        let transfer = FileTransfer::new(browser, 100 * 1024 * 1024)
+            .with_temp_dir(other_fs.path().to_path_buf());
+
+        // The upload is written next to its destination so it can be renamed
+        let dest_path = temp_dir.path().join("moved.txt");
+        let content = b"same filesystem";
+        transfer
+            .start_upload(&dest_path, content.len() as u64, 0o644, false)
+            .unwrap();
+        let temp_path = transfer.uploads.read().unwrap()[&*dest_path.to_string_lossy()]
+            .temp_path
+            .clone();
+        assert_eq!(temp_path.parent(), dest_path.parent());
+        assert!(temp_path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with(".remoshell-upload_"));
+
+        transfer.write_chunk(&dest_path, 0, content).unwrap();
+        transfer
+            .complete_upload(&dest_path, &Sha256::digest(content))
+            .unwrap();
+        assert_eq!(fs::read(&dest_path).unwrap(), content);
+        assert!(!temp_path.exists());
+    }
+
     #[test]
     fn test_upload_not_found() {
         let temp_dir = TempDir::new().unwrap();
@@ -779,4 +1570,239 @@ mod tests {
         let result = transfer.download_chunk(&other_dir.path().join("secret.txt"), 0, 100);
         assert!(matches!(result, Err(TransferError::PathValidation(_))));
     }
+
+    #[test]
+    fn test_text_edit_compare_and_swap() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(temp_dir.path(), "app.conf", b"port = 80\n");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let browser = DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]);
+        let transfer = FileTransfer::new(browser, 100 * 1024 * 1024);
+
+        let (content, hash) = transfer.read_text(&path).unwrap();
+        assert_eq!(content, "port = 80\n");
+        assert_eq!(hash, *blake3::hash(b"port = 80\n").as_bytes());
+
+        let new_hash = transfer
+            .write_text(&path, "port = 8080\n", Some(&hash))
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"port = 8080\n");
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        // A second edit of the original contents has been overtaken
+        let result = transfer.write_text(&path, "port = 443\n", Some(&hash));
+        assert!(matches!(result, Err(TransferError::Conflict(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), b"port = 8080\n");
+
+        // Creating needs the file to be absent
+        assert!(matches!(
+            transfer.write_text(&path, "", None),
+            Err(TransferError::Conflict(_))
+        ));
+        let created = temp_dir.path().join("new.conf");
+        transfer.write_text(&created, "x = 1\n", None).unwrap();
+        assert_eq!(std::fs::read(&created).unwrap(), b"x = 1\n");
+
+        transfer
+            .write_text(&path, "port = 443\n", Some(&new_hash))
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"port = 443\n");
+
+        // No temp files are left behind
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_text_edit_writes_through_symlink() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = create_test_file(temp_dir.path(), "real.conf", b"port = 80\n");
+        let link = temp_dir.path().join("link.conf");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let browser = DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]);
+        let transfer = FileTransfer::new(browser, 100 * 1024 * 1024);
+
+        let (_, hash) = transfer.read_text(&link).unwrap();
+        transfer
+            .write_text(&link, "port = 8080\n", Some(&hash))
+            .unwrap();
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read(&target).unwrap(), b"port = 8080\n");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_text_edit_refuses_escaping_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let other_dir = TempDir::new().unwrap();
+        let secret = create_test_file(other_dir.path(), "secret.conf", b"key = 1\n");
+        let escaping = temp_dir.path().join("escaping.conf");
+        std::os::unix::fs::symlink(&secret, &escaping).unwrap();
+        let dangling = temp_dir.path().join("dangling.conf");
+        std::os::unix::fs::symlink(temp_dir.path().join("missing.conf"), &dangling).unwrap();
+
+        let browser = DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]);
+        let transfer = FileTransfer::new(browser, 100 * 1024 * 1024);
+
+        let hash = *blake3::hash(b"key = 1\n").as_bytes();
+        assert!(matches!(
+            transfer.write_text(&escaping, "key = 2\n", Some(&hash)),
+            Err(TransferError::PathValidation(_))
+        ));
+        assert_eq!(std::fs::read(&secret).unwrap(), b"key = 1\n");
+        assert!(matches!(
+            transfer.write_text(&dangling, "x = 1\n", None),
+            Err(TransferError::PathValidation(_))
+        ));
+        assert!(!temp_dir.path().join("missing.conf").exists());
+        for link in [&escaping, &dangling] {
+            assert!(std::fs::symlink_metadata(link)
+                .unwrap()
+                .file_type()
+                .is_symlink());
+        }
+    }
+
+    #[test]
+    fn test_text_edit_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let binary = create_test_file(temp_dir.path(), "blob.bin", &[0xff, 0xfe, 0x00]);
+        let large = create_test_file(temp_dir.path(), "large.txt", &[b'a'; 65]);
+
+        let browser = DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]);
+        let transfer = FileTransfer::new(browser, 100 * 1024 * 1024).with_max_text_size(64);
+
+        assert!(matches!(
+            transfer.read_text(&binary),
+            Err(TransferError::NotText(_))
+        ));
+        assert!(matches!(
+            transfer.read_text(&large),
+            Err(TransferError::FileTooLarge {
+                size: 65,
+                limit: 64
+            })
+        ));
+        assert!(matches!(
+            transfer.read_text(temp_dir.path()),
+            Err(TransferError::IsADirectory(_))
+        ));
+
+        let content = "a".repeat(65);
+        let result = transfer.write_text(&temp_dir.path().join("new.txt"), &content, None);
+        assert!(matches!(result, Err(TransferError::FileTooLarge { .. })));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_download_streams_window() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(temp_dir.path(), "stream.bin", &[7u8; 100]);
+        let path = path.to_string_lossy().to_string();
+
+        let browser = DirectoryBrowser::allow_all();
+        let transfer = Arc::new(FileTransfer::new(browser, 100 * 1024 * 1024));
+        let first = transfer
+            .download_chunk_message(&path, 0, 10, false)
+            .unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let streams = Arc::new(DownloadStreams::new(tx));
+        let device_id = DeviceId::from_bytes([1u8; 16]);
+        let stream = DownloadStream {
+            path: path.clone(),
+            offset: 0,
+            chunk_size: 10,
+            window: 3,
+            verify: false,
+        };
+        streams.start(Arc::clone(&transfer), device_id, stream, &first);
+
+        // Two chunks join the first in flight, then the stream waits
+        for offset in [10, 20] {
+            match rx.recv().await.unwrap().1 {
+                Message::FileDownloadChunk(chunk) => assert_eq!(chunk.offset, offset),
+                other => panic!("Expected FileDownloadChunk, got {:?}", other),
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(rx.try_recv().is_err());
+
+        // Acking two chunks lets two more through
+        assert!(streams.ack(&device_id, &path, 20));
+        for offset in [30, 40] {
+            match rx.recv().await.unwrap().1 {
+                Message::FileDownloadChunk(chunk) => assert_eq!(chunk.offset, offset),
+                other => panic!("Expected FileDownloadChunk, got {:?}", other),
+            }
+        }
+        assert!(!streams.ack(&DeviceId::from_bytes([2u8; 16]), &path, 50));
+
+        // Without further acks, the stream gives up
+        tokio::time::sleep(DOWNLOAD_ACK_TIMEOUT + Duration::from_secs(1)).await;
+        assert!(streams.is_empty());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_download_streams_drain() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(temp_dir.path(), "stream.bin", &[7u8; 100]);
+        let path = path.to_string_lossy().to_string();
+
+        let browser = DirectoryBrowser::allow_all();
+        let transfer = Arc::new(FileTransfer::new(browser, 100 * 1024 * 1024));
+        let first = transfer
+            .download_chunk_message(&path, 0, 10, false)
+            .unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let streams = Arc::new(DownloadStreams::new(tx));
+        let stream = DownloadStream {
+            path: path.clone(),
+            offset: 0,
+            chunk_size: 10,
+            window: 2,
+            verify: false,
+        };
+        streams.start(
+            Arc::clone(&transfer),
+            DeviceId::from_bytes([1u8; 16]),
+            stream,
+            &first,
+        );
+        match rx.recv().await.unwrap().1 {
+            Message::FileDownloadChunk(chunk) => assert_eq!(chunk.offset, 10),
+            other => panic!("Expected FileDownloadChunk, got {:?}", other),
+        }
+
+        // The stream waiting for an ack stops without sending more chunks
+        assert_eq!(streams.drain(Duration::from_secs(1)).await, Some(1));
+        assert!(streams.is_empty());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_sync_uploads() {
+        let temp_dir = TempDir::new().unwrap();
+        let upload_dir = temp_dir.path().join("uploads");
+        let browser = DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]);
+        let transfer =
+            FileTransfer::new(browser, 100 * 1024 * 1024).with_temp_dir(upload_dir.clone());
+        assert_eq!(transfer.sync_uploads().unwrap(), 0);
+
+        let dest = temp_dir.path().join("upload.bin");
+        transfer.start_upload(&dest, 10, 0o644, false).unwrap();
+        transfer.write_chunk(&dest, 0, b"hello").unwrap();
+        assert_eq!(transfer.sync_uploads().unwrap(), 1);
+        assert_eq!(transfer.get_upload_status(&dest), Some((5, 10)));
+    }
 }
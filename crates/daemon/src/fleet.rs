@@ -0,0 +1,383 @@
+//! Fleet enrollment.
+//!
+//! With an enrollment token under `[fleet]`, a daemon provisions itself the
+//! first time it starts: it registers its device ID and public key with the
+//! fleet endpoint, which answers with the devices the daemon should trust
+//! and their permissions, in the same form as a provisioning file. Many
+//! machines can be set up from one image this way, without pairing each.
+//!
+//! ```text
+//! POST <endpoint>
+//! Authorization: Bearer <enrollment token>
+//!
+//! { "device_id": "a1:b2:...", "public_key": "base64...",
+//!   "hostname": "build-07", "version": "0.1.0" }
+//!
+//! 200 OK
+//! { "fleet": "ci-runners", "prune": false, "devices": [ ... ] }
+//! ```
+//!
+//! Once the answer has been applied, an enrollment record is written to the
+//! data directory and later starts skip enrollment. The signaling server
+//! ships a reference endpoint at `/fleet/enroll`.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use protocol::DeviceIdentity;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::config::FleetConfig;
+use crate::devices::{ProvisioningFile, ReconcileReport, TrustStore};
+use crate::files::PathPermissions;
+
+/// File name of the enrollment record inside the data directory.
+pub const ENROLLMENT_FILE_NAME: &str = "fleet-enrollment.json";
+
+/// Time allowed for the enrollment request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Body posted to the fleet endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EnrollmentRequest {
+    /// Fingerprint of the daemon's device ID.
+    pub device_id: String,
+    /// Base64-encoded Ed25519 public key of the daemon.
+    pub public_key: String,
+    /// Host name of the machine.
+    pub hostname: String,
+    /// Daemon version.
+    pub version: String,
+}
+
+/// The fleet endpoint's answer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EnrollmentResponse {
+    /// Name of the fleet the daemon joined, if the endpoint reports one.
+    #[serde(default)]
+    pub fleet: Option<String>,
+    /// Initial trusted devices and their permissions.
+    #[serde(flatten)]
+    pub provisioning: ProvisioningFile,
+}
+
+/// What is recorded once a daemon has enrolled.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EnrollmentRecord {
+    /// Endpoint the daemon enrolled with.
+    pub endpoint: String,
+    /// Name of the fleet, if the endpoint reported one.
+    pub fleet: Option<String>,
+    /// When the daemon enrolled (Unix timestamp).
+    pub enrolled_at: u64,
+}
+
+impl EnrollmentRecord {
+    /// Loads the enrollment record from `data_dir`, if the daemon enrolled.
+    pub fn load(data_dir: &Path) -> Result<Option<Self>> {
+        let path = data_dir.join(ENROLLMENT_FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read enrollment record: {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("Invalid enrollment record: {}", path.display()))
+    }
+
+    /// Writes the record to `data_dir`, replacing it atomically.
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        let path = data_dir.join(ENROLLMENT_FILE_NAME);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write enrollment record: {}", path.display()))?;
+        fs::rename(&temp_path, &path)
+            .with_context(|| format!("Failed to write enrollment record: {}", path.display()))
+    }
+}
+
+/// Client for the fleet endpoint.
+#[derive(Debug)]
+pub struct FleetClient {
+    endpoint: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl FleetClient {
+    /// Creates a client for `config`, or returns `None` if no enrollment
+    /// token is configured.
+    pub fn from_config(config: &FleetConfig) -> Result<Option<Self>> {
+        let (Some(endpoint), Some(token)) = (&config.endpoint, &config.enrollment_token) else {
+            return Ok(None);
+        };
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("remoshell-daemon/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Some(Self {
+            endpoint: endpoint.clone(),
+            token: token.clone(),
+            client,
+        }))
+    }
+
+    /// Returns the fleet endpoint.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Registers the daemon with the fleet and returns its initial devices.
+    pub async fn enroll(&self, identity: &DeviceIdentity) -> Result<EnrollmentResponse> {
+        let request = EnrollmentRequest {
+            device_id: identity.fingerprint(),
+            public_key: base64::engine::general_purpose::STANDARD
+                .encode(identity.public_key_bytes()),
+            hostname: sysinfo::System::host_name().unwrap_or_default(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .bearer_auth(&self.token)
+            .json(&request)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach fleet endpoint {}", self.endpoint))?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            anyhow::bail!("Fleet endpoint rejected the enrollment token ({})", status);
+        }
+        if !status.is_success() {
+            anyhow::bail!("Fleet endpoint answered {}", status);
+        }
+        response
+            .json()
+            .await
+            .context("Invalid answer from fleet endpoint")
+    }
+
+    /// Enrolls the daemon unless it already has, applying the devices the
+    /// fleet sends to the trust store and path permissions and saving both.
+    ///
+    /// Returns `None` if the daemon had already enrolled. Nothing is
+    /// recorded when enrollment fails, so the next start tries again.
+    pub async fn enroll_once(
+        &self,
+        identity: &DeviceIdentity,
+        data_dir: &Path,
+        trust_store: &TrustStore,
+        path_permissions: &PathPermissions,
+    ) -> Result<Option<ReconcileReport>> {
+        if EnrollmentRecord::load(data_dir)?.is_some() {
+            return Ok(None);
+        }
+
+        let response = self.enroll(identity).await?;
+        let report = response
+            .provisioning
+            .reconcile(trust_store, path_permissions)
+            .context("Fleet endpoint sent invalid devices")?;
+        if report.changed() {
+            trust_store.save()?;
+            path_permissions.save()?;
+        }
+
+        EnrollmentRecord {
+            endpoint: self.endpoint.clone(),
+            fleet: response.fleet.clone(),
+            enrolled_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+        .save(data_dir)?;
+
+        info!(
+            target: "audit",
+            endpoint = %self.endpoint,
+            fleet = response.fleet.as_deref().unwrap_or(""),
+            devices = response.provisioning.devices.len(),
+            "Enrolled with fleet"
+        );
+        Ok(Some(report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::ProvisionedDevice;
+    use crate::devices::TrustLevel;
+    use crate::files::permissions::PermissionLevel;
+    use std::collections::BTreeSet;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// A request received by [`serve`].
+    struct Received {
+        authorization: Option<String>,
+        body: Vec<u8>,
+    }
+
+    /// Answers one request with `status` and `body` and reports it.
+    async fn serve(status: u16, body: String) -> (String, mpsc::UnboundedReceiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/fleet/enroll", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut authorization = None;
+            let mut length = 0;
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                let Some((key, value)) = line.trim_end().split_once(": ") else {
+                    break;
+                };
+                if key.eq_ignore_ascii_case("authorization") {
+                    authorization = Some(value.to_string());
+                } else if key.eq_ignore_ascii_case("content-length") {
+                    length = value.parse().unwrap();
+                }
+            }
+            let mut request = vec![0; length];
+            reader.read_exact(&mut request).await.unwrap();
+            let _ = tx.send(Received {
+                authorization,
+                body: request,
+            });
+
+            let response = format!(
+                "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            reader
+                .get_mut()
+                .write_all(response.as_bytes())
+                .await
+                .unwrap();
+        });
+        (url, rx)
+    }
+
+    fn client(endpoint: &str) -> FleetClient {
+        FleetClient::from_config(&FleetConfig {
+            endpoint: Some(endpoint.to_string()),
+            enrollment_token: Some("tok3n".to_string()),
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    fn create_stores(temp_dir: &TempDir) -> (TrustStore, PathPermissions) {
+        (
+            TrustStore::new(temp_dir.path().join("trusted_devices.json")),
+            PathPermissions::new(
+                temp_dir.path().join("permissions.json"),
+                vec![temp_dir.path().to_path_buf()],
+            ),
+        )
+    }
+
+    #[test]
+    fn test_no_client_without_token() {
+        let config = FleetConfig {
+            endpoint: Some("https://fleet.example.com/enroll".to_string()),
+            enrollment_token: None,
+        };
+        assert!(FleetClient::from_config(&config).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_enroll_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let (trust_store, permissions) = create_stores(&temp_dir);
+        let daemon = DeviceIdentity::generate();
+        let laptop = DeviceIdentity::generate();
+        let answer = EnrollmentResponse {
+            fleet: Some("ci-runners".to_string()),
+            provisioning: ProvisioningFile {
+                prune: false,
+                devices: vec![ProvisionedDevice {
+                    device_id: laptop.fingerprint(),
+                    name: "ops-laptop".to_string(),
+                    public_key: base64::engine::general_purpose::STANDARD
+                        .encode(laptop.public_key_bytes()),
+                    trust_level: TrustLevel::Trusted,
+                    capabilities: BTreeSet::from(["agent-forwarding".to_string()]),
+                    default_level: PermissionLevel::Read,
+                    paths: Vec::new(),
+                }],
+            },
+        };
+        let (url, mut requests) = serve(200, serde_json::to_string(&answer).unwrap()).await;
+        let fleet = client(&url);
+
+        let report = fleet
+            .enroll_once(&daemon, temp_dir.path(), &trust_store, &permissions)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(report.changed());
+
+        let received = requests.recv().await.unwrap();
+        assert_eq!(received.authorization.as_deref(), Some("Bearer tok3n"));
+        let request: EnrollmentRequest = serde_json::from_slice(&received.body).unwrap();
+        assert_eq!(request.device_id, daemon.fingerprint());
+
+        let device = trust_store.get_device(laptop.device_id()).unwrap().unwrap();
+        assert!(device.has_capability("agent-forwarding"));
+        assert!(permissions
+            .can_device_read(laptop.device_id(), temp_dir.path())
+            .unwrap());
+        // Both stores were saved
+        let (reloaded, _) = create_stores(&temp_dir);
+        reloaded.load().unwrap();
+        assert!(reloaded.is_trusted(laptop.device_id()).unwrap());
+
+        let record = EnrollmentRecord::load(temp_dir.path()).unwrap().unwrap();
+        assert_eq!(record.endpoint, url);
+        assert_eq!(record.fleet.as_deref(), Some("ci-runners"));
+
+        // Later starts do not contact the endpoint again
+        assert!(fleet
+            .enroll_once(&daemon, temp_dir.path(), &trust_store, &permissions)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_token_is_not_recorded() {
+        let temp_dir = TempDir::new().unwrap();
+        let (trust_store, permissions) = create_stores(&temp_dir);
+        let (url, _requests) = serve(401, String::new()).await;
+
+        let err = client(&url)
+            .enroll_once(
+                &DeviceIdentity::generate(),
+                temp_dir.path(),
+                &trust_store,
+                &permissions,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("rejected the enrollment token"));
+        assert!(EnrollmentRecord::load(temp_dir.path()).unwrap().is_none());
+        assert!(trust_store.list_devices().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,730 @@
+//! Honeypot mode for untrusted devices.
+//!
+//! With `security.honeypot.enabled`, requests from unknown, pending and
+//! revoked devices are not rejected. Approval requests appear to succeed, and
+//! session, file and host requests are answered by a fake shell with canned
+//! output. Nothing reaches a real PTY or the file system. Every attempt is
+//! logged under the `honeypot` target and appended to the honeypot log, and
+//! each answer is delayed to slow automated clients down.
+
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dashmap::DashMap;
+use protocol::messages::{
+    DataStream, DeviceApproved, ErrorCode, ErrorMessage, FileEntry, FileEntryDetails,
+    FileEntryType, FileListDetail, FileListResponse, Message, SessionCloseReason, SessionClosed,
+    SessionCreated, SessionData,
+};
+use protocol::DeviceId;
+use rand::Rng;
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::config::HoneypotConfig;
+
+/// File name of the honeypot log inside the data directory.
+pub const LOG_FILE_NAME: &str = "honeypot.jsonl";
+
+/// Maximum number of fake sessions open at once.
+const MAX_SESSIONS: usize = 64;
+
+/// Maximum length of a command line; further input is dropped.
+const MAX_LINE_LEN: usize = 4096;
+
+/// Modification time reported for fake files.
+const FAKE_MTIME: u64 = 1_700_000_000;
+
+/// Directories of the fake file system; names ending in `/` are directories.
+const FAKE_DIRS: &[(&str, &[&str])] = &[
+    (
+        "/",
+        &[
+            "bin/", "boot/", "dev/", "etc/", "home/", "lib/", "opt/", "root/", "srv/", "tmp/",
+            "usr/", "var/",
+        ],
+    ),
+    ("/etc", &["hostname", "passwd", "shadow", "ssh/"]),
+    ("/home", &["admin/"]),
+    ("/root", &[".bash_history", ".ssh/", "backup.tar.gz"]),
+    ("/root/.ssh", &["authorized_keys"]),
+];
+
+/// Contents of the fake files that can be read.
+const FAKE_FILES: &[(&str, &str)] = &[
+    (
+        "/etc/passwd",
+        "root:x:0:0:root:/root:/bin/bash\n\
+         daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+         www-data:x:33:33:www-data:/var/www:/usr/sbin/nologin\n\
+         admin:x:1000:1000:admin:/home/admin:/bin/bash\n",
+    ),
+    (
+        "/root/.bash_history",
+        "ls\ncd /srv\nsystemctl restart nginx\n",
+    ),
+    ("/root/.ssh/authorized_keys", ""),
+];
+
+/// One attempt, as written to the honeypot log.
+#[derive(Debug, Serialize)]
+struct Attempt<'a> {
+    timestamp: u64,
+    device_id: String,
+    event: &'a str,
+    session_id: Option<&'a str>,
+    detail: &'a str,
+}
+
+/// State of a fake shell session.
+#[derive(Debug)]
+struct FakeSession {
+    device_id: DeviceId,
+    cwd: String,
+    line: Vec<u8>,
+    greeted: bool,
+    last_was_cr: bool,
+}
+
+/// Output of a fake shell for a chunk of input.
+#[derive(Debug, Default)]
+struct Feed {
+    output: Vec<u8>,
+    commands: Vec<String>,
+    exited: bool,
+}
+
+/// Answers untrusted devices with a fake shell and logs what they try.
+#[derive(Debug)]
+pub struct Honeypot {
+    hostname: String,
+    delay: Duration,
+    log_file: PathBuf,
+    sessions: DashMap<String, FakeSession>,
+}
+
+impl Honeypot {
+    /// Creates a honeypot for `config`, logging to `data_dir` unless the
+    /// configuration names a log file.
+    pub fn new(config: &HoneypotConfig, data_dir: &Path) -> Self {
+        Self {
+            hostname: config.hostname.clone(),
+            delay: Duration::from_millis(config.delay_ms),
+            log_file: config
+                .log_file
+                .clone()
+                .unwrap_or_else(|| data_dir.join(LOG_FILE_NAME)),
+            sessions: DashMap::new(),
+        }
+    }
+
+    /// Returns whether the honeypot answers `message` for untrusted devices.
+    ///
+    /// Approval, handshake and control messages keep their normal handling.
+    pub fn intercepts(message: &Message) -> bool {
+        matches!(
+            message,
+            Message::SessionCreate(_)
+                | Message::SessionAttach(_)
+                | Message::SessionDetach(_)
+                | Message::SessionKill(_)
+                | Message::SessionResize(_)
+                | Message::SessionSignal(_)
+                | Message::UpdateSessionEnv(_)
+                | Message::SessionData(_)
+                | Message::SessionMetadataRequest(_)
+                | Message::SessionSearchRequest(_)
+                | Message::ListTemplates(_)
+                | Message::AgentData(_)
+                | Message::AgentClose(_)
+                | Message::DisplayData(_)
+                | Message::DisplayClose(_)
+                | Message::FileListRequest(_)
+                | Message::FileDownloadRequest(_)
+                | Message::FileDownloadAck(_)
+                | Message::FileUploadStart(_)
+                | Message::FileUploadChunk(_)
+                | Message::FileUploadComplete(_)
+                | Message::FileCopyGrantRequest(_)
+                | Message::FileCopyRequest(_)
+                | Message::FileReadText(_)
+                | Message::FileWriteText(_)
+                | Message::FileRootsRequest(_)
+                | Message::FileThumbnailRequest(_)
+                | Message::FileTailRequest(_)
+                | Message::FileTailStop(_)
+                | Message::SystemInfoRequest(_)
+                | Message::ProcessListRequest(_)
+                | Message::ProcessKill(_)
+                | Message::PowerActionRequest(_)
+                | Message::PowerActionConfirm(_)
+                | Message::ConfigRequest(_)
+                | Message::ConfigPatch(_)
+                | Message::SecurityKeyAssertion(_)
+        )
+    }
+
+    /// Answers `message` from an untrusted device.
+    pub async fn handle(&self, message: Message, device_id: &DeviceId) -> Option<Message> {
+        tokio::time::sleep(self.delay).await;
+
+        match message {
+            Message::SessionCreate(req) => {
+                let detail = format!(
+                    "shell={:?} cwd={:?} template={:?} env={:?}",
+                    req.shell, req.cwd, req.template, req.env
+                );
+                if self.sessions.len() >= MAX_SESSIONS {
+                    self.record(device_id, None, "session_create", &detail);
+                    return Some(denied("Too many sessions"));
+                }
+                let session_id = uuid::Uuid::new_v4().to_string();
+                self.record(device_id, Some(&session_id), "session_create", &detail);
+                self.sessions.insert(
+                    session_id.clone(),
+                    FakeSession {
+                        device_id: *device_id,
+                        cwd: "/root".to_string(),
+                        line: Vec::new(),
+                        greeted: false,
+                        last_was_cr: false,
+                    },
+                );
+                Some(Message::SessionCreated(SessionCreated {
+                    session_id,
+                    pid: rand::thread_rng().gen_range(1000..40000),
+                }))
+            }
+            Message::SessionData(data) if data.stream == DataStream::Stdin => {
+                self.handle_input(data, device_id)
+            }
+            Message::SessionKill(req) => {
+                self.record(device_id, Some(&req.session_id), "session_kill", "");
+                self.sessions.remove(&req.session_id)?;
+                Some(Message::SessionClosed(SessionClosed {
+                    session_id: req.session_id,
+                    exit_code: None,
+                    signal: req.signal,
+                    reason: Some(SessionCloseReason::Killed.description().to_string()),
+                    close_reason: Some(SessionCloseReason::Killed),
+                }))
+            }
+            Message::FileListRequest(req) => {
+                self.record(device_id, None, "file_list", &req.path);
+                let entries = fake_dir(&resolve("/", &req.path))
+                    .unwrap_or(&[])
+                    .iter()
+                    .filter(|name| req.include_hidden || !name.starts_with('.'))
+                    .map(|name| {
+                        let mut entry = fake_entry(&req.path, name);
+                        if req.detail != FileListDetail::Basic {
+                            entry.details = Some(fake_details());
+                        }
+                        entry
+                    })
+                    .collect();
+                Some(Message::FileListResponse(FileListResponse {
+                    path: req.path,
+                    entries,
+                }))
+            }
+            Message::FileDownloadRequest(req) => {
+                self.record(device_id, None, "file_download", &req.path);
+                Some(denied("Permission denied"))
+            }
+            Message::FileTailRequest(req) => {
+                self.record(device_id, None, "file_tail", &req.path);
+                Some(denied("Permission denied"))
+            }
+            Message::FileUploadStart(req) => {
+                let detail = format!("path={} size={}", req.path, req.size);
+                self.record(device_id, None, "file_upload", &detail);
+                Some(denied("Permission denied"))
+            }
+            // Attaching, resizing and the like need no answer
+            Message::SessionAttach(_)
+            | Message::SessionDetach(_)
+            | Message::SessionResize(_)
+            | Message::SessionSignal(_)
+            | Message::UpdateSessionEnv(_)
+            | Message::SessionData(_)
+            | Message::AgentData(_)
+            | Message::AgentClose(_)
+            | Message::DisplayData(_)
+            | Message::DisplayClose(_)
+            | Message::FileDownloadAck(_)
+            | Message::FileTailStop(_)
+            | Message::FileUploadChunk(_) => None,
+            other => {
+                self.record(device_id, None, other.name(), "");
+                Some(denied("Permission denied"))
+            }
+        }
+    }
+
+    /// Returns a fake approval for a device the trust store rejected.
+    pub fn approve(&self, device_id: &DeviceId, claimed_id: String, name: &str) -> Message {
+        self.record(device_id, None, "approval_request", name);
+        Message::DeviceApproved(DeviceApproved {
+            device_id: claimed_id,
+            expires_at: None,
+            allowed_capabilities: vec!["shell".to_string(), "file-transfer".to_string()],
+        })
+    }
+
+    fn handle_input(&self, data: SessionData, device_id: &DeviceId) -> Option<Message> {
+        let feed = {
+            let mut session = self.sessions.get_mut(&data.session_id)?;
+            if session.device_id != *device_id {
+                return None;
+            }
+            self.feed(&mut session, &data.data)
+        };
+        for command in &feed.commands {
+            self.record(device_id, Some(&data.session_id), "command", command);
+        }
+
+        if feed.exited {
+            self.sessions.remove(&data.session_id);
+            return Some(Message::SessionClosed(SessionClosed {
+                session_id: data.session_id,
+                exit_code: Some(0),
+                signal: None,
+                reason: Some("logout".to_string()),
+                close_reason: Some(SessionCloseReason::Exited),
+            }));
+        }
+        Some(Message::SessionData(SessionData {
+            session_id: data.session_id,
+            stream: DataStream::Stdout,
+            data: feed.output,
+        }))
+    }
+
+    /// Runs a chunk of terminal input through the fake shell's line editor.
+    fn feed(&self, session: &mut FakeSession, input: &[u8]) -> Feed {
+        let mut feed = Feed::default();
+        if !session.greeted {
+            session.greeted = true;
+            let banner = format!(
+                "Welcome to Ubuntu 22.04.3 LTS (GNU/Linux 5.15.0-91-generic x86_64)\r\n\r\n{}",
+                self.prompt(&session.cwd)
+            );
+            feed.output.extend_from_slice(banner.as_bytes());
+        }
+
+        for &byte in input {
+            let after_cr = std::mem::replace(&mut session.last_was_cr, byte == b'\r');
+            match byte {
+                b'\n' if after_cr => {}
+                b'\r' | b'\n' => {
+                    feed.output.extend_from_slice(b"\r\n");
+                    let line = String::from_utf8_lossy(&std::mem::take(&mut session.line))
+                        .trim()
+                        .to_string();
+                    if !line.is_empty() {
+                        feed.commands.push(line.clone());
+                    }
+                    match self.run(&mut session.cwd, &line) {
+                        Some(output) => {
+                            feed.output
+                                .extend_from_slice(output.replace('\n', "\r\n").as_bytes());
+                        }
+                        None => {
+                            feed.exited = true;
+                            return feed;
+                        }
+                    }
+                    feed.output
+                        .extend_from_slice(self.prompt(&session.cwd).as_bytes());
+                }
+                // Backspace
+                0x08 | 0x7f if session.line.pop().is_some() => {
+                    feed.output.extend_from_slice(b"\x08 \x08");
+                }
+                // Ctrl-C
+                0x03 => {
+                    session.line.clear();
+                    feed.output.extend_from_slice(b"^C\r\n");
+                    feed.output
+                        .extend_from_slice(self.prompt(&session.cwd).as_bytes());
+                }
+                // Ctrl-D
+                0x04 if session.line.is_empty() => {
+                    feed.exited = true;
+                    return feed;
+                }
+                byte if byte >= 0x20 && byte != 0x7f && session.line.len() < MAX_LINE_LEN => {
+                    session.line.push(byte);
+                    feed.output.push(byte);
+                }
+                _ => {}
+            }
+        }
+        feed
+    }
+
+    /// Returns the canned output of `line`, or `None` if it ends the session.
+    fn run(&self, cwd: &mut String, line: &str) -> Option<String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Some(String::new());
+        };
+        let args: Vec<&str> = words.collect();
+
+        let output = match command {
+            "exit" | "logout" => return None,
+            "whoami" => "root\n".to_string(),
+            "id" => "uid=0(root) gid=0(root) groups=0(root)\n".to_string(),
+            "hostname" => format!("{}\n", self.hostname),
+            "uname" if args.contains(&"-a") => format!(
+                "Linux {} 5.15.0-91-generic #101-Ubuntu SMP Tue Nov 14 13:30:08 UTC 2023 x86_64 x86_64 x86_64 GNU/Linux\n",
+                self.hostname
+            ),
+            "uname" => "Linux\n".to_string(),
+            "uptime" => " 10:42:17 up 41 days,  3:12,  1 user,  load average: 0.08, 0.03, 0.01\n"
+                .to_string(),
+            "pwd" => format!("{}\n", cwd),
+            "echo" => format!("{}\n", args.join(" ")),
+            "cd" => {
+                let target = resolve(cwd, args.first().copied().unwrap_or("~"));
+                if fake_dir(&target).is_some() || is_fake_dir(&target) {
+                    *cwd = target;
+                    String::new()
+                } else {
+                    format!("-bash: cd: {}: No such file or directory\n", args[0])
+                }
+            }
+            "ls" | "ll" => {
+                let show_hidden = args.iter().any(|a| a.starts_with('-') && a.contains('a'));
+                let target = args.iter().find(|a| !a.starts_with('-')).copied();
+                let path = resolve(cwd, target.unwrap_or("."));
+                match fake_dir(&path) {
+                    Some(entries) => {
+                        let names: Vec<&str> = entries
+                            .iter()
+                            .filter(|name| show_hidden || !name.starts_with('.'))
+                            .map(|name| name.trim_end_matches('/'))
+                            .collect();
+                        if names.is_empty() {
+                            String::new()
+                        } else {
+                            format!("{}\n", names.join("  "))
+                        }
+                    }
+                    None if is_fake_dir(&path) => String::new(),
+                    None => format!(
+                        "ls: cannot access '{}': No such file or directory\n",
+                        target.unwrap_or(".")
+                    ),
+                }
+            }
+            "cat" => args
+                .iter()
+                .map(|arg| {
+                    let path = resolve(cwd, arg);
+                    match path.as_str() {
+                        "/etc/hostname" => format!("{}\n", self.hostname),
+                        "/etc/shadow" | "/root/backup.tar.gz" => {
+                            format!("cat: {}: Permission denied\n", arg)
+                        }
+                        _ => FAKE_FILES
+                            .iter()
+                            .find(|(file, _)| *file == path)
+                            .map(|(_, content)| content.to_string())
+                            .unwrap_or_else(|| {
+                                format!("cat: {}: No such file or directory\n", arg)
+                            }),
+                    }
+                })
+                .collect(),
+            "wget" | "curl" => format!(
+                "{}: unable to resolve host address '{}'\n",
+                command,
+                args.iter()
+                    .find(|a| !a.starts_with('-'))
+                    .copied()
+                    .unwrap_or("")
+            ),
+            _ => format!("-bash: {}: command not found\n", command),
+        };
+        Some(output)
+    }
+
+    fn prompt(&self, cwd: &str) -> String {
+        let shown = match cwd.strip_prefix("/root") {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+            _ => cwd.to_string(),
+        };
+        format!("root@{}:{}# ", self.hostname, shown)
+    }
+
+    /// Logs an attempt and appends it to the honeypot log.
+    fn record(&self, device_id: &DeviceId, session_id: Option<&str>, event: &str, detail: &str) {
+        warn!(
+            target: "honeypot",
+            device_id = %device_id,
+            session_id,
+            event,
+            detail,
+            "Honeypot attempt"
+        );
+
+        let attempt = Attempt {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            device_id: device_id.to_string(),
+            event,
+            session_id,
+            detail,
+        };
+        let result = serde_json::to_string(&attempt)
+            .map_err(std::io::Error::from)
+            .and_then(|line| {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .mode(0o600)
+                    .open(&self.log_file)?;
+                writeln!(file, "{}", line)
+            });
+        if let Err(e) = result {
+            error!(
+                path = %self.log_file.display(),
+                error = %e,
+                "Failed to write honeypot log"
+            );
+        }
+    }
+}
+
+/// Returns a permission error as sent to the device.
+fn denied(message: &str) -> Message {
+    Message::Error(ErrorMessage::new(ErrorCode::PermissionDenied, message))
+}
+
+/// Resolves `path` against `cwd` in the fake file system.
+fn resolve(cwd: &str, path: &str) -> String {
+    let joined = if path == "~" || path.starts_with("~/") {
+        format!("/root{}", &path[1..])
+    } else if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", cwd, path)
+    };
+
+    let mut parts = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Returns the entries of a fake directory with known contents.
+fn fake_dir(path: &str) -> Option<&'static [&'static str]> {
+    FAKE_DIRS
+        .iter()
+        .find(|(dir, _)| *dir == path)
+        .map(|(_, entries)| *entries)
+}
+
+/// Returns whether `path` is listed as a directory in its parent.
+fn is_fake_dir(path: &str) -> bool {
+    let Some((parent, name)) = path.rsplit_once('/') else {
+        return false;
+    };
+    let parent = if parent.is_empty() { "/" } else { parent };
+    fake_dir(parent).is_some_and(|entries| entries.contains(&format!("{}/", name).as_str()))
+}
+
+/// Everything in the fake file system belongs to root.
+fn fake_details() -> FileEntryDetails {
+    FileEntryDetails {
+        uid: 0,
+        gid: 0,
+        owner: Some("root".to_string()),
+        group: Some("root".to_string()),
+        symlink_target: None,
+        xattrs: Vec::new(),
+    }
+}
+
+fn fake_entry(dir: &str, name: &str) -> FileEntry {
+    match name.strip_suffix('/') {
+        Some(name) => FileEntry {
+            name: name.to_string(),
+            entry_type: FileEntryType::Directory,
+            size: 0,
+            mode: 0o755,
+            modified: FAKE_MTIME,
+            details: None,
+        },
+        None => {
+            let path = resolve(dir, name);
+            let size = FAKE_FILES
+                .iter()
+                .find(|(file, _)| *file == path)
+                .map(|(_, content)| content.len() as u64)
+                .unwrap_or(48_213);
+            FileEntry {
+                name: name.to_string(),
+                entry_type: FileEntryType::File,
+                size,
+                mode: 0o644,
+                modified: FAKE_MTIME,
+                details: None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::messages::{FileListRequest, Ping, SessionCreate};
+    use tempfile::TempDir;
+
+    fn honeypot(temp_dir: &TempDir) -> Honeypot {
+        let config = HoneypotConfig {
+            enabled: true,
+            delay_ms: 0,
+            ..HoneypotConfig::default()
+        };
+        Honeypot::new(&config, temp_dir.path())
+    }
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([5u8; 16])
+    }
+
+    async fn create_session(honeypot: &Honeypot) -> String {
+        let create = Message::SessionCreate(SessionCreate::default());
+        match honeypot.handle(create, &device()).await {
+            Some(Message::SessionCreated(created)) => created.session_id,
+            other => panic!("unexpected answer: {:?}", other),
+        }
+    }
+
+    async fn type_input(honeypot: &Honeypot, session_id: &str, input: &str) -> Option<Message> {
+        let data = Message::SessionData(SessionData {
+            session_id: session_id.to_string(),
+            stream: DataStream::Stdin,
+            data: input.as_bytes().to_vec(),
+        });
+        honeypot.handle(data, &device()).await
+    }
+
+    async fn output(honeypot: &Honeypot, session_id: &str, input: &str) -> String {
+        match type_input(honeypot, session_id, input).await {
+            Some(Message::SessionData(data)) => String::from_utf8(data.data).unwrap(),
+            other => panic!("unexpected answer: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_intercepts() {
+        assert!(Honeypot::intercepts(&Message::FileListRequest(
+            FileListRequest {
+                path: "/".to_string(),
+                include_hidden: false,
+                detail: FileListDetail::Basic,
+            }
+        )));
+        assert!(!Honeypot::intercepts(&Message::Ping(Ping {
+            timestamp: 0,
+            payload: Vec::new(),
+        })));
+    }
+
+    #[tokio::test]
+    async fn test_fake_shell() {
+        let temp_dir = TempDir::new().unwrap();
+        let honeypot = honeypot(&temp_dir);
+        let session_id = create_session(&honeypot).await;
+
+        // The first input is preceded by a banner and prompt, and echoed
+        let first = output(&honeypot, &session_id, "who").await;
+        assert!(first.starts_with("Welcome to Ubuntu"));
+        assert!(first.ends_with("root@srv01:~# who"));
+
+        assert_eq!(
+            output(&honeypot, &session_id, "ami\r").await,
+            "ami\r\nroot\r\nroot@srv01:~# "
+        );
+        assert_eq!(
+            output(&honeypot, &session_id, "cd /etc\r\nls\r\n").await,
+            "cd /etc\r\nroot@srv01:/etc# ls\r\nhostname  passwd  shadow  ssh\r\nroot@srv01:/etc# "
+        );
+        assert_eq!(
+            output(&honeypot, &session_id, "nmapp\x7f\r").await,
+            "nmapp\x08 \x08\r\n-bash: nmap: command not found\r\nroot@srv01:/etc# "
+        );
+
+        assert!(matches!(
+            type_input(&honeypot, &session_id, "exit\r").await,
+            Some(Message::SessionClosed(_))
+        ));
+        assert!(type_input(&honeypot, &session_id, "id\r").await.is_none());
+
+        // Every command was logged
+        let log = std::fs::read_to_string(temp_dir.path().join(LOG_FILE_NAME)).unwrap();
+        let commands: Vec<String> = log
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|attempt| attempt["event"] == "command")
+            .map(|attempt| attempt["detail"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(commands, ["whoami", "cd /etc", "ls", "nmap", "exit"]);
+    }
+
+    #[tokio::test]
+    async fn test_fake_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let honeypot = honeypot(&temp_dir);
+
+        let list = Message::FileListRequest(FileListRequest {
+            path: "/root".to_string(),
+            include_hidden: false,
+            detail: FileListDetail::Extended,
+        });
+        let Some(Message::FileListResponse(response)) = honeypot.handle(list, &device()).await
+        else {
+            panic!("expected a listing");
+        };
+        let names: Vec<&str> = response.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["backup.tar.gz"]);
+        let details = response.entries[0].details.as_ref().unwrap();
+        assert_eq!(details.owner.as_deref(), Some("root"));
+
+        // The real data directory is never listed
+        let list = Message::FileListRequest(FileListRequest {
+            path: temp_dir.path().to_string_lossy().into_owned(),
+            include_hidden: true,
+            detail: FileListDetail::Basic,
+        });
+        let Some(Message::FileListResponse(response)) = honeypot.handle(list, &device()).await
+        else {
+            panic!("expected a listing");
+        };
+        assert!(response.entries.is_empty());
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("/root", ".."), "/");
+        assert_eq!(resolve("/root", "~/.ssh"), "/root/.ssh");
+        assert_eq!(resolve("/etc", "../home/./admin/"), "/home/admin");
+        assert!(is_fake_dir("/home/admin"));
+        assert!(!is_fake_dir("/etc/passwd"));
+    }
+}
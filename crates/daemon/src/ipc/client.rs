@@ -108,8 +108,20 @@ impl IpcClient {
 
     /// Internal send implementation without timeout.
     async fn send_internal(&mut self, request: IpcRequest) -> Result<IpcResponse, IpcError> {
-        // Serialize and send request
-        let mut json = serde_json::to_string(&request).map_err(IpcError::Json)?;
+        self.write_request(&request).await?;
+
+        match self.recv().await? {
+            Some(response) => Ok(response),
+            None => Err(IpcError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "daemon closed connection",
+            ))),
+        }
+    }
+
+    /// Serialize and write a request without waiting for a response.
+    async fn write_request(&mut self, request: &IpcRequest) -> Result<(), IpcError> {
+        let mut json = serde_json::to_string(request).map_err(IpcError::Json)?;
         json.push('\n');
 
         self.writer
@@ -118,7 +130,14 @@ impl IpcClient {
             .map_err(IpcError::Io)?;
         self.writer.flush().await.map_err(IpcError::Io)?;
 
-        // Read response
+        Ok(())
+    }
+
+    /// Read the next response from the daemon without a timeout.
+    ///
+    /// This is used for streaming requests where the daemon sends multiple
+    /// responses. Returns `None` if the daemon closed the connection.
+    pub async fn recv(&mut self) -> Result<Option<IpcResponse>, IpcError> {
         let mut line = String::new();
         let bytes_read = self
             .reader
@@ -127,14 +146,11 @@ impl IpcClient {
             .map_err(IpcError::Io)?;
 
         if bytes_read == 0 {
-            return Err(IpcError::Io(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                "daemon closed connection",
-            )));
+            return Ok(None);
         }
 
         let response = serde_json::from_str(line.trim()).map_err(IpcError::Json)?;
-        Ok(response)
+        Ok(Some(response))
     }
 
     /// Send a ping request to check if the daemon is responsive.
@@ -176,6 +192,69 @@ impl IpcClient {
         self.send(IpcRequest::KillSession { session_id, signal })
             .await
     }
+
+    /// Ask the daemon to re-read its trust store from disk.
+    pub async fn reload_trust_store(&mut self) -> Result<IpcResponse, IpcError> {
+        self.send(IpcRequest::ReloadTrustStore).await
+    }
+
+    /// Ask the daemon whether a pairing code has been consumed.
+    pub async fn pairing_status(&mut self, code: String) -> Result<IpcResponse, IpcError> {
+        self.send(IpcRequest::PairingStatus { code }).await
+    }
+
+    /// Ask the daemon which addresses its network endpoints are bound to.
+    pub async fn network_info(&mut self) -> Result<IpcResponse, IpcError> {
+        self.send(IpcRequest::NetworkInfo).await
+    }
+
+    /// List the other daemons of the mesh.
+    pub async fn mesh_status(&mut self) -> Result<IpcResponse, IpcError> {
+        self.send(IpcRequest::MeshStatus).await
+    }
+
+    /// Turn maintenance mode on or off.
+    pub async fn set_maintenance(
+        &mut self,
+        enabled: bool,
+        message: Option<String>,
+    ) -> Result<IpcResponse, IpcError> {
+        self.send(IpcRequest::SetMaintenance { enabled, message })
+            .await
+    }
+
+    /// List the sessions waiting for the operator to allow them.
+    pub async fn list_session_confirmations(&mut self) -> Result<IpcResponse, IpcError> {
+        self.send(IpcRequest::ListSessionConfirmations).await
+    }
+
+    /// Allow or deny a session waiting for confirmation.
+    pub async fn answer_session_confirmation(
+        &mut self,
+        id: u64,
+        allow: bool,
+    ) -> Result<IpcResponse, IpcError> {
+        self.send(IpcRequest::AnswerSessionConfirmation { id, allow })
+            .await
+    }
+
+    /// Subscribe to the daemon's log stream.
+    ///
+    /// After this call, use [`IpcClient::recv`] to read `LogLine` responses.
+    /// Without `follow`, the stream is terminated by a `LogsEnd` response.
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - Optional minimum level to include (e.g. "warn").
+    /// * `follow` - Whether to keep streaming new lines as they are logged.
+    pub async fn subscribe_logs(
+        &mut self,
+        level: Option<String>,
+        follow: bool,
+    ) -> Result<(), IpcError> {
+        self.write_request(&IpcRequest::LogsSubscribe { level, follow })
+            .await
+    }
 }
 
 #[cfg(test)]
@@ -234,6 +313,8 @@ mod tests {
                 uptime_secs: 100,
                 session_count: 2,
                 device_count: 1,
+                recovered_from_crash: None,
+                maintenance: None,
             })
             .await
             .unwrap();
@@ -250,6 +331,7 @@ mod tests {
                 uptime_secs,
                 session_count,
                 device_count,
+                ..
             } => {
                 assert!(running);
                 assert_eq!(uptime_secs, 100);
@@ -303,6 +385,8 @@ mod tests {
                     id: "sess-1".to_string(),
                     connected_at: 1000,
                     peer_id: Some("peer-1".to_string()),
+                    cwd: None,
+                    last_exit_code: None,
                 }],
             })
             .await
@@ -405,6 +489,53 @@ mod tests {
         server_handle.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_client_subscribe_logs() {
+        use crate::logging::LogLine;
+
+        let temp_dir = tempdir().unwrap();
+        let socket_path = temp_dir.path().join("test.sock");
+
+        let server = IpcServer::bind(&socket_path).await.unwrap();
+
+        let server_handle = tokio::spawn(async move {
+            let mut conn = server.accept().await.unwrap();
+            let request = conn.read_request().await.unwrap().unwrap();
+            assert_eq!(
+                request,
+                IpcRequest::LogsSubscribe {
+                    level: Some("warn".to_string()),
+                    follow: false,
+                }
+            );
+            conn.send_response(&IpcResponse::LogLine(LogLine {
+                timestamp_ms: 1,
+                level: "WARN".to_string(),
+                target: "test".to_string(),
+                message: "first".to_string(),
+            }))
+            .await
+            .unwrap();
+            conn.send_response(&IpcResponse::LogsEnd).await.unwrap();
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut client = IpcClient::connect(&socket_path).await.unwrap();
+        client
+            .subscribe_logs(Some("warn".to_string()), false)
+            .await
+            .unwrap();
+
+        match client.recv().await.unwrap() {
+            Some(IpcResponse::LogLine(line)) => assert_eq!(line.message, "first"),
+            other => panic!("Expected LogLine response, got {:?}", other),
+        }
+        assert_eq!(client.recv().await.unwrap(), Some(IpcResponse::LogsEnd));
+
+        server_handle.await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_client_timeout() {
         let temp_dir = tempdir().unwrap();
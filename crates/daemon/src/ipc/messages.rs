@@ -3,8 +3,16 @@
 //! This module defines the request and response types used for communication
 //! between the CLI and the daemon over Unix Domain Sockets.
 
+use std::net::SocketAddr;
+
 use serde::{Deserialize, Serialize};
 
+use crate::devices::PairingCodeStatus;
+use crate::logging::LogLine;
+use crate::maintenance::MaintenanceState;
+use crate::mesh::MeshRecord;
+use crate::orchestrator::CrashRecovery;
+
 /// Requests that can be sent from the CLI to the daemon.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum IpcRequest {
@@ -24,6 +32,43 @@ pub enum IpcRequest {
         /// Common values: 1 (SIGHUP), 9 (SIGKILL), 15 (SIGTERM).
         signal: Option<i32>,
     },
+    /// Stream buffered (and optionally live) daemon log lines.
+    ///
+    /// The daemon replies with a sequence of `LogLine` responses. Without
+    /// `follow`, the stream ends with `LogsEnd`.
+    LogsSubscribe {
+        /// Minimum level to include (e.g. "warn"). Defaults to all levels.
+        level: Option<String>,
+        /// Keep the connection open and stream new lines as they are logged.
+        follow: bool,
+    },
+    /// Re-read the trust store from disk after the CLI changed it.
+    ReloadTrustStore,
+    /// Report whether a pairing code has been consumed.
+    PairingStatus {
+        /// The pairing code, as shown by `remoshell pair`.
+        code: String,
+    },
+    /// Report the addresses the daemon's network endpoints are bound to.
+    NetworkInfo,
+    /// List the sessions waiting for the operator to allow them.
+    ListSessionConfirmations,
+    /// Allow or deny a session waiting for confirmation.
+    AnswerSessionConfirmation {
+        /// The request, as listed by `ListSessionConfirmations`.
+        id: u64,
+        /// Whether to allow the session.
+        allow: bool,
+    },
+    /// List the other daemons of the mesh.
+    MeshStatus,
+    /// Turn maintenance mode on or off.
+    SetMaintenance {
+        /// Whether to turn it on.
+        enabled: bool,
+        /// What to tell devices whose requests are refused.
+        message: Option<String>,
+    },
 }
 
 /// Responses sent from the daemon to the CLI.
@@ -41,9 +86,25 @@ pub enum IpcResponse {
         session_count: usize,
         /// Number of connected devices.
         device_count: usize,
+        /// The unclean shutdown the daemon recovered from when it started.
+        #[serde(default)]
+        recovered_from_crash: Option<CrashRecovery>,
+        /// The maintenance under way, if any.
+        #[serde(default)]
+        maintenance: Option<MaintenanceState>,
     },
     /// Acknowledgment that the daemon is stopping.
+    ///
+    /// Followed by [`IpcResponse::StopProgress`] responses until the daemon
+    /// has stopped and closes the connection.
     Stopping,
+    /// A step of a graceful stop completed.
+    StopProgress {
+        /// The subsystem that was stopped, e.g. `sessions`.
+        subsystem: String,
+        /// What stopping it involved, e.g. `closed 2 sessions`.
+        detail: String,
+    },
     /// List of active sessions.
     Sessions {
         /// Information about each active session.
@@ -54,6 +115,49 @@ pub enum IpcResponse {
         /// The ID of the killed session.
         session_id: String,
     },
+    /// A single log line streamed in response to `LogsSubscribe`.
+    LogLine(LogLine),
+    /// Marks the end of a non-following log stream.
+    LogsEnd,
+    /// Confirmation that the trust store was reloaded.
+    TrustStoreReloaded {
+        /// Number of devices in the reloaded trust store.
+        device_count: usize,
+    },
+    /// State of a pairing code.
+    PairingStatus {
+        /// The code's state.
+        status: PairingCodeStatus,
+    },
+    /// The daemon's network endpoints.
+    NetworkInfo {
+        /// The QUIC endpoint, if enabled.
+        quic: Option<QuicEndpointInfo>,
+    },
+    /// Sessions waiting for the operator to allow them.
+    SessionConfirmations {
+        /// The waiting sessions, oldest first.
+        pending: Vec<IpcPendingSession>,
+    },
+    /// Confirmation that a waiting session was answered.
+    SessionConfirmationAnswered {
+        /// The request that was answered.
+        id: u64,
+        /// Whether the session was allowed.
+        allowed: bool,
+    },
+    /// The other daemons of the mesh, as last seen.
+    MeshStatus {
+        /// The mesh group.
+        group: String,
+        /// The other daemons, ordered by host name.
+        hosts: Vec<MeshRecord>,
+    },
+    /// Maintenance mode after a change.
+    Maintenance {
+        /// The maintenance under way, or `None` if it is off.
+        state: Option<MaintenanceState>,
+    },
     /// An error occurred processing the request.
     Error {
         /// Human-readable error message.
@@ -70,11 +174,42 @@ pub struct IpcSessionInfo {
     pub connected_at: u64,
     /// Optional peer identifier (device ID or connection ID).
     pub peer_id: Option<String>,
+    /// Working directory reported by shell integration, if any.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Exit status of the last command reported by shell integration, if any.
+    #[serde(default)]
+    pub last_exit_code: Option<i32>,
+}
+
+/// A session waiting for the operator to allow it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IpcPendingSession {
+    /// Number identifying the request.
+    pub id: u64,
+    /// Fingerprint of the device asking for the session.
+    pub device_id: String,
+    /// The device's name in the trust store.
+    pub device_name: String,
+    /// Seconds until the session is denied.
+    pub expires_in_secs: u64,
+}
+
+/// The effective binding of the daemon's QUIC endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuicEndpointInfo {
+    /// The endpoint's iroh node ID.
+    pub node_id: String,
+    /// Local UDP sockets the endpoint is bound to.
+    pub bound_addresses: Vec<SocketAddr>,
+    /// Whether connections may go through relay servers.
+    pub relay: bool,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::orchestrator::OrchestratorState;
 
     #[test]
     fn test_request_ping_serialization() {
@@ -145,6 +280,153 @@ mod tests {
         assert_eq!(deserialized, request);
     }
 
+    #[test]
+    fn test_request_logs_subscribe_serialization() {
+        let request = IpcRequest::LogsSubscribe {
+            level: Some("warn".to_string()),
+            follow: true,
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(json.contains("LogsSubscribe"));
+        assert!(json.contains("warn"));
+
+        let deserialized: IpcRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, request);
+    }
+
+    #[test]
+    fn test_request_reload_trust_store_serialization() {
+        let request = IpcRequest::ReloadTrustStore;
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#""ReloadTrustStore""#);
+
+        let deserialized: IpcRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, request);
+    }
+
+    #[test]
+    fn test_pairing_status_serialization() {
+        let request = IpcRequest::PairingStatus {
+            code: "ABCD-1234".to_string(),
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"PairingStatus":{"code":"ABCD-1234"}}"#);
+        assert_eq!(serde_json::from_str::<IpcRequest>(&json).unwrap(), request);
+
+        let response = IpcResponse::PairingStatus {
+            status: PairingCodeStatus::Consumed {
+                device_id: "a1b2c3d4:e5f67890:12345678:9abcdef0".to_string(),
+                consumed_at: 1_700_000_000,
+            },
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains(r#""state":"consumed""#));
+        assert_eq!(
+            serde_json::from_str::<IpcResponse>(&json).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn test_network_info_serialization() {
+        let request = IpcRequest::NetworkInfo;
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#""NetworkInfo""#);
+        assert_eq!(serde_json::from_str::<IpcRequest>(&json).unwrap(), request);
+
+        let response = IpcResponse::NetworkInfo {
+            quic: Some(QuicEndpointInfo {
+                node_id: "ae58ff8833241ac82d6ff7611046ed67b5072d142c588d0063e942d9a75502b6"
+                    .to_string(),
+                bound_addresses: vec![
+                    "192.168.1.20:51000".parse().unwrap(),
+                    "[::1]:51000".parse().unwrap(),
+                ],
+                relay: false,
+            }),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains(r#""bound_addresses":["192.168.1.20:51000","[::1]:51000"]"#));
+        assert_eq!(
+            serde_json::from_str::<IpcResponse>(&json).unwrap(),
+            response
+        );
+
+        let response = IpcResponse::NetworkInfo { quic: None };
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(json, r#"{"NetworkInfo":{"quic":null}}"#);
+    }
+
+    #[test]
+    fn test_mesh_status_serialization() {
+        let json = serde_json::to_string(&IpcRequest::MeshStatus).unwrap();
+        assert_eq!(json, r#""MeshStatus""#);
+
+        let response = IpcResponse::MeshStatus {
+            group: "0123abcd".to_string(),
+            hosts: vec![MeshRecord {
+                group: "0123abcd".to_string(),
+                device_id: "a1b2:c3d4".to_string(),
+                hostname: "nas".to_string(),
+                online: true,
+                sessions: 1,
+                version: "0.1.0".to_string(),
+                timestamp: 1700000000,
+                ttl_secs: 180,
+            }],
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        let deserialized: IpcResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, response);
+    }
+
+    #[test]
+    fn test_maintenance_serialization() {
+        let request = IpcRequest::SetMaintenance {
+            enabled: true,
+            message: Some("disk upgrade".to_string()),
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(serde_json::from_str::<IpcRequest>(&json).unwrap(), request);
+
+        let response = IpcResponse::Maintenance {
+            state: Some(MaintenanceState {
+                message: Some("disk upgrade".to_string()),
+                since: 1_700_000_000,
+            }),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            serde_json::from_str::<IpcResponse>(&json).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn test_session_confirmation_serialization() {
+        let request = IpcRequest::AnswerSessionConfirmation { id: 3, allow: true };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            json,
+            r#"{"AnswerSessionConfirmation":{"id":3,"allow":true}}"#
+        );
+        assert_eq!(serde_json::from_str::<IpcRequest>(&json).unwrap(), request);
+
+        let response = IpcResponse::SessionConfirmations {
+            pending: vec![IpcPendingSession {
+                id: 3,
+                device_id: "a1b2c3d4:e5f67890:12345678:9abcdef0".to_string(),
+                device_name: "Phone".to_string(),
+                expires_in_secs: 25,
+            }],
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            serde_json::from_str::<IpcResponse>(&json).unwrap(),
+            response
+        );
+    }
+
     #[test]
     fn test_response_pong_serialization() {
         let response = IpcResponse::Pong;
@@ -162,6 +444,8 @@ mod tests {
             uptime_secs: 3600,
             session_count: 2,
             device_count: 3,
+            recovered_from_crash: None,
+            maintenance: None,
         };
         let json = serde_json::to_string(&response).unwrap();
         assert!(json.contains("Status"));
@@ -172,6 +456,37 @@ mod tests {
         assert_eq!(deserialized, response);
     }
 
+    #[test]
+    fn test_response_status_with_crash_recovery() {
+        let response = IpcResponse::Status {
+            running: true,
+            uptime_secs: 60,
+            session_count: 0,
+            device_count: 0,
+            recovered_from_crash: Some(CrashRecovery {
+                crashed_at: 1_700_000_000,
+                state: OrchestratorState::Running,
+                lost_sessions: 4,
+                lost_devices: 1,
+            }),
+            maintenance: None,
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        let deserialized: IpcResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, response);
+
+        // Daemons without crash reporting omit the field
+        let json =
+            r#"{"Status":{"running":true,"uptime_secs":1,"session_count":0,"device_count":0}}"#;
+        match serde_json::from_str(json).unwrap() {
+            IpcResponse::Status {
+                recovered_from_crash,
+                ..
+            } => assert_eq!(recovered_from_crash, None),
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
     #[test]
     fn test_response_stopping_serialization() {
         let response = IpcResponse::Stopping;
@@ -180,6 +495,20 @@ mod tests {
 
         let deserialized: IpcResponse = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized, response);
+
+        let response = IpcResponse::StopProgress {
+            subsystem: "sessions".to_string(),
+            detail: "closed 2 sessions".to_string(),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            json,
+            r#"{"StopProgress":{"subsystem":"sessions","detail":"closed 2 sessions"}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<IpcResponse>(&json).unwrap(),
+            response
+        );
     }
 
     #[test]
@@ -190,11 +519,15 @@ mod tests {
                     id: "session-1".to_string(),
                     connected_at: 1700000000,
                     peer_id: Some("peer-abc".to_string()),
+                    cwd: None,
+                    last_exit_code: None,
                 },
                 IpcSessionInfo {
                     id: "session-2".to_string(),
                     connected_at: 1700000100,
                     peer_id: None,
+                    cwd: None,
+                    last_exit_code: None,
                 },
             ],
         };
@@ -219,6 +552,42 @@ mod tests {
         assert_eq!(deserialized, response);
     }
 
+    #[test]
+    fn test_response_log_line_serialization() {
+        let response = IpcResponse::LogLine(LogLine {
+            timestamp_ms: 1700000000000,
+            level: "WARN".to_string(),
+            target: "daemon::orchestrator".to_string(),
+            message: "signaling disconnected".to_string(),
+        });
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains("LogLine"));
+        assert!(json.contains("signaling disconnected"));
+
+        let deserialized: IpcResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, response);
+    }
+
+    #[test]
+    fn test_response_logs_end_serialization() {
+        let response = IpcResponse::LogsEnd;
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(json, r#""LogsEnd""#);
+
+        let deserialized: IpcResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, response);
+    }
+
+    #[test]
+    fn test_response_trust_store_reloaded_serialization() {
+        let response = IpcResponse::TrustStoreReloaded { device_count: 3 };
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains("TrustStoreReloaded"));
+
+        let deserialized: IpcResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, response);
+    }
+
     #[test]
     fn test_response_error_serialization() {
         let response = IpcResponse::Error {
@@ -238,6 +607,8 @@ mod tests {
             id: "test-id".to_string(),
             connected_at: 1234567890,
             peer_id: Some("peer-123".to_string()),
+            cwd: None,
+            last_exit_code: None,
         };
         let json = serde_json::to_string(&session).unwrap();
 
@@ -245,12 +616,23 @@ mod tests {
         assert_eq!(deserialized, session);
     }
 
+    #[test]
+    fn test_ipc_session_info_without_shell_fields() {
+        // Responses from older daemons omit the shell integration fields
+        let json = r#"{"id":"test-id","connected_at":1234567890,"peer_id":null}"#;
+        let session: IpcSessionInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(session.cwd, None);
+        assert_eq!(session.last_exit_code, None);
+    }
+
     #[test]
     fn test_ipc_session_info_without_peer_id() {
         let session = IpcSessionInfo {
             id: "test-id".to_string(),
             connected_at: 1234567890,
             peer_id: None,
+            cwd: None,
+            last_exit_code: None,
         };
         let json = serde_json::to_string(&session).unwrap();
         assert!(json.contains("null"));
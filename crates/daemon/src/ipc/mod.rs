@@ -23,7 +23,7 @@
 //!
 //! #[tokio::main]
 //! async fn main() -> std::io::Result<()> {
-//!     let socket_path = get_socket_path();
+//!     let socket_path = get_socket_path(None);
 //!     let server = IpcServer::bind(&socket_path).await?;
 //!
 //!     loop {
@@ -45,7 +45,7 @@
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!     let socket_path = get_socket_path();
+//!     let socket_path = get_socket_path(None);
 //!     let mut client = IpcClient::connect(&socket_path).await?;
 //!
 //!     if client.ping().await? {
@@ -62,7 +62,7 @@ pub mod pidfile;
 mod server;
 
 pub use client::IpcClient;
-pub use messages::{IpcRequest, IpcResponse, IpcSessionInfo};
+pub use messages::{IpcPendingSession, IpcRequest, IpcResponse, IpcSessionInfo, QuicEndpointInfo};
 pub use pidfile::{get_daemon_pid, get_pid_file_path, is_daemon_running};
 pub use server::{IpcConnection, IpcError, IpcServer};
 
@@ -78,6 +78,9 @@ use std::path::PathBuf;
 /// 1. If `$XDG_RUNTIME_DIR` is set: `$XDG_RUNTIME_DIR/remoshell/daemon.sock`
 /// 2. Otherwise: `/tmp/remoshell-$UID/daemon.sock`
 ///
+/// A named `instance` uses `daemon-<instance>.sock` in the same directory, so
+/// several daemons can run side by side.
+///
 /// The XDG_RUNTIME_DIR is preferred because:
 /// - It's typically on a tmpfs (fast, volatile)
 /// - It has proper permissions (0700)
@@ -88,32 +91,40 @@ use std::path::PathBuf;
 /// ```rust
 /// use daemon::ipc::get_socket_path;
 ///
-/// let path = get_socket_path();
+/// let path = get_socket_path(None);
 /// println!("Socket will be at: {:?}", path);
 /// ```
 #[cfg(unix)]
-pub fn get_socket_path() -> PathBuf {
+pub fn get_socket_path(instance: Option<&str>) -> PathBuf {
     use std::os::unix::fs::MetadataExt;
 
+    let file_name = instance_file_name("daemon", instance, "sock");
     if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
-        PathBuf::from(runtime_dir)
-            .join("remoshell")
-            .join("daemon.sock")
+        PathBuf::from(runtime_dir).join("remoshell").join(file_name)
     } else {
         // Get UID by checking metadata of a file we own
         let uid = std::fs::metadata("/proc/self")
             .map(|m| m.uid())
             .unwrap_or(0);
 
-        PathBuf::from(format!("/tmp/remoshell-{}", uid)).join("daemon.sock")
+        PathBuf::from(format!("/tmp/remoshell-{}", uid)).join(file_name)
     }
 }
 
 /// Non-Unix platforms are not supported for Unix Domain Sockets.
 #[cfg(not(unix))]
-pub fn get_socket_path() -> PathBuf {
+pub fn get_socket_path(instance: Option<&str>) -> PathBuf {
     // This will fail at runtime on non-Unix platforms
-    PathBuf::from("/tmp/remoshell-unsupported/daemon.sock")
+    PathBuf::from("/tmp/remoshell-unsupported").join(instance_file_name("daemon", instance, "sock"))
+}
+
+/// Returns `<stem>.<extension>`, or `<stem>-<instance>.<extension>` for a
+/// named instance.
+pub(crate) fn instance_file_name(stem: &str, instance: Option<&str>, extension: &str) -> String {
+    match instance {
+        Some(name) => format!("{}-{}.{}", stem, name, extension),
+        None => format!("{}.{}", stem, extension),
+    }
 }
 
 #[cfg(test)]
@@ -130,7 +141,7 @@ mod tests {
         unsafe {
             std::env::set_var("XDG_RUNTIME_DIR", "/run/user/1000");
         }
-        let path = get_socket_path();
+        let path = get_socket_path(None);
         assert_eq!(path, PathBuf::from("/run/user/1000/remoshell/daemon.sock"));
 
         // Restore original value
@@ -154,7 +165,7 @@ mod tests {
         unsafe {
             std::env::remove_var("XDG_RUNTIME_DIR");
         }
-        let path = get_socket_path();
+        let path = get_socket_path(None);
         // Should contain /tmp/remoshell- prefix
         assert!(path.to_str().unwrap().starts_with("/tmp/remoshell-"));
         assert!(path.to_str().unwrap().ends_with("/daemon.sock"));
@@ -168,15 +179,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_socket_path_per_instance() {
+        assert!(get_socket_path(None).ends_with("daemon.sock"));
+        assert!(get_socket_path(Some("test")).ends_with("daemon-test.sock"));
+    }
+
     #[test]
     fn test_socket_path_is_absolute() {
-        let path = get_socket_path();
+        let path = get_socket_path(None);
         assert!(path.is_absolute());
     }
 
     #[test]
     fn test_socket_path_ends_with_sock() {
-        let path = get_socket_path();
+        let path = get_socket_path(None);
         assert!(path.extension().map(|e| e == "sock").unwrap_or(false));
     }
 }
@@ -9,13 +9,15 @@
 //! - `$XDG_DATA_HOME/remoshell/daemon.pid` if XDG_DATA_HOME is set
 //! - `~/.local/share/remoshell/daemon.pid` otherwise
 //!
+//! A named instance uses `daemon-<instance>.pid` in the same directory.
+//!
 //! ## Example
 //!
 //! ```rust
 //! use daemon::ipc::pidfile::{is_daemon_running, get_daemon_pid};
 //!
-//! if is_daemon_running() {
-//!     if let Some(pid) = get_daemon_pid() {
+//! if is_daemon_running(None) {
+//!     if let Some(pid) = get_daemon_pid(None) {
 //!         println!("Daemon is already running with PID {}", pid);
 //!     }
 //! }
@@ -30,15 +32,17 @@ use std::path::PathBuf;
 /// - `$XDG_DATA_HOME/remoshell/daemon.pid` if XDG_DATA_HOME is set
 /// - `~/.local/share/remoshell/daemon.pid` otherwise
 ///
+/// A named `instance` uses `daemon-<instance>.pid` instead.
+///
 /// ## Example
 ///
 /// ```rust
 /// use daemon::ipc::pidfile::get_pid_file_path;
 ///
-/// let path = get_pid_file_path();
+/// let path = get_pid_file_path(None);
 /// println!("PID file location: {:?}", path);
 /// ```
-pub fn get_pid_file_path() -> PathBuf {
+pub fn get_pid_file_path(instance: Option<&str>) -> PathBuf {
     // Use XDG_DATA_HOME or default to ~/.local/share
     let data_dir = std::env::var("XDG_DATA_HOME")
         .map(PathBuf::from)
@@ -47,10 +51,12 @@ pub fn get_pid_file_path() -> PathBuf {
                 .unwrap_or_else(|| PathBuf::from("/tmp"))
                 .join(".local/share")
         });
-    data_dir.join("remoshell").join("daemon.pid")
+    data_dir
+        .join("remoshell")
+        .join(super::instance_file_name("daemon", instance, "pid"))
 }
 
-/// Check if a daemon process is currently running.
+/// Check if a daemon process is currently running for `instance`.
 ///
 /// Returns `true` if:
 /// - PID file exists
@@ -64,17 +70,17 @@ pub fn get_pid_file_path() -> PathBuf {
 /// ```rust
 /// use daemon::ipc::pidfile::is_daemon_running;
 ///
-/// if is_daemon_running() {
+/// if is_daemon_running(None) {
 ///     println!("Daemon is already running");
 /// } else {
 ///     println!("No daemon running, safe to start");
 /// }
 /// ```
-pub fn is_daemon_running() -> bool {
-    get_daemon_pid().is_some()
+pub fn is_daemon_running(instance: Option<&str>) -> bool {
+    get_daemon_pid(instance).is_some()
 }
 
-/// Get the PID of the running daemon, if any.
+/// Get the PID of the running daemon for `instance`, if any.
 ///
 /// Returns `Some(pid)` if daemon is running, `None` otherwise.
 /// Automatically cleans up stale PID files.
@@ -84,13 +90,13 @@ pub fn is_daemon_running() -> bool {
 /// ```rust
 /// use daemon::ipc::pidfile::get_daemon_pid;
 ///
-/// match get_daemon_pid() {
+/// match get_daemon_pid(None) {
 ///     Some(pid) => println!("Daemon running with PID {}", pid),
 ///     None => println!("No daemon running"),
 /// }
 /// ```
-pub fn get_daemon_pid() -> Option<u32> {
-    let pid_path = get_pid_file_path();
+pub fn get_daemon_pid(instance: Option<&str>) -> Option<u32> {
+    let pid_path = get_pid_file_path(instance);
 
     // Read PID from file
     let pid_str = match fs::read_to_string(&pid_path) {
@@ -158,7 +164,7 @@ mod tests {
 
     #[test]
     fn test_get_pid_file_path_structure() {
-        let path = get_pid_file_path();
+        let path = get_pid_file_path(None);
         // Path should end with remoshell/daemon.pid
         assert!(path.ends_with("remoshell/daemon.pid"));
         // Path should be absolute or start with expected patterns
@@ -170,12 +176,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_pid_file_path_per_instance() {
+        let path = get_pid_file_path(Some("test"));
+        assert!(path.ends_with("remoshell/daemon-test.pid"));
+        assert_eq!(path.parent(), get_pid_file_path(None).parent());
+    }
+
     #[test]
     fn test_get_daemon_pid_no_file() {
         // With no PID file, should return None
         // This test works in clean environment or when no daemon is running
         // Just verify it doesn't panic and returns expected types
-        let result = get_daemon_pid();
+        let result = get_daemon_pid(None);
         // result is Option<u32> - verify it's a valid type
         if let Some(pid) = result {
             assert!(pid > 0, "If a PID is returned, it should be positive");
@@ -186,7 +199,7 @@ mod tests {
     #[test]
     fn test_is_daemon_running_no_panic() {
         // Verify the function doesn't panic and returns a bool
-        let result = is_daemon_running();
+        let result = is_daemon_running(None);
         // Just verify we got a boolean back
         let _ = result;
     }
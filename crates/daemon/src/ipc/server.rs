@@ -46,6 +46,12 @@ impl IpcServer {
         }
 
         let listener = UnixListener::bind(path)?;
+        // Only the daemon's user may connect, whatever the directory allows
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
 
         Ok(Self { listener })
     }
@@ -70,18 +76,28 @@ impl IpcServer {
 pub struct IpcConnection {
     reader: BufReader<tokio::io::ReadHalf<UnixStream>>,
     writer: tokio::io::WriteHalf<UnixStream>,
+    /// User ID of the client process, if the platform reports it.
+    peer_uid: Option<u32>,
 }
 
 impl IpcConnection {
     /// Create a new IPC connection from a Unix stream.
     fn new(stream: UnixStream) -> Self {
+        let peer_uid = stream.peer_cred().ok().map(|cred| cred.uid());
         let (read_half, write_half) = tokio::io::split(stream);
         Self {
             reader: BufReader::new(read_half),
             writer: write_half,
+            peer_uid,
         }
     }
 
+    /// Returns the user ID of the client process, if the platform reports
+    /// it.
+    pub fn peer_uid(&self) -> Option<u32> {
+        self.peer_uid
+    }
+
     /// Read the next request from the client.
     ///
     /// Returns `None` if the client has disconnected.
@@ -148,6 +164,10 @@ mod tests {
 
         let server = IpcServer::bind(&socket_path).await.unwrap();
         assert!(socket_path.exists());
+        let mode = std::os::unix::fs::PermissionsExt::mode(
+            &std::fs::metadata(&socket_path).unwrap().permissions(),
+        );
+        assert_eq!(mode & 0o777, 0o600);
         drop(server);
     }
 
@@ -200,6 +220,7 @@ mod tests {
 
         // Accept connection and handle request
         let mut conn = server.accept().await.unwrap();
+        assert_eq!(conn.peer_uid(), Some(nix::unistd::getuid().as_raw()));
         let request = conn.read_request().await.unwrap().unwrap();
         assert_eq!(request, IpcRequest::Ping);
 
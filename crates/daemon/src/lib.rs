@@ -58,27 +58,76 @@
 //! }
 //! ```
 //!
+//! ## Cargo Features
+//!
+//! All enabled by default; embedders can turn off what they do not need,
+//! e.g. `default-features = false, features = ["quic"]` for a headless
+//! QUIC-only agent:
+//!
+//! - `webrtc`: WebRTC connections from browser clients, and the signaling
+//!   client they are set up through
+//! - `quic`: QUIC connections from native clients over iroh
+//! - `tui`: The terminal interface of `start --tui`
+//!
 //! ## Modules
 //!
+//! - [`auditd`]: Session records for the Linux audit system
+//! - [`authz`]: Roles and authorization of management operations
 //! - [`config`]: Configuration loading and defaults
+//! - [`crash`]: Panic capture and crash reports
 //! - [`session`]: PTY session creation and management
+//! - [`soak`]: Counters dumped during soak tests to catch leaks
+//! - [`sysmon`]: Host and process information for client dashboards
+//! - [`power`]: Confirmed remote reboot, shutdown and suspend
+//! - [`remote_config`]: Reading and patching the configuration from clients
 //! - [`devices`]: Device trust store
+//! - [`doctor`]: Self-checks for `remoshell doctor`
 //! - [`files`]: File browsing and transfer
+//! - [`fleet`]: Zero-touch enrollment with a fleet endpoint
+//! - [`honeypot`]: Fake shell answering untrusted devices
 //! - [`ipc`]: Unix Domain Socket IPC for CLI-daemon communication
+//! - [`logging`]: In-memory log buffer for `remoshell logs`
+//! - [`maintenance`]: Refusing new sessions and uploads during maintenance
+//! - [`mesh`]: Status sharing between daemons of the same admin
+//! - [`mqtt`]: Presence publishing to an MQTT broker
 //! - [`network`]: WebRTC and QUIC connection handlers
+//! - [`notify`]: Notifications pushed to connected clients
 //! - [`router`]: Message routing to handlers
+//! - [`telemetry`]: Per-connection and per-session tracing spans, OTLP export
 //! - [`ui`]: TUI, QR code generation, systemd integration
+//! - [`util`]: Duration, size and relative time formatting
+//! - [`webhooks`]: Signed event webhooks with retries
 //! - [`orchestrator`]: Main daemon coordinator
+//! - [`output`]: Machine-readable output of CLI commands
 
+pub mod auditd;
+pub mod authz;
 pub mod config;
+pub mod crash;
 pub mod devices;
+pub mod doctor;
 pub mod files;
+pub mod fleet;
+pub mod honeypot;
 pub mod ipc;
+pub mod logging;
+pub mod maintenance;
+pub mod mesh;
+pub mod mqtt;
 pub mod network;
+pub mod notify;
 pub mod orchestrator;
+pub mod output;
+pub mod power;
+pub mod remote_config;
 pub mod router;
 pub mod session;
+pub mod soak;
+pub mod sysmon;
+pub mod telemetry;
 pub mod ui;
+pub mod util;
+pub mod webhooks;
 
 // Re-export protocol for convenience
 pub use protocol;
@@ -95,10 +144,11 @@ pub use session::{
 };
 
 // Re-export network types for convenience
-pub use network::{
-    ChannelType, Connection, IceServer, QuicConfig, QuicConnectionHandler, WebRtcConfig,
-    WebRtcConnectionHandler, REMOSHELL_ALPN,
-};
+pub use network::{ChannelType, Connection};
+#[cfg(feature = "webrtc")]
+pub use network::{IceServer, WebRtcConfig, WebRtcConnectionHandler};
+#[cfg(feature = "quic")]
+pub use network::{QuicConfig, QuicConnectionHandler, REMOSHELL_ALPN};
 
 // Re-export files types for convenience
 pub use files::{
@@ -109,17 +159,19 @@ pub use files::{
 pub use router::{MessageRouter, RouterError, RouterResult};
 
 // Re-export UI types for convenience
+#[cfg(feature = "tui")]
 pub use ui::{
-    device_id_to_hex, generate_png_qr, generate_terminal_qr, process_approval_result,
-    ApprovalAction, ApprovalInfo, ApprovalResult, DaemonStats, DeviceInfo, PairingInfo,
-    SessionInfo, Tab, TuiApp, TuiEvent, DEFAULT_EXPIRY_SECONDS,
+    device_id_to_hex, process_approval_result, ApprovalAction, ApprovalInfo, ApprovalResult,
+    DaemonStats, DeviceInfo, SessionInfo, Tab, TuiApp, TuiEvent,
 };
+pub use ui::{generate_png_qr, generate_terminal_qr, PairingInfo, DEFAULT_EXPIRY_SECONDS};
 
 // Re-export systemd types (Linux only, with stubs for other platforms)
 #[cfg(target_os = "linux")]
 pub use ui::{
-    generate_minimal_unit_file, generate_unit_file, is_systemd, notify_mainpid, notify_ready,
-    notify_status, notify_stopping, notify_watchdog, SignalHandler, SystemdContext,
+    generate_minimal_unit_file, generate_template_unit_file, generate_unit_file, is_systemd,
+    notify_mainpid, notify_ready, notify_status, notify_stopping, notify_watchdog, SignalHandler,
+    SystemdContext,
 };
 
 // Stub implementations for non-Linux platforms
@@ -148,6 +200,11 @@ pub fn generate_unit_file(_exec_path: Option<&str>) -> String {
     String::from("# systemd unit files are only supported on Linux")
 }
 
+#[cfg(not(target_os = "linux"))]
+pub fn generate_template_unit_file(_exec_path: Option<&str>) -> String {
+    String::from("# systemd unit files are only supported on Linux")
+}
+
 #[cfg(not(target_os = "linux"))]
 pub fn generate_minimal_unit_file(_exec_path: Option<&str>) -> String {
     String::from("# systemd unit files are only supported on Linux")
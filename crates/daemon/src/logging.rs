@@ -0,0 +1,339 @@
+//! In-memory log capture for streaming daemon logs to the CLI.
+//!
+//! This module provides a [`LogBuffer`] that keeps the most recent log lines
+//! in a ring buffer and broadcasts new lines to live subscribers. A
+//! [`LogBufferLayer`] plugs the buffer into the `tracing` subscriber stack so
+//! that `remoshell logs` works even when the daemon runs detached and journald
+//! is unavailable.
+//!
+//! A [`LogLevelHandle`] changes the level of the daemon's log filter while it
+//! runs, for configuration changes made without a restart.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+/// Default number of log lines retained in the ring buffer.
+pub const DEFAULT_LOG_BUFFER_CAPACITY: usize = 1000;
+
+/// Capacity of the broadcast channel used for live log subscribers.
+const LIVE_CHANNEL_CAPACITY: usize = 256;
+
+/// A single captured log line.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogLine {
+    /// Unix timestamp in milliseconds when the event was recorded.
+    pub timestamp_ms: u64,
+    /// Log level (e.g. "INFO", "WARN").
+    pub level: String,
+    /// The tracing target (usually the module path).
+    pub target: String,
+    /// The formatted message including any structured fields.
+    pub message: String,
+}
+
+impl LogLine {
+    /// Returns true if this line is at least as severe as `min_level`.
+    ///
+    /// Lines with an unparseable level are always included.
+    pub fn matches_level(&self, min_level: Level) -> bool {
+        match self.level.parse::<Level>() {
+            Ok(level) => level <= min_level,
+            Err(_) => true,
+        }
+    }
+}
+
+/// Parses a log level name such as "warn" or "DEBUG".
+pub fn parse_level(level: &str) -> Option<Level> {
+    level.parse::<Level>().ok()
+}
+
+/// A bounded buffer of recent log lines with live fan-out.
+///
+/// Cloning a `LogBuffer` is cheap; all clones share the same storage.
+#[derive(Clone)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<LogLine>>>,
+    capacity: usize,
+    live_tx: broadcast::Sender<LogLine>,
+}
+
+impl LogBuffer {
+    /// Creates a new log buffer retaining up to `capacity` lines.
+    pub fn new(capacity: usize) -> Self {
+        let (live_tx, _) = broadcast::channel(LIVE_CHANNEL_CAPACITY);
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+            live_tx,
+        }
+    }
+
+    /// Appends a line, evicting the oldest one if the buffer is full.
+    pub fn push(&self, line: LogLine) {
+        if let Ok(mut lines) = self.lines.lock() {
+            if lines.len() >= self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line.clone());
+        }
+        // No subscribers is not an error
+        let _ = self.live_tx.send(line);
+    }
+
+    /// Returns a copy of the buffered lines at or above `min_level`.
+    pub fn snapshot(&self, min_level: Level) -> Vec<LogLine> {
+        self.lines
+            .lock()
+            .map(|lines| {
+                lines
+                    .iter()
+                    .filter(|line| line.matches_level(min_level))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Subscribes to lines pushed after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<LogLine> {
+        self.live_tx.subscribe()
+    }
+
+    /// Returns the number of buffered lines.
+    pub fn len(&self) -> usize {
+        self.lines.lock().map(|lines| lines.len()).unwrap_or(0)
+    }
+
+    /// Returns true if no lines are buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of retained lines.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Creates a tracing layer that records events into this buffer.
+    pub fn layer(&self) -> LogBufferLayer {
+        LogBufferLayer {
+            buffer: self.clone(),
+        }
+    }
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_BUFFER_CAPACITY)
+    }
+}
+
+impl std::fmt::Debug for LogBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogBuffer")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// A `tracing_subscriber` layer that captures events into a [`LogBuffer`].
+pub struct LogBufferLayer {
+    buffer: LogBuffer,
+}
+
+impl<S: Subscriber> Layer<S> for LogBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        self.buffer.push(LogLine {
+            timestamp_ms,
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.finish(),
+        });
+    }
+}
+
+/// Changes the level of the daemon's log filter while it runs.
+#[derive(Clone)]
+pub struct LogLevelHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogLevelHandle {
+    /// Returns a filter layer logging at `level`, to install first on the
+    /// registry, and a handle changing its level.
+    pub fn layer(level: &str) -> (reload::Layer<EnvFilter, Registry>, Self) {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new(level));
+        (layer, Self { handle })
+    }
+
+    /// Sets the level events are logged at.
+    pub fn set_level(&self, level: &str) -> Result<(), reload::Error> {
+        self.handle.reload(EnvFilter::new(level))
+    }
+}
+
+impl std::fmt::Debug for LogLevelHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogLevelHandle").finish_non_exhaustive()
+    }
+}
+
+/// Collects the `message` field and any additional fields of an event.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self) -> String {
+        if self.fields.is_empty() {
+            self.message
+        } else if self.message.is_empty() {
+            self.fields
+        } else {
+            format!("{} {}", self.message, self.fields)
+        }
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            if !self.fields.is_empty() {
+                self.fields.push(' ');
+            }
+            let _ = write!(self.fields, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn line(level: &str, message: &str) -> LogLine {
+        LogLine {
+            timestamp_ms: 0,
+            level: level.to_string(),
+            target: "test".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_buffer_evicts_oldest() {
+        let buffer = LogBuffer::new(2);
+        buffer.push(line("INFO", "one"));
+        buffer.push(line("INFO", "two"));
+        buffer.push(line("INFO", "three"));
+
+        let lines = buffer.snapshot(Level::TRACE);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].message, "two");
+        assert_eq!(lines[1].message, "three");
+    }
+
+    #[test]
+    fn test_snapshot_filters_by_level() {
+        let buffer = LogBuffer::new(10);
+        buffer.push(line("DEBUG", "debug"));
+        buffer.push(line("INFO", "info"));
+        buffer.push(line("WARN", "warn"));
+        buffer.push(line("ERROR", "error"));
+
+        let lines = buffer.snapshot(Level::WARN);
+        let messages: Vec<_> = lines.iter().map(|l| l.message.as_str()).collect();
+        assert_eq!(messages, vec!["warn", "error"]);
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("warn"), Some(Level::WARN));
+        assert_eq!(parse_level("DEBUG"), Some(Level::DEBUG));
+        assert_eq!(parse_level("verbose"), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_receives_new_lines() {
+        let buffer = LogBuffer::new(10);
+        let mut rx = buffer.subscribe();
+        buffer.push(line("INFO", "live"));
+
+        let received = rx.recv().await.unwrap();
+        assert_eq!(received.message, "live");
+    }
+
+    #[test]
+    fn test_layer_captures_events() {
+        let buffer = LogBuffer::new(10);
+        let subscriber = tracing_subscriber::registry().with(buffer.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(peer = "abc", "connection dropped");
+        });
+
+        let lines = buffer.snapshot(Level::TRACE);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].level, "WARN");
+        assert_eq!(lines[0].message, "connection dropped peer=\"abc\"");
+    }
+
+    #[test]
+    fn test_level_handle_changes_filter() {
+        let buffer = LogBuffer::new(10);
+        let (filter, handle) = LogLevelHandle::layer("info");
+        let subscriber = tracing_subscriber::registry()
+            .with(filter)
+            .with(buffer.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("hidden");
+            handle.set_level("debug").unwrap();
+            tracing::debug!("shown");
+        });
+
+        let lines = buffer.snapshot(Level::TRACE);
+        let messages: Vec<_> = lines.iter().map(|l| l.message.as_str()).collect();
+        assert_eq!(messages, vec!["shown"]);
+    }
+
+    #[test]
+    fn test_log_line_serialization() {
+        let original = line("INFO", "hello");
+        let json = serde_json::to_string(&original).unwrap();
+        let deserialized: LogLine = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, original);
+    }
+}
@@ -4,14 +4,29 @@
 
 use std::path::PathBuf;
 
+use anyhow::Context;
 use clap::{Parser, Subcommand, ValueEnum};
 use daemon::config::{Config, DEFAULT_SIGNALING_URL};
+use daemon::doctor;
 use daemon::ipc::{get_daemon_pid, get_socket_path, is_daemon_running, IpcClient, IpcResponse};
-use daemon::orchestrator::{DaemonOrchestrator, OrchestratorEvent, OrchestratorState};
+use daemon::logging::{LogBuffer, LogLevelHandle, LogLine};
+use daemon::orchestrator::{
+    CrashRecovery, DaemonOrchestrator, OrchestratorEvent, OrchestratorState, StateFile,
+    IDENTITY_FILE_NAME,
+};
+use daemon::output::{self, OutputFormat};
+use daemon::soak::SoakReport;
+use daemon::telemetry::{self, TelemetryGuard};
 use daemon::ui::qr::{
-    generate_pairing_code, generate_png_qr_from_data, generate_terminal_qr_from_data, pairing_url,
-    register_pairing_code, PairingInfo,
+    generate_numeric_pairing_code, generate_pairing_code, generate_png_qr_from_data,
+    generate_terminal_qr_from_data, pairing_url, register_pairing_code, PairingInfo,
 };
+use daemon::util::format::{self as display, format_duration, format_relative_time};
+use daemon::util::i18n::{self, tr, tr_with};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
 /// RemoShell Daemon - headless service for remote shell connections.
 #[derive(Parser, Debug)]
@@ -22,10 +37,27 @@ pub struct Cli {
     #[arg(short, long, global = true, value_name = "FILE")]
     pub config: Option<PathBuf>,
 
+    /// Named configuration profile with its own data directory
+    #[arg(long, global = true, value_name = "NAME", env = "REMOSHELL_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Daemon instance, for running several daemons on one host
+    #[arg(long, global = true, value_name = "NAME", env = "REMOSHELL_INSTANCE")]
+    pub instance: Option<String>,
+
     /// Enable verbose logging
     #[arg(short, long, global = true)]
     pub verbose: bool,
 
+    /// Format of command results
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    pub output: OutputFormat,
+
+    /// Screen-reader-friendly text: no tables, alignment or pictures, and
+    /// with `start`, announcements and y/n approval prompts instead of logs
+    #[arg(long, global = true, env = "REMOSHELL_PLAIN")]
+    pub plain: bool,
+
     /// Subcommand to execute
     #[command(subcommand)]
     pub command: Commands,
@@ -43,6 +75,20 @@ pub enum Commands {
         /// Run in systemd service mode (no TUI, structured logging)
         #[arg(long)]
         systemd: bool,
+
+        /// Append internal counters to this file as JSON lines, to catch leaks
+        #[arg(long, value_name = "PATH")]
+        soak_report: Option<PathBuf>,
+
+        /// Seconds between two soak report entries
+        #[arg(
+            long,
+            value_name = "SECS",
+            default_value = "60",
+            requires = "soak_report",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        soak_interval: u64,
     },
 
     /// Stop the running daemon
@@ -59,6 +105,39 @@ pub enum Commands {
     /// Show daemon status
     Status,
 
+    /// Check the configuration, data files and network for problems
+    Doctor,
+
+    /// Show the addresses the daemon's QUIC endpoint is bound to
+    Network,
+
+    /// Show the other daemons of the mesh and whether they are online
+    Mesh,
+
+    /// Turn maintenance mode on or off: new sessions and writes to the host
+    /// are refused while open sessions carry on
+    #[command(subcommand)]
+    Maintenance(MaintenanceCommands),
+
+    /// Print the prompt hook that applies environment updates from clients,
+    /// e.g. `eval "$(remoshell-daemon shell-hook bash)"` in ~/.bashrc
+    ShellHook {
+        /// Shell to print the hook for
+        #[arg(value_parser = ["bash", "zsh"])]
+        shell: String,
+    },
+
+    /// Show daemon logs
+    Logs {
+        /// Keep streaming new log lines as they are written
+        #[arg(long, short)]
+        follow: bool,
+
+        /// Minimum log level to show
+        #[arg(long, value_parser = ["trace", "debug", "info", "warn", "error"])]
+        level: Option<String>,
+    },
+
     /// Manage connected devices
     #[command(subcommand)]
     Devices(DevicesCommands),
@@ -67,6 +146,10 @@ pub enum Commands {
     #[command(subcommand)]
     Sessions(SessionsCommands),
 
+    /// Describe the client protocol
+    #[command(subcommand)]
+    Protocol(ProtocolCommands),
+
     /// Generate a pairing code for device authentication
     Pair {
         /// Output format for the pairing code
@@ -74,8 +157,8 @@ pub enum Commands {
         format: PairFormat,
 
         /// Output file path for PNG format (defaults to ./pairing-qr.png)
-        #[arg(long, short)]
-        output: Option<PathBuf>,
+        #[arg(long, short = 'o')]
+        output_file: Option<PathBuf>,
 
         /// Relay/signaling server URL
         #[arg(long, default_value = DEFAULT_SIGNALING_URL)]
@@ -84,6 +167,24 @@ pub enum Commands {
         /// Expiry time in seconds (default: 300 = 5 minutes)
         #[arg(long, default_value = "300")]
         expiry: u64,
+
+        /// Trust a client by its base64 public key instead of showing a code,
+        /// and print the connection info as JSON
+        #[arg(long, value_name = "PUBLIC_KEY", requires = "name")]
+        preauthorize: Option<String>,
+
+        /// Name of the preauthorized device
+        #[arg(long, requires = "preauthorize")]
+        name: Option<String>,
+
+        /// Show a numeric code that can be typed on devices without a camera
+        #[arg(long, conflicts_with = "preauthorize")]
+        numeric: bool,
+
+        /// Report whether a code shown earlier has been used, instead of
+        /// generating a new one
+        #[arg(long, value_name = "CODE", conflicts_with = "preauthorize")]
+        status: Option<String>,
     },
 }
 
@@ -91,7 +192,11 @@ pub enum Commands {
 #[derive(Subcommand, Debug, Clone)]
 pub enum DevicesCommands {
     /// List all known devices
-    List,
+    List {
+        /// Also show word and randomart fingerprints for out-of-band comparison
+        #[arg(long, short)]
+        verbose: bool,
+    },
 
     /// Trust a device by its ID
     Trust {
@@ -104,6 +209,81 @@ pub enum DevicesCommands {
         /// Device ID to revoke
         device_id: String,
     },
+
+    /// Trust a known device for a limited time, until it first disconnects
+    GrantOnce {
+        /// Device ID to grant access to
+        device_id: String,
+
+        /// How long the grant lasts, e.g. 30m, 2h or 1h30m
+        #[arg(long, value_parser = parse_grant_duration)]
+        duration: u64,
+
+        /// What the device may do, comma-separated
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "shell",
+            value_parser = [
+                "shell",
+                "files",
+                "process-kill",
+                "power",
+                "agent-forwarding",
+                "display-forwarding",
+            ]
+        )]
+        caps: Vec<String>,
+    },
+
+    /// Set the management role of a device (viewer, admin or owner)
+    Role {
+        /// Device ID to change
+        device_id: String,
+
+        /// The role, or `none` to remove management access
+        #[arg(value_enum)]
+        role: DeviceRole,
+    },
+
+    /// Issue a one-time token that lets a revoked device be re-approved
+    Reinvite {
+        /// Device ID to re-invite
+        device_id: String,
+
+        /// Token lifetime in seconds (default: 86400 = 24 hours)
+        #[arg(long, default_value = "86400")]
+        expiry: u64,
+    },
+
+    /// Export the trust store as a file signed by this daemon
+    Export {
+        /// Output format
+        #[arg(long, short, value_enum, default_value = "json")]
+        format: TrustFileFormat,
+
+        /// Output file path (defaults to stdout)
+        #[arg(long, short = 'o')]
+        output_file: Option<PathBuf>,
+    },
+
+    /// Import devices from a signed trust store export
+    Import {
+        /// Path to the export file
+        file: PathBuf,
+
+        /// Input format (inferred from the file extension if omitted)
+        #[arg(long, short, value_enum)]
+        format: Option<TrustFileFormat>,
+
+        /// How to combine the export with existing devices
+        #[arg(long, short, value_enum, default_value = "merge")]
+        mode: TrustImportMode,
+
+        /// Only accept exports signed by this daemon fingerprint
+        #[arg(long)]
+        signer: Option<String>,
+    },
 }
 
 /// Subcommands for session management.
@@ -111,7 +291,7 @@ pub enum DevicesCommands {
 pub enum SessionsCommands {
     /// List all active sessions
     List {
-        /// Output in JSON format
+        /// Output in JSON format (same as `--output json`)
         #[arg(long)]
         json: bool,
     },
@@ -130,6 +310,121 @@ pub enum SessionsCommands {
         #[arg(long, short)]
         force: bool,
     },
+
+    /// List sessions waiting for confirmation
+    /// (security.session_confirmation)
+    Pending {
+        /// Output in JSON format (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Allow a session waiting for confirmation
+    Allow {
+        /// Request number, as listed by `sessions pending`
+        id: u64,
+    },
+
+    /// Deny a session waiting for confirmation
+    Deny {
+        /// Request number, as listed by `sessions pending`
+        id: u64,
+    },
+}
+
+/// Subcommands of `maintenance`.
+#[derive(Subcommand, Debug, Clone)]
+pub enum MaintenanceCommands {
+    /// Refuse new sessions and uploads, or change the message of the
+    /// maintenance under way
+    On {
+        /// Message shown to devices whose requests are refused
+        #[arg(long, short)]
+        message: Option<String>,
+    },
+    /// Accept new sessions and uploads again
+    Off,
+}
+
+/// Subcommands describing the client protocol.
+#[derive(Subcommand, Debug, Clone)]
+pub enum ProtocolCommands {
+    /// Print a description of every protocol message, generated from the
+    /// message types, for client implementers
+    DumpSchema {
+        /// Output format
+        #[arg(long, short, value_enum, default_value = "json")]
+        format: SchemaFormat,
+    },
+}
+
+/// Output format for the protocol schema.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaFormat {
+    /// JSON Schema document
+    Json,
+    /// Markdown reference
+    Markdown,
+}
+
+/// File format for trust store export and import.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustFileFormat {
+    /// JSON document
+    Json,
+    /// TOML document
+    Toml,
+}
+
+impl From<TrustFileFormat> for daemon::devices::ExportFormat {
+    fn from(format: TrustFileFormat) -> Self {
+        match format {
+            TrustFileFormat::Json => Self::Json,
+            TrustFileFormat::Toml => Self::Toml,
+        }
+    }
+}
+
+/// Management role of a device, for `devices role`.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceRole {
+    /// Read status, sessions and logs
+    Viewer,
+    /// Also approve devices and sessions and kill sessions
+    Admin,
+    /// Also change the configuration and stop the daemon
+    Owner,
+    /// No management access
+    None,
+}
+
+impl From<DeviceRole> for Option<daemon::authz::Role> {
+    fn from(role: DeviceRole) -> Self {
+        match role {
+            DeviceRole::Viewer => Some(daemon::authz::Role::Viewer),
+            DeviceRole::Admin => Some(daemon::authz::Role::Admin),
+            DeviceRole::Owner => Some(daemon::authz::Role::Owner),
+            DeviceRole::None => None,
+        }
+    }
+}
+
+/// How an imported trust store is combined with existing devices.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustImportMode {
+    /// Add and update devices, keeping devices not in the export
+    Merge,
+    /// Replace all devices with those in the export
+    Replace,
+}
+
+impl From<TrustImportMode> for daemon::devices::ImportMode {
+    fn from(mode: TrustImportMode) -> Self {
+        match mode {
+            TrustImportMode::Merge => Self::Merge,
+            TrustImportMode::Replace => Self::Replace,
+        }
+    }
 }
 
 /// Output format for pairing codes.
@@ -145,38 +440,101 @@ pub enum PairFormat {
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
+    let profile = cli.profile.as_deref();
+    if let Some(name) = profile {
+        daemon::config::validate_profile_name(name)?;
+    }
+    let instance = cli.instance.as_deref();
+    if let Some(name) = instance {
+        daemon::config::validate_instance_name(name)?;
+    }
+    let config_path = daemon::config::resolve_config_path(cli.config.as_deref(), profile);
+
+    // Doctor reports configuration problems instead of failing on them
+    if let Commands::Doctor = cli.command {
+        let report = doctor::run(&config_path, profile, instance).await;
+        if cli.output.is_json() {
+            output::print_json(&report)?;
+        } else {
+            print!("{}", report.render());
+        }
+        std::process::exit(if report.has_failures() { 1 } else { 0 });
+    }
+
+    // The schema comes from the message types, not the configuration
+    if let Commands::Protocol(ProtocolCommands::DumpSchema { format }) = &cli.command {
+        match format {
+            SchemaFormat::Json => output::print_json(&protocol::schema::json_schema())?,
+            SchemaFormat::Markdown => print!("{}", protocol::schema::markdown()),
+        }
+        return Ok(());
+    }
+
+    // The hook is the same for every configuration
+    if let Commands::ShellHook { shell } = &cli.command {
+        if let Some(hook) = daemon::session::env::shell_hook(shell) {
+            print!("{}", hook);
+        }
+        return Ok(());
+    }
+
     // Load configuration (before tracing init so TUI mode can redirect logs)
-    let mut config = if let Some(config_path) = &cli.config {
-        Config::load(config_path)?
-    } else {
-        Config::load_default()?
-    };
+    let mut config = Config::load_scoped(&config_path, profile, instance)?;
 
     // Apply environment variable overrides
     config.apply_env_overrides();
 
     // Validate configuration
     config.validate()?;
+    display::init(&config.display);
+    i18n::init(&config.display);
 
     // Initialize tracing (after config load so we know the data_dir for TUI log file)
-    let filter = if cli.verbose { "debug" } else { "info" };
-    let _guard = init_tracing(&cli.command, filter, &config.daemon.data_dir)?;
+    let plain = cli.plain && !cli.output.is_json();
+    let filter = if cli.verbose {
+        "debug"
+    } else if matches!(cli.command, Commands::Start { .. }) {
+        config.daemon.log_level.as_str()
+    } else if plain {
+        // Keep progress logs off the terminal a screen reader is reading
+        "warn"
+    } else {
+        "info"
+    };
+    let log_buffer = LogBuffer::default();
+    let (_guards, log_level) = init_tracing(&cli.command, plain, filter, &config, &log_buffer)?;
 
     tracing::info!("RemoShell daemon starting...");
-    if let Some(ref config_path) = cli.config {
+    if let Some(name) = profile {
+        tracing::info!("Using profile: {}", name);
+    }
+    if let Some(name) = instance {
+        tracing::info!("Using instance: {}", name);
+    }
+    if cli.config.is_some() || profile.is_some() {
         tracing::info!("Using config file: {:?}", config_path);
     }
 
+    let json = cli.output.is_json();
+
     // Handle commands
     match cli.command {
-        Commands::Start { tui, systemd } => {
+        Commands::Start {
+            tui,
+            systemd,
+            soak_report,
+            soak_interval,
+        } => {
             if tui && systemd {
                 anyhow::bail!("Cannot use both --tui and --systemd flags");
             }
+            if plain && (tui || systemd) {
+                anyhow::bail!("--plain cannot be used with --tui or --systemd");
+            }
 
             // Check for existing daemon BEFORE starting
-            if is_daemon_running() {
-                let pid = get_daemon_pid().unwrap_or(0);
+            if is_daemon_running(instance) {
+                let pid = get_daemon_pid(instance).unwrap_or(0);
                 eprintln!("Error: Daemon already running (PID: {})", pid);
                 eprintln!();
                 eprintln!("To stop the existing daemon, run:");
@@ -189,6 +547,17 @@ async fn main() -> anyhow::Result<()> {
 
             // Create the orchestrator
             let mut orchestrator = DaemonOrchestrator::new(config)?;
+            orchestrator.set_log_buffer(log_buffer);
+            orchestrator.set_config_path(config_path.clone());
+            if let Some(handle) = log_level {
+                orchestrator.set_log_level_handle(handle);
+            }
+            if let Some(path) = soak_report {
+                orchestrator.set_soak_report(
+                    SoakReport::new(path)
+                        .with_interval(std::time::Duration::from_secs(soak_interval)),
+                );
+            }
             let device_id = orchestrator.device_id_fingerprint();
 
             if tui {
@@ -199,7 +568,7 @@ async fn main() -> anyhow::Result<()> {
                 run_systemd_mode(&mut orchestrator).await?;
             } else {
                 tracing::info!("Starting daemon (device ID: {})", device_id);
-                run_headless(&mut orchestrator).await?;
+                run_headless(&mut orchestrator, plain).await?;
             }
         }
         Commands::Stop { force, timeout } => {
@@ -207,67 +576,300 @@ async fn main() -> anyhow::Result<()> {
 
             if force {
                 // Force stop using SIGKILL
-                match force_stop_daemon() {
+                match force_stop_daemon(instance) {
                     Ok(()) => {
-                        println!("Daemon forcefully terminated");
+                        if json {
+                            output::print_json(&output::StopOutput {
+                                stopped: true,
+                                forced: true,
+                            })?;
+                        } else {
+                            println!("{}", tr("cli.stop.forced"));
+                        }
                         std::process::exit(0);
                     }
                     Err(e) => {
-                        eprintln!("Failed to stop daemon: {}", e);
+                        eprintln!("{}", tr_with("cli.stop.failed", &[("error", &e)]));
                         std::process::exit(1);
                     }
                 }
             } else {
                 // Graceful shutdown via IPC
-                match graceful_stop_daemon(timeout).await {
+                match graceful_stop_daemon(instance, timeout).await {
                     Ok(()) => {
-                        println!("Daemon stopped successfully");
+                        if json {
+                            output::print_json(&output::StopOutput {
+                                stopped: true,
+                                forced: false,
+                            })?;
+                        } else {
+                            println!("{}", tr("cli.stop.stopped"));
+                        }
                         std::process::exit(0);
                     }
                     Err(e) => {
-                        eprintln!("Failed to stop daemon: {}", e);
-                        eprintln!("Try: remoshell-daemon stop --force");
+                        eprintln!("{}", tr_with("cli.stop.failed", &[("error", &e)]));
+                        eprintln!("{}", tr("cli.stop.try_force"));
                         std::process::exit(1);
                     }
                 }
             }
         }
+        Commands::Doctor => unreachable!("doctor runs before the configuration is loaded"),
+        Commands::Protocol(_) => {
+            unreachable!("protocol commands run before the configuration is loaded")
+        }
+        Commands::ShellHook { .. } => {
+            unreachable!("shell-hook runs before the configuration is loaded")
+        }
         Commands::Status => {
             tracing::info!("Checking daemon status");
 
-            match query_daemon_status().await {
+            match query_daemon_status(instance).await {
                 Ok(status) => {
-                    println!(
-                        "Daemon Status: {}",
-                        if status.running { "running" } else { "stopped" }
-                    );
-                    println!("  Uptime:   {}", format_duration(status.uptime_secs));
-                    println!("  Sessions: {}", status.session_count);
-                    println!("  Devices:  {}", status.device_count);
+                    if json {
+                        output::print_json(&status)?;
+                    } else {
+                        let state = if status.running {
+                            tr("cli.status.running")
+                        } else {
+                            tr("cli.status.stopped")
+                        };
+                        println!("{}", tr_with("cli.status.daemon", &[("state", &state)]));
+                        print_fields(
+                            &[
+                                (tr("cli.status.uptime"), format_duration(status.uptime_secs)),
+                                (tr("cli.status.sessions"), status.session_count.to_string()),
+                                (tr("cli.status.devices"), status.device_count.to_string()),
+                            ],
+                            plain,
+                        );
+                        if let Some(recovered) = &status.recovered_from_crash {
+                            println!(
+                                "{}{}",
+                                indent(plain),
+                                tr_with(
+                                    "cli.status.recovered",
+                                    &[
+                                        ("when", &format_relative_time(recovered.crashed_at)),
+                                        ("count", &recovered.lost_sessions),
+                                    ]
+                                )
+                            );
+                        }
+                        if let Some(maintenance) = &status.maintenance {
+                            let when = format_relative_time(maintenance.since);
+                            let line = match &maintenance.message {
+                                Some(message) => tr_with(
+                                    "cli.status.maintenance_message",
+                                    &[("when", &when), ("message", message)],
+                                ),
+                                None => tr_with("cli.status.maintenance", &[("when", &when)]),
+                            };
+                            println!("{}{}", indent(plain), line);
+                        }
+                    }
                     std::process::exit(0);
                 }
                 Err(e) => {
-                    eprintln!("Daemon is not running: {}", e);
+                    eprintln!("{}", tr_with("cli.status.not_running", &[("error", &e)]));
+                    // Tell whether the last daemon stopped cleanly
+                    let state_file = StateFile::new(&config.daemon.data_dir);
+                    if let Some(crash) = state_file
+                        .load()
+                        .ok()
+                        .flatten()
+                        .and_then(|previous| CrashRecovery::from_previous(&previous))
+                    {
+                        eprintln!(
+                            "{}",
+                            tr_with(
+                                "cli.status.last_crash",
+                                &[
+                                    ("when", &format_relative_time(crash.crashed_at)),
+                                    ("count", &crash.lost_sessions),
+                                ]
+                            )
+                        );
+                    }
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Network => match query_network_info(instance).await {
+            Ok(network) => {
+                if json {
+                    output::print_json(&network)?;
+                } else if let Some(quic) = network.quic {
+                    println!("QUIC endpoint: {}", quic.node_id);
+                    let mut fields: Vec<_> = quic
+                        .bound_addresses
+                        .iter()
+                        .map(|address| ("Listening:", format!("{}/udp", address)))
+                        .collect();
+                    let relay = if quic.relay { "enabled" } else { "disabled" };
+                    fields.push(("Relay:", relay.to_string()));
+                    print_fields(&fields, plain);
+                } else {
+                    println!("QUIC endpoint: disabled");
+                }
+            }
+            Err(e) => {
+                eprintln!("Daemon is not running: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Mesh => match query_mesh_status(instance).await {
+            Ok(mesh) => {
+                if json {
+                    output::print_json(&mesh)?;
+                } else {
+                    println!("Mesh group: {}", mesh.group);
+                    if mesh.hosts.is_empty() {
+                        println!("No other daemons seen");
+                    }
+                    for host in &mesh.hosts {
+                        let state = if host.online { "online" } else { "offline" };
+                        println!("{} ({})", host.hostname, host.device_id);
+                        print_fields(
+                            &[
+                                ("State:", state.to_string()),
+                                ("Sessions:", host.sessions.to_string()),
+                                ("Version:", host.version.clone()),
+                                ("Updated:", format_relative_time(host.timestamp)),
+                            ],
+                            plain,
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to read mesh status: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Maintenance(command) => {
+            let (enabled, message) = match command {
+                MaintenanceCommands::On { message } => (true, message.clone()),
+                MaintenanceCommands::Off => (false, None),
+            };
+            match set_maintenance(instance, enabled, message).await {
+                Ok(result) => {
+                    if json {
+                        output::print_json(&result)?;
+                    } else if let Some(state) = &result.maintenance {
+                        let when = format_relative_time(state.since);
+                        println!("{}", tr_with("cli.maintenance.on", &[("when", &when)]));
+                        if let Some(message) = &state.message {
+                            print_fields(
+                                &[(tr("cli.maintenance.message"), message.clone())],
+                                plain,
+                            );
+                        }
+                    } else {
+                        println!("{}", tr("cli.maintenance.off"));
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", tr_with("cli.maintenance.failed", &[("error", &e)]));
                     std::process::exit(1);
                 }
             }
         }
+        Commands::Logs { follow, level } => {
+            if let Err(e) = stream_daemon_logs(instance, level, follow, cli.output).await {
+                eprintln!("Failed to read daemon logs: {}", e);
+                std::process::exit(1);
+            }
+        }
         Commands::Devices(cmd) => {
-            let trust_store = daemon::TrustStore::with_default_path();
+            let trust_store = daemon::TrustStore::new(
+                config
+                    .daemon
+                    .data_dir
+                    .join(daemon::devices::TRUST_STORE_FILE_NAME),
+            );
             trust_store.load()?;
 
             match cmd {
-                DevicesCommands::List => {
+                DevicesCommands::List { verbose } => {
                     let devices = trust_store.list_devices()?;
-                    if devices.is_empty() {
-                        println!("No devices registered.");
+                    if json {
+                        let devices: Vec<_> =
+                            devices.iter().map(output::DeviceOutput::from).collect();
+                        output::print_json(&devices)?;
+                    } else if devices.is_empty() {
+                        println!("{}", tr("cli.devices.none"));
                     } else {
-                        println!("Registered devices:");
+                        println!("{}", tr("cli.devices.header"));
                         for device in devices {
-                            println!(
-                                "  {} - {} ({:?})",
-                                device.device_id, device.name, device.trust_level
-                            );
+                            let warning = if daemon::devices::names::is_mixed_script(&device.name) {
+                                format!(" {}", tr("cli.devices.mixed_scripts"))
+                            } else {
+                                String::new()
+                            };
+                            if plain {
+                                let trust = daemon::ui::DisplayTrustLevel::from(device.trust_level);
+                                let line = tr_with(
+                                    "cli.devices.plain",
+                                    &[
+                                        ("name", &device.name),
+                                        ("trust", &trust.as_str()),
+                                        ("device", &device.device_id),
+                                    ],
+                                );
+                                println!("{}{}", line, warning);
+                            } else {
+                                println!(
+                                    "  {} - {} ({:?}){}",
+                                    device.device_id, device.name, device.trust_level, warning
+                                );
+                            }
+                            if let Some(role) = device.role {
+                                println!(
+                                    "{}{}",
+                                    indent(plain).repeat(2),
+                                    tr_with("cli.devices.role", &[("role", &role)])
+                                );
+                            }
+                            if let Some(grant) = &device.grant {
+                                let grant = output::GrantOutput::from(grant);
+                                let caps = grant.capabilities.into_iter().collect::<Vec<_>>();
+                                println!(
+                                    "{}{}",
+                                    indent(plain).repeat(2),
+                                    tr_with(
+                                        "cli.devices.grant",
+                                        &[
+                                            ("caps", &caps.join(", ")),
+                                            ("expires", &format_relative_time(grant.expires_at)),
+                                        ]
+                                    )
+                                );
+                            }
+                            if verbose {
+                                if let Some(raw_name) = &device.raw_name {
+                                    let name = format!("{:?}", raw_name);
+                                    println!(
+                                        "{}{}",
+                                        indent(plain).repeat(2),
+                                        tr_with("cli.devices.sent_as", &[("name", &name)])
+                                    );
+                                }
+                                let words = device.device_id.fingerprint_words().join(" ");
+                                println!(
+                                    "{}{}",
+                                    indent(plain).repeat(2),
+                                    tr_with("cli.devices.words", &[("words", &words)])
+                                );
+                                // Randomart only helps someone looking at it
+                                if !plain {
+                                    for line in device.device_id.randomart().lines() {
+                                        println!("    {}", line);
+                                    }
+                                    println!();
+                                }
+                            }
                         }
                     }
                 }
@@ -276,33 +878,267 @@ async fn main() -> anyhow::Result<()> {
                     let did = parse_device_id(&device_id)?;
                     trust_store.set_trust_level(&did, daemon::TrustLevel::Trusted)?;
                     trust_store.save()?;
-                    println!("Device {} is now trusted", device_id);
+                    notify_trust_store_changed(instance).await;
+                    if json {
+                        output::print_json(&output::TrustChangeOutput {
+                            device_id: did.fingerprint(),
+                            trust_level: daemon::TrustLevel::Trusted,
+                        })?;
+                    } else {
+                        println!(
+                            "{}",
+                            tr_with("cli.devices.trusted", &[("device", &device_id)])
+                        );
+                    }
                 }
                 DevicesCommands::Revoke { device_id } => {
                     let did = parse_device_id(&device_id)?;
                     trust_store.set_trust_level(&did, daemon::TrustLevel::Revoked)?;
                     trust_store.save()?;
-                    println!("Device {} has been revoked", device_id);
+                    notify_trust_store_changed(instance).await;
+                    if json {
+                        output::print_json(&output::TrustChangeOutput {
+                            device_id: did.fingerprint(),
+                            trust_level: daemon::TrustLevel::Revoked,
+                        })?;
+                    } else {
+                        println!(
+                            "{}",
+                            tr_with("cli.devices.revoked", &[("device", &device_id)])
+                        );
+                    }
+                }
+                DevicesCommands::GrantOnce {
+                    device_id,
+                    duration,
+                    caps,
+                } => {
+                    let did = parse_device_id(&device_id)?;
+                    let device = trust_store.grant_temporary(
+                        &did,
+                        std::time::Duration::from_secs(duration),
+                        caps.into_iter().collect(),
+                    )?;
+                    trust_store.save()?;
+                    notify_trust_store_changed(instance).await;
+
+                    let grant = device.grant.as_ref().expect("grant just created");
+                    let capabilities = grant.capabilities.iter().cloned().collect::<Vec<_>>();
+                    tracing::info!(
+                        target: "audit",
+                        device_id = %device_id,
+                        name = %device.name,
+                        duration_secs = duration,
+                        capabilities = %capabilities.join(","),
+                        "Temporary access granted"
+                    );
+
+                    if json {
+                        output::print_json(&output::GrantOnceOutput {
+                            device_id: did.fingerprint(),
+                            name: device.name.clone(),
+                            grant: output::GrantOutput::from(grant),
+                        })?;
+                    } else {
+                        println!(
+                            "{}",
+                            tr_with(
+                                "cli.devices.granted",
+                                &[
+                                    ("device", &device_id),
+                                    ("caps", &capabilities.join(", ")),
+                                    ("duration", &format_duration(duration)),
+                                ]
+                            )
+                        );
+                    }
+                }
+                DevicesCommands::Role { device_id, role } => {
+                    let did = parse_device_id(&device_id)?;
+                    let role: Option<daemon::authz::Role> = role.into();
+                    trust_store.set_role(&did, role)?;
+                    trust_store.save()?;
+                    notify_trust_store_changed(instance).await;
+                    tracing::info!(
+                        target: "audit",
+                        device_id = %device_id,
+                        role = ?role,
+                        "Device role changed"
+                    );
+                    if json {
+                        output::print_json(&output::RoleChangeOutput {
+                            device_id: did.fingerprint(),
+                            role,
+                        })?;
+                    } else {
+                        match role {
+                            Some(role) => println!(
+                                "{}",
+                                tr_with(
+                                    "cli.devices.role_set",
+                                    &[("device", &device_id), ("role", &role)]
+                                )
+                            ),
+                            None => println!(
+                                "{}",
+                                tr_with("cli.devices.role_cleared", &[("device", &device_id)])
+                            ),
+                        }
+                    }
+                }
+                DevicesCommands::Reinvite { device_id, expiry } => {
+                    let did = parse_device_id(&device_id)?;
+                    let device = trust_store
+                        .get_device(&did)?
+                        .ok_or_else(|| anyhow::anyhow!("Device {} not found", device_id))?;
+                    if device.trust_level != daemon::TrustLevel::Revoked {
+                        anyhow::bail!(
+                            "Device {} is not revoked (trust level: {:?})",
+                            device_id,
+                            device.trust_level
+                        );
+                    }
+
+                    let store = daemon::devices::ReinviteStore::for_trust_store(trust_store.path());
+                    let token = store.issue(did, std::time::Duration::from_secs(expiry))?;
+                    tracing::info!(
+                        target: "audit",
+                        device_id = %device_id,
+                        name = %device.name,
+                        expiry_secs = expiry,
+                        "Re-invite token issued"
+                    );
+
+                    if json {
+                        output::print_json(&output::ReinviteOutput {
+                            device_id: did.fingerprint(),
+                            name: device.name,
+                            token,
+                            expires_in_secs: expiry,
+                        })?;
+                    } else {
+                        println!("Re-invite token for {} ({}):", device_id, device.name);
+                        println!();
+                        println!("  {}", token);
+                        println!();
+                        println!(
+                            "The token is valid once for {}. Enter it on the device when reconnecting.",
+                            format_duration(expiry)
+                        );
+                    }
+                }
+                DevicesCommands::Export {
+                    format,
+                    output_file,
+                } => {
+                    let identity = DaemonOrchestrator::load_or_generate_identity(
+                        &config.daemon.data_dir.join(IDENTITY_FILE_NAME),
+                    )?;
+                    let export = daemon::devices::TrustExport::from_store(&trust_store, &identity)?;
+                    let contents = export.to_string(format.into())?;
+
+                    match output_file {
+                        Some(path) => {
+                            std::fs::write(&path, contents).with_context(|| {
+                                format!("Failed to write export: {}", path.display())
+                            })?;
+                            eprintln!(
+                                "Exported {} devices to {} (signed by {})",
+                                export.devices.len(),
+                                path.display(),
+                                identity.fingerprint()
+                            );
+                        }
+                        None => print!("{}", contents),
+                    }
+                }
+                DevicesCommands::Import {
+                    file,
+                    format,
+                    mode,
+                    signer,
+                } => {
+                    let format = format
+                        .map(Into::into)
+                        .or_else(|| daemon::devices::ExportFormat::from_path(&file))
+                        .ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "Cannot infer format of {}; pass --format json|toml",
+                                file.display()
+                            )
+                        })?;
+                    let contents = std::fs::read_to_string(&file)
+                        .with_context(|| format!("Failed to read export: {}", file.display()))?;
+                    let export = daemon::devices::TrustExport::parse(&contents, format)?;
+
+                    let export_signer = export.verify()?;
+                    if let Some(expected) = signer {
+                        let expected = parse_device_id(&expected)?;
+                        if export_signer.device_id() != &expected {
+                            anyhow::bail!(
+                                "Export is signed by {}, expected {}",
+                                export_signer.fingerprint(),
+                                expected.fingerprint()
+                            );
+                        }
+                    }
+
+                    let summary = export.apply(&trust_store, mode.into())?;
+                    trust_store.save()?;
+                    notify_trust_store_changed(instance).await;
+                    tracing::info!(
+                        target: "audit",
+                        signer = %export_signer.fingerprint(),
+                        mode = ?mode,
+                        added = summary.added,
+                        updated = summary.updated,
+                        removed = summary.removed,
+                        "Trust store imported"
+                    );
+
+                    if json {
+                        output::print_json(&output::ImportOutput {
+                            signer: export_signer.fingerprint(),
+                            devices: export.devices.len(),
+                            added: summary.added,
+                            updated: summary.updated,
+                            unchanged: summary.unchanged,
+                            removed: summary.removed,
+                        })?;
+                    } else {
+                        println!(
+                            "Imported {} devices signed by {}: {} added, {} updated, {} unchanged, {} removed",
+                            export.devices.len(),
+                            export_signer.fingerprint(),
+                            summary.added,
+                            summary.updated,
+                            summary.unchanged,
+                            summary.removed
+                        );
+                    }
                 }
             }
         }
         Commands::Sessions(cmd) => {
+            let allow = matches!(cmd, SessionsCommands::Allow { .. });
             // Sessions commands require a running daemon
             match cmd {
-                SessionsCommands::List { json } => match query_sessions_list().await {
-                    Ok(sessions) => {
-                        if json {
-                            println!("{}", serde_json::to_string_pretty(&sessions).unwrap());
-                        } else {
-                            print_sessions_table(&sessions);
+                SessionsCommands::List { json: list_json } => {
+                    match query_sessions_list(instance).await {
+                        Ok(sessions) => {
+                            if json || list_json {
+                                output::print_json(&sessions)?;
+                            } else {
+                                print_sessions_table(&sessions, plain);
+                            }
+                            std::process::exit(0);
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to list sessions: {}", e);
+                            std::process::exit(1);
                         }
-                        std::process::exit(0);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to list sessions: {}", e);
-                        std::process::exit(1);
                     }
-                },
+                }
                 SessionsCommands::Kill {
                     session_id,
                     signal,
@@ -320,12 +1156,20 @@ async fn main() -> anyhow::Result<()> {
                         }
                     };
 
-                    match kill_session(&session_id, signal_num).await {
+                    match kill_session(instance, &session_id, signal_num).await {
                         Ok(()) => {
-                            println!(
-                                "Session {} terminated with {} ({})",
-                                session_id, signal_to_send, signal_num
-                            );
+                            if json {
+                                output::print_json(&output::SessionKillOutput {
+                                    session_id,
+                                    signal: signal_to_send,
+                                    signal_number: signal_num,
+                                })?;
+                            } else {
+                                println!(
+                                    "Session {} terminated with {} ({})",
+                                    session_id, signal_to_send, signal_num
+                                );
+                            }
                             std::process::exit(0);
                         }
                         Err(e) => {
@@ -334,34 +1178,135 @@ async fn main() -> anyhow::Result<()> {
                         }
                     }
                 }
+                SessionsCommands::Pending { json: list_json } => {
+                    match query_session_confirmations(instance).await {
+                        Ok(pending) => {
+                            if json || list_json {
+                                output::print_json(&pending)?;
+                            } else if pending.is_empty() {
+                                println!("{}", tr("cli.sessions.pending_none"));
+                            } else {
+                                for request in &pending {
+                                    println!(
+                                        "{}",
+                                        tr_with(
+                                            "cli.sessions.pending",
+                                            &[
+                                                ("id", &request.id),
+                                                ("name", &request.device_name),
+                                                ("device", &request.device_id),
+                                                ("secs", &request.expires_in_secs),
+                                            ],
+                                        )
+                                    );
+                                }
+                            }
+                            std::process::exit(0);
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to list pending sessions: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                SessionsCommands::Allow { id } | SessionsCommands::Deny { id } => {
+                    match answer_session_confirmation(instance, id, allow).await {
+                        Ok(()) => {
+                            if json {
+                                output::print_json(&output::SessionConfirmationOutput {
+                                    id,
+                                    allowed: allow,
+                                })?;
+                            } else if allow {
+                                println!("{}", tr_with("cli.sessions.allowed", &[("id", &id)]));
+                            } else {
+                                println!("{}", tr_with("cli.sessions.denied", &[("id", &id)]));
+                            }
+                            std::process::exit(0);
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to answer session request {}: {}", id, e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Pair {
+            status: Some(code), ..
+        } => match query_pairing_status(instance, code.clone()).await {
+            Ok(status) => {
+                if json {
+                    output::print_json(&output::PairingStatusOutput { code, status })?;
+                } else {
+                    print_pairing_status(&code, &status);
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to query pairing code: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Pair {
+            relay_url,
+            expiry,
+            preauthorize: Some(public_key),
+            name,
+            ..
+        } => {
+            let name = name.unwrap_or_default();
+            let public_key = parse_public_key(&public_key)?;
+            let identity = DaemonOrchestrator::load_or_generate_identity(
+                &config.daemon.data_dir.join(IDENTITY_FILE_NAME),
+            )?;
+
+            let trust_store = daemon::TrustStore::new(
+                config
+                    .daemon
+                    .data_dir
+                    .join(daemon::devices::TRUST_STORE_FILE_NAME),
+            );
+            trust_store.load()?;
+            let (device_id, changed) = trust_store.preauthorize(public_key, &name)?;
+            if changed {
+                trust_store.save()?;
+                tracing::info!(
+                    target: "audit",
+                    device_id = %device_id,
+                    name = %name,
+                    "Device preauthorized"
+                );
+                notify_trust_store_changed(instance).await;
+                eprintln!(
+                    "Preauthorized device {} ({})",
+                    device_id.fingerprint(),
+                    name
+                );
+            } else {
+                eprintln!(
+                    "Device {} ({}) is already trusted",
+                    device_id.fingerprint(),
+                    name
+                );
             }
+
+            let pairing_info = PairingInfo::from_identity(&identity, relay_url, Some(expiry));
+            println!("{}", pairing_info.to_json()?);
         }
         Commands::Pair {
             format,
-            output,
+            output_file,
             relay_url,
             expiry,
+            numeric,
+            ..
         } => {
             tracing::info!("Generating pairing code with format: {:?}", format);
 
             // Load or generate device identity
-            let data_dir = config.daemon.data_dir;
-            let identity_path = data_dir.join("identity.key");
-            let identity = if identity_path.exists() {
-                let bytes = std::fs::read(&identity_path)?;
-                if bytes.len() != 32 {
-                    anyhow::bail!("Invalid identity file");
-                }
-                let mut key = [0u8; 32];
-                key.copy_from_slice(&bytes);
-                protocol::DeviceIdentity::from_secret_key_bytes(&key)
-            } else {
-                let identity = protocol::DeviceIdentity::generate();
-                std::fs::create_dir_all(&data_dir)?;
-                std::fs::write(&identity_path, identity.secret_key_bytes())?;
-                tracing::info!("Generated new device identity");
-                identity
-            };
+            let identity = DaemonOrchestrator::load_or_generate_identity(
+                &config.daemon.data_dir.join(IDENTITY_FILE_NAME),
+            )?;
 
             tracing::info!("Device identity: {}", identity.device_id().fingerprint());
 
@@ -369,8 +1314,23 @@ async fn main() -> anyhow::Result<()> {
             let pairing_info =
                 PairingInfo::from_identity(&identity, relay_url.clone(), Some(expiry));
 
-            // Generate a short pairing code
-            let code = generate_pairing_code();
+            // Generate a short pairing code and record it so it can only be
+            // used once
+            let code = if numeric {
+                generate_numeric_pairing_code()
+            } else {
+                generate_pairing_code()
+            };
+            daemon::devices::PairingCodeStore::for_trust_store(
+                &config
+                    .daemon
+                    .data_dir
+                    .join(daemon::devices::TRUST_STORE_FILE_NAME),
+            )
+            .register(
+                &code,
+                std::time::Duration::from_secs(pairing_info.seconds_until_expiry()),
+            )?;
 
             // Build pairing URL
             let url = pairing_url(daemon::config::DEFAULT_WEB_APP_URL, &code);
@@ -392,17 +1352,13 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
 
-            match format {
+            let qr_file = match format {
+                PairFormat::Terminal if json || plain => None,
                 PairFormat::Terminal => match generate_terminal_qr_from_data(url.as_bytes()) {
                     Ok(qr) => {
                         println!("\nScan this QR code to pair:\n");
                         println!("{}", qr);
-                        println!("Pairing code: {}", code);
-                        println!("URL: {}", url);
-                        println!(
-                            "Expires in: {} seconds",
-                            pairing_info.seconds_until_expiry()
-                        );
+                        None
                     }
                     Err(e) => {
                         tracing::error!("Failed to generate QR code: {}", e);
@@ -410,24 +1366,36 @@ async fn main() -> anyhow::Result<()> {
                     }
                 },
                 PairFormat::Png => {
-                    let output_path = output.unwrap_or_else(|| PathBuf::from("pairing-qr.png"));
+                    let output_path =
+                        output_file.unwrap_or_else(|| PathBuf::from("pairing-qr.png"));
 
                     match generate_png_qr_from_data(url.as_bytes(), &output_path) {
-                        Ok(()) => {
-                            println!("QR code saved to: {}", output_path.display());
-                            println!("Pairing code: {}", code);
-                            println!("URL: {}", url);
-                            println!(
-                                "Expires in: {} seconds",
-                                pairing_info.seconds_until_expiry()
-                            );
-                        }
+                        Ok(()) => Some(output_path),
                         Err(e) => {
                             tracing::error!("Failed to generate QR code: {}", e);
                             anyhow::bail!("Failed to generate QR code: {}", e);
                         }
                     }
                 }
+            };
+
+            if json {
+                output::print_json(&output::PairOutput {
+                    code,
+                    url,
+                    expires_in_secs: pairing_info.seconds_until_expiry(),
+                    qr_file,
+                })?;
+            } else {
+                if let Some(path) = qr_file {
+                    println!("QR code saved to: {}", path.display());
+                }
+                println!("Pairing code: {}", code);
+                println!("URL: {}", url);
+                println!(
+                    "Expires in: {}",
+                    format_duration(pairing_info.seconds_until_expiry())
+                );
             }
         }
     }
@@ -435,32 +1403,82 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Guards that flush buffered log lines and exported spans when dropped.
+struct TracingGuards {
+    _log_file: Option<tracing_appender::non_blocking::WorkerGuard>,
+    _telemetry: Option<TelemetryGuard>,
+}
+
 /// Initialize tracing based on the command mode.
 ///
 /// In TUI mode, logs are written to a file to avoid corrupting the terminal display.
-/// In all other modes, logs go to stderr as usual.
+/// In all other modes, logs go to stderr as usual. When starting the daemon, log
+/// events are also captured in `log_buffer` so `remoshell logs` can stream them,
+/// spans are exported over OTLP if `telemetry.otlp_endpoint` is set, and the
+/// returned handle changes the log level while the daemon runs.
 fn init_tracing(
     command: &Commands,
+    plain: bool,
     filter: &str,
-    data_dir: &std::path::Path,
-) -> anyhow::Result<Option<tracing_appender::non_blocking::WorkerGuard>> {
+    config: &Config,
+    log_buffer: &LogBuffer,
+) -> anyhow::Result<(TracingGuards, Option<LogLevelHandle>)> {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
     match command {
-        Commands::Start { tui: true, .. } => {
-            // TUI mode: logs to file to avoid corrupting terminal
+        Commands::Start { tui, .. } if *tui || plain => {
+            // TUI and plain modes: logs to file to keep the terminal for the
+            // interface
+            let data_dir = &config.daemon.data_dir;
             std::fs::create_dir_all(data_dir)?;
             let file_appender = tracing_appender::rolling::daily(data_dir, "daemon.log");
             let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
-            tracing_subscriber::fmt()
-                .with_env_filter(filter)
-                .with_writer(non_blocking)
-                .with_ansi(false)
+            let (otlp, telemetry) = telemetry::otlp_layer(&config.telemetry)?.unzip();
+            let (filter, log_level) = LogLevelHandle::layer(filter);
+            tracing_subscriber::registry()
+                .with(filter)
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_writer(non_blocking)
+                        .with_ansi(false),
+                )
+                .with(log_buffer.layer())
+                .with(otlp)
                 .init();
-            Ok(Some(guard))
+            let guards = TracingGuards {
+                _log_file: Some(guard),
+                _telemetry: telemetry,
+            };
+            Ok((guards, Some(log_level)))
+        }
+        Commands::Start { .. } => {
+            // Headless/systemd: logs to stderr and the in-memory buffer
+            let (otlp, telemetry) = telemetry::otlp_layer(&config.telemetry)?.unzip();
+            let (filter, log_level) = LogLevelHandle::layer(filter);
+            tracing_subscriber::registry()
+                .with(filter)
+                .with(tracing_subscriber::fmt::layer())
+                .with(log_buffer.layer())
+                .with(otlp)
+                .init();
+            let guards = TracingGuards {
+                _log_file: None,
+                _telemetry: telemetry,
+            };
+            Ok((guards, Some(log_level)))
         }
         _ => {
-            // Headless/systemd/CLI: logs to stderr
-            tracing_subscriber::fmt().with_env_filter(filter).init();
-            Ok(None)
+            // CLI: logs to stderr
+            tracing_subscriber::fmt()
+                .with_env_filter(filter)
+                .with_writer(std::io::stderr)
+                .init();
+            let guards = TracingGuards {
+                _log_file: None,
+                _telemetry: None,
+            };
+            Ok((guards, None))
         }
     }
 }
@@ -514,19 +1532,72 @@ fn parse_device_id(fingerprint: &str) -> anyhow::Result<protocol::DeviceId> {
     Ok(protocol::DeviceId::from_bytes(arr))
 }
 
-/// Status information returned from the daemon.
-struct DaemonStatus {
-    running: bool,
-    uptime_secs: u64,
-    session_count: usize,
-    device_count: usize,
+/// Parse the `--duration` of `devices grant-once`.
+fn parse_grant_duration(text: &str) -> Result<u64, String> {
+    match display::parse_duration(text) {
+        Some(0) => Err("must be longer than 0s".to_string()),
+        Some(secs) => Ok(secs),
+        None => Err("expected a duration such as 30m, 2h or 1h30m".to_string()),
+    }
+}
+
+/// Parse a device public key given as base64 (as shown by clients) or hex.
+fn parse_public_key(encoded: &str) -> anyhow::Result<[u8; 32]> {
+    use base64::Engine;
+
+    let encoded = encoded.trim();
+    let bytes = if encoded.len() == 64 && encoded.chars().all(|c| c.is_ascii_hexdigit()) {
+        hex::decode(encoded)?
+    } else {
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| anyhow::anyhow!("Invalid public key: {}", e))?
+    };
+
+    bytes.try_into().map_err(|b: Vec<u8>| {
+        anyhow::anyhow!("Invalid public key: expected 32 bytes, got {}", b.len())
+    })
+}
+
+/// Tell a running daemon to reload its trust store after the CLI changed it.
+///
+/// Does nothing if the daemon is not running; the change is picked up on
+/// the next start.
+async fn notify_trust_store_changed(instance: Option<&str>) {
+    use std::time::Duration;
+
+    if !is_daemon_running(instance) {
+        return;
+    }
+
+    let result =
+        match IpcClient::connect_with_timeout(&get_socket_path(instance), Duration::from_secs(5))
+            .await
+        {
+            Ok(mut client) => client.reload_trust_store().await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+    match result {
+        Ok(IpcResponse::TrustStoreReloaded { .. }) => {
+            tracing::debug!("Running daemon reloaded its trust store");
+        }
+        Ok(IpcResponse::Error { message }) => {
+            eprintln!("Warning: daemon failed to reload trust store: {}", message)
+        }
+        Ok(_) => eprintln!("Warning: unexpected response from daemon"),
+        Err(e) => eprintln!(
+            "Warning: could not notify the running daemon ({}); restart it to apply the change",
+            e
+        ),
+    }
 }
 
 /// Query the daemon status via IPC.
-async fn query_daemon_status() -> anyhow::Result<DaemonStatus> {
+async fn query_daemon_status(instance: Option<&str>) -> anyhow::Result<output::StatusOutput> {
     use std::time::Duration;
 
-    let socket_path = get_socket_path();
+    let socket_path = get_socket_path(instance);
 
     // Connect with timeout
     let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
@@ -545,11 +1616,15 @@ async fn query_daemon_status() -> anyhow::Result<DaemonStatus> {
             uptime_secs,
             session_count,
             device_count,
-        } => Ok(DaemonStatus {
+            recovered_from_crash,
+            maintenance,
+        } => Ok(output::StatusOutput {
             running,
             uptime_secs,
             session_count,
             device_count,
+            recovered_from_crash,
+            maintenance,
         }),
         IpcResponse::Error { message } => {
             anyhow::bail!("Daemon returned error: {}", message)
@@ -558,11 +1633,140 @@ async fn query_daemon_status() -> anyhow::Result<DaemonStatus> {
     }
 }
 
+/// Query the addresses of the daemon's network endpoints.
+async fn query_network_info(instance: Option<&str>) -> anyhow::Result<output::NetworkOutput> {
+    use std::time::Duration;
+
+    let socket_path = get_socket_path(instance);
+    let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
+        .await
+        .map_err(|e| anyhow::anyhow!("Cannot connect to daemon: {}", e))?;
+
+    match client
+        .network_info()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query network info: {}", e))?
+    {
+        IpcResponse::NetworkInfo { quic } => Ok(output::NetworkOutput { quic }),
+        IpcResponse::Error { message } => {
+            anyhow::bail!("Daemon returned error: {}", message)
+        }
+        _ => anyhow::bail!("Unexpected response from daemon"),
+    }
+}
+
+/// Query the other daemons of the mesh.
+async fn query_mesh_status(instance: Option<&str>) -> anyhow::Result<output::MeshOutput> {
+    use std::time::Duration;
+
+    let socket_path = get_socket_path(instance);
+    let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
+        .await
+        .map_err(|e| anyhow::anyhow!("Cannot connect to daemon: {}", e))?;
+
+    match client
+        .mesh_status()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query mesh status: {}", e))?
+    {
+        IpcResponse::MeshStatus { group, hosts } => Ok(output::MeshOutput { group, hosts }),
+        IpcResponse::Error { message } => {
+            anyhow::bail!("Daemon returned error: {}", message)
+        }
+        _ => anyhow::bail!("Unexpected response from daemon"),
+    }
+}
+
+/// Turn the daemon's maintenance mode on or off.
+async fn set_maintenance(
+    instance: Option<&str>,
+    enabled: bool,
+    message: Option<String>,
+) -> anyhow::Result<output::MaintenanceOutput> {
+    use std::time::Duration;
+
+    let socket_path = get_socket_path(instance);
+    let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
+        .await
+        .map_err(|e| anyhow::anyhow!("Cannot connect to daemon: {}", e))?;
+
+    match client
+        .set_maintenance(enabled, message)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to set maintenance mode: {}", e))?
+    {
+        IpcResponse::Maintenance { state } => Ok(output::MaintenanceOutput { maintenance: state }),
+        IpcResponse::Error { message } => {
+            anyhow::bail!("Daemon returned error: {}", message)
+        }
+        _ => anyhow::bail!("Unexpected response from daemon"),
+    }
+}
+
 /// Query the list of active sessions from the daemon.
-async fn query_sessions_list() -> anyhow::Result<Vec<daemon::ipc::IpcSessionInfo>> {
+/// Query the daemon for the state of a pairing code.
+async fn query_pairing_status(
+    instance: Option<&str>,
+    code: String,
+) -> anyhow::Result<daemon::devices::PairingCodeStatus> {
+    use std::time::Duration;
+
+    let socket_path = get_socket_path(instance);
+
+    let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
+        .await
+        .map_err(|_| anyhow::anyhow!("Daemon is not running (cannot connect to socket)"))?;
+
+    let response = client
+        .pairing_status(code)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query pairing code: {}", e))?;
+
+    match response {
+        IpcResponse::PairingStatus { status } => Ok(status),
+        IpcResponse::Error { message } => {
+            anyhow::bail!("Daemon returned error: {}", message)
+        }
+        _ => anyhow::bail!("Unexpected response from daemon"),
+    }
+}
+
+/// Print the state of a pairing code.
+fn print_pairing_status(code: &str, status: &daemon::devices::PairingCodeStatus) {
+    use daemon::devices::PairingCodeStatus;
+
+    match status {
+        PairingCodeStatus::Unknown => println!("Pairing code {} is unknown", code),
+        PairingCodeStatus::Pending { expires_at } => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            println!(
+                "Pairing code {} has not been used yet (expires in {})",
+                code,
+                format_duration(expires_at.saturating_sub(now))
+            )
+        }
+        PairingCodeStatus::Consumed {
+            device_id,
+            consumed_at,
+        } => println!(
+            "Pairing code {} was used by device {} {}",
+            code,
+            device_id,
+            format_relative_time(*consumed_at)
+        ),
+        PairingCodeStatus::Expired => println!("Pairing code {} expired unused", code),
+    }
+}
+
+async fn query_sessions_list(
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<daemon::ipc::IpcSessionInfo>> {
     use std::time::Duration;
 
-    let socket_path = get_socket_path();
+    let socket_path = get_socket_path(instance);
 
     // Connect with timeout
     let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
@@ -584,16 +1788,65 @@ async fn query_sessions_list() -> anyhow::Result<Vec<daemon::ipc::IpcSessionInfo
     }
 }
 
+/// Query the sessions waiting for confirmation via IPC.
+async fn query_session_confirmations(
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<daemon::ipc::IpcPendingSession>> {
+    use std::time::Duration;
+
+    let socket_path = get_socket_path(instance);
+    let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
+        .await
+        .map_err(|_| anyhow::anyhow!("Daemon is not running (cannot connect to socket)"))?;
+
+    let response = client
+        .list_session_confirmations()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query pending sessions: {}", e))?;
+
+    match response {
+        IpcResponse::SessionConfirmations { pending } => Ok(pending),
+        IpcResponse::Error { message } => anyhow::bail!("{}", message),
+        _ => anyhow::bail!("Unexpected response from daemon"),
+    }
+}
+
+/// Allow or deny a session waiting for confirmation via IPC.
+async fn answer_session_confirmation(
+    instance: Option<&str>,
+    id: u64,
+    allow: bool,
+) -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    let socket_path = get_socket_path(instance);
+    let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
+        .await
+        .map_err(|_| anyhow::anyhow!("Daemon is not running (cannot connect to socket)"))?;
+
+    let response = client
+        .answer_session_confirmation(id, allow)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to send answer: {}", e))?;
+
+    match response {
+        IpcResponse::SessionConfirmationAnswered { .. } => Ok(()),
+        IpcResponse::Error { message } => anyhow::bail!("{}", message),
+        _ => anyhow::bail!("Unexpected response from daemon"),
+    }
+}
+
 /// Kill a specific session by ID via IPC.
 ///
 /// # Arguments
 ///
+/// * `instance` - The daemon instance running the session.
 /// * `session_id` - The ID of the session to kill.
 /// * `signal` - The signal number to send.
-async fn kill_session(session_id: &str, signal: i32) -> anyhow::Result<()> {
+async fn kill_session(instance: Option<&str>, session_id: &str, signal: i32) -> anyhow::Result<()> {
     use std::time::Duration;
 
-    let socket_path = get_socket_path();
+    let socket_path = get_socket_path(instance);
 
     // Connect with timeout
     let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
@@ -621,10 +1874,77 @@ async fn kill_session(session_id: &str, signal: i32) -> anyhow::Result<()> {
     }
 }
 
+/// Stream daemon logs via IPC and print them to stdout.
+///
+/// Without `follow`, prints the buffered lines and returns. With `follow`,
+/// keeps printing new lines until the daemon stops or the user interrupts.
+/// JSON output prints one object per line.
+async fn stream_daemon_logs(
+    instance: Option<&str>,
+    level: Option<String>,
+    follow: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    let socket_path = get_socket_path(instance);
+
+    // Connect with timeout
+    let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
+        .await
+        .map_err(|_| anyhow::anyhow!("Daemon is not running (cannot connect to socket)"))?;
+
+    client
+        .subscribe_logs(level, follow)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to subscribe to logs: {}", e))?;
+
+    loop {
+        match client.recv().await? {
+            Some(IpcResponse::LogLine(line)) => match format {
+                OutputFormat::Text => println!("{}", format_log_line(&line)),
+                OutputFormat::Json => println!("{}", serde_json::to_string(&line)?),
+            },
+            Some(IpcResponse::LogsEnd) | None => return Ok(()),
+            Some(IpcResponse::Error { message }) => {
+                anyhow::bail!("Daemon returned error: {}", message)
+            }
+            Some(_) => anyhow::bail!("Unexpected response from daemon"),
+        }
+    }
+}
+
+/// Format a log line as `HH:MM:SS.mmm LEVEL target: message` (UTC).
+fn format_log_line(line: &LogLine) -> String {
+    let millis = line.timestamp_ms % 1000;
+    let secs_of_day = (line.timestamp_ms / 1000) % 86400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03} {:>5} {}: {}",
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60,
+        secs_of_day % 60,
+        millis,
+        line.level,
+        line.target,
+        line.message
+    )
+}
+
 /// Print sessions in a formatted ASCII table.
-fn print_sessions_table(sessions: &[daemon::ipc::IpcSessionInfo]) {
+fn print_sessions_table(sessions: &[daemon::ipc::IpcSessionInfo], plain: bool) {
     if sessions.is_empty() {
-        println!("No active sessions.");
+        println!("{}", tr("cli.sessions.none"));
+        return;
+    }
+
+    if plain {
+        for session in sessions {
+            println!("{}", plain_session_line(session));
+        }
+        println!(
+            "{}",
+            tr_with("cli.sessions.total", &[("count", &sessions.len())])
+        );
         return;
     }
 
@@ -644,52 +1964,90 @@ fn print_sessions_table(sessions: &[daemon::ipc::IpcSessionInfo]) {
 
     // Print header
     println!(
-        "{:<id_width$}  {:<peer_width$}  {:>12}",
+        "{:<id_width$}  {:<peer_width$}  {:>12}  {:>4}  CWD",
         "ID",
         "PEER ID",
         "CONNECTED",
+        "EXIT",
         id_width = id_width,
         peer_width = peer_width
     );
-    println!("{}", "-".repeat(id_width + peer_width + 16));
+    println!("{}", "-".repeat(id_width + peer_width + 27));
 
     // Print rows
     for session in sessions {
         let peer_id = session.peer_id.as_deref().unwrap_or("-");
         let connected = format_relative_time(session.connected_at);
+        let exit_code = session
+            .last_exit_code
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let cwd = session.cwd.as_deref().unwrap_or("-");
 
         println!(
-            "{:<id_width$}  {:<peer_width$}  {:>12}",
+            "{:<id_width$}  {:<peer_width$}  {:>12}  {:>4}  {}",
             truncate_str(&session.id, id_width),
             truncate_str(peer_id, peer_width),
             connected,
+            exit_code,
+            cwd,
             id_width = id_width,
             peer_width = peer_width
         );
     }
 
     println!();
-    println!("Total: {} session(s)", sessions.len());
+    println!(
+        "{}",
+        tr_with("cli.sessions.total", &[("count", &sessions.len())])
+    );
 }
 
-/// Format a Unix timestamp as relative time (e.g., "5m ago").
-fn format_relative_time(timestamp: u64) -> String {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-
-    let elapsed = now.saturating_sub(timestamp);
-
-    if elapsed < 60 {
-        format!("{}s ago", elapsed)
-    } else if elapsed < 3600 {
-        format!("{}m ago", elapsed / 60)
-    } else if elapsed < 86400 {
-        format!("{}h ago", elapsed / 3600)
+/// Returns the indentation of nested lines, which plain output leaves out.
+fn indent(plain: bool) -> &'static str {
+    if plain {
+        ""
     } else {
-        format!("{}d ago", elapsed / 86400)
+        "  "
+    }
+}
+
+/// Print `label value` lines, with the values aligned unless `plain`.
+fn print_fields(fields: &[(&str, String)], plain: bool) {
+    let width = fields
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (label, value) in fields {
+        if plain {
+            println!("{} {}", label, value);
+        } else {
+            println!("  {:<width$} {}", label, value, width = width);
+        }
+    }
+}
+
+/// Describe a session in one sentence, for plain output.
+fn plain_session_line(session: &daemon::ipc::IpcSessionInfo) -> String {
+    let device = session.peer_id.as_deref().unwrap_or("-");
+    let mut line = tr_with(
+        "cli.sessions.plain",
+        &[
+            ("id", &session.id),
+            ("device", &device),
+            ("when", &format_relative_time(session.connected_at)),
+        ],
+    );
+    if let Some(code) = session.last_exit_code {
+        line.push_str(", ");
+        line.push_str(&tr_with("cli.sessions.plain_exit", &[("code", &code)]));
     }
+    if let Some(cwd) = &session.cwd {
+        line.push_str(", ");
+        line.push_str(&tr_with("cli.sessions.plain_cwd", &[("cwd", cwd)]));
+    }
+    line
 }
 
 /// Truncate a string to a maximum length, adding "..." if truncated.
@@ -704,18 +2062,18 @@ fn truncate_str(s: &str, max_len: usize) -> String {
 /// Gracefully stop the daemon via IPC.
 ///
 /// Sends a shutdown request to the daemon and waits for acknowledgment.
-async fn graceful_stop_daemon(timeout_secs: u64) -> anyhow::Result<()> {
+async fn graceful_stop_daemon(instance: Option<&str>, timeout_secs: u64) -> anyhow::Result<()> {
     use daemon::ipc::get_pid_file_path;
     use std::time::Duration;
 
-    let socket_path = get_socket_path();
+    let socket_path = get_socket_path(instance);
 
     // Connect to daemon
     let mut client = IpcClient::connect_with_timeout(&socket_path, Duration::from_secs(5))
         .await
         .map_err(|_| anyhow::anyhow!("Daemon is not running (cannot connect to socket)"))?;
 
-    println!("Sending shutdown request...");
+    eprintln!("{}", tr("cli.stop.sending"));
 
     // Send shutdown request with custom timeout
     client.set_timeout(Duration::from_secs(timeout_secs));
@@ -726,7 +2084,7 @@ async fn graceful_stop_daemon(timeout_secs: u64) -> anyhow::Result<()> {
 
     match response {
         IpcResponse::Stopping => {
-            println!("Shutdown acknowledged, waiting for daemon to exit...");
+            eprintln!("{}", tr("cli.stop.acknowledged"));
         }
         IpcResponse::Error { message } => {
             anyhow::bail!("Daemon returned error: {}", message);
@@ -736,10 +2094,21 @@ async fn graceful_stop_daemon(timeout_secs: u64) -> anyhow::Result<()> {
         }
     }
 
-    // Wait for daemon to actually exit by polling the socket
     let start = std::time::Instant::now();
     let timeout = Duration::from_secs(timeout_secs);
 
+    // Report the daemon's progress until it closes the connection
+    while let Some(remaining) = timeout.checked_sub(start.elapsed()) {
+        match tokio::time::timeout(remaining, client.recv()).await {
+            Ok(Ok(Some(IpcResponse::StopProgress { subsystem, detail }))) => {
+                eprintln!("  {}: {}", subsystem, detail);
+            }
+            Ok(Ok(Some(_))) => {}
+            Ok(Ok(None)) | Ok(Err(_)) | Err(_) => break,
+        }
+    }
+
+    // Wait for daemon to actually exit by polling the socket
     while start.elapsed() < timeout {
         // Check if socket is gone (daemon exited)
         if !socket_path.exists() {
@@ -752,7 +2121,7 @@ async fn graceful_stop_daemon(timeout_secs: u64) -> anyhow::Result<()> {
             .is_err()
         {
             // Clean up stale PID file if it exists
-            let pid_path = get_pid_file_path();
+            let pid_path = get_pid_file_path(instance);
             let _ = std::fs::remove_file(&pid_path);
             return Ok(());
         }
@@ -769,12 +2138,12 @@ async fn graceful_stop_daemon(timeout_secs: u64) -> anyhow::Result<()> {
 /// Force stop the daemon using SIGKILL.
 ///
 /// Reads the daemon PID from the PID file and sends SIGKILL.
-fn force_stop_daemon() -> anyhow::Result<()> {
+fn force_stop_daemon(instance: Option<&str>) -> anyhow::Result<()> {
     use daemon::ipc::get_pid_file_path;
     use nix::sys::signal::{kill, Signal};
     use nix::unistd::Pid;
 
-    let pid_path = get_pid_file_path();
+    let pid_path = get_pid_file_path(instance);
 
     if !pid_path.exists() {
         return Err(anyhow::anyhow!(
@@ -793,38 +2162,36 @@ fn force_stop_daemon() -> anyhow::Result<()> {
     kill(Pid::from_raw(pid), Signal::SIGKILL)
         .map_err(|e| anyhow::anyhow!("Failed to kill daemon (PID {}): {}", pid, e))?;
 
-    println!("Sent SIGKILL to daemon (PID {})", pid);
+    eprintln!("Sent SIGKILL to daemon (PID {})", pid);
 
     // Clean up PID file
     let _ = std::fs::remove_file(&pid_path);
 
     // Clean up socket file
-    let socket_path = get_socket_path();
+    let socket_path = get_socket_path(instance);
     let _ = std::fs::remove_file(&socket_path);
-
-    Ok(())
-}
-
-/// Format a duration in seconds to human-readable format.
-fn format_duration(secs: u64) -> String {
-    let hours = secs / 3600;
-    let minutes = (secs % 3600) / 60;
-    let seconds = secs % 60;
-
-    if hours > 0 {
-        format!("{}h {}m {}s", hours, minutes, seconds)
-    } else if minutes > 0 {
-        format!("{}m {}s", minutes, seconds)
-    } else {
-        format!("{}s", seconds)
-    }
+
+    Ok(())
 }
 
 /// Run the daemon in headless mode.
-async fn run_headless(orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<()> {
+///
+/// With `plain`, events and approval prompts are announced on the terminal.
+async fn run_headless(orchestrator: &mut DaemonOrchestrator, plain: bool) -> anyhow::Result<()> {
     // Start the orchestrator
     orchestrator.start().await?;
 
+    let announcements = tokio_util::sync::CancellationToken::new();
+    let announcer = plain.then(|| {
+        tokio::spawn(daemon::ui::announce::run(
+            orchestrator.subscribe(),
+            orchestrator.trust_store().clone(),
+            orchestrator.session_confirmations().cloned(),
+            orchestrator.device_id_fingerprint(),
+            announcements.clone(),
+        ))
+    });
+
     // Subscribe to orchestrator events for logging
     let mut events = orchestrator.subscribe();
 
@@ -841,28 +2208,56 @@ async fn run_headless(orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<(
                 OrchestratorEvent::PeerDisconnected { device_id, reason } => {
                     tracing::info!("Peer disconnected: {} ({})", device_id, reason);
                 }
+                #[cfg(feature = "webrtc")]
                 OrchestratorEvent::SignalingStateChanged(state) => {
                     tracing::debug!("Signaling state: {:?}", state);
                 }
                 OrchestratorEvent::Error { message } => {
                     tracing::error!("Orchestrator error: {}", message);
                 }
+                OrchestratorEvent::SubsystemFailed {
+                    name, restarting, ..
+                } => {
+                    if !restarting {
+                        tracing::warn!("Subsystem {} will not be restarted", name);
+                    }
+                }
+                OrchestratorEvent::TaskPanicked {
+                    task, restarting, ..
+                } => {
+                    if !restarting {
+                        tracing::warn!("Task {} will not be restarted", task);
+                    }
+                }
+                // Logged by the orchestrator
+                OrchestratorEvent::ShutdownProgress { .. }
+                | OrchestratorEvent::GrantEnded { .. } => {}
             }
         }
     });
 
-    // Wait for shutdown signal (SIGTERM or SIGINT)
-    wait_for_shutdown_signal().await;
+    // Wait for a shutdown signal or a stop request
+    wait_for_shutdown_signal(orchestrator.stop_requested()).await;
     tracing::info!("Received shutdown signal");
 
+    announcements.cancel();
+    if let Some(announcer) = announcer {
+        match announcer.await {
+            Ok(Err(e)) => tracing::warn!("Failed to write announcements: {}", e),
+            Err(e) => tracing::warn!("Announcement task failed: {}", e),
+            Ok(Ok(())) => {}
+        }
+    }
+
     // Stop the orchestrator
     orchestrator.stop().await?;
 
     Ok(())
 }
 
-/// Wait for a shutdown signal (SIGTERM or SIGINT).
-async fn wait_for_shutdown_signal() {
+/// Wait for a shutdown signal (SIGTERM or SIGINT) or a stop requested over
+/// IPC.
+async fn wait_for_shutdown_signal(stop_requested: tokio_util::sync::CancellationToken) {
     use tokio::signal::unix::{signal, SignalKind};
 
     let mut sigterm = signal(SignalKind::terminate()).expect("Failed to register SIGTERM handler");
@@ -875,6 +2270,9 @@ async fn wait_for_shutdown_signal() {
         _ = sigint.recv() => {
             tracing::info!("Received SIGINT");
         }
+        _ = stop_requested.cancelled() => {
+            tracing::info!("Received stop request");
+        }
     }
 }
 
@@ -905,13 +2303,30 @@ async fn run_systemd_mode(orchestrator: &mut DaemonOrchestrator) -> anyhow::Resu
                 OrchestratorEvent::Error { message } => {
                     tracing::error!("Orchestrator error: {}", message);
                 }
+                OrchestratorEvent::SubsystemFailed {
+                    name,
+                    restarting: false,
+                    ..
+                } => {
+                    daemon::notify_status(&format!("Degraded: {} subsystem failed", name));
+                }
+                OrchestratorEvent::TaskPanicked {
+                    task,
+                    restarting: false,
+                    ..
+                } => {
+                    daemon::notify_status(&format!("Degraded: {} task crashed", task));
+                }
+                OrchestratorEvent::ShutdownProgress { subsystem, detail } => {
+                    daemon::notify_status(&format!("Stopping: {} {}", subsystem, detail));
+                }
                 _ => {}
             }
         }
     });
 
-    // Wait for shutdown signal (SIGTERM or SIGINT)
-    wait_for_shutdown_signal().await;
+    // Wait for a shutdown signal or a stop request
+    wait_for_shutdown_signal(orchestrator.stop_requested()).await;
     tracing::info!("Received shutdown signal");
 
     // Notify systemd we're stopping
@@ -924,6 +2339,7 @@ async fn run_systemd_mode(orchestrator: &mut DaemonOrchestrator) -> anyhow::Resu
 }
 
 /// Run the daemon with TUI interface.
+#[cfg(feature = "tui")]
 async fn run_with_tui(orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<()> {
     use daemon::session::SessionManager as _;
     use daemon::ui::tui::{process_approval_result, PairingConfig, TuiApp, TuiEvent};
@@ -941,11 +2357,18 @@ async fn run_with_tui(orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<(
             device_id_bytes,
             public_key_bytes: identity.public_key_bytes(),
             relay_url: orchestrator.signaling_url().to_string(),
+            pairing_codes: daemon::devices::PairingCodeStore::for_trust_store(
+                orchestrator.trust_store().path(),
+            ),
         }
     };
 
     // Create TUI app
     let (mut tui_app, tui_tx) = TuiApp::new(Some(pairing_config))?;
+    if let Some(confirmations) = orchestrator.session_confirmations() {
+        tui_app.set_session_confirmations(confirmations.clone());
+    }
+    tui_app.set_maintenance(orchestrator.maintenance().clone());
 
     // Take the approval receiver to handle approval results
     let mut approval_rx = tui_app
@@ -975,6 +2398,7 @@ async fn run_with_tui(orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<(
     let stats_session_mgr = orchestrator.session_manager().clone();
     let stats_handle = tokio::spawn(async move {
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+        let mut last_metadata = std::collections::HashMap::new();
         loop {
             interval.tick().await;
             let devices_connected = stats_connections.read().await.len();
@@ -990,23 +2414,62 @@ async fn run_with_tui(orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<(
             {
                 break;
             }
+
+            // Forward shell integration changes (cwd, exit status)
+            let sessions = stats_session_mgr.list();
+            last_metadata.retain(|id, _| sessions.iter().any(|s| &s.id == id));
+            for session in sessions {
+                if last_metadata.get(&session.id) == Some(&session.shell) {
+                    continue;
+                }
+                let event = TuiEvent::SessionMetadata {
+                    session_id: session.id.clone(),
+                    cwd: session.shell.cwd.clone(),
+                    last_exit_code: session.shell.last_exit_code,
+                };
+                last_metadata.insert(session.id, session.shell);
+                if stats_tx.send(event).await.is_err() {
+                    return;
+                }
+            }
         }
     });
 
     // Spawn task to forward orchestrator events to TUI
     let mut orch_events = orchestrator.subscribe();
     let orch_tx = tui_tx.clone();
+    let orch_trust_store = std::sync::Arc::clone(orchestrator.trust_store());
     let orch_handle = tokio::spawn(async move {
+        let lookup = |device_id: &str| {
+            parse_device_id(device_id)
+                .ok()
+                .and_then(|id| orch_trust_store.get_device(&id).ok().flatten())
+        };
         while let Ok(event) = orch_events.recv().await {
             let tui_event = match event {
-                OrchestratorEvent::PeerConnected { device_id } => Some(TuiEvent::DeviceConnected {
-                    device_id,
-                    name: "Unknown".to_string(),
-                    trust_level: DisplayTrustLevel::Unknown,
-                }),
+                OrchestratorEvent::PeerConnected { device_id } => {
+                    let device = lookup(&device_id);
+                    Some(TuiEvent::DeviceConnected {
+                        name: device
+                            .as_ref()
+                            .map_or_else(|| "Unknown".to_string(), |d| d.name.clone()),
+                        trust_level: device
+                            .as_ref()
+                            .map_or(DisplayTrustLevel::Unknown, |d| d.trust_level.into()),
+                        grant_expires_at: device
+                            .and_then(|d| d.grant)
+                            .map(|grant| grant.expires_at),
+                        device_id,
+                    })
+                }
                 OrchestratorEvent::PeerDisconnected { device_id, .. } => {
                     Some(TuiEvent::DeviceDisconnected { device_id })
                 }
+                OrchestratorEvent::GrantEnded { device_id, .. } => Some(TuiEvent::GrantEnded {
+                    trust_level: lookup(&device_id)
+                        .map_or(DisplayTrustLevel::Unknown, |d| d.trust_level.into()),
+                    device_id,
+                }),
                 _ => None,
             };
             if let Some(evt) = tui_event {
@@ -1017,8 +2480,12 @@ async fn run_with_tui(orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<(
         }
     });
 
-    // Run the TUI event loop
-    let result = tui_app.run().await;
+    // Run the TUI event loop until it quits or a stop is requested
+    let stop_requested = orchestrator.stop_requested();
+    let result = tokio::select! {
+        result = tui_app.run() => result,
+        _ = stop_requested.cancelled() => Ok(()),
+    };
 
     // Cleanup
     orch_handle.abort();
@@ -1034,6 +2501,12 @@ async fn run_with_tui(orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<(
     result.map_err(|e| anyhow::anyhow!("TUI error: {}", e))
 }
 
+/// Stands in for the TUI in builds without the `tui` feature.
+#[cfg(not(feature = "tui"))]
+async fn run_with_tui(_orchestrator: &mut DaemonOrchestrator) -> anyhow::Result<()> {
+    anyhow::bail!("--tui is not available: the daemon was built without the `tui` feature")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1049,7 +2522,7 @@ mod tests {
     fn test_start_command() {
         let cli = Cli::try_parse_from(["remoshell", "start"]).unwrap();
         match cli.command {
-            Commands::Start { tui, systemd } => {
+            Commands::Start { tui, systemd, .. } => {
                 assert!(!tui);
                 assert!(!systemd);
             }
@@ -1061,7 +2534,7 @@ mod tests {
     fn test_start_with_tui() {
         let cli = Cli::try_parse_from(["remoshell", "start", "--tui"]).unwrap();
         match cli.command {
-            Commands::Start { tui, systemd } => {
+            Commands::Start { tui, systemd, .. } => {
                 assert!(tui);
                 assert!(!systemd);
             }
@@ -1073,7 +2546,7 @@ mod tests {
     fn test_start_with_systemd() {
         let cli = Cli::try_parse_from(["remoshell", "start", "--systemd"]).unwrap();
         match cli.command {
-            Commands::Start { tui, systemd } => {
+            Commands::Start { tui, systemd, .. } => {
                 assert!(!tui);
                 assert!(systemd);
             }
@@ -1081,6 +2554,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_start_with_soak_report() {
+        let cli = Cli::try_parse_from([
+            "remoshell",
+            "start",
+            "--soak-report",
+            "/tmp/soak.jsonl",
+            "--soak-interval",
+            "5",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Start {
+                soak_report,
+                soak_interval,
+                ..
+            } => {
+                assert_eq!(soak_report, Some(PathBuf::from("/tmp/soak.jsonl")));
+                assert_eq!(soak_interval, 5);
+            }
+            _ => panic!("Expected Start command"),
+        }
+    }
+
+    #[test]
+    fn test_start_soak_interval_requires_report() {
+        assert!(Cli::try_parse_from(["remoshell", "start", "--soak-interval", "5"]).is_err());
+        assert!(Cli::try_parse_from([
+            "remoshell",
+            "start",
+            "--soak-report",
+            "soak.jsonl",
+            "--soak-interval",
+            "0"
+        ])
+        .is_err());
+    }
+
     #[test]
     fn test_stop_command() {
         let cli = Cli::try_parse_from(["remoshell", "stop"]).unwrap();
@@ -1147,11 +2658,91 @@ mod tests {
         assert!(matches!(cli.command, Commands::Status));
     }
 
+    #[test]
+    fn test_network_command() {
+        let cli = Cli::try_parse_from(["remoshell", "network"]).unwrap();
+        assert!(matches!(cli.command, Commands::Network));
+    }
+
+    #[test]
+    fn test_mesh_command() {
+        let cli = Cli::try_parse_from(["remoshell", "mesh"]).unwrap();
+        assert!(matches!(cli.command, Commands::Mesh));
+    }
+
+    #[test]
+    fn test_maintenance_command() {
+        let cli = Cli::try_parse_from([
+            "remoshell",
+            "maintenance",
+            "on",
+            "--message",
+            "disk upgrade",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Maintenance(MaintenanceCommands::On { message }) => {
+                assert_eq!(message.as_deref(), Some("disk upgrade"));
+            }
+            other => panic!("expected maintenance on, got {:?}", other),
+        }
+
+        let cli = Cli::try_parse_from(["remoshell", "maintenance", "off"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Maintenance(MaintenanceCommands::Off)
+        ));
+    }
+
+    #[test]
+    fn test_doctor_command() {
+        let cli = Cli::try_parse_from(["remoshell", "doctor"]).unwrap();
+        assert!(matches!(cli.command, Commands::Doctor));
+    }
+
+    #[test]
+    fn test_shell_hook_command() {
+        let cli = Cli::try_parse_from(["remoshell", "shell-hook", "zsh"]).unwrap();
+        match cli.command {
+            Commands::ShellHook { shell } => assert_eq!(shell, "zsh"),
+            _ => panic!("Expected ShellHook command"),
+        }
+        assert!(Cli::try_parse_from(["remoshell", "shell-hook", "fish"]).is_err());
+    }
+
+    #[test]
+    fn test_protocol_dump_schema() {
+        let cli = Cli::try_parse_from(["remoshell", "protocol", "dump-schema"]).unwrap();
+        match cli.command {
+            Commands::Protocol(ProtocolCommands::DumpSchema { format }) => {
+                assert_eq!(format, SchemaFormat::Json)
+            }
+            _ => panic!("Expected Protocol DumpSchema command"),
+        }
+        let cli = Cli::try_parse_from(["remoshell", "protocol", "dump-schema", "-f", "markdown"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Protocol(ProtocolCommands::DumpSchema {
+                format: SchemaFormat::Markdown
+            })
+        ));
+    }
+
     #[test]
     fn test_devices_list() {
         let cli = Cli::try_parse_from(["remoshell", "devices", "list"]).unwrap();
         match cli.command {
-            Commands::Devices(DevicesCommands::List) => {}
+            Commands::Devices(DevicesCommands::List { verbose }) => assert!(!verbose),
+            _ => panic!("Expected Devices List command"),
+        }
+    }
+
+    #[test]
+    fn test_devices_list_verbose() {
+        let cli = Cli::try_parse_from(["remoshell", "devices", "list", "--verbose"]).unwrap();
+        match cli.command {
+            Commands::Devices(DevicesCommands::List { verbose }) => assert!(verbose),
             _ => panic!("Expected Devices List command"),
         }
     }
@@ -1178,6 +2769,188 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_devices_reinvite() {
+        let cli = Cli::try_parse_from(["remoshell", "devices", "reinvite", "device789"]).unwrap();
+        match cli.command {
+            Commands::Devices(DevicesCommands::Reinvite { device_id, expiry }) => {
+                assert_eq!(device_id, "device789");
+                assert_eq!(expiry, 86400);
+            }
+            _ => panic!("Expected Devices Reinvite command"),
+        }
+    }
+
+    #[test]
+    fn test_devices_grant_once() {
+        let cli = Cli::try_parse_from([
+            "remoshell",
+            "devices",
+            "grant-once",
+            "device789",
+            "--duration",
+            "30m",
+            "--caps",
+            "shell,files",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Devices(DevicesCommands::GrantOnce {
+                device_id,
+                duration,
+                caps,
+            }) => {
+                assert_eq!(device_id, "device789");
+                assert_eq!(duration, 1800);
+                assert_eq!(caps, ["shell", "files"]);
+            }
+            _ => panic!("Expected Devices GrantOnce command"),
+        }
+
+        // Shell access only by default
+        let cli = Cli::try_parse_from([
+            "remoshell",
+            "devices",
+            "grant-once",
+            "device789",
+            "--duration",
+            "1h",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Devices(DevicesCommands::GrantOnce { caps, .. }) if caps == ["shell"]
+        ));
+
+        for args in [
+            ["--duration", "soon", "--caps", "shell"],
+            ["--duration", "0s", "--caps", "shell"],
+            ["--duration", "30m", "--caps", "root"],
+        ] {
+            let mut argv = vec!["remoshell", "devices", "grant-once", "device789"];
+            argv.extend(args);
+            assert!(Cli::try_parse_from(argv).is_err());
+        }
+    }
+
+    #[test]
+    fn test_devices_role() {
+        let cli =
+            Cli::try_parse_from(["remoshell", "devices", "role", "device789", "admin"]).unwrap();
+        match cli.command {
+            Commands::Devices(DevicesCommands::Role { device_id, role }) => {
+                assert_eq!(device_id, "device789");
+                assert_eq!(role, DeviceRole::Admin);
+                assert_eq!(
+                    Option::<daemon::authz::Role>::from(role),
+                    Some(daemon::authz::Role::Admin)
+                );
+            }
+            _ => panic!("Expected Devices Role command"),
+        }
+
+        let cli =
+            Cli::try_parse_from(["remoshell", "devices", "role", "device789", "none"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Devices(DevicesCommands::Role {
+                role: DeviceRole::None,
+                ..
+            })
+        ));
+        assert!(
+            Cli::try_parse_from(["remoshell", "devices", "role", "device789", "root"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_devices_export() {
+        let cli =
+            Cli::try_parse_from(["remoshell", "devices", "export", "--format", "toml"]).unwrap();
+        match cli.command {
+            Commands::Devices(DevicesCommands::Export {
+                format,
+                output_file,
+            }) => {
+                assert_eq!(format, TrustFileFormat::Toml);
+                assert!(output_file.is_none());
+            }
+            _ => panic!("Expected Devices Export command"),
+        }
+    }
+
+    #[test]
+    fn test_devices_import() {
+        let cli = Cli::try_parse_from([
+            "remoshell",
+            "devices",
+            "import",
+            "fleet.json",
+            "--mode",
+            "replace",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Devices(DevicesCommands::Import {
+                file,
+                format,
+                mode,
+                signer,
+            }) => {
+                assert_eq!(file, PathBuf::from("fleet.json"));
+                assert!(format.is_none());
+                assert_eq!(mode, TrustImportMode::Replace);
+                assert!(signer.is_none());
+            }
+            _ => panic!("Expected Devices Import command"),
+        }
+    }
+
+    #[test]
+    fn test_profile_flag() {
+        let cli = Cli::try_parse_from(["remoshell", "--profile", "work", "status"]).unwrap();
+        assert_eq!(cli.profile.as_deref(), Some("work"));
+
+        let cli =
+            Cli::try_parse_from(["remoshell", "devices", "list", "--profile", "home"]).unwrap();
+        assert_eq!(cli.profile.as_deref(), Some("home"));
+    }
+
+    #[test]
+    fn test_instance_flag() {
+        let cli = Cli::try_parse_from(["remoshell", "--instance", "test", "start"]).unwrap();
+        assert_eq!(cli.instance.as_deref(), Some("test"));
+
+        let cli = Cli::try_parse_from(["remoshell", "stop", "--instance", "test"]).unwrap();
+        assert_eq!(cli.instance.as_deref(), Some("test"));
+    }
+
+    #[test]
+    fn test_output_format_default_text() {
+        let cli = Cli::try_parse_from(["remoshell", "status"]).unwrap();
+        assert_eq!(cli.output, OutputFormat::Text);
+    }
+
+    #[test]
+    fn test_output_format_json_global() {
+        let cli = Cli::try_parse_from(["remoshell", "--output", "json", "status"]).unwrap();
+        assert_eq!(cli.output, OutputFormat::Json);
+
+        let cli =
+            Cli::try_parse_from(["remoshell", "devices", "list", "--output", "json"]).unwrap();
+        assert_eq!(cli.output, OutputFormat::Json);
+        assert!(matches!(
+            cli.command,
+            Commands::Devices(DevicesCommands::List { .. })
+        ));
+    }
+
+    #[test]
+    fn test_output_format_invalid() {
+        let result = Cli::try_parse_from(["remoshell", "--output", "yaml", "status"]);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_sessions_list() {
         let cli = Cli::try_parse_from(["remoshell", "sessions", "list"]).unwrap();
@@ -1326,23 +3099,86 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sessions_confirmation_commands() {
+        let cli = Cli::try_parse_from(["remoshell", "sessions", "pending", "--json"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Sessions(SessionsCommands::Pending { json: true })
+        ));
+
+        let cli = Cli::try_parse_from(["remoshell", "sessions", "allow", "3"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Sessions(SessionsCommands::Allow { id: 3 })
+        ));
+
+        let cli = Cli::try_parse_from(["remoshell", "sessions", "deny", "4"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Sessions(SessionsCommands::Deny { id: 4 })
+        ));
+
+        assert!(Cli::try_parse_from(["remoshell", "sessions", "allow", "abc"]).is_err());
+    }
+
     #[test]
     fn test_pair_default_format() {
         let cli = Cli::try_parse_from(["remoshell", "pair"]).unwrap();
         match cli.command {
             Commands::Pair {
                 format,
-                output,
+                output_file,
                 relay_url,
                 expiry,
+                preauthorize,
+                name,
+                numeric,
+                status,
             } => {
                 assert_eq!(format, PairFormat::Terminal);
-                assert!(output.is_none());
+                assert!(output_file.is_none());
                 assert_eq!(relay_url, DEFAULT_SIGNALING_URL);
                 assert_eq!(expiry, 300);
+                assert!(preauthorize.is_none());
+                assert!(name.is_none());
+                assert!(!numeric);
+                assert!(status.is_none());
+            }
+            _ => panic!("Expected Pair command"),
+        }
+    }
+
+    #[test]
+    fn test_pair_numeric() {
+        let cli = Cli::try_parse_from(["remoshell", "pair", "--numeric"]).unwrap();
+        match cli.command {
+            Commands::Pair { numeric, .. } => assert!(numeric),
+            _ => panic!("Expected Pair command"),
+        }
+    }
+
+    #[test]
+    fn test_pair_status() {
+        let cli = Cli::try_parse_from(["remoshell", "pair", "--status", "ABCD-1234"]).unwrap();
+        match cli.command {
+            Commands::Pair { status, .. } => {
+                assert_eq!(status.as_deref(), Some("ABCD-1234"));
             }
             _ => panic!("Expected Pair command"),
         }
+
+        assert!(Cli::try_parse_from([
+            "remoshell",
+            "pair",
+            "--status",
+            "ABCD-1234",
+            "--preauthorize",
+            "key",
+            "--name",
+            "phone"
+        ])
+        .is_err());
     }
 
     #[test]
@@ -1379,14 +3215,36 @@ mod tests {
     }
 
     #[test]
-    fn test_pair_with_output() {
-        let cli =
-            Cli::try_parse_from(["remoshell", "pair", "-f", "png", "--output", "/tmp/qr.png"])
-                .unwrap();
+    fn test_pair_with_output_file() {
+        let cli = Cli::try_parse_from([
+            "remoshell",
+            "pair",
+            "-f",
+            "png",
+            "--output-file",
+            "/tmp/qr.png",
+        ])
+        .unwrap();
         match cli.command {
-            Commands::Pair { format, output, .. } => {
+            Commands::Pair {
+                format,
+                output_file,
+                ..
+            } => {
                 assert_eq!(format, PairFormat::Png);
-                assert_eq!(output, Some(PathBuf::from("/tmp/qr.png")));
+                assert_eq!(output_file, Some(PathBuf::from("/tmp/qr.png")));
+            }
+            _ => panic!("Expected Pair command"),
+        }
+    }
+
+    #[test]
+    fn test_pair_output_file_short_flag() {
+        let cli =
+            Cli::try_parse_from(["remoshell", "pair", "-f", "png", "-o", "/tmp/qr.png"]).unwrap();
+        match cli.command {
+            Commands::Pair { output_file, .. } => {
+                assert_eq!(output_file, Some(PathBuf::from("/tmp/qr.png")));
             }
             _ => panic!("Expected Pair command"),
         }
@@ -1416,6 +3274,93 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pair_preauthorize() {
+        let cli = Cli::try_parse_from([
+            "remoshell",
+            "pair",
+            "--preauthorize",
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+            "--name",
+            "ci-runner",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Pair {
+                preauthorize, name, ..
+            } => {
+                assert_eq!(
+                    preauthorize.as_deref(),
+                    Some("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=")
+                );
+                assert_eq!(name.as_deref(), Some("ci-runner"));
+            }
+            _ => panic!("Expected Pair command"),
+        }
+    }
+
+    #[test]
+    fn test_pair_preauthorize_requires_name() {
+        let result = Cli::try_parse_from(["remoshell", "pair", "--preauthorize", "key"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_public_key() {
+        let key = [7u8; 32];
+        let b64 = {
+            use base64::Engine;
+            base64::engine::general_purpose::STANDARD.encode(key)
+        };
+        assert_eq!(parse_public_key(&b64).unwrap(), key);
+        assert_eq!(parse_public_key(&hex::encode(key)).unwrap(), key);
+        assert!(parse_public_key("dG9vIHNob3J0").is_err());
+    }
+
+    #[test]
+    fn test_logs_command() {
+        let cli = Cli::try_parse_from(["remoshell", "logs"]).unwrap();
+        match cli.command {
+            Commands::Logs { follow, level } => {
+                assert!(!follow);
+                assert_eq!(level, None);
+            }
+            _ => panic!("Expected Logs command"),
+        }
+    }
+
+    #[test]
+    fn test_logs_follow_with_level() {
+        let cli = Cli::try_parse_from(["remoshell", "logs", "-f", "--level", "warn"]).unwrap();
+        match cli.command {
+            Commands::Logs { follow, level } => {
+                assert!(follow);
+                assert_eq!(level.as_deref(), Some("warn"));
+            }
+            _ => panic!("Expected Logs command"),
+        }
+    }
+
+    #[test]
+    fn test_logs_invalid_level_fails() {
+        let result = Cli::try_parse_from(["remoshell", "logs", "--level", "verbose"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_format_log_line() {
+        let line = LogLine {
+            timestamp_ms: 3_723_004,
+            level: "WARN".to_string(),
+            target: "daemon::orchestrator".to_string(),
+            message: "signaling disconnected".to_string(),
+        };
+        assert_eq!(
+            format_log_line(&line),
+            "01:02:03.004  WARN daemon::orchestrator: signaling disconnected"
+        );
+    }
+
     #[test]
     fn test_global_verbose_flag() {
         let cli = Cli::try_parse_from(["remoshell", "--verbose", "status"]).unwrap();
@@ -1428,6 +3373,14 @@ mod tests {
         assert!(cli.verbose);
     }
 
+    #[test]
+    fn test_global_plain_flag() {
+        let cli = Cli::try_parse_from(["remoshell", "sessions", "list", "--plain"]).unwrap();
+        assert!(cli.plain);
+        let cli = Cli::try_parse_from(["remoshell", "start", "--plain"]).unwrap();
+        assert!(cli.plain);
+    }
+
     #[test]
     fn test_global_config_flag() {
         let cli = Cli::try_parse_from(["remoshell", "--config", "/path/to/config.toml", "status"])
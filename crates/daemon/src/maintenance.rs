@@ -0,0 +1,210 @@
+//! Maintenance mode, turned on and off with `remoshell maintenance`.
+//!
+//! While the daemon is in maintenance, devices cannot open new sessions or
+//! write to the host: uploads, text file saves, incoming direct copies and
+//! configuration changes are refused. They are told why, with the
+//! operator's message if one was given. Sessions that are already open, and
+//! uploads already under way, carry on, so the host can be drained before
+//! work on it starts.
+//!
+//! The mode is not saved: a restarted daemon leaves maintenance.
+
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use protocol::messages::Message;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::router::middleware::{Interceptor, Request};
+use crate::router::RouterError;
+
+/// The daemon's maintenance, while it is on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceState {
+    /// What the operator told devices, if anything.
+    pub message: Option<String>,
+    /// Unix timestamp in seconds when maintenance started.
+    pub since: u64,
+}
+
+impl MaintenanceState {
+    /// Returns the explanation refused requests get.
+    pub fn describe(&self) -> String {
+        match &self.message {
+            Some(message) => format!("the daemon is in maintenance: {}", message),
+            None => "the daemon is in maintenance".to_string(),
+        }
+    }
+}
+
+/// Returns true if maintenance refuses `message`: a new session or a
+/// request that starts a write to the host.
+pub fn refuses(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::SessionCreate(_)
+            | Message::FileUploadStart(_)
+            | Message::FileWriteText(_)
+            | Message::FileCopyGrantRequest(_)
+            | Message::ConfigPatch(_)
+    )
+}
+
+/// Whether the daemon is in maintenance, and the interceptor that refuses
+/// requests while it is.
+#[derive(Debug, Default)]
+pub struct MaintenanceMode {
+    state: RwLock<Option<MaintenanceState>>,
+}
+
+impl MaintenanceMode {
+    /// Creates the mode, off.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the maintenance under way, if any.
+    pub fn state(&self) -> Option<MaintenanceState> {
+        self.state.read().ok().and_then(|state| state.clone())
+    }
+
+    /// Returns true if the daemon is in maintenance.
+    pub fn is_on(&self) -> bool {
+        self.state.read().is_ok_and(|state| state.is_some())
+    }
+
+    /// Starts maintenance, or replaces the message of the one under way.
+    pub fn enable(&self, message: Option<String>) -> MaintenanceState {
+        let message = message
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty());
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        let since = state
+            .as_ref()
+            .map_or_else(now_secs, |current| current.since);
+        let new_state = MaintenanceState { message, since };
+        info!(
+            target: "audit",
+            message = ?new_state.message,
+            "Maintenance mode on"
+        );
+        *state = Some(new_state.clone());
+        new_state
+    }
+
+    /// Ends maintenance. Returns false if it was not on.
+    pub fn disable(&self) -> bool {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        let was_on = state.take().is_some();
+        if was_on {
+            info!(target: "audit", "Maintenance mode off");
+        }
+        was_on
+    }
+}
+
+impl Interceptor for MaintenanceMode {
+    fn name(&self) -> &'static str {
+        "maintenance"
+    }
+
+    fn before(&self, request: &Request<'_>) -> Result<(), RouterError> {
+        if !refuses(request.message) {
+            return Ok(());
+        }
+        match self.state() {
+            Some(state) => Err(RouterError::Maintenance(state.describe())),
+            None => Ok(()),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::messages::{
+        ConfigPatch, ErrorCode, FileCopyGrantRequest, FileReadText, FileWriteText, SessionCreate,
+        SessionResize,
+    };
+    use protocol::DeviceId;
+
+    fn before(mode: &MaintenanceMode, message: &Message) -> Result<(), RouterError> {
+        mode.before(&Request {
+            message,
+            device_id: &DeviceId::from_bytes([1; 16]),
+        })
+    }
+
+    #[test]
+    fn test_refuses_new_sessions_while_on() {
+        let mode = MaintenanceMode::new();
+        let create = Message::SessionCreate(SessionCreate::default());
+        let resize = Message::SessionResize(SessionResize {
+            session_id: "s".to_string(),
+            cols: 80,
+            rows: 24,
+        });
+        assert!(before(&mode, &create).is_ok());
+
+        mode.enable(Some("  disk upgrade  ".to_string()));
+        let err = before(&mode, &create).unwrap_err();
+        assert_eq!(err.error_code(), ErrorCode::Busy);
+        assert_eq!(
+            err.to_string(),
+            "the daemon is in maintenance: disk upgrade"
+        );
+        // Open sessions are left alone
+        assert!(before(&mode, &resize).is_ok());
+
+        assert!(mode.disable());
+        assert!(!mode.disable());
+        assert!(before(&mode, &create).is_ok());
+    }
+
+    #[test]
+    fn test_refuses_writes() {
+        let mode = MaintenanceMode::new();
+        mode.enable(None);
+
+        let writes = [
+            Message::FileWriteText(FileWriteText {
+                path: "/tmp/notes.txt".to_string(),
+                content: "hello".to_string(),
+                expected_hash: None,
+            }),
+            Message::FileCopyGrantRequest(FileCopyGrantRequest {
+                path: "/tmp/copy".to_string(),
+                overwrite: false,
+            }),
+            Message::ConfigPatch(ConfigPatch::default()),
+        ];
+        for message in &writes {
+            assert!(before(&mode, message).is_err(), "{}", message.name());
+        }
+        // Reading is still allowed
+        let read = Message::FileReadText(FileReadText {
+            path: "/tmp/notes.txt".to_string(),
+        });
+        assert!(before(&mode, &read).is_ok());
+    }
+
+    #[test]
+    fn test_enable_keeps_start_time() {
+        let mode = MaintenanceMode::new();
+        let first = mode.enable(None);
+        assert!(mode.is_on());
+        assert_eq!(first.describe(), "the daemon is in maintenance");
+
+        let second = mode.enable(Some("back at 10:00".to_string()));
+        assert_eq!(second.since, first.since);
+        assert_eq!(mode.state(), Some(second));
+    }
+}
@@ -0,0 +1,426 @@
+//! Mesh status gossip.
+//!
+//! Daemons that trust a common admin device can share small presence records
+//! through the signaling server, so that the admin's client sees which hosts
+//! are online without connecting to each. The admin device's public key
+//! names the mesh group: every daemon configured with the same
+//! `mesh.admin_device` publishes to, and reads from, the same group.
+//!
+//! Each record is signed with the publishing daemon's identity key and names
+//! its group, so the signaling server and other daemons can check who sent
+//! it and that it was not copied from another group. Records carry a
+//! timestamp and expire unless republished.
+
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use protocol::{DeviceIdentity, PeerIdentity, Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::ui::qr::signaling_url_to_http;
+
+/// Domain separator hashed with the admin device's key to name a group.
+const GROUP_CONTEXT: &[u8] = b"remoshell-mesh-v1";
+
+/// Time allowed for each request to the signaling server.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Records older than this many publishing intervals are ignored.
+const EXPIRY_INTERVALS: u64 = 3;
+
+/// Returns the mesh group named by an admin device's public key.
+pub fn group_id(admin_public_key: &[u8; 32]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(GROUP_CONTEXT);
+    hasher.update(admin_public_key);
+    hex::encode(&hasher.finalize()[..16])
+}
+
+/// A daemon's presence and status.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MeshRecord {
+    /// Group the record was published to.
+    pub group: String,
+    /// Fingerprint of the daemon.
+    pub device_id: String,
+    /// Host name of the daemon's machine.
+    pub hostname: String,
+    /// False in the last record a daemon publishes before stopping.
+    pub online: bool,
+    /// Number of active sessions.
+    pub sessions: usize,
+    /// Daemon version.
+    pub version: String,
+    /// When the record was published (Unix timestamp).
+    pub timestamp: u64,
+    /// Seconds after `timestamp` until the record is stale.
+    pub ttl_secs: u64,
+}
+
+impl MeshRecord {
+    /// Returns whether the record is stale at `now` (Unix timestamp).
+    pub fn is_stale_at(&self, now: u64) -> bool {
+        now > self.timestamp.saturating_add(self.ttl_secs)
+    }
+}
+
+/// A record as exchanged through the signaling server.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignedMeshRecord {
+    /// The JSON-encoded [`MeshRecord`], exactly as signed.
+    pub payload: String,
+    /// Base64-encoded Ed25519 public key of the daemon.
+    pub public_key: String,
+    /// Base64-encoded signature of `payload`.
+    pub signature: String,
+}
+
+impl SignedMeshRecord {
+    /// Signs a record with the daemon's identity.
+    pub fn sign(record: &MeshRecord, identity: &DeviceIdentity) -> Result<Self> {
+        let payload = serde_json::to_string(record)?;
+        let signature = identity.sign(payload.as_bytes());
+        let engine = base64::engine::general_purpose::STANDARD;
+        Ok(Self {
+            public_key: engine.encode(identity.public_key_bytes()),
+            signature: engine.encode(signature.as_bytes()),
+            payload,
+        })
+    }
+
+    /// Checks the signature and that the record belongs to `group` and was
+    /// published by the owner of the key, and returns it.
+    pub fn verify(&self, group: &str) -> Result<MeshRecord> {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let public_key: [u8; 32] = engine
+            .decode(&self.public_key)
+            .context("Invalid public key encoding")?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Public key must be 32 bytes"))?;
+        let signature: [u8; 64] = engine
+            .decode(&self.signature)
+            .context("Invalid signature encoding")?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Signature must be 64 bytes"))?;
+
+        let peer = PeerIdentity::from_public_key_bytes(&public_key)?;
+        peer.verify(self.payload.as_bytes(), &Signature::from_bytes(signature))
+            .context("Invalid signature")?;
+
+        let record: MeshRecord = serde_json::from_str(&self.payload).context("Invalid record")?;
+        if record.device_id != peer.device_id().fingerprint() {
+            anyhow::bail!("Record for {} signed by another key", record.device_id);
+        }
+        if record.group != group {
+            anyhow::bail!("Record belongs to another group");
+        }
+        Ok(record)
+    }
+}
+
+/// Body of the signaling server's answer when listing a group.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MeshListing {
+    /// The group's records.
+    #[serde(default)]
+    pub hosts: Vec<SignedMeshRecord>,
+}
+
+impl MeshListing {
+    /// Returns the valid, current records of other daemons in `group`,
+    /// ordered by host name. Invalid records are logged and dropped.
+    pub fn verified_hosts(&self, group: &str, own_device_id: &str, now: u64) -> Vec<MeshRecord> {
+        let mut hosts: Vec<_> = self
+            .hosts
+            .iter()
+            .filter_map(|signed| match signed.verify(group) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    debug!("Ignoring mesh record: {:#}", e);
+                    None
+                }
+            })
+            .filter(|record| record.device_id != own_device_id && !record.is_stale_at(now))
+            .collect();
+        hosts.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+        hosts
+    }
+}
+
+/// The other daemons of the group, as last seen.
+#[derive(Debug)]
+pub struct MeshPeers {
+    group: String,
+    hosts: Mutex<Vec<MeshRecord>>,
+}
+
+impl MeshPeers {
+    /// Creates an empty view of `group`.
+    pub fn new(group: String) -> Self {
+        Self {
+            group,
+            hosts: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns the group.
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Returns the hosts seen in the last successful exchange.
+    pub fn hosts(&self) -> Vec<MeshRecord> {
+        self.hosts.lock().unwrap().clone()
+    }
+
+    fn set_hosts(&self, hosts: Vec<MeshRecord>) {
+        *self.hosts.lock().unwrap() = hosts;
+    }
+}
+
+/// Publishes this daemon's record and reads the group's.
+#[derive(Debug)]
+pub struct MeshClient {
+    url: String,
+    group: String,
+    interval: Duration,
+    client: reqwest::Client,
+}
+
+impl MeshClient {
+    /// Creates a client for the group named by `admin_public_key`, on the
+    /// signaling server at `signaling_url`.
+    pub fn new(
+        signaling_url: &str,
+        admin_public_key: &[u8; 32],
+        interval: Duration,
+    ) -> Result<Self> {
+        let group = group_id(admin_public_key);
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("remoshell-daemon/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            url: format!(
+                "{}/mesh/{}",
+                signaling_url_to_http(signaling_url).trim_end_matches('/'),
+                group
+            ),
+            group,
+            interval,
+            client,
+        })
+    }
+
+    /// Returns the group.
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Builds and signs this daemon's record.
+    pub fn record(
+        &self,
+        identity: &DeviceIdentity,
+        online: bool,
+        sessions: usize,
+    ) -> Result<SignedMeshRecord> {
+        let record = MeshRecord {
+            group: self.group.clone(),
+            device_id: identity.fingerprint(),
+            hostname: sysinfo::System::host_name().unwrap_or_default(),
+            online,
+            sessions,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: unix_now(),
+            ttl_secs: self.interval.as_secs() * EXPIRY_INTERVALS,
+        };
+        SignedMeshRecord::sign(&record, identity)
+    }
+
+    /// Publishes a signed record to the group.
+    pub async fn publish(&self, record: &SignedMeshRecord) -> Result<()> {
+        let response = self
+            .client
+            .put(&self.url)
+            .json(record)
+            .send()
+            .await
+            .context("Failed to reach signaling server")?;
+        if !response.status().is_success() {
+            anyhow::bail!("Signaling server answered {}", response.status());
+        }
+        Ok(())
+    }
+
+    /// Reads the group's records.
+    pub async fn fetch(&self) -> Result<MeshListing> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .context("Failed to reach signaling server")?;
+        if !response.status().is_success() {
+            anyhow::bail!("Signaling server answered {}", response.status());
+        }
+        response.json().await.context("Invalid mesh listing")
+    }
+
+    /// Publishes this daemon's status every interval and refreshes `peers`
+    /// with the other daemons' until shutdown, then publishes that the
+    /// daemon went offline.
+    ///
+    /// Failed exchanges are logged and retried at the next interval.
+    pub async fn run(
+        self,
+        identity: DeviceIdentity,
+        sessions: impl Fn() -> usize,
+        peers: &MeshPeers,
+        shutdown_token: CancellationToken,
+    ) -> Result<()> {
+        let own_device_id = identity.fingerprint();
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+
+            let exchange = async {
+                self.publish(&self.record(&identity, true, sessions())?)
+                    .await?;
+                self.fetch().await
+            };
+            match exchange.await {
+                Ok(listing) => {
+                    peers.set_hosts(listing.verified_hosts(&self.group, &own_device_id, unix_now()))
+                }
+                Err(e) => warn!("Mesh status exchange failed: {:#}", e),
+            }
+        }
+
+        debug!("Mesh task received shutdown signal");
+        let goodbye = self.record(&identity, false, 0)?;
+        if let Err(e) = self.publish(&goodbye).await {
+            debug!("Failed to publish offline mesh record: {:#}", e);
+        }
+        Ok(())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> MeshClient {
+        let admin = DeviceIdentity::generate();
+        MeshClient::new(
+            "wss://signal.example.com/",
+            &admin.public_key_bytes(),
+            Duration::from_secs(60),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_group_id() {
+        let admin = DeviceIdentity::generate();
+        let group = group_id(&admin.public_key_bytes());
+        assert_eq!(group.len(), 32);
+        assert_eq!(group, group_id(&admin.public_key_bytes()));
+        assert_ne!(
+            group,
+            group_id(&DeviceIdentity::generate().public_key_bytes())
+        );
+
+        let client = MeshClient::new(
+            "wss://signal.example.com/",
+            &admin.public_key_bytes(),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        assert_eq!(
+            client.url,
+            format!("https://signal.example.com/mesh/{}", group)
+        );
+    }
+
+    #[test]
+    fn test_signed_record_roundtrip() {
+        let client = client();
+        let identity = DeviceIdentity::generate();
+        let signed = client.record(&identity, true, 2).unwrap();
+
+        let record = signed.verify(client.group()).unwrap();
+        assert_eq!(record.device_id, identity.fingerprint());
+        assert!(record.online);
+        assert_eq!(record.sessions, 2);
+        assert_eq!(record.ttl_secs, 180);
+        assert!(!record.is_stale_at(record.timestamp + 180));
+        assert!(record.is_stale_at(record.timestamp + 181));
+
+        // Records cannot be moved to another group
+        assert!(signed.verify("another-group").is_err());
+    }
+
+    #[test]
+    fn test_tampered_records_are_rejected() {
+        let client = client();
+        let identity = DeviceIdentity::generate();
+        let signed = client.record(&identity, true, 0).unwrap();
+
+        let mut tampered = signed.clone();
+        tampered.payload = tampered.payload.replace("\"sessions\":0", "\"sessions\":9");
+        assert!(tampered.verify(client.group()).is_err());
+
+        // Signed by another daemon, claiming this one's ID
+        let impostor = DeviceIdentity::generate();
+        let mut record: MeshRecord = serde_json::from_str(&signed.payload).unwrap();
+        record.hostname = "impostor".to_string();
+        let mut forged = SignedMeshRecord::sign(&record, &impostor).unwrap();
+        assert!(forged.verify(client.group()).is_err());
+        forged.public_key = signed.public_key.clone();
+        assert!(forged.verify(client.group()).is_err());
+    }
+
+    #[test]
+    fn test_verified_hosts() {
+        let client = client();
+        let own = DeviceIdentity::generate();
+        let peer = DeviceIdentity::generate();
+        let mut invalid = client.record(&peer, true, 0).unwrap();
+        let engine = base64::engine::general_purpose::STANDARD;
+        let mut signature = engine.decode(&invalid.signature).unwrap();
+        signature[0] ^= 1;
+        invalid.signature = engine.encode(signature);
+
+        let listing = MeshListing {
+            hosts: vec![
+                client.record(&own, true, 0).unwrap(),
+                client.record(&peer, true, 1).unwrap(),
+                invalid,
+            ],
+        };
+        let now = unix_now();
+        let hosts = listing.verified_hosts(client.group(), &own.fingerprint(), now);
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].device_id, peer.fingerprint());
+
+        // Stale records are dropped
+        assert!(listing
+            .verified_hosts(client.group(), &own.fingerprint(), now + 3600)
+            .is_empty());
+    }
+}
@@ -0,0 +1,430 @@
+//! Presence publishing to an MQTT broker.
+//!
+//! Home-automation systems such as Home Assistant or Node-RED can subscribe
+//! to these topics to react when someone is remoted into the host. With the
+//! default prefix `remoshell/<hostname>`, the daemon publishes retained
+//! messages to:
+//!
+//! - `<prefix>/status`: `online` while the daemon runs, `offline` once it
+//!   stops (also set by the broker as the last will if the daemon vanishes)
+//! - `<prefix>/devices`: number of connected devices
+//! - `<prefix>/sessions`: number of open shell sessions
+//! - `<prefix>/active`: `ON` while at least one device is connected, `OFF`
+//!   otherwise
+//! - `<prefix>/state`: all of the above as one JSON object
+//!
+//! The orchestrator reports the counts through a [`watch`] channel; they are
+//! published whenever they change and again after each reconnect.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use rumqttc::{
+    AsyncClient, Event, LastWill, MqttOptions, Outgoing, Packet, QoS, TlsConfiguration, Transport,
+};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::config::MqttConfig;
+
+/// Payload of the status topic while the daemon runs.
+pub const ONLINE: &str = "online";
+
+/// Payload of the status topic once the daemon has stopped.
+pub const OFFLINE: &str = "offline";
+
+/// Interval between keep-alive pings.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Delay before reconnecting after the connection to the broker fails.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Time allowed at shutdown for the offline status to reach the broker.
+const SHUTDOWN_FLUSH: Duration = Duration::from_secs(2);
+
+/// Requests the client can queue before publishing fails.
+const REQUEST_CAPACITY: usize = 32;
+
+/// What is published about the daemon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Presence {
+    /// Number of connected devices.
+    pub devices: usize,
+    /// Number of open shell sessions.
+    pub sessions: usize,
+}
+
+impl Presence {
+    /// Returns whether anyone is connected.
+    pub fn active(&self) -> bool {
+        self.devices > 0
+    }
+}
+
+/// Topics the presence is published to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topics {
+    /// `online` or `offline`.
+    pub status: String,
+    /// Number of connected devices.
+    pub devices: String,
+    /// Number of open sessions.
+    pub sessions: String,
+    /// `ON` while anyone is connected.
+    pub active: String,
+    /// JSON object with all of the above.
+    pub state: String,
+}
+
+impl Topics {
+    /// Creates the topics below `prefix`.
+    pub fn new(prefix: &str) -> Self {
+        let prefix = prefix.trim_end_matches('/');
+        let topic = |name: &str| format!("{}/{}", prefix, name);
+        Self {
+            status: topic("status"),
+            devices: topic("devices"),
+            sessions: topic("sessions"),
+            active: topic("active"),
+            state: topic("state"),
+        }
+    }
+}
+
+/// Returns `name` made safe for use as a single topic level.
+fn topic_level(name: &str) -> String {
+    let level: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '+' | '#' => '-',
+            c if c.is_whitespace() => '-',
+            c => c,
+        })
+        .collect();
+    if level.is_empty() {
+        "daemon".to_string()
+    } else {
+        level
+    }
+}
+
+/// Publishes the daemon's presence to an MQTT broker.
+#[derive(Debug)]
+pub struct MqttPresence {
+    options: MqttOptions,
+    topics: Topics,
+}
+
+impl MqttPresence {
+    /// Creates a publisher for `config`.
+    ///
+    /// Fails if the CA file cannot be read.
+    pub fn from_config(config: &MqttConfig) -> anyhow::Result<Self> {
+        let host = topic_level(&sysinfo::System::host_name().unwrap_or_default());
+        let client_id = config
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("remoshell-{}", host));
+        let topics = Topics::new(
+            &config
+                .topic_prefix
+                .clone()
+                .unwrap_or_else(|| format!("remoshell/{}", host)),
+        );
+
+        let mut options = MqttOptions::new(client_id, config.host.clone(), config.port);
+        options.set_keep_alive(KEEP_ALIVE);
+        options.set_last_will(LastWill::new(
+            topics.status.clone(),
+            OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            options.set_credentials(
+                username.clone(),
+                config.password.clone().unwrap_or_default(),
+            );
+        }
+        if config.tls {
+            let transport = match &config.ca_file {
+                Some(path) => {
+                    let ca = std::fs::read(path).with_context(|| {
+                        format!("Failed to read MQTT CA file {}", path.display())
+                    })?;
+                    Transport::tls_with_config(TlsConfiguration::Simple {
+                        ca,
+                        alpn: None,
+                        client_auth: None,
+                    })
+                }
+                None => Transport::tls_with_default_config(),
+            };
+            options.set_transport(transport);
+        }
+
+        Ok(Self { options, topics })
+    }
+
+    /// Returns the topics the presence is published to.
+    pub fn topics(&self) -> &Topics {
+        &self.topics
+    }
+
+    /// Publishes `presence` until shutdown, then marks the daemon offline.
+    ///
+    /// Connection failures are retried every few seconds; the daemon keeps
+    /// running without the broker.
+    pub async fn run(
+        self,
+        mut presence: watch::Receiver<Presence>,
+        shutdown_token: CancellationToken,
+    ) {
+        let (host, port) = self.options.broker_address();
+        let broker = format!("{}:{}", host, port);
+        let (client, mut eventloop) = AsyncClient::new(self.options.clone(), REQUEST_CAPACITY);
+        let mut connected = false;
+        let mut failing = false;
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                changed = presence.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let current = *presence.borrow_and_update();
+                    // Otherwise published once the connection is back
+                    if connected {
+                        self.publish_presence(&client, current);
+                    }
+                }
+                event = eventloop.poll() => match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!(broker = %broker, "Connected to MQTT broker");
+                        connected = true;
+                        failing = false;
+                        self.publish(&client, &self.topics.status, ONLINE);
+                        let current = *presence.borrow_and_update();
+                        self.publish_presence(&client, current);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        // Only the first of a series of failures is worth a warning
+                        if failing {
+                            debug!(broker = %broker, error = %e, "MQTT broker still unreachable");
+                        } else {
+                            warn!(broker = %broker, error = %e, "MQTT connection failed, retrying");
+                        }
+                        connected = false;
+                        failing = true;
+                        tokio::select! {
+                            _ = shutdown_token.cancelled() => break,
+                            _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+                        }
+                    }
+                },
+            }
+        }
+
+        // A clean disconnect does not trigger the last will, so say it ourselves
+        if connected {
+            self.publish(&client, &self.topics.status, OFFLINE);
+            let _ = client.try_disconnect();
+            let flushed = tokio::time::timeout(SHUTDOWN_FLUSH, async {
+                loop {
+                    match eventloop.poll().await {
+                        Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+                        Ok(_) => {}
+                    }
+                }
+            })
+            .await;
+            if flushed.is_err() {
+                debug!(broker = %broker, "Timed out disconnecting from MQTT broker");
+            }
+        }
+    }
+
+    fn publish_presence(&self, client: &AsyncClient, presence: Presence) {
+        self.publish(client, &self.topics.devices, presence.devices.to_string());
+        self.publish(client, &self.topics.sessions, presence.sessions.to_string());
+        self.publish(
+            client,
+            &self.topics.active,
+            if presence.active() { "ON" } else { "OFF" },
+        );
+        let state = serde_json::json!({
+            "devices": presence.devices,
+            "sessions": presence.sessions,
+            "active": presence.active(),
+        });
+        self.publish(client, &self.topics.state, state.to_string());
+    }
+
+    fn publish(&self, client: &AsyncClient, topic: &str, payload: impl Into<Vec<u8>>) {
+        // Retained, so that subscribers get the current state when they connect
+        if let Err(e) = client.try_publish(topic, QoS::AtLeastOnce, true, payload) {
+            warn!(topic, error = %e, "Failed to queue MQTT message");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    /// A retained message received by the test broker.
+    type Published = (String, String, bool);
+
+    async fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let header = stream.read_u8().await.ok()?;
+        let mut length = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = stream.read_u8().await.ok()?;
+            length |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0u8; length];
+        stream.read_exact(&mut body).await.ok()?;
+        Some((header, body))
+    }
+
+    /// Accepts one client and reports what it publishes, just enough MQTT
+    /// 3.1.1 for the publisher.
+    async fn spawn_broker() -> (u16, mpsc::UnboundedReceiver<Published>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while let Some((header, body)) = read_packet(&mut stream).await {
+                match header >> 4 {
+                    // CONNECT
+                    1 => stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap(),
+                    // PUBLISH
+                    3 => {
+                        let qos = (header >> 1) & 0x03;
+                        let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                        let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                        let offset = if qos > 0 {
+                            4 + topic_len
+                        } else {
+                            2 + topic_len
+                        };
+                        let payload = String::from_utf8(body[offset..].to_vec()).unwrap();
+                        let _ = tx.send((topic, payload, header & 0x01 == 1));
+                        if qos > 0 {
+                            let id = &body[2 + topic_len..offset];
+                            // The client may already be gone after its last message
+                            let _ = stream.write_all(&[0x40, 0x02, id[0], id[1]]).await;
+                        }
+                    }
+                    // PINGREQ
+                    12 => stream.write_all(&[0xd0, 0x00]).await.unwrap(),
+                    // DISCONNECT
+                    14 => break,
+                    _ => {}
+                }
+            }
+        });
+
+        (port, rx)
+    }
+
+    async fn next(rx: &mut mpsc::UnboundedReceiver<Published>) -> (String, String) {
+        let (topic, payload, retain) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for a message")
+            .expect("broker stopped");
+        assert!(retain, "{} was not retained", topic);
+        (topic, payload)
+    }
+
+    fn config(port: u16) -> MqttConfig {
+        MqttConfig {
+            enabled: true,
+            host: "127.0.0.1".to_string(),
+            port,
+            topic_prefix: Some("test/nas".to_string()),
+            ..MqttConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_topics_and_levels() {
+        let topics = Topics::new("home/nas/");
+        assert_eq!(topics.status, "home/nas/status");
+        assert_eq!(topics.active, "home/nas/active");
+
+        assert_eq!(topic_level("nas"), "nas");
+        assert_eq!(topic_level("my box/#1+"), "my-box--1-");
+        assert_eq!(topic_level(""), "daemon");
+    }
+
+    #[test]
+    fn test_from_config() {
+        let presence = MqttPresence::from_config(&config(1883)).unwrap();
+        assert_eq!(presence.topics().status, "test/nas/status");
+
+        // The broker reports the daemon offline if the connection drops
+        let will = presence.options.last_will().unwrap();
+        assert_eq!(will.topic, "test/nas/status");
+        assert_eq!(&will.message[..], OFFLINE.as_bytes());
+        assert!(will.retain);
+
+        let mut missing_ca = config(8883);
+        missing_ca.tls = true;
+        missing_ca.ca_file = Some("/nonexistent/ca.pem".into());
+        assert!(MqttPresence::from_config(&missing_ca).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_publishes_presence() {
+        let (port, mut rx) = spawn_broker().await;
+        let (presence_tx, presence_rx) = watch::channel(Presence::default());
+        let shutdown_token = CancellationToken::new();
+        let publisher = MqttPresence::from_config(&config(port)).unwrap();
+        let task = tokio::spawn(publisher.run(presence_rx, shutdown_token.clone()));
+
+        let expect = |topic: &str, payload: &str| (topic.to_string(), payload.to_string());
+        assert_eq!(next(&mut rx).await, expect("test/nas/status", ONLINE));
+        assert_eq!(next(&mut rx).await, expect("test/nas/devices", "0"));
+        assert_eq!(next(&mut rx).await, expect("test/nas/sessions", "0"));
+        assert_eq!(next(&mut rx).await, expect("test/nas/active", "OFF"));
+        assert_eq!(
+            next(&mut rx).await,
+            expect(
+                "test/nas/state",
+                r#"{"active":false,"devices":0,"sessions":0}"#
+            )
+        );
+
+        presence_tx
+            .send(Presence {
+                devices: 1,
+                sessions: 2,
+            })
+            .unwrap();
+        assert_eq!(next(&mut rx).await, expect("test/nas/devices", "1"));
+        assert_eq!(next(&mut rx).await, expect("test/nas/sessions", "2"));
+        assert_eq!(next(&mut rx).await, expect("test/nas/active", "ON"));
+        next(&mut rx).await;
+
+        shutdown_token.cancel();
+        assert_eq!(next(&mut rx).await, expect("test/nas/status", OFFLINE));
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
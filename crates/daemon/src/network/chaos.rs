@@ -0,0 +1,636 @@
+//! Fault injection for chaos testing connection handling.
+//!
+//! [`ChaosConnection`] wraps any [`Connection`] (a QUIC or WebRTC handler,
+//! or a loopback end) and applies a [`FaultPlan`] to the messages crossing
+//! it:
+//! - Dropping a message
+//! - Delaying its delivery
+//! - Duplicating it, as a datagram transport may
+//! - Disconnecting, either instead of a given message or once a given time
+//!   has passed
+//!
+//! Rules pick messages by direction, channel and position, or draw them
+//! from an RNG seeded by the plan, so a failing run replays exactly.
+//! Timing uses `tokio::time`, which makes delays deterministic under paused
+//! time. A [`ChaosHandle`] reports what was injected and forces a disconnect
+//! from another task.
+//!
+//! This module is only built with the `chaos` feature, which is meant for
+//! tests and must not be enabled in release builds.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use protocol::error::{ProtocolError, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use super::{ChannelType, Connection};
+
+/// A fault applied to one message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The message is lost.
+    Drop,
+    /// The message is delivered late by the given time.
+    Delay(Duration),
+    /// The message is delivered twice.
+    Duplicate,
+    /// The connection closes instead of carrying the message.
+    Disconnect,
+}
+
+/// The way a message crosses the wrapped connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent by the wrapped end.
+    Send,
+    /// Received by the wrapped end.
+    Recv,
+}
+
+/// When a rule fires, counted over the messages matching its direction and
+/// channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    /// Only the nth matching message, counting from 1.
+    Nth(u64),
+    /// Every nth matching message.
+    Every(u64),
+    /// Each matching message with the given probability.
+    Probability(f64),
+}
+
+/// Applies a fault to the messages picked by a trigger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultRule {
+    /// Direction of the messages the rule considers.
+    pub direction: Direction,
+    /// Channel of the messages the rule considers; `None` for all.
+    pub channel: Option<ChannelType>,
+    /// Which of the considered messages get the fault.
+    pub trigger: Trigger,
+    /// The fault to apply.
+    pub fault: Fault,
+}
+
+impl FaultRule {
+    /// Creates a rule for messages on every channel.
+    pub fn new(direction: Direction, trigger: Trigger, fault: Fault) -> Self {
+        Self {
+            direction,
+            channel: None,
+            trigger,
+            fault,
+        }
+    }
+
+    /// Restricts the rule to one channel.
+    pub fn on_channel(mut self, channel: ChannelType) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    fn matches(&self, direction: Direction, channel: ChannelType) -> bool {
+        self.direction == direction && self.channel.is_none_or(|c| c == channel)
+    }
+}
+
+/// The faults injected into one connection.
+///
+/// When several rules fire for the same message, the first one added wins.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultPlan {
+    /// Rules checked for every message, in order.
+    pub rules: Vec<FaultRule>,
+    /// Time after which the connection is closed, counted from wrapping it.
+    pub disconnect_after: Option<Duration>,
+    /// Seed for the RNG drawing probabilistic triggers.
+    pub seed: u64,
+}
+
+impl FaultPlan {
+    /// Adds a rule.
+    pub fn with_rule(mut self, rule: FaultRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Closes the connection once `after` has passed.
+    pub fn with_disconnect_after(mut self, after: Duration) -> Self {
+        self.disconnect_after = Some(after);
+        self
+    }
+
+    /// Sets the RNG seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Counters for the faults a connection has injected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultStats {
+    /// Messages dropped.
+    pub dropped: u64,
+    /// Messages delayed.
+    pub delayed: u64,
+    /// Messages duplicated.
+    pub duplicated: u64,
+    /// Whether the connection was closed by a fault.
+    pub disconnected: bool,
+}
+
+/// Observes and controls a [`ChaosConnection`] from another task.
+#[derive(Clone)]
+pub struct ChaosHandle {
+    shared: Arc<Shared>,
+}
+
+impl ChaosHandle {
+    /// Returns counters for the faults injected so far.
+    pub fn stats(&self) -> FaultStats {
+        *self.shared.lock()
+    }
+
+    /// Closes the connection, waking a pending receive.
+    pub fn disconnect(&self) {
+        self.shared.lock().disconnected = true;
+        self.shared.notify.notify_waiters();
+    }
+}
+
+/// A connection that injects the faults of a [`FaultPlan`].
+pub struct ChaosConnection<C> {
+    inner: C,
+    rules: Vec<FaultRule>,
+    /// Messages each rule has considered.
+    counts: Vec<u64>,
+    rng: StdRng,
+    disconnect_at: Option<Instant>,
+    /// Received messages waiting for their delivery time, per channel.
+    held: [Vec<Held>; 3],
+    next_sequence: u64,
+    closed: bool,
+    shared: Arc<Shared>,
+}
+
+impl<C: Connection> ChaosConnection<C> {
+    /// Wraps a connection; the plan's disconnect timer starts now.
+    pub fn new(inner: C, plan: FaultPlan) -> Self {
+        Self {
+            inner,
+            counts: vec![0; plan.rules.len()],
+            rules: plan.rules,
+            rng: StdRng::seed_from_u64(plan.seed),
+            disconnect_at: plan.disconnect_after.map(|after| Instant::now() + after),
+            held: Default::default(),
+            next_sequence: 0,
+            closed: false,
+            shared: Arc::new(Shared::default()),
+        }
+    }
+
+    /// Returns a handle observing this connection.
+    pub fn handle(&self) -> ChaosHandle {
+        ChaosHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Returns the wrapped connection.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Picks the fault for the next message, counting it for every rule.
+    fn next_fault(&mut self, direction: Direction, channel: ChannelType) -> Option<Fault> {
+        let mut chosen = None;
+        for (rule, count) in self.rules.iter().zip(&mut self.counts) {
+            if !rule.matches(direction, channel) {
+                continue;
+            }
+            *count += 1;
+            let fires = match rule.trigger {
+                Trigger::Nth(n) => *count == n,
+                Trigger::Every(n) => n > 0 && *count % n == 0,
+                Trigger::Probability(p) => self.rng.gen::<f64>() < p,
+            };
+            if fires && chosen.is_none() {
+                chosen = Some(rule.fault);
+            }
+        }
+
+        let mut stats = self.shared.lock();
+        match chosen {
+            Some(Fault::Drop) => stats.dropped += 1,
+            Some(Fault::Delay(_)) => stats.delayed += 1,
+            Some(Fault::Duplicate) => stats.duplicated += 1,
+            Some(Fault::Disconnect) => stats.disconnected = true,
+            None => {}
+        }
+        chosen
+    }
+
+    fn should_disconnect(&self) -> bool {
+        self.shared.lock().disconnected || self.disconnect_at.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// Closes the wrapped connection and returns the error to report.
+    async fn disconnect(&mut self) -> ProtocolError {
+        self.shared.lock().disconnected = true;
+        if !self.closed {
+            self.closed = true;
+            let _ = self.inner.close().await;
+        }
+        ProtocolError::ConnectionClosed("connection closed by fault injection".to_string())
+    }
+
+    async fn send_faulty(&mut self, channel: ChannelType, data: &[u8]) -> Result<()> {
+        if self.should_disconnect() {
+            return Err(self.disconnect().await);
+        }
+        match self.next_fault(Direction::Send, channel) {
+            None => self.inner.send(channel, data).await,
+            Some(Fault::Drop) => Ok(()),
+            Some(Fault::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                self.inner.send(channel, data).await
+            }
+            Some(Fault::Duplicate) => {
+                self.inner.send(channel, data).await?;
+                self.inner.send(channel, data).await
+            }
+            Some(Fault::Disconnect) => Err(self.disconnect().await),
+        }
+    }
+
+    /// Queues a received message for delivery at `deliver_at`.
+    fn hold(&mut self, channel: ChannelType, mut deliver_at: Instant, data: Vec<u8>) {
+        let held = &mut self.held[queue_index(channel)];
+        if is_ordered(channel) {
+            // A message cannot overtake one held before it
+            if let Some(last) = held.iter().map(|h| h.deliver_at).max() {
+                deliver_at = deliver_at.max(last);
+            }
+        }
+        held.push(Held {
+            deliver_at,
+            sequence: self.next_sequence,
+            data,
+        });
+        self.next_sequence += 1;
+    }
+
+    /// Takes the earliest held message that is due.
+    fn take_due(&mut self, channel: ChannelType, now: Instant) -> Option<Vec<u8>> {
+        let held = &mut self.held[queue_index(channel)];
+        let index = held
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.deliver_at <= now)
+            .min_by_key(|(_, h)| (h.deliver_at, h.sequence))
+            .map(|(index, _)| index)?;
+        Some(held.swap_remove(index).data)
+    }
+
+    /// Returns when the next wakeup is due: a held message or the
+    /// scheduled disconnect.
+    fn next_wakeup(&self, channel: ChannelType) -> Option<Instant> {
+        let held = self.held[queue_index(channel)].iter().map(|h| h.deliver_at);
+        held.chain(self.disconnect_at).min()
+    }
+
+    async fn recv_faulty(&mut self, channel: ChannelType) -> Result<Vec<u8>> {
+        let shared = Arc::clone(&self.shared);
+        loop {
+            // Register for wakeups before checking the flag so a disconnect
+            // in between is not missed
+            let notified = shared.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.should_disconnect() {
+                return Err(self.disconnect().await);
+            }
+            let now = Instant::now();
+            if let Some(data) = self.take_due(channel, now) {
+                return Ok(data);
+            }
+
+            // Messages are queued in the same poll that receives them, so
+            // dropping this future never loses one
+            let wakeup = self.next_wakeup(channel);
+            let received = tokio::select! {
+                result = self.inner.recv(channel) => Some(result?),
+                _ = sleep_until(wakeup) => None,
+                _ = &mut notified => None,
+            };
+            let Some(data) = received else {
+                continue;
+            };
+
+            match self.next_fault(Direction::Recv, channel) {
+                None => self.hold(channel, now, data),
+                Some(Fault::Drop) => {}
+                Some(Fault::Delay(delay)) => self.hold(channel, now + delay, data),
+                Some(Fault::Duplicate) => {
+                    self.hold(channel, now, data.clone());
+                    self.hold(channel, now, data);
+                }
+                Some(Fault::Disconnect) => return Err(self.disconnect().await),
+            }
+        }
+    }
+}
+
+impl<C: Connection> Connection for ChaosConnection<C> {
+    fn send<'a>(
+        &'a mut self,
+        channel: ChannelType,
+        data: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(self.send_faulty(channel, data))
+    }
+
+    fn recv<'a>(
+        &'a mut self,
+        channel: ChannelType,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send + 'a>> {
+        Box::pin(self.recv_faulty(channel))
+    }
+
+    fn close<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        self.closed = true;
+        self.inner.close()
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.closed && !self.should_disconnect() && self.inner.is_connected()
+    }
+
+    fn peer_public_key(&self) -> Option<[u8; 32]> {
+        self.inner.peer_public_key()
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Returns whether a channel delivers messages in the order they were sent.
+fn is_ordered(channel: ChannelType) -> bool {
+    !matches!(channel, ChannelType::Terminal)
+}
+
+fn queue_index(channel: ChannelType) -> usize {
+    match channel {
+        ChannelType::Control => 0,
+        ChannelType::Terminal => 1,
+        ChannelType::Files => 2,
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    stats: Mutex<FaultStats>,
+    notify: Notify,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, FaultStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A received message waiting for its delivery time.
+struct Held {
+    deliver_at: Instant,
+    sequence: u64,
+    data: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::loopback::{self, LoopbackConnection, SimulatedLink};
+
+    fn chaos_pair(plan: FaultPlan) -> (LoopbackConnection, ChaosConnection<LoopbackConnection>) {
+        let (client, daemon_end) = loopback::pair(SimulatedLink::default());
+        (client, ChaosConnection::new(daemon_end, plan))
+    }
+
+    async fn recv<C: Connection>(conn: &mut C, channel: ChannelType) -> Vec<u8> {
+        conn.recv(channel).await.unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_rules_passes_messages_through() {
+        let (mut client, mut chaos) = chaos_pair(FaultPlan::default());
+
+        client.send(ChannelType::Control, b"ping").await.unwrap();
+        assert_eq!(recv(&mut chaos, ChannelType::Control).await, b"ping");
+        chaos.send(ChannelType::Files, b"pong").await.unwrap();
+        assert_eq!(recv(&mut client, ChannelType::Files).await, b"pong");
+        assert_eq!(chaos.handle().stats(), FaultStats::default());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drop_nth_received_message() {
+        let plan = FaultPlan::default().with_rule(FaultRule::new(
+            Direction::Recv,
+            Trigger::Nth(2),
+            Fault::Drop,
+        ));
+        let (mut client, mut chaos) = chaos_pair(plan);
+
+        for i in 1..=3u8 {
+            client.send(ChannelType::Control, &[i]).await.unwrap();
+        }
+        assert_eq!(recv(&mut chaos, ChannelType::Control).await, [1]);
+        assert_eq!(recv(&mut chaos, ChannelType::Control).await, [3]);
+        assert_eq!(chaos.handle().stats().dropped, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rule_only_counts_its_channel() {
+        let plan = FaultPlan::default().with_rule(
+            FaultRule::new(Direction::Send, Trigger::Every(2), Fault::Drop)
+                .on_channel(ChannelType::Terminal),
+        );
+        let (mut client, mut chaos) = chaos_pair(plan);
+
+        for i in 1..=4u8 {
+            chaos.send(ChannelType::Terminal, &[i]).await.unwrap();
+            chaos.send(ChannelType::Control, &[i]).await.unwrap();
+        }
+        assert_eq!(recv(&mut client, ChannelType::Terminal).await, [1]);
+        assert_eq!(recv(&mut client, ChannelType::Terminal).await, [3]);
+        for i in 1..=4u8 {
+            assert_eq!(recv(&mut client, ChannelType::Control).await, [i]);
+        }
+        assert_eq!(chaos.handle().stats().dropped, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delay_keeps_ordered_channels_in_order() {
+        let plan = FaultPlan::default().with_rule(FaultRule::new(
+            Direction::Recv,
+            Trigger::Nth(1),
+            Fault::Delay(Duration::from_millis(50)),
+        ));
+        let (mut client, mut chaos) = chaos_pair(plan);
+        let start = Instant::now();
+
+        client.send(ChannelType::Control, b"slow").await.unwrap();
+        client.send(ChannelType::Control, b"next").await.unwrap();
+        assert_eq!(recv(&mut chaos, ChannelType::Control).await, b"slow");
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+        assert_eq!(recv(&mut chaos, ChannelType::Control).await, b"next");
+        assert_eq!(chaos.handle().stats().delayed, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delay_lets_datagrams_overtake() {
+        let plan = FaultPlan::default().with_rule(FaultRule::new(
+            Direction::Recv,
+            Trigger::Nth(1),
+            Fault::Delay(Duration::from_millis(50)),
+        ));
+        let (mut client, mut chaos) = chaos_pair(plan);
+
+        client.send(ChannelType::Terminal, b"slow").await.unwrap();
+        client.send(ChannelType::Terminal, b"fast").await.unwrap();
+        assert_eq!(recv(&mut chaos, ChannelType::Terminal).await, b"fast");
+        assert_eq!(recv(&mut chaos, ChannelType::Terminal).await, b"slow");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_duplicate_in_both_directions() {
+        let plan = FaultPlan::default()
+            .with_rule(FaultRule::new(
+                Direction::Recv,
+                Trigger::Nth(1),
+                Fault::Duplicate,
+            ))
+            .with_rule(FaultRule::new(
+                Direction::Send,
+                Trigger::Nth(1),
+                Fault::Duplicate,
+            ));
+        let (mut client, mut chaos) = chaos_pair(plan);
+
+        client.send(ChannelType::Terminal, b"in").await.unwrap();
+        assert_eq!(recv(&mut chaos, ChannelType::Terminal).await, b"in");
+        assert_eq!(recv(&mut chaos, ChannelType::Terminal).await, b"in");
+        chaos.send(ChannelType::Terminal, b"out").await.unwrap();
+        assert_eq!(recv(&mut client, ChannelType::Terminal).await, b"out");
+        assert_eq!(recv(&mut client, ChannelType::Terminal).await, b"out");
+        assert_eq!(chaos.handle().stats().duplicated, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_first_rule_wins() {
+        let plan = FaultPlan::default()
+            .with_rule(FaultRule::new(
+                Direction::Recv,
+                Trigger::Every(1),
+                Fault::Duplicate,
+            ))
+            .with_rule(FaultRule::new(
+                Direction::Recv,
+                Trigger::Every(1),
+                Fault::Drop,
+            ));
+        let (mut client, mut chaos) = chaos_pair(plan);
+
+        client.send(ChannelType::Control, b"twice").await.unwrap();
+        assert_eq!(recv(&mut chaos, ChannelType::Control).await, b"twice");
+        assert_eq!(recv(&mut chaos, ChannelType::Control).await, b"twice");
+        let stats = chaos.handle().stats();
+        assert_eq!((stats.duplicated, stats.dropped), (1, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_disconnect_on_message_closes_both_ends() {
+        let plan = FaultPlan::default().with_rule(FaultRule::new(
+            Direction::Send,
+            Trigger::Nth(2),
+            Fault::Disconnect,
+        ));
+        let (mut client, mut chaos) = chaos_pair(plan);
+
+        chaos.send(ChannelType::Control, b"one").await.unwrap();
+        let err = chaos.send(ChannelType::Control, b"two").await.unwrap_err();
+        assert!(err.to_string().contains("connection closed"));
+        assert!(!chaos.is_connected());
+        assert!(chaos.handle().stats().disconnected);
+
+        assert_eq!(recv(&mut client, ChannelType::Control).await, b"one");
+        assert!(client.recv(ChannelType::Control).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_disconnect_after_wakes_pending_recv() {
+        let plan = FaultPlan::default().with_disconnect_after(Duration::from_secs(5));
+        let (_client, mut chaos) = chaos_pair(plan);
+        let start = Instant::now();
+
+        let err = chaos.recv(ChannelType::Control).await.unwrap_err();
+        assert!(err.to_string().contains("connection closed"));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        assert!(chaos.handle().stats().disconnected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_handle_disconnect_wakes_pending_recv() {
+        let (mut client, chaos) = chaos_pair(FaultPlan::default());
+        let handle = chaos.handle();
+        let waiter = tokio::spawn(async move {
+            let mut chaos = chaos;
+            chaos.recv(ChannelType::Files).await
+        });
+        tokio::task::yield_now().await;
+
+        handle.disconnect();
+        assert!(waiter.await.unwrap().is_err());
+        assert!(client.recv(ChannelType::Files).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_probability_is_deterministic() {
+        async fn survivors(seed: u64) -> Vec<u8> {
+            let plan = FaultPlan::default()
+                .with_rule(FaultRule::new(
+                    Direction::Send,
+                    Trigger::Probability(0.5),
+                    Fault::Drop,
+                ))
+                .with_seed(seed);
+            let (mut client, mut chaos) = chaos_pair(plan);
+            for i in 0..32u8 {
+                chaos.send(ChannelType::Control, &[i]).await.unwrap();
+            }
+            chaos.close().await.unwrap();
+
+            let mut received = Vec::new();
+            while let Ok(data) = client.recv(ChannelType::Control).await {
+                received.push(data[0]);
+            }
+            received
+        }
+
+        let first = survivors(11).await;
+        assert!(!first.is_empty() && first.len() < 32);
+        assert_eq!(survivors(11).await, first);
+        assert_ne!(survivors(12).await, first);
+    }
+}
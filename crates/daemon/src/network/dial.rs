@@ -0,0 +1,298 @@
+//! TCP dialing over IPv4 and IPv6.
+//!
+//! Hosts are resolved to all of their addresses, which are dialed
+//! happy-eyeballs style (RFC 8305): the families alternate, starting with the
+//! one the resolver listed first, a new attempt starts whenever the previous
+//! one has not connected within [`CONNECTION_ATTEMPT_DELAY`] or has failed,
+//! and the first connection wins. A host that is only reachable over one
+//! family thus costs one attempt delay rather than a connect timeout.
+//!
+//! Addresses are written `host:port`, with IPv6 literals in brackets, e.g.
+//! `[2001:db8::1]:7677`.
+
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use tokio::net::TcpStream;
+
+/// How long an attempt runs alone before the next address is tried.
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Splits `host:port` into its host, without IPv6 brackets, and port.
+pub fn split_host_port(address: &str) -> Option<(&str, u16)> {
+    let (host, port) = address.rsplit_once(':')?;
+    let port = port.parse().ok()?;
+    let host = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.strip_suffix(']')?,
+        // An IPv6 literal must be bracketed to carry a port
+        None if host.contains(':') => return None,
+        None => host,
+    };
+    (!host.is_empty()).then_some((host, port))
+}
+
+/// Joins a host and port, bracketing IPv6 literals.
+pub fn join_host_port(host: &str, port: u16) -> String {
+    let host = unbracket(host);
+    if host.parse::<std::net::Ipv6Addr>().is_ok() {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// Removes the brackets around an IPv6 literal, as in URL hosts.
+pub fn unbracket(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// Orders addresses so the families alternate, starting with the family of
+/// the first address. Order within a family is kept.
+pub fn interleave(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addresses.first() else {
+        return addresses;
+    };
+    let first_is_v6 = first.is_ipv6();
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addresses
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_v6);
+    let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+    preferred.reverse();
+    other.reverse();
+    loop {
+        match (preferred.pop(), other.pop()) {
+            (None, None) => break,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+    ordered
+}
+
+/// Resolves a host, which may be an IP literal, to interleaved addresses.
+pub async fn resolve(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    let host = unbracket(host);
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addresses.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} has no addresses", host),
+        ));
+    }
+    Ok(interleave(addresses))
+}
+
+/// Races connection attempts to the addresses, in order.
+///
+/// Each attempt gets `delay` to succeed before the next one starts; a
+/// failure starts the next one at once. Returns the first connection, or the
+/// last error once every attempt has failed.
+pub async fn race<T, F, Fut>(addresses: &[SocketAddr], delay: Duration, connect: F) -> io::Result<T>
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+{
+    let mut pending = addresses.iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error = io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to");
+
+    loop {
+        if let Some(&address) = pending.next() {
+            let attempt = connect(address);
+            attempts.push(async move { (address, attempt.await) });
+        } else if attempts.is_empty() {
+            return Err(last_error);
+        }
+
+        // Wait for a result, or for the delay if more addresses remain
+        let more = pending.len() > 0;
+        let timer = tokio::time::sleep(delay);
+        tokio::pin!(timer);
+        loop {
+            tokio::select! {
+                Some((address, result)) = attempts.next() => match result {
+                    Ok(connection) => return Ok(connection),
+                    Err(e) => {
+                        tracing::debug!(%address, error = %e, "connection attempt failed");
+                        last_error = e;
+                        if more || attempts.is_empty() {
+                            break;
+                        }
+                    }
+                },
+                _ = &mut timer, if more => break,
+            }
+        }
+    }
+}
+
+/// Connects to a host over TCP, racing its IPv4 and IPv6 addresses.
+pub async fn connect_tcp(host: &str, port: u16) -> io::Result<TcpStream> {
+    let addresses = resolve(host, port).await?;
+    race(&addresses, CONNECTION_ATTEMPT_DELAY, TcpStream::connect).await
+}
+
+/// Connects to a `host:port` address over TCP.
+pub async fn connect_tcp_address(address: &str) -> io::Result<TcpStream> {
+    let (host, port) = split_host_port(address).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not a host and port", address),
+        )
+    })?;
+    connect_tcp(host, port).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use tokio::net::TcpListener;
+
+    fn v4(last: u8) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::new(192, 0, 2, last), 80))
+    }
+
+    fn v6(last: u16) -> SocketAddr {
+        SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, last), 80))
+    }
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(split_host_port("nas.lan:7677"), Some(("nas.lan", 7677)));
+        assert_eq!(split_host_port("10.0.0.1:7677"), Some(("10.0.0.1", 7677)));
+        assert_eq!(
+            split_host_port("[2001:db8::1]:7677"),
+            Some(("2001:db8::1", 7677))
+        );
+        assert_eq!(split_host_port("2001:db8::1:7677"), None);
+        assert_eq!(split_host_port("[2001:db8::1]"), None);
+        assert_eq!(split_host_port("nas.lan"), None);
+        assert_eq!(split_host_port(":7677"), None);
+    }
+
+    #[test]
+    fn test_join_host_port() {
+        assert_eq!(join_host_port("nas.lan", 443), "nas.lan:443");
+        assert_eq!(join_host_port("::1", 443), "[::1]:443");
+        assert_eq!(join_host_port("[::1]", 443), "[::1]:443");
+        assert_eq!(unbracket("[fd00::1]"), "fd00::1");
+        assert_eq!(unbracket("10.0.0.1"), "10.0.0.1");
+    }
+
+    #[test]
+    fn test_interleave_alternates_families() {
+        assert_eq!(
+            interleave(vec![v6(1), v6(2), v6(3), v4(1), v4(2)]),
+            vec![v6(1), v4(1), v6(2), v4(2), v6(3)]
+        );
+        assert_eq!(
+            interleave(vec![v4(1), v4(2), v6(1)]),
+            vec![v4(1), v6(1), v4(2)]
+        );
+        assert_eq!(interleave(vec![v4(1), v4(2)]), vec![v4(1), v4(2)]);
+        assert!(interleave(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_literals() {
+        assert_eq!(
+            resolve("[::1]", 80).await.unwrap(),
+            vec![SocketAddr::from((Ipv6Addr::LOCALHOST, 80))]
+        );
+        assert_eq!(
+            resolve("127.0.0.1", 80).await.unwrap(),
+            vec![SocketAddr::from((Ipv4Addr::LOCALHOST, 80))]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_race_moves_on_from_unanswered_family() {
+        // A v6-only network: IPv4 attempts never complete
+        let addresses = [v4(1), v6(1), v4(2)];
+        let started = tokio::time::Instant::now();
+        let connected = race(&addresses, CONNECTION_ATTEMPT_DELAY, |address| async move {
+            if address.is_ipv4() {
+                std::future::pending::<()>().await;
+            }
+            Ok(address)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(connected, v6(1));
+        assert_eq!(started.elapsed(), CONNECTION_ATTEMPT_DELAY);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_race_skips_failed_attempts_without_delay() {
+        let addresses = [v6(1), v4(1)];
+        let started = tokio::time::Instant::now();
+        let connected = race(&addresses, CONNECTION_ATTEMPT_DELAY, |address| async move {
+            if address.is_ipv6() {
+                return Err(io::Error::from(io::ErrorKind::NetworkUnreachable));
+            }
+            Ok(address)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(connected, v4(1));
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_race_keeps_slow_attempt_running() {
+        // The first attempt answers after the second one has started
+        let addresses = [v6(1), v4(1)];
+        let connected = race(&addresses, CONNECTION_ATTEMPT_DELAY, |address| async move {
+            if address.is_ipv6() {
+                tokio::time::sleep(CONNECTION_ATTEMPT_DELAY * 2).await;
+                Ok(address)
+            } else {
+                std::future::pending().await
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(connected, v6(1));
+    }
+
+    #[tokio::test]
+    async fn test_race_reports_last_error() {
+        let addresses = [v6(1), v4(1)];
+        let result: io::Result<()> = race(&addresses, CONNECTION_ATTEMPT_DELAY, |_| async {
+            Err(io::Error::from(io::ErrorKind::ConnectionRefused))
+        })
+        .await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+
+        let result: io::Result<()> =
+            race(&[], CONNECTION_ATTEMPT_DELAY, |_| async { Ok(()) }).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_connect_ipv6_literal() {
+        let Ok(listener) = TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).await else {
+            // No IPv6 on this host
+            return;
+        };
+        let port = listener.local_addr().unwrap().port();
+
+        let stream = connect_tcp_address(&format!("[::1]:{}", port))
+            .await
+            .unwrap();
+        assert!(stream.peer_addr().unwrap().is_ipv6());
+
+        assert!(connect_tcp_address(&format!("::1:{}", port)).await.is_err());
+    }
+}
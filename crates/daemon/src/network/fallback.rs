@@ -0,0 +1,530 @@
+//! Transport selection with fallback and mid-session migration.
+//!
+//! QUIC is the preferred transport, but networks that block UDP to arbitrary
+//! ports stop it from connecting at all. [`TransportSelector`] dials the
+//! transports a client has [`Dialer`]s for in order of preference, giving
+//! each attempt a bounded time, and settles for the first that connects.
+//! A transport that failed is remembered as blocked for a while and tried
+//! last, so reconnecting does not wait on it again.
+//!
+//! [`FailoverConnection`] wraps the selected connection. With migration
+//! enabled, a transport failure in the middle of a session dials a new
+//! transport through the same selector and re-attaches the sessions the
+//! client was attached to, so the caller keeps using the same connection.
+//!
+//! Supporting another transport, such as WebSocket, takes a
+//! [`TransportKind`] variant and a dialer for it.
+
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use protocol::dial::{DialRecorder, DialStepKind};
+use protocol::error::{ProtocolError, Result};
+use protocol::messages::{Envelope, Message, SessionAttach};
+use tokio::time::Instant;
+
+use super::{ChannelType, Connection};
+
+/// Default time given to each connection attempt.
+pub const DEFAULT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default time a failed transport is tried after the others.
+pub const DEFAULT_BLOCK_DURATION: Duration = Duration::from_secs(600);
+
+/// A transport a client can connect over, in order of preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TransportKind {
+    /// QUIC streams over iroh.
+    Quic,
+    /// WebRTC data channels.
+    WebRtc,
+}
+
+impl TransportKind {
+    /// Returns the transport name used in logs and errors.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransportKind::Quic => "quic",
+            TransportKind::WebRtc => "webrtc",
+        }
+    }
+}
+
+/// Future returned by [`Dialer::dial`].
+pub type DialFuture<'a> = Pin<Box<dyn Future<Output = Result<Box<dyn Connection>>> + Send + 'a>>;
+
+/// Opens connections over one transport.
+pub trait Dialer: Send + Sync {
+    /// Returns the transport this dialer connects over.
+    fn kind(&self) -> TransportKind;
+
+    /// Connects to the daemon, completing any handshake.
+    fn dial(&self) -> DialFuture<'_>;
+}
+
+/// Dials transports in order of preference.
+pub struct TransportSelector {
+    dialers: Vec<Box<dyn Dialer>>,
+    attempt_timeout: Duration,
+    block_duration: Duration,
+    /// When each failed transport stops being tried last.
+    blocked: Mutex<HashMap<TransportKind, Instant>>,
+}
+
+impl Default for TransportSelector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransportSelector {
+    /// Creates a selector without dialers.
+    pub fn new() -> Self {
+        Self {
+            dialers: Vec::new(),
+            attempt_timeout: DEFAULT_ATTEMPT_TIMEOUT,
+            block_duration: DEFAULT_BLOCK_DURATION,
+            blocked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a dialer. Dialers are tried in [`TransportKind`] order,
+    /// whatever order they are added in.
+    pub fn with_dialer(mut self, dialer: impl Dialer + 'static) -> Self {
+        self.dialers.push(Box::new(dialer));
+        self.dialers.sort_by_key(|dialer| dialer.kind());
+        self
+    }
+
+    /// Sets the time given to each connection attempt.
+    pub fn attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a failed transport is tried after the others.
+    pub fn block_duration(mut self, duration: Duration) -> Self {
+        self.block_duration = duration;
+        self
+    }
+
+    /// Marks a transport as blocked, so it is tried last for a while.
+    pub fn mark_blocked(&self, kind: TransportKind) {
+        self.lock_blocked()
+            .insert(kind, Instant::now() + self.block_duration);
+    }
+
+    /// Returns whether a transport is currently tried last.
+    pub fn is_blocked(&self, kind: TransportKind) -> bool {
+        self.lock_blocked()
+            .get(&kind)
+            .is_some_and(|until| *until > Instant::now())
+    }
+
+    /// Connects over the most preferred transport that works.
+    ///
+    /// Every transport that fails or times out is marked blocked. Fails
+    /// only if all of them do, logging a dial report of every attempt.
+    pub async fn connect(&self) -> Result<(TransportKind, Box<dyn Connection>)> {
+        if self.dialers.is_empty() {
+            return Err(ProtocolError::HandshakeFailed(
+                "no transports configured".into(),
+            ));
+        }
+
+        let mut order: Vec<&dyn Dialer> = self.dialers.iter().map(AsRef::as_ref).collect();
+        // Stable, so preference still applies within each group
+        order.sort_by_key(|dialer| self.is_blocked(dialer.kind()));
+
+        let mut recorder = DialRecorder::new("daemon");
+        let mut failures = Vec::new();
+        for dialer in order {
+            let kind = dialer.kind();
+            let attempt = async {
+                match tokio::time::timeout(self.attempt_timeout, dialer.dial()).await {
+                    Ok(Ok(connection)) => Ok(connection),
+                    Ok(Err(e)) => Err(e.to_string()),
+                    Err(_) => Err(format!("timed out after {:?}", self.attempt_timeout)),
+                }
+            };
+            let error = match recorder
+                .step(DialStepKind::Transport, Some(kind.name()), attempt)
+                .await
+            {
+                Ok(connection) => {
+                    tracing::info!(transport = kind.name(), "Connected");
+                    tracing::debug!(report = %recorder.succeed(), "Dial report");
+                    self.lock_blocked().remove(&kind);
+                    return Ok((kind, connection));
+                }
+                Err(error) => error,
+            };
+            tracing::warn!(transport = kind.name(), "Transport failed: {}", error);
+            self.mark_blocked(kind);
+            failures.push(format!("{}: {}", kind.name(), error));
+        }
+
+        let error = format!("all transports failed ({})", failures.join("; "));
+        tracing::warn!(report = %recorder.fail("all transports failed"), "Dial report");
+        Err(ProtocolError::HandshakeFailed(error))
+    }
+
+    fn lock_blocked(&self) -> std::sync::MutexGuard<'_, HashMap<TransportKind, Instant>> {
+        self.blocked.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns whether an error means the transport itself failed.
+fn is_transport_failure(error: &ProtocolError) -> bool {
+    matches!(
+        error,
+        ProtocolError::ConnectionClosed(_)
+            | ProtocolError::TransferFailed(_)
+            | ProtocolError::Timeout(_)
+    )
+}
+
+/// A connection that can move to another transport mid-session.
+///
+/// Session attachments are followed from the control messages crossing the
+/// connection: a session is attached once the client sends `SessionAttach`
+/// or receives `SessionCreated`, and detached on `SessionDetach`,
+/// `SessionKill` or `SessionClosed`.
+///
+/// Migration runs inside the `send` or `recv` call that hit the failure;
+/// cancelling that call while it migrates leaves the old transport in
+/// place, and the next call migrates again.
+pub struct FailoverConnection {
+    selector: Arc<TransportSelector>,
+    transport: TransportKind,
+    connection: Box<dyn Connection>,
+    migration: bool,
+    sessions: BTreeSet<String>,
+}
+
+impl FailoverConnection {
+    /// Connects through `selector`, with migration disabled.
+    pub async fn connect(selector: Arc<TransportSelector>) -> Result<Self> {
+        let (transport, connection) = selector.connect().await?;
+        Ok(Self {
+            selector,
+            transport,
+            connection,
+            migration: false,
+            sessions: BTreeSet::new(),
+        })
+    }
+
+    /// Enables or disables mid-session migration.
+    pub fn with_migration(mut self, enabled: bool) -> Self {
+        self.migration = enabled;
+        self
+    }
+
+    /// Returns the transport currently in use.
+    pub fn transport(&self) -> TransportKind {
+        self.transport
+    }
+
+    /// Returns the sessions that are re-attached after a migration.
+    pub fn attached_sessions(&self) -> impl Iterator<Item = &str> {
+        self.sessions.iter().map(String::as_str)
+    }
+
+    /// Updates session attachments from a control message.
+    fn track(&mut self, data: &[u8]) {
+        let Ok(envelope) = Envelope::from_msgpack(data) else {
+            return;
+        };
+        match envelope.payload {
+            Message::SessionAttach(attach) => {
+                self.sessions.insert(attach.session_id);
+            }
+            Message::SessionCreated(created) => {
+                self.sessions.insert(created.session_id);
+            }
+            Message::SessionDetach(detach) => {
+                self.sessions.remove(&detach.session_id);
+            }
+            Message::SessionKill(kill) => {
+                self.sessions.remove(&kill.session_id);
+            }
+            Message::SessionClosed(closed) => {
+                self.sessions.remove(&closed.session_id);
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves to a new transport after `error`, or returns `error` if
+    /// migration does not apply.
+    async fn migrate(&mut self, error: ProtocolError) -> Result<()> {
+        if !self.migration || !is_transport_failure(&error) {
+            return Err(error);
+        }
+        tracing::warn!(
+            transport = self.transport.name(),
+            "Transport failed mid-session, migrating: {}",
+            error
+        );
+
+        self.selector.mark_blocked(self.transport);
+        let (transport, mut connection) = self.selector.connect().await?;
+
+        for session_id in &self.sessions {
+            let attach = Message::SessionAttach(SessionAttach {
+                session_id: session_id.clone(),
+            });
+            // Sequence numbers are per connection, so the new one starts over
+            let data = Envelope::new(0, attach).to_msgpack()?;
+            connection.send(ChannelType::Control, &data).await?;
+        }
+
+        let mut old = std::mem::replace(&mut self.connection, connection);
+        let _ = old.close().await;
+        tracing::info!(
+            from = self.transport.name(),
+            to = transport.name(),
+            sessions = self.sessions.len(),
+            "Migrated connection"
+        );
+        self.transport = transport;
+        Ok(())
+    }
+}
+
+impl Connection for FailoverConnection {
+    fn send<'a>(
+        &'a mut self,
+        channel: ChannelType,
+        data: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            if let Err(e) = self.connection.send(channel, data).await {
+                self.migrate(e).await?;
+                self.connection.send(channel, data).await?;
+            }
+            if channel == ChannelType::Control {
+                self.track(data);
+            }
+            Ok(())
+        })
+    }
+
+    fn recv<'a>(
+        &'a mut self,
+        channel: ChannelType,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send + 'a>> {
+        Box::pin(async move {
+            let data = match self.connection.recv(channel).await {
+                Ok(data) => data,
+                Err(e) => {
+                    self.migrate(e).await?;
+                    self.connection.recv(channel).await?
+                }
+            };
+            if channel == ChannelType::Control {
+                self.track(&data);
+            }
+            Ok(data)
+        })
+    }
+
+    fn close<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        self.connection.close()
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connection.is_connected()
+    }
+
+    fn peer_public_key(&self) -> Option<[u8; 32]> {
+        self.connection.peer_public_key()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::loopback::{self, LoopbackConnection, SimulatedLink};
+    use protocol::messages::{SessionCreated, SessionDetach};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::mpsc;
+
+    /// Dials loopback connections, handing the far ends to the test.
+    struct TestDialer {
+        kind: TransportKind,
+        works: bool,
+        dials: Arc<AtomicUsize>,
+        far_ends: mpsc::UnboundedSender<LoopbackConnection>,
+    }
+
+    impl Dialer for TestDialer {
+        fn kind(&self) -> TransportKind {
+            self.kind
+        }
+
+        fn dial(&self) -> DialFuture<'_> {
+            Box::pin(async move {
+                self.dials.fetch_add(1, Ordering::SeqCst);
+                if !self.works {
+                    // Blocked transports usually hang rather than fail
+                    std::future::pending::<()>().await;
+                }
+                let (near, far) = loopback::pair(SimulatedLink::default());
+                let _ = self.far_ends.send(far);
+                Ok(Box::new(near) as Box<dyn Connection>)
+            })
+        }
+    }
+
+    fn dialer(
+        kind: TransportKind,
+        works: bool,
+    ) -> (
+        TestDialer,
+        Arc<AtomicUsize>,
+        mpsc::UnboundedReceiver<LoopbackConnection>,
+    ) {
+        let dials = Arc::new(AtomicUsize::new(0));
+        let (far_ends, rx) = mpsc::unbounded_channel();
+        let dialer = TestDialer {
+            kind,
+            works,
+            dials: dials.clone(),
+            far_ends,
+        };
+        (dialer, dials, rx)
+    }
+
+    fn control(message: Message) -> Vec<u8> {
+        Envelope::new(1, message).to_msgpack().unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_prefers_quic() {
+        let (webrtc, webrtc_dials, _webrtc_ends) = dialer(TransportKind::WebRtc, true);
+        let (quic, quic_dials, _quic_ends) = dialer(TransportKind::Quic, true);
+        let selector = TransportSelector::new()
+            .with_dialer(webrtc)
+            .with_dialer(quic);
+
+        let (kind, _connection) = selector.connect().await.unwrap();
+        assert_eq!(kind, TransportKind::Quic);
+        assert_eq!(quic_dials.load(Ordering::SeqCst), 1);
+        assert_eq!(webrtc_dials.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_falls_back_when_quic_is_blocked() {
+        let (quic, quic_dials, _quic_ends) = dialer(TransportKind::Quic, false);
+        let (webrtc, _, _webrtc_ends) = dialer(TransportKind::WebRtc, true);
+        let selector = TransportSelector::new()
+            .with_dialer(quic)
+            .with_dialer(webrtc)
+            .attempt_timeout(Duration::from_secs(2))
+            .block_duration(Duration::from_secs(60));
+
+        let start = Instant::now();
+        let (kind, _connection) = selector.connect().await.unwrap();
+        assert_eq!(kind, TransportKind::WebRtc);
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert!(selector.is_blocked(TransportKind::Quic));
+
+        // Reconnecting goes straight to WebRTC while QUIC is blocked
+        let start = Instant::now();
+        let (kind, _connection) = selector.connect().await.unwrap();
+        assert_eq!(kind, TransportKind::WebRtc);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(quic_dials.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(!selector.is_blocked(TransportKind::Quic));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_all_transports_failing() {
+        let (quic, _, _quic_ends) = dialer(TransportKind::Quic, false);
+        let selector = TransportSelector::new()
+            .with_dialer(quic)
+            .attempt_timeout(Duration::from_secs(1));
+
+        let err = selector.connect().await.err().unwrap();
+        assert!(err.to_string().contains("quic: timed out"));
+        assert!(TransportSelector::new().connect().await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_migrates_and_reattaches_sessions() {
+        let (quic, _, mut quic_ends) = dialer(TransportKind::Quic, true);
+        let (webrtc, _, mut webrtc_ends) = dialer(TransportKind::WebRtc, true);
+        let selector = Arc::new(
+            TransportSelector::new()
+                .with_dialer(quic)
+                .with_dialer(webrtc),
+        );
+
+        let mut client = FailoverConnection::connect(selector.clone())
+            .await
+            .unwrap()
+            .with_migration(true);
+        assert_eq!(client.transport(), TransportKind::Quic);
+        let mut daemon = quic_ends.recv().await.unwrap();
+
+        for session_id in ["a", "b"] {
+            let attach = control(Message::SessionAttach(SessionAttach {
+                session_id: session_id.into(),
+            }));
+            client.send(ChannelType::Control, &attach).await.unwrap();
+            daemon.recv(ChannelType::Control).await.unwrap();
+        }
+        let created = control(Message::SessionCreated(SessionCreated {
+            session_id: "c".into(),
+            pid: 1,
+        }));
+        daemon.send(ChannelType::Control, &created).await.unwrap();
+        client.recv(ChannelType::Control).await.unwrap();
+        let detach = control(Message::SessionDetach(SessionDetach {
+            session_id: "a".into(),
+        }));
+        client.send(ChannelType::Control, &detach).await.unwrap();
+        assert_eq!(client.attached_sessions().collect::<Vec<_>>(), ["b", "c"]);
+
+        // The QUIC path drops; the next send moves to WebRTC
+        daemon.close().await.unwrap();
+        client.send(ChannelType::Terminal, b"input").await.unwrap();
+        assert_eq!(client.transport(), TransportKind::WebRtc);
+        assert!(selector.is_blocked(TransportKind::Quic));
+
+        let mut daemon = webrtc_ends.recv().await.unwrap();
+        for session_id in ["b", "c"] {
+            let data = daemon.recv(ChannelType::Control).await.unwrap();
+            match Envelope::from_msgpack(&data).unwrap().payload {
+                Message::SessionAttach(attach) => assert_eq!(attach.session_id, session_id),
+                other => panic!("Expected SessionAttach, got {:?}", other),
+            }
+        }
+        assert_eq!(daemon.recv(ChannelType::Terminal).await.unwrap(), b"input");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_migration_by_default() {
+        let (quic, _, mut quic_ends) = dialer(TransportKind::Quic, true);
+        let (webrtc, webrtc_dials, _webrtc_ends) = dialer(TransportKind::WebRtc, true);
+        let selector = Arc::new(
+            TransportSelector::new()
+                .with_dialer(quic)
+                .with_dialer(webrtc),
+        );
+
+        let mut client = FailoverConnection::connect(selector).await.unwrap();
+        let mut daemon = quic_ends.recv().await.unwrap();
+        daemon.close().await.unwrap();
+
+        assert!(client.recv(ChannelType::Control).await.is_err());
+        assert_eq!(webrtc_dials.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,526 @@
+//! In-memory loopback transport for deterministic simulation.
+//!
+//! This module provides a [`Connection`] implementation that never touches a
+//! socket. [`pair`] returns two connected ends; whatever one end sends, the
+//! other receives after a delay computed from a [`SimulatedLink`]:
+//! - Fixed latency plus uniformly distributed jitter
+//! - Packet loss, modeled as retransmissions that each add the retransmit
+//!   timeout, since every data channel is reliable
+//! - Ordering matching the WebRTC data channels: the control and files
+//!   channels are ordered, the terminal channel is not
+//!
+//! Delays are drawn from an RNG seeded by the link, and timing uses
+//! `tokio::time`, so a test running with paused time replays exactly the same
+//! schedule on every run. This lets router and session logic be tested
+//! under adverse network conditions, and performance regressions be
+//! reproduced, without real sockets.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use protocol::error::{ProtocolError, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use super::{ChannelType, Connection};
+
+/// Default time before a lost message is retransmitted.
+pub const DEFAULT_RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Highest accepted packet loss rate.
+///
+/// A rate of 1.0 would retransmit forever.
+pub const MAX_PACKET_LOSS: f64 = 0.99;
+
+/// Network conditions applied to every message crossing a simulated link.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedLink {
+    /// One-way delay applied to every message.
+    pub latency: Duration,
+    /// Upper bound of the random delay added to the latency.
+    pub jitter: Duration,
+    /// Probability that a transmission is lost, between 0.0 and
+    /// [`MAX_PACKET_LOSS`].
+    pub packet_loss: f64,
+    /// Delay added by each retransmission of a lost message.
+    pub retransmit_timeout: Duration,
+    /// Seed for the RNG drawing jitter and losses.
+    pub seed: u64,
+}
+
+impl Default for SimulatedLink {
+    /// An ideal link: no latency, jitter or loss.
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            packet_loss: 0.0,
+            retransmit_timeout: DEFAULT_RETRANSMIT_TIMEOUT,
+            seed: 0,
+        }
+    }
+}
+
+impl SimulatedLink {
+    /// Sets the one-way latency.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Sets the maximum jitter.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the packet loss rate, clamped to `0.0..=MAX_PACKET_LOSS`.
+    pub fn with_packet_loss(mut self, packet_loss: f64) -> Self {
+        self.packet_loss = if packet_loss.is_nan() {
+            0.0
+        } else {
+            packet_loss.clamp(0.0, MAX_PACKET_LOSS)
+        };
+        self
+    }
+
+    /// Sets the retransmit timeout.
+    pub fn with_retransmit_timeout(mut self, timeout: Duration) -> Self {
+        self.retransmit_timeout = timeout;
+        self
+    }
+
+    /// Sets the RNG seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Counters for the messages one end of a loopback pair has sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Messages sent.
+    pub sent: u64,
+    /// Messages received by the peer.
+    pub delivered: u64,
+    /// Simulated retransmissions caused by packet loss.
+    pub retransmissions: u64,
+}
+
+/// Creates a connected pair of loopback connections.
+///
+/// Both directions use `link`; the reverse direction draws from a
+/// differently seeded RNG so the two schedules are independent.
+pub fn pair(link: SimulatedLink) -> (LoopbackConnection, LoopbackConnection) {
+    let reverse_seed = link.seed ^ 0x9e37_79b9_7f4a_7c15;
+    let a_to_b = Arc::new(Lane::new(link.clone(), link.seed));
+    let b_to_a = Arc::new(Lane::new(link, reverse_seed));
+
+    let a = LoopbackConnection {
+        outgoing: Arc::clone(&a_to_b),
+        incoming: Arc::clone(&b_to_a),
+        peer_public_key: None,
+    };
+    let b = LoopbackConnection {
+        outgoing: b_to_a,
+        incoming: a_to_b,
+        peer_public_key: None,
+    };
+    (a, b)
+}
+
+/// One end of an in-memory connection.
+pub struct LoopbackConnection {
+    outgoing: Arc<Lane>,
+    incoming: Arc<Lane>,
+    peer_public_key: Option<[u8; 32]>,
+}
+
+impl LoopbackConnection {
+    /// Sets the key reported by [`Connection::peer_public_key`], standing in
+    /// for the key a Noise handshake would authenticate.
+    pub fn with_peer_public_key(mut self, key: [u8; 32]) -> Self {
+        self.peer_public_key = Some(key);
+        self
+    }
+
+    /// Returns counters for the messages this end has sent.
+    pub fn stats(&self) -> LinkStats {
+        self.outgoing.lock().stats
+    }
+
+    fn send_now(&self, channel: ChannelType, data: &[u8]) -> Result<()> {
+        let mut state = self.outgoing.lock();
+        if state.closed {
+            return Err(ProtocolError::ConnectionClosed(
+                "connection closed".to_string(),
+            ));
+        }
+
+        let link = &self.outgoing.link;
+        let mut delay = link.latency;
+        if !link.jitter.is_zero() {
+            delay += link.jitter.mul_f64(state.rng.gen::<f64>());
+        }
+        while link.packet_loss > 0.0 && state.rng.gen::<f64>() < link.packet_loss {
+            delay += link.retransmit_timeout;
+            state.stats.retransmissions += 1;
+        }
+
+        let mut deliver_at = Instant::now() + delay;
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.stats.sent += 1;
+
+        let queue = &mut state.queues[queue_index(channel)];
+        if is_ordered(channel) {
+            // A message cannot overtake the one sent before it
+            if let Some(last) = queue.last_deliver_at {
+                deliver_at = deliver_at.max(last);
+            }
+            queue.last_deliver_at = Some(deliver_at);
+        }
+        queue.pending.push(Reverse(Pending {
+            deliver_at,
+            sequence,
+            data: data.to_vec(),
+        }));
+        drop(state);
+
+        self.outgoing.notify[queue_index(channel)].notify_waiters();
+        Ok(())
+    }
+
+    async fn recv_next(&self, channel: ChannelType) -> Result<Vec<u8>> {
+        let index = queue_index(channel);
+        loop {
+            // Register for wakeups before inspecting the queue so a message
+            // sent in between is not missed
+            let notified = self.incoming.notify[index].notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let next = {
+                let mut state = self.incoming.lock();
+                let now = Instant::now();
+                match state.queues[index].pending.peek() {
+                    Some(Reverse(head)) if head.deliver_at <= now => {
+                        let Reverse(head) = state.queues[index]
+                            .pending
+                            .pop()
+                            .expect("peeked message is present");
+                        state.stats.delivered += 1;
+                        return Ok(head.data);
+                    }
+                    Some(Reverse(head)) => Some(head.deliver_at),
+                    None if state.closed => {
+                        return Err(ProtocolError::ConnectionClosed(
+                            "channel closed".to_string(),
+                        ));
+                    }
+                    None => None,
+                }
+            };
+
+            match next {
+                Some(deliver_at) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(deliver_at) => {}
+                        _ = &mut notified => {}
+                    }
+                }
+                None => notified.await,
+            }
+        }
+    }
+
+    fn close_now(&self) {
+        for lane in [&self.outgoing, &self.incoming] {
+            lane.lock().closed = true;
+            for notify in &lane.notify {
+                notify.notify_waiters();
+            }
+        }
+    }
+}
+
+impl Connection for LoopbackConnection {
+    fn send<'a>(
+        &'a mut self,
+        channel: ChannelType,
+        data: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move { self.send_now(channel, data) })
+    }
+
+    fn recv<'a>(
+        &'a mut self,
+        channel: ChannelType,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send + 'a>> {
+        Box::pin(self.recv_next(channel))
+    }
+
+    fn close<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            self.close_now();
+            Ok(())
+        })
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.outgoing.lock().closed
+    }
+
+    fn peer_public_key(&self) -> Option<[u8; 32]> {
+        self.peer_public_key
+    }
+}
+
+impl Drop for LoopbackConnection {
+    fn drop(&mut self) {
+        self.close_now();
+    }
+}
+
+/// Returns whether a channel delivers messages in the order they were sent.
+fn is_ordered(channel: ChannelType) -> bool {
+    !matches!(channel, ChannelType::Terminal)
+}
+
+fn queue_index(channel: ChannelType) -> usize {
+    match channel {
+        ChannelType::Control => 0,
+        ChannelType::Terminal => 1,
+        ChannelType::Files => 2,
+    }
+}
+
+/// One direction of a loopback pair.
+struct Lane {
+    link: SimulatedLink,
+    state: Mutex<LaneState>,
+    notify: [Notify; 3],
+}
+
+impl Lane {
+    fn new(link: SimulatedLink, seed: u64) -> Self {
+        Self {
+            link,
+            state: Mutex::new(LaneState {
+                queues: Default::default(),
+                rng: StdRng::seed_from_u64(seed),
+                next_sequence: 0,
+                closed: false,
+                stats: LinkStats::default(),
+            }),
+            notify: Default::default(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LaneState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct LaneState {
+    queues: [Queue; 3],
+    rng: StdRng,
+    next_sequence: u64,
+    closed: bool,
+    stats: LinkStats,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: BinaryHeap<Reverse<Pending>>,
+    /// Delivery time of the last message, for ordered channels.
+    last_deliver_at: Option<Instant>,
+}
+
+/// A message in flight, ordered by delivery time and then send order.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Pending {
+    deliver_at: Instant,
+    sequence: u64,
+    data: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn recv(conn: &mut LoopbackConnection, channel: ChannelType) -> Vec<u8> {
+        conn.recv(channel).await.unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ideal_link_delivers_immediately() {
+        let (mut a, mut b) = pair(SimulatedLink::default());
+        let start = Instant::now();
+
+        a.send(ChannelType::Control, b"ping").await.unwrap();
+        assert_eq!(recv(&mut b, ChannelType::Control).await, b"ping");
+        b.send(ChannelType::Files, b"pong").await.unwrap();
+        assert_eq!(recv(&mut a, ChannelType::Files).await, b"pong");
+
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(
+            a.stats(),
+            LinkStats {
+                sent: 1,
+                delivered: 1,
+                retransmissions: 0
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latency_delays_delivery() {
+        let link = SimulatedLink::default().with_latency(Duration::from_millis(50));
+        let (mut a, mut b) = pair(link);
+        let start = Instant::now();
+
+        a.send(ChannelType::Control, b"one").await.unwrap();
+        assert_eq!(recv(&mut b, ChannelType::Control).await, b"one");
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_channels_are_independent() {
+        let (mut a, mut b) = pair(SimulatedLink::default());
+        a.send(ChannelType::Files, b"file").await.unwrap();
+        a.send(ChannelType::Control, b"control").await.unwrap();
+
+        assert_eq!(recv(&mut b, ChannelType::Control).await, b"control");
+        assert_eq!(recv(&mut b, ChannelType::Files).await, b"file");
+    }
+
+    /// Sends numbered messages and returns the order they arrive in.
+    async fn arrival_order(link: SimulatedLink, channel: ChannelType) -> Vec<u8> {
+        let (mut a, mut b) = pair(link);
+        for i in 0..32u8 {
+            a.send(channel, &[i]).await.unwrap();
+        }
+        let mut order = Vec::new();
+        for _ in 0..32 {
+            order.push(recv(&mut b, channel).await[0]);
+        }
+        order
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_jitter_reorders_only_unordered_channels() {
+        let link = SimulatedLink::default()
+            .with_latency(Duration::from_millis(20))
+            .with_jitter(Duration::from_millis(40))
+            .with_seed(7);
+
+        let in_order: Vec<u8> = (0..32).collect();
+        assert_eq!(
+            arrival_order(link.clone(), ChannelType::Control).await,
+            in_order
+        );
+        assert_ne!(arrival_order(link, ChannelType::Terminal).await, in_order);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_schedule_is_deterministic() {
+        let link = SimulatedLink::default()
+            .with_jitter(Duration::from_millis(40))
+            .with_seed(42);
+
+        let first = arrival_order(link.clone(), ChannelType::Terminal).await;
+        assert_eq!(
+            arrival_order(link.clone(), ChannelType::Terminal).await,
+            first
+        );
+        assert_ne!(
+            arrival_order(link.with_seed(43), ChannelType::Terminal).await,
+            first
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_packet_loss_retransmits() {
+        let link = SimulatedLink::default()
+            .with_latency(Duration::from_millis(10))
+            .with_packet_loss(0.5)
+            .with_retransmit_timeout(Duration::from_millis(100))
+            .with_seed(1);
+        let (mut a, mut b) = pair(link);
+        let start = Instant::now();
+
+        for i in 0..20u8 {
+            a.send(ChannelType::Control, &[i]).await.unwrap();
+        }
+        for i in 0..20u8 {
+            assert_eq!(recv(&mut b, ChannelType::Control).await, [i]);
+        }
+
+        // Nothing is lost, but every loss costs a retransmit timeout
+        let stats = a.stats();
+        assert_eq!(stats.delivered, 20);
+        assert!(stats.retransmissions > 0);
+        assert!(start.elapsed() >= Duration::from_millis(110));
+    }
+
+    #[test]
+    fn test_packet_loss_is_clamped() {
+        assert_eq!(
+            SimulatedLink::default().with_packet_loss(1.5).packet_loss,
+            MAX_PACKET_LOSS
+        );
+        assert_eq!(
+            SimulatedLink::default().with_packet_loss(-1.0).packet_loss,
+            0.0
+        );
+        assert_eq!(
+            SimulatedLink::default()
+                .with_packet_loss(f64::NAN)
+                .packet_loss,
+            0.0
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_close_drains_then_fails() {
+        let link = SimulatedLink::default().with_latency(Duration::from_millis(5));
+        let (mut a, mut b) = pair(link);
+
+        a.send(ChannelType::Control, b"last").await.unwrap();
+        a.close().await.unwrap();
+        assert!(!a.is_connected());
+        assert!(!b.is_connected());
+        assert!(a.send(ChannelType::Control, b"late").await.is_err());
+
+        // In-flight messages still arrive
+        assert_eq!(recv(&mut b, ChannelType::Control).await, b"last");
+        let err = b.recv(ChannelType::Control).await.unwrap_err();
+        assert!(err.to_string().contains("channel closed"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dropping_an_end_wakes_the_peer() {
+        let (a, mut b) = pair(SimulatedLink::default());
+        let waiter = tokio::spawn(async move { b.recv(ChannelType::Control).await });
+        tokio::task::yield_now().await;
+
+        drop(a);
+        assert!(waiter.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_peer_public_key() {
+        let (a, b) = pair(SimulatedLink::default());
+        let a = a.with_peer_public_key([9; 32]);
+        assert_eq!(a.peer_public_key(), Some([9; 32]));
+        assert_eq!(b.peer_public_key(), None);
+    }
+}
@@ -1,11 +1,27 @@
 //! Network module for peer-to-peer connections.
 //!
 //! This module provides connection handling for both:
-//! - WebRTC connections for browser clients (ICE, signaling, data channels)
-//! - QUIC connections for native Tauri clients (iroh, hole punching, TLS 1.3)
+//! - WebRTC connections for browser clients (ICE, signaling, data channels),
+//!   behind the `webrtc` feature
+//! - QUIC connections for native Tauri clients (iroh, hole punching, TLS 1.3),
+//!   behind the `quic` feature
+//! - In-memory loopback connections for deterministic simulation in tests
+//! - Transport selection that prefers QUIC and falls back to WebRTC
+//! - Happy-eyeballs TCP dialing over IPv4 and IPv6
+//! - Outbound connections through HTTP CONNECT and SOCKS5 proxies
+//! - Fault injection for chaos tests, behind the `chaos` feature
 
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod dial;
+pub mod fallback;
+pub mod loopback;
+pub mod proxy;
+#[cfg(feature = "quic")]
 pub mod quic;
+#[cfg(feature = "webrtc")]
 pub mod signaling;
+#[cfg(feature = "webrtc")]
 pub mod webrtc;
 
 use std::future::Future;
@@ -65,13 +81,43 @@ impl ChannelType {
             ChannelType::Files => "files",
         }
     }
+    /// Returns the channel type with the given WebRTC data channel name.
+    pub fn from_channel_name(name: &str) -> Option<Self> {
+        match name {
+            "control" => Some(ChannelType::Control),
+            "terminal" => Some(ChannelType::Terminal),
+            "files" => Some(ChannelType::Files),
+            _ => None,
+        }
+    }
+
+    /// Returns the priority of the channel's data on a saturated link, higher
+    /// first: terminal I/O, then control messages, then file transfers.
+    ///
+    /// QUIC connections give each channel's send stream this priority, so a
+    /// file transfer filling the link does not delay keystrokes and output.
+    pub fn priority(&self) -> i32 {
+        match self {
+            ChannelType::Terminal => 2,
+            ChannelType::Control => 1,
+            ChannelType::Files => 0,
+        }
+    }
 }
 
 // Re-export key types
-pub use quic::{QuicConfig, QuicConnectionHandler, REMOSHELL_ALPN};
+#[cfg(feature = "chaos")]
+pub use chaos::{ChaosConnection, ChaosHandle, FaultPlan, FaultRule};
+pub use fallback::{Dialer, FailoverConnection, TransportKind, TransportSelector};
+pub use loopback::{LinkStats, LoopbackConnection, SimulatedLink};
+pub use proxy::ProxySettings;
+#[cfg(feature = "quic")]
+pub use quic::{QuicConfig, QuicConnectionHandler, QuicListener, REMOSHELL_ALPN};
+#[cfg(feature = "webrtc")]
 pub use signaling::{
     ConnectionState as SignalingConnectionState, IncomingMessage as SignalingIncomingMessage,
     OutgoingMessage as SignalingOutgoingMessage, SignalingClient, SignalingConfig, SignalingEvent,
     WebSocketSignalingClient,
 };
+#[cfg(feature = "webrtc")]
 pub use webrtc::{IceServer, WebRtcConfig, WebRtcConnectionHandler};
@@ -5,21 +5,27 @@
 //! - Automatic relay fallback for reliable connectivity
 //! - TLS 1.3 encryption (native to QUIC)
 //! - Bi-directional streams for data transfer
+//!
+//! A daemon accepts connections through a [`QuicListener`], whose endpoint
+//! can be pinned to specific addresses and a port range for firewall rules.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::ops::RangeInclusive;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 
-use iroh::endpoint::Connection;
-#[cfg(test)]
-use iroh::RelayMode;
-use iroh::{Endpoint, NodeAddr, NodeId, RelayUrl, SecretKey};
+use iroh::endpoint::{Connection, DirectAddrInfo, Incoming, RemoteInfo};
+use iroh::{Endpoint, NodeAddr, NodeId, RelayMode, RelayUrl, SecretKey};
+use protocol::dial::{DialRecorder, DialReport, DialStep, DialStepKind, StepOutcome};
 use protocol::error::{ProtocolError, Result};
 use tokio::sync::{mpsc, Mutex, RwLock};
+use tracing::Instrument;
 
 use super::{ChannelType, Connection as ConnectionTrait};
+use crate::telemetry;
 
 /// The ALPN protocol identifier for RemoShell QUIC connections.
 pub const REMOSHELL_ALPN: &[u8] = b"remoshell/1";
@@ -45,6 +51,16 @@ pub struct QuicConfig {
     pub connect_timeout: Duration,
     /// Stream operation timeout.
     pub stream_timeout: Duration,
+    /// Addresses to bind the endpoint to, at most one per address family.
+    /// Empty binds to all interfaces.
+    pub bind_addresses: Vec<IpAddr>,
+    /// UDP ports the endpoint may bind to, tried in order. `None` binds to
+    /// a random port.
+    pub port_range: Option<RangeInclusive<u16>>,
+    /// Whether connections may go through relay servers.
+    pub relay_enabled: bool,
+    /// HTTP proxy for connections to relay servers.
+    pub proxy_url: Option<url::Url>,
 }
 
 impl Default for QuicConfig {
@@ -53,6 +69,10 @@ impl Default for QuicConfig {
             relay_url: None,
             connect_timeout: DEFAULT_CONNECT_TIMEOUT,
             stream_timeout: DEFAULT_STREAM_TIMEOUT,
+            bind_addresses: Vec::new(),
+            port_range: None,
+            relay_enabled: true,
+            proxy_url: None,
         }
     }
 }
@@ -77,38 +97,206 @@ impl QuicConfig {
         self.stream_timeout = timeout;
         self
     }
+
+    /// Binds the endpoint to the given addresses instead of all interfaces.
+    pub fn bind_addresses(mut self, addresses: Vec<IpAddr>) -> Self {
+        self.bind_addresses = addresses;
+        self
+    }
+
+    /// Binds the endpoint to the first free port of the range.
+    pub fn port_range(mut self, ports: RangeInclusive<u16>) -> Self {
+        self.port_range = Some(ports);
+        self
+    }
+
+    /// Reaches relay servers through an HTTP proxy.
+    pub fn proxy_url(mut self, proxy_url: url::Url) -> Self {
+        self.proxy_url = Some(proxy_url);
+        self
+    }
+
+    /// Disables relay servers, so only direct connections are made.
+    pub fn without_relay(mut self) -> Self {
+        self.relay_enabled = false;
+        self
+    }
+}
+
+/// Binds an iroh endpoint as the configuration asks.
+///
+/// iroh silently moves to a random port when the requested one is taken, so
+/// each port of the range is checked against the sockets actually bound.
+/// With pinned addresses, a family without one is bound to loopback, since
+/// iroh always binds both families.
+async fn bind_endpoint(config: &QuicConfig, secret_key: Option<SecretKey>) -> Result<Endpoint> {
+    let pinned = !config.bind_addresses.is_empty();
+    let ipv4 = config
+        .bind_addresses
+        .iter()
+        .find_map(|addr| match addr {
+            IpAddr::V4(addr) => Some(*addr),
+            IpAddr::V6(_) => None,
+        })
+        .unwrap_or(if pinned {
+            Ipv4Addr::LOCALHOST
+        } else {
+            Ipv4Addr::UNSPECIFIED
+        });
+    let ipv6 = config
+        .bind_addresses
+        .iter()
+        .find_map(|addr| match addr {
+            IpAddr::V6(addr) => Some(*addr),
+            IpAddr::V4(_) => None,
+        })
+        .unwrap_or(if pinned {
+            Ipv6Addr::LOCALHOST
+        } else {
+            Ipv6Addr::UNSPECIFIED
+        });
+
+    let ports = config.port_range.clone().unwrap_or(0..=0);
+    for port in ports.clone() {
+        let mut builder = Endpoint::builder()
+            .alpns(vec![REMOSHELL_ALPN.to_vec()])
+            .bind_addr_v4(SocketAddrV4::new(ipv4, port))
+            .bind_addr_v6(SocketAddrV6::new(ipv6, port, 0, 0));
+        if let Some(ref secret_key) = secret_key {
+            builder = builder.secret_key(secret_key.clone());
+        }
+        if config.relay_enabled {
+            if let Some(ref relay_url) = config.relay_url {
+                // The relay URL is used during connection, not endpoint creation
+                tracing::debug!("QUIC endpoint will use relay: {}", relay_url);
+            }
+            if let Some(ref proxy_url) = config.proxy_url {
+                builder = builder.proxy_url(proxy_url.clone());
+            }
+        } else {
+            builder = builder.relay_mode(RelayMode::Disabled).clear_discovery();
+        }
+
+        let endpoint = builder.bind().await.map_err(|e| {
+            ProtocolError::HandshakeFailed(format!("failed to create iroh endpoint: {}", e))
+        })?;
+        if port == 0 {
+            return Ok(endpoint);
+        }
+
+        let (v4, v6) = endpoint.bound_sockets();
+        if v4.port() == port && v6.is_none_or(|v6| v6.port() == port) {
+            return Ok(endpoint);
+        }
+        tracing::debug!(port, "QUIC port is in use, trying the next one");
+        endpoint.close().await;
+    }
+
+    Err(ProtocolError::HandshakeFailed(format!(
+        "no free UDP port in {}-{}",
+        ports.start(),
+        ports.end()
+    )))
+}
+
+/// Accepts QUIC connections on the daemon's endpoint.
+///
+/// Each accepted connection gets its own [`QuicConnectionHandler`] sharing
+/// the listener's endpoint.
+pub struct QuicListener {
+    /// The endpoint connections arrive on.
+    endpoint: Endpoint,
+    /// Configuration for accepted connections.
+    config: QuicConfig,
+}
+
+impl QuicListener {
+    /// Binds the listener's endpoint with the given identity.
+    pub async fn bind(config: QuicConfig, secret_key: SecretKey) -> Result<Self> {
+        let endpoint = bind_endpoint(&config, Some(secret_key)).await?;
+        Ok(Self { endpoint, config })
+    }
+
+    /// Returns the node ID (public key) of the endpoint.
+    pub fn node_id(&self) -> NodeId {
+        self.endpoint.node_id()
+    }
+
+    /// Returns the local UDP sockets the endpoint is bound to.
+    pub fn bound_addresses(&self) -> Vec<SocketAddr> {
+        let (v4, v6) = self.endpoint.bound_sockets();
+        std::iter::once(v4).chain(v6).collect()
+    }
+
+    /// Returns whether connections may go through relay servers.
+    pub fn relay_enabled(&self) -> bool {
+        self.config.relay_enabled
+    }
+
+    /// Waits for the next incoming connection.
+    ///
+    /// Returns `None` once the listener is closed.
+    pub async fn accept(&self) -> Option<Incoming> {
+        self.endpoint.accept().await
+    }
+
+    /// Completes an incoming connection and accepts its streams.
+    ///
+    /// A failure is logged with a dial report of the handshake.
+    pub async fn establish(&self, incoming: Incoming) -> Result<QuicConnectionHandler> {
+        let mut recorder = DialRecorder::new(incoming.remote_address().to_string());
+        let handler =
+            QuicConnectionHandler::from_endpoint(self.endpoint.clone(), self.config.clone());
+        let result = async {
+            recorder
+                .step(
+                    DialStepKind::Handshake,
+                    None,
+                    handler.accept_incoming(incoming),
+                )
+                .await?;
+            recorder
+                .step(DialStepKind::Streams, None, handler.accept_streams())
+                .await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!(report = %recorder.fail(&e), "QUIC connection failed");
+            return Err(e);
+        }
+        handler.spawn_stream_readers();
+        Ok(handler)
+    }
+
+    /// Closes the endpoint and every connection accepted on it.
+    pub async fn close(&self) {
+        self.endpoint.close().await;
+    }
 }
 
 /// Internal state for managing bi-directional streams per channel type.
 #[derive(Default)]
 struct StreamChannels {
-    control: Option<StreamPair>,
-    terminal: Option<StreamPair>,
-    files: Option<StreamPair>,
+    control: Option<Arc<StreamPair>>,
+    terminal: Option<Arc<StreamPair>>,
+    files: Option<Arc<StreamPair>>,
 }
 
 impl StreamChannels {
-    fn get(&self, channel_type: ChannelType) -> Option<&StreamPair> {
-        match channel_type {
-            ChannelType::Control => self.control.as_ref(),
-            ChannelType::Terminal => self.terminal.as_ref(),
-            ChannelType::Files => self.files.as_ref(),
-        }
-    }
-
-    fn get_mut(&mut self, channel_type: ChannelType) -> Option<&mut StreamPair> {
+    fn get(&self, channel_type: ChannelType) -> Option<Arc<StreamPair>> {
         match channel_type {
-            ChannelType::Control => self.control.as_mut(),
-            ChannelType::Terminal => self.terminal.as_mut(),
-            ChannelType::Files => self.files.as_mut(),
+            ChannelType::Control => self.control.clone(),
+            ChannelType::Terminal => self.terminal.clone(),
+            ChannelType::Files => self.files.clone(),
         }
     }
 
     fn set(&mut self, channel_type: ChannelType, stream: StreamPair) {
+        let stream = Some(Arc::new(stream));
         match channel_type {
-            ChannelType::Control => self.control = Some(stream),
-            ChannelType::Terminal => self.terminal = Some(stream),
-            ChannelType::Files => self.files = Some(stream),
+            ChannelType::Control => self.control = stream,
+            ChannelType::Terminal => self.terminal = stream,
+            ChannelType::Files => self.files = stream,
         }
     }
 
@@ -117,10 +305,35 @@ impl StreamChannels {
     }
 }
 
+/// Messages read from one channel's stream, waiting to be received.
+type ChannelReceiver = Arc<Mutex<mpsc::Receiver<Vec<u8>>>>;
+
 /// A pair of send and receive streams for bi-directional communication.
+///
+/// Each half has its own lock, so a reader waiting for data never holds up
+/// a writer, and a channel waiting on flow control never holds up the
+/// others.
 struct StreamPair {
-    send: iroh::endpoint::SendStream,
-    recv: iroh::endpoint::RecvStream,
+    send: Mutex<iroh::endpoint::SendStream>,
+    recv: Mutex<iroh::endpoint::RecvStream>,
+}
+
+impl StreamPair {
+    /// Pairs the streams of a channel, giving the send stream the channel's
+    /// [priority](ChannelType::priority).
+    fn new(
+        channel_type: ChannelType,
+        send: iroh::endpoint::SendStream,
+        recv: iroh::endpoint::RecvStream,
+    ) -> Result<Self> {
+        send.set_priority(channel_type.priority()).map_err(|e| {
+            ProtocolError::ConnectionClosed(format!("{:?} stream closed: {}", channel_type, e))
+        })?;
+        Ok(Self {
+            send: Mutex::new(send),
+            recv: Mutex::new(recv),
+        })
+    }
 }
 
 /// QUIC connection handler for native Tauri clients.
@@ -137,8 +350,9 @@ pub struct QuicConnectionHandler {
     connection: Arc<RwLock<Option<Connection>>>,
     /// Stream channels for different message types.
     streams: Arc<Mutex<StreamChannels>>,
-    /// Receiver for incoming messages from streams.
-    message_rx: Arc<Mutex<HashMap<ChannelType, mpsc::Receiver<Vec<u8>>>>>,
+    /// Receiver for incoming messages from streams, locked per channel so
+    /// that waiting on one channel does not hold up the others.
+    message_rx: Arc<Mutex<HashMap<ChannelType, ChannelReceiver>>>,
     /// Senders for incoming messages (used by stream readers).
     message_tx: Arc<RwLock<HashMap<ChannelType, mpsc::Sender<Vec<u8>>>>>,
     /// Whether the connection is established.
@@ -154,60 +368,21 @@ impl QuicConnectionHandler {
     ///
     /// This sets up the iroh endpoint and prepares for connections.
     pub async fn new(config: QuicConfig) -> Result<Self> {
-        let builder = Endpoint::builder().alpns(vec![REMOSHELL_ALPN.to_vec()]);
-
-        // Configure relay if provided
-        if let Some(ref relay_url) = config.relay_url {
-            // The relay URL is used during connection, not endpoint creation
-            tracing::debug!("QUIC endpoint will use relay: {}", relay_url);
-        }
-
-        let endpoint = builder.bind().await.map_err(|e| {
-            ProtocolError::HandshakeFailed(format!("failed to create iroh endpoint: {}", e))
-        })?;
-
-        // Create message channels for each channel type
-        let mut message_rx = HashMap::new();
-        let mut message_tx = HashMap::new();
-
-        for channel_type in [
-            ChannelType::Control,
-            ChannelType::Terminal,
-            ChannelType::Files,
-        ] {
-            let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
-            message_tx.insert(channel_type, tx);
-            message_rx.insert(channel_type, rx);
-        }
-
-        Ok(Self {
-            endpoint,
-            connection: Arc::new(RwLock::new(None)),
-            streams: Arc::new(Mutex::new(StreamChannels::default())),
-            message_rx: Arc::new(Mutex::new(message_rx)),
-            message_tx: Arc::new(RwLock::new(message_tx)),
-            connected: Arc::new(RwLock::new(false)),
-            peer_node_id: Arc::new(RwLock::new(None)),
-            config,
-        })
+        let endpoint = bind_endpoint(&config, None).await?;
+        Ok(Self::from_endpoint(endpoint, config))
     }
 
     /// Creates a new QUIC connection handler with a specific secret key.
     ///
     /// This allows using a persistent identity across restarts.
     pub async fn with_secret_key(config: QuicConfig, secret_key: SecretKey) -> Result<Self> {
-        let builder = Endpoint::builder()
-            .secret_key(secret_key)
-            .alpns(vec![REMOSHELL_ALPN.to_vec()]);
-
-        if let Some(ref relay_url) = config.relay_url {
-            tracing::debug!("QUIC endpoint will use relay: {}", relay_url);
-        }
-
-        let endpoint = builder.bind().await.map_err(|e| {
-            ProtocolError::HandshakeFailed(format!("failed to create iroh endpoint: {}", e))
-        })?;
+        let endpoint = bind_endpoint(&config, Some(secret_key)).await?;
+        Ok(Self::from_endpoint(endpoint, config))
+    }
 
+    /// Creates a handler for connections of an already bound endpoint.
+    fn from_endpoint(endpoint: Endpoint, config: QuicConfig) -> Self {
+        // Create message channels for each channel type
         let mut message_rx = HashMap::new();
         let mut message_tx = HashMap::new();
 
@@ -218,10 +393,10 @@ impl QuicConnectionHandler {
         ] {
             let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
             message_tx.insert(channel_type, tx);
-            message_rx.insert(channel_type, rx);
+            message_rx.insert(channel_type, Arc::new(Mutex::new(rx)));
         }
 
-        Ok(Self {
+        Self {
             endpoint,
             connection: Arc::new(RwLock::new(None)),
             streams: Arc::new(Mutex::new(StreamChannels::default())),
@@ -230,7 +405,7 @@ impl QuicConnectionHandler {
             connected: Arc::new(RwLock::new(false)),
             peer_node_id: Arc::new(RwLock::new(None)),
             config,
-        })
+        }
     }
 
     /// Returns the node ID (public key) of this endpoint.
@@ -262,6 +437,11 @@ impl QuicConnectionHandler {
             .await
             .ok_or_else(|| ProtocolError::ConnectionClosed("endpoint closed".into()))?;
 
+        self.accept_incoming(incoming).await
+    }
+
+    /// Completes the QUIC handshake of a connection the endpoint received.
+    async fn accept_incoming(&self, incoming: Incoming) -> Result<()> {
         let connection = incoming.await.map_err(|e| {
             ProtocolError::HandshakeFailed(format!("failed to accept connection: {}", e))
         })?;
@@ -293,17 +473,32 @@ impl QuicConnectionHandler {
 
     /// Connects to a peer using their node address.
     ///
-    /// This initiates a QUIC connection with built-in hole punching.
+    /// This initiates a QUIC connection with built-in hole punching. A
+    /// failure is logged with a dial report of the paths that were tried.
     pub async fn connect(&self, node_addr: NodeAddr) -> Result<()> {
         let remote_node_id = node_addr.node_id;
+        let recorder = DialRecorder::new(remote_node_id.to_string());
 
-        let connection = tokio::time::timeout(
+        let result = tokio::time::timeout(
             self.config.connect_timeout,
-            self.endpoint.connect(node_addr, REMOSHELL_ALPN),
+            self.endpoint.connect(node_addr.clone(), REMOSHELL_ALPN),
         )
-        .await
-        .map_err(|_| ProtocolError::Timeout("connection timed out".into()))?
-        .map_err(|e| ProtocolError::HandshakeFailed(format!("failed to connect: {}", e)))?;
+        .await;
+        let connection = match result {
+            Ok(Ok(connection)) => connection,
+            failure => {
+                let error = match failure {
+                    Ok(Err(e)) => {
+                        ProtocolError::HandshakeFailed(format!("failed to connect: {}", e))
+                    }
+                    _ => ProtocolError::Timeout("connection timed out".into()),
+                };
+                let info = self.endpoint.remote_info(remote_node_id);
+                let report = dial_report(recorder, &node_addr, info.as_ref(), &error);
+                tracing::warn!(report = %report, "QUIC dial failed");
+                return Err(error);
+            }
+        };
 
         // Store the peer's node ID
         {
@@ -361,7 +556,7 @@ impl QuicConnectionHandler {
                 ProtocolError::TransferFailed(format!("failed to send channel id: {}", e))
             })?;
 
-            streams.set(channel_type, StreamPair { send, recv });
+            streams.set(channel_type, StreamPair::new(channel_type, send, recv)?);
             tracing::debug!("Created {:?} stream", channel_type);
         }
 
@@ -410,7 +605,7 @@ impl QuicConnectionHandler {
                 continue;
             }
 
-            streams.set(channel_type, StreamPair { send, recv });
+            streams.set(channel_type, StreamPair::new(channel_type, send, recv)?);
             tracing::debug!("Accepted {:?} stream", channel_type);
             accepted_count += 1;
         }
@@ -433,37 +628,42 @@ impl QuicConnectionHandler {
             let message_tx = message_tx.clone();
             let connected = connected.clone();
 
-            tokio::spawn(async move {
-                loop {
-                    let result = Self::read_from_stream(&streams, channel_type).await;
-
-                    match result {
-                        Ok(data) => {
-                            let tx = {
-                                let senders = message_tx.read().await;
-                                senders.get(&channel_type).cloned()
-                            };
-
-                            if let Some(tx) = tx {
-                                if let Err(e) = tx.send(data).await {
-                                    tracing::error!(
-                                        "failed to forward {:?} message: {}",
-                                        channel_type,
-                                        e
-                                    );
-                                    break;
+            let span = telemetry::channel_span(channel_type);
+
+            tokio::spawn(
+                async move {
+                    loop {
+                        let result = Self::read_from_stream(&streams, channel_type).await;
+
+                        match result {
+                            Ok(data) => {
+                                let tx = {
+                                    let senders = message_tx.read().await;
+                                    senders.get(&channel_type).cloned()
+                                };
+
+                                if let Some(tx) = tx {
+                                    if let Err(e) = tx.send(data).await {
+                                        tracing::error!(
+                                            "failed to forward {:?} message: {}",
+                                            channel_type,
+                                            e
+                                        );
+                                        break;
+                                    }
                                 }
                             }
-                        }
-                        Err(e) => {
-                            tracing::debug!("{:?} stream closed or error: {}", channel_type, e);
-                            let mut conn = connected.write().await;
-                            *conn = false;
-                            break;
+                            Err(e) => {
+                                tracing::debug!("{:?} stream closed or error: {}", channel_type, e);
+                                let mut conn = connected.write().await;
+                                *conn = false;
+                                break;
+                            }
                         }
                     }
                 }
-            });
+                .instrument(span),
+            );
         }
     }
 
@@ -472,16 +672,14 @@ impl QuicConnectionHandler {
         streams: &Arc<Mutex<StreamChannels>>,
         channel_type: ChannelType,
     ) -> Result<Vec<u8>> {
-        let mut streams = streams.lock().await;
-        let stream_pair = streams.get_mut(channel_type).ok_or_else(|| {
+        let stream_pair = streams.lock().await.get(channel_type).ok_or_else(|| {
             ProtocolError::ConnectionClosed(format!("{:?} stream not available", channel_type))
         })?;
+        let mut recv = stream_pair.recv.lock().await;
 
         // Read 4-byte length prefix
         let mut len_buf = [0u8; 4];
-        stream_pair
-            .recv
-            .read_exact(&mut len_buf)
+        recv.read_exact(&mut len_buf)
             .await
             .map_err(|e| ProtocolError::ConnectionClosed(format!("stream read error: {}", e)))?;
 
@@ -495,9 +693,7 @@ impl QuicConnectionHandler {
 
         // Read the message data
         let mut data = vec![0u8; len];
-        stream_pair
-            .recv
-            .read_exact(&mut data)
+        recv.read_exact(&mut data)
             .await
             .map_err(|e| ProtocolError::ConnectionClosed(format!("stream read error: {}", e)))?;
 
@@ -517,23 +713,19 @@ impl QuicConnectionHandler {
             });
         }
 
-        let mut streams = streams.lock().await;
-        let stream_pair = streams.get_mut(channel_type).ok_or_else(|| {
+        let stream_pair = streams.lock().await.get(channel_type).ok_or_else(|| {
             ProtocolError::ConnectionClosed(format!("{:?} stream not available", channel_type))
         })?;
+        let mut send = stream_pair.send.lock().await;
 
         // Write 4-byte length prefix
         let len = data.len() as u32;
-        stream_pair
-            .send
-            .write_all(&len.to_be_bytes())
+        send.write_all(&len.to_be_bytes())
             .await
             .map_err(|e| ProtocolError::TransferFailed(format!("stream write error: {}", e)))?;
 
         // Write the message data
-        stream_pair
-            .send
-            .write_all(data)
+        send.write_all(data)
             .await
             .map_err(|e| ProtocolError::TransferFailed(format!("stream write error: {}", e)))?;
 
@@ -547,11 +739,17 @@ impl QuicConnectionHandler {
 
     /// Receives data from a specific channel.
     pub async fn recv(&self, channel_type: ChannelType) -> Result<Vec<u8>> {
-        let mut receivers = self.message_rx.lock().await;
-        let rx = receivers.get_mut(&channel_type).ok_or_else(|| {
-            ProtocolError::ConnectionClosed(format!("{:?} channel not available", channel_type))
-        })?;
+        let rx = self
+            .message_rx
+            .lock()
+            .await
+            .get(&channel_type)
+            .cloned()
+            .ok_or_else(|| {
+                ProtocolError::ConnectionClosed(format!("{:?} channel not available", channel_type))
+            })?;
 
+        let mut rx = rx.lock().await;
         rx.recv()
             .await
             .ok_or_else(|| ProtocolError::ConnectionClosed("channel closed".into()))
@@ -602,7 +800,7 @@ impl QuicConnectionHandler {
             ] {
                 let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
                 message_tx.insert(channel_type, tx);
-                message_rx.insert(channel_type, rx);
+                message_rx.insert(channel_type, Arc::new(Mutex::new(rx)));
             }
         }
 
@@ -636,45 +834,14 @@ impl QuicConnectionHandler {
     /// This disables discovery and relay for faster local connections.
     #[cfg(test)]
     pub(crate) async fn new_for_testing() -> Result<Self> {
-        let builder = Endpoint::builder()
-            .alpns(vec![REMOSHELL_ALPN.to_vec()])
-            .relay_mode(RelayMode::Disabled)
-            .clear_discovery();
-
-        let endpoint = builder.bind().await.map_err(|e| {
-            ProtocolError::HandshakeFailed(format!("failed to create iroh endpoint: {}", e))
-        })?;
-
-        let mut message_rx = HashMap::new();
-        let mut message_tx = HashMap::new();
-
-        for channel_type in [
-            ChannelType::Control,
-            ChannelType::Terminal,
-            ChannelType::Files,
-        ] {
-            let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
-            message_tx.insert(channel_type, tx);
-            message_rx.insert(channel_type, rx);
-        }
-
         // Use shorter timeouts for testing
-        let config = QuicConfig {
-            relay_url: None,
-            connect_timeout: Duration::from_secs(5),
-            stream_timeout: Duration::from_secs(5),
-        };
+        let config = QuicConfig::default()
+            .without_relay()
+            .connect_timeout(Duration::from_secs(5))
+            .stream_timeout(Duration::from_secs(5));
 
-        Ok(Self {
-            endpoint,
-            connection: Arc::new(RwLock::new(None)),
-            streams: Arc::new(Mutex::new(StreamChannels::default())),
-            message_rx: Arc::new(Mutex::new(message_rx)),
-            message_tx: Arc::new(RwLock::new(message_tx)),
-            connected: Arc::new(RwLock::new(false)),
-            peer_node_id: Arc::new(RwLock::new(None)),
-            config,
-        })
+        let endpoint = bind_endpoint(&config, None).await?;
+        Ok(Self::from_endpoint(endpoint, config))
     }
 }
 
@@ -691,16 +858,7 @@ impl ConnectionTrait for QuicConnectionHandler {
         &'a mut self,
         channel: ChannelType,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send + 'a>> {
-        Box::pin(async move {
-            let mut receivers = self.message_rx.lock().await;
-            let rx = receivers.get_mut(&channel).ok_or_else(|| {
-                ProtocolError::ConnectionClosed(format!("{:?} channel not available", channel))
-            })?;
-
-            rx.recv()
-                .await
-                .ok_or_else(|| ProtocolError::ConnectionClosed("channel closed".into()))
-        })
+        Box::pin(async move { QuicConnectionHandler::recv(self, channel).await })
     }
 
     fn close<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
@@ -717,6 +875,95 @@ impl ConnectionTrait for QuicConnectionHandler {
     }
 }
 
+/// Completes the report of a failed dial with the paths iroh tried.
+fn dial_report(
+    mut recorder: DialRecorder,
+    node_addr: &NodeAddr,
+    info: Option<&RemoteInfo>,
+    error: impl std::fmt::Display,
+) -> DialReport {
+    recorder.extend(path_steps(node_addr, info));
+    let elapsed = recorder.elapsed();
+    recorder.push(
+        DialStep::new(DialStepKind::Handshake, StepOutcome::Failed)
+            .timing(Duration::ZERO, elapsed)
+            .detail(error.to_string()),
+    );
+    recorder.fail(error)
+}
+
+/// Describes the relay, direct and hole-punched paths to a peer.
+///
+/// iroh does not report its attempts, so they are read back from what it
+/// learned about the peer: a relay that carried traffic has been alive, and
+/// a direct address that answered a ping has a latency.
+fn path_steps(node_addr: &NodeAddr, info: Option<&RemoteInfo>) -> Vec<DialStep> {
+    let mut steps = Vec::new();
+
+    let relay = info.and_then(|info| info.relay_url.as_ref());
+    let relay_url = node_addr
+        .relay_url
+        .clone()
+        .or_else(|| relay.map(|relay| relay.relay_url.clone()));
+    let relay_alive = relay.filter(|relay| relay.last_alive.is_some() || relay.latency.is_some());
+    steps.push(match (&relay_url, relay_alive) {
+        (None, _) => DialStep::new(DialStepKind::RelayLookup, StepOutcome::Skipped)
+            .detail("no relay URL for the peer"),
+        (Some(url), Some(relay)) => {
+            let step = DialStep::new(DialStepKind::RelayLookup, StepOutcome::Succeeded)
+                .target(url.to_string());
+            match relay.latency {
+                Some(latency) => step.detail(format!("latency {}ms", latency.as_millis())),
+                None => step,
+            }
+        }
+        (Some(url), None) => DialStep::new(DialStepKind::RelayLookup, StepOutcome::Failed)
+            .target(url.to_string())
+            .detail("no answer through the relay"),
+    });
+
+    // Addresses the caller gave, plus any iroh discovered
+    let mut candidates: BTreeMap<SocketAddr, Option<&DirectAddrInfo>> = node_addr
+        .direct_addresses
+        .iter()
+        .map(|addr| (*addr, None))
+        .collect();
+    for addr in info.map_or(&[][..], |info| &info.addrs) {
+        candidates.insert(addr.addr, Some(addr));
+    }
+    let mut direct = false;
+    for (addr, seen) in &candidates {
+        let latency = seen.and_then(|seen| seen.latency);
+        let used = seen.is_some_and(|seen| seen.last_payload.is_some());
+        let step = if latency.is_some() || used {
+            direct = true;
+            let step = DialStep::new(DialStepKind::Direct, StepOutcome::Succeeded);
+            match latency {
+                Some(latency) => step.detail(format!("latency {}ms", latency.as_millis())),
+                None => step,
+            }
+        } else {
+            DialStep::new(DialStepKind::Direct, StepOutcome::Failed).detail("no response")
+        };
+        steps.push(step.target(addr.to_string()));
+    }
+
+    steps.push(if candidates.is_empty() {
+        DialStep::new(DialStepKind::HolePunch, StepOutcome::Skipped)
+            .detail("the peer has no known direct addresses")
+    } else if relay_alive.is_none() {
+        DialStep::new(DialStepKind::HolePunch, StepOutcome::Skipped)
+            .detail("no relay to coordinate through")
+    } else if direct {
+        DialStep::new(DialStepKind::HolePunch, StepOutcome::Succeeded)
+    } else {
+        DialStep::new(DialStepKind::HolePunch, StepOutcome::Failed)
+            .detail("no direct path through NAT")
+    });
+
+    steps
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -727,6 +974,130 @@ mod tests {
         assert!(config.relay_url.is_none());
         assert_eq!(config.connect_timeout, DEFAULT_CONNECT_TIMEOUT);
         assert_eq!(config.stream_timeout, DEFAULT_STREAM_TIMEOUT);
+        assert!(config.bind_addresses.is_empty());
+        assert!(config.port_range.is_none());
+        assert!(config.relay_enabled);
+    }
+
+    /// Returns a UDP port that was free a moment ago.
+    fn free_udp_port() -> u16 {
+        std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[tokio::test]
+    async fn test_listener_binds_pinned_address_and_port() {
+        let port = free_udp_port();
+        let config = QuicConfig::default()
+            .bind_addresses(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)])
+            .port_range(port..=port)
+            .without_relay();
+        let secret_key = SecretKey::generate(rand::rngs::OsRng);
+        let expected_node_id = secret_key.public();
+
+        let listener = QuicListener::bind(config, secret_key).await.unwrap();
+
+        assert_eq!(listener.node_id(), expected_node_id);
+        assert!(!listener.relay_enabled());
+        let addresses = listener.bound_addresses();
+        assert_eq!(addresses[0], SocketAddr::from((Ipv4Addr::LOCALHOST, port)));
+        assert!(addresses.iter().all(|addr| addr.ip().is_loopback()));
+        listener.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_listener_skips_ports_in_use() {
+        let taken = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let config = QuicConfig::default()
+            .bind_addresses(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)])
+            .port_range(port..=port)
+            .without_relay();
+
+        let result = QuicListener::bind(config, SecretKey::generate(rand::rngs::OsRng)).await;
+        assert!(result.is_err());
+    }
+
+    fn direct_addr(addr: SocketAddr, latency: Option<Duration>) -> DirectAddrInfo {
+        DirectAddrInfo {
+            addr,
+            latency,
+            last_control: None,
+            last_payload: None,
+            last_alive: None,
+            sources: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_dial_report_without_paths() {
+        let node_id = SecretKey::generate(rand::rngs::OsRng).public();
+        let report = dial_report(
+            DialRecorder::new(node_id.to_string()),
+            &NodeAddr::new(node_id),
+            None,
+            "connection timed out",
+        );
+
+        let outcomes: Vec<_> = report
+            .steps
+            .iter()
+            .map(|step| (step.kind, step.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (DialStepKind::RelayLookup, StepOutcome::Skipped),
+                (DialStepKind::HolePunch, StepOutcome::Skipped),
+                (DialStepKind::Handshake, StepOutcome::Failed),
+            ]
+        );
+        assert_eq!(report.error.as_deref(), Some("connection timed out"));
+    }
+
+    #[test]
+    fn test_dial_report_direct_candidates() {
+        let node_id = SecretKey::generate(rand::rngs::OsRng).public();
+        let given: SocketAddr = "192.0.2.1:4433".parse().unwrap();
+        let discovered: SocketAddr = "[2001:db8::1]:4433".parse().unwrap();
+        let relay_url: RelayUrl = "https://relay.example.com".parse().unwrap();
+        let node_addr = NodeAddr::from_parts(node_id, Some(relay_url), vec![given]);
+        let info = RemoteInfo {
+            node_id,
+            relay_url: None,
+            addrs: vec![
+                direct_addr(given, Some(Duration::from_millis(12))),
+                direct_addr(discovered, None),
+            ],
+            conn_type: Default::default(),
+            latency: None,
+            last_used: None,
+        };
+
+        let steps = path_steps(&node_addr, Some(&info));
+
+        assert_eq!(steps[0].kind, DialStepKind::RelayLookup);
+        assert_eq!(steps[0].outcome, StepOutcome::Failed);
+        assert_eq!(
+            steps[0].target.as_deref(),
+            Some("https://relay.example.com./")
+        );
+        let given_step = steps
+            .iter()
+            .find(|step| step.target.as_deref() == Some("192.0.2.1:4433"))
+            .unwrap();
+        assert_eq!(given_step.outcome, StepOutcome::Succeeded);
+        assert_eq!(given_step.detail.as_deref(), Some("latency 12ms"));
+        let discovered_step = steps
+            .iter()
+            .find(|step| step.target.as_deref() == Some("[2001:db8::1]:4433"))
+            .unwrap();
+        assert_eq!(discovered_step.outcome, StepOutcome::Failed);
+        assert_eq!(steps.last().unwrap().kind, DialStepKind::HolePunch);
+        assert_eq!(steps.last().unwrap().outcome, StepOutcome::Skipped);
     }
 
     #[tokio::test]
@@ -944,4 +1315,88 @@ mod tests {
         client.close().await.ok();
         server.close().await.ok();
     }
+
+    /// Connects a client handler to a server handler over localhost, with
+    /// their streams and readers running.
+    async fn connected_pair() -> (QuicConnectionHandler, QuicConnectionHandler) {
+        let server = QuicConnectionHandler::new_for_testing()
+            .await
+            .expect("failed to create server");
+        let client = QuicConnectionHandler::new_for_testing()
+            .await
+            .expect("failed to create client");
+
+        let bound_sockets = server.endpoint().bound_sockets();
+        let mut direct_addrs: Vec<std::net::SocketAddr> = vec![bound_sockets.0];
+        if let Some(addr) = bound_sockets.1 {
+            direct_addrs.push(addr);
+        }
+        let server_addr = NodeAddr::from_parts(server.node_id(), None, direct_addrs);
+
+        let server_handle = tokio::spawn(async move {
+            server.accept().await.expect("server accept failed");
+            server
+                .accept_streams()
+                .await
+                .expect("server accept streams failed");
+            server.spawn_stream_readers();
+            server
+        });
+
+        client
+            .connect(server_addr)
+            .await
+            .expect("client connect failed");
+        client
+            .create_streams()
+            .await
+            .expect("client create streams failed");
+        client.spawn_stream_readers();
+
+        let server = server_handle.await.expect("server task panicked");
+        (client, server)
+    }
+
+    /// Note: This test is ignored by default because it requires network connectivity.
+    /// Run with `--ignored` to include.
+    #[tokio::test]
+    #[ignore = "requires network connectivity, run with --ignored"]
+    async fn test_terminal_not_held_up_by_files() {
+        let (client, server) = connected_pair().await;
+        let client = Arc::new(client);
+
+        // Nothing receives the server's files channel, so the transfer soon
+        // waits on flow control
+        let transfer = {
+            let client = Arc::clone(&client);
+            tokio::spawn(async move {
+                let chunk = vec![0u8; 64 * 1024];
+                while client.send(ChannelType::Files, &chunk).await.is_ok() {}
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!transfer.is_finished());
+
+        for channel in [ChannelType::Terminal, ChannelType::Control] {
+            tokio::time::timeout(Duration::from_secs(5), client.send(channel, b"ls\n"))
+                .await
+                .expect("send held up by the file transfer")
+                .expect("send failed");
+            let received = tokio::time::timeout(Duration::from_secs(5), server.recv(channel))
+                .await
+                .expect("recv held up by the file transfer")
+                .expect("recv failed");
+            assert_eq!(received, b"ls\n");
+        }
+
+        transfer.abort();
+        client.close().await.ok();
+        server.close().await.ok();
+    }
+
+    #[test]
+    fn test_channel_priorities() {
+        assert!(ChannelType::Terminal.priority() > ChannelType::Control.priority());
+        assert!(ChannelType::Control.priority() > ChannelType::Files.priority());
+    }
 }
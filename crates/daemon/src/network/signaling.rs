@@ -12,6 +12,7 @@
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use super::proxy::ProxySettings;
 use crate::config::DEFAULT_SIGNALING_URL;
 
 use futures_util::{SinkExt, StreamExt};
@@ -19,7 +20,7 @@ use protocol::error::{ProtocolError, Result};
 use serde::{Deserialize, Serialize};
 use tokio::sync::{mpsc, RwLock};
 use tokio_tungstenite::{
-    connect_async,
+    client_async_tls,
     tungstenite::{Error as WsError, Message as WsMessage},
 };
 use url::Url;
@@ -257,6 +258,8 @@ pub struct SignalingConfig {
     pub heartbeat_interval: Duration,
     /// Timeout for heartbeat pong response.
     pub heartbeat_timeout: Duration,
+    /// Proxies for the WebSocket connection.
+    pub proxy: ProxySettings,
 }
 
 impl Default for SignalingConfig {
@@ -269,6 +272,7 @@ impl Default for SignalingConfig {
             auto_reconnect: true,
             heartbeat_interval: Duration::from_secs(30),
             heartbeat_timeout: Duration::from_secs(10),
+            proxy: ProxySettings::default(),
         }
     }
 }
@@ -282,6 +286,12 @@ impl SignalingConfig {
         }
     }
 
+    /// Connects through the given proxies.
+    pub fn with_proxy(mut self, proxy: ProxySettings) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
     /// Sets whether to automatically reconnect on disconnect.
     pub fn with_auto_reconnect(mut self, auto_reconnect: bool) -> Self {
         self.auto_reconnect = auto_reconnect;
@@ -640,10 +650,18 @@ impl WebSocketSignalingClient {
 
         tracing::info!("Connecting to signaling server: {}", ws_url);
 
-        let (ws_stream, _) = connect_async(&ws_url).await.map_err(|e| match e {
-            WsError::Io(io_err) => ProtocolError::from(io_err),
-            _ => ProtocolError::ConnectionClosed(format!("WebSocket connection failed: {}", e)),
-        })?;
+        // Dial the server ourselves, through the proxy if one applies, to
+        // race its IPv4 and IPv6 addresses
+        let url = Url::parse(&ws_url)
+            .map_err(|e| ProtocolError::HandshakeFailed(format!("invalid signaling URL: {}", e)))?;
+        let stream = self.config.proxy.connect(&url).await?;
+
+        let (ws_stream, _) = client_async_tls(&ws_url, stream)
+            .await
+            .map_err(|e| match e {
+                WsError::Io(io_err) => ProtocolError::from(io_err),
+                _ => ProtocolError::ConnectionClosed(format!("WebSocket connection failed: {}", e)),
+            })?;
 
         let (mut ws_sink, mut ws_stream) = ws_stream.split();
 
@@ -746,11 +764,14 @@ impl WebSocketSignalingClient {
     }
 
     /// Starts the connection in the background.
-    pub fn start(self: Arc<Self>) {
+    ///
+    /// The returned handle completes when the connection loop exits, which
+    /// happens after a disconnect or when reconnecting is disabled.
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
         let client = self.clone();
         tokio::spawn(async move {
             client.run_connection_loop().await;
-        });
+        })
     }
 }
 
@@ -1232,6 +1253,28 @@ mod tests {
         assert_eq!(url, "wss://example.com/room/abc123");
     }
 
+    #[tokio::test]
+    async fn test_connect_to_ipv6_literal() {
+        let Ok(listener) = tokio::net::TcpListener::bind("[::1]:0").await else {
+            // No IPv6 on this host
+            return;
+        };
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            let _ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            peer
+        });
+
+        let config = SignalingConfig::new(format!("ws://[::1]:{}", port));
+        let client = WebSocketSignalingClient::new(config);
+        client
+            .connect_internal("abc123")
+            .await
+            .expect("should connect over IPv6");
+        assert!(server.await.unwrap().is_ipv6());
+    }
+
     /// Integration test for the signaling flow.
     ///
     /// Note: This test requires a running signaling server to pass.
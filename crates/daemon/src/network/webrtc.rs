@@ -4,9 +4,18 @@
 //! - ICE server configuration (STUN/TURN)
 //! - Signaling integration (offer/answer)
 //! - Data channel creation (control, terminal, files)
-//! - Noise protocol encryption over data channels
-
-use std::collections::HashMap;
+//! - Noise protocol encryption over data channels, rekeyed periodically when
+//!   both peers announce support in their handshake payloads
+//!
+//! All three channels are reliable. The control and files channels are
+//! ordered; the terminal channel is not, so a delayed message does not hold
+//! up terminal output behind it. One Noise session encrypts the messages of
+//! every channel, and Noise requires them to be decrypted in the order they
+//! were encrypted, so after the handshake each message is prefixed with its
+//! number in that order and the receiver holds back messages that arrive
+//! early.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -14,12 +23,16 @@ use std::time::Duration;
 
 use protocol::crypto::DeviceIdentity;
 use protocol::error::{ProtocolError, Result};
-use protocol::noise::NoiseSession;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use protocol::messages::{Capabilities, Envelope, Message};
+use protocol::noise::{NoiseSession, RekeyPolicy, REKEY_FEATURE};
+use tokio::sync::{mpsc, Mutex, Notify, RwLock};
+use tracing::Instrument;
 use webrtc::api::interceptor_registry::register_default_interceptors;
 use webrtc::api::media_engine::MediaEngine;
 use webrtc::api::APIBuilder;
+use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
 use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
 use webrtc::data_channel::RTCDataChannel;
 use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::interceptor::registry::Registry;
@@ -29,6 +42,7 @@ use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
 
 use super::{ChannelType, Connection};
+use crate::telemetry;
 
 /// Default STUN servers for ICE connectivity.
 pub const DEFAULT_STUN_SERVERS: &[&str] = &[
@@ -40,6 +54,36 @@ pub const DEFAULT_STUN_SERVERS: &[&str] = &[
 /// Each recv operation during handshake will timeout after this duration.
 const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// Buffer size of the incoming message queue.
+const CHANNEL_BUFFER_SIZE: usize = 256;
+
+/// Most messages held back while waiting for an earlier one.
+const MAX_EARLY_MESSAGES: usize = 1024;
+
+/// Size of the number prefixed to each encrypted message.
+const SEQUENCE_LEN: usize = 8;
+
+/// A message and the channel it was received on.
+type Frame = (ChannelType, Vec<u8>);
+
+/// All channel types, in the order they are created.
+const CHANNEL_TYPES: [ChannelType; 3] = [
+    ChannelType::Control,
+    ChannelType::Terminal,
+    ChannelType::Files,
+];
+
+/// Returns the data channel settings of a channel type.
+///
+/// Every channel is reliable, as a lost message would stall the ones
+/// numbered after it. Only the terminal channel is unordered.
+fn data_channel_init(channel_type: ChannelType) -> RTCDataChannelInit {
+    RTCDataChannelInit {
+        ordered: Some(channel_type != ChannelType::Terminal),
+        ..Default::default()
+    }
+}
+
 /// ICE server configuration.
 #[derive(Debug, Clone)]
 pub struct IceServer {
@@ -98,6 +142,8 @@ pub struct WebRtcConfig {
     pub ice_servers: Vec<IceServer>,
     /// Timeout for ICE candidate gathering.
     pub ice_gathering_timeout: Duration,
+    /// When to rekey the Noise transport, if the peer supports it.
+    pub rekey: RekeyPolicy,
 }
 
 impl Default for WebRtcConfig {
@@ -108,6 +154,7 @@ impl Default for WebRtcConfig {
                 .map(|&url| IceServer::stun(url))
                 .collect(),
             ice_gathering_timeout: Duration::from_secs(30),
+            rekey: RekeyPolicy::default(),
         }
     }
 }
@@ -118,6 +165,7 @@ impl WebRtcConfig {
         Self {
             ice_servers,
             ice_gathering_timeout: Duration::from_secs(30),
+            rekey: RekeyPolicy::default(),
         }
     }
 
@@ -160,6 +208,92 @@ impl DataChannels {
             ChannelType::Files => self.files = Some(channel),
         }
     }
+
+    fn all_open(&self) -> bool {
+        CHANNEL_TYPES.iter().all(|&channel_type| {
+            self.get(channel_type)
+                .is_some_and(|channel| channel.ready_state() == RTCDataChannelState::Open)
+        })
+    }
+}
+
+/// Noise transport shared by the data channels.
+///
+/// Messages are numbered in the order they are encrypted. The channels are
+/// independent, so messages can arrive in a different order; the receiver
+/// keeps those that arrive early until the ones before them are decrypted.
+struct Transport {
+    noise: NoiseSession,
+    /// Number of the next message sent.
+    send_next: u64,
+    /// Number of the next message to decrypt.
+    recv_next: u64,
+    /// Messages that arrived before their turn, by number.
+    early: BTreeMap<u64, Frame>,
+    /// Decrypted messages not yet received, by channel.
+    ready: HashMap<ChannelType, VecDeque<Vec<u8>>>,
+}
+
+impl Transport {
+    fn new(noise: NoiseSession) -> Self {
+        Self {
+            noise,
+            send_next: 0,
+            recv_next: 0,
+            early: BTreeMap::new(),
+            ready: HashMap::new(),
+        }
+    }
+
+    /// Encrypts `data` and prefixes it with its number.
+    fn seal(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let ciphertext = self.noise.encrypt(data)?;
+        Ok(self.number(&ciphertext))
+    }
+
+    /// Prefixes an encrypted message with its number.
+    fn number(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        let mut message = Vec::with_capacity(SEQUENCE_LEN + ciphertext.len());
+        message.extend_from_slice(&self.send_next.to_be_bytes());
+        message.extend_from_slice(ciphertext);
+        self.send_next += 1;
+        message
+    }
+
+    /// Accepts a message received on `channel_type`, decrypting it and any
+    /// held back messages that follow it once it is its turn.
+    fn open(&mut self, channel_type: ChannelType, message: &[u8]) -> Result<()> {
+        if message.len() < SEQUENCE_LEN {
+            return Err(ProtocolError::Decryption("message too short".into()));
+        }
+        let (number, ciphertext) = message.split_at(SEQUENCE_LEN);
+        let number = u64::from_be_bytes(number.try_into().unwrap_or_default());
+
+        if number < self.recv_next || self.early.contains_key(&number) {
+            tracing::warn!(number, "dropping duplicate message");
+            return Ok(());
+        }
+        if self.early.len() >= MAX_EARLY_MESSAGES {
+            return Err(ProtocolError::Decryption(
+                "too many messages received out of order".into(),
+            ));
+        }
+        self.early
+            .insert(number, (channel_type, ciphertext.to_vec()));
+
+        while let Some((channel_type, ciphertext)) = self.early.remove(&self.recv_next) {
+            self.recv_next += 1;
+            match self.noise.read_transport(&ciphertext)? {
+                Some(plaintext) => self
+                    .ready
+                    .entry(channel_type)
+                    .or_default()
+                    .push_back(plaintext),
+                None => tracing::debug!("Peer rekeyed Noise transport"),
+            }
+        }
+        Ok(())
+    }
 }
 
 /// WebRTC connection handler for browser clients.
@@ -168,19 +302,27 @@ impl DataChannels {
 /// - ICE negotiation for NAT traversal
 /// - Multiple data channels for different message types
 /// - Noise protocol encryption for secure communication
+///
+/// Clones share the same connection.
+#[derive(Clone)]
 pub struct WebRtcConnectionHandler {
     /// The WebRTC peer connection.
     peer_connection: Arc<RTCPeerConnection>,
     /// Data channels for different message types.
     data_channels: Arc<RwLock<DataChannels>>,
-    /// Noise session for encryption/decryption.
-    noise_session: Arc<Mutex<Option<NoiseSession>>>,
+    /// Signaled when a data channel opens.
+    channel_opened: Arc<Notify>,
+    /// Noise transport, once the handshake is complete.
+    transport: Arc<std::sync::Mutex<Option<Transport>>>,
     /// Device identity for Noise handshake.
     identity: DeviceIdentity,
-    /// Receiver for incoming messages from data channels.
-    message_rx: Arc<Mutex<HashMap<ChannelType, mpsc::Receiver<Vec<u8>>>>>,
-    /// Senders for incoming messages (used by data channel callbacks).
-    message_tx: Arc<RwLock<HashMap<ChannelType, mpsc::Sender<Vec<u8>>>>>,
+    /// Messages received on other channels while the handshake waited for
+    /// one on the control channel.
+    held: Arc<std::sync::Mutex<Vec<Frame>>>,
+    /// Receiver for incoming messages from all data channels.
+    message_rx: Arc<Mutex<mpsc::Receiver<Frame>>>,
+    /// Sender for incoming messages (used by data channel callbacks).
+    message_tx: mpsc::Sender<Frame>,
     /// Whether the connection is established.
     connected: Arc<RwLock<bool>>,
     /// Remote peer's X25519 public key (from Noise handshake).
@@ -194,6 +336,10 @@ impl WebRtcConnectionHandler {
     ///
     /// This sets up the WebRTC API and creates a peer connection with the given configuration.
     pub async fn new(config: WebRtcConfig, identity: DeviceIdentity) -> Result<Self> {
+        // DTLS needs a process-wide crypto provider, and more than one is
+        // compiled in, so rustls cannot pick one itself
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
         // Create a MediaEngine (required even for data-only connections)
         let mut media_engine = MediaEngine::default();
         media_engine.register_default_codecs().map_err(|e| {
@@ -222,27 +368,19 @@ impl WebRtcConnectionHandler {
 
         let peer_connection = Arc::new(peer_connection);
 
-        // Create message channels for each channel type
-        let mut message_rx = HashMap::new();
-        let mut message_tx = HashMap::new();
-
-        for channel_type in [
-            ChannelType::Control,
-            ChannelType::Terminal,
-            ChannelType::Files,
-        ] {
-            let (tx, rx) = mpsc::channel(256);
-            message_tx.insert(channel_type, tx);
-            message_rx.insert(channel_type, rx);
-        }
+        // Messages from every channel share one queue, so the transport can
+        // put them back in order
+        let (message_tx, message_rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
 
         let handler = Self {
             peer_connection,
             data_channels: Arc::new(RwLock::new(DataChannels::default())),
-            noise_session: Arc::new(Mutex::new(None)),
+            channel_opened: Arc::new(Notify::new()),
+            transport: Arc::new(std::sync::Mutex::new(None)),
+            held: Arc::new(std::sync::Mutex::new(Vec::new())),
             identity,
             message_rx: Arc::new(Mutex::new(message_rx)),
-            message_tx: Arc::new(RwLock::new(message_tx)),
+            message_tx,
             connected: Arc::new(RwLock::new(false)),
             peer_public_key: Arc::new(RwLock::new(None)),
             config,
@@ -268,133 +406,126 @@ impl WebRtcConnectionHandler {
     ///
     /// This should be called before creating an offer.
     pub async fn create_data_channels(&self) -> Result<()> {
-        // Control channel: ordered, reliable
-        let control_options = webrtc::data_channel::data_channel_init::RTCDataChannelInit {
-            ordered: Some(true),
-            ..Default::default()
-        };
-        let control = self
-            .peer_connection
-            .create_data_channel(ChannelType::Control.channel_name(), Some(control_options))
-            .await
-            .map_err(|e| {
-                ProtocolError::HandshakeFailed(format!("failed to create control channel: {}", e))
-            })?;
-        self.setup_data_channel(ChannelType::Control, control)
-            .await?;
-
-        // Terminal channel: unordered for low latency
-        let terminal_options = webrtc::data_channel::data_channel_init::RTCDataChannelInit {
-            ordered: Some(false),
-            ..Default::default()
-        };
-        let terminal = self
-            .peer_connection
-            .create_data_channel(ChannelType::Terminal.channel_name(), Some(terminal_options))
-            .await
-            .map_err(|e| {
-                ProtocolError::HandshakeFailed(format!("failed to create terminal channel: {}", e))
-            })?;
-        self.setup_data_channel(ChannelType::Terminal, terminal)
-            .await?;
-
-        // Files channel: ordered, reliable
-        let files_options = webrtc::data_channel::data_channel_init::RTCDataChannelInit {
-            ordered: Some(true),
-            ..Default::default()
-        };
-        let files = self
-            .peer_connection
-            .create_data_channel(ChannelType::Files.channel_name(), Some(files_options))
-            .await
-            .map_err(|e| {
-                ProtocolError::HandshakeFailed(format!("failed to create files channel: {}", e))
-            })?;
-        self.setup_data_channel(ChannelType::Files, files).await?;
+        for channel_type in CHANNEL_TYPES {
+            let channel = self
+                .peer_connection
+                .create_data_channel(
+                    channel_type.channel_name(),
+                    Some(data_channel_init(channel_type)),
+                )
+                .await
+                .map_err(|e| {
+                    ProtocolError::HandshakeFailed(format!(
+                        "failed to create {} channel: {}",
+                        channel_type.channel_name(),
+                        e
+                    ))
+                })?;
+            Self::setup_data_channel(
+                &self.data_channels,
+                &self.message_tx,
+                &self.channel_opened,
+                channel_type,
+                channel,
+            )
+            .await;
+        }
 
         Ok(())
     }
 
     /// Sets up handlers for incoming data channels (as answerer).
+    ///
+    /// Channels with unknown labels, and unreliable channels, are ignored.
     pub async fn setup_incoming_data_channels(&self) {
         let data_channels = self.data_channels.clone();
         let message_tx = self.message_tx.clone();
+        let channel_opened = self.channel_opened.clone();
 
         self.peer_connection
             .on_data_channel(Box::new(move |channel: Arc<RTCDataChannel>| {
                 let data_channels = data_channels.clone();
                 let message_tx = message_tx.clone();
+                let channel_opened = channel_opened.clone();
 
                 Box::pin(async move {
                     let label = channel.label().to_string();
-                    let channel_type = match label.as_str() {
-                        "control" => ChannelType::Control,
-                        "terminal" => ChannelType::Terminal,
-                        "files" => ChannelType::Files,
-                        _ => {
-                            tracing::warn!("unknown data channel: {}", label);
-                            return;
-                        }
+                    let Some(channel_type) = ChannelType::from_channel_name(&label) else {
+                        tracing::warn!("unknown data channel: {}", label);
+                        return;
                     };
-
-                    // Store the channel
+                    if channel.max_retransmits().is_some()
+                        || channel.max_packet_lifetime().is_some()
                     {
-                        let mut channels = data_channels.write().await;
-                        channels.set(channel_type, channel.clone());
-                    }
-
-                    // Set up message handler
-                    let tx = {
-                        let senders = message_tx.read().await;
-                        senders.get(&channel_type).cloned()
-                    };
-
-                    if let Some(tx) = tx {
-                        channel.on_message(Box::new(move |msg: DataChannelMessage| {
-                            let tx = tx.clone();
-                            Box::pin(async move {
-                                if let Err(e) = tx.send(msg.data.to_vec()).await {
-                                    tracing::error!("failed to forward message: {}", e);
-                                }
-                            })
-                        }));
+                        tracing::warn!("ignoring unreliable data channel: {}", label);
+                        return;
                     }
 
+                    Self::setup_data_channel(
+                        &data_channels,
+                        &message_tx,
+                        &channel_opened,
+                        channel_type,
+                        channel,
+                    )
+                    .await;
                     tracing::debug!("data channel '{}' established", label);
                 })
             }));
     }
 
-    /// Sets up a data channel with message handling.
+    /// Stores a data channel and forwards its messages to the incoming
+    /// queue.
     async fn setup_data_channel(
-        &self,
+        data_channels: &RwLock<DataChannels>,
+        message_tx: &mpsc::Sender<Frame>,
+        channel_opened: &Arc<Notify>,
         channel_type: ChannelType,
         channel: Arc<RTCDataChannel>,
-    ) -> Result<()> {
+    ) {
         // Store the channel
         {
-            let mut channels = self.data_channels.write().await;
+            let mut channels = data_channels.write().await;
             channels.set(channel_type, channel.clone());
         }
 
-        // Set up message handler
-        let tx = {
-            let senders = self.message_tx.read().await;
-            senders.get(&channel_type).cloned()
-        };
+        let opened = channel_opened.clone();
+        channel.on_open(Box::new(move || {
+            opened.notify_one();
+            Box::pin(async {})
+        }));
 
-        if let Some(tx) = tx {
-            channel.on_message(Box::new(move |msg: DataChannelMessage| {
-                let tx = tx.clone();
-                Box::pin(async move {
-                    if let Err(e) = tx.send(msg.data.to_vec()).await {
+        // Set up message handler
+        let tx = message_tx.clone();
+        let span = telemetry::channel_span(channel_type);
+        channel.on_message(Box::new(move |msg: DataChannelMessage| {
+            let tx = tx.clone();
+            Box::pin(
+                async move {
+                    if let Err(e) = tx.send((channel_type, msg.data.to_vec())).await {
                         tracing::error!("failed to forward message: {}", e);
                     }
-                })
-            }));
-        }
+                }
+                .instrument(span.clone()),
+            )
+        }));
+    }
 
-        Ok(())
+    /// Waits until all three data channels are open.
+    ///
+    /// The answerer should call this before the Noise handshake, since the
+    /// channels only exist once the offerer's are announced.
+    pub async fn wait_for_data_channels(&self) -> Result<()> {
+        tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+            loop {
+                if self.data_channels.read().await.all_open() {
+                    return;
+                }
+                self.channel_opened.notified().await;
+            }
+        })
+        .await
+        .map_err(|_| ProtocolError::Timeout("data channels did not open".into()))
     }
 
     /// Creates an SDP offer for signaling.
@@ -549,12 +680,16 @@ impl WebRtcConnectionHandler {
                 tracing::error!("Failed to receive handshake message 2: {}", e);
                 ProtocolError::HandshakeFailed(e.to_string())
             })?;
-        noise.read_handshake_message(&msg2)?;
+        let peer_payload = noise.read_handshake_message(&msg2)?;
 
         // Message 3: Send -> s, se
-        let msg3 = noise.write_handshake_message(&[])?;
+        let msg3 = noise.write_handshake_message(&handshake_payload())?;
         self.send_raw(ChannelType::Control, &msg3).await?;
 
+        if supports_rekey(&peer_payload) {
+            noise.enable_rekey(self.config.rekey);
+        }
+
         // Store the peer's public key
         if let Some(key) = noise.get_remote_static() {
             let mut peer_key = self.peer_public_key.write().await;
@@ -563,10 +698,7 @@ impl WebRtcConnectionHandler {
 
         // Transition to transport mode
         noise.into_transport()?;
-
-        // Store the noise session
-        let mut session = self.noise_session.lock().await;
-        *session = Some(noise);
+        self.install_transport(noise)?;
 
         Ok(())
     }
@@ -591,7 +723,7 @@ impl WebRtcConnectionHandler {
         noise.read_handshake_message(&msg1)?;
 
         // Message 2: Send <- e, ee, s, es
-        let msg2 = noise.write_handshake_message(&[])?;
+        let msg2 = noise.write_handshake_message(&handshake_payload())?;
         self.send_raw(ChannelType::Control, &msg2).await?;
 
         // Message 3: Receive -> s, se
@@ -605,7 +737,10 @@ impl WebRtcConnectionHandler {
                 tracing::error!("Failed to receive handshake message 3: {}", e);
                 ProtocolError::HandshakeFailed(e.to_string())
             })?;
-        noise.read_handshake_message(&msg3)?;
+        let peer_payload = noise.read_handshake_message(&msg3)?;
+        if supports_rekey(&peer_payload) {
+            noise.enable_rekey(self.config.rekey);
+        }
 
         // Store the peer's public key
         if let Some(key) = noise.get_remote_static() {
@@ -615,14 +750,27 @@ impl WebRtcConnectionHandler {
 
         // Transition to transport mode
         noise.into_transport()?;
+        self.install_transport(noise)?;
 
-        // Store the noise session
-        let mut session = self.noise_session.lock().await;
-        *session = Some(noise);
+        Ok(())
+    }
 
+    /// Stores the Noise session once the handshake is complete, and accepts
+    /// the messages that arrived before it.
+    fn install_transport(&self, noise: NoiseSession) -> Result<()> {
+        let mut transport = Transport::new(noise);
+        let held = std::mem::take(&mut *self.held.lock().unwrap_or_else(|e| e.into_inner()));
+        for (channel_type, message) in held {
+            transport.open(channel_type, &message)?;
+        }
+        *self.lock_transport() = Some(transport);
         Ok(())
     }
 
+    fn lock_transport(&self) -> std::sync::MutexGuard<'_, Option<Transport>> {
+        self.transport.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Sends raw (unencrypted) data over a data channel.
     ///
     /// Used during handshake before encryption is established.
@@ -642,37 +790,96 @@ impl WebRtcConnectionHandler {
 
     /// Receives raw (unencrypted) data from a data channel.
     ///
-    /// Used during handshake before encryption is established.
+    /// Used during handshake before encryption is established. Messages on
+    /// other channels can only be encrypted ones sent by a peer that already
+    /// finished its side of the handshake; they are held until this side
+    /// finishes too.
     async fn recv_raw(&self, channel_type: ChannelType) -> Result<Vec<u8>> {
-        let mut receivers = self.message_rx.lock().await;
-        let rx = receivers.get_mut(&channel_type).ok_or_else(|| {
-            ProtocolError::ConnectionClosed(format!("{:?} channel not available", channel_type))
-        })?;
-
-        rx.recv()
-            .await
-            .ok_or_else(|| ProtocolError::ConnectionClosed("channel closed".into()))
+        let mut rx = self.message_rx.lock().await;
+        loop {
+            let (received_on, data) = rx
+                .recv()
+                .await
+                .ok_or_else(|| ProtocolError::ConnectionClosed("channel closed".into()))?;
+            if received_on == channel_type {
+                return Ok(data);
+            }
+            let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+            if held.len() >= MAX_EARLY_MESSAGES {
+                return Err(ProtocolError::HandshakeFailed(
+                    "too many messages received during handshake".into(),
+                ));
+            }
+            held.push((received_on, data));
+        }
     }
 
     /// Sends encrypted data over a data channel.
+    ///
+    /// If the sending key is due for rotation, a rekey marker is sent on the
+    /// control channel first.
     async fn send_encrypted(&self, channel_type: ChannelType, data: &[u8]) -> Result<()> {
-        let mut session = self.noise_session.lock().await;
-        let noise = session.as_mut().ok_or(ProtocolError::HandshakeIncomplete)?;
-
-        let ciphertext = noise.encrypt(data)?;
-        drop(session);
+        let (marker, message) = {
+            let mut transport = self.lock_transport();
+            let transport = transport
+                .as_mut()
+                .ok_or(ProtocolError::HandshakeIncomplete)?;
+
+            let marker = if transport.noise.rekey_due() {
+                let marker = transport.noise.write_rekey()?;
+                tracing::debug!("Rekeyed Noise transport");
+                Some(transport.number(&marker))
+            } else {
+                None
+            };
+            (marker, transport.seal(data)?)
+        };
 
-        self.send_raw(channel_type, &ciphertext).await
+        if let Some(marker) = marker {
+            self.send_raw(ChannelType::Control, &marker).await?;
+        }
+        self.send_raw(channel_type, &message).await
     }
 
-    /// Receives and decrypts data from a data channel.
+    /// Receives and decrypts data from a data channel, following the peer's
+    /// rekey markers.
+    ///
+    /// Messages for other channels that arrive meanwhile are kept for them.
     async fn recv_encrypted(&self, channel_type: ChannelType) -> Result<Vec<u8>> {
-        let ciphertext = self.recv_raw(channel_type).await?;
-
-        let mut session = self.noise_session.lock().await;
-        let noise = session.as_mut().ok_or(ProtocolError::HandshakeIncomplete)?;
+        loop {
+            if let Some(plaintext) = self.take_ready(channel_type)? {
+                return Ok(plaintext);
+            }
+
+            let mut rx = self.message_rx.lock().await;
+            // Another receiver may have decrypted one while this one waited
+            if let Some(plaintext) = self.take_ready(channel_type)? {
+                return Ok(plaintext);
+            }
+            let (received_on, message) = rx
+                .recv()
+                .await
+                .ok_or_else(|| ProtocolError::ConnectionClosed("channel closed".into()))?;
+            drop(rx);
+
+            let mut transport = self.lock_transport();
+            transport
+                .as_mut()
+                .ok_or(ProtocolError::HandshakeIncomplete)?
+                .open(received_on, &message)?;
+        }
+    }
 
-        noise.decrypt(&ciphertext)
+    /// Takes the next decrypted message of a channel, if any.
+    fn take_ready(&self, channel_type: ChannelType) -> Result<Option<Vec<u8>>> {
+        let mut transport = self.lock_transport();
+        let transport = transport
+            .as_mut()
+            .ok_or(ProtocolError::HandshakeIncomplete)?;
+        Ok(transport
+            .ready
+            .get_mut(&channel_type)
+            .and_then(VecDeque::pop_front))
     }
 
     /// Returns the underlying peer connection for advanced operations.
@@ -681,6 +888,29 @@ impl WebRtcConnectionHandler {
     }
 }
 
+/// Returns the payload sent in our Noise handshake message: an envelope
+/// announcing our capabilities.
+fn handshake_payload() -> Vec<u8> {
+    let mut capabilities = Capabilities::default();
+    capabilities.features.push(REKEY_FEATURE.to_string());
+    Envelope::new(0, Message::Capabilities(capabilities))
+        .to_msgpack()
+        .unwrap_or_default()
+}
+
+/// Returns whether a peer's handshake payload announces rekey support.
+///
+/// Older peers send an empty payload and never rekey.
+fn supports_rekey(payload: &[u8]) -> bool {
+    match Envelope::from_msgpack(payload) {
+        Ok(Envelope {
+            payload: Message::Capabilities(capabilities),
+            ..
+        }) => capabilities.features.iter().any(|f| f == REKEY_FEATURE),
+        _ => false,
+    }
+}
+
 impl Connection for WebRtcConnectionHandler {
     fn send<'a>(
         &'a mut self,
@@ -784,6 +1014,18 @@ mod tests {
         assert!(offer.is_ok());
     }
 
+    #[test]
+    fn test_rekey_negotiation() {
+        assert!(supports_rekey(&handshake_payload()));
+        // Older peers send an empty handshake payload
+        assert!(!supports_rekey(&[]));
+
+        let without = Envelope::new(0, Message::Capabilities(Capabilities::default()))
+            .to_msgpack()
+            .unwrap();
+        assert!(!supports_rekey(&without));
+    }
+
     #[tokio::test]
     async fn test_channel_type_names() {
         assert_eq!(ChannelType::Control.channel_name(), "control");
@@ -791,57 +1033,150 @@ mod tests {
         assert_eq!(ChannelType::Files.channel_name(), "files");
     }
 
+    /// Completes a Noise handshake between two sessions.
+    fn noise_pair() -> (NoiseSession, NoiseSession) {
+        let mut initiator = NoiseSession::new_initiator(&DeviceIdentity::generate()).unwrap();
+        let mut responder = NoiseSession::new_responder(&DeviceIdentity::generate()).unwrap();
+        let msg1 = initiator.write_handshake_message(&[]).unwrap();
+        responder.read_handshake_message(&msg1).unwrap();
+        let msg2 = responder.write_handshake_message(&[]).unwrap();
+        initiator.read_handshake_message(&msg2).unwrap();
+        let msg3 = initiator.write_handshake_message(&[]).unwrap();
+        responder.read_handshake_message(&msg3).unwrap();
+        initiator.into_transport().unwrap();
+        responder.into_transport().unwrap();
+        (initiator, responder)
+    }
+
+    #[test]
+    fn test_transport_reorders_messages() {
+        let (initiator, responder) = noise_pair();
+        let mut sender = Transport::new(initiator);
+        let mut receiver = Transport::new(responder);
+
+        let first = sender.seal(b"first").unwrap();
+        let second = sender.seal(b"second").unwrap();
+        let third = sender.seal(b"third").unwrap();
+
+        // The later messages arrive first and wait for the earlier one
+        receiver.open(ChannelType::Files, &third).unwrap();
+        receiver.open(ChannelType::Terminal, &second).unwrap();
+        assert!(receiver.ready.is_empty());
+        assert_eq!(receiver.early.len(), 2);
+
+        receiver.open(ChannelType::Terminal, &first).unwrap();
+        assert!(receiver.early.is_empty());
+        assert_eq!(
+            receiver.ready[&ChannelType::Terminal],
+            VecDeque::from([b"first".to_vec(), b"second".to_vec()])
+        );
+        assert_eq!(
+            receiver.ready[&ChannelType::Files],
+            VecDeque::from([b"third".to_vec()])
+        );
+
+        // Duplicates are dropped without breaking the session
+        receiver.open(ChannelType::Terminal, &first).unwrap();
+        let fourth = sender.seal(b"fourth").unwrap();
+        receiver.open(ChannelType::Control, &fourth).unwrap();
+        assert_eq!(
+            receiver.ready[&ChannelType::Control],
+            VecDeque::from([b"fourth".to_vec()])
+        );
+
+        assert!(receiver.open(ChannelType::Control, &[0; 4]).is_err());
+    }
+
+    /// Creates a handler that only gathers host candidates.
+    async fn local_handler() -> WebRtcConnectionHandler {
+        WebRtcConnectionHandler::new(
+            WebRtcConfig::with_ice_servers(vec![]),
+            DeviceIdentity::generate(),
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Sets the local description and waits for its candidates.
+    async fn gathered_description(
+        handler: &WebRtcConnectionHandler,
+        offer: bool,
+    ) -> RTCSessionDescription {
+        let mut gathered = handler.peer_connection().gathering_complete_promise().await;
+        if offer {
+            handler.create_offer().await.unwrap();
+        } else {
+            handler.create_answer().await.unwrap();
+        }
+        let _ = gathered.recv().await;
+        handler.peer_connection().local_description().await.unwrap()
+    }
+
     /// Integration test for full WebRTC connection with Noise handshake.
     ///
-    /// This test creates two peers, establishes a WebRTC connection between them,
-    /// performs a Noise handshake, and exchanges encrypted messages.
+    /// This test creates two peers, establishes a WebRTC connection between
+    /// them over the local host, performs a Noise handshake, and exchanges
+    /// encrypted messages on every channel.
     #[tokio::test]
     async fn test_full_connection_establishment() {
-        // Create two identities
-        let offerer_identity = DeviceIdentity::generate();
-        let answerer_identity = DeviceIdentity::generate();
+        let mut offerer = local_handler().await;
+        let mut answerer = local_handler().await;
 
-        // Create handlers
-        let offerer = WebRtcConnectionHandler::new(WebRtcConfig::default(), offerer_identity)
-            .await
-            .expect("failed to create offerer");
+        offerer.create_data_channels().await.unwrap();
+        answerer.setup_incoming_data_channels().await;
 
-        let answerer = WebRtcConnectionHandler::new(WebRtcConfig::default(), answerer_identity)
-            .await
-            .expect("failed to create answerer");
+        let offer = gathered_description(&offerer, true).await;
+        answerer.set_remote_description(offer).await.unwrap();
+        let answer = gathered_description(&answerer, false).await;
+        offerer.set_remote_description(answer).await.unwrap();
 
-        // Offerer creates data channels and offer
-        offerer
-            .create_data_channels()
-            .await
-            .expect("failed to create data channels");
-        let offer = offerer
-            .create_offer()
-            .await
-            .expect("failed to create offer");
+        tokio::try_join!(
+            offerer.wait_for_data_channels(),
+            answerer.wait_for_data_channels()
+        )
+        .unwrap();
 
-        // Set up answerer to receive data channels
-        answerer.setup_incoming_data_channels().await;
+        // The answerer's channels carry the settings the offerer chose
+        {
+            let channels = answerer.data_channels.read().await;
+            for channel_type in CHANNEL_TYPES {
+                let channel = channels.get(channel_type).unwrap();
+                assert_eq!(channel.ordered(), channel_type != ChannelType::Terminal);
+                assert!(channel.max_retransmits().is_none());
+            }
+        }
 
-        // Answerer processes offer and creates answer
-        answerer
-            .set_remote_description(offer)
-            .await
-            .expect("failed to set remote description");
-        let answer = answerer
-            .create_answer()
-            .await
-            .expect("failed to create answer");
+        tokio::try_join!(
+            offerer.perform_noise_handshake_initiator(),
+            answerer.perform_noise_handshake_responder()
+        )
+        .unwrap();
+        assert!(offerer.is_connected());
+        assert!(answerer.peer_public_key().is_some());
+
+        for round in 0..20u8 {
+            for channel_type in CHANNEL_TYPES {
+                offerer.send(channel_type, &[round]).await.unwrap();
+            }
+        }
+        // Received per channel in the order sent, whatever the interleaving
+        for channel_type in [
+            ChannelType::Files,
+            ChannelType::Terminal,
+            ChannelType::Control,
+        ] {
+            for round in 0..20u8 {
+                assert_eq!(answerer.recv(channel_type).await.unwrap(), vec![round]);
+            }
+        }
 
-        // Offerer processes answer
-        offerer
-            .set_remote_description(answer)
-            .await
-            .expect("failed to set remote description");
+        for channel_type in CHANNEL_TYPES {
+            let reply = channel_type.channel_name().as_bytes();
+            answerer.send(channel_type, reply).await.unwrap();
+            assert_eq!(offerer.recv(channel_type).await.unwrap(), reply);
+        }
 
-        // Note: In a real test, we would wait for ICE to complete and then
-        // perform the Noise handshake. However, without a signaling server
-        // to exchange ICE candidates, we can't fully test the connection.
-        // The above tests verify the API works correctly.
+        offerer.close().await.unwrap();
+        answerer.close().await.unwrap();
     }
 }
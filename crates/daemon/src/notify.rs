@@ -0,0 +1,141 @@
+//! Notifications pushed from the daemon to connected clients.
+//!
+//! Components that want to tell the user something outside of a
+//! request/response exchange, such as an impending shutdown or an expired
+//! approval, send a [`Notification`] through a [`Notifier`]. The orchestrator
+//! relays queued notifications to the connected devices on the Control
+//! channel; notifications for devices that are not connected are dropped.
+//!
+//! Bells and desktop notifications raised by programs in a session are sent
+//! to every connected device as [`NotificationCategory::Terminal`]
+//! notifications, see [`alert_notification`].
+
+use protocol::messages::{Notification, NotificationCategory, NotificationLevel};
+use protocol::DeviceId;
+use tokio::sync::mpsc;
+
+use crate::session::{SessionAlert, TerminalAlert};
+use crate::util::i18n::tr;
+
+/// Who a notification is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recipient {
+    /// A single device.
+    Device(DeviceId),
+    /// Every connected device.
+    All,
+}
+
+/// A notification waiting to be relayed.
+pub type OutboundNotification = (Recipient, Notification);
+
+/// Queues notifications for connected clients.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    outbound: mpsc::UnboundedSender<OutboundNotification>,
+}
+
+impl Notifier {
+    /// Creates a notifier and the receiver its notifications are relayed from.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<OutboundNotification>) {
+        let (outbound, rx) = mpsc::unbounded_channel();
+        (Self { outbound }, rx)
+    }
+
+    /// Queues a notification for one device.
+    pub fn send(&self, device_id: DeviceId, notification: Notification) {
+        self.queue(Recipient::Device(device_id), notification);
+    }
+
+    /// Queues a notification for every connected device.
+    pub fn broadcast(&self, notification: Notification) {
+        self.queue(Recipient::All, notification);
+    }
+
+    fn queue(&self, recipient: Recipient, notification: Notification) {
+        // The receiver only goes away when the daemon shuts down
+        let _ = self.outbound.send((recipient, notification));
+    }
+}
+
+/// Notice sent to connected devices for a bell or desktop notification
+/// raised in a session.
+pub fn alert_notification((session_id, alert): SessionAlert) -> Notification {
+    let (title, body) = match alert {
+        TerminalAlert::Bell => (
+            tr("notify.bell.title").to_string(),
+            tr("notify.bell.body").to_string(),
+        ),
+        TerminalAlert::Notify { title, body } => (
+            title.unwrap_or_else(|| tr("notify.terminal.title").to_string()),
+            body,
+        ),
+    };
+    Notification::new(
+        NotificationLevel::Info,
+        NotificationCategory::Terminal,
+        title,
+        body,
+    )
+    .with_session(session_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notifier_queues_in_order() {
+        let (notifier, mut rx) = Notifier::channel();
+        let device_id = DeviceId::from_bytes([7u8; 16]);
+        let notice = Notification::new(
+            NotificationLevel::Info,
+            NotificationCategory::General,
+            "Hello",
+            "World",
+        );
+
+        notifier.send(device_id, notice.clone());
+        notifier.broadcast(notice.clone());
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            (Recipient::Device(device_id), notice.clone())
+        );
+        assert_eq!(rx.try_recv().unwrap(), (Recipient::All, notice));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_notifier_without_receiver() {
+        let (notifier, rx) = Notifier::channel();
+        drop(rx);
+
+        // Must not panic once the relay has stopped
+        notifier.broadcast(Notification::new(
+            NotificationLevel::Warning,
+            NotificationCategory::Shutdown,
+            "Stopping",
+            "",
+        ));
+    }
+
+    #[test]
+    fn test_alert_notification() {
+        let bell = alert_notification(("s1".to_string(), TerminalAlert::Bell));
+        assert_eq!(bell.category, NotificationCategory::Terminal);
+        assert_eq!(bell.title, "Bell");
+        assert_eq!(bell.session_id.as_deref(), Some("s1"));
+
+        let notice = alert_notification((
+            "s2".to_string(),
+            TerminalAlert::Notify {
+                title: None,
+                body: "Build finished".to_string(),
+            },
+        ));
+        assert_eq!(notice.title, "Terminal notification");
+        assert_eq!(notice.body, "Build finished");
+        assert_eq!(notice.session_id.as_deref(), Some("s2"));
+    }
+}
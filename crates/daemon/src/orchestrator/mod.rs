@@ -0,0 +1,3201 @@
+//! Daemon orchestrator for wiring together all components.
+//!
+//! This module provides the `DaemonOrchestrator` that initializes and coordinates
+//! all daemon subsystems: session management, device trust, message routing,
+//! network handlers (WebRTC/QUIC), and signaling. Each transport is only
+//! built with its Cargo feature, `webrtc` or `quic`.
+//!
+//! Long-running subsystems are owned by a [`Supervisor`] as restartable units,
+//! so a failure in one of them does not require restarting the whole daemon.
+
+#[cfg(feature = "quic")]
+mod quic;
+mod recovery;
+#[cfg(feature = "webrtc")]
+mod signaling;
+mod supervisor;
+
+pub use recovery::{CrashRecovery, PersistedState, StateFile, StateRecorder, STATE_FILE_NAME};
+pub use supervisor::{RestartPolicy, Supervisor, UnitFuture, UnitState, UnitStatus};
+
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use protocol::crypto::DeviceIdentity;
+use protocol::messages::{
+    HostMultiplexer, Message, Notification, NotificationCategory, NotificationLevel,
+    SessionCloseReason,
+};
+use protocol::DeviceId;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::auditd::{AuditLog, AuditRecord, AuditWriter};
+use crate::authz::{self, Operation, Principal};
+use crate::config::{Config, WebhookEvent};
+use crate::crash;
+use crate::devices::{
+    PairingCodeStore, ProvisioningFile, ReconcileReport, SecurityKeyGate, SessionConfirmations,
+    TrustLevel, TrustStore, TRUST_STORE_FILE_NAME,
+};
+use crate::files::{
+    DirectoryBrowser, DownloadStreams, FileCopy, FileMessage, FileTails, FileTransfer,
+    PathPermissions, ReaderConfig, TailLimits, Thumbnailer,
+};
+use crate::fleet::FleetClient;
+use crate::honeypot::Honeypot;
+use crate::ipc::{
+    get_socket_path, IpcConnection, IpcPendingSession, IpcRequest, IpcResponse, IpcServer,
+    IpcSessionInfo, QuicEndpointInfo,
+};
+use crate::logging::{parse_level, LogBuffer, LogLevelHandle};
+use crate::maintenance::MaintenanceMode;
+use crate::mesh::{self, MeshClient, MeshPeers};
+use crate::mqtt::{MqttPresence, Presence};
+#[cfg(feature = "webrtc")]
+use crate::network::signaling::{ConnectionState, WebSocketSignalingClient};
+#[cfg(feature = "quic")]
+use crate::network::QuicListener;
+use crate::network::{ChannelType, Connection, ProxySettings};
+use crate::notify::{alert_notification, Notifier, OutboundNotification, Recipient};
+use crate::power::PowerManager;
+use crate::remote_config::RemoteConfig;
+use crate::router::{AnsweredSession, MessageRouter};
+use crate::session::{
+    AgentForwarder, AgentMessage, ClosedSession, DisplayForwarder, DisplayMessage, EnvAllowlist,
+    HostSessions, OutputMessage, OutputStreams, SessionAlert, SessionEnvExporter, SessionManager,
+    SessionManagerImpl, ShellPolicy,
+};
+use crate::soak::{self, SoakProbe, SoakReport};
+use crate::telemetry;
+use crate::util::i18n::tr;
+use crate::webhooks::{WebhookDispatcher, WebhookPayload, Webhooks};
+#[cfg(feature = "webrtc")]
+use webrtc::peer_connection::RTCPeerConnection;
+
+/// File name of the daemon identity key inside the data directory.
+pub const IDENTITY_FILE_NAME: &str = "identity.key";
+
+/// Default cleanup interval for sessions (in seconds).
+const SESSION_CLEANUP_INTERVAL_SECS: u64 = 60;
+
+/// Default cleanup interval for expired pending approvals (in seconds).
+const APPROVAL_CLEANUP_INTERVAL_SECS: u64 = 60;
+
+/// Supervisor unit name for the IPC server.
+const IPC_UNIT: &str = "ipc-server";
+
+/// Supervisor unit name for the signaling client and WebRTC offer handling.
+#[cfg(feature = "webrtc")]
+const SIGNALING_UNIT: &str = "signaling";
+
+/// Supervisor unit name for the pending approval cleanup task.
+const APPROVAL_CLEANUP_UNIT: &str = "approval-cleanup";
+
+/// Supervisor unit name for the task ending temporary grants.
+const GRANT_EXPIRY_UNIT: &str = "grant-expiry";
+
+/// Interval between checks for expired temporary grants.
+const GRANT_EXPIRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Name of the mesh status unit.
+const MESH_UNIT: &str = "mesh";
+
+/// Name of the provisioning reload unit.
+const PROVISIONING_UNIT: &str = "provisioning";
+
+/// Interval between checks of the provisioning file for changes.
+const PROVISIONING_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Name of the configuration reload unit.
+const CONFIG_RELOAD_UNIT: &str = "config-reload";
+
+/// Interval between checks of the configuration file for changes.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval between samples of the presence published over MQTT.
+const PRESENCE_SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Maximum time to wait for supervised units to stop during shutdown.
+const UNIT_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum time to wait for streamed downloads to reach a chunk boundary
+/// during shutdown.
+const TRANSFER_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Directory, inside the data directory, holding forwarded SSH agent sockets.
+const AGENT_SOCKET_DIR: &str = "agent";
+
+/// Directory, inside the data directory, holding forwarded Wayland sockets.
+const DISPLAY_SOCKET_DIR: &str = "display";
+
+/// Directory, inside the data directory, holding session environment files.
+const SESSION_ENV_DIR: &str = "env";
+
+/// How often the session and device counts are recorded in the state file.
+const STATE_RECORD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Daemon orchestrator state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrchestratorState {
+    /// Initial state, not started.
+    Stopped,
+    /// Starting up, initializing components.
+    Starting,
+    /// Running and accepting connections.
+    Running,
+    /// Shutting down gracefully.
+    ShuttingDown,
+}
+
+/// Connection information for an active peer.
+pub struct ActiveConnection {
+    /// Device ID of the peer.
+    pub device_id: String,
+    /// Distinguishes this connection from the device's earlier and later
+    /// ones, since a device that moves to another transport replaces its
+    /// connection.
+    pub id: u64,
+    /// The transport carrying the connection.
+    pub handler: Box<dyn Connection>,
+    /// The WebRTC peer connection, for signaling that arrives after the
+    /// offer. `None` for connections over other transports.
+    #[cfg(feature = "webrtc")]
+    pub peer_connection: Option<Arc<RTCPeerConnection>>,
+    /// Whether the Noise handshake is complete.
+    pub noise_complete: bool,
+}
+
+/// Returns a new [`ActiveConnection::id`].
+fn next_connection_id() -> u64 {
+    static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+    NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Waits for a scoped thread, passing its panic on to the caller.
+fn join_scoped<T>(handle: std::thread::ScopedJoinHandle<'_, T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Events emitted by the orchestrator.
+#[derive(Debug, Clone)]
+pub enum OrchestratorEvent {
+    /// Orchestrator state changed.
+    StateChanged(OrchestratorState),
+    /// A new peer connected.
+    PeerConnected { device_id: String },
+    /// A peer disconnected.
+    PeerDisconnected { device_id: String, reason: String },
+    /// Signaling connection state changed.
+    #[cfg(feature = "webrtc")]
+    SignalingStateChanged(ConnectionState),
+    /// Error occurred.
+    Error { message: String },
+    /// A supervised subsystem failed or panicked.
+    SubsystemFailed {
+        name: String,
+        error: String,
+        restarting: bool,
+    },
+    /// A background task panicked.
+    TaskPanicked {
+        task: String,
+        message: String,
+        restarting: bool,
+    },
+    /// A step of a graceful stop completed.
+    ShutdownProgress { subsystem: String, detail: String },
+    /// A temporary grant from `devices grant-once` ended.
+    GrantEnded {
+        device_id: String,
+        name: String,
+        reason: String,
+    },
+}
+
+/// Daemon orchestrator that manages all subsystems.
+pub struct DaemonOrchestrator {
+    /// Configuration.
+    config: Config,
+    /// Device identity for this daemon.
+    identity: DeviceIdentity,
+    /// Current state.
+    state: Arc<RwLock<OrchestratorState>>,
+    /// Session manager for PTY sessions.
+    session_manager: Arc<SessionManagerImpl>,
+    /// Trust store for device management.
+    trust_store: Arc<TrustStore>,
+    /// Per-device path permissions.
+    path_permissions: Arc<PathPermissions>,
+    /// Directory browser for file listing.
+    directory_browser: Arc<DirectoryBrowser>,
+    /// File transfer handler.
+    file_transfer: Arc<FileTransfer>,
+    /// Message router.
+    router: Arc<MessageRouter<SessionManagerImpl>>,
+    /// The configuration clients read and patch.
+    remote_config: Arc<RemoteConfig>,
+    /// Maintenance mode, which refuses new sessions and uploads while on.
+    maintenance: Arc<MaintenanceMode>,
+    /// Signaling client for the current run of the signaling unit.
+    #[cfg(feature = "webrtc")]
+    signaling_client: Arc<RwLock<Option<Arc<WebSocketSignalingClient>>>>,
+    /// Active connections by device ID.
+    connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+    /// Cancellation token for graceful shutdown.
+    shutdown_token: CancellationToken,
+    /// Cancelled when a stop is requested over IPC.
+    stop_requested: CancellationToken,
+    /// Event sender.
+    event_tx: broadcast::Sender<OrchestratorEvent>,
+    /// Start time for uptime tracking.
+    start_time: Option<Instant>,
+    /// Records state in the data directory, once started.
+    state_recorder: Option<Arc<StateRecorder>>,
+    /// Supervisor for restartable subsystems.
+    supervisor: Arc<Supervisor>,
+    /// Recent log lines served to `remoshell logs`.
+    log_buffer: LogBuffer,
+    /// Where to write soak snapshots, if anywhere.
+    soak_report: Option<SoakReport>,
+    /// Sessions waiting for the operator to allow them, if every new session
+    /// must be confirmed.
+    session_confirmations: Option<Arc<SessionConfirmations>>,
+    /// Session requests the operator has answered, until the task finishing
+    /// them takes them.
+    answered_sessions: Option<mpsc::UnboundedReceiver<AnsweredSession>>,
+    /// Output of attached sessions, until the relay task takes it.
+    output_messages: Option<mpsc::UnboundedReceiver<OutputMessage>>,
+    /// Messages from forwarded SSH agent sockets, until the relay task takes
+    /// them.
+    agent_messages: Option<mpsc::UnboundedReceiver<AgentMessage>>,
+    /// Messages from forwarded display sockets, until the relay task takes
+    /// them.
+    display_messages: Option<mpsc::UnboundedReceiver<DisplayMessage>>,
+    /// Chunks of streamed downloads, until the relay task takes them.
+    file_messages: Option<mpsc::UnboundedReceiver<FileMessage>>,
+    /// Direct file copies with other daemons, if enabled.
+    file_copy: Option<Arc<FileCopy>>,
+    /// QUIC endpoint for native clients, once started if enabled.
+    #[cfg(feature = "quic")]
+    quic_listener: Option<Arc<QuicListener>>,
+    /// Queues notifications for connected clients.
+    notifier: Notifier,
+    /// Queued notifications, until the relay task takes them.
+    notifications: Option<mpsc::UnboundedReceiver<OutboundNotification>>,
+    /// Bells and desktop notifications from sessions, until the relay task
+    /// takes them.
+    session_alerts: Option<mpsc::UnboundedReceiver<SessionAlert>>,
+    /// Sessions that have ended, until the relay task takes them.
+    closed_sessions: Option<mpsc::UnboundedReceiver<ClosedSession>>,
+    /// Reports events to the configured webhooks.
+    webhooks: Webhooks,
+    /// Reported webhook events, until the dispatcher takes them.
+    webhook_events: Option<mpsc::UnboundedReceiver<WebhookPayload>>,
+    /// Audit records, until the writer takes them.
+    audit_records: Option<mpsc::UnboundedReceiver<AuditRecord>>,
+}
+
+impl DaemonOrchestrator {
+    /// Creates a new daemon orchestrator.
+    pub fn new(config: Config) -> Result<Self> {
+        // Paths devices may browse
+        let allowed_paths = if config.file.allowed_paths.is_empty() {
+            // Default to home directory if no paths specified
+            vec![
+                dirs::home_dir().unwrap_or_else(|| PathBuf::from("/")),
+                PathBuf::from("/tmp"),
+            ]
+        } else {
+            config.file.allowed_paths.clone()
+        };
+
+        // Load the identity, trust store and path permissions in parallel,
+        // as they are independent files
+        let identity_path = config.daemon.data_dir.join(IDENTITY_FILE_NAME);
+        let trust_store_path = config.daemon.data_dir.join(TRUST_STORE_FILE_NAME);
+        let trust_store = Arc::new(TrustStore::new(&trust_store_path));
+        let permissions_path = config.daemon.data_dir.join("permissions.json");
+        let path_permissions = Arc::new(PathPermissions::new(
+            permissions_path,
+            allowed_paths.clone(),
+        ));
+        let (identity, trust_store_loaded, permissions_loaded) = std::thread::scope(|scope| {
+            let identity = scope.spawn(|| Self::load_or_generate_identity(&identity_path));
+            let trust_store_loaded = scope.spawn(|| trust_store.load());
+            let permissions_loaded = path_permissions.load();
+            (
+                join_scoped(identity),
+                join_scoped(trust_store_loaded),
+                permissions_loaded,
+            )
+        });
+        let identity = identity?;
+        trust_store_loaded.context("Failed to load trust store")?;
+        permissions_loaded.context("Failed to load path permissions")?;
+
+        info!("Daemon identity: {}", identity.device_id().fingerprint());
+
+        // Initialize session manager
+        let (audit, audit_records) = AuditLog::channel();
+        let mut session_manager = SessionManagerImpl::new()
+            .with_shell_integration(config.session.shell_integration)
+            .with_scrollback_lines(config.session.scrollback_lines);
+        if config.auditd.enabled {
+            session_manager = session_manager.with_audit(audit.clone());
+        }
+        let session_alerts = if config.session.terminal_notifications {
+            let (alerts_tx, alerts_rx) = mpsc::unbounded_channel();
+            session_manager = session_manager.with_alerts(alerts_tx);
+            Some(alerts_rx)
+        } else {
+            None
+        };
+        let (closed_tx, closed_sessions) = mpsc::unbounded_channel();
+        session_manager = session_manager.with_closed_sessions(closed_tx);
+        if config.session.idle_timeout > 0 {
+            session_manager =
+                session_manager.with_idle_timeout(Duration::from_secs(config.session.idle_timeout));
+        }
+        let session_manager = Arc::new(session_manager);
+
+        // Initialize directory browser with allowed paths
+        let directory_browser = Arc::new(
+            DirectoryBrowser::new(allowed_paths.clone())
+                .with_exclude(config.file.exclude_patterns()?)
+                .with_show_hidden(config.file.show_hidden_overrides()?),
+        );
+
+        // Initialize file transfer handler
+        let browser_for_transfer = DirectoryBrowser::new(allowed_paths);
+        let file_transfer = Arc::new(
+            FileTransfer::new(browser_for_transfer, config.file.max_size)
+                .with_temp_dir(
+                    config
+                        .file
+                        .upload_temp_dir
+                        .clone()
+                        .unwrap_or_else(|| config.daemon.data_dir.join("tmp")),
+                )
+                .with_reader_config(ReaderConfig {
+                    buffer_size: config.file.read_buffer_size,
+                    pooled_buffers: config.file.read_buffer_pool,
+                    open_files: config.file.open_file_cache,
+                })
+                .with_max_text_size(config.file.text_max_size),
+        );
+
+        // Reconcile against the provisioning file before accepting connections
+        if let Some(path) = &config.security.provisioning_file {
+            Self::apply_provisioning(path, &trust_store, &path_permissions)
+                .context("Failed to apply provisioning file")?;
+        }
+
+        // Initialize message router
+        let (output_tx, output_messages) = mpsc::unbounded_channel();
+        let mut router = MessageRouter::new(
+            Arc::clone(&session_manager),
+            Arc::clone(&file_transfer),
+            Arc::clone(&directory_browser),
+            Arc::clone(&trust_store),
+            Arc::clone(&path_permissions),
+        )
+        .with_session_templates(config.session.templates.clone())
+        .with_session_owners(session_manager.owners().clone())
+        .with_output_streams(Arc::new(OutputStreams::new(output_tx)))
+        .with_env_allowlist(EnvAllowlist::new(config.session.env_allowlist.clone()))
+        .with_shell_policy(ShellPolicy::new(
+            Some(config.session.default_shell.clone()),
+            config.session.allowed_shells.clone(),
+        ));
+        if config.session.host_sessions.enabled {
+            let host_sessions = HostSessions::from_config(&config.session.host_sessions);
+            for multiplexer in [HostMultiplexer::Tmux, HostMultiplexer::Screen] {
+                match host_sessions.find(multiplexer) {
+                    Some(path) => {
+                        debug!(?multiplexer, path = %path.display(), "Found host multiplexer")
+                    }
+                    None => debug!(?multiplexer, "Host multiplexer not installed"),
+                }
+            }
+            router = router.with_host_sessions(Arc::new(host_sessions));
+        }
+        if config.security.fido2.enabled {
+            let gate = SecurityKeyGate::from_config(&config.security.fido2)
+                .context("Failed to load security key configuration")?;
+            router = router.with_security_keys(Arc::new(gate));
+        }
+        let session_confirmations = config.security.session_confirmation.enabled.then(|| {
+            Arc::new(SessionConfirmations::new(Duration::from_secs(
+                config.security.session_confirmation.timeout,
+            )))
+        });
+        let answered_sessions = match &session_confirmations {
+            Some(confirmations) => {
+                let (answered_tx, answered_rx) = mpsc::unbounded_channel();
+                router = router.with_session_confirmations(Arc::clone(confirmations), answered_tx);
+                Some(answered_rx)
+            }
+            None => None,
+        };
+        let agent_messages = if config.session.agent_forwarding {
+            let (agent_tx, agent_rx) = mpsc::unbounded_channel();
+            let forwarder =
+                AgentForwarder::new(config.daemon.data_dir.join(AGENT_SOCKET_DIR), agent_tx);
+            router = router.with_agent_forwarder(Arc::new(forwarder));
+            Some(agent_rx)
+        } else {
+            None
+        };
+        let display_messages = if config.session.display_forwarding {
+            let (display_tx, display_rx) = mpsc::unbounded_channel();
+            let forwarder = DisplayForwarder::new(
+                config.daemon.data_dir.join(DISPLAY_SOCKET_DIR),
+                config.session.x11_display_offset,
+                display_tx,
+            );
+            router = router.with_display_forwarder(Arc::new(forwarder));
+            Some(display_rx)
+        } else {
+            None
+        };
+        if config.session.env_updates {
+            let exporter = SessionEnvExporter::new(config.daemon.data_dir.join(SESSION_ENV_DIR));
+            router = router.with_env_exporter(Arc::new(exporter));
+        }
+        let thumbnails = &config.file.thumbnails;
+        router = router.with_thumbnailer(Arc::new(Thumbnailer::new(
+            thumbnails.max_source_size,
+            Duration::from_millis(thumbnails.timeout_ms),
+            thumbnails.max_concurrent,
+        )));
+        let (file_tx, file_messages) = mpsc::unbounded_channel();
+        router = router.with_download_streams(Arc::new(DownloadStreams::new(file_tx.clone())));
+        let tail = &config.file.tail;
+        if tail.enabled {
+            let limits = TailLimits {
+                max_lines: tail.max_lines,
+                max_streams: tail.max_streams,
+                poll_interval: Duration::from_millis(tail.poll_interval_ms),
+                max_rate: tail.max_rate,
+            };
+            router = router.with_file_tails(Arc::new(FileTails::new(file_tx.clone(), limits)));
+        }
+        let file_copy = if config.file.copy.enabled {
+            let copy = Arc::new(FileCopy::new(
+                identity.clone(),
+                Arc::clone(&file_transfer),
+                config.file.copy.advertised_addresses(),
+                file_tx,
+            ));
+            router = router.with_file_copy(Arc::clone(&copy));
+            Some(copy)
+        } else {
+            None
+        };
+        if config.power.enabled {
+            router = router.with_power_manager(Arc::new(PowerManager::from_config(&config.power)));
+        }
+        let remote_config = Arc::new(RemoteConfig::new(
+            config.clone(),
+            Arc::clone(&session_manager),
+            Arc::clone(&directory_browser),
+        ));
+        router = router.with_remote_config(Arc::clone(&remote_config));
+        let maintenance = Arc::new(MaintenanceMode::new());
+        router = router.with_interceptor(maintenance.clone());
+        let (notifier, notifications) = Notifier::channel();
+        router = router.with_notifier(notifier.clone());
+        let (webhooks, webhook_events) = Webhooks::channel(identity.device_id().fingerprint());
+        if !config.webhooks.endpoints.is_empty() {
+            router = router.with_webhooks(webhooks.clone());
+        }
+        if config.auditd.enabled {
+            router = router.with_audit(audit);
+        }
+        if config.security.max_messages_per_second > 0 {
+            router = router.with_rate_limit(config.security.max_messages_per_second);
+        }
+        if config.security.honeypot.enabled {
+            warn!("Honeypot mode enabled: untrusted devices get a fake shell");
+            router = router.with_honeypot(Arc::new(Honeypot::new(
+                &config.security.honeypot,
+                &config.daemon.data_dir,
+            )));
+        }
+        let router = Arc::new(router);
+
+        let (event_tx, _) = broadcast::channel(256);
+        let shutdown_token = CancellationToken::new();
+        let supervisor = Arc::new(Supervisor::new(shutdown_token.clone(), event_tx.clone()));
+
+        Ok(Self {
+            config,
+            identity,
+            state: Arc::new(RwLock::new(OrchestratorState::Stopped)),
+            session_manager,
+            trust_store,
+            path_permissions,
+            directory_browser,
+            file_transfer,
+            router,
+            remote_config,
+            maintenance,
+            #[cfg(feature = "webrtc")]
+            signaling_client: Arc::new(RwLock::new(None)),
+            connections: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            shutdown_token,
+            stop_requested: CancellationToken::new(),
+            event_tx,
+            start_time: None,
+            state_recorder: None,
+            supervisor,
+            log_buffer: LogBuffer::default(),
+            soak_report: None,
+            session_confirmations,
+            answered_sessions,
+            output_messages: Some(output_messages),
+            agent_messages,
+            display_messages,
+            file_messages: Some(file_messages),
+            file_copy,
+            #[cfg(feature = "quic")]
+            quic_listener: None,
+            notifier,
+            notifications: Some(notifications),
+            session_alerts,
+            closed_sessions: Some(closed_sessions),
+            webhooks,
+            webhook_events: Some(webhook_events),
+            audit_records: Some(audit_records),
+        })
+    }
+
+    /// Loads or generates the device identity.
+    pub fn load_or_generate_identity(path: &PathBuf) -> Result<DeviceIdentity> {
+        if path.exists() {
+            // Load existing identity
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("Failed to read identity file: {}", path.display()))?;
+            if bytes.len() != 32 {
+                anyhow::bail!(
+                    "Invalid identity file: expected 32 bytes, got {}",
+                    bytes.len()
+                );
+            }
+            let mut key = [0u8; 32];
+            key.copy_from_slice(&bytes);
+            Ok(DeviceIdentity::from_secret_key_bytes(&key))
+        } else {
+            // Generate new identity
+            let identity = DeviceIdentity::generate();
+
+            // Ensure parent directory exists
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            }
+
+            // Save the identity, readable only by the owner
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(path)
+                .and_then(|mut file| file.write_all(&identity.secret_key_bytes()))
+                .with_context(|| format!("Failed to write identity file: {}", path.display()))?;
+
+            info!("Generated new device identity and saved to {:?}", path);
+            Ok(identity)
+        }
+    }
+
+    /// Returns the device ID fingerprint.
+    pub fn device_id_fingerprint(&self) -> String {
+        self.identity.device_id().fingerprint()
+    }
+
+    /// Returns the current state.
+    pub async fn state(&self) -> OrchestratorState {
+        *self.state.read().await
+    }
+
+    /// Returns a receiver for orchestrator events.
+    pub fn subscribe(&self) -> broadcast::Receiver<OrchestratorEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Sets the log buffer served to IPC log subscribers.
+    ///
+    /// This should be the same buffer that is installed as a tracing layer,
+    /// and must be set before calling `start()`.
+    pub fn set_log_buffer(&mut self, log_buffer: LogBuffer) {
+        self.log_buffer = log_buffer;
+    }
+
+    /// Sets the configuration file that configuration changes from clients
+    /// are saved to. Until it is set, they are refused.
+    pub fn set_config_path(&mut self, path: PathBuf) {
+        self.remote_config.set_path(path);
+    }
+
+    /// Sets the handle that configuration changes from clients change the
+    /// log level through.
+    pub fn set_log_level_handle(&mut self, handle: LogLevelHandle) {
+        self.remote_config.set_log_level_handle(handle);
+    }
+
+    /// Sets where to write soak snapshots while running.
+    ///
+    /// Must be set before calling `start()`.
+    pub fn set_soak_report(&mut self, report: SoakReport) {
+        self.soak_report = Some(report);
+    }
+
+    /// Returns a probe reading the counters reported in soak snapshots.
+    pub fn soak_probe(&self) -> SoakProbe {
+        SoakProbe::new(
+            Arc::clone(&self.session_manager),
+            Arc::clone(&self.router),
+            Arc::clone(&self.file_transfer),
+            Arc::clone(&self.connections),
+            self.event_tx.clone(),
+        )
+    }
+
+    /// Starts the daemon orchestrator.
+    pub async fn start(&mut self) -> Result<()> {
+        // Check current state
+        {
+            let mut state = self.state.write().await;
+            if *state != OrchestratorState::Stopped {
+                anyhow::bail!("Orchestrator is already running");
+            }
+            *state = OrchestratorState::Starting;
+        }
+        self.emit_event(OrchestratorEvent::StateChanged(OrchestratorState::Starting));
+
+        info!("Starting daemon orchestrator...");
+
+        // Capture panics as crash reports in the data directory, and report
+        // them to the `crash` webhook
+        crash::install_panic_hook(Self::crash_handler(
+            &self.config.daemon.data_dir,
+            self.webhooks.clone(),
+        ));
+
+        // Record start time for uptime tracking
+        self.start_time = Some(Instant::now());
+
+        // Create PID file
+        let pid_file = self.get_pid_file_path();
+        self.create_pid_file(&pid_file)
+            .context("Failed to create PID file")?;
+        debug!("Created PID file at {:?}", pid_file);
+
+        // Record state from now on, and report what a daemon that did not
+        // stop cleanly lost
+        let state_recorder = Arc::new(StateRecorder::open(&self.config.daemon.data_dir));
+        if let Some(recovered) = state_recorder.recovered() {
+            warn!(
+                target: "audit",
+                crashed_at = recovered.crashed_at,
+                state = ?recovered.state,
+                lost_sessions = recovered.lost_sessions,
+                lost_devices = recovered.lost_devices,
+                "Recovered from an unclean shutdown"
+            );
+        }
+        state_recorder.set_state(OrchestratorState::Starting);
+        self.state_recorder = Some(Arc::clone(&state_recorder));
+
+        // Enroll with the fleet on first start, before accepting connections
+        if let Some(fleet) = FleetClient::from_config(&self.config.fleet)? {
+            let report = fleet
+                .enroll_once(
+                    &self.identity,
+                    &self.config.daemon.data_dir,
+                    &self.trust_store,
+                    &self.path_permissions,
+                )
+                .await
+                .context("Failed to enroll with the fleet")?;
+            match report {
+                Some(report) => {
+                    for drift in &report.drift {
+                        info!(target: "audit", endpoint = fleet.endpoint(), "Fleet enrollment: {}", drift);
+                    }
+                }
+                None => debug!("Already enrolled with fleet {}", fleet.endpoint()),
+            }
+        }
+
+        // Share status with the other daemons of the admin's mesh
+        let mesh_peers = match self.mesh_admin_key()? {
+            Some(admin_key) => {
+                let peers = Arc::new(MeshPeers::new(mesh::group_id(&admin_key)));
+                let signaling_url = self.config.network.signaling_url.clone();
+                let interval = Duration::from_secs(self.config.mesh.interval);
+                let identity = self.identity.clone();
+                let session_manager = Arc::clone(&self.session_manager);
+                let peers_for_task = Arc::clone(&peers);
+                self.supervisor
+                    .spawn(MESH_UNIT, RestartPolicy::default(), move |token| {
+                        let signaling_url = signaling_url.clone();
+                        let identity = identity.clone();
+                        let session_manager = Arc::clone(&session_manager);
+                        let peers = Arc::clone(&peers_for_task);
+                        Box::pin(async move {
+                            MeshClient::new(&signaling_url, &admin_key, interval)?
+                                .run(identity, || session_manager.count(), &peers, token)
+                                .await
+                        })
+                    })
+                    .await;
+                info!("Sharing status with mesh group {}", peers.group());
+                Some(peers)
+            }
+            None => None,
+        };
+
+        let proxies = ProxySettings::from_config(&self.config.network.proxy);
+
+        // Bind the endpoints concurrently; IPC only starts serving once the
+        // QUIC addresses it reports are known
+        let socket_path = get_socket_path(self.config.daemon.instance.as_deref());
+        let file_copy_listen = self
+            .file_copy
+            .is_some()
+            .then(|| self.config.file.copy.listen.clone());
+        let (quic_info, ipc_server, file_copy_listener) = tokio::try_join!(
+            self.bind_quic(&proxies),
+            async {
+                IpcServer::bind(&socket_path)
+                    .await
+                    .context("Failed to start IPC server")
+            },
+            Self::bind_file_copy(file_copy_listen),
+        )?;
+        info!("Started IPC server at {:?}", socket_path);
+
+        // Supervise the IPC server; restarts rebind the socket
+        let initial_ipc_server = Arc::new(std::sync::Mutex::new(Some(ipc_server)));
+        let session_manager_for_ipc = Arc::clone(&self.session_manager);
+        let start_time_for_ipc = self.start_time;
+        let shutdown_token_for_ipc = self.shutdown_token.clone();
+        let stop_requested_for_ipc = self.stop_requested.clone();
+        let event_tx_for_ipc = self.event_tx.clone();
+        let connections_for_ipc = Arc::clone(&self.connections);
+        let trust_store_for_ipc = Arc::clone(&self.trust_store);
+        let log_buffer_for_ipc = self.log_buffer.clone();
+        let webhooks_for_ipc = self.webhooks.clone();
+        let quic_info_for_ipc = quic_info;
+        let recovered_for_ipc = state_recorder.recovered();
+        let confirmations_for_ipc = self.session_confirmations.clone();
+        let mesh_for_ipc = mesh_peers;
+        let maintenance_for_ipc = Arc::clone(&self.maintenance);
+
+        self.supervisor
+            .spawn(IPC_UNIT, RestartPolicy::default(), move |token| {
+                let initial = initial_ipc_server.lock().ok().and_then(|mut s| s.take());
+                let socket_path = socket_path.clone();
+                let session_manager = Arc::clone(&session_manager_for_ipc);
+                let shutdown_token = shutdown_token_for_ipc.clone();
+                let stop_requested = stop_requested_for_ipc.clone();
+                let event_tx = event_tx_for_ipc.clone();
+                let connections = Arc::clone(&connections_for_ipc);
+                let trust_store = Arc::clone(&trust_store_for_ipc);
+                let log_buffer = log_buffer_for_ipc.clone();
+                let webhooks = webhooks_for_ipc.clone();
+                let quic_info = quic_info_for_ipc.clone();
+                let recovered = recovered_for_ipc.clone();
+                let confirmations = confirmations_for_ipc.clone();
+                let mesh = mesh_for_ipc.clone();
+                let maintenance = Arc::clone(&maintenance_for_ipc);
+                Box::pin(async move {
+                    let server = match initial {
+                        Some(server) => server,
+                        None => IpcServer::bind(&socket_path)
+                            .await
+                            .context("Failed to rebind IPC server")?,
+                    };
+                    Self::handle_ipc_requests(
+                        server,
+                        session_manager,
+                        start_time_for_ipc,
+                        shutdown_token,
+                        stop_requested,
+                        event_tx,
+                        connections,
+                        trust_store,
+                        log_buffer,
+                        webhooks,
+                        quic_info,
+                        recovered,
+                        confirmations,
+                        mesh,
+                        maintenance,
+                        token,
+                    )
+                    .await
+                })
+            })
+            .await;
+
+        // Start session cleanup task
+        let session_manager = Arc::clone(&self.session_manager);
+        session_manager.start_cleanup_task(SESSION_CLEANUP_INTERVAL_SECS);
+        debug!("Started session cleanup task");
+
+        // Start approval cleanup task
+        let trust_store_for_cleanup = Arc::clone(&self.trust_store);
+        let approval_timeout = self.config.security.approval_timeout;
+        let notifier_for_cleanup = self.notifier.clone();
+        self.supervisor
+            .spawn(
+                APPROVAL_CLEANUP_UNIT,
+                RestartPolicy::default(),
+                move |token| {
+                    Box::pin(Self::run_approval_cleanup_task(
+                        Arc::clone(&trust_store_for_cleanup),
+                        approval_timeout,
+                        notifier_for_cleanup.clone(),
+                        token,
+                    ))
+                },
+            )
+            .await;
+        debug!("Started approval cleanup task");
+
+        // End temporary grants when they expire or the device disconnects
+        let trust_store = Arc::clone(&self.trust_store);
+        let session_manager_for_grants = Arc::clone(&self.session_manager);
+        let connections_for_grants = Arc::clone(&self.connections);
+        let event_tx = self.event_tx.clone();
+        self.supervisor
+            .spawn(GRANT_EXPIRY_UNIT, RestartPolicy::default(), move |token| {
+                Box::pin(Self::run_grant_expiry_task(
+                    Arc::clone(&trust_store),
+                    Arc::clone(&session_manager_for_grants),
+                    Arc::clone(&connections_for_grants),
+                    event_tx.clone(),
+                    token,
+                ))
+            })
+            .await;
+
+        // Reload the provisioning file when it changes or on SIGHUP
+        if let Some(path) = self.config.security.provisioning_file.clone() {
+            let trust_store = Arc::clone(&self.trust_store);
+            let path_permissions = Arc::clone(&self.path_permissions);
+            self.supervisor
+                .spawn(PROVISIONING_UNIT, RestartPolicy::default(), move |token| {
+                    Box::pin(Self::run_provisioning_reload_task(
+                        path.clone(),
+                        Arc::clone(&trust_store),
+                        Arc::clone(&path_permissions),
+                        token,
+                    ))
+                })
+                .await;
+            debug!("Started provisioning reload task");
+        }
+
+        // Apply hand edits of the settings clients can patch when the
+        // configuration file changes or on SIGHUP
+        if let Some(path) = self.remote_config.path() {
+            let remote_config = Arc::clone(&self.remote_config);
+            self.supervisor
+                .spawn(CONFIG_RELOAD_UNIT, RestartPolicy::default(), move |token| {
+                    Box::pin(Self::run_config_reload_task(
+                        path.clone(),
+                        Arc::clone(&remote_config),
+                        token,
+                    ))
+                })
+                .await;
+            debug!("Started configuration reload task");
+        }
+
+        // Finish session requests as the operator answers them
+        if let Some(answered_sessions) = self.answered_sessions.take() {
+            let (reply_tx, reply_rx) = mpsc::unbounded_channel();
+            Self::spawn_monitored(
+                "session-confirmation",
+                self.event_tx.clone(),
+                Self::finish_answered_sessions(
+                    answered_sessions,
+                    Arc::clone(&self.router),
+                    reply_tx,
+                    self.shutdown_token.clone(),
+                ),
+            );
+            Self::spawn_monitored(
+                "session-confirmation-relay",
+                self.event_tx.clone(),
+                Self::relay_device_messages(
+                    reply_rx,
+                    ChannelType::Control,
+                    Arc::clone(&self.connections),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            debug!("Started session confirmation task");
+        }
+
+        // Relay the output of attached sessions to their clients
+        if let Some(output_messages) = self.output_messages.take() {
+            Self::spawn_monitored(
+                "output-relay",
+                self.event_tx.clone(),
+                Self::relay_device_messages(
+                    output_messages,
+                    ChannelType::Terminal,
+                    Arc::clone(&self.connections),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            debug!("Started session output relay task");
+        }
+
+        // Relay forwarded SSH agent traffic to the connected clients
+        if let Some(agent_messages) = self.agent_messages.take() {
+            Self::spawn_monitored(
+                "agent-relay",
+                self.event_tx.clone(),
+                Self::relay_device_messages(
+                    agent_messages,
+                    ChannelType::Control,
+                    Arc::clone(&self.connections),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            debug!("Started SSH agent relay task");
+        }
+
+        // Relay forwarded display traffic on the files channel, so that bulk
+        // GUI traffic does not hold up session control messages
+        if let Some(display_messages) = self.display_messages.take() {
+            Self::spawn_monitored(
+                "display-relay",
+                self.event_tx.clone(),
+                Self::relay_device_messages(
+                    display_messages,
+                    ChannelType::Files,
+                    Arc::clone(&self.connections),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            debug!("Started display relay task");
+        }
+
+        // Relay streamed download chunks to the connected clients
+        if let Some(file_messages) = self.file_messages.take() {
+            Self::spawn_monitored(
+                "file-relay",
+                self.event_tx.clone(),
+                Self::relay_device_messages(
+                    file_messages,
+                    ChannelType::Files,
+                    Arc::clone(&self.connections),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            debug!("Started file relay task");
+        }
+
+        // Accept direct copies from other daemons
+        if let (Some(file_copy), Some(listener)) = (&self.file_copy, file_copy_listener) {
+            Self::spawn_monitored(
+                "file-copy",
+                self.event_tx.clone(),
+                Arc::clone(file_copy).serve(listener, self.shutdown_token.clone()),
+            );
+        }
+
+        // Accept connections from native clients
+        self.spawn_quic_listener();
+
+        // Relay notifications to the connected clients
+        if let Some(notifications) = self.notifications.take() {
+            Self::spawn_monitored(
+                "notification-relay",
+                self.event_tx.clone(),
+                Self::relay_notifications(
+                    notifications,
+                    Arc::clone(&self.connections),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            debug!("Started notification relay task");
+        }
+
+        // Turn bells and desktop notifications from sessions into
+        // notifications for every connected client
+        if let Some(session_alerts) = self.session_alerts.take() {
+            Self::spawn_monitored(
+                "session-alert-relay",
+                self.event_tx.clone(),
+                Self::relay_session_alerts(
+                    session_alerts,
+                    self.notifier.clone(),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            debug!("Started session alert relay task");
+        }
+
+        // Tell devices when the sessions they created end
+        if let Some(closed_sessions) = self.closed_sessions.take() {
+            Self::spawn_monitored(
+                "session-closed-relay",
+                self.event_tx.clone(),
+                Self::relay_closed_sessions(
+                    closed_sessions,
+                    Arc::clone(&self.connections),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            debug!("Started session closed relay task");
+        }
+
+        // Deliver events to the configured webhooks
+        if let Some(webhook_events) = self.webhook_events.take() {
+            if !self.config.webhooks.endpoints.is_empty() {
+                let dispatcher = WebhookDispatcher::from_config(
+                    &self.config.webhooks,
+                    &self.config.daemon.data_dir,
+                )
+                .context("Failed to create webhook client")?;
+                Self::spawn_monitored(
+                    "webhook-dispatcher",
+                    self.event_tx.clone(),
+                    dispatcher.run(webhook_events, self.shutdown_token.clone()),
+                );
+                debug!(
+                    endpoints = self.config.webhooks.endpoints.len(),
+                    "Started webhook dispatcher"
+                );
+            }
+        }
+
+        // Record sessions in the system audit log
+        if let Some(audit_records) = self.audit_records.take() {
+            if self.config.auditd.enabled {
+                let writer = AuditWriter::from_config(&self.config.auditd)
+                    .context("Failed to open the audit log")?;
+                Self::spawn_monitored(
+                    "auditd-writer",
+                    self.event_tx.clone(),
+                    writer.run(audit_records, self.shutdown_token.clone()),
+                );
+                debug!("Started audit record writer");
+            }
+        }
+
+        // Dump counters for soak tests
+        if let Some(report) = self.soak_report.clone() {
+            Self::spawn_monitored(
+                "soak-report",
+                self.event_tx.clone(),
+                soak::run_reporter(self.soak_probe(), report, self.shutdown_token.clone()),
+            );
+            debug!("Started soak report");
+        }
+
+        // Publish presence for home automation
+        if self.config.mqtt.enabled {
+            let publisher = MqttPresence::from_config(&self.config.mqtt)
+                .context("Failed to configure MQTT presence")?;
+            let (presence_tx, presence_rx) = watch::channel(Presence::default());
+            Self::spawn_monitored(
+                "presence-sampler",
+                self.event_tx.clone(),
+                Self::sample_presence(
+                    presence_tx,
+                    Arc::clone(&self.connections),
+                    Arc::clone(&self.session_manager),
+                    self.shutdown_token.clone(),
+                ),
+            );
+            Self::spawn_monitored(
+                "mqtt-presence",
+                self.event_tx.clone(),
+                publisher.run(presence_rx, self.shutdown_token.clone()),
+            );
+            debug!(broker = %self.config.mqtt.host, "Started MQTT presence publisher");
+        }
+
+        // Only dial the signaling server when WebRTC is enabled
+        if self.config.network.webrtc {
+            self.start_signaling(proxies).await;
+        } else {
+            info!("WebRTC disabled, not connecting to the signaling server");
+            if !self.config.network.quic.enabled {
+                warn!("No transport enabled, devices cannot connect");
+            }
+        }
+
+        // Keep the recorded session and device counts current
+        Self::spawn_monitored(
+            "state-recorder",
+            self.event_tx.clone(),
+            Self::record_counts(
+                state_recorder,
+                Arc::clone(&self.session_manager),
+                Arc::clone(&self.connections),
+                self.shutdown_token.clone(),
+            ),
+        );
+
+        // Update state to running
+        {
+            let mut state = self.state.write().await;
+            *state = OrchestratorState::Running;
+        }
+        self.record_state(OrchestratorState::Running);
+        self.emit_event(OrchestratorEvent::StateChanged(OrchestratorState::Running));
+
+        info!(
+            startup_ms = self
+                .start_time
+                .map_or(0, |started| started.elapsed().as_millis() as u64),
+            "Daemon orchestrator started successfully"
+        );
+        Ok(())
+    }
+
+    /// Binds the listener for direct copies from other daemons, if enabled.
+    async fn bind_file_copy(listen: Option<String>) -> Result<Option<tokio::net::TcpListener>> {
+        let Some(listen) = listen else {
+            return Ok(None);
+        };
+        let listener = tokio::net::TcpListener::bind(&listen)
+            .await
+            .with_context(|| format!("Failed to listen for file copies on {}", listen))?;
+        info!("Accepting file copies on {}", listen);
+        Ok(Some(listener))
+    }
+
+    /// Spawns a background task whose panics are reported but not restarted.
+    ///
+    /// Used for tasks that own non-reconstructible state, such as a bound
+    /// socket or a single peer connection.
+    fn spawn_monitored<Fut>(
+        name: &'static str,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+        future: Fut,
+    ) where
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        tokio::spawn(async move {
+            if let Err(join_error) = tokio::spawn(crash::run_named(name, future)).await {
+                if join_error.is_panic() {
+                    let message = crash::panic_message(join_error.into_panic().as_ref());
+                    error!(task = name, "Background task panicked: {}", message);
+                    let _ = event_tx.send(OrchestratorEvent::TaskPanicked {
+                        task: name.to_string(),
+                        message,
+                        restarting: false,
+                    });
+                }
+            }
+        });
+    }
+
+    /// Returns the handler for captured panics: it writes crash reports into
+    /// the data directory and reports each one to `webhooks`.
+    fn crash_handler(data_dir: &Path, webhooks: Webhooks) -> crash::CrashHandler {
+        crash::CrashHandler::new(crash::crash_dir(data_dir)).with_notifier(move |report, path| {
+            webhooks.emit(
+                WebhookEvent::Crash,
+                serde_json::json!({
+                    "task": report.task,
+                    "message": report.message,
+                    "location": report.location,
+                    "report": path.map(|path| path.display().to_string()),
+                }),
+            );
+        })
+    }
+
+    /// Finishes session requests as the operator answers them, queueing the
+    /// new session or the refusal for the device on `replies`.
+    async fn finish_answered_sessions(
+        mut answered: mpsc::UnboundedReceiver<AnsweredSession>,
+        router: Arc<MessageRouter<SessionManagerImpl>>,
+        replies: mpsc::UnboundedSender<(DeviceId, Message)>,
+        shutdown_token: CancellationToken,
+    ) {
+        loop {
+            let answered = tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                next = answered.recv() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+
+            let device_id = answered.device_id;
+            let reply = match router.finish_answered_session(answered).await {
+                Ok(Some(reply)) => reply,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(device_id = %device_id, error = %e, "Session request refused");
+                    Message::Error(e.to_error_message(None))
+                }
+            };
+            let _ = replies.send((device_id, reply));
+        }
+    }
+
+    /// Sends messages the daemon pushes on its own, such as forwarded SSH
+    /// agent traffic or streamed download chunks, to their devices on
+    /// `channel`.
+    ///
+    /// Messages for devices that are not connected are dropped; their agent
+    /// channels and download streams are closed when the connection handler
+    /// stops.
+    async fn relay_device_messages(
+        mut messages: mpsc::UnboundedReceiver<(DeviceId, Message)>,
+        channel: ChannelType,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        shutdown_token: CancellationToken,
+    ) {
+        use protocol::messages::Envelope;
+
+        let mut sequence: u64 = 1;
+        let mut closing = false;
+        loop {
+            let (device_id, message) = tokio::select! {
+                // Deliver what is already queued, such as the last chunks of
+                // file transfers, before the connections close
+                _ = shutdown_token.cancelled(), if !closing => {
+                    messages.close();
+                    closing = true;
+                    continue;
+                }
+                next = messages.recv() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+
+            let data = match Envelope::new(sequence, message).to_msgpack() {
+                Ok(data) => data,
+                Err(e) => {
+                    error!(device_id = %device_id, error = %e, "Failed to encode relayed message");
+                    continue;
+                }
+            };
+            sequence += 1;
+
+            let mut conns = connections.write().await;
+            match conns.get_mut(&device_id.to_string()) {
+                Some(conn) => {
+                    if let Err(e) = conn.handler.send(channel, &data).await {
+                        warn!(device_id = %device_id, error = %e, "Failed to send relayed message");
+                    }
+                }
+                None => {
+                    debug!(device_id = %device_id, "Dropping message for disconnected device")
+                }
+            }
+        }
+    }
+
+    /// Keeps `presence` up to date with the connected devices and open
+    /// sessions.
+    async fn sample_presence(
+        presence: watch::Sender<Presence>,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        session_manager: Arc<SessionManagerImpl>,
+        shutdown_token: CancellationToken,
+    ) {
+        let mut interval = tokio::time::interval(PRESENCE_SAMPLE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            let current = Presence {
+                devices: connections.read().await.len(),
+                sessions: session_manager.count(),
+            };
+            presence.send_if_modified(|published| {
+                let changed = *published != current;
+                *published = current;
+                changed
+            });
+        }
+    }
+
+    /// Sends queued notifications to their devices.
+    async fn relay_notifications(
+        mut notifications: mpsc::UnboundedReceiver<OutboundNotification>,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        shutdown_token: CancellationToken,
+    ) {
+        let mut sequence: u64 = 1;
+        loop {
+            let (recipient, notification) = tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                next = notifications.recv() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+            Self::deliver_notification(&connections, sequence, recipient, notification).await;
+            sequence += 1;
+        }
+    }
+
+    /// Queues a notification for every connected device for each bell or
+    /// desktop notification raised in a session.
+    async fn relay_session_alerts(
+        mut alerts: mpsc::UnboundedReceiver<SessionAlert>,
+        notifier: Notifier,
+        shutdown_token: CancellationToken,
+    ) {
+        loop {
+            let alert = tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                next = alerts.recv() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+            notifier.broadcast(alert_notification(alert));
+        }
+    }
+
+    /// Sends a `SessionClosed` to the device that created each session that
+    /// ends.
+    async fn relay_closed_sessions(
+        mut closed_sessions: mpsc::UnboundedReceiver<ClosedSession>,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        shutdown_token: CancellationToken,
+    ) {
+        let mut sequence: u64 = 1;
+        loop {
+            let closed = tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                next = closed_sessions.recv() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+            Self::deliver_session_closed(&connections, sequence, closed).await;
+            sequence += 1;
+        }
+    }
+
+    /// Sends a `SessionClosed` on the Terminal channel of the device that
+    /// created the session.
+    ///
+    /// Sessions without an owner to tell, or whose owner is not connected,
+    /// are skipped.
+    async fn deliver_session_closed(
+        connections: &RwLock<std::collections::HashMap<String, ActiveConnection>>,
+        sequence: u64,
+        closed: ClosedSession,
+    ) {
+        use protocol::messages::Envelope;
+
+        let Some(owner) = closed.owner else {
+            return;
+        };
+        let message = Message::SessionClosed(closed.message());
+        let data = match Envelope::new(sequence, message).to_msgpack() {
+            Ok(data) => data,
+            Err(e) => {
+                error!(session_id = %closed.id, error = %e, "Failed to encode SessionClosed");
+                return;
+            }
+        };
+
+        let mut conns = connections.write().await;
+        match conns.get_mut(&owner.to_string()) {
+            Some(conn) => {
+                if let Err(e) = conn.handler.send(ChannelType::Terminal, &data).await {
+                    warn!(device_id = %owner, error = %e, "Failed to send SessionClosed");
+                }
+            }
+            None => debug!(
+                session_id = %closed.id,
+                device_id = %owner,
+                "Dropping SessionClosed for disconnected device"
+            ),
+        }
+    }
+
+    /// Tells every connected client that the daemon is stopping and will
+    /// close its connection within `grace`.
+    ///
+    /// Returns the number of clients told.
+    async fn announce_shutdown(
+        connections: &RwLock<std::collections::HashMap<String, ActiveConnection>>,
+        reason: &str,
+        grace: Duration,
+    ) -> usize {
+        use protocol::messages::{Envelope, ServerShutdownPending};
+
+        let message = Message::ServerShutdownPending(ServerShutdownPending {
+            reason: reason.to_string(),
+            grace_ms: grace.as_millis() as u64,
+        });
+        let data = match Envelope::new(0, message).to_msgpack() {
+            Ok(data) => data,
+            Err(e) => {
+                error!(error = %e, "Failed to encode ServerShutdownPending");
+                return 0;
+            }
+        };
+
+        let mut conns = connections.write().await;
+        let mut announced = 0;
+        for (device_id, conn) in conns.iter_mut() {
+            match conn.handler.send(ChannelType::Control, &data).await {
+                Ok(()) => announced += 1,
+                Err(e) => {
+                    warn!(device_id = %device_id, error = %e, "Failed to send ServerShutdownPending")
+                }
+            }
+        }
+        announced
+    }
+
+    /// Sends a notification on the Control channel of its recipients.
+    ///
+    /// Recipients that are not connected are skipped.
+    async fn deliver_notification(
+        connections: &RwLock<std::collections::HashMap<String, ActiveConnection>>,
+        sequence: u64,
+        recipient: Recipient,
+        notification: Notification,
+    ) {
+        use protocol::messages::Envelope;
+
+        let data = match Envelope::new(sequence, Message::Notification(notification)).to_msgpack() {
+            Ok(data) => data,
+            Err(e) => {
+                error!(error = %e, "Failed to encode notification");
+                return;
+            }
+        };
+
+        let mut conns = connections.write().await;
+        let targets: Vec<&mut ActiveConnection> = match recipient {
+            Recipient::All => conns.values_mut().collect(),
+            Recipient::Device(device_id) => {
+                conns.get_mut(&device_id.to_string()).into_iter().collect()
+            }
+        };
+        if targets.is_empty() {
+            debug!(
+                ?recipient,
+                "Dropping notification without connected recipients"
+            );
+        }
+        for conn in targets {
+            if let Err(e) = conn.handler.send(ChannelType::Control, &data).await {
+                warn!(device_id = %conn.device_id, error = %e, "Failed to send notification");
+            }
+        }
+    }
+
+    /// Stores a connection and starts routing its messages.
+    ///
+    /// A connection the device already had is closed and replaced, as when
+    /// the device moves to another transport.
+    async fn register_connection(
+        connections: &Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        router: &Arc<MessageRouter<SessionManagerImpl>>,
+        event_tx: &broadcast::Sender<OrchestratorEvent>,
+        shutdown_token: &CancellationToken,
+        connection: ActiveConnection,
+    ) {
+        let device_id = connection.device_id.clone();
+        let id = connection.id;
+
+        let mut conns = connections.write().await;
+        let replaced = conns.insert(device_id.clone(), connection);
+        drop(conns); // Release lock before spawning task
+
+        let migrated = replaced.is_some();
+        if let Some(mut old) = replaced {
+            let _ = old.handler.close().await;
+        }
+
+        Self::start_connection(
+            connections,
+            router,
+            event_tx,
+            shutdown_token,
+            device_id,
+            id,
+            migrated,
+        );
+    }
+
+    /// Announces a stored connection and starts routing its messages.
+    ///
+    /// A `migrated` connection replaced one the device already had, so
+    /// subscribers are not told about it again.
+    #[allow(clippy::too_many_arguments)]
+    fn start_connection(
+        connections: &Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        router: &Arc<MessageRouter<SessionManagerImpl>>,
+        event_tx: &broadcast::Sender<OrchestratorEvent>,
+        shutdown_token: &CancellationToken,
+        device_id: String,
+        id: u64,
+        migrated: bool,
+    ) {
+        if migrated {
+            info!(device_id = %device_id, "Device moved its connection to a new transport");
+        } else {
+            // Notify subscribers that a peer connected
+            let _ = event_tx.send(OrchestratorEvent::PeerConnected {
+                device_id: device_id.clone(),
+            });
+        }
+
+        // Spawn a task to handle messages from this connection
+        let connections_for_handler = Arc::clone(connections);
+        let router_for_handler = Arc::clone(router);
+        let event_tx_for_handler = event_tx.clone();
+        let shutdown_token_for_handler = shutdown_token.clone();
+        let device_id_for_handler = device_id.clone();
+
+        Self::spawn_monitored(
+            "connection-handler",
+            event_tx.clone(),
+            Self::handle_connection_messages(
+                device_id_for_handler,
+                id,
+                connections_for_handler,
+                router_for_handler,
+                event_tx_for_handler,
+                shutdown_token_for_handler,
+            )
+            .instrument(telemetry::connection_span(&device_id)),
+        );
+    }
+
+    /// Handles incoming messages from a connection.
+    ///
+    /// This task runs for the lifetime of the connection, receiving messages from
+    /// the control, terminal and files channels, routing them through the MessageRouter,
+    /// and sending responses back to the client. If the device replaces the
+    /// connection, the task stops and leaves the device's agent channels,
+    /// downloads and tails to the replacement.
+    async fn handle_connection_messages(
+        device_id: String,
+        id: u64,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        router: Arc<MessageRouter<SessionManagerImpl>>,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+        shutdown_token: CancellationToken,
+    ) {
+        use protocol::messages::Envelope;
+
+        info!("Starting message handler for connection");
+
+        // Parse the device ID from fingerprint format
+        let parsed_device_id = match Self::parse_device_id_from_fingerprint(&device_id) {
+            Some(id) => id,
+            None => {
+                warn!("Failed to parse device ID, using placeholder");
+                DeviceId::from_bytes([0u8; 16])
+            }
+        };
+
+        let mut sequence: u64 = 1;
+
+        // Channels to try, in order of priority
+        let channels = [
+            ChannelType::Control,
+            ChannelType::Terminal,
+            ChannelType::Files,
+        ];
+        let mut current_channel_idx = 0;
+
+        loop {
+            // Check for shutdown
+            if shutdown_token.is_cancelled() {
+                info!("Message handler received shutdown signal");
+                break;
+            }
+
+            // Round-robin between channels to handle messages from all channels
+            let channel_type = channels[current_channel_idx];
+            current_channel_idx = (current_channel_idx + 1) % channels.len();
+
+            // Try to receive a message from the current channel
+            let recv_result = {
+                let mut conns = connections.write().await;
+                let Some(conn) = conns.get_mut(&device_id) else {
+                    info!("Connection no longer exists, stopping message handler");
+                    break;
+                };
+                if conn.id != id {
+                    info!("Connection replaced, stopping message handler");
+                    return;
+                }
+
+                // Use a short timeout to allow checking other channels
+                tokio::select! {
+                    biased;
+                    _ = shutdown_token.cancelled() => {
+                        info!("Shutdown during recv");
+                        return; // Exit directly since we're in a spawned task
+                    }
+                    result = conn.handler.recv(channel_type) => {
+                        Some(result)
+                    }
+                    _ = tokio::time::sleep(Duration::from_millis(10)) => {
+                        None // Timeout, try next channel
+                    }
+                }
+            };
+
+            let Some(result) = recv_result else {
+                continue;
+            };
+
+            let data = match result {
+                Ok(data) => data,
+                Err(e) => {
+                    // Check if this is a "no data" error or a real connection error
+                    let error_str = e.to_string();
+                    if error_str.contains("channel closed")
+                        || error_str.contains("connection closed")
+                    {
+                        // Connection might be closed or had an error
+                        debug!(error = %e, "Error receiving message, connection may be closed");
+                        // Remove connection and emit disconnect event, unless
+                        // the device already replaced it
+                        let mut conns = connections.write().await;
+                        if conns.get(&device_id).is_some_and(|conn| conn.id != id) {
+                            info!("Replaced connection closed, stopping message handler");
+                            return;
+                        }
+                        if conns.remove(&device_id).is_some() {
+                            let _ = event_tx.send(OrchestratorEvent::PeerDisconnected {
+                                device_id: device_id.clone(),
+                                reason: format!("Receive error: {}", e),
+                            });
+                        }
+                        break;
+                    }
+                    // Other errors - log and continue
+                    debug!(error = %e, channel = channel_type.channel_name(), "Receive error");
+                    continue;
+                }
+            };
+
+            // Decode the envelope
+            let envelope = match Envelope::from_msgpack(&data) {
+                Ok(env) => env,
+                Err(e) => {
+                    warn!(error = %e, "Failed to decode message envelope");
+                    continue;
+                }
+            };
+
+            let span = telemetry::message_span(channel_type, envelope.sequence);
+            async {
+                debug!("Received message");
+
+                // Get the authenticated public key from the connection for device verification
+                let authenticated_public_key = {
+                    let conns = connections.read().await;
+                    conns
+                        .get(&device_id)
+                        .and_then(|conn| conn.handler.peer_public_key())
+                };
+
+                // Route the message through the router
+                let response = router
+                    .route(
+                        envelope.payload,
+                        &parsed_device_id,
+                        authenticated_public_key.as_ref(),
+                    )
+                    .await;
+
+                // Handle the routing result
+                match response {
+                    Ok(Some(response_msg)) => {
+                        // Send the response back on the same channel
+                        let response_envelope = Envelope::new(sequence, response_msg);
+                        sequence += 1;
+
+                        match response_envelope.to_msgpack() {
+                            Ok(response_data) => {
+                                let mut conns = connections.write().await;
+                                if let Some(conn) = conns.get_mut(&device_id) {
+                                    if let Err(e) =
+                                        conn.handler.send(channel_type, &response_data).await
+                                    {
+                                        warn!(error = %e, "Failed to send response");
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                error!(error = %e, "Failed to encode response");
+                            }
+                        }
+                    }
+                    Ok(None) => {
+                        // No response needed (e.g., for data messages or acknowledgments)
+                        debug!("Message handled, no response needed");
+                    }
+                    Err(e) => {
+                        // Send an error response
+                        warn!(error = %e, "Error routing message");
+                        let error_msg = e.to_error_message(None);
+                        let error_response = Message::Error(error_msg);
+                        let response_envelope = Envelope::new(sequence, error_response);
+                        sequence += 1;
+
+                        if let Ok(response_data) = response_envelope.to_msgpack() {
+                            let mut conns = connections.write().await;
+                            if let Some(conn) = conns.get_mut(&device_id) {
+                                let _ = conn.handler.send(channel_type, &response_data).await;
+                            }
+                        }
+                    }
+                }
+            }
+            .instrument(span)
+            .await;
+        }
+
+        router.close_agent_forwarding(&parsed_device_id);
+        router.close_display_forwarding(&parsed_device_id);
+        router.close_download_streams(&parsed_device_id);
+        router.close_file_tails(&parsed_device_id);
+        router.close_session_confirmations(&parsed_device_id);
+        router.close_output_streams(&parsed_device_id);
+        router.close_interceptors(&parsed_device_id);
+        info!("Message handler stopped");
+    }
+
+    /// Parse a device ID from its fingerprint format.
+    fn parse_device_id_from_fingerprint(fingerprint: &str) -> Option<DeviceId> {
+        // Remove colons and decode hex
+        let hex_str: String = fingerprint.chars().filter(|c| *c != ':').collect();
+
+        if hex_str.len() != 32 {
+            // DeviceId is 16 bytes = 32 hex chars
+            return None;
+        }
+
+        let bytes = hex::decode(&hex_str).ok()?;
+        if bytes.len() != 16 {
+            return None;
+        }
+
+        let mut arr = [0u8; 16];
+        arr.copy_from_slice(&bytes);
+        Some(DeviceId::from_bytes(arr))
+    }
+
+    /// Runs the periodic approval cleanup task.
+    ///
+    /// This task runs at `APPROVAL_CLEANUP_INTERVAL_SECS` intervals and removes
+    /// pending approvals that have exceeded the configured timeout.
+    async fn run_approval_cleanup_task(
+        trust_store: Arc<TrustStore>,
+        timeout_secs: u64,
+        notifier: Notifier,
+        shutdown_token: CancellationToken,
+    ) -> Result<()> {
+        // Skip cleanup if timeout is 0 (disabled)
+        if timeout_secs == 0 {
+            debug!("Approval timeout is 0, cleanup task disabled");
+            return Ok(());
+        }
+
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(APPROVAL_CLEANUP_INTERVAL_SECS));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => {
+                    debug!("Approval cleanup task received shutdown signal");
+                    return Ok(());
+                }
+                _ = interval.tick() => {
+                    match trust_store.cleanup_expired_approvals(timeout_secs) {
+                        Ok(expired) => {
+                            for device_id in expired {
+                                info!(?device_id, "Pending approval expired");
+                                notifier.send(
+                                    device_id,
+                                    Notification::new(
+                                        NotificationLevel::Warning,
+                                        NotificationCategory::Approval,
+                                        tr("notify.approval_expired.title"),
+                                        tr("notify.approval_expired.body"),
+                                    ),
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Failed to cleanup expired approvals: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs the task ending temporary grants.
+    ///
+    /// A grant ends when it expires, checked every `GRANT_EXPIRY_INTERVAL`,
+    /// or when the device disconnects for the first time. If the task falls
+    /// behind on events and misses a disconnection, it compares the devices
+    /// it saw connected with those connected now.
+    async fn run_grant_expiry_task(
+        trust_store: Arc<TrustStore>,
+        session_manager: Arc<SessionManagerImpl>,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+        shutdown_token: CancellationToken,
+    ) -> Result<()> {
+        let mut events = event_tx.subscribe();
+        let mut interval = tokio::time::interval(GRANT_EXPIRY_INTERVAL);
+        let mut connected = Self::connected_devices(&connections).await;
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => {
+                    debug!("Grant expiry task received shutdown signal");
+                    return Ok(());
+                }
+                _ = interval.tick() => {
+                    match trust_store.expired_grants(SystemTime::now()) {
+                        Ok(expired) => {
+                            for device_id in expired {
+                                Self::end_temporary_grant(
+                                    &trust_store,
+                                    &session_manager,
+                                    &event_tx,
+                                    &device_id,
+                                    "expired",
+                                )
+                                .await;
+                            }
+                        }
+                        Err(e) => warn!("Failed to check temporary grants: {}", e),
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(OrchestratorEvent::PeerConnected { device_id }) => {
+                        if let Some(device_id) = Self::parse_device_id_from_fingerprint(&device_id) {
+                            connected.insert(device_id);
+                        }
+                    }
+                    Ok(OrchestratorEvent::PeerDisconnected { device_id, .. }) => {
+                        if let Some(device_id) = Self::parse_device_id_from_fingerprint(&device_id) {
+                            connected.remove(&device_id);
+                            Self::end_temporary_grant(
+                                &trust_store,
+                                &session_manager,
+                                &event_tx,
+                                &device_id,
+                                "disconnected",
+                            )
+                            .await;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(missed, "Grant expiry task missed events, checking connected devices");
+                        let now_connected = Self::connected_devices(&connections).await;
+                        for device_id in connected.difference(&now_connected) {
+                            Self::end_temporary_grant(
+                                &trust_store,
+                                &session_manager,
+                                &event_tx,
+                                device_id,
+                                "disconnected",
+                            )
+                            .await;
+                        }
+                        connected = now_connected;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+            }
+        }
+    }
+
+    /// Returns the devices connected now.
+    async fn connected_devices(
+        connections: &RwLock<std::collections::HashMap<String, ActiveConnection>>,
+    ) -> std::collections::HashSet<DeviceId> {
+        connections
+            .read()
+            .await
+            .keys()
+            .filter_map(|device_id| Self::parse_device_id_from_fingerprint(device_id))
+            .collect()
+    }
+
+    /// Ends a device's temporary grant, if it has one, and closes its
+    /// sessions. The end is logged under the `audit` target.
+    async fn end_temporary_grant(
+        trust_store: &TrustStore,
+        session_manager: &SessionManagerImpl,
+        event_tx: &broadcast::Sender<OrchestratorEvent>,
+        device_id: &DeviceId,
+        reason: &str,
+    ) {
+        let device = match trust_store.end_grant(device_id) {
+            Ok(Some(device)) => device,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(device_id = %device_id, "Failed to end temporary grant: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = trust_store.save() {
+            warn!("Failed to save trust store: {}", e);
+        }
+
+        let closed = session_manager
+            .close_owned_by(device_id, SessionCloseReason::OwnerRevoked)
+            .await;
+        info!(
+            target: "audit",
+            device_id = %device_id,
+            name = %device.name,
+            reason,
+            sessions_closed = closed,
+            "Temporary access ended"
+        );
+        let _ = event_tx.send(OrchestratorEvent::GrantEnded {
+            device_id: device_id.to_string(),
+            name: device.name,
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Returns the public key of the mesh's admin device, or `None` if mesh
+    /// mode is off or the admin device is not trusted.
+    fn mesh_admin_key(&self) -> Result<Option<[u8; 32]>> {
+        if !self.config.mesh.enabled {
+            return Ok(None);
+        }
+        let Some(admin) = self.config.mesh.admin_device_id()? else {
+            return Ok(None);
+        };
+        match self.trust_store.get_device(&admin)? {
+            Some(device) if device.is_trusted_at(SystemTime::now()) => Ok(Some(device.public_key)),
+            _ => {
+                warn!("Mesh mode disabled: admin device {} is not trusted", admin);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reconciles the trust store and path permissions against a provisioning
+    /// file, saving both stores if anything changed and logging each drift.
+    fn apply_provisioning(
+        path: &Path,
+        trust_store: &TrustStore,
+        path_permissions: &PathPermissions,
+    ) -> Result<ReconcileReport> {
+        let report = ProvisioningFile::load(path)?.reconcile(trust_store, path_permissions)?;
+
+        for drift in &report.drift {
+            warn!(target: "audit", file = %path.display(), "Provisioning drift: {}", drift);
+        }
+        if report.changed() {
+            trust_store.save()?;
+            path_permissions.save()?;
+        }
+        if report.is_clean() {
+            info!("Trust store matches provisioning file {:?}", path);
+        }
+
+        Ok(report)
+    }
+
+    /// Background task that re-applies the provisioning file when its
+    /// modification time changes or the daemon receives SIGHUP.
+    ///
+    /// An invalid file is logged and ignored, keeping the current state.
+    async fn run_provisioning_reload_task(
+        path: PathBuf,
+        trust_store: Arc<TrustStore>,
+        path_permissions: Arc<PathPermissions>,
+        shutdown_token: CancellationToken,
+    ) -> Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+        let mut sighup =
+            signal(SignalKind::hangup()).context("Failed to register SIGHUP handler")?;
+        let mut interval = tokio::time::interval(PROVISIONING_POLL_INTERVAL);
+        let mut last_modified = modified(&path);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => {
+                    debug!("Provisioning reload task received shutdown signal");
+                    return Ok(());
+                }
+                _ = sighup.recv() => {
+                    info!("Received SIGHUP, reloading provisioning file {:?}", path);
+                }
+                _ = interval.tick() => {
+                    let current = modified(&path);
+                    if current == last_modified {
+                        continue;
+                    }
+                    last_modified = current;
+                    info!("Provisioning file {:?} changed, reloading", path);
+                }
+            }
+
+            if let Err(e) = Self::apply_provisioning(&path, &trust_store, &path_permissions) {
+                error!("Failed to apply provisioning file {:?}: {:#}", path, e);
+            }
+        }
+    }
+
+    /// Background task that reloads the settings clients can patch when the
+    /// configuration file's modification time changes or the daemon
+    /// receives SIGHUP, so that [`RemoteConfig`] serves and applies what the
+    /// file says rather than what it said at startup.
+    ///
+    /// An invalid file is logged and ignored, keeping the current settings.
+    async fn run_config_reload_task(
+        path: PathBuf,
+        remote_config: Arc<RemoteConfig>,
+        shutdown_token: CancellationToken,
+    ) -> Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+        let mut sighup =
+            signal(SignalKind::hangup()).context("Failed to register SIGHUP handler")?;
+        let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+        let mut last_modified = modified(&path);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => {
+                    debug!("Configuration reload task received shutdown signal");
+                    return Ok(());
+                }
+                _ = sighup.recv() => {}
+                _ = interval.tick() => {
+                    let current = modified(&path);
+                    if current == last_modified {
+                        continue;
+                    }
+                    last_modified = current;
+                }
+            }
+
+            if let Err(e) = remote_config.reload() {
+                error!("Failed to reload configuration file {:?}: {}", path, e);
+            }
+        }
+    }
+
+    /// Stops the daemon orchestrator gracefully.
+    pub async fn stop(&mut self) -> Result<()> {
+        // Check current state
+        {
+            let mut state = self.state.write().await;
+            if *state == OrchestratorState::Stopped {
+                return Ok(());
+            }
+            if *state == OrchestratorState::ShuttingDown {
+                anyhow::bail!("Orchestrator is already shutting down");
+            }
+            *state = OrchestratorState::ShuttingDown;
+        }
+        self.record_state(OrchestratorState::ShuttingDown);
+        self.emit_event(OrchestratorEvent::StateChanged(
+            OrchestratorState::ShuttingDown,
+        ));
+
+        info!("Stopping daemon orchestrator...");
+
+        // Tell clients why they are about to be disconnected
+        let announced = Self::announce_shutdown(
+            &self.connections,
+            "The daemon is stopping",
+            TRANSFER_DRAIN_TIMEOUT + UNIT_STOP_TIMEOUT,
+        )
+        .await;
+        Self::deliver_notification(
+            &self.connections,
+            0,
+            Recipient::All,
+            Notification::new(
+                NotificationLevel::Warning,
+                NotificationCategory::Shutdown,
+                tr("notify.stopping.title"),
+                tr("notify.stopping.body"),
+            ),
+        )
+        .await;
+
+        self.report_progress("clients", format!("notified {} clients", announced));
+
+        // Close sessions while their owners can still be told why
+        let sessions = self.session_manager.list();
+        for (sequence, session_info) in sessions.iter().enumerate() {
+            debug!("Closing session {}", session_info.id);
+            match self
+                .session_manager
+                .close(
+                    &session_info.id,
+                    Some(9),
+                    SessionCloseReason::DaemonShutdown,
+                )
+                .await
+            {
+                Ok(closed) => {
+                    Self::deliver_session_closed(&self.connections, sequence as u64 + 1, closed)
+                        .await
+                }
+                Err(e) => warn!("Error closing session {}: {}", session_info.id, e),
+            }
+        }
+        self.report_progress("sessions", format!("closed {} sessions", sessions.len()));
+
+        // Let file transfers reach a chunk boundary
+        let drained = self.router.drain_transfers(TRANSFER_DRAIN_TIMEOUT).await;
+        if drained.timed_out {
+            warn!("Streamed downloads did not stop in time");
+        }
+        self.report_progress(
+            "transfers",
+            format!(
+                "stopped {} downloads, flushed {} uploads{}",
+                drained.downloads,
+                drained.uploads,
+                if drained.timed_out {
+                    " (timed out)"
+                } else {
+                    ""
+                }
+            ),
+        );
+
+        // Signal shutdown to all tasks (this also stops supervised units)
+        self.shutdown_token.cancel();
+
+        // Remove socket file
+        let socket_path = get_socket_path(self.config.daemon.instance.as_deref());
+        if let Err(e) = std::fs::remove_file(&socket_path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove socket file: {}", e);
+            }
+        }
+
+        // Remove PID file
+        let pid_file = self.get_pid_file_path();
+        self.remove_pid_file(&pid_file);
+        debug!("Removed PID file");
+
+        // Disconnect signaling client
+        self.disconnect_signaling().await;
+
+        // Wait for supervised units to wind down
+        self.supervisor.join(UNIT_STOP_TIMEOUT).await;
+        self.report_progress("subsystems", "stopped");
+
+        // Close all connections
+        let closed = {
+            let mut conns = self.connections.write().await;
+            let closed = conns.len();
+            for (device_id, mut conn) in conns.drain() {
+                debug!("Closing connection to {}", device_id);
+                if let Err(e) = conn.handler.close().await {
+                    warn!("Error closing connection to {}: {}", device_id, e);
+                }
+            }
+            closed
+        };
+        self.report_progress("connections", format!("closed {} connections", closed));
+
+        // Save trust store
+        match self.trust_store.save() {
+            Ok(()) => self.report_progress("trust-store", "saved"),
+            Err(e) => warn!("Error saving trust store: {}", e),
+        }
+
+        // Update state
+        {
+            let mut state = self.state.write().await;
+            *state = OrchestratorState::Stopped;
+        }
+        self.record_state(OrchestratorState::Stopped);
+        self.emit_event(OrchestratorEvent::StateChanged(OrchestratorState::Stopped));
+
+        info!("Daemon orchestrator stopped");
+        Ok(())
+    }
+
+    /// Reports a completed step of a graceful stop.
+    fn report_progress(&self, subsystem: &str, detail: impl Into<String>) {
+        let detail = detail.into();
+        info!(subsystem, detail = %detail, "Shutdown progress");
+        self.emit_event(OrchestratorEvent::ShutdownProgress {
+            subsystem: subsystem.to_string(),
+            detail,
+        });
+    }
+
+    /// Records a state transition in the data directory, once started.
+    fn record_state(&self, state: OrchestratorState) {
+        if let Some(recorder) = &self.state_recorder {
+            recorder.set_state(state);
+        }
+    }
+
+    /// Records the number of sessions and connected devices every
+    /// [`STATE_RECORD_INTERVAL`], so that a crash report can tell what was
+    /// lost.
+    async fn record_counts(
+        recorder: Arc<StateRecorder>,
+        session_manager: Arc<SessionManagerImpl>,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        shutdown_token: CancellationToken,
+    ) {
+        let mut ticks = tokio::time::interval(STATE_RECORD_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                _ = ticks.tick() => {}
+            }
+            let device_count = connections.read().await.len();
+            recorder.set_counts(session_manager.count(), device_count);
+        }
+    }
+
+    /// Gets the path to the PID file.
+    fn get_pid_file_path(&self) -> PathBuf {
+        crate::ipc::get_pid_file_path(self.config.daemon.instance.as_deref())
+    }
+
+    /// Creates the PID file with the current process ID.
+    fn create_pid_file(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, std::process::id().to_string())
+    }
+
+    /// Removes the PID file.
+    fn remove_pid_file(&self, path: &Path) {
+        if let Err(e) = std::fs::remove_file(path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove PID file: {}", e);
+            }
+        }
+    }
+
+    /// Handles IPC requests in a separate task.
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_ipc_requests(
+        server: IpcServer,
+        session_manager: Arc<SessionManagerImpl>,
+        start_time: Option<Instant>,
+        shutdown_token: CancellationToken,
+        stop_requested: CancellationToken,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        trust_store: Arc<TrustStore>,
+        log_buffer: LogBuffer,
+        webhooks: Webhooks,
+        quic_info: Option<QuicEndpointInfo>,
+        recovered: Option<CrashRecovery>,
+        confirmations: Option<Arc<SessionConfirmations>>,
+        mesh: Option<Arc<MeshPeers>>,
+        maintenance: Arc<MaintenanceMode>,
+        unit_token: CancellationToken,
+    ) -> Result<()> {
+        loop {
+            tokio::select! {
+                _ = unit_token.cancelled() => {
+                    debug!("IPC server received shutdown signal");
+                    return Ok(());
+                }
+                result = server.accept() => {
+                    match result {
+                        Ok(mut conn) => {
+                            let session_manager = Arc::clone(&session_manager);
+                            let shutdown_token = shutdown_token.clone();
+                            let stop_requested = stop_requested.clone();
+                            let event_tx = event_tx.clone();
+                            let connections = Arc::clone(&connections);
+                            let trust_store = Arc::clone(&trust_store);
+                            let log_buffer = log_buffer.clone();
+                            let webhooks = webhooks.clone();
+                            let quic_info = quic_info.clone();
+                            let recovered = recovered.clone();
+                            let confirmations = confirmations.clone();
+                            let mesh = mesh.clone();
+                            let maintenance = Arc::clone(&maintenance);
+                            tokio::spawn(async move {
+                                let principal = Principal::ipc_peer(conn.peer_uid());
+                                while let Ok(Some(request)) = conn.read_request().await {
+                                    let operation = Operation::of_ipc_request(&request);
+                                    if let Err(e) = authz::authorize(&principal, operation) {
+                                        let response = IpcResponse::Error {
+                                            message: e.to_string(),
+                                        };
+                                        if conn.send_response(&response).await.is_err() {
+                                            break;
+                                        }
+                                        continue;
+                                    }
+                                    // Log subscriptions stream many responses and own
+                                    // the connection until the client goes away
+                                    if let IpcRequest::LogsSubscribe { level, follow } = &request {
+                                        Self::stream_logs(
+                                            &mut conn,
+                                            &log_buffer,
+                                            level.as_deref(),
+                                            *follow,
+                                            &shutdown_token,
+                                        )
+                                        .await;
+                                        break;
+                                    }
+                                    // Subscribe before requesting a stop so that no
+                                    // progress is missed
+                                    let stop_progress = matches!(request, IpcRequest::Stop)
+                                        .then(|| event_tx.subscribe());
+                                    let response = Self::handle_ipc_request(
+                                        &request,
+                                        &session_manager,
+                                        start_time,
+                                        &stop_requested,
+                                        &connections,
+                                        &trust_store,
+                                        &webhooks,
+                                        quic_info.as_ref(),
+                                        recovered.as_ref(),
+                                        confirmations.as_deref(),
+                                        mesh.as_deref(),
+                                        &maintenance,
+                                    )
+                                    .await;
+                                    if conn.send_response(&response).await.is_err() {
+                                        break;
+                                    }
+                                    // A stop reports its progress until the daemon
+                                    // has stopped
+                                    if let Some(events) = stop_progress {
+                                        Self::send_stop_progress(&mut conn, events).await;
+                                        break;
+                                    }
+                                }
+                            });
+                        }
+                        Err(e) => {
+                            return Err(e).context("Failed to accept IPC connection");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Streams buffered and (optionally) live log lines to an IPC client.
+    async fn stream_logs(
+        conn: &mut IpcConnection,
+        log_buffer: &LogBuffer,
+        level: Option<&str>,
+        follow: bool,
+        shutdown_token: &CancellationToken,
+    ) {
+        let min_level = match level {
+            Some(name) => match parse_level(name) {
+                Some(level) => level,
+                None => {
+                    let _ = conn
+                        .send_response(&IpcResponse::Error {
+                            message: format!("Invalid log level: {}", name),
+                        })
+                        .await;
+                    return;
+                }
+            },
+            None => tracing::Level::TRACE,
+        };
+
+        // Subscribe before taking the snapshot so no line falls in between
+        let mut live_rx = log_buffer.subscribe();
+
+        for line in log_buffer.snapshot(min_level) {
+            if conn
+                .send_response(&IpcResponse::LogLine(line))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        if !follow {
+            let _ = conn.send_response(&IpcResponse::LogsEnd).await;
+            return;
+        }
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => {
+                    let _ = conn.send_response(&IpcResponse::LogsEnd).await;
+                    return;
+                }
+                result = live_rx.recv() => {
+                    match result {
+                        Ok(line) => {
+                            if !line.matches_level(min_level) {
+                                continue;
+                            }
+                            if conn.send_response(&IpcResponse::LogLine(line)).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            debug!("Log subscriber lagged, skipped {} lines", skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => {
+                            let _ = conn.send_response(&IpcResponse::LogsEnd).await;
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends the progress of a stop to an IPC client until the daemon has
+    /// stopped.
+    async fn send_stop_progress(
+        conn: &mut IpcConnection,
+        mut events: broadcast::Receiver<OrchestratorEvent>,
+    ) {
+        loop {
+            let response = match events.recv().await {
+                Ok(OrchestratorEvent::ShutdownProgress { subsystem, detail }) => {
+                    IpcResponse::StopProgress { subsystem, detail }
+                }
+                Ok(OrchestratorEvent::StateChanged(OrchestratorState::Stopped))
+                | Err(broadcast::error::RecvError::Closed) => return,
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("Stop progress lagged, skipped {} events", skipped);
+                    continue;
+                }
+            };
+            if conn.send_response(&response).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Handles a single IPC request and returns the response.
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_ipc_request(
+        request: &IpcRequest,
+        session_manager: &Arc<SessionManagerImpl>,
+        start_time: Option<Instant>,
+        stop_requested: &CancellationToken,
+        connections: &Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        trust_store: &TrustStore,
+        webhooks: &Webhooks,
+        quic_info: Option<&QuicEndpointInfo>,
+        recovered: Option<&CrashRecovery>,
+        confirmations: Option<&SessionConfirmations>,
+        mesh: Option<&MeshPeers>,
+        maintenance: &MaintenanceMode,
+    ) -> IpcResponse {
+        match request {
+            IpcRequest::Ping => IpcResponse::Pong,
+            IpcRequest::Status => {
+                let uptime_secs = start_time.map(|t| t.elapsed().as_secs()).unwrap_or(0);
+                let session_count = session_manager.count();
+                let device_count = connections.read().await.len();
+                IpcResponse::Status {
+                    running: true,
+                    uptime_secs,
+                    session_count,
+                    device_count,
+                    recovered_from_crash: recovered.cloned(),
+                    maintenance: maintenance.state(),
+                }
+            }
+            IpcRequest::Stop => {
+                info!("Received stop request via IPC");
+                stop_requested.cancel();
+                IpcResponse::Stopping
+            }
+            IpcRequest::ListSessions => {
+                let sessions = session_manager
+                    .list()
+                    .into_iter()
+                    .map(|s| IpcSessionInfo {
+                        id: s.id.to_string(),
+                        connected_at: 0, // TODO: Track actual connection time
+                        peer_id: None,   // TODO: Track peer ID when available
+                        cwd: s.shell.cwd,
+                        last_exit_code: s.shell.last_exit_code,
+                    })
+                    .collect();
+                IpcResponse::Sessions { sessions }
+            }
+            IpcRequest::KillSession { session_id, signal } => {
+                // Use provided signal or default to SIGTERM (15)
+                let sig = signal.unwrap_or(15);
+                match session_manager.kill(session_id, Some(sig)).await {
+                    Ok(_) => IpcResponse::SessionKilled {
+                        session_id: session_id.clone(),
+                    },
+                    Err(e) => IpcResponse::Error {
+                        message: e.to_string(),
+                    },
+                }
+            }
+            IpcRequest::ReloadTrustStore => {
+                let trusted_before = Self::trusted_device_ids(trust_store);
+                match trust_store.load().and_then(|_| trust_store.list_devices()) {
+                    Ok(devices) => {
+                        info!("Reloaded trust store via IPC ({} devices)", devices.len());
+                        // Devices revoked with `devices revoke` while the daemon runs
+                        for device in &devices {
+                            if device.trust_level != TrustLevel::Revoked {
+                                continue;
+                            }
+                            let closed = session_manager
+                                .close_owned_by(&device.device_id, SessionCloseReason::OwnerRevoked)
+                                .await;
+                            if closed > 0 {
+                                info!(
+                                    device_id = %device.device_id,
+                                    count = closed,
+                                    "Closed sessions of revoked device"
+                                );
+                            }
+                        }
+                        // Devices trusted with `devices trust` while the daemon runs
+                        for device in &devices {
+                            if device.trust_level == TrustLevel::Trusted
+                                && !trusted_before.contains(&device.device_id)
+                            {
+                                webhooks.emit(
+                                    WebhookEvent::DeviceApproved,
+                                    serde_json::json!({
+                                        "device_id": device.device_id.to_string(),
+                                        "name": device.name,
+                                        "method": "trust_store",
+                                    }),
+                                );
+                            }
+                        }
+                        IpcResponse::TrustStoreReloaded {
+                            device_count: devices.len(),
+                        }
+                    }
+                    Err(e) => IpcResponse::Error {
+                        message: format!("Failed to reload trust store: {:#}", e),
+                    },
+                }
+            }
+            IpcRequest::PairingStatus { code } => {
+                match PairingCodeStore::for_trust_store(trust_store.path()).status(code) {
+                    Ok(status) => IpcResponse::PairingStatus { status },
+                    Err(e) => IpcResponse::Error {
+                        message: format!("Failed to read pairing codes: {:#}", e),
+                    },
+                }
+            }
+            IpcRequest::NetworkInfo => IpcResponse::NetworkInfo {
+                quic: quic_info.cloned(),
+            },
+            IpcRequest::SetMaintenance { enabled, message } => {
+                if *enabled {
+                    maintenance.enable(message.clone());
+                } else {
+                    maintenance.disable();
+                }
+                IpcResponse::Maintenance {
+                    state: maintenance.state(),
+                }
+            }
+            IpcRequest::MeshStatus => match mesh {
+                Some(mesh) => IpcResponse::MeshStatus {
+                    group: mesh.group().to_string(),
+                    hosts: mesh.hosts(),
+                },
+                None => IpcResponse::Error {
+                    message: "Mesh mode is not enabled (mesh.enabled)".to_string(),
+                },
+            },
+            IpcRequest::ListSessionConfirmations => match confirmations {
+                Some(confirmations) => IpcResponse::SessionConfirmations {
+                    pending: confirmations
+                        .pending()
+                        .into_iter()
+                        .map(|pending| IpcPendingSession {
+                            id: pending.id,
+                            device_id: pending.device_id.to_string(),
+                            device_name: pending.device_name,
+                            expires_in_secs: pending.expires_in_secs,
+                        })
+                        .collect(),
+                },
+                None => Self::session_confirmation_disabled(),
+            },
+            IpcRequest::AnswerSessionConfirmation { id, allow } => match confirmations {
+                Some(confirmations) if confirmations.answer(*id, *allow) => {
+                    info!(
+                        target: "audit",
+                        request = id,
+                        allowed = allow,
+                        "Session confirmation answered via IPC"
+                    );
+                    IpcResponse::SessionConfirmationAnswered {
+                        id: *id,
+                        allowed: *allow,
+                    }
+                }
+                Some(_) => IpcResponse::Error {
+                    message: format!("No session is waiting for confirmation as request {}", id),
+                },
+                None => Self::session_confirmation_disabled(),
+            },
+            // Streaming requests are handled by the connection loop
+            IpcRequest::LogsSubscribe { .. } => IpcResponse::Error {
+                message: "Log subscriptions must be streamed".to_string(),
+            },
+        }
+    }
+
+    /// Answers a session confirmation request when confirmation is off.
+    fn session_confirmation_disabled() -> IpcResponse {
+        IpcResponse::Error {
+            message: "Session confirmation is not enabled (security.session_confirmation)"
+                .to_string(),
+        }
+    }
+
+    /// Returns the IDs of the devices currently trusted.
+    fn trusted_device_ids(trust_store: &TrustStore) -> std::collections::HashSet<DeviceId> {
+        trust_store
+            .list_devices()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|device| device.trust_level == TrustLevel::Trusted)
+            .map(|device| device.device_id)
+            .collect()
+    }
+
+    /// Emits an orchestrator event.
+    fn emit_event(&self, event: OrchestratorEvent) {
+        let _ = self.event_tx.send(event);
+    }
+
+    /// Returns the notifier for pushing notifications to connected clients.
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+
+    /// Returns the handle for reporting events to the configured webhooks.
+    pub fn webhooks(&self) -> &Webhooks {
+        &self.webhooks
+    }
+
+    /// Returns the session manager.
+    pub fn session_manager(&self) -> &Arc<SessionManagerImpl> {
+        &self.session_manager
+    }
+
+    /// Returns the trust store.
+    pub fn trust_store(&self) -> &Arc<TrustStore> {
+        &self.trust_store
+    }
+
+    /// Returns the sessions waiting for the operator to allow them, if every
+    /// new session must be confirmed.
+    pub fn session_confirmations(&self) -> Option<&Arc<SessionConfirmations>> {
+        self.session_confirmations.as_ref()
+    }
+
+    /// Returns maintenance mode, to show or change whether it is on.
+    pub fn maintenance(&self) -> &Arc<MaintenanceMode> {
+        &self.maintenance
+    }
+
+    /// Returns the message router.
+    pub fn router(&self) -> &Arc<MessageRouter<SessionManagerImpl>> {
+        &self.router
+    }
+
+    /// Returns the number of active connections.
+    pub async fn connection_count(&self) -> usize {
+        self.connections.read().await.len()
+    }
+
+    /// Attaches an established connection and starts routing its messages.
+    ///
+    /// WebRTC connections are attached when their offer arrives over
+    /// signaling. This is the entry point for other transports, such as the
+    /// in-memory loopback used to simulate network conditions in tests.
+    /// A connection the device already has is replaced, as when the device
+    /// migrates to another transport.
+    pub async fn attach_connection(
+        &self,
+        device_id: impl Into<String>,
+        handler: Box<dyn Connection>,
+    ) {
+        let connection = ActiveConnection {
+            device_id: device_id.into(),
+            id: next_connection_id(),
+            handler,
+            #[cfg(feature = "webrtc")]
+            peer_connection: None,
+            noise_complete: false,
+        };
+        Self::register_connection(
+            &self.connections,
+            &self.router,
+            &self.event_tx,
+            &self.shutdown_token,
+            connection,
+        )
+        .await;
+    }
+
+    /// Returns a shared reference to the active connections map.
+    pub fn connections(&self) -> Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>> {
+        Arc::clone(&self.connections)
+    }
+
+    /// Returns the subsystem supervisor.
+    pub fn supervisor(&self) -> &Arc<Supervisor> {
+        &self.supervisor
+    }
+
+    /// Returns the shutdown token for external tasks to observe shutdown.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown_token.clone()
+    }
+
+    /// Returns a token cancelled when a stop is requested over IPC; the
+    /// caller running the daemon then calls [`stop`](Self::stop).
+    pub fn stop_requested(&self) -> CancellationToken {
+        self.stop_requested.clone()
+    }
+
+    /// Returns the directory browser for file listing operations.
+    pub fn directory_browser(&self) -> &Arc<DirectoryBrowser> {
+        &self.directory_browser
+    }
+
+    /// Returns the file transfer handler for upload/download operations.
+    pub fn file_transfer(&self) -> &Arc<FileTransfer> {
+        &self.file_transfer
+    }
+
+    /// Returns the device identity.
+    pub fn identity(&self) -> &DeviceIdentity {
+        &self.identity
+    }
+
+    /// Returns the signaling URL.
+    pub fn signaling_url(&self) -> &str {
+        &self.config.network.signaling_url
+    }
+}
+
+/// Stand-ins for builds without the `quic` feature, where configuration
+/// validation keeps QUIC disabled.
+#[cfg(not(feature = "quic"))]
+impl DaemonOrchestrator {
+    async fn bind_quic(&mut self, _proxies: &ProxySettings) -> Result<Option<QuicEndpointInfo>> {
+        Ok(None)
+    }
+
+    fn spawn_quic_listener(&self) {}
+}
+
+/// Stand-ins for builds without the `webrtc` feature, where configuration
+/// validation keeps WebRTC disabled.
+#[cfg(not(feature = "webrtc"))]
+impl DaemonOrchestrator {
+    async fn start_signaling(&self, _proxies: ProxySettings) {}
+
+    async fn disconnect_signaling(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_config(temp_dir: &TempDir) -> Config {
+        let mut config = Config::default();
+        config.daemon.data_dir = temp_dir.path().to_path_buf();
+        config.file.allowed_paths = vec![temp_dir.path().to_path_buf()];
+        config
+    }
+
+    #[tokio::test]
+    async fn test_orchestrator_creation() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = create_test_config(&temp_dir);
+
+        let orchestrator = DaemonOrchestrator::new(config);
+        assert!(orchestrator.is_ok());
+
+        let orchestrator = orchestrator.unwrap();
+        assert_eq!(orchestrator.state().await, OrchestratorState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_identity_generation() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let identity_path = temp_dir.path().join("identity.key");
+
+        // First call should generate new identity
+        let identity1 = DaemonOrchestrator::load_or_generate_identity(&identity_path).unwrap();
+        assert!(identity_path.exists());
+        let mode = std::fs::metadata(&identity_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Second call should load existing identity
+        let identity2 = DaemonOrchestrator::load_or_generate_identity(&identity_path).unwrap();
+        assert_eq!(identity1.device_id(), identity2.device_id());
+    }
+
+    #[tokio::test]
+    async fn test_orchestrator_loads_data_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let identity = DaemonOrchestrator::load_or_generate_identity(
+            &temp_dir.path().join(IDENTITY_FILE_NAME),
+        )
+        .unwrap();
+
+        let orchestrator = DaemonOrchestrator::new(create_test_config(&temp_dir)).unwrap();
+        assert_eq!(
+            orchestrator.device_id_fingerprint(),
+            identity.device_id().fingerprint()
+        );
+
+        // A broken trust store still fails startup, whichever file loads first
+        std::fs::write(temp_dir.path().join(TRUST_STORE_FILE_NAME), "not json").unwrap();
+        let error = DaemonOrchestrator::new(create_test_config(&temp_dir))
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("Failed to load trust store"));
+    }
+
+    #[tokio::test]
+    async fn test_orchestrator_state_transitions() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = create_test_config(&temp_dir);
+
+        let orchestrator = DaemonOrchestrator::new(config).unwrap();
+
+        // Initial state should be Stopped
+        assert_eq!(orchestrator.state().await, OrchestratorState::Stopped);
+
+        // Note: Full start/stop tests require a running signaling server
+    }
+
+    #[tokio::test]
+    async fn test_device_id_fingerprint() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = create_test_config(&temp_dir);
+
+        let orchestrator = DaemonOrchestrator::new(config).unwrap();
+        let fingerprint = orchestrator.device_id_fingerprint();
+
+        // Fingerprint should be in the format "xxxx:xxxx:xxxx:xxxx:xxxx:xxxx:xxxx:xxxx"
+        assert!(fingerprint.contains(':'));
+        assert!(!fingerprint.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_event_subscription() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = create_test_config(&temp_dir);
+
+        let orchestrator = DaemonOrchestrator::new(config).unwrap();
+        let _receiver = orchestrator.subscribe();
+
+        // Just verify we can subscribe without errors
+    }
+
+    #[tokio::test]
+    async fn test_connection_count() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = create_test_config(&temp_dir);
+
+        let orchestrator = DaemonOrchestrator::new(config).unwrap();
+        assert_eq!(orchestrator.connection_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_notification() {
+        use crate::network::loopback::{self, SimulatedLink};
+        use crate::network::ChannelType;
+        use protocol::messages::{Envelope, Message};
+
+        let connections = RwLock::new(std::collections::HashMap::new());
+        let mut clients = Vec::new();
+        let mut device_ids = Vec::new();
+        for byte in [1u8, 2] {
+            let device_id = DeviceId::from_bytes([byte; 16]);
+            let (client, daemon_end) = loopback::pair(SimulatedLink::default());
+            connections.write().await.insert(
+                device_id.to_string(),
+                ActiveConnection {
+                    device_id: device_id.to_string(),
+                    id: next_connection_id(),
+                    handler: Box::new(daemon_end),
+                    #[cfg(feature = "webrtc")]
+                    peer_connection: None,
+                    noise_complete: true,
+                },
+            );
+            clients.push(client);
+            device_ids.push(device_id);
+        }
+        let notice = |title: &str| {
+            Notification::new(
+                NotificationLevel::Info,
+                NotificationCategory::General,
+                title,
+                "",
+            )
+        };
+
+        DaemonOrchestrator::deliver_notification(
+            &connections,
+            1,
+            Recipient::Device(device_ids[0]),
+            notice("first"),
+        )
+        .await;
+        DaemonOrchestrator::deliver_notification(&connections, 2, Recipient::All, notice("all"))
+            .await;
+
+        let mut received = Vec::new();
+        for client in &mut clients {
+            let mut titles = Vec::new();
+            while let Ok(Ok(data)) = tokio::time::timeout(
+                Duration::from_millis(100),
+                client.recv(ChannelType::Control),
+            )
+            .await
+            {
+                match Envelope::from_msgpack(&data).unwrap().payload {
+                    Message::Notification(n) => titles.push(n.title),
+                    other => panic!("Expected Notification, got {:?}", other),
+                }
+            }
+            received.push(titles);
+        }
+        assert_eq!(received, vec![vec!["first", "all"], vec!["all"]]);
+    }
+
+    #[tokio::test]
+    async fn test_grant_ends_on_first_disconnect() {
+        let temp_dir = TempDir::new().unwrap();
+        let orchestrator = DaemonOrchestrator::new(create_test_config(&temp_dir)).unwrap();
+        let trust_store = Arc::clone(&orchestrator.trust_store);
+        let identity = DeviceIdentity::generate();
+        let device_id = *identity.device_id();
+        trust_store
+            .add_device(crate::devices::TrustedDevice::new_unknown(
+                device_id,
+                "Guest".to_string(),
+                identity.public_key_bytes(),
+            ))
+            .unwrap();
+        trust_store
+            .grant_temporary(
+                &device_id,
+                Duration::from_secs(1800),
+                [crate::devices::SHELL_ACCESS.to_string()].into(),
+            )
+            .unwrap();
+
+        let mut events = orchestrator.subscribe();
+        let token = CancellationToken::new();
+        let task = tokio::spawn(DaemonOrchestrator::run_grant_expiry_task(
+            Arc::clone(&trust_store),
+            Arc::clone(&orchestrator.session_manager),
+            Arc::clone(&orchestrator.connections),
+            orchestrator.event_tx.clone(),
+            token.clone(),
+        ));
+        // Let the task subscribe before the device disconnects
+        tokio::task::yield_now().await;
+        orchestrator.emit_event(OrchestratorEvent::PeerDisconnected {
+            device_id: device_id.to_string(),
+            reason: "closed".to_string(),
+        });
+
+        let ended = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Ok(OrchestratorEvent::GrantEnded { name, reason, .. }) = events.recv().await
+                {
+                    return (name, reason);
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(ended, ("Guest".to_string(), "disconnected".to_string()));
+        assert!(trust_store.get_device(&device_id).unwrap().is_none());
+
+        token.cancel();
+        task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_grant_ends_on_disconnect_missed_while_lagging() {
+        let temp_dir = TempDir::new().unwrap();
+        let orchestrator = DaemonOrchestrator::new(create_test_config(&temp_dir)).unwrap();
+        let trust_store = Arc::clone(&orchestrator.trust_store);
+        let identity = DeviceIdentity::generate();
+        let device_id = *identity.device_id();
+        trust_store
+            .add_device(crate::devices::TrustedDevice::new_unknown(
+                device_id,
+                "Guest".to_string(),
+                identity.public_key_bytes(),
+            ))
+            .unwrap();
+        trust_store
+            .grant_temporary(
+                &device_id,
+                Duration::from_secs(1800),
+                [crate::devices::SHELL_ACCESS.to_string()].into(),
+            )
+            .unwrap();
+
+        // Room for a single event, so that a burst makes the task lag
+        let (event_tx, _) = broadcast::channel(1);
+        let token = CancellationToken::new();
+        let task = tokio::spawn(DaemonOrchestrator::run_grant_expiry_task(
+            Arc::clone(&trust_store),
+            Arc::clone(&orchestrator.session_manager),
+            Arc::clone(&orchestrator.connections),
+            event_tx.clone(),
+            token.clone(),
+        ));
+        tokio::task::yield_now().await;
+        event_tx
+            .send(OrchestratorEvent::PeerConnected {
+                device_id: device_id.to_string(),
+            })
+            .unwrap();
+        tokio::task::yield_now().await;
+        assert!(trust_store.get_device(&device_id).unwrap().is_some());
+
+        // The disconnection is pushed out by later events before the task
+        // reads it
+        event_tx
+            .send(OrchestratorEvent::PeerDisconnected {
+                device_id: device_id.to_string(),
+                reason: "closed".to_string(),
+            })
+            .unwrap();
+        for _ in 0..3 {
+            event_tx
+                .send(OrchestratorEvent::PeerConnected {
+                    device_id: "other".to_string(),
+                })
+                .unwrap();
+        }
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while trust_store.get_device(&device_id).unwrap().is_some() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        token.cancel();
+        task.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_crash_handler_emits_webhook() {
+        let temp_dir = TempDir::new().unwrap();
+        let (webhooks, mut events) = Webhooks::channel("daemon".to_string());
+        let handler = DaemonOrchestrator::crash_handler(temp_dir.path(), webhooks);
+
+        handler.handle(&crash::CrashReport {
+            timestamp_ms: 1700000000000,
+            task: Some("router".to_string()),
+            thread: None,
+            message: "boom".to_string(),
+            location: Some("src/router.rs:1:1".to_string()),
+            backtrace: String::new(),
+            version: "0.0.0".to_string(),
+        });
+
+        let payload = events.try_recv().unwrap();
+        assert_eq!(payload.event, WebhookEvent::Crash);
+        assert_eq!(payload.data["task"], "router");
+        assert_eq!(payload.data["message"], "boom");
+        let report = payload.data["report"].as_str().unwrap();
+        assert!(Path::new(report).starts_with(crash::crash_dir(temp_dir.path())));
+    }
+
+    #[tokio::test]
+    async fn test_monitored_task_reports_panic() {
+        let (event_tx, mut events) = broadcast::channel(16);
+
+        DaemonOrchestrator::spawn_monitored("monitored", event_tx, async {
+            panic!("monitored failure");
+        });
+
+        match tokio::time::timeout(Duration::from_secs(5), events.recv()).await {
+            Ok(Ok(OrchestratorEvent::TaskPanicked {
+                task, restarting, ..
+            })) => {
+                assert_eq!(task, "monitored");
+                assert!(!restarting);
+            }
+            other => panic!("Expected TaskPanicked event, got {:?}", other),
+        }
+    }
+}
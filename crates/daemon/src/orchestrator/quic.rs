@@ -0,0 +1,138 @@
+//! QUIC connections from native clients.
+//!
+//! Built with the `quic` feature.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use protocol::crypto::PeerIdentity;
+use tokio::sync::{broadcast, RwLock};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::{next_connection_id, ActiveConnection, DaemonOrchestrator, OrchestratorEvent};
+use crate::ipc::QuicEndpointInfo;
+use crate::network::{ProxySettings, QuicConfig, QuicListener};
+use crate::router::MessageRouter;
+use crate::session::SessionManagerImpl;
+
+impl DaemonOrchestrator {
+    /// Binds the QUIC endpoint for native clients, if enabled, and returns
+    /// what IPC reports about it.
+    pub(super) async fn bind_quic(
+        &mut self,
+        proxies: &ProxySettings,
+    ) -> Result<Option<QuicEndpointInfo>> {
+        let quic = &self.config.network.quic;
+        if !quic.enabled {
+            return Ok(None);
+        }
+        let mut quic_config = QuicConfig::default().bind_addresses(quic.bind_ips()?);
+        if let Some(ports) = quic.ports() {
+            quic_config = quic_config.port_range(ports);
+        }
+        if !quic.relay {
+            quic_config = quic_config.without_relay();
+        } else if let Some(proxy) = proxies.relay_proxy() {
+            quic_config = quic_config.proxy_url(proxy.clone());
+        }
+        let secret_key = iroh::SecretKey::from_bytes(&self.identity.secret_key_bytes());
+        let listener = QuicListener::bind(quic_config, secret_key)
+            .await
+            .context("Failed to bind the QUIC endpoint")?;
+        info!(addresses = ?listener.bound_addresses(), "Accepting QUIC connections");
+        let info = QuicEndpointInfo {
+            node_id: listener.node_id().to_string(),
+            bound_addresses: listener.bound_addresses(),
+            relay: listener.relay_enabled(),
+        };
+        self.quic_listener = Some(Arc::new(listener));
+        Ok(Some(info))
+    }
+
+    /// Accepts connections on the QUIC endpoint, if bound.
+    pub(super) fn spawn_quic_listener(&self) {
+        if let Some(listener) = &self.quic_listener {
+            Self::spawn_monitored(
+                "quic-listener",
+                self.event_tx.clone(),
+                Self::accept_quic_connections(
+                    Arc::clone(listener),
+                    Arc::clone(&self.connections),
+                    Arc::clone(&self.router),
+                    self.event_tx.clone(),
+                    self.shutdown_token.clone(),
+                ),
+            );
+        }
+    }
+
+    /// Accepts QUIC connections until shutdown, then closes the endpoint.
+    ///
+    /// The peer's iroh node ID is its Ed25519 identity key, so the device ID
+    /// is derived from it.
+    async fn accept_quic_connections(
+        listener: Arc<QuicListener>,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        router: Arc<MessageRouter<SessionManagerImpl>>,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+        shutdown_token: CancellationToken,
+    ) {
+        loop {
+            let incoming = tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                incoming = listener.accept() => match incoming {
+                    Some(incoming) => incoming,
+                    None => break,
+                },
+            };
+
+            let listener = Arc::clone(&listener);
+            let connections = Arc::clone(&connections);
+            let router = Arc::clone(&router);
+            let event_tx_for_handshake = event_tx.clone();
+            let shutdown_token = shutdown_token.clone();
+            Self::spawn_monitored("quic-handshake", event_tx.clone(), async move {
+                let handler = tokio::select! {
+                    _ = shutdown_token.cancelled() => return,
+                    result = listener.establish(incoming) => match result {
+                        Ok(handler) => handler,
+                        Err(e) => {
+                            debug!(error = %e, "QUIC handshake abandoned");
+                            return;
+                        }
+                    },
+                };
+                let Some(peer) = handler.peer_node_id().and_then(|node_id| {
+                    PeerIdentity::from_public_key_bytes(node_id.as_bytes()).ok()
+                }) else {
+                    warn!("QUIC peer has no valid identity");
+                    let _ = handler.close().await;
+                    return;
+                };
+
+                let device_id = peer.device_id().fingerprint();
+                info!(device_id = %device_id, "Accepted QUIC connection");
+                let connection = ActiveConnection {
+                    device_id,
+                    id: next_connection_id(),
+                    handler: Box::new(handler),
+                    #[cfg(feature = "webrtc")]
+                    peer_connection: None,
+                    noise_complete: false,
+                };
+                Self::register_connection(
+                    &connections,
+                    &router,
+                    &event_tx_for_handshake,
+                    &shutdown_token,
+                    connection,
+                )
+                .await;
+            });
+        }
+
+        listener.close().await;
+        debug!("QUIC endpoint closed");
+    }
+}
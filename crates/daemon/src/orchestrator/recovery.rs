@@ -0,0 +1,262 @@
+//! Persisted orchestrator state, for noticing unclean shutdowns.
+//!
+//! The orchestrator records its state and its last-known session and device
+//! counts in the data directory as they change. A clean stop records
+//! `Stopped`; finding any other state when the daemon next starts means the
+//! previous daemon crashed or was killed, and the sessions it held were
+//! lost. The report of that is kept in the file until the next clean stop,
+//! so `remoshell status` can show it.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::OrchestratorState;
+
+/// Name of the state file inside the data directory.
+pub const STATE_FILE_NAME: &str = "daemon-state.json";
+
+/// The orchestrator state as last recorded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PersistedState {
+    /// The orchestrator's state.
+    pub state: OrchestratorState,
+    /// Process ID of the daemon.
+    pub pid: u32,
+    /// Unix timestamp when the daemon started.
+    pub started_at: u64,
+    /// Unix timestamp of the last update.
+    pub updated_at: u64,
+    /// Number of sessions at the last update.
+    pub session_count: usize,
+    /// Number of connected devices at the last update.
+    pub device_count: usize,
+    /// The unclean shutdown the daemon recovered from when it started.
+    #[serde(default)]
+    pub recovered: Option<CrashRecovery>,
+}
+
+/// An unclean shutdown of a previous daemon.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CrashRecovery {
+    /// Unix timestamp when the previous daemon was last known to be running.
+    pub crashed_at: u64,
+    /// State the previous daemon was in.
+    pub state: OrchestratorState,
+    /// Number of sessions lost.
+    pub lost_sessions: usize,
+    /// Number of devices that were connected.
+    pub lost_devices: usize,
+}
+
+impl CrashRecovery {
+    /// Returns the unclean shutdown `previous` describes, if it is not a
+    /// clean stop.
+    pub fn from_previous(previous: &PersistedState) -> Option<Self> {
+        (previous.state != OrchestratorState::Stopped).then_some(Self {
+            crashed_at: previous.updated_at,
+            state: previous.state,
+            lost_sessions: previous.session_count,
+            lost_devices: previous.device_count,
+        })
+    }
+}
+
+/// The state file of a data directory.
+#[derive(Debug, Clone)]
+pub struct StateFile {
+    path: PathBuf,
+}
+
+impl StateFile {
+    /// Returns the state file of `data_dir`.
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(STATE_FILE_NAME),
+        }
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the recorded state, or `None` if no daemon recorded any.
+    pub fn load(&self) -> io::Result<Option<PersistedState>> {
+        let json = match std::fs::read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Records `state`, replacing the file so that a crash while writing
+    /// leaves the previous state in place.
+    pub fn store(&self, state: &PersistedState) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(state).map_err(io::Error::other)?;
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, json)?;
+        std::fs::rename(&temp, &self.path)
+    }
+}
+
+/// Keeps the state file of a running daemon up to date.
+pub struct StateRecorder {
+    file: StateFile,
+    current: Mutex<PersistedState>,
+}
+
+impl StateRecorder {
+    /// Takes over the state file of `data_dir` for this process, reading
+    /// what the previous daemon left in it.
+    ///
+    /// Nothing is written until the first update.
+    pub fn open(data_dir: &Path) -> Self {
+        let file = StateFile::new(data_dir);
+        let previous = file.load().unwrap_or_else(|e| {
+            tracing::warn!(path = ?file.path(), error = %e, "Ignoring unreadable state file");
+            None
+        });
+        let now = unix_now();
+        let current = PersistedState {
+            state: OrchestratorState::Starting,
+            pid: std::process::id(),
+            started_at: now,
+            updated_at: now,
+            session_count: 0,
+            device_count: 0,
+            recovered: previous.as_ref().and_then(CrashRecovery::from_previous),
+        };
+        Self {
+            file,
+            current: Mutex::new(current),
+        }
+    }
+
+    /// Returns the unclean shutdown the daemon recovered from, if any.
+    pub fn recovered(&self) -> Option<CrashRecovery> {
+        self.lock().recovered.clone()
+    }
+
+    /// Records a state transition.
+    ///
+    /// A clean stop also clears the crash report.
+    pub fn set_state(&self, state: OrchestratorState) {
+        self.update(|current| {
+            current.state = state;
+            if state == OrchestratorState::Stopped {
+                current.recovered = None;
+            }
+            true
+        });
+    }
+
+    /// Records the number of sessions and connected devices, if changed.
+    pub fn set_counts(&self, session_count: usize, device_count: usize) {
+        self.update(|current| {
+            let changed =
+                current.session_count != session_count || current.device_count != device_count;
+            current.session_count = session_count;
+            current.device_count = device_count;
+            changed
+        });
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut PersistedState) -> bool) {
+        let mut current = self.lock();
+        if !apply(&mut current) {
+            return;
+        }
+        current.updated_at = unix_now();
+        if let Err(e) = self.file.store(&current) {
+            tracing::warn!(path = ?self.file.path(), error = %e, "Failed to record daemon state");
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PersistedState> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_clean_stop_is_not_a_crash() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let recorder = StateRecorder::open(temp_dir.path());
+        assert_eq!(recorder.recovered(), None);
+        recorder.set_state(OrchestratorState::Running);
+        recorder.set_counts(2, 1);
+        recorder.set_state(OrchestratorState::Stopped);
+
+        let recorded = StateFile::new(temp_dir.path()).load().unwrap().unwrap();
+        assert_eq!(recorded.state, OrchestratorState::Stopped);
+        assert_eq!(StateRecorder::open(temp_dir.path()).recovered(), None);
+    }
+
+    #[test]
+    fn test_unclean_shutdown_is_reported() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let recorder = StateRecorder::open(temp_dir.path());
+        recorder.set_state(OrchestratorState::Running);
+        recorder.set_counts(3, 2);
+        let crashed_at = StateFile::new(temp_dir.path())
+            .load()
+            .unwrap()
+            .unwrap()
+            .updated_at;
+        drop(recorder);
+
+        let recorder = StateRecorder::open(temp_dir.path());
+        let recovered = recorder.recovered().unwrap();
+        assert_eq!(
+            recovered,
+            CrashRecovery {
+                crashed_at,
+                state: OrchestratorState::Running,
+                lost_sessions: 3,
+                lost_devices: 2,
+            }
+        );
+
+        // The report stays in the file until the next clean stop
+        recorder.set_state(OrchestratorState::Running);
+        let recorded = StateFile::new(temp_dir.path()).load().unwrap().unwrap();
+        assert_eq!(recorded.recovered, Some(recovered));
+        recorder.set_state(OrchestratorState::Stopped);
+        let recorded = StateFile::new(temp_dir.path()).load().unwrap().unwrap();
+        assert_eq!(recorded.recovered, None);
+    }
+
+    #[test]
+    fn test_missing_and_corrupt_state_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = StateFile::new(temp_dir.path());
+        assert!(file.load().unwrap().is_none());
+
+        std::fs::write(file.path(), "{").unwrap();
+        assert_eq!(file.load().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(StateRecorder::open(temp_dir.path()).recovered(), None);
+    }
+}
@@ -0,0 +1,410 @@
+//! WebRTC connections set up through the signaling server.
+//!
+//! Built with the `webrtc` feature. Offers arrive from the signaling
+//! server; a WebRTC handler, and with it the ICE agent, is only created for
+//! each offer.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use protocol::crypto::DeviceIdentity;
+use tokio::sync::{broadcast, RwLock};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn, Instrument};
+
+use super::{
+    next_connection_id, ActiveConnection, DaemonOrchestrator, OrchestratorEvent, RestartPolicy,
+    SIGNALING_UNIT,
+};
+use crate::config::Config;
+use crate::network::signaling::{
+    SignalingClient, SignalingConfig, SignalingEvent, WebSocketSignalingClient,
+};
+use crate::network::webrtc::{WebRtcConfig, WebRtcConnectionHandler};
+use crate::network::ProxySettings;
+use crate::router::MessageRouter;
+use crate::session::SessionManagerImpl;
+use crate::telemetry;
+use crate::ui::to_base58;
+
+impl DaemonOrchestrator {
+    /// Supervises signaling; each restart creates a fresh signaling client,
+    /// and WebRTC handlers for new offers are created inside this unit.
+    pub(super) async fn start_signaling(&self, proxies: ProxySettings) {
+        let signaling_slot = Arc::clone(&self.signaling_client);
+        let event_tx = self.event_tx.clone();
+        let shutdown_token = self.shutdown_token.clone();
+        let identity = self.identity.clone();
+        let connections = Arc::clone(&self.connections);
+        let router = Arc::clone(&self.router);
+        let config = self.config.clone();
+
+        self.supervisor
+            .spawn(SIGNALING_UNIT, RestartPolicy::default(), move |token| {
+                let signaling_config = SignalingConfig::new(&config.network.signaling_url)
+                    .with_proxy(proxies.clone())
+                    .with_auto_reconnect(true)
+                    .with_initial_backoff(Duration::from_millis(500))
+                    .with_max_backoff(Duration::from_secs(30));
+                let signaling_client = Arc::new(WebSocketSignalingClient::new(signaling_config));
+                let signaling_slot = Arc::clone(&signaling_slot);
+                let event_tx = event_tx.clone();
+                let shutdown_token = shutdown_token.clone();
+                let identity = identity.clone();
+                let connections = Arc::clone(&connections);
+                let router = Arc::clone(&router);
+                let config = config.clone();
+                Box::pin(async move {
+                    *signaling_slot.write().await = Some(Arc::clone(&signaling_client));
+                    let connection_loop = Arc::clone(&signaling_client).start();
+                    info!("Started signaling client");
+
+                    let result = tokio::select! {
+                        result = Self::handle_signaling_loop(
+                            Arc::clone(&signaling_client),
+                            event_tx,
+                            shutdown_token,
+                            identity,
+                            connections,
+                            router,
+                            config,
+                        ) => result,
+                        _ = connection_loop => {
+                            Err(anyhow::anyhow!("Signaling connection loop exited"))
+                        }
+                        _ = token.cancelled() => Ok(()),
+                    };
+
+                    let _ = signaling_client.disconnect().await;
+                    result
+                })
+            })
+            .await;
+    }
+
+    /// Handles the signaling event loop.
+    ///
+    /// Returns an error if the signaling event stream ends unexpectedly, so
+    /// the supervisor can restart the signaling unit.
+    async fn handle_signaling_loop(
+        signaling_client: Arc<WebSocketSignalingClient>,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+        shutdown_token: CancellationToken,
+        identity: DeviceIdentity,
+        connections: Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        router: Arc<MessageRouter<SessionManagerImpl>>,
+        config: Config,
+    ) -> Result<()> {
+        let Some(mut events) = signaling_client.events() else {
+            anyhow::bail!("Failed to get signaling events receiver - already taken");
+        };
+
+        // Use base58-encoded device ID as the room ID (matches client's pairing data format)
+        let room_id = to_base58(identity.device_id().as_bytes());
+
+        // Join the room — the connection loop will connect once the room is set
+        if let Err(e) = signaling_client.join_room(&room_id).await {
+            error!("Failed to set signaling room: {}", e);
+        }
+        info!("Signaling room set to: {}", room_id);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => {
+                    info!("Signaling loop received shutdown signal");
+                    return Ok(());
+                }
+                event = events.recv() => {
+                    let Some(event) = event else {
+                        anyhow::bail!("Signaling event stream closed");
+                    };
+                    match event {
+                        SignalingEvent::StateChanged(state) => {
+                            debug!("Signaling state changed: {:?}", state);
+                            let _ = event_tx.send(OrchestratorEvent::SignalingStateChanged(state));
+                        }
+                        SignalingEvent::RoomJoined { peer_id, existing_peers } => {
+                            info!("Joined room with peer_id={}, existing_peers={:?}", peer_id, existing_peers);
+                        }
+                        SignalingEvent::OfferReceived { sdp, from_peer_id } => {
+                            info!("Received offer from peer {}", from_peer_id);
+                            Self::handle_offer(
+                                &signaling_client,
+                                &identity,
+                                &connections,
+                                &router,
+                                &event_tx,
+                                &shutdown_token,
+                                &config,
+                                sdp,
+                                Some(from_peer_id),
+                            )
+                            .await;
+                        }
+                        SignalingEvent::AnswerReceived { sdp, from_peer_id } => {
+                            info!("Received answer from peer {}", from_peer_id);
+                            Self::handle_answer(&connections, sdp, Some(from_peer_id)).await;
+                        }
+                        SignalingEvent::IceCandidateReceived {
+                            candidate,
+                            sdp_mid,
+                            sdp_mline_index,
+                            from_peer_id,
+                        } => {
+                            debug!("Received ICE candidate from peer {}", from_peer_id);
+                            Self::handle_ice_candidate(
+                                &connections,
+                                candidate,
+                                sdp_mid,
+                                sdp_mline_index,
+                                Some(from_peer_id),
+                            )
+                            .await;
+                        }
+                        SignalingEvent::PeerJoined { peer_id } => {
+                            info!("Peer joined: {}", peer_id);
+                        }
+                        SignalingEvent::PeerLeft { peer_id } => {
+                            info!("Peer left: {}", peer_id);
+                            let mut conns = connections.write().await;
+                            if conns.remove(&peer_id).is_some() {
+                                let _ = event_tx.send(OrchestratorEvent::PeerDisconnected {
+                                    device_id: peer_id,
+                                    reason: "Peer left signaling room".to_string(),
+                                });
+                            }
+                        }
+                        SignalingEvent::Error { message } => {
+                            error!("Signaling error: {}", message);
+                            let _ = event_tx.send(OrchestratorEvent::Error { message });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handles an incoming WebRTC offer.
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_offer(
+        signaling_client: &Arc<WebSocketSignalingClient>,
+        identity: &DeviceIdentity,
+        connections: &Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        router: &Arc<MessageRouter<SessionManagerImpl>>,
+        event_tx: &broadcast::Sender<OrchestratorEvent>,
+        shutdown_token: &CancellationToken,
+        config: &Config,
+        sdp: String,
+        from_device_id: Option<String>,
+    ) {
+        let device_id = from_device_id
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+
+        // Create WebRTC handler
+        let ice_servers = config
+            .network
+            .stun_servers
+            .iter()
+            .map(crate::network::webrtc::IceServer::stun)
+            .collect();
+        let webrtc_config = WebRtcConfig::with_ice_servers(ice_servers);
+
+        let handler = match WebRtcConnectionHandler::new(webrtc_config, identity.clone()).await {
+            Ok(h) => h,
+            Err(e) => {
+                error!("Failed to create WebRTC handler: {}", e);
+                return;
+            }
+        };
+
+        // Set up incoming data channels
+        handler.setup_incoming_data_channels().await;
+
+        // Parse and set remote description (offer)
+        let offer =
+            match webrtc::peer_connection::sdp::session_description::RTCSessionDescription::offer(
+                sdp,
+            ) {
+                Ok(o) => o,
+                Err(e) => {
+                    error!("Invalid SDP offer: {}", e);
+                    return;
+                }
+            };
+        if let Err(e) = handler.set_remote_description(offer).await {
+            error!("Failed to set remote description: {}", e);
+            return;
+        }
+
+        // Create answer
+        let _answer = match handler.create_answer().await {
+            Ok(a) => a,
+            Err(e) => {
+                error!("Failed to create answer: {}", e);
+                return;
+            }
+        };
+
+        // Gather ICE candidates
+        let answer_with_candidates = match handler.gather_ice_candidates().await {
+            Ok(a) => a,
+            Err(e) => {
+                error!("Failed to gather ICE candidates: {}", e);
+                return;
+            }
+        };
+
+        // Send answer
+        if let Err(e) = signaling_client
+            .send_answer(&answer_with_candidates.sdp, from_device_id.as_deref())
+            .await
+        {
+            error!("Failed to send answer: {}", e);
+            return;
+        }
+
+        // Store the connection right away so ICE candidates reach it, but
+        // only route its messages once the Noise handshake is complete
+        let peer_connection = Arc::clone(handler.peer_connection());
+        let responder = handler.clone();
+        let id = next_connection_id();
+        let replaced = connections.write().await.insert(
+            device_id.clone(),
+            ActiveConnection {
+                device_id: device_id.clone(),
+                id,
+                peer_connection: Some(Arc::clone(&peer_connection)),
+                handler: Box::new(handler),
+                noise_complete: false,
+            },
+        );
+        let migrated = replaced.is_some();
+        if let Some(mut old) = replaced {
+            info!("Device is moving its connection to WebRTC");
+            let _ = old.handler.close().await;
+        }
+
+        let connections = Arc::clone(connections);
+        let router = Arc::clone(router);
+        let event_tx_for_handshake = event_tx.clone();
+        let shutdown_token = shutdown_token.clone();
+        let span = telemetry::connection_span(&device_id);
+        Self::spawn_monitored(
+            "webrtc-handshake",
+            event_tx.clone(),
+            async move {
+                let result = tokio::select! {
+                    _ = shutdown_token.cancelled() => return,
+                    result = async {
+                        responder.wait_for_data_channels().await?;
+                        responder.perform_noise_handshake_responder().await
+                    } => result,
+                };
+
+                let mut conns = connections.write().await;
+                // The device may have reconnected meanwhile
+                let Some(conn) = conns.get_mut(&device_id).filter(|conn| conn.id == id) else {
+                    return;
+                };
+
+                if let Err(e) = result {
+                    warn!(error = %e, "WebRTC handshake failed");
+                    conns.remove(&device_id);
+                    drop(conns);
+                    let _ = peer_connection.close().await;
+                    // The connection this one replaced is gone too
+                    if migrated {
+                        let _ = event_tx_for_handshake.send(OrchestratorEvent::PeerDisconnected {
+                            device_id,
+                            reason: format!("WebRTC handshake failed: {}", e),
+                        });
+                    }
+                    return;
+                }
+                conn.noise_complete = true;
+                drop(conns);
+
+                info!("WebRTC handshake complete");
+                Self::start_connection(
+                    &connections,
+                    &router,
+                    &event_tx_for_handshake,
+                    &shutdown_token,
+                    device_id,
+                    id,
+                    migrated,
+                );
+            }
+            .instrument(span),
+        );
+    }
+
+    /// Handles an incoming WebRTC answer.
+    async fn handle_answer(
+        connections: &Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        sdp: String,
+        from_device_id: Option<String>,
+    ) {
+        let device_id = from_device_id.unwrap_or_else(|| "unknown".to_string());
+
+        let mut conns = connections.write().await;
+        if let Some(conn) = conns.get_mut(&device_id) {
+            let answer = match webrtc::peer_connection::sdp::session_description::RTCSessionDescription::answer(sdp) {
+                Ok(a) => a,
+                Err(e) => {
+                    error!("Invalid SDP answer from device {}: {}", device_id, e);
+                    return;
+                }
+            };
+            let Some(peer_connection) = &conn.peer_connection else {
+                warn!("Received answer for non-WebRTC connection: {}", device_id);
+                return;
+            };
+            if let Err(e) = peer_connection.set_remote_description(answer).await {
+                error!("Failed to set remote description for answer: {}", e);
+            }
+        } else {
+            warn!("Received answer from unknown device: {}", device_id);
+        }
+    }
+
+    /// Handles an incoming ICE candidate.
+    async fn handle_ice_candidate(
+        connections: &Arc<RwLock<std::collections::HashMap<String, ActiveConnection>>>,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_mline_index: Option<u16>,
+        from_device_id: Option<String>,
+    ) {
+        let device_id = from_device_id.unwrap_or_else(|| "unknown".to_string());
+
+        let conns = connections.read().await;
+        if let Some(peer_connection) = conns
+            .get(&device_id)
+            .and_then(|conn| conn.peer_connection.as_ref())
+        {
+            // Add ICE candidate to the peer connection
+            let candidate_init = webrtc::ice_transport::ice_candidate::RTCIceCandidateInit {
+                candidate,
+                sdp_mid,
+                sdp_mline_index,
+                ..Default::default()
+            };
+
+            if let Err(e) = peer_connection.add_ice_candidate(candidate_init).await {
+                error!("Failed to add ICE candidate: {}", e);
+            }
+        } else {
+            warn!("Received ICE candidate from unknown device: {}", device_id);
+        }
+    }
+
+    /// Disconnects the signaling client, if it is running.
+    pub(super) async fn disconnect_signaling(&self) {
+        if let Some(client) = self.signaling_client.read().await.as_ref() {
+            let _ = client.disconnect().await;
+        }
+    }
+}
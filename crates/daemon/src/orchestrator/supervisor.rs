@@ -0,0 +1,423 @@
+//! Supervision of restartable daemon subsystems.
+//!
+//! The [`Supervisor`] owns long-running subsystems (IPC server, signaling,
+//! background maintenance) as named units. When a unit fails or panics it is
+//! restarted with exponential backoff, so a failure in one subsystem does not
+//! require restarting the whole daemon.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use super::OrchestratorEvent;
+use crate::crash;
+
+/// Future returned by a unit factory for a single run of the unit.
+pub type UnitFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+
+/// Default delay before the first restart of a failed unit.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Default upper bound for the restart delay.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Default run time after which a unit's restart backoff is reset.
+pub const DEFAULT_RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// Restart policy for a supervised unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Maximum consecutive restarts before giving up (`None` = unlimited).
+    pub max_restarts: Option<u32>,
+    /// Delay before the first restart.
+    pub initial_backoff: Duration,
+    /// Upper bound for the restart delay.
+    pub max_backoff: Duration,
+    /// A run lasting at least this long resets the backoff and restart count.
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: None,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            reset_after: DEFAULT_RESET_AFTER,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Sets the maximum number of consecutive restarts.
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = Some(max_restarts);
+        self
+    }
+
+    /// Sets the initial restart delay.
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the maximum restart delay.
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Returns the delay before restart number `attempt` (starting at 0).
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.min(16));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Lifecycle state of a supervised unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitState {
+    /// The unit is running.
+    Running,
+    /// The unit failed and is waiting to be restarted.
+    Restarting,
+    /// The unit finished or was shut down.
+    Stopped,
+    /// The unit exhausted its restart budget.
+    Failed,
+}
+
+/// Status snapshot of a supervised unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitStatus {
+    /// Unit name.
+    pub name: &'static str,
+    /// Current lifecycle state.
+    pub state: UnitState,
+    /// Number of restarts since the unit was added.
+    pub restarts: u32,
+    /// The most recent failure, if any.
+    pub last_error: Option<String>,
+}
+
+/// Supervisor for restartable daemon subsystems.
+pub struct Supervisor {
+    /// Token cancelled when the daemon shuts down.
+    shutdown_token: CancellationToken,
+    /// Sender for orchestrator events.
+    event_tx: broadcast::Sender<OrchestratorEvent>,
+    /// Status of each unit by name.
+    units: Arc<RwLock<HashMap<&'static str, UnitStatus>>>,
+    /// Handles of the per-unit supervision tasks.
+    handles: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Supervisor {
+    /// Creates a new supervisor bound to the given shutdown token.
+    pub fn new(
+        shutdown_token: CancellationToken,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+    ) -> Self {
+        Self {
+            shutdown_token,
+            event_tx,
+            units: Arc::new(RwLock::new(HashMap::new())),
+            handles: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Adds a unit and starts it.
+    ///
+    /// `factory` is called with a cancellation token for every run of the
+    /// unit. A run that returns `Ok(())` ends the unit; an error or panic
+    /// triggers a restart according to `policy`.
+    pub async fn spawn<F>(&self, name: &'static str, policy: RestartPolicy, factory: F)
+    where
+        F: Fn(CancellationToken) -> UnitFuture + Send + Sync + 'static,
+    {
+        self.units.write().await.insert(
+            name,
+            UnitStatus {
+                name,
+                state: UnitState::Running,
+                restarts: 0,
+                last_error: None,
+            },
+        );
+
+        let handle = tokio::spawn(Self::supervise(
+            name,
+            policy,
+            factory,
+            self.shutdown_token.clone(),
+            self.event_tx.clone(),
+            Arc::clone(&self.units),
+        ));
+        self.handles.lock().await.push(handle);
+    }
+
+    /// Runs a unit, restarting it on failure until shutdown or budget exhaustion.
+    async fn supervise<F>(
+        name: &'static str,
+        policy: RestartPolicy,
+        factory: F,
+        shutdown_token: CancellationToken,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+        units: Arc<RwLock<HashMap<&'static str, UnitStatus>>>,
+    ) where
+        F: Fn(CancellationToken) -> UnitFuture + Send + Sync + 'static,
+    {
+        let mut consecutive_failures: u32 = 0;
+
+        loop {
+            Self::update(&units, name, |s| s.state = UnitState::Running).await;
+
+            let started = Instant::now();
+            let run = factory(shutdown_token.child_token());
+            let result = tokio::spawn(crash::run_named(name, run)).await;
+
+            if shutdown_token.is_cancelled() {
+                break;
+            }
+
+            let failure = match result {
+                Ok(Ok(())) => {
+                    info!(unit = name, "Unit finished");
+                    break;
+                }
+                Ok(Err(e)) => {
+                    let message = format!("{:#}", e);
+                    error!(unit = name, "Unit failed: {}", message);
+                    message
+                }
+                Err(join_error) if join_error.is_panic() => {
+                    let message = crash::panic_message(join_error.into_panic().as_ref());
+                    error!(unit = name, "Unit panicked: {}", message);
+                    format!("panic: {}", message)
+                }
+                Err(_) => break,
+            };
+
+            if started.elapsed() >= policy.reset_after {
+                consecutive_failures = 0;
+            }
+
+            let restarting = policy
+                .max_restarts
+                .is_none_or(|max| consecutive_failures < max);
+
+            Self::update(&units, name, |s| {
+                s.last_error = Some(failure.clone());
+                s.state = if restarting {
+                    UnitState::Restarting
+                } else {
+                    UnitState::Failed
+                };
+            })
+            .await;
+
+            let _ = event_tx.send(OrchestratorEvent::SubsystemFailed {
+                name: name.to_string(),
+                error: failure,
+                restarting,
+            });
+
+            if !restarting {
+                warn!(unit = name, "Unit exceeded its restart budget, giving up");
+                return;
+            }
+
+            let backoff = policy.backoff_for(consecutive_failures);
+            consecutive_failures += 1;
+            info!(unit = name, "Restarting unit in {:?}", backoff);
+
+            tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                _ = tokio::time::sleep(backoff) => {}
+            }
+
+            Self::update(&units, name, |s| s.restarts += 1).await;
+        }
+
+        Self::update(&units, name, |s| s.state = UnitState::Stopped).await;
+    }
+
+    /// Applies `f` to the status of unit `name`.
+    async fn update(
+        units: &RwLock<HashMap<&'static str, UnitStatus>>,
+        name: &'static str,
+        f: impl FnOnce(&mut UnitStatus),
+    ) {
+        if let Some(status) = units.write().await.get_mut(name) {
+            f(status);
+        }
+    }
+
+    /// Returns the status of a unit by name.
+    pub async fn status(&self, name: &str) -> Option<UnitStatus> {
+        self.units.read().await.get(name).cloned()
+    }
+
+    /// Returns the status of all units, sorted by name.
+    pub async fn statuses(&self) -> Vec<UnitStatus> {
+        let mut statuses: Vec<_> = self.units.read().await.values().cloned().collect();
+        statuses.sort_by_key(|s| s.name);
+        statuses
+    }
+
+    /// Waits for all supervision tasks to finish.
+    ///
+    /// Units only finish once the shutdown token is cancelled, they return
+    /// `Ok(())`, or they exhaust their restart budget.
+    pub async fn join(&self, timeout: Duration) {
+        let handles: Vec<_> = self.handles.lock().await.drain(..).collect();
+        for handle in handles {
+            if tokio::time::timeout(timeout, handle).await.is_err() {
+                warn!("Timed out waiting for a supervised unit to stop");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn fast_policy() -> RestartPolicy {
+        RestartPolicy::default()
+            .with_initial_backoff(Duration::from_millis(10))
+            .with_max_backoff(Duration::from_millis(20))
+    }
+
+    async fn wait_for_state(supervisor: &Supervisor, name: &str, state: UnitState) -> UnitStatus {
+        for _ in 0..200 {
+            if let Some(status) = supervisor.status(name).await {
+                if status.state == state {
+                    return status;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Unit {} never reached {:?}", name, state);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = RestartPolicy::default()
+            .with_initial_backoff(Duration::from_secs(1))
+            .with_max_backoff(Duration::from_secs(5));
+        assert_eq!(policy.backoff_for(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff_for(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff_for(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff_for(3), Duration::from_secs(5));
+        assert_eq!(policy.backoff_for(100), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_unit_restarts_after_error() {
+        let (event_tx, mut events) = broadcast::channel(16);
+        let supervisor = Supervisor::new(CancellationToken::new(), event_tx);
+        let runs = Arc::new(AtomicU32::new(0));
+        let runs_for_unit = Arc::clone(&runs);
+
+        supervisor
+            .spawn("flaky", fast_policy(), move |_token| {
+                let runs = Arc::clone(&runs_for_unit);
+                Box::pin(async move {
+                    if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                        anyhow::bail!("socket closed");
+                    }
+                    Ok(())
+                })
+            })
+            .await;
+
+        let status = wait_for_state(&supervisor, "flaky", UnitState::Stopped).await;
+        assert_eq!(status.restarts, 1);
+        assert_eq!(status.last_error.as_deref(), Some("socket closed"));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        match events.recv().await.unwrap() {
+            OrchestratorEvent::SubsystemFailed {
+                name, restarting, ..
+            } => {
+                assert_eq!(name, "flaky");
+                assert!(restarting);
+            }
+            other => panic!("Expected SubsystemFailed event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unit_restarts_after_panic() {
+        let (event_tx, _events) = broadcast::channel(16);
+        let supervisor = Supervisor::new(CancellationToken::new(), event_tx);
+        let runs = Arc::new(AtomicU32::new(0));
+        let runs_for_unit = Arc::clone(&runs);
+
+        supervisor
+            .spawn("panicky", fast_policy(), move |_token| {
+                let runs = Arc::clone(&runs_for_unit);
+                Box::pin(async move {
+                    if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                        panic!("first run fails");
+                    }
+                    Ok(())
+                })
+            })
+            .await;
+
+        let status = wait_for_state(&supervisor, "panicky", UnitState::Stopped).await;
+        assert_eq!(status.restarts, 1);
+        assert_eq!(status.last_error.as_deref(), Some("panic: first run fails"));
+    }
+
+    #[tokio::test]
+    async fn test_unit_gives_up_after_max_restarts() {
+        let (event_tx, _events) = broadcast::channel(16);
+        let supervisor = Supervisor::new(CancellationToken::new(), event_tx);
+
+        supervisor
+            .spawn("broken", fast_policy().with_max_restarts(2), |_token| {
+                Box::pin(async { anyhow::bail!("always fails") })
+            })
+            .await;
+
+        let status = wait_for_state(&supervisor, "broken", UnitState::Failed).await;
+        assert_eq!(status.restarts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_units() {
+        let (event_tx, _events) = broadcast::channel(16);
+        let shutdown_token = CancellationToken::new();
+        let supervisor = Supervisor::new(shutdown_token.clone(), event_tx);
+
+        supervisor
+            .spawn("long-running", fast_policy(), |token| {
+                Box::pin(async move {
+                    token.cancelled().await;
+                    Ok(())
+                })
+            })
+            .await;
+
+        wait_for_state(&supervisor, "long-running", UnitState::Running).await;
+        shutdown_token.cancel();
+        supervisor.join(Duration::from_secs(1)).await;
+
+        let status = supervisor.status("long-running").await.unwrap();
+        assert_eq!(status.state, UnitState::Stopped);
+        assert_eq!(status.restarts, 0);
+    }
+}
@@ -0,0 +1,403 @@
+//! Machine-readable output of CLI commands.
+//!
+//! With `--output json`, commands print one of the types below instead of
+//! text. The field names and meanings are part of the CLI's interface and are
+//! documented in `docs/CLI_OUTPUT.md`: fields may be added, but existing ones
+//! are not renamed, removed or given a different type.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::authz::Role;
+use crate::devices::names::is_mixed_script;
+use crate::devices::{TemporaryGrant, TrustLevel, TrustedDevice};
+use crate::maintenance::MaintenanceState;
+use crate::orchestrator::CrashRecovery;
+
+/// Format of command results.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// Stable JSON for scripts and monitoring
+    Json,
+}
+
+impl OutputFormat {
+    /// Returns true for JSON output.
+    pub fn is_json(self) -> bool {
+        self == Self::Json
+    }
+}
+
+/// Result of `status`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StatusOutput {
+    /// Whether the daemon is running.
+    pub running: bool,
+    /// Seconds since the daemon started.
+    pub uptime_secs: u64,
+    /// Number of active sessions.
+    pub session_count: usize,
+    /// Number of connected devices.
+    pub device_count: usize,
+    /// The unclean shutdown the daemon recovered from when it started.
+    pub recovered_from_crash: Option<CrashRecovery>,
+    /// The maintenance under way, or `None` when the daemon is not in
+    /// maintenance.
+    pub maintenance: Option<MaintenanceState>,
+}
+
+/// A device in the result of `devices list`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DeviceOutput {
+    /// Device fingerprint (colon-separated hex).
+    pub device_id: String,
+    /// Human-readable name for the device.
+    pub name: String,
+    /// The name as the device sent it, when normalization changed it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<String>,
+    /// The name mixes scripts with look-alike letters.
+    pub mixed_script_name: bool,
+    /// Base64-encoded Ed25519 public key.
+    pub public_key: String,
+    /// The trust level of this device.
+    pub trust_level: TrustLevel,
+    /// Unix timestamp when the device was first seen.
+    pub first_seen: u64,
+    /// Unix timestamp when the device was last seen.
+    pub last_seen: u64,
+    /// Features granted to this device beyond shell and file access.
+    pub capabilities: BTreeSet<String>,
+    /// Temporary access from `devices grant-once`, if that is what trusts
+    /// the device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temporary_grant: Option<GrantOutput>,
+    /// Management role, if the device has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    /// Word fingerprint for out-of-band comparison.
+    pub fingerprint_words: Vec<String>,
+}
+
+impl From<&TrustedDevice> for DeviceOutput {
+    fn from(device: &TrustedDevice) -> Self {
+        Self {
+            device_id: device.device_id.fingerprint(),
+            name: device.name.clone(),
+            raw_name: device.raw_name.clone(),
+            mixed_script_name: is_mixed_script(&device.name),
+            public_key: base64::engine::general_purpose::STANDARD.encode(device.public_key),
+            trust_level: device.trust_level,
+            first_seen: unix_secs(device.first_seen),
+            last_seen: unix_secs(device.last_seen),
+            capabilities: device.capabilities.clone(),
+            temporary_grant: device.grant.as_ref().map(GrantOutput::from),
+            role: device.role,
+            fingerprint_words: device
+                .device_id
+                .fingerprint_words()
+                .iter()
+                .map(|w| w.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Result of `devices trust` and `devices revoke`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TrustChangeOutput {
+    /// Device fingerprint (colon-separated hex).
+    pub device_id: String,
+    /// The device's new trust level.
+    pub trust_level: TrustLevel,
+}
+
+/// Result of `devices role`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RoleChangeOutput {
+    /// Device fingerprint (colon-separated hex).
+    pub device_id: String,
+    /// The device's new role, `null` without management access.
+    pub role: Option<Role>,
+}
+
+/// A temporary grant, in `devices list` and the result of
+/// `devices grant-once`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GrantOutput {
+    /// Unix timestamp when the grant expires.
+    pub expires_at: u64,
+    /// What the grant allows: `shell`, `files` and optional capabilities.
+    pub capabilities: BTreeSet<String>,
+}
+
+impl From<&TemporaryGrant> for GrantOutput {
+    fn from(grant: &TemporaryGrant) -> Self {
+        Self {
+            expires_at: unix_secs(grant.expires_at),
+            capabilities: grant.capabilities.clone(),
+        }
+    }
+}
+
+/// Result of `devices grant-once`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GrantOnceOutput {
+    /// Device fingerprint (colon-separated hex).
+    pub device_id: String,
+    /// Human-readable name for the device.
+    pub name: String,
+    /// The grant.
+    #[serde(flatten)]
+    pub grant: GrantOutput,
+}
+
+/// Result of `devices reinvite`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ReinviteOutput {
+    /// Device fingerprint (colon-separated hex).
+    pub device_id: String,
+    /// Human-readable name for the device.
+    pub name: String,
+    /// One-time re-invite token.
+    pub token: String,
+    /// Seconds until the token expires.
+    pub expires_in_secs: u64,
+}
+
+/// Result of `devices import`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ImportOutput {
+    /// Fingerprint of the daemon that signed the export.
+    pub signer: String,
+    /// Number of devices in the export.
+    pub devices: usize,
+    /// Devices that were not in the trust store.
+    pub added: usize,
+    /// Existing devices that changed.
+    pub updated: usize,
+    /// Existing devices that already matched the export.
+    pub unchanged: usize,
+    /// Devices removed because they were not in the export.
+    pub removed: usize,
+}
+
+/// Result of `sessions kill`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SessionKillOutput {
+    /// The session that was terminated.
+    pub session_id: String,
+    /// Name of the signal sent, as given on the command line.
+    pub signal: String,
+    /// Number of the signal sent.
+    pub signal_number: i32,
+}
+
+/// Result of `sessions allow` and `sessions deny`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SessionConfirmationOutput {
+    /// The request that was answered.
+    pub id: u64,
+    /// Whether the session was allowed.
+    pub allowed: bool,
+}
+
+/// Result of `stop`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StopOutput {
+    /// Whether the daemon was stopped.
+    pub stopped: bool,
+    /// Whether it was killed rather than shut down gracefully.
+    pub forced: bool,
+}
+
+/// Result of `pair`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PairOutput {
+    /// Short pairing code.
+    pub code: String,
+    /// URL that opens the web client with the code.
+    pub url: String,
+    /// Seconds until the code expires.
+    pub expires_in_secs: u64,
+    /// Path of the QR code image, with `--format png`.
+    pub qr_file: Option<PathBuf>,
+}
+
+/// Result of `pair --status`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PairingStatusOutput {
+    /// The queried pairing code.
+    pub code: String,
+    /// State of the code.
+    #[serde(flatten)]
+    pub status: crate::devices::PairingCodeStatus,
+}
+
+/// Result of `network`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct NetworkOutput {
+    /// The QUIC endpoint, or `None` when `network.quic` is disabled.
+    pub quic: Option<crate::ipc::QuicEndpointInfo>,
+}
+
+/// Result of `mesh`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MeshOutput {
+    /// The mesh group, derived from the admin device's key.
+    pub group: String,
+    /// The other daemons of the mesh, ordered by host name.
+    pub hosts: Vec<crate::mesh::MeshRecord>,
+}
+
+/// Result of `maintenance on` and `maintenance off`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MaintenanceOutput {
+    /// The maintenance under way, or `None` once it is turned off.
+    pub maintenance: Option<MaintenanceState>,
+}
+
+/// Prints a value to stdout as pretty-printed JSON.
+///
+/// Write errors, such as a closed pipe, are returned rather than panicking.
+pub fn print_json<T: Serialize>(value: &T) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, value)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::DeviceIdentity;
+    use std::time::Duration;
+
+    #[test]
+    fn test_device_output_fields() {
+        let identity = DeviceIdentity::generate();
+        let mut device = TrustedDevice::new(
+            *identity.device_id(),
+            "laptop".to_string(),
+            identity.public_key_bytes(),
+        );
+        device.first_seen = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        device.last_seen = UNIX_EPOCH + Duration::from_secs(1_700_000_100);
+
+        let json = serde_json::to_value(DeviceOutput::from(&device)).unwrap();
+        assert_eq!(json["device_id"], identity.fingerprint());
+        assert_eq!(json["name"], "laptop");
+        assert!(json.get("raw_name").is_none());
+        assert_eq!(json["mixed_script_name"], false);
+        assert_eq!(json["trust_level"], "trusted");
+        assert_eq!(json["first_seen"], 1_700_000_000u64);
+        assert_eq!(json["last_seen"], 1_700_000_100u64);
+        assert_eq!(json["capabilities"], serde_json::json!([]));
+        assert!(json.get("temporary_grant").is_none());
+        assert!(json.get("role").is_none());
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD
+                .decode(json["public_key"].as_str().unwrap())
+                .unwrap(),
+            identity.public_key_bytes()
+        );
+        assert!(!json["fingerprint_words"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_output_field_names() {
+        let status = serde_json::to_value(StatusOutput {
+            running: true,
+            uptime_secs: 5,
+            session_count: 1,
+            device_count: 2,
+            recovered_from_crash: None,
+            maintenance: None,
+        })
+        .unwrap();
+        assert_eq!(
+            status,
+            serde_json::json!({
+                "running": true,
+                "uptime_secs": 5,
+                "session_count": 1,
+                "device_count": 2,
+                "recovered_from_crash": null,
+                "maintenance": null
+            })
+        );
+
+        let pair = serde_json::to_value(PairOutput {
+            code: "ABC123".to_string(),
+            url: "https://example.com/pair/ABC123".to_string(),
+            expires_in_secs: 300,
+            qr_file: None,
+        })
+        .unwrap();
+        assert_eq!(pair["qr_file"], serde_json::Value::Null);
+        assert_eq!(pair["expires_in_secs"], 300);
+
+        let role = serde_json::to_value(RoleChangeOutput {
+            device_id: "aa:bb".to_string(),
+            role: Some(Role::Admin),
+        })
+        .unwrap();
+        assert_eq!(
+            role,
+            serde_json::json!({ "device_id": "aa:bb", "role": "admin" })
+        );
+
+        let grant = serde_json::to_value(GrantOnceOutput {
+            device_id: "aa:bb".to_string(),
+            name: "guest".to_string(),
+            grant: GrantOutput {
+                expires_at: 1_700_001_800,
+                capabilities: BTreeSet::from(["shell".to_string()]),
+            },
+        })
+        .unwrap();
+        assert_eq!(
+            grant,
+            serde_json::json!({
+                "device_id": "aa:bb",
+                "name": "guest",
+                "expires_at": 1_700_001_800u64,
+                "capabilities": ["shell"]
+            })
+        );
+
+        let network = serde_json::to_value(NetworkOutput {
+            quic: Some(crate::ipc::QuicEndpointInfo {
+                node_id: "ae58ff88".to_string(),
+                bound_addresses: vec!["192.168.1.20:51000".parse().unwrap()],
+                relay: false,
+            }),
+        })
+        .unwrap();
+        assert_eq!(
+            network,
+            serde_json::json!({
+                "quic": {
+                    "node_id": "ae58ff88",
+                    "bound_addresses": ["192.168.1.20:51000"],
+                    "relay": false
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Remote power actions: reboot, shutdown and suspend.
+//!
+//! A power action takes two steps, so that a single stray message cannot
+//! take the host down. The client sends `PowerActionRequest` and the daemon
+//! answers with a `PowerActionChallenge` holding a one-time token. Once the
+//! user has confirmed, the client sends the token back in
+//! `PowerActionConfirm` and the daemon runs the configured command.
+//!
+//! Power actions are only available when `[power] enabled` is set, and only
+//! to devices granted the `power` capability.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use protocol::messages::{
+    Notification, NotificationCategory, NotificationLevel, PowerAction, PowerActionChallenge,
+};
+use protocol::DeviceId;
+use thiserror::Error;
+
+use crate::config::PowerConfig;
+use crate::util::i18n::tr;
+
+/// Device capability required to request power actions.
+pub const POWER_CAPABILITY: &str = "power";
+
+/// How long a power action may be confirmed, in seconds.
+const CONFIRMATION_TTL: u64 = 30;
+
+/// Errors from confirming or running a power action.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PowerError {
+    #[error("no command is configured for {0}")]
+    NotConfigured(&'static str),
+
+    #[error("unknown or already confirmed token")]
+    UnknownToken,
+
+    #[error("confirmation has expired")]
+    Expired,
+
+    #[error("confirmation was issued to another device")]
+    WrongDevice,
+
+    #[error("{command} failed: {reason}")]
+    CommandFailed { command: String, reason: String },
+}
+
+/// A power action waiting for confirmation.
+#[derive(Debug, Clone)]
+struct PendingAction {
+    device_id: DeviceId,
+    action: PowerAction,
+    expires_at: u64,
+}
+
+/// Issues confirmation tokens for power actions and runs them.
+#[derive(Debug)]
+pub struct PowerManager {
+    reboot_command: Vec<String>,
+    shutdown_command: Vec<String>,
+    suspend_command: Vec<String>,
+    pending: Mutex<HashMap<String, PendingAction>>,
+}
+
+impl PowerManager {
+    /// Creates a manager running the commands in the `power` configuration.
+    pub fn from_config(config: &PowerConfig) -> Self {
+        Self {
+            reboot_command: config.reboot_command.clone(),
+            shutdown_command: config.shutdown_command.clone(),
+            suspend_command: config.suspend_command.clone(),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the command that performs an action.
+    fn command(&self, action: PowerAction) -> Result<&[String], PowerError> {
+        let command = match action {
+            PowerAction::Reboot => &self.reboot_command,
+            PowerAction::Shutdown => &self.shutdown_command,
+            PowerAction::Suspend => &self.suspend_command,
+        };
+        if command.is_empty() {
+            return Err(PowerError::NotConfigured(action_name(action)));
+        }
+        Ok(command)
+    }
+
+    /// Issues a confirmation token for an action requested by a device.
+    pub fn request(
+        &self,
+        device_id: &DeviceId,
+        action: PowerAction,
+        now: u64,
+    ) -> Result<PowerActionChallenge, PowerError> {
+        self.command(action)?;
+
+        let token = uuid::Uuid::new_v4().to_string();
+        let expires_at = now + CONFIRMATION_TTL;
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, p| p.expires_at > now);
+        pending.insert(
+            token.clone(),
+            PendingAction {
+                device_id: *device_id,
+                action,
+                expires_at,
+            },
+        );
+
+        Ok(PowerActionChallenge {
+            token,
+            action,
+            expires_at,
+        })
+    }
+
+    /// Consumes a confirmation token and returns the confirmed action.
+    ///
+    /// A token can be used once, whether or not the confirmation is valid.
+    pub fn confirm(
+        &self,
+        device_id: &DeviceId,
+        token: &str,
+        now: u64,
+    ) -> Result<PowerAction, PowerError> {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(token)
+            .ok_or(PowerError::UnknownToken)?;
+        if pending.device_id != *device_id {
+            return Err(PowerError::WrongDevice);
+        }
+        if now >= pending.expires_at {
+            return Err(PowerError::Expired);
+        }
+        Ok(pending.action)
+    }
+
+    /// Runs the command for an action and waits for it to exit.
+    pub async fn run(&self, action: PowerAction) -> Result<(), PowerError> {
+        let command = self.command(action)?;
+        let failed = |reason: String| PowerError::CommandFailed {
+            command: command.join(" "),
+            reason,
+        };
+
+        let status = tokio::process::Command::new(&command[0])
+            .args(&command[1..])
+            .status()
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        if !status.success() {
+            return Err(failed(status.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the name of an action used in logs and errors.
+pub fn action_name(action: PowerAction) -> &'static str {
+    match action {
+        PowerAction::Reboot => "reboot",
+        PowerAction::Shutdown => "shutdown",
+        PowerAction::Suspend => "suspend",
+    }
+}
+
+/// Notice sent to connected devices when a power action is about to run.
+pub fn action_notification(action: PowerAction) -> Notification {
+    let (title, body) = match action {
+        PowerAction::Reboot => ("notify.power.reboot.title", "notify.power.reboot.body"),
+        PowerAction::Shutdown => ("notify.power.shutdown.title", "notify.power.shutdown.body"),
+        PowerAction::Suspend => ("notify.power.suspend.title", "notify.power.suspend.body"),
+    };
+    Notification::new(
+        NotificationLevel::Warning,
+        NotificationCategory::Shutdown,
+        tr(title),
+        tr(body),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(command: &[&str]) -> PowerManager {
+        let command: Vec<String> = command.iter().map(|s| s.to_string()).collect();
+        PowerManager::from_config(&PowerConfig {
+            enabled: true,
+            reboot_command: command.clone(),
+            shutdown_command: command,
+            suspend_command: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_confirm_once() {
+        let manager = manager(&["true"]);
+        let device = DeviceId::from_bytes([1u8; 16]);
+
+        let challenge = manager.request(&device, PowerAction::Reboot, 1000).unwrap();
+        assert_eq!(challenge.action, PowerAction::Reboot);
+        assert_eq!(challenge.expires_at, 1000 + CONFIRMATION_TTL);
+
+        assert_eq!(
+            manager.confirm(&device, &challenge.token, 1010),
+            Ok(PowerAction::Reboot)
+        );
+        assert_eq!(
+            manager.confirm(&device, &challenge.token, 1010),
+            Err(PowerError::UnknownToken)
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_confirmations() {
+        let manager = manager(&["true"]);
+        let device = DeviceId::from_bytes([1u8; 16]);
+        let other = DeviceId::from_bytes([2u8; 16]);
+
+        let challenge = manager
+            .request(&device, PowerAction::Shutdown, 1000)
+            .unwrap();
+        assert_eq!(
+            manager.confirm(&other, &challenge.token, 1010),
+            Err(PowerError::WrongDevice)
+        );
+
+        let challenge = manager
+            .request(&device, PowerAction::Shutdown, 1000)
+            .unwrap();
+        assert_eq!(
+            manager.confirm(&device, &challenge.token, 1000 + CONFIRMATION_TTL),
+            Err(PowerError::Expired)
+        );
+
+        // No suspend command is configured
+        assert_eq!(
+            manager
+                .request(&device, PowerAction::Suspend, 1000)
+                .unwrap_err(),
+            PowerError::NotConfigured("suspend")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        assert!(manager(&["true"]).run(PowerAction::Reboot).await.is_ok());
+        assert!(matches!(
+            manager(&["false"]).run(PowerAction::Reboot).await,
+            Err(PowerError::CommandFailed { .. })
+        ));
+        assert!(matches!(
+            manager(&["true"]).run(PowerAction::Suspend).await,
+            Err(PowerError::NotConfigured(_))
+        ));
+    }
+}
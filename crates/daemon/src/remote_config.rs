@@ -0,0 +1,483 @@
+//! Reading and changing the daemon's configuration from a client.
+//!
+//! Devices with a management role can read the effective configuration with
+//! `ConfigRequest`; secrets are redacted. Owners can change the settings that
+//! are safe to change while the daemon runs with `ConfigPatch`: the session
+//! idle timeout, the names left out of directory listings and the log level.
+//!
+//! A patch is checked against the whole configuration, written to the
+//! configuration file and applied to the running session manager, directory
+//! browser and log filter, so it holds across restarts without needing one.
+//! Only the patched keys are written, which keeps values that came from the
+//! environment out of the file and the operator's comments in it. The file
+//! is replaced atomically, so a failed write leaves the old one in place.
+//!
+//! The daemon reloads the same settings when the file is edited by hand, so
+//! what clients read stays what is applied (see [`RemoteConfig::reload`]).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use protocol::messages::{ConfigPatch, ConfigView};
+use thiserror::Error;
+
+use crate::config::{Config, ConfigError};
+use crate::files::DirectoryBrowser;
+use crate::logging::LogLevelHandle;
+use crate::session::SessionManagerImpl;
+use crate::util::file_store::write_atomic;
+
+/// Errors from reading or patching the configuration.
+#[derive(Debug, Error)]
+pub enum RemoteConfigError {
+    #[error("the change sets no settings")]
+    EmptyPatch,
+
+    #[error("invalid configuration: {0}")]
+    Invalid(#[from] ConfigError),
+
+    #[error("the daemon has no configuration file to save changes to")]
+    NoConfigFile,
+
+    #[error("failed to save the configuration: {0:#}")]
+    Save(anyhow::Error),
+
+    #[error("failed to load the configuration: {0:#}")]
+    Load(anyhow::Error),
+
+    #[error("failed to render the configuration: {0:#}")]
+    Render(anyhow::Error),
+}
+
+impl RemoteConfigError {
+    /// Whether the error comes from the request rather than the daemon.
+    pub fn is_invalid_request(&self) -> bool {
+        matches!(self, Self::EmptyPatch | Self::Invalid(_))
+    }
+}
+
+/// Describes the settings a patch changes, such as
+/// `idle_timeout=900 log_level="debug"`, for the audit log.
+pub fn describe(patch: &ConfigPatch) -> String {
+    let mut changes = Vec::new();
+    if let Some(timeout) = patch.idle_timeout {
+        changes.push(format!("idle_timeout={}", timeout));
+    }
+    if let Some(exclude) = &patch.file_exclude {
+        changes.push(format!("file_exclude={:?}", exclude));
+    }
+    if let Some(level) = &patch.log_level {
+        changes.push(format!("log_level={:?}", level));
+    }
+    changes.join(" ")
+}
+
+/// What patches read and change.
+#[derive(Debug)]
+struct State {
+    /// The effective configuration, with the patches made so far.
+    config: Config,
+    /// The file patches are saved to.
+    path: Option<PathBuf>,
+    /// Changes the level of the daemon's logs, if they can be changed.
+    log_level: Option<LogLevelHandle>,
+}
+
+/// The effective configuration, and the subsystems patches are applied to.
+pub struct RemoteConfig {
+    state: Mutex<State>,
+    session_manager: Arc<SessionManagerImpl>,
+    directory_browser: Arc<DirectoryBrowser>,
+}
+
+impl RemoteConfig {
+    /// Creates a view of `config` whose patches apply to `session_manager`
+    /// and `directory_browser`.
+    pub fn new(
+        config: Config,
+        session_manager: Arc<SessionManagerImpl>,
+        directory_browser: Arc<DirectoryBrowser>,
+    ) -> Self {
+        Self {
+            state: Mutex::new(State {
+                config,
+                path: None,
+                log_level: None,
+            }),
+            session_manager,
+            directory_browser,
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sets the configuration file patches are saved to. Until it is set,
+    /// patches are refused.
+    pub fn set_path(&self, path: PathBuf) {
+        self.state().path = Some(path);
+    }
+
+    /// Sets the handle patches change the log level through.
+    pub fn set_log_level_handle(&self, handle: LogLevelHandle) {
+        self.state().log_level = Some(handle);
+    }
+
+    /// Returns the effective configuration, with secrets redacted.
+    pub fn view(&self) -> Result<ConfigView, RemoteConfigError> {
+        render(&self.state().config)
+    }
+
+    /// Returns the configuration file patches are saved to, if set.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.state().path.clone()
+    }
+
+    /// Validates, saves and applies `patch`, and returns the new
+    /// configuration.
+    pub fn patch(&self, patch: &ConfigPatch) -> Result<ConfigView, RemoteConfigError> {
+        if is_empty(patch) {
+            return Err(RemoteConfigError::EmptyPatch);
+        }
+
+        let mut state = self.state();
+        let path = state.path.clone().ok_or(RemoteConfigError::NoConfigFile)?;
+        let config = patched(&state.config, patch)?;
+        save_patch(&path, patch, &config).map_err(RemoteConfigError::Save)?;
+        self.apply(&mut state, patch, config)?;
+        render(&state.config)
+    }
+
+    /// Re-reads the configuration file and applies the settings patches can
+    /// change, if they were edited by hand since they were last applied.
+    /// Other settings need a restart and keep their running values.
+    ///
+    /// Returns true if any setting changed.
+    pub fn reload(&self) -> Result<bool, RemoteConfigError> {
+        let mut state = self.state();
+        let Some(path) = state.path.clone() else {
+            return Ok(false);
+        };
+        let mut file = Config::load(&path).map_err(RemoteConfigError::Load)?;
+        file.apply_env_overrides();
+
+        let running = &state.config;
+        let changes = ConfigPatch {
+            idle_timeout: (file.session.idle_timeout != running.session.idle_timeout)
+                .then_some(file.session.idle_timeout),
+            file_exclude: (file.file.exclude != running.file.exclude).then_some(file.file.exclude),
+            log_level: (file.daemon.log_level != running.daemon.log_level)
+                .then_some(file.daemon.log_level),
+        };
+        if is_empty(&changes) {
+            return Ok(false);
+        }
+
+        let config = patched(&state.config, &changes)?;
+        self.apply(&mut state, &changes, config)?;
+        tracing::info!(changes = %describe(&changes), "Configuration reloaded");
+        Ok(true)
+    }
+
+    /// Applies the settings `patch` sets in `config` to the running
+    /// subsystems, and makes `config` the effective configuration.
+    fn apply(
+        &self,
+        state: &mut State,
+        patch: &ConfigPatch,
+        config: Config,
+    ) -> Result<(), RemoteConfigError> {
+        if patch.idle_timeout.is_some() {
+            let timeout = config.session.idle_timeout;
+            self.session_manager
+                .set_idle_timeout((timeout > 0).then(|| Duration::from_secs(timeout)));
+        }
+        if patch.file_exclude.is_some() {
+            self.directory_browser
+                .set_exclude(config.file.exclude_patterns()?);
+        }
+        if patch.log_level.is_some() {
+            if let Some(handle) = &state.log_level {
+                if let Err(e) = handle.set_level(&config.daemon.log_level) {
+                    tracing::warn!("Failed to change the log level: {}", e);
+                }
+            }
+        }
+
+        state.config = config;
+        Ok(())
+    }
+}
+
+/// Returns true if `patch` sets no settings.
+fn is_empty(patch: &ConfigPatch) -> bool {
+    patch.idle_timeout.is_none() && patch.file_exclude.is_none() && patch.log_level.is_none()
+}
+
+/// Returns `config` with the settings `patch` sets, checked as a whole.
+fn patched(config: &Config, patch: &ConfigPatch) -> Result<Config, RemoteConfigError> {
+    let mut config = config.clone();
+    if let Some(timeout) = patch.idle_timeout {
+        config.session.idle_timeout = timeout;
+    }
+    if let Some(exclude) = &patch.file_exclude {
+        config.file.exclude = exclude.clone();
+    }
+    if let Some(level) = &patch.log_level {
+        config.daemon.log_level = level.to_lowercase();
+    }
+    if i64::try_from(config.session.idle_timeout).is_err() {
+        return Err(ConfigError::InvalidSession(
+            "idle_timeout".to_string(),
+            format!("must be at most {} seconds", i64::MAX),
+        )
+        .into());
+    }
+    config.validate()?;
+    config.file.exclude_patterns()?;
+    Ok(config)
+}
+
+impl std::fmt::Debug for RemoteConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteConfig")
+            .field("path", &self.state().path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Renders `config` for clients, with secrets redacted.
+fn render(config: &Config) -> Result<ConfigView, RemoteConfigError> {
+    let toml = config
+        .redacted()
+        .to_toml()
+        .map_err(RemoteConfigError::Render)?;
+    Ok(ConfigView { toml })
+}
+
+/// Writes the keys `patch` sets, with their values from `config`, into the
+/// configuration file at `path`, keeping everything else in it, comments
+/// included.
+fn save_patch(path: &Path, patch: &ConfigPatch, config: &Config) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use toml_edit::{value, Array, DocumentMut};
+
+    let mut document = if path.exists() {
+        fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?
+            .parse::<DocumentMut>()
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?
+    } else {
+        DocumentMut::new()
+    };
+
+    let mut set = |section: &str, key: &str, item: toml_edit::Item| -> anyhow::Result<()> {
+        let table = document
+            .entry(section)
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .with_context(|| format!("[{}] is not a table in {}", section, path.display()))?;
+        table.insert(key, item);
+        Ok(())
+    };
+    if patch.idle_timeout.is_some() {
+        // Checked to fit before saving
+        let timeout = i64::try_from(config.session.idle_timeout).unwrap_or(i64::MAX);
+        set("session", "idle_timeout", value(timeout))?;
+    }
+    if patch.file_exclude.is_some() {
+        let exclude: Array = config.file.exclude.iter().collect();
+        set("file", "exclude", value(exclude))?;
+    }
+    if patch.log_level.is_some() {
+        set(
+            "daemon",
+            "log_level",
+            value(config.daemon.log_level.as_str()),
+        )?;
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create config directory: {}", parent.display()))?;
+    }
+    // Replace the file a symlinked configuration points to, not the link
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    write_atomic(&target, document.to_string().as_bytes())
+        .with_context(|| format!("Failed to write config file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn remote_config(temp_dir: &TempDir) -> (RemoteConfig, PathBuf) {
+        let path = temp_dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "# Managed by hand\n[network]\nsignaling_url = \"wss://signaling.lan\"\n\n[mqtt]\npassword = \"hunter2\"\n",
+        )
+        .unwrap();
+        let mut config = Config::load(&path).unwrap();
+        config.session.default_shell = "/bin/sh".to_string();
+        let remote = RemoteConfig::new(
+            config,
+            Arc::new(SessionManagerImpl::new()),
+            Arc::new(DirectoryBrowser::allow_all()),
+        );
+        remote.set_path(path.clone());
+        (remote, path)
+    }
+
+    #[test]
+    fn test_view_redacts_secrets() {
+        let temp_dir = TempDir::new().unwrap();
+        let (remote, _) = remote_config(&temp_dir);
+
+        let view = remote.view().unwrap();
+        assert!(view.toml.contains("wss://signaling.lan"));
+        assert!(!view.toml.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_patch_saves_and_applies() {
+        let temp_dir = TempDir::new().unwrap();
+        let (remote, path) = remote_config(&temp_dir);
+
+        let view = remote
+            .patch(&ConfigPatch {
+                idle_timeout: Some(900),
+                file_exclude: Some(vec!["node_modules".to_string()]),
+                log_level: Some("DEBUG".to_string()),
+            })
+            .unwrap();
+        assert!(view.toml.contains("idle_timeout = 900"));
+
+        // Only the patched keys are added to the file
+        let saved = Config::load(&path).unwrap();
+        assert_eq!(saved.session.idle_timeout, 900);
+        assert_eq!(saved.file.exclude, ["node_modules"]);
+        assert_eq!(saved.daemon.log_level, "debug");
+        assert_eq!(saved.network.signaling_url, "wss://signaling.lan");
+        assert_eq!(saved.mqtt.password.as_deref(), Some("hunter2"));
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("# Managed by hand\n"));
+        assert!(!contents.contains("default_shell"));
+
+        // Listings leave the new exclusions out
+        fs::create_dir(temp_dir.path().join("node_modules")).unwrap();
+        let entries = remote
+            .directory_browser
+            .list_directory(temp_dir.path(), false)
+            .unwrap();
+        assert!(entries.iter().all(|entry| entry.name != "node_modules"));
+    }
+
+    #[test]
+    fn test_invalid_patch_changes_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let (remote, path) = remote_config(&temp_dir);
+        let before = fs::read_to_string(&path).unwrap();
+
+        for patch in [
+            ConfigPatch::default(),
+            ConfigPatch {
+                log_level: Some("verbose".to_string()),
+                ..Default::default()
+            },
+            ConfigPatch {
+                file_exclude: Some(vec!["[".to_string()]),
+                ..Default::default()
+            },
+            ConfigPatch {
+                idle_timeout: Some(u64::MAX),
+                ..Default::default()
+            },
+        ] {
+            let err = remote.patch(&patch).unwrap_err();
+            assert!(err.is_invalid_request(), "{:?}", err);
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        assert_eq!(remote.state().config.daemon.log_level, "info");
+    }
+
+    #[test]
+    fn test_patch_keeps_symlinked_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let (remote, target) = remote_config(&temp_dir);
+        let link = temp_dir.path().join("link.toml");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        remote.set_path(link.clone());
+
+        remote
+            .patch(&ConfigPatch {
+                idle_timeout: Some(120),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(Config::load(&target).unwrap().session.idle_timeout, 120);
+    }
+
+    #[test]
+    fn test_reload_applies_hand_edits() {
+        let temp_dir = TempDir::new().unwrap();
+        let (remote, path) = remote_config(&temp_dir);
+        assert!(!remote.reload().unwrap());
+
+        let mut contents = fs::read_to_string(&path).unwrap();
+        contents.push_str("\n[file]\nexclude = [\"target\"]\n\n[session]\nidle_timeout = 300\n");
+        fs::write(&path, contents).unwrap();
+
+        assert!(remote.reload().unwrap());
+        let view = remote.view().unwrap();
+        assert!(view.toml.contains("idle_timeout = 300"));
+        fs::create_dir(temp_dir.path().join("target")).unwrap();
+        let entries = remote
+            .directory_browser
+            .list_directory(temp_dir.path(), false)
+            .unwrap();
+        assert!(entries.iter().all(|entry| entry.name != "target"));
+
+        // Nothing left to apply, including after a patch saved by the daemon
+        assert!(!remote.reload().unwrap());
+        remote
+            .patch(&ConfigPatch {
+                idle_timeout: Some(600),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(!remote.reload().unwrap());
+    }
+
+    #[test]
+    fn test_reload_ignores_invalid_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let (remote, path) = remote_config(&temp_dir);
+        fs::write(&path, "[daemon]\nlog_level = \"chatty\"\n").unwrap();
+
+        assert!(remote.reload().is_err());
+        assert_eq!(remote.state().config.daemon.log_level, "info");
+    }
+
+    #[test]
+    fn test_patch_needs_config_file() {
+        let remote = RemoteConfig::new(
+            Config::default(),
+            Arc::new(SessionManagerImpl::new()),
+            Arc::new(DirectoryBrowser::allow_all()),
+        );
+        let patch = ConfigPatch {
+            idle_timeout: Some(60),
+            ..Default::default()
+        };
+        assert!(matches!(
+            remote.patch(&patch),
+            Err(RemoteConfigError::NoConfigFile)
+        ));
+    }
+}
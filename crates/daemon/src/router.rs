@@ -3,28 +3,87 @@
 //! This module provides the `MessageRouter` struct that receives protocol messages
 //! and routes them to the appropriate subsystem (session manager, file manager,
 //! device manager) based on message type.
+//!
+//! Checks that apply across message types, such as device trust and rate
+//! limits, run as [`middleware`] interceptors before the handler.
 
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use protocol::messages::{
-    DataStream, DeviceApprovalRequest, DeviceApproved, DeviceInfo, DeviceRejected, ErrorCode,
-    ErrorMessage, FileDownloadChunk, FileDownloadRequest, FileListRequest, FileListResponse,
-    FileUploadChunk, FileUploadComplete, FileUploadStart, Message, Ping, Pong, SessionAttach,
-    SessionClosed, SessionCreate, SessionCreated, SessionData, SessionDetach, SessionKill,
-    SessionResize,
+    AgentClose, AgentData, ConfigPatch, DataStream, DeviceApprovalRequest, DeviceApproved,
+    DeviceInfo, DeviceRejected, DisplayClose, DisplayData, DisplayProtocol, ErrorCode,
+    ErrorMessage, FileAccess, FileCopyGrantRequest, FileCopyRequest, FileDownloadAck,
+    FileDownloadRequest, FileListRequest, FileListResponse, FileReadText, FileRoot, FileRoots,
+    FileRootsRequest, FileTailRequest, FileTailStop, FileText, FileTextWritten,
+    FileThumbnailRequest, FileTransferReport, FileUploadChunk, FileUploadComplete, FileUploadStart,
+    FileWriteText, HostSession, Message, Ping, Pong, PowerActionConfirm, PowerActionRequest,
+    PowerActionStarted, ProcessKill, ProcessList, ProcessListRequest, SearchMatch,
+    SecurityKeyAssertion, SessionAttach, SessionCloseReason, SessionClosed, SessionCreate,
+    SessionCreated, SessionData, SessionDetach, SessionKill, SessionMetadata,
+    SessionMetadataRequest, SessionResize, SessionSearchRequest, SessionSearchResult,
+    SessionSignal, SystemInfoRequest, TemplateInfo, TemplateList, UpdateSessionEnv,
 };
 use protocol::DeviceId;
-use tracing::{debug, error, info, warn};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn, Instrument, Span};
+
+use crate::auditd::AuditLog;
+use crate::config::{SessionTemplate, WebhookEvent};
+use crate::devices::{
+    ConfirmOutcome, ConsumeOutcome, DeviceName, GatedAction, PairingCodeStore, PendingApproval,
+    RedeemOutcome, ReinviteStore, SecurityKeyGate, SessionConfirmations, TrustCache, TrustLevel,
+    TrustStore, TrustedDevice, FILES_ACCESS, SHELL_ACCESS,
+};
+use crate::files::browser::BrowserError;
+use crate::files::{
+    protocol_entries, DirectoryBrowser, DownloadStream, DownloadStreams, DrainedTransfers,
+    FileCopy, FileTails, FileTransfer, PathPermissions, PermissionLevel, TailError, ThumbnailError,
+    Thumbnailer, TransferError, MAX_DOWNLOAD_WINDOW,
+};
+use crate::honeypot::Honeypot;
+use crate::notify::Notifier;
+use crate::power::{self, PowerError, PowerManager, POWER_CAPABILITY};
+use crate::remote_config::{self, RemoteConfig};
+use crate::session::{
+    client_env, scrollback, AgentError, AgentForwarder, DisplayError, DisplayForwarder,
+    EnvAllowlist, EnvError, HostSessionError, HostSessions, LagPolicy, OutputStreams,
+    PreparedAgentSocket, PreparedDisplaySocket, SearchQuery, SessionEnvExporter, SessionError,
+    SessionId, SessionManager, SessionOwners, ShellError, ShellPolicy, AGENT_FORWARDING_CAPABILITY,
+    DISPLAY_FORWARDING_CAPABILITY, ENV_FILE_VAR,
+};
+use crate::soak::RouterCounts;
+use crate::sysmon::{self, SysmonError, PROCESS_KILL_CAPABILITY};
+use crate::telemetry;
+use crate::webhooks::Webhooks;
 
-use crate::devices::{PendingApproval, TrustLevel, TrustStore};
-use crate::files::{DirectoryBrowser, FileTransfer, PathPermissions};
-use crate::session::{SessionError, SessionId, SessionManager, SessionStatus};
+pub mod middleware;
+
+use middleware::{
+    AuditRejections, GrantCheck, Interceptor, MessageMetrics, MessageStats, Outcome, RateLimit,
+    Request, RoleCheck, TrustCheck,
+};
 
 /// Result type for router operations.
 pub type RouterResult = Result<Option<Message>, RouterError>;
 
+/// Delay between confirming a power action and running it.
+const POWER_ACTION_DELAY: Duration = Duration::from_secs(1);
+
+/// A session request the operator has answered, to be finished with
+/// [`MessageRouter::finish_answered_session`].
+#[derive(Debug)]
+pub struct AnsweredSession {
+    /// The device that asked for the session.
+    pub device_id: DeviceId,
+    /// The request, as received.
+    pub request: SessionCreate,
+    /// The operator's answer.
+    pub outcome: ConfirmOutcome,
+}
+
 /// Errors that can occur during message routing.
 #[derive(Debug, thiserror::Error)]
 pub enum RouterError {
@@ -32,9 +91,17 @@ pub enum RouterError {
     #[error("session error: {0}")]
     Session(#[from] SessionError),
 
-    /// File operation error.
-    #[error("file error: {0}")]
-    File(String),
+    /// File operation error, with the code to report it with.
+    #[error("file error: {message}")]
+    File { code: ErrorCode, message: String },
+
+    /// A requested resource does not exist.
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    /// A forwarding channel or connection the request needs is not open.
+    #[error("not connected: {0}")]
+    NotConnected(String),
 
     /// Device/trust error.
     #[error("device error: {0}")]
@@ -48,42 +115,111 @@ pub enum RouterError {
     #[error("internal error: {0}")]
     Internal(String),
 
-    /// Permission denied error.
+    /// The device is trusted but lacks the capability or path permission.
     #[error("permission denied: {0}")]
     Permission(String),
 
+    /// The device sends requests too quickly.
+    #[error("rate limited: {message}")]
+    RateLimited {
+        message: String,
+        /// How long the device should wait before retrying, if known.
+        retry_after: Option<Duration>,
+    },
+
     /// Authentication error.
     #[error("authentication error: {0}")]
     Auth(String),
+
+    /// A message type this daemon does not know.
+    #[error("unsupported message type: {0}")]
+    Unsupported(String),
+
+    /// The daemon is in maintenance and refuses the request.
+    #[error("{0}")]
+    Maintenance(String),
 }
 
 impl RouterError {
-    /// Convert the error to a protocol ErrorMessage.
-    pub fn to_error_message(&self, context: Option<String>) -> ErrorMessage {
-        let (code, recoverable) = match self {
+    /// Returns the protocol error code to report the error with.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
             RouterError::Session(e) => match e {
-                SessionError::NotFound(_) => (ErrorCode::NotFound, false),
-                SessionError::AlreadyTerminated(_) => (ErrorCode::InvalidRequest, false),
-                SessionError::SpawnFailed(_) => (ErrorCode::InternalError, true),
-                SessionError::WriteFailed(_) => (ErrorCode::InternalError, true),
-                SessionError::ReadFailed(_) => (ErrorCode::InternalError, true),
-                SessionError::ResizeFailed(_) => (ErrorCode::InternalError, true),
-                SessionError::KillFailed(_) => (ErrorCode::InternalError, true),
-                SessionError::Io(_) => (ErrorCode::InternalError, true),
+                SessionError::NotFound(_) => ErrorCode::NotFound,
+                SessionError::AlreadyTerminated(_) => ErrorCode::InvalidRequest,
+                SessionError::SpawnFailed(_) => ErrorCode::InternalError,
+                SessionError::WriteFailed(_) => ErrorCode::InternalError,
+                SessionError::ReadFailed(_) => ErrorCode::InternalError,
+                SessionError::ResizeFailed(_) => ErrorCode::InternalError,
+                SessionError::KillFailed(_) => ErrorCode::InternalError,
+                SessionError::SignalFailed(_) => ErrorCode::InternalError,
+                SessionError::InvalidSearch(_) => ErrorCode::InvalidRequest,
+                SessionError::InvalidCwd(_) => ErrorCode::InvalidRequest,
+                SessionError::Io(_) => ErrorCode::InternalError,
             },
-            RouterError::File(_) => (ErrorCode::InternalError, true),
-            RouterError::Device(_) => (ErrorCode::Unauthorized, false),
-            RouterError::Permission(_) => (ErrorCode::Unauthorized, false),
-            RouterError::InvalidRequest(_) => (ErrorCode::InvalidRequest, false),
-            RouterError::Internal(_) => (ErrorCode::InternalError, true),
-            RouterError::Auth(_) => (ErrorCode::Unauthorized, false),
-        };
+            RouterError::File { code, .. } => *code,
+            RouterError::NotFound(_) => ErrorCode::NotFound,
+            RouterError::NotConnected(_) => ErrorCode::NotConnected,
+            RouterError::Device(_) => ErrorCode::Unauthorized,
+            RouterError::Permission(_) => ErrorCode::Unauthorized,
+            RouterError::RateLimited { .. } => ErrorCode::RateLimited,
+            RouterError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            RouterError::Internal(_) => ErrorCode::InternalError,
+            RouterError::Auth(_) => ErrorCode::Unauthorized,
+            RouterError::Unsupported(_) => ErrorCode::Unsupported,
+            RouterError::Maintenance(_) => ErrorCode::Busy,
+        }
+    }
+
+    /// Convert the error to a protocol ErrorMessage.
+    ///
+    /// Whether it is recoverable follows from its code.
+    pub fn to_error_message(&self, context: Option<String>) -> ErrorMessage {
+        let mut message = ErrorMessage::new(self.error_code(), self.to_string());
+        message.context = context;
+        if let RouterError::RateLimited {
+            retry_after: Some(retry_after),
+            ..
+        } = self
+        {
+            message.retry_after_ms = Some(retry_after.as_millis() as u64);
+        }
+        message
+    }
+}
+
+impl From<BrowserError> for RouterError {
+    fn from(e: BrowserError) -> Self {
+        RouterError::File {
+            code: e.error_code(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<TransferError> for RouterError {
+    fn from(e: TransferError) -> Self {
+        RouterError::File {
+            code: e.error_code(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<TailError> for RouterError {
+    fn from(e: TailError) -> Self {
+        RouterError::File {
+            code: e.error_code(),
+            message: e.to_string(),
+        }
+    }
+}
 
-        ErrorMessage {
-            code,
-            message: self.to_string(),
-            context,
-            recoverable,
+impl From<ThumbnailError> for RouterError {
+    fn from(e: ThumbnailError) -> Self {
+        RouterError::File {
+            code: e.error_code(),
+            message: e.to_string(),
         }
     }
 }
@@ -115,8 +251,69 @@ pub struct MessageRouter<S: SessionManager> {
     directory_browser: Arc<DirectoryBrowser>,
     /// Trust store for device management.
     trust_store: Arc<TrustStore>,
+    /// Rejects messages from untrusted devices, the first interceptor.
+    trust_check: TrustCheck,
+    /// Rejects management messages the device's role does not allow.
+    role_check: RoleCheck,
+    /// Rejects messages a device's temporary grant does not cover.
+    grant_check: GrantCheck,
+    /// Limits the messages each device sends, if set.
+    rate_limit: Option<RateLimit>,
+    /// Logs rejected messages.
+    audit_rejections: AuditRejections,
+    /// Counts routed messages.
+    metrics: MessageMetrics,
+    /// Interceptors added after the built-in ones.
+    interceptors: Vec<Arc<dyn Interceptor>>,
     /// Path permissions for device file access control.
     path_permissions: Arc<PathPermissions>,
+    /// Security key requirements for high-privilege actions, if enabled.
+    security_keys: Option<Arc<SecurityKeyGate>>,
+    /// Sessions waiting for the operator to allow them, if every new session
+    /// must be confirmed.
+    session_confirmations: Option<Arc<SessionConfirmations>>,
+    /// Where session requests go once the operator has answered them.
+    answered_sessions: Option<mpsc::UnboundedSender<AnsweredSession>>,
+    /// SSH agent forwarding, if enabled.
+    agent_forwarder: Option<Arc<AgentForwarder>>,
+    /// X11 and Wayland forwarding, if enabled.
+    display_forwarder: Option<Arc<DisplayForwarder>>,
+    /// Environment files of sessions, if clients may update them.
+    env_exporter: Option<Arc<SessionEnvExporter>>,
+    /// Variables clients may set in sessions.
+    env_allowlist: EnvAllowlist,
+    /// Default shell of sessions and the shells clients may ask for.
+    shells: ShellPolicy,
+    /// Session templates clients can create sessions from, by name.
+    session_templates: BTreeMap<String, SessionTemplate>,
+    /// tmux and screen sessions on the host that sessions can attach to, if
+    /// enabled.
+    host_sessions: Option<Arc<HostSessions>>,
+    /// Remote power actions, if enabled.
+    power_manager: Option<Arc<PowerManager>>,
+    /// The effective configuration, if clients may read and patch it.
+    remote_config: Option<Arc<RemoteConfig>>,
+    /// Notifications for connected clients, if relayed.
+    notifier: Option<Notifier>,
+    /// Event webhooks, if any endpoints are configured.
+    webhooks: Option<Webhooks>,
+    /// Audit log sessions are recorded in, if enabled.
+    audit: Option<AuditLog>,
+    /// Records which device created each session, if session ends are
+    /// reported to their creators.
+    session_owners: Option<SessionOwners>,
+    /// Output of attached sessions, if streamed to clients.
+    output_streams: Option<Arc<OutputStreams>>,
+    /// Fake shell answering untrusted devices, if honeypot mode is enabled.
+    honeypot: Option<Arc<Honeypot>>,
+    /// Streamed downloads, if the daemon can push chunks to clients.
+    download_streams: Option<Arc<DownloadStreams>>,
+    /// Direct copies to and from other daemons, if enabled.
+    file_copy: Option<Arc<FileCopy>>,
+    /// Image previews for file browsers, if enabled.
+    thumbnailer: Option<Arc<Thumbnailer>>,
+    /// Files followed like `tail -f`, if enabled.
+    file_tails: Option<Arc<FileTails>>,
 }
 
 impl<S: SessionManager> MessageRouter<S> {
@@ -128,12 +325,411 @@ impl<S: SessionManager> MessageRouter<S> {
         trust_store: Arc<TrustStore>,
         path_permissions: Arc<PathPermissions>,
     ) -> Self {
+        let trust_cache = Arc::new(TrustCache::new(trust_store.clone()));
         Self {
             session_manager,
             file_transfer,
             directory_browser,
+            trust_check: TrustCheck::new(Arc::clone(&trust_cache)),
+            role_check: RoleCheck::new(Arc::clone(&trust_cache)),
+            grant_check: GrantCheck::new(trust_cache),
+            rate_limit: None,
+            audit_rejections: AuditRejections,
+            metrics: MessageMetrics::default(),
+            interceptors: Vec::new(),
             trust_store,
             path_permissions,
+            security_keys: None,
+            session_confirmations: None,
+            answered_sessions: None,
+            agent_forwarder: None,
+            display_forwarder: None,
+            env_exporter: None,
+            env_allowlist: EnvAllowlist::default(),
+            shells: ShellPolicy::default(),
+            session_templates: BTreeMap::new(),
+            host_sessions: None,
+            power_manager: None,
+            remote_config: None,
+            notifier: None,
+            webhooks: None,
+            audit: None,
+            session_owners: None,
+            output_streams: None,
+            honeypot: None,
+            download_streams: None,
+            file_copy: None,
+            thumbnailer: None,
+            file_tails: None,
+        }
+    }
+
+    /// Require security key assertions for the actions gated by `gate`.
+    pub fn with_security_keys(mut self, gate: Arc<SecurityKeyGate>) -> Self {
+        self.security_keys = Some(gate);
+        self
+    }
+
+    /// Create sessions only once the operator allows them through
+    /// `confirmations`.
+    ///
+    /// Requests wait for the answer without holding up the device's other
+    /// messages; once answered they are sent to `answered`, to be finished
+    /// with [`finish_answered_session`](Self::finish_answered_session).
+    pub fn with_session_confirmations(
+        mut self,
+        confirmations: Arc<SessionConfirmations>,
+        answered: mpsc::UnboundedSender<AnsweredSession>,
+    ) -> Self {
+        self.session_confirmations = Some(confirmations);
+        self.answered_sessions = Some(answered);
+        self
+    }
+
+    /// Allow sessions to forward the client's SSH agent through `forwarder`.
+    pub fn with_agent_forwarder(mut self, forwarder: Arc<AgentForwarder>) -> Self {
+        self.agent_forwarder = Some(forwarder);
+        self
+    }
+
+    /// Allow sessions to forward GUI applications to the client's display
+    /// through `forwarder`.
+    pub fn with_display_forwarder(mut self, forwarder: Arc<DisplayForwarder>) -> Self {
+        self.display_forwarder = Some(forwarder);
+        self
+    }
+
+    /// Give each session an environment file in `exporter` that clients can
+    /// update with `UpdateSessionEnv`.
+    pub fn with_env_exporter(mut self, exporter: Arc<SessionEnvExporter>) -> Self {
+        self.env_exporter = Some(exporter);
+        self
+    }
+
+    /// Restrict the variables clients may set in sessions to `allowlist`.
+    pub fn with_env_allowlist(mut self, allowlist: EnvAllowlist) -> Self {
+        self.env_allowlist = allowlist;
+        self
+    }
+
+    /// Run sessions with the default shell of `policy`, and restrict the
+    /// shells clients may ask for to its allowlist.
+    pub fn with_shell_policy(mut self, policy: ShellPolicy) -> Self {
+        self.shells = policy;
+        self
+    }
+
+    /// Offer `templates` to clients for creating sessions.
+    pub fn with_session_templates(mut self, templates: BTreeMap<String, SessionTemplate>) -> Self {
+        self.session_templates = templates;
+        self
+    }
+
+    /// Let sessions attach to tmux or screen sessions on the host through
+    /// `host_sessions`.
+    pub fn with_host_sessions(mut self, host_sessions: Arc<HostSessions>) -> Self {
+        self.host_sessions = Some(host_sessions);
+        self
+    }
+
+    /// Allow devices with the `power` capability to reboot, shut down or
+    /// suspend the host through `manager`.
+    pub fn with_power_manager(mut self, manager: Arc<PowerManager>) -> Self {
+        self.power_manager = Some(manager);
+        self
+    }
+
+    /// Let devices with a management role read the configuration, and
+    /// owners patch it, through `remote_config`.
+    pub fn with_remote_config(mut self, remote_config: Arc<RemoteConfig>) -> Self {
+        self.remote_config = Some(remote_config);
+        self
+    }
+
+    /// Push notifications about handled requests, such as an impending
+    /// power action, to clients through `notifier`.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Report approvals, new sessions, uploads and authentication failures
+    /// to `webhooks`.
+    pub fn with_webhooks(mut self, webhooks: Webhooks) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Record the sessions devices open in `audit`.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Record the device that creates each session in `owners`, so that it
+    /// can be told when the session ends.
+    pub fn with_session_owners(mut self, owners: SessionOwners) -> Self {
+        self.session_owners = Some(owners);
+        self
+    }
+
+    /// Stream the output of sessions to the devices attached to them with
+    /// `streams`.
+    pub fn with_output_streams(mut self, streams: Arc<OutputStreams>) -> Self {
+        self.output_streams = Some(streams);
+        self
+    }
+
+    /// Reject messages from a device beyond `per_second` a second.
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limit = Some(RateLimit::new(per_second));
+        self
+    }
+
+    /// Run `interceptor` on every message, after the built-in interceptors.
+    pub fn with_interceptor(mut self, interceptor: Arc<dyn Interceptor>) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Answer session, file and host requests from untrusted devices with
+    /// `honeypot` instead of rejecting them.
+    pub fn with_honeypot(mut self, honeypot: Arc<Honeypot>) -> Self {
+        self.honeypot = Some(honeypot);
+        self
+    }
+
+    /// Stream downloads that request a window through `streams`, instead of
+    /// answering them one chunk per request.
+    pub fn with_download_streams(mut self, streams: Arc<DownloadStreams>) -> Self {
+        self.download_streams = Some(streams);
+        self
+    }
+
+    /// Issue and accept direct file copies between daemons through `copy`.
+    pub fn with_file_copy(mut self, copy: Arc<FileCopy>) -> Self {
+        self.file_copy = Some(copy);
+        self
+    }
+
+    /// Describe files and preview images for file browsers with `thumbnailer`.
+    pub fn with_thumbnailer(mut self, thumbnailer: Arc<Thumbnailer>) -> Self {
+        self.thumbnailer = Some(thumbnailer);
+        self
+    }
+
+    /// Stream the end of files to devices with `tails`.
+    pub fn with_file_tails(mut self, tails: Arc<FileTails>) -> Self {
+        self.file_tails = Some(tails);
+        self
+    }
+
+    fn emit_webhook(&self, event: WebhookEvent, data: serde_json::Value) {
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.emit(event, data);
+        }
+    }
+
+    /// Drops what interceptors keep for a device, e.g. when it disconnects.
+    pub fn close_interceptors(&self, device_id: &DeviceId) {
+        for interceptor in self.interceptors() {
+            interceptor.device_disconnected(device_id);
+        }
+    }
+
+    /// Returns counts of the messages routed so far, by message type.
+    pub fn message_metrics(&self) -> BTreeMap<&'static str, MessageStats> {
+        self.metrics.snapshot()
+    }
+
+    /// Returns the interceptors in the order messages pass through them.
+    fn interceptors(&self) -> impl Iterator<Item = &dyn Interceptor> {
+        let built_in: [Option<&dyn Interceptor>; 6] = [
+            Some(&self.trust_check),
+            Some(&self.role_check),
+            Some(&self.grant_check),
+            self.rate_limit
+                .as_ref()
+                .map(|limit| limit as &dyn Interceptor),
+            Some(&self.audit_rejections),
+            Some(&self.metrics),
+        ];
+        built_in
+            .into_iter()
+            .flatten()
+            .chain(self.interceptors.iter().map(|i| i.as_ref()))
+    }
+
+    /// Stops SSH agent forwarding for all of a device's sessions, e.g. when
+    /// it disconnects.
+    pub fn close_agent_forwarding(&self, device_id: &DeviceId) {
+        if let Some(forwarder) = &self.agent_forwarder {
+            forwarder.close_device(device_id);
+        }
+    }
+
+    /// Stops display forwarding for all of a device's sessions, e.g. when it
+    /// disconnects.
+    pub fn close_display_forwarding(&self, device_id: &DeviceId) {
+        if let Some(forwarder) = &self.display_forwarder {
+            forwarder.close_device(device_id);
+        }
+    }
+
+    /// Stops a device's streamed downloads, e.g. when it disconnects.
+    pub fn close_download_streams(&self, device_id: &DeviceId) {
+        if let Some(streams) = &self.download_streams {
+            streams.close_device(device_id);
+        }
+    }
+
+    /// Brings file transfers to a chunk boundary, when the daemon stops.
+    ///
+    /// Streamed downloads stop after the chunk they are reading, waiting up
+    /// to `timeout`, and uploads in progress are flushed to disk.
+    pub async fn drain_transfers(&self, timeout: Duration) -> DrainedTransfers {
+        let mut drained = DrainedTransfers::default();
+        if let Some(streams) = &self.download_streams {
+            match streams.drain(timeout).await {
+                Some(downloads) => drained.downloads = downloads,
+                None => drained.timed_out = true,
+            }
+        }
+        match self.file_transfer.sync_uploads() {
+            Ok(uploads) => drained.uploads = uploads,
+            Err(e) => warn!(error = %e, "Failed to flush uploads"),
+        }
+        drained
+    }
+
+    /// Stops streaming session output to a device, e.g. when it disconnects.
+    pub fn close_output_streams(&self, device_id: &DeviceId) {
+        if let Some(streams) = &self.output_streams {
+            streams.close_device(device_id);
+        }
+    }
+
+    /// Stops the files a device follows, e.g. when it disconnects.
+    pub fn close_file_tails(&self, device_id: &DeviceId) {
+        if let Some(tails) = &self.file_tails {
+            tails.close_device(device_id);
+        }
+    }
+
+    /// Returns the sizes of the per-session and per-device maps, for soak
+    /// reports.
+    pub fn resource_counts(&self) -> RouterCounts {
+        RouterCounts {
+            session_owners: self.session_owners.as_ref().map_or(0, SessionOwners::len),
+            output_streams: self.output_streams.as_ref().map_or(0, |s| s.len()),
+            download_streams: self.download_streams.as_ref().map_or(0, |s| s.len()),
+            file_tails: self.file_tails.as_ref().map_or(0, |t| t.len()),
+        }
+    }
+
+    /// Returns a challenge if the action requires a security key assertion
+    /// that the device has not yet provided.
+    fn security_key_challenge(
+        &self,
+        device_id: &DeviceId,
+        action: GatedAction,
+        path: Option<&Path>,
+    ) -> Option<Message> {
+        let gate = self.security_keys.as_ref()?;
+        let now = current_timestamp_secs();
+        if !gate.requires_assertion(device_id, action, path, now) {
+            return None;
+        }
+
+        info!(
+            target: "audit",
+            device_id = %device_id,
+            action = action.as_str(),
+            "Security key assertion required"
+        );
+        Some(Message::SecurityKeyChallenge(
+            gate.issue_challenge(device_id, action, now),
+        ))
+    }
+
+    /// Asks the operator to allow a new session, if sessions must be
+    /// confirmed, and hands the request back to be finished once answered.
+    ///
+    /// Returns the request if no confirmation is needed.
+    fn request_confirmation(
+        &self,
+        req: SessionCreate,
+        device_id: &DeviceId,
+    ) -> Option<SessionCreate> {
+        let (Some(confirmations), Some(answered)) =
+            (&self.session_confirmations, &self.answered_sessions)
+        else {
+            return Some(req);
+        };
+        let device_name = match self.trust_store.get_device(device_id) {
+            Ok(Some(device)) => device.name,
+            _ => device_id.to_string(),
+        };
+
+        info!(
+            target: "audit",
+            device_id = %device_id,
+            device_name = %device_name,
+            "Waiting for the operator to confirm a session"
+        );
+        let confirmations = Arc::clone(confirmations);
+        let answered = answered.clone();
+        let device_id = *device_id;
+        tokio::spawn(async move {
+            let outcome = confirmations.confirm(device_id, device_name).await;
+            info!(
+                target: "audit",
+                device_id = %device_id,
+                outcome = ?outcome,
+                "Session confirmation answered"
+            );
+            let _ = answered.send(AnsweredSession {
+                device_id,
+                request: req,
+                outcome,
+            });
+        });
+        None
+    }
+
+    /// Creates a session the operator has allowed, or returns why it was
+    /// refused, to be sent to the device that asked for it.
+    ///
+    /// The device's trust is checked again, as it may have been revoked
+    /// while the request waited.
+    pub async fn finish_answered_session(&self, answered: AnsweredSession) -> RouterResult {
+        let AnsweredSession {
+            device_id,
+            request,
+            outcome,
+        } = answered;
+        match outcome {
+            ConfirmOutcome::Allowed => {
+                self.require_trusted(&device_id)?;
+                self.create_session(request, &device_id).await
+            }
+            ConfirmOutcome::Denied => Err(RouterError::Permission(
+                "session denied by the operator".to_string(),
+            )),
+            ConfirmOutcome::TimedOut => Err(RouterError::Permission(format!(
+                "session not confirmed by the operator within {} seconds",
+                self.session_confirmations
+                    .as_ref()
+                    .map_or(0, |confirmations| confirmations.timeout().as_secs())
+            ))),
+        }
+    }
+
+    /// Denies the session requests a device has waiting for confirmation,
+    /// e.g. when it disconnects.
+    pub fn close_session_confirmations(&self, device_id: &DeviceId) {
+        if let Some(confirmations) = &self.session_confirmations {
+            confirmations.cancel_device(device_id);
         }
     }
 
@@ -188,26 +784,37 @@ impl<S: SessionManager> MessageRouter<S> {
     }
 
     /// Verifies that the device is trusted.
-    ///
-    /// Returns `Ok(())` if the device has `TrustLevel::Trusted`, otherwise
-    /// returns `Err(RouterError::Device)` with an appropriate error message.
     fn require_trusted(&self, device_id: &DeviceId) -> Result<(), RouterError> {
-        match self.trust_store.get_device(device_id) {
-            Ok(Some(device)) => match device.trust_level {
-                TrustLevel::Trusted => Ok(()),
-                TrustLevel::Unknown => {
-                    Err(RouterError::Device("Device pending approval".to_string()))
-                }
-                TrustLevel::Revoked => {
-                    Err(RouterError::Device("Device has been revoked".to_string()))
-                }
-            },
-            Ok(None) => Err(RouterError::Device("Device not registered".to_string())),
-            Err(e) => Err(RouterError::Device(format!(
-                "Failed to check device trust: {}",
-                e
-            ))),
+        self.trust_check.check(device_id)
+    }
+
+    /// Verify that a device has been granted a capability.
+    ///
+    /// Denials are logged under the `audit` target.
+    fn require_capability(
+        &self,
+        device_id: &DeviceId,
+        capability: &str,
+    ) -> Result<(), RouterError> {
+        let allowed = self
+            .trust_store
+            .get_device(device_id)
+            .ok()
+            .flatten()
+            .is_some_and(|device| device.has_capability(capability));
+        if !allowed {
+            warn!(
+                target: "audit",
+                device_id = %device_id,
+                capability,
+                "Request denied: missing capability"
+            );
+            return Err(RouterError::Permission(format!(
+                "device lacks the {} capability",
+                capability
+            )));
         }
+        Ok(())
     }
 
     /// Route a message to the appropriate handler.
@@ -226,18 +833,104 @@ impl<S: SessionManager> MessageRouter<S> {
         message: Message,
         device_id: &DeviceId,
         authenticated_public_key: Option<&[u8; 32]>,
+    ) -> RouterResult {
+        let span = telemetry::route_span(&message);
+        let result = self
+            .run_pipeline(message, device_id, authenticated_public_key)
+            .instrument(span)
+            .await;
+        if let Err(RouterError::Auth(reason)) = &result {
+            self.emit_webhook(
+                WebhookEvent::AuthFailure,
+                serde_json::json!({ "device_id": device_id.to_string(), "reason": reason }),
+            );
+        }
+        result
+    }
+
+    /// Passes the message through the interceptors and to its handler.
+    ///
+    /// Requests the honeypot answers never reach the interceptors: they
+    /// guard the real daemon, which the device does not get to see.
+    async fn run_pipeline(
+        &self,
+        message: Message,
+        device_id: &DeviceId,
+        authenticated_public_key: Option<&[u8; 32]>,
     ) -> RouterResult {
         debug!(?message, ?device_id, "Routing message");
 
+        if let Some(honeypot) = &self.honeypot {
+            if Honeypot::intercepts(&message) && self.require_trusted(device_id).is_err() {
+                return Ok(honeypot.handle(message, device_id).await);
+            }
+        }
+
+        let started = Instant::now();
+        let name = message.name();
+        let request = Request {
+            message: &message,
+            device_id,
+        };
+        let rejection = self.interceptors().find_map(|interceptor| {
+            interceptor
+                .before(&request)
+                .err()
+                .map(|e| (interceptor.name(), e))
+        });
+        let (result, rejected_by) = match rejection {
+            Some((interceptor, e)) => (Err(e), Some(interceptor)),
+            None => (
+                self.dispatch(message, device_id, authenticated_public_key)
+                    .await,
+                None,
+            ),
+        };
+
+        let outcome = Outcome {
+            message: name,
+            device_id,
+            result: &result,
+            rejected_by,
+            elapsed: started.elapsed(),
+        };
+        for interceptor in self.interceptors() {
+            interceptor.after(&outcome);
+        }
+        result
+    }
+
+    async fn dispatch(
+        &self,
+        message: Message,
+        device_id: &DeviceId,
+        authenticated_public_key: Option<&[u8; 32]>,
+    ) -> RouterResult {
         match message {
             // Session messages (require trusted device)
             Message::SessionCreate(req) => self.handle_session_create(req, device_id).await,
             Message::SessionAttach(req) => self.handle_session_attach(req, device_id).await,
-            Message::SessionDetach(req) => self.handle_session_detach(req).await,
+            Message::SessionDetach(req) => self.handle_session_detach(req, device_id).await,
             Message::SessionKill(req) => self.handle_session_kill(req, device_id).await,
             Message::SessionResize(req) => self.handle_session_resize(req).await,
-            Message::SessionData(data) => self.handle_session_data(data, device_id).await,
-            Message::SessionCreated(_) | Message::SessionClosed(_) => {
+            Message::SessionSignal(req) => self.handle_session_signal(req).await,
+            Message::UpdateSessionEnv(req) => self.handle_update_session_env(req, device_id),
+            Message::SessionData(data) => self.handle_session_data(data).await,
+            Message::SessionMetadataRequest(req) => self.handle_session_metadata(req).await,
+            Message::SessionSearchRequest(req) => self.handle_session_search(req).await,
+            Message::ListTemplates(_) => self.handle_list_templates(),
+            Message::AgentData(data) => self.handle_agent_data(data, device_id).await,
+            Message::AgentClose(close) => self.handle_agent_close(close, device_id).await,
+            Message::DisplayData(data) => self.handle_display_data(data, device_id),
+            Message::DisplayClose(close) => self.handle_display_close(close, device_id),
+            Message::SessionCreated(_)
+            | Message::SessionOutputTruncated(_)
+            | Message::SessionClosed(_)
+            | Message::SessionMetadata(_)
+            | Message::SessionSearchResult(_)
+            | Message::TemplateList(_)
+            | Message::AgentOpen(_)
+            | Message::DisplayOpen(_) => {
                 // These are response messages, not requests - ignore them
                 debug!("Ignoring response message received as request");
                 Ok(None)
@@ -246,24 +939,85 @@ impl<S: SessionManager> MessageRouter<S> {
             // File messages (require permission checks)
             Message::FileListRequest(req) => self.handle_file_list(req, device_id).await,
             Message::FileDownloadRequest(req) => self.handle_file_download(req, device_id).await,
+            Message::FileDownloadAck(ack) => self.handle_file_download_ack(ack, device_id).await,
             Message::FileUploadStart(req) => self.handle_file_upload_start(req, device_id).await,
             Message::FileUploadChunk(req) => self.handle_file_upload_chunk(req, device_id).await,
             Message::FileUploadComplete(req) => {
                 self.handle_file_upload_complete(req, device_id).await
             }
-            Message::FileListResponse(_) | Message::FileDownloadChunk(_) => {
+            Message::FileCopyGrantRequest(req) => {
+                self.handle_file_copy_grant_request(req, device_id).await
+            }
+            Message::FileCopyRequest(req) => self.handle_file_copy_request(req, device_id).await,
+            Message::FileReadText(req) => self.handle_file_read_text(req, device_id).await,
+            Message::FileRootsRequest(req) => self.handle_file_roots(req, device_id),
+            Message::FileWriteText(req) => self.handle_file_write_text(req, device_id).await,
+            Message::FileThumbnailRequest(req) => self.handle_file_thumbnail(req, device_id).await,
+            Message::FileTailRequest(req) => self.handle_file_tail(req, device_id).await,
+            Message::FileTailStop(req) => self.handle_file_tail_stop(req, device_id),
+            Message::FileListResponse(_)
+            | Message::FileDownloadChunk(_)
+            | Message::FileTransferReport(_)
+            | Message::FileCopyGrant(_)
+            | Message::FileText(_)
+            | Message::FileTextWritten(_)
+            | Message::FileRoots(_)
+            | Message::FileThumbnail(_)
+            | Message::FileTailData(_) => {
                 // These are response messages, not requests - ignore them
                 debug!("Ignoring response message received as request");
                 Ok(None)
             }
 
             // Device messages
-            Message::DeviceInfo(info) => self.handle_device_info(info).await,
-            Message::DeviceApprovalRequest(req) => {
-                self.handle_device_approval_request(req, authenticated_public_key)
+            Message::DeviceInfo(mut info) => {
+                info.name = self.normalize_device_name(&info.device_id, &info.name).name;
+                self.handle_device_info(info).await
+            }
+            Message::DeviceApprovalRequest(mut req) => {
+                let device_name = self.normalize_device_name(&req.device_id, &req.name);
+                req.name = device_name.name.clone();
+                let name = req.name.clone();
+                let result = self
+                    .handle_device_approval_request(
+                        req,
+                        device_name.raw_if_changed(),
+                        authenticated_public_key,
+                    )
+                    .await;
+                match (&self.honeypot, result) {
+                    (Some(honeypot), Ok(Some(Message::DeviceRejected(rejected)))) => {
+                        Ok(Some(honeypot.approve(device_id, rejected.device_id, &name)))
+                    }
+                    (_, result) => result,
+                }
+            }
+            Message::SecurityKeyAssertion(assertion) => {
+                self.handle_security_key_assertion(assertion, device_id)
                     .await
             }
-            Message::DeviceApproved(_) | Message::DeviceRejected(_) => {
+            Message::DeviceApproved(_)
+            | Message::DeviceRejected(_)
+            | Message::SecurityKeyChallenge(_)
+            | Message::SecurityKeyVerified(_) => {
+                // These are response messages, not requests - ignore them
+                debug!("Ignoring response message received as request");
+                Ok(None)
+            }
+
+            // Host messages
+            Message::SystemInfoRequest(req) => self.handle_system_info(req),
+            Message::ProcessListRequest(req) => self.handle_process_list(req).await,
+            Message::ProcessKill(req) => self.handle_process_kill(req, device_id),
+            Message::PowerActionRequest(req) => self.handle_power_request(req, device_id),
+            Message::PowerActionConfirm(req) => self.handle_power_confirm(req, device_id),
+            Message::ConfigRequest(_) => self.handle_config_request(),
+            Message::ConfigPatch(req) => self.handle_config_patch(req, device_id),
+            Message::SystemInfo(_)
+            | Message::ProcessList(_)
+            | Message::PowerActionChallenge(_)
+            | Message::PowerActionStarted(_)
+            | Message::ConfigView(_) => {
                 // These are response messages, not requests - ignore them
                 debug!("Ignoring response message received as request");
                 Ok(None)
@@ -286,6 +1040,16 @@ impl<S: SessionManager> MessageRouter<S> {
                 debug!("Received capabilities message");
                 Ok(None)
             }
+            Message::Notification(_) | Message::ServerShutdownPending(_) => {
+                // Notifications only flow from the daemon to clients
+                debug!("Ignoring notification received from client");
+                Ok(None)
+            }
+            Message::Unknown { tag, .. } => {
+                // Sent by a newer client; answer so it can fall back
+                warn!(%tag, "Received unsupported message type");
+                Err(RouterError::Unsupported(tag))
+            }
         }
     }
 
@@ -298,22 +1062,152 @@ impl<S: SessionManager> MessageRouter<S> {
         req: SessionCreate,
         device_id: &DeviceId,
     ) -> RouterResult {
-        // Verify device is trusted before creating session
-        self.require_trusted(device_id)?;
+        if let Some(challenge) =
+            self.security_key_challenge(device_id, GatedAction::SessionCreate, None)
+        {
+            return Ok(Some(challenge));
+        }
+        match self.request_confirmation(req, device_id) {
+            Some(req) => self.create_session(req, device_id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Creates a session the device may open.
+    async fn create_session(&self, mut req: SessionCreate, device_id: &DeviceId) -> RouterResult {
+        // Template variables and shells come from the operator and skip the
+        // allowlists
+        client_env::apply_client_hints(&mut req);
+        self.env_allowlist.filter(&mut req.env);
+        if let Some(shell) = &req.shell {
+            self.shells.check(shell).map_err(|e| match e {
+                ShellError::NotPermitted(_) => RouterError::Permission(e.to_string()),
+                _ => RouterError::InvalidRequest(e.to_string()),
+            })?;
+        }
+
+        let template = req.template.take();
+        let mut command = match &template {
+            Some(name) => self.apply_template(name, &mut req)?,
+            None => None,
+        };
+        if req.shell.is_none() {
+            req.shell = self.shells.default_shell().map(str::to_string);
+        }
+        let host_session = req.host_session.take();
+        if let Some(host_session) = &host_session {
+            if command.is_some() {
+                return Err(RouterError::InvalidRequest(
+                    "host_session cannot be used with a template that runs a command".to_string(),
+                ));
+            }
+            command = Some(self.host_session_command(host_session).await?);
+        }
+
+        let agent_socket = if req.forward_agent {
+            Some(self.prepare_agent_socket(device_id)?)
+        } else {
+            None
+        };
+        let display_socket = match req.forward_display {
+            Some(protocol) => Some(self.prepare_display_socket(device_id, protocol)?),
+            None => None,
+        };
 
         info!(
             cols = req.cols,
             rows = req.rows,
             shell = ?req.shell,
+            login_shell = req.login_shell,
+            template = ?template,
+            forward_agent = req.forward_agent,
+            forward_display = ?req.forward_display,
+            host_session = ?host_session.as_ref().map(|host| &host.name),
             "Creating new session"
         );
 
-        let (session_id, pid) = self
-            .session_manager
-            .create(req.shell, req.cols, req.rows, req.env, req.cwd)
-            .await?;
+        let env_file = match &self.env_exporter {
+            Some(exporter) => Some(
+                exporter
+                    .prepare()
+                    .map_err(|e| RouterError::Internal(e.to_string()))?,
+            ),
+            None => None,
+        };
+
+        let mut env = req.env;
+        if let Some(file) = &env_file {
+            env.retain(|(key, _)| key != ENV_FILE_VAR);
+            env.push((
+                ENV_FILE_VAR.to_string(),
+                file.path().to_string_lossy().into_owned(),
+            ));
+        }
+        if let Some((_, socket)) = &agent_socket {
+            env.retain(|(key, _)| key != "SSH_AUTH_SOCK");
+            env.push((
+                "SSH_AUTH_SOCK".to_string(),
+                socket.path().to_string_lossy().into_owned(),
+            ));
+        }
+        if let Some((_, socket)) = &display_socket {
+            let (name, value) = socket.env();
+            env.retain(|(key, _)| key != name);
+            env.push((name.to_string(), value.to_string()));
+        }
+
+        let (session_id, pid) = if req.login_shell {
+            self.session_manager
+                .create_login_shell(req.shell, req.cols, req.rows, env, req.cwd)
+                .await?
+        } else {
+            self.session_manager
+                .create(req.shell, req.cols, req.rows, env, req.cwd)
+                .await?
+        };
+
+        Span::current().record("session_id", session_id.as_str());
+        info!(pid = pid, "Session created");
+        self.emit_webhook(
+            WebhookEvent::SessionStarted,
+            serde_json::json!({
+                "device_id": device_id.to_string(),
+                "session_id": session_id.to_string(),
+                "pid": pid,
+                "template": template,
+            }),
+        );
+
+        if let Some(audit) = &self.audit {
+            let device_name = self
+                .trust_store
+                .get_device(device_id)
+                .ok()
+                .flatten()
+                .map(|device| device.name)
+                .unwrap_or_default();
+            audit.session_started(&session_id, pid, device_id, &device_name);
+        }
+        if let Some(owners) = &self.session_owners {
+            owners.insert(session_id.clone(), *device_id);
+        }
 
-        info!(session_id = %session_id, pid = pid, "Session created");
+        if let Some((forwarder, socket)) = agent_socket {
+            forwarder.start(&session_id, *device_id, socket);
+        }
+        if let Some((forwarder, socket)) = display_socket {
+            forwarder.start(&session_id, *device_id, socket);
+        }
+        if let (Some(exporter), Some(file)) = (&self.env_exporter, env_file) {
+            exporter.start(&session_id, *device_id, file);
+        }
+
+        if let Some(command) = command {
+            // Typed as if entered at the prompt, so the shell stays open afterwards
+            self.session_manager
+                .write(&session_id, format!("{}\r", command).as_bytes())
+                .await?;
+        }
 
         Ok(Some(Message::SessionCreated(SessionCreated {
             session_id: session_id.to_string(),
@@ -321,37 +1215,114 @@ impl<S: SessionManager> MessageRouter<S> {
         })))
     }
 
+    /// Fills in the parts of `req` that template `name` sets and the client
+    /// left open, and returns the template's initial command.
+    fn apply_template(
+        &self,
+        name: &str,
+        req: &mut SessionCreate,
+    ) -> Result<Option<String>, RouterError> {
+        let template = self.session_templates.get(name).ok_or_else(|| {
+            RouterError::InvalidRequest(format!("unknown session template: {}", name))
+        })?;
+
+        if req.shell.is_none() {
+            req.shell = template.shell.clone();
+        }
+        if req.cwd.is_none() {
+            req.cwd = template.cwd.clone();
+        }
+        req.login_shell |= template.login_shell;
+        if let Some(cols) = template.cols {
+            req.cols = cols;
+        }
+        if let Some(rows) = template.rows {
+            req.rows = rows;
+        }
+
+        // Variables from the request override the template's
+        let mut env: Vec<(String, String)> = template
+            .env
+            .iter()
+            .filter(|(key, _)| !req.env.iter().any(|(k, _)| k == *key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        env.append(&mut req.env);
+        req.env = env;
+
+        Ok(template.command.clone())
+    }
+
+    /// Returns the command that attaches a new session to a tmux or screen
+    /// session on the host.
+    async fn host_session_command(&self, request: &HostSession) -> Result<String, RouterError> {
+        let host_sessions = self
+            .host_sessions
+            .as_ref()
+            .ok_or_else(|| RouterError::InvalidRequest("host sessions are disabled".to_string()))?;
+        host_sessions.command(request).await.map_err(|e| match e {
+            HostSessionError::Io(..) => RouterError::Internal(e.to_string()),
+            _ => RouterError::InvalidRequest(e.to_string()),
+        })
+    }
+
+    fn handle_list_templates(&self) -> RouterResult {
+        let templates = self
+            .session_templates
+            .iter()
+            .map(|(name, template)| TemplateInfo {
+                name: name.clone(),
+                description: template.description.clone(),
+                shell: template.shell.clone(),
+                cwd: template.cwd.clone(),
+                command: template.command.clone(),
+                cols: template.cols,
+                rows: template.rows,
+            })
+            .collect();
+
+        Ok(Some(Message::TemplateList(TemplateList { templates })))
+    }
+
     async fn handle_session_attach(
         &self,
         req: SessionAttach,
         device_id: &DeviceId,
     ) -> RouterResult {
-        // Verify device is trusted before attaching to session
-        self.require_trusted(device_id)?;
-
         info!(session_id = %req.session_id, "Attaching to session");
 
         let session_id: SessionId = req.session_id.clone();
-        let _rx = self.session_manager.attach(&session_id).await?;
+        // Clients watch live, so one that falls behind skips output rather
+        // than holding up the session
+        let rx = self
+            .session_manager
+            .attach(&session_id, LagPolicy::DropWithMarker)
+            .await?;
 
-        // Return None - actual data will be streamed via the receiver
-        // The caller is responsible for handling the broadcast receiver
+        // Output is streamed outside of the request/response exchange
+        if let Some(streams) = &self.output_streams {
+            streams.start(session_id, *device_id, rx);
+        }
         Ok(None)
     }
 
-    async fn handle_session_detach(&self, req: SessionDetach) -> RouterResult {
+    async fn handle_session_detach(
+        &self,
+        req: SessionDetach,
+        device_id: &DeviceId,
+    ) -> RouterResult {
         info!(session_id = %req.session_id, "Detaching from session");
 
         let session_id: SessionId = req.session_id.clone();
         self.session_manager.detach(&session_id).await?;
+        if let Some(streams) = &self.output_streams {
+            streams.stop(&session_id, device_id);
+        }
 
         Ok(None)
     }
 
     async fn handle_session_kill(&self, req: SessionKill, device_id: &DeviceId) -> RouterResult {
-        // Verify device is trusted before killing session
-        self.require_trusted(device_id)?;
-
         info!(
             session_id = %req.session_id,
             signal = ?req.signal,
@@ -359,21 +1330,32 @@ impl<S: SessionManager> MessageRouter<S> {
         );
 
         let session_id: SessionId = req.session_id.clone();
+        // The response tells the requester, so the owner only needs telling
+        // when it is another device
+        if let Some(owners) = &self.session_owners {
+            owners.release(&session_id, device_id);
+        }
         let status = self.session_manager.kill(&session_id, req.signal).await?;
 
-        // Convert SessionStatus enum to exit_code/signal
-        let (exit_code, signal) = match status {
-            SessionStatus::Running => (None, None),
-            SessionStatus::Exited(code) => (Some(code), None),
-            SessionStatus::Killed(sig) => (None, Some(sig)),
-            SessionStatus::Terminated => (None, None),
-        };
+        if let Some(forwarder) = &self.agent_forwarder {
+            forwarder.close_session(&session_id);
+        }
+        if let Some(forwarder) = &self.display_forwarder {
+            forwarder.close_session(&session_id);
+        }
+        if let Some(exporter) = &self.env_exporter {
+            exporter.close_session(&session_id);
+        }
+        if let Some(streams) = &self.output_streams {
+            streams.close_session(&session_id);
+        }
 
         Ok(Some(Message::SessionClosed(SessionClosed {
             session_id: req.session_id,
-            exit_code,
-            signal,
-            reason: Some("Session killed by request".to_string()),
+            exit_code: status.exit_code(),
+            signal: status.signal(),
+            reason: Some(SessionCloseReason::Killed.description().to_string()),
+            close_reason: Some(SessionCloseReason::Killed),
         })))
     }
 
@@ -385,6 +1367,12 @@ impl<S: SessionManager> MessageRouter<S> {
             "Resizing session"
         );
 
+        if req.cols == 0 || req.rows == 0 {
+            return Err(RouterError::InvalidRequest(
+                "Terminal size must be non-zero".to_string(),
+            ));
+        }
+
         let session_id: SessionId = req.session_id.clone();
         self.session_manager
             .resize(&session_id, req.cols, req.rows)
@@ -393,10 +1381,109 @@ impl<S: SessionManager> MessageRouter<S> {
         Ok(None)
     }
 
-    async fn handle_session_data(&self, data: SessionData, device_id: &DeviceId) -> RouterResult {
-        // Verify device is trusted before sending session data
-        self.require_trusted(device_id)?;
+    async fn handle_session_signal(&self, req: SessionSignal) -> RouterResult {
+        debug!(
+            session_id = %req.session_id,
+            signal = req.signal.name(),
+            "Signaling session"
+        );
+
+        let session_id: SessionId = req.session_id.clone();
+        self.session_manager.signal(&session_id, req.signal).await?;
+
+        Ok(None)
+    }
+
+    fn handle_update_session_env(
+        &self,
+        req: UpdateSessionEnv,
+        device_id: &DeviceId,
+    ) -> RouterResult {
+        let exporter = self.env_exporter.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("Session environment updates are disabled".to_string())
+        })?;
+
+        debug!(
+            session_id = %req.session_id,
+            set = req.set.len(),
+            unset = req.unset.len(),
+            "Updating session environment"
+        );
+
+        if let Some((name, _)) = req
+            .set
+            .iter()
+            .find(|(name, _)| !self.env_allowlist.allows(name))
+        {
+            return Err(RouterError::InvalidRequest(format!(
+                "{} is not in the session env_allowlist",
+                name
+            )));
+        }
+
+        let session_id: SessionId = req.session_id.clone();
+        if !self.session_manager.exists(&session_id) {
+            return Err(SessionError::NotFound(session_id).into());
+        }
+        exporter
+            .update(&session_id, device_id, req.set, req.unset)
+            .map_err(|e| match e {
+                EnvError::Io(e) => RouterError::Internal(e.to_string()),
+                e => RouterError::InvalidRequest(e.to_string()),
+            })?;
+
+        Ok(None)
+    }
+
+    async fn handle_session_metadata(&self, req: SessionMetadataRequest) -> RouterResult {
+        let session_id: SessionId = req.session_id.clone();
+        let info = self
+            .session_manager
+            .get(&session_id)
+            .await
+            .ok_or(SessionError::NotFound(session_id))?;
+
+        Ok(Some(Message::SessionMetadata(SessionMetadata {
+            session_id: req.session_id,
+            cwd: info.shell.cwd,
+            last_exit_code: info.shell.last_exit_code,
+            last_command: info.shell.last_command,
+            command_running: info.shell.command_running,
+        })))
+    }
+
+    async fn handle_session_search(&self, req: SessionSearchRequest) -> RouterResult {
+        let max_results = match req.max_results {
+            0 => scrollback::DEFAULT_SEARCH_RESULTS,
+            n => (n as usize).min(scrollback::MAX_SEARCH_RESULTS),
+        };
+        let query = SearchQuery {
+            pattern: req.pattern,
+            regex: req.regex,
+            case_sensitive: req.case_sensitive,
+            max_results,
+        };
+        let results = self.session_manager.search(&req.session_id, &query).await?;
+
+        Ok(Some(Message::SessionSearchResult(SessionSearchResult {
+            session_id: req.session_id,
+            matches: results
+                .matches
+                .into_iter()
+                .map(|m| SearchMatch {
+                    line: m.line,
+                    start: m.start as u32,
+                    end: m.end as u32,
+                    text: m.text,
+                })
+                .collect(),
+            truncated: results.truncated,
+            first_line: results.first_line,
+            last_line: results.last_line,
+        })))
+    }
 
+    async fn handle_session_data(&self, data: SessionData) -> RouterResult {
         // Only handle stdin data (client -> daemon)
         if data.stream != DataStream::Stdin {
             return Err(RouterError::InvalidRequest(
@@ -415,23 +1502,28 @@ impl<S: SessionManager> MessageRouter<S> {
     // =========================================================================
 
     async fn handle_file_list(&self, req: FileListRequest, device_id: &DeviceId) -> RouterResult {
-        debug!(path = %req.path, include_hidden = req.include_hidden, "Listing directory");
+        debug!(
+            path = %req.path,
+            include_hidden = req.include_hidden,
+            detail = ?req.detail,
+            "Listing directory"
+        );
 
         let path = Path::new(&req.path);
 
         // Check permission before listing directory
         self.check_file_permission(device_id, path, FileOperation::List)?;
 
+        let include_hidden = self
+            .directory_browser
+            .show_hidden(device_id, req.include_hidden);
         let entries = self
             .directory_browser
-            .list_directory(path, req.include_hidden)
-            .map_err(|e| RouterError::File(e.to_string()))?;
-
-        let protocol_entries: Vec<_> = entries.iter().map(|e| e.to_protocol()).collect();
+            .list_directory(path, include_hidden)?;
 
         Ok(Some(Message::FileListResponse(FileListResponse {
             path: req.path,
-            entries: protocol_entries,
+            entries: protocol_entries(&entries, req.detail),
         })))
     }
 
@@ -444,26 +1536,50 @@ impl<S: SessionManager> MessageRouter<S> {
             path = %req.path,
             offset = req.offset,
             chunk_size = req.chunk_size,
+            verify = req.verify,
+            window = req.window,
             "Downloading file chunk"
         );
 
-        let path = Path::new(&req.path);
-
         // Check permission before downloading file
-        self.check_file_permission(device_id, path, FileOperation::Read)?;
+        self.check_file_permission(device_id, Path::new(&req.path), FileOperation::Read)?;
+
+        let chunk = self.file_transfer.download_chunk_message(
+            &req.path,
+            req.offset,
+            req.chunk_size,
+            req.verify,
+        )?;
+
+        // The first chunk is the response; the rest of a windowed download
+        // is pushed as the client acks it
+        if req.window > 0 {
+            if let Some(streams) = &self.download_streams {
+                let stream = DownloadStream {
+                    path: req.path,
+                    offset: req.offset,
+                    chunk_size: req.chunk_size,
+                    window: req.window.min(MAX_DOWNLOAD_WINDOW),
+                    verify: req.verify,
+                };
+                streams.start(Arc::clone(&self.file_transfer), *device_id, stream, &chunk);
+            }
+        }
 
-        let (data, total_size, is_last) = self
-            .file_transfer
-            .download_chunk(path, req.offset, req.chunk_size)
-            .map_err(|e| RouterError::File(e.to_string()))?;
+        Ok(Some(Message::FileDownloadChunk(chunk)))
+    }
 
-        Ok(Some(Message::FileDownloadChunk(FileDownloadChunk {
-            path: req.path,
-            offset: req.offset,
-            total_size,
-            data,
-            is_last,
-        })))
+    async fn handle_file_download_ack(
+        &self,
+        ack: FileDownloadAck,
+        device_id: &DeviceId,
+    ) -> RouterResult {
+        if let Some(streams) = &self.download_streams {
+            if !streams.ack(device_id, &ack.path, ack.offset) {
+                debug!(path = %ack.path, "Ignoring ack for a download that is not streaming");
+            }
+        }
+        Ok(None)
     }
 
     async fn handle_file_upload_start(
@@ -484,14 +1600,185 @@ impl<S: SessionManager> MessageRouter<S> {
         // Check permission before starting upload
         self.check_file_permission(device_id, path, FileOperation::Write)?;
 
+        if let Some(challenge) =
+            self.security_key_challenge(device_id, GatedAction::FileWrite, Some(path))
+        {
+            return Ok(Some(challenge));
+        }
+
         self.file_transfer
-            .start_upload(path, req.size, req.mode, req.overwrite)
-            .map_err(|e| RouterError::File(e.to_string()))?;
+            .start_upload(path, req.size, req.mode, req.overwrite)?;
 
         // No response needed - client should start sending chunks
         Ok(None)
     }
 
+    fn handle_file_roots(&self, _req: FileRootsRequest, device_id: &DeviceId) -> RouterResult {
+        let roots = self
+            .path_permissions
+            .device_roots(device_id)
+            .map_err(|e| RouterError::Internal(format!("Failed to list file roots: {}", e)))?
+            .into_iter()
+            .filter_map(|(path, level)| {
+                let access = match level {
+                    PermissionLevel::None => return None,
+                    PermissionLevel::Read => FileAccess::Read,
+                    PermissionLevel::ReadWrite => FileAccess::ReadWrite,
+                    PermissionLevel::Full => FileAccess::Full,
+                };
+                let usage = sysmon::filesystem_usage(&path);
+                Some(FileRoot {
+                    path: path.display().to_string(),
+                    access,
+                    total_bytes: usage.as_ref().map(|usage| usage.total),
+                    available_bytes: usage.as_ref().map(|usage| usage.available),
+                })
+            })
+            .collect();
+
+        Ok(Some(Message::FileRoots(FileRoots { roots })))
+    }
+
+    async fn handle_file_thumbnail(
+        &self,
+        req: FileThumbnailRequest,
+        device_id: &DeviceId,
+    ) -> RouterResult {
+        debug!(path = %req.path, max_size = req.max_size, "Making file thumbnail");
+
+        let thumbnailer = self.thumbnailer.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("file thumbnails are not enabled".to_string())
+        })?;
+        let path = Path::new(&req.path);
+        self.check_file_permission(device_id, path, FileOperation::Read)?;
+
+        let canonical = self.directory_browser.validate_path(path)?;
+        let mut thumbnail = thumbnailer.thumbnail(&canonical, req.max_size).await?;
+        thumbnail.path = req.path;
+
+        Ok(Some(Message::FileThumbnail(thumbnail)))
+    }
+
+    async fn handle_file_tail(&self, req: FileTailRequest, device_id: &DeviceId) -> RouterResult {
+        debug!(path = %req.path, lines = req.lines, follow = req.follow, "Tailing file");
+
+        let tails = self.file_tails.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("file tailing is not enabled".to_string())
+        })?;
+        let path = Path::new(&req.path);
+        self.check_file_permission(device_id, path, FileOperation::Read)?;
+
+        let canonical = self.directory_browser.validate_path(path)?;
+
+        // The last lines are the response; appended data is pushed as it
+        // arrives
+        let data = tails.start(*device_id, req.path, canonical, req.lines, req.follow)?;
+
+        Ok(Some(Message::FileTailData(data)))
+    }
+
+    fn handle_file_tail_stop(&self, req: FileTailStop, device_id: &DeviceId) -> RouterResult {
+        if let Some(tails) = &self.file_tails {
+            if !tails.stop(device_id, &req.path) {
+                debug!(path = %req.path, "Ignoring stop for a file that is not followed");
+            }
+        }
+        Ok(None)
+    }
+
+    async fn handle_file_read_text(&self, req: FileReadText, device_id: &DeviceId) -> RouterResult {
+        debug!(path = %req.path, "Reading text file");
+
+        let path = Path::new(&req.path);
+        self.check_file_permission(device_id, path, FileOperation::Read)?;
+
+        let (content, hash) = self.file_transfer.read_text(path)?;
+
+        Ok(Some(Message::FileText(FileText {
+            path: req.path,
+            content,
+            hash: hash.to_vec(),
+        })))
+    }
+
+    async fn handle_file_write_text(
+        &self,
+        req: FileWriteText,
+        device_id: &DeviceId,
+    ) -> RouterResult {
+        debug!(
+            path = %req.path,
+            size = req.content.len(),
+            create = req.expected_hash.is_none(),
+            "Writing text file"
+        );
+
+        let path = Path::new(&req.path);
+        self.check_file_permission(device_id, path, FileOperation::Write)?;
+
+        if let Some(challenge) =
+            self.security_key_challenge(device_id, GatedAction::FileWrite, Some(path))
+        {
+            return Ok(Some(challenge));
+        }
+
+        let hash =
+            self.file_transfer
+                .write_text(path, &req.content, req.expected_hash.as_deref())?;
+
+        Ok(Some(Message::FileTextWritten(FileTextWritten {
+            path: req.path,
+            hash: hash.to_vec(),
+        })))
+    }
+
+    async fn handle_file_copy_grant_request(
+        &self,
+        req: FileCopyGrantRequest,
+        device_id: &DeviceId,
+    ) -> RouterResult {
+        debug!(path = %req.path, overwrite = req.overwrite, "Issuing file copy grant");
+
+        let copy = self.file_copy.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("direct file copy is not enabled".to_string())
+        })?;
+        let path = Path::new(&req.path);
+
+        // The grant stands in for an upload, so it needs write permission
+        self.check_file_permission(device_id, path, FileOperation::Write)?;
+
+        if let Some(challenge) =
+            self.security_key_challenge(device_id, GatedAction::FileWrite, Some(path))
+        {
+            return Ok(Some(challenge));
+        }
+
+        let grant = copy.grant(*device_id, &req.path, req.overwrite);
+        Ok(Some(Message::FileCopyGrant(grant)))
+    }
+
+    async fn handle_file_copy_request(
+        &self,
+        req: FileCopyRequest,
+        device_id: &DeviceId,
+    ) -> RouterResult {
+        debug!(
+            source = %req.source_path,
+            destination = %req.grant.path,
+            "Starting file copy"
+        );
+
+        let copy = self.file_copy.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("direct file copy is not enabled".to_string())
+        })?;
+
+        self.check_file_permission(device_id, Path::new(&req.source_path), FileOperation::Read)?;
+
+        // The outcome is sent to the device when the copy finishes
+        copy.start(*device_id, req.source_path, req.grant);
+        Ok(None)
+    }
+
     async fn handle_file_upload_chunk(
         &self,
         req: FileUploadChunk,
@@ -501,6 +1788,7 @@ impl<S: SessionManager> MessageRouter<S> {
             path = %req.path,
             offset = req.offset,
             size = req.data.len(),
+            hashed = req.hash.is_some(),
             "Writing upload chunk"
         );
 
@@ -509,9 +1797,12 @@ impl<S: SessionManager> MessageRouter<S> {
         // Check permission before writing chunk
         self.check_file_permission(device_id, path, FileOperation::Write)?;
 
-        self.file_transfer
-            .write_chunk(path, req.offset, &req.data)
-            .map_err(|e| RouterError::File(e.to_string()))?;
+        self.file_transfer.write_verified_chunk(
+            path,
+            req.offset,
+            &req.data,
+            req.hash.as_deref(),
+        )?;
 
         // No response needed - client should continue sending chunks
         Ok(None)
@@ -529,20 +1820,55 @@ impl<S: SessionManager> MessageRouter<S> {
         // Check permission before completing upload
         self.check_file_permission(device_id, path, FileOperation::Write)?;
 
-        self.file_transfer
-            .complete_upload(path, &req.checksum)
-            .map_err(|e| RouterError::File(e.to_string()))?;
+        let summary = self.file_transfer.complete_upload(path, &req.checksum)?;
+
+        info!(
+            path = %req.path,
+            size = summary.size,
+            chunks = summary.chunks,
+            verified_chunks = summary.verified_chunks,
+            duration_ms = summary.duration.as_millis() as u64,
+            "File upload completed successfully"
+        );
+        self.emit_webhook(
+            WebhookEvent::FileUploaded,
+            serde_json::json!({ "device_id": device_id.to_string(), "path": req.path }),
+        );
 
-        info!(path = %req.path, "File upload completed successfully");
+        if !req.report {
+            // No response needed - success is implied by lack of error
+            return Ok(None);
+        }
 
-        // No response needed - success is implied by lack of error
-        Ok(None)
+        Ok(Some(Message::FileTransferReport(FileTransferReport {
+            path: req.path,
+            size: summary.size,
+            hash: summary.hash.to_vec(),
+            chunks: summary.chunks,
+            verified_chunks: summary.verified_chunks,
+            duration_ms: summary.duration.as_millis() as u64,
+        })))
     }
 
     // =========================================================================
     // Device Handlers
     // =========================================================================
 
+    /// Normalizes a device name sent by a client before it is logged, shown
+    /// or stored.
+    fn normalize_device_name(&self, device_id: &str, raw: &str) -> DeviceName {
+        let name = DeviceName::normalize(raw);
+        if name.mixed_script {
+            warn!(
+                target: "audit",
+                device_id = %device_id,
+                name = %name.name,
+                "Device name mixes scripts with look-alike letters"
+            );
+        }
+        name
+    }
+
     async fn handle_device_info(&self, info: DeviceInfo) -> RouterResult {
         info!(
             device_id = %info.device_id,
@@ -564,9 +1890,12 @@ impl<S: SessionManager> MessageRouter<S> {
         Ok(None)
     }
 
+    /// Handles an approval request whose name was normalized, `raw_name`
+    /// being the name as sent if that changed it.
     async fn handle_device_approval_request(
         &self,
         req: DeviceApprovalRequest,
+        raw_name: Option<String>,
         authenticated_public_key: Option<&[u8; 32]>,
     ) -> RouterResult {
         info!(
@@ -600,29 +1929,31 @@ impl<S: SessionManager> MessageRouter<S> {
             }
         }
 
+        if let Some(code) = req.pairing_code.as_deref() {
+            if let Some(rejected) = self.consume_pairing_code(&device_id, &req, code).await? {
+                return Ok(Some(rejected));
+            }
+        }
+
         // First, check if the device is already in the trusted devices store
         match self.trust_store.get_device(&device_id) {
             Ok(Some(device)) => {
                 match device.trust_level {
                     TrustLevel::Trusted => {
                         info!(device_id = %req.device_id, "Device already trusted");
-                        Ok(Some(Message::DeviceApproved(DeviceApproved {
-                            device_id: req.device_id,
-                            expires_at: None, // No expiration for already trusted devices
-                            allowed_capabilities: vec![
-                                "shell".to_string(),
-                                "file-transfer".to_string(),
-                            ],
-                        })))
-                    }
-                    TrustLevel::Revoked => {
-                        warn!(device_id = %req.device_id, "Device is revoked");
-                        Ok(Some(Message::DeviceRejected(DeviceRejected {
-                            device_id: req.device_id,
-                            reason: "Device has been revoked".to_string(),
-                            retry_allowed: false,
-                        })))
+                        Ok(Some(device_approved(&device, req.device_id)))
                     }
+                    TrustLevel::Revoked => match req.reinvite_token.as_deref() {
+                        Some(token) => self.handle_reinvite(&device, &req, token).await,
+                        None => {
+                            warn!(device_id = %req.device_id, "Device is revoked");
+                            Ok(Some(Message::DeviceRejected(DeviceRejected {
+                                device_id: req.device_id,
+                                reason: "Device has been revoked".to_string(),
+                                retry_allowed: false,
+                            })))
+                        }
+                    },
                     TrustLevel::Unknown => {
                         // Device exists but not yet approved - this would typically
                         // trigger a user prompt in a real implementation
@@ -646,6 +1977,14 @@ impl<S: SessionManager> MessageRouter<S> {
                     })));
                 }
 
+                // While pairing codes are outstanding, a new device must
+                // present one, so the single-use check cannot be skipped
+                if req.pairing_code.is_none() {
+                    if let Some(rejected) = self.require_pairing_code(&req).await? {
+                        return Ok(Some(rejected));
+                    }
+                }
+
                 // New device - handle based on require_approval setting
                 let public_key: [u8; 32] = req.public_key.try_into().map_err(|_| {
                     RouterError::InvalidRequest("Invalid public key length".to_string())
@@ -658,7 +1997,8 @@ impl<S: SessionManager> MessageRouter<S> {
                         req.name.clone(),
                         public_key,
                         None, // remote_addr not available here, could be passed from connection handler
-                    );
+                    )
+                    .with_raw_name(raw_name);
 
                     self.trust_store
                         .add_pending(pending)
@@ -677,9 +2017,9 @@ impl<S: SessionManager> MessageRouter<S> {
                     })))
                 } else {
                     // require_approval is false - add as unknown (legacy behavior)
-                    use crate::devices::TrustedDevice;
                     let new_device =
-                        TrustedDevice::new_unknown(device_id, req.name.clone(), public_key);
+                        TrustedDevice::new_unknown(device_id, req.name.clone(), public_key)
+                            .with_raw_name(raw_name);
 
                     self.trust_store
                         .add_device(new_device)
@@ -707,98 +2047,592 @@ impl<S: SessionManager> MessageRouter<S> {
         }
     }
 
-    // =========================================================================
-    // Control Handlers
-    // =========================================================================
+    /// Re-approves a revoked device that presents a valid re-invite token.
+    ///
+    /// Every attempt is recorded under the `audit` tracing target.
+    async fn handle_reinvite(
+        &self,
+        device: &TrustedDevice,
+        req: &DeviceApprovalRequest,
+        token: &str,
+    ) -> RouterResult {
+        let reject = |reason: &str| {
+            Ok(Some(Message::DeviceRejected(DeviceRejected {
+                device_id: req.device_id.clone(),
+                reason: reason.to_string(),
+                retry_allowed: false,
+            })))
+        };
 
-    async fn handle_ping(&self, ping: Ping) -> RouterResult {
-        debug!(timestamp = ping.timestamp, "Received ping");
+        if req.public_key.as_slice() != device.public_key.as_slice() {
+            warn!(
+                target: "audit",
+                device_id = %req.device_id,
+                "Re-invite rejected: public key does not match revoked device"
+            );
+            return reject("Device has been revoked");
+        }
 
-        Ok(Some(Message::Pong(Pong {
-            timestamp: ping.timestamp,
-            payload: ping.payload,
-        })))
-    }
-}
+        let store = ReinviteStore::for_trust_store(self.trust_store.path());
+        let (device_id, token) = (device.device_id, token.to_string());
+        let outcome = run_blocking_store(move || store.redeem(&device_id, &token)).await?;
+
+        match outcome {
+            RedeemOutcome::Accepted => {
+                self.trust_store
+                    .set_trust_level(&device.device_id, TrustLevel::Trusted)
+                    .map_err(|e| RouterError::Device(e.to_string()))?;
+                if let Err(e) = self.trust_store.save() {
+                    error!(error = %e, "Failed to save trust store");
+                }
 
-/// Helper function to get current timestamp in milliseconds.
-#[allow(dead_code)]
-fn current_timestamp_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_millis() as u64)
-        .unwrap_or(0)
-}
+                info!(
+                    target: "audit",
+                    device_id = %req.device_id,
+                    name = %req.name,
+                    "Revoked device re-approved with re-invite token"
+                );
+                self.emit_webhook(
+                    WebhookEvent::DeviceApproved,
+                    serde_json::json!({
+                        "device_id": req.device_id,
+                        "name": req.name,
+                        "method": "reinvite",
+                    }),
+                );
 
-/// Parse a device ID from its fingerprint format (e.g., "a1b2:c3d4:e5f6:7890:1234:5678:9abc:def0").
-///
-/// Returns None if the string is not in the expected format.
-fn parse_device_id_from_fingerprint(fingerprint: &str) -> Option<DeviceId> {
-    // Remove colons and decode hex
-    let hex_str: String = fingerprint.chars().filter(|c| *c != ':').collect();
+                // The device comes back with the rights it had when it was
+                // revoked
+                Ok(Some(device_approved(device, req.device_id.clone())))
+            }
+            RedeemOutcome::Expired => {
+                warn!(target: "audit", device_id = %req.device_id, "Re-invite rejected: token expired");
+                reject("Re-invite token has expired")
+            }
+            RedeemOutcome::InvalidToken | RedeemOutcome::NotIssued => {
+                warn!(
+                    target: "audit",
+                    device_id = %req.device_id,
+                    outcome = ?outcome,
+                    "Re-invite rejected: invalid token"
+                );
+                reject("Invalid re-invite token")
+            }
+        }
+    }
 
-    if hex_str.len() != 32 {
-        // DeviceId is 16 bytes = 32 hex chars
-        return None;
+    /// Binds a pairing code to the device presenting it.
+    ///
+    /// Returns a rejection if the code is unknown, expired, or bound to
+    /// another device. Every attempt is logged under the `audit` target.
+    async fn consume_pairing_code(
+        &self,
+        device_id: &DeviceId,
+        req: &DeviceApprovalRequest,
+        code: &str,
+    ) -> Result<Option<Message>, RouterError> {
+        let store = PairingCodeStore::for_trust_store(self.trust_store.path());
+        let (code, device_id) = (code.to_string(), *device_id);
+        let outcome = run_blocking_store(move || store.consume(&code, &device_id)).await?;
+
+        let reason = match outcome {
+            ConsumeOutcome::Accepted => {
+                info!(
+                    target: "audit",
+                    device_id = %req.device_id,
+                    name = %req.name,
+                    "Pairing code consumed"
+                );
+                return Ok(None);
+            }
+            ConsumeOutcome::AlreadyBound => return Ok(None),
+            ConsumeOutcome::Reused => "Pairing code has already been used",
+            ConsumeOutcome::Expired => "Pairing code has expired",
+            ConsumeOutcome::Unknown => "Invalid pairing code",
+        };
+        warn!(
+            target: "audit",
+            device_id = %req.device_id,
+            name = %req.name,
+            outcome = ?outcome,
+            "Pairing code rejected"
+        );
+        Ok(Some(Message::DeviceRejected(DeviceRejected {
+            device_id: req.device_id.clone(),
+            reason: reason.to_string(),
+            retry_allowed: false,
+        })))
     }
 
-    let bytes = hex::decode(&hex_str).ok()?;
-    if bytes.len() != 16 {
-        return None;
+    /// Rejects a new device that sent no pairing code while codes issued
+    /// by `remoshell pair` are still waiting to be used.
+    async fn require_pairing_code(
+        &self,
+        req: &DeviceApprovalRequest,
+    ) -> Result<Option<Message>, RouterError> {
+        let store = PairingCodeStore::for_trust_store(self.trust_store.path());
+        let outstanding = run_blocking_store(move || store.has_outstanding()).await?;
+        if !outstanding {
+            return Ok(None);
+        }
+
+        warn!(
+            target: "audit",
+            device_id = %req.device_id,
+            name = %req.name,
+            "Pairing code missing while codes are outstanding"
+        );
+        Ok(Some(Message::DeviceRejected(DeviceRejected {
+            device_id: req.device_id.clone(),
+            reason: "Pairing code required".to_string(),
+            retry_allowed: true,
+        })))
     }
 
-    let mut arr = [0u8; 16];
-    arr.copy_from_slice(&bytes);
-    Some(DeviceId::from_bytes(arr))
-}
+    /// Checks that the device may forward its agent and binds a socket for
+    /// the new session.
+    fn prepare_agent_socket(
+        &self,
+        device_id: &DeviceId,
+    ) -> Result<(Arc<AgentForwarder>, PreparedAgentSocket), RouterError> {
+        let forwarder = self.agent_forwarder.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("SSH agent forwarding is disabled".to_string())
+        })?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
-    use tokio::sync::broadcast;
+        self.require_capability(device_id, AGENT_FORWARDING_CAPABILITY)?;
 
-    /// Mock session manager for testing.
-    struct MockSessionManager {
-        should_fail: bool,
+        let socket = forwarder
+            .prepare()
+            .map_err(|e| RouterError::Internal(e.to_string()))?;
+        Ok((Arc::clone(forwarder), socket))
     }
 
-    impl MockSessionManager {
-        fn new() -> Self {
-            Self { should_fail: false }
-        }
+    /// Relays data from the client's SSH agent to a session.
+    async fn handle_agent_data(&self, data: AgentData, device_id: &DeviceId) -> RouterResult {
+        let forwarder = self.agent_forwarder.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("SSH agent forwarding is disabled".to_string())
+        })?;
 
-        fn failing() -> Self {
-            Self { should_fail: true }
+        forwarder.write(device_id, data).map_err(|e| match e {
+            AgentError::Io(_) => RouterError::Internal(e.to_string()),
+            _ => RouterError::NotConnected(e.to_string()),
+        })?;
+        Ok(None)
+    }
+
+    /// Closes an agent channel at the client's request.
+    async fn handle_agent_close(&self, close: AgentClose, device_id: &DeviceId) -> RouterResult {
+        if let Some(forwarder) = &self.agent_forwarder {
+            // The channel may already be gone if the process hung up first
+            let _ = forwarder.close_channel(device_id, close);
         }
+        Ok(None)
     }
 
-    impl SessionManager for MockSessionManager {
-        async fn create(
-            &self,
-            _shell: Option<String>,
-            _cols: u16,
-            _rows: u16,
-            _env: Vec<(String, String)>,
-            _cwd: Option<String>,
-        ) -> Result<(SessionId, u32), SessionError> {
+    /// Checks that the device may forward its display and binds a socket for
+    /// the new session.
+    fn prepare_display_socket(
+        &self,
+        device_id: &DeviceId,
+        protocol: DisplayProtocol,
+    ) -> Result<(Arc<DisplayForwarder>, PreparedDisplaySocket), RouterError> {
+        let forwarder = self.display_forwarder.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("Display forwarding is disabled".to_string())
+        })?;
+
+        self.require_capability(device_id, DISPLAY_FORWARDING_CAPABILITY)?;
+
+        let socket = forwarder
+            .prepare(protocol)
+            .map_err(|e| RouterError::Internal(e.to_string()))?;
+        Ok((Arc::clone(forwarder), socket))
+    }
+
+    /// Relays data from the client's display to a session.
+    fn handle_display_data(&self, data: DisplayData, device_id: &DeviceId) -> RouterResult {
+        let forwarder = self.display_forwarder.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("Display forwarding is disabled".to_string())
+        })?;
+
+        forwarder.write(device_id, data).map_err(|e| match e {
+            DisplayError::Io(_) => RouterError::Internal(e.to_string()),
+            _ => RouterError::NotConnected(e.to_string()),
+        })?;
+        Ok(None)
+    }
+
+    /// Closes a display channel at the client's request.
+    fn handle_display_close(&self, close: DisplayClose, device_id: &DeviceId) -> RouterResult {
+        if let Some(forwarder) = &self.display_forwarder {
+            // The channel may already be gone if the application exited first
+            let _ = forwarder.close_channel(device_id, close);
+        }
+        Ok(None)
+    }
+
+    /// Verifies a security key assertion answering an earlier challenge.
+    async fn handle_security_key_assertion(
+        &self,
+        assertion: SecurityKeyAssertion,
+        device_id: &DeviceId,
+    ) -> RouterResult {
+        let gate = self.security_keys.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("security keys are not enabled".to_string())
+        })?;
+
+        match gate.verify(device_id, &assertion, current_timestamp_secs()) {
+            Ok(verified) => Ok(Some(Message::SecurityKeyVerified(verified))),
+            Err(e) => {
+                warn!(
+                    target: "audit",
+                    device_id = %device_id,
+                    error = %e,
+                    "Security key assertion rejected"
+                );
+                Err(RouterError::Auth(e.to_string()))
+            }
+        }
+    }
+
+    // =========================================================================
+    // Host Handlers
+    // =========================================================================
+
+    fn handle_system_info(&self, _req: SystemInfoRequest) -> RouterResult {
+        let info = sysmon::system_info(self.directory_browser.allowed_paths());
+        Ok(Some(Message::SystemInfo(info)))
+    }
+
+    async fn handle_process_list(&self, req: ProcessListRequest) -> RouterResult {
+        let processes = sysmon::process_list(req.sort, req.limit).await;
+        Ok(Some(Message::ProcessList(ProcessList { processes })))
+    }
+
+    fn handle_process_kill(&self, req: ProcessKill, device_id: &DeviceId) -> RouterResult {
+        self.require_capability(device_id, PROCESS_KILL_CAPABILITY)?;
+
+        info!(
+            target: "audit",
+            device_id = %device_id,
+            pid = req.pid,
+            signal = req.signal.name(),
+            "Signaling host process"
+        );
+        sysmon::kill_process(req.pid, req.signal).map_err(|e| match e {
+            SysmonError::PermissionDenied(_) => RouterError::Permission(e.to_string()),
+            SysmonError::Signal(..) => RouterError::Internal(e.to_string()),
+            SysmonError::InvalidPid(_) => RouterError::InvalidRequest(e.to_string()),
+            SysmonError::NoSuchProcess(_) => RouterError::NotFound(e.to_string()),
+        })?;
+
+        Ok(None)
+    }
+
+    /// Returns the power manager, if power actions are enabled, after checking
+    /// that the device may use it.
+    fn power_manager(&self, device_id: &DeviceId) -> Result<Arc<PowerManager>, RouterError> {
+        let manager = self
+            .power_manager
+            .as_ref()
+            .ok_or_else(|| RouterError::InvalidRequest("power actions are disabled".to_string()))?;
+        self.require_capability(device_id, POWER_CAPABILITY)?;
+        Ok(Arc::clone(manager))
+    }
+
+    fn handle_power_request(&self, req: PowerActionRequest, device_id: &DeviceId) -> RouterResult {
+        let manager = self.power_manager(device_id)?;
+
+        let challenge = manager
+            .request(device_id, req.action, current_timestamp_secs())
+            .map_err(|e| RouterError::InvalidRequest(e.to_string()))?;
+        info!(
+            target: "audit",
+            device_id = %device_id,
+            action = power::action_name(req.action),
+            "Power action requested"
+        );
+        Ok(Some(Message::PowerActionChallenge(challenge)))
+    }
+
+    fn handle_power_confirm(&self, req: PowerActionConfirm, device_id: &DeviceId) -> RouterResult {
+        let manager = self.power_manager(device_id)?;
+
+        let action = manager
+            .confirm(device_id, &req.token, current_timestamp_secs())
+            .map_err(|e| match e {
+                PowerError::WrongDevice => RouterError::Permission(e.to_string()),
+                _ => RouterError::InvalidRequest(e.to_string()),
+            })?;
+        info!(
+            target: "audit",
+            device_id = %device_id,
+            action = power::action_name(action),
+            "Power action confirmed"
+        );
+
+        // Warn every connected device, not just the one that asked
+        if let Some(notifier) = &self.notifier {
+            notifier.broadcast(power::action_notification(action));
+        }
+
+        // Give the reply time to reach the client before the host goes down
+        tokio::spawn(async move {
+            tokio::time::sleep(POWER_ACTION_DELAY).await;
+            if let Err(e) = manager.run(action).await {
+                error!(
+                    target: "audit",
+                    action = power::action_name(action),
+                    "Power action failed: {}",
+                    e
+                );
+            }
+        });
+
+        Ok(Some(Message::PowerActionStarted(PowerActionStarted {
+            action,
+        })))
+    }
+
+    /// Returns the remote configuration. The role check has already let the
+    /// device read or patch it.
+    fn remote_config(&self) -> Result<Arc<RemoteConfig>, RouterError> {
+        let remote_config = self.remote_config.as_ref().ok_or_else(|| {
+            RouterError::InvalidRequest("remote configuration is unavailable".to_string())
+        })?;
+        Ok(Arc::clone(remote_config))
+    }
+
+    fn handle_config_request(&self) -> RouterResult {
+        let remote_config = self.remote_config()?;
+
+        let view = remote_config
+            .view()
+            .map_err(|e| RouterError::Internal(e.to_string()))?;
+        Ok(Some(Message::ConfigView(view)))
+    }
+
+    fn handle_config_patch(&self, req: ConfigPatch, device_id: &DeviceId) -> RouterResult {
+        let remote_config = self.remote_config()?;
+
+        let changes = remote_config::describe(&req);
+        match remote_config.patch(&req) {
+            Ok(view) => {
+                info!(
+                    target: "audit",
+                    device_id = %device_id,
+                    changes = %changes,
+                    "Configuration changed"
+                );
+                Ok(Some(Message::ConfigView(view)))
+            }
+            Err(e) => {
+                warn!(
+                    target: "audit",
+                    device_id = %device_id,
+                    changes = %changes,
+                    error = %e,
+                    "Configuration change rejected"
+                );
+                if e.is_invalid_request() {
+                    Err(RouterError::InvalidRequest(e.to_string()))
+                } else {
+                    Err(RouterError::Internal(e.to_string()))
+                }
+            }
+        }
+    }
+
+    // =========================================================================
+    // Control Handlers
+    // =========================================================================
+
+    async fn handle_ping(&self, ping: Ping) -> RouterResult {
+        debug!(timestamp = ping.timestamp, "Received ping");
+
+        Ok(Some(Message::Pong(Pong {
+            timestamp: ping.timestamp,
+            payload: ping.payload,
+        })))
+    }
+}
+
+/// Helper function to get the current Unix timestamp in seconds.
+fn current_timestamp_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Helper function to get current timestamp in milliseconds.
+#[allow(dead_code)]
+fn current_timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Builds the approval sent to a trusted device.
+///
+/// Temporary grants tell the device when they end and what they cover; a
+/// permanently trusted device gets every base right.
+/// Runs an operation on the pairing code or re-invite store on the blocking
+/// pool, as it may wait for the store's file lock and rewrites the file.
+async fn run_blocking_store<R: Send + 'static>(
+    operation: impl FnOnce() -> anyhow::Result<R> + Send + 'static,
+) -> Result<R, RouterError> {
+    tokio::task::spawn_blocking(operation)
+        .await
+        .map_err(|e| RouterError::Internal(format!("store operation failed: {}", e)))?
+        .map_err(|e| RouterError::Device(e.to_string()))
+}
+
+fn device_approved(device: &TrustedDevice, device_id: String) -> Message {
+    let expires_at = device.grant.as_ref().map(|grant| {
+        grant
+            .expires_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    });
+    let allowed_capabilities = [(SHELL_ACCESS, "shell"), (FILES_ACCESS, "file-transfer")]
+        .into_iter()
+        .filter(|(access, _)| device.has_access(access))
+        .map(|(_, capability)| capability.to_string())
+        .collect();
+    Message::DeviceApproved(DeviceApproved {
+        device_id,
+        expires_at,
+        allowed_capabilities,
+    })
+}
+
+/// Parse a device ID from its fingerprint format (e.g., "a1b2:c3d4:e5f6:7890:1234:5678:9abc:def0").
+///
+/// Returns None if the string is not in the expected format.
+fn parse_device_id_from_fingerprint(fingerprint: &str) -> Option<DeviceId> {
+    // Remove colons and decode hex
+    let hex_str: String = fingerprint.chars().filter(|c| *c != ':').collect();
+
+    if hex_str.len() != 32 {
+        // DeviceId is 16 bytes = 32 hex chars
+        return None;
+    }
+
+    let bytes = hex::decode(&hex_str).ok()?;
+    if bytes.len() != 16 {
+        return None;
+    }
+
+    let mut arr = [0u8; 16];
+    arr.copy_from_slice(&bytes);
+    Some(DeviceId::from_bytes(arr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::TailLimits;
+    use crate::session::{OutputReceiver, Scrollback, SearchResults, SessionStatus, ShellMetadata};
+    use bytes::Bytes;
+    use protocol::messages::{FileDownloadChunk, FileListDetail, ProcessSignal};
+    use std::io::Write;
+    use tempfile::TempDir;
+    use tokio::sync::broadcast;
+
+    /// Arguments of a `create` call on the mock session manager.
+    #[derive(Debug, Clone, PartialEq)]
+    struct CreateCall {
+        shell: Option<String>,
+        cols: u16,
+        rows: u16,
+        env: Vec<(String, String)>,
+        cwd: Option<String>,
+        login_shell: bool,
+    }
+
+    /// Mock session manager for testing.
+    struct MockSessionManager {
+        should_fail: bool,
+        created: std::sync::Mutex<Vec<CreateCall>>,
+        written: std::sync::Mutex<Vec<u8>>,
+    }
+
+    impl MockSessionManager {
+        fn new() -> Self {
+            Self {
+                should_fail: false,
+                created: Default::default(),
+                written: Default::default(),
+            }
+        }
+
+        fn failing() -> Self {
+            Self {
+                should_fail: true,
+                ..Self::new()
+            }
+        }
+
+        fn record(&self, call: CreateCall) -> Result<(SessionId, u32), SessionError> {
+            self.created.lock().unwrap().push(call);
             if self.should_fail {
                 Err(SessionError::SpawnFailed("Mock failure".to_string()))
             } else {
                 Ok(("test-session-123".to_string(), 12345))
             }
         }
+    }
+
+    impl SessionManager for MockSessionManager {
+        async fn create(
+            &self,
+            shell: Option<String>,
+            cols: u16,
+            rows: u16,
+            env: Vec<(String, String)>,
+            cwd: Option<String>,
+        ) -> Result<(SessionId, u32), SessionError> {
+            self.record(CreateCall {
+                shell,
+                cols,
+                rows,
+                env,
+                cwd,
+                login_shell: false,
+            })
+        }
+
+        async fn create_login_shell(
+            &self,
+            shell: Option<String>,
+            cols: u16,
+            rows: u16,
+            env: Vec<(String, String)>,
+            cwd: Option<String>,
+        ) -> Result<(SessionId, u32), SessionError> {
+            self.record(CreateCall {
+                shell,
+                cols,
+                rows,
+                env,
+                cwd,
+                login_shell: true,
+            })
+        }
 
         async fn attach(
             &self,
             session_id: &SessionId,
-        ) -> Result<broadcast::Receiver<Vec<u8>>, SessionError> {
+            _policy: LagPolicy,
+        ) -> Result<OutputReceiver, SessionError> {
             if self.should_fail {
                 Err(SessionError::NotFound(session_id.clone()))
             } else {
                 let (tx, rx) = broadcast::channel(16);
-                drop(tx);
-                Ok(rx)
+                tx.send(Bytes::from_static(b"mock output")).unwrap();
+                Ok(rx.into())
             }
         }
 
@@ -810,7 +2644,8 @@ mod tests {
             }
         }
 
-        async fn write(&self, session_id: &SessionId, _data: &[u8]) -> Result<(), SessionError> {
+        async fn write(&self, session_id: &SessionId, data: &[u8]) -> Result<(), SessionError> {
+            self.written.lock().unwrap().extend_from_slice(data);
             if self.should_fail {
                 Err(SessionError::NotFound(session_id.clone()))
             } else {
@@ -843,15 +2678,57 @@ mod tests {
             }
         }
 
+        async fn signal(
+            &self,
+            session_id: &SessionId,
+            _signal: ProcessSignal,
+        ) -> Result<(), SessionError> {
+            if self.should_fail {
+                Err(SessionError::NotFound(session_id.clone()))
+            } else {
+                Ok(())
+            }
+        }
+
+        async fn search(
+            &self,
+            session_id: &SessionId,
+            query: &SearchQuery,
+        ) -> Result<SearchResults, SessionError> {
+            if self.should_fail {
+                return Err(SessionError::NotFound(session_id.clone()));
+            }
+            let mut scrollback = Scrollback::new(100);
+            scrollback.push(b"cargo build\nerror[E0308]: mismatched types\n$ ");
+            scrollback
+                .search(query)
+                .map_err(|e| SessionError::InvalidSearch(e.to_string()))
+        }
+
         fn list(&self) -> Vec<crate::session::manager::SessionInfo> {
             vec![]
         }
 
         async fn get(
             &self,
-            _session_id: &SessionId,
+            session_id: &SessionId,
         ) -> Option<crate::session::manager::SessionInfo> {
-            None
+            if self.should_fail {
+                return None;
+            }
+            Some(crate::session::manager::SessionInfo {
+                id: session_id.clone(),
+                pid: Some(12345),
+                cols: 80,
+                rows: 24,
+                running: true,
+                subscribers: 1,
+                shell: ShellMetadata {
+                    cwd: Some("/home/test".to_string()),
+                    last_exit_code: Some(2),
+                    ..Default::default()
+                },
+            })
         }
 
         fn exists(&self, _session_id: &SessionId) -> bool {
@@ -960,6 +2837,14 @@ mod tests {
             shell: Some("/bin/bash".to_string()),
             env: vec![],
             cwd: None,
+            forward_agent: false,
+            template: None,
+            timezone: None,
+            locale: None,
+            term: None,
+            forward_display: None,
+            host_session: None,
+            login_shell: false,
         });
 
         let result = router.route(msg, &device_id, None).await;
@@ -978,50 +2863,444 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_route_session_create_failure() {
+    async fn test_route_session_create_requires_security_key() {
+        use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+        use base64::Engine;
+
         let temp_dir = TempDir::new().unwrap();
-        // Create a router with a failing session manager but trusted device
-        let session_manager = Arc::new(MockSessionManager::failing());
-        let browser_for_transfer = DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]);
-        let file_transfer = Arc::new(
-            FileTransfer::new(browser_for_transfer, 100 * 1024 * 1024)
-                .with_temp_dir(temp_dir.path().join("tmp")),
-        );
-        let directory_browser =
-            Arc::new(DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]));
-        let trust_store = Arc::new(TrustStore::new(temp_dir.path().join("trust.json")));
-        let path_permissions = Arc::new(PathPermissions::new(
-            temp_dir.path().join("permissions.json"),
-            vec![temp_dir.path().to_path_buf()],
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let key = p256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let gate = SecurityKeyGate::from_config(&crate::config::Fido2Config {
+            enabled: true,
+            credentials: vec![crate::config::Fido2Credential {
+                name: "test key".to_string(),
+                id: URL_SAFE_NO_PAD.encode(b"credential-1"),
+                public_key: STANDARD.encode(key.verifying_key().to_encoded_point(false).as_bytes()),
+            }],
+            ..Default::default()
+        })
+        .unwrap();
+        let router = router.with_security_keys(Arc::new(gate));
+
+        let msg = Message::SessionCreate(SessionCreate::default());
+        let challenge = match router.route(msg, &device_id, None).await.unwrap() {
+            Some(Message::SecurityKeyChallenge(challenge)) => challenge,
+            other => panic!("Expected SecurityKeyChallenge, got {:?}", other),
+        };
+        assert_eq!(challenge.action, "session_create");
+
+        // A forged assertion is rejected and the session stays gated
+        let forged = Message::SecurityKeyAssertion(SecurityKeyAssertion {
+            challenge_id: challenge.challenge_id,
+            credential_id: b"credential-1".to_vec(),
+            authenticator_data: vec![0u8; 37],
+            client_data_json: b"{}".to_vec(),
+            signature: vec![0u8; 64],
+        });
+        assert!(matches!(
+            router.route(forged, &device_id, None).await,
+            Err(RouterError::Auth(_))
         ));
-        let device_id = create_trusted_device(&trust_store);
 
-        // Set up permissions for the device
-        let device_perms = crate::files::DevicePermissions::allow_all_dangerous(device_id);
-        path_permissions
-            .set_device_permissions(device_perms)
-            .unwrap();
+        let msg = Message::SessionCreate(SessionCreate::default());
+        assert!(matches!(
+            router.route(msg, &device_id, None).await.unwrap(),
+            Some(Message::SecurityKeyChallenge(_))
+        ));
+    }
 
-        let router = MessageRouter::new(
-            session_manager,
-            file_transfer,
-            directory_browser,
-            trust_store,
-            path_permissions,
-        );
+    #[tokio::test]
+    async fn test_route_session_create_waits_for_confirmation() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let confirmations = Arc::new(SessionConfirmations::new(Duration::from_secs(30)));
+        let (answered_tx, mut answered) = tokio::sync::mpsc::unbounded_channel();
+        let router = router.with_session_confirmations(Arc::clone(&confirmations), answered_tx);
+
+        for allow in [false, true] {
+            let msg = Message::SessionCreate(SessionCreate::default());
+            assert!(matches!(
+                router.route(msg, &device_id, None).await,
+                Ok(None)
+            ));
+            let pending = loop {
+                if let Some(pending) = confirmations.pending().pop() {
+                    break pending;
+                }
+                tokio::task::yield_now().await;
+            };
+            assert_eq!(pending.device_id, device_id);
+
+            // The device's other sessions keep working while it waits
+            router.session_manager.written.lock().unwrap().clear();
+            let msg = Message::SessionData(SessionData {
+                session_id: "existing".to_string(),
+                stream: DataStream::Stdin,
+                data: b"ls\n".to_vec(),
+            });
+            assert!(matches!(
+                router.route(msg, &device_id, None).await,
+                Ok(None)
+            ));
+            assert_eq!(*router.session_manager.written.lock().unwrap(), b"ls\n");
+
+            assert!(confirmations.answer(pending.id, allow));
+            let answer = answered.recv().await.unwrap();
+            assert_eq!(answer.device_id, device_id);
+            match router.finish_answered_session(answer).await {
+                Ok(Some(Message::SessionCreated(_))) if allow => {}
+                Err(RouterError::Permission(_)) if !allow => {}
+                other => panic!("Unexpected answer to allow={}: {:?}", allow, other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_confirmation_denied_on_disconnect() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let confirmations = Arc::new(SessionConfirmations::new(Duration::from_secs(30)));
+        let (answered_tx, mut answered) = tokio::sync::mpsc::unbounded_channel();
+        let router = router.with_session_confirmations(Arc::clone(&confirmations), answered_tx);
 
         let msg = Message::SessionCreate(SessionCreate::default());
+        router.route(msg, &device_id, None).await.unwrap();
+        while confirmations.pending().is_empty() {
+            tokio::task::yield_now().await;
+        }
 
-        let result = router.route(msg, &device_id, None).await;
-        assert!(result.is_err());
+        router.close_session_confirmations(&device_id);
+        let answer = answered.recv().await.unwrap();
+        assert_eq!(answer.outcome, ConfirmOutcome::Denied);
+        assert!(confirmations.pending().is_empty());
     }
 
     #[tokio::test]
-    async fn test_route_session_create_untrusted_device_rejected() {
+    async fn test_route_temporary_grant() {
         let temp_dir = TempDir::new().unwrap();
-        let router = create_test_router(&temp_dir);
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        router
+            .trust_store
+            .set_trust_level(&device_id, TrustLevel::Revoked)
+            .unwrap();
+        router
+            .trust_store
+            .grant_temporary(
+                &device_id,
+                Duration::from_secs(1800),
+                [SHELL_ACCESS.to_string()].into(),
+            )
+            .unwrap();
 
-        // Use an unregistered device ID
+        let msg = Message::SessionCreate(SessionCreate::default());
+        assert!(matches!(
+            router.route(msg, &device_id, None).await,
+            Ok(Some(Message::SessionCreated(_)))
+        ));
+        // The grant covers the shell only
+        let msg = Message::FileRootsRequest(FileRootsRequest::default());
+        assert!(matches!(
+            router.route(msg, &device_id, None).await,
+            Err(RouterError::Permission(_))
+        ));
+
+        // Expired grants are refused before the daemon removes them
+        let mut device = router.trust_store.get_device(&device_id).unwrap().unwrap();
+        device.grant.as_mut().unwrap().expires_at = SystemTime::now() - Duration::from_secs(1);
+        router.trust_store.add_device(device).unwrap();
+        let msg = Message::SessionCreate(SessionCreate::default());
+        assert!(matches!(
+            router.route(msg, &device_id, None).await,
+            Err(RouterError::Device(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_files_grant_refuses_shell_messages() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        router
+            .trust_store
+            .set_trust_level(&device_id, TrustLevel::Revoked)
+            .unwrap();
+        router
+            .trust_store
+            .grant_temporary(
+                &device_id,
+                Duration::from_secs(1800),
+                [FILES_ACCESS.to_string()].into(),
+            )
+            .unwrap();
+
+        let session_id = "session-1".to_string();
+        let refused = [
+            Message::SessionData(SessionData {
+                session_id: session_id.clone(),
+                stream: DataStream::Stdin,
+                data: b"id\n".to_vec(),
+            }),
+            Message::SessionSignal(SessionSignal {
+                session_id: session_id.clone(),
+                signal: ProcessSignal::Interrupt,
+            }),
+            Message::SessionKill(SessionKill {
+                session_id,
+                signal: None,
+            }),
+            Message::ProcessListRequest(ProcessListRequest::default()),
+            Message::ConfigRequest(protocol::messages::ConfigRequest::default()),
+        ];
+        for msg in refused {
+            let name = msg.name();
+            match router.route(msg, &device_id, None).await {
+                Err(RouterError::Permission(_)) => {}
+                other => panic!("Expected {} to be refused, got {:?}", name, other),
+            }
+        }
+
+        let msg = Message::FileRootsRequest(FileRootsRequest::default());
+        assert!(matches!(
+            router.route(msg, &device_id, None).await,
+            Ok(Some(Message::FileRoots(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_with_agent_forwarding() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let request = || {
+            Message::SessionCreate(SessionCreate {
+                forward_agent: true,
+                template: None,
+                ..Default::default()
+            })
+        };
+
+        // Disabled on the daemon
+        assert!(matches!(
+            router.route(request(), &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let forwarder = Arc::new(AgentForwarder::new(temp_dir.path().join("agent"), tx));
+        let router = router.with_agent_forwarder(Arc::clone(&forwarder));
+
+        // The device lacks the capability
+        assert!(matches!(
+            router.route(request(), &device_id, None).await,
+            Err(RouterError::Permission(_))
+        ));
+
+        let mut device = router.trust_store.get_device(&device_id).unwrap().unwrap();
+        device
+            .capabilities
+            .insert(AGENT_FORWARDING_CAPABILITY.to_string());
+        router.trust_store.add_device(device).unwrap();
+
+        match router.route(request(), &device_id, None).await.unwrap() {
+            Some(Message::SessionCreated(created)) => {
+                assert!(forwarder.is_forwarding(&created.session_id));
+            }
+            other => panic!("Expected SessionCreated, got {:?}", other),
+        }
+
+        router.close_agent_forwarding(&device_id);
+        assert!(!forwarder.is_forwarding("test-session-123"));
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_with_display_forwarding() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let request = || {
+            Message::SessionCreate(SessionCreate {
+                forward_display: Some(DisplayProtocol::X11),
+                host_session: None,
+                ..Default::default()
+            })
+        };
+
+        // Disabled on the daemon
+        assert!(matches!(
+            router.route(request(), &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let forwarder = Arc::new(
+            DisplayForwarder::new(temp_dir.path().join("display"), 10, tx)
+                .with_x11_dir(temp_dir.path().join(".X11-unix")),
+        );
+        let router = router.with_display_forwarder(Arc::clone(&forwarder));
+
+        // The device lacks the capability
+        assert!(matches!(
+            router.route(request(), &device_id, None).await,
+            Err(RouterError::Permission(_))
+        ));
+
+        let mut device = router.trust_store.get_device(&device_id).unwrap().unwrap();
+        device
+            .capabilities
+            .insert(DISPLAY_FORWARDING_CAPABILITY.to_string());
+        router.trust_store.add_device(device).unwrap();
+
+        router.route(request(), &device_id, None).await.unwrap();
+        assert!(forwarder.is_forwarding("test-session-123"));
+        let calls = router.session_manager.created.lock().unwrap().clone();
+        assert!(calls[0]
+            .env
+            .contains(&("DISPLAY".to_string(), ":10".to_string())));
+
+        router.close_display_forwarding(&device_id);
+        assert!(!forwarder.is_forwarding("test-session-123"));
+    }
+
+    #[tokio::test]
+    async fn test_route_update_session_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let update = |name: &str| {
+            Message::UpdateSessionEnv(UpdateSessionEnv {
+                session_id: "test-session-123".to_string(),
+                set: vec![(name.to_string(), "localhost:10.0".to_string())],
+                unset: vec![],
+            })
+        };
+
+        // Disabled on the daemon
+        assert!(matches!(
+            router.route(update("DISPLAY"), &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+
+        let exporter = Arc::new(SessionEnvExporter::new(temp_dir.path().join("env")));
+        let router = router.with_env_exporter(Arc::clone(&exporter));
+        let created = router
+            .route(
+                Message::SessionCreate(SessionCreate::default()),
+                &device_id,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(created, Some(Message::SessionCreated(_))));
+        assert!(exporter.is_exporting("test-session-123"));
+
+        let calls = router.session_manager.created.lock().unwrap().clone();
+        let path = calls[0]
+            .env
+            .iter()
+            .find(|(key, _)| key == ENV_FILE_VAR)
+            .map(|(_, value)| std::path::PathBuf::from(value))
+            .unwrap();
+
+        assert!(router
+            .route(update("DISPLAY"), &device_id, None)
+            .await
+            .unwrap()
+            .is_none());
+        let script = std::fs::read_to_string(&path).unwrap();
+        assert!(script.contains("export DISPLAY='localhost:10.0'"));
+
+        assert!(matches!(
+            router.route(update("NOT-A-NAME"), &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+
+        let kill = Message::SessionKill(SessionKill {
+            session_id: "test-session-123".to_string(),
+            signal: None,
+        });
+        router.route(kill, &device_id, None).await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_client_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let router = router
+            .with_env_exporter(Arc::new(SessionEnvExporter::new(
+                temp_dir.path().join("env"),
+            )))
+            .with_env_allowlist(EnvAllowlist::new(vec![
+                "TZ".to_string(),
+                "LANG".to_string(),
+            ]));
+
+        let create = Message::SessionCreate(SessionCreate {
+            env: vec![("LD_PRELOAD".to_string(), "/tmp/evil.so".to_string())],
+            timezone: Some("Europe/Paris".to_string()),
+            locale: Some("fr-FR".to_string()),
+            term: Some("xterm-256color".to_string()),
+            ..Default::default()
+        });
+        router.route(create, &device_id, None).await.unwrap();
+
+        let calls = router.session_manager.created.lock().unwrap().clone();
+        let names: Vec<&str> = calls[0].env.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(names, vec!["TZ", "LANG", ENV_FILE_VAR]);
+        assert_eq!(calls[0].env[1].1, "fr_FR.UTF-8");
+
+        let update = Message::UpdateSessionEnv(UpdateSessionEnv {
+            session_id: "test-session-123".to_string(),
+            set: vec![("TERM".to_string(), "dumb".to_string())],
+            unset: vec![],
+        });
+        assert!(matches!(
+            router.route(update, &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        // Create a router with a failing session manager but trusted device
+        let session_manager = Arc::new(MockSessionManager::failing());
+        let browser_for_transfer = DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]);
+        let file_transfer = Arc::new(
+            FileTransfer::new(browser_for_transfer, 100 * 1024 * 1024)
+                .with_temp_dir(temp_dir.path().join("tmp")),
+        );
+        let directory_browser =
+            Arc::new(DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]));
+        let trust_store = Arc::new(TrustStore::new(temp_dir.path().join("trust.json")));
+        let path_permissions = Arc::new(PathPermissions::new(
+            temp_dir.path().join("permissions.json"),
+            vec![temp_dir.path().to_path_buf()],
+        ));
+        let device_id = create_trusted_device(&trust_store);
+
+        // Set up permissions for the device
+        let device_perms = crate::files::DevicePermissions::allow_all_dangerous(device_id);
+        path_permissions
+            .set_device_permissions(device_perms)
+            .unwrap();
+
+        let router = MessageRouter::new(
+            session_manager,
+            file_transfer,
+            directory_browser,
+            trust_store,
+            path_permissions,
+        );
+
+        let msg = Message::SessionCreate(SessionCreate::default());
+
+        let result = router.route(msg, &device_id, None).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_untrusted_device_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+
+        // Use an unregistered device ID
         let untrusted_device = DeviceId::from_bytes([1u8; 16]);
 
         let msg = Message::SessionCreate(SessionCreate {
@@ -1030,6 +3309,14 @@ mod tests {
             shell: Some("/bin/bash".to_string()),
             env: vec![],
             cwd: None,
+            forward_agent: false,
+            template: None,
+            timezone: None,
+            locale: None,
+            term: None,
+            forward_display: None,
+            host_session: None,
+            login_shell: false,
         });
 
         let result = router.route(msg, &untrusted_device, None).await;
@@ -1093,6 +3380,14 @@ mod tests {
             shell: None,
             env: vec![],
             cwd: None,
+            forward_agent: false,
+            template: None,
+            timezone: None,
+            locale: None,
+            term: None,
+            forward_display: None,
+            host_session: None,
+            login_shell: false,
         });
 
         let result = router.route(msg, &device_id, None).await;
@@ -1138,6 +3433,30 @@ mod tests {
         assert!(result.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn test_route_session_attach_streams_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let router = router.with_output_streams(Arc::new(OutputStreams::new(tx)));
+
+        let msg = Message::SessionAttach(SessionAttach {
+            session_id: "test-session".to_string(),
+        });
+        assert!(router.route(msg, &device_id, None).await.unwrap().is_none());
+
+        let (to, message) = rx.recv().await.unwrap();
+        assert_eq!(to, device_id);
+        assert_eq!(
+            message,
+            Message::SessionData(SessionData {
+                session_id: "test-session".to_string(),
+                stream: DataStream::Stdout,
+                data: b"mock output".to_vec(),
+            })
+        );
+    }
+
     #[tokio::test]
     async fn test_route_session_kill() {
         let temp_dir = TempDir::new().unwrap();
@@ -1155,11 +3474,56 @@ mod tests {
             Some(Message::SessionClosed(closed)) => {
                 assert_eq!(closed.session_id, "test-session");
                 assert_eq!(closed.exit_code, Some(0));
+                assert_eq!(closed.close_reason, Some(SessionCloseReason::Killed));
             }
             _ => panic!("Expected SessionClosed response"),
         }
     }
 
+    #[tokio::test]
+    async fn test_route_session_owners() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let owners = SessionOwners::new();
+        let router = router.with_session_owners(owners.clone());
+
+        let msg = Message::SessionCreate(SessionCreate {
+            cols: 80,
+            rows: 24,
+            shell: None,
+            env: vec![],
+            cwd: None,
+            forward_agent: false,
+            template: None,
+            timezone: None,
+            locale: None,
+            term: None,
+            forward_display: None,
+            host_session: None,
+            login_shell: false,
+        });
+        router.route(msg, &device_id, None).await.unwrap();
+        let session_id = "test-session-123".to_string();
+        assert_eq!(owners.get(&session_id), Some(device_id));
+
+        // Another device killing the session leaves the owner to be told
+        let other = DeviceId::from_bytes([9u8; 16]);
+        router
+            .trust_store
+            .add_device(TrustedDevice::new(other, "Other".to_string(), [0u8; 32]))
+            .unwrap();
+        let msg = Message::SessionKill(SessionKill {
+            session_id: session_id.clone(),
+            signal: None,
+        });
+        router.route(msg.clone(), &other, None).await.unwrap();
+        assert_eq!(owners.get(&session_id), Some(device_id));
+
+        // The owner learns from the response instead
+        router.route(msg, &device_id, None).await.unwrap();
+        assert_eq!(owners.get(&session_id), None);
+    }
+
     #[tokio::test]
     async fn test_route_session_resize() {
         let temp_dir = TempDir::new().unwrap();
@@ -1174,6 +3538,542 @@ mod tests {
         let result = router.route(msg, &device_id, None).await;
         assert!(result.is_ok());
         assert!(result.unwrap().is_none());
+
+        let msg = Message::SessionResize(SessionResize {
+            session_id: "test-session".to_string(),
+            cols: 0,
+            rows: 40,
+        });
+        let result = router.route(msg, &device_id, None).await;
+        assert!(matches!(result, Err(RouterError::InvalidRequest(_))));
+    }
+
+    fn logs_template() -> BTreeMap<String, SessionTemplate> {
+        let template = SessionTemplate {
+            description: Some("Follow the application log".to_string()),
+            login_shell: true,
+            cwd: Some("/var/log".to_string()),
+            env: [("LESS", "-R"), ("PAGER", "less")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            command: Some("tail -f app.log".to_string()),
+            cols: Some(200),
+            ..Default::default()
+        };
+        BTreeMap::from([("logs".to_string(), template)])
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_from_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let router = router.with_session_templates(logs_template());
+
+        let msg = Message::SessionCreate(SessionCreate {
+            env: vec![("PAGER".to_string(), "more".to_string())],
+            template: Some("logs".to_string()),
+            ..Default::default()
+        });
+        let result = router.route(msg, &device_id, None).await;
+        assert!(matches!(result, Ok(Some(Message::SessionCreated(_)))));
+
+        let created = router.session_manager.created.lock().unwrap().clone();
+        assert_eq!(
+            created,
+            vec![CreateCall {
+                shell: None,
+                cols: 200,
+                rows: 24,
+                env: vec![
+                    ("LESS".to_string(), "-R".to_string()),
+                    ("PAGER".to_string(), "more".to_string()),
+                ],
+                cwd: Some("/var/log".to_string()),
+                login_shell: true,
+            }]
+        );
+        assert_eq!(
+            router.session_manager.written.lock().unwrap().as_slice(),
+            b"tail -f app.log\r"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_unknown_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+
+        let msg = Message::SessionCreate(SessionCreate {
+            template: Some("missing".to_string()),
+            ..Default::default()
+        });
+        let result = router.route(msg, &device_id, None).await;
+        assert!(matches!(result, Err(RouterError::InvalidRequest(_))));
+        assert!(router.session_manager.created.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_shell_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let not_executable = temp_dir.path().join("notes.txt");
+        std::fs::write(&not_executable, "").unwrap();
+        let not_executable = not_executable.to_string_lossy().into_owned();
+        let router = router
+            .with_shell_policy(ShellPolicy::new(
+                Some("/bin/sh".to_string()),
+                vec!["sh".to_string(), not_executable.clone()],
+            ))
+            .with_session_templates(BTreeMap::from([(
+                "python".to_string(),
+                SessionTemplate {
+                    shell: Some("/usr/bin/python3".to_string()),
+                    ..Default::default()
+                },
+            )]));
+        let create = |shell: Option<&str>, template: Option<&str>| {
+            Message::SessionCreate(SessionCreate {
+                shell: shell.map(str::to_string),
+                template: template.map(str::to_string),
+                ..Default::default()
+            })
+        };
+
+        // The default shell fills in for requests without one
+        router
+            .route(create(None, None), &device_id, None)
+            .await
+            .unwrap();
+        router
+            .route(create(Some("sh"), None), &device_id, None)
+            .await
+            .unwrap();
+        // Template shells skip the allowlist
+        router
+            .route(create(None, Some("python")), &device_id, None)
+            .await
+            .unwrap();
+        let shells: Vec<Option<String>> = router
+            .session_manager
+            .created
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|call| call.shell.clone())
+            .collect();
+        assert_eq!(
+            shells,
+            vec![
+                Some("/bin/sh".to_string()),
+                Some("sh".to_string()),
+                Some("/usr/bin/python3".to_string()),
+            ]
+        );
+
+        assert!(matches!(
+            router
+                .route(create(Some("/usr/bin/python3"), None), &device_id, None)
+                .await,
+            Err(RouterError::Permission(_))
+        ));
+        assert!(matches!(
+            router
+                .route(create(Some(&not_executable), None), &device_id, None)
+                .await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+        assert_eq!(router.session_manager.created.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_route_session_create_host_session() {
+        use crate::config::HostSessionConfig;
+        use protocol::messages::HostMultiplexer;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let request = || {
+            Message::SessionCreate(SessionCreate {
+                host_session: Some(HostSession {
+                    multiplexer: HostMultiplexer::Tmux,
+                    name: "work".to_string(),
+                    create: false,
+                }),
+                ..Default::default()
+            })
+        };
+
+        let result = router.route(request(), &device_id, None).await;
+        assert!(matches!(result, Err(RouterError::InvalidRequest(_))));
+
+        let tmux = temp_dir.path().join("tmux");
+        std::fs::write(&tmux, "#!/bin/sh\nexit 0\n").unwrap();
+        std::fs::set_permissions(&tmux, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let router =
+            router.with_host_sessions(Arc::new(HostSessions::from_config(&HostSessionConfig {
+                tmux: tmux.to_string_lossy().into_owned(),
+                ..Default::default()
+            })));
+
+        let result = router.route(request(), &device_id, None).await;
+        assert!(matches!(result, Ok(Some(Message::SessionCreated(_)))));
+        assert_eq!(
+            String::from_utf8(router.session_manager.written.lock().unwrap().clone()).unwrap(),
+            format!("exec '{}' attach-session -t '=work'\r", tmux.display())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route_list_templates() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let router = router.with_session_templates(logs_template());
+
+        let msg = Message::ListTemplates(protocol::messages::ListTemplates {});
+        match router.route(msg.clone(), &device_id, None).await {
+            Ok(Some(Message::TemplateList(list))) => {
+                assert_eq!(list.templates.len(), 1);
+                let logs = &list.templates[0];
+                assert_eq!(logs.name, "logs");
+                assert_eq!(logs.cwd.as_deref(), Some("/var/log"));
+                assert_eq!(logs.command.as_deref(), Some("tail -f app.log"));
+                assert_eq!(logs.cols, Some(200));
+                assert_eq!(logs.rows, None);
+            }
+            other => panic!("Expected TemplateList, got {:?}", other),
+        }
+
+        // Untrusted devices cannot list templates
+        let router = create_test_router(&temp_dir).with_session_templates(logs_template());
+        let result = router.route(msg, &test_device_id(), None).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route_system_info() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+
+        let msg = Message::SystemInfoRequest(SystemInfoRequest {});
+        match router.route(msg.clone(), &device_id, None).await {
+            Ok(Some(Message::SystemInfo(info))) => {
+                assert!(info.cpu_count >= 1);
+                assert!(info.memory_total > 0);
+                assert!(!info.disks.is_empty());
+            }
+            other => panic!("Expected SystemInfo, got {:?}", other),
+        }
+
+        // Untrusted devices cannot read host information
+        let router = create_test_router(&temp_dir);
+        let result = router.route(msg, &test_device_id(), None).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route_process_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+
+        let msg = Message::ProcessListRequest(ProcessListRequest {
+            sort: Some(protocol::messages::ProcessSort::Cpu),
+            limit: Some(5),
+        });
+        match router.route(msg, &device_id, None).await {
+            Ok(Some(Message::ProcessList(list))) => {
+                assert!(!list.processes.is_empty());
+                assert!(list.processes.len() <= 5);
+            }
+            other => panic!("Expected ProcessList, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_process_kill_requires_capability() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let kill = Message::ProcessKill(ProcessKill {
+            pid: child.id(),
+            signal: protocol::messages::ProcessSignal::Kill,
+        });
+
+        // The device lacks the capability
+        assert!(matches!(
+            router.route(kill.clone(), &device_id, None).await,
+            Err(RouterError::Permission(_))
+        ));
+
+        let mut device = router.trust_store.get_device(&device_id).unwrap().unwrap();
+        device
+            .capabilities
+            .insert(PROCESS_KILL_CAPABILITY.to_string());
+        router.trust_store.add_device(device).unwrap();
+
+        assert!(router
+            .route(kill, &device_id, None)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(!child.wait().unwrap().success());
+
+        let invalid = Message::ProcessKill(ProcessKill {
+            pid: 0,
+            signal: protocol::messages::ProcessSignal::Terminate,
+        });
+        assert!(matches!(
+            router.route(invalid, &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_power_action() {
+        use protocol::messages::{PowerAction, PowerActionConfirm, PowerActionRequest};
+
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let request = || {
+            Message::PowerActionRequest(PowerActionRequest {
+                action: PowerAction::Reboot,
+            })
+        };
+
+        // Disabled on the daemon
+        assert!(matches!(
+            router.route(request(), &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+
+        let manager = PowerManager::from_config(&crate::config::PowerConfig {
+            enabled: true,
+            reboot_command: vec!["true".to_string()],
+            shutdown_command: vec!["true".to_string()],
+            suspend_command: vec!["true".to_string()],
+        });
+        let (notifier, mut notifications) = Notifier::channel();
+        let router = router
+            .with_power_manager(Arc::new(manager))
+            .with_notifier(notifier);
+
+        // The device lacks the capability
+        assert!(matches!(
+            router.route(request(), &device_id, None).await,
+            Err(RouterError::Permission(_))
+        ));
+
+        let mut device = router.trust_store.get_device(&device_id).unwrap().unwrap();
+        device.capabilities.insert(POWER_CAPABILITY.to_string());
+        router.trust_store.add_device(device).unwrap();
+
+        let challenge = match router.route(request(), &device_id, None).await.unwrap() {
+            Some(Message::PowerActionChallenge(challenge)) => challenge,
+            other => panic!("Expected PowerActionChallenge, got {:?}", other),
+        };
+        assert_eq!(challenge.action, PowerAction::Reboot);
+
+        let confirm = Message::PowerActionConfirm(PowerActionConfirm {
+            token: challenge.token,
+        });
+        match router
+            .route(confirm.clone(), &device_id, None)
+            .await
+            .unwrap()
+        {
+            Some(Message::PowerActionStarted(started)) => {
+                assert_eq!(started.action, PowerAction::Reboot);
+            }
+            other => panic!("Expected PowerActionStarted, got {:?}", other),
+        }
+        let (recipient, notice) = notifications.try_recv().unwrap();
+        assert_eq!(recipient, crate::notify::Recipient::All);
+        assert_eq!(
+            notice.category,
+            protocol::messages::NotificationCategory::Shutdown
+        );
+
+        // Tokens can be used once
+        assert!(matches!(
+            router.route(confirm, &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_config_requires_role() {
+        use crate::authz::Role;
+        use crate::session::SessionManagerImpl;
+
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let patch = Message::ConfigPatch(ConfigPatch {
+            idle_timeout: Some(600),
+            ..Default::default()
+        });
+
+        // Not available without a configuration, even to owners
+        let set_role = |router: &MessageRouter<_>, role| {
+            let mut device = router.trust_store.get_device(&device_id).unwrap().unwrap();
+            device.role = role;
+            router.trust_store.add_device(device).unwrap();
+        };
+        set_role(&router, Some(Role::Owner));
+        assert!(matches!(
+            router
+                .route(Message::ConfigRequest(Default::default()), &device_id, None)
+                .await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+        set_role(&router, None);
+
+        let config_path = temp_dir.path().join("config.toml");
+        let mut config = crate::config::Config::default();
+        config.session.default_shell = "/bin/sh".to_string();
+        let remote_config = RemoteConfig::new(
+            config,
+            Arc::new(SessionManagerImpl::new()),
+            Arc::clone(&router.directory_browser),
+        );
+        remote_config.set_path(config_path.clone());
+        let router = router.with_remote_config(Arc::new(remote_config));
+        let set_role = |role| set_role(&router, Some(role));
+
+        // The device has no role
+        assert!(matches!(
+            router
+                .route(Message::ConfigRequest(Default::default()), &device_id, None)
+                .await,
+            Err(RouterError::Permission(_))
+        ));
+
+        // Viewers read the configuration but cannot change it
+        set_role(Role::Viewer);
+        match router
+            .route(Message::ConfigRequest(Default::default()), &device_id, None)
+            .await
+        {
+            Ok(Some(Message::ConfigView(view))) => assert!(view.toml.contains("idle_timeout = 0")),
+            other => panic!("Expected ConfigView, got {:?}", other),
+        }
+        assert!(matches!(
+            router.route(patch.clone(), &device_id, None).await,
+            Err(RouterError::Permission(_))
+        ));
+        assert!(!config_path.exists());
+
+        set_role(Role::Owner);
+        match router.route(patch, &device_id, None).await {
+            Ok(Some(Message::ConfigView(view))) => {
+                assert!(view.toml.contains("idle_timeout = 600"))
+            }
+            other => panic!("Expected ConfigView, got {:?}", other),
+        }
+        assert_eq!(
+            crate::config::Config::load(&config_path)
+                .unwrap()
+                .session
+                .idle_timeout,
+            600
+        );
+
+        let invalid = Message::ConfigPatch(ConfigPatch {
+            log_level: Some("chatty".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            router.route(invalid, &device_id, None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_session_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+
+        let msg = Message::SessionSearchRequest(SessionSearchRequest {
+            session_id: "test-session".to_string(),
+            pattern: "ERROR".to_string(),
+            regex: false,
+            case_sensitive: false,
+            max_results: 0,
+        });
+        match router.route(msg, &device_id, None).await {
+            Ok(Some(Message::SessionSearchResult(result))) => {
+                assert_eq!(result.session_id, "test-session");
+                assert_eq!(
+                    result.matches,
+                    vec![SearchMatch {
+                        line: 1,
+                        start: 0,
+                        end: 5,
+                        text: "error[E0308]: mismatched types".to_string(),
+                    }]
+                );
+                assert!(!result.truncated);
+                assert_eq!((result.first_line, result.last_line), (0, 2));
+            }
+            other => panic!("Expected SessionSearchResult, got {:?}", other),
+        }
+
+        // Invalid patterns are rejected as invalid requests
+        let msg = Message::SessionSearchRequest(SessionSearchRequest {
+            session_id: "test-session".to_string(),
+            pattern: "(".to_string(),
+            regex: true,
+            case_sensitive: false,
+            max_results: 0,
+        });
+        let err = router
+            .route(msg.clone(), &device_id, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_error_message(None).code, ErrorCode::InvalidRequest);
+
+        // Untrusted devices cannot search
+        let router = create_test_router(&temp_dir);
+        assert!(router.route(msg, &test_device_id(), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route_session_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+
+        let msg = Message::SessionMetadataRequest(SessionMetadataRequest {
+            session_id: "test-session".to_string(),
+        });
+
+        let result = router.route(msg, &device_id, None).await;
+        match result.unwrap() {
+            Some(Message::SessionMetadata(metadata)) => {
+                assert_eq!(metadata.session_id, "test-session");
+                assert_eq!(metadata.cwd.as_deref(), Some("/home/test"));
+                assert_eq!(metadata.last_exit_code, Some(2));
+            }
+            _ => panic!("Expected SessionMetadata response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_session_signal() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+
+        let msg = Message::SessionSignal(SessionSignal {
+            session_id: "test-session".to_string(),
+            signal: ProcessSignal::Interrupt,
+        });
+
+        let result = router.route(msg, &device_id, None).await;
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_none());
     }
 
     #[tokio::test]
@@ -1226,6 +4126,7 @@ mod tests {
         let msg = Message::FileListRequest(FileListRequest {
             path: temp_dir.path().to_string_lossy().to_string(),
             include_hidden: false,
+            detail: FileListDetail::Basic,
         });
 
         let result = router.route(msg, &test_device_id(), None).await;
@@ -1255,6 +4156,8 @@ mod tests {
                 .to_string(),
             offset: 0,
             chunk_size: 1024,
+            verify: false,
+            window: 0,
         });
 
         let result = router.route(msg, &test_device_id(), None).await;
@@ -1266,56 +4169,445 @@ mod tests {
                 assert_eq!(chunk.total_size, content.len() as u64);
                 assert!(chunk.is_last);
             }
-            _ => panic!("Expected FileDownloadChunk"),
+            _ => panic!("Expected FileDownloadChunk"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_file_upload_flow() {
+        let temp_dir = TempDir::new().unwrap();
+        let upload_dir = temp_dir.path().join("uploads");
+        std::fs::create_dir_all(&upload_dir).unwrap();
+
+        let router = create_test_router(&temp_dir);
+        let dest_path = upload_dir.join("uploaded.txt");
+
+        // Start upload
+        let msg = Message::FileUploadStart(FileUploadStart {
+            path: dest_path.to_string_lossy().to_string(),
+            size: 12,
+            mode: 0o644,
+            overwrite: false,
+        });
+        let result = router.route(msg, &test_device_id(), None).await;
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_none());
+
+        // Send chunk
+        let msg = Message::FileUploadChunk(FileUploadChunk {
+            path: dest_path.to_string_lossy().to_string(),
+            offset: 0,
+            data: b"Hello World!".to_vec(),
+            hash: None,
+        });
+        let result = router.route(msg, &test_device_id(), None).await;
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_none());
+
+        // Complete upload with correct checksum
+        use sha2::{Digest, Sha256};
+        let mut hasher = Sha256::new();
+        hasher.update(b"Hello World!");
+        let checksum = hasher.finalize().to_vec();
+
+        let msg = Message::FileUploadComplete(FileUploadComplete {
+            path: dest_path.to_string_lossy().to_string(),
+            checksum,
+            report: false,
+        });
+        let result = router.route(msg, &test_device_id(), None).await;
+        assert!(result.is_ok());
+
+        // Verify file was created
+        assert!(dest_path.exists());
+        assert_eq!(std::fs::read(&dest_path).unwrap(), b"Hello World!");
+    }
+
+    #[tokio::test]
+    async fn test_route_file_download_verified() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = b"Hello, verified World!";
+        let path = temp_dir.path().join("download.txt");
+        std::fs::write(&path, content).unwrap();
+
+        let router = create_test_router(&temp_dir);
+
+        let mut offset = 0;
+        let mut received = Vec::new();
+        loop {
+            let msg = Message::FileDownloadRequest(FileDownloadRequest {
+                path: path.to_string_lossy().to_string(),
+                offset,
+                chunk_size: 8,
+                verify: true,
+                window: 0,
+            });
+            let chunk = match router.route(msg, &test_device_id(), None).await {
+                Ok(Some(Message::FileDownloadChunk(chunk))) => chunk,
+                other => panic!("Expected FileDownloadChunk, got {:?}", other),
+            };
+
+            assert_eq!(
+                chunk.hash.as_deref(),
+                Some(blake3::hash(&chunk.data).as_bytes().as_slice())
+            );
+            received.extend_from_slice(&chunk.data);
+            offset += chunk.data.len() as u64;
+
+            if chunk.is_last {
+                assert_eq!(
+                    chunk.file_hash.as_deref(),
+                    Some(blake3::hash(content).as_bytes().as_slice())
+                );
+                break;
+            }
+            // The file hash is only sent with the last chunk
+            assert_eq!(chunk.file_hash, None);
+        }
+        assert_eq!(received, content);
+    }
+
+    #[tokio::test]
+    async fn test_route_file_download_streamed() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = b"Hello, streamed World!";
+        let path = temp_dir.path().join("download.txt");
+        std::fs::write(&path, content).unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let streams = Arc::new(DownloadStreams::new(tx));
+        let router = create_test_router(&temp_dir).with_download_streams(Arc::clone(&streams));
+        let device_id = test_device_id();
+
+        let msg = Message::FileDownloadRequest(FileDownloadRequest {
+            path: path.clone(),
+            offset: 0,
+            chunk_size: 4,
+            verify: false,
+            window: 2,
+        });
+        let first = match router.route(msg, &device_id, None).await {
+            Ok(Some(Message::FileDownloadChunk(chunk))) => chunk,
+            other => panic!("Expected FileDownloadChunk, got {:?}", other),
+        };
+        assert_eq!(first.data, b"Hell");
+        let mut received = first.data;
+
+        loop {
+            // One chunk past the first fills the window
+            let (to, msg) = rx.recv().await.unwrap();
+            assert_eq!(to, device_id);
+            let chunk = match msg {
+                Message::FileDownloadChunk(chunk) => chunk,
+                other => panic!("Expected FileDownloadChunk, got {:?}", other),
+            };
+            assert_eq!(chunk.offset, received.len() as u64);
+            received.extend_from_slice(&chunk.data);
+            if chunk.is_last {
+                break;
+            }
+            assert!(rx.try_recv().is_err());
+
+            let ack = Message::FileDownloadAck(FileDownloadAck {
+                path: path.clone(),
+                offset: chunk.offset,
+            });
+            assert!(router.route(ack, &device_id, None).await.unwrap().is_none());
+        }
+        assert_eq!(received, content);
+
+        // The stream ends with its last chunk
+        while !streams.is_empty() {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_file_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+
+        let msg = Message::FileRootsRequest(FileRootsRequest {});
+        match router.route(msg.clone(), &test_device_id(), None).await {
+            Ok(Some(Message::FileRoots(FileRoots { roots }))) => {
+                assert_eq!(roots.len(), 1);
+                let root = &roots[0];
+                assert_eq!(
+                    root.path,
+                    temp_dir
+                        .path()
+                        .canonicalize()
+                        .unwrap()
+                        .display()
+                        .to_string()
+                );
+                assert_eq!(root.access, FileAccess::Full);
+                assert!(root.total_bytes.unwrap() >= root.available_bytes.unwrap());
+            }
+            other => panic!("Expected FileRoots, got {:?}", other),
+        }
+
+        // Devices without permissions may not browse anything
+        let other = DeviceId::from_bytes([9u8; 16]);
+        match router.route(msg, &other, None).await {
+            Ok(Some(Message::FileRoots(FileRoots { roots }))) => assert!(roots.is_empty()),
+            other => panic!("Expected FileRoots, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_file_thumbnail() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("photo.png");
+        image::RgbImage::new(40, 20)
+            .save_with_format(&path, image::ImageFormat::Png)
+            .unwrap();
+        let msg = Message::FileThumbnailRequest(FileThumbnailRequest {
+            path: path.to_string_lossy().to_string(),
+            max_size: 0,
+        });
+
+        // Thumbnails must be enabled
+        let router = create_test_router(&temp_dir);
+        assert!(matches!(
+            router.route(msg.clone(), &test_device_id(), None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+
+        let router = router.with_thumbnailer(Arc::new(Thumbnailer::new(
+            1024 * 1024,
+            Duration::from_secs(10),
+            1,
+        )));
+        match router.route(msg.clone(), &test_device_id(), None).await {
+            Ok(Some(Message::FileThumbnail(thumbnail))) => {
+                assert_eq!(thumbnail.path, path.to_string_lossy());
+                assert_eq!(thumbnail.mime_type, "image/png");
+                assert!(thumbnail.jpeg.is_some());
+                assert_eq!((thumbnail.width, thumbnail.height), (40, 20));
+            }
+            other => panic!("Expected FileThumbnail, got {:?}", other),
+        }
+
+        // Previews need read permission
+        let other = DeviceId::from_bytes([9u8; 16]);
+        assert!(matches!(
+            router.route(msg, &other, None).await,
+            Err(RouterError::Permission(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_file_tail() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.log");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+        let device_id = test_device_id();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let tails = Arc::new(FileTails::new(
+            tx,
+            TailLimits {
+                poll_interval: Duration::from_millis(10),
+                ..TailLimits::default()
+            },
+        ));
+        let router = create_test_router(&temp_dir).with_file_tails(Arc::clone(&tails));
+
+        let msg = Message::FileTailRequest(FileTailRequest {
+            path: path.clone(),
+            lines: 1,
+            follow: true,
+        });
+        match router.route(msg.clone(), &device_id, None).await {
+            Ok(Some(Message::FileTailData(data))) => {
+                assert_eq!(data.path, path);
+                assert_eq!(data.data, b"two\n");
+            }
+            other => panic!("Expected FileTailData, got {:?}", other),
+        }
+        assert_eq!(tails.len(), 1);
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"three\n")
+            .unwrap();
+        let (to, msg) = rx.recv().await.unwrap();
+        assert_eq!(to, device_id);
+        match msg {
+            Message::FileTailData(data) => assert_eq!(data.data, b"three\n"),
+            other => panic!("Expected FileTailData, got {:?}", other),
+        }
+
+        let stop = Message::FileTailStop(FileTailStop { path: path.clone() });
+        assert!(matches!(
+            router.route(stop, &device_id, None).await,
+            Ok(None)
+        ));
+        assert!(tails.is_empty());
+
+        // Tailing needs read permission
+        let other = DeviceId::from_bytes([9u8; 16]);
+        let msg = Message::FileTailRequest(FileTailRequest {
+            path,
+            lines: 10,
+            follow: false,
+        });
+        assert!(matches!(
+            router.route(msg, &other, None).await,
+            Err(RouterError::Permission(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_file_text_edit() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.conf");
+        std::fs::write(&path, "debug = false\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let router = create_test_router(&temp_dir);
+        let device_id = test_device_id();
+
+        let read = Message::FileReadText(FileReadText { path: path.clone() });
+        let text = match router.route(read, &device_id, None).await {
+            Ok(Some(Message::FileText(text))) => text,
+            other => panic!("Expected FileText, got {:?}", other),
+        };
+        assert_eq!(text.content, "debug = false\n");
+
+        let write = |expected_hash: Vec<u8>| {
+            Message::FileWriteText(FileWriteText {
+                path: path.clone(),
+                content: "debug = true\n".to_string(),
+                expected_hash: Some(expected_hash),
+            })
+        };
+        match router
+            .route(write(text.hash.clone()), &device_id, None)
+            .await
+        {
+            Ok(Some(Message::FileTextWritten(written))) => {
+                assert_eq!(written.hash, blake3::hash(b"debug = true\n").as_bytes());
+            }
+            other => panic!("Expected FileTextWritten, got {:?}", other),
+        }
+
+        // Writing again from the old contents conflicts
+        let err = router
+            .route(write(text.hash), &device_id, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error_code(), ErrorCode::Conflict);
+        assert!(!err.to_error_message(None).recoverable);
+    }
+
+    #[tokio::test]
+    async fn test_route_file_copy_grant() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir
+            .path()
+            .join("copy.bin")
+            .to_string_lossy()
+            .to_string();
+        let request = || {
+            Message::FileCopyGrantRequest(FileCopyGrantRequest {
+                path: path.clone(),
+                overwrite: false,
+            })
+        };
+
+        // Refused unless direct copies are enabled
+        let router = create_test_router(&temp_dir);
+        assert!(matches!(
+            router.route(request(), &test_device_id(), None).await,
+            Err(RouterError::InvalidRequest(_))
+        ));
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let transfer = Arc::new(FileTransfer::new(
+            DirectoryBrowser::new(vec![temp_dir.path().to_path_buf()]),
+            1024,
+        ));
+        let identity = protocol::crypto::DeviceIdentity::generate();
+        let copy = Arc::new(FileCopy::new(
+            identity.clone(),
+            transfer,
+            vec!["192.168.1.20:7677".to_string()],
+            tx,
+        ));
+        let router = create_test_router(&temp_dir).with_file_copy(Arc::clone(&copy));
+        match router.route(request(), &test_device_id(), None).await {
+            Ok(Some(Message::FileCopyGrant(grant))) => {
+                assert_eq!(grant.path, path);
+                assert_eq!(grant.token.len(), 32);
+                assert_eq!(grant.addresses, vec!["192.168.1.20:7677".to_string()]);
+                assert_eq!(grant.public_key, identity.public_key_bytes());
+            }
+            other => panic!("Expected FileCopyGrant, got {:?}", other),
         }
+        assert_eq!(copy.pending_grants(), 1);
     }
 
     #[tokio::test]
-    async fn test_route_file_upload_flow() {
+    async fn test_route_file_upload_report() {
+        use sha2::{Digest, Sha256};
+
         let temp_dir = TempDir::new().unwrap();
         let upload_dir = temp_dir.path().join("uploads");
         std::fs::create_dir_all(&upload_dir).unwrap();
 
         let router = create_test_router(&temp_dir);
-        let dest_path = upload_dir.join("uploaded.txt");
+        let dest_path = upload_dir.join("reported.txt");
+        let path = dest_path.to_string_lossy().to_string();
+        let content = b"Hello World!";
 
-        // Start upload
         let msg = Message::FileUploadStart(FileUploadStart {
-            path: dest_path.to_string_lossy().to_string(),
-            size: 12,
+            path: path.clone(),
+            size: content.len() as u64,
             mode: 0o644,
             overwrite: false,
         });
-        let result = router.route(msg, &test_device_id(), None).await;
-        assert!(result.is_ok());
-        assert!(result.unwrap().is_none());
+        router.route(msg, &test_device_id(), None).await.unwrap();
 
-        // Send chunk
+        // A chunk that does not match its hash is rejected
         let msg = Message::FileUploadChunk(FileUploadChunk {
-            path: dest_path.to_string_lossy().to_string(),
+            path: path.clone(),
             offset: 0,
-            data: b"Hello World!".to_vec(),
+            data: b"Hello Wor1d!".to_vec(),
+            hash: Some(blake3::hash(content).as_bytes().to_vec()),
         });
         let result = router.route(msg, &test_device_id(), None).await;
-        assert!(result.is_ok());
-        assert!(result.unwrap().is_none());
+        assert!(matches!(result, Err(RouterError::File { .. })));
 
-        // Complete upload with correct checksum
-        use sha2::{Digest, Sha256};
-        let mut hasher = Sha256::new();
-        hasher.update(b"Hello World!");
-        let checksum = hasher.finalize().to_vec();
+        let msg = Message::FileUploadChunk(FileUploadChunk {
+            path: path.clone(),
+            offset: 0,
+            data: content.to_vec(),
+            hash: Some(blake3::hash(content).as_bytes().to_vec()),
+        });
+        router.route(msg, &test_device_id(), None).await.unwrap();
 
         let msg = Message::FileUploadComplete(FileUploadComplete {
-            path: dest_path.to_string_lossy().to_string(),
-            checksum,
+            path: path.clone(),
+            checksum: Sha256::digest(content).to_vec(),
+            report: true,
         });
-        let result = router.route(msg, &test_device_id(), None).await;
-        assert!(result.is_ok());
-
-        // Verify file was created
-        assert!(dest_path.exists());
-        assert_eq!(std::fs::read(&dest_path).unwrap(), b"Hello World!");
+        match router.route(msg, &test_device_id(), None).await {
+            Ok(Some(Message::FileTransferReport(report))) => {
+                assert_eq!(report.path, path);
+                assert_eq!(report.size, content.len() as u64);
+                assert_eq!(report.hash, blake3::hash(content).as_bytes().to_vec());
+                assert_eq!(report.chunks, 1);
+                assert_eq!(report.verified_chunks, 1);
+            }
+            other => panic!("Expected FileTransferReport, got {:?}", other),
+        }
+        assert_eq!(std::fs::read(&dest_path).unwrap(), content);
     }
 
     // =========================================================================
@@ -1355,6 +4647,8 @@ mod tests {
             name: "New Device".to_string(),
             public_key: identity.public_key_bytes().to_vec(),
             reason: Some("Testing".to_string()),
+            reinvite_token: None,
+            pairing_code: None,
         });
 
         let result = router.route(msg, &test_device_id(), None).await;
@@ -1369,6 +4663,81 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_route_device_approval_request_normalizes_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+
+        let identity = protocol::DeviceIdentity::generate();
+        let raw_name = "\u{0410}lice's\u{202E} laptop\x1b[0m\n";
+        let msg = Message::DeviceApprovalRequest(DeviceApprovalRequest {
+            device_id: identity.device_id().to_string(),
+            name: raw_name.to_string(),
+            public_key: identity.public_key_bytes().to_vec(),
+            reason: None,
+            reinvite_token: None,
+            pairing_code: None,
+        });
+        let result = router.route(msg, identity.device_id(), None).await;
+        assert!(matches!(result, Ok(Some(Message::DeviceRejected(_)))));
+
+        let device = router
+            .trust_store
+            .get_device(identity.device_id())
+            .unwrap()
+            .unwrap();
+        assert_eq!(device.name, "\u{0410}lice's laptop[0m");
+        assert_eq!(device.raw_name.as_deref(), Some(raw_name));
+    }
+
+    #[tokio::test]
+    async fn test_honeypot_answers_untrusted_devices() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, trusted_id) = create_test_router_with_trusted_device(&temp_dir);
+        let config = crate::config::HoneypotConfig {
+            enabled: true,
+            delay_ms: 0,
+            ..Default::default()
+        };
+        let router = router.with_honeypot(Arc::new(Honeypot::new(&config, temp_dir.path())));
+
+        // A new device appears to be approved, but stays pending
+        let identity = protocol::DeviceIdentity::generate();
+        let msg = Message::DeviceApprovalRequest(DeviceApprovalRequest {
+            device_id: identity.device_id().to_string(),
+            name: "Scanner".to_string(),
+            public_key: identity.public_key_bytes().to_vec(),
+            reason: None,
+            reinvite_token: None,
+            pairing_code: None,
+        });
+        let result = router.route(msg, identity.device_id(), None).await;
+        assert!(matches!(result, Ok(Some(Message::DeviceApproved(_)))));
+        assert!(router.require_trusted(identity.device_id()).is_err());
+
+        // Its sessions are fake
+        let msg = Message::SessionCreate(SessionCreate::default());
+        match router.route(msg, identity.device_id(), None).await.unwrap() {
+            Some(Message::SessionCreated(created)) => {
+                assert_ne!(created.session_id, "test-session-123")
+            }
+            other => panic!("Expected a fake session, got {:?}", other),
+        }
+
+        // Trusted devices still reach the session manager
+        let msg = Message::SessionCreate(SessionCreate::default());
+        match router.route(msg, &trusted_id, None).await.unwrap() {
+            Some(Message::SessionCreated(created)) => {
+                assert_eq!(created.session_id, "test-session-123")
+            }
+            other => panic!("Expected a real session, got {:?}", other),
+        }
+
+        let log =
+            std::fs::read_to_string(temp_dir.path().join(crate::honeypot::LOG_FILE_NAME)).unwrap();
+        assert_eq!(log.lines().count(), 2);
+    }
+
     #[tokio::test]
     async fn test_route_device_approval_request_public_key_mismatch() {
         let temp_dir = TempDir::new().unwrap();
@@ -1384,6 +4753,8 @@ mod tests {
             name: "Test Device".to_string(),
             public_key: identity.public_key_bytes().to_vec(),
             reason: Some("Testing".to_string()),
+            reinvite_token: None,
+            pairing_code: None,
         });
 
         // Provide a DIFFERENT authenticated public key (simulating spoofing)
@@ -1422,6 +4793,8 @@ mod tests {
             name: "Test Device".to_string(),
             public_key: public_key_bytes.to_vec(),
             reason: Some("Testing".to_string()),
+            reinvite_token: None,
+            pairing_code: None,
         });
 
         // Provide the SAME authenticated public key
@@ -1440,6 +4813,290 @@ mod tests {
         }
     }
 
+    /// Registers a revoked device and returns its identity.
+    fn create_revoked_device(trust_store: &TrustStore) -> protocol::DeviceIdentity {
+        let identity = protocol::DeviceIdentity::generate();
+        let mut device = TrustedDevice::new(
+            *identity.device_id(),
+            "Old Phone".to_string(),
+            identity.public_key_bytes(),
+        );
+        device.trust_level = TrustLevel::Revoked;
+        trust_store.add_device(device).unwrap();
+        identity
+    }
+
+    #[tokio::test]
+    async fn test_route_device_approval_request_reinvite_accepted() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+        let identity = create_revoked_device(&router.trust_store);
+
+        let token = ReinviteStore::for_trust_store(router.trust_store.path())
+            .issue(*identity.device_id(), crate::devices::DEFAULT_REINVITE_TTL)
+            .unwrap();
+
+        let msg = Message::DeviceApprovalRequest(DeviceApprovalRequest {
+            device_id: identity.device_id().to_string(),
+            name: "Old Phone".to_string(),
+            public_key: identity.public_key_bytes().to_vec(),
+            reason: None,
+            reinvite_token: Some(token.clone()),
+            pairing_code: None,
+        });
+
+        let result = router.route(msg.clone(), &test_device_id(), None).await;
+        assert!(matches!(result, Ok(Some(Message::DeviceApproved(_)))));
+        assert!(router.trust_store.is_trusted(identity.device_id()).unwrap());
+
+        // The token cannot be reused once the device is revoked again
+        router
+            .trust_store
+            .set_trust_level(identity.device_id(), TrustLevel::Revoked)
+            .unwrap();
+        let result = router.route(msg, &test_device_id(), None).await;
+        match result.unwrap() {
+            Some(Message::DeviceRejected(rejected)) => assert!(!rejected.retry_allowed),
+            _ => panic!("Expected DeviceRejected for reused token"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_device_approval_request_reinvite_keeps_capabilities() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+        let identity = protocol::DeviceIdentity::generate();
+        let mut device = TrustedDevice::new(
+            *identity.device_id(),
+            "Old Phone".to_string(),
+            identity.public_key_bytes(),
+        );
+        device.trust_level = TrustLevel::Revoked;
+        device.grant = Some(crate::devices::TemporaryGrant {
+            expires_at: SystemTime::now() + std::time::Duration::from_secs(3600),
+            capabilities: [FILES_ACCESS.to_string()].into_iter().collect(),
+            previous_trust_level: TrustLevel::Unknown,
+        });
+        router.trust_store.add_device(device).unwrap();
+
+        let token = ReinviteStore::for_trust_store(router.trust_store.path())
+            .issue(*identity.device_id(), crate::devices::DEFAULT_REINVITE_TTL)
+            .unwrap();
+        let msg = Message::DeviceApprovalRequest(DeviceApprovalRequest {
+            device_id: identity.device_id().to_string(),
+            name: "Old Phone".to_string(),
+            public_key: identity.public_key_bytes().to_vec(),
+            reason: None,
+            reinvite_token: Some(token),
+            pairing_code: None,
+        });
+
+        // A device limited to file transfer does not come back with a shell
+        match router.route(msg, &test_device_id(), None).await.unwrap() {
+            Some(Message::DeviceApproved(approved)) => {
+                assert_eq!(approved.allowed_capabilities, vec!["file-transfer"]);
+                assert!(approved.expires_at.is_some());
+            }
+            _ => panic!("Expected DeviceApproved"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_device_approval_request_reinvite_invalid_token() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+        let identity = create_revoked_device(&router.trust_store);
+
+        ReinviteStore::for_trust_store(router.trust_store.path())
+            .issue(*identity.device_id(), crate::devices::DEFAULT_REINVITE_TTL)
+            .unwrap();
+
+        let msg = Message::DeviceApprovalRequest(DeviceApprovalRequest {
+            device_id: identity.device_id().to_string(),
+            name: "Old Phone".to_string(),
+            public_key: identity.public_key_bytes().to_vec(),
+            reason: None,
+            reinvite_token: Some("guessed".to_string()),
+            pairing_code: None,
+        });
+
+        let result = router.route(msg, &test_device_id(), None).await;
+        match result.unwrap() {
+            Some(Message::DeviceRejected(rejected)) => {
+                assert_eq!(rejected.reason, "Invalid re-invite token");
+                assert!(!rejected.retry_allowed);
+            }
+            _ => panic!("Expected DeviceRejected for invalid token"),
+        }
+        assert!(!router.trust_store.is_trusted(identity.device_id()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_route_device_approval_request_pairing_code_single_use() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+        let store = PairingCodeStore::for_trust_store(router.trust_store.path());
+        store
+            .register("PAIR-0001", std::time::Duration::from_secs(300))
+            .unwrap();
+
+        let request = |identity: &protocol::DeviceIdentity| {
+            Message::DeviceApprovalRequest(DeviceApprovalRequest {
+                device_id: identity.device_id().to_string(),
+                name: "Phone".to_string(),
+                public_key: identity.public_key_bytes().to_vec(),
+                reason: None,
+                reinvite_token: None,
+                pairing_code: Some("PAIR-0001".to_string()),
+            })
+        };
+
+        // The first device consumes the code and goes on to approval as usual
+        let first = protocol::DeviceIdentity::generate();
+        let result = router.route(request(&first), &test_device_id(), None).await;
+        match result.unwrap() {
+            Some(Message::DeviceRejected(rejected)) => assert!(rejected.retry_allowed),
+            _ => panic!("Expected the device to await approval"),
+        }
+        assert!(router
+            .trust_store
+            .get_device(first.device_id())
+            .unwrap()
+            .is_some());
+
+        // A second device replaying the code is rejected outright
+        let second = protocol::DeviceIdentity::generate();
+        let result = router
+            .route(request(&second), &test_device_id(), None)
+            .await;
+        match result.unwrap() {
+            Some(Message::DeviceRejected(rejected)) => {
+                assert_eq!(rejected.reason, "Pairing code has already been used");
+                assert!(!rejected.retry_allowed);
+            }
+            _ => panic!("Expected DeviceRejected for a reused code"),
+        }
+        assert!(router
+            .trust_store
+            .get_device(second.device_id())
+            .unwrap()
+            .is_none());
+
+        match store.status("PAIR-0001").unwrap() {
+            crate::devices::PairingCodeStatus::Consumed { device_id, .. } => {
+                assert_eq!(device_id, first.device_id().fingerprint())
+            }
+            other => panic!("Expected consumed code, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_pairing_code_waits_for_lock_off_the_runtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = Arc::new(create_test_router(&temp_dir));
+        let store = PairingCodeStore::for_trust_store(router.trust_store.path());
+        store
+            .register("PAIR-0003", std::time::Duration::from_secs(300))
+            .unwrap();
+
+        // The CLI holds the store's lock for a while
+        let lock_file = std::fs::File::create(store.path().with_extension("lock")).unwrap();
+        let lock = nix::fcntl::Flock::lock(lock_file, nix::fcntl::FlockArg::LockExclusive).unwrap();
+        let holder = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            drop(lock);
+        });
+
+        let identity = protocol::DeviceIdentity::generate();
+        let request = tokio::spawn({
+            let router = Arc::clone(&router);
+            async move {
+                let msg = Message::DeviceApprovalRequest(DeviceApprovalRequest {
+                    device_id: identity.device_id().to_string(),
+                    name: "Phone".to_string(),
+                    public_key: identity.public_key_bytes().to_vec(),
+                    reason: None,
+                    reinvite_token: None,
+                    pairing_code: Some("PAIR-0003".to_string()),
+                });
+                router.route(msg, &test_device_id(), None).await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Other messages are handled while the request waits for the lock
+        let ping = Message::Ping(Ping {
+            timestamp: 0,
+            payload: vec![],
+        });
+        assert!(matches!(
+            router.route(ping, &test_device_id(), None).await,
+            Ok(Some(Message::Pong(_)))
+        ));
+        assert!(!request.is_finished());
+
+        holder.join().unwrap();
+        assert!(matches!(
+            request.await.unwrap(),
+            Ok(Some(Message::DeviceRejected(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_device_approval_request_requires_outstanding_pairing_code() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+        let store = PairingCodeStore::for_trust_store(router.trust_store.path());
+        store
+            .register("PAIR-0002", std::time::Duration::from_secs(300))
+            .unwrap();
+
+        let identity = protocol::DeviceIdentity::generate();
+        let request = |pairing_code: Option<&str>| {
+            Message::DeviceApprovalRequest(DeviceApprovalRequest {
+                device_id: identity.device_id().to_string(),
+                name: "Phone".to_string(),
+                public_key: identity.public_key_bytes().to_vec(),
+                reason: None,
+                reinvite_token: None,
+                pairing_code: pairing_code.map(str::to_string),
+            })
+        };
+
+        // Leaving the code out does not get around the single-use check
+        let result = router.route(request(None), &test_device_id(), None).await;
+        match result.unwrap() {
+            Some(Message::DeviceRejected(rejected)) => {
+                assert_eq!(rejected.reason, "Pairing code required");
+                assert!(rejected.retry_allowed);
+            }
+            _ => panic!("Expected DeviceRejected without a pairing code"),
+        }
+        assert!(!router.trust_store.is_pending(identity.device_id()).unwrap());
+        assert!(store.has_outstanding().unwrap());
+
+        // Once the code is used, devices without one are queued as before
+        router
+            .route(request(Some("PAIR-0002")), &test_device_id(), None)
+            .await
+            .unwrap();
+        let other = protocol::DeviceIdentity::generate();
+        let msg = Message::DeviceApprovalRequest(DeviceApprovalRequest {
+            device_id: other.device_id().to_string(),
+            name: "Tablet".to_string(),
+            public_key: other.public_key_bytes().to_vec(),
+            reason: None,
+            reinvite_token: None,
+            pairing_code: None,
+        });
+        match router.route(msg, &test_device_id(), None).await.unwrap() {
+            Some(Message::DeviceRejected(rejected)) => {
+                assert_ne!(rejected.reason, "Pairing code required")
+            }
+            _ => panic!("Expected the device to await approval"),
+        }
+    }
+
     // =========================================================================
     // Control Message Tests
     // =========================================================================
@@ -1486,18 +5143,37 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let router = create_test_router(&temp_dir);
 
-        let msg = Message::Error(ErrorMessage {
-            code: ErrorCode::InternalError,
-            message: "Something went wrong".to_string(),
-            context: None,
-            recoverable: false,
-        });
+        let msg = Message::Error(ErrorMessage::new(
+            ErrorCode::InternalError,
+            "Something went wrong",
+        ));
 
         let result = router.route(msg, &test_device_id(), None).await;
         assert!(result.is_ok());
         assert!(result.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn test_route_unknown_message_unsupported() {
+        let temp_dir = TempDir::new().unwrap();
+        let router = create_test_router(&temp_dir);
+
+        let msg = Message::Unknown {
+            tag: "SessionShare".to_string(),
+            payload: vec![0x90],
+        };
+
+        let err = router
+            .route(msg, &test_device_id(), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RouterError::Unsupported(ref tag) if tag == "SessionShare"));
+        let error = err.to_error_message(None);
+        assert_eq!(error.code, ErrorCode::Unsupported);
+        assert!(!error.recoverable);
+        assert!(error.message.contains("SessionShare"));
+    }
+
     // =========================================================================
     // Response Messages (should be ignored)
     // =========================================================================
@@ -1524,6 +5200,7 @@ mod tests {
             exit_code: Some(0),
             signal: None,
             reason: None,
+            close_reason: None,
         });
         assert!(router
             .route(msg, &test_device_id(), None)
@@ -1549,6 +5226,8 @@ mod tests {
             total_size: 100,
             data: vec![],
             is_last: true,
+            hash: None,
+            file_hash: None,
         });
         assert!(router
             .route(msg, &test_device_id(), None)
@@ -1581,6 +5260,118 @@ mod tests {
             .is_none());
     }
 
+    // =========================================================================
+    // Interceptor Tests
+    // =========================================================================
+
+    /// Refuses configuration changes, as a maintenance mode would.
+    struct Maintenance;
+
+    impl Interceptor for Maintenance {
+        fn name(&self) -> &'static str {
+            "maintenance"
+        }
+
+        fn before(&self, request: &Request<'_>) -> Result<(), RouterError> {
+            match request.message {
+                Message::ConfigPatch(_) => Err(RouterError::InvalidRequest(
+                    "the daemon is in maintenance".to_string(),
+                )),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_role_check_refuses_viewer() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let mut device = router.trust_store.get_device(&device_id).unwrap().unwrap();
+        device.role = Some(crate::authz::Role::Viewer);
+        router.trust_store.add_device(device).unwrap();
+
+        let result = router
+            .route(
+                Message::ConfigPatch(ConfigPatch::default()),
+                &device_id,
+                None,
+            )
+            .await;
+        assert!(
+            matches!(&result, Err(RouterError::Permission(m)) if m.contains("viewer")),
+            "{:?}",
+            result
+        );
+        // Messages that are not management requests need no role
+        let list = Message::ListTemplates(protocol::messages::ListTemplates {});
+        assert!(router.route(list, &device_id, None).await.is_ok());
+
+        let metrics = router.message_metrics();
+        assert_eq!(metrics["ConfigPatch"].rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn test_interceptor_rejects_before_handler() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let router = router.with_interceptor(Arc::new(Maintenance));
+        let list = || Message::ListTemplates(protocol::messages::ListTemplates {});
+        // Owners get past the role check to the maintenance interceptor
+        let mut device = router.trust_store.get_device(&device_id).unwrap().unwrap();
+        device.role = Some(crate::authz::Role::Owner);
+        router.trust_store.add_device(device).unwrap();
+
+        assert!(router.route(list(), &device_id, None).await.is_ok());
+        // The handler would answer that remote configuration is unavailable
+        let result = router
+            .route(
+                Message::ConfigPatch(ConfigPatch::default()),
+                &device_id,
+                None,
+            )
+            .await;
+        assert!(
+            matches!(&result, Err(RouterError::InvalidRequest(m)) if m.contains("maintenance")),
+            "{:?}",
+            result
+        );
+
+        // Untrusted devices are turned away by the trust check first
+        let stranger = DeviceId::from_bytes([9; 16]);
+        assert!(matches!(
+            router.route(list(), &stranger, None).await,
+            Err(RouterError::Device(_))
+        ));
+
+        let metrics = router.message_metrics();
+        assert_eq!(metrics["ListTemplates"].handled, 1);
+        assert_eq!(metrics["ListTemplates"].rejected, 1);
+        assert_eq!(metrics["ConfigPatch"].rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn test_route_rate_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let (router, device_id) = create_test_router_with_trusted_device(&temp_dir);
+        let router = router.with_rate_limit(2);
+        let ping = || {
+            Message::Ping(Ping {
+                timestamp: 0,
+                payload: vec![],
+            })
+        };
+
+        assert!(router.route(ping(), &device_id, None).await.is_ok());
+        assert!(router.route(ping(), &device_id, None).await.is_ok());
+        let err = router.route(ping(), &device_id, None).await.unwrap_err();
+        assert_eq!(err.error_code(), ErrorCode::RateLimited);
+        assert!(err.to_error_message(None).retry_after_ms.is_some());
+
+        // A new connection starts with a full allowance
+        router.close_interceptors(&device_id);
+        assert!(router.route(ping(), &device_id, None).await.is_ok());
+    }
+
     // =========================================================================
     // Error Handling Tests
     // =========================================================================
@@ -1595,6 +5386,19 @@ mod tests {
         assert_eq!(msg.context, Some("test-session".to_string()));
     }
 
+    #[test]
+    fn test_router_insufficient_space_to_message() {
+        let err = RouterError::from(TransferError::InsufficientSpace {
+            required: 2048,
+            available: 1024,
+        });
+        let msg = err.to_error_message(Some("/tmp/upload.bin".to_string()));
+
+        assert_eq!(msg.code, ErrorCode::InsufficientSpace);
+        assert!(!msg.recoverable);
+        assert!(msg.message.contains("2.0 KiB required"));
+    }
+
     #[tokio::test]
     async fn test_file_list_path_outside_allowed() {
         let temp_dir = TempDir::new().unwrap();
@@ -1604,6 +5408,7 @@ mod tests {
         let msg = Message::FileListRequest(FileListRequest {
             path: "/nonexistent/path/that/does/not/exist".to_string(),
             include_hidden: false,
+            detail: FileListDetail::Basic,
         });
 
         let result = router.route(msg, &test_device_id(), None).await;
@@ -1622,12 +5427,19 @@ mod tests {
         let msg = Message::FileListRequest(FileListRequest {
             path: nonexistent.to_string_lossy().to_string(),
             include_hidden: false,
+            detail: FileListDetail::Basic,
         });
 
         let result = router.route(msg, &test_device_id(), None).await;
         assert!(result.is_err());
         // File error because the path is allowed but doesn't exist
-        assert!(matches!(result, Err(RouterError::File(_))));
+        assert!(matches!(
+            result,
+            Err(RouterError::File {
+                code: ErrorCode::NotFound,
+                ..
+            })
+        ));
     }
 
     // =========================================================================
@@ -1676,6 +5488,7 @@ mod tests {
                 .to_string_lossy()
                 .to_string(),
             include_hidden: false,
+            detail: FileListDetail::Basic,
         });
 
         let result = router.route(msg, &device_id, None).await;
@@ -1723,6 +5536,8 @@ mod tests {
             path: test_file.to_string_lossy().to_string(),
             offset: 0,
             chunk_size: 1024,
+            verify: false,
+            window: 0,
         });
 
         let result = router.route(msg, &device_id, None).await;
@@ -1823,6 +5638,8 @@ mod tests {
             path: test_file.to_string_lossy().to_string(),
             offset: 0,
             chunk_size: 1024,
+            verify: false,
+            window: 0,
         });
 
         let result = router.route(msg, &device_id, None).await;
@@ -1833,6 +5650,7 @@ mod tests {
         let msg = Message::FileListRequest(FileListRequest {
             path: temp_dir.path().to_string_lossy().to_string(),
             include_hidden: false,
+            detail: FileListDetail::Basic,
         });
 
         let result = router.route(msg, &device_id, None).await;
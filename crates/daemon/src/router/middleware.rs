@@ -0,0 +1,603 @@
+//! Interceptors run around the handler of every routed message.
+//!
+//! [`MessageRouter::route`](super::MessageRouter::route) passes each message
+//! through a chain of [`Interceptor`]s before its handler: the trust check,
+//! the role check, the grant check, the rate limit if one is set, the audit
+//! of rejections and the message metrics, then any added with
+//! [`with_interceptor`](super::MessageRouter::with_interceptor). An
+//! interceptor rejects a message by returning an error from
+//! [`Interceptor::before`]; later interceptors and the handler then never
+//! see it. Every interceptor is told the outcome in [`Interceptor::after`],
+//! rejections included.
+//!
+//! A policy that applies across message types, such as a maintenance mode
+//! refusing anything that writes, is one interceptor rather than a check
+//! copied into each handler.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use protocol::messages::Message;
+use protocol::DeviceId;
+use tracing::warn;
+
+use super::{RouterError, RouterResult};
+use crate::authz::{self, Operation, Principal};
+use crate::devices::{TrustCache, TrustLevel, FILES_ACCESS, SHELL_ACCESS};
+
+/// A message on its way to its handler.
+#[derive(Debug, Clone, Copy)]
+pub struct Request<'a> {
+    /// The message.
+    pub message: &'a Message,
+    /// The device that sent it.
+    pub device_id: &'a DeviceId,
+}
+
+/// What came of a routed message.
+#[derive(Debug, Clone, Copy)]
+pub struct Outcome<'a> {
+    /// The message type, from [`Message::name`].
+    pub message: &'static str,
+    /// The device that sent it.
+    pub device_id: &'a DeviceId,
+    /// What the handler returned, or the rejection.
+    pub result: &'a RouterResult,
+    /// The interceptor that rejected the message, if one did.
+    pub rejected_by: Option<&'static str>,
+    /// Time from the first interceptor to the end of the handler.
+    pub elapsed: Duration,
+}
+
+/// A step every routed message passes through.
+pub trait Interceptor: Send + Sync {
+    /// Names the interceptor in logs and in [`Outcome::rejected_by`].
+    fn name(&self) -> &'static str;
+
+    /// Called before the handler. An error rejects the message and is sent
+    /// to the device instead of a response.
+    fn before(&self, _request: &Request<'_>) -> Result<(), RouterError> {
+        Ok(())
+    }
+
+    /// Called once the message is handled or rejected.
+    fn after(&self, _outcome: &Outcome<'_>) {}
+
+    /// Called when a device disconnects, to drop what is kept for it.
+    fn device_disconnected(&self, _device_id: &DeviceId) {}
+}
+
+/// Returns true if only trusted devices may send `message`.
+///
+/// Pairing, control and response messages are left out, as are file
+/// requests, which path permissions decide.
+pub fn requires_trust(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::SessionCreate(_)
+            | Message::SessionAttach(_)
+            | Message::SessionKill(_)
+            | Message::SessionSignal(_)
+            | Message::UpdateSessionEnv(_)
+            | Message::SessionData(_)
+            | Message::SessionMetadataRequest(_)
+            | Message::SessionSearchRequest(_)
+            | Message::ListTemplates(_)
+            | Message::AgentData(_)
+            | Message::DisplayData(_)
+            | Message::SecurityKeyAssertion(_)
+            | Message::SystemInfoRequest(_)
+            | Message::ProcessListRequest(_)
+            | Message::ProcessKill(_)
+            | Message::PowerActionRequest(_)
+            | Message::PowerActionConfirm(_)
+            | Message::ConfigRequest(_)
+            | Message::ConfigPatch(_)
+    )
+}
+
+/// Rejects the messages in [`requires_trust`] from devices that are not
+/// trusted.
+#[derive(Debug)]
+pub struct TrustCheck {
+    cache: Arc<TrustCache>,
+}
+
+impl TrustCheck {
+    /// Creates a check reading trust through `cache`.
+    pub fn new(cache: Arc<TrustCache>) -> Self {
+        Self { cache }
+    }
+
+    /// Verifies that the device is trusted.
+    ///
+    /// Returns `Ok(())` if the device has `TrustLevel::Trusted` and no expired
+    /// temporary grant, otherwise returns `Err(RouterError::Device)` with an
+    /// appropriate error message.
+    pub fn check(&self, device_id: &DeviceId) -> Result<(), RouterError> {
+        match self.cache.get(device_id) {
+            Ok(Some(device)) => match device.trust_level {
+                TrustLevel::Trusted if device.is_trusted_at(SystemTime::now()) => Ok(()),
+                TrustLevel::Trusted => Err(RouterError::Device(
+                    "Temporary access has expired".to_string(),
+                )),
+                TrustLevel::Unknown => {
+                    Err(RouterError::Device("Device pending approval".to_string()))
+                }
+                TrustLevel::Revoked => {
+                    Err(RouterError::Device("Device has been revoked".to_string()))
+                }
+            },
+            Ok(None) => Err(RouterError::Device("Device not registered".to_string())),
+            Err(e) => Err(RouterError::Device(format!(
+                "Failed to check device trust: {}",
+                e
+            ))),
+        }
+    }
+}
+
+impl Interceptor for TrustCheck {
+    fn name(&self) -> &'static str {
+        "trust"
+    }
+
+    fn before(&self, request: &Request<'_>) -> Result<(), RouterError> {
+        if requires_trust(request.message) {
+            self.check(request.device_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rejects management messages, such as configuration patches, from
+/// devices whose role does not allow the operation.
+///
+/// Denials are logged under the `audit` target by [`authz::authorize`].
+#[derive(Debug)]
+pub struct RoleCheck {
+    cache: Arc<TrustCache>,
+}
+
+impl RoleCheck {
+    /// Creates a check reading roles through `cache`.
+    pub fn new(cache: Arc<TrustCache>) -> Self {
+        Self { cache }
+    }
+
+    /// Returns the principal behind a device's messages. Only devices
+    /// trusted now keep their role.
+    pub fn principal(&self, device_id: &DeviceId) -> Principal {
+        let trust = self.cache.get(device_id).ok().flatten();
+        Principal::from_trust(*device_id, trust.as_ref(), SystemTime::now())
+    }
+}
+
+impl Interceptor for RoleCheck {
+    fn name(&self) -> &'static str {
+        "authz"
+    }
+
+    fn before(&self, request: &Request<'_>) -> Result<(), RouterError> {
+        let Some(operation) = Operation::of_message(request.message) else {
+            return Ok(());
+        };
+        authz::authorize(&self.principal(request.device_id), operation)
+            .map_err(|e| RouterError::Permission(e.to_string()))
+    }
+}
+
+/// What a temporary grant must cover for a device to send a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Any device may send it: pairing and control messages.
+    Open,
+    /// The grant must include this base right, [`SHELL_ACCESS`] or
+    /// [`FILES_ACCESS`].
+    Requires(&'static str),
+    /// No grant covers it.
+    Denied,
+}
+
+/// Returns what a temporary grant must cover for a device to send `message`.
+///
+/// Every message type is listed, so a new one has to be given a right here
+/// before it builds; responses, management and unknown messages are denied.
+pub fn required_access(message: &Message) -> Access {
+    match message {
+        Message::SessionCreate(_)
+        | Message::SessionAttach(_)
+        | Message::SessionDetach(_)
+        | Message::SessionKill(_)
+        | Message::SessionResize(_)
+        | Message::SessionSignal(_)
+        | Message::UpdateSessionEnv(_)
+        | Message::SessionData(_)
+        | Message::SessionMetadataRequest(_)
+        | Message::SessionSearchRequest(_)
+        | Message::ListTemplates(_)
+        | Message::AgentData(_)
+        | Message::AgentClose(_)
+        | Message::DisplayData(_)
+        | Message::DisplayClose(_)
+        | Message::SystemInfoRequest(_)
+        | Message::ProcessListRequest(_)
+        | Message::ProcessKill(_)
+        | Message::PowerActionRequest(_)
+        | Message::PowerActionConfirm(_) => Access::Requires(SHELL_ACCESS),
+
+        Message::FileListRequest(_)
+        | Message::FileDownloadRequest(_)
+        | Message::FileDownloadAck(_)
+        | Message::FileUploadStart(_)
+        | Message::FileUploadChunk(_)
+        | Message::FileUploadComplete(_)
+        | Message::FileCopyGrantRequest(_)
+        | Message::FileCopyRequest(_)
+        | Message::FileReadText(_)
+        | Message::FileWriteText(_)
+        | Message::FileRootsRequest(_)
+        | Message::FileThumbnailRequest(_)
+        | Message::FileTailRequest(_)
+        | Message::FileTailStop(_) => Access::Requires(FILES_ACCESS),
+
+        Message::DeviceInfo(_)
+        | Message::DeviceApprovalRequest(_)
+        | Message::SecurityKeyAssertion(_)
+        | Message::Ping(_)
+        | Message::Pong(_)
+        | Message::Error(_)
+        | Message::Capabilities(_) => Access::Open,
+
+        Message::ConfigRequest(_)
+        | Message::ConfigPatch(_)
+        | Message::SessionCreated(_)
+        | Message::SessionOutputTruncated(_)
+        | Message::SessionClosed(_)
+        | Message::SessionMetadata(_)
+        | Message::SessionSearchResult(_)
+        | Message::TemplateList(_)
+        | Message::AgentOpen(_)
+        | Message::DisplayOpen(_)
+        | Message::FileListResponse(_)
+        | Message::FileDownloadChunk(_)
+        | Message::FileTransferReport(_)
+        | Message::FileCopyGrant(_)
+        | Message::FileText(_)
+        | Message::FileTextWritten(_)
+        | Message::FileRoots(_)
+        | Message::FileThumbnail(_)
+        | Message::FileTailData(_)
+        | Message::DeviceApproved(_)
+        | Message::DeviceRejected(_)
+        | Message::SecurityKeyChallenge(_)
+        | Message::SecurityKeyVerified(_)
+        | Message::SystemInfo(_)
+        | Message::ProcessList(_)
+        | Message::PowerActionChallenge(_)
+        | Message::PowerActionStarted(_)
+        | Message::ConfigView(_)
+        | Message::Notification(_)
+        | Message::ServerShutdownPending(_)
+        | Message::Unknown { .. } => Access::Denied,
+    }
+}
+
+/// Rejects messages from devices with a temporary grant that the grant does
+/// not cover, as decided by [`required_access`]. Devices without a grant are
+/// not restricted.
+///
+/// Denials are logged under the `audit` target.
+#[derive(Debug)]
+pub struct GrantCheck {
+    cache: Arc<TrustCache>,
+}
+
+impl GrantCheck {
+    /// Creates a check reading grants through `cache`.
+    pub fn new(cache: Arc<TrustCache>) -> Self {
+        Self { cache }
+    }
+}
+
+impl Interceptor for GrantCheck {
+    fn name(&self) -> &'static str {
+        "grant"
+    }
+
+    fn before(&self, request: &Request<'_>) -> Result<(), RouterError> {
+        let Some(trust) = self.cache.get(request.device_id).ok().flatten() else {
+            return Ok(());
+        };
+        if trust.grant_rights.is_none() {
+            return Ok(());
+        }
+        let denied = match required_access(request.message) {
+            Access::Open => return Ok(()),
+            Access::Requires(access) if trust.has_access(access) => return Ok(()),
+            Access::Requires(access) => access,
+            Access::Denied => request.message.name(),
+        };
+        warn!(
+            target: "audit",
+            device_id = %request.device_id,
+            message = request.message.name(),
+            access = denied,
+            "Request denied: not covered by temporary grant"
+        );
+        Err(RouterError::Permission(format!(
+            "temporary access does not include {}",
+            denied
+        )))
+    }
+}
+
+/// Messages a device may still send after a second of silence.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Whether the last message was rejected, so that only the first
+    /// rejection in a row is logged.
+    limited: bool,
+}
+
+/// Rejects messages from a device beyond a number per second, allowing
+/// bursts of up to one second's worth.
+#[derive(Debug)]
+pub struct RateLimit {
+    per_second: u32,
+    buckets: Mutex<HashMap<DeviceId, Bucket>>,
+}
+
+impl RateLimit {
+    /// Creates a limit of `per_second` messages per device, which must not
+    /// be zero.
+    pub fn new(per_second: u32) -> Self {
+        debug_assert!(per_second > 0);
+        Self {
+            per_second: per_second.max(1),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the number of devices tracked.
+    pub fn len(&self) -> usize {
+        self.buckets.lock().map(|b| b.len()).unwrap_or(0)
+    }
+
+    /// Returns true if no devices are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn take(&self, device_id: &DeviceId, now: Instant) -> Result<(), RouterError> {
+        let rate = f64::from(self.per_second);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets.entry(*device_id).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+            limited: false,
+        });
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refilled).min(rate);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.limited = false;
+            return Ok(());
+        }
+        if !bucket.limited {
+            bucket.limited = true;
+            warn!(
+                target: "audit",
+                device_id = %device_id,
+                limit = self.per_second,
+                "Device rate limited"
+            );
+        }
+        Err(RouterError::RateLimited {
+            message: format!("more than {} messages per second", self.per_second),
+            retry_after: Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate)),
+        })
+    }
+}
+
+impl Interceptor for RateLimit {
+    fn name(&self) -> &'static str {
+        "rate_limit"
+    }
+
+    fn before(&self, request: &Request<'_>) -> Result<(), RouterError> {
+        self.take(request.device_id, Instant::now())
+    }
+
+    fn device_disconnected(&self, device_id: &DeviceId) {
+        if let Ok(mut buckets) = self.buckets.lock() {
+            buckets.remove(device_id);
+        }
+    }
+}
+
+/// Logs the messages interceptors reject under the `audit` target.
+///
+/// Rate limiting is left out: [`RateLimit`] logs when a device starts being
+/// limited rather than every message it drops.
+#[derive(Debug, Default)]
+pub struct AuditRejections;
+
+impl Interceptor for AuditRejections {
+    fn name(&self) -> &'static str {
+        "audit"
+    }
+
+    fn after(&self, outcome: &Outcome<'_>) {
+        let (Some(interceptor), Err(error)) = (outcome.rejected_by, outcome.result) else {
+            return;
+        };
+        if matches!(error, RouterError::RateLimited { .. }) {
+            return;
+        }
+        warn!(
+            target: "audit",
+            device_id = %outcome.device_id,
+            message = outcome.message,
+            interceptor,
+            reason = %error,
+            "Request rejected"
+        );
+    }
+}
+
+/// Counts of the messages of one type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageStats {
+    /// Messages the handler returned a result for.
+    pub handled: u64,
+    /// Messages the handler returned an error for.
+    pub failed: u64,
+    /// Messages an interceptor rejected.
+    pub rejected: u64,
+    /// Time spent on the messages, rejections included.
+    pub total_time: Duration,
+}
+
+/// Counts routed messages by type.
+#[derive(Debug, Default)]
+pub struct MessageMetrics {
+    stats: Mutex<HashMap<&'static str, MessageStats>>,
+}
+
+impl MessageMetrics {
+    /// Returns the counts so far, by message type.
+    pub fn snapshot(&self) -> BTreeMap<&'static str, MessageStats> {
+        self.stats
+            .lock()
+            .map(|stats| stats.iter().map(|(name, s)| (*name, *s)).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Interceptor for MessageMetrics {
+    fn name(&self) -> &'static str {
+        "metrics"
+    }
+
+    fn after(&self, outcome: &Outcome<'_>) {
+        let Ok(mut stats) = self.stats.lock() else {
+            return;
+        };
+        let stats = stats.entry(outcome.message).or_default();
+        match (outcome.rejected_by, outcome.result) {
+            (Some(_), _) => stats.rejected += 1,
+            (None, Ok(_)) => stats.handled += 1,
+            (None, Err(_)) => stats.failed += 1,
+        }
+        stats.total_time += outcome.elapsed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::messages::{DataStream, Ping, SessionData};
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([1; 16])
+    }
+
+    #[test]
+    fn test_requires_trust() {
+        assert!(requires_trust(&Message::SessionData(SessionData {
+            session_id: "s".to_string(),
+            data: vec![],
+            stream: DataStream::Stdin,
+        })));
+        assert!(!requires_trust(&Message::Ping(Ping {
+            timestamp: 0,
+            payload: vec![],
+        })));
+    }
+
+    #[test]
+    fn test_required_access() {
+        let data = Message::SessionData(SessionData {
+            session_id: "s".to_string(),
+            data: vec![],
+            stream: DataStream::Stdin,
+        });
+        assert_eq!(required_access(&data), Access::Requires(SHELL_ACCESS));
+        assert_eq!(
+            required_access(&Message::FileRootsRequest(Default::default())),
+            Access::Requires(FILES_ACCESS)
+        );
+        assert_eq!(
+            required_access(&Message::Ping(Ping {
+                timestamp: 0,
+                payload: vec![],
+            })),
+            Access::Open
+        );
+        assert_eq!(
+            required_access(&Message::ConfigRequest(Default::default())),
+            Access::Denied
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_refills() {
+        let limit = RateLimit::new(2);
+        let start = Instant::now();
+        assert!(limit.take(&device(), start).is_ok());
+        assert!(limit.take(&device(), start).is_ok());
+
+        let err = limit.take(&device(), start).unwrap_err();
+        let RouterError::RateLimited { retry_after, .. } = err else {
+            panic!("expected RateLimited, got {:?}", err);
+        };
+        assert_eq!(retry_after, Some(Duration::from_millis(500)));
+
+        // Other devices have their own allowance
+        assert!(limit.take(&DeviceId::from_bytes([2; 16]), start).is_ok());
+
+        assert!(limit
+            .take(&device(), start + Duration::from_millis(500))
+            .is_ok());
+        assert_eq!(limit.len(), 2);
+        limit.device_disconnected(&device());
+        assert_eq!(limit.len(), 1);
+    }
+
+    #[test]
+    fn test_metrics_count_outcomes() {
+        let metrics = MessageMetrics::default();
+        let device_id = device();
+        let outcomes: [(RouterResult, Option<&'static str>); 3] = [
+            (Ok(None), None),
+            (Err(RouterError::NotFound("s".to_string())), None),
+            (Err(RouterError::Device("no".to_string())), Some("trust")),
+        ];
+        for (result, rejected_by) in &outcomes {
+            metrics.after(&Outcome {
+                message: "SessionData",
+                device_id: &device_id,
+                result,
+                rejected_by: *rejected_by,
+                elapsed: Duration::from_millis(1),
+            });
+        }
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            snapshot["SessionData"],
+            MessageStats {
+                handled: 1,
+                failed: 1,
+                rejected: 1,
+                total_time: Duration::from_millis(3),
+            }
+        );
+    }
+}
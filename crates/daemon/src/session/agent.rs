@@ -0,0 +1,469 @@
+//! SSH agent forwarding into sessions.
+//!
+//! When a session is created with `forward_agent`, the daemon binds a UNIX
+//! socket in a private directory and sets `SSH_AUTH_SOCK` to it in the
+//! session's environment. Every connection a process makes to the socket
+//! becomes an agent channel: the daemon announces it to the client with
+//! `AgentOpen`, relays the bytes in `AgentData` messages, and sends
+//! `AgentClose` when the process hangs up. The client relays each channel to
+//! its local ssh-agent, so private keys never leave the client.
+
+use std::collections::HashMap;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use protocol::messages::{AgentClose, AgentData, AgentOpen, Message};
+use protocol::DeviceId;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Device capability required to request agent forwarding.
+pub const AGENT_FORWARDING_CAPABILITY: &str = "agent-forwarding";
+
+/// Name of the socket inside each session's agent directory.
+const SOCKET_NAME: &str = "agent.sock";
+
+/// Size of the buffer used to read from agent connections.
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Maximum number of concurrent agent connections per session.
+const MAX_CHANNELS_PER_SESSION: usize = 16;
+
+/// A message for a device, produced outside of a request/response exchange.
+pub type AgentMessage = (DeviceId, Message);
+
+/// Errors from relaying agent data.
+#[derive(Debug, Error)]
+pub enum AgentError {
+    /// The session does not forward an agent for this device.
+    #[error("agent forwarding is not active for session {0}")]
+    UnknownSession(String),
+
+    /// The channel is not open.
+    #[error("agent channel {0} is not open")]
+    UnknownChannel(u32),
+
+    /// The agent socket could not be created.
+    #[error("agent socket error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Open channels of a session, keyed by channel ID.
+type Channels = Arc<Mutex<HashMap<u32, mpsc::UnboundedSender<Vec<u8>>>>>;
+
+/// An agent socket that is bound but not yet attached to a session.
+///
+/// Dropping it removes the socket, so a failed session creation leaves
+/// nothing behind.
+#[derive(Debug)]
+pub struct PreparedAgentSocket {
+    dir: PathBuf,
+    path: PathBuf,
+    listener: Option<UnixListener>,
+}
+
+impl PreparedAgentSocket {
+    /// Returns the socket path to use as `SSH_AUTH_SOCK`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PreparedAgentSocket {
+    fn drop(&mut self) {
+        if self.listener.is_some() {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+/// Agent forwarding state of one session.
+struct ForwardedSession {
+    device_id: DeviceId,
+    dir: PathBuf,
+    channels: Channels,
+    accept_task: JoinHandle<()>,
+}
+
+impl ForwardedSession {
+    fn shutdown(self) {
+        self.accept_task.abort();
+        // Dropping the senders ends the channel tasks
+        self.channels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Creates per-session agent sockets and relays their connections.
+pub struct AgentForwarder {
+    socket_dir: PathBuf,
+    outbound: mpsc::UnboundedSender<AgentMessage>,
+    sessions: Mutex<HashMap<String, ForwardedSession>>,
+}
+
+impl AgentForwarder {
+    /// Creates a forwarder that places sockets under `socket_dir` and sends
+    /// messages for clients to `outbound`.
+    pub fn new(
+        socket_dir: impl Into<PathBuf>,
+        outbound: mpsc::UnboundedSender<AgentMessage>,
+    ) -> Self {
+        Self {
+            socket_dir: socket_dir.into(),
+            outbound,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Binds a new agent socket in a private directory.
+    pub fn prepare(&self) -> Result<PreparedAgentSocket, AgentError> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true).mode(0o700);
+        builder.create(&self.socket_dir)?;
+
+        let dir = self
+            .socket_dir
+            .join(uuid::Uuid::new_v4().simple().to_string());
+        builder.recursive(false).create(&dir)?;
+
+        let path = dir.join(SOCKET_NAME);
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&dir);
+                return Err(e.into());
+            }
+        };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+
+        Ok(PreparedAgentSocket {
+            dir,
+            path,
+            listener: Some(listener),
+        })
+    }
+
+    /// Starts accepting connections on a prepared socket for a session.
+    pub fn start(&self, session_id: &str, device_id: DeviceId, mut socket: PreparedAgentSocket) {
+        let Some(listener) = socket.listener.take() else {
+            return;
+        };
+
+        let channels = Channels::default();
+        let accept_task = tokio::spawn(accept_loop(
+            listener,
+            session_id.to_string(),
+            device_id,
+            self.outbound.clone(),
+            Arc::clone(&channels),
+        ));
+
+        info!(
+            target: "audit",
+            session_id = %session_id,
+            device_id = %device_id,
+            "SSH agent forwarding enabled"
+        );
+
+        let previous = self.sessions().insert(
+            session_id.to_string(),
+            ForwardedSession {
+                device_id,
+                dir: socket.dir.clone(),
+                channels,
+                accept_task,
+            },
+        );
+        if let Some(previous) = previous {
+            previous.shutdown();
+        }
+    }
+
+    /// Returns true if agent forwarding is active for the session.
+    pub fn is_forwarding(&self, session_id: &str) -> bool {
+        self.sessions().contains_key(session_id)
+    }
+
+    /// Writes data from the client's agent to a channel.
+    pub fn write(&self, device_id: &DeviceId, data: AgentData) -> Result<(), AgentError> {
+        let channels = self.channels(device_id, &data.session_id)?;
+        let channels = channels.lock().unwrap_or_else(|e| e.into_inner());
+        let sender = channels
+            .get(&data.channel_id)
+            .ok_or(AgentError::UnknownChannel(data.channel_id))?;
+        sender
+            .send(data.data)
+            .map_err(|_| AgentError::UnknownChannel(data.channel_id))
+    }
+
+    /// Closes a channel at the client's request.
+    pub fn close_channel(&self, device_id: &DeviceId, close: AgentClose) -> Result<(), AgentError> {
+        let channels = self.channels(device_id, &close.session_id)?;
+        let removed = channels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&close.channel_id);
+        removed
+            .map(|_| ())
+            .ok_or(AgentError::UnknownChannel(close.channel_id))
+    }
+
+    /// Stops forwarding for a session and removes its socket.
+    pub fn close_session(&self, session_id: &str) {
+        if let Some(session) = self.sessions().remove(session_id) {
+            debug!(session_id = %session_id, "Stopping SSH agent forwarding");
+            session.shutdown();
+        }
+    }
+
+    /// Stops forwarding for every session of a device, e.g. on disconnect.
+    pub fn close_device(&self, device_id: &DeviceId) {
+        let mut sessions = self.sessions();
+        let ids: Vec<String> = sessions
+            .iter()
+            .filter(|(_, s)| s.device_id == *device_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ids {
+            if let Some(session) = sessions.remove(&id) {
+                session.shutdown();
+            }
+        }
+    }
+
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, ForwardedSession>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn channels(&self, device_id: &DeviceId, session_id: &str) -> Result<Channels, AgentError> {
+        self.sessions()
+            .get(session_id)
+            .filter(|s| s.device_id == *device_id)
+            .map(|s| Arc::clone(&s.channels))
+            .ok_or_else(|| AgentError::UnknownSession(session_id.to_string()))
+    }
+}
+
+impl Drop for AgentForwarder {
+    fn drop(&mut self) {
+        for (_, session) in self.sessions().drain() {
+            session.shutdown();
+        }
+    }
+}
+
+/// Accepts connections to a session's agent socket.
+async fn accept_loop(
+    listener: UnixListener,
+    session_id: String,
+    device_id: DeviceId,
+    outbound: mpsc::UnboundedSender<AgentMessage>,
+    channels: Channels,
+) {
+    let mut next_channel_id: u32 = 1;
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!(session_id = %session_id, error = %e, "Agent socket accept failed");
+                break;
+            }
+        };
+
+        let channel_id = next_channel_id;
+        next_channel_id = next_channel_id.wrapping_add(1).max(1);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        {
+            let mut open = channels.lock().unwrap_or_else(|e| e.into_inner());
+            if open.len() >= MAX_CHANNELS_PER_SESSION {
+                warn!(session_id = %session_id, "Too many agent connections, refusing");
+                continue;
+            }
+            open.insert(channel_id, tx);
+        }
+
+        debug!(session_id = %session_id, channel_id, "Agent connection opened");
+        let open = Message::AgentOpen(AgentOpen {
+            session_id: session_id.clone(),
+            channel_id,
+        });
+        if outbound.send((device_id, open)).is_err() {
+            break;
+        }
+
+        tokio::spawn(relay_channel(
+            stream,
+            session_id.clone(),
+            channel_id,
+            device_id,
+            rx,
+            outbound.clone(),
+            Arc::clone(&channels),
+        ));
+    }
+}
+
+/// Relays one agent connection until either side closes it.
+async fn relay_channel(
+    stream: UnixStream,
+    session_id: String,
+    channel_id: u32,
+    device_id: DeviceId,
+    mut from_client: mpsc::UnboundedReceiver<Vec<u8>>,
+    outbound: mpsc::UnboundedSender<AgentMessage>,
+    channels: Channels,
+) {
+    let (mut reader, mut writer) = stream.into_split();
+    let mut buf = vec![0u8; READ_BUFFER_SIZE];
+    let mut closed_by_client = false;
+
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let data = Message::AgentData(AgentData {
+                        session_id: session_id.clone(),
+                        channel_id,
+                        data: buf[..n].to_vec(),
+                    });
+                    if outbound.send((device_id, data)).is_err() {
+                        break;
+                    }
+                }
+            },
+            data = from_client.recv() => match data {
+                Some(data) => {
+                    if writer.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                None => {
+                    closed_by_client = true;
+                    break;
+                }
+            },
+        }
+    }
+
+    channels
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&channel_id);
+    if !closed_by_client {
+        let _ = outbound.send((
+            device_id,
+            Message::AgentClose(AgentClose {
+                session_id,
+                channel_id,
+            }),
+        ));
+    }
+    debug!(channel_id, "Agent connection closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([3u8; 16])
+    }
+
+    #[tokio::test]
+    async fn test_prepared_socket_is_removed_on_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let forwarder = AgentForwarder::new(temp_dir.path().join("agent"), tx);
+
+        let socket = forwarder.prepare().unwrap();
+        let path = socket.path().to_path_buf();
+        assert!(path.exists());
+        let mode = std::fs::metadata(path.parent().unwrap())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_relays_agent_connection() {
+        let temp_dir = TempDir::new().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let forwarder = AgentForwarder::new(temp_dir.path().join("agent"), tx);
+
+        let socket = forwarder.prepare().unwrap();
+        let path = socket.path().to_path_buf();
+        forwarder.start("sess-1", device(), socket);
+        assert!(forwarder.is_forwarding("sess-1"));
+
+        // A process in the session sends a request to the agent
+        let mut client = UnixStream::connect(&path).await.unwrap();
+        let (owner, open) = rx.recv().await.unwrap();
+        assert_eq!(owner, device());
+        let channel_id = match open {
+            Message::AgentOpen(open) => open.channel_id,
+            other => panic!("Expected AgentOpen, got {:?}", other),
+        };
+
+        client.write_all(&[0, 0, 0, 1, 11]).await.unwrap();
+        match rx.recv().await.unwrap().1 {
+            Message::AgentData(data) => assert_eq!(data.data, vec![0, 0, 0, 1, 11]),
+            other => panic!("Expected AgentData, got {:?}", other),
+        }
+
+        // The client's agent answers
+        forwarder
+            .write(
+                &device(),
+                AgentData {
+                    session_id: "sess-1".to_string(),
+                    channel_id,
+                    data: vec![0, 0, 0, 1, 12],
+                },
+            )
+            .unwrap();
+        let mut reply = [0u8; 5];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [0, 0, 0, 1, 12]);
+
+        // Another device cannot write to the channel
+        let other = DeviceId::from_bytes([4u8; 16]);
+        assert!(matches!(
+            forwarder.write(
+                &other,
+                AgentData {
+                    session_id: "sess-1".to_string(),
+                    channel_id,
+                    data: vec![],
+                },
+            ),
+            Err(AgentError::UnknownSession(_))
+        ));
+
+        // The process hanging up closes the channel
+        drop(client);
+        match rx.recv().await.unwrap().1 {
+            Message::AgentClose(close) => assert_eq!(close.channel_id, channel_id),
+            other => panic!("Expected AgentClose, got {:?}", other),
+        }
+
+        forwarder.close_session("sess-1");
+        assert!(!forwarder.is_forwarding("sess-1"));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,254 @@
+//! Environment variables supplied by clients.
+//!
+//! A `SessionCreate` may carry the client's timezone, locale and terminal
+//! type, which become `TZ`, `LANG` and `TERM` in the new shell so `date` and
+//! log timestamps read the way the user expects. These, the request's `env`
+//! and later `UpdateSessionEnv` changes all pass through the
+//! `session.env_allowlist`; variables from session templates do not, since
+//! the operator wrote them.
+
+use protocol::messages::SessionCreate;
+use tracing::warn;
+
+/// Maximum length of a timezone, locale or terminal name.
+const MAX_HINT_LEN: usize = 64;
+
+/// Names of variables clients may set in sessions.
+///
+/// Entries are variable names, or prefixes ending in `*`; `*` alone allows
+/// every variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvAllowlist {
+    patterns: Vec<String>,
+}
+
+impl Default for EnvAllowlist {
+    /// Allows every variable.
+    fn default() -> Self {
+        Self::new(vec!["*".to_string()])
+    }
+}
+
+impl EnvAllowlist {
+    /// Creates an allowlist from names and `*`-terminated prefixes.
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self { patterns }
+    }
+
+    /// Returns true if clients may set `name`.
+    pub fn allows(&self, name: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+    }
+
+    /// Removes the variables clients may not set, logging each one dropped.
+    pub fn filter(&self, env: &mut Vec<(String, String)>) {
+        env.retain(|(name, _)| {
+            let allowed = self.allows(name);
+            if !allowed {
+                warn!(variable = %name, "Dropping client variable not in env_allowlist");
+            }
+            allowed
+        });
+    }
+}
+
+/// Returns true if `pattern` is a valid allowlist entry.
+pub fn is_valid_pattern(pattern: &str) -> bool {
+    let name = pattern.strip_suffix('*').unwrap_or(pattern);
+    if name.is_empty() {
+        return pattern == "*";
+    }
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Adds `TZ`, `LANG` and `TERM` to the request's `env` from its timezone,
+/// locale and terminal hints.
+///
+/// Variables already in `env` are left alone, and hints that do not look
+/// like a timezone, locale or terminal name are dropped.
+pub fn apply_client_hints(req: &mut SessionCreate) {
+    let hints = [
+        ("TZ", req.timezone.take().and_then(timezone_value)),
+        ("LANG", req.locale.take().and_then(locale_value)),
+        ("TERM", req.term.take().and_then(term_value)),
+    ];
+    for (name, value) in hints {
+        if let Some(value) = value {
+            if !req.env.iter().any(|(key, _)| key == name) {
+                req.env.push((name.to_string(), value));
+            }
+        }
+    }
+}
+
+/// Validates an IANA timezone name such as `Europe/Paris` or `UTC`.
+fn timezone_value(timezone: String) -> Option<String> {
+    let valid = !timezone.is_empty()
+        && timezone.len() <= MAX_HINT_LEN
+        && !timezone.starts_with('/')
+        && !timezone
+            .split('/')
+            .any(|part| part.is_empty() || part == "..")
+        && timezone
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '+' | '-'));
+    if !valid {
+        warn!(timezone = %timezone, "Ignoring invalid client timezone");
+        return None;
+    }
+    Some(timezone)
+}
+
+/// Converts a locale to a POSIX locale name.
+///
+/// BCP 47 tags such as `fr-FR` become `fr_FR.UTF-8`; names that already
+/// carry a codeset, and `C` or `POSIX`, are kept as they are.
+fn locale_value(locale: String) -> Option<String> {
+    let valid = !locale.is_empty()
+        && locale.len() <= MAX_HINT_LEN
+        && locale
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '@'));
+    let language = locale
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or_default();
+    if !valid || language.is_empty() || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        warn!(locale = %locale, "Ignoring invalid client locale");
+        return None;
+    }
+    if locale == "C" || locale == "POSIX" || locale.contains('.') {
+        return Some(locale);
+    }
+
+    // fr-FR, fr_FR or fr; BCP 47 script and variant subtags are dropped
+    let mut parts = locale.split(['-', '_']);
+    let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+    let region =
+        parts.find(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic()));
+    Some(match region {
+        Some(region) => format!("{}_{}.UTF-8", language, region.to_ascii_uppercase()),
+        None => format!("{}.UTF-8", language),
+    })
+}
+
+/// Validates a terminal type such as `xterm-256color`.
+fn term_value(term: String) -> Option<String> {
+    let valid = !term.is_empty()
+        && term.len() <= MAX_HINT_LEN
+        && term
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '+' | '-'));
+    if !valid {
+        warn!(term = %term, "Ignoring invalid client terminal type");
+        return None;
+    }
+    Some(term)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(patterns: &[&str]) -> EnvAllowlist {
+        EnvAllowlist::new(patterns.iter().map(|p| p.to_string()).collect())
+    }
+
+    #[test]
+    fn test_allowlist_matching() {
+        assert!(EnvAllowlist::default().allows("LD_PRELOAD"));
+
+        let list = allowlist(&["TZ", "LC_*"]);
+        assert!(list.allows("TZ"));
+        assert!(list.allows("LC_TIME"));
+        assert!(!list.allows("TZDIR"));
+        assert!(!list.allows("LANG"));
+        assert!(!allowlist(&[]).allows("TZ"));
+    }
+
+    #[test]
+    fn test_allowlist_filter() {
+        let mut env = vec![
+            ("TZ".to_string(), "UTC".to_string()),
+            ("LD_PRELOAD".to_string(), "/tmp/evil.so".to_string()),
+        ];
+        allowlist(&["TZ", "LANG"]).filter(&mut env);
+        assert_eq!(env, vec![("TZ".to_string(), "UTC".to_string())]);
+    }
+
+    #[test]
+    fn test_valid_patterns() {
+        assert!(is_valid_pattern("*"));
+        assert!(is_valid_pattern("TZ"));
+        assert!(is_valid_pattern("LC_*"));
+        assert!(!is_valid_pattern(""));
+        assert!(!is_valid_pattern("**"));
+        assert!(!is_valid_pattern("L*C"));
+        assert!(!is_valid_pattern("1TZ"));
+    }
+
+    #[test]
+    fn test_apply_client_hints() {
+        let mut req = SessionCreate {
+            timezone: Some("America/New_York".to_string()),
+            locale: Some("fr-FR".to_string()),
+            term: Some("xterm-256color".to_string()),
+            ..Default::default()
+        };
+        apply_client_hints(&mut req);
+        assert_eq!(
+            req.env,
+            vec![
+                ("TZ".to_string(), "America/New_York".to_string()),
+                ("LANG".to_string(), "fr_FR.UTF-8".to_string()),
+                ("TERM".to_string(), "xterm-256color".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explicit_env_wins_over_hints() {
+        let mut req = SessionCreate {
+            env: vec![("TZ".to_string(), "UTC".to_string())],
+            timezone: Some("Europe/Paris".to_string()),
+            ..Default::default()
+        };
+        apply_client_hints(&mut req);
+        assert_eq!(req.env, vec![("TZ".to_string(), "UTC".to_string())]);
+    }
+
+    #[test]
+    fn test_invalid_hints_are_dropped() {
+        let mut req = SessionCreate {
+            timezone: Some("../../etc/passwd".to_string()),
+            locale: Some("en_US; rm -rf /".to_string()),
+            term: Some("xterm\n".to_string()),
+            ..Default::default()
+        };
+        apply_client_hints(&mut req);
+        assert!(req.env.is_empty());
+    }
+
+    #[test]
+    fn test_locale_conversion() {
+        let convert = |locale: &str| locale_value(locale.to_string());
+        assert_eq!(convert("en-US").as_deref(), Some("en_US.UTF-8"));
+        assert_eq!(convert("de").as_deref(), Some("de.UTF-8"));
+        assert_eq!(convert("zh-Hans-CN").as_deref(), Some("zh_CN.UTF-8"));
+        assert_eq!(
+            convert("pt_BR.ISO-8859-1").as_deref(),
+            Some("pt_BR.ISO-8859-1")
+        );
+        assert_eq!(convert("C").as_deref(), Some("C"));
+        assert_eq!(convert(""), None);
+    }
+}
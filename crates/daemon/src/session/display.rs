@@ -0,0 +1,594 @@
+//! X11 and Wayland application forwarding into sessions (experimental).
+//!
+//! When a session is created with `forward_display`, the daemon listens on a
+//! UNIX socket that GUI applications in the session can reach:
+//!
+//! - For X11, the socket is `X<n>` in the X11 socket directory
+//!   (`/tmp/.X11-unix`), for the first free display number from
+//!   `session.x11_display_offset`, and `DISPLAY` is set to `:<n>`.
+//! - For Wayland, the socket is in a private directory and its path is set
+//!   in `REMOSHELL_WAYPIPE_SOCKET`, for
+//!   `waypipe --socket "$REMOSHELL_WAYPIPE_SOCKET" server -- <app>`.
+//!
+//! As with agent forwarding, every connection becomes a channel: the daemon
+//! announces it with `DisplayOpen`, relays the bytes in `DisplayData`
+//! messages, and sends `DisplayClose` when the application hangs up. The
+//! client relays each channel to its local X server or `waypipe client`.
+
+use std::collections::HashMap;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use protocol::messages::{DisplayClose, DisplayData, DisplayOpen, DisplayProtocol, Message};
+use protocol::DeviceId;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Device capability required to request display forwarding.
+pub const DISPLAY_FORWARDING_CAPABILITY: &str = "display-forwarding";
+
+/// Variable naming the waypipe socket of a session forwarding Wayland.
+pub const WAYPIPE_SOCKET_VAR: &str = "REMOSHELL_WAYPIPE_SOCKET";
+
+/// Directory X11 clients look for display sockets in.
+pub const X11_SOCKET_DIR: &str = "/tmp/.X11-unix";
+
+/// Name of the socket inside each session's Wayland directory.
+const WAYPIPE_SOCKET_NAME: &str = "waypipe.sock";
+
+/// Number of display numbers tried after the offset before giving up.
+const MAX_X11_DISPLAYS: u16 = 1000;
+
+/// Size of the buffer used to read from display connections.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Maximum number of concurrent display connections per session.
+const MAX_CHANNELS_PER_SESSION: usize = 64;
+
+/// A message for a device, produced outside of a request/response exchange.
+pub type DisplayMessage = (DeviceId, Message);
+
+/// Errors from forwarding displays.
+#[derive(Debug, Error)]
+pub enum DisplayError {
+    /// The session does not forward a display for this device.
+    #[error("display forwarding is not active for session {0}")]
+    UnknownSession(String),
+
+    /// The channel is not open.
+    #[error("display channel {0} is not open")]
+    UnknownChannel(u32),
+
+    /// Every X11 display number in range is taken.
+    #[error("no free X11 display number from {0}")]
+    NoFreeDisplay(u16),
+
+    /// The display socket could not be created.
+    #[error("display socket error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Open channels of a session, keyed by channel ID.
+type Channels = Arc<Mutex<HashMap<u32, mpsc::UnboundedSender<Vec<u8>>>>>;
+
+/// A display socket that is bound but not yet attached to a session.
+///
+/// Dropping it removes the socket, so a failed session creation leaves
+/// nothing behind.
+#[derive(Debug)]
+pub struct PreparedDisplaySocket {
+    protocol: DisplayProtocol,
+    /// Socket file (X11) or private directory (Wayland) to remove.
+    cleanup: PathBuf,
+    env: (&'static str, String),
+    listener: Option<UnixListener>,
+}
+
+impl PreparedDisplaySocket {
+    /// Returns the variable, and its value, that points applications in the
+    /// session at the socket.
+    pub fn env(&self) -> (&'static str, &str) {
+        (self.env.0, &self.env.1)
+    }
+}
+
+impl Drop for PreparedDisplaySocket {
+    fn drop(&mut self) {
+        if self.listener.is_some() {
+            remove(&self.cleanup);
+        }
+    }
+}
+
+/// Display forwarding state of one session.
+struct ForwardedDisplay {
+    device_id: DeviceId,
+    cleanup: PathBuf,
+    channels: Channels,
+    accept_task: JoinHandle<()>,
+}
+
+impl ForwardedDisplay {
+    fn shutdown(self) {
+        self.accept_task.abort();
+        // Dropping the senders ends the channel tasks
+        self.channels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        remove(&self.cleanup);
+    }
+}
+
+/// Creates per-session display sockets and relays their connections.
+pub struct DisplayForwarder {
+    socket_dir: PathBuf,
+    x11_dir: PathBuf,
+    display_offset: u16,
+    outbound: mpsc::UnboundedSender<DisplayMessage>,
+    sessions: Mutex<HashMap<String, ForwardedDisplay>>,
+}
+
+impl DisplayForwarder {
+    /// Creates a forwarder that places Wayland sockets under `socket_dir`,
+    /// X11 sockets in [`X11_SOCKET_DIR`] from display `display_offset`, and
+    /// sends messages for clients to `outbound`.
+    pub fn new(
+        socket_dir: impl Into<PathBuf>,
+        display_offset: u16,
+        outbound: mpsc::UnboundedSender<DisplayMessage>,
+    ) -> Self {
+        Self {
+            socket_dir: socket_dir.into(),
+            x11_dir: PathBuf::from(X11_SOCKET_DIR),
+            display_offset,
+            outbound,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Places X11 sockets in `dir` instead of [`X11_SOCKET_DIR`].
+    pub fn with_x11_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.x11_dir = dir.into();
+        self
+    }
+
+    /// Binds a new display socket for `protocol`.
+    pub fn prepare(
+        &self,
+        protocol: DisplayProtocol,
+    ) -> Result<PreparedDisplaySocket, DisplayError> {
+        match protocol {
+            DisplayProtocol::X11 => self.prepare_x11(),
+            DisplayProtocol::Wayland => self.prepare_wayland(),
+        }
+    }
+
+    fn prepare_x11(&self) -> Result<PreparedDisplaySocket, DisplayError> {
+        if !self.x11_dir.exists() {
+            // Shared by every user's X servers, like /tmp
+            std::fs::create_dir_all(&self.x11_dir)?;
+            std::fs::set_permissions(&self.x11_dir, std::fs::Permissions::from_mode(0o1777))?;
+        }
+
+        let last = self.display_offset.saturating_add(MAX_X11_DISPLAYS);
+        for display in self.display_offset..last {
+            let path = self.x11_dir.join(format!("X{}", display));
+            if path.exists() {
+                continue;
+            }
+            let listener = match UnixListener::bind(&path) {
+                Ok(listener) => listener,
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if let Err(e) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            {
+                let _ = std::fs::remove_file(&path);
+                return Err(e.into());
+            }
+            return Ok(PreparedDisplaySocket {
+                protocol: DisplayProtocol::X11,
+                cleanup: path,
+                env: ("DISPLAY", format!(":{}", display)),
+                listener: Some(listener),
+            });
+        }
+        Err(DisplayError::NoFreeDisplay(self.display_offset))
+    }
+
+    fn prepare_wayland(&self) -> Result<PreparedDisplaySocket, DisplayError> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true).mode(0o700);
+        builder.create(&self.socket_dir)?;
+
+        let dir = self
+            .socket_dir
+            .join(uuid::Uuid::new_v4().simple().to_string());
+        builder.recursive(false).create(&dir)?;
+
+        let path = dir.join(WAYPIPE_SOCKET_NAME);
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&dir);
+                return Err(e.into());
+            }
+        };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+
+        Ok(PreparedDisplaySocket {
+            protocol: DisplayProtocol::Wayland,
+            cleanup: dir,
+            env: (WAYPIPE_SOCKET_VAR, path.to_string_lossy().into_owned()),
+            listener: Some(listener),
+        })
+    }
+
+    /// Starts accepting connections on a prepared socket for a session.
+    pub fn start(&self, session_id: &str, device_id: DeviceId, mut socket: PreparedDisplaySocket) {
+        let Some(listener) = socket.listener.take() else {
+            return;
+        };
+
+        let channels = Channels::default();
+        let accept_task = tokio::spawn(accept_loop(
+            listener,
+            session_id.to_string(),
+            device_id,
+            self.outbound.clone(),
+            Arc::clone(&channels),
+        ));
+
+        info!(
+            target: "audit",
+            session_id = %session_id,
+            device_id = %device_id,
+            protocol = ?socket.protocol,
+            display = %socket.env.1,
+            "Display forwarding enabled"
+        );
+
+        let previous = self.sessions().insert(
+            session_id.to_string(),
+            ForwardedDisplay {
+                device_id,
+                cleanup: socket.cleanup.clone(),
+                channels,
+                accept_task,
+            },
+        );
+        if let Some(previous) = previous {
+            previous.shutdown();
+        }
+    }
+
+    /// Returns true if display forwarding is active for the session.
+    pub fn is_forwarding(&self, session_id: &str) -> bool {
+        self.sessions().contains_key(session_id)
+    }
+
+    /// Writes data from the client's display to a channel.
+    pub fn write(&self, device_id: &DeviceId, data: DisplayData) -> Result<(), DisplayError> {
+        let channels = self.channels(device_id, &data.session_id)?;
+        let channels = channels.lock().unwrap_or_else(|e| e.into_inner());
+        let sender = channels
+            .get(&data.channel_id)
+            .ok_or(DisplayError::UnknownChannel(data.channel_id))?;
+        sender
+            .send(data.data)
+            .map_err(|_| DisplayError::UnknownChannel(data.channel_id))
+    }
+
+    /// Closes a channel at the client's request.
+    pub fn close_channel(
+        &self,
+        device_id: &DeviceId,
+        close: DisplayClose,
+    ) -> Result<(), DisplayError> {
+        let channels = self.channels(device_id, &close.session_id)?;
+        let removed = channels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&close.channel_id);
+        removed
+            .map(|_| ())
+            .ok_or(DisplayError::UnknownChannel(close.channel_id))
+    }
+
+    /// Stops forwarding for a session and removes its socket.
+    pub fn close_session(&self, session_id: &str) {
+        if let Some(session) = self.sessions().remove(session_id) {
+            debug!(session_id = %session_id, "Stopping display forwarding");
+            session.shutdown();
+        }
+    }
+
+    /// Stops forwarding for every session of a device, e.g. on disconnect.
+    pub fn close_device(&self, device_id: &DeviceId) {
+        let mut sessions = self.sessions();
+        let ids: Vec<String> = sessions
+            .iter()
+            .filter(|(_, s)| s.device_id == *device_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ids {
+            if let Some(session) = sessions.remove(&id) {
+                session.shutdown();
+            }
+        }
+    }
+
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, ForwardedDisplay>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn channels(&self, device_id: &DeviceId, session_id: &str) -> Result<Channels, DisplayError> {
+        self.sessions()
+            .get(session_id)
+            .filter(|s| s.device_id == *device_id)
+            .map(|s| Arc::clone(&s.channels))
+            .ok_or_else(|| DisplayError::UnknownSession(session_id.to_string()))
+    }
+}
+
+impl Drop for DisplayForwarder {
+    fn drop(&mut self) {
+        for (_, session) in self.sessions().drain() {
+            session.shutdown();
+        }
+    }
+}
+
+/// Removes a socket file or a private socket directory.
+fn remove(path: &Path) {
+    if path.is_dir() {
+        let _ = std::fs::remove_dir_all(path);
+    } else {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Accepts connections to a session's display socket.
+async fn accept_loop(
+    listener: UnixListener,
+    session_id: String,
+    device_id: DeviceId,
+    outbound: mpsc::UnboundedSender<DisplayMessage>,
+    channels: Channels,
+) {
+    let mut next_channel_id: u32 = 1;
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!(session_id = %session_id, error = %e, "Display socket accept failed");
+                break;
+            }
+        };
+
+        let channel_id = next_channel_id;
+        next_channel_id = next_channel_id.wrapping_add(1).max(1);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        {
+            let mut open = channels.lock().unwrap_or_else(|e| e.into_inner());
+            if open.len() >= MAX_CHANNELS_PER_SESSION {
+                warn!(session_id = %session_id, "Too many display connections, refusing");
+                continue;
+            }
+            open.insert(channel_id, tx);
+        }
+
+        debug!(session_id = %session_id, channel_id, "Display connection opened");
+        let open = Message::DisplayOpen(DisplayOpen {
+            session_id: session_id.clone(),
+            channel_id,
+        });
+        if outbound.send((device_id, open)).is_err() {
+            break;
+        }
+
+        tokio::spawn(relay_channel(
+            stream,
+            session_id.clone(),
+            channel_id,
+            device_id,
+            rx,
+            outbound.clone(),
+            Arc::clone(&channels),
+        ));
+    }
+}
+
+/// Relays one display connection until either side closes it.
+async fn relay_channel(
+    stream: UnixStream,
+    session_id: String,
+    channel_id: u32,
+    device_id: DeviceId,
+    mut from_client: mpsc::UnboundedReceiver<Vec<u8>>,
+    outbound: mpsc::UnboundedSender<DisplayMessage>,
+    channels: Channels,
+) {
+    let (mut reader, mut writer) = stream.into_split();
+    let mut buf = vec![0u8; READ_BUFFER_SIZE];
+    let mut closed_by_client = false;
+
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let data = Message::DisplayData(DisplayData {
+                        session_id: session_id.clone(),
+                        channel_id,
+                        data: buf[..n].to_vec(),
+                    });
+                    if outbound.send((device_id, data)).is_err() {
+                        break;
+                    }
+                }
+            },
+            data = from_client.recv() => match data {
+                Some(data) => {
+                    if writer.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                None => {
+                    closed_by_client = true;
+                    break;
+                }
+            },
+        }
+    }
+
+    channels
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&channel_id);
+    if !closed_by_client {
+        let _ = outbound.send((
+            device_id,
+            Message::DisplayClose(DisplayClose {
+                session_id,
+                channel_id,
+            }),
+        ));
+    }
+    debug!(channel_id, "Display connection closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([5u8; 16])
+    }
+
+    fn forwarder(
+        temp_dir: &TempDir,
+        tx: mpsc::UnboundedSender<DisplayMessage>,
+    ) -> DisplayForwarder {
+        DisplayForwarder::new(temp_dir.path().join("display"), 10, tx)
+            .with_x11_dir(temp_dir.path().join(".X11-unix"))
+    }
+
+    #[tokio::test]
+    async fn test_x11_sockets_take_free_display_numbers() {
+        let temp_dir = TempDir::new().unwrap();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let forwarder = forwarder(&temp_dir, tx);
+
+        let first = forwarder.prepare(DisplayProtocol::X11).unwrap();
+        assert_eq!(first.env(), ("DISPLAY", ":10"));
+        let path = temp_dir.path().join(".X11-unix/X10");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // An X server already owns :11
+        std::fs::write(temp_dir.path().join(".X11-unix/X11"), b"").unwrap();
+        let second = forwarder.prepare(DisplayProtocol::X11).unwrap();
+        assert_eq!(second.env(), ("DISPLAY", ":12"));
+
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_prepared_wayland_socket_is_removed_on_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let forwarder = forwarder(&temp_dir, tx);
+
+        let socket = forwarder.prepare(DisplayProtocol::Wayland).unwrap();
+        let (name, value) = socket.env();
+        assert_eq!(name, WAYPIPE_SOCKET_VAR);
+        let path = PathBuf::from(value);
+        assert!(path.exists());
+        let mode = std::fs::metadata(path.parent().unwrap())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_relays_display_connection() {
+        let temp_dir = TempDir::new().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let forwarder = forwarder(&temp_dir, tx);
+
+        let socket = forwarder.prepare(DisplayProtocol::X11).unwrap();
+        forwarder.start("sess-1", device(), socket);
+        assert!(forwarder.is_forwarding("sess-1"));
+        let path = temp_dir.path().join(".X11-unix/X10");
+
+        // An application in the session opens the display
+        let mut app = UnixStream::connect(&path).await.unwrap();
+        let (owner, open) = rx.recv().await.unwrap();
+        assert_eq!(owner, device());
+        let channel_id = match open {
+            Message::DisplayOpen(open) => open.channel_id,
+            other => panic!("Expected DisplayOpen, got {:?}", other),
+        };
+
+        app.write_all(b"l\0\x0b\0").await.unwrap();
+        match rx.recv().await.unwrap().1 {
+            Message::DisplayData(data) => assert_eq!(data.data, b"l\0\x0b\0"),
+            other => panic!("Expected DisplayData, got {:?}", other),
+        }
+
+        // The client's X server answers
+        forwarder
+            .write(
+                &device(),
+                DisplayData {
+                    session_id: "sess-1".to_string(),
+                    channel_id,
+                    data: vec![1, 0, 11, 0],
+                },
+            )
+            .unwrap();
+        let mut reply = [0u8; 4];
+        app.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [1, 0, 11, 0]);
+
+        // Another device cannot write to the channel
+        let other = DeviceId::from_bytes([6u8; 16]);
+        assert!(matches!(
+            forwarder.write(
+                &other,
+                DisplayData {
+                    session_id: "sess-1".to_string(),
+                    channel_id,
+                    data: vec![],
+                },
+            ),
+            Err(DisplayError::UnknownSession(_))
+        ));
+
+        // The application exiting closes the channel
+        drop(app);
+        match rx.recv().await.unwrap().1 {
+            Message::DisplayClose(close) => assert_eq!(close.channel_id, channel_id),
+            other => panic!("Expected DisplayClose, got {:?}", other),
+        }
+
+        forwarder.close_device(&device());
+        assert!(!forwarder.is_forwarding("sess-1"));
+        assert!(!path.exists());
+    }
+}
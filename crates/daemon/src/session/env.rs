@@ -0,0 +1,437 @@
+//! Environment variables exported into running sessions.
+//!
+//! A process's environment cannot be changed from outside once it runs, so
+//! the daemon gives each session a private file, named by
+//! `REMOSHELL_ENV_FILE` in the session's environment. `UpdateSessionEnv`
+//! rewrites the file with `export` and `unset` lines, and a prompt hook in the
+//! shell (see [`shell_hook`]) sources it before each prompt. Commands started
+//! after an update thus see the client's `DISPLAY`, `SSH_AUTH_SOCK` or
+//! timezone, while the shell itself needs no restart.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use protocol::DeviceId;
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// Variable naming the session's environment file.
+pub const ENV_FILE_VAR: &str = "REMOSHELL_ENV_FILE";
+
+/// Maximum number of variables a session's file may set or unset.
+pub const MAX_VARIABLES: usize = 64;
+
+/// Maximum length of a variable name.
+const MAX_NAME_LEN: usize = 256;
+
+/// Maximum length of a variable value.
+const MAX_VALUE_LEN: usize = 16 * 1024;
+
+/// First line of every environment file.
+const HEADER: &str =
+    "# Written by remoshell-daemon. Sourced by the shell hook before each prompt.\n";
+
+/// Errors from updating a session's environment.
+#[derive(Debug, Error)]
+pub enum EnvError {
+    /// The session has no environment file.
+    #[error("environment updates are not active for session {0}")]
+    UnknownSession(String),
+
+    /// A variable name is not a valid shell identifier.
+    #[error("invalid variable name: {0:?}")]
+    InvalidName(String),
+
+    /// A variable value is too long or contains a NUL byte.
+    #[error("invalid value for {0}")]
+    InvalidValue(String),
+
+    /// The variable is managed by the daemon.
+    #[error("{0} cannot be changed")]
+    Reserved(String),
+
+    /// The update would leave too many variables in the file.
+    #[error("too many variables: at most {MAX_VARIABLES} can be exported")]
+    TooMany,
+
+    /// The environment file could not be written.
+    #[error("environment file error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// An environment file that is created but not yet attached to a session.
+///
+/// Dropping it removes the file, so a failed session creation leaves nothing
+/// behind.
+#[derive(Debug)]
+pub struct PreparedEnvFile {
+    path: PathBuf,
+    attached: bool,
+}
+
+impl PreparedEnvFile {
+    /// Returns the file path to use as `REMOSHELL_ENV_FILE`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PreparedEnvFile {
+    fn drop(&mut self) {
+        if !self.attached {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The exported variables of one session.
+struct ExportedEnv {
+    device_id: DeviceId,
+    path: PathBuf,
+    /// Variables to export, or to unset when `None`.
+    vars: BTreeMap<String, Option<String>>,
+}
+
+/// Creates per-session environment files and applies updates to them.
+pub struct SessionEnvExporter {
+    dir: PathBuf,
+    sessions: Mutex<HashMap<String, ExportedEnv>>,
+}
+
+impl SessionEnvExporter {
+    /// Creates an exporter that places environment files under `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates an empty environment file in the private directory.
+    pub fn prepare(&self) -> Result<PreparedEnvFile, EnvError> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)?;
+
+        let path = self
+            .dir
+            .join(format!("{}.env", uuid::Uuid::new_v4().simple()));
+        write_file(&path, &BTreeMap::new())?;
+        Ok(PreparedEnvFile {
+            path,
+            attached: false,
+        })
+    }
+
+    /// Attaches a prepared file to a session created by a device.
+    pub fn start(&self, session_id: &str, device_id: DeviceId, mut file: PreparedEnvFile) {
+        file.attached = true;
+        let previous = self.sessions().insert(
+            session_id.to_string(),
+            ExportedEnv {
+                device_id,
+                path: file.path.clone(),
+                vars: BTreeMap::new(),
+            },
+        );
+        if let Some(previous) = previous {
+            let _ = std::fs::remove_file(previous.path);
+        }
+    }
+
+    /// Returns true if the session has an environment file.
+    pub fn is_exporting(&self, session_id: &str) -> bool {
+        self.sessions().contains_key(session_id)
+    }
+
+    /// Sets and unsets variables for commands the session starts from now on.
+    ///
+    /// The update is applied as a whole or not at all. Returns the number of
+    /// variables in the session's file afterwards.
+    pub fn update(
+        &self,
+        session_id: &str,
+        device_id: &DeviceId,
+        set: Vec<(String, String)>,
+        unset: Vec<String>,
+    ) -> Result<usize, EnvError> {
+        for (name, value) in &set {
+            validate_name(name)?;
+            if value.len() > MAX_VALUE_LEN || value.contains('\0') {
+                return Err(EnvError::InvalidValue(name.clone()));
+            }
+        }
+        for name in &unset {
+            validate_name(name)?;
+        }
+
+        let mut sessions = self.sessions();
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| EnvError::UnknownSession(session_id.to_string()))?;
+
+        let mut vars = session.vars.clone();
+        for name in unset {
+            vars.insert(name, None);
+        }
+        for (name, value) in set {
+            vars.insert(name, Some(value));
+        }
+        if vars.len() > MAX_VARIABLES {
+            return Err(EnvError::TooMany);
+        }
+
+        write_file(&session.path, &vars)?;
+        session.vars = vars;
+
+        info!(
+            target: "audit",
+            session_id = %session_id,
+            device_id = %device_id,
+            owner = %session.device_id,
+            variables = ?session.vars.keys().collect::<Vec<_>>(),
+            "Session environment updated"
+        );
+        Ok(session.vars.len())
+    }
+
+    /// Removes a session's environment file.
+    pub fn close_session(&self, session_id: &str) {
+        if let Some(session) = self.sessions().remove(session_id) {
+            debug!(session_id = %session_id, "Removing session environment file");
+            let _ = std::fs::remove_file(session.path);
+        }
+    }
+
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, ExportedEnv>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for SessionEnvExporter {
+    fn drop(&mut self) {
+        for (_, session) in self.sessions().drain() {
+            let _ = std::fs::remove_file(session.path);
+        }
+    }
+}
+
+/// Checks that `name` is a shell identifier the daemon does not manage.
+fn validate_name(name: &str) -> Result<(), EnvError> {
+    let mut chars = name.chars();
+    let valid = name.len() <= MAX_NAME_LEN
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(EnvError::InvalidName(name.to_string()));
+    }
+    if name == ENV_FILE_VAR {
+        return Err(EnvError::Reserved(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Renders variables as POSIX shell commands.
+pub fn render(vars: &BTreeMap<String, Option<String>>) -> String {
+    let mut script = String::from(HEADER);
+    for (name, value) in vars {
+        match value {
+            Some(value) => script.push_str(&format!(
+                "export {}='{}'\n",
+                name,
+                value.replace('\'', "'\\''")
+            )),
+            None => script.push_str(&format!("unset {}\n", name)),
+        }
+    }
+    script
+}
+
+/// Replaces the file's contents, so the shell never sources a partial write.
+fn write_file(path: &Path, vars: &BTreeMap<String, Option<String>>) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)?;
+    file.write_all(render(vars).as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Returns the prompt hook that sources the environment file, for `bash` or
+/// `zsh`.
+pub fn shell_hook(shell: &str) -> Option<&'static str> {
+    match shell {
+        "bash" => Some(concat!(
+            "__remoshell_env() {\n",
+            "  local status=$?\n",
+            "  [ -r \"$REMOSHELL_ENV_FILE\" ] && . \"$REMOSHELL_ENV_FILE\"\n",
+            "  return $status\n",
+            "}\n",
+            "PROMPT_COMMAND=\"__remoshell_env${PROMPT_COMMAND:+;$PROMPT_COMMAND}\"\n",
+        )),
+        "zsh" => Some(concat!(
+            "__remoshell_env() {\n",
+            "  [[ -r \"$REMOSHELL_ENV_FILE\" ]] && . \"$REMOSHELL_ENV_FILE\"\n",
+            "  return 0\n",
+            "}\n",
+            "autoload -Uz add-zsh-hook\n",
+            "add-zsh-hook precmd __remoshell_env\n",
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([3; 16])
+    }
+
+    #[test]
+    fn test_render_quotes_values() {
+        let mut vars = BTreeMap::new();
+        vars.insert("DISPLAY".to_string(), Some(":0".to_string()));
+        vars.insert("NOTE".to_string(), Some("it's $HOME".to_string()));
+        vars.insert("TZ".to_string(), None);
+
+        assert_eq!(
+            render(&vars),
+            format!(
+                "{}export DISPLAY=':0'\nexport NOTE='it'\\''s $HOME'\nunset TZ\n",
+                HEADER
+            )
+        );
+    }
+
+    #[test]
+    fn test_update_rewrites_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let exporter = SessionEnvExporter::new(temp_dir.path().join("env"));
+        let file = exporter.prepare().unwrap();
+        let path = file.path().to_path_buf();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), HEADER);
+        exporter.start("sess-1", device(), file);
+
+        let count = exporter
+            .update(
+                "sess-1",
+                &device(),
+                vec![("DISPLAY".to_string(), "localhost:10".to_string())],
+                vec!["TZ".to_string()],
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        let script = std::fs::read_to_string(&path).unwrap();
+        assert!(script.contains("export DISPLAY='localhost:10'\n"));
+        assert!(script.contains("unset TZ\n"));
+
+        // Later updates replace earlier ones
+        exporter
+            .update("sess-1", &device(), vec![], vec!["DISPLAY".to_string()])
+            .unwrap();
+        let script = std::fs::read_to_string(&path).unwrap();
+        assert!(script.contains("unset DISPLAY\n"));
+        assert!(!script.contains("export DISPLAY"));
+
+        exporter.close_session("sess-1");
+        assert!(!path.exists());
+        assert!(!exporter.is_exporting("sess-1"));
+    }
+
+    #[test]
+    fn test_update_rejects_invalid_variables() {
+        let temp_dir = TempDir::new().unwrap();
+        let exporter = SessionEnvExporter::new(temp_dir.path());
+        exporter.start("sess-1", device(), exporter.prepare().unwrap());
+
+        let set = |name: &str, value: &str| vec![(name.to_string(), value.to_string())];
+        assert!(matches!(
+            exporter.update("sess-1", &device(), set("1ABC", "x"), vec![]),
+            Err(EnvError::InvalidName(_))
+        ));
+        assert!(matches!(
+            exporter.update("sess-1", &device(), set("A;rm", "x"), vec![]),
+            Err(EnvError::InvalidName(_))
+        ));
+        assert!(matches!(
+            exporter.update("sess-1", &device(), set("A", "a\0b"), vec![]),
+            Err(EnvError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            exporter.update("sess-1", &device(), set(ENV_FILE_VAR, "/tmp/x"), vec![]),
+            Err(EnvError::Reserved(_))
+        ));
+        assert!(matches!(
+            exporter.update("sess-2", &device(), set("A", "x"), vec![]),
+            Err(EnvError::UnknownSession(_))
+        ));
+
+        let many = (0..=MAX_VARIABLES)
+            .map(|i| (format!("VAR_{}", i), "x".to_string()))
+            .collect();
+        assert!(matches!(
+            exporter.update("sess-1", &device(), many, vec![]),
+            Err(EnvError::TooMany)
+        ));
+    }
+
+    #[test]
+    fn test_file_sources_in_sh() {
+        let temp_dir = TempDir::new().unwrap();
+        let exporter = SessionEnvExporter::new(temp_dir.path());
+        let file = exporter.prepare().unwrap();
+        let path = file.path().to_path_buf();
+        exporter.start("sess-1", device(), file);
+
+        let value = "it's \"quoted\" $HOME `id`\nsecond line";
+        exporter
+            .update(
+                "sess-1",
+                &device(),
+                vec![("NOTE".to_string(), value.to_string())],
+                vec![],
+            )
+            .unwrap();
+
+        let output = std::process::Command::new("/bin/sh")
+            .arg("-c")
+            .arg(". \"$1\" && printf %s \"$NOTE\"")
+            .arg("sh")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), value);
+    }
+
+    #[test]
+    fn test_prepared_file_removed_on_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        let exporter = SessionEnvExporter::new(temp_dir.path());
+        let file = exporter.prepare().unwrap();
+        let path = file.path().to_path_buf();
+        assert!(path.exists());
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_shell_hook() {
+        assert!(shell_hook("bash").unwrap().contains("PROMPT_COMMAND"));
+        assert!(shell_hook("zsh").unwrap().contains("add-zsh-hook precmd"));
+        assert!(shell_hook("fish").is_none());
+    }
+}
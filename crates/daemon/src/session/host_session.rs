@@ -0,0 +1,330 @@
+//! Sessions attached to tmux or screen sessions on the host.
+//!
+//! A `SessionCreate` with `host_session` names a multiplexer session on the
+//! host, so work started at the host's keyboard can be continued remotely.
+//! The new shell replaces itself with the multiplexer client (`exec tmux
+//! attach-session ...`), so detaching ends the remote session and leaves the
+//! host session running for the next client.
+//!
+//! Names are checked against `session.host_sessions.allowed_names`, and the
+//! multiplexer is looked up when each request arrives, so installing tmux
+//! does not need a daemon restart.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use protocol::messages::{HostMultiplexer, HostSession};
+use thiserror::Error;
+
+use crate::config::HostSessionConfig;
+
+/// Maximum length of a host session name.
+const MAX_NAME_LEN: usize = 64;
+
+/// Errors from resolving a host session.
+#[derive(Debug, Error)]
+pub enum HostSessionError {
+    /// The name contains characters other than letters, digits, `-` and `_`.
+    #[error("invalid host session name: {0:?}")]
+    InvalidName(String),
+
+    /// The name is not in `allowed_names`.
+    #[error("host session {0:?} is not in allowed_names")]
+    NotAllowed(String),
+
+    /// Creating sessions is turned off, or not supported by the multiplexer.
+    #[error("creating {0} sessions is not allowed")]
+    CreateNotAllowed(&'static str),
+
+    /// The multiplexer is not installed on the host.
+    #[error("{0} is not installed on the host")]
+    NotInstalled(&'static str),
+
+    /// No session with this name is running.
+    #[error("no {multiplexer} session named {name:?}")]
+    NotFound {
+        /// Multiplexer that was asked.
+        multiplexer: &'static str,
+        /// Requested session name.
+        name: String,
+    },
+
+    /// The multiplexer could not be run.
+    #[error("failed to run {0}: {1}")]
+    Io(&'static str, #[source] std::io::Error),
+}
+
+/// Resolves `SessionCreate.host_session` requests into the command the new
+/// shell runs.
+#[derive(Debug, Clone)]
+pub struct HostSessions {
+    allow_create: bool,
+    allowed_names: Vec<String>,
+    tmux: String,
+    screen: String,
+}
+
+impl HostSessions {
+    /// Creates a resolver from the `session.host_sessions` configuration.
+    pub fn from_config(config: &HostSessionConfig) -> Self {
+        Self {
+            allow_create: config.allow_create,
+            allowed_names: config.allowed_names.clone(),
+            tmux: config.tmux.clone(),
+            screen: config.screen.clone(),
+        }
+    }
+
+    /// Returns the path of a multiplexer's program, if it is installed.
+    pub fn find(&self, multiplexer: HostMultiplexer) -> Option<PathBuf> {
+        let program = match multiplexer {
+            HostMultiplexer::Tmux => &self.tmux,
+            HostMultiplexer::Screen => &self.screen,
+        };
+        which::which(program).ok()
+    }
+
+    /// Returns the command line that attaches the new shell to the requested
+    /// session.
+    ///
+    /// Unless the request creates the session, it must already be running.
+    pub async fn command(&self, request: &HostSession) -> Result<String, HostSessionError> {
+        let name = &request.name;
+        let multiplexer = request.multiplexer;
+        if !is_valid_name(name) {
+            return Err(HostSessionError::InvalidName(name.clone()));
+        }
+        if !self.allows(name) {
+            return Err(HostSessionError::NotAllowed(name.clone()));
+        }
+        if request.create && (!self.allow_create || multiplexer == HostMultiplexer::Screen) {
+            return Err(HostSessionError::CreateNotAllowed(program_name(
+                multiplexer,
+            )));
+        }
+
+        let program = self
+            .find(multiplexer)
+            .ok_or(HostSessionError::NotInstalled(program_name(multiplexer)))?;
+        if !request.create && !session_exists(&program, multiplexer, name).await? {
+            return Err(HostSessionError::NotFound {
+                multiplexer: program_name(multiplexer),
+                name: name.clone(),
+            });
+        }
+
+        // Validated names need no quoting, except that zsh would expand an
+        // unquoted `=name` to a command path
+        let program = shell_quote(&program.to_string_lossy());
+        Ok(match (multiplexer, request.create) {
+            (HostMultiplexer::Tmux, false) => {
+                format!("exec {} attach-session -t '={}'", program, name)
+            }
+            (HostMultiplexer::Tmux, true) => format!("exec {} new-session -A -s {}", program, name),
+            (HostMultiplexer::Screen, _) => format!("exec {} -x {}", program, name),
+        })
+    }
+
+    fn allows(&self, name: &str) -> bool {
+        self.allowed_names
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+    }
+}
+
+/// Returns true if `pattern` is a valid `allowed_names` entry.
+pub fn is_valid_pattern(pattern: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some("") => true,
+        Some(prefix) => is_valid_name(prefix),
+        None => is_valid_name(pattern),
+    }
+}
+
+/// Returns true if `name` is usable as a tmux and screen session name.
+///
+/// tmux reserves `.` and `:` for window and pane targets, and the command
+/// line built from the name must not need quoting.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn program_name(multiplexer: HostMultiplexer) -> &'static str {
+    match multiplexer {
+        HostMultiplexer::Tmux => "tmux",
+        HostMultiplexer::Screen => "screen",
+    }
+}
+
+/// Asks the multiplexer whether a session named `name` is running.
+async fn session_exists(
+    program: &Path,
+    multiplexer: HostMultiplexer,
+    name: &str,
+) -> Result<bool, HostSessionError> {
+    let mut command = tokio::process::Command::new(program);
+    match multiplexer {
+        // `=` makes tmux match the name exactly rather than as a prefix
+        HostMultiplexer::Tmux => command.args(["has-session", "-t", &format!("={}", name)]),
+        HostMultiplexer::Screen => command.arg("-ls").arg(name),
+    };
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| HostSessionError::Io(program_name(multiplexer), e))?;
+
+    Ok(match multiplexer {
+        HostMultiplexer::Tmux => output.status.success(),
+        // screen -ls exits non-zero even when it lists sessions
+        HostMultiplexer::Screen => {
+            screen_lists_session(&String::from_utf8_lossy(&output.stdout), name)
+        }
+    })
+}
+
+/// Returns true if `screen -ls` output lists a session named `name`.
+///
+/// Sessions are listed as `<tab><pid>.<name><tab>(<state>)`.
+fn screen_lists_session(output: &str, name: &str) -> bool {
+    output.lines().any(|line| {
+        line.split_whitespace()
+            .next()
+            .and_then(|id| id.split_once('.'))
+            .is_some_and(|(pid, session)| {
+                session == name && !pid.is_empty() && pid.bytes().all(|b| b.is_ascii_digit())
+            })
+    })
+}
+
+/// Quotes `value` for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    /// Writes a fake multiplexer that runs `script`.
+    fn fake_program(dir: &TempDir, name: &str, script: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn host_sessions(tmux: &str, screen: &str) -> HostSessions {
+        HostSessions::from_config(&HostSessionConfig {
+            allowed_names: vec!["work".to_string(), "build-*".to_string()],
+            tmux: tmux.to_string(),
+            screen: screen.to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn request(multiplexer: HostMultiplexer, name: &str, create: bool) -> HostSession {
+        HostSession {
+            multiplexer,
+            name: name.to_string(),
+            create,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tmux_commands() {
+        let dir = TempDir::new().unwrap();
+        let tmux = fake_program(&dir, "tmux", "exit 0");
+        let sessions = host_sessions(&tmux, "screen");
+
+        let attach = sessions
+            .command(&request(HostMultiplexer::Tmux, "work", false))
+            .await
+            .unwrap();
+        assert_eq!(attach, format!("exec '{}' attach-session -t '=work'", tmux));
+
+        let create = sessions
+            .command(&request(HostMultiplexer::Tmux, "build-1", true))
+            .await
+            .unwrap();
+        assert_eq!(create, format!("exec '{}' new-session -A -s build-1", tmux));
+    }
+
+    #[tokio::test]
+    async fn test_missing_session_rejected() {
+        let dir = TempDir::new().unwrap();
+        let tmux = fake_program(&dir, "tmux", "exit 1");
+        let sessions = host_sessions(&tmux, "screen");
+
+        let result = sessions
+            .command(&request(HostMultiplexer::Tmux, "work", false))
+            .await;
+        assert!(matches!(result, Err(HostSessionError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_screen_session_lookup() {
+        let dir = TempDir::new().unwrap();
+        let screen = fake_program(
+            &dir,
+            "screen",
+            "printf 'There is a screen on:\\n\\t4242.work\\t(Detached)\\n'; exit 1",
+        );
+        let sessions = host_sessions("tmux", &screen);
+
+        let attach = sessions
+            .command(&request(HostMultiplexer::Screen, "work", false))
+            .await
+            .unwrap();
+        assert_eq!(attach, format!("exec '{}' -x work", screen));
+
+        let result = sessions
+            .command(&request(HostMultiplexer::Screen, "build-2", false))
+            .await;
+        assert!(matches!(result, Err(HostSessionError::NotFound { .. })));
+        let result = sessions
+            .command(&request(HostMultiplexer::Screen, "work", true))
+            .await;
+        assert!(matches!(result, Err(HostSessionError::CreateNotAllowed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_names_checked() {
+        let sessions = host_sessions("/nonexistent/tmux", "screen");
+
+        let result = sessions
+            .command(&request(HostMultiplexer::Tmux, "work; rm -rf ~", false))
+            .await;
+        assert!(matches!(result, Err(HostSessionError::InvalidName(_))));
+        let result = sessions
+            .command(&request(HostMultiplexer::Tmux, "personal", false))
+            .await;
+        assert!(matches!(result, Err(HostSessionError::NotAllowed(_))));
+        let result = sessions
+            .command(&request(HostMultiplexer::Tmux, "work", false))
+            .await;
+        assert!(matches!(
+            result,
+            Err(HostSessionError::NotInstalled("tmux"))
+        ));
+    }
+
+    #[test]
+    fn test_valid_patterns() {
+        assert!(is_valid_pattern("*"));
+        assert!(is_valid_pattern("work"));
+        assert!(is_valid_pattern("build-*"));
+        assert!(!is_valid_pattern(""));
+        assert!(!is_valid_pattern("a.b"));
+        assert!(!is_valid_pattern("a*b"));
+    }
+}
@@ -3,13 +3,22 @@
 //! This module provides a thread-safe session manager that can create,
 //! retrieve, and manage multiple PTY sessions concurrently.
 
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use anyhow::Result;
 use dashmap::DashMap;
-use tokio::sync::broadcast;
-
-use super::pty::{Session, SessionError, SessionId, SessionStatus};
+use nix::sys::signal::Signal;
+use protocol::messages::{ProcessSignal, SessionCloseReason, SessionClosed};
+use protocol::DeviceId;
+use tokio::sync::{mpsc, watch};
+
+use super::pty::{
+    LagPolicy, OutputReceiver, Session, SessionAlert, SessionError, SessionId, SessionStatus,
+};
+use super::scrollback::{SearchQuery, SearchResults, DEFAULT_SCROLLBACK_LINES};
+use super::shell_integration::ShellMetadata;
+use crate::auditd::AuditLog;
 
 /// Trait for session management operations.
 ///
@@ -37,13 +46,28 @@ pub trait SessionManager: Send + Sync {
         cwd: Option<String>,
     ) -> Result<(SessionId, u32), SessionError>;
 
+    /// Creates a new session like [`create`](Self::create), running the
+    /// shell as a login shell.
+    async fn create_login_shell(
+        &self,
+        shell: Option<String>,
+        cols: u16,
+        rows: u16,
+        env: Vec<(String, String)>,
+        cwd: Option<String>,
+    ) -> Result<(SessionId, u32), SessionError> {
+        self.create(shell, cols, rows, env, cwd).await
+    }
+
     /// Attaches to an existing session.
     ///
-    /// Returns a receiver for the session's output broadcast.
+    /// Returns a receiver for the session's output, which handles falling
+    /// behind according to `policy`.
     async fn attach(
         &self,
         session_id: &SessionId,
-    ) -> Result<broadcast::Receiver<Vec<u8>>, SessionError>;
+        policy: LagPolicy,
+    ) -> Result<OutputReceiver, SessionError>;
 
     /// Detaches from a session.
     ///
@@ -72,6 +96,24 @@ pub trait SessionManager: Send + Sync {
         signal: Option<i32>,
     ) -> Result<SessionStatus, SessionError>;
 
+    /// Delivers a signal to a session's foreground process group.
+    ///
+    /// # Arguments
+    /// * `session_id` - The session to signal.
+    /// * `signal` - The signal to deliver.
+    async fn signal(
+        &self,
+        session_id: &SessionId,
+        signal: ProcessSignal,
+    ) -> Result<(), SessionError>;
+
+    /// Searches a session's recent output.
+    async fn search(
+        &self,
+        session_id: &SessionId,
+        query: &SearchQuery,
+    ) -> Result<SearchResults, SessionError>;
+
     /// Lists all active sessions.
     fn list(&self) -> Vec<SessionInfo>;
 
@@ -100,6 +142,93 @@ pub struct SessionInfo {
     pub running: bool,
     /// Number of attached clients.
     pub subscribers: usize,
+    /// Shell integration state (working directory, last exit status).
+    pub shell: ShellMetadata,
+}
+
+/// The devices that created each session.
+///
+/// Shared between the session manager, which drops sessions from it as they
+/// end, and the router, which records the creator of each new session.
+#[derive(Debug, Clone, Default)]
+pub struct SessionOwners {
+    owners: Arc<DashMap<SessionId, DeviceId>>,
+}
+
+impl SessionOwners {
+    /// Creates an empty set of owners.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the device that created a session.
+    pub fn insert(&self, session_id: SessionId, device_id: DeviceId) {
+        self.owners.insert(session_id, device_id);
+    }
+
+    /// Returns the device that created a session.
+    pub fn get(&self, session_id: &SessionId) -> Option<DeviceId> {
+        self.owners.get(session_id).map(|entry| *entry.value())
+    }
+
+    /// Forgets a session's owner, returning it.
+    pub fn remove(&self, session_id: &SessionId) -> Option<DeviceId> {
+        self.owners.remove(session_id).map(|(_, owner)| owner)
+    }
+
+    /// Forgets a session's owner if it is `device_id`.
+    ///
+    /// Returns true if the owner was forgotten.
+    pub fn release(&self, session_id: &SessionId, device_id: &DeviceId) -> bool {
+        self.owners
+            .remove_if(session_id, |_, owner| owner == device_id)
+            .is_some()
+    }
+
+    /// Returns the sessions a device created.
+    pub fn owned_by(&self, device_id: &DeviceId) -> Vec<SessionId> {
+        self.owners
+            .iter()
+            .filter(|entry| entry.value() == device_id)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Returns the number of sessions with a recorded owner.
+    pub fn len(&self) -> usize {
+        self.owners.len()
+    }
+
+    /// Returns true if no session has a recorded owner.
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+}
+
+/// A session that has ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedSession {
+    /// The session that ended.
+    pub id: SessionId,
+    /// The device that created the session, if it is still to be told.
+    pub owner: Option<DeviceId>,
+    /// How the shell ended.
+    pub status: SessionStatus,
+    /// Why the session ended.
+    pub reason: SessionCloseReason,
+}
+
+impl ClosedSession {
+    /// Returns the `SessionClosed` message that tells clients about the end.
+    pub fn message(&self) -> SessionClosed {
+        SessionClosed {
+            session_id: self.id.clone(),
+            exit_code: self.status.exit_code(),
+            signal: self.status.signal(),
+            reason: Some(self.reason.description().to_string()),
+            close_reason: Some(self.reason),
+        }
+    }
 }
 
 /// Thread-safe session manager implementation using DashMap.
@@ -109,6 +238,23 @@ pub struct SessionInfo {
 pub struct SessionManagerImpl {
     /// Map of session ID to session.
     sessions: DashMap<SessionId, Arc<tokio::sync::Mutex<Session>>>,
+    /// Shell integration metadata per session, readable without locking the session.
+    metadata: DashMap<SessionId, watch::Receiver<ShellMetadata>>,
+    /// Whether new sessions parse shell integration sequences.
+    shell_integration: bool,
+    /// Number of output lines new sessions keep for searching.
+    scrollback_lines: usize,
+    /// Audit log new sessions record their commands and end in.
+    audit: Option<AuditLog>,
+    /// Where new sessions send their bells and desktop notifications.
+    alerts: Option<mpsc::UnboundedSender<SessionAlert>>,
+    /// The devices that created each session.
+    owners: SessionOwners,
+    /// Where sessions are reported as they end.
+    closed: Option<mpsc::UnboundedSender<ClosedSession>>,
+    /// How long a session may go without input or output before cleanup
+    /// closes it.
+    idle_timeout: RwLock<Option<Duration>>,
 }
 
 impl SessionManagerImpl {
@@ -116,28 +262,205 @@ impl SessionManagerImpl {
     pub fn new() -> Self {
         Self {
             sessions: DashMap::new(),
+            metadata: DashMap::new(),
+            shell_integration: true,
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            audit: None,
+            alerts: None,
+            owners: SessionOwners::new(),
+            closed: None,
+            idle_timeout: RwLock::new(None),
+        }
+    }
+
+    /// Sets whether new sessions parse shell integration sequences.
+    pub fn with_shell_integration(mut self, enabled: bool) -> Self {
+        self.shell_integration = enabled;
+        self
+    }
+
+    /// Sets the number of output lines new sessions keep for searching.
+    pub fn with_scrollback_lines(mut self, lines: usize) -> Self {
+        self.scrollback_lines = lines;
+        self
+    }
+
+    /// Sets the audit log new sessions record their commands and end in.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Sets where new sessions send their bells and desktop notifications.
+    pub fn with_alerts(mut self, alerts: mpsc::UnboundedSender<SessionAlert>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Sets where sessions are reported as they end.
+    ///
+    /// Every end is reported except those from [`close`](Self::close),
+    /// whose caller reports it.
+    pub fn with_closed_sessions(mut self, closed: mpsc::UnboundedSender<ClosedSession>) -> Self {
+        self.closed = Some(closed);
+        self
+    }
+
+    /// Sets how long a session may go without input or output before
+    /// cleanup closes it.
+    pub fn with_idle_timeout(self, timeout: Duration) -> Self {
+        self.set_idle_timeout(Some(timeout));
+        self
+    }
+
+    /// Changes how long a session may go without input or output, or stops
+    /// closing idle sessions if `timeout` is `None`. Takes effect at the next
+    /// cleanup.
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        *self.idle_timeout.write().unwrap_or_else(|e| e.into_inner()) = timeout;
+    }
+
+    /// Returns the devices that created each session.
+    pub fn owners(&self) -> &SessionOwners {
+        &self.owners
+    }
+
+    /// Kills a session and removes it from the manager.
+    ///
+    /// Unlike [`SessionManager::kill`], the end is not reported; it is
+    /// returned for the caller to deliver.
+    pub async fn close(
+        &self,
+        session_id: &SessionId,
+        signal: Option<i32>,
+        reason: SessionCloseReason,
+    ) -> Result<ClosedSession, SessionError> {
+        let session_arc = self
+            .sessions
+            .get(session_id)
+            .map(|entry| Arc::clone(entry.value()))
+            .ok_or_else(|| SessionError::NotFound(session_id.clone()))?;
+
+        let session = session_arc.lock().await;
+        let status = session.kill(signal).await?;
+
+        // Remove from map after killing
+        drop(session);
+        self.sessions.remove(session_id);
+        self.metadata.remove(session_id);
+
+        tracing::info!(
+            session_id = %session_id,
+            status = ?status,
+            reason = ?reason,
+            "Session closed and removed"
+        );
+
+        Ok(ClosedSession {
+            id: session_id.clone(),
+            owner: self.owners.remove(session_id),
+            status,
+            reason,
+        })
+    }
+
+    /// Closes every session a device created, reporting each end.
+    ///
+    /// Returns the number of sessions closed.
+    pub async fn close_owned_by(&self, device_id: &DeviceId, reason: SessionCloseReason) -> usize {
+        let mut count = 0;
+        for id in self.owners.owned_by(device_id) {
+            match self.close(&id, Some(Signal::SIGHUP as i32), reason).await {
+                Ok(closed) => {
+                    self.report(closed);
+                    count += 1;
+                }
+                Err(e) => {
+                    tracing::warn!(session_id = %id, error = %e, "Failed to close session");
+                }
+            }
+        }
+        count
+    }
+
+    /// Reports the end of a session.
+    fn report(&self, closed: ClosedSession) {
+        if let Some(tx) = &self.closed {
+            let _ = tx.send(closed);
         }
     }
 
     /// Cleans up terminated sessions.
     ///
     /// This removes sessions that are no longer running from the manager.
+    /// Sessions whose shell has exited while background jobs keep the
+    /// terminal open have those jobs terminated first, and sessions idle
+    /// for longer than the idle timeout are closed.
     /// Should be called periodically to free resources.
     pub async fn cleanup(&self) {
-        let mut to_remove = Vec::new();
-
-        // Collect IDs of terminated sessions
-        for entry in self.sessions.iter() {
-            let session = entry.value().lock().await;
-            if !session.is_running() {
-                to_remove.push(entry.key().clone());
+        // Clone the sessions out so no map lock is held while waiting
+        let sessions: Vec<_> = self
+            .sessions
+            .iter()
+            .map(|entry| (entry.key().clone(), Arc::clone(entry.value())))
+            .collect();
+
+        let mut ended = Vec::new();
+        let mut idle = Vec::new();
+        let idle_timeout = *self.idle_timeout.read().unwrap_or_else(|e| e.into_inner());
+        for (id, session) in sessions {
+            let session = session.lock().await;
+            let was_running = session.is_running();
+            match session.try_wait().await {
+                Ok(Some(status)) => {
+                    if was_running {
+                        let orphans = session.terminate_orphans().await;
+                        if orphans > 0 {
+                            tracing::info!(
+                                session_id = %id,
+                                count = orphans,
+                                "Terminated processes left behind by the shell"
+                            );
+                        }
+                    }
+                    ended.push((id, status));
+                }
+                _ if !was_running => ended.push((id, SessionStatus::Terminated)),
+                _ => {
+                    if idle_timeout.is_some_and(|timeout| session.idle_for() >= timeout) {
+                        idle.push(id);
+                    }
+                }
             }
         }
 
         // Remove terminated sessions
-        for id in to_remove {
-            if let Some((id, _)) = self.sessions.remove(&id) {
-                tracing::info!(session_id = %id, "Cleaned up terminated session");
+        for (id, status) in ended {
+            self.metadata.remove(&id);
+            if self.sessions.remove(&id).is_some() {
+                tracing::info!(session_id = %id, status = ?status, "Cleaned up terminated session");
+                self.report(ClosedSession {
+                    owner: self.owners.remove(&id),
+                    id,
+                    status,
+                    reason: SessionCloseReason::Exited,
+                });
+            }
+        }
+
+        for id in idle {
+            match self
+                .close(
+                    &id,
+                    Some(Signal::SIGHUP as i32),
+                    SessionCloseReason::IdleTimeout,
+                )
+                .await
+            {
+                Ok(closed) => self.report(closed),
+                Err(e) => {
+                    tracing::warn!(session_id = %id, error = %e, "Failed to close idle session")
+                }
             }
         }
     }
@@ -156,25 +479,22 @@ impl SessionManagerImpl {
             }
         });
     }
-}
 
-impl Default for SessionManagerImpl {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl SessionManager for SessionManagerImpl {
-    async fn create(
+    /// Spawns a session and starts tracking it.
+    fn spawn(
         &self,
         shell: Option<String>,
         cols: u16,
         rows: u16,
         env: Vec<(String, String)>,
         cwd: Option<String>,
+        login_shell: bool,
     ) -> Result<(SessionId, u32), SessionError> {
-        // Spawn the session
-        let (session, _rx) = Session::spawn(shell, cols, rows, env, cwd)?;
+        let (mut session, _rx) = Session::spawn_with(shell, cols, rows, env, cwd, login_shell)?;
+        session.set_shell_integration(self.shell_integration);
+        session.set_scrollback_lines(self.scrollback_lines);
+        session.set_audit(self.audit.clone());
+        session.set_alerts(self.alerts.clone());
 
         let session_id = session.id().clone();
         let pid = session.pid().unwrap_or(0);
@@ -183,6 +503,8 @@ impl SessionManager for SessionManagerImpl {
         session.start_read_loop();
 
         // Store the session
+        self.metadata
+            .insert(session_id.clone(), session.subscribe_metadata());
         self.sessions.insert(
             session_id.clone(),
             Arc::new(tokio::sync::Mutex::new(session)),
@@ -198,11 +520,42 @@ impl SessionManager for SessionManagerImpl {
 
         Ok((session_id, pid))
     }
+}
+
+impl Default for SessionManagerImpl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionManager for SessionManagerImpl {
+    async fn create(
+        &self,
+        shell: Option<String>,
+        cols: u16,
+        rows: u16,
+        env: Vec<(String, String)>,
+        cwd: Option<String>,
+    ) -> Result<(SessionId, u32), SessionError> {
+        self.spawn(shell, cols, rows, env, cwd, false)
+    }
+
+    async fn create_login_shell(
+        &self,
+        shell: Option<String>,
+        cols: u16,
+        rows: u16,
+        env: Vec<(String, String)>,
+        cwd: Option<String>,
+    ) -> Result<(SessionId, u32), SessionError> {
+        self.spawn(shell, cols, rows, env, cwd, true)
+    }
 
     async fn attach(
         &self,
         session_id: &SessionId,
-    ) -> Result<broadcast::Receiver<Vec<u8>>, SessionError> {
+        policy: LagPolicy,
+    ) -> Result<OutputReceiver, SessionError> {
         let session_arc = self
             .sessions
             .get(session_id)
@@ -215,10 +568,11 @@ impl SessionManager for SessionManagerImpl {
             return Err(SessionError::AlreadyTerminated(session_id.clone()));
         }
 
-        let rx = session.subscribe();
+        let rx = session.subscribe_with(policy);
 
         tracing::debug!(
             session_id = %session_id,
+            policy = ?policy,
             subscribers = session.subscriber_count(),
             "Client attached to session"
         );
@@ -270,6 +624,19 @@ impl SessionManager for SessionManagerImpl {
         session_id: &SessionId,
         signal: Option<i32>,
     ) -> Result<SessionStatus, SessionError> {
+        let closed = self
+            .close(session_id, signal, SessionCloseReason::Killed)
+            .await?;
+        let status = closed.status;
+        self.report(closed);
+        Ok(status)
+    }
+
+    async fn signal(
+        &self,
+        session_id: &SessionId,
+        signal: ProcessSignal,
+    ) -> Result<(), SessionError> {
         let session_arc = self
             .sessions
             .get(session_id)
@@ -277,19 +644,22 @@ impl SessionManager for SessionManagerImpl {
             .ok_or_else(|| SessionError::NotFound(session_id.clone()))?;
 
         let session = session_arc.lock().await;
-        let status = session.kill(signal).await?;
-
-        // Remove from map after killing
-        drop(session);
-        self.sessions.remove(session_id);
+        session.signal(signal).await
+    }
 
-        tracing::info!(
-            session_id = %session_id,
-            status = ?status,
-            "Session killed and removed"
-        );
+    async fn search(
+        &self,
+        session_id: &SessionId,
+        query: &SearchQuery,
+    ) -> Result<SearchResults, SessionError> {
+        let session_arc = self
+            .sessions
+            .get(session_id)
+            .map(|entry| Arc::clone(entry.value()))
+            .ok_or_else(|| SessionError::NotFound(session_id.clone()))?;
 
-        Ok(status)
+        let session = session_arc.lock().await;
+        session.search(query)
     }
 
     fn list(&self) -> Vec<SessionInfo> {
@@ -299,6 +669,11 @@ impl SessionManager for SessionManagerImpl {
             .iter()
             .map(|entry| {
                 let id = entry.key().clone();
+                let shell = self
+                    .metadata
+                    .get(&id)
+                    .map(|rx| rx.borrow().clone())
+                    .unwrap_or_default();
                 // We can't await here, so we provide minimal info
                 // For full info, use get() which is async
                 SessionInfo {
@@ -308,6 +683,7 @@ impl SessionManager for SessionManagerImpl {
                     rows: 0,
                     running: true, // Assume running if in map
                     subscribers: 0,
+                    shell,
                 }
             })
             .collect()
@@ -329,6 +705,7 @@ impl SessionManager for SessionManagerImpl {
             rows,
             running: session.is_running(),
             subscribers: session.subscriber_count(),
+            shell: session.shell_metadata(),
         })
     }
 
@@ -344,7 +721,7 @@ impl SessionManager for SessionManagerImpl {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::Duration;
+    use crate::session::pty::SessionOutput;
     use tokio::time::timeout;
 
     #[tokio::test]
@@ -409,7 +786,7 @@ mod tests {
             .await
             .unwrap();
 
-        let result = manager.attach(&session_id).await;
+        let result = manager.attach(&session_id, LagPolicy::DropWithMarker).await;
         assert!(result.is_ok(), "Failed to attach: {:?}", result.err());
 
         // Clean up
@@ -420,7 +797,9 @@ mod tests {
     async fn test_manager_attach_nonexistent() {
         let manager = SessionManagerImpl::new();
 
-        let result = manager.attach(&"nonexistent".to_string()).await;
+        let result = manager
+            .attach(&"nonexistent".to_string(), LagPolicy::DropWithMarker)
+            .await;
         assert!(matches!(result, Err(SessionError::NotFound(_))));
     }
 
@@ -434,7 +813,10 @@ mod tests {
             .unwrap();
 
         // Attach first
-        let _rx = manager.attach(&session_id).await.unwrap();
+        let _rx = manager
+            .attach(&session_id, LagPolicy::DropWithMarker)
+            .await
+            .unwrap();
 
         // Detach
         let result = manager.detach(&session_id).await;
@@ -518,6 +900,16 @@ mod tests {
         assert!(matches!(result, Err(SessionError::NotFound(_))));
     }
 
+    #[tokio::test]
+    async fn test_manager_signal_nonexistent() {
+        let manager = SessionManagerImpl::new();
+
+        let result = manager
+            .signal(&"nonexistent".to_string(), ProcessSignal::Interrupt)
+            .await;
+        assert!(matches!(result, Err(SessionError::NotFound(_))));
+    }
+
     #[tokio::test]
     async fn test_manager_list() {
         let manager = SessionManagerImpl::new();
@@ -554,7 +946,10 @@ mod tests {
             .unwrap();
 
         // Attach to receive output
-        let mut rx = manager.attach(&session_id).await.unwrap();
+        let mut rx = manager
+            .attach(&session_id, LagPolicy::DropWithMarker)
+            .await
+            .unwrap();
 
         // Write a command
         manager
@@ -565,7 +960,9 @@ mod tests {
         // Wait for output
         let mut found_output = false;
         for _ in 0..50 {
-            if let Ok(Ok(data)) = timeout(Duration::from_millis(100), rx.recv()).await {
+            if let Ok(Some(SessionOutput::Data(data))) =
+                timeout(Duration::from_millis(100), rx.recv()).await
+            {
                 let output = String::from_utf8_lossy(&data);
                 if output.contains("roundtrip_test_marker") {
                     found_output = true;
@@ -580,6 +977,54 @@ mod tests {
         let _ = manager.kill(&session_id, Some(9)).await;
     }
 
+    #[tokio::test]
+    async fn test_manager_search() {
+        let manager = SessionManagerImpl::new();
+
+        let (session_id, _) = manager
+            .create(Some("/bin/sh".to_string()), 80, 24, vec![], None)
+            .await
+            .unwrap();
+
+        // The echoed input does not contain the expanded marker
+        manager
+            .write(&session_id, b"echo search_$((40 + 2))_marker\n")
+            .await
+            .unwrap();
+
+        let query = SearchQuery {
+            pattern: "SEARCH_42_MARKER".to_string(),
+            regex: false,
+            case_sensitive: false,
+            max_results: 10,
+        };
+        let mut found = None;
+        for _ in 0..50 {
+            let results = manager.search(&session_id, &query).await.unwrap();
+            if let Some(m) = results.matches.first() {
+                found = Some(m.clone());
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let found = found.expect("Output was not added to the scrollback");
+        // The line may start with a prompt, depending on the shell
+        let text: String = found
+            .text
+            .chars()
+            .skip(found.start)
+            .take(found.end - found.start)
+            .collect();
+        assert_eq!(text, "search_42_marker");
+
+        let result = manager.search(&"nonexistent".to_string(), &query).await;
+        assert!(matches!(result, Err(SessionError::NotFound(_))));
+
+        // Clean up
+        let _ = manager.kill(&session_id, Some(9)).await;
+    }
+
     #[tokio::test]
     async fn test_manager_multiple_attach() {
         let manager = SessionManagerImpl::new();
@@ -590,9 +1035,18 @@ mod tests {
             .unwrap();
 
         // Attach multiple clients
-        let _rx1 = manager.attach(&session_id).await.unwrap();
-        let _rx2 = manager.attach(&session_id).await.unwrap();
-        let _rx3 = manager.attach(&session_id).await.unwrap();
+        let _rx1 = manager
+            .attach(&session_id, LagPolicy::DropWithMarker)
+            .await
+            .unwrap();
+        let _rx2 = manager
+            .attach(&session_id, LagPolicy::DropWithMarker)
+            .await
+            .unwrap();
+        let _rx3 = manager
+            .attach(&session_id, LagPolicy::DropWithMarker)
+            .await
+            .unwrap();
 
         let info = manager.get(&session_id).await.unwrap();
         assert_eq!(info.subscribers, 3);
@@ -625,4 +1079,141 @@ mod tests {
         manager.cleanup().await;
         assert_eq!(manager.count(), 0);
     }
+
+    #[tokio::test]
+    async fn test_manager_cleanup_terminates_orphaned_jobs() {
+        let manager = SessionManagerImpl::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let pid_file = dir.path().join("pid");
+
+        let (session_id, _) = manager
+            .create(Some("/bin/sh".to_string()), 80, 24, vec![], None)
+            .await
+            .unwrap();
+        // The job keeps the terminal open after the shell exits
+        let line = format!("sleep 1000 & echo $! > {}; exit\n", pid_file.display());
+        manager.write(&session_id, line.as_bytes()).await.unwrap();
+
+        let mut removed = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            manager.cleanup().await;
+            if !manager.exists(&session_id) {
+                removed = true;
+                break;
+            }
+        }
+        assert!(removed, "session with an orphaned job was not cleaned up");
+
+        let job: u32 = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let pid = sysinfo::Pid::from_u32(job);
+        let mut system = sysinfo::System::new();
+        system.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), true);
+        assert!(system
+            .process(pid)
+            .is_none_or(|process| process.status() == sysinfo::ProcessStatus::Zombie));
+    }
+
+    /// Runs cleanup until a session is removed.
+    async fn cleanup_until_removed(manager: &SessionManagerImpl, session_id: &SessionId) {
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            manager.cleanup().await;
+            if !manager.exists(session_id) {
+                return;
+            }
+        }
+        panic!("session {} was not cleaned up", session_id);
+    }
+
+    #[tokio::test]
+    async fn test_manager_reports_closed_sessions() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let manager = SessionManagerImpl::new().with_closed_sessions(tx);
+        let owner = DeviceId::from_bytes([7u8; 16]);
+
+        let (exited, _) = manager
+            .create(Some("/bin/sh".to_string()), 80, 24, vec![], None)
+            .await
+            .unwrap();
+        manager.owners().insert(exited.clone(), owner);
+        manager.write(&exited, b"exit 3\n").await.unwrap();
+        cleanup_until_removed(&manager, &exited).await;
+
+        let closed = rx.recv().await.unwrap();
+        assert_eq!(closed.id, exited);
+        assert_eq!(closed.owner, Some(owner));
+        assert_eq!(closed.status, SessionStatus::Exited(3));
+        assert_eq!(closed.reason, SessionCloseReason::Exited);
+        assert!(manager.owners().get(&exited).is_none());
+
+        let (killed, _) = manager
+            .create(Some("/bin/sh".to_string()), 80, 24, vec![], None)
+            .await
+            .unwrap();
+        manager.kill(&killed, Some(9)).await.unwrap();
+
+        let closed = rx.recv().await.unwrap();
+        assert_eq!(closed.id, killed);
+        assert_eq!(closed.owner, None);
+        assert_eq!(closed.reason, SessionCloseReason::Killed);
+        let message = closed.message();
+        assert_eq!(message.close_reason, Some(SessionCloseReason::Killed));
+        assert_eq!(message.signal, closed.status.signal());
+    }
+
+    #[tokio::test]
+    async fn test_manager_closes_idle_sessions() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let manager = SessionManagerImpl::new()
+            .with_closed_sessions(tx)
+            .with_idle_timeout(Duration::from_millis(500));
+
+        let (session_id, _) = manager
+            .create(Some("/bin/sh".to_string()), 80, 24, vec![], None)
+            .await
+            .unwrap();
+        cleanup_until_removed(&manager, &session_id).await;
+
+        let closed = rx.recv().await.unwrap();
+        assert_eq!(closed.id, session_id);
+        assert_eq!(closed.reason, SessionCloseReason::IdleTimeout);
+    }
+
+    #[tokio::test]
+    async fn test_manager_close_owned_by() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let manager = SessionManagerImpl::new().with_closed_sessions(tx);
+        let revoked = DeviceId::from_bytes([1u8; 16]);
+        let other = DeviceId::from_bytes([2u8; 16]);
+
+        let (owned, _) = manager
+            .create(Some("/bin/sh".to_string()), 80, 24, vec![], None)
+            .await
+            .unwrap();
+        let (kept, _) = manager
+            .create(Some("/bin/sh".to_string()), 80, 24, vec![], None)
+            .await
+            .unwrap();
+        manager.owners().insert(owned.clone(), revoked);
+        manager.owners().insert(kept.clone(), other);
+
+        let count = manager
+            .close_owned_by(&revoked, SessionCloseReason::OwnerRevoked)
+            .await;
+        assert_eq!(count, 1);
+        assert!(!manager.exists(&owned));
+        assert!(manager.exists(&kept));
+
+        let closed = rx.recv().await.unwrap();
+        assert_eq!(closed.id, owned);
+        assert_eq!(closed.owner, Some(revoked));
+        assert_eq!(closed.reason, SessionCloseReason::OwnerRevoked);
+
+        manager.kill(&kept, Some(9)).await.unwrap();
+    }
 }
@@ -1,12 +1,43 @@
 //! Session management module.
 //!
 //! This module provides PTY spawning and session lifecycle management.
-//! Sessions can be created, attached to, detached from, resized, and killed.
+//! Sessions can be created, attached to, detached from, resized, and killed,
+//! and can be given environment variables when they start and while they run.
 
+pub mod agent;
+pub mod client_env;
+pub mod display;
+pub mod env;
+pub mod host_session;
 pub mod manager;
 pub mod multiplexer;
 pub mod pty;
+pub mod scrollback;
+pub mod shell_integration;
+pub mod shells;
+pub mod stream;
 
-pub use manager::{SessionManager, SessionManagerImpl};
+pub use agent::{
+    AgentError, AgentForwarder, AgentMessage, PreparedAgentSocket, AGENT_FORWARDING_CAPABILITY,
+};
+pub use client_env::EnvAllowlist;
+pub use display::{
+    DisplayError, DisplayForwarder, DisplayMessage, PreparedDisplaySocket,
+    DISPLAY_FORWARDING_CAPABILITY, WAYPIPE_SOCKET_VAR,
+};
+pub use env::{EnvError, PreparedEnvFile, SessionEnvExporter, ENV_FILE_VAR};
+pub use host_session::{HostSessionError, HostSessions};
+pub use manager::{ClosedSession, SessionManager, SessionManagerImpl, SessionOwners};
 pub use multiplexer::{ClientHandle, ClientId, ClientStats, SessionOutputBroadcaster};
-pub use pty::{Session, SessionError, SessionId, SessionStatus};
+pub use pty::{
+    LagPolicy, OutputReceiver, Session, SessionAlert, SessionError, SessionId, SessionOutput,
+    SessionStatus,
+};
+pub use scrollback::{
+    Scrollback, ScrollbackMatch, SearchError, SearchQuery, SearchResults, DEFAULT_SCROLLBACK_LINES,
+};
+pub use shell_integration::{
+    FinishedCommand, ShellIntegrationParser, ShellMetadata, TerminalAlert,
+};
+pub use shells::{ShellError, ShellPolicy, ETC_SHELLS};
+pub use stream::{OutputMessage, OutputStreams};
@@ -4,19 +4,37 @@
 //! A session represents a single terminal session with a shell process.
 
 use std::io::{Read, Write};
+use std::os::fd::{BorrowedFd, RawFd};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use bytes::{Bytes, BytesMut};
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::signal::{kill, killpg, Signal};
+use nix::unistd::Pid;
 use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use protocol::messages::ProcessSignal;
+use sysinfo::{ProcessRefreshKind, ProcessStatus, ProcessesToUpdate, System};
 use thiserror::Error;
-use tokio::sync::broadcast;
 use tokio::sync::Mutex;
+use tokio::sync::{broadcast, mpsc, watch};
+use tracing::Instrument;
 use uuid::Uuid;
 
+use super::scrollback::{Scrollback, SearchQuery, SearchResults, DEFAULT_SCROLLBACK_LINES};
+use super::shell_integration::{ShellIntegrationParser, ShellMetadata, TerminalAlert};
+use crate::auditd::AuditLog;
+use crate::telemetry;
+
 /// Unique identifier for a session.
 pub type SessionId = String;
 
+/// A bell or notification raised in a session.
+pub type SessionAlert = (SessionId, TerminalAlert);
+
 /// Errors that can occur during session operations.
 #[derive(Error, Debug)]
 pub enum SessionError {
@@ -48,6 +66,18 @@ pub enum SessionError {
     #[error("failed to kill session: {0}")]
     KillFailed(String),
 
+    /// Failed to deliver a signal to the session.
+    #[error("failed to signal session: {0}")]
+    SignalFailed(String),
+
+    /// The scrollback search pattern was rejected.
+    #[error("invalid search: {0}")]
+    InvalidSearch(String),
+
+    /// The working directory does not exist or is not a directory.
+    #[error("invalid working directory: {0}")]
+    InvalidCwd(String),
+
     /// I/O error.
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -66,12 +96,236 @@ pub enum SessionStatus {
     Terminated,
 }
 
-/// Buffer size for reading from PTY.
-const READ_BUFFER_SIZE: usize = 4096;
+impl SessionStatus {
+    /// Returns the exit code, if the shell exited on its own.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Self::Exited(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of the signal that killed the shell, if any.
+    pub fn signal(&self) -> Option<i32> {
+        match self {
+            Self::Killed(signal) => Some(*signal),
+            _ => None,
+        }
+    }
+}
+
+impl From<&portable_pty::ExitStatus> for SessionStatus {
+    /// portable-pty only describes a fatal signal by its `strsignal` text
+    /// ("Terminated by Hangup"), so the number is looked up from that.
+    fn from(status: &portable_pty::ExitStatus) -> Self {
+        match status.to_string().strip_prefix("Terminated by ") {
+            Some(description) => {
+                signal_number(description).map_or(SessionStatus::Terminated, SessionStatus::Killed)
+            }
+            None => SessionStatus::Exited(status.exit_code() as i32),
+        }
+    }
+}
+
+/// Most output read from the PTY by one read.
+///
+/// Linux returns at most 4 KiB per PTY read; other systems may return more.
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Most output gathered into one chunk for subscribers.
+///
+/// Reads continue while the PTY has output ready, so a burst such as `cat`
+/// of a large file is broadcast in chunks of this size rather than one per
+/// read.
+pub const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Size of the buffer output chunks are read into.
+///
+/// Chunks share this allocation; it is reused once subscribers have dropped
+/// them, so a session keeping up with its output does not allocate per read.
+const OUTPUT_BUFFER_CAPACITY: usize = 4 * READ_CHUNK_SIZE;
 
 /// Channel capacity for broadcast output.
 const BROADCAST_CAPACITY: usize = 256;
 
+/// Chunks queued for a [`LagPolicy::Block`] subscriber before the session's
+/// output waits for it.
+const BLOCKING_CAPACITY: usize = 16;
+
+/// How a subscriber that falls behind a session's output is treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Skip the output the subscriber missed and tell it how much was
+    /// skipped. Suits live viewers, which only need the current screen.
+    DropWithMarker,
+    /// Hold up the session's output until the subscriber catches up. Suits
+    /// recorders, which must see every byte; a subscriber that stops
+    /// reading stalls the session.
+    Block,
+}
+
+/// Output received from a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionOutput {
+    /// A chunk of output.
+    Data(Bytes),
+    /// The subscriber fell behind and this many chunks were dropped.
+    Truncated {
+        /// Number of chunks dropped.
+        dropped_chunks: u64,
+    },
+}
+
+/// Receives a session's output, according to a [`LagPolicy`].
+#[derive(Debug)]
+pub struct OutputReceiver {
+    inner: OutputReceiverInner,
+}
+
+#[derive(Debug)]
+enum OutputReceiverInner {
+    Live(broadcast::Receiver<Bytes>),
+    Blocking(mpsc::Receiver<Bytes>),
+}
+
+impl OutputReceiver {
+    /// Receives the next output.
+    ///
+    /// Returns `None` once the session's output has ended.
+    pub async fn recv(&mut self) -> Option<SessionOutput> {
+        match &mut self.inner {
+            OutputReceiverInner::Live(rx) => match rx.recv().await {
+                Ok(data) => Some(SessionOutput::Data(data)),
+                Err(broadcast::error::RecvError::Lagged(dropped_chunks)) => {
+                    Some(SessionOutput::Truncated { dropped_chunks })
+                }
+                Err(broadcast::error::RecvError::Closed) => None,
+            },
+            OutputReceiverInner::Blocking(rx) => rx.recv().await.map(SessionOutput::Data),
+        }
+    }
+}
+
+impl From<broadcast::Receiver<Bytes>> for OutputReceiver {
+    fn from(rx: broadcast::Receiver<Bytes>) -> Self {
+        Self {
+            inner: OutputReceiverInner::Live(rx),
+        }
+    }
+}
+
+/// Senders of the [`LagPolicy::Block`] subscribers of a session.
+type BlockingSubscribers = Arc<std::sync::Mutex<Vec<mpsc::Sender<Bytes>>>>;
+
+/// Minimum time between resizes applied to the PTY.
+///
+/// Each resize sends SIGWINCH to the foreground process, which redraws the
+/// screen. Dragging a window edge or rotating a phone produces bursts of
+/// resizes; only the last size in each interval is applied, so programs
+/// redraw once for the final size rather than many times for stale ones.
+pub const RESIZE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Minimum time between bells forwarded from one session.
+///
+/// Tab completion and `less` ring the bell often; only the first bell in
+/// each interval reaches clients.
+pub const BELL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Minimum time between desktop notifications forwarded from one session.
+pub const NOTIFY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time processes left in a session get to exit after SIGHUP and SIGTERM
+/// before they are sent SIGKILL.
+pub const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// How often processes are checked for during the grace period.
+const TERMINATE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Terminal size last applied to the PTY and any size waiting to be.
+#[derive(Debug)]
+struct ResizeState {
+    /// Size the PTY currently has, as (cols, rows).
+    applied: (u16, u16),
+    /// When the PTY was last resized, or a resize of it started.
+    applied_at: Option<Instant>,
+    /// Size being applied by the resize started at `applied_at`, until it
+    /// finishes.
+    in_flight: Option<(u16, u16)>,
+    /// Latest requested size, if a deferred resize is scheduled.
+    pending: Option<(u16, u16)>,
+}
+
+/// What to do about a requested terminal size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResizeStep {
+    /// Nothing: the PTY has or is getting the size, or a scheduled resize
+    /// will pick it up.
+    Skip,
+    /// Resize the PTY now.
+    Apply,
+    /// Schedule a resize for the given instant.
+    Defer(Instant),
+}
+
+impl ResizeState {
+    /// Returns the size the PTY has once the resize in progress, if any,
+    /// finishes.
+    fn target(&self) -> (u16, u16) {
+        self.in_flight.unwrap_or(self.applied)
+    }
+
+    /// Decides what to do about `size`, requested at `now`. An
+    /// [`ResizeStep::Apply`] counts as started at `now`.
+    fn request(&mut self, size: (u16, u16), now: Instant) -> ResizeStep {
+        if self.pending.is_some() {
+            self.pending = Some(size);
+            return ResizeStep::Skip;
+        }
+        if self.target() == size {
+            return ResizeStep::Skip;
+        }
+        match self.applied_at {
+            Some(at) if now.duration_since(at) < RESIZE_INTERVAL => {
+                self.pending = Some(size);
+                ResizeStep::Defer(at + RESIZE_INTERVAL)
+            }
+            _ => {
+                self.start(size, now);
+                ResizeStep::Apply
+            }
+        }
+    }
+
+    /// Takes the size a deferred resize starting at `now` should apply, if
+    /// the PTY is not already getting it.
+    fn take_pending(&mut self, now: Instant) -> Option<(u16, u16)> {
+        let size = self.pending.take()?;
+        if size == self.target() {
+            return None;
+        }
+        self.start(size, now);
+        Some(size)
+    }
+
+    fn start(&mut self, size: (u16, u16), now: Instant) {
+        self.applied_at = Some(now);
+        self.in_flight = Some(size);
+    }
+
+    /// Records that the resize started at `started` gave the PTY `size`, or
+    /// failed, unless a later resize has started since. The size counts as
+    /// applied only once the PTY has it, so a failed resize can be retried
+    /// with the same size.
+    fn finish(&mut self, size: (u16, u16), started: Instant, applied: bool) {
+        if self.applied_at == Some(started) {
+            if applied {
+                self.applied = size;
+            }
+            self.in_flight = None;
+        }
+    }
+}
+
 /// A PTY session with a shell process.
 ///
 /// The session manages a pseudo-terminal with a shell process. It provides
@@ -91,17 +345,41 @@ pub struct Session {
     child: Arc<Mutex<Box<dyn Child + Send + Sync>>>,
 
     /// Broadcast sender for output data.
-    output_tx: broadcast::Sender<Vec<u8>>,
+    output_tx: broadcast::Sender<Bytes>,
+
+    /// Subscribers that hold up output rather than miss any.
+    blocking_subscribers: BlockingSubscribers,
 
     /// Flag indicating if the session is still running.
     running: Arc<AtomicBool>,
 
-    /// Current terminal size.
+    /// Current terminal size, as last requested.
     cols: u16,
     rows: u16,
 
+    /// Resize coalescing state.
+    resize_state: Arc<std::sync::Mutex<ResizeState>>,
+
     /// Process ID.
     pid: Option<u32>,
+
+    /// Whether to parse shell integration sequences from the output.
+    shell_integration: bool,
+
+    /// Latest shell integration metadata.
+    metadata_tx: watch::Sender<ShellMetadata>,
+
+    /// Audit log that commands and the end of the session are recorded in.
+    audit: Option<AuditLog>,
+
+    /// Where bells and desktop notifications from the session are sent.
+    alerts: Option<mpsc::UnboundedSender<SessionAlert>>,
+
+    /// Recent output as plain text, for searching.
+    scrollback: Arc<std::sync::Mutex<Scrollback>>,
+
+    /// When input was last written or output last read.
+    last_activity: Arc<std::sync::Mutex<Instant>>,
 }
 
 impl Session {
@@ -122,12 +400,32 @@ impl Session {
         rows: u16,
         env: Vec<(String, String)>,
         cwd: Option<String>,
-    ) -> Result<(Self, broadcast::Receiver<Vec<u8>>), SessionError> {
+    ) -> Result<(Self, broadcast::Receiver<Bytes>), SessionError> {
+        Self::spawn_with(shell, cols, rows, env, cwd, false)
+    }
+
+    /// Spawns a new PTY session like [`spawn`](Self::spawn), running the
+    /// shell as a login shell if `login_shell` is set.
+    ///
+    /// A `cwd` of `~` or starting with `~/` is relative to the home
+    /// directory. The working directory must exist.
+    pub fn spawn_with(
+        shell: Option<String>,
+        cols: u16,
+        rows: u16,
+        env: Vec<(String, String)>,
+        cwd: Option<String>,
+        login_shell: bool,
+    ) -> Result<(Self, broadcast::Receiver<Bytes>), SessionError> {
         let id = Uuid::new_v4().to_string();
 
         // Detect shell
         let shell_cmd = detect_shell(shell);
 
+        // The PTY starts in the home directory when the working directory
+        // does not exist, which hides typos, so check it first
+        let cwd = cwd.as_deref().map(resolve_cwd).transpose()?;
+
         // Create PTY system
         let pty_system = native_pty_system();
 
@@ -141,8 +439,15 @@ impl Session {
             })
             .map_err(|e| SessionError::SpawnFailed(e.to_string()))?;
 
-        // Build command
-        let mut cmd = CommandBuilder::new(&shell_cmd);
+        // Build command. The default program runs $SHELL with argv[0]
+        // prefixed by `-`, which is how login shells are started
+        let mut cmd = if login_shell {
+            let mut cmd = CommandBuilder::new_default_prog();
+            cmd.env("SHELL", &shell_cmd);
+            cmd
+        } else {
+            CommandBuilder::new(&shell_cmd)
+        };
 
         // Set working directory
         if let Some(ref dir) = cwd {
@@ -177,10 +482,25 @@ impl Session {
             writer: Arc::new(Mutex::new(writer)),
             child: Arc::new(Mutex::new(child)),
             output_tx,
+            blocking_subscribers: Arc::default(),
             running: Arc::new(AtomicBool::new(true)),
             cols,
             rows,
+            resize_state: Arc::new(std::sync::Mutex::new(ResizeState {
+                applied: (cols, rows),
+                applied_at: None,
+                in_flight: None,
+                pending: None,
+            })),
             pid,
+            shell_integration: true,
+            metadata_tx: watch::Sender::new(ShellMetadata::default()),
+            audit: None,
+            alerts: None,
+            scrollback: Arc::new(std::sync::Mutex::new(Scrollback::new(
+                DEFAULT_SCROLLBACK_LINES,
+            ))),
+            last_activity: Arc::new(std::sync::Mutex::new(Instant::now())),
         };
 
         Ok((session, output_rx))
@@ -201,11 +521,67 @@ impl Session {
         (self.cols, self.rows)
     }
 
+    /// Enables or disables shell integration parsing.
+    ///
+    /// Takes effect when the read loop is started.
+    pub fn set_shell_integration(&mut self, enabled: bool) {
+        self.shell_integration = enabled;
+    }
+
+    /// Sets the audit log that commands and the end of the session are
+    /// recorded in.
+    ///
+    /// Takes effect when the read loop is started.
+    pub fn set_audit(&mut self, audit: Option<AuditLog>) {
+        self.audit = audit;
+    }
+
+    /// Sets where bells and desktop notifications from the session are sent.
+    ///
+    /// Takes effect when the read loop is started.
+    pub fn set_alerts(&mut self, alerts: Option<mpsc::UnboundedSender<SessionAlert>>) {
+        self.alerts = alerts;
+    }
+
+    /// Sets the number of output lines kept for searching.
+    ///
+    /// Takes effect when the read loop is started.
+    pub fn set_scrollback_lines(&mut self, lines: usize) {
+        self.scrollback = Arc::new(std::sync::Mutex::new(Scrollback::new(lines)));
+    }
+
+    /// Searches the session's recent output.
+    pub fn search(&self, query: &SearchQuery) -> Result<SearchResults, SessionError> {
+        self.scrollback
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .search(query)
+            .map_err(|e| SessionError::InvalidSearch(e.to_string()))
+    }
+
+    /// Returns the latest shell integration metadata.
+    pub fn shell_metadata(&self) -> ShellMetadata {
+        self.metadata_tx.borrow().clone()
+    }
+
+    /// Subscribes to shell integration metadata updates.
+    pub fn subscribe_metadata(&self) -> watch::Receiver<ShellMetadata> {
+        self.metadata_tx.subscribe()
+    }
+
     /// Returns whether the session is still running.
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
 
+    /// Returns how long the session has had neither input nor output.
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .elapsed()
+    }
+
     /// Writes data to the PTY (stdin).
     ///
     /// This sends input to the shell process.
@@ -214,6 +590,8 @@ impl Session {
             return Err(SessionError::AlreadyTerminated(self.id.clone()));
         }
 
+        *self.last_activity.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+
         let mut writer = self.writer.lock().await;
         writer
             .write_all(data)
@@ -227,136 +605,177 @@ impl Session {
 
     /// Starts the read loop for capturing output.
     ///
-    /// This spawns a blocking task that reads from the PTY and broadcasts
-    /// the output to all subscribers. The loop continues until the session
-    /// is terminated or an error occurs.
+    /// This starts a thread that reads from the PTY and broadcasts the
+    /// output to all subscribers. The loop continues until the session is
+    /// terminated or an error occurs.
     pub fn start_read_loop(&self) {
         let master = Arc::clone(&self.master);
-        let output_tx = self.output_tx.clone();
         let running = Arc::clone(&self.running);
-        let session_id = self.id.clone();
-
-        tokio::spawn(async move {
-            // Get the reader from the master
-            let reader = {
-                let master = master.lock().await;
-                match master.try_clone_reader() {
-                    Ok(reader) => reader,
-                    Err(e) => {
-                        tracing::error!(
-                            session_id = %session_id,
-                            error = %e,
-                            "Failed to get PTY reader"
-                        );
-                        running.store(false, Ordering::SeqCst);
-                        return;
-                    }
-                }
-            };
-
-            // Wrap reader in Arc<Mutex> for the blocking task
-            let reader = Arc::new(std::sync::Mutex::new(reader));
-
-            loop {
-                if !running.load(Ordering::SeqCst) {
-                    tracing::debug!(session_id = %session_id, "Read loop stopping: session not running");
-                    break;
-                }
-
-                let reader_clone = Arc::clone(&reader);
-
-                // Use spawn_blocking to read from the PTY
-                let result = tokio::task::spawn_blocking(move || {
-                    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
-                    let mut reader = reader_clone.lock().unwrap();
-                    match reader.read(&mut buffer) {
-                        Ok(0) => Ok(None), // EOF
-                        Ok(n) => {
-                            buffer.truncate(n);
-                            Ok(Some(buffer))
+        let shell_integration = self.shell_integration;
+        let mut pump = OutputPump {
+            session_id: self.id.clone(),
+            output_tx: self.output_tx.clone(),
+            blocking_subscribers: Arc::clone(&self.blocking_subscribers),
+            metadata_tx: self.metadata_tx.clone(),
+            shell_integration,
+            parser: (shell_integration || self.alerts.is_some()).then(ShellIntegrationParser::new),
+            audit: self.audit.clone(),
+            alerts: self.alerts.clone(),
+            last_bell: None,
+            last_notify: None,
+            scrollback: Arc::clone(&self.scrollback),
+            last_activity: Arc::clone(&self.last_activity),
+        };
+        let span = telemetry::session_span(&self.id);
+        let thread_span = span.clone();
+
+        tokio::spawn(
+            async move {
+                // Get the reader from the master
+                let (mut reader, fd) = {
+                    let master = master.lock().await;
+                    match master.try_clone_reader() {
+                        Ok(reader) => (reader, master.as_raw_fd()),
+                        Err(e) => {
+                            tracing::error!(error = %e, "Failed to get PTY reader");
+                            running.store(false, Ordering::SeqCst);
+                            return;
                         }
-                        Err(e) => Err(e),
                     }
-                })
-                .await;
-
-                match result {
-                    Ok(Ok(Some(data))) => {
-                        // Broadcast the output
-                        if output_tx.send(data).is_err() {
-                            // No receivers, but that's okay - session might be detached
-                            tracing::trace!(
-                                session_id = %session_id,
-                                "No receivers for output"
-                            );
+                };
+
+                // Reads block, so they get a thread of their own rather than
+                // a blocking task per read
+                let thread_running = Arc::clone(&running);
+                let spawned = std::thread::Builder::new()
+                    .name("pty-read".to_string())
+                    .spawn(move || {
+                        let _span = thread_span.enter();
+                        // Holding the master keeps `fd` open
+                        let _master = master;
+                        let running = thread_running;
+                        let mut buffer = BytesMut::with_capacity(OUTPUT_BUFFER_CAPACITY);
+
+                        loop {
+                            if !running.load(Ordering::SeqCst) {
+                                tracing::debug!("Read loop stopping: session not running");
+                                break;
+                            }
+
+                            match read_chunk(reader.as_mut(), fd, &mut buffer) {
+                                Ok(Some(data)) => pump.output(data),
+                                Ok(None) => {
+                                    // EOF - process exited
+                                    tracing::info!("PTY EOF - process exited");
+                                    running.store(false, Ordering::SeqCst);
+                                    break;
+                                }
+                                Err(e) => {
+                                    // I/O error
+                                    if running.load(Ordering::SeqCst) {
+                                        tracing::error!(error = %e, "Error reading from PTY");
+                                    }
+                                    running.store(false, Ordering::SeqCst);
+                                    break;
+                                }
+                            }
                         }
-                    }
-                    Ok(Ok(None)) => {
-                        // EOF - process exited
-                        tracing::info!(session_id = %session_id, "PTY EOF - process exited");
-                        running.store(false, Ordering::SeqCst);
-                        break;
-                    }
-                    Ok(Err(e)) => {
-                        // I/O error
-                        if running.load(Ordering::SeqCst) {
-                            tracing::error!(
-                                session_id = %session_id,
-                                error = %e,
-                                "Error reading from PTY"
-                            );
-                        }
-                        running.store(false, Ordering::SeqCst);
-                        break;
-                    }
-                    Err(e) => {
-                        // Task join error
-                        tracing::error!(
-                            session_id = %session_id,
-                            error = %e,
-                            "Read task panicked"
-                        );
-                        running.store(false, Ordering::SeqCst);
-                        break;
-                    }
+
+                        pump.finish();
+                    });
+                if let Err(e) = spawned {
+                    tracing::error!(error = %e, "Failed to start PTY read thread");
+                    running.store(false, Ordering::SeqCst);
                 }
             }
-        });
+            .instrument(span),
+        );
     }
 
     /// Resizes the PTY to the given dimensions.
+    ///
+    /// Resizes are coalesced: a size equal to the one the PTY has, or is
+    /// being given, is ignored, and at most one resize is applied per [`RESIZE_INTERVAL`]. A resize
+    /// requested sooner is deferred to the end of the interval, replacing
+    /// any size already waiting. [`Session::size`] reports the requested
+    /// size immediately.
     pub async fn resize(&mut self, cols: u16, rows: u16) -> Result<(), SessionError> {
         if !self.is_running() {
             return Err(SessionError::AlreadyTerminated(self.id.clone()));
         }
 
-        let master = self.master.lock().await;
-        master
-            .resize(PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .map_err(|e| SessionError::ResizeFailed(e.to_string()))?;
-
         self.cols = cols;
         self.rows = rows;
 
-        tracing::debug!(
-            session_id = %self.id,
-            cols = cols,
-            rows = rows,
-            "Resized PTY"
-        );
+        let started = Instant::now();
+        let step = self
+            .resize_state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .request((cols, rows), started);
+
+        match step {
+            ResizeStep::Skip => Ok(()),
+            ResizeStep::Defer(deadline) => {
+                tracing::trace!(
+                    session_id = %self.id,
+                    cols = cols,
+                    rows = rows,
+                    "Deferring PTY resize"
+                );
+                self.schedule_resize(deadline);
+                Ok(())
+            }
+            ResizeStep::Apply => {
+                let result = apply_resize(&self.id, &self.master, cols, rows).await;
+                self.resize_state
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .finish((cols, rows), started, result.is_ok());
+                result
+            }
+        }
+    }
 
-        Ok(())
+    /// Applies the pending size once `deadline` has passed.
+    fn schedule_resize(&self, deadline: Instant) {
+        let id = self.id.clone();
+        let master = Arc::clone(&self.master);
+        let state = Arc::clone(&self.resize_state);
+        let running = Arc::clone(&self.running);
+
+        tokio::spawn(async move {
+            tokio::time::sleep_until(deadline.into()).await;
+            if !running.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let started = Instant::now();
+            let Some(size) = state
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take_pending(started)
+            else {
+                return;
+            };
+
+            let result = apply_resize(&id, &master, size.0, size.1).await;
+            if let Err(e) = &result {
+                tracing::warn!(session_id = %id, error = %e, "Deferred PTY resize failed");
+            }
+            state
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .finish(size, started, result.is_ok());
+        });
     }
 
     /// Kills the session and cleans up resources.
     ///
-    /// If a signal is provided, sends that signal to the process.
-    /// Otherwise, waits for the process to exit naturally.
+    /// If a signal is provided, every process in the session is terminated
+    /// (see [`terminate_session`]). Otherwise, waits for the shell to exit
+    /// naturally. Either way, background jobs the shell leaves behind are
+    /// terminated so that they do not keep the terminal open.
     pub async fn kill(&self, signal: Option<i32>) -> Result<SessionStatus, SessionError> {
         if !self.is_running() {
             return Err(SessionError::AlreadyTerminated(self.id.clone()));
@@ -367,13 +786,17 @@ impl Session {
 
         let mut child = self.child.lock().await;
 
-        // Kill the child process
         if signal.is_some() {
-            // Note: portable-pty's kill() doesn't take a signal parameter
-            // It just terminates the process
-            child
-                .kill()
-                .map_err(|e| SessionError::KillFailed(e.to_string()))?;
+            match self.pid {
+                Some(pid) => {
+                    terminate_session(pid, TERMINATE_GRACE_PERIOD).await;
+                }
+                // Note: portable-pty's kill() doesn't take a signal parameter
+                // It just terminates the process
+                None => child
+                    .kill()
+                    .map_err(|e| SessionError::KillFailed(e.to_string()))?,
+            }
         }
 
         // Wait for the process to exit
@@ -381,8 +804,11 @@ impl Session {
             .wait()
             .map_err(|e| SessionError::KillFailed(e.to_string()))?;
 
-        let code = status.exit_code();
-        let session_status = SessionStatus::Exited(code as i32);
+        if let (None, Some(pid)) = (signal, self.pid) {
+            terminate_session(pid, TERMINATE_GRACE_PERIOD).await;
+        }
+
+        let session_status = SessionStatus::from(&status);
 
         tracing::info!(
             session_id = %self.id,
@@ -393,6 +819,50 @@ impl Session {
         Ok(session_status)
     }
 
+    /// Terminates the processes left in the session once its shell has
+    /// exited.
+    ///
+    /// Background jobs keep the terminal open after the shell exits, so the
+    /// read loop never sees end of file; terminating them ends the session.
+    /// Returns the number of processes that were terminated.
+    pub async fn terminate_orphans(&self) -> usize {
+        match self.pid {
+            Some(pid) => terminate_session(pid, TERMINATE_GRACE_PERIOD).await,
+            None => 0,
+        }
+    }
+
+    /// Delivers a signal to the session's foreground process group.
+    ///
+    /// The foreground group is read from the PTY so that, for example, an
+    /// interrupt reaches the job currently running in the shell rather than
+    /// the shell itself. Falls back to the shell's own process group when the
+    /// foreground group cannot be determined.
+    pub async fn signal(&self, signal: ProcessSignal) -> Result<(), SessionError> {
+        if !self.is_running() {
+            return Err(SessionError::AlreadyTerminated(self.id.clone()));
+        }
+
+        let pgid = {
+            let master = self.master.lock().await;
+            master.process_group_leader()
+        }
+        .or_else(|| self.pid.map(|pid| pid as i32))
+        .ok_or_else(|| SessionError::SignalFailed("no process group".to_string()))?;
+
+        killpg(Pid::from_raw(pgid), to_nix_signal(signal))
+            .map_err(|e| SessionError::SignalFailed(e.to_string()))?;
+
+        tracing::debug!(
+            session_id = %self.id,
+            pgid = pgid,
+            signal = signal.name(),
+            "Delivered signal to session"
+        );
+
+        Ok(())
+    }
+
     /// Checks if the child process has exited and returns its status.
     ///
     /// This does not wait for the process to exit; it only checks the current status.
@@ -402,9 +872,7 @@ impl Session {
         match child.try_wait() {
             Ok(Some(status)) => {
                 self.running.store(false, Ordering::SeqCst);
-                let code = status.exit_code();
-                let session_status = SessionStatus::Exited(code as i32);
-                Ok(Some(session_status))
+                Ok(Some(SessionStatus::from(&status)))
             }
             Ok(None) => Ok(None),
             Err(e) => Err(SessionError::Io(e)),
@@ -415,13 +883,39 @@ impl Session {
     ///
     /// Returns a receiver that will receive all output data from the PTY.
     /// Multiple clients can subscribe to the same session.
-    pub fn subscribe(&self) -> broadcast::Receiver<Vec<u8>> {
+    pub fn subscribe(&self) -> broadcast::Receiver<Bytes> {
         self.output_tx.subscribe()
     }
 
+    /// Subscribes to the output, treating the subscriber according to
+    /// `policy` when it falls behind.
+    pub fn subscribe_with(&self, policy: LagPolicy) -> OutputReceiver {
+        match policy {
+            LagPolicy::DropWithMarker => self.subscribe().into(),
+            LagPolicy::Block => {
+                let (tx, rx) = mpsc::channel(BLOCKING_CAPACITY);
+                self.lock_blocking_subscribers().push(tx);
+                OutputReceiver {
+                    inner: OutputReceiverInner::Blocking(rx),
+                }
+            }
+        }
+    }
+
     /// Returns the number of active subscribers.
     pub fn subscriber_count(&self) -> usize {
-        self.output_tx.receiver_count()
+        let blocking = self
+            .lock_blocking_subscribers()
+            .iter()
+            .filter(|tx| !tx.is_closed())
+            .count();
+        self.output_tx.receiver_count() + blocking
+    }
+
+    fn lock_blocking_subscribers(&self) -> std::sync::MutexGuard<'_, Vec<mpsc::Sender<Bytes>>> {
+        self.blocking_subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
     }
 }
 
@@ -439,6 +933,301 @@ fn detect_shell(shell: Option<String>) -> String {
     std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
 }
 
+/// Expands a leading `~` in a session's working directory and checks that
+/// it is a directory.
+fn resolve_cwd(cwd: &str) -> Result<PathBuf, SessionError> {
+    let home = || {
+        dirs::home_dir()
+            .ok_or_else(|| SessionError::InvalidCwd("home directory is unknown".to_string()))
+    };
+    let path = match cwd.strip_prefix('~') {
+        Some("") => home()?,
+        Some(rest) if rest.starts_with('/') => home()?.join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(cwd),
+    };
+
+    match std::fs::metadata(&path) {
+        Ok(metadata) if metadata.is_dir() => Ok(path),
+        Ok(_) => Err(SessionError::InvalidCwd(format!(
+            "{} is not a directory",
+            path.display()
+        ))),
+        Err(e) => Err(SessionError::InvalidCwd(format!(
+            "{}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+/// Resizes the PTY, which sends SIGWINCH to its foreground process group.
+async fn apply_resize(
+    id: &SessionId,
+    master: &Mutex<Box<dyn MasterPty + Send>>,
+    cols: u16,
+    rows: u16,
+) -> Result<(), SessionError> {
+    master
+        .lock()
+        .await
+        .resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| SessionError::ResizeFailed(e.to_string()))?;
+
+    tracing::debug!(
+        session_id = %id,
+        cols = cols,
+        rows = rows,
+        "Resized PTY"
+    );
+
+    Ok(())
+}
+
+/// Terminates every process in the terminal session `sid`.
+///
+/// Shells are spawned as session leaders (portable-pty calls `setsid`), so
+/// the session holds the shell, its jobs in every process group, and their
+/// children. Each process is sent SIGHUP and SIGTERM, then SIGCONT so that
+/// stopped jobs can act on them; interactive shells ignore SIGTERM but exit
+/// on SIGHUP. Processes still running after `grace` are sent SIGKILL.
+/// Processes that started their own session with `setsid`, such as a tmux
+/// server, are not affected.
+///
+/// Returns the number of processes that were signalled.
+pub async fn terminate_session(sid: u32, grace: Duration) -> usize {
+    let mut system = System::new();
+    let members = session_members(&mut system, sid);
+    if members.is_empty() {
+        return 0;
+    }
+
+    for &pid in &members {
+        for signal in [Signal::SIGHUP, Signal::SIGTERM, Signal::SIGCONT] {
+            // The process may already have exited
+            let _ = kill(Pid::from_raw(pid as i32), signal);
+        }
+    }
+
+    let deadline = Instant::now() + grace;
+    let mut remaining = session_members(&mut system, sid);
+    while !remaining.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(TERMINATE_POLL_INTERVAL).await;
+        remaining = session_members(&mut system, sid);
+    }
+
+    if !remaining.is_empty() {
+        tracing::warn!(
+            sid = sid,
+            count = remaining.len(),
+            "Killing session processes that ignored SIGTERM"
+        );
+        for &pid in &remaining {
+            let _ = kill(Pid::from_raw(pid as i32), Signal::SIGKILL);
+        }
+    }
+
+    tracing::debug!(
+        sid = sid,
+        count = members.len(),
+        "Terminated session processes"
+    );
+    members.len()
+}
+
+/// Returns the live processes in the terminal session `sid`.
+///
+/// Zombies are left out: they have already exited, and orphans are reaped
+/// by init rather than by the daemon.
+fn session_members(system: &mut System, sid: u32) -> Vec<u32> {
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing());
+    system
+        .processes()
+        .values()
+        .filter(|process| {
+            process.session_id().map(|id| id.as_u32()) == Some(sid)
+                && !matches!(
+                    process.status(),
+                    ProcessStatus::Zombie | ProcessStatus::Dead
+                )
+        })
+        .map(|process| process.pid().as_u32())
+        .collect()
+}
+
+/// Hands output read from a session's PTY to the session's consumers.
+struct OutputPump {
+    session_id: SessionId,
+    output_tx: broadcast::Sender<Bytes>,
+    blocking_subscribers: BlockingSubscribers,
+    metadata_tx: watch::Sender<ShellMetadata>,
+    shell_integration: bool,
+    parser: Option<ShellIntegrationParser>,
+    audit: Option<AuditLog>,
+    alerts: Option<mpsc::UnboundedSender<SessionAlert>>,
+    last_bell: Option<Instant>,
+    last_notify: Option<Instant>,
+    scrollback: Arc<std::sync::Mutex<Scrollback>>,
+    last_activity: Arc<std::sync::Mutex<Instant>>,
+}
+
+impl OutputPump {
+    /// Tracks shell state in a chunk of output and broadcasts it.
+    fn output(&mut self, data: Bytes) {
+        *self.last_activity.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+
+        // Track shell state before handing the data off
+        if let Some(parser) = self.parser.as_mut() {
+            if parser.feed(&data) && self.shell_integration {
+                self.metadata_tx.send_replace(parser.metadata().clone());
+            }
+            let finished = parser.take_finished();
+            if let Some(audit) = &self.audit {
+                for command in &finished {
+                    audit.command(&self.session_id, command);
+                }
+            }
+            for alert in parser.take_alerts() {
+                let (last, interval) = match alert {
+                    TerminalAlert::Bell => (&mut self.last_bell, BELL_INTERVAL),
+                    TerminalAlert::Notify { .. } => (&mut self.last_notify, NOTIFY_INTERVAL),
+                };
+                if last.is_some_and(|at| at.elapsed() < interval) {
+                    continue;
+                }
+                *last = Some(Instant::now());
+                if let Some(alerts) = &self.alerts {
+                    let _ = alerts.send((self.session_id.clone(), alert));
+                }
+            }
+        }
+
+        self.scrollback
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(&data);
+
+        // Wait for blocking subscribers first, outside the lock so that
+        // new subscribers are not held up
+        let blocking = self
+            .blocking_subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if !blocking.is_empty() {
+            let mut closed = false;
+            for tx in &blocking {
+                closed |= tx.blocking_send(data.clone()).is_err();
+            }
+            if closed {
+                self.blocking_subscribers
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .retain(|tx| !tx.is_closed());
+            }
+        }
+
+        // Broadcast the output
+        if self.output_tx.send(data).is_err() {
+            // No receivers, but that's okay - session might be detached
+            tracing::trace!("No receivers for output");
+        }
+    }
+
+    /// Records the end of the session's output.
+    fn finish(self) {
+        // Blocking subscribers see the end of the output even while the
+        // session is kept around
+        self.blocking_subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        if let Some(audit) = &self.audit {
+            audit.session_ended(&self.session_id);
+        }
+    }
+}
+
+/// Reads the next chunk of output from the PTY into `buffer`.
+///
+/// Blocks until output is available, then keeps reading while more is
+/// ready on `fd`, up to [`READ_CHUNK_SIZE`]. Returns `None` at end of file.
+/// An error after some output was read is left for the next call.
+fn read_chunk(
+    reader: &mut (dyn Read + Send),
+    fd: Option<RawFd>,
+    buffer: &mut BytesMut,
+) -> std::io::Result<Option<Bytes>> {
+    // Reclaims the whole allocation once subscribers dropped earlier chunks
+    buffer.reserve(READ_CHUNK_SIZE);
+
+    loop {
+        let filled = buffer.len();
+        buffer.resize((filled + READ_BUFFER_SIZE).min(READ_CHUNK_SIZE), 0);
+        let result = reader.read(&mut buffer[filled..]);
+        buffer.truncate(filled + result.as_ref().map_or(0, |read| *read));
+        match result {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) if filled > 0 => break,
+            Err(e) => return Err(e),
+        }
+
+        if buffer.len() == READ_CHUNK_SIZE || !fd.is_some_and(output_ready) {
+            break;
+        }
+    }
+
+    Ok((!buffer.is_empty()).then(|| buffer.split().freeze()))
+}
+
+/// Returns true if `fd` has output ready to read without blocking.
+fn output_ready(fd: RawFd) -> bool {
+    // SAFETY: the read loop keeps the PTY master, and so `fd`, open
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+    matches!(poll(&mut fds, PollTimeout::ZERO), Ok(ready) if ready > 0)
+        && fds[0]
+            .revents()
+            .is_some_and(|events| events.contains(PollFlags::POLLIN))
+}
+
+/// Maps a protocol signal to the platform signal.
+pub(crate) fn to_nix_signal(signal: ProcessSignal) -> Signal {
+    match signal {
+        ProcessSignal::Interrupt => Signal::SIGINT,
+        ProcessSignal::Quit => Signal::SIGQUIT,
+        ProcessSignal::Suspend => Signal::SIGTSTP,
+        ProcessSignal::Continue => Signal::SIGCONT,
+        ProcessSignal::Hangup => Signal::SIGHUP,
+        ProcessSignal::Terminate => Signal::SIGTERM,
+        ProcessSignal::Kill => Signal::SIGKILL,
+    }
+}
+
+/// Returns the number of the signal `strsignal` describes as `description`.
+///
+/// portable-pty falls back to "Signal <n>" when `strsignal` has no text.
+fn signal_number(description: &str) -> Option<i32> {
+    if let Some(number) = description.strip_prefix("Signal ") {
+        return number.parse().ok();
+    }
+    Signal::iterator()
+        .map(|signal| signal as i32)
+        .find(|&number| {
+            // SAFETY: strsignal returns null or a NUL-terminated string that
+            // stays valid until the next call on this thread
+            let text = unsafe { nix::libc::strsignal(number) };
+            !text.is_null()
+                && unsafe { std::ffi::CStr::from_ptr(text) }.to_bytes() == description.as_bytes()
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -485,6 +1274,66 @@ mod tests {
         let _ = session.kill(Some(9)).await;
     }
 
+    #[tokio::test]
+    async fn test_session_spawn_login_shell() {
+        let (session, mut rx) = Session::spawn_with(
+            Some("/bin/sh".to_string()),
+            80,
+            24,
+            vec![],
+            Some("~".to_string()),
+            true,
+        )
+        .unwrap();
+        session.start_read_loop();
+        session
+            .write(b"echo \"argv0=$0 cwd=$PWD\"\n")
+            .await
+            .unwrap();
+
+        let expected = format!(
+            "argv0=-sh cwd={}",
+            dirs::home_dir().unwrap().canonicalize().unwrap().display()
+        );
+        let mut output = String::new();
+        let found = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Ok(data) = rx.recv().await {
+                output.push_str(&String::from_utf8_lossy(&data));
+                if output.contains(&expected) {
+                    return true;
+                }
+            }
+            false
+        })
+        .await;
+        assert_eq!(found, Ok(true), "unexpected output: {:?}", output);
+
+        let _ = session.kill(Some(9)).await;
+    }
+
+    #[test]
+    fn test_resolve_cwd() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(resolve_cwd("~").unwrap(), home);
+        assert_eq!(resolve_cwd("/tmp").unwrap(), PathBuf::from("/tmp"));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(matches!(
+            resolve_cwd(file.to_str().unwrap()),
+            Err(SessionError::InvalidCwd(msg)) if msg.contains("not a directory")
+        ));
+        assert!(matches!(
+            resolve_cwd(dir.path().join("missing").to_str().unwrap()),
+            Err(SessionError::InvalidCwd(_))
+        ));
+        assert!(matches!(
+            Session::spawn(None, 80, 24, vec![], Some("/nonexistent/dir".to_string())),
+            Err(SessionError::InvalidCwd(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_session_write() {
         let (session, _rx) =
@@ -519,6 +1368,166 @@ mod tests {
         let _ = session.kill(Some(9)).await;
     }
 
+    #[tokio::test]
+    async fn test_session_resize_coalesces() {
+        let (mut session, _rx) =
+            Session::spawn(Some("/bin/sh".to_string()), 80, 24, vec![], None).unwrap();
+
+        // Resizing to the current size does nothing
+        session.resize(80, 24).await.unwrap();
+        assert!(session.resize_state.lock().unwrap().applied_at.is_none());
+
+        session.resize(100, 30).await.unwrap();
+        for i in 0..100 {
+            session.resize(60 + i, 20 + i / 4).await.unwrap();
+        }
+        assert_eq!(session.size(), (159, 44));
+        {
+            let state = session.resize_state.lock().unwrap();
+            assert_eq!(state.applied, (100, 30));
+            assert_eq!(state.pending, Some((159, 44)));
+        }
+
+        // Only the last size of the burst is applied
+        tokio::time::sleep(RESIZE_INTERVAL * 4).await;
+        {
+            let state = session.resize_state.lock().unwrap();
+            assert_eq!(state.applied, (159, 44));
+            assert_eq!(state.pending, None);
+        }
+
+        // Clean up
+        let _ = session.kill(Some(9)).await;
+    }
+
+    #[test]
+    fn test_resize_state_records_latest_resize() {
+        let first = Instant::now();
+        let second = first + Duration::from_millis(1);
+        let mut state = ResizeState {
+            applied: (80, 24),
+            applied_at: Some(first),
+            in_flight: None,
+            pending: None,
+        };
+
+        // An older resize finishing after a later one started is not recorded
+        state.applied_at = Some(second);
+        state.finish((100, 30), first, true);
+        assert_eq!(state.applied, (80, 24));
+
+        state.finish((120, 40), second, true);
+        assert_eq!(state.applied, (120, 40));
+
+        // A failed resize can be retried with the same size
+        let third = second + RESIZE_INTERVAL;
+        assert_eq!(state.request((132, 40), third), ResizeStep::Apply);
+        state.finish((132, 40), third, false);
+        let fourth = third + RESIZE_INTERVAL;
+        assert_eq!(state.request((132, 40), fourth), ResizeStep::Apply);
+    }
+
+    #[test]
+    fn test_resize_state_returns_to_size_in_flight() {
+        let start = Instant::now();
+        let mut state = ResizeState {
+            applied: (80, 24),
+            applied_at: Some(start),
+            in_flight: None,
+            pending: None,
+        };
+
+        // A deferred resize to B starts
+        let deadline = start + RESIZE_INTERVAL;
+        assert_eq!(state.request((120, 40), start), ResizeStep::Defer(deadline));
+        assert_eq!(state.take_pending(deadline), Some((120, 40)));
+
+        // Back to A while B is still being applied, within the interval
+        let back = deadline + Duration::from_millis(1);
+        assert_eq!(
+            state.request((80, 24), back),
+            ResizeStep::Defer(deadline + RESIZE_INTERVAL)
+        );
+        state.finish((120, 40), deadline, true);
+        assert_eq!(state.applied, (120, 40));
+
+        // The PTY ends at A
+        let next = deadline + RESIZE_INTERVAL;
+        assert_eq!(state.take_pending(next), Some((80, 24)));
+        state.finish((80, 24), next, true);
+        assert_eq!(state.applied, (80, 24));
+        assert_eq!(state.target(), (80, 24));
+    }
+
+    #[tokio::test]
+    async fn test_session_resize_storm_with_wide_output() {
+        let (mut session, mut rx) =
+            Session::spawn(Some("/bin/sh".to_string()), 80, 24, vec![], None).unwrap();
+        session.start_read_loop();
+
+        // CJK and emoji are two columns wide; written as octal escapes so
+        // that only the command's output contains the characters
+        let wide = "\u{4e2d}\u{6587}\u{1f600}\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let escaped: String = wide.bytes().map(|b| format!("\\{:03o}", b)).collect();
+        for i in 0..20u16 {
+            session
+                .write(format!("printf '{}\\n'\n", escaped).as_bytes())
+                .await
+                .unwrap();
+            for j in 0..10u16 {
+                session.resize(40 + i * 3 + j, 10 + j).await.unwrap();
+            }
+        }
+        session.resize(132, 40).await.unwrap();
+        tokio::time::sleep(RESIZE_INTERVAL * 4).await;
+        session
+            .write(b"stty size; echo storm_$((1 + 1))_done\n")
+            .await
+            .unwrap();
+
+        let mut output = Vec::new();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while !String::from_utf8_lossy(&output).contains("storm_2_done") {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Ok(data)) => output.extend_from_slice(&data),
+                Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+                _ => panic!("Timed out waiting for output"),
+            }
+        }
+
+        // Wide characters are not split or mangled by the resizes
+        let output = String::from_utf8(output).expect("Output is not valid UTF-8");
+        assert_eq!(output.matches(wide).count(), 20);
+        assert!(output.contains("40 132"), "PTY size was not updated");
+
+        let results = session
+            .search(&SearchQuery {
+                pattern: wide.to_string(),
+                regex: false,
+                case_sensitive: true,
+                max_results: 100,
+            })
+            .unwrap();
+        assert_eq!(results.matches.len(), 20);
+        assert_eq!(results.matches[0].end - results.matches[0].start, 8);
+
+        // Clean up
+        let _ = session.kill(Some(9)).await;
+    }
+
+    #[tokio::test]
+    async fn test_session_signal() {
+        let (session, _rx) =
+            Session::spawn(Some("/bin/sh".to_string()), 80, 24, vec![], None).unwrap();
+        session.start_read_loop();
+
+        let result = session.signal(ProcessSignal::Interrupt).await;
+        assert!(result.is_ok(), "Failed to signal: {:?}", result.err());
+
+        // Clean up
+        let _ = session.kill(Some(9)).await;
+    }
+
     #[tokio::test]
     async fn test_session_kill() {
         let (session, _rx) =
@@ -585,6 +1594,53 @@ mod tests {
         let _ = session.kill(Some(9)).await;
     }
 
+    #[tokio::test]
+    async fn test_live_receiver_reports_dropped_output() {
+        let (tx, rx) = broadcast::channel(2);
+        let mut rx = OutputReceiver::from(rx);
+        for chunk in ["a", "b", "c", "d", "e"] {
+            tx.send(Bytes::from_static(chunk.as_bytes())).unwrap();
+        }
+        drop(tx);
+
+        assert_eq!(
+            rx.recv().await,
+            Some(SessionOutput::Truncated { dropped_chunks: 3 })
+        );
+        assert_eq!(rx.recv().await, Some(SessionOutput::Data("d".into())));
+        assert_eq!(rx.recv().await, Some(SessionOutput::Data("e".into())));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_subscriber_misses_no_output() {
+        const LEN: usize = 4_000_000;
+        let (session, _rx) =
+            Session::spawn(Some("/bin/sh".to_string()), 80, 24, vec![], None).unwrap();
+        let mut recorder = session.subscribe_with(LagPolicy::Block);
+        assert_eq!(session.subscriber_count(), 2);
+        session.start_read_loop();
+
+        // Octal escapes keep the counted byte out of the echoed command
+        let command = format!("head -c {LEN} /dev/zero | tr '\\000' '\\121'\n");
+        session.write(command.as_bytes()).await.unwrap();
+        // Far more output than the recorder's queue holds arrives meanwhile
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let mut received = 0;
+        while received < LEN {
+            match timeout(Duration::from_secs(10), recorder.recv()).await {
+                Ok(Some(SessionOutput::Data(data))) => {
+                    received += data.iter().filter(|&&b| b == b'Q').count();
+                }
+                other => panic!("unexpected output: {other:?}"),
+            }
+        }
+        assert_eq!(received, LEN);
+
+        let _ = session.kill(Some(9)).await;
+    }
+
     #[tokio::test]
     async fn test_session_env_vars() {
         let env = vec![("TEST_VAR".to_string(), "test_value".to_string())];
@@ -667,4 +1723,163 @@ mod tests {
             assert_eq!(code, 42);
         }
     }
+
+    /// Starts `command` as a background job and returns its process ID.
+    async fn start_background_job(session: &Session, command: &str) -> u32 {
+        let dir = tempfile::TempDir::new().unwrap();
+        let pid_file = dir.path().join("pid");
+        let line = format!("{} & echo $! > {}\n", command, pid_file.display());
+        session.write(line.as_bytes()).await.unwrap();
+
+        for _ in 0..50 {
+            if let Ok(pid) = std::fs::read_to_string(&pid_file) {
+                if let Ok(pid) = pid.trim().parse() {
+                    return pid;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("background job did not start");
+    }
+
+    fn is_alive(pid: u32) -> bool {
+        let pid = sysinfo::Pid::from_u32(pid);
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+        system
+            .process(pid)
+            .is_some_and(|process| process.status() != ProcessStatus::Zombie)
+    }
+
+    #[tokio::test]
+    async fn test_kill_terminates_background_jobs() {
+        let (session, _rx) =
+            Session::spawn(Some("/bin/sh".to_string()), 80, 24, vec![], None).unwrap();
+        session.start_read_loop();
+
+        let job = start_background_job(&session, "sleep 1000").await;
+        assert!(is_alive(job));
+
+        let status = session.kill(Some(9)).await.unwrap();
+        assert!(matches!(status, SessionStatus::Killed(_)), "{:?}", status);
+        assert!(!is_alive(job));
+    }
+
+    /// Returns the read and write ends of a pipe.
+    fn pipe() -> (std::fs::File, std::fs::File) {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        (read.into(), write.into())
+    }
+
+    #[test]
+    fn test_read_chunk_gathers_ready_output() {
+        use std::os::fd::AsRawFd;
+
+        let (mut reader, mut writer) = pipe();
+        let fd = reader.as_raw_fd();
+        let mut buffer = BytesMut::with_capacity(OUTPUT_BUFFER_CAPACITY);
+
+        // More than one read's worth arrives as a single chunk
+        writer.write_all(&[b'x'; 40 * 1024]).unwrap();
+        let chunk = read_chunk(&mut reader, Some(fd), &mut buffer)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk.len(), 40 * 1024);
+
+        // Without the descriptor, each read is its own chunk
+        writer.write_all(&[b'y'; 40 * 1024]).unwrap();
+        let chunk = read_chunk(&mut reader, None, &mut buffer).unwrap().unwrap();
+        assert_eq!(chunk.len(), READ_BUFFER_SIZE);
+        assert!(chunk.iter().all(|&b| b == b'y'));
+
+        drop(writer);
+        let rest = read_chunk(&mut reader, Some(fd), &mut buffer)
+            .unwrap()
+            .unwrap();
+        assert_eq!(rest.len(), 40 * 1024 - READ_BUFFER_SIZE);
+        assert!(read_chunk(&mut reader, Some(fd), &mut buffer)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_read_chunk_reuses_buffer() {
+        use std::os::fd::AsRawFd;
+
+        let (mut reader, mut writer) = pipe();
+        let fd = reader.as_raw_fd();
+        let mut buffer = BytesMut::with_capacity(OUTPUT_BUFFER_CAPACITY);
+
+        let mut start = None;
+        for _ in 0..16 {
+            writer.write_all(&[b'x'; READ_CHUNK_SIZE / 2]).unwrap();
+            let chunk = read_chunk(&mut reader, Some(fd), &mut buffer)
+                .unwrap()
+                .unwrap();
+            let start = *start.get_or_insert(chunk.as_ptr() as usize);
+            let offset = (chunk.as_ptr() as usize).wrapping_sub(start);
+            assert!(
+                offset < OUTPUT_BUFFER_CAPACITY,
+                "chunk was read into a new allocation"
+            );
+        }
+    }
+
+    #[test]
+    fn test_session_status_from_exit_status() {
+        use portable_pty::ExitStatus;
+
+        assert_eq!(
+            SessionStatus::from(&ExitStatus::with_exit_code(3)),
+            SessionStatus::Exited(3)
+        );
+        assert_eq!(
+            SessionStatus::from(&ExitStatus::with_signal("Hangup")),
+            SessionStatus::Killed(Signal::SIGHUP as i32)
+        );
+        assert_eq!(
+            SessionStatus::from(&ExitStatus::with_signal("Killed")),
+            SessionStatus::Killed(Signal::SIGKILL as i32)
+        );
+        assert_eq!(
+            SessionStatus::from(&ExitStatus::with_signal("Signal 40")),
+            SessionStatus::Killed(40)
+        );
+        assert_eq!(
+            SessionStatus::from(&ExitStatus::with_signal("Bogus")),
+            SessionStatus::Terminated
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_for_resets_on_input() {
+        let (session, _rx) =
+            Session::spawn(Some("/bin/sh".to_string()), 80, 24, vec![], None).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(session.idle_for() >= Duration::from_millis(100));
+        session.write(b"\n").await.unwrap();
+        assert!(session.idle_for() < Duration::from_millis(100));
+
+        session.kill(Some(9)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_terminate_session_escalates_to_kill() {
+        let (session, _rx) =
+            Session::spawn(Some("/bin/sh".to_string()), 80, 24, vec![], None).unwrap();
+        session.start_read_loop();
+
+        let job = start_background_job(&session, "sh -c 'trap \"\" HUP TERM; sleep 1000'").await;
+        let signalled = terminate_session(session.pid().unwrap(), Duration::from_millis(300)).await;
+        assert!(
+            signalled >= 2,
+            "expected the shell and its job, got {}",
+            signalled
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!is_alive(job));
+        assert!(session_members(&mut System::new(), session.pid().unwrap()).is_empty());
+    }
 }
@@ -0,0 +1,427 @@
+//! Server-side scrollback for session output.
+//!
+//! Each session keeps its recent output as plain text lines, with escape
+//! sequences removed, so clients can search it with `SessionSearchRequest`
+//! instead of holding the whole scrollback themselves. Lines are numbered
+//! from the start of the session; numbers keep increasing as old lines are
+//! dropped, so a client can tell whether a match is still on its screen.
+
+use std::collections::VecDeque;
+
+use regex::{Regex, RegexBuilder};
+use thiserror::Error;
+
+/// Default number of lines kept per session.
+pub const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
+
+/// Upper bound on the memory used by one session's scrollback.
+pub const MAX_SCROLLBACK_BYTES: usize = 8 * 1024 * 1024;
+
+/// Output without a line break is split into lines of at most this many
+/// bytes.
+pub const MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// Longest search pattern accepted.
+pub const MAX_PATTERN_LEN: usize = 1024;
+
+/// Number of matches returned when the client does not ask for a number.
+pub const DEFAULT_SEARCH_RESULTS: usize = 100;
+
+/// Most matches returned for one search.
+pub const MAX_SEARCH_RESULTS: usize = 1000;
+
+/// Compiled size limit for regular expressions, to bound the memory an
+/// untrusted pattern can use.
+const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+
+/// Errors from searching the scrollback.
+#[derive(Debug, Error)]
+pub enum SearchError {
+    /// The pattern is longer than [`MAX_PATTERN_LEN`].
+    #[error("search pattern is longer than {MAX_PATTERN_LEN} bytes")]
+    PatternTooLong,
+
+    /// The pattern is not a valid regular expression.
+    #[error("invalid search pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+}
+
+/// A scrollback search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// Text or regular expression to find.
+    pub pattern: String,
+    /// Whether `pattern` is a regular expression.
+    pub regex: bool,
+    /// Whether matching is case sensitive.
+    pub case_sensitive: bool,
+    /// Maximum number of matches to return.
+    pub max_results: usize,
+}
+
+/// A match in the scrollback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrollbackMatch {
+    /// Number of the line the match is on.
+    pub line: u64,
+    /// Character offset of the start of the match in the line.
+    pub start: usize,
+    /// Character offset just past the end of the match.
+    pub end: usize,
+    /// The text of the line.
+    pub text: String,
+}
+
+/// Result of a scrollback search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResults {
+    /// Matches, newest first.
+    pub matches: Vec<ScrollbackMatch>,
+    /// Whether matches were left out because of `max_results`.
+    pub truncated: bool,
+    /// Number of the oldest line held.
+    pub first_line: u64,
+    /// Number of the line currently being written.
+    pub last_line: u64,
+}
+
+/// Escape sequence parser state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// After ESC.
+    Start,
+    /// In a CSI sequence (`ESC [`).
+    Csi,
+    /// In an OSC, DCS or similar string sequence.
+    String,
+    /// After ESC inside a string sequence, which may be its terminator.
+    StringEsc,
+}
+
+/// Recent output of a session as plain text lines.
+#[derive(Debug)]
+pub struct Scrollback {
+    /// Completed lines, oldest first.
+    lines: VecDeque<String>,
+    /// Maximum number of completed lines kept.
+    capacity: usize,
+    /// Number of the oldest line in `lines`.
+    first_line: u64,
+    /// Bytes held in `lines`.
+    bytes: usize,
+    /// The line currently being written.
+    current: Vec<u8>,
+    /// Whether a carriage return was seen, so that further text overwrites
+    /// the current line.
+    carriage_return: bool,
+    escape: Escape,
+}
+
+impl Scrollback {
+    /// Creates a scrollback keeping up to `capacity` lines.
+    ///
+    /// A capacity of zero keeps nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            capacity,
+            first_line: 0,
+            bytes: 0,
+            current: Vec::new(),
+            carriage_return: false,
+            escape: Escape::None,
+        }
+    }
+
+    /// Appends session output.
+    pub fn push(&mut self, data: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut rest = data;
+        while !rest.is_empty() {
+            // Copy runs of text at once rather than byte by byte
+            if self.escape == Escape::None {
+                let run = rest.iter().position(|&b| !is_text(b)).unwrap_or(rest.len());
+                if run > 0 {
+                    self.push_text(&rest[..run]);
+                    rest = &rest[run..];
+                    continue;
+                }
+            }
+            let byte = rest[0];
+            rest = &rest[1..];
+
+            self.escape = match (self.escape, byte) {
+                (Escape::None, 0x1b) => Escape::Start,
+                (Escape::None, _) => {
+                    self.push_byte(byte);
+                    Escape::None
+                }
+                (Escape::Start, b'[') => Escape::Csi,
+                (Escape::Start, b']' | b'P' | b'X' | b'^' | b'_') => Escape::String,
+                // Two-byte sequences, or the intermediate byte of a
+                // character set selection, which is followed by one more
+                (Escape::Start, 0x20..=0x2f) => Escape::Start,
+                (Escape::Start, _) => Escape::None,
+                (Escape::Csi, 0x40..=0x7e) => Escape::None,
+                (Escape::Csi, _) => Escape::Csi,
+                (Escape::String, 0x07) => Escape::None,
+                (Escape::String | Escape::StringEsc, 0x1b) => Escape::StringEsc,
+                (Escape::StringEsc, b'\\') => Escape::None,
+                (Escape::String | Escape::StringEsc, _) => Escape::String,
+            };
+        }
+    }
+
+    /// Number of the oldest line held.
+    pub fn first_line(&self) -> u64 {
+        self.first_line
+    }
+
+    /// Number of the line currently being written.
+    pub fn last_line(&self) -> u64 {
+        self.first_line + self.lines.len() as u64
+    }
+
+    /// Searches the scrollback, including the line currently being written.
+    pub fn search(&self, query: &SearchQuery) -> Result<SearchResults, SearchError> {
+        let matcher = build_matcher(query)?;
+        let current = String::from_utf8_lossy(&self.current);
+        let lines = std::iter::once(current.as_ref())
+            .chain(self.lines.iter().rev().map(String::as_str))
+            .zip((self.first_line..=self.last_line()).rev());
+
+        let mut matches = Vec::new();
+        let mut truncated = false;
+        'lines: for (text, line) in lines {
+            let mut found: Vec<_> = matcher.find_iter(text).filter(|m| !m.is_empty()).collect();
+            // Newest first, within the line too
+            found.reverse();
+            for m in found {
+                if matches.len() == query.max_results {
+                    truncated = true;
+                    break 'lines;
+                }
+                matches.push(ScrollbackMatch {
+                    line,
+                    start: text[..m.start()].chars().count(),
+                    end: text[..m.end()].chars().count(),
+                    text: text.to_string(),
+                });
+            }
+        }
+
+        Ok(SearchResults {
+            matches,
+            truncated,
+            first_line: self.first_line,
+            last_line: self.last_line(),
+        })
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => {
+                self.carriage_return = false;
+                self.finish_line();
+            }
+            b'\r' => self.carriage_return = true,
+            _ if is_text(byte) => self.push_text(&[byte]),
+            // Other control characters are not text
+            _ => {}
+        }
+    }
+
+    /// Appends text to the current line, splitting lines that grow too long.
+    fn push_text(&mut self, mut text: &[u8]) {
+        if self.carriage_return {
+            self.current.clear();
+            self.carriage_return = false;
+        }
+        while !text.is_empty() {
+            let (head, tail) = text.split_at(text.len().min(MAX_LINE_BYTES - self.current.len()));
+            self.current.extend_from_slice(head);
+            if self.current.len() >= MAX_LINE_BYTES {
+                self.finish_line();
+            }
+            text = tail;
+        }
+    }
+
+    fn finish_line(&mut self) {
+        let line = String::from_utf8_lossy(&self.current).into_owned();
+        self.current.clear();
+        self.bytes += line.len();
+        self.lines.push_back(line);
+
+        while self.lines.len() > self.capacity || self.bytes > MAX_SCROLLBACK_BYTES {
+            let Some(dropped) = self.lines.pop_front() else {
+                break;
+            };
+            self.bytes -= dropped.len();
+            self.first_line += 1;
+        }
+    }
+}
+
+/// Returns true if `byte` is kept as line text.
+fn is_text(byte: u8) -> bool {
+    byte == b'\t' || (byte >= 0x20 && byte != 0x7f)
+}
+
+/// Compiles the matcher for a query.
+fn build_matcher(query: &SearchQuery) -> Result<Regex, SearchError> {
+    if query.pattern.len() > MAX_PATTERN_LEN {
+        return Err(SearchError::PatternTooLong);
+    }
+    let pattern = if query.regex {
+        query.pattern.clone()
+    } else {
+        regex::escape(&query.pattern)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!query.case_sensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(SearchError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pattern: &str) -> SearchQuery {
+        SearchQuery {
+            pattern: pattern.to_string(),
+            regex: false,
+            case_sensitive: false,
+            max_results: DEFAULT_SEARCH_RESULTS,
+        }
+    }
+
+    #[test]
+    fn test_push_strips_escapes() {
+        let mut scrollback = Scrollback::new(100);
+        scrollback.push(b"\x1b[1;32mgreen\x1b[0m text\r\n");
+        scrollback.push(b"\x1b]0;window title\x07prompt$ ");
+        scrollback.push(b"\x1b]133;A\x1b\\ls\r\n");
+        // Progress output overwrites the line
+        scrollback.push(b"10%\r50%\r100%\n");
+
+        assert_eq!(
+            scrollback.lines,
+            ["green text", "prompt$ ls", "100%"]
+                .map(String::from)
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn test_push_across_chunks() {
+        let mut scrollback = Scrollback::new(100);
+        scrollback.push(b"abc\x1b[");
+        scrollback.push(b"31mdef\xc3");
+        scrollback.push(b"\xa9\n");
+
+        assert_eq!(scrollback.lines, vec!["abcdef\u{e9}".to_string()]);
+    }
+
+    #[test]
+    fn test_long_lines_split() {
+        let mut scrollback = Scrollback::new(100);
+        scrollback.push(&[b'a'; MAX_LINE_BYTES - 1]);
+        scrollback.push(&[b'b'; MAX_LINE_BYTES + 2]);
+        scrollback.push(b"\n");
+
+        let lengths: Vec<_> = scrollback.lines.iter().map(String::len).collect();
+        assert_eq!(lengths, [MAX_LINE_BYTES, MAX_LINE_BYTES, 1]);
+        assert!(scrollback.lines[0].ends_with('b'));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut scrollback = Scrollback::new(2);
+        scrollback.push(b"one\ntwo\nthree\nfour");
+
+        assert_eq!(scrollback.first_line(), 1);
+        assert_eq!(scrollback.last_line(), 3);
+        assert!(scrollback.search(&query("one")).unwrap().matches.is_empty());
+
+        let results = scrollback.search(&query("four")).unwrap();
+        assert_eq!(results.matches[0].line, 3);
+
+        let mut disabled = Scrollback::new(0);
+        disabled.push(b"hidden\n");
+        assert!(disabled
+            .search(&query("hidden"))
+            .unwrap()
+            .matches
+            .is_empty());
+    }
+
+    #[test]
+    fn test_search() {
+        let mut scrollback = Scrollback::new(100);
+        scrollback.push("error: café failed\nok\nERROR again, error\n$ ".as_bytes());
+
+        let results = scrollback.search(&query("error")).unwrap();
+        assert!(!results.truncated);
+        let found: Vec<_> = results
+            .matches
+            .iter()
+            .map(|m| (m.line, m.start, m.end))
+            .collect();
+        assert_eq!(found, vec![(2, 13, 18), (2, 0, 5), (0, 0, 5)]);
+        assert_eq!(results.matches[2].text, "error: café failed");
+
+        // Offsets count characters, not bytes
+        let results = scrollback.search(&query("failed")).unwrap();
+        assert_eq!((results.matches[0].start, results.matches[0].end), (12, 18));
+
+        let mut case_sensitive = query("ERROR");
+        case_sensitive.case_sensitive = true;
+        assert_eq!(scrollback.search(&case_sensitive).unwrap().matches.len(), 1);
+
+        // The prompt is searchable before its line ends
+        assert_eq!(scrollback.search(&query("$")).unwrap().matches[0].line, 3);
+
+        let mut limited = query("error");
+        limited.max_results = 2;
+        let results = scrollback.search(&limited).unwrap();
+        assert!(results.truncated);
+        assert_eq!(results.matches.len(), 2);
+    }
+
+    #[test]
+    fn test_search_regex() {
+        let mut scrollback = Scrollback::new(100);
+        scrollback.push(b"exit 0\nexit 127\n");
+
+        let mut regex = query(r"exit [1-9]\d*");
+        regex.regex = true;
+        let results = scrollback.search(&regex).unwrap();
+        assert_eq!(results.matches.len(), 1);
+        assert_eq!(results.matches[0].line, 1);
+
+        // Literal searches do not interpret the pattern
+        assert!(scrollback
+            .search(&query(r"exit [1-9]\d*"))
+            .unwrap()
+            .matches
+            .is_empty());
+
+        regex.pattern = "(".to_string();
+        assert!(matches!(
+            scrollback.search(&regex),
+            Err(SearchError::InvalidPattern(_))
+        ));
+        regex.pattern = "a".repeat(MAX_PATTERN_LEN + 1);
+        assert!(matches!(
+            scrollback.search(&regex),
+            Err(SearchError::PatternTooLong)
+        ));
+    }
+}
@@ -0,0 +1,505 @@
+//! Shell integration sequence parsing.
+//!
+//! Shells configured for terminal integration emit OSC escape sequences that
+//! describe the prompt, the running command and the working directory. This
+//! module scans PTY output for those sequences so the daemon can report the
+//! current directory and the last command's exit status for each session.
+//! It also picks out the bells and desktop notifications programs ring so
+//! they can be forwarded to clients.
+//!
+//! Supported sequences:
+//! - `OSC 7 ; file://host/path` - working directory
+//! - `OSC 133 ; A|B|C|D[;exit]` - prompt and command markers (FinalTerm)
+//! - `OSC 633 ; E ; cmdline` and `OSC 633 ; P ; Cwd=path` - VS Code extensions
+//! - `OSC 1337 ; CurrentDir=path` - iTerm2 working directory
+//! - `BEL` outside of a sequence - terminal bell
+//! - `OSC 9 ; message` - desktop notification (iTerm2)
+//! - `OSC 777 ; notify ; title ; body` - desktop notification (rxvt, VTE)
+//!
+//! Sequences may be terminated by BEL or ST and may span multiple reads.
+
+/// Maximum length of a buffered OSC payload. Longer sequences are discarded.
+const MAX_OSC_LEN: usize = 4096;
+
+/// Maximum number of characters kept from a notification title.
+const MAX_ALERT_TITLE_LEN: usize = 128;
+
+/// Maximum number of characters kept from a notification body.
+const MAX_ALERT_BODY_LEN: usize = 1024;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Shell state reported through integration sequences.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellMetadata {
+    /// Current working directory of the shell.
+    pub cwd: Option<String>,
+    /// Exit status of the most recently finished command.
+    pub last_exit_code: Option<i32>,
+    /// Command line of the most recently started command.
+    pub last_command: Option<String>,
+    /// Whether a command is currently executing.
+    pub command_running: bool,
+}
+
+/// A command that ran to completion, as reported by shell integration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinishedCommand {
+    /// Command line, if the shell reported it (`OSC 633 ; E`).
+    pub command: Option<String>,
+    /// Working directory the command was started in.
+    pub cwd: Option<String>,
+    /// Exit status, if the shell reported it.
+    pub exit_code: Option<i32>,
+}
+
+/// Something a program in the session asked the terminal to bring to the
+/// user's attention.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminalAlert {
+    /// The terminal bell (`BEL`).
+    Bell,
+    /// A desktop notification (`OSC 9` or `OSC 777`).
+    Notify {
+        /// Title, if the sequence carried one.
+        title: Option<String>,
+        /// Notification text.
+        body: String,
+    },
+}
+
+/// Scanner state between calls to [`ShellIntegrationParser::feed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Plain output.
+    Ground,
+    /// Seen ESC outside of an OSC sequence.
+    Escape,
+    /// Inside an OSC payload.
+    Osc,
+    /// Seen ESC inside an OSC payload (possible ST).
+    OscEscape,
+}
+
+/// Incremental parser for shell integration sequences.
+///
+/// Output is passed through unchanged; the parser only observes it.
+#[derive(Debug)]
+pub struct ShellIntegrationParser {
+    state: State,
+    buffer: Vec<u8>,
+    overflow: bool,
+    metadata: ShellMetadata,
+    /// Command line reported for the next or running command.
+    pending_command: Option<String>,
+    /// Working directory of the running command.
+    command_cwd: Option<String>,
+    /// Commands finished since the last call to `take_finished`.
+    finished: Vec<FinishedCommand>,
+    /// Alerts raised since the last call to `take_alerts`.
+    alerts: Vec<TerminalAlert>,
+}
+
+impl ShellIntegrationParser {
+    /// Creates a new parser with empty metadata.
+    pub fn new() -> Self {
+        Self {
+            state: State::Ground,
+            buffer: Vec::new(),
+            overflow: false,
+            metadata: ShellMetadata::default(),
+            pending_command: None,
+            command_cwd: None,
+            finished: Vec::new(),
+            alerts: Vec::new(),
+        }
+    }
+
+    /// Returns the commands that finished since the last call, oldest first.
+    pub fn take_finished(&mut self) -> Vec<FinishedCommand> {
+        std::mem::take(&mut self.finished)
+    }
+
+    /// Returns the bells and notifications raised since the last call,
+    /// oldest first.
+    pub fn take_alerts(&mut self) -> Vec<TerminalAlert> {
+        std::mem::take(&mut self.alerts)
+    }
+
+    /// Returns the metadata collected so far.
+    pub fn metadata(&self) -> &ShellMetadata {
+        &self.metadata
+    }
+
+    /// Scans a chunk of PTY output.
+    ///
+    /// Returns true if the metadata changed.
+    pub fn feed(&mut self, data: &[u8]) -> bool {
+        let before = self.metadata.clone();
+
+        let mut rest = data;
+        while !rest.is_empty() {
+            // Plain output only matters up to the next escape or bell
+            if self.state == State::Ground {
+                match rest.iter().position(|&b| b == ESC || b == BEL) {
+                    Some(skip) => rest = &rest[skip..],
+                    None => break,
+                }
+            }
+            let byte = rest[0];
+            rest = &rest[1..];
+
+            self.state = match (self.state, byte) {
+                (State::Ground, ESC) => State::Escape,
+                (State::Ground, BEL) => {
+                    self.alerts.push(TerminalAlert::Bell);
+                    State::Ground
+                }
+                (State::Ground, _) => State::Ground,
+                (State::Escape, b']') => {
+                    self.buffer.clear();
+                    self.overflow = false;
+                    State::Osc
+                }
+                (State::Escape, ESC) => State::Escape,
+                (State::Escape, _) => State::Ground,
+                (State::Osc, BEL) => {
+                    self.finish_osc();
+                    State::Ground
+                }
+                (State::Osc, ESC) => State::OscEscape,
+                (State::Osc, _) => {
+                    self.push(byte);
+                    State::Osc
+                }
+                (State::OscEscape, b'\\') => {
+                    self.finish_osc();
+                    State::Ground
+                }
+                // ESC ] restarts the sequence; any other byte aborts it
+                (State::OscEscape, b']') => {
+                    self.buffer.clear();
+                    self.overflow = false;
+                    State::Osc
+                }
+                (State::OscEscape, ESC) => State::Escape,
+                (State::OscEscape, _) => State::Ground,
+            };
+        }
+
+        self.metadata != before
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.buffer.len() < MAX_OSC_LEN {
+            self.buffer.push(byte);
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    fn finish_osc(&mut self) {
+        if self.overflow {
+            self.buffer.clear();
+            return;
+        }
+
+        let payload = String::from_utf8_lossy(&self.buffer).into_owned();
+        self.buffer.clear();
+        self.handle_osc(&payload);
+    }
+
+    fn handle_osc(&mut self, payload: &str) {
+        let (code, rest) = payload.split_once(';').unwrap_or((payload, ""));
+
+        match code {
+            "7" => {
+                if let Some(path) = parse_file_url(rest) {
+                    self.metadata.cwd = Some(path);
+                }
+            }
+            "133" | "633" => {
+                let (kind, args) = rest.split_once(';').unwrap_or((rest, ""));
+                match kind {
+                    "A" | "B" => self.metadata.command_running = false,
+                    "C" => {
+                        self.metadata.command_running = true;
+                        self.command_cwd = self.metadata.cwd.clone();
+                    }
+                    "D" => {
+                        let code = args.split(';').next().unwrap_or("");
+                        let exit_code = code.parse::<i32>().ok();
+                        if exit_code.is_some() {
+                            self.metadata.last_exit_code = exit_code;
+                        }
+                        // Shells also send D at a prompt where no command ran
+                        if self.metadata.command_running {
+                            self.finished.push(FinishedCommand {
+                                command: self.pending_command.take(),
+                                cwd: self.command_cwd.take(),
+                                exit_code,
+                            });
+                        }
+                        self.metadata.command_running = false;
+                    }
+                    "E" if code == "633" => {
+                        let command = unescape_vscode(args.split(';').next().unwrap_or(""));
+                        self.pending_command = Some(command.clone());
+                        self.metadata.last_command = Some(command);
+                    }
+                    "P" if code == "633" => {
+                        if let Some(cwd) = args.strip_prefix("Cwd=") {
+                            self.metadata.cwd = Some(unescape_vscode(cwd));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "1337" => {
+                if let Some(cwd) = rest.strip_prefix("CurrentDir=") {
+                    self.metadata.cwd = Some(cwd.to_string());
+                }
+            }
+            // ConEmu reuses OSC 9 with numeric subcommands (progress, tab
+            // titles); those are not notifications
+            "9" => {
+                let subcommand = rest.split(';').next().unwrap_or("");
+                if !subcommand.is_empty() && subcommand.bytes().all(|b| b.is_ascii_digit()) {
+                    return;
+                }
+                if let Some(body) = alert_text(rest, MAX_ALERT_BODY_LEN) {
+                    self.alerts
+                        .push(TerminalAlert::Notify { title: None, body });
+                }
+            }
+            "777" => {
+                let mut parts = rest.splitn(3, ';');
+                if parts.next() != Some("notify") {
+                    return;
+                }
+                let title = parts
+                    .next()
+                    .and_then(|title| alert_text(title, MAX_ALERT_TITLE_LEN));
+                let body = parts
+                    .next()
+                    .and_then(|body| alert_text(body, MAX_ALERT_BODY_LEN));
+                if let Some(body) = body.or_else(|| title.clone()) {
+                    let title = title.filter(|title| *title != body);
+                    self.alerts.push(TerminalAlert::Notify { title, body });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Default for ShellIntegrationParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Strips control characters from notification text and truncates it to
+/// `max_len` characters; returns None if nothing is left.
+fn alert_text(text: &str, max_len: usize) -> Option<String> {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(max_len)
+        .collect();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Extracts the percent-decoded path from a `file://host/path` URL.
+fn parse_file_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+/// Parses two ASCII hex digits into a byte.
+fn hex_byte(digits: &[u8]) -> Option<u8> {
+    let digits = std::str::from_utf8(digits).ok()?;
+    u8::from_str_radix(digits, 16).ok()
+}
+
+/// Decodes `%XX` escapes, leaving malformed escapes untouched.
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(byte) = hex_byte(&bytes[i + 1..i + 3]) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Decodes the `\\` and `\xNN` escapes used by VS Code shell integration.
+fn unescape_vscode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if bytes.get(i + 1) == Some(&b'\\') {
+                out.push(b'\\');
+                i += 2;
+                continue;
+            }
+            if bytes.get(i + 1) == Some(&b'x') && i + 3 < bytes.len() {
+                if let Some(byte) = hex_byte(&bytes[i + 2..i + 4]) {
+                    out.push(byte);
+                    i += 4;
+                    continue;
+                }
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc7_cwd() {
+        let mut parser = ShellIntegrationParser::new();
+        assert!(parser.feed(b"\x1b]7;file://host/home/user/my%20dir\x07"));
+        assert_eq!(parser.metadata().cwd.as_deref(), Some("/home/user/my dir"));
+    }
+
+    #[test]
+    fn test_osc133_exit_status() {
+        let mut parser = ShellIntegrationParser::new();
+        parser.feed(b"\x1b]133;C\x07");
+        assert!(parser.metadata().command_running);
+
+        parser.feed(b"output\r\n\x1b]133;D;127\x1b\\\x1b]133;A\x07$ ");
+        assert!(!parser.metadata().command_running);
+        assert_eq!(parser.metadata().last_exit_code, Some(127));
+    }
+
+    #[test]
+    fn test_sequence_split_across_reads() {
+        let mut parser = ShellIntegrationParser::new();
+        assert!(!parser.feed(b"text\x1b]7;file://h"));
+        assert!(!parser.feed(b"ost/tmp\x1b"));
+        // Terminator split after ESC still completes the sequence
+        assert!(parser.feed(b"\\"));
+        assert_eq!(parser.metadata().cwd.as_deref(), Some("/tmp"));
+    }
+
+    #[test]
+    fn test_osc633_command_and_cwd() {
+        let mut parser = ShellIntegrationParser::new();
+        parser.feed(b"\x1b]633;E;echo a\\x3bb\x07\x1b]633;P;Cwd=/srv\x07");
+        assert_eq!(parser.metadata().last_command.as_deref(), Some("echo a;b"));
+        assert_eq!(parser.metadata().cwd.as_deref(), Some("/srv"));
+    }
+
+    #[test]
+    fn test_finished_commands() {
+        let mut parser = ShellIntegrationParser::new();
+        parser.feed(b"\x1b]7;file://host/srv\x07\x1b]633;E;make\x07\x1b]133;C\x07");
+        // A directory change reported before the command finishes
+        parser.feed(b"\x1b]7;file://host/tmp\x07\x1b]133;D;2\x07\x1b]133;A\x07");
+        // D at an empty prompt is not a command
+        parser.feed(b"\x1b]133;D\x07\x1b]133;C\x07\x1b]133;D;0\x07");
+
+        assert_eq!(
+            parser.take_finished(),
+            vec![
+                FinishedCommand {
+                    command: Some("make".to_string()),
+                    cwd: Some("/srv".to_string()),
+                    exit_code: Some(2),
+                },
+                FinishedCommand {
+                    command: None,
+                    cwd: Some("/tmp".to_string()),
+                    exit_code: Some(0),
+                },
+            ]
+        );
+        assert!(parser.take_finished().is_empty());
+    }
+
+    #[test]
+    fn test_iterm2_current_dir() {
+        let mut parser = ShellIntegrationParser::new();
+        parser.feed(b"\x1b]1337;CurrentDir=/var/log\x07");
+        assert_eq!(parser.metadata().cwd.as_deref(), Some("/var/log"));
+    }
+
+    #[test]
+    fn test_unrelated_sequences_ignored() {
+        let mut parser = ShellIntegrationParser::new();
+        assert!(!parser.feed(b"\x1b[1;31mred\x1b[0m\x1b]0;title\x07"));
+        assert_eq!(parser.metadata(), &ShellMetadata::default());
+    }
+
+    #[test]
+    fn test_bell_and_notifications() {
+        let mut parser = ShellIntegrationParser::new();
+        // BEL terminating an OSC sequence is not a bell
+        assert!(!parser.feed(b"done\x07\x1b]0;title\x07"));
+        parser.feed(b"\x1b]9;Build finished\x07");
+        parser.feed(b"\x1b]777;notify;make;exited with 2\x1b\\");
+        parser.feed(b"\x1b]777;notify;Tests passed\x07");
+
+        assert_eq!(
+            parser.take_alerts(),
+            vec![
+                TerminalAlert::Bell,
+                TerminalAlert::Notify {
+                    title: None,
+                    body: "Build finished".to_string(),
+                },
+                TerminalAlert::Notify {
+                    title: Some("make".to_string()),
+                    body: "exited with 2".to_string(),
+                },
+                TerminalAlert::Notify {
+                    title: None,
+                    body: "Tests passed".to_string(),
+                },
+            ]
+        );
+        assert!(parser.take_alerts().is_empty());
+    }
+
+    #[test]
+    fn test_non_notification_osc9_ignored() {
+        let mut parser = ShellIntegrationParser::new();
+        // ConEmu progress, a non-notify OSC 777 and an empty message
+        parser.feed(b"\x1b]9;4;1;50\x07\x1b]777;preexec\x07\x1b]9;\x07");
+        assert!(parser.take_alerts().is_empty());
+    }
+
+    #[test]
+    fn test_oversized_sequence_discarded() {
+        let mut parser = ShellIntegrationParser::new();
+        let mut data = b"\x1b]7;file://host/".to_vec();
+        data.extend(std::iter::repeat_n(b'a', MAX_OSC_LEN));
+        data.push(BEL);
+        assert!(!parser.feed(&data));
+        assert_eq!(parser.metadata().cwd, None);
+    }
+}
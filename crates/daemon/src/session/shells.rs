@@ -0,0 +1,225 @@
+//! Shells that sessions run.
+//!
+//! Sessions run `session.default_shell` unless the client or a session
+//! template asks for another. Shells from templates are trusted, since the
+//! operator wrote them; a shell named in `SessionCreate.shell` must be in
+//! `session.allowed_shells` and be an executable file.
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// Allowlist entry standing for the shells listed in `/etc/shells`.
+pub const ETC_SHELLS: &str = "/etc/shells";
+
+/// Errors checking a shell requested by a client.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ShellError {
+    /// The shell is not in `session.allowed_shells`.
+    #[error("shell {0} is not in the session allowed_shells")]
+    NotPermitted(String),
+
+    /// No such file, or no such command in `PATH`.
+    #[error("shell {0} was not found")]
+    NotFound(String),
+
+    /// The shell is not an executable file.
+    #[error("shell {0} is not an executable file")]
+    NotExecutable(String),
+}
+
+/// The default shell of sessions and the shells clients may ask for.
+///
+/// Allowlist entries are paths or command names, [`ETC_SHELLS`] for the
+/// shells listed in that file, or `*` for any executable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellPolicy {
+    default_shell: Option<String>,
+    allowed: Vec<String>,
+    etc_shells: PathBuf,
+}
+
+impl Default for ShellPolicy {
+    /// Runs `$SHELL` by default and allows any shell.
+    fn default() -> Self {
+        Self::new(None, vec!["*".to_string()])
+    }
+}
+
+impl ShellPolicy {
+    /// Creates a policy running `default_shell` (or `$SHELL` when `None`)
+    /// and allowing the shells in `allowed`.
+    pub fn new(default_shell: Option<String>, allowed: Vec<String>) -> Self {
+        Self {
+            default_shell,
+            allowed,
+            etc_shells: PathBuf::from(ETC_SHELLS),
+        }
+    }
+
+    /// Reads the [`ETC_SHELLS`] entry from `path` instead, for tests.
+    pub fn with_etc_shells(mut self, path: impl Into<PathBuf>) -> Self {
+        self.etc_shells = path.into();
+        self
+    }
+
+    /// Returns the shell sessions run when none is requested.
+    pub fn default_shell(&self) -> Option<&str> {
+        self.default_shell.as_deref()
+    }
+
+    /// Checks that sessions may run a shell requested by a client.
+    ///
+    /// Permission is checked first, so clients cannot probe for files
+    /// outside the allowlist.
+    pub fn check(&self, requested: &str) -> Result<(), ShellError> {
+        let path = resolve(requested);
+        if !self.allows(requested, path.as_deref()) {
+            return Err(ShellError::NotPermitted(requested.to_string()));
+        }
+
+        let path = path.ok_or_else(|| ShellError::NotFound(requested.to_string()))?;
+        let metadata =
+            std::fs::metadata(&path).map_err(|_| ShellError::NotFound(requested.to_string()))?;
+        if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+            return Err(ShellError::NotExecutable(requested.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Returns true if the allowlist covers `requested`, found at `path`.
+    fn allows(&self, requested: &str, path: Option<&Path>) -> bool {
+        if self.allowed.iter().any(|entry| entry == "*") {
+            return true;
+        }
+        // Compare canonical paths, so /bin/bash matches /usr/bin/bash where
+        // /bin links to /usr/bin
+        let Some(path) = path.and_then(|path| path.canonicalize().ok()) else {
+            return false;
+        };
+        let matches = |entry: &str| {
+            resolve(entry)
+                .and_then(|entry| entry.canonicalize().ok())
+                .is_some_and(|entry| entry == path)
+        };
+
+        self.allowed.iter().any(|entry| {
+            if entry == ETC_SHELLS {
+                self.etc_shells_entries().iter().any(|shell| matches(shell))
+            } else {
+                entry == requested || matches(entry)
+            }
+        })
+    }
+
+    /// Returns the shells listed in the [`ETC_SHELLS`] file.
+    fn etc_shells_entries(&self) -> Vec<String> {
+        std::fs::read_to_string(&self.etc_shells)
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Returns the path of a shell given as a path or a command in `PATH`.
+fn resolve(shell: &str) -> Option<PathBuf> {
+    if shell.contains('/') {
+        Some(PathBuf::from(shell))
+    } else {
+        which::which(shell).ok()
+    }
+}
+
+/// Returns true if `entry` is a valid `allowed_shells` entry.
+pub fn is_valid_entry(entry: &str) -> bool {
+    entry == "*" || (!entry.is_empty() && !entry.chars().any(char::is_whitespace))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_file(dir: &TempDir, name: &str, contents: &str, mode: u32) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_default_allows_any_executable() {
+        let policy = ShellPolicy::default();
+        assert_eq!(policy.default_shell(), None);
+        assert_eq!(policy.check("/bin/sh"), Ok(()));
+        assert_eq!(policy.check("sh"), Ok(()));
+        assert_eq!(
+            policy.check("/nonexistent/shell"),
+            Err(ShellError::NotFound("/nonexistent/shell".to_string()))
+        );
+        assert_eq!(
+            policy.check("no-such-shell-here"),
+            Err(ShellError::NotFound("no-such-shell-here".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_explicit_allowlist() {
+        let dir = TempDir::new().unwrap();
+        let allowed = write_file(&dir, "allowed-sh", "#!/bin/sh\n", 0o755);
+        let other = write_file(&dir, "other-sh", "#!/bin/sh\n", 0o755);
+        let data = write_file(&dir, "data", "", 0o644);
+
+        let policy = ShellPolicy::new(None, vec![allowed.clone(), data.clone()]);
+        assert_eq!(policy.check(&allowed), Ok(()));
+        assert_eq!(
+            policy.check(&other),
+            Err(ShellError::NotPermitted(other.clone()))
+        );
+        assert_eq!(
+            policy.check(&data),
+            Err(ShellError::NotExecutable(data.clone()))
+        );
+        // Files outside the allowlist are refused whether or not they exist
+        assert_eq!(
+            policy.check("/nonexistent/shell"),
+            Err(ShellError::NotPermitted("/nonexistent/shell".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_etc_shells_allowlist() {
+        let dir = TempDir::new().unwrap();
+        let listed = write_file(&dir, "listed-sh", "#!/bin/sh\n", 0o755);
+        let unlisted = write_file(&dir, "unlisted-sh", "#!/bin/sh\n", 0o755);
+        let etc_shells = write_file(
+            &dir,
+            "shells",
+            &format!("# valid login shells\n\n{}\n", listed),
+            0o644,
+        );
+
+        let policy = ShellPolicy::new(Some("/bin/sh".to_string()), vec![ETC_SHELLS.to_string()])
+            .with_etc_shells(etc_shells);
+        assert_eq!(policy.default_shell(), Some("/bin/sh"));
+        assert_eq!(policy.check(&listed), Ok(()));
+        assert_eq!(
+            policy.check(&unlisted),
+            Err(ShellError::NotPermitted(unlisted.clone()))
+        );
+    }
+
+    #[test]
+    fn test_valid_entries() {
+        assert!(is_valid_entry("*"));
+        assert!(is_valid_entry("/etc/shells"));
+        assert!(is_valid_entry("/usr/bin/fish"));
+        assert!(is_valid_entry("zsh"));
+        assert!(!is_valid_entry(""));
+        assert!(!is_valid_entry("/bin/bash -l"));
+    }
+}
@@ -0,0 +1,227 @@
+//! Streaming session output to attached clients.
+//!
+//! Attaching to a session starts a stream that sends its output to the
+//! device in `SessionData` messages until the device detaches, disconnects
+//! or the session ends. Clients are live viewers: one that falls behind
+//! skips the output it missed and is sent `SessionOutputTruncated` in its
+//! place, rather than holding up the session or other clients.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use protocol::messages::{DataStream, Message, SessionData, SessionOutputTruncated};
+use protocol::DeviceId;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use super::pty::{OutputReceiver, SessionId, SessionOutput};
+
+/// A message for a device, produced outside of a request/response exchange.
+pub type OutputMessage = (DeviceId, Message);
+
+/// Stop channel of a stream.
+struct StreamHandle {
+    id: u64,
+    _stop: oneshot::Sender<()>,
+}
+
+/// Session output streamed to devices.
+///
+/// Each stream runs in its own task, which queues messages on `outbound`.
+/// Streams are keyed by session and device: attaching again replaces the
+/// running stream, and dropping a stream's handle stops it.
+pub struct OutputStreams {
+    outbound: mpsc::UnboundedSender<OutputMessage>,
+    streams: Mutex<HashMap<(SessionId, DeviceId), StreamHandle>>,
+    next_id: AtomicU64,
+}
+
+impl OutputStreams {
+    /// Creates an empty set of streams that queue messages on `outbound`.
+    pub fn new(outbound: mpsc::UnboundedSender<OutputMessage>) -> Self {
+        Self {
+            outbound,
+            streams: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Streams a session's output from `receiver` to a device.
+    pub fn start(
+        self: &Arc<Self>,
+        session_id: SessionId,
+        device_id: DeviceId,
+        receiver: OutputReceiver,
+    ) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (stop, stopped) = oneshot::channel();
+        let key = (session_id, device_id);
+        // Dropping the previous handle stops an earlier stream to the device
+        self.lock()
+            .insert(key.clone(), StreamHandle { id, _stop: stop });
+
+        let streams = Arc::clone(self);
+        tokio::spawn(async move {
+            streams.forward(&key, receiver, stopped).await;
+            let mut running = streams.lock();
+            if running.get(&key).is_some_and(|handle| handle.id == id) {
+                running.remove(&key);
+            }
+        });
+    }
+
+    /// Stops streaming a session to a device.
+    ///
+    /// Returns false if the session was not streamed to the device.
+    pub fn stop(&self, session_id: &str, device_id: &DeviceId) -> bool {
+        self.lock()
+            .remove(&(session_id.to_string(), *device_id))
+            .is_some()
+    }
+
+    /// Stops the streams of a session, when it is killed.
+    pub fn close_session(&self, session_id: &str) {
+        self.lock().retain(|(id, _), _| id != session_id);
+    }
+
+    /// Stops the streams to a device, when it disconnects.
+    pub fn close_device(&self, device_id: &DeviceId) {
+        self.lock().retain(|(_, id), _| id != device_id);
+    }
+
+    /// Returns the number of running streams.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no output is being streamed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(SessionId, DeviceId), StreamHandle>> {
+        self.streams.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn forward(
+        &self,
+        key: &(SessionId, DeviceId),
+        mut receiver: OutputReceiver,
+        mut stopped: oneshot::Receiver<()>,
+    ) {
+        let (session_id, device_id) = key;
+        loop {
+            let output = tokio::select! {
+                biased;
+                _ = &mut stopped => {
+                    debug!(session_id = %session_id, "Output stream stopped");
+                    return;
+                }
+                output = receiver.recv() => output,
+            };
+
+            let message = match output {
+                Some(SessionOutput::Data(data)) => Message::SessionData(SessionData {
+                    session_id: session_id.clone(),
+                    stream: DataStream::Stdout,
+                    data: data.to_vec(),
+                }),
+                Some(SessionOutput::Truncated { dropped_chunks }) => {
+                    warn!(
+                        session_id = %session_id,
+                        device_id = %device_id,
+                        dropped_chunks,
+                        "Client fell behind session output"
+                    );
+                    Message::SessionOutputTruncated(SessionOutputTruncated {
+                        session_id: session_id.clone(),
+                        dropped_chunks,
+                    })
+                }
+                None => return,
+            };
+            if self.outbound.send((*device_id, message)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use tokio::sync::broadcast;
+    use tokio::time::timeout;
+
+    fn device() -> DeviceId {
+        DeviceId::from_bytes([4u8; 16])
+    }
+
+    #[tokio::test]
+    async fn test_streams_output_and_truncation() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let streams = Arc::new(OutputStreams::new(tx));
+        let (output_tx, output_rx) = broadcast::channel(2);
+        for chunk in ["a", "b", "c"] {
+            output_tx
+                .send(Bytes::from_static(chunk.as_bytes()))
+                .unwrap();
+        }
+
+        streams.start("sess-1".to_string(), device(), output_rx.into());
+        assert_eq!(streams.len(), 1);
+
+        let (to, message) = rx.recv().await.unwrap();
+        assert_eq!(to, device());
+        assert_eq!(
+            message,
+            Message::SessionOutputTruncated(SessionOutputTruncated {
+                session_id: "sess-1".to_string(),
+                dropped_chunks: 1,
+            })
+        );
+        for expected in ["b", "c"] {
+            match rx.recv().await.unwrap().1 {
+                Message::SessionData(data) => {
+                    assert_eq!(data.stream, DataStream::Stdout);
+                    assert_eq!(data.data, expected.as_bytes());
+                }
+                other => panic!("unexpected message: {other:?}"),
+            }
+        }
+
+        // The stream ends with the session's output
+        drop(output_tx);
+        timeout(Duration::from_secs(5), async {
+            while !streams.is_empty() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stop_and_close() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let streams = Arc::new(OutputStreams::new(tx));
+        let (output_tx, _) = broadcast::channel::<Bytes>(2);
+        let other = DeviceId::from_bytes([5u8; 16]);
+
+        streams.start("sess-1".to_string(), device(), output_tx.subscribe().into());
+        streams.start("sess-1".to_string(), other, output_tx.subscribe().into());
+        streams.start("sess-2".to_string(), device(), output_tx.subscribe().into());
+        assert_eq!(streams.len(), 3);
+
+        assert!(streams.stop("sess-2", &device()));
+        assert!(!streams.stop("sess-2", &device()));
+        streams.close_device(&other);
+        assert_eq!(streams.len(), 1);
+        streams.close_session("sess-1");
+        assert!(streams.is_empty());
+    }
+}
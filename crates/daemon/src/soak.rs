@@ -0,0 +1,339 @@
+//! Soak test reports for catching resource leaks.
+//!
+//! `remoshell-daemon start --soak-report <path>` appends a [`SoakSnapshot`]
+//! of internal counters to `path` as one JSON line per interval: live tokio
+//! tasks, open file descriptors, the sizes of the session, stream and upload
+//! maps, broadcast subscribers and memory. A daemon left running under load
+//! should show these counters return to the same baseline whenever it is
+//! idle; a steady climb points at a leak.
+//!
+//! Memory comes from jemalloc's statistics when the daemon is built with the
+//! `jemalloc` feature, which also makes jemalloc the global allocator.
+//! Otherwise only the resident set size is reported, where the platform
+//! exposes it.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, RwLock};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::files::FileTransfer;
+use crate::orchestrator::{ActiveConnection, OrchestratorEvent};
+use crate::router::MessageRouter;
+use crate::session::{SessionManager, SessionManagerImpl};
+
+/// Default time between two snapshots.
+pub const DEFAULT_SOAK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where and how often to write soak snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoakReport {
+    /// File the snapshots are appended to.
+    pub path: PathBuf,
+    /// Time between two snapshots.
+    pub interval: Duration,
+}
+
+impl SoakReport {
+    /// Reports to `path` every [`DEFAULT_SOAK_INTERVAL`].
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            interval: DEFAULT_SOAK_INTERVAL,
+        }
+    }
+
+    /// Sets the time between two snapshots.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+/// Internal counters of a running daemon at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SoakSnapshot {
+    /// Unix timestamp in seconds.
+    pub timestamp: u64,
+    /// Seconds since the probe was created.
+    pub uptime_secs: u64,
+    /// Tokio tasks that have been spawned and not yet finished.
+    pub tasks: usize,
+    /// File descriptors open in the process, where the platform lists them.
+    pub open_fds: Option<usize>,
+    /// Sessions in the session map, running or not yet cleaned up.
+    pub sessions: usize,
+    /// Entries in the map of session creators.
+    pub session_owners: usize,
+    /// Clients attached to session output, over all sessions.
+    pub session_subscribers: usize,
+    /// Devices with an open connection.
+    pub connections: usize,
+    /// Receivers of orchestrator events.
+    pub event_subscribers: usize,
+    /// Session output streams to devices.
+    pub output_streams: usize,
+    /// File download streams to devices.
+    pub download_streams: usize,
+    /// Files being followed for devices.
+    pub file_tails: usize,
+    /// Uploads started and neither completed nor replaced.
+    pub uploads: usize,
+    /// Memory use.
+    pub memory: MemoryStats,
+}
+
+/// Memory use of the process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// Bytes allocated by the application, from jemalloc.
+    pub allocated_bytes: Option<u64>,
+    /// Bytes of physical memory mapped by the process.
+    pub resident_bytes: Option<u64>,
+}
+
+/// The map sizes of a [`MessageRouter`] reported in a [`SoakSnapshot`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouterCounts {
+    /// Entries in the map of session creators.
+    pub session_owners: usize,
+    /// Session output streams to devices.
+    pub output_streams: usize,
+    /// File download streams to devices.
+    pub download_streams: usize,
+    /// Files being followed for devices.
+    pub file_tails: usize,
+}
+
+/// Reads the counters of a daemon for [`SoakSnapshot`]s.
+///
+/// Created by [`DaemonOrchestrator::soak_probe`](crate::DaemonOrchestrator::soak_probe).
+#[derive(Clone)]
+pub struct SoakProbe {
+    started: Instant,
+    session_manager: Arc<SessionManagerImpl>,
+    router: Arc<MessageRouter<SessionManagerImpl>>,
+    file_transfer: Arc<FileTransfer>,
+    connections: Arc<RwLock<HashMap<String, ActiveConnection>>>,
+    event_tx: broadcast::Sender<OrchestratorEvent>,
+}
+
+impl SoakProbe {
+    pub(crate) fn new(
+        session_manager: Arc<SessionManagerImpl>,
+        router: Arc<MessageRouter<SessionManagerImpl>>,
+        file_transfer: Arc<FileTransfer>,
+        connections: Arc<RwLock<HashMap<String, ActiveConnection>>>,
+        event_tx: broadcast::Sender<OrchestratorEvent>,
+    ) -> Self {
+        Self {
+            started: Instant::now(),
+            session_manager,
+            router,
+            file_transfer,
+            connections,
+            event_tx,
+        }
+    }
+
+    /// Reads the current counters.
+    ///
+    /// Must be called on a tokio runtime.
+    pub async fn snapshot(&self) -> SoakSnapshot {
+        let router = self.router.resource_counts();
+        SoakSnapshot {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            tasks: tokio::runtime::Handle::current()
+                .metrics()
+                .num_alive_tasks(),
+            open_fds: open_fds(),
+            sessions: self.session_manager.count(),
+            session_owners: router.session_owners,
+            session_subscribers: self
+                .session_manager
+                .list()
+                .iter()
+                .map(|session| session.subscribers)
+                .sum(),
+            connections: self.connections.read().await.len(),
+            event_subscribers: self.event_tx.receiver_count(),
+            output_streams: router.output_streams,
+            download_streams: router.download_streams,
+            file_tails: router.file_tails,
+            uploads: self.file_transfer.upload_count(),
+            memory: memory_stats(),
+        }
+    }
+}
+
+/// Appends a snapshot to the report every interval, and a last one on
+/// shutdown.
+pub async fn run_reporter(probe: SoakProbe, report: SoakReport, shutdown_token: CancellationToken) {
+    let mut file = match OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&report.path)
+    {
+        Ok(file) => file,
+        Err(e) => {
+            warn!(path = %report.path.display(), error = %e, "Failed to open soak report");
+            return;
+        }
+    };
+    info!(
+        path = %report.path.display(),
+        interval_secs = report.interval.as_secs(),
+        "Writing soak report"
+    );
+
+    let mut interval = tokio::time::interval(report.interval);
+    loop {
+        let stopping = tokio::select! {
+            _ = shutdown_token.cancelled() => true,
+            _ = interval.tick() => false,
+        };
+        let snapshot = probe.snapshot().await;
+        if let Err(e) = append(&mut file, &snapshot) {
+            warn!(path = %report.path.display(), error = %e, "Failed to write soak report");
+        }
+        if stopping {
+            break;
+        }
+    }
+}
+
+fn append(file: &mut File, snapshot: &SoakSnapshot) -> io::Result<()> {
+    let mut line = serde_json::to_vec(snapshot)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    file.flush()
+}
+
+/// Counts the file descriptors open in the process.
+fn open_fds() -> Option<usize> {
+    let dir = if cfg!(target_os = "linux") {
+        "/proc/self/fd"
+    } else {
+        "/dev/fd"
+    };
+    // Listing the directory opens one more descriptor
+    let entries = std::fs::read_dir(dir).ok()?;
+    Some(entries.count().saturating_sub(1))
+}
+
+#[cfg(feature = "jemalloc")]
+fn memory_stats() -> MemoryStats {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // The statistics are cached until the epoch advances
+    if epoch::advance().is_err() {
+        return MemoryStats::default();
+    }
+    MemoryStats {
+        allocated_bytes: stats::allocated::read().ok().map(|bytes| bytes as u64),
+        resident_bytes: stats::resident::read().ok().map(|bytes| bytes as u64),
+    }
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn memory_stats() -> MemoryStats {
+    MemoryStats {
+        allocated_bytes: None,
+        resident_bytes: resident_bytes(),
+    }
+}
+
+/// Reads the resident set size from `/proc`.
+#[cfg(not(feature = "jemalloc"))]
+fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::DaemonOrchestrator;
+    use tempfile::TempDir;
+
+    fn test_orchestrator(temp_dir: &TempDir) -> DaemonOrchestrator {
+        let mut config = Config::default();
+        config.daemon.data_dir = temp_dir.path().to_path_buf();
+        config.file.allowed_paths = vec![temp_dir.path().to_path_buf()];
+        DaemonOrchestrator::new(config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_counts_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let orchestrator = test_orchestrator(&temp_dir);
+        let probe = orchestrator.soak_probe();
+
+        let before = probe.snapshot().await;
+        assert_eq!(before.sessions, 0);
+        assert_eq!(before.connections, 0);
+
+        let manager = orchestrator.session_manager();
+        let (id, _) = manager
+            .create(Some("/bin/sh".to_string()), 80, 24, vec![], None)
+            .await
+            .unwrap();
+        let during = probe.snapshot().await;
+        assert_eq!(during.sessions, 1);
+        if let (Some(before), Some(during)) = (before.open_fds, during.open_fds) {
+            assert!(during > before);
+        }
+
+        manager.kill(&id, Some(9)).await.unwrap();
+        assert_eq!(probe.snapshot().await.sessions, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reporter_appends_json_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let orchestrator = test_orchestrator(&temp_dir);
+        let path = temp_dir.path().join("soak.jsonl");
+        let report = SoakReport::new(&path).with_interval(Duration::from_secs(10));
+        let token = CancellationToken::new();
+
+        let reporter = tokio::spawn(run_reporter(
+            orchestrator.soak_probe(),
+            report,
+            token.clone(),
+        ));
+        // The first snapshot is immediate, then one every interval
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        token.cancel();
+        reporter.await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let snapshots: Vec<SoakSnapshot> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // Three ticks and the one written on shutdown
+        assert_eq!(snapshots.len(), 4);
+        assert!(snapshots.iter().all(|snapshot| snapshot.sessions == 0));
+    }
+
+    #[test]
+    fn test_open_fds_is_counted() {
+        if let Some(count) = open_fds() {
+            assert!(count > 0);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Host and process information for client dashboards.
+//!
+//! [`system_info`] answers `SystemInfoRequest` with a snapshot of the host:
+//! its name and OS, CPU load, memory, and the disk usage of the paths clients
+//! may access, so a client can show host health without opening a shell.
+//! [`process_list`] and [`kill_process`] back a remote task manager view.
+
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::unistd::Pid;
+use protocol::messages::{DiskUsage, ProcessInfo, ProcessSignal, ProcessSort, SystemInfo};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind, Users};
+use thiserror::Error;
+
+use crate::session::pty::to_nix_signal;
+
+/// Device capability required to signal host processes.
+pub const PROCESS_KILL_CAPABILITY: &str = "process-kill";
+
+/// Errors from signaling host processes.
+#[derive(Debug, Error)]
+pub enum SysmonError {
+    /// The PID cannot name a single process.
+    #[error("invalid process ID {0}")]
+    InvalidPid(u32),
+
+    /// No process has the PID.
+    #[error("no process with ID {0}")]
+    NoSuchProcess(u32),
+
+    /// The daemon's user may not signal the process.
+    #[error("not allowed to signal process {0}")]
+    PermissionDenied(u32),
+
+    /// Sending the signal failed for another reason.
+    #[error("failed to signal process {0}: {1}")]
+    Signal(u32, Errno),
+}
+
+/// Collects a snapshot of the host.
+///
+/// `allowed_paths` are the paths clients may access; an empty list means all
+/// paths, and reports the root filesystem.
+pub fn system_info(allowed_paths: &[PathBuf]) -> SystemInfo {
+    let mut system = System::new();
+    system.refresh_memory();
+    let load = System::load_average();
+
+    SystemInfo {
+        hostname: System::host_name().unwrap_or_default(),
+        os_name: System::name(),
+        os_version: System::os_version(),
+        kernel_version: System::kernel_version(),
+        arch: System::cpu_arch(),
+        cpu_count: std::thread::available_parallelism()
+            .map(|n| n.get() as u32)
+            .unwrap_or(1),
+        load_average: [
+            hundredths(load.one),
+            hundredths(load.five),
+            hundredths(load.fifteen),
+        ],
+        memory_total: system.total_memory(),
+        memory_used: system.used_memory(),
+        swap_total: system.total_swap(),
+        swap_used: system.used_swap(),
+        disks: disk_usage(allowed_paths),
+        uptime_secs: System::uptime(),
+    }
+}
+
+/// Returns the usage of the filesystem holding each path, skipping paths
+/// that cannot be queried. An empty list reports the root filesystem.
+pub fn disk_usage(paths: &[PathBuf]) -> Vec<DiskUsage> {
+    let root = [PathBuf::from("/")];
+    let paths = if paths.is_empty() { &root[..] } else { paths };
+    paths
+        .iter()
+        .filter_map(|path| filesystem_usage(path))
+        .collect()
+}
+
+/// Returns the usage of the filesystem holding `path`, if it can be
+/// queried.
+// statvfs field widths differ between platforms
+#[allow(clippy::unnecessary_cast)]
+pub fn filesystem_usage(path: &Path) -> Option<DiskUsage> {
+    let stat = nix::sys::statvfs::statvfs(path).ok()?;
+    let fragment_size = stat.fragment_size() as u64;
+    Some(DiskUsage {
+        path: path.display().to_string(),
+        total: stat.blocks() as u64 * fragment_size,
+        available: stat.blocks_available() as u64 * fragment_size,
+    })
+}
+
+/// Lists the processes running on the host.
+///
+/// CPU usage is measured over [`sysinfo::MINIMUM_CPU_UPDATE_INTERVAL`], so
+/// this takes about 200 ms. Processes are sorted by `sort` (PID if `None`)
+/// and truncated to `limit`.
+pub async fn process_list(sort: Option<ProcessSort>, limit: Option<u32>) -> Vec<ProcessInfo> {
+    let refresh = ProcessRefreshKind::nothing()
+        .with_cpu()
+        .with_memory()
+        .with_user(UpdateKind::OnlyIfNotSet)
+        .with_cmd(UpdateKind::OnlyIfNotSet);
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh);
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh);
+
+    let users = Users::new_with_refreshed_list();
+    let mut processes: Vec<ProcessInfo> = system
+        .processes()
+        .iter()
+        .filter(|(_, process)| process.thread_kind().is_none())
+        .map(|(pid, process)| {
+            let cmd = process.cmd();
+            let command = if cmd.is_empty() {
+                process.name().to_string_lossy().into_owned()
+            } else {
+                cmd.iter()
+                    .map(|arg| arg.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            ProcessInfo {
+                pid: pid.as_u32(),
+                user: process
+                    .user_id()
+                    .and_then(|uid| users.get_user_by_id(uid))
+                    .map(|user| user.name().to_string()),
+                cpu_usage: hundredths(f64::from(process.cpu_usage())),
+                memory: process.memory(),
+                command,
+            }
+        })
+        .collect();
+
+    sort_processes(&mut processes, sort.unwrap_or(ProcessSort::Pid));
+    if let Some(limit) = limit {
+        processes.truncate(limit as usize);
+    }
+    processes
+}
+
+fn sort_processes(processes: &mut [ProcessInfo], sort: ProcessSort) {
+    match sort {
+        ProcessSort::Pid => processes.sort_by_key(|p| p.pid),
+        ProcessSort::Cpu => processes.sort_by_key(|p| (Reverse(p.cpu_usage), p.pid)),
+        ProcessSort::Memory => processes.sort_by_key(|p| (Reverse(p.memory), p.pid)),
+        ProcessSort::Command => {
+            processes.sort_by(|a, b| a.command.cmp(&b.command).then(a.pid.cmp(&b.pid)))
+        }
+    }
+}
+
+/// Sends a signal to a host process.
+///
+/// PID 0 and values that do not fit a PID are rejected, since `kill(2)` would
+/// treat them as process groups.
+pub fn kill_process(pid: u32, signal: ProcessSignal) -> Result<(), SysmonError> {
+    let raw = i32::try_from(pid)
+        .ok()
+        .filter(|&raw| raw > 0)
+        .ok_or(SysmonError::InvalidPid(pid))?;
+
+    nix::sys::signal::kill(Pid::from_raw(raw), to_nix_signal(signal)).map_err(|e| match e {
+        Errno::ESRCH => SysmonError::NoSuchProcess(pid),
+        Errno::EPERM => SysmonError::PermissionDenied(pid),
+        e => SysmonError::Signal(pid, e),
+    })
+}
+
+fn hundredths(load: f64) -> u32 {
+    (load * 100.0).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_system_info() {
+        let info = system_info(&[]);
+        assert!(!info.arch.is_empty());
+        assert!(info.cpu_count >= 1);
+        assert!(info.memory_total > 0);
+        assert!(info.memory_used <= info.memory_total);
+        assert_eq!(info.disks.len(), 1);
+        assert_eq!(info.disks[0].path, "/");
+    }
+
+    #[test]
+    fn test_disk_usage_of_allowed_paths() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+
+        let disks = disk_usage(&[dir.path().to_path_buf(), missing]);
+        assert_eq!(disks.len(), 1);
+        assert_eq!(disks[0].path, dir.path().display().to_string());
+        assert!(disks[0].total > 0);
+        assert!(disks[0].available <= disks[0].total);
+    }
+
+    #[tokio::test]
+    async fn test_process_list() {
+        let processes = process_list(Some(ProcessSort::Memory), None).await;
+        let own = processes
+            .iter()
+            .find(|p| p.pid == std::process::id())
+            .expect("own process listed");
+        assert!(own.memory > 0);
+        assert!(!own.command.is_empty());
+        assert!(processes.windows(2).all(|w| w[0].memory >= w[1].memory));
+
+        let limited = process_list(None, Some(2)).await;
+        assert_eq!(limited.len(), 2);
+        assert!(limited[0].pid < limited[1].pid);
+    }
+
+    #[test]
+    fn test_sort_processes() {
+        let process = |pid, cpu_usage, command: &str| ProcessInfo {
+            pid,
+            user: None,
+            cpu_usage,
+            memory: u64::from(pid) * 10,
+            command: command.to_string(),
+        };
+        let mut processes = vec![
+            process(3, 50, "bash"),
+            process(1, 900, "init"),
+            process(2, 50, "awk"),
+        ];
+
+        sort_processes(&mut processes, ProcessSort::Cpu);
+        let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [1, 2, 3]);
+
+        sort_processes(&mut processes, ProcessSort::Memory);
+        let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [3, 2, 1]);
+
+        sort_processes(&mut processes, ProcessSort::Command);
+        let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [2, 3, 1]);
+    }
+
+    #[test]
+    fn test_kill_process() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        kill_process(child.id(), ProcessSignal::Kill).unwrap();
+        assert!(!child.wait().unwrap().success());
+
+        assert!(matches!(
+            kill_process(0, ProcessSignal::Terminate),
+            Err(SysmonError::InvalidPid(0))
+        ));
+        assert!(matches!(
+            kill_process(u32::MAX, ProcessSignal::Terminate),
+            Err(SysmonError::InvalidPid(_))
+        ));
+    }
+
+    #[test]
+    fn test_hundredths() {
+        assert_eq!(hundredths(0.0), 0);
+        assert_eq!(hundredths(1.254), 125);
+        assert_eq!(hundredths(0.999), 100);
+    }
+}
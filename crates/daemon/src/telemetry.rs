@@ -0,0 +1,269 @@
+//! Tracing spans and optional OpenTelemetry export.
+//!
+//! Work done on behalf of a peer runs inside a `connection` span carrying its
+//! `device_id`. Network handlers read each data channel or stream inside a
+//! `channel` span. Each message the peer sends is handled in a `message` span
+//! with the `channel` and sequence number it arrived on, and routing adds a `route`
+//! span naming the message type and, when it refers to one, the `session_id`.
+//! A session's PTY reader runs in a `session` span that is a child of the
+//! route that created it, so one session can be followed from the connection
+//! that opened it down to its output.
+//!
+//! When `telemetry.otlp_endpoint` is set, [`otlp_layer`] exports these spans
+//! to an OpenTelemetry collector over OTLP/HTTP.
+
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use protocol::messages::Message;
+use tracing::{debug_span, field, info_span, Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::TelemetryConfig;
+use crate::network::ChannelType;
+
+/// Path the OTLP/HTTP trace exporter posts to.
+const OTLP_TRACES_PATH: &str = "/v1/traces";
+
+/// Span covering everything done for one peer connection.
+pub fn connection_span(device_id: &str) -> Span {
+    info_span!("connection", device_id = %device_id)
+}
+
+/// Span covering the transport work for one channel of a connection.
+pub fn channel_span(channel: ChannelType) -> Span {
+    debug_span!("channel", channel = channel.channel_name())
+}
+
+/// Span covering one message received on a channel.
+pub fn message_span(channel: ChannelType, sequence: u64) -> Span {
+    debug_span!("message", channel = channel.channel_name(), sequence)
+}
+
+/// Span covering the routing of one message.
+///
+/// Terminal and agent data, pings and pongs are traced at debug level so an
+/// interactive session does not export a span per keystroke.
+pub fn route_span(message: &Message) -> Span {
+    let span = match message {
+        Message::SessionData(_) | Message::AgentData(_) | Message::Ping(_) | Message::Pong(_) => {
+            debug_span!("route", message = message.name(), session_id = field::Empty)
+        }
+        _ => info_span!("route", message = message.name(), session_id = field::Empty),
+    };
+    if let Some(session_id) = message.session_id() {
+        span.record("session_id", session_id);
+    }
+    span
+}
+
+/// Span covering the lifetime of a session's PTY.
+pub fn session_span(session_id: &str) -> Span {
+    info_span!("session", session_id = %session_id)
+}
+
+/// Shuts the tracer provider down when dropped, flushing pending spans.
+pub struct TelemetryGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush exported spans: {}", e);
+        }
+    }
+}
+
+/// Builds a layer exporting spans to the configured OTLP collector.
+///
+/// Returns `None` when no endpoint is configured. The guard must be kept
+/// alive for as long as spans should be exported.
+pub fn otlp_layer<S>(
+    config: &TelemetryConfig,
+) -> Result<Option<(OpenTelemetryLayer<S, SdkTracer>, TelemetryGuard)>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_endpoint(endpoint))
+        .build()
+        .context("Failed to create OTLP exporter")?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+
+    Ok(Some((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        TelemetryGuard { provider },
+    )))
+}
+
+/// Appends the traces path to a collector base URL unless already present.
+fn traces_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with(OTLP_TRACES_PATH) {
+        endpoint.to_string()
+    } else {
+        format!("{}{}", endpoint, OTLP_TRACES_PATH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::messages::{SessionCreate, SessionKill};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    /// Records the fields of every span created or updated.
+    #[derive(Clone, Default)]
+    struct FieldRecorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl Visit for FieldRecorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for FieldRecorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            attrs.record(&mut self.clone());
+        }
+
+        fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    fn recorded_fields(f: impl FnOnce()) -> Vec<(String, String)> {
+        let recorder = FieldRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::with_default(subscriber, f);
+        let fields = recorder.0.lock().unwrap().clone();
+        fields
+    }
+
+    fn has_field(fields: &[(String, String)], name: &str, value: &str) -> bool {
+        fields.iter().any(|(n, v)| n == name && v == value)
+    }
+
+    #[test]
+    fn test_spans_carry_context_fields() {
+        let fields = recorded_fields(|| {
+            let _connection = connection_span("ab:cd").entered();
+            let _message = message_span(ChannelType::Control, 7).entered();
+            let _route = route_span(&Message::SessionKill(SessionKill {
+                session_id: "sess-1".to_string(),
+                signal: None,
+            }))
+            .entered();
+        });
+
+        assert!(has_field(&fields, "device_id", "ab:cd"));
+        assert!(has_field(&fields, "channel", "control"));
+        assert!(has_field(&fields, "sequence", "7"));
+        assert!(has_field(&fields, "message", "SessionKill"));
+        assert!(has_field(&fields, "session_id", "sess-1"));
+    }
+
+    #[test]
+    fn test_route_span_session_id_recorded_later() {
+        let fields = recorded_fields(|| {
+            let span = route_span(&Message::SessionCreate(SessionCreate::default()));
+            span.record("session_id", "sess-2");
+        });
+
+        assert!(has_field(&fields, "message", "SessionCreate"));
+        assert!(has_field(&fields, "session_id", "sess-2"));
+    }
+
+    #[test]
+    fn test_traces_endpoint() {
+        assert_eq!(
+            traces_endpoint("http://localhost:4318"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_endpoint("http://localhost:4318/"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_endpoint("https://otel.example.com/v1/traces"),
+            "https://otel.example.com/v1/traces"
+        );
+    }
+
+    #[test]
+    fn test_otlp_layer_exports_spans() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = TelemetryConfig {
+            otlp_endpoint: Some(format!("http://{}", listener.local_addr().unwrap())),
+            ..TelemetryConfig::default()
+        };
+        let collector = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .unwrap();
+            request_line
+        });
+
+        let (layer, guard) = otlp_layer(&config).unwrap().unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = session_span("sess-1").entered();
+        });
+        // Shutting down flushes the batch
+        drop(guard);
+
+        assert!(collector
+            .join()
+            .unwrap()
+            .starts_with("POST /v1/traces HTTP/1.1"));
+    }
+
+    #[test]
+    fn test_otlp_layer_disabled_without_endpoint() {
+        let layer = otlp_layer::<tracing_subscriber::Registry>(&TelemetryConfig::default());
+        assert!(layer.unwrap().is_none());
+    }
+}
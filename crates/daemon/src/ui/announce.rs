@@ -0,0 +1,572 @@
+//! Screen-reader-friendly front end for a daemon running in the foreground.
+//!
+//! `start --plain` runs the daemon without the TUI. Instead of drawing
+//! screens, the [`Announcer`] prints one sentence per event to stdout and
+//! asks about each device waiting for approval, and each session waiting for
+//! confirmation, with a y/n prompt answered on stdin. Lines are only ever
+//! appended, never redrawn, so a screen reader reads each of them once and
+//! in order.
+//!
+//! Requests are prompted for one at a time, oldest first. Requests that
+//! expire before they are answered are announced and dropped.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+
+use crate::devices::names::is_mixed_script;
+use crate::devices::{PendingApproval, PendingSession, SessionConfirmations, TrustStore};
+use crate::orchestrator::OrchestratorEvent;
+use crate::util::i18n::{tr, tr_with};
+use protocol::DeviceId;
+
+/// How often the pending approval queue is checked for new requests.
+const PENDING_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An answer to an approval prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    /// Approve and trust the device.
+    Approve,
+    /// Reject the device.
+    Reject,
+}
+
+impl Answer {
+    /// Parses an answer typed at the prompt, in English or the configured
+    /// language. Returns `None` for anything else.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let is_one_of = |words: &str| {
+            !line.is_empty()
+                && words
+                    .split(',')
+                    .any(|word| word.trim().eq_ignore_ascii_case(line))
+        };
+        if is_one_of("y, yes") || is_one_of(tr("announce.yes")) {
+            Some(Answer::Approve)
+        } else if is_one_of("n, no") || is_one_of(tr("announce.no")) {
+            Some(Answer::Reject)
+        } else {
+            None
+        }
+    }
+}
+
+/// A request waiting for an answer at the prompt.
+#[derive(Debug, Clone)]
+enum Request {
+    /// A device asking to be approved.
+    Device(PendingApproval),
+    /// A trusted device asking to open a session.
+    Session(PendingSession),
+}
+
+/// Identifies a request across polls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RequestKey {
+    Device(DeviceId),
+    Session(u64),
+}
+
+impl Request {
+    fn key(&self) -> RequestKey {
+        match self {
+            Request::Device(request) => RequestKey::Device(request.device_id),
+            Request::Session(request) => RequestKey::Session(request.id),
+        }
+    }
+
+    fn is_session(&self) -> bool {
+        matches!(self, Request::Session(_))
+    }
+
+    fn device_name(&self) -> &str {
+        match self {
+            Request::Device(request) => &request.device_name,
+            Request::Session(request) => &request.device_name,
+        }
+    }
+}
+
+/// Prints daemon events and approval prompts as plain lines.
+pub struct Announcer<W: Write> {
+    out: W,
+    trust_store: Arc<TrustStore>,
+    /// Sessions waiting for confirmation, if sessions must be confirmed.
+    session_confirmations: Option<Arc<SessionConfirmations>>,
+    /// Requests waiting for an answer; the first one has been prompted for.
+    queue: VecDeque<Request>,
+}
+
+impl<W: Write> Announcer<W> {
+    /// Creates an announcer writing to `out`.
+    pub fn new(out: W, trust_store: Arc<TrustStore>) -> Self {
+        Self {
+            out,
+            trust_store,
+            session_confirmations: None,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Also prompts for the sessions waiting in `confirmations`.
+    pub fn with_session_confirmations(mut self, confirmations: Arc<SessionConfirmations>) -> Self {
+        self.session_confirmations = Some(confirmations);
+        self
+    }
+
+    /// Announces an orchestrator event, if it is one users act on.
+    pub fn event(&mut self, event: &OrchestratorEvent) -> io::Result<()> {
+        match event {
+            OrchestratorEvent::PeerConnected { device_id } => {
+                let device = self.device_name(device_id);
+                self.say(tr_with("announce.connected", &[("device", &device)]))
+            }
+            OrchestratorEvent::PeerDisconnected { device_id, reason } => {
+                let device = self.device_name(device_id);
+                self.say(tr_with(
+                    "announce.disconnected",
+                    &[("device", &device), ("reason", reason)],
+                ))
+            }
+            OrchestratorEvent::GrantEnded { name, reason, .. } => self.say(tr_with(
+                "announce.grant_ended",
+                &[("name", name), ("reason", reason)],
+            )),
+            OrchestratorEvent::SubsystemFailed {
+                name,
+                error,
+                restarting: false,
+            } => self.say(tr_with(
+                "announce.failed",
+                &[("name", name), ("error", error)],
+            )),
+            OrchestratorEvent::TaskPanicked {
+                task,
+                message,
+                restarting: false,
+            } => self.say(tr_with(
+                "announce.failed",
+                &[("name", task), ("error", message)],
+            )),
+            OrchestratorEvent::Error { message } => {
+                self.say(tr_with("announce.error", &[("message", message)]))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Brings the queue up to date with the approvals pending in the trust
+    /// store, announcing new and expired requests and prompting for the
+    /// oldest one.
+    pub fn update_pending(&mut self, mut pending: Vec<PendingApproval>) -> io::Result<()> {
+        pending.sort_by_key(|p| std::cmp::Reverse(p.requested_at.elapsed()));
+        self.update(false, pending.into_iter().map(Request::Device).collect())
+    }
+
+    /// Brings the queue up to date with the sessions waiting for
+    /// confirmation, like [`Announcer::update_pending`] for devices.
+    pub fn update_sessions(&mut self, pending: Vec<PendingSession>) -> io::Result<()> {
+        self.update(true, pending.into_iter().map(Request::Session).collect())
+    }
+
+    /// Replaces the queued requests of one kind with `pending`.
+    fn update(&mut self, sessions: bool, pending: Vec<Request>) -> io::Result<()> {
+        let prompted = self.queue.front().map(Request::key);
+
+        // Requests that left the queue without an answer here have expired
+        let mut expired = Vec::new();
+        self.queue.retain(|request| {
+            let still_pending = request.is_session() != sessions
+                || pending.iter().any(|p| p.key() == request.key());
+            if !still_pending {
+                expired.push(request.clone());
+            }
+            still_pending
+        });
+        for request in expired {
+            let key = match request {
+                Request::Device(_) => "announce.expired",
+                Request::Session(_) => "announce.session_expired",
+            };
+            self.say(tr_with(key, &[("name", &request.device_name())]))?;
+        }
+
+        for request in pending {
+            if self.queue.iter().any(|r| r.key() == request.key()) {
+                continue;
+            }
+            self.announce_request(&request)?;
+            self.queue.push_back(request);
+        }
+
+        if self.queue.front().map(Request::key) != prompted {
+            self.prompt()?;
+        }
+        Ok(())
+    }
+
+    /// Applies a line typed at the prompt to the oldest request.
+    pub fn answer(&mut self, line: &str) -> io::Result<()> {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        if self.queue.is_empty() {
+            return self.say(tr("announce.no_request"));
+        }
+        let Some(answer) = Answer::parse(line) else {
+            return self.say(tr("announce.unknown_answer"));
+        };
+
+        let Some(request) = self.queue.pop_front() else {
+            return Ok(());
+        };
+        let key = match request {
+            Request::Device(ref request) => self.answer_device(request, answer),
+            Request::Session(ref request) => {
+                // The request may have expired since it was prompted for
+                let allow = answer == Answer::Approve;
+                let answered = self
+                    .session_confirmations
+                    .as_ref()
+                    .is_some_and(|confirmations| confirmations.answer(request.id, allow));
+                match (answered, answer) {
+                    (false, _) => "announce.session_expired",
+                    (true, Answer::Approve) => "announce.session_allowed",
+                    (true, Answer::Reject) => "announce.session_denied",
+                }
+            }
+        };
+        self.say(tr_with(key, &[("name", &request.device_name())]))?;
+        self.prompt()
+    }
+
+    /// Approves or rejects a device, returning the message to announce.
+    fn answer_device(&self, request: &PendingApproval, answer: Answer) -> &'static str {
+        // The request may have expired since it was prompted for
+        let answered = match answer {
+            Answer::Approve => self.trust_store.approve_pending(&request.device_id).is_ok(),
+            Answer::Reject => self
+                .trust_store
+                .reject_pending(&request.device_id)
+                .unwrap_or(false),
+        };
+
+        match (answered, answer) {
+            (false, _) => "announce.expired",
+            (true, Answer::Approve) => {
+                if let Err(e) = self.trust_store.save() {
+                    tracing::error!(error = %e, "Failed to save trust store");
+                }
+                "announce.approved"
+            }
+            (true, Answer::Reject) => "announce.rejected",
+        }
+    }
+
+    /// Announces a new request.
+    fn announce_request(&mut self, request: &Request) -> io::Result<()> {
+        match request {
+            Request::Device(request) => {
+                let words = request.device_id.fingerprint_words().join(" ");
+                self.say(tr_with(
+                    "announce.request",
+                    &[("name", &request.device_name), ("words", &words)],
+                ))?;
+                if is_mixed_script(&request.device_name) {
+                    self.say(tr("announce.mixed_scripts"))?;
+                }
+                Ok(())
+            }
+            Request::Session(request) => self.say(tr_with(
+                "announce.session_request",
+                &[("name", &request.device_name)],
+            )),
+        }
+    }
+
+    /// Asks about the oldest request, if any.
+    fn prompt(&mut self) -> io::Result<()> {
+        let Some(request) = self.queue.front() else {
+            return Ok(());
+        };
+        let key = match request {
+            Request::Device(_) => "announce.prompt",
+            Request::Session(_) => "announce.session_prompt",
+        };
+        let message = tr_with(key, &[("name", &request.device_name())]);
+        self.say(message)
+    }
+
+    /// Returns the name of a device given its fingerprint, or the
+    /// fingerprint if the device is not in the trust store.
+    fn device_name(&self, device_id: &str) -> String {
+        self.trust_store
+            .list_devices()
+            .ok()
+            .and_then(|devices| {
+                devices
+                    .into_iter()
+                    .find(|device| device.device_id.to_string() == device_id)
+            })
+            .map(|device| device.name)
+            .unwrap_or_else(|| device_id.to_string())
+    }
+
+    fn say(&mut self, line: impl AsRef<str>) -> io::Result<()> {
+        writeln!(self.out, "{}", line.as_ref())?;
+        self.out.flush()
+    }
+}
+
+/// Announces events on stdout and reads approval answers from stdin until
+/// `stop` is cancelled.
+pub async fn run(
+    mut events: broadcast::Receiver<OrchestratorEvent>,
+    trust_store: Arc<TrustStore>,
+    session_confirmations: Option<Arc<SessionConfirmations>>,
+    device_id: String,
+    stop: CancellationToken,
+) -> io::Result<()> {
+    let mut announcer = Announcer::new(io::stdout(), Arc::clone(&trust_store));
+    if let Some(confirmations) = &session_confirmations {
+        announcer = announcer.with_session_confirmations(Arc::clone(confirmations));
+    }
+    announcer.say(tr_with("announce.started", &[("device", &device_id)]))?;
+
+    // Reading stdin blocks, so it gets its own thread rather than holding up
+    // the runtime at shutdown
+    let (line_tx, mut lines) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line_tx.blocking_send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut poll = tokio::time::interval(PENDING_POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = stop.cancelled() => break,
+            event = events.recv() => match event {
+                Ok(event) => announcer.event(&event)?,
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = poll.tick() => {
+                match trust_store.list_pending() {
+                    Ok(pending) => announcer.update_pending(pending)?,
+                    Err(e) => tracing::warn!(error = %e, "Failed to list pending approvals"),
+                }
+                if let Some(confirmations) = &session_confirmations {
+                    announcer.update_sessions(confirmations.pending())?;
+                }
+            }
+            Some(line) = lines.recv() => announcer.answer(&line)?,
+        }
+    }
+
+    announcer.say(tr("announce.stopping"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pending(name: &str) -> PendingApproval {
+        let identity = protocol::DeviceIdentity::generate();
+        PendingApproval::new(
+            *identity.device_id(),
+            name.to_string(),
+            identity.public_key_bytes(),
+            None,
+        )
+    }
+
+    fn announcer(temp_dir: &TempDir) -> Announcer<Vec<u8>> {
+        let store = TrustStore::new(temp_dir.path().join("trusted_devices.json"));
+        Announcer::new(Vec::new(), Arc::new(store))
+    }
+
+    fn take_output(announcer: &mut Announcer<Vec<u8>>) -> String {
+        String::from_utf8(std::mem::take(&mut announcer.out)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        assert_eq!(Answer::parse("y"), Some(Answer::Approve));
+        assert_eq!(Answer::parse(" YES \n"), Some(Answer::Approve));
+        assert_eq!(Answer::parse("n"), Some(Answer::Reject));
+        assert_eq!(Answer::parse("No"), Some(Answer::Reject));
+        assert_eq!(Answer::parse("maybe"), None);
+        assert_eq!(Answer::parse(""), None);
+    }
+
+    #[test]
+    fn test_prompts_one_request_at_a_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut announcer = announcer(&temp_dir);
+        let phone = pending("Phone");
+        let tablet = pending("Tablet");
+        announcer.trust_store.add_pending(phone.clone()).unwrap();
+        announcer.trust_store.add_pending(tablet.clone()).unwrap();
+
+        announcer
+            .update_pending(vec![phone.clone(), tablet.clone()])
+            .unwrap();
+        let output = take_output(&mut announcer);
+        assert!(output.contains("Phone is asking to connect"));
+        assert!(output.contains("Tablet is asking to connect"));
+        assert!(output.contains("Approve Phone?"));
+        assert!(!output.contains("Approve Tablet?"));
+
+        // Nothing changed, nothing is repeated
+        announcer
+            .update_pending(vec![phone.clone(), tablet.clone()])
+            .unwrap();
+        assert_eq!(take_output(&mut announcer), "");
+
+        announcer.answer("y").unwrap();
+        let output = take_output(&mut announcer);
+        assert!(output.contains("Phone approved."));
+        assert!(output.contains("Approve Tablet?"));
+        assert!(announcer.trust_store.is_trusted(&phone.device_id).unwrap());
+
+        announcer.answer("n").unwrap();
+        assert!(take_output(&mut announcer).contains("Tablet rejected."));
+        assert!(!announcer.trust_store.is_pending(&tablet.device_id).unwrap());
+        assert!(!announcer.trust_store.is_trusted(&tablet.device_id).unwrap());
+
+        announcer.answer("y").unwrap();
+        assert_eq!(
+            take_output(&mut announcer),
+            "No device is waiting for approval.\n"
+        );
+    }
+
+    #[test]
+    fn test_unknown_answer_keeps_request() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut announcer = announcer(&temp_dir);
+        let phone = pending("Phone");
+        announcer.trust_store.add_pending(phone.clone()).unwrap();
+        announcer.update_pending(vec![phone.clone()]).unwrap();
+        take_output(&mut announcer);
+
+        announcer.answer("maybe").unwrap();
+        assert_eq!(take_output(&mut announcer), "Please type y or n.\n");
+        assert!(announcer.trust_store.is_pending(&phone.device_id).unwrap());
+    }
+
+    #[test]
+    fn test_expired_request_is_announced() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut announcer = announcer(&temp_dir);
+        let phone = pending("Phone");
+        let tablet = pending("Tablet");
+        announcer
+            .update_pending(vec![phone.clone(), tablet.clone()])
+            .unwrap();
+        take_output(&mut announcer);
+
+        announcer.update_pending(vec![tablet]).unwrap();
+        let output = take_output(&mut announcer);
+        assert!(output.contains("The approval request from Phone expired."));
+        assert!(output.contains("Approve Tablet?"));
+    }
+
+    #[tokio::test]
+    async fn test_prompts_for_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let confirmations = Arc::new(SessionConfirmations::new(Duration::from_secs(30)));
+        let mut announcer =
+            announcer(&temp_dir).with_session_confirmations(Arc::clone(&confirmations));
+        let device_id = *protocol::DeviceIdentity::generate().device_id();
+        let waiter = tokio::spawn({
+            let confirmations = Arc::clone(&confirmations);
+            async move { confirmations.confirm(device_id, "Laptop".to_string()).await }
+        });
+        let pending = loop {
+            let pending = confirmations.pending();
+            if !pending.is_empty() {
+                break pending;
+            }
+            tokio::task::yield_now().await;
+        };
+
+        announcer.update_sessions(pending.clone()).unwrap();
+        let output = take_output(&mut announcer);
+        assert!(output.contains("Laptop is asking to open a session."));
+        assert!(output.contains("Allow a session for Laptop?"));
+
+        announcer.answer("y").unwrap();
+        assert_eq!(take_output(&mut announcer), "Session for Laptop allowed.\n");
+        assert_eq!(
+            waiter.await.unwrap(),
+            crate::devices::ConfirmOutcome::Allowed
+        );
+
+        // A request that disappears without an answer has expired
+        announcer.update_sessions(pending).unwrap();
+        take_output(&mut announcer);
+        announcer.update_sessions(Vec::new()).unwrap();
+        assert_eq!(
+            take_output(&mut announcer),
+            "The session request from Laptop expired.\n"
+        );
+    }
+
+    #[test]
+    fn test_connection_events_use_device_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut announcer = announcer(&temp_dir);
+        let identity = protocol::DeviceIdentity::generate();
+        let device_id = *identity.device_id();
+        announcer
+            .trust_store
+            .add_device(crate::devices::TrustedDevice::new(
+                device_id,
+                "Laptop".to_string(),
+                identity.public_key_bytes(),
+            ))
+            .unwrap();
+
+        announcer
+            .event(&OrchestratorEvent::PeerConnected {
+                device_id: device_id.to_string(),
+            })
+            .unwrap();
+        announcer
+            .event(&OrchestratorEvent::PeerDisconnected {
+                device_id: "aa:bb".to_string(),
+                reason: "closed".to_string(),
+            })
+            .unwrap();
+        announcer
+            .event(&OrchestratorEvent::GrantEnded {
+                device_id: device_id.to_string(),
+                name: "Laptop".to_string(),
+                reason: "expired".to_string(),
+            })
+            .unwrap();
+        announcer
+            .event(&OrchestratorEvent::ShutdownProgress {
+                subsystem: "sessions".to_string(),
+                detail: "closed".to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            take_output(&mut announcer),
+            "Laptop connected.\naa:bb disconnected: closed.\n\
+             Temporary access of Laptop ended: expired.\n"
+        );
+    }
+}
@@ -114,6 +114,13 @@ pub struct PairingInfo {
     /// The relay/signaling server URL.
     pub relay_url: String,
 
+    /// Unix timestamp, on the daemon's clock, when this pairing code was
+    /// generated. Clients compare it with their own clock to measure skew
+    /// and judge `expires` relative to the daemon. Zero in payloads from
+    /// daemons that predate it.
+    #[serde(default)]
+    pub issued_at: u64,
+
     /// Unix timestamp when this pairing code expires.
     pub expires: u64,
 }
@@ -136,17 +143,17 @@ impl PairingInfo {
         expiry_seconds: Option<u64>,
     ) -> Self {
         let expiry = expiry_seconds.unwrap_or(DEFAULT_EXPIRY_SECONDS);
-        let expires = SystemTime::now()
+        let issued_at = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
-            .as_secs()
-            + expiry;
+            .as_secs();
 
         Self {
             device_id,
             public_key: BASE64.encode(public_key),
             relay_url,
-            expires,
+            issued_at,
+            expires: issued_at + expiry,
         }
     }
 
@@ -493,6 +500,56 @@ pub fn generate_pairing_code() -> String {
     format!("{}-{}", part1, part2)
 }
 
+/// Generates a random numeric pairing code in `DDD-DDD-DDDD` format.
+///
+/// Nine random digits are followed by a Luhn check digit, so a code with a
+/// single mistyped digit or two swapped neighbours is rejected before it is
+/// looked up. Numeric codes are meant to be typed on devices without a
+/// camera, where the alphanumeric code is awkward to enter.
+pub fn generate_numeric_pairing_code() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut digits: String = (0..9)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+        .collect();
+    digits.push(char::from(b'0' + luhn_check_digit(&digits)));
+    format!("{}-{}-{}", &digits[..3], &digits[3..6], &digits[6..])
+}
+
+/// Returns true if `code` is a numeric pairing code with a valid check digit.
+///
+/// Spaces and hyphens are ignored.
+pub fn is_valid_numeric_pairing_code(code: &str) -> bool {
+    let digits: String = code.chars().filter(|c| !matches!(c, '-' | ' ')).collect();
+    if digits.len() != 10 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    luhn_check_digit(&digits[..9]) == digits.as_bytes()[9] - b'0'
+}
+
+/// Computes the Luhn check digit for a string of ASCII digits.
+fn luhn_check_digit(digits: &str) -> u8 {
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| {
+            let d = u32::from(b - b'0');
+            if i % 2 == 0 {
+                let doubled = d * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                d
+            }
+        })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
 /// Builds a pairing URL by appending the code as a `?peer=` query parameter.
 pub fn pairing_url(web_app_base: &str, code: &str) -> String {
     let base = web_app_base.trim_end_matches('/');
@@ -725,6 +782,7 @@ mod tests {
             device_id: "TestDeviceId".to_string(),
             public_key: BASE64.encode(public_key),
             relay_url: "wss://relay.example.com".to_string(),
+            issued_at: 1234567590,
             expires: 1234567890,
         };
 
@@ -733,10 +791,30 @@ mod tests {
         // Verify JSON structure
         assert!(json.contains("\"device_id\":\"TestDeviceId\""));
         assert!(json.contains("\"relay_url\":\"wss://relay.example.com\""));
+        assert!(json.contains("\"issued_at\":1234567590"));
         assert!(json.contains("\"expires\":1234567890"));
         assert!(json.contains("\"public_key\":"));
     }
 
+    #[test]
+    fn test_pairing_info_issued_at() {
+        let info = PairingInfo::new(
+            "device".to_string(),
+            &[0u8; 32],
+            "wss://relay.example.com".to_string(),
+            Some(120),
+        );
+        assert_eq!(info.expires - info.issued_at, 120);
+
+        // Payloads from older daemons have no issue time
+        let legacy = PairingInfo::from_json(
+            r#"{"device_id":"d","public_key":"k","relay_url":"wss://r","expires":1234567890}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.issued_at, 0);
+        assert_eq!(legacy.expires, 1234567890);
+    }
+
     #[test]
     fn test_terminal_qr_generation() {
         let public_key = [1u8; 32];
@@ -973,6 +1051,34 @@ mod tests {
         assert_ne!(code1, code2, "Two consecutive codes should be different");
     }
 
+    #[test]
+    fn test_generate_numeric_pairing_code() {
+        let code = generate_numeric_pairing_code();
+        let parts: Vec<&str> = code.split('-').collect();
+        assert_eq!(
+            parts.iter().map(|p| p.len()).collect::<Vec<_>>(),
+            vec![3, 3, 4]
+        );
+        assert!(code.chars().all(|c| c == '-' || c.is_ascii_digit()));
+        assert!(is_valid_numeric_pairing_code(&code));
+    }
+
+    #[test]
+    fn test_numeric_pairing_code_check_digit() {
+        assert_eq!(luhn_check_digit("799273987"), 5);
+        assert!(is_valid_numeric_pairing_code("799-273-9875"));
+        assert!(is_valid_numeric_pairing_code("799 273 9875"));
+        assert!(is_valid_numeric_pairing_code("7992739875"));
+
+        // Single wrong digit
+        assert!(!is_valid_numeric_pairing_code("799-273-9876"));
+        // Adjacent transposition
+        assert!(!is_valid_numeric_pairing_code("979-273-9875"));
+        // Wrong length or characters
+        assert!(!is_valid_numeric_pairing_code("799-273-987"));
+        assert!(!is_valid_numeric_pairing_code("AXBK-7392"));
+    }
+
     #[test]
     fn test_pairing_url_basic() {
         let url = pairing_url("https://moukrea.github.io/remoshell/", "AXBK-7392");
@@ -284,6 +284,55 @@ WantedBy=default.target
     )
 }
 
+/// Generate a systemd template unit file for named daemon instances.
+///
+/// Save it as `~/.config/systemd/user/remoshell@.service`; each instance
+/// is then started with `systemctl --user start remoshell@<name>`, which
+/// runs the daemon with `--instance <name>`. Instances have their own
+/// socket, PID file and data directory, so several can run side by side.
+///
+/// # Arguments
+///
+/// * `exec_path` - Optional path to the executable. If `None`, uses `/usr/bin/remoshell-daemon`.
+pub fn generate_template_unit_file(exec_path: Option<&str>) -> String {
+    let exec = exec_path.unwrap_or("/usr/bin/remoshell-daemon");
+
+    format!(
+        r#"[Unit]
+Description=RemoteShell P2P Terminal Daemon (%i)
+Documentation=https://github.com/remoshell/remoshell
+After=network.target
+
+[Service]
+Type=notify
+ExecStart={} --instance %i start --systemd
+Restart=on-failure
+RestartSec=5
+TimeoutStartSec=30
+TimeoutStopSec=30
+
+# Security hardening
+NoNewPrivileges=yes
+ProtectSystem=strict
+ProtectHome=read-only
+PrivateTmp=yes
+ProtectKernelTunables=yes
+ProtectKernelModules=yes
+ProtectControlGroups=yes
+
+# Allow network access
+RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6
+
+# Allow reading user config and writing instance data
+ReadWritePaths=%h/.config/remoshell %h/.local/share/remoshell
+
+[Install]
+WantedBy=default.target
+"#,
+        exec
+    )
+}
+
 /// Generate a minimal systemd unit file without security hardening.
 ///
 /// This is useful for development or when the hardened unit file
@@ -611,6 +660,22 @@ mod tests {
         assert!(unit_file.contains("PrivateTmp=yes"));
     }
 
+    #[test]
+    fn test_generate_template_unit_file() {
+        let unit_file = generate_template_unit_file(None);
+
+        assert!(unit_file.contains("Type=notify"));
+        assert!(
+            unit_file.contains("ExecStart=/usr/bin/remoshell-daemon --instance %i start --systemd")
+        );
+        assert!(unit_file.contains("Description=RemoteShell P2P Terminal Daemon (%i)"));
+        assert!(unit_file.contains("NoNewPrivileges=yes"));
+        assert!(unit_file.contains("WantedBy=default.target"));
+
+        let custom = generate_template_unit_file(Some("/opt/bin/remoshell"));
+        assert!(custom.contains("ExecStart=/opt/bin/remoshell --instance %i start --systemd"));
+    }
+
     #[test]
     fn test_generate_minimal_unit_file() {
         let unit_file = generate_minimal_unit_file(None);
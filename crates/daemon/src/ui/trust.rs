@@ -0,0 +1,40 @@
+//! Device trust levels as shown to users.
+
+use crate::util::i18n::tr;
+
+/// Trust level for display purposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayTrustLevel {
+    /// Unknown device, requires approval.
+    Unknown,
+    /// Trusted device.
+    Trusted,
+    /// Fully trusted device (can perform sensitive operations).
+    FullyTrusted,
+    /// Revoked device (permanently blocked).
+    Revoked,
+}
+
+impl DisplayTrustLevel {
+    /// Returns the display string for this trust level.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DisplayTrustLevel::Unknown => tr("tui.trust.unknown"),
+            DisplayTrustLevel::Trusted => tr("tui.trust.trusted"),
+            DisplayTrustLevel::FullyTrusted => tr("tui.trust.fully_trusted"),
+            DisplayTrustLevel::Revoked => tr("tui.trust.revoked"),
+        }
+    }
+}
+
+impl From<crate::devices::TrustLevel> for DisplayTrustLevel {
+    fn from(level: crate::devices::TrustLevel) -> Self {
+        use crate::devices::TrustLevel;
+
+        match level {
+            TrustLevel::Unknown => DisplayTrustLevel::Unknown,
+            TrustLevel::Trusted => DisplayTrustLevel::Trusted,
+            TrustLevel::Revoked => DisplayTrustLevel::Revoked,
+        }
+    }
+}
@@ -6,7 +6,9 @@
 
 use std::io::{self, Stdout};
 use std::net::IpAddr;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use crossterm::{
     event::{
@@ -25,6 +27,17 @@ use ratatui::{
 };
 use tokio::sync::mpsc;
 
+use crate::devices::names::is_mixed_script;
+use crate::devices::{PendingSession, SessionConfirmations};
+use crate::maintenance::MaintenanceMode;
+use crate::ui::announce::Answer;
+pub use crate::ui::trust::DisplayTrustLevel;
+use crate::util::format::format_duration;
+use crate::util::i18n::{tr, tr_with};
+
+/// Fingerprint words per line in the pairing overlay.
+const PAIRING_WORDS_PER_LINE: usize = 4;
+
 /// Action to take on an approval request.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ApprovalAction {
@@ -57,9 +70,16 @@ pub enum TuiEvent {
         device_id: String,
         name: String,
         trust_level: DisplayTrustLevel,
+        /// When the device's temporary grant expires, if it has one.
+        grant_expires_at: Option<SystemTime>,
     },
     /// A device disconnected.
     DeviceDisconnected { device_id: String },
+    /// A device's temporary grant ended, leaving it at `trust_level`.
+    GrantEnded {
+        device_id: String,
+        trust_level: DisplayTrustLevel,
+    },
     /// A new session was created.
     SessionCreated {
         session_id: String,
@@ -69,6 +89,12 @@ pub enum TuiEvent {
     },
     /// A session was terminated.
     SessionTerminated { session_id: String },
+    /// Shell integration metadata for a session changed.
+    SessionMetadata {
+        session_id: String,
+        cwd: Option<String>,
+        last_exit_code: Option<i32>,
+    },
     /// A device is requesting approval.
     ApprovalRequested {
         device_id: String,
@@ -136,10 +162,10 @@ impl Tab {
     /// Returns the title of the tab.
     pub fn title(&self) -> &'static str {
         match self {
-            Tab::Status => "Status",
-            Tab::Sessions => "Sessions",
-            Tab::Devices => "Devices",
-            Tab::Approvals => "Approvals",
+            Tab::Status => tr("tui.tab.status"),
+            Tab::Sessions => tr("tui.tab.sessions"),
+            Tab::Devices => tr("tui.tab.devices"),
+            Tab::Approvals => tr("tui.tab.approvals"),
         }
     }
 
@@ -160,19 +186,6 @@ impl Tab {
     }
 }
 
-/// Trust level for display purposes.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum DisplayTrustLevel {
-    /// Unknown device, requires approval.
-    Unknown,
-    /// Trusted device.
-    Trusted,
-    /// Fully trusted device (can perform sensitive operations).
-    FullyTrusted,
-    /// Revoked device (permanently blocked).
-    Revoked,
-}
-
 impl DisplayTrustLevel {
     /// Returns the display color for this trust level.
     pub fn color(&self) -> Color {
@@ -183,16 +196,6 @@ impl DisplayTrustLevel {
             DisplayTrustLevel::Revoked => Color::Red,
         }
     }
-
-    /// Returns the display string for this trust level.
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            DisplayTrustLevel::Unknown => "Unknown",
-            DisplayTrustLevel::Trusted => "Trusted",
-            DisplayTrustLevel::FullyTrusted => "Fully Trusted",
-            DisplayTrustLevel::Revoked => "Revoked",
-        }
-    }
 }
 
 /// Information about a connected device for display.
@@ -210,6 +213,21 @@ pub struct DeviceInfo {
     pub last_seen: String,
     /// Number of active sessions from this device.
     pub session_count: usize,
+    /// When the device's temporary grant expires, if it has one.
+    pub grant_expires_at: Option<SystemTime>,
+}
+
+impl DeviceInfo {
+    /// Returns the seconds left on the device's temporary grant, if it has
+    /// one.
+    pub fn grant_remaining_secs(&self) -> Option<u64> {
+        self.grant_expires_at.map(|expires_at| {
+            expires_at
+                .duration_since(SystemTime::now())
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        })
+    }
 }
 
 /// Information about an active session for display.
@@ -227,6 +245,10 @@ pub struct SessionInfo {
     pub started_at: Instant,
     /// Number of connected subscribers.
     pub subscriber_count: usize,
+    /// Working directory reported by shell integration.
+    pub cwd: Option<String>,
+    /// Exit status of the last command reported by shell integration.
+    pub last_exit_code: Option<i32>,
 }
 
 /// Information about a pending approval request.
@@ -253,6 +275,8 @@ pub struct DaemonStats {
     pub sessions_active: usize,
     /// Daemon uptime in seconds.
     pub uptime_secs: u64,
+    /// Whether the daemon is in maintenance.
+    pub maintenance: bool,
 }
 
 /// Configuration for generating pairing codes on demand.
@@ -264,6 +288,8 @@ pub struct PairingConfig {
     pub public_key_bytes: [u8; 32],
     /// Relay/signaling server URL.
     pub relay_url: String,
+    /// Store the shown codes are registered in, so each can be used once.
+    pub pairing_codes: crate::devices::PairingCodeStore,
 }
 
 /// State of the pairing overlay modal.
@@ -312,6 +338,12 @@ pub struct TuiApp {
     pairing_config: Option<PairingConfig>,
     /// Current state of the pairing overlay.
     pairing_overlay: PairingOverlay,
+    /// Whether the user asked to suspend the TUI (Ctrl-Z).
+    suspend_requested: bool,
+    /// Sessions waiting for confirmation, if sessions must be confirmed.
+    session_confirmations: Option<Arc<SessionConfirmations>>,
+    /// Maintenance mode, shown in the status bar while on.
+    maintenance: Option<Arc<MaintenanceMode>>,
 }
 
 impl TuiApp {
@@ -356,6 +388,9 @@ impl TuiApp {
                 approval_rx: Some(approval_rx),
                 pairing_config,
                 pairing_overlay: PairingOverlay::Hidden,
+                suspend_requested: false,
+                session_confirmations: None,
+                maintenance: None,
             },
             tx_clone,
         ))
@@ -396,6 +431,9 @@ impl TuiApp {
                 approval_rx: Some(approval_rx),
                 pairing_config: None,
                 pairing_overlay: PairingOverlay::Hidden,
+                suspend_requested: false,
+                session_confirmations: None,
+                maintenance: None,
             },
             tx_clone,
         ))
@@ -415,6 +453,24 @@ impl TuiApp {
         self.approval_rx.take()
     }
 
+    /// Asks about each session waiting in `confirmations` in a prompt drawn
+    /// over the current tab.
+    pub fn set_session_confirmations(&mut self, confirmations: Arc<SessionConfirmations>) {
+        self.session_confirmations = Some(confirmations);
+    }
+
+    /// Shows in the status bar whether `maintenance` is on.
+    pub fn set_maintenance(&mut self, maintenance: Arc<MaintenanceMode>) {
+        self.maintenance = Some(maintenance);
+    }
+
+    /// Returns the oldest session waiting for confirmation, if any.
+    fn pending_session(&self) -> Option<PendingSession> {
+        self.session_confirmations
+            .as_ref()
+            .and_then(|confirmations| confirmations.pending().into_iter().next())
+    }
+
     /// Returns the current state of the "always trust" toggle.
     pub fn always_trust(&self) -> bool {
         self.always_trust
@@ -499,6 +555,25 @@ impl TuiApp {
 
     /// Handles a keyboard event.
     pub fn handle_key(&mut self, key: KeyEvent) {
+        // A session waiting for confirmation takes y/n before anything else
+        if let Some(pending) = self.pending_session() {
+            if let KeyCode::Char(c) = key.code {
+                if let (Some(answer), Some(confirmations)) = (
+                    Answer::parse(&c.to_string()),
+                    self.session_confirmations.as_ref(),
+                ) {
+                    let allow = answer == Answer::Approve;
+                    tracing::info!(
+                        "{} session for device {}",
+                        if allow { "Allowing" } else { "Denying" },
+                        pending.device_id
+                    );
+                    confirmations.answer(pending.id, allow);
+                    return;
+                }
+            }
+        }
+
         // If pairing overlay is active, only handle overlay keys
         if self.is_pairing_overlay_visible() {
             match key.code {
@@ -516,6 +591,9 @@ impl TuiApp {
             KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 self.should_quit = true;
             }
+            _ if is_suspend_key(&key) => {
+                self.suspend_requested = true;
+            }
             KeyCode::Tab | KeyCode::Right => {
                 self.next_tab();
             }
@@ -536,25 +614,17 @@ impl TuiApp {
                 self.handle_enter();
             }
             // Approval tab specific keys
-            KeyCode::Char('a') | KeyCode::Char('A') => {
-                if self.current_tab == Tab::Approvals {
-                    self.handle_approval_action(ApprovalAction::Accept);
-                }
+            KeyCode::Char('a') | KeyCode::Char('A') if self.current_tab == Tab::Approvals => {
+                self.handle_approval_action(ApprovalAction::Accept);
             }
-            KeyCode::Char('r') | KeyCode::Char('R') => {
-                if self.current_tab == Tab::Approvals {
-                    self.handle_approval_action(ApprovalAction::Reject);
-                }
+            KeyCode::Char('r') | KeyCode::Char('R') if self.current_tab == Tab::Approvals => {
+                self.handle_approval_action(ApprovalAction::Reject);
             }
-            KeyCode::Char('t') | KeyCode::Char('T') => {
-                if self.current_tab == Tab::Approvals {
-                    self.toggle_always_trust();
-                }
+            KeyCode::Char('t') | KeyCode::Char('T') if self.current_tab == Tab::Approvals => {
+                self.toggle_always_trust();
             }
-            KeyCode::Char('p') | KeyCode::Char('P') => {
-                if self.current_tab == Tab::Devices {
-                    self.show_pairing_overlay();
-                }
+            KeyCode::Char('p') | KeyCode::Char('P') if self.current_tab == Tab::Devices => {
+                self.show_pairing_overlay();
             }
             _ => {}
         }
@@ -666,6 +736,13 @@ impl TuiApp {
 
         // Generate a short pairing code
         let pairing_code = super::qr::generate_pairing_code();
+        if let Err(e) = config.pairing_codes.register(
+            &pairing_code,
+            std::time::Duration::from_secs(pairing_info.seconds_until_expiry()),
+        ) {
+            tracing::error!("Failed to record pairing code: {}", e);
+            return;
+        }
 
         // Build URL for QR code
         let url = super::qr::pairing_url(crate::config::DEFAULT_WEB_APP_URL, &pairing_code);
@@ -723,14 +800,16 @@ impl TuiApp {
                 device_id,
                 name,
                 trust_level,
+                grant_expires_at,
             } => {
                 self.devices.push(DeviceInfo {
                     id: device_id,
                     name,
                     connected: true,
                     trust_level,
-                    last_seen: "Just now".to_string(),
+                    last_seen: tr("tui.device.just_now").to_string(),
                     session_count: 0,
+                    grant_expires_at,
                 });
             }
             TuiEvent::DeviceDisconnected { device_id } => {
@@ -738,6 +817,15 @@ impl TuiApp {
                     self.devices[pos].connected = false;
                 }
             }
+            TuiEvent::GrantEnded {
+                device_id,
+                trust_level,
+            } => {
+                for device in self.devices.iter_mut().filter(|d| d.id == device_id) {
+                    device.trust_level = trust_level;
+                    device.grant_expires_at = None;
+                }
+            }
             TuiEvent::SessionCreated {
                 session_id,
                 device_id,
@@ -751,11 +839,23 @@ impl TuiApp {
                     pid,
                     started_at: Instant::now(),
                     subscriber_count: 1, // Initial subscriber (the creator)
+                    cwd: None,
+                    last_exit_code: None,
                 });
             }
             TuiEvent::SessionTerminated { session_id } => {
                 self.sessions.retain(|s| s.id != session_id);
             }
+            TuiEvent::SessionMetadata {
+                session_id,
+                cwd,
+                last_exit_code,
+            } => {
+                if let Some(session) = self.sessions.iter_mut().find(|s| s.id == session_id) {
+                    session.cwd = cwd;
+                    session.last_exit_code = last_exit_code;
+                }
+            }
             TuiEvent::ApprovalRequested {
                 device_id,
                 name,
@@ -808,6 +908,7 @@ impl TuiApp {
     pub fn draw(&mut self) -> io::Result<()> {
         // Update uptime from start time
         self.stats.uptime_secs = self.start_time.elapsed().as_secs();
+        self.stats.maintenance = self.maintenance.as_ref().is_some_and(|m| m.is_on());
 
         // Clone state for drawing
         let current_tab = self.current_tab;
@@ -818,6 +919,7 @@ impl TuiApp {
         let always_trust = self.always_trust;
         let mut list_state = self.list_state.clone();
         let pairing_overlay = self.pairing_overlay.clone();
+        let pending_session = self.pending_session();
 
         self.terminal.draw(|frame| {
             Self::render_frame(
@@ -830,6 +932,7 @@ impl TuiApp {
                 always_trust,
                 &mut list_state,
                 &pairing_overlay,
+                pending_session.as_ref(),
             );
         })?;
 
@@ -849,6 +952,7 @@ impl TuiApp {
         always_trust: bool,
         list_state: &mut ListState,
         pairing_overlay: &PairingOverlay,
+        pending_session: Option<&PendingSession>,
     ) {
         let size = frame.area();
 
@@ -895,6 +999,65 @@ impl TuiApp {
                 pairing_code,
             );
         }
+
+        // A session waiting for confirmation goes over everything else
+        if let Some(pending) = pending_session {
+            Self::render_session_confirmation(frame, frame.area(), pending);
+        }
+    }
+
+    /// Renders the prompt for a session waiting for confirmation.
+    fn render_session_confirmation(frame: &mut Frame, area: Rect, pending: &PendingSession) {
+        let modal_w = 56.min(area.width.saturating_sub(2));
+        let modal_h = 8.min(area.height.saturating_sub(2));
+        let x = area.x + (area.width.saturating_sub(modal_w)) / 2;
+        let y = area.y + (area.height.saturating_sub(modal_h)) / 2;
+        let modal_rect = Rect::new(x, y, modal_w, modal_h);
+
+        frame.render_widget(Clear, modal_rect);
+
+        let lines = vec![
+            Line::from(vec![
+                Span::styled(
+                    tr_with(
+                        "tui.confirm.request",
+                        &[("name", &pending.device_name.as_str())],
+                    ),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                mixed_script_warning(&pending.device_name),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    format!("{} ", tr("tui.confirm.device")),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::styled(
+                    pending.device_id.to_string(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]),
+            Line::from(Span::styled(
+                tr_with("tui.confirm.expires", &[("secs", &pending.expires_in_secs)]),
+                Style::default().fg(Color::Red),
+            )),
+            Line::from(""),
+            Line::from(Span::styled(
+                tr("tui.confirm.help"),
+                Style::default().fg(Color::DarkGray),
+            )),
+        ];
+
+        let paragraph = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" {} ", tr("tui.confirm.title")))
+                .border_style(Style::default().fg(Color::Yellow))
+                .style(Style::default().bg(Color::Reset)),
+        );
+        frame.render_widget(paragraph, modal_rect);
     }
 
     /// Renders the tab bar.
@@ -944,9 +1107,12 @@ impl TuiApp {
 
         let text = vec![
             Line::from(vec![
-                Span::styled("Daemon Status: ", Style::default().fg(Color::Gray)),
                 Span::styled(
-                    "Running",
+                    format!("{} ", tr("tui.status.daemon")),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::styled(
+                    tr("tui.status.running"),
                     Style::default()
                         .fg(Color::Green)
                         .add_modifier(Modifier::BOLD),
@@ -954,19 +1120,28 @@ impl TuiApp {
             ]),
             Line::from(""),
             Line::from(vec![
-                Span::styled("Uptime: ", Style::default().fg(Color::Gray)),
+                Span::styled(
+                    format!("{} ", tr("tui.status.uptime")),
+                    Style::default().fg(Color::Gray),
+                ),
                 Span::styled(uptime, Style::default().fg(Color::Cyan)),
             ]),
             Line::from(""),
             Line::from(vec![
-                Span::styled("Connected Devices: ", Style::default().fg(Color::Gray)),
+                Span::styled(
+                    format!("{} ", tr("tui.status.connected_devices")),
+                    Style::default().fg(Color::Gray),
+                ),
                 Span::styled(
                     connected_devices.to_string(),
                     Style::default().fg(Color::Yellow),
                 ),
             ]),
             Line::from(vec![
-                Span::styled("Active Sessions: ", Style::default().fg(Color::Gray)),
+                Span::styled(
+                    format!("{} ", tr("tui.status.active_sessions")),
+                    Style::default().fg(Color::Gray),
+                ),
                 Span::styled(
                     sessions.len().to_string(),
                     Style::default().fg(Color::Yellow),
@@ -974,7 +1149,7 @@ impl TuiApp {
             ]),
             Line::from(""),
             Line::from(vec![Span::styled(
-                "Press Tab/Arrow keys to switch tabs, q to quit",
+                tr("tui.status.help"),
                 Style::default().fg(Color::DarkGray),
             )]),
         ];
@@ -983,7 +1158,7 @@ impl TuiApp {
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title(" Status Overview "),
+                    .title(format!(" {} ", tr("tui.status.title"))),
             )
             .style(Style::default().fg(Color::White));
 
@@ -1023,11 +1198,10 @@ impl TuiApp {
             .collect();
 
         let list = List::new(items)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(format!(" Sessions ({}) ", sessions.len())),
-            )
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                " {} ",
+                tr_with("tui.sessions.title", &[("count", &sessions.len())])
+            )))
             .highlight_style(
                 Style::default()
                     .bg(Color::DarkGray)
@@ -1056,27 +1230,39 @@ impl TuiApp {
 
             vec![
                 Line::from(vec![
-                    Span::styled("Session ID: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.session.id")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(&s.id, Style::default().fg(Color::Cyan)),
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Device: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.session.device")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(&s.device_id, Style::default().fg(Color::Yellow)),
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("PID: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.session.pid")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(
                         s.pid
                             .map(|p| p.to_string())
-                            .unwrap_or_else(|| "N/A".to_string()),
+                            .unwrap_or_else(|| tr("tui.not_available").to_string()),
                         Style::default().fg(Color::White),
                     ),
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Terminal Size: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.session.size")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(
                         format!("{} x {}", s.size.0, s.size.1),
                         Style::default().fg(Color::White),
@@ -1084,7 +1270,10 @@ impl TuiApp {
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Subscribers: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.session.subscribers")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(
                         s.subscriber_count.to_string(),
                         Style::default().fg(Color::Magenta),
@@ -1092,20 +1281,50 @@ impl TuiApp {
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Uptime: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.session.uptime")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(uptime_str, Style::default().fg(Color::Green)),
                 ]),
+                Line::from(""),
+                Line::from(vec![
+                    Span::styled(
+                        format!("{} ", tr("tui.session.directory")),
+                        Style::default().fg(Color::Gray),
+                    ),
+                    Span::styled(
+                        s.cwd.as_deref().unwrap_or(tr("tui.not_available")),
+                        Style::default().fg(Color::White),
+                    ),
+                ]),
+                Line::from(""),
+                Line::from(vec![
+                    Span::styled(
+                        format!("{} ", tr("tui.session.last_exit")),
+                        Style::default().fg(Color::Gray),
+                    ),
+                    match s.last_exit_code {
+                        Some(0) => Span::styled("0", Style::default().fg(Color::Green)),
+                        Some(code) => {
+                            Span::styled(code.to_string(), Style::default().fg(Color::Red))
+                        }
+                        None => {
+                            Span::styled(tr("tui.not_available"), Style::default().fg(Color::White))
+                        }
+                    },
+                ]),
             ]
         } else {
             vec![
                 Line::from(""),
                 Line::from(vec![Span::styled(
-                    "  No session selected",
+                    format!("  {}", tr("tui.session.none")),
                     Style::default().fg(Color::DarkGray),
                 )]),
                 Line::from(""),
                 Line::from(vec![Span::styled(
-                    "  Use j/k or Up/Down to select",
+                    format!("  {}", tr("tui.select_hint")),
                     Style::default().fg(Color::DarkGray),
                 )]),
             ]
@@ -1115,7 +1334,7 @@ impl TuiApp {
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title(" Session Details "),
+                    .title(format!(" {} ", tr("tui.session.title"))),
             )
             .style(Style::default().fg(Color::White));
 
@@ -1134,13 +1353,13 @@ impl TuiApp {
         let content = if let Some(d) = device {
             let status_span = if d.connected {
                 Span::styled(
-                    "Online",
+                    tr("tui.device.online"),
                     Style::default()
                         .fg(Color::Green)
                         .add_modifier(Modifier::BOLD),
                 )
             } else {
-                Span::styled("Offline", Style::default().fg(Color::Red))
+                Span::styled(tr("tui.device.offline"), Style::default().fg(Color::Red))
             };
 
             let trust_span = Span::styled(
@@ -1150,9 +1369,12 @@ impl TuiApp {
                     .add_modifier(Modifier::BOLD),
             );
 
-            vec![
+            let mut lines = vec![
                 Line::from(vec![
-                    Span::styled("Device Name: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.device.name")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(
                         &d.name,
                         Style::default()
@@ -1162,43 +1384,74 @@ impl TuiApp {
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Device ID: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.device.id")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(&d.id, Style::default().fg(Color::DarkGray)),
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Status: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.device.status")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     status_span,
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Trust Level: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.device.trust")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     trust_span,
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Last Seen: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.device.last_seen")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(&d.last_seen, Style::default().fg(Color::White)),
                 ]),
                 Line::from(""),
                 Line::from(vec![
-                    Span::styled("Active Sessions: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{} ", tr("tui.device.active_sessions")),
+                        Style::default().fg(Color::Gray),
+                    ),
                     Span::styled(
                         d.session_count.to_string(),
                         Style::default().fg(Color::Magenta),
                     ),
                 ]),
-            ]
+            ];
+            if let Some(secs) = d.grant_remaining_secs() {
+                let time = format_duration(secs);
+                lines.push(Line::from(""));
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        format!("{} ", tr("tui.device.grant")),
+                        Style::default().fg(Color::Gray),
+                    ),
+                    Span::styled(
+                        tr_with("tui.device.grant_left", &[("time", &time)]),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                ]));
+            }
+            lines.extend(fingerprint_lines(&d.id));
+            lines
         } else {
             vec![
                 Line::from(""),
                 Line::from(vec![Span::styled(
-                    "  No device selected",
+                    format!("  {}", tr("tui.device.none")),
                     Style::default().fg(Color::DarkGray),
                 )]),
                 Line::from(""),
                 Line::from(vec![Span::styled(
-                    "  Use j/k or Up/Down to select",
+                    format!("  {}", tr("tui.select_hint")),
                     Style::default().fg(Color::DarkGray),
                 )]),
             ]
@@ -1208,7 +1461,7 @@ impl TuiApp {
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title(" Device Details "),
+                    .title(format!(" {} ", tr("tui.device.title"))),
             )
             .style(Style::default().fg(Color::White));
 
@@ -1251,22 +1504,31 @@ impl TuiApp {
                         Span::styled(" [?]", Style::default().fg(Color::Yellow))
                     }
                 };
+                // Counts down the time left on a temporary grant
+                let grant = match d.grant_remaining_secs() {
+                    Some(secs) => Span::styled(
+                        format!(" {}", format_duration(secs)),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    None => Span::raw(""),
+                };
                 let content = Line::from(vec![
                     status,
                     Span::raw(" "),
                     Span::styled(&d.name, Style::default().fg(Color::Cyan)),
+                    mixed_script_warning(&d.name),
                     trust_indicator,
+                    grant,
                 ]);
                 ListItem::new(content)
             })
             .collect();
 
         let list = List::new(items)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(format!(" Devices ({}) ", devices.len())),
-            )
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                " {} ",
+                tr_with("tui.devices.title", &[("count", &devices.len())])
+            )))
             .highlight_style(
                 Style::default()
                     .bg(Color::DarkGray)
@@ -1307,7 +1569,7 @@ impl TuiApp {
                 // Format IP address
                 let ip_str = match &a.ip_address {
                     Some(ip) => ip.to_string(),
-                    None => "unknown".to_string(),
+                    None => tr("tui.approvals.unknown_ip").to_string(),
                 };
 
                 // Truncate device ID for display
@@ -1324,11 +1586,12 @@ impl TuiApp {
                             .fg(Color::Yellow)
                             .add_modifier(Modifier::BOLD),
                     ),
-                    Span::raw(" | ID: "),
+                    mixed_script_warning(&a.name),
+                    Span::raw(format!(" | {} ", tr("tui.approvals.id"))),
                     Span::styled(truncated_id, Style::default().fg(Color::DarkGray)),
-                    Span::raw(" | IP: "),
+                    Span::raw(format!(" | {} ", tr("tui.approvals.ip"))),
                     Span::styled(ip_str, Style::default().fg(Color::Cyan)),
-                    Span::raw(" | Waiting: "),
+                    Span::raw(format!(" | {} ", tr("tui.approvals.waiting"))),
                     Span::styled(age, Style::default().fg(Color::Magenta)),
                 ]);
                 ListItem::new(content)
@@ -1336,9 +1599,12 @@ impl TuiApp {
             .collect();
 
         let title = if approvals.is_empty() {
-            " Pending Approvals (none) ".to_string()
+            format!(" {} ", tr("tui.approvals.title_none"))
         } else {
-            format!(" Pending Approvals ({}) ", approvals.len())
+            format!(
+                " {} ",
+                tr_with("tui.approvals.title", &[("count", &approvals.len())])
+            )
         };
 
         let list = List::new(items)
@@ -1355,13 +1621,16 @@ impl TuiApp {
         // Render help text with always_trust checkbox
         let trust_checkbox = if always_trust {
             Span::styled(
-                "[X] Always trust",
+                format!("[X] {}", tr("tui.approvals.always_trust")),
                 Style::default()
                     .fg(Color::Green)
                     .add_modifier(Modifier::BOLD),
             )
         } else {
-            Span::styled("[ ] Always trust", Style::default().fg(Color::Gray))
+            Span::styled(
+                format!("[ ] {}", tr("tui.approvals.always_trust")),
+                Style::default().fg(Color::Gray),
+            )
         };
 
         let help_lines = vec![
@@ -1372,33 +1641,37 @@ impl TuiApp {
                         .fg(Color::Green)
                         .add_modifier(Modifier::BOLD),
                 ),
-                Span::raw(": Accept  "),
+                Span::raw(format!(": {}  ", tr("tui.approvals.accept"))),
                 Span::styled(
                     "R",
                     Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
                 ),
-                Span::raw(": Reject  "),
+                Span::raw(format!(": {}  ", tr("tui.approvals.reject"))),
                 Span::styled(
                     "T",
                     Style::default()
                         .fg(Color::Yellow)
                         .add_modifier(Modifier::BOLD),
                 ),
-                Span::raw(": Toggle trust  "),
+                Span::raw(format!(": {}  ", tr("tui.approvals.toggle_trust"))),
                 trust_checkbox,
             ]),
             Line::from(vec![
                 Span::styled("  j/k", Style::default().fg(Color::Cyan)),
-                Span::raw(" or "),
+                Span::raw(format!(" {} ", tr("tui.approvals.or"))),
                 Span::styled("Up/Down", Style::default().fg(Color::Cyan)),
-                Span::raw(": Navigate  "),
+                Span::raw(format!(": {}  ", tr("tui.approvals.navigate"))),
                 Span::styled("Enter", Style::default().fg(Color::Cyan)),
-                Span::raw(": Accept"),
+                Span::raw(format!(": {}", tr("tui.approvals.accept"))),
             ]),
         ];
 
         let help_paragraph = Paragraph::new(help_lines)
-            .block(Block::default().borders(Borders::ALL).title(" Actions "))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {} ", tr("tui.approvals.actions"))),
+            )
             .style(Style::default().fg(Color::White));
 
         frame.render_widget(help_paragraph, chunks[1]);
@@ -1408,33 +1681,54 @@ impl TuiApp {
     fn render_status_bar(frame: &mut Frame, area: Rect, stats: &DaemonStats, current_tab: Tab) {
         let uptime = format_duration(stats.uptime_secs);
 
-        let mut spans = vec![
-            Span::styled(" Devices: ", Style::default().fg(Color::Gray)),
+        let mut spans = Vec::new();
+        if stats.maintenance {
+            spans.push(Span::styled(
+                format!(" {} |", tr("tui.bar.maintenance")),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ));
+        }
+        spans.extend([
+            Span::styled(
+                format!(" {} ", tr("tui.bar.devices")),
+                Style::default().fg(Color::Gray),
+            ),
             Span::styled(
                 stats.devices_connected.to_string(),
                 Style::default().fg(Color::Cyan),
             ),
-            Span::styled(" | Sessions: ", Style::default().fg(Color::Gray)),
+            Span::styled(
+                format!(" | {} ", tr("tui.bar.sessions")),
+                Style::default().fg(Color::Gray),
+            ),
             Span::styled(
                 stats.sessions_active.to_string(),
                 Style::default().fg(Color::Cyan),
             ),
-            Span::styled(" | Uptime: ", Style::default().fg(Color::Gray)),
+            Span::styled(
+                format!(" | {} ", tr("tui.bar.uptime")),
+                Style::default().fg(Color::Gray),
+            ),
             Span::styled(uptime, Style::default().fg(Color::Cyan)),
-            Span::styled(" | Press 'q' to quit", Style::default().fg(Color::DarkGray)),
-        ];
+            Span::styled(
+                format!(" | {}", tr("tui.bar.quit")),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]);
 
         // Add tab-specific hints
         match current_tab {
             Tab::Devices => {
                 spans.push(Span::styled(
-                    " | 'p' to pair",
+                    format!(" | {}", tr("tui.bar.pair")),
                     Style::default().fg(Color::DarkGray),
                 ));
             }
             Tab::Approvals => {
                 spans.push(Span::styled(
-                    " | 'a'ccept 'r'eject 't'rust",
+                    format!(" | {}", tr("tui.bar.approvals")),
                     Style::default().fg(Color::DarkGray),
                 ));
             }
@@ -1460,17 +1754,23 @@ impl TuiApp {
         pairing_code: &str,
     ) {
         let quiet_zone: usize = 4;
-        let qr_height = if qr_width > 0 {
-            qr_modules.len() / qr_width
-        } else {
-            0
-        };
+        let qr_height = qr_modules.len().checked_div(qr_width).unwrap_or(0);
         let full_w = qr_width + 2 * quiet_zone;
         let full_h = qr_height + 2 * quiet_zone;
         let qr_rows = full_h.div_ceil(2) as u16; // terminal rows (2 QR rows per terminal row)
 
-        // content: qr_rows + 1 (separator) + 1 (code) + 1 (instruction) + 1 (expiry) + 1 (separator) + 1 (help)
-        let content_h = qr_rows + 6;
+        // Fingerprint words, for comparison with the pairing screen in the app
+        let words = pairing_info
+            .device_id_bytes()
+            .ok()
+            .and_then(|bytes| <[u8; protocol::DEVICE_ID_LENGTH]>::try_from(bytes).ok())
+            .map(|bytes| protocol::DeviceId::from_bytes(bytes).fingerprint_words());
+        let word_rows = words
+            .as_ref()
+            .map_or(0, |w| w.len().div_ceil(PAIRING_WORDS_PER_LINE)) as u16;
+
+        // content: qr_rows + 1 (separator) + 1 (code) + word_rows + 1 (instruction) + 1 (expiry) + 1 (separator) + 1 (help)
+        let content_h = qr_rows + word_rows + 6;
         let modal_h = (content_h + 2).min(area.height.saturating_sub(2)); // +2 for border
         let modal_w = (full_w as u16 + 2)
             .max(46)
@@ -1492,7 +1792,10 @@ impl TuiApp {
 
         // Pairing code displayed prominently
         lines.push(Line::from(vec![
-            Span::styled("Code: ", Style::default().fg(Color::Gray)),
+            Span::styled(
+                format!("{} ", tr("tui.pairing.code")),
+                Style::default().fg(Color::Gray),
+            ),
             Span::styled(
                 pairing_code,
                 Style::default()
@@ -1501,9 +1804,16 @@ impl TuiApp {
             ),
         ]));
 
+        for chunk in words.iter().flat_map(|w| w.chunks(PAIRING_WORDS_PER_LINE)) {
+            lines.push(Line::from(vec![
+                Span::styled("      ", Style::default()),
+                Span::styled(chunk.join(" "), Style::default().fg(Color::Cyan)),
+            ]));
+        }
+
         // Instruction
         lines.push(Line::from(Span::styled(
-            "Scan QR or enter code in the app",
+            tr("tui.pairing.instructions"),
             Style::default().fg(Color::Gray),
         )));
 
@@ -1511,7 +1821,7 @@ impl TuiApp {
         let remaining = pairing_info.seconds_until_expiry();
         if remaining == 0 {
             lines.push(Line::from(Span::styled(
-                "Expired - press 'p' to regenerate",
+                tr("tui.pairing.expired"),
                 Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
             )));
         } else {
@@ -1525,7 +1835,10 @@ impl TuiApp {
                 Color::Red
             };
             lines.push(Line::from(vec![
-                Span::styled("Expires in ", Style::default().fg(Color::Gray)),
+                Span::styled(
+                    format!("{} ", tr("tui.pairing.expires_in")),
+                    Style::default().fg(Color::Gray),
+                ),
                 Span::styled(format!("{}:{:02}", m, s), Style::default().fg(color)),
             ]));
         }
@@ -1533,14 +1846,14 @@ impl TuiApp {
         // Separator + help
         lines.push(Line::from(""));
         lines.push(Line::from(Span::styled(
-            "Press Esc to close, p to regenerate",
+            tr("tui.pairing.help"),
             Style::default().fg(Color::DarkGray),
         )));
 
         let paragraph = Paragraph::new(lines).block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(" Pair New Device ")
+                .title(format!(" {} ", tr("tui.pairing.title")))
                 .border_style(Style::default().fg(Color::Cyan))
                 .style(Style::default().bg(Color::Reset)),
         );
@@ -1552,8 +1865,29 @@ impl TuiApp {
     pub async fn run(&mut self) -> io::Result<()> {
         let tick_rate = Duration::from_millis(250);
         let mut last_tick = Instant::now();
+        let job_control = JobControlSignals::install()?;
 
         loop {
+            // Suspend on Ctrl-Z or an external SIGTSTP
+            if self.suspend_requested || job_control.take(&job_control.suspend) {
+                self.suspend_requested = false;
+                self.suspend()?;
+                // suspend() already re-entered the TUI; ignore the matching SIGCONT
+                tokio::task::yield_now().await;
+                job_control.take(&job_control.resumed);
+            }
+
+            // Re-enter the TUI if we were continued after an external SIGSTOP
+            if job_control.take(&job_control.resumed) {
+                self.resume()?;
+            }
+
+            // Repaint everything after a terminal resize
+            if job_control.take(&job_control.resized) {
+                self.terminal.autoresize()?;
+                self.terminal.clear()?;
+            }
+
             // Draw the UI
             self.draw()?;
 
@@ -1564,8 +1898,13 @@ impl TuiApp {
 
             // Poll for crossterm events with timeout
             if event::poll(timeout)? {
-                if let Event::Key(key) = event::read()? {
-                    self.handle_key(key);
+                match event::read()? {
+                    Event::Key(key) => self.handle_key(key),
+                    Event::Resize(_, _) => {
+                        self.terminal.autoresize()?;
+                        self.terminal.clear()?;
+                    }
+                    _ => {}
                 }
             }
 
@@ -1588,6 +1927,30 @@ impl TuiApp {
         Ok(())
     }
 
+    /// Suspends the process like a shell job, restoring the terminal first.
+    ///
+    /// The terminal is returned to cooked mode and the main screen before the
+    /// process stops itself, and the TUI is re-entered once it is continued.
+    pub fn suspend(&mut self) -> io::Result<()> {
+        self.restore()?;
+        nix::sys::signal::raise(nix::sys::signal::Signal::SIGSTOP).map_err(io::Error::from)?;
+        // Execution continues here after SIGCONT
+        self.resume()
+    }
+
+    /// Re-enters raw mode and the alternate screen and forces a full redraw.
+    pub fn resume(&mut self) -> io::Result<()> {
+        enable_raw_mode()?;
+        execute!(
+            self.terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableMouseCapture
+        )?;
+        self.terminal.hide_cursor()?;
+        self.terminal.clear()?;
+        Ok(())
+    }
+
     /// Restores the terminal to its original state.
     pub fn restore(&mut self) -> io::Result<()> {
         disable_raw_mode()?;
@@ -1614,6 +1977,66 @@ impl Drop for TuiApp {
     }
 }
 
+/// Returns true if the key event is the job-control suspend key (Ctrl-Z).
+///
+/// In raw mode the terminal does not turn Ctrl-Z into SIGTSTP, so the TUI
+/// has to recognize it itself.
+pub fn is_suspend_key(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+/// Flags set by Unix job-control and resize signals for the TUI event loop.
+///
+/// Handling SIGTSTP ourselves replaces the default stop action, which would
+/// otherwise freeze the process with the terminal still in raw mode.
+struct JobControlSignals {
+    /// Set when SIGTSTP is received.
+    suspend: Arc<AtomicBool>,
+    /// Set when SIGCONT is received.
+    resumed: Arc<AtomicBool>,
+    /// Set when SIGWINCH is received.
+    resized: Arc<AtomicBool>,
+}
+
+impl JobControlSignals {
+    /// Registers signal listeners that set the corresponding flags.
+    fn install() -> io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let signals = Self {
+            suspend: Arc::new(AtomicBool::new(false)),
+            resumed: Arc::new(AtomicBool::new(false)),
+            resized: Arc::new(AtomicBool::new(false)),
+        };
+
+        for (kind, flag) in [
+            (
+                SignalKind::from_raw(nix::libc::SIGTSTP),
+                Arc::clone(&signals.suspend),
+            ),
+            (
+                SignalKind::from_raw(nix::libc::SIGCONT),
+                Arc::clone(&signals.resumed),
+            ),
+            (SignalKind::window_change(), Arc::clone(&signals.resized)),
+        ] {
+            let mut stream = signal(kind)?;
+            tokio::spawn(async move {
+                while stream.recv().await.is_some() {
+                    flag.store(true, Ordering::SeqCst);
+                }
+            });
+        }
+
+        Ok(signals)
+    }
+
+    /// Clears the flag and returns whether it was set.
+    fn take(&self, flag: &AtomicBool) -> bool {
+        flag.swap(false, Ordering::SeqCst)
+    }
+}
+
 /// Builds ratatui Lines from raw QR module data with explicit per-cell colors.
 ///
 /// Uses upper half block (▀) with fg=top_row_color, bg=bottom_row_color.
@@ -1657,18 +2080,49 @@ fn build_qr_lines(modules: &[bool], width: usize, quiet_zone: usize) -> Vec<Line
     lines
 }
 
-/// Formats a duration in seconds to a human-readable string.
-fn format_duration(secs: u64) -> String {
-    let hours = secs / 3600;
-    let minutes = (secs % 3600) / 60;
-    let seconds = secs % 60;
+/// Builds the word and randomart fingerprint lines for a device ID.
+///
+/// Returns no lines if the ID is not a valid fingerprint.
+fn fingerprint_lines(device_id: &str) -> Vec<Line<'static>> {
+    let hex: String = device_id.chars().filter(|c| *c != ':').collect();
+    let Ok(device_id) = parse_device_id_from_hex(&hex) else {
+        return Vec::new();
+    };
 
-    if hours > 0 {
-        format!("{}h {}m {}s", hours, minutes, seconds)
-    } else if minutes > 0 {
-        format!("{}m {}s", minutes, seconds)
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            tr("tui.device.fingerprint"),
+            Style::default().fg(Color::Gray),
+        )),
+    ];
+    // Six words per line keeps the phrase readable in a narrow pane
+    for words in device_id.fingerprint_words().chunks(6) {
+        lines.push(Line::from(Span::styled(
+            format!("  {}", words.join(" ")),
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+    lines.push(Line::from(""));
+    for line in device_id.randomart().lines() {
+        lines.push(Line::from(Span::styled(
+            format!("  {}", line),
+            Style::default().fg(Color::White),
+        )));
+    }
+    lines
+}
+
+/// Returns a warning for device names mixing scripts with look-alike
+/// letters, which may imitate another device, or an empty span.
+fn mixed_script_warning(name: &str) -> Span<'static> {
+    if is_mixed_script(name) {
+        Span::styled(
+            format!(" {}", tr("tui.device.mixed_scripts")),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )
     } else {
-        format!("{}s", seconds)
+        Span::raw("")
     }
 }
 
@@ -1846,24 +2300,14 @@ mod tests {
     }
 
     #[test]
-    fn test_format_duration_seconds() {
-        assert_eq!(format_duration(0), "0s");
-        assert_eq!(format_duration(30), "30s");
-        assert_eq!(format_duration(59), "59s");
-    }
-
-    #[test]
-    fn test_format_duration_minutes() {
-        assert_eq!(format_duration(60), "1m 0s");
-        assert_eq!(format_duration(90), "1m 30s");
-        assert_eq!(format_duration(3599), "59m 59s");
-    }
+    fn test_is_suspend_key() {
+        let ctrl_z = KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL);
+        let plain_z = KeyEvent::new(KeyCode::Char('z'), KeyModifiers::NONE);
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
 
-    #[test]
-    fn test_format_duration_hours() {
-        assert_eq!(format_duration(3600), "1h 0m 0s");
-        assert_eq!(format_duration(3661), "1h 1m 1s");
-        assert_eq!(format_duration(86400), "24h 0m 0s");
+        assert!(is_suspend_key(&ctrl_z));
+        assert!(!is_suspend_key(&plain_z));
+        assert!(!is_suspend_key(&ctrl_c));
     }
 
     #[test]
@@ -1878,6 +2322,7 @@ mod tests {
         assert_eq!(stats.devices_connected, 0);
         assert_eq!(stats.sessions_active, 0);
         assert_eq!(stats.uptime_secs, 0);
+        assert!(!stats.maintenance);
     }
 
     #[test]
@@ -1887,10 +2332,15 @@ mod tests {
             device_id: "test".to_string(),
             name: "Test Device".to_string(),
             trust_level: DisplayTrustLevel::Unknown,
+            grant_expires_at: None,
         };
         let _ = TuiEvent::DeviceDisconnected {
             device_id: "test".to_string(),
         };
+        let _ = TuiEvent::GrantEnded {
+            device_id: "test".to_string(),
+            trust_level: DisplayTrustLevel::Unknown,
+        };
         let _ = TuiEvent::SessionCreated {
             session_id: "sess1".to_string(),
             device_id: "dev1".to_string(),
@@ -1900,6 +2350,11 @@ mod tests {
         let _ = TuiEvent::SessionTerminated {
             session_id: "sess1".to_string(),
         };
+        let _ = TuiEvent::SessionMetadata {
+            session_id: "sess1".to_string(),
+            cwd: Some("/tmp".to_string()),
+            last_exit_code: Some(1),
+        };
         let _ = TuiEvent::ApprovalRequested {
             device_id: "dev1".to_string(),
             name: "Device".to_string(),
@@ -1931,6 +2386,7 @@ mod tests {
             trust_level: DisplayTrustLevel::Trusted,
             last_seen: "Just now".to_string(),
             session_count: 2,
+            grant_expires_at: None,
         };
         assert_eq!(device.id, "device-123");
         assert_eq!(device.name, "My Device");
@@ -1940,6 +2396,43 @@ mod tests {
         assert_eq!(device.session_count, 2);
     }
 
+    #[test]
+    fn test_device_info_grant_countdown() {
+        let mut device = DeviceInfo {
+            id: "device-123".to_string(),
+            name: "Guest".to_string(),
+            connected: true,
+            trust_level: DisplayTrustLevel::Trusted,
+            last_seen: "Just now".to_string(),
+            session_count: 0,
+            grant_expires_at: Some(SystemTime::now() + Duration::from_secs(1800)),
+        };
+        let remaining = device.grant_remaining_secs().unwrap();
+        assert!(remaining > 1790 && remaining <= 1800);
+
+        device.grant_expires_at = Some(SystemTime::now() - Duration::from_secs(5));
+        assert_eq!(device.grant_remaining_secs(), Some(0));
+
+        device.grant_expires_at = None;
+        assert_eq!(device.grant_remaining_secs(), None);
+    }
+
+    #[test]
+    fn test_fingerprint_lines() {
+        let device_id = protocol::DeviceId::from_bytes([0x7f; 16]);
+        let lines = fingerprint_lines(&device_id.fingerprint());
+
+        // Blank line, heading, two lines of words, blank line, randomart
+        assert_eq!(lines.len(), 16);
+        assert_eq!(
+            lines[2].to_string(),
+            "  legal winner thank year wave sausage"
+        );
+        assert_eq!(lines[5].to_string(), "  +---[RemoShell]---+");
+
+        assert!(fingerprint_lines("device-123").is_empty());
+    }
+
     #[test]
     fn test_display_trust_level() {
         // Test color mapping
@@ -1964,6 +2457,8 @@ mod tests {
             pid: Some(9876),
             started_at: Instant::now(),
             subscriber_count: 2,
+            cwd: Some("/home/user".to_string()),
+            last_exit_code: Some(0),
         };
         assert_eq!(session.id, "session-456");
         assert_eq!(session.device_id, "device-123");
@@ -2060,6 +2555,7 @@ mod tests {
             device_id: "dev1".to_string(),
             name: "Test Device".to_string(),
             trust_level: DisplayTrustLevel::Trusted,
+            grant_expires_at: None,
         })
         .await
         .unwrap();
@@ -2071,6 +2567,7 @@ mod tests {
                 device_id,
                 name,
                 trust_level,
+                ..
             } => {
                 assert_eq!(device_id, "dev1");
                 assert_eq!(name, "Test Device");
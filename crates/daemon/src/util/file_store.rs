@@ -0,0 +1,220 @@
+//! Small JSON files shared by the CLI and a running daemon.
+//!
+//! Stores such as the pairing codes and re-invite tokens live in a JSON file
+//! next to the trust store, so that a code registered by `remoshell pair` is
+//! seen by the daemon without a restart. A [`JsonFileStore`] reads the file
+//! for every operation and changes it under an exclusive `flock` on a
+//! `.lock` file beside it, so two connections, or the CLI and the daemon,
+//! cannot both act on the same state: the second one sees what the first
+//! wrote. Writes go through [`write_atomic`], so a crash or a full disk
+//! leaves the previous file in place rather than a truncated one.
+//!
+//! Operations block while they wait for the lock and do file IO, so async
+//! code runs them with `tokio::task::spawn_blocking`.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use nix::fcntl::{Flock, FlockArg};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Replaces the file at `path` with `contents`.
+///
+/// The contents are written to a temporary file in the same directory and
+/// synced before it is renamed over `path`, so readers see either the old
+/// file or the whole new one. The new file keeps the old one's permissions.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut temp = File::create(&temp_path)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        temp.set_permissions(metadata.permissions())?;
+    }
+    temp.write_all(contents)?;
+    temp.sync_all()?;
+    drop(temp);
+
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    // Make the rename itself durable
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// A JSON file holding a `T`, changed under a lock.
+pub struct JsonFileStore<T> {
+    path: PathBuf,
+    /// What the file holds, for error messages, e.g. "pairing code store".
+    what: &'static str,
+    _data: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned + Default> JsonFileStore<T> {
+    /// Creates a store backed by the file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P, what: &'static str) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            what,
+            _data: PhantomData,
+        }
+    }
+
+    /// Returns the path to the store file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the file, or returns the default if there is none yet.
+    pub fn read(&self) -> Result<T> {
+        if !self.path.exists() {
+            return Ok(T::default());
+        }
+
+        let contents = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}: {}", self.what, self.path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}: {}", self.what, self.path.display()))
+    }
+
+    /// Reads the file, passes it to `f` and writes it back if `f` changed
+    /// it, all while holding the store's lock.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R> {
+        let _lock = self.lock()?;
+        let mut data = self.read()?;
+        let before = self.serialize(&data)?;
+        let result = f(&mut data);
+        let after = self.serialize(&data)?;
+        if after != before {
+            write_atomic(&self.path, after.as_bytes()).with_context(|| {
+                format!("Failed to write {}: {}", self.what, self.path.display())
+            })?;
+        }
+        Ok(result)
+    }
+
+    fn serialize(&self, data: &T) -> Result<String> {
+        serde_json::to_string_pretty(data)
+            .with_context(|| format!("Failed to serialize {}", self.what))
+    }
+
+    /// Takes the exclusive lock on the `.lock` file next to the store,
+    /// waiting for any other holder, in this process or another, to finish.
+    fn lock(&self) -> Result<Flock<File>> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).with_context(|| {
+                format!(
+                    "Failed to create {} directory: {}",
+                    self.what,
+                    parent.display()
+                )
+            })?;
+        }
+
+        let lock_path = self.path.with_extension("lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+        Flock::lock(file, FlockArg::LockExclusive)
+            .map_err(|(_, errno)| errno)
+            .with_context(|| format!("Failed to lock {}", lock_path.display()))
+    }
+}
+
+impl<T> Clone for JsonFileStore<T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            what: self.what,
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for JsonFileStore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonFileStore")
+            .field("path", &self.path)
+            .field("what", &self.what)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_update_writes_only_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let store: JsonFileStore<Vec<u32>> =
+            JsonFileStore::new(temp_dir.path().join("numbers.json"), "numbers");
+
+        assert!(store.read().unwrap().is_empty());
+        store.update(|_| ()).unwrap();
+        assert!(!store.path().exists());
+
+        store.update(|numbers| numbers.push(1)).unwrap();
+        assert_eq!(store.read().unwrap(), vec![1]);
+        assert!(!temp_dir.path().join("numbers.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("secret.toml");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_serialized() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("numbers.json");
+        let threads = 8;
+        let barrier = Arc::new(Barrier::new(threads));
+
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                let store: JsonFileStore<Vec<usize>> = JsonFileStore::new(&path, "numbers");
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    store.update(|numbers| numbers.push(i)).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let store: JsonFileStore<Vec<usize>> = JsonFileStore::new(&path, "numbers");
+        let mut numbers = store.read().unwrap();
+        numbers.sort_unstable();
+        assert_eq!(numbers, (0..threads).collect::<Vec<_>>());
+    }
+}
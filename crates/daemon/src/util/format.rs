@@ -0,0 +1,359 @@
+//! Human-readable durations, sizes and relative times.
+//!
+//! The CLI tables, the TUI and the messages the daemon sends to clients all
+//! format values through this module so they read the same everywhere.
+//! Durations and relative times are compact (`1h 2m 3s`, `5m ago`); sizes use
+//! IEC (`1.5 MiB`) or SI (`1.6 MB`) units and the decimal separator of the
+//! configured locale.
+//!
+//! [`init`] sets the process-wide [`Formatter`] from the `[display]` section
+//! of the configuration; until it is called, sizes use IEC units and a `.`
+//! separator.
+
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::DisplayConfig;
+
+/// Languages that write a comma as the decimal separator.
+const COMMA_DECIMAL_LANGUAGES: &[&str] = &[
+    "bg", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr", "hu", "id", "is",
+    "it", "lt", "lv", "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr",
+    "uk", "vi",
+];
+
+static FORMATTER: OnceLock<Formatter> = OnceLock::new();
+
+/// Units sizes are shown in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeUnits {
+    /// Powers of 1024: KiB, MiB, GiB.
+    #[default]
+    Iec,
+    /// Powers of 1000: kB, MB, GB.
+    Si,
+}
+
+impl SizeUnits {
+    fn base(&self) -> f64 {
+        match self {
+            SizeUnits::Iec => 1024.0,
+            SizeUnits::Si => 1000.0,
+        }
+    }
+
+    fn names(&self) -> &'static [&'static str] {
+        match self {
+            SizeUnits::Iec => &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"],
+            SizeUnits::Si => &["B", "kB", "MB", "GB", "TB", "PB", "EB"],
+        }
+    }
+}
+
+/// The number conventions of a locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    decimal_separator: char,
+}
+
+impl Default for Locale {
+    /// The `C` locale.
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl Locale {
+    /// Parses a locale name such as `de_DE.UTF-8`, `fr-FR`, `en` or `C`.
+    pub fn parse(name: &str) -> Option<Self> {
+        if name == "C" || name == "POSIX" {
+            return Some(Self::default());
+        }
+        let language = name.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic())
+        {
+            return None;
+        }
+        let language = language.to_ascii_lowercase();
+        let decimal_separator = if COMMA_DECIMAL_LANGUAGES.contains(&language.as_str()) {
+            ','
+        } else {
+            '.'
+        };
+        Some(Self { decimal_separator })
+    }
+
+    /// Reads the locale from `LC_ALL`, `LC_NUMERIC` or `LANG`, in that
+    /// order, falling back to `C`.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    /// Returns the character written between whole and fractional digits.
+    pub fn decimal_separator(&self) -> char {
+        self.decimal_separator
+    }
+}
+
+/// Formats values for display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Formatter {
+    units: SizeUnits,
+    locale: Locale,
+}
+
+impl Formatter {
+    /// Creates a formatter.
+    pub fn new(units: SizeUnits, locale: Locale) -> Self {
+        Self { units, locale }
+    }
+
+    /// Creates a formatter from the `[display]` configuration. Without a
+    /// configured locale, the environment's is used.
+    pub fn from_config(config: &DisplayConfig) -> Self {
+        let locale = config
+            .locale
+            .as_deref()
+            .and_then(Locale::parse)
+            .unwrap_or_else(Locale::from_env);
+        Self::new(config.size_units, locale)
+    }
+
+    /// Formats a byte count, e.g. `512 B` or `1.5 MiB`.
+    pub fn size(&self, bytes: u64) -> String {
+        let base = self.units.base();
+        let names = self.units.names();
+        if (bytes as f64) < base {
+            return format!("{} {}", bytes, names[0]);
+        }
+
+        let mut value = bytes as f64;
+        let mut unit = 0;
+        while value >= base && unit < names.len() - 1 {
+            value /= base;
+            unit += 1;
+        }
+        // 1023.96 KiB would otherwise show as 1024.0 KiB
+        if (value * 10.0).round() / 10.0 >= base && unit < names.len() - 1 {
+            value /= base;
+            unit += 1;
+        }
+
+        let number = format!("{:.1}", value);
+        let number = match self.locale.decimal_separator {
+            '.' => number,
+            separator => number.replace('.', &separator.to_string()),
+        };
+        format!("{} {}", number, names[unit])
+    }
+}
+
+/// Sets the process-wide formatter. Only the first call has an effect.
+pub fn init(config: &DisplayConfig) {
+    let _ = FORMATTER.set(Formatter::from_config(config));
+}
+
+/// Returns the process-wide formatter.
+pub fn formatter() -> &'static Formatter {
+    FORMATTER.get_or_init(Formatter::default)
+}
+
+/// Formats a byte count with the process-wide formatter.
+pub fn format_size(bytes: u64) -> String {
+    formatter().size(bytes)
+}
+
+/// Formats a duration in seconds, e.g. `30s`, `1m 30s` or `1h 0m 0s`.
+pub fn format_duration(secs: u64) -> String {
+    let hours = secs / 3600;
+    let minutes = (secs % 3600) / 60;
+    let seconds = secs % 60;
+
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Parses a duration such as `30m`, `1h30m`, `2d` or `1h 0m 0s` into seconds.
+///
+/// A bare number is read as seconds. Returns `None` for an empty or
+/// malformed duration.
+pub fn parse_duration(text: &str) -> Option<u64> {
+    let text = text.trim();
+    if let Ok(secs) = text.parse() {
+        return Some(secs);
+    }
+
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+    let mut seen_unit = false;
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+        } else if c.is_whitespace() {
+            continue;
+        } else {
+            let unit = match c {
+                's' => 1,
+                'm' => 60,
+                'h' => 3600,
+                'd' => 86400,
+                _ => return None,
+            };
+            let value: u64 = digits.parse().ok()?;
+            total = total.checked_add(value.checked_mul(unit)?)?;
+            digits.clear();
+            seen_unit = true;
+        }
+    }
+
+    (seen_unit && digits.is_empty()).then_some(total)
+}
+
+/// Formats a Unix timestamp relative to now, e.g. `5m ago` or `in 2h`.
+pub fn format_relative_time(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    relative_time_at(timestamp, now)
+}
+
+/// Formats a Unix timestamp relative to `now`.
+pub fn relative_time_at(timestamp: u64, now: u64) -> String {
+    if timestamp <= now {
+        format!("{} ago", coarse(now - timestamp))
+    } else {
+        format!("in {}", coarse(timestamp - now))
+    }
+}
+
+/// Formats seconds in their largest whole unit, e.g. `5m`.
+fn coarse(secs: u64) -> String {
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h", secs / 3600)
+    } else {
+        format!("{}d", secs / 86400)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration_seconds() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(30), "30s");
+        assert_eq!(format_duration(59), "59s");
+    }
+
+    #[test]
+    fn test_format_duration_minutes() {
+        assert_eq!(format_duration(60), "1m 0s");
+        assert_eq!(format_duration(90), "1m 30s");
+        assert_eq!(format_duration(3599), "59m 59s");
+    }
+
+    #[test]
+    fn test_format_duration_hours() {
+        assert_eq!(format_duration(3600), "1h 0m 0s");
+        assert_eq!(format_duration(3661), "1h 1m 1s");
+        assert_eq!(format_duration(86400), "24h 0m 0s");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45"), Some(45));
+        assert_eq!(parse_duration("30m"), Some(1800));
+        assert_eq!(parse_duration("1h30m"), Some(5400));
+        assert_eq!(parse_duration("2d"), Some(172800));
+        assert_eq!(parse_duration(&format_duration(3661)), Some(3661));
+
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("30x"), None);
+        assert_eq!(parse_duration("1h30"), None);
+    }
+
+    #[test]
+    fn test_relative_time() {
+        assert_eq!(relative_time_at(1000, 1000), "0s ago");
+        assert_eq!(relative_time_at(1000, 1045), "45s ago");
+        assert_eq!(relative_time_at(1000, 1000 + 300), "5m ago");
+        assert_eq!(relative_time_at(1000, 1000 + 7200), "2h ago");
+        assert_eq!(relative_time_at(1000, 1000 + 3 * 86400), "3d ago");
+        assert_eq!(relative_time_at(1000 + 120, 1000), "in 2m");
+    }
+
+    #[test]
+    fn test_size_iec() {
+        let formatter = Formatter::default();
+        assert_eq!(formatter.size(0), "0 B");
+        assert_eq!(formatter.size(1023), "1023 B");
+        assert_eq!(formatter.size(1024), "1.0 KiB");
+        assert_eq!(formatter.size(1536 * 1024), "1.5 MiB");
+        assert_eq!(formatter.size(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(formatter.size(100 * 1024 * 1024), "100.0 MiB");
+        assert_eq!(formatter.size(u64::MAX), "16.0 EiB");
+    }
+
+    #[test]
+    fn test_size_si() {
+        let formatter = Formatter::new(SizeUnits::Si, Locale::default());
+        assert_eq!(formatter.size(999), "999 B");
+        assert_eq!(formatter.size(1000), "1.0 kB");
+        assert_eq!(formatter.size(1_572_864), "1.6 MB");
+        assert_eq!(formatter.size(2_500_000_000), "2.5 GB");
+    }
+
+    #[test]
+    fn test_locale_decimal_separator() {
+        assert_eq!(
+            Locale::parse("de_DE.UTF-8").unwrap().decimal_separator(),
+            ','
+        );
+        assert_eq!(Locale::parse("fr-FR").unwrap().decimal_separator(), ',');
+        assert_eq!(
+            Locale::parse("en_US.UTF-8").unwrap().decimal_separator(),
+            '.'
+        );
+        assert_eq!(Locale::parse("C").unwrap().decimal_separator(), '.');
+        assert_eq!(Locale::parse("ja").unwrap().decimal_separator(), '.');
+        assert!(Locale::parse("").is_none());
+        assert!(Locale::parse("1234").is_none());
+
+        let formatter = Formatter::new(SizeUnits::Iec, Locale::parse("de_DE").unwrap());
+        assert_eq!(formatter.size(1536), "1,5 KiB");
+        assert_eq!(formatter.size(12), "12 B");
+    }
+
+    #[test]
+    fn test_formatter_from_config() {
+        let config = DisplayConfig {
+            size_units: SizeUnits::Si,
+            locale: Some("pt_BR".to_string()),
+            language: None,
+        };
+        let formatter = Formatter::from_config(&config);
+        assert_eq!(formatter.size(1500), "1,5 kB");
+    }
+}
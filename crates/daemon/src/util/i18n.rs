@@ -0,0 +1,227 @@
+//! Translations of the text the CLI, the TUI and notifications show.
+//!
+//! Messages are looked up by key in a catalog per language, kept in
+//! `crates/daemon/locales/<code>.txt` as `key = text` lines. Text may name
+//! arguments in braces, such as `{count}`, which [`tr_with`] fills in. Keys
+//! missing from a catalog fall back to English.
+//!
+//! [`init`] sets the process-wide language from the `[display]` section of
+//! the configuration, or from `LC_ALL`, `LC_MESSAGES` or `LANG`; until it is
+//! called, messages are in English. Logs, JSON output and protocol errors
+//! stay in English so they can be searched and parsed.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::config::DisplayConfig;
+
+const ENGLISH: &str = include_str!("../../locales/en.txt");
+const FRENCH: &str = include_str!("../../locales/fr.txt");
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Languages with a catalog.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    /// English, used for keys other catalogs lack.
+    #[default]
+    English,
+    /// French.
+    French,
+}
+
+impl Language {
+    /// Every language with a catalog.
+    pub const ALL: &'static [Language] = &[Language::English, Language::French];
+
+    /// Parses a language code or locale name such as `fr`, `fr_FR.UTF-8` or
+    /// `C`. Returns `None` for languages without a catalog.
+    pub fn parse(name: &str) -> Option<Self> {
+        if name == "C" || name == "POSIX" {
+            return Some(Language::English);
+        }
+        let code = name.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|language| language.code().eq_ignore_ascii_case(code))
+    }
+
+    /// Reads the language from `LC_ALL`, `LC_MESSAGES` or `LANG`, in that
+    /// order, falling back to English.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    /// Returns the ISO 639-1 code of the language.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::French => "fr",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Language::English => ENGLISH,
+            Language::French => FRENCH,
+        }
+    }
+}
+
+/// The messages of one language.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    language: Language,
+    messages: HashMap<&'static str, &'static str>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::new(Language::English)
+    }
+}
+
+impl Catalog {
+    /// Loads the catalog of `language`, filled in with English.
+    pub fn new(language: Language) -> Self {
+        let mut messages = parse(ENGLISH);
+        if language != Language::English {
+            messages.extend(parse(language.source()));
+        }
+        Self { language, messages }
+    }
+
+    /// Returns the language of the catalog.
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Returns the message for `key`, or the key itself if no catalog has
+    /// it.
+    pub fn get<'a>(&self, key: &'a str) -> &'a str {
+        self.messages.get(key).copied().unwrap_or(key)
+    }
+
+    /// Returns the message for `key` with `{name}` arguments filled in.
+    pub fn get_with(&self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let mut message = self.get(key).to_string();
+        for (name, value) in args {
+            message = message.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        message
+    }
+}
+
+/// Parses `key = text` lines, skipping blank lines and `#` comments.
+fn parse(source: &'static str) -> HashMap<&'static str, &'static str> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, text)| (key.trim(), text.trim()))
+        .collect()
+}
+
+/// Sets the process-wide language. Only the first call has an effect.
+pub fn init(config: &DisplayConfig) {
+    let language = config
+        .language
+        .as_deref()
+        .and_then(Language::parse)
+        .unwrap_or_else(Language::from_env);
+    let _ = CATALOG.set(Catalog::new(language));
+}
+
+/// Returns the process-wide catalog.
+pub fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(Catalog::default)
+}
+
+/// Translates `key` with the process-wide catalog.
+pub fn tr(key: &'static str) -> &'static str {
+    catalog().get(key)
+}
+
+/// Translates `key` with the process-wide catalog, filling in `{name}`
+/// arguments.
+pub fn tr_with(key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    catalog().get_with(key, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Returns the `{name}` arguments of a message.
+    fn arguments(message: &str) -> BTreeSet<&str> {
+        message
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(Language::parse("fr"), Some(Language::French));
+        assert_eq!(Language::parse("fr_CA.UTF-8"), Some(Language::French));
+        assert_eq!(Language::parse("FR-fr"), Some(Language::French));
+        assert_eq!(Language::parse("en_US.UTF-8"), Some(Language::English));
+        assert_eq!(Language::parse("C"), Some(Language::English));
+        assert_eq!(Language::parse("de_DE"), None);
+        assert_eq!(Language::parse(""), None);
+    }
+
+    #[test]
+    fn test_catalogs_match_english() {
+        let english = parse(ENGLISH);
+        for language in Language::ALL {
+            let messages = parse(language.source());
+            for (key, message) in &messages {
+                let Some(original) = english.get(key) else {
+                    panic!("{} has a key English lacks: {}", language.code(), key);
+                };
+                assert_eq!(
+                    arguments(message),
+                    arguments(original),
+                    "{} {} has different arguments",
+                    language.code(),
+                    key
+                );
+            }
+            let missing: Vec<_> = english
+                .keys()
+                .filter(|key| !messages.contains_key(*key))
+                .collect();
+            assert!(
+                missing.is_empty(),
+                "{} lacks {:?}",
+                language.code(),
+                missing
+            );
+        }
+    }
+
+    #[test]
+    fn test_catalog_lookup() {
+        let english = Catalog::default();
+        let french = Catalog::new(Language::French);
+        assert_eq!(english.get("tui.tab.devices"), "Devices");
+        assert_eq!(french.get("tui.tab.devices"), "Appareils");
+        assert_eq!(french.language(), Language::French);
+        assert_eq!(
+            french.get_with("cli.sessions.total", &[("count", &3)]),
+            "Total : 3 session(s)"
+        );
+        assert_eq!(french.get("no.such.key"), "no.such.key");
+    }
+}
@@ -0,0 +1,5 @@
+//! Small helpers shared by the CLI, the TUI and the daemon.
+
+pub mod file_store;
+pub mod format;
+pub mod i18n;
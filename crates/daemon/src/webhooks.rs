@@ -0,0 +1,487 @@
+//! Event webhooks.
+//!
+//! Operators register HTTPS endpoints under `[webhooks]` to receive a JSON
+//! payload whenever a selected event happens: a device is approved, a session
+//! starts, a file upload completes, a device fails authentication, or the
+//! daemon captures a panic.
+//!
+//! Components report events through a [`Webhooks`] handle. A
+//! [`WebhookDispatcher`] posts each event to the endpoints that want it,
+//! signing the body with the endpoint's secret, and retries failed deliveries
+//! with exponential backoff. Deliveries that still fail are appended to the
+//! dead-letter file so that they can be inspected or replayed.
+
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::config::{WebhookEndpoint, WebhookEvent, WebhooksConfig};
+
+/// File name of the dead-letter log inside the data directory.
+pub const DEAD_LETTER_FILE_NAME: &str = "webhooks-dead-letter.jsonl";
+
+/// Header holding the HMAC-SHA256 signature of the body.
+pub const SIGNATURE_HEADER: &str = "X-RemoShell-Signature";
+
+/// Header naming the event.
+pub const EVENT_HEADER: &str = "X-RemoShell-Event";
+
+/// Header holding the payload ID, which stays the same across retries.
+pub const DELIVERY_HEADER: &str = "X-RemoShell-Delivery";
+
+/// Delay before the first retry; doubled for each further attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Longest delay between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Time allowed for a single delivery attempt.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Body posted to webhook endpoints.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WebhookPayload {
+    /// Unique ID of the event.
+    pub id: String,
+    /// What happened.
+    pub event: WebhookEvent,
+    /// When it happened (Unix timestamp).
+    pub timestamp: u64,
+    /// Fingerprint of the daemon reporting the event.
+    pub daemon: String,
+    /// Event details.
+    pub data: serde_json::Value,
+}
+
+/// A delivery that failed on every attempt, as written to the dead-letter
+/// file.
+#[derive(Debug, Clone, Serialize)]
+struct DeadLetter<'a> {
+    failed_at: u64,
+    url: &'a str,
+    attempts: u32,
+    error: &'a str,
+    payload: &'a WebhookPayload,
+}
+
+/// Reports events to the webhook dispatcher.
+#[derive(Debug, Clone)]
+pub struct Webhooks {
+    daemon: String,
+    events: mpsc::UnboundedSender<WebhookPayload>,
+}
+
+impl Webhooks {
+    /// Creates a handle for the daemon with fingerprint `daemon` and the
+    /// receiver its events are dispatched from.
+    pub fn channel(daemon: String) -> (Self, mpsc::UnboundedReceiver<WebhookPayload>) {
+        let (events, rx) = mpsc::unbounded_channel();
+        (Self { daemon, events }, rx)
+    }
+
+    /// Reports an event.
+    pub fn emit(&self, event: WebhookEvent, data: serde_json::Value) {
+        let payload = WebhookPayload {
+            id: uuid::Uuid::new_v4().to_string(),
+            event,
+            timestamp: unix_now(),
+            daemon: self.daemon.clone(),
+            data,
+        };
+        // The receiver is dropped when no endpoints are configured
+        let _ = self.events.send(payload);
+    }
+}
+
+/// Returns the signature header value for `body` signed with `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Why a delivery attempt failed.
+struct AttemptError {
+    message: String,
+    retryable: bool,
+}
+
+/// Posts events to the configured endpoints.
+#[derive(Debug)]
+pub struct WebhookDispatcher {
+    endpoints: Vec<WebhookEndpoint>,
+    max_attempts: u32,
+    dead_letter_file: PathBuf,
+    initial_backoff: Duration,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    /// Creates a dispatcher for `config`, with the dead-letter file in
+    /// `data_dir` unless the configuration names one.
+    pub fn from_config(config: &WebhooksConfig, data_dir: &Path) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("remoshell-daemon/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            endpoints: config.endpoints.clone(),
+            max_attempts: config.max_attempts.max(1),
+            dead_letter_file: config
+                .dead_letter_file
+                .clone()
+                .unwrap_or_else(|| data_dir.join(DEAD_LETTER_FILE_NAME)),
+            initial_backoff: INITIAL_BACKOFF,
+            client,
+        })
+    }
+
+    /// Delivers events from `events` until shutdown.
+    ///
+    /// Each delivery runs on its own task, so a slow endpoint does not hold
+    /// up the others. Deliveries still being retried at shutdown are written
+    /// to the dead-letter file.
+    pub async fn run(
+        self,
+        mut events: mpsc::UnboundedReceiver<WebhookPayload>,
+        shutdown_token: CancellationToken,
+    ) {
+        let dispatcher = Arc::new(self);
+        loop {
+            let payload = tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                next = events.recv() => match next {
+                    Some(payload) => Arc::new(payload),
+                    None => break,
+                },
+            };
+
+            for (index, endpoint) in dispatcher.endpoints.iter().enumerate() {
+                if !endpoint.wants(payload.event) {
+                    continue;
+                }
+                let dispatcher = Arc::clone(&dispatcher);
+                let payload = Arc::clone(&payload);
+                let shutdown_token = shutdown_token.clone();
+                tokio::spawn(async move {
+                    let endpoint = &dispatcher.endpoints[index];
+                    tokio::select! {
+                        _ = dispatcher.deliver(endpoint, &payload) => {}
+                        _ = shutdown_token.cancelled() => {
+                            dispatcher.dead_letter(endpoint, &payload, 0, "daemon shut down");
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// Posts `payload` to `endpoint`, retrying until it succeeds or the
+    /// attempts run out.
+    async fn deliver(&self, endpoint: &WebhookEndpoint, payload: &WebhookPayload) {
+        let body = match serde_json::to_vec(payload) {
+            Ok(body) => body,
+            Err(e) => {
+                error!(error = %e, "Failed to encode webhook payload");
+                return;
+            }
+        };
+        let signature = sign(&endpoint.secret, &body);
+
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match self.attempt(endpoint, payload, &body, &signature).await {
+                Ok(()) => {
+                    debug!(url = %endpoint.url, id = %payload.id, attempt, "Webhook delivered");
+                    return;
+                }
+                Err(e) if e.retryable && attempt < self.max_attempts => {
+                    warn!(
+                        url = %endpoint.url,
+                        id = %payload.id,
+                        attempt,
+                        error = %e.message,
+                        "Webhook delivery failed, retrying in {:?}",
+                        backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    self.dead_letter(endpoint, payload, attempt, &e.message);
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn attempt(
+        &self,
+        endpoint: &WebhookEndpoint,
+        payload: &WebhookPayload,
+        body: &[u8],
+        signature: &str,
+    ) -> Result<(), AttemptError> {
+        let response = self
+            .client
+            .post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .header(EVENT_HEADER, event_name(payload.event))
+            .header(DELIVERY_HEADER, &payload.id)
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|e| AttemptError {
+                message: e.to_string(),
+                retryable: true,
+            })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        // Other client errors will not go away by sending the same request
+        let retryable = status.is_server_error()
+            || status == reqwest::StatusCode::REQUEST_TIMEOUT
+            || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+        Err(AttemptError {
+            message: format!("endpoint answered {}", status),
+            retryable,
+        })
+    }
+
+    /// Appends a failed delivery to the dead-letter file.
+    fn dead_letter(
+        &self,
+        endpoint: &WebhookEndpoint,
+        payload: &WebhookPayload,
+        attempts: u32,
+        error: &str,
+    ) {
+        info!(
+            url = %endpoint.url,
+            id = %payload.id,
+            attempts,
+            error,
+            "Webhook delivery failed, writing to dead-letter file"
+        );
+
+        let entry = DeadLetter {
+            failed_at: unix_now(),
+            url: &endpoint.url,
+            attempts,
+            error,
+            payload,
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(std::io::Error::from)
+            .and_then(|line| {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .mode(0o600)
+                    .open(&self.dead_letter_file)?;
+                writeln!(file, "{}", line)
+            });
+        if let Err(e) = result {
+            error!(
+                path = %self.dead_letter_file.display(),
+                error = %e,
+                "Failed to write webhook dead-letter file"
+            );
+        }
+    }
+}
+
+/// Returns the name of an event as used in payloads and headers.
+pub fn event_name(event: WebhookEvent) -> &'static str {
+    match event {
+        WebhookEvent::DeviceApproved => "device_approved",
+        WebhookEvent::SessionStarted => "session_started",
+        WebhookEvent::FileUploaded => "file_uploaded",
+        WebhookEvent::AuthFailure => "auth_failure",
+        WebhookEvent::Crash => "crash",
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// A request received by [`serve`].
+    struct Received {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Answers one request per status in `statuses` and reports each request.
+    async fn serve(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut headers = Vec::new();
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).await.unwrap();
+                    let Some((key, value)) = line.trim_end().split_once(": ") else {
+                        break;
+                    };
+                    headers.push((key.to_string(), value.to_string()));
+                }
+                let length = headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+                    .map(|(_, value)| value.parse().unwrap())
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let _ = tx.send(Received { headers, body });
+
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                reader
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn dispatcher(url: &str, max_attempts: u32, temp_dir: &TempDir) -> WebhookDispatcher {
+        let config = WebhooksConfig {
+            endpoints: vec![WebhookEndpoint {
+                url: url.to_string(),
+                secret: "s3cret".to_string(),
+                events: vec![WebhookEvent::SessionStarted],
+            }],
+            max_attempts,
+            dead_letter_file: None,
+        };
+        let mut dispatcher = WebhookDispatcher::from_config(&config, temp_dir.path()).unwrap();
+        dispatcher.initial_backoff = Duration::from_millis(10);
+        dispatcher
+    }
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_payload_format() {
+        let (webhooks, mut rx) = Webhooks::channel("a1b2:c3d4".to_string());
+        webhooks.emit(
+            WebhookEvent::FileUploaded,
+            serde_json::json!({ "path": "/tmp/report.pdf" }),
+        );
+
+        let json = serde_json::to_value(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(json["event"], "file_uploaded");
+        assert_eq!(json["daemon"], "a1b2:c3d4");
+        assert_eq!(json["data"]["path"], "/tmp/report.pdf");
+        assert!(json["timestamp"].as_u64().unwrap() > 0);
+        assert!(!json["id"].as_str().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_signed_and_retried() {
+        let temp_dir = TempDir::new().unwrap();
+        let (url, mut requests) = serve(vec![503, 200]).await;
+        let (webhooks, events) = Webhooks::channel("daemon".to_string());
+        let shutdown_token = CancellationToken::new();
+        tokio::spawn(dispatcher(&url, 3, &temp_dir).run(events, shutdown_token.clone()));
+
+        // Not subscribed, so never sent
+        webhooks.emit(WebhookEvent::AuthFailure, serde_json::json!({}));
+        webhooks.emit(
+            WebhookEvent::SessionStarted,
+            serde_json::json!({ "session_id": "sess-1" }),
+        );
+
+        let first = requests.recv().await.unwrap();
+        let second = requests.recv().await.unwrap();
+        assert_eq!(first.body, second.body);
+        assert_eq!(
+            first.header(DELIVERY_HEADER),
+            second.header(DELIVERY_HEADER)
+        );
+        assert_eq!(second.header(EVENT_HEADER), Some("session_started"));
+        assert_eq!(
+            second.header(SIGNATURE_HEADER),
+            Some(sign("s3cret", &second.body).as_str())
+        );
+        let payload: serde_json::Value = serde_json::from_slice(&second.body).unwrap();
+        assert_eq!(payload["data"]["session_id"], "sess-1");
+
+        shutdown_token.cancel();
+        assert!(!temp_dir.path().join(DEAD_LETTER_FILE_NAME).exists());
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_dead_lettered() {
+        let temp_dir = TempDir::new().unwrap();
+        let (url, mut requests) = serve(vec![500, 500]).await;
+        let dispatcher = dispatcher(&url, 2, &temp_dir);
+        let endpoint = dispatcher.endpoints[0].clone();
+        let (webhooks, mut events) = Webhooks::channel("daemon".to_string());
+        webhooks.emit(WebhookEvent::SessionStarted, serde_json::json!({}));
+        let payload = events.try_recv().unwrap();
+
+        dispatcher.deliver(&endpoint, &payload).await;
+        assert!(requests.recv().await.is_some());
+        assert!(requests.recv().await.is_some());
+
+        let contents =
+            std::fs::read_to_string(temp_dir.path().join(DEAD_LETTER_FILE_NAME)).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(contents.trim_end()).unwrap();
+        assert_eq!(entry["url"], url);
+        assert_eq!(entry["attempts"], 2);
+        assert_eq!(entry["payload"]["id"], payload.id);
+    }
+}
@@ -0,0 +1,475 @@
+//! Chaos tests for connection handling.
+//!
+//! The daemon end of a loopback link is wrapped in a `ChaosConnection` and
+//! attached to an orchestrator, so the real message handler and router run
+//! while frames are dropped, delayed or duplicated and the link is cut.
+//! Each test checks that sessions and transfers either survive or fail with
+//! an error a client can act on.
+//!
+//! Run with `cargo test -p daemon --features chaos --test chaos`.
+
+use std::path::Path;
+use std::time::Duration;
+
+use daemon::config::Config;
+use daemon::devices::TrustedDevice;
+use daemon::files::{DevicePermissions, PathPermissions};
+use daemon::network::chaos::{Direction, Fault, Trigger};
+use daemon::network::loopback::{self, LoopbackConnection, SimulatedLink};
+use daemon::network::{
+    ChannelType, ChaosConnection, ChaosHandle, Connection, FaultPlan, FaultRule,
+};
+use daemon::orchestrator::{DaemonOrchestrator, OrchestratorEvent};
+use daemon::session::SessionManager;
+use protocol::messages::{
+    Envelope, ErrorCode, ErrorMessage, FileUploadChunk, FileUploadComplete, FileUploadStart,
+    Message, Ping, SessionCreate,
+};
+use protocol::DeviceIdentity;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use tokio::sync::broadcast;
+
+/// How long to wait for a reply that should arrive.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the link must stay quiet before no more replies are expected.
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+
+/// A daemon with one trusted device allowed to write in its data directory.
+struct Harness {
+    orchestrator: DaemonOrchestrator,
+    events: broadcast::Receiver<OrchestratorEvent>,
+    device: DeviceIdentity,
+    dir: TempDir,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.daemon.data_dir = dir.path().to_path_buf();
+        config.file.allowed_paths = vec![dir.path().to_path_buf()];
+        config.security.require_approval = false;
+
+        let device = DeviceIdentity::generate();
+        let permissions = PathPermissions::new(
+            dir.path().join("permissions.json"),
+            vec![dir.path().to_path_buf()],
+        );
+        permissions
+            .set_device_permissions(DevicePermissions::allow_all_dangerous(*device.device_id()))
+            .unwrap();
+        permissions.save().unwrap();
+
+        let orchestrator = DaemonOrchestrator::new(config).unwrap();
+        orchestrator
+            .trust_store()
+            .add_device(TrustedDevice::new(
+                *device.device_id(),
+                "Chaos Device".to_string(),
+                device.public_key_bytes(),
+            ))
+            .unwrap();
+        let events = orchestrator.subscribe();
+
+        Self {
+            orchestrator,
+            events,
+            device,
+            dir,
+        }
+    }
+
+    /// Connects the device through a link with the given faults on the
+    /// daemon's end.
+    async fn connect(&mut self, plan: FaultPlan) -> (LoopbackConnection, ChaosHandle) {
+        let (client, daemon_end) = loopback::pair(SimulatedLink::default());
+        let daemon_end = ChaosConnection::new(daemon_end, plan);
+        let handle = daemon_end.handle();
+        self.orchestrator
+            .attach_connection(self.device.device_id().fingerprint(), Box::new(daemon_end))
+            .await;
+        loop {
+            if let OrchestratorEvent::PeerConnected { .. } = self.next_event().await {
+                return (client, handle);
+            }
+        }
+    }
+
+    /// Waits for the device's connection to drop and returns the reason.
+    async fn disconnected(&mut self) -> String {
+        loop {
+            if let OrchestratorEvent::PeerDisconnected { reason, .. } = self.next_event().await {
+                return reason;
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> OrchestratorEvent {
+        tokio::time::timeout(REPLY_TIMEOUT, self.events.recv())
+            .await
+            .expect("no orchestrator event")
+            .unwrap()
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.dir.path().join(name).display().to_string()
+    }
+}
+
+async fn send(client: &mut impl Connection, channel: ChannelType, sequence: u64, message: Message) {
+    let data = Envelope::new(sequence, message).to_msgpack().unwrap();
+    client.send(channel, &data).await.unwrap();
+}
+
+async fn recv(client: &mut impl Connection, channel: ChannelType) -> Message {
+    let data = tokio::time::timeout(REPLY_TIMEOUT, client.recv(channel))
+        .await
+        .expect("no reply")
+        .unwrap();
+    Envelope::from_msgpack(&data).unwrap().payload
+}
+
+/// Receives replies until the channel stays quiet.
+async fn drain(client: &mut impl Connection, channel: ChannelType) -> Vec<Message> {
+    let mut replies = Vec::new();
+    while let Ok(data) = tokio::time::timeout(QUIET_PERIOD, client.recv(channel)).await {
+        replies.push(Envelope::from_msgpack(&data.unwrap()).unwrap().payload);
+    }
+    replies
+}
+
+fn expect_error(message: Message) -> ErrorMessage {
+    match message {
+        Message::Error(error) => error,
+        other => panic!("Expected Error, got {:?}", other),
+    }
+}
+
+/// Uploads `content` in chunks of `chunk_size` over the files channel and
+/// returns the daemon's reply to the completion.
+async fn upload(
+    client: &mut impl Connection,
+    path: &str,
+    content: &[u8],
+    chunk_size: usize,
+) -> Message {
+    let mut sequence = 1;
+    send(
+        client,
+        ChannelType::Files,
+        sequence,
+        Message::FileUploadStart(FileUploadStart {
+            path: path.to_string(),
+            size: content.len() as u64,
+            mode: 0o644,
+            overwrite: true,
+        }),
+    )
+    .await;
+    for (i, chunk) in content.chunks(chunk_size).enumerate() {
+        sequence += 1;
+        send(
+            client,
+            ChannelType::Files,
+            sequence,
+            Message::FileUploadChunk(FileUploadChunk {
+                path: path.to_string(),
+                offset: (i * chunk_size) as u64,
+                data: chunk.to_vec(),
+                hash: None,
+            }),
+        )
+        .await;
+    }
+    send(
+        client,
+        ChannelType::Files,
+        sequence + 1,
+        Message::FileUploadComplete(FileUploadComplete {
+            path: path.to_string(),
+            checksum: Sha256::digest(content).to_vec(),
+            report: true,
+        }),
+    )
+    .await;
+    recv(client, ChannelType::Files).await
+}
+
+fn test_content() -> Vec<u8> {
+    (0..64 * 1024).map(|i| (i % 251) as u8).collect()
+}
+
+#[tokio::test]
+async fn test_session_survives_lossy_terminal_channel() {
+    let mut harness = Harness::new();
+    let plan = FaultPlan::default()
+        .with_rule(
+            FaultRule::new(Direction::Recv, Trigger::Every(3), Fault::Drop)
+                .on_channel(ChannelType::Terminal),
+        )
+        .with_rule(
+            FaultRule::new(Direction::Recv, Trigger::Every(2), Fault::Duplicate)
+                .on_channel(ChannelType::Terminal),
+        )
+        .with_rule(
+            FaultRule::new(
+                Direction::Send,
+                Trigger::Nth(1),
+                Fault::Delay(Duration::from_millis(50)),
+            )
+            .on_channel(ChannelType::Terminal),
+        );
+    let (mut client, handle) = harness.connect(plan).await;
+
+    send(
+        &mut client,
+        ChannelType::Control,
+        1,
+        Message::SessionCreate(SessionCreate {
+            shell: Some("/bin/sh".to_string()),
+            ..SessionCreate::default()
+        }),
+    )
+    .await;
+    let session_id = match recv(&mut client, ChannelType::Control).await {
+        Message::SessionCreated(created) => created.session_id,
+        other => panic!("Expected SessionCreated, got {:?}", other),
+    };
+
+    for i in 1..=6u64 {
+        send(
+            &mut client,
+            ChannelType::Terminal,
+            i + 1,
+            Message::Ping(Ping {
+                timestamp: i,
+                payload: vec![],
+            }),
+        )
+        .await;
+    }
+
+    // Every third datagram is lost and every other surviving one arrives
+    // twice; the daemon answers each copy it receives
+    let mut answered: Vec<u64> = drain(&mut client, ChannelType::Terminal)
+        .await
+        .into_iter()
+        .map(|reply| match reply {
+            Message::Pong(pong) => pong.timestamp,
+            other => panic!("Expected Pong, got {:?}", other),
+        })
+        .collect();
+    answered.sort_unstable();
+    assert_eq!(answered, vec![1, 2, 2, 4, 4, 5]);
+
+    let stats = handle.stats();
+    assert_eq!((stats.dropped, stats.duplicated, stats.delayed), (2, 2, 1));
+    assert!(harness.orchestrator.session_manager().exists(&session_id));
+
+    // The control channel is untouched by the terminal's faults
+    send(
+        &mut client,
+        ChannelType::Control,
+        8,
+        Message::Ping(Ping {
+            timestamp: 8,
+            payload: vec![],
+        }),
+    )
+    .await;
+    assert!(matches!(
+        recv(&mut client, ChannelType::Control).await,
+        Message::Pong(_)
+    ));
+
+    let _ = harness
+        .orchestrator
+        .session_manager()
+        .kill(&session_id, Some(9))
+        .await;
+}
+
+#[tokio::test]
+async fn test_session_survives_forced_disconnect() {
+    let mut harness = Harness::new();
+    let plan = FaultPlan::default().with_disconnect_after(Duration::from_secs(1));
+    let (mut client, handle) = harness.connect(plan).await;
+
+    send(
+        &mut client,
+        ChannelType::Control,
+        1,
+        Message::SessionCreate(SessionCreate {
+            shell: Some("/bin/sh".to_string()),
+            ..SessionCreate::default()
+        }),
+    )
+    .await;
+    let session_id = match recv(&mut client, ChannelType::Control).await {
+        Message::SessionCreated(created) => created.session_id,
+        other => panic!("Expected SessionCreated, got {:?}", other),
+    };
+
+    // The link is cut on schedule; the client sees it closed and the daemon
+    // forgets the connection
+    let reason = harness.disconnected().await;
+    assert!(reason.contains("fault injection"), "{}", reason);
+    assert!(handle.stats().disconnected);
+    assert!(client.recv(ChannelType::Control).await.is_err());
+    assert_eq!(harness.orchestrator.connection_count().await, 0);
+
+    // The shell outlives the connection, and a new one can reach it
+    assert!(harness.orchestrator.session_manager().exists(&session_id));
+    let (mut client, _) = harness.connect(FaultPlan::default()).await;
+    send(
+        &mut client,
+        ChannelType::Control,
+        1,
+        Message::Ping(Ping {
+            timestamp: 1,
+            payload: vec![],
+        }),
+    )
+    .await;
+    assert!(matches!(
+        recv(&mut client, ChannelType::Control).await,
+        Message::Pong(_)
+    ));
+
+    let _ = harness
+        .orchestrator
+        .session_manager()
+        .kill(&session_id, Some(9))
+        .await;
+}
+
+#[tokio::test]
+async fn test_upload_survives_delayed_frames() {
+    let mut harness = Harness::new();
+    let plan = FaultPlan::default()
+        .with_rule(
+            FaultRule::new(
+                Direction::Recv,
+                Trigger::Every(2),
+                Fault::Delay(Duration::from_millis(30)),
+            )
+            .on_channel(ChannelType::Files),
+        )
+        .with_rule(FaultRule::new(
+            Direction::Send,
+            Trigger::Every(1),
+            Fault::Delay(Duration::from_millis(20)),
+        ));
+    let (mut client, handle) = harness.connect(plan).await;
+    let path = harness.path("delayed.bin");
+    let content = test_content();
+
+    match upload(&mut client, &path, &content, 8 * 1024).await {
+        Message::FileTransferReport(report) => {
+            assert_eq!(report.size, content.len() as u64);
+            assert_eq!(report.chunks, 8);
+        }
+        other => panic!("Expected FileTransferReport, got {:?}", other),
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), content);
+    assert!(handle.stats().delayed >= 5);
+}
+
+#[tokio::test]
+async fn test_dropped_chunk_fails_upload_cleanly() {
+    let mut harness = Harness::new();
+    // The second chunk, after the start, never arrives
+    let plan = FaultPlan::default().with_rule(
+        FaultRule::new(Direction::Recv, Trigger::Nth(3), Fault::Drop)
+            .on_channel(ChannelType::Files),
+    );
+    let (mut client, _) = harness.connect(plan).await;
+    let path = harness.path("dropped.bin");
+    let content = test_content();
+
+    // The chunk after the gap is rejected as out of order, and completing
+    // finds the file short
+    let completion = upload(&mut client, &path, &content, 16 * 1024).await;
+    let out_of_order = expect_error(completion);
+    assert_eq!(out_of_order.code, ErrorCode::InvalidRequest);
+    assert!(!out_of_order.recoverable);
+
+    let replies = drain(&mut client, ChannelType::Files).await;
+    let codes: Vec<ErrorCode> = replies
+        .into_iter()
+        .map(|reply| expect_error(reply).code)
+        .collect();
+    // The last chunk is out of order too, then the size check fails
+    assert_eq!(
+        codes,
+        vec![ErrorCode::InvalidRequest, ErrorCode::InternalError]
+    );
+    assert!(!Path::new(&path).exists());
+
+    // Nothing is left half-written: the upload can start over
+    match upload(&mut client, &path, &content, 16 * 1024).await {
+        Message::FileTransferReport(report) => assert_eq!(report.size, content.len() as u64),
+        other => panic!("Expected FileTransferReport, got {:?}", other),
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), content);
+}
+
+#[tokio::test]
+async fn test_disconnect_mid_upload_fails_cleanly() {
+    let mut harness = Harness::new();
+    // The link closes instead of delivering the third chunk
+    let plan = FaultPlan::default().with_rule(
+        FaultRule::new(Direction::Recv, Trigger::Nth(4), Fault::Disconnect)
+            .on_channel(ChannelType::Files),
+    );
+    let (mut client, handle) = harness.connect(plan).await;
+    let path = harness.path("cut.bin");
+    let content = test_content();
+
+    send(
+        &mut client,
+        ChannelType::Files,
+        1,
+        Message::FileUploadStart(FileUploadStart {
+            path: path.clone(),
+            size: content.len() as u64,
+            mode: 0o644,
+            overwrite: false,
+        }),
+    )
+    .await;
+    let mut disconnected = false;
+    for (i, chunk) in content.chunks(16 * 1024).enumerate() {
+        let data = Envelope::new(
+            i as u64 + 2,
+            Message::FileUploadChunk(FileUploadChunk {
+                path: path.clone(),
+                offset: (i * 16 * 1024) as u64,
+                data: chunk.to_vec(),
+                hash: None,
+            }),
+        )
+        .to_msgpack()
+        .unwrap();
+        if client.send(ChannelType::Files, &data).await.is_err() {
+            disconnected = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    assert!(disconnected, "the client kept sending after the cut");
+    harness.disconnected().await;
+    assert!(handle.stats().disconnected);
+    assert!(!Path::new(&path).exists());
+
+    // After reconnecting, the client uploads the file from the start
+    let (mut client, _) = harness.connect(FaultPlan::default()).await;
+    match upload(&mut client, &path, &content, 16 * 1024).await {
+        Message::FileTransferReport(report) => assert_eq!(report.size, content.len() as u64),
+        other => panic!("Expected FileTransferReport, got {:?}", other),
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), content);
+}
@@ -5,19 +5,36 @@
 //! - Session management
 //! - File operations
 //! - Message routing
+//! - Message routing over simulated network links
+//! - Transport fallback and mid-session migration
+//! - Message routing over local WebRTC connections
 
+use std::future::Future;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use daemon::config::Config;
 use daemon::devices::TrustStore;
 use daemon::files::{DirectoryBrowser, FileTransfer, PathPermissions};
-use daemon::orchestrator::{DaemonOrchestrator, OrchestratorState};
+use daemon::network::fallback::DialFuture;
+use daemon::network::loopback::{self, SimulatedLink};
+use daemon::network::{
+    ChannelType, Connection, Dialer, FailoverConnection, TransportKind, TransportSelector,
+};
+#[cfg(feature = "webrtc")]
+use daemon::network::{WebRtcConfig, WebRtcConnectionHandler};
+use daemon::orchestrator::{DaemonOrchestrator, OrchestratorEvent, OrchestratorState};
 use daemon::router::MessageRouter;
-use daemon::session::{SessionManager, SessionManagerImpl};
-use protocol::messages::{FileListRequest, Message, Ping, SessionCreate};
-use protocol::DeviceId;
+use daemon::session::{LagPolicy, SessionManager, SessionManagerImpl};
+use protocol::messages::{
+    Envelope, FileListDetail, FileListRequest, Message, Ping, ProcessSignal, SessionCreate,
+};
+use protocol::{DeviceId, ProtocolError};
 use tempfile::TempDir;
+use tokio::sync::mpsc;
 
 /// Create a test configuration with a temporary directory.
 fn create_test_config() -> (Config, TempDir) {
@@ -135,7 +152,7 @@ async fn test_session_write_and_attach() {
         .unwrap();
 
     // Attach to session
-    let rx = manager.attach(&session_id).await;
+    let rx = manager.attach(&session_id, LagPolicy::DropWithMarker).await;
     assert!(rx.is_ok());
 
     // Write to session
@@ -234,10 +251,11 @@ impl SessionManager for MockSessionManager {
     async fn attach(
         &self,
         _session_id: &String,
-    ) -> Result<tokio::sync::broadcast::Receiver<Vec<u8>>, daemon::session::SessionError> {
-        let (tx, rx) = tokio::sync::broadcast::channel(16);
+        _policy: daemon::session::LagPolicy,
+    ) -> Result<daemon::session::OutputReceiver, daemon::session::SessionError> {
+        let (tx, rx) = tokio::sync::broadcast::channel::<bytes::Bytes>(16);
         drop(tx);
-        Ok(rx)
+        Ok(rx.into())
     }
 
     async fn detach(&self, _session_id: &String) -> Result<(), daemon::session::SessionError> {
@@ -269,6 +287,27 @@ impl SessionManager for MockSessionManager {
         Ok(daemon::session::SessionStatus::Exited(0))
     }
 
+    async fn signal(
+        &self,
+        _session_id: &String,
+        _signal: ProcessSignal,
+    ) -> Result<(), daemon::session::SessionError> {
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        _session_id: &String,
+        _query: &daemon::session::SearchQuery,
+    ) -> Result<daemon::session::SearchResults, daemon::session::SessionError> {
+        Ok(daemon::session::SearchResults {
+            matches: vec![],
+            truncated: false,
+            first_line: 0,
+            last_line: 0,
+        })
+    }
+
     fn list(&self) -> Vec<daemon::session::manager::SessionInfo> {
         vec![]
     }
@@ -297,6 +336,14 @@ async fn test_router_session_create() {
         shell: Some("/bin/bash".to_string()),
         env: vec![],
         cwd: None,
+        forward_agent: false,
+        template: None,
+        timezone: None,
+        locale: None,
+        term: None,
+        forward_display: None,
+        host_session: None,
+        login_shell: false,
     });
 
     let result = router.route(msg, &test_device_id(), None).await;
@@ -347,6 +394,7 @@ async fn test_router_file_list() {
     let msg = Message::FileListRequest(FileListRequest {
         path: temp_dir.path().to_string_lossy().to_string(),
         include_hidden: false,
+        detail: FileListDetail::Basic,
     });
 
     let result = router.route(msg, &test_device_id(), None).await;
@@ -631,6 +679,8 @@ async fn test_pending_approval_flow() {
         name: "New Device".to_string(),
         public_key: public_key_bytes.to_vec(),
         reason: Some("Testing pending approval".to_string()),
+        reinvite_token: None,
+        pairing_code: None,
     });
 
     let result = router
@@ -709,6 +759,8 @@ async fn test_approval_to_trusted_transition() {
         name: "Pending Device".to_string(),
         public_key: public_key_bytes.to_vec(),
         reason: Some("Testing".to_string()),
+        reinvite_token: None,
+        pairing_code: None,
     });
 
     let result = router.route(msg, &device_id, Some(&public_key_bytes)).await;
@@ -725,6 +777,14 @@ async fn test_approval_to_trusted_transition() {
         shell: None,
         env: vec![],
         cwd: None,
+        forward_agent: false,
+        template: None,
+        timezone: None,
+        locale: None,
+        term: None,
+        forward_display: None,
+        host_session: None,
+        login_shell: false,
     });
 
     let result = router.route(msg, &device_id, None).await;
@@ -744,6 +804,14 @@ async fn test_approval_to_trusted_transition() {
         shell: None,
         env: vec![],
         cwd: None,
+        forward_agent: false,
+        template: None,
+        timezone: None,
+        locale: None,
+        term: None,
+        forward_display: None,
+        host_session: None,
+        login_shell: false,
     });
 
     let result = router.route(msg, &device_id, None).await;
@@ -813,6 +881,14 @@ async fn test_session_access_requires_trust() {
         shell: None,
         env: vec![],
         cwd: None,
+        forward_agent: false,
+        template: None,
+        timezone: None,
+        locale: None,
+        term: None,
+        forward_display: None,
+        host_session: None,
+        login_shell: false,
     });
 
     let result = router.route(msg, &device_id, None).await;
@@ -840,6 +916,14 @@ async fn test_session_access_requires_trust() {
         shell: None,
         env: vec![],
         cwd: None,
+        forward_agent: false,
+        template: None,
+        timezone: None,
+        locale: None,
+        term: None,
+        forward_display: None,
+        host_session: None,
+        login_shell: false,
     });
 
     let result = router.route(msg, &device_id, None).await;
@@ -908,6 +992,7 @@ async fn test_file_permission_enforcement() {
     let msg = Message::FileListRequest(FileListRequest {
         path: allowed_dir.to_string_lossy().to_string(),
         include_hidden: false,
+        detail: FileListDetail::Basic,
     });
 
     let result = router.route(msg, &device_id, None).await;
@@ -923,6 +1008,7 @@ async fn test_file_permission_enforcement() {
     let msg = Message::FileListRequest(FileListRequest {
         path: forbidden_dir.to_string_lossy().to_string(),
         include_hidden: false,
+        detail: FileListDetail::Basic,
     });
 
     let result = router.route(msg, &device_id, None).await;
@@ -1016,6 +1102,8 @@ async fn test_public_key_mismatch_rejection() {
         name: "Spoofed Device".to_string(),
         public_key: claimed_public_key.to_vec(),
         reason: Some("Testing spoofing".to_string()),
+        reinvite_token: None,
+        pairing_code: None,
     });
 
     // Provide a DIFFERENT authenticated public key (simulating spoofing attempt)
@@ -1037,3 +1125,354 @@ async fn test_public_key_mismatch_rejection() {
         _ => panic!("Expected RouterError::Auth for public key mismatch"),
     }
 }
+
+// =============================================================================
+// Simulated Network Tests
+// =============================================================================
+
+/// Sends a message over a simulated connection and waits for the reply.
+async fn exchange(client: &mut impl Connection, sequence: u64, message: Message) -> Message {
+    let data = Envelope::new(sequence, message).to_msgpack().unwrap();
+    client.send(ChannelType::Control, &data).await.unwrap();
+    let reply = client.recv(ChannelType::Control).await.unwrap();
+    Envelope::from_msgpack(&reply).unwrap().payload
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_simulated_ping_over_lossy_link() {
+    let (config, _temp_dir) = create_test_config();
+    let orchestrator = DaemonOrchestrator::new(config).unwrap();
+    let mut events = orchestrator.subscribe();
+
+    let link = SimulatedLink::default()
+        .with_latency(Duration::from_millis(40))
+        .with_jitter(Duration::from_millis(20))
+        .with_packet_loss(0.1)
+        .with_seed(3140);
+    let (mut client, daemon_end) = loopback::pair(link);
+    let device = protocol::DeviceIdentity::generate();
+    orchestrator
+        .attach_connection(device.device_id().fingerprint(), Box::new(daemon_end))
+        .await;
+
+    assert!(matches!(
+        events.recv().await.unwrap(),
+        OrchestratorEvent::PeerConnected { .. }
+    ));
+    assert_eq!(orchestrator.connection_count().await, 1);
+
+    let start = tokio::time::Instant::now();
+    for i in 0..10u64 {
+        let reply = exchange(
+            &mut client,
+            i + 1,
+            Message::Ping(Ping {
+                timestamp: i,
+                payload: vec![i as u8],
+            }),
+        )
+        .await;
+        match reply {
+            Message::Pong(pong) => {
+                assert_eq!(pong.timestamp, i);
+                assert_eq!(pong.payload, vec![i as u8]);
+            }
+            other => panic!("Expected Pong, got {:?}", other),
+        }
+    }
+
+    // Every round trip crosses the link twice
+    assert!(start.elapsed() >= Duration::from_millis(10 * 2 * 40));
+    assert_eq!(client.stats().delivered, 10);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_simulated_untrusted_session_create_is_rejected() {
+    let (config, _temp_dir) = create_test_config();
+    let orchestrator = DaemonOrchestrator::new(config).unwrap();
+
+    let link = SimulatedLink::default().with_latency(Duration::from_millis(25));
+    let (mut client, daemon_end) = loopback::pair(link);
+    let device = protocol::DeviceIdentity::generate();
+    orchestrator
+        .attach_connection(device.device_id().fingerprint(), Box::new(daemon_end))
+        .await;
+
+    let reply = exchange(
+        &mut client,
+        1,
+        Message::SessionCreate(SessionCreate::default()),
+    )
+    .await;
+    match reply {
+        Message::Error(error) => assert!(error.message.contains("not registered")),
+        other => panic!("Expected Error, got {:?}", other),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_simulated_disconnect_removes_connection() {
+    let (config, _temp_dir) = create_test_config();
+    let orchestrator = DaemonOrchestrator::new(config).unwrap();
+    let mut events = orchestrator.subscribe();
+
+    let (mut client, daemon_end) = loopback::pair(SimulatedLink::default());
+    let device = protocol::DeviceIdentity::generate();
+    let device_id = device.device_id().fingerprint();
+    orchestrator
+        .attach_connection(device_id.clone(), Box::new(daemon_end))
+        .await;
+    assert!(matches!(
+        events.recv().await.unwrap(),
+        OrchestratorEvent::PeerConnected { .. }
+    ));
+
+    client.close().await.unwrap();
+    match events.recv().await.unwrap() {
+        OrchestratorEvent::PeerDisconnected { device_id: id, .. } => assert_eq!(id, device_id),
+        other => panic!("Expected PeerDisconnected, got {:?}", other),
+    }
+    assert_eq!(orchestrator.connection_count().await, 0);
+}
+
+/// A client connection whose path can be cut while the daemon still holds
+/// its end, as when a network change breaks one transport.
+struct CuttableConnection {
+    inner: loopback::LoopbackConnection,
+    cut: Arc<AtomicBool>,
+}
+
+impl Connection for CuttableConnection {
+    fn send<'a>(
+        &'a mut self,
+        channel: ChannelType,
+        data: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = protocol::Result<()>> + Send + 'a>> {
+        if self.cut.load(Ordering::SeqCst) {
+            return Box::pin(async { Err(ProtocolError::ConnectionClosed("path cut".into())) });
+        }
+        self.inner.send(channel, data)
+    }
+
+    fn recv<'a>(
+        &'a mut self,
+        channel: ChannelType,
+    ) -> Pin<Box<dyn Future<Output = protocol::Result<Vec<u8>>> + Send + 'a>> {
+        if self.cut.load(Ordering::SeqCst) {
+            return Box::pin(async { Err(ProtocolError::ConnectionClosed("path cut".into())) });
+        }
+        self.inner.recv(channel)
+    }
+
+    fn close<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = protocol::Result<()>> + Send + 'a>> {
+        self.inner.close()
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.cut.load(Ordering::SeqCst) && self.inner.is_connected()
+    }
+
+    fn peer_public_key(&self) -> Option<[u8; 32]> {
+        self.inner.peer_public_key()
+    }
+}
+
+/// Dials simulated connections, handing the daemon ends to the test.
+struct SimulatedDialer {
+    kind: TransportKind,
+    cut: Arc<AtomicBool>,
+    daemon_ends: mpsc::UnboundedSender<loopback::LoopbackConnection>,
+}
+
+impl Dialer for SimulatedDialer {
+    fn kind(&self) -> TransportKind {
+        self.kind
+    }
+
+    fn dial(&self) -> DialFuture<'_> {
+        Box::pin(async move {
+            let (client, daemon_end) = loopback::pair(SimulatedLink::default());
+            let _ = self.daemon_ends.send(daemon_end);
+            Ok(Box::new(CuttableConnection {
+                inner: client,
+                cut: self.cut.clone(),
+            }) as Box<dyn Connection>)
+        })
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_simulated_migration_keeps_device_connected() {
+    let (config, _temp_dir) = create_test_config();
+    let orchestrator = DaemonOrchestrator::new(config).unwrap();
+    let mut events = orchestrator.subscribe();
+
+    let quic_cut = Arc::new(AtomicBool::new(false));
+    let (daemon_ends, mut dialed) = mpsc::unbounded_channel();
+    let selector = TransportSelector::new()
+        .with_dialer(SimulatedDialer {
+            kind: TransportKind::WebRtc,
+            cut: Arc::new(AtomicBool::new(false)),
+            daemon_ends: daemon_ends.clone(),
+        })
+        .with_dialer(SimulatedDialer {
+            kind: TransportKind::Quic,
+            cut: quic_cut.clone(),
+            daemon_ends,
+        });
+    let mut client = FailoverConnection::connect(Arc::new(selector))
+        .await
+        .unwrap()
+        .with_migration(true);
+    assert_eq!(client.transport(), TransportKind::Quic);
+
+    let device = protocol::DeviceIdentity::generate();
+    let device_id = device.device_id().fingerprint();
+    orchestrator
+        .attach_connection(device_id.clone(), Box::new(dialed.recv().await.unwrap()))
+        .await;
+    assert!(matches!(
+        events.recv().await.unwrap(),
+        OrchestratorEvent::PeerConnected { .. }
+    ));
+
+    let ping = |i: u64| {
+        Envelope::new(
+            i,
+            Message::Ping(Ping {
+                timestamp: i,
+                payload: vec![],
+            }),
+        )
+        .to_msgpack()
+        .unwrap()
+    };
+    client.send(ChannelType::Control, &ping(1)).await.unwrap();
+    client.recv(ChannelType::Control).await.unwrap();
+
+    // The QUIC path breaks before the daemon notices; the ping is resent
+    // over WebRTC, which the daemon accepts as the same device
+    quic_cut.store(true, Ordering::SeqCst);
+    client.send(ChannelType::Control, &ping(2)).await.unwrap();
+    assert_eq!(client.transport(), TransportKind::WebRtc);
+    orchestrator
+        .attach_connection(device_id.clone(), Box::new(dialed.recv().await.unwrap()))
+        .await;
+
+    let reply = client.recv(ChannelType::Control).await.unwrap();
+    match Envelope::from_msgpack(&reply).unwrap().payload {
+        Message::Pong(pong) => assert_eq!(pong.timestamp, 2),
+        other => panic!("Expected Pong, got {:?}", other),
+    }
+    assert_eq!(orchestrator.connection_count().await, 1);
+
+    // Moving transports neither disconnects nor reconnects the device
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    while let Ok(event) = events.try_recv() {
+        assert!(
+            !matches!(
+                event,
+                OrchestratorEvent::PeerConnected { .. }
+                    | OrchestratorEvent::PeerDisconnected { .. }
+            ),
+            "unexpected {:?}",
+            event
+        );
+    }
+}
+
+// =============================================================================
+// WebRTC Tests
+// =============================================================================
+
+/// Sets a handler's local description and waits for its host candidates.
+#[cfg(feature = "webrtc")]
+async fn local_description(
+    handler: &WebRtcConnectionHandler,
+    offer: bool,
+) -> webrtc::peer_connection::sdp::session_description::RTCSessionDescription {
+    let mut gathered = handler.peer_connection().gathering_complete_promise().await;
+    if offer {
+        handler.create_offer().await.unwrap();
+    } else {
+        handler.create_answer().await.unwrap();
+    }
+    let _ = gathered.recv().await;
+    handler.peer_connection().local_description().await.unwrap()
+}
+
+/// Connects a client and a daemon WebRTC handler over the local host and
+/// completes their Noise handshake.
+#[cfg(feature = "webrtc")]
+async fn webrtc_pair() -> (WebRtcConnectionHandler, WebRtcConnectionHandler) {
+    let config = WebRtcConfig::with_ice_servers(vec![]);
+    let client = WebRtcConnectionHandler::new(config.clone(), protocol::DeviceIdentity::generate())
+        .await
+        .unwrap();
+    let daemon_end = WebRtcConnectionHandler::new(config, protocol::DeviceIdentity::generate())
+        .await
+        .unwrap();
+
+    client.create_data_channels().await.unwrap();
+    daemon_end.setup_incoming_data_channels().await;
+    let offer = local_description(&client, true).await;
+    daemon_end.set_remote_description(offer).await.unwrap();
+    let answer = local_description(&daemon_end, false).await;
+    client.set_remote_description(answer).await.unwrap();
+
+    tokio::try_join!(
+        client.wait_for_data_channels(),
+        daemon_end.wait_for_data_channels()
+    )
+    .unwrap();
+    tokio::try_join!(
+        client.perform_noise_handshake_initiator(),
+        daemon_end.perform_noise_handshake_responder()
+    )
+    .unwrap();
+    (client, daemon_end)
+}
+
+#[tokio::test]
+#[cfg(feature = "webrtc")]
+async fn test_webrtc_routes_every_channel() {
+    let (config, _temp_dir) = create_test_config();
+    let orchestrator = DaemonOrchestrator::new(config).unwrap();
+
+    let (mut client, daemon_end) = webrtc_pair().await;
+    let device = protocol::DeviceIdentity::generate();
+    orchestrator
+        .attach_connection(device.device_id().fingerprint(), Box::new(daemon_end))
+        .await;
+
+    for (i, channel) in [
+        ChannelType::Control,
+        ChannelType::Terminal,
+        ChannelType::Files,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let ping = Message::Ping(Ping {
+            timestamp: i as u64,
+            payload: channel.channel_name().as_bytes().to_vec(),
+        });
+        let data = Envelope::new(i as u64 + 1, ping).to_msgpack().unwrap();
+        client.send(channel, &data).await.unwrap();
+
+        // Replies come back on the channel the request used
+        let reply = tokio::time::timeout(Duration::from_secs(10), client.recv(channel))
+            .await
+            .expect("no reply")
+            .unwrap();
+        match Envelope::from_msgpack(&reply).unwrap().payload {
+            Message::Pong(pong) => {
+                assert_eq!(pong.timestamp, i as u64);
+                assert_eq!(pong.payload, channel.channel_name().as_bytes());
+            }
+            other => panic!("Expected Pong, got {:?}", other),
+        }
+    }
+
+    client.close().await.unwrap();
+}
@@ -0,0 +1,172 @@
+//! Soak test for resource leaks in the session lifecycle.
+//!
+//! A trusted device creates and kills sessions through the real connection
+//! handler and router, then the daemon's soak counters must be back at the
+//! baseline taken before the first session.
+//!
+//! Takes several minutes, so it only runs when asked for:
+//! `cargo test -p daemon --release --test soak -- --ignored`. It runs
+//! 10,000 cycles by default; set `SOAK_CYCLES` to change it.
+
+use std::time::Duration;
+
+use daemon::config::Config;
+use daemon::devices::TrustedDevice;
+use daemon::network::loopback::{self, SimulatedLink};
+use daemon::network::{ChannelType, Connection};
+use daemon::orchestrator::{DaemonOrchestrator, OrchestratorEvent};
+use daemon::soak::{SoakProbe, SoakSnapshot};
+use protocol::messages::{Envelope, Message, SessionCreate, SessionKill};
+use protocol::DeviceIdentity;
+use tempfile::TempDir;
+
+const DEFAULT_CYCLES: u64 = 10_000;
+
+/// How long to wait for a reply that should arrive.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long finished tasks and closed descriptors get to be cleaned up.
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tasks and descriptors that may legitimately outlive the cycles, such as
+/// a reader still blocked on a PTY that has just been closed.
+const SLACK: usize = 16;
+
+fn cycles() -> u64 {
+    std::env::var("SOAK_CYCLES")
+        .ok()
+        .and_then(|cycles| cycles.parse().ok())
+        .unwrap_or(DEFAULT_CYCLES)
+}
+
+async fn send(client: &mut impl Connection, sequence: u64, message: Message) {
+    let data = Envelope::new(sequence, message).to_msgpack().unwrap();
+    client.send(ChannelType::Control, &data).await.unwrap();
+}
+
+async fn recv(client: &mut impl Connection) -> Message {
+    let data = tokio::time::timeout(REPLY_TIMEOUT, client.recv(ChannelType::Control))
+        .await
+        .expect("no reply")
+        .unwrap();
+    Envelope::from_msgpack(&data).unwrap().payload
+}
+
+/// Discards the shell output already sent to the client.
+async fn discard_output(client: &mut impl Connection) {
+    while let Ok(data) =
+        tokio::time::timeout(Duration::ZERO, client.recv(ChannelType::Terminal)).await
+    {
+        data.unwrap();
+    }
+}
+
+fn is_settled(baseline: &SoakSnapshot, snapshot: &SoakSnapshot) -> bool {
+    let fds_settled = match (baseline.open_fds, snapshot.open_fds) {
+        (Some(baseline), Some(now)) => now <= baseline + SLACK,
+        _ => true,
+    };
+    snapshot.sessions == 0
+        && snapshot.session_owners == 0
+        && snapshot.output_streams == 0
+        && snapshot.tasks <= baseline.tasks + SLACK
+        && fds_settled
+}
+
+/// Waits for the counters to come back to the baseline and returns the last
+/// snapshot.
+async fn settle(probe: &SoakProbe, baseline: &SoakSnapshot) -> SoakSnapshot {
+    let deadline = tokio::time::Instant::now() + SETTLE_TIMEOUT;
+    loop {
+        let snapshot = probe.snapshot().await;
+        if is_settled(baseline, &snapshot) || tokio::time::Instant::now() >= deadline {
+            return snapshot;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore = "soak test, run with --ignored"]
+async fn test_session_create_kill_cycles_do_not_leak() {
+    let dir = TempDir::new().unwrap();
+    let mut config = Config::default();
+    config.daemon.data_dir = dir.path().to_path_buf();
+    config.security.require_approval = false;
+
+    let device = DeviceIdentity::generate();
+    let orchestrator = DaemonOrchestrator::new(config).unwrap();
+    orchestrator
+        .trust_store()
+        .add_device(TrustedDevice::new(
+            *device.device_id(),
+            "Soak Device".to_string(),
+            device.public_key_bytes(),
+        ))
+        .unwrap();
+    let mut events = orchestrator.subscribe();
+
+    let (mut client, daemon_end) = loopback::pair(SimulatedLink::default());
+    orchestrator
+        .attach_connection(device.device_id().fingerprint(), Box::new(daemon_end))
+        .await;
+    loop {
+        let event = tokio::time::timeout(REPLY_TIMEOUT, events.recv())
+            .await
+            .expect("no orchestrator event")
+            .unwrap();
+        if let OrchestratorEvent::PeerConnected { .. } = event {
+            break;
+        }
+    }
+    drop(events);
+
+    let probe = orchestrator.soak_probe();
+    let baseline = probe.snapshot().await;
+    assert_eq!(baseline.sessions, 0);
+    assert_eq!(baseline.connections, 1);
+
+    let mut sequence = 0;
+    for cycle in 0..cycles() {
+        sequence += 1;
+        send(
+            &mut client,
+            sequence,
+            Message::SessionCreate(SessionCreate {
+                shell: Some("/bin/sh".to_string()),
+                ..SessionCreate::default()
+            }),
+        )
+        .await;
+        let session_id = match recv(&mut client).await {
+            Message::SessionCreated(created) => created.session_id,
+            other => panic!("Cycle {}: expected SessionCreated, got {:?}", cycle, other),
+        };
+
+        sequence += 1;
+        send(
+            &mut client,
+            sequence,
+            Message::SessionKill(SessionKill {
+                session_id,
+                signal: Some(9),
+            }),
+        )
+        .await;
+        match recv(&mut client).await {
+            Message::SessionClosed(_) => {}
+            other => panic!("Cycle {}: expected SessionClosed, got {:?}", cycle, other),
+        }
+        discard_output(&mut client).await;
+    }
+
+    let after = settle(&probe, &baseline).await;
+    assert!(
+        is_settled(&baseline, &after),
+        "counters did not return to baseline\nbefore: {:?}\nafter:  {:?}",
+        baseline,
+        after
+    );
+    assert_eq!(after.connections, 1);
+    assert_eq!(after.session_subscribers, 0);
+}
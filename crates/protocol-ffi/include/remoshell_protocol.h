@@ -0,0 +1,302 @@
+/* RemoShell protocol C bindings. Generated by cbindgen, do not edit. */
+
+#ifndef REMOSHELL_PROTOCOL_H
+#define REMOSHELL_PROTOCOL_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Length of secret keys, public keys and Noise static keys, in bytes.
+#define REMOSHELL_KEY_LENGTH 32
+
+// Outcome of a call.
+typedef enum RemoshellStatus {
+  // The call succeeded.
+  REMOSHELL_STATUS_OK = 0,
+  // The call succeeded without producing data: the buffer does not hold a
+  // whole frame yet, or the decrypted message was a rekey marker.
+  REMOSHELL_STATUS_EMPTY = 1,
+  // A pointer was null or a length was wrong.
+  REMOSHELL_STATUS_INVALID_ARGUMENT = 2,
+  // The protocol rejected the call, for example a failed decryption.
+  REMOSHELL_STATUS_ERROR = 3,
+  // The library panicked. The handles used in the call must be freed.
+  REMOSHELL_STATUS_PANIC = 4,
+} RemoshellStatus;
+
+// Length-prefixed framing, compressing large payloads with LZ4.
+typedef struct RemoshellFrameCodec RemoshellFrameCodec;
+
+// A device identity: an Ed25519 key pair and the device ID derived from it.
+typedef struct RemoshellIdentity RemoshellIdentity;
+
+// A Noise XX session: the handshake, then transport encryption.
+typedef struct RemoshellNoiseSession RemoshellNoiseSession;
+
+// Bytes owned by the library. Release with [`remoshell_buffer_free`].
+typedef struct RemoshellBuffer {
+  // The bytes, or null if there are none.
+  uint8_t *data;
+  // Number of bytes.
+  size_t len;
+} RemoshellBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the protocol version this library speaks.
+uint8_t remoshell_protocol_version(void);
+
+// Returns a description of the last error on this thread, or null if no
+// call has failed yet. The string stays valid until the next failing call
+// on this thread.
+const char *remoshell_last_error(void);
+
+// Releases a buffer returned by the library. Null buffers are ignored.
+//
+// # Safety
+//
+// `buffer` must come from the library and must not be used afterwards.
+void remoshell_buffer_free(struct RemoshellBuffer buffer);
+
+// Releases a string returned by the library. Null is ignored.
+//
+// # Safety
+//
+// `string` must come from the library and must not be used afterwards.
+void remoshell_string_free(char *string);
+
+// Generates a new identity.
+//
+// # Safety
+//
+// `out` must be valid for writes.
+enum RemoshellStatus remoshell_identity_generate(struct RemoshellIdentity **out);
+
+// Restores an identity from its 32-byte secret key.
+//
+// # Safety
+//
+// `secret_key` must point to 32 readable bytes and `out` must be valid for
+// writes.
+enum RemoshellStatus remoshell_identity_from_secret_key(const uint8_t *secret_key,
+                                                        struct RemoshellIdentity **out);
+
+// Copies the 32-byte secret key, for storage, into `out`.
+//
+// # Safety
+//
+// `identity` must be a live identity and `out` must point to 32 writable
+// bytes.
+enum RemoshellStatus remoshell_identity_secret_key(const struct RemoshellIdentity *identity,
+                                                   uint8_t *out);
+
+// Copies the 32-byte public key into `out`.
+//
+// # Safety
+//
+// `identity` must be a live identity and `out` must point to 32 writable
+// bytes.
+enum RemoshellStatus remoshell_identity_public_key(const struct RemoshellIdentity *identity,
+                                                   uint8_t *out);
+
+// Returns the device ID as a colon-separated fingerprint. Release it with
+// [`remoshell_string_free`].
+//
+// # Safety
+//
+// `identity` must be a live identity and `out` must be valid for writes.
+enum RemoshellStatus remoshell_identity_fingerprint(const struct RemoshellIdentity *identity,
+                                                    char **out);
+
+// Returns the fingerprint as space-separated words, for comparing out of
+// band. Release it with [`remoshell_string_free`].
+//
+// # Safety
+//
+// `identity` must be a live identity and `out` must be valid for writes.
+enum RemoshellStatus remoshell_identity_fingerprint_words(const struct RemoshellIdentity *identity,
+                                                          char **out);
+
+// Releases an identity. Null is ignored.
+//
+// # Safety
+//
+// `identity` must come from the library and must not be used afterwards.
+void remoshell_identity_free(struct RemoshellIdentity *identity);
+
+// Starts a handshake as the initiator, as clients do.
+//
+// # Safety
+//
+// `identity` must be a live identity and `out` must be valid for writes.
+enum RemoshellStatus remoshell_noise_initiator(const struct RemoshellIdentity *identity,
+                                               struct RemoshellNoiseSession **out);
+
+// Starts a handshake as the responder.
+//
+// # Safety
+//
+// `identity` must be a live identity and `out` must be valid for writes.
+enum RemoshellStatus remoshell_noise_responder(const struct RemoshellIdentity *identity,
+                                               struct RemoshellNoiseSession **out);
+
+// Writes the next handshake message, carrying `payload`.
+//
+// # Safety
+//
+// `session` must be a live session, `payload` must point to `payload_len`
+// readable bytes and `out` must be valid for writes.
+enum RemoshellStatus remoshell_noise_write_handshake(struct RemoshellNoiseSession *session,
+                                                     const uint8_t *payload,
+                                                     size_t payload_len,
+                                                     struct RemoshellBuffer *out);
+
+// Reads a handshake message from the peer and returns its payload.
+//
+// # Safety
+//
+// `session` must be a live session, `message` must point to `message_len`
+// readable bytes and `out` must be valid for writes.
+enum RemoshellStatus remoshell_noise_read_handshake(struct RemoshellNoiseSession *session,
+                                                    const uint8_t *message,
+                                                    size_t message_len,
+                                                    struct RemoshellBuffer *out);
+
+// Returns true once every handshake message has been exchanged, and false
+// for a null session.
+//
+// # Safety
+//
+// `session` must be null or a live session.
+bool remoshell_noise_is_handshake_complete(const struct RemoshellNoiseSession *session);
+
+// Switches to transport mode after the handshake.
+//
+// # Safety
+//
+// `session` must be a live session.
+enum RemoshellStatus remoshell_noise_into_transport(struct RemoshellNoiseSession *session);
+
+// Copies the peer's 32-byte static Noise key into `out`. Returns
+// [`RemoshellStatus::Empty`] until the handshake has revealed it.
+//
+// # Safety
+//
+// `session` must be a live session and `out` must point to 32 writable
+// bytes.
+enum RemoshellStatus remoshell_noise_remote_static(const struct RemoshellNoiseSession *session,
+                                                   uint8_t *out);
+
+// Returns the handshake hash, which both peers share, for binding identity
+// proofs to this session. Returns [`RemoshellStatus::Empty`] before the
+// handshake is complete.
+//
+// # Safety
+//
+// `session` must be a live session and `out` must be valid for writes.
+enum RemoshellStatus remoshell_noise_handshake_hash(const struct RemoshellNoiseSession *session,
+                                                    struct RemoshellBuffer *out);
+
+// Encrypts a transport message.
+//
+// # Safety
+//
+// `session` must be a live session, `plaintext` must point to
+// `plaintext_len` readable bytes and `out` must be valid for writes.
+enum RemoshellStatus remoshell_noise_encrypt(struct RemoshellNoiseSession *session,
+                                             const uint8_t *plaintext,
+                                             size_t plaintext_len,
+                                             struct RemoshellBuffer *out);
+
+// Decrypts a transport message. Returns [`RemoshellStatus::Empty`] for
+// rekey markers, which carry no data.
+//
+// # Safety
+//
+// `session` must be a live session, `ciphertext` must point to
+// `ciphertext_len` readable bytes and `out` must be valid for writes.
+enum RemoshellStatus remoshell_noise_decrypt(struct RemoshellNoiseSession *session,
+                                             const uint8_t *ciphertext,
+                                             size_t ciphertext_len,
+                                             struct RemoshellBuffer *out);
+
+// Switches sending keys periodically, once both peers have announced
+// support for rekeying.
+//
+// # Safety
+//
+// `session` must be a live session.
+enum RemoshellStatus remoshell_noise_enable_rekey(struct RemoshellNoiseSession *session);
+
+// Returns true when the sending key is due to be replaced, and false for a
+// null session.
+//
+// # Safety
+//
+// `session` must be null or a live session.
+bool remoshell_noise_rekey_due(const struct RemoshellNoiseSession *session);
+
+// Writes a rekey marker and switches to a new sending key.
+//
+// # Safety
+//
+// `session` must be a live session and `out` must be valid for writes.
+enum RemoshellStatus remoshell_noise_write_rekey(struct RemoshellNoiseSession *session,
+                                                 struct RemoshellBuffer *out);
+
+// Releases a session. Null is ignored.
+//
+// # Safety
+//
+// `session` must come from the library and must not be used afterwards.
+void remoshell_noise_free(struct RemoshellNoiseSession *session);
+
+// Creates a codec, compressing payloads unless `compression` is false.
+//
+// # Safety
+//
+// `out` must be valid for writes.
+enum RemoshellStatus remoshell_frame_codec_new(bool compression, struct RemoshellFrameCodec **out);
+
+// Wraps a payload in a frame.
+//
+// # Safety
+//
+// `codec` must be a live codec, `payload` must point to `payload_len`
+// readable bytes and `out` must be valid for writes.
+enum RemoshellStatus remoshell_frame_encode(const struct RemoshellFrameCodec *codec,
+                                            const uint8_t *payload,
+                                            size_t payload_len,
+                                            struct RemoshellBuffer *out);
+
+// Reads the first frame of a buffer into `out` and the number of bytes it
+// took up into `consumed`. Returns [`RemoshellStatus::Empty`] if the buffer
+// does not hold a whole frame yet.
+//
+// # Safety
+//
+// `codec` must be a live codec, `data` must point to `data_len` readable
+// bytes, and `out` and `consumed` must be valid for writes.
+enum RemoshellStatus remoshell_frame_decode(const struct RemoshellFrameCodec *codec,
+                                            const uint8_t *data,
+                                            size_t data_len,
+                                            struct RemoshellBuffer *out,
+                                            size_t *consumed);
+
+// Releases a codec. Null is ignored.
+//
+// # Safety
+//
+// `codec` must come from the library and must not be used afterwards.
+void remoshell_frame_codec_free(struct RemoshellFrameCodec *codec);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* REMOSHELL_PROTOCOL_H */
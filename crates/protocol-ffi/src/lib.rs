@@ -0,0 +1,885 @@
+//! # RemoShell Protocol for C
+//!
+//! A C ABI over the [`protocol`] crate, so that native mobile apps written
+//! in Swift or Kotlin talk to daemons with the same code the daemon runs.
+//!
+//! ## Overview
+//!
+//! - [`RemoshellIdentity`]: A device identity, generated or restored from its
+//!   secret key
+//! - [`RemoshellNoiseSession`]: The Noise XX handshake and transport
+//!   encryption
+//! - [`RemoshellFrameCodec`]: Length-prefixed framing with LZ4 compression
+//!
+//! The header is `include/remoshell_protocol.h`, generated by cbindgen.
+//!
+//! ## Conventions
+//!
+//! - Functions return a [`RemoshellStatus`] and write their results through
+//!   out pointers. On failure, [`remoshell_last_error`] describes the error.
+//! - Handles are created by `*_new` or `*_generate` functions and released by
+//!   the matching `*_free` function.
+//! - Bytes returned to the caller are in a [`RemoshellBuffer`], released with
+//!   [`remoshell_buffer_free`]; strings are released with
+//!   [`remoshell_string_free`].
+//! - A handle may be used from any thread, but not from two threads at once.
+//! - Panics are caught and reported as [`RemoshellStatus::Panic`].
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use protocol::{DeviceIdentity, Frame, FrameCodec, NoiseSession, ProtocolError, RekeyPolicy};
+
+/// Length of secret keys, public keys and Noise static keys, in bytes.
+pub const REMOSHELL_KEY_LENGTH: usize = 32;
+
+/// Outcome of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoshellStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// The call succeeded without producing data: the buffer does not hold a
+    /// whole frame yet, or the decrypted message was a rekey marker.
+    Empty = 1,
+    /// A pointer was null or a length was wrong.
+    InvalidArgument = 2,
+    /// The protocol rejected the call, for example a failed decryption.
+    Error = 3,
+    /// The library panicked. The handles used in the call must be freed.
+    Panic = 4,
+}
+
+/// Bytes owned by the library. Release with [`remoshell_buffer_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct RemoshellBuffer {
+    /// The bytes, or null if there are none.
+    pub data: *mut u8,
+    /// Number of bytes.
+    pub len: usize,
+}
+
+impl RemoshellBuffer {
+    const EMPTY: Self = Self {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        if bytes.is_empty() {
+            return Self::EMPTY;
+        }
+        let bytes = bytes.into_boxed_slice();
+        let len = bytes.len();
+        Self {
+            data: Box::into_raw(bytes).cast(),
+            len,
+        }
+    }
+}
+
+/// A device identity: an Ed25519 key pair and the device ID derived from it.
+pub struct RemoshellIdentity(DeviceIdentity);
+
+/// A Noise XX session: the handshake, then transport encryption.
+pub struct RemoshellNoiseSession(NoiseSession);
+
+/// Length-prefixed framing, compressing large payloads with LZ4.
+pub struct RemoshellFrameCodec(FrameCodec);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Why a call failed.
+enum Failure {
+    InvalidArgument(&'static str),
+    Protocol(ProtocolError),
+}
+
+impl From<ProtocolError> for Failure {
+    fn from(error: ProtocolError) -> Self {
+        Failure::Protocol(error)
+    }
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Runs the body of an exported function, recording why it failed.
+fn call(body: impl FnOnce() -> Result<RemoshellStatus, Failure>) -> RemoshellStatus {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(status)) => status,
+        Ok(Err(Failure::InvalidArgument(message))) => {
+            set_last_error(message.to_string());
+            RemoshellStatus::InvalidArgument
+        }
+        Ok(Err(Failure::Protocol(error))) => {
+            set_last_error(error.to_string());
+            RemoshellStatus::Error
+        }
+        Err(_) => {
+            set_last_error("panic in the RemoShell protocol library".to_string());
+            RemoshellStatus::Panic
+        }
+    }
+}
+
+/// Borrows `len` bytes at `data`, which may be null when `len` is 0.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Failure> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(Failure::InvalidArgument("data is null"))
+    } else {
+        Ok(std::slice::from_raw_parts(data, len))
+    }
+}
+
+/// Borrows the object behind a handle or out pointer.
+unsafe fn borrow<'a, T>(pointer: *const T) -> Result<&'a T, Failure> {
+    pointer
+        .as_ref()
+        .ok_or(Failure::InvalidArgument("null pointer"))
+}
+
+/// Mutably borrows the object behind a handle or out pointer.
+unsafe fn borrow_mut<'a, T>(pointer: *mut T) -> Result<&'a mut T, Failure> {
+    pointer
+        .as_mut()
+        .ok_or(Failure::InvalidArgument("null pointer"))
+}
+
+/// Writes a handle to an out pointer.
+unsafe fn write_handle<T>(out: *mut *mut T, value: T) -> Result<RemoshellStatus, Failure> {
+    *borrow_mut(out)? = Box::into_raw(Box::new(value));
+    Ok(RemoshellStatus::Ok)
+}
+
+/// Writes bytes to an out buffer.
+unsafe fn write_buffer(
+    out: *mut RemoshellBuffer,
+    bytes: Vec<u8>,
+) -> Result<RemoshellStatus, Failure> {
+    *borrow_mut(out)? = RemoshellBuffer::from_vec(bytes);
+    Ok(RemoshellStatus::Ok)
+}
+
+/// Writes a 32-byte key to a caller-owned array.
+unsafe fn write_key(
+    out: *mut u8,
+    key: [u8; REMOSHELL_KEY_LENGTH],
+) -> Result<RemoshellStatus, Failure> {
+    if out.is_null() {
+        return Err(Failure::InvalidArgument("key output is null"));
+    }
+    ptr::copy_nonoverlapping(key.as_ptr(), out, REMOSHELL_KEY_LENGTH);
+    Ok(RemoshellStatus::Ok)
+}
+
+/// Writes a string to an out pointer.
+unsafe fn write_string(out: *mut *mut c_char, value: String) -> Result<RemoshellStatus, Failure> {
+    let value = CString::new(value).map_err(|_| Failure::InvalidArgument("string has a NUL"))?;
+    *borrow_mut(out)? = value.into_raw();
+    Ok(RemoshellStatus::Ok)
+}
+
+// ============================================================================
+// Library
+// ============================================================================
+
+/// Returns the protocol version this library speaks.
+#[no_mangle]
+pub extern "C" fn remoshell_protocol_version() -> u8 {
+    protocol::PROTOCOL_VERSION
+}
+
+/// Returns a description of the last error on this thread, or null if no
+/// call has failed yet. The string stays valid until the next failing call
+/// on this thread.
+#[no_mangle]
+pub extern "C" fn remoshell_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Releases a buffer returned by the library. Null buffers are ignored.
+///
+/// # Safety
+///
+/// `buffer` must come from the library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_buffer_free(buffer: RemoshellBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Releases a string returned by the library. Null is ignored.
+///
+/// # Safety
+///
+/// `string` must come from the library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+// ============================================================================
+// Identity
+// ============================================================================
+
+/// Generates a new identity.
+///
+/// # Safety
+///
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_identity_generate(
+    out: *mut *mut RemoshellIdentity,
+) -> RemoshellStatus {
+    call(|| write_handle(out, RemoshellIdentity(DeviceIdentity::generate())))
+}
+
+/// Restores an identity from its 32-byte secret key.
+///
+/// # Safety
+///
+/// `secret_key` must point to 32 readable bytes and `out` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_identity_from_secret_key(
+    secret_key: *const u8,
+    out: *mut *mut RemoshellIdentity,
+) -> RemoshellStatus {
+    call(|| {
+        let secret_key: &[u8; REMOSHELL_KEY_LENGTH] = bytes(secret_key, REMOSHELL_KEY_LENGTH)?
+            .try_into()
+            .map_err(|_| Failure::InvalidArgument("secret key must be 32 bytes"))?;
+        write_handle(
+            out,
+            RemoshellIdentity(DeviceIdentity::from_secret_key_bytes(secret_key)),
+        )
+    })
+}
+
+/// Copies the 32-byte secret key, for storage, into `out`.
+///
+/// # Safety
+///
+/// `identity` must be a live identity and `out` must point to 32 writable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_identity_secret_key(
+    identity: *const RemoshellIdentity,
+    out: *mut u8,
+) -> RemoshellStatus {
+    call(|| write_key(out, borrow(identity)?.0.secret_key_bytes()))
+}
+
+/// Copies the 32-byte public key into `out`.
+///
+/// # Safety
+///
+/// `identity` must be a live identity and `out` must point to 32 writable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_identity_public_key(
+    identity: *const RemoshellIdentity,
+    out: *mut u8,
+) -> RemoshellStatus {
+    call(|| write_key(out, borrow(identity)?.0.public_key_bytes()))
+}
+
+/// Returns the device ID as a colon-separated fingerprint. Release it with
+/// [`remoshell_string_free`].
+///
+/// # Safety
+///
+/// `identity` must be a live identity and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_identity_fingerprint(
+    identity: *const RemoshellIdentity,
+    out: *mut *mut c_char,
+) -> RemoshellStatus {
+    call(|| write_string(out, borrow(identity)?.0.fingerprint()))
+}
+
+/// Returns the fingerprint as space-separated words, for comparing out of
+/// band. Release it with [`remoshell_string_free`].
+///
+/// # Safety
+///
+/// `identity` must be a live identity and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_identity_fingerprint_words(
+    identity: *const RemoshellIdentity,
+    out: *mut *mut c_char,
+) -> RemoshellStatus {
+    call(|| {
+        let words = borrow(identity)?.0.device_id().fingerprint_words();
+        write_string(out, words.join(" "))
+    })
+}
+
+/// Releases an identity. Null is ignored.
+///
+/// # Safety
+///
+/// `identity` must come from the library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_identity_free(identity: *mut RemoshellIdentity) {
+    if !identity.is_null() {
+        drop(Box::from_raw(identity));
+    }
+}
+
+// ============================================================================
+// Noise Session
+// ============================================================================
+
+/// Starts a handshake as the initiator, as clients do.
+///
+/// # Safety
+///
+/// `identity` must be a live identity and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_initiator(
+    identity: *const RemoshellIdentity,
+    out: *mut *mut RemoshellNoiseSession,
+) -> RemoshellStatus {
+    call(|| {
+        let session = NoiseSession::new_initiator(&borrow(identity)?.0)?;
+        write_handle(out, RemoshellNoiseSession(session))
+    })
+}
+
+/// Starts a handshake as the responder.
+///
+/// # Safety
+///
+/// `identity` must be a live identity and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_responder(
+    identity: *const RemoshellIdentity,
+    out: *mut *mut RemoshellNoiseSession,
+) -> RemoshellStatus {
+    call(|| {
+        let session = NoiseSession::new_responder(&borrow(identity)?.0)?;
+        write_handle(out, RemoshellNoiseSession(session))
+    })
+}
+
+/// Writes the next handshake message, carrying `payload`.
+///
+/// # Safety
+///
+/// `session` must be a live session, `payload` must point to `payload_len`
+/// readable bytes and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_write_handshake(
+    session: *mut RemoshellNoiseSession,
+    payload: *const u8,
+    payload_len: usize,
+    out: *mut RemoshellBuffer,
+) -> RemoshellStatus {
+    call(|| {
+        let message = borrow_mut(session)?
+            .0
+            .write_handshake_message(bytes(payload, payload_len)?)?;
+        write_buffer(out, message)
+    })
+}
+
+/// Reads a handshake message from the peer and returns its payload.
+///
+/// # Safety
+///
+/// `session` must be a live session, `message` must point to `message_len`
+/// readable bytes and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_read_handshake(
+    session: *mut RemoshellNoiseSession,
+    message: *const u8,
+    message_len: usize,
+    out: *mut RemoshellBuffer,
+) -> RemoshellStatus {
+    call(|| {
+        let payload = borrow_mut(session)?
+            .0
+            .read_handshake_message(bytes(message, message_len)?)?;
+        write_buffer(out, payload)
+    })
+}
+
+/// Returns true once every handshake message has been exchanged, and false
+/// for a null session.
+///
+/// # Safety
+///
+/// `session` must be null or a live session.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_is_handshake_complete(
+    session: *const RemoshellNoiseSession,
+) -> bool {
+    session
+        .as_ref()
+        .is_some_and(|session| session.0.is_handshake_complete())
+}
+
+/// Switches to transport mode after the handshake.
+///
+/// # Safety
+///
+/// `session` must be a live session.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_into_transport(
+    session: *mut RemoshellNoiseSession,
+) -> RemoshellStatus {
+    call(|| {
+        borrow_mut(session)?.0.into_transport()?;
+        Ok(RemoshellStatus::Ok)
+    })
+}
+
+/// Copies the peer's 32-byte static Noise key into `out`. Returns
+/// [`RemoshellStatus::Empty`] until the handshake has revealed it.
+///
+/// # Safety
+///
+/// `session` must be a live session and `out` must point to 32 writable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_remote_static(
+    session: *const RemoshellNoiseSession,
+    out: *mut u8,
+) -> RemoshellStatus {
+    call(|| match borrow(session)?.0.get_remote_static() {
+        Some(key) => write_key(out, key),
+        None => Ok(RemoshellStatus::Empty),
+    })
+}
+
+/// Returns the handshake hash, which both peers share, for binding identity
+/// proofs to this session. Returns [`RemoshellStatus::Empty`] before the
+/// handshake is complete.
+///
+/// # Safety
+///
+/// `session` must be a live session and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_handshake_hash(
+    session: *const RemoshellNoiseSession,
+    out: *mut RemoshellBuffer,
+) -> RemoshellStatus {
+    call(|| match borrow(session)?.0.handshake_hash() {
+        Some(hash) => write_buffer(out, hash.to_vec()),
+        None => Ok(RemoshellStatus::Empty),
+    })
+}
+
+/// Encrypts a transport message.
+///
+/// # Safety
+///
+/// `session` must be a live session, `plaintext` must point to
+/// `plaintext_len` readable bytes and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_encrypt(
+    session: *mut RemoshellNoiseSession,
+    plaintext: *const u8,
+    plaintext_len: usize,
+    out: *mut RemoshellBuffer,
+) -> RemoshellStatus {
+    call(|| {
+        let ciphertext = borrow_mut(session)?
+            .0
+            .encrypt(bytes(plaintext, plaintext_len)?)?;
+        write_buffer(out, ciphertext)
+    })
+}
+
+/// Decrypts a transport message. Returns [`RemoshellStatus::Empty`] for
+/// rekey markers, which carry no data.
+///
+/// # Safety
+///
+/// `session` must be a live session, `ciphertext` must point to
+/// `ciphertext_len` readable bytes and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_decrypt(
+    session: *mut RemoshellNoiseSession,
+    ciphertext: *const u8,
+    ciphertext_len: usize,
+    out: *mut RemoshellBuffer,
+) -> RemoshellStatus {
+    call(|| {
+        let session = borrow_mut(session)?;
+        let out = borrow_mut(out)?;
+        match session
+            .0
+            .read_transport(bytes(ciphertext, ciphertext_len)?)?
+        {
+            Some(plaintext) => write_buffer(out, plaintext),
+            None => {
+                *out = RemoshellBuffer::EMPTY;
+                Ok(RemoshellStatus::Empty)
+            }
+        }
+    })
+}
+
+/// Switches sending keys periodically, once both peers have announced
+/// support for rekeying.
+///
+/// # Safety
+///
+/// `session` must be a live session.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_enable_rekey(
+    session: *mut RemoshellNoiseSession,
+) -> RemoshellStatus {
+    call(|| {
+        borrow_mut(session)?.0.enable_rekey(RekeyPolicy::default());
+        Ok(RemoshellStatus::Ok)
+    })
+}
+
+/// Returns true when the sending key is due to be replaced, and false for a
+/// null session.
+///
+/// # Safety
+///
+/// `session` must be null or a live session.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_rekey_due(session: *const RemoshellNoiseSession) -> bool {
+    session
+        .as_ref()
+        .is_some_and(|session| session.0.rekey_due())
+}
+
+/// Writes a rekey marker and switches to a new sending key.
+///
+/// # Safety
+///
+/// `session` must be a live session and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_write_rekey(
+    session: *mut RemoshellNoiseSession,
+    out: *mut RemoshellBuffer,
+) -> RemoshellStatus {
+    call(|| {
+        let marker = borrow_mut(session)?.0.write_rekey()?;
+        write_buffer(out, marker)
+    })
+}
+
+/// Releases a session. Null is ignored.
+///
+/// # Safety
+///
+/// `session` must come from the library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_noise_free(session: *mut RemoshellNoiseSession) {
+    if !session.is_null() {
+        drop(Box::from_raw(session));
+    }
+}
+
+// ============================================================================
+// Frame Codec
+// ============================================================================
+
+/// Creates a codec, compressing payloads unless `compression` is false.
+///
+/// # Safety
+///
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_frame_codec_new(
+    compression: bool,
+    out: *mut *mut RemoshellFrameCodec,
+) -> RemoshellStatus {
+    call(|| {
+        let mut codec = FrameCodec::new();
+        codec.set_compression(compression);
+        write_handle(out, RemoshellFrameCodec(codec))
+    })
+}
+
+/// Wraps a payload in a frame.
+///
+/// # Safety
+///
+/// `codec` must be a live codec, `payload` must point to `payload_len`
+/// readable bytes and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_frame_encode(
+    codec: *const RemoshellFrameCodec,
+    payload: *const u8,
+    payload_len: usize,
+    out: *mut RemoshellBuffer,
+) -> RemoshellStatus {
+    call(|| {
+        let frame = Frame::new(bytes(payload, payload_len)?.to_vec());
+        let encoded = borrow(codec)?.0.encode(&frame)?;
+        write_buffer(out, encoded)
+    })
+}
+
+/// Reads the first frame of a buffer into `out` and the number of bytes it
+/// took up into `consumed`. Returns [`RemoshellStatus::Empty`] if the buffer
+/// does not hold a whole frame yet.
+///
+/// # Safety
+///
+/// `codec` must be a live codec, `data` must point to `data_len` readable
+/// bytes, and `out` and `consumed` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_frame_decode(
+    codec: *const RemoshellFrameCodec,
+    data: *const u8,
+    data_len: usize,
+    out: *mut RemoshellBuffer,
+    consumed: *mut usize,
+) -> RemoshellStatus {
+    call(|| {
+        let codec = borrow(codec)?;
+        let out = borrow_mut(out)?;
+        let consumed = borrow_mut(consumed)?;
+        match codec.0.try_decode(bytes(data, data_len)?)? {
+            Some((frame, len)) => {
+                *consumed = len;
+                write_buffer(out, frame.payload)
+            }
+            None => {
+                *out = RemoshellBuffer::EMPTY;
+                *consumed = 0;
+                Ok(RemoshellStatus::Empty)
+            }
+        }
+    })
+}
+
+/// Releases a codec. Null is ignored.
+///
+/// # Safety
+///
+/// `codec` must come from the library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn remoshell_frame_codec_free(codec: *mut RemoshellFrameCodec) {
+    if !codec.is_null() {
+        drop(Box::from_raw(codec));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    /// Copies a buffer's bytes and frees it.
+    fn take(buffer: &mut RemoshellBuffer) -> Vec<u8> {
+        let buffer = std::mem::replace(buffer, RemoshellBuffer::EMPTY);
+        let bytes = unsafe { bytes(buffer.data, buffer.len) }
+            .ok()
+            .unwrap()
+            .to_vec();
+        unsafe { remoshell_buffer_free(buffer) };
+        bytes
+    }
+
+    fn identity() -> *mut RemoshellIdentity {
+        let mut identity = ptr::null_mut();
+        assert_eq!(
+            unsafe { remoshell_identity_generate(&mut identity) },
+            RemoshellStatus::Ok
+        );
+        identity
+    }
+
+    /// Sends a handshake message from one session to the other.
+    fn exchange(from: *mut RemoshellNoiseSession, to: *mut RemoshellNoiseSession) {
+        let mut message = RemoshellBuffer::EMPTY;
+        let mut payload = RemoshellBuffer::EMPTY;
+        unsafe {
+            assert_eq!(
+                remoshell_noise_write_handshake(from, ptr::null(), 0, &mut message),
+                RemoshellStatus::Ok
+            );
+            let message = take(&mut message);
+            assert_eq!(
+                remoshell_noise_read_handshake(to, message.as_ptr(), message.len(), &mut payload),
+                RemoshellStatus::Ok
+            );
+        }
+        assert!(take(&mut payload).is_empty());
+    }
+
+    #[test]
+    fn test_identity_roundtrip() {
+        let identity = identity();
+        let mut secret_key = [0u8; REMOSHELL_KEY_LENGTH];
+        let mut restored = ptr::null_mut();
+        let mut fingerprint = ptr::null_mut();
+        let mut restored_fingerprint = ptr::null_mut();
+        let mut words = ptr::null_mut();
+        unsafe {
+            remoshell_identity_secret_key(identity, secret_key.as_mut_ptr());
+            assert_eq!(
+                remoshell_identity_from_secret_key(secret_key.as_ptr(), &mut restored),
+                RemoshellStatus::Ok
+            );
+            remoshell_identity_fingerprint(identity, &mut fingerprint);
+            remoshell_identity_fingerprint(restored, &mut restored_fingerprint);
+            assert_eq!(
+                CStr::from_ptr(fingerprint),
+                CStr::from_ptr(restored_fingerprint)
+            );
+            remoshell_identity_fingerprint_words(identity, &mut words);
+            assert_eq!(
+                CStr::from_ptr(words).to_str().unwrap().split(' ').count(),
+                protocol::FINGERPRINT_WORD_COUNT
+            );
+
+            remoshell_string_free(fingerprint);
+            remoshell_string_free(restored_fingerprint);
+            remoshell_string_free(words);
+            remoshell_identity_free(identity);
+            remoshell_identity_free(restored);
+        }
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut identity = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                remoshell_identity_from_secret_key(ptr::null(), &mut identity),
+                RemoshellStatus::InvalidArgument
+            );
+            assert!(identity.is_null());
+            assert!(!remoshell_last_error().is_null());
+            assert_eq!(
+                remoshell_noise_initiator(ptr::null(), &mut ptr::null_mut()),
+                RemoshellStatus::InvalidArgument
+            );
+            assert!(!remoshell_noise_is_handshake_complete(ptr::null()));
+            remoshell_buffer_free(RemoshellBuffer::EMPTY);
+            remoshell_identity_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let mut codec = ptr::null_mut();
+        let payload = vec![b'a'; 4096];
+        let mut frame = RemoshellBuffer::EMPTY;
+        let mut decoded = RemoshellBuffer::EMPTY;
+        let mut consumed = 0;
+        unsafe {
+            remoshell_frame_codec_new(true, &mut codec);
+            remoshell_frame_encode(codec, payload.as_ptr(), payload.len(), &mut frame);
+            let frame = take(&mut frame);
+            assert!(frame.len() < payload.len(), "large payloads are compressed");
+
+            assert_eq!(
+                remoshell_frame_decode(
+                    codec,
+                    frame.as_ptr(),
+                    frame.len() - 1,
+                    &mut decoded,
+                    &mut consumed
+                ),
+                RemoshellStatus::Empty
+            );
+            assert_eq!(
+                remoshell_frame_decode(
+                    codec,
+                    frame.as_ptr(),
+                    frame.len(),
+                    &mut decoded,
+                    &mut consumed
+                ),
+                RemoshellStatus::Ok
+            );
+            assert_eq!(consumed, frame.len());
+            assert_eq!(take(&mut decoded), payload);
+            remoshell_frame_codec_free(codec);
+        }
+    }
+
+    #[test]
+    fn test_noise_handshake_and_transport() {
+        let client_identity = identity();
+        let daemon_identity = identity();
+        let mut client = ptr::null_mut();
+        let mut daemon = ptr::null_mut();
+        let mut buffer = RemoshellBuffer::EMPTY;
+        let mut remote_static = [0u8; REMOSHELL_KEY_LENGTH];
+        unsafe {
+            assert_eq!(
+                remoshell_noise_remote_static(ptr::null(), remote_static.as_mut_ptr()),
+                RemoshellStatus::InvalidArgument
+            );
+            remoshell_noise_initiator(client_identity, &mut client);
+            remoshell_noise_responder(daemon_identity, &mut daemon);
+            exchange(client, daemon);
+            exchange(daemon, client);
+            exchange(client, daemon);
+            assert!(remoshell_noise_is_handshake_complete(client));
+            assert!(remoshell_noise_is_handshake_complete(daemon));
+            assert_eq!(
+                remoshell_noise_remote_static(client, remote_static.as_mut_ptr()),
+                RemoshellStatus::Ok
+            );
+            remoshell_noise_handshake_hash(client, &mut buffer);
+            let client_hash = take(&mut buffer);
+            remoshell_noise_handshake_hash(daemon, &mut buffer);
+            assert_eq!(take(&mut buffer), client_hash);
+
+            remoshell_noise_into_transport(client);
+            remoshell_noise_into_transport(daemon);
+            remoshell_noise_encrypt(client, b"hello".as_ptr(), 5, &mut buffer);
+            let ciphertext = take(&mut buffer);
+            assert_eq!(
+                remoshell_noise_decrypt(daemon, ciphertext.as_ptr(), ciphertext.len(), &mut buffer),
+                RemoshellStatus::Ok
+            );
+            assert_eq!(take(&mut buffer), b"hello");
+
+            remoshell_noise_enable_rekey(client);
+            remoshell_noise_enable_rekey(daemon);
+            remoshell_noise_write_rekey(client, &mut buffer);
+            let marker = take(&mut buffer);
+            assert_eq!(
+                remoshell_noise_decrypt(daemon, marker.as_ptr(), marker.len(), &mut buffer),
+                RemoshellStatus::Empty
+            );
+            assert_eq!(
+                remoshell_noise_decrypt(daemon, ciphertext.as_ptr(), ciphertext.len(), &mut buffer),
+                RemoshellStatus::Error,
+                "replayed messages are rejected"
+            );
+            assert!(!CStr::from_ptr(remoshell_last_error()).is_empty());
+
+            remoshell_noise_free(client);
+            remoshell_noise_free(daemon);
+            remoshell_identity_free(client_identity);
+            remoshell_identity_free(daemon_identity);
+        }
+    }
+}
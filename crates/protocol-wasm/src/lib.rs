@@ -0,0 +1,311 @@
+//! # RemoShell Protocol for Browsers
+//!
+//! WebAssembly bindings to the [`protocol`] crate, so that a browser client
+//! encodes, frames and encrypts messages with the same code as the daemon.
+//!
+//! ## Overview
+//!
+//! - [`Identity`]: A device identity, generated or restored from its secret
+//!   key
+//! - [`encodeEnvelope`] / [`decodeEnvelope`]: Envelopes as JSON on the
+//!   JavaScript side and MessagePack on the wire
+//! - [`FrameCodec`]: Length-prefixed framing with LZ4 compression
+//! - [`NoiseSession`]: The Noise XX handshake and transport encryption
+//!
+//! Envelope JSON uses the serde representation of
+//! [`Envelope`](protocol::Envelope): messages are `{ "type": ..., "data": ... }`
+//! objects and byte fields are arrays of numbers.
+//!
+//! Errors are thrown as JavaScript `Error`s carrying the protocol error
+//! message.
+//!
+//! ## Building
+//!
+//! ```text
+//! wasm-pack build crates/protocol-wasm --target web
+//! ```
+//!
+//! The package is written to `crates/protocol-wasm/pkg`, with TypeScript
+//! definitions.
+
+#![allow(non_snake_case)]
+
+use protocol::{DecodeLimits, Envelope, Frame, RekeyPolicy};
+use wasm_bindgen::prelude::*;
+
+/// A device identity: an Ed25519 key pair and the device ID derived from it.
+#[wasm_bindgen]
+pub struct Identity(protocol::DeviceIdentity);
+
+#[wasm_bindgen]
+impl Identity {
+    /// Generates a new identity.
+    #[wasm_bindgen(constructor)]
+    pub fn generate() -> Identity {
+        Identity(protocol::DeviceIdentity::generate())
+    }
+
+    /// Restores an identity from its 32-byte secret key.
+    pub fn fromSecretKey(secret_key: &[u8]) -> Result<Identity, JsError> {
+        let bytes: [u8; 32] = secret_key
+            .try_into()
+            .map_err(|_| JsError::new("secret key must be 32 bytes"))?;
+        Ok(Identity(protocol::DeviceIdentity::from_secret_key_bytes(
+            &bytes,
+        )))
+    }
+
+    /// Returns the 32-byte secret key, for storage.
+    pub fn secretKey(&self) -> Vec<u8> {
+        self.0.secret_key_bytes().to_vec()
+    }
+
+    /// Returns the 32-byte public key.
+    pub fn publicKey(&self) -> Vec<u8> {
+        self.0.public_key_bytes().to_vec()
+    }
+
+    /// Returns the device ID as a colon-separated fingerprint.
+    pub fn fingerprint(&self) -> String {
+        self.0.fingerprint()
+    }
+
+    /// Returns the fingerprint as words, for comparing out of band.
+    pub fn fingerprintWords(&self) -> Vec<String> {
+        self.0
+            .device_id()
+            .fingerprint_words()
+            .iter()
+            .map(|word| word.to_string())
+            .collect()
+    }
+}
+
+/// Encodes an envelope given as JSON to MessagePack.
+#[wasm_bindgen]
+pub fn encodeEnvelope(json: &str) -> Result<Vec<u8>, JsError> {
+    let envelope: Envelope = serde_json::from_str(json)?;
+    Ok(envelope.to_msgpack()?)
+}
+
+/// Decodes a MessagePack envelope to JSON, within the default decoding
+/// limits.
+#[wasm_bindgen]
+pub fn decodeEnvelope(bytes: &[u8]) -> Result<String, JsError> {
+    let envelope = Envelope::from_msgpack_with_limits(bytes, &DecodeLimits::default())?;
+    Ok(serde_json::to_string(&envelope)?)
+}
+
+/// A frame read from a buffer.
+#[wasm_bindgen]
+pub struct DecodedFrame {
+    payload: Vec<u8>,
+    consumed: usize,
+}
+
+#[wasm_bindgen]
+impl DecodedFrame {
+    /// Returns the payload of the frame, decompressed.
+    #[wasm_bindgen(getter)]
+    pub fn payload(&self) -> Vec<u8> {
+        self.payload.clone()
+    }
+
+    /// Returns how many bytes of the buffer the frame took up.
+    #[wasm_bindgen(getter)]
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+}
+
+/// Length-prefixed framing, compressing large payloads with LZ4.
+#[wasm_bindgen]
+pub struct FrameCodec(protocol::FrameCodec);
+
+#[wasm_bindgen]
+impl FrameCodec {
+    /// Creates a codec, compressing payloads unless `compression` is false.
+    #[wasm_bindgen(constructor)]
+    pub fn new(compression: bool) -> FrameCodec {
+        let mut codec = protocol::FrameCodec::new();
+        codec.set_compression(compression);
+        FrameCodec(codec)
+    }
+
+    /// Wraps a payload in a frame.
+    pub fn encode(&self, payload: &[u8]) -> Result<Vec<u8>, JsError> {
+        Ok(self.0.encode(&Frame::new(payload.to_vec()))?)
+    }
+
+    /// Reads the first frame of a buffer, or returns `undefined` if the
+    /// buffer does not hold a whole frame yet.
+    pub fn decode(&self, data: &[u8]) -> Result<Option<DecodedFrame>, JsError> {
+        Ok(self
+            .0
+            .try_decode(data)?
+            .map(|(frame, consumed)| DecodedFrame {
+                payload: frame.payload,
+                consumed,
+            }))
+    }
+}
+
+/// A Noise XX session: the handshake, then transport encryption.
+#[wasm_bindgen]
+pub struct NoiseSession(protocol::NoiseSession);
+
+#[wasm_bindgen]
+impl NoiseSession {
+    /// Starts a handshake as the initiator, as clients do.
+    pub fn initiator(identity: &Identity) -> Result<NoiseSession, JsError> {
+        Ok(NoiseSession(protocol::NoiseSession::new_initiator(
+            &identity.0,
+        )?))
+    }
+
+    /// Starts a handshake as the responder.
+    pub fn responder(identity: &Identity) -> Result<NoiseSession, JsError> {
+        Ok(NoiseSession(protocol::NoiseSession::new_responder(
+            &identity.0,
+        )?))
+    }
+
+    /// Writes the next handshake message, carrying `payload`.
+    pub fn writeHandshake(&mut self, payload: &[u8]) -> Result<Vec<u8>, JsError> {
+        Ok(self.0.write_handshake_message(payload)?)
+    }
+
+    /// Reads a handshake message from the peer and returns its payload.
+    pub fn readHandshake(&mut self, message: &[u8]) -> Result<Vec<u8>, JsError> {
+        Ok(self.0.read_handshake_message(message)?)
+    }
+
+    /// Returns true once every handshake message has been exchanged.
+    pub fn isHandshakeComplete(&self) -> bool {
+        self.0.is_handshake_complete()
+    }
+
+    /// Switches to transport mode after the handshake.
+    pub fn intoTransport(&mut self) -> Result<(), JsError> {
+        Ok(self.0.into_transport()?)
+    }
+
+    /// Returns the peer's static Noise key, once the handshake has revealed
+    /// it.
+    pub fn remoteStatic(&self) -> Option<Vec<u8>> {
+        self.0.get_remote_static().map(|key| key.to_vec())
+    }
+
+    /// Returns the handshake hash, which both peers share, for binding
+    /// identity proofs to this session.
+    pub fn handshakeHash(&self) -> Option<Vec<u8>> {
+        self.0.handshake_hash().map(<[u8]>::to_vec)
+    }
+
+    /// Encrypts a transport message.
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, JsError> {
+        Ok(self.0.encrypt(plaintext)?)
+    }
+
+    /// Decrypts a transport message. Returns `undefined` for rekey markers,
+    /// which carry no data.
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Option<Vec<u8>>, JsError> {
+        Ok(self.0.read_transport(ciphertext)?)
+    }
+
+    /// Switches sending keys periodically, once both peers have announced
+    /// support for rekeying.
+    pub fn enableRekey(&mut self) {
+        self.0.enable_rekey(RekeyPolicy::default());
+    }
+
+    /// Returns true when the sending key is due to be replaced.
+    pub fn rekeyDue(&self) -> bool {
+        self.0.rekey_due()
+    }
+
+    /// Writes a rekey marker and switches to a new sending key.
+    pub fn writeRekey(&mut self) -> Result<Vec<u8>, JsError> {
+        Ok(self.0.write_rekey()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_roundtrip() {
+        let identity = Identity::generate();
+        let restored = Identity::fromSecretKey(&identity.secretKey()).unwrap();
+        assert_eq!(restored.fingerprint(), identity.fingerprint());
+        assert_eq!(restored.publicKey(), identity.publicKey());
+        assert_eq!(
+            identity.fingerprintWords().len(),
+            protocol::FINGERPRINT_WORD_COUNT
+        );
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let json = r#"{"version":1,"sequence":7,"payload":{"type":"Ping","data":{"timestamp":42,"payload":[1,2,3]}}}"#;
+        let bytes = encodeEnvelope(json).unwrap();
+        assert_eq!(
+            Envelope::from_msgpack(&bytes).unwrap().sequence,
+            7,
+            "decodes natively"
+        );
+        assert_eq!(decodeEnvelope(&bytes).unwrap(), json);
+    }
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let codec = FrameCodec::new(true);
+        let payload = vec![b'a'; 4096];
+        let mut buffer = codec.encode(&payload).unwrap();
+        assert!(
+            buffer.len() < payload.len(),
+            "large payloads are compressed"
+        );
+
+        assert!(codec.decode(&buffer[..buffer.len() - 1]).unwrap().is_none());
+        buffer.extend_from_slice(&codec.encode(b"next").unwrap());
+        let frame = codec.decode(&buffer).unwrap().unwrap();
+        assert_eq!(frame.payload(), payload);
+        let next = codec.decode(&buffer[frame.consumed()..]).unwrap().unwrap();
+        assert_eq!(next.payload(), b"next");
+    }
+
+    #[test]
+    fn test_noise_handshake_and_transport() {
+        let client_identity = Identity::generate();
+        let daemon_identity = Identity::generate();
+        let mut client = NoiseSession::initiator(&client_identity).unwrap();
+        let mut daemon = NoiseSession::responder(&daemon_identity).unwrap();
+
+        let message = client.writeHandshake(&[]).unwrap();
+        daemon.readHandshake(&message).unwrap();
+        let message = daemon.writeHandshake(&[]).unwrap();
+        client.readHandshake(&message).unwrap();
+        let message = client.writeHandshake(&[]).unwrap();
+        daemon.readHandshake(&message).unwrap();
+        assert!(client.isHandshakeComplete() && daemon.isHandshakeComplete());
+        assert!(client.remoteStatic().is_some());
+        assert_eq!(client.handshakeHash(), daemon.handshakeHash());
+
+        client.intoTransport().unwrap();
+        daemon.intoTransport().unwrap();
+        let ciphertext = client.encrypt(b"hello").unwrap();
+        assert_eq!(daemon.decrypt(&ciphertext).unwrap().unwrap(), b"hello");
+
+        client.enableRekey();
+        daemon.enableRekey();
+        let marker = client.writeRekey().unwrap();
+        assert_eq!(daemon.decrypt(&marker).unwrap(), None);
+        let ciphertext = client.encrypt(b"after rekey").unwrap();
+        assert_eq!(
+            daemon.decrypt(&ciphertext).unwrap().unwrap(),
+            b"after rekey"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Performance benchmarks for the protocol's per-message work.
+//!
+//! These benchmarks measure what every message pays on the wire:
+//! - Frame encoding and decoding, with and without LZ4 compression
+//! - Noise transport encryption and decryption
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use protocol::framing::{Frame, FrameCodec};
+use protocol::noise::{NoiseSession, MAX_NOISE_MESSAGE_SIZE, NOISE_OVERHEAD};
+use protocol::DeviceIdentity;
+
+/// Payload sizes: a keystroke echo, a terminal output chunk, a file chunk.
+const SIZES: &[(&str, usize)] = &[("64B", 64), ("4KB", 4096), ("64KB", 65536)];
+
+/// Returns terminal-like output, which compresses well.
+fn terminal_output(len: usize) -> Vec<u8> {
+    b"drwxr-xr-x  2 user staff  4096 Jan  1 12:00 src\r\n"
+        .iter()
+        .copied()
+        .cycle()
+        .take(len)
+        .collect()
+}
+
+/// Returns the codecs under test: compression enabled and disabled.
+fn codecs() -> [(&'static str, FrameCodec); 2] {
+    [
+        ("compressed", FrameCodec::new()),
+        ("uncompressed", FrameCodec::without_compression()),
+    ]
+}
+
+/// Benchmark frame encoding.
+fn bench_frame_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame_encode");
+
+    for (codec_name, codec) in codecs() {
+        for &(size_name, size) in SIZES {
+            let frame = Frame::new(terminal_output(size));
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(
+                BenchmarkId::new(codec_name, size_name),
+                &frame,
+                |b, frame| b.iter(|| codec.encode(black_box(frame)).unwrap()),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+/// Benchmark frame decoding.
+fn bench_frame_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame_decode");
+
+    for (codec_name, codec) in codecs() {
+        for &(size_name, size) in SIZES {
+            let encoded = codec.encode(&Frame::new(terminal_output(size))).unwrap();
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(
+                BenchmarkId::new(codec_name, size_name),
+                &encoded,
+                |b, encoded| b.iter(|| codec.decode(black_box(encoded)).unwrap()),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+/// Returns two sessions that completed a Noise XX handshake.
+fn transport_pair() -> (NoiseSession, NoiseSession) {
+    let mut initiator = NoiseSession::new_initiator(&DeviceIdentity::generate()).unwrap();
+    let mut responder = NoiseSession::new_responder(&DeviceIdentity::generate()).unwrap();
+
+    let msg1 = initiator.write_handshake_message(&[]).unwrap();
+    responder.read_handshake_message(&msg1).unwrap();
+    let msg2 = responder.write_handshake_message(&[]).unwrap();
+    initiator.read_handshake_message(&msg2).unwrap();
+    let msg3 = initiator.write_handshake_message(&[]).unwrap();
+    responder.read_handshake_message(&msg3).unwrap();
+
+    initiator.into_transport().unwrap();
+    responder.into_transport().unwrap();
+    (initiator, responder)
+}
+
+/// Benchmark Noise transport encryption and decryption.
+fn bench_noise(c: &mut Criterion) {
+    let mut group = c.benchmark_group("noise");
+    let max_plaintext = MAX_NOISE_MESSAGE_SIZE - NOISE_OVERHEAD;
+
+    // A Noise message cannot hold a 64KB payload, so the largest case is the
+    // largest plaintext that fits
+    let sizes = [("64B", 64), ("4KB", 4096), ("max", max_plaintext)];
+
+    for (size_name, size) in sizes {
+        let plaintext = vec![0x5a; size];
+        group.throughput(Throughput::Bytes(size as u64));
+
+        let (mut initiator, _) = transport_pair();
+        group.bench_with_input(
+            BenchmarkId::new("encrypt", size_name),
+            &plaintext,
+            |b, plaintext| b.iter(|| initiator.encrypt(black_box(plaintext)).unwrap()),
+        );
+
+        // Nonces are sequential, so every ciphertext is decrypted once and
+        // in the order it was produced
+        let (mut initiator, mut responder) = transport_pair();
+        group.bench_with_input(
+            BenchmarkId::new("decrypt", size_name),
+            &plaintext,
+            |b, plaintext| {
+                b.iter_batched(
+                    || initiator.encrypt(plaintext).unwrap(),
+                    |ciphertext| responder.decrypt(black_box(&ciphertext)).unwrap(),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_frame_encode, bench_frame_decode, bench_noise);
+
+criterion_main!(benches);
@@ -24,6 +24,14 @@ fn main() {
             shell: None,
             env: vec![],
             cwd: None,
+            forward_agent: false,
+            template: None,
+            timezone: None,
+            locale: None,
+            term: None,
+            forward_display: None,
+            host_session: None,
+            login_shell: false,
         }),
     );
     print_test_vector("session_create_default", &session_create);
@@ -47,6 +55,7 @@ fn main() {
             message: "Not found".to_string(),
             context: Some("test".to_string()),
             recoverable: false,
+            retry_after_ms: None,
         }),
     );
     print_test_vector("error", &error);
@@ -62,6 +71,7 @@ fn main() {
                 size: 100,
                 mode: 0o644,
                 modified: 1704067200,
+                details: None,
             }],
         }),
     );
@@ -0,0 +1,15 @@
+//! Fuzzes MessagePack envelope decoding.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use protocol::Envelope;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(envelope) = Envelope::from_msgpack(data) {
+        // Anything we accept must survive a roundtrip
+        let bytes = envelope.to_msgpack().expect("re-encode");
+        let decoded = Envelope::from_msgpack(&bytes).expect("re-decode");
+        assert_eq!(envelope, decoded);
+    }
+});
@@ -0,0 +1,22 @@
+//! Fuzzes `FrameCodec::decode` and `FrameCodec::try_decode`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use protocol::{FrameCodec, MAX_FRAME_SIZE};
+
+fuzz_target!(|data: &[u8]| {
+    let codec = FrameCodec::new();
+
+    if let Ok((frame, consumed)) = codec.decode(data) {
+        assert!(consumed <= data.len());
+        assert!(frame.payload.len() <= MAX_FRAME_SIZE);
+    }
+
+    // Streaming decode must agree with the one-shot decode on complete input
+    if let Ok(Some((frame, consumed))) = codec.try_decode(data) {
+        let (expected, expected_consumed) = codec.decode(data).expect("try_decode accepted");
+        assert_eq!(frame.payload, expected.payload);
+        assert_eq!(consumed, expected_consumed);
+    }
+});
@@ -0,0 +1,26 @@
+//! Fuzzes Noise XX handshake message parsing on both sides.
+
+#![no_main]
+
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+use protocol::{DeviceIdentity, NoiseSession};
+
+fn identity() -> &'static DeviceIdentity {
+    static IDENTITY: OnceLock<DeviceIdentity> = OnceLock::new();
+    IDENTITY.get_or_init(|| DeviceIdentity::from_secret_key_bytes(&[7u8; 32]))
+}
+
+fuzz_target!(|data: &[u8]| {
+    // A responder reading the first handshake message
+    let mut responder = NoiseSession::new_responder(identity()).expect("responder");
+    let _ = responder.read_handshake_message(data);
+
+    // An initiator reading the response to its first message
+    let mut initiator = NoiseSession::new_initiator(identity()).expect("initiator");
+    initiator
+        .write_handshake_message(&[])
+        .expect("first message");
+    let _ = initiator.read_handshake_message(data);
+});
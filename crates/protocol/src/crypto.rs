@@ -2,6 +2,10 @@
 //!
 //! This module provides Ed25519 key generation, device identity management,
 //! message signing, and signature verification.
+//!
+//! For out-of-band comparison, a [`DeviceId`] can also be shown as OpenSSH-style
+//! randomart ([`DeviceId::randomart`]) or as BIP39 words
+//! ([`DeviceId::fingerprint_words`]).
 
 use ed25519_dalek::{
     Signature as Ed25519Signature, Signer, SigningKey, Verifier, VerifyingKey, PUBLIC_KEY_LENGTH,
@@ -11,10 +15,29 @@ use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 
 use crate::error::{ProtocolError, Result};
+use crate::wordlist::BIP39_ENGLISH;
 
 /// Length of a device ID in bytes (SHA-256 output truncated to 16 bytes).
 pub const DEVICE_ID_LENGTH: usize = 16;
 
+/// Number of words in a word fingerprint (128 bits plus a 4-bit checksum).
+pub const FINGERPRINT_WORD_COUNT: usize = 12;
+
+/// Bits encoded by each BIP39 word.
+const BITS_PER_WORD: usize = 11;
+
+/// Width of the randomart board, as in OpenSSH.
+const RANDOMART_WIDTH: usize = 17;
+
+/// Height of the randomart board, as in OpenSSH.
+const RANDOMART_HEIGHT: usize = 9;
+
+/// Randomart symbols for increasing visit counts, as in OpenSSH.
+const RANDOMART_SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^";
+
+/// Title shown in the top border of the randomart.
+const RANDOMART_TITLE: &str = "[RemoShell]";
+
 /// A device identifier derived from the public key.
 ///
 /// This is a 16-byte identifier derived by hashing the public key with SHA-256
@@ -46,6 +69,90 @@ impl DeviceId {
             .join(":")
     }
 
+    /// Renders this device ID as OpenSSH-style randomart.
+    ///
+    /// The "drunken bishop" walk from OpenSSH's `VisualHostKey` turns the ID
+    /// into a small picture that is easier to compare at a glance than hex.
+    /// `S` and `E` mark where the walk starts and ends. The result has 11
+    /// lines and no trailing newline.
+    pub fn randomart(&self) -> String {
+        let mut field = [[0usize; RANDOMART_WIDTH]; RANDOMART_HEIGHT];
+        let start = (RANDOMART_WIDTH / 2, RANDOMART_HEIGHT / 2);
+        let (mut x, mut y) = start;
+
+        for byte in self.0 {
+            // Each byte gives four moves, least significant bit pair first
+            let mut bits = byte;
+            for _ in 0..4 {
+                x = if bits & 0x1 != 0 {
+                    (x + 1).min(RANDOMART_WIDTH - 1)
+                } else {
+                    x.saturating_sub(1)
+                };
+                y = if bits & 0x2 != 0 {
+                    (y + 1).min(RANDOMART_HEIGHT - 1)
+                } else {
+                    y.saturating_sub(1)
+                };
+                field[y][x] += 1;
+                bits >>= 2;
+            }
+        }
+
+        let mut lines = Vec::with_capacity(RANDOMART_HEIGHT + 2);
+        lines.push(format!(
+            "+{:-^width$}+",
+            RANDOMART_TITLE,
+            width = RANDOMART_WIDTH
+        ));
+        for (row, counts) in field.iter().enumerate() {
+            let cells: String = counts
+                .iter()
+                .enumerate()
+                .map(|(col, &count)| {
+                    if (col, row) == (x, y) {
+                        'E'
+                    } else if (col, row) == start {
+                        'S'
+                    } else {
+                        RANDOMART_SYMBOLS[count.min(RANDOMART_SYMBOLS.len() - 1)] as char
+                    }
+                })
+                .collect();
+            lines.push(format!("|{}|", cells));
+        }
+        lines.push(format!("+{}+", "-".repeat(RANDOMART_WIDTH)));
+        lines.join("\n")
+    }
+
+    /// Encodes this device ID as BIP39 English words.
+    ///
+    /// The 16-byte ID is encoded exactly like 128 bits of BIP39 entropy, so
+    /// the words carry a checksum and can be checked with any BIP39 tool.
+    pub fn fingerprint_words(&self) -> [&'static str; FINGERPRINT_WORD_COUNT] {
+        use sha2::{Digest, Sha256};
+
+        let entropy = u128::from_be_bytes(self.0);
+        // The checksum is the first 4 bits of the SHA-256 of the entropy
+        let checksum = u128::from(Sha256::digest(self.0)[0] >> 4);
+        let total_bits = DEVICE_ID_LENGTH * 8;
+
+        let mut words = [""; FINGERPRINT_WORD_COUNT];
+        for (i, word) in words.iter_mut().enumerate() {
+            let mut index = 0;
+            for bit in i * BITS_PER_WORD..(i + 1) * BITS_PER_WORD {
+                let value = if bit < total_bits {
+                    (entropy >> (total_bits - 1 - bit)) & 1
+                } else {
+                    (checksum >> (total_bits + 3 - bit)) & 1
+                };
+                index = (index << 1) | value as usize;
+            }
+            *word = BIP39_ENGLISH[index];
+        }
+        words
+    }
+
     /// Derives a DeviceId from a public key by hashing it with SHA-256.
     fn from_public_key(public_key: &VerifyingKey) -> Self {
         use sha2::{Digest, Sha256};
@@ -77,6 +184,18 @@ impl Signature {
         &self.0
     }
 
+    /// Creates a Signature from a byte slice.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let bytes: [u8; SIGNATURE_LENGTH] = bytes.try_into().map_err(|_| {
+            ProtocolError::InvalidSignature(format!(
+                "invalid signature length: expected {}, got {}",
+                SIGNATURE_LENGTH,
+                bytes.len()
+            ))
+        })?;
+        Ok(Self(bytes))
+    }
+
     /// Converts from ed25519_dalek Signature.
     fn from_ed25519(sig: Ed25519Signature) -> Self {
         Self(sig.to_bytes())
@@ -428,6 +547,56 @@ mod tests {
             .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == ':'));
     }
 
+    #[test]
+    fn test_fingerprint_words_match_bip39_vectors() {
+        // Test vectors from the BIP39 reference implementation
+        let cases = [
+            (
+                [0x00; 16],
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            ),
+            (
+                [0x7f; 16],
+                "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            ),
+            (
+                [0x80; 16],
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+            ),
+            (
+                [0xff; 16],
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+            ),
+        ];
+
+        for (bytes, expected) in cases {
+            let words = DeviceId::from_bytes(bytes).fingerprint_words();
+            assert_eq!(words.join(" "), expected);
+        }
+    }
+
+    #[test]
+    fn test_randomart() {
+        let art = DeviceId::from_bytes([0x00; 16]).randomart();
+        let lines: Vec<&str> = art.lines().collect();
+
+        assert_eq!(lines.len(), RANDOMART_HEIGHT + 2);
+        assert_eq!(lines[0], "+---[RemoShell]---+");
+        assert_eq!(lines[RANDOMART_HEIGHT + 1], "+-----------------+");
+        assert!(lines
+            .iter()
+            .all(|line| line.chars().count() == RANDOMART_WIDTH + 2));
+
+        // All-zero bytes walk up and left into the top-left corner
+        assert_eq!(lines[1], "|E....            |");
+        assert_eq!(lines[2], "|     .           |");
+        assert_eq!(lines[5], "|        S        |");
+
+        // Different IDs give different pictures
+        let other = DeviceIdentity::generate().device_id().randomart();
+        assert_ne!(art, other);
+    }
+
     #[test]
     fn test_device_id_display() {
         let identity = DeviceIdentity::generate();
@@ -547,4 +716,17 @@ mod tests {
         // Verification should fail with corrupted signature
         assert!(identity.verify(message, &corrupted_sig).is_err());
     }
+
+    #[test]
+    fn test_signature_from_slice() {
+        let identity = DeviceIdentity::generate();
+        let signature = identity.sign(b"message");
+
+        let parsed = Signature::from_slice(signature.as_bytes()).unwrap();
+        assert_eq!(parsed, signature);
+        assert!(matches!(
+            Signature::from_slice(&signature.as_bytes()[..32]),
+            Err(ProtocolError::InvalidSignature(_))
+        ));
+    }
 }
@@ -0,0 +1,345 @@
+//! Structured reports of connection attempts.
+//!
+//! The final error of a failed connection rarely says where it went wrong:
+//! the relay may not have answered, hole punching may have found no path, or
+//! every direct address may have stayed silent. A [`DialReport`] lists each
+//! step of an attempt with what it targeted, when it ran, how long it took
+//! and why it failed. Clients receive one when a connection fails and
+//! daemons log theirs, so both ends of a failure can be compared.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::time::Instant;
+
+/// A step of a connection attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DialStepKind {
+    /// Reaching the peer through its relay server.
+    RelayLookup,
+    /// Opening a direct path through NAT, coordinated over the relay.
+    HolePunch,
+    /// Reaching one direct address of the peer.
+    Direct,
+    /// Connecting over one transport, such as QUIC or WebRTC.
+    Transport,
+    /// The QUIC or Noise handshake.
+    Handshake,
+    /// Opening the control, terminal and files streams.
+    Streams,
+}
+
+impl DialStepKind {
+    /// Returns the step name used in logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DialStepKind::RelayLookup => "relay lookup",
+            DialStepKind::HolePunch => "hole punch",
+            DialStepKind::Direct => "direct",
+            DialStepKind::Transport => "transport",
+            DialStepKind::Handshake => "handshake",
+            DialStepKind::Streams => "streams",
+        }
+    }
+}
+
+/// How a step ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepOutcome {
+    /// The step worked.
+    Succeeded,
+    /// The step was tried and failed.
+    Failed,
+    /// The step was not tried.
+    Skipped,
+}
+
+impl StepOutcome {
+    fn name(&self) -> &'static str {
+        match self {
+            StepOutcome::Succeeded => "ok",
+            StepOutcome::Failed => "failed",
+            StepOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+/// One step of a connection attempt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DialStep {
+    /// What the step did.
+    pub kind: DialStepKind,
+    /// What the step targeted: a relay URL, socket address or transport.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// When the step started, in milliseconds from the start of the attempt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_ms: Option<u64>,
+    /// How long the step took, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// How the step ended.
+    pub outcome: StepOutcome,
+    /// Why the step failed or was skipped, or what it measured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl DialStep {
+    /// Creates a step without target, timing or detail.
+    pub fn new(kind: DialStepKind, outcome: StepOutcome) -> Self {
+        Self {
+            kind,
+            target: None,
+            started_ms: None,
+            duration_ms: None,
+            outcome,
+            detail: None,
+        }
+    }
+
+    /// Sets what the step targeted.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Sets when the step started, relative to the attempt, and how long it
+    /// took.
+    pub fn timing(mut self, started: Duration, duration: Duration) -> Self {
+        self.started_ms = Some(millis(started));
+        self.duration_ms = Some(millis(duration));
+        self
+    }
+
+    /// Sets the reason or measurement shown with the step.
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+impl fmt::Display for DialStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.kind.name())?;
+        if let Some(target) = &self.target {
+            write!(f, " {}", target)?;
+        }
+        write!(f, ": {}", self.outcome.name())?;
+        if let Some(duration) = self.duration_ms {
+            write!(f, " in {}ms", duration)?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// The steps of one connection attempt and how it ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DialReport {
+    /// What was dialed: a node ID, address or URL.
+    pub target: String,
+    /// The steps, in the order they were recorded.
+    pub steps: Vec<DialStep>,
+    /// How long the attempt took, in milliseconds.
+    pub elapsed_ms: u64,
+    /// Why the attempt failed; `None` if it connected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DialReport {
+    /// Returns whether the attempt connected.
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Returns the steps that failed.
+    pub fn failed_steps(&self) -> impl Iterator<Item = &DialStep> {
+        self.steps
+            .iter()
+            .filter(|step| step.outcome == StepOutcome::Failed)
+    }
+}
+
+impl fmt::Display for DialReport {
+    /// Formats the report on one line, for logs and error messages.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            Some(error) => write!(f, "{}: {} after {}ms", self.target, error, self.elapsed_ms)?,
+            None => write!(f, "{}: connected in {}ms", self.target, self.elapsed_ms)?,
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            f.write_str(if i == 0 { " [" } else { "; " })?;
+            write!(f, "{}", step)?;
+        }
+        if !self.steps.is_empty() {
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [`DialReport`] while an attempt runs.
+#[derive(Debug)]
+pub struct DialRecorder {
+    started: Instant,
+    target: String,
+    steps: Vec<DialStep>,
+}
+
+impl DialRecorder {
+    /// Starts recording an attempt to reach `target`.
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            started: Instant::now(),
+            target: target.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Returns the time since the attempt started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Runs a step, recording its timing and outcome. A failed step is
+    /// recorded with its error as the detail.
+    pub async fn step<T, E, F>(
+        &mut self,
+        kind: DialStepKind,
+        target: Option<&str>,
+        step: F,
+    ) -> std::result::Result<T, E>
+    where
+        E: fmt::Display,
+        F: Future<Output = std::result::Result<T, E>>,
+    {
+        let started = self.elapsed();
+        let result = step.await;
+        let duration = self.elapsed().saturating_sub(started);
+
+        let outcome = match result {
+            Ok(_) => StepOutcome::Succeeded,
+            Err(_) => StepOutcome::Failed,
+        };
+        let mut recorded = DialStep::new(kind, outcome).timing(started, duration);
+        recorded.target = target.map(str::to_string);
+        if let Err(e) = &result {
+            recorded.detail = Some(e.to_string());
+        }
+        self.steps.push(recorded);
+        result
+    }
+
+    /// Records a step observed outside [`DialRecorder::step`].
+    pub fn push(&mut self, step: DialStep) {
+        self.steps.push(step);
+    }
+
+    /// Records several observed steps.
+    pub fn extend(&mut self, steps: impl IntoIterator<Item = DialStep>) {
+        self.steps.extend(steps);
+    }
+
+    /// Ends the attempt as connected.
+    pub fn succeed(self) -> DialReport {
+        self.finish(None)
+    }
+
+    /// Ends the attempt as failed.
+    pub fn fail(self, error: impl fmt::Display) -> DialReport {
+        self.finish(Some(error.to_string()))
+    }
+
+    fn finish(self, error: Option<String>) -> DialReport {
+        DialReport {
+            elapsed_ms: millis(self.started.elapsed()),
+            target: self.target,
+            steps: self.steps,
+            error,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_recorder_times_steps() {
+        let mut recorder = DialRecorder::new("daemon");
+        let result: Result<(), String> = recorder
+            .step(DialStepKind::Transport, Some("quic"), async {
+                Err("blocked".to_string())
+            })
+            .await;
+        assert!(result.is_err());
+        let result: Result<u8, String> = recorder
+            .step(DialStepKind::Transport, Some("webrtc"), async { Ok(7) })
+            .await;
+        assert_eq!(result, Ok(7));
+
+        let report = recorder.succeed();
+        assert!(report.succeeded());
+        assert_eq!(report.steps.len(), 2);
+        assert_eq!(report.steps[0].outcome, StepOutcome::Failed);
+        assert_eq!(report.steps[0].target.as_deref(), Some("quic"));
+        assert_eq!(report.steps[0].detail.as_deref(), Some("blocked"));
+        assert!(report.steps[0].started_ms.is_some());
+        assert_eq!(report.steps[1].outcome, StepOutcome::Succeeded);
+        assert_eq!(report.failed_steps().count(), 1);
+    }
+
+    #[test]
+    fn test_report_display() {
+        let mut recorder = DialRecorder::new("node");
+        recorder.push(
+            DialStep::new(DialStepKind::RelayLookup, StepOutcome::Skipped).detail("no relay URL"),
+        );
+        recorder.push(
+            DialStep::new(DialStepKind::Direct, StepOutcome::Failed)
+                .target("192.0.2.1:4433")
+                .timing(Duration::ZERO, Duration::from_millis(30))
+                .detail("no response"),
+        );
+        let mut report = recorder.fail("connection timed out");
+        report.elapsed_ms = 5000;
+
+        assert_eq!(
+            report.to_string(),
+            "node: connection timed out after 5000ms [relay lookup: skipped (no relay URL); \
+             direct 192.0.2.1:4433: failed in 30ms (no response)]"
+        );
+
+        let report = DialRecorder::new("node").succeed();
+        assert!(report.to_string().starts_with("node: connected in "));
+    }
+
+    #[test]
+    fn test_report_serialization() {
+        let mut recorder = DialRecorder::new("node");
+        recorder.push(DialStep::new(DialStepKind::HolePunch, StepOutcome::Failed));
+        let report = recorder.fail("unreachable");
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["steps"][0]["kind"], "hole_punch");
+        assert_eq!(json["steps"][0]["outcome"], "failed");
+        assert!(json["steps"][0].get("target").is_none());
+        assert_eq!(json["error"], "unreachable");
+
+        let decoded: DialReport = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, report);
+    }
+}
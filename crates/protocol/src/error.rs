@@ -1,5 +1,7 @@
 //! Error types for the protocol crate.
 
+use alloc::string::String;
+
 use thiserror::Error;
 
 /// Protocol error type covering all possible failure modes.
@@ -59,6 +61,32 @@ pub enum ProtocolError {
         got: u16,
     },
 
+    // Decoding limit errors
+    /// A decoded string is longer than allowed.
+    #[error("string too long: {len} bytes exceeds maximum of {max} bytes")]
+    StringTooLong {
+        /// Actual string length.
+        len: usize,
+        /// Maximum allowed length.
+        max: usize,
+    },
+
+    /// A decoded array or map has more entries than allowed.
+    #[error("collection too large: {len} entries exceeds maximum of {max} entries")]
+    CollectionTooLarge {
+        /// Actual number of entries.
+        len: usize,
+        /// Maximum allowed number of entries.
+        max: usize,
+    },
+
+    /// Decoded data is nested deeper than allowed.
+    #[error("nesting too deep: exceeds maximum depth of {max}")]
+    NestingTooDeep {
+        /// Maximum allowed depth.
+        max: usize,
+    },
+
     // Connection errors
     /// Connection was closed unexpectedly.
     #[error("connection closed: {0}")]
@@ -97,10 +125,11 @@ pub enum ProtocolError {
 }
 
 /// Result type alias for protocol operations.
-pub type Result<T> = std::result::Result<T, ProtocolError>;
+pub type Result<T> = core::result::Result<T, ProtocolError>;
 
 // Conversions from underlying crate errors
 
+#[cfg(feature = "std")]
 impl From<serde_json::Error> for ProtocolError {
     fn from(err: serde_json::Error) -> Self {
         if err.is_data() || err.is_eof() || err.is_syntax() {
@@ -111,18 +140,21 @@ impl From<serde_json::Error> for ProtocolError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<rmp_serde::encode::Error> for ProtocolError {
     fn from(err: rmp_serde::encode::Error) -> Self {
         ProtocolError::Serialization(err.to_string())
     }
 }
 
+#[cfg(feature = "std")]
 impl From<rmp_serde::decode::Error> for ProtocolError {
     fn from(err: rmp_serde::decode::Error) -> Self {
         ProtocolError::Deserialization(err.to_string())
     }
 }
 
+#[cfg(feature = "std")]
 impl From<snow::Error> for ProtocolError {
     fn from(err: snow::Error) -> Self {
         let msg = err.to_string();
@@ -139,12 +171,14 @@ impl From<snow::Error> for ProtocolError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<ed25519_dalek::SignatureError> for ProtocolError {
     fn from(err: ed25519_dalek::SignatureError) -> Self {
         ProtocolError::InvalidSignature(err.to_string())
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for ProtocolError {
     fn from(err: std::io::Error) -> Self {
         use std::io::ErrorKind;
@@ -247,6 +281,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decoding_limit_errors_display() {
+        let err = ProtocolError::StringTooLong { len: 10, max: 4 };
+        assert_eq!(
+            err.to_string(),
+            "string too long: 10 bytes exceeds maximum of 4 bytes"
+        );
+        let err = ProtocolError::CollectionTooLarge { len: 10, max: 4 };
+        assert_eq!(
+            err.to_string(),
+            "collection too large: 10 entries exceeds maximum of 4 entries"
+        );
+        let err = ProtocolError::NestingTooDeep { max: 32 };
+        assert_eq!(
+            err.to_string(),
+            "nesting too deep: exceeds maximum depth of 32"
+        );
+    }
+
     #[test]
     fn test_connection_closed_error_display() {
         let err = ProtocolError::ConnectionClosed("peer disconnected".to_string());
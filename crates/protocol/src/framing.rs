@@ -14,6 +14,8 @@
 //! The compressed flag in the frame header indicates whether the payload
 //! is compressed.
 
+use alloc::{format, string::ToString, vec::Vec};
+
 use crate::error::{ProtocolError, Result};
 
 /// Magic bytes identifying a RemoShell frame.
@@ -254,9 +256,7 @@ impl FrameCodec {
 
         // Decompress if needed
         let payload = if flags.is_compressed() {
-            lz4_flex::decompress_size_prepended(payload_data).map_err(|e| {
-                ProtocolError::Deserialization(format!("failed to decompress payload: {}", e))
-            })?
+            decompress_payload(payload_data)?
         } else {
             payload_data.to_vec()
         };
@@ -312,6 +312,25 @@ impl FrameCodec {
     }
 }
 
+/// Decompresses a size-prepended LZ4 payload.
+///
+/// The uncompressed size is attacker-controlled, so it is checked against
+/// [`MAX_FRAME_SIZE`] before anything is allocated.
+fn decompress_payload(data: &[u8]) -> Result<Vec<u8>> {
+    let (size, compressed) = lz4_flex::block::uncompressed_size(data).map_err(|e| {
+        ProtocolError::Deserialization(format!("failed to decompress payload: {}", e))
+    })?;
+    if size > MAX_FRAME_SIZE {
+        return Err(ProtocolError::Deserialization(format!(
+            "failed to decompress payload: size {} exceeds maximum of {} bytes",
+            size, MAX_FRAME_SIZE
+        )));
+    }
+
+    lz4_flex::block::decompress(compressed, size)
+        .map_err(|e| ProtocolError::Deserialization(format!("failed to decompress payload: {}", e)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -494,6 +513,23 @@ mod tests {
         assert!(matches!(err, ProtocolError::FrameTooLarge { .. }));
     }
 
+    #[test]
+    fn test_decode_rejects_oversized_decompressed_size() {
+        let codec = FrameCodec::new();
+
+        // A tiny compressed payload claiming to expand to 4 GiB
+        let mut bad_frame = Vec::new();
+        bad_frame.extend_from_slice(&FRAME_MAGIC);
+        bad_frame.extend_from_slice(&6u32.to_be_bytes());
+        bad_frame.push(FrameFlags::COMPRESSED);
+        bad_frame.extend_from_slice(&u32::MAX.to_le_bytes());
+        bad_frame.push(0);
+
+        let err = codec.decode(&bad_frame).unwrap_err();
+        assert!(matches!(err, ProtocolError::Deserialization(_)));
+        assert!(err.to_string().contains("exceeds maximum"));
+    }
+
     #[test]
     fn test_decode_insufficient_header() {
         let codec = FrameCodec::new();
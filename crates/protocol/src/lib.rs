@@ -52,24 +52,59 @@
 //! ## Modules
 //!
 //! - [`crypto`]: Device identity, key management, and signatures
+//! - [`dial`]: Structured reports of connection attempts
 //! - [`messages`]: Protocol message definitions
 //! - [`framing`]: Frame codec with compression
 //! - [`noise`]: Noise XX handshake and encryption
+//! - [`limits`]: Limits for decoding untrusted MessagePack
+//! - `schema`: JSON Schema and Markdown reference for messages (`schema`
+//!   feature)
 //! - [`error`]: Error types
+//!
+//! ## Embedded Clients
+//!
+//! With default features off, the crate is `no_std` and only needs an
+//! allocator. It keeps [`framing`], [`messages`], [`limits`] and [`error`],
+//! so that microcontrollers can frame messages for a daemon. The `std`
+//! feature, on by default, adds MessagePack encoding, identities, the Noise
+//! handshake and dial reports.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod crypto;
+#[cfg(feature = "std")]
+pub mod dial;
 pub mod error;
 pub mod framing;
+pub mod limits;
 pub mod messages;
+#[cfg(feature = "std")]
 pub mod noise;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "std")]
+mod time;
+#[cfg(feature = "std")]
+mod wordlist;
 
-pub use crypto::{DeviceId, DeviceIdentity, PeerIdentity, Signature, DEVICE_ID_LENGTH};
+#[cfg(feature = "std")]
+pub use crypto::{
+    DeviceId, DeviceIdentity, PeerIdentity, Signature, DEVICE_ID_LENGTH, FINGERPRINT_WORD_COUNT,
+};
+#[cfg(feature = "std")]
+pub use dial::{DialRecorder, DialReport, DialStep, DialStepKind, StepOutcome};
 pub use error::{ProtocolError, Result};
 pub use framing::{
     Frame, FrameCodec, FrameFlags, COMPRESSION_THRESHOLD, FRAME_HEADER_SIZE, FRAME_MAGIC,
     MAX_FRAME_SIZE,
 };
+pub use limits::{validate_msgpack, DecodeLimits};
 pub use messages::{Envelope, Message, PROTOCOL_VERSION};
+#[cfg(feature = "std")]
 pub use noise::{
-    HandshakePhase, NoiseSession, Role, SecureHandshake, MAX_NOISE_MESSAGE_SIZE, NOISE_OVERHEAD,
+    HandshakePhase, NoiseSession, RekeyPolicy, Role, SecureHandshake, MAX_NOISE_MESSAGE_SIZE,
+    NOISE_OVERHEAD, REKEY_FEATURE,
 };
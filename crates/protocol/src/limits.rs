@@ -0,0 +1,308 @@
+//! Structural limits for decoding untrusted MessagePack.
+//!
+//! Envelopes arrive from the network, so before handing them to serde they
+//! are walked once by [`validate_msgpack`]. The walk rejects input that
+//! declares more data than it contains, strings or collections larger than
+//! the configured limits, and nesting deeper than the configured depth. This
+//! keeps decoding bounded in time and memory regardless of what a peer sends,
+//! and turns malformed input into typed [`ProtocolError`]s.
+
+use alloc::{format, vec, vec::Vec};
+
+use crate::error::{ProtocolError, Result};
+
+/// Default maximum nesting depth of arrays and maps.
+pub const MAX_DEPTH: usize = 32;
+
+/// Default maximum length of a string in bytes.
+pub const MAX_STRING_LEN: usize = 256 * 1024;
+
+/// Default maximum number of entries in an array or map.
+pub const MAX_COLLECTION_LEN: usize = 1024 * 1024;
+
+/// Limits enforced when decoding MessagePack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum nesting depth of arrays and maps.
+    pub max_depth: usize,
+    /// Maximum length of a string in bytes.
+    pub max_string_len: usize,
+    /// Maximum number of entries in an array or map.
+    pub max_collection_len: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_DEPTH,
+            max_string_len: MAX_STRING_LEN,
+            max_collection_len: MAX_COLLECTION_LEN,
+        }
+    }
+}
+
+/// Checks that `bytes` starts with one well-formed MessagePack value within
+/// `limits`.
+///
+/// Trailing bytes after the value are ignored, as they are by `rmp_serde`.
+pub fn validate_msgpack(bytes: &[u8], limits: &DecodeLimits) -> Result<()> {
+    let mut reader = Reader { bytes, pos: 0 };
+    // Number of values still to read at each open nesting level
+    let mut pending: Vec<usize> = vec![1];
+
+    while let Some(remaining) = pending.last_mut() {
+        if *remaining == 0 {
+            pending.pop();
+            continue;
+        }
+        *remaining -= 1;
+
+        if let Some(children) = reader.skip_value(limits)? {
+            if pending.len() > limits.max_depth {
+                return Err(ProtocolError::NestingTooDeep {
+                    max: limits.max_depth,
+                });
+            }
+            pending.push(children);
+        }
+    }
+
+    Ok(())
+}
+
+/// A cursor over MessagePack bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    /// Reads one value header and skips any payload.
+    ///
+    /// Returns the number of child values for arrays and maps.
+    fn skip_value(&mut self, limits: &DecodeLimits) -> Result<Option<usize>> {
+        let marker = self.read_uint(1)?;
+        let children = match marker {
+            // positive fixint, nil, bool, negative fixint
+            0x00..=0x7f | 0xc0 | 0xc2 | 0xc3 | 0xe0..=0xff => None,
+            // fixmap
+            0x80..=0x8f => Some(self.map_children(marker & 0x0f, limits)?),
+            // fixarray
+            0x90..=0x9f => Some(self.array_children(marker & 0x0f, limits)?),
+            // fixstr
+            0xa0..=0xbf => {
+                self.skip_string(marker & 0x1f, limits)?;
+                None
+            }
+            // bin 8/16/32
+            0xc4..=0xc6 => {
+                let len = self.read_uint(1 << (marker - 0xc4))?;
+                self.skip(len)?;
+                None
+            }
+            // ext 8/16/32: length, type, data
+            0xc7..=0xc9 => {
+                let len = self.read_uint(1 << (marker - 0xc7))?;
+                self.skip(len.saturating_add(1))?;
+                None
+            }
+            // float 32/64, uint 8/16/32/64, int 8/16/32/64
+            0xca..=0xd3 => {
+                let width = match marker {
+                    0xca => 4,
+                    0xcb => 8,
+                    0xcc..=0xcf => 1 << (marker - 0xcc),
+                    _ => 1 << (marker - 0xd0),
+                };
+                self.skip(width)?;
+                None
+            }
+            // fixext 1/2/4/8/16: type, data
+            0xd4..=0xd8 => {
+                self.skip(1 + (1 << (marker - 0xd4)))?;
+                None
+            }
+            // str 8/16/32
+            0xd9..=0xdb => {
+                let len = self.read_uint(1 << (marker - 0xd9))?;
+                self.skip_string(len, limits)?;
+                None
+            }
+            // array 16/32
+            0xdc | 0xdd => {
+                let len = self.read_uint(2 << (marker - 0xdc))?;
+                Some(self.array_children(len, limits)?)
+            }
+            // map 16/32
+            0xde | 0xdf => {
+                let len = self.read_uint(2 << (marker - 0xde))?;
+                Some(self.map_children(len, limits)?)
+            }
+            // 0xc1 is never used
+            _ => {
+                return Err(ProtocolError::Deserialization(format!(
+                    "invalid MessagePack marker 0x{:02x} at offset {}",
+                    marker,
+                    self.pos - 1
+                )))
+            }
+        };
+        Ok(children)
+    }
+
+    fn array_children(&self, len: usize, limits: &DecodeLimits) -> Result<usize> {
+        self.check_collection(len, len, limits)
+    }
+
+    fn map_children(&self, len: usize, limits: &DecodeLimits) -> Result<usize> {
+        self.check_collection(len, len.saturating_mul(2), limits)
+    }
+
+    /// Checks a collection's length, returning its number of child values.
+    fn check_collection(
+        &self,
+        len: usize,
+        children: usize,
+        limits: &DecodeLimits,
+    ) -> Result<usize> {
+        if len > limits.max_collection_len {
+            return Err(ProtocolError::CollectionTooLarge {
+                len,
+                max: limits.max_collection_len,
+            });
+        }
+        // Every value takes at least one byte
+        if children > self.remaining() {
+            return Err(self.truncated(children));
+        }
+        Ok(children)
+    }
+
+    fn skip_string(&mut self, len: usize, limits: &DecodeLimits) -> Result<()> {
+        if len > limits.max_string_len {
+            return Err(ProtocolError::StringTooLong {
+                len,
+                max: limits.max_string_len,
+            });
+        }
+        self.skip(len)
+    }
+
+    /// Reads a big-endian unsigned integer of `width` bytes.
+    fn read_uint(&mut self, width: usize) -> Result<usize> {
+        let bytes = self.take(width)?;
+        Ok(bytes
+            .iter()
+            .fold(0usize, |value, &byte| (value << 8) | usize::from(byte)))
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        if len > self.remaining() {
+            return Err(self.truncated(len));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn truncated(&self, needed: usize) -> ProtocolError {
+        ProtocolError::Deserialization(format!(
+            "truncated MessagePack: need {} bytes at offset {}, have {}",
+            needed,
+            self.pos,
+            self.remaining()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Envelope, Message, SessionCreate};
+
+    fn validate(bytes: &[u8]) -> Result<()> {
+        validate_msgpack(bytes, &DecodeLimits::default())
+    }
+
+    #[test]
+    fn test_accepts_envelopes() {
+        let envelope = Envelope::new(1, Message::SessionCreate(SessionCreate::default()));
+        let bytes = envelope.to_msgpack().unwrap();
+        validate(&bytes).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_truncated_input() {
+        assert!(validate(&[]).is_err());
+        // str 8 claiming 10 bytes with only 2 present
+        assert!(matches!(
+            validate(&[0xd9, 10, b'a', b'b']),
+            Err(ProtocolError::Deserialization(_))
+        ));
+        // array 32 claiming 2^32 - 1 entries
+        assert!(matches!(
+            validate(&[0xdd, 0xff, 0xff, 0xff, 0xff]),
+            Err(ProtocolError::CollectionTooLarge { .. })
+        ));
+        // array 16 claiming more entries than bytes
+        assert!(matches!(
+            validate(&[0xdc, 0x00, 0x10, 0x01]),
+            Err(ProtocolError::Deserialization(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_marker() {
+        assert!(matches!(
+            validate(&[0xc1]),
+            Err(ProtocolError::Deserialization(_))
+        ));
+    }
+
+    #[test]
+    fn test_enforces_limits() {
+        let limits = DecodeLimits {
+            max_depth: 2,
+            max_string_len: 3,
+            max_collection_len: 2,
+        };
+
+        // [[1]] is fine, [[[1]]] is too deep
+        validate_msgpack(&[0x91, 0x91, 0x01], &limits).unwrap();
+        assert!(matches!(
+            validate_msgpack(&[0x91, 0x91, 0x91, 0x01], &limits),
+            Err(ProtocolError::NestingTooDeep { max: 2 })
+        ));
+
+        assert!(matches!(
+            validate_msgpack(&[0xa4, b'a', b'b', b'c', b'd'], &limits),
+            Err(ProtocolError::StringTooLong { len: 4, max: 3 })
+        ));
+        assert!(matches!(
+            validate_msgpack(&[0x93, 0x01, 0x02, 0x03], &limits),
+            Err(ProtocolError::CollectionTooLarge { len: 3, max: 2 })
+        ));
+        assert!(matches!(
+            validate_msgpack(&[0x83, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06], &limits),
+            Err(ProtocolError::CollectionTooLarge { len: 3, max: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_deep_nesting_does_not_overflow() {
+        // A million nested single-element arrays
+        let bytes = vec![0x91; 1_000_000];
+        assert!(matches!(
+            validate(&bytes),
+            Err(ProtocolError::NestingTooDeep { .. })
+        ));
+    }
+}
@@ -3,8 +3,18 @@
 //! This module defines all RPC message types used for communication between
 //! the daemon and clients. All messages are serialized using MessagePack.
 
+use alloc::string::{String, ToString};
+use alloc::{vec, vec::Vec};
+
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "std")]
+use crate::error::ProtocolError;
+#[cfg(feature = "std")]
+use crate::framing::MAX_FRAME_SIZE;
+#[cfg(feature = "std")]
+use crate::limits::{validate_msgpack, DecodeLimits};
+
 /// Protocol version for client-daemon communication.
 ///
 /// This version is SEPARATE from the release version and should only be
@@ -18,6 +28,7 @@ use serde::{Deserialize, Serialize};
 ///
 /// Do NOT increment when:
 /// - Adding optional message fields with defaults
+/// - Adding message types, which older peers decode as [`Message::Unknown`]
 /// - Fixing bugs that don't change the protocol
 /// - Making release version bumps
 ///
@@ -29,6 +40,7 @@ pub const PROTOCOL_VERSION: u8 = 1;
 /// The envelope provides versioning and sequence numbers for message ordering
 /// and compatibility checking.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Envelope {
     /// Protocol version for compatibility checking.
     pub version: u8,
@@ -51,6 +63,7 @@ impl Envelope {
 
 /// Top-level message enum containing all message types.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "type", content = "data")]
 pub enum Message {
     // Session messages
@@ -66,10 +79,40 @@ pub enum Message {
     SessionKill(SessionKill),
     /// Terminal resize notification.
     SessionResize(SessionResize),
+    /// Signal delivery to a session's foreground process group.
+    SessionSignal(SessionSignal),
+    /// Environment variables for commands a session starts from now on.
+    UpdateSessionEnv(UpdateSessionEnv),
     /// Session data (stdin/stdout/stderr).
     SessionData(SessionData),
+    /// Some of a session's output was skipped for a client that fell behind.
+    SessionOutputTruncated(SessionOutputTruncated),
     /// Session closed notification.
     SessionClosed(SessionClosed),
+    /// Request for a session's shell metadata.
+    SessionMetadataRequest(SessionMetadataRequest),
+    /// Shell metadata (working directory, last exit status) for a session.
+    SessionMetadata(SessionMetadata),
+    /// Request to search a session's recent output.
+    SessionSearchRequest(SessionSearchRequest),
+    /// Matches found in a session's recent output.
+    SessionSearchResult(SessionSearchResult),
+    /// Request for the daemon's session templates.
+    ListTemplates(ListTemplates),
+    /// The daemon's session templates.
+    TemplateList(TemplateList),
+    /// A process in the session connected to the forwarded SSH agent.
+    AgentOpen(AgentOpen),
+    /// SSH agent protocol data on a forwarded agent channel.
+    AgentData(AgentData),
+    /// A forwarded agent channel was closed.
+    AgentClose(AgentClose),
+    /// A GUI application in the session connected to the forwarded display.
+    DisplayOpen(DisplayOpen),
+    /// X11 or Wayland protocol data on a forwarded display channel.
+    DisplayData(DisplayData),
+    /// A forwarded display channel was closed.
+    DisplayClose(DisplayClose),
 
     // File messages
     /// Request to list files in a directory.
@@ -80,12 +123,44 @@ pub enum Message {
     FileDownloadRequest(FileDownloadRequest),
     /// Chunk of downloaded file data.
     FileDownloadChunk(FileDownloadChunk),
+    /// Acknowledges chunks of a streamed download.
+    FileDownloadAck(FileDownloadAck),
     /// Start a file upload.
     FileUploadStart(FileUploadStart),
     /// Chunk of uploaded file data.
     FileUploadChunk(FileUploadChunk),
     /// Complete a file upload.
     FileUploadComplete(FileUploadComplete),
+    /// Integrity report for a completed upload.
+    FileTransferReport(FileTransferReport),
+    /// Ask for a grant to receive a file from another daemon.
+    FileCopyGrantRequest(FileCopyGrantRequest),
+    /// Grant to copy a file directly to this daemon.
+    FileCopyGrant(FileCopyGrant),
+    /// Copy a file directly to another daemon.
+    FileCopyRequest(FileCopyRequest),
+    /// Read a small text file for editing.
+    FileReadText(FileReadText),
+    /// Contents of a text file, with its hash.
+    FileText(FileText),
+    /// Replace a text file if it has not changed since it was read.
+    FileWriteText(FileWriteText),
+    /// Confirmation of a text file write.
+    FileTextWritten(FileTextWritten),
+    /// Ask for the paths the device may browse.
+    FileRootsRequest(FileRootsRequest),
+    /// Paths the device may browse, with its access and free space.
+    FileRoots(FileRoots),
+    /// Ask for a preview of a file.
+    FileThumbnailRequest(FileThumbnailRequest),
+    /// A file's MIME type and, for images, a small preview.
+    FileThumbnail(FileThumbnail),
+    /// Stream the end of a file and, optionally, data appended to it.
+    FileTailRequest(FileTailRequest),
+    /// Data from the end of a tailed file.
+    FileTailData(FileTailData),
+    /// Stop following a tailed file.
+    FileTailStop(FileTailStop),
 
     // Device messages
     /// Device information announcement.
@@ -96,6 +171,38 @@ pub enum Message {
     DeviceApproved(DeviceApproved),
     /// Device connection rejected.
     DeviceRejected(DeviceRejected),
+    /// Challenge for a security key assertion required by daemon policy.
+    SecurityKeyChallenge(SecurityKeyChallenge),
+    /// Security key assertion answering a challenge.
+    SecurityKeyAssertion(SecurityKeyAssertion),
+    /// Security key assertion accepted.
+    SecurityKeyVerified(SecurityKeyVerified),
+
+    // Host messages
+    /// Request for the daemon host's system information.
+    SystemInfoRequest(SystemInfoRequest),
+    /// System information about the daemon host.
+    SystemInfo(SystemInfo),
+    /// Request for the processes running on the daemon host.
+    ProcessListRequest(ProcessListRequest),
+    /// Processes running on the daemon host.
+    ProcessList(ProcessList),
+    /// Signal a process on the daemon host.
+    ProcessKill(ProcessKill),
+    /// Request to reboot, shut down or suspend the daemon host.
+    PowerActionRequest(PowerActionRequest),
+    /// Token to confirm a requested power action with.
+    PowerActionChallenge(PowerActionChallenge),
+    /// Confirmation of a requested power action.
+    PowerActionConfirm(PowerActionConfirm),
+    /// A confirmed power action is about to run.
+    PowerActionStarted(PowerActionStarted),
+    /// Request for the daemon's effective configuration.
+    ConfigRequest(ConfigRequest),
+    /// The daemon's effective configuration, with secrets redacted.
+    ConfigView(ConfigView),
+    /// Change to the settings that can be changed remotely.
+    ConfigPatch(ConfigPatch),
 
     // Control messages
     /// Ping for keepalive.
@@ -106,6 +213,131 @@ pub enum Message {
     Error(ErrorMessage),
     /// Capabilities announcement.
     Capabilities(Capabilities),
+    /// Notice pushed by the daemon for the user.
+    Notification(Notification),
+    /// The daemon is stopping and will close the connection.
+    ServerShutdownPending(ServerShutdownPending),
+
+    /// A message type this build does not know, sent by a newer peer.
+    ///
+    /// Decoding produces it instead of failing, so peers running different
+    /// releases keep talking during a rolling upgrade. It is not part of the
+    /// wire protocol: encoding writes the original message back out.
+    #[serde(skip)]
+    Unknown {
+        /// The `type` tag of the message.
+        tag: String,
+        /// The MessagePack encoding of the message's `data`, empty if it had
+        /// none.
+        payload: Vec<u8>,
+    },
+}
+
+impl Message {
+    /// Returns the variant name, as it appears in the `type` tag on the wire.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SessionCreate(_) => "SessionCreate",
+            Self::SessionCreated(_) => "SessionCreated",
+            Self::SessionAttach(_) => "SessionAttach",
+            Self::SessionDetach(_) => "SessionDetach",
+            Self::SessionKill(_) => "SessionKill",
+            Self::SessionResize(_) => "SessionResize",
+            Self::SessionSignal(_) => "SessionSignal",
+            Self::UpdateSessionEnv(_) => "UpdateSessionEnv",
+            Self::SessionData(_) => "SessionData",
+            Self::SessionOutputTruncated(_) => "SessionOutputTruncated",
+            Self::SessionClosed(_) => "SessionClosed",
+            Self::SessionMetadataRequest(_) => "SessionMetadataRequest",
+            Self::SessionMetadata(_) => "SessionMetadata",
+            Self::SessionSearchRequest(_) => "SessionSearchRequest",
+            Self::SessionSearchResult(_) => "SessionSearchResult",
+            Self::ListTemplates(_) => "ListTemplates",
+            Self::TemplateList(_) => "TemplateList",
+            Self::AgentOpen(_) => "AgentOpen",
+            Self::AgentData(_) => "AgentData",
+            Self::AgentClose(_) => "AgentClose",
+            Self::DisplayOpen(_) => "DisplayOpen",
+            Self::DisplayData(_) => "DisplayData",
+            Self::DisplayClose(_) => "DisplayClose",
+            Self::FileListRequest(_) => "FileListRequest",
+            Self::FileListResponse(_) => "FileListResponse",
+            Self::FileDownloadRequest(_) => "FileDownloadRequest",
+            Self::FileDownloadChunk(_) => "FileDownloadChunk",
+            Self::FileDownloadAck(_) => "FileDownloadAck",
+            Self::FileUploadStart(_) => "FileUploadStart",
+            Self::FileUploadChunk(_) => "FileUploadChunk",
+            Self::FileUploadComplete(_) => "FileUploadComplete",
+            Self::FileTransferReport(_) => "FileTransferReport",
+            Self::FileCopyGrantRequest(_) => "FileCopyGrantRequest",
+            Self::FileCopyGrant(_) => "FileCopyGrant",
+            Self::FileCopyRequest(_) => "FileCopyRequest",
+            Self::FileReadText(_) => "FileReadText",
+            Self::FileText(_) => "FileText",
+            Self::FileWriteText(_) => "FileWriteText",
+            Self::FileTextWritten(_) => "FileTextWritten",
+            Self::FileRootsRequest(_) => "FileRootsRequest",
+            Self::FileRoots(_) => "FileRoots",
+            Self::FileThumbnailRequest(_) => "FileThumbnailRequest",
+            Self::FileThumbnail(_) => "FileThumbnail",
+            Self::FileTailRequest(_) => "FileTailRequest",
+            Self::FileTailData(_) => "FileTailData",
+            Self::FileTailStop(_) => "FileTailStop",
+            Self::DeviceInfo(_) => "DeviceInfo",
+            Self::DeviceApprovalRequest(_) => "DeviceApprovalRequest",
+            Self::DeviceApproved(_) => "DeviceApproved",
+            Self::DeviceRejected(_) => "DeviceRejected",
+            Self::SecurityKeyChallenge(_) => "SecurityKeyChallenge",
+            Self::SecurityKeyAssertion(_) => "SecurityKeyAssertion",
+            Self::SecurityKeyVerified(_) => "SecurityKeyVerified",
+            Self::SystemInfoRequest(_) => "SystemInfoRequest",
+            Self::SystemInfo(_) => "SystemInfo",
+            Self::ProcessListRequest(_) => "ProcessListRequest",
+            Self::ProcessList(_) => "ProcessList",
+            Self::ProcessKill(_) => "ProcessKill",
+            Self::PowerActionRequest(_) => "PowerActionRequest",
+            Self::PowerActionChallenge(_) => "PowerActionChallenge",
+            Self::PowerActionConfirm(_) => "PowerActionConfirm",
+            Self::PowerActionStarted(_) => "PowerActionStarted",
+            Self::ConfigRequest(_) => "ConfigRequest",
+            Self::ConfigView(_) => "ConfigView",
+            Self::ConfigPatch(_) => "ConfigPatch",
+            Self::Ping(_) => "Ping",
+            Self::Pong(_) => "Pong",
+            Self::Error(_) => "Error",
+            Self::Capabilities(_) => "Capabilities",
+            Self::Notification(_) => "Notification",
+            Self::ServerShutdownPending(_) => "ServerShutdownPending",
+            Self::Unknown { .. } => "Unknown",
+        }
+    }
+
+    /// Returns the session a message refers to, if any.
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            Self::SessionCreated(m) => Some(&m.session_id),
+            Self::SessionAttach(m) => Some(&m.session_id),
+            Self::SessionDetach(m) => Some(&m.session_id),
+            Self::SessionKill(m) => Some(&m.session_id),
+            Self::SessionResize(m) => Some(&m.session_id),
+            Self::SessionSignal(m) => Some(&m.session_id),
+            Self::UpdateSessionEnv(m) => Some(&m.session_id),
+            Self::SessionData(m) => Some(&m.session_id),
+            Self::SessionOutputTruncated(m) => Some(&m.session_id),
+            Self::SessionClosed(m) => Some(&m.session_id),
+            Self::SessionMetadataRequest(m) => Some(&m.session_id),
+            Self::SessionMetadata(m) => Some(&m.session_id),
+            Self::SessionSearchRequest(m) => Some(&m.session_id),
+            Self::SessionSearchResult(m) => Some(&m.session_id),
+            Self::AgentOpen(m) => Some(&m.session_id),
+            Self::AgentData(m) => Some(&m.session_id),
+            Self::AgentClose(m) => Some(&m.session_id),
+            Self::DisplayOpen(m) => Some(&m.session_id),
+            Self::DisplayData(m) => Some(&m.session_id),
+            Self::DisplayClose(m) => Some(&m.session_id),
+            _ => None,
+        }
+    }
 }
 
 // ============================================================================
@@ -114,6 +346,7 @@ pub enum Message {
 
 /// Request to create a new shell session.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SessionCreate {
     /// Requested terminal columns.
     pub cols: u16,
@@ -123,8 +356,33 @@ pub struct SessionCreate {
     pub shell: Option<String>,
     /// Environment variables to set.
     pub env: Vec<(String, String)>,
-    /// Working directory for the session.
+    /// Working directory for the session; `~` is the user's home directory.
+    /// Sessions are not created in directories that do not exist.
     pub cwd: Option<String>,
+    /// Forward the client's SSH agent into the session.
+    #[serde(default)]
+    pub forward_agent: bool,
+    /// Name of a daemon session template to start from.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Client's IANA timezone (e.g. `Europe/Paris`), exported as `TZ`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Client's locale (e.g. `fr-FR` or `fr_FR.UTF-8`), exported as `LANG`.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Client terminal type (e.g. `xterm-256color`), exported as `TERM`.
+    #[serde(default)]
+    pub term: Option<String>,
+    /// Forward GUI applications of the session to the client's display.
+    #[serde(default)]
+    pub forward_display: Option<DisplayProtocol>,
+    /// Attach the session to a tmux or screen session on the host.
+    #[serde(default)]
+    pub host_session: Option<HostSession>,
+    /// Run the shell as a login shell, which reads the user's profile.
+    #[serde(default)]
+    pub login_shell: bool,
 }
 
 impl Default for SessionCreate {
@@ -135,12 +393,56 @@ impl Default for SessionCreate {
             shell: None,
             env: Vec::new(),
             cwd: None,
+            forward_agent: false,
+            template: None,
+            timezone: None,
+            locale: None,
+            term: None,
+            forward_display: None,
+            host_session: None,
+            login_shell: false,
         }
     }
 }
 
+/// Display protocol forwarded with `SessionCreate.forward_display`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DisplayProtocol {
+    /// X11: the daemon serves an X display and sets `DISPLAY`.
+    X11,
+    /// Wayland through waypipe: the daemon serves a socket for
+    /// `waypipe server` and sets `REMOSHELL_WAYPIPE_SOCKET`.
+    Wayland,
+}
+
+/// Terminal multiplexer running on the daemon's host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum HostMultiplexer {
+    /// tmux.
+    Tmux,
+    /// GNU screen.
+    Screen,
+}
+
+/// Multiplexer session on the host that `SessionCreate.host_session`
+/// attaches to, so work started at the host's keyboard can be continued
+/// remotely.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct HostSession {
+    /// Multiplexer the session belongs to.
+    pub multiplexer: HostMultiplexer,
+    /// Session name, as in `tmux attach -t <name>` or `screen -x <name>`.
+    pub name: String,
+    /// Create the session if it does not exist (tmux only).
+    pub create: bool,
+}
+
 /// Response confirming session creation.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SessionCreated {
     /// Unique session identifier.
     pub session_id: String,
@@ -150,6 +452,7 @@ pub struct SessionCreated {
 
 /// Request to attach to an existing session.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SessionAttach {
     /// Session ID to attach to.
     pub session_id: String,
@@ -157,6 +460,7 @@ pub struct SessionAttach {
 
 /// Request to detach from a session.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SessionDetach {
     /// Session ID to detach from.
     pub session_id: String,
@@ -164,6 +468,7 @@ pub struct SessionDetach {
 
 /// Request to kill a session.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SessionKill {
     /// Session ID to kill.
     pub session_id: String,
@@ -173,6 +478,7 @@ pub struct SessionKill {
 
 /// Terminal resize notification.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SessionResize {
     /// Session ID to resize.
     pub session_id: String,
@@ -182,8 +488,77 @@ pub struct SessionResize {
     pub rows: u16,
 }
 
+/// Request to deliver a signal to a session's foreground process group.
+///
+/// Clients should prefer this over writing raw control bytes (e.g. `^C`)
+/// so that signals behave consistently regardless of terminal mode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SessionSignal {
+    /// Session ID to signal.
+    pub session_id: String,
+    /// The signal to deliver.
+    pub signal: ProcessSignal,
+}
+
+/// Signals that clients may deliver to a session.
+///
+/// Signals are named rather than numbered because signal numbers differ
+/// between platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ProcessSignal {
+    /// Keyboard interrupt (SIGINT, `^C`).
+    Interrupt,
+    /// Keyboard quit (SIGQUIT, `^\`).
+    Quit,
+    /// Keyboard stop (SIGTSTP, `^Z`).
+    Suspend,
+    /// Continue a stopped process (SIGCONT).
+    Continue,
+    /// Hangup (SIGHUP).
+    Hangup,
+    /// Termination request (SIGTERM).
+    Terminate,
+    /// Forced kill (SIGKILL).
+    Kill,
+}
+
+impl ProcessSignal {
+    /// Returns the conventional signal name (e.g. "SIGINT").
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Interrupt => "SIGINT",
+            Self::Quit => "SIGQUIT",
+            Self::Suspend => "SIGTSTP",
+            Self::Continue => "SIGCONT",
+            Self::Hangup => "SIGHUP",
+            Self::Terminate => "SIGTERM",
+            Self::Kill => "SIGKILL",
+        }
+    }
+}
+
+/// Request to export environment variables into a session.
+///
+/// A running shell's environment cannot be changed from outside, so the
+/// variables reach commands through a file the shell sources before each
+/// prompt (see `remoshell-daemon shell-hook`). Updates accumulate: later
+/// ones replace earlier values of the same variables.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct UpdateSessionEnv {
+    /// Session ID to update.
+    pub session_id: String,
+    /// Variables to set, such as `DISPLAY` or `TZ`.
+    pub set: Vec<(String, String)>,
+    /// Variables to remove.
+    pub unset: Vec<String>,
+}
+
 /// Session data (input or output).
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SessionData {
     /// Session ID this data belongs to.
     pub session_id: String,
@@ -191,11 +566,13 @@ pub struct SessionData {
     pub stream: DataStream,
     /// The actual data bytes.
     #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
     pub data: Vec<u8>,
 }
 
 /// Data stream type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum DataStream {
     /// Standard input (client to daemon).
     Stdin,
@@ -205,8 +582,23 @@ pub enum DataStream {
     Stderr,
 }
 
+/// Notification that a client fell behind a session's output and some of
+/// it was skipped.
+///
+/// Sent in place of the skipped output, so that the client can tell its
+/// screen may be out of date.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SessionOutputTruncated {
+    /// Session whose output was skipped.
+    pub session_id: String,
+    /// Number of output chunks skipped.
+    pub dropped_chunks: u64,
+}
+
 /// Session closed notification.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SessionClosed {
     /// Session ID that was closed.
     pub session_id: String,
@@ -216,6 +608,226 @@ pub struct SessionClosed {
     pub signal: Option<i32>,
     /// Human-readable reason for closure.
     pub reason: Option<String>,
+    /// Why the session ended. `None` from daemons that predate it.
+    #[serde(default)]
+    pub close_reason: Option<SessionCloseReason>,
+}
+
+/// Why a session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum SessionCloseReason {
+    /// The shell exited, or was killed by a signal not sent by the daemon.
+    Exited,
+    /// A client killed the session with `SessionKill`.
+    Killed,
+    /// The daemon shut down.
+    DaemonShutdown,
+    /// The session saw no input or output for `session.idle_timeout`.
+    IdleTimeout,
+    /// The device that created the session was revoked.
+    OwnerRevoked,
+}
+
+impl SessionCloseReason {
+    /// Returns a short description for display.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Exited => "Session exited",
+            Self::Killed => "Session killed by request",
+            Self::DaemonShutdown => "Daemon shut down",
+            Self::IdleTimeout => "Session was idle for too long",
+            Self::OwnerRevoked => "Owning device was revoked",
+        }
+    }
+}
+
+/// Request for a session's shell metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SessionMetadataRequest {
+    /// Session ID to query.
+    pub session_id: String,
+}
+
+/// Shell metadata for a session.
+///
+/// Populated from shell integration sequences (OSC 7 / OSC 133) when the
+/// session's shell emits them; fields are `None` otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SessionMetadata {
+    /// Session ID this metadata belongs to.
+    pub session_id: String,
+    /// Current working directory of the shell.
+    pub cwd: Option<String>,
+    /// Exit status of the most recently finished command.
+    pub last_exit_code: Option<i32>,
+    /// Command line of the most recently started command.
+    pub last_command: Option<String>,
+    /// Whether a command is currently executing.
+    pub command_running: bool,
+}
+
+/// Request to search the output a session has kept on the daemon.
+///
+/// Lets clients find text in the scrollback without holding all of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SessionSearchRequest {
+    /// Session ID to search.
+    pub session_id: String,
+    /// Text or regular expression to find.
+    pub pattern: String,
+    /// Whether `pattern` is a regular expression.
+    pub regex: bool,
+    /// Whether matching is case sensitive.
+    pub case_sensitive: bool,
+    /// Maximum number of matches to return, or 0 for the daemon's default.
+    /// The daemon caps this.
+    pub max_results: u32,
+}
+
+/// Matches found in a session's output, newest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SessionSearchResult {
+    /// Session ID that was searched.
+    pub session_id: String,
+    /// The matches, newest first.
+    pub matches: Vec<SearchMatch>,
+    /// Whether more matches were found than returned.
+    pub truncated: bool,
+    /// Number of the oldest line the daemon still holds.
+    pub first_line: u64,
+    /// Number of the line currently being written.
+    pub last_line: u64,
+}
+
+/// A match in a session's output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SearchMatch {
+    /// Line number, counted from the start of the session.
+    pub line: u64,
+    /// Offset of the start of the match in `text`, in characters.
+    pub start: u32,
+    /// Offset just past the end of the match in `text`, in characters.
+    pub end: u32,
+    /// The line, with escape sequences removed.
+    pub text: String,
+}
+
+/// Request for the session templates configured on the daemon.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ListTemplates {}
+
+/// Session templates configured on the daemon, sorted by name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TemplateList {
+    /// Available templates.
+    pub templates: Vec<TemplateInfo>,
+}
+
+/// A session template, as shown in client launcher menus.
+///
+/// Environment variables are not included, since they may hold secrets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TemplateInfo {
+    /// Name to pass in `SessionCreate.template`.
+    pub name: String,
+    /// Short description of the template.
+    pub description: Option<String>,
+    /// Shell the session runs.
+    pub shell: Option<String>,
+    /// Working directory of the session.
+    pub cwd: Option<String>,
+    /// Command typed into the shell once the session starts.
+    pub command: Option<String>,
+    /// Terminal columns, replacing the client's size.
+    pub cols: Option<u16>,
+    /// Terminal rows, replacing the client's size.
+    pub rows: Option<u16>,
+}
+
+/// Notification that a process in a session connected to the forwarded SSH
+/// agent socket.
+///
+/// The client opens a connection to its local agent for the channel, or
+/// answers with `AgentClose` if it declines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AgentOpen {
+    /// Session whose agent socket was connected to.
+    pub session_id: String,
+    /// Channel identifier, unique within the session.
+    pub channel_id: u32,
+}
+
+/// SSH agent protocol data on a forwarded agent channel, in either direction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AgentData {
+    /// Session the channel belongs to.
+    pub session_id: String,
+    /// Channel identifier.
+    pub channel_id: u32,
+    /// Raw agent protocol bytes.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub data: Vec<u8>,
+}
+
+/// Closes a forwarded agent channel, in either direction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AgentClose {
+    /// Session the channel belongs to.
+    pub session_id: String,
+    /// Channel identifier.
+    pub channel_id: u32,
+}
+
+/// Notification that a GUI application in a session connected to the
+/// forwarded display.
+///
+/// The client opens a connection to its local X server or `waypipe client`
+/// for the channel, or answers with `DisplayClose` if it declines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DisplayOpen {
+    /// Session whose display was connected to.
+    pub session_id: String,
+    /// Channel identifier, unique within the session.
+    pub channel_id: u32,
+}
+
+/// X11 or waypipe protocol data on a forwarded display channel, in either
+/// direction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DisplayData {
+    /// Session the channel belongs to.
+    pub session_id: String,
+    /// Channel identifier.
+    pub channel_id: u32,
+    /// Raw display protocol bytes.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub data: Vec<u8>,
+}
+
+/// Closes a forwarded display channel, in either direction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DisplayClose {
+    /// Session the channel belongs to.
+    pub session_id: String,
+    /// Channel identifier.
+    pub channel_id: u32,
 }
 
 // ============================================================================
@@ -224,15 +836,33 @@ pub struct SessionClosed {
 
 /// Request to list files in a directory.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileListRequest {
     /// Path to list.
     pub path: String,
     /// Include hidden files.
     pub include_hidden: bool,
+    /// How much to report about each entry.
+    #[serde(default)]
+    pub detail: FileListDetail,
+}
+
+/// Level of detail of the entries in a [`FileListResponse`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum FileListDetail {
+    /// Name, type, size, mode and modification time only.
+    #[default]
+    Basic,
+    /// Also ownership and symlink targets, in [`FileEntry::details`].
+    Extended,
+    /// Everything in `Extended`, plus extended attributes.
+    Full,
 }
 
 /// Response with directory listing.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileListResponse {
     /// Path that was listed.
     pub path: String,
@@ -242,6 +872,7 @@ pub struct FileListResponse {
 
 /// A single file or directory entry.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileEntry {
     /// Entry name (not full path).
     pub name: String,
@@ -253,10 +884,45 @@ pub struct FileEntry {
     pub mode: u32,
     /// Last modified timestamp (Unix epoch seconds).
     pub modified: u64,
+    /// Ownership, link target and extended attributes, present when the
+    /// listing asked for more than [`FileListDetail::Basic`].
+    #[serde(default)]
+    pub details: Option<FileEntryDetails>,
+}
+
+/// Extra information about a [`FileEntry`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileEntryDetails {
+    /// Numeric owner ID.
+    pub uid: u32,
+    /// Numeric group ID.
+    pub gid: u32,
+    /// Owner name, if the ID maps to a user.
+    pub owner: Option<String>,
+    /// Group name, if the ID maps to a group.
+    pub group: Option<String>,
+    /// Where the entry points to, if it is a symlink.
+    pub symlink_target: Option<String>,
+    /// Extended attributes; empty unless [`FileListDetail::Full`] was asked.
+    pub xattrs: Vec<FileXattr>,
+}
+
+/// An extended attribute of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileXattr {
+    /// Attribute name, including its namespace (e.g. `user.comment`).
+    pub name: String,
+    /// Attribute value.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub value: Vec<u8>,
 }
 
 /// Type of file entry.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum FileEntryType {
     /// Regular file.
     File,
@@ -270,6 +936,7 @@ pub enum FileEntryType {
 
 /// Request to download a file.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileDownloadRequest {
     /// Path to download.
     pub path: String,
@@ -277,10 +944,19 @@ pub struct FileDownloadRequest {
     pub offset: u64,
     /// Maximum chunk size.
     pub chunk_size: u32,
+    /// Ask for a BLAKE3 hash of each chunk, and of the whole file with the
+    /// last chunk.
+    #[serde(default)]
+    pub verify: bool,
+    /// Number of chunks the daemon may send ahead of the client's
+    /// [`FileDownloadAck`]s. Zero sends one chunk per request.
+    #[serde(default)]
+    pub window: u32,
 }
 
 /// Chunk of downloaded file data.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileDownloadChunk {
     /// Path being downloaded.
     pub path: String,
@@ -290,13 +966,34 @@ pub struct FileDownloadChunk {
     pub total_size: u64,
     /// The chunk data.
     #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
     pub data: Vec<u8>,
     /// Whether this is the last chunk.
     pub is_last: bool,
+    /// BLAKE3 hash of `data`, if the download was requested with `verify`.
+    #[serde(default, with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<Vec<u8>>"))]
+    pub hash: Option<Vec<u8>>,
+    /// BLAKE3 hash of the whole file, sent with the last chunk of a
+    /// download requested with `verify`.
+    #[serde(default, with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<Vec<u8>>"))]
+    pub file_hash: Option<Vec<u8>>,
+}
+
+/// Acknowledges chunks of a streamed download.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileDownloadAck {
+    /// Path being downloaded.
+    pub path: String,
+    /// Offset up to which the file has been received.
+    pub offset: u64,
 }
 
 /// Start a file upload.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileUploadStart {
     /// Destination path.
     pub path: String,
@@ -310,6 +1007,7 @@ pub struct FileUploadStart {
 
 /// Chunk of uploaded file data.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileUploadChunk {
     /// Destination path.
     pub path: String,
@@ -317,17 +1015,267 @@ pub struct FileUploadChunk {
     pub offset: u64,
     /// The chunk data.
     #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
     pub data: Vec<u8>,
+    /// BLAKE3 hash of `data`. When present the daemon checks it and
+    /// rejects the chunk on mismatch, so it can be sent again.
+    #[serde(default, with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<Vec<u8>>"))]
+    pub hash: Option<Vec<u8>>,
 }
 
 /// Complete a file upload.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FileUploadComplete {
     /// Destination path.
     pub path: String,
     /// SHA-256 hash of the complete file for verification.
     #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
     pub checksum: Vec<u8>,
+    /// Ask for a [`FileTransferReport`] in reply.
+    #[serde(default)]
+    pub report: bool,
+}
+
+/// Integrity report for a completed upload, sent in reply to a
+/// [`FileUploadComplete`] with `report` set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileTransferReport {
+    /// Destination path.
+    pub path: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// BLAKE3 hash of the file as written.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub hash: Vec<u8>,
+    /// Number of chunks received.
+    pub chunks: u32,
+    /// Number of chunks that carried a hash and matched it.
+    pub verified_chunks: u32,
+    /// Time from the start of the upload to its completion, in
+    /// milliseconds.
+    pub duration_ms: u64,
+}
+
+/// Asks the daemon that will receive a copied file for a [`FileCopyGrant`].
+///
+/// A client connected to two daemons copies a file between them by asking
+/// the destination for a grant and handing it to the source in a
+/// [`FileCopyRequest`]. The file then travels directly between the daemons.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileCopyGrantRequest {
+    /// Destination path.
+    pub path: String,
+    /// Whether to overwrite an existing file.
+    pub overwrite: bool,
+}
+
+/// Short-lived, single-use grant to copy a file to the daemon that issued it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileCopyGrant {
+    /// Destination path the grant is for.
+    pub path: String,
+    /// Secret the source daemon presents when it connects.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub token: Vec<u8>,
+    /// Addresses (`host:port`) the issuing daemon accepts copies on.
+    pub addresses: Vec<String>,
+    /// Ed25519 public key of the issuing daemon, which it proves ownership
+    /// of when the source connects.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub public_key: Vec<u8>,
+    /// Seconds until the grant expires.
+    pub expires_in_secs: u32,
+}
+
+/// Asks a daemon to copy one of its files directly to the daemon that
+/// issued `grant`.
+///
+/// The daemon answers when the copy is done, with the destination's
+/// [`FileTransferReport`] or an [`ErrorMessage`] whose context is
+/// `source_path`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileCopyRequest {
+    /// Path of the file to copy.
+    pub source_path: String,
+    /// Grant from the destination daemon.
+    pub grant: FileCopyGrant,
+}
+
+/// Reads a text file in one message, for quick edits of small files such
+/// as configuration files.
+///
+/// The daemon answers with a [`FileText`], or an error if the file is
+/// larger than its text size limit or not valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileReadText {
+    /// Path of the file.
+    pub path: String,
+}
+
+/// Contents of a text file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileText {
+    /// Path of the file.
+    pub path: String,
+    /// Contents of the file.
+    pub content: String,
+    /// BLAKE3 hash of the contents, to pass back in [`FileWriteText`].
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub hash: Vec<u8>,
+}
+
+/// Replaces the contents of a text file, unless someone else changed it
+/// first.
+///
+/// The write only succeeds if the file's current BLAKE3 hash is
+/// `expected_hash`, or, without one, if the file does not exist. Otherwise
+/// the daemon answers with a `Conflict` error and leaves the file alone, so
+/// the client can read it again and merge.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileWriteText {
+    /// Path of the file.
+    pub path: String,
+    /// New contents of the file.
+    pub content: String,
+    /// Hash from the [`FileText`] the edit started from, or `None` to
+    /// create a new file.
+    #[serde(default, with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<Vec<u8>>"))]
+    pub expected_hash: Option<Vec<u8>>,
+}
+
+/// Confirms a [`FileWriteText`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileTextWritten {
+    /// Path of the file.
+    pub path: String,
+    /// BLAKE3 hash of the new contents, for the next write.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub hash: Vec<u8>,
+}
+
+/// Asks which paths the device may browse, so a file browser can start
+/// there instead of guessing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileRootsRequest {}
+
+/// Paths the device may browse.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileRoots {
+    /// Roots in path order. Empty if the device may not access any file.
+    pub roots: Vec<FileRoot>,
+}
+
+/// A path the device may browse, and everything under it unless a more
+/// specific root says otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileRoot {
+    /// Absolute path.
+    pub path: String,
+    /// What the device may do under the path.
+    pub access: FileAccess,
+    /// Size of the filesystem holding the path in bytes, if known.
+    pub total_bytes: Option<u64>,
+    /// Bytes available to the daemon on that filesystem, if known.
+    pub available_bytes: Option<u64>,
+}
+
+/// What a device may do under a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum FileAccess {
+    /// List directories and download files.
+    Read,
+    /// Also upload and edit files.
+    ReadWrite,
+    /// Also delete files and create directories.
+    Full,
+}
+
+/// Ask for a preview of a file, so a file browser can show one without
+/// downloading the file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileThumbnailRequest {
+    /// Path of the file.
+    pub path: String,
+    /// Longest edge of the thumbnail in pixels; 0 lets the daemon choose.
+    pub max_size: u32,
+}
+
+/// Reply to a [`FileThumbnailRequest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileThumbnail {
+    /// Path of the file.
+    pub path: String,
+    /// MIME type of the file, for picking an icon.
+    pub mime_type: String,
+    /// JPEG thumbnail, absent when the file is not an image the daemon can
+    /// decode within its limits.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<Vec<u8>>"))]
+    pub jpeg: Option<Vec<u8>>,
+    /// Width of the thumbnail in pixels, 0 without one.
+    pub width: u32,
+    /// Height of the thumbnail in pixels, 0 without one.
+    pub height: u32,
+}
+
+/// Stream the last lines of a text file and, if `follow` is set, the data
+/// appended to it afterwards, like `tail -f`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileTailRequest {
+    /// Path of the file.
+    pub path: String,
+    /// Number of lines from the end of the file to send first.
+    pub lines: u32,
+    /// Keep sending data appended to the file until a [`FileTailStop`].
+    pub follow: bool,
+}
+
+/// Data from a file being tailed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileTailData {
+    /// Path of the file, as requested.
+    pub path: String,
+    /// Offset of `data` in the file.
+    pub offset: u64,
+    /// Bytes of the file, which may end within a line or character.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub data: Vec<u8>,
+    /// The file was truncated or replaced, so `data` starts the file anew.
+    pub reset: bool,
+}
+
+/// Stop following a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileTailStop {
+    /// Path of the file, as requested.
+    pub path: String,
 }
 
 // ============================================================================
@@ -336,6 +1284,7 @@ pub struct FileUploadComplete {
 
 /// Device information announcement.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DeviceInfo {
     /// Unique device identifier (public key fingerprint).
     pub device_id: String,
@@ -353,6 +1302,7 @@ pub struct DeviceInfo {
 
 /// Request approval to connect.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DeviceApprovalRequest {
     /// Device ID requesting approval.
     pub device_id: String,
@@ -360,13 +1310,23 @@ pub struct DeviceApprovalRequest {
     pub name: String,
     /// Public key for verification.
     #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
     pub public_key: Vec<u8>,
     /// Human-readable reason for connection.
     pub reason: Option<String>,
+    /// One-time token issued by `remoshell devices reinvite`, allowing a
+    /// revoked device to be re-approved.
+    #[serde(default)]
+    pub reinvite_token: Option<String>,
+    /// Pairing code the client paired with. The first device to present a
+    /// code is bound to it; other devices presenting it are rejected.
+    #[serde(default)]
+    pub pairing_code: Option<String>,
 }
 
 /// Device connection approved.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DeviceApproved {
     /// Device ID that was approved.
     pub device_id: String,
@@ -378,6 +1338,7 @@ pub struct DeviceApproved {
 
 /// Device connection rejected.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DeviceRejected {
     /// Device ID that was rejected.
     pub device_id: String,
@@ -387,32 +1348,302 @@ pub struct DeviceRejected {
     pub retry_allowed: bool,
 }
 
+/// Challenge for a security key (FIDO2) assertion.
+///
+/// Sent in place of the response to a request that daemon policy gates on a
+/// security key, such as the first session of the day. The client performs
+/// a WebAuthn-style assertion over `challenge` and answers with
+/// [`SecurityKeyAssertion`], then repeats the original request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SecurityKeyChallenge {
+    /// Identifier to echo back in the assertion.
+    pub challenge_id: String,
+    /// Random challenge to be signed by the authenticator.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub challenge: Vec<u8>,
+    /// Relying party ID the credential is scoped to.
+    pub rp_id: String,
+    /// Base64url-encoded IDs of the credentials the daemon accepts.
+    pub allowed_credentials: Vec<String>,
+    /// The gated action (`session_create` or `file_write`).
+    pub action: String,
+    /// Whether the authenticator must verify the user (PIN or biometrics).
+    pub user_verification: bool,
+    /// When the challenge expires (Unix timestamp).
+    pub expires_at: u64,
+}
+
+/// Security key assertion answering a [`SecurityKeyChallenge`].
+///
+/// The fields are those of a WebAuthn `AuthenticatorAssertionResponse`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SecurityKeyAssertion {
+    /// Challenge being answered.
+    pub challenge_id: String,
+    /// ID of the credential that produced the assertion.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub credential_id: Vec<u8>,
+    /// Authenticator data.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub authenticator_data: Vec<u8>,
+    /// Client data JSON, containing the base64url-encoded challenge.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub client_data_json: Vec<u8>,
+    /// DER-encoded ECDSA P-256 signature over the authenticator data and the
+    /// SHA-256 hash of the client data.
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
+    pub signature: Vec<u8>,
+}
+
+/// Security key assertion accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SecurityKeyVerified {
+    /// Challenge that was answered.
+    pub challenge_id: String,
+    /// Until when protected file writes are allowed without a new assertion
+    /// (Unix timestamp).
+    pub valid_until: u64,
+}
+
+// ============================================================================
+// Host Messages
+// ============================================================================
+
+/// Request for the daemon host's system information.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SystemInfoRequest {}
+
+/// System information about the daemon host, for client dashboards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SystemInfo {
+    /// Host name.
+    pub hostname: String,
+    /// Operating system name (e.g. "Ubuntu").
+    pub os_name: Option<String>,
+    /// Operating system version (e.g. "24.04").
+    pub os_version: Option<String>,
+    /// Kernel version.
+    pub kernel_version: Option<String>,
+    /// CPU architecture (e.g. "x86_64").
+    pub arch: String,
+    /// Number of logical CPUs.
+    pub cpu_count: u32,
+    /// Load averages over 1, 5 and 15 minutes, multiplied by 100.
+    pub load_average: [u32; 3],
+    /// Total memory in bytes.
+    pub memory_total: u64,
+    /// Used memory in bytes.
+    pub memory_used: u64,
+    /// Total swap in bytes.
+    pub swap_total: u64,
+    /// Used swap in bytes.
+    pub swap_used: u64,
+    /// Usage of the filesystems holding the paths the daemon allows file
+    /// access to.
+    pub disks: Vec<DiskUsage>,
+    /// Seconds since the host booted.
+    pub uptime_secs: u64,
+}
+
+/// Usage of the filesystem holding a path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DiskUsage {
+    /// The path, as configured on the daemon.
+    pub path: String,
+    /// Size of the filesystem in bytes.
+    pub total: u64,
+    /// Bytes available to unprivileged users.
+    pub available: u64,
+}
+
+/// Request for the processes running on the daemon host.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ProcessListRequest {
+    /// Order of the returned processes (by PID if not set).
+    pub sort: Option<ProcessSort>,
+    /// Maximum number of processes to return, after sorting.
+    pub limit: Option<u32>,
+}
+
+/// Order of a process list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ProcessSort {
+    /// Ascending process ID.
+    Pid,
+    /// Highest CPU usage first.
+    Cpu,
+    /// Highest memory usage first.
+    Memory,
+    /// Command line, alphabetically.
+    Command,
+}
+
+/// Processes running on the daemon host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ProcessList {
+    /// The processes, in the requested order.
+    pub processes: Vec<ProcessInfo>,
+}
+
+/// A process running on the daemon host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ProcessInfo {
+    /// Process ID.
+    pub pid: u32,
+    /// Name of the user running the process, if known.
+    pub user: Option<String>,
+    /// CPU usage in hundredths of a percent of one CPU, so a process using
+    /// two CPUs fully reports 20000.
+    pub cpu_usage: u32,
+    /// Resident memory in bytes.
+    pub memory: u64,
+    /// Command line, or the process name if the command line is not readable.
+    pub command: String,
+}
+
+/// Signal a process on the daemon host.
+///
+/// Requires the `process-kill` device capability.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ProcessKill {
+    /// Process to signal.
+    pub pid: u32,
+    /// The signal to deliver.
+    pub signal: ProcessSignal,
+}
+
+/// Power actions a client can request on the daemon host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum PowerAction {
+    /// Restart the host.
+    Reboot,
+    /// Power the host off.
+    Shutdown,
+    /// Suspend the host to RAM.
+    Suspend,
+}
+
+/// Request to reboot, shut down or suspend the daemon host.
+///
+/// Requires the `power` device capability. The daemon answers with a
+/// [`PowerActionChallenge`] rather than performing the action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PowerActionRequest {
+    /// The action to perform.
+    pub action: PowerAction,
+}
+
+/// Token to confirm a requested power action with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PowerActionChallenge {
+    /// One-time token to send back in [`PowerActionConfirm`].
+    pub token: String,
+    /// The requested action.
+    pub action: PowerAction,
+    /// Until when the action can be confirmed (Unix timestamp).
+    pub expires_at: u64,
+}
+
+/// Confirmation of a requested power action, sent once the user agreed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PowerActionConfirm {
+    /// Token from the [`PowerActionChallenge`].
+    pub token: String,
+}
+
+/// A confirmed power action is about to run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PowerActionStarted {
+    /// The action being performed.
+    pub action: PowerAction,
+}
+
+/// Request for the daemon's effective configuration.
+///
+/// Requires a management role; the daemon answers with [`ConfigView`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ConfigRequest {}
+
+/// The daemon's effective configuration, with secrets redacted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ConfigView {
+    /// The configuration in the daemon's TOML format, after environment
+    /// overrides and remote changes.
+    pub toml: String,
+}
+
+/// Change to the settings that can be changed remotely. Fields left unset
+/// are kept.
+///
+/// Requires the `owner` role. The daemon validates the result, saves it to
+/// its configuration file, applies it without restarting and answers with
+/// the new [`ConfigView`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ConfigPatch {
+    /// Seconds a session may go without input or output before it is
+    /// closed (0 = never).
+    pub idle_timeout: Option<u64>,
+    /// Glob patterns of names left out of directory listings.
+    pub file_exclude: Option<Vec<String>>,
+    /// Log level: trace, debug, info, warn or error.
+    pub log_level: Option<String>,
+}
+
 // ============================================================================
 // Control Messages
 // ============================================================================
 
 /// Ping for keepalive and latency measurement.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Ping {
     /// Timestamp when ping was sent (for latency calculation).
     pub timestamp: u64,
     /// Optional payload for echo.
     #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
     pub payload: Vec<u8>,
 }
 
 /// Pong response to ping.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Pong {
     /// Original timestamp from ping.
     pub timestamp: u64,
     /// Echo of the original payload.
     #[serde(with = "serde_bytes")]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<u8>"))]
     pub payload: Vec<u8>,
 }
 
 /// Error message.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ErrorMessage {
     /// Error code for programmatic handling.
     pub code: ErrorCode,
@@ -420,16 +1651,47 @@ pub struct ErrorMessage {
     pub message: String,
     /// Optional context (e.g., session_id, path).
     pub context: Option<String>,
-    /// Whether the error is recoverable.
+    /// Whether the request may succeed if sent again, set from
+    /// [`ErrorCode::is_retryable`].
     pub recoverable: bool,
+    /// How long to wait before retrying, in milliseconds, when the daemon
+    /// knows, e.g. for [`ErrorCode::RateLimited`].
+    #[serde(default)]
+    pub retry_after_ms: Option<u64>,
+}
+
+impl ErrorMessage {
+    /// Create an error message, recoverable if the code is retryable.
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            context: None,
+            recoverable: code.is_retryable(),
+            retry_after_ms: None,
+        }
+    }
+
+    /// Set the context of the error, such as a session ID or path.
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    /// Set how long to wait before retrying, in milliseconds.
+    pub fn with_retry_after(mut self, retry_after_ms: u64) -> Self {
+        self.retry_after_ms = Some(retry_after_ms);
+        self
+    }
 }
 
 /// Error codes for common error conditions.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ErrorCode {
     /// Unknown or unspecified error.
     Unknown,
-    /// Authentication or authorization failure.
+    /// The device is not trusted, or failed to authenticate.
     Unauthorized,
     /// Resource not found.
     NotFound,
@@ -443,14 +1705,47 @@ pub enum ErrorCode {
     RateLimited,
     /// Resource already exists.
     AlreadyExists,
-    /// Insufficient permissions.
+    /// The device is trusted but not allowed to do this, e.g. by path
+    /// permissions or a missing capability.
     PermissionDenied,
     /// Protocol version mismatch.
     VersionMismatch,
+    /// Not enough disk space for the request.
+    InsufficientSpace,
+    /// The resource changed since the client last read it.
+    Conflict,
+    /// The peer does not support the message type.
+    Unsupported,
+    /// The device has reached a limit, such as the number of files it
+    /// follows.
+    QuotaExceeded,
+    /// The request needs a connection or channel that is not open.
+    NotConnected,
+    /// The resource is in use by another request.
+    Busy,
+}
+
+impl ErrorCode {
+    /// Returns whether a request that failed with this code may succeed if
+    /// sent again unchanged, possibly after a delay.
+    ///
+    /// Errors that need the request or the daemon's configuration to change
+    /// first are not retryable.
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            Self::InternalError
+                | Self::Timeout
+                | Self::RateLimited
+                | Self::NotConnected
+                | Self::Busy
+        )
+    }
 }
 
 /// Capabilities announcement.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Capabilities {
     /// Supported protocol versions.
     pub protocol_versions: Vec<u8>,
@@ -480,19 +1775,188 @@ impl Default for Capabilities {
     }
 }
 
+/// Severity of a [`Notification`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum NotificationLevel {
+    /// Something the user may want to know.
+    Info,
+    /// Something that needs the user's attention.
+    Warning,
+    /// Something went wrong.
+    Error,
+}
+
+/// What a [`Notification`] is about, so clients can group or filter them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum NotificationCategory {
+    /// Anything not covered by another category.
+    General,
+    /// The outcome of a device approval.
+    Approval,
+    /// The daemon or its host is about to go away.
+    Shutdown,
+    /// A resource limit is close or has been reached.
+    Quota,
+    /// A bell or desktop notification raised by a program in a session.
+    Terminal,
+}
+
+/// Notice pushed by the daemon for the user.
+///
+/// Sent on the Control channel without a preceding request; clients do not
+/// reply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Notification {
+    /// How urgent the notice is.
+    pub level: NotificationLevel,
+    /// Short summary, suitable as the title of an OS notification.
+    pub title: String,
+    /// Details for the user.
+    pub body: String,
+    /// What the notice is about.
+    pub category: NotificationCategory,
+    /// Session the notice came from, for [`NotificationCategory::Terminal`].
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+impl Notification {
+    /// Create a notification.
+    pub fn new(
+        level: NotificationLevel,
+        category: NotificationCategory,
+        title: impl Into<String>,
+        body: impl Into<String>,
+    ) -> Self {
+        Self {
+            level,
+            title: title.into(),
+            body: body.into(),
+            category,
+            session_id: None,
+        }
+    }
+
+    /// Set the session the notification came from.
+    pub fn with_session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+}
+
+/// The daemon is stopping and will close the connection.
+///
+/// Sent on the Control channel to every connected client before the daemon
+/// closes sessions and drains file transfers, so clients can stop sending
+/// new work and avoid reconnecting straight away; clients do not reply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ServerShutdownPending {
+    /// Why the daemon is stopping.
+    pub reason: String,
+    /// Longest time, in milliseconds, before the connection is closed.
+    pub grace_ms: u64,
+}
+
 // ============================================================================
 // Serialization helpers
 // ============================================================================
 
+/// Envelope with the message data left undecoded, for message types this
+/// build does not know.
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize)]
+struct RawEnvelope {
+    version: u8,
+    sequence: u64,
+    payload: RawMessage,
+}
+
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize)]
+struct RawMessage {
+    #[serde(rename = "type")]
+    tag: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<rmpv::Value>,
+}
+
+#[cfg(feature = "std")]
 impl Envelope {
     /// Serialize the envelope to MessagePack bytes.
+    ///
+    /// A [`Message::Unknown`] is written back out with its original tag and
+    /// data.
     pub fn to_msgpack(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
-        rmp_serde::to_vec(self)
+        let Message::Unknown { tag, payload } = &self.payload else {
+            return rmp_serde::to_vec(self);
+        };
+        let data = if payload.is_empty() {
+            None
+        } else {
+            let value = rmpv::decode::read_value(&mut payload.as_slice())
+                .map_err(|e| rmp_serde::encode::Error::Syntax(e.to_string()))?;
+            Some(value)
+        };
+        rmp_serde::to_vec(&RawEnvelope {
+            version: self.version,
+            sequence: self.sequence,
+            payload: RawMessage {
+                tag: tag.clone(),
+                data,
+            },
+        })
     }
 
     /// Deserialize an envelope from MessagePack bytes.
-    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
-        rmp_serde::from_slice(bytes)
+    ///
+    /// The bytes are checked against the default [`DecodeLimits`] first, so
+    /// untrusted input cannot cause unbounded allocation or recursion.
+    pub fn from_msgpack(bytes: &[u8]) -> crate::Result<Self> {
+        Self::from_msgpack_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    /// Deserialize an envelope from MessagePack bytes with custom limits.
+    pub fn from_msgpack_with_limits(bytes: &[u8], limits: &DecodeLimits) -> crate::Result<Self> {
+        if bytes.len() > MAX_FRAME_SIZE {
+            return Err(ProtocolError::FrameTooLarge {
+                size: bytes.len(),
+                max: MAX_FRAME_SIZE,
+            });
+        }
+        validate_msgpack(bytes, limits)?;
+        match rmp_serde::from_slice(bytes) {
+            Ok(envelope) => Ok(envelope),
+            Err(e) => Self::decode_unknown(bytes, &e).ok_or_else(|| e.into()),
+        }
+    }
+
+    /// Decode an envelope whose message type failed to decode as
+    /// [`Message::Unknown`].
+    ///
+    /// Returns `None` if the type is known, so malformed known messages are
+    /// still rejected.
+    fn decode_unknown(bytes: &[u8], error: &rmp_serde::decode::Error) -> Option<Self> {
+        let raw: RawEnvelope = rmp_serde::from_slice(bytes).ok()?;
+        let unknown_variant = format!("unknown variant `{}`", raw.payload.tag);
+        if !error.to_string().contains(&unknown_variant) {
+            return None;
+        }
+        let mut payload = Vec::new();
+        if let Some(data) = &raw.payload.data {
+            rmpv::encode::write_value(&mut payload, data).ok()?;
+        }
+        Some(Self {
+            version: raw.version,
+            sequence: raw.sequence,
+            payload: Message::Unknown {
+                tag: raw.payload.tag,
+                payload,
+            },
+        })
     }
 }
 
@@ -545,14 +2009,71 @@ mod tests {
                 ("LANG".to_string(), "en_US.UTF-8".to_string()),
             ],
             cwd: Some("/home/user".to_string()),
+            forward_agent: true,
+            template: Some("logs".to_string()),
+            timezone: Some("Europe/Paris".to_string()),
+            locale: Some("fr-FR".to_string()),
+            term: Some("xterm-256color".to_string()),
+            forward_display: Some(DisplayProtocol::X11),
+            host_session: Some(HostSession {
+                multiplexer: HostMultiplexer::Tmux,
+                name: "work".to_string(),
+                create: true,
+            }),
+            login_shell: false,
         }));
     }
 
+    #[test]
+    fn test_session_create_without_forward_agent_field() {
+        // Older clients encode only the first five fields
+        let legacy = (
+            "SessionCreate",
+            (
+                80u16,
+                24u16,
+                None::<String>,
+                Vec::<(String, String)>::new(),
+                None::<String>,
+            ),
+        );
+        let bytes = rmp_serde::to_vec(&legacy).unwrap();
+        let message: Message = rmp_serde::from_slice(&bytes).unwrap();
+
+        match message {
+            Message::SessionCreate(req) => {
+                assert_eq!(req.cols, 80);
+                assert!(!req.forward_agent);
+                assert_eq!(req.template, None);
+                assert_eq!(req.timezone, None);
+                assert_eq!(req.term, None);
+                assert_eq!(req.forward_display, None);
+            }
+            other => panic!("Expected SessionCreate, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_session_create_default_roundtrip() {
         roundtrip_envelope(Message::SessionCreate(SessionCreate::default()));
     }
 
+    #[test]
+    fn test_template_messages_roundtrip() {
+        roundtrip_envelope(Message::ListTemplates(ListTemplates {}));
+        roundtrip_envelope(Message::TemplateList(TemplateList {
+            templates: vec![TemplateInfo {
+                name: "logs".to_string(),
+                description: Some("Follow the application log".to_string()),
+                shell: None,
+                cwd: Some("/var/log".to_string()),
+                command: Some("tail -f app.log".to_string()),
+                cols: Some(200),
+                rows: None,
+            }],
+        }));
+    }
+
     #[test]
     fn test_session_created_roundtrip() {
         roundtrip_envelope(Message::SessionCreated(SessionCreated {
@@ -592,6 +2113,139 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn test_session_signal_roundtrip() {
+        roundtrip_envelope(Message::SessionSignal(SessionSignal {
+            session_id: "sess-abc123".to_string(),
+            signal: ProcessSignal::Interrupt,
+        }));
+    }
+
+    #[test]
+    fn test_update_session_env_roundtrip() {
+        roundtrip_envelope(Message::UpdateSessionEnv(UpdateSessionEnv {
+            session_id: "sess-abc123".to_string(),
+            set: vec![("DISPLAY".to_string(), "localhost:10.0".to_string())],
+            unset: vec!["TZ".to_string()],
+        }));
+    }
+
+    #[test]
+    fn test_session_metadata_roundtrip() {
+        roundtrip_envelope(Message::SessionMetadataRequest(SessionMetadataRequest {
+            session_id: "sess-abc123".to_string(),
+        }));
+        roundtrip_envelope(Message::SessionMetadata(SessionMetadata {
+            session_id: "sess-abc123".to_string(),
+            cwd: Some("/home/user".to_string()),
+            last_exit_code: Some(1),
+            last_command: Some("make test".to_string()),
+            command_running: false,
+        }));
+    }
+
+    #[test]
+    fn test_session_search_roundtrip() {
+        roundtrip_envelope(Message::SessionSearchRequest(SessionSearchRequest {
+            session_id: "sess-abc123".to_string(),
+            pattern: r"error\b".to_string(),
+            regex: true,
+            case_sensitive: false,
+            max_results: 50,
+        }));
+        roundtrip_envelope(Message::SessionSearchResult(SessionSearchResult {
+            session_id: "sess-abc123".to_string(),
+            matches: vec![SearchMatch {
+                line: 1042,
+                start: 7,
+                end: 12,
+                text: "build: error in main.rs".to_string(),
+            }],
+            truncated: false,
+            first_line: 40,
+            last_line: 1050,
+        }));
+    }
+
+    #[test]
+    fn test_agent_roundtrip() {
+        roundtrip_envelope(Message::AgentOpen(AgentOpen {
+            session_id: "sess-abc123".to_string(),
+            channel_id: 1,
+        }));
+        roundtrip_envelope(Message::AgentData(AgentData {
+            session_id: "sess-abc123".to_string(),
+            channel_id: 1,
+            data: vec![0, 0, 0, 1, 11],
+        }));
+        roundtrip_envelope(Message::AgentClose(AgentClose {
+            session_id: "sess-abc123".to_string(),
+            channel_id: 1,
+        }));
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        roundtrip_envelope(Message::DisplayOpen(DisplayOpen {
+            session_id: "sess-abc123".to_string(),
+            channel_id: 1,
+        }));
+        roundtrip_envelope(Message::DisplayData(DisplayData {
+            session_id: "sess-abc123".to_string(),
+            channel_id: 1,
+            data: vec![b'l', 0, 11, 0],
+        }));
+        roundtrip_envelope(Message::DisplayClose(DisplayClose {
+            session_id: "sess-abc123".to_string(),
+            channel_id: 1,
+        }));
+    }
+
+    #[test]
+    fn test_message_name_matches_wire_tag() {
+        let msg = Message::SessionKill(SessionKill {
+            session_id: "sess-1".to_string(),
+            signal: None,
+        });
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["type"], msg.name());
+        let ping = Message::Ping(Ping {
+            timestamp: 0,
+            payload: vec![],
+        });
+        assert_eq!(ping.name(), "Ping");
+    }
+
+    #[test]
+    fn test_message_session_id() {
+        let data = Message::SessionData(SessionData {
+            session_id: "sess-1".to_string(),
+            stream: DataStream::Stdin,
+            data: vec![],
+        });
+        assert_eq!(data.session_id(), Some("sess-1"));
+        assert_eq!(
+            Message::SessionCreate(SessionCreate::default()).session_id(),
+            None
+        );
+        assert_eq!(
+            Message::FileListRequest(FileListRequest {
+                path: "/".to_string(),
+                include_hidden: false,
+                detail: FileListDetail::Basic,
+            })
+            .session_id(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_process_signal_name() {
+        assert_eq!(ProcessSignal::Interrupt.name(), "SIGINT");
+        assert_eq!(ProcessSignal::Quit.name(), "SIGQUIT");
+        assert_eq!(ProcessSignal::Suspend.name(), "SIGTSTP");
+    }
+
     #[test]
     fn test_session_data_stdin_roundtrip() {
         roundtrip_envelope(Message::SessionData(SessionData {
@@ -626,6 +2280,7 @@ mod tests {
             exit_code: Some(0),
             signal: None,
             reason: Some("Process exited normally".to_string()),
+            close_reason: Some(SessionCloseReason::Exited),
         }));
     }
 
@@ -636,6 +2291,34 @@ mod tests {
             exit_code: None,
             signal: Some(9),
             reason: Some("Killed by SIGKILL".to_string()),
+            close_reason: None,
+        }));
+    }
+
+    #[test]
+    fn test_session_close_reasons_roundtrip() {
+        for close_reason in [
+            SessionCloseReason::Exited,
+            SessionCloseReason::Killed,
+            SessionCloseReason::DaemonShutdown,
+            SessionCloseReason::IdleTimeout,
+            SessionCloseReason::OwnerRevoked,
+        ] {
+            roundtrip_envelope(Message::SessionClosed(SessionClosed {
+                session_id: "sess-abc123".to_string(),
+                exit_code: None,
+                signal: Some(1),
+                reason: Some(close_reason.description().to_string()),
+                close_reason: Some(close_reason),
+            }));
+        }
+    }
+
+    #[test]
+    fn test_session_output_truncated_roundtrip() {
+        roundtrip_envelope(Message::SessionOutputTruncated(SessionOutputTruncated {
+            session_id: "sess-abc123".to_string(),
+            dropped_chunks: 42,
         }));
     }
 
@@ -646,6 +2329,7 @@ mod tests {
         roundtrip_envelope(Message::FileListRequest(FileListRequest {
             path: "/home/user/documents".to_string(),
             include_hidden: true,
+            detail: FileListDetail::Basic,
         }));
     }
 
@@ -660,6 +2344,7 @@ mod tests {
                     size: 1024,
                     mode: 0o644,
                     modified: 1704067200,
+                    details: None,
                 },
                 FileEntry {
                     name: "docs".to_string(),
@@ -667,6 +2352,7 @@ mod tests {
                     size: 0,
                     mode: 0o755,
                     modified: 1704067200,
+                    details: None,
                 },
                 FileEntry {
                     name: "link".to_string(),
@@ -674,6 +2360,7 @@ mod tests {
                     size: 0,
                     mode: 0o777,
                     modified: 1704067200,
+                    details: None,
                 },
             ],
         }));
@@ -685,6 +2372,91 @@ mod tests {
             path: "/home/user/large-file.bin".to_string(),
             offset: 1024,
             chunk_size: 65536,
+            verify: true,
+            window: 8,
+        }));
+    }
+
+    #[test]
+    fn test_file_list_detail_roundtrip() {
+        roundtrip_envelope(Message::FileListRequest(FileListRequest {
+            path: "/home/user".to_string(),
+            include_hidden: false,
+            detail: FileListDetail::Full,
+        }));
+        roundtrip_envelope(Message::FileListResponse(FileListResponse {
+            path: "/home/user".to_string(),
+            entries: vec![FileEntry {
+                name: "link".to_string(),
+                entry_type: FileEntryType::Symlink,
+                size: 0,
+                mode: 0o777,
+                modified: 1704067200,
+                details: Some(FileEntryDetails {
+                    uid: 1000,
+                    gid: 100,
+                    owner: Some("user".to_string()),
+                    group: None,
+                    symlink_target: Some("../target".to_string()),
+                    xattrs: vec![FileXattr {
+                        name: "user.comment".to_string(),
+                        value: vec![0, 159, 146, 150],
+                    }],
+                }),
+            }],
+        }));
+    }
+
+    #[test]
+    fn test_file_list_without_detail_fields() {
+        // Older peers encode neither the requested detail nor entry details
+        let legacy = ("FileListRequest", ("/home/user", true));
+        let bytes = rmp_serde::to_vec(&legacy).unwrap();
+        let message: Message = rmp_serde::from_slice(&bytes).unwrap();
+        match message {
+            Message::FileListRequest(req) => assert_eq!(req.detail, FileListDetail::Basic),
+            other => panic!("Expected FileListRequest, got {:?}", other),
+        }
+
+        let legacy = (
+            "FileListResponse",
+            (
+                "/home/user",
+                vec![("file.txt", FileEntryType::File, 5u64, 0o644u32, 0u64)],
+            ),
+        );
+        let bytes = rmp_serde::to_vec(&legacy).unwrap();
+        let message: Message = rmp_serde::from_slice(&bytes).unwrap();
+        match message {
+            Message::FileListResponse(resp) => assert_eq!(resp.entries[0].details, None),
+            other => panic!("Expected FileListResponse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_file_download_request_without_window_field() {
+        // Older clients encode no window, and get one chunk per request
+        let legacy = (
+            "FileDownloadRequest",
+            ("/home/user/large-file.bin", 0u64, 65536u32, true),
+        );
+        let bytes = rmp_serde::to_vec(&legacy).unwrap();
+        let message: Message = rmp_serde::from_slice(&bytes).unwrap();
+
+        match message {
+            Message::FileDownloadRequest(req) => {
+                assert!(req.verify);
+                assert_eq!(req.window, 0);
+            }
+            other => panic!("Expected FileDownloadRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_file_download_ack_roundtrip() {
+        roundtrip_envelope(Message::FileDownloadAck(FileDownloadAck {
+            path: "/home/user/large-file.bin".to_string(),
+            offset: 524288,
         }));
     }
 
@@ -696,6 +2468,17 @@ mod tests {
             total_size: 100,
             data: b"Hello, World!".to_vec(),
             is_last: false,
+            hash: None,
+            file_hash: None,
+        }));
+        roundtrip_envelope(Message::FileDownloadChunk(FileDownloadChunk {
+            path: "/home/user/file.txt".to_string(),
+            offset: 87,
+            total_size: 100,
+            data: b"Hello, World!".to_vec(),
+            is_last: true,
+            hash: Some(vec![0x11; 32]),
+            file_hash: Some(vec![0x22; 32]),
         }));
     }
 
@@ -715,6 +2498,164 @@ mod tests {
             path: "/home/user/upload.bin".to_string(),
             offset: 65536,
             data: vec![0u8; 1024],
+            hash: Some(vec![0x33; 32]),
+        }));
+    }
+
+    #[test]
+    fn test_file_upload_chunk_without_hash_field() {
+        // Older clients encode only the first three fields
+        let legacy = (
+            "FileUploadChunk",
+            (
+                "/home/user/upload.bin",
+                65536u64,
+                serde_bytes::ByteBuf::from(vec![0u8; 16]),
+            ),
+        );
+        let bytes = rmp_serde::to_vec(&legacy).unwrap();
+        let message: Message = rmp_serde::from_slice(&bytes).unwrap();
+
+        match message {
+            Message::FileUploadChunk(chunk) => {
+                assert_eq!(chunk.offset, 65536);
+                assert_eq!(chunk.hash, None);
+            }
+            other => panic!("Expected FileUploadChunk, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_file_transfer_report_roundtrip() {
+        roundtrip_envelope(Message::FileTransferReport(FileTransferReport {
+            path: "/home/user/upload.bin".to_string(),
+            size: 1048576,
+            hash: vec![0x44; 32],
+            chunks: 16,
+            verified_chunks: 16,
+            duration_ms: 1250,
+        }));
+    }
+
+    fn sample_copy_grant() -> FileCopyGrant {
+        FileCopyGrant {
+            path: "/srv/backup/db.sqlite".to_string(),
+            token: vec![0x17; 32],
+            addresses: vec![
+                "192.168.1.20:7677".to_string(),
+                "nas.local:7677".to_string(),
+            ],
+            public_key: vec![0x42; 32],
+            expires_in_secs: 60,
+        }
+    }
+
+    #[test]
+    fn test_file_copy_grant_request_roundtrip() {
+        roundtrip_envelope(Message::FileCopyGrantRequest(FileCopyGrantRequest {
+            path: "/srv/backup/db.sqlite".to_string(),
+            overwrite: true,
+        }));
+    }
+
+    #[test]
+    fn test_file_copy_grant_roundtrip() {
+        roundtrip_envelope(Message::FileCopyGrant(sample_copy_grant()));
+    }
+
+    #[test]
+    fn test_file_copy_request_roundtrip() {
+        roundtrip_envelope(Message::FileCopyRequest(FileCopyRequest {
+            source_path: "/home/user/db.sqlite".to_string(),
+            grant: sample_copy_grant(),
+        }));
+    }
+
+    #[test]
+    fn test_file_text_roundtrip() {
+        roundtrip_envelope(Message::FileReadText(FileReadText {
+            path: "/etc/nginx/nginx.conf".to_string(),
+        }));
+        roundtrip_envelope(Message::FileText(FileText {
+            path: "/etc/nginx/nginx.conf".to_string(),
+            content: "worker_processes auto;\n".to_string(),
+            hash: vec![0x2b; 32],
+        }));
+        roundtrip_envelope(Message::FileTextWritten(FileTextWritten {
+            path: "/etc/nginx/nginx.conf".to_string(),
+            hash: vec![0x3c; 32],
+        }));
+    }
+
+    #[test]
+    fn test_file_write_text_roundtrip() {
+        for expected_hash in [Some(vec![0x2b; 32]), None] {
+            roundtrip_envelope(Message::FileWriteText(FileWriteText {
+                path: "/etc/nginx/nginx.conf".to_string(),
+                content: "worker_processes 4;\n".to_string(),
+                expected_hash,
+            }));
+        }
+    }
+
+    #[test]
+    fn test_file_roots_roundtrip() {
+        roundtrip_envelope(Message::FileRootsRequest(FileRootsRequest {}));
+        roundtrip_envelope(Message::FileRoots(FileRoots {
+            roots: vec![
+                FileRoot {
+                    path: "/home/user".to_string(),
+                    access: FileAccess::Full,
+                    total_bytes: Some(500_000_000_000),
+                    available_bytes: Some(120_000_000_000),
+                },
+                FileRoot {
+                    path: "/var/log".to_string(),
+                    access: FileAccess::Read,
+                    total_bytes: None,
+                    available_bytes: None,
+                },
+            ],
+        }));
+    }
+
+    #[test]
+    fn test_file_thumbnail_roundtrip() {
+        roundtrip_envelope(Message::FileThumbnailRequest(FileThumbnailRequest {
+            path: "/home/user/photo.jpg".to_string(),
+            max_size: 128,
+        }));
+        roundtrip_envelope(Message::FileThumbnail(FileThumbnail {
+            path: "/home/user/photo.jpg".to_string(),
+            mime_type: "image/jpeg".to_string(),
+            jpeg: Some(vec![0xff, 0xd8, 0xff, 0xe0]),
+            width: 128,
+            height: 96,
+        }));
+        roundtrip_envelope(Message::FileThumbnail(FileThumbnail {
+            path: "/home/user/notes.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            jpeg: None,
+            width: 0,
+            height: 0,
+        }));
+    }
+
+    #[test]
+    fn test_file_tail_roundtrip() {
+        roundtrip_envelope(Message::FileTailRequest(FileTailRequest {
+            path: "/var/log/syslog".to_string(),
+            lines: 100,
+            follow: true,
+        }));
+        roundtrip_envelope(Message::FileTailData(FileTailData {
+            path: "/var/log/syslog".to_string(),
+            offset: 4096,
+            data: b"started\n".to_vec(),
+            reset: false,
+        }));
+        roundtrip_envelope(Message::FileTailStop(FileTailStop {
+            path: "/var/log/syslog".to_string(),
         }));
     }
 
@@ -723,6 +2664,7 @@ mod tests {
         roundtrip_envelope(Message::FileUploadComplete(FileUploadComplete {
             path: "/home/user/upload.bin".to_string(),
             checksum: vec![0xab, 0xcd, 0xef, 0x12, 0x34, 0x56, 0x78, 0x90],
+            report: true,
         }));
     }
 
@@ -747,9 +2689,48 @@ mod tests {
             name: "Work Computer".to_string(),
             public_key: vec![0x04; 32],
             reason: Some("Need to access project files".to_string()),
+            reinvite_token: None,
+            pairing_code: None,
+        }));
+    }
+
+    #[test]
+    fn test_device_approval_request_with_reinvite_token_roundtrip() {
+        roundtrip_envelope(Message::DeviceApprovalRequest(DeviceApprovalRequest {
+            device_id: "revoked-device-456".to_string(),
+            name: "Old Phone".to_string(),
+            public_key: vec![0x05; 32],
+            reason: None,
+            reinvite_token: Some("0123abcd".to_string()),
+            pairing_code: None,
         }));
     }
 
+    #[test]
+    fn test_device_approval_request_without_reinvite_token_field() {
+        // Older clients encode only the first four fields
+        let legacy = (
+            "DeviceApprovalRequest",
+            (
+                "legacy-device",
+                "Legacy",
+                serde_bytes::ByteBuf::from(vec![0x06; 32]),
+                None::<String>,
+            ),
+        );
+        let bytes = rmp_serde::to_vec(&legacy).unwrap();
+        let message: Message = rmp_serde::from_slice(&bytes).unwrap();
+
+        match message {
+            Message::DeviceApprovalRequest(req) => {
+                assert_eq!(req.device_id, "legacy-device");
+                assert_eq!(req.reinvite_token, None);
+                assert_eq!(req.pairing_code, None);
+            }
+            other => panic!("Expected DeviceApprovalRequest, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_device_approved_roundtrip() {
         roundtrip_envelope(Message::DeviceApproved(DeviceApproved {
@@ -770,6 +2751,108 @@ mod tests {
 
     // Control message roundtrip tests
 
+    #[test]
+    fn test_security_key_roundtrip() {
+        roundtrip_envelope(Message::SecurityKeyChallenge(SecurityKeyChallenge {
+            challenge_id: "chal-1".to_string(),
+            challenge: vec![7; 32],
+            rp_id: "remoshell".to_string(),
+            allowed_credentials: vec!["Y3JlZC0x".to_string()],
+            action: "session_create".to_string(),
+            user_verification: true,
+            expires_at: 1_700_000_060,
+        }));
+        roundtrip_envelope(Message::SecurityKeyAssertion(SecurityKeyAssertion {
+            challenge_id: "chal-1".to_string(),
+            credential_id: b"cred-1".to_vec(),
+            authenticator_data: vec![1; 37],
+            client_data_json: br#"{"type":"webauthn.get"}"#.to_vec(),
+            signature: vec![0x30, 0x44],
+        }));
+        roundtrip_envelope(Message::SecurityKeyVerified(SecurityKeyVerified {
+            challenge_id: "chal-1".to_string(),
+            valid_until: 1_700_000_300,
+        }));
+    }
+
+    #[test]
+    fn test_system_info_roundtrip() {
+        roundtrip_envelope(Message::SystemInfoRequest(SystemInfoRequest {}));
+        roundtrip_envelope(Message::SystemInfo(SystemInfo {
+            hostname: "build-box".to_string(),
+            os_name: Some("Debian GNU/Linux".to_string()),
+            os_version: Some("12".to_string()),
+            kernel_version: Some("6.1.0".to_string()),
+            arch: "x86_64".to_string(),
+            cpu_count: 8,
+            load_average: [125, 80, 42],
+            memory_total: 16 << 30,
+            memory_used: 5 << 30,
+            swap_total: 0,
+            swap_used: 0,
+            disks: vec![DiskUsage {
+                path: "/home".to_string(),
+                total: 500 << 30,
+                available: 120 << 30,
+            }],
+            uptime_secs: 86_400,
+        }));
+    }
+
+    #[test]
+    fn test_process_messages_roundtrip() {
+        roundtrip_envelope(Message::ProcessListRequest(ProcessListRequest::default()));
+        roundtrip_envelope(Message::ProcessListRequest(ProcessListRequest {
+            sort: Some(ProcessSort::Cpu),
+            limit: Some(20),
+        }));
+        roundtrip_envelope(Message::ProcessList(ProcessList {
+            processes: vec![ProcessInfo {
+                pid: 4242,
+                user: Some("www-data".to_string()),
+                cpu_usage: 1250,
+                memory: 64 << 20,
+                command: "nginx: worker process".to_string(),
+            }],
+        }));
+        roundtrip_envelope(Message::ProcessKill(ProcessKill {
+            pid: 4242,
+            signal: ProcessSignal::Terminate,
+        }));
+    }
+
+    #[test]
+    fn test_power_messages_roundtrip() {
+        roundtrip_envelope(Message::PowerActionRequest(PowerActionRequest {
+            action: PowerAction::Reboot,
+        }));
+        roundtrip_envelope(Message::PowerActionChallenge(PowerActionChallenge {
+            token: "0b0e9a8c-1f5d-4c4e-9d55-3e7f2a6b8c11".to_string(),
+            action: PowerAction::Shutdown,
+            expires_at: 1_704_067_230,
+        }));
+        roundtrip_envelope(Message::PowerActionConfirm(PowerActionConfirm {
+            token: "0b0e9a8c-1f5d-4c4e-9d55-3e7f2a6b8c11".to_string(),
+        }));
+        roundtrip_envelope(Message::PowerActionStarted(PowerActionStarted {
+            action: PowerAction::Suspend,
+        }));
+    }
+
+    #[test]
+    fn test_config_messages_roundtrip() {
+        roundtrip_envelope(Message::ConfigRequest(ConfigRequest {}));
+        roundtrip_envelope(Message::ConfigView(ConfigView {
+            toml: "[session]\nidle_timeout = 3600\n".to_string(),
+        }));
+        roundtrip_envelope(Message::ConfigPatch(ConfigPatch::default()));
+        roundtrip_envelope(Message::ConfigPatch(ConfigPatch {
+            idle_timeout: Some(900),
+            file_exclude: Some(vec![".git".to_string(), "*.sock".to_string()]),
+            log_level: Some("debug".to_string()),
+        }));
+    }
+
     #[test]
     fn test_ping_roundtrip() {
         roundtrip_envelope(Message::Ping(Ping {
@@ -793,6 +2876,7 @@ mod tests {
             message: "Session not found".to_string(),
             context: Some("sess-unknown".to_string()),
             recoverable: false,
+            retry_after_ms: None,
         }));
     }
 
@@ -816,6 +2900,33 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn test_notification_roundtrip() {
+        roundtrip_envelope(Message::Notification(Notification::new(
+            NotificationLevel::Warning,
+            NotificationCategory::Shutdown,
+            "Daemon stopping",
+            "The daemon on build-host is shutting down",
+        )));
+        roundtrip_envelope(Message::Notification(
+            Notification::new(
+                NotificationLevel::Info,
+                NotificationCategory::Terminal,
+                "make",
+                "Build finished",
+            )
+            .with_session("session-1"),
+        ));
+    }
+
+    #[test]
+    fn test_server_shutdown_pending_roundtrip() {
+        roundtrip_envelope(Message::ServerShutdownPending(ServerShutdownPending {
+            reason: "stop requested".to_string(),
+            grace_ms: 5000,
+        }));
+    }
+
     // Error code tests
 
     #[test]
@@ -831,16 +2942,133 @@ mod tests {
             ErrorCode::AlreadyExists,
             ErrorCode::PermissionDenied,
             ErrorCode::VersionMismatch,
+            ErrorCode::InsufficientSpace,
+            ErrorCode::Conflict,
+            ErrorCode::Unsupported,
+            ErrorCode::QuotaExceeded,
+            ErrorCode::NotConnected,
+            ErrorCode::Busy,
         ];
 
         for code in codes {
-            roundtrip_envelope(Message::Error(ErrorMessage {
+            roundtrip_envelope(Message::Error(ErrorMessage::new(
                 code,
-                message: format!("Test error: {:?}", code),
-                context: None,
-                recoverable: true,
-            }));
+                format!("Test error: {:?}", code),
+            )));
+        }
+    }
+
+    #[test]
+    fn test_error_message_retry_after() {
+        let error = ErrorMessage::new(ErrorCode::RateLimited, "slow down")
+            .with_context("sess-1")
+            .with_retry_after(1500);
+        assert!(error.recoverable);
+        assert_eq!(error.context.as_deref(), Some("sess-1"));
+        assert_eq!(error.retry_after_ms, Some(1500));
+        roundtrip_envelope(Message::Error(error));
+
+        // Older peers do not send a retry delay
+        let bytes = rmp_serde::to_vec(&(
+            1u8,
+            1u64,
+            ("Error", ("RateLimited", "slow down", None::<String>, true)),
+        ))
+        .unwrap();
+        match Envelope::from_msgpack(&bytes).unwrap().payload {
+            Message::Error(error) => assert_eq!(error.retry_after_ms, None),
+            other => panic!("Expected Error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_code_retryable() {
+        assert!(ErrorCode::Timeout.is_retryable());
+        assert!(ErrorCode::RateLimited.is_retryable());
+        assert!(ErrorCode::Busy.is_retryable());
+        assert!(ErrorCode::NotConnected.is_retryable());
+        assert!(!ErrorCode::QuotaExceeded.is_retryable());
+        assert!(!ErrorCode::PermissionDenied.is_retryable());
+        assert!(!ErrorCode::Unauthorized.is_retryable());
+        assert!(!ErrorCode::Conflict.is_retryable());
+        assert!(ErrorMessage::new(ErrorCode::InternalError, "oops").recoverable);
+        assert!(!ErrorMessage::new(ErrorCode::NotFound, "gone").recoverable);
+    }
+
+    // Unknown message tests
+
+    #[test]
+    fn test_unknown_message_decodes() {
+        // A message type from a newer release
+        let bytes = rmp_serde::to_vec(&(1u8, 7u64, ("SessionShare", ("s1", 3u32)))).unwrap();
+        let envelope = Envelope::from_msgpack(&bytes).unwrap();
+        assert_eq!(envelope.version, 1);
+        assert_eq!(envelope.sequence, 7);
+        match &envelope.payload {
+            Message::Unknown { tag, payload } => {
+                assert_eq!(tag, "SessionShare");
+                let data: (String, u32) = rmp_serde::from_slice(payload).unwrap();
+                assert_eq!(data, ("s1".to_string(), 3));
+            }
+            other => panic!("Expected Unknown, got {:?}", other),
         }
+        assert_eq!(envelope.payload.name(), "Unknown");
+
+        // Encoding writes the original message back out
+        assert_eq!(envelope.to_msgpack().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_unknown_message_without_data() {
+        let bytes = rmp_serde::to_vec(&(1u8, 2u64, ("Goodbye",))).unwrap();
+        let envelope = Envelope::from_msgpack(&bytes).unwrap();
+        assert_eq!(
+            envelope.payload,
+            Message::Unknown {
+                tag: "Goodbye".to_string(),
+                payload: vec![],
+            }
+        );
+        assert_eq!(envelope.to_msgpack().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_unknown_message_from_map() {
+        // JavaScript clients encode structs as maps
+        let value = rmpv::Value::Map(vec![
+            ("version".into(), 1.into()),
+            ("sequence".into(), 3.into()),
+            (
+                "payload".into(),
+                rmpv::Value::Map(vec![
+                    ("type".into(), "SessionShare".into()),
+                    (
+                        "data".into(),
+                        rmpv::Value::Map(vec![("id".into(), "s1".into())]),
+                    ),
+                ]),
+            ),
+        ]);
+        let mut bytes = Vec::new();
+        rmpv::encode::write_value(&mut bytes, &value).unwrap();
+
+        let envelope = Envelope::from_msgpack(&bytes).unwrap();
+        assert!(matches!(
+            envelope.payload,
+            Message::Unknown { ref tag, .. } if tag == "SessionShare"
+        ));
+    }
+
+    #[test]
+    fn test_malformed_known_message_is_an_error() {
+        // Known type with data of the wrong shape
+        let bytes = rmp_serde::to_vec(&(1u8, 1u64, ("SessionAttach", (42u32,)))).unwrap();
+        assert!(Envelope::from_msgpack(&bytes).is_err());
+
+        // Known type with an unknown value in a nested enum
+        let bytes =
+            rmp_serde::to_vec(&(1u8, 1u64, ("SessionData", ("s1", "Stdweird", [0u8; 0])))).unwrap();
+        assert!(Envelope::from_msgpack(&bytes).is_err());
     }
 
     // Binary size tests
@@ -928,6 +3156,14 @@ mod tests {
                 ("GREETING".to_string(), "Hello!".to_string()),
             ],
             cwd: Some("/home/user/documents".to_string()),
+            forward_agent: false,
+            template: None,
+            timezone: None,
+            locale: None,
+            term: None,
+            forward_display: None,
+            host_session: None,
+            login_shell: false,
         }));
     }
 
@@ -936,6 +3172,7 @@ mod tests {
         roundtrip_envelope(Message::FileListRequest(FileListRequest {
             path: "/home/user/My Documents/file (1).txt".to_string(),
             include_hidden: false,
+            detail: FileListDetail::Basic,
         }));
     }
 
@@ -944,6 +3181,36 @@ mod tests {
         roundtrip_envelope(Message::FileUploadComplete(FileUploadComplete {
             path: "/tmp/test".to_string(),
             checksum: (0u8..=255).collect(),
+            report: false,
         }));
     }
+
+    #[test]
+    fn test_from_msgpack_enforces_limits() {
+        let envelope = Envelope::new(
+            1,
+            Message::SessionData(SessionData {
+                session_id: "s".repeat(64),
+                stream: DataStream::Stdout,
+                data: vec![0; 16],
+            }),
+        );
+        let bytes = envelope.to_msgpack().unwrap();
+
+        let limits = DecodeLimits {
+            max_string_len: 32,
+            ..DecodeLimits::default()
+        };
+        assert!(matches!(
+            Envelope::from_msgpack_with_limits(&bytes, &limits),
+            Err(ProtocolError::StringTooLong { len: 64, max: 32 })
+        ));
+
+        // Deeply nested input fails cleanly instead of overflowing the stack
+        let nested = vec![0x91; 100_000];
+        assert!(matches!(
+            Envelope::from_msgpack(&nested),
+            Err(ProtocolError::NestingTooDeep { .. })
+        ));
+    }
 }
@@ -14,11 +14,21 @@
 //! <- e, ee, s, es
 //! -> s, se
 //! ```
+//!
+//! ## Rekeying
+//!
+//! Peers that both announce [`REKEY_FEATURE`] switch keys periodically using
+//! the Noise `REKEY` function. The sender encrypts a rekey marker under its
+//! current key and then rekeys its sending cipher; the receiver rekeys its
+//! receiving cipher after decrypting the marker.
+
+use std::time::Duration;
 
 use snow::{Builder, HandshakeState, TransportState};
 
 use crate::crypto::{DeviceIdentity, PeerIdentity};
 use crate::error::{ProtocolError, Result};
+use crate::time::Instant;
 
 /// The Noise protocol pattern used for handshakes.
 ///
@@ -38,6 +48,34 @@ pub const MAX_NOISE_MESSAGE_SIZE: usize = 65535;
 /// Overhead added by Noise encryption (Poly1305 tag).
 pub const NOISE_OVERHEAD: usize = 16;
 
+/// Feature announced in [`Capabilities`](crate::messages::Capabilities) by
+/// peers that understand rekey markers.
+pub const REKEY_FEATURE: &str = "noise-rekey";
+
+/// Plaintext of a rekey marker.
+///
+/// 0xc1 is never used in MessagePack, so a marker cannot be mistaken for an
+/// encoded envelope.
+const REKEY_MARKER: &[u8] = &[0xc1, b'r', b'k'];
+
+/// When a transport session switches to a new sending key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RekeyPolicy {
+    /// Maximum time a sending key is used.
+    pub interval: Duration,
+    /// Maximum number of plaintext bytes encrypted under one sending key.
+    pub max_bytes: u64,
+}
+
+impl Default for RekeyPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(3600),
+            max_bytes: 1024 * 1024 * 1024, // 1 GiB
+        }
+    }
+}
+
 /// State of the Noise handshake process.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HandshakePhase {
@@ -97,6 +135,14 @@ pub struct NoiseSession {
     peer_identity: Option<PeerIdentity>,
     /// Buffer for handshake operations
     buffer: Vec<u8>,
+    /// Rekey policy, if both peers support rekeying
+    rekey_policy: Option<RekeyPolicy>,
+    /// Time the current sending key was installed
+    key_installed: Instant,
+    /// Plaintext bytes encrypted under the current sending key
+    bytes_since_rekey: u64,
+    /// Hash of the completed handshake, kept after switching to transport
+    handshake_hash: Option<Vec<u8>>,
 }
 
 impl NoiseSession {
@@ -124,6 +170,10 @@ impl NoiseSession {
             role: Role::Initiator,
             peer_identity: None,
             buffer: vec![0u8; MAX_NOISE_MESSAGE_SIZE],
+            rekey_policy: None,
+            key_installed: Instant::now(),
+            bytes_since_rekey: 0,
+            handshake_hash: None,
         })
     }
 
@@ -151,6 +201,10 @@ impl NoiseSession {
             role: Role::Responder,
             peer_identity: None,
             buffer: vec![0u8; MAX_NOISE_MESSAGE_SIZE],
+            rekey_policy: None,
+            key_installed: Instant::now(),
+            bytes_since_rekey: 0,
+            handshake_hash: None,
         })
     }
 
@@ -263,6 +317,14 @@ impl NoiseSession {
             }
         }
 
+        if message.len() > MAX_NOISE_MESSAGE_SIZE {
+            return Err(ProtocolError::HandshakeFailed(format!(
+                "handshake message too large: {} bytes exceeds maximum of {} bytes",
+                message.len(),
+                MAX_NOISE_MESSAGE_SIZE
+            )));
+        }
+
         let len = handshake.read_message(message, &mut self.buffer)?;
         let payload = self.buffer[..len].to_vec();
 
@@ -335,6 +397,21 @@ impl NoiseSession {
         Some(key)
     }
 
+    /// Returns the hash of the completed handshake.
+    ///
+    /// Both peers compute the same hash, which is unique to the session.
+    /// Signing it binds a long-term identity to the session. Returns `None`
+    /// until the handshake is complete.
+    pub fn handshake_hash(&self) -> Option<&[u8]> {
+        if self.phase != HandshakePhase::Complete {
+            return None;
+        }
+        match &self.handshake {
+            Some(handshake) => Some(handshake.get_handshake_hash()),
+            None => self.handshake_hash.as_deref(),
+        }
+    }
+
     /// Transitions from handshake to transport mode.
     ///
     /// This should be called after the handshake is complete.
@@ -352,8 +429,10 @@ impl NoiseSession {
             .take()
             .ok_or(ProtocolError::HandshakeIncomplete)?;
 
+        self.handshake_hash = Some(handshake.get_handshake_hash().to_vec());
         let transport = handshake.into_transport_mode()?;
         self.transport = Some(transport);
+        self.key_installed = Instant::now();
 
         // Clear the handshake buffer and resize for transport
         self.buffer = vec![0u8; MAX_NOISE_MESSAGE_SIZE];
@@ -380,6 +459,7 @@ impl NoiseSession {
         }
 
         let len = transport.write_message(plaintext, &mut self.buffer)?;
+        self.bytes_since_rekey += plaintext.len() as u64;
         Ok(self.buffer[..len].to_vec())
     }
 
@@ -404,6 +484,72 @@ impl NoiseSession {
         let len = transport.read_message(ciphertext, &mut self.buffer)?;
         Ok(self.buffer[..len].to_vec())
     }
+
+    /// Enables rekeying under `policy`.
+    ///
+    /// Only call this once the peer has announced [`REKEY_FEATURE`]; a peer
+    /// without it would treat the rekey marker as application data.
+    pub fn enable_rekey(&mut self, policy: RekeyPolicy) {
+        self.rekey_policy = Some(policy);
+    }
+
+    /// Returns whether rekeying is enabled.
+    pub fn rekey_enabled(&self) -> bool {
+        self.rekey_policy.is_some()
+    }
+
+    /// Returns whether the sending key has reached the limits of the rekey
+    /// policy and [`write_rekey`](Self::write_rekey) should be called.
+    pub fn rekey_due(&self) -> bool {
+        match self.rekey_policy {
+            Some(policy) if self.transport.is_some() => {
+                self.bytes_since_rekey >= policy.max_bytes
+                    || self.key_installed.elapsed() >= policy.interval
+            }
+            _ => false,
+        }
+    }
+
+    /// Switches to a new sending key.
+    ///
+    /// Returns the rekey marker, encrypted under the old key, which must be
+    /// sent to the peer before any message encrypted afterwards.
+    pub fn write_rekey(&mut self) -> Result<Vec<u8>> {
+        if self.rekey_policy.is_none() {
+            return Err(ProtocolError::Encryption(
+                "rekeying was not negotiated".to_string(),
+            ));
+        }
+
+        let marker = self.encrypt(REKEY_MARKER)?;
+        let transport = self
+            .transport
+            .as_mut()
+            .ok_or(ProtocolError::HandshakeIncomplete)?;
+        transport.rekey_outgoing();
+        self.key_installed = Instant::now();
+        self.bytes_since_rekey = 0;
+        Ok(marker)
+    }
+
+    /// Decrypts a transport message, handling rekey markers.
+    ///
+    /// Returns `None` if the message was a rekey marker, after switching to
+    /// the peer's new sending key. Markers are only recognized once rekeying
+    /// is enabled.
+    pub fn read_transport(&mut self, ciphertext: &[u8]) -> Result<Option<Vec<u8>>> {
+        let plaintext = self.decrypt(ciphertext)?;
+        if self.rekey_policy.is_none() || plaintext != REKEY_MARKER {
+            return Ok(Some(plaintext));
+        }
+
+        let transport = self
+            .transport
+            .as_mut()
+            .ok_or(ProtocolError::HandshakeIncomplete)?;
+        transport.rekey_incoming();
+        Ok(None)
+    }
 }
 
 /// Trait for types that can perform a secure handshake.
@@ -537,6 +683,15 @@ mod tests {
 
         // The keys should be different (each side has the other's key)
         assert_ne!(initiator_peer_key, responder_peer_key);
+
+        // Both sides agree on the handshake hash, before and after
+        // switching to transport
+        let hash = initiator.handshake_hash().unwrap().to_vec();
+        assert_eq!(responder.handshake_hash(), Some(hash.as_slice()));
+        initiator.into_transport().unwrap();
+        responder.into_transport().unwrap();
+        assert_eq!(initiator.handshake_hash(), Some(hash.as_slice()));
+        assert_eq!(responder.handshake_hash(), Some(hash.as_slice()));
     }
 
     #[test]
@@ -786,6 +941,81 @@ mod tests {
         assert_eq!(decrypted, large_payload);
     }
 
+    fn transport_pair() -> (NoiseSession, NoiseSession) {
+        let mut initiator = NoiseSession::new_initiator(&DeviceIdentity::generate()).unwrap();
+        let mut responder = NoiseSession::new_responder(&DeviceIdentity::generate()).unwrap();
+
+        let msg1 = initiator.write_handshake_message(&[]).unwrap();
+        responder.read_handshake_message(&msg1).unwrap();
+        let msg2 = responder.write_handshake_message(&[]).unwrap();
+        initiator.read_handshake_message(&msg2).unwrap();
+        let msg3 = initiator.write_handshake_message(&[]).unwrap();
+        responder.read_handshake_message(&msg3).unwrap();
+
+        initiator.into_transport().unwrap();
+        responder.into_transport().unwrap();
+        (initiator, responder)
+    }
+
+    #[test]
+    fn test_data_flows_across_rekey() {
+        let (mut initiator, mut responder) = transport_pair();
+        let policy = RekeyPolicy {
+            interval: Duration::from_secs(3600),
+            max_bytes: 64,
+        };
+        initiator.enable_rekey(policy);
+        responder.enable_rekey(policy);
+
+        let mut received = Vec::new();
+        let mut rekeys = 0;
+        for i in 0..10u8 {
+            // Rekey whenever the byte limit is reached, like a connection would
+            if initiator.rekey_due() {
+                let marker = initiator.write_rekey().unwrap();
+                assert_eq!(responder.read_transport(&marker).unwrap(), None);
+                rekeys += 1;
+            }
+            let ciphertext = initiator.encrypt(&[i; 32]).unwrap();
+            received.push(responder.read_transport(&ciphertext).unwrap().unwrap());
+
+            // The other direction keeps its key
+            let reply = responder.encrypt(&[i]).unwrap();
+            assert_eq!(initiator.read_transport(&reply).unwrap(), Some(vec![i]));
+        }
+
+        assert_eq!(rekeys, 4);
+        assert_eq!(received, (0..10u8).map(|i| vec![i; 32]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_rekey_changes_key() {
+        let (mut initiator, mut responder) = transport_pair();
+        initiator.enable_rekey(RekeyPolicy::default());
+        responder.enable_rekey(RekeyPolicy::default());
+        assert!(!initiator.rekey_due());
+
+        // A message encrypted after the rekey fails without the marker
+        let _marker = initiator.write_rekey().unwrap();
+        let ciphertext = initiator.encrypt(b"after").unwrap();
+        assert!(responder.read_transport(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_rekey_requires_negotiation() {
+        let (mut initiator, mut responder) = transport_pair();
+        assert!(initiator.write_rekey().is_err());
+        assert!(!initiator.rekey_due());
+
+        // Without rekeying enabled, a marker is ordinary data
+        initiator.enable_rekey(RekeyPolicy::default());
+        let marker = initiator.write_rekey().unwrap();
+        assert_eq!(
+            responder.read_transport(&marker).unwrap(),
+            Some(REKEY_MARKER.to_vec())
+        );
+    }
+
     #[test]
     fn test_empty_payload_encryption() {
         let initiator_identity = DeviceIdentity::generate();
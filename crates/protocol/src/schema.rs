@@ -0,0 +1,415 @@
+//! JSON Schema and Markdown reference for protocol messages.
+//!
+//! Both are generated from the message types with schemars, so they follow
+//! the code that encodes and decodes messages. `remoshell-daemon protocol
+//! dump-schema` prints them for third-party client implementers.
+//!
+//! Fields are listed in declaration order, which is the order MessagePack
+//! encodes them in: structs are encoded as arrays, so a client must send
+//! fields in this order and may only omit trailing optional fields.
+
+use std::fmt::Write;
+
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+
+use crate::messages::{Envelope, PROTOCOL_VERSION};
+
+/// Prefix of references to the definitions of a [`RootSchema`].
+const DEFINITIONS: &str = "#/definitions/";
+
+/// Returns the JSON Schema of [`Envelope`], with every message type in its
+/// definitions.
+pub fn json_schema() -> RootSchema {
+    let mut schema = schemars::schema_for!(Envelope);
+    schema.schema.extensions.insert(
+        "x-protocol-version".to_string(),
+        Value::from(PROTOCOL_VERSION),
+    );
+    schema
+}
+
+/// Returns a Markdown reference of the envelope, every message and the types
+/// they use.
+pub fn markdown() -> String {
+    let schema = json_schema();
+    let defs = &schema.definitions;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# RemoShell Protocol Messages\n");
+    let _ = writeln!(
+        out,
+        "Generated from the protocol crate for protocol version {}. Fields are \
+         listed in encoding order: MessagePack encodes structs as arrays, so \
+         only trailing optional fields may be omitted.\n",
+        PROTOCOL_VERSION
+    );
+
+    let _ = writeln!(out, "## Envelope\n");
+    write_description(&mut out, &schema.schema);
+    write_fields(&mut out, &schema.schema);
+
+    let _ = writeln!(out, "## Messages\n");
+    let _ = writeln!(
+        out,
+        "Messages are encoded as `{{ \"type\": <name>, \"data\": <fields> }}`.\n"
+    );
+    let mut payloads = Vec::new();
+    for variant in message_variants(&schema) {
+        let Some(name) = variant_name(variant) else {
+            continue;
+        };
+        let _ = writeln!(out, "### {}\n", name);
+        write_description(&mut out, variant);
+        let data = variant
+            .object
+            .as_ref()
+            .and_then(|object| object.properties.get("data"));
+        let Some(data) = data else {
+            let _ = writeln!(out, "No data.\n");
+            continue;
+        };
+        let payload =
+            reference(data).and_then(|def| Some((def, defs.get(def).and_then(as_object)?)));
+        match payload {
+            Some((def, payload)) => {
+                payloads.push(def);
+                if def != name {
+                    let _ = writeln!(out, "Data: [`{}`](#{}).\n", def, anchor(def));
+                }
+                if description(payload) != description(variant) {
+                    write_description(&mut out, payload);
+                }
+                write_fields(&mut out, payload);
+            }
+            None => {
+                let _ = writeln!(out, "Data: {}.\n", type_name(data));
+            }
+        }
+    }
+
+    let _ = writeln!(out, "## Types\n");
+    for (name, def) in defs {
+        let Some(def) = as_object(def) else {
+            continue;
+        };
+        if name == "Message" || payloads.contains(&name.as_str()) {
+            continue;
+        }
+        let _ = writeln!(out, "### {}\n", name);
+        write_description(&mut out, def);
+        write_fields(&mut out, def);
+        write_values(&mut out, def);
+    }
+
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+/// Returns the schema of each [`Message`](crate::Message) variant.
+fn message_variants(schema: &RootSchema) -> impl Iterator<Item = &SchemaObject> {
+    schema
+        .definitions
+        .get("Message")
+        .and_then(as_object)
+        .and_then(|message| message.subschemas.as_ref())
+        .and_then(|subschemas| subschemas.one_of.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(as_object)
+}
+
+/// Returns the `type` tag of a message variant.
+fn variant_name(variant: &SchemaObject) -> Option<&str> {
+    let tag = variant.object.as_ref()?.properties.get("type")?;
+    as_object(tag)?.enum_values.as_ref()?.first()?.as_str()
+}
+
+fn as_object(schema: &Schema) -> Option<&SchemaObject> {
+    match schema {
+        Schema::Object(object) => Some(object),
+        Schema::Bool(_) => None,
+    }
+}
+
+fn description(schema: &SchemaObject) -> Option<&str> {
+    schema.metadata.as_ref()?.description.as_deref()
+}
+
+/// Writes the description of a schema as a paragraph.
+fn write_description(out: &mut String, schema: &SchemaObject) {
+    if let Some(description) = description(schema) {
+        let _ = writeln!(out, "{}\n", strip_doc_links(description));
+    }
+}
+
+/// Writes the properties of an object schema as a table.
+fn write_fields(out: &mut String, schema: &SchemaObject) {
+    let Some(object) = schema.object.as_ref() else {
+        return;
+    };
+    if object.properties.is_empty() {
+        return;
+    }
+
+    let _ = writeln!(out, "| Field | Type | Required | Description |");
+    let _ = writeln!(out, "|-------|------|----------|-------------|");
+    for (name, property) in &object.properties {
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            name,
+            type_name(property),
+            if object.required.contains(name) {
+                "Yes"
+            } else {
+                "No"
+            },
+            cell(as_object(property).and_then(description)),
+        );
+    }
+    out.push('\n');
+}
+
+/// Writes the values of an enum schema as a list.
+fn write_values(out: &mut String, schema: &SchemaObject) {
+    let values: Vec<String> = if let Some(values) = &schema.enum_values {
+        values
+            .iter()
+            .map(|value| format!("- `{}`", value_name(value)))
+            .collect()
+    } else if let Some(options) = schema
+        .subschemas
+        .as_ref()
+        .and_then(|subschemas| subschemas.one_of.as_ref())
+    {
+        options.iter().map(option).collect()
+    } else {
+        return;
+    };
+    for value in values {
+        let _ = writeln!(out, "{}", value);
+    }
+    out.push('\n');
+}
+
+/// Describes one option of a `oneOf` enum schema.
+fn option(schema: &Schema) -> String {
+    let Some(object) = as_object(schema) else {
+        return format!("- {}", type_name(schema));
+    };
+    let description = description(object)
+        .map(|description| format!(": {}", single_line(&strip_doc_links(description))))
+        .unwrap_or_default();
+    if let Some(values) = &object.enum_values {
+        let names: Vec<String> = values
+            .iter()
+            .map(|value| format!("`{}`", value_name(value)))
+            .collect();
+        return format!("- {}{}", names.join(", "), description);
+    }
+    // Variants with data are objects with a single property named after them
+    if let Some((name, data)) = object
+        .object
+        .as_ref()
+        .and_then(|object| object.properties.iter().next())
+    {
+        return format!("- `{}` ({}){}", name, type_name(data), description);
+    }
+    format!("- {}{}", type_name(schema), description)
+}
+
+/// Returns a short name for the type a schema describes.
+fn type_name(schema: &Schema) -> String {
+    let Some(object) = as_object(schema) else {
+        return "any".to_string();
+    };
+    if let Some(def) = reference(schema) {
+        return format!("[{}](#{})", def, anchor(def));
+    }
+    if let Some(subschemas) = &object.subschemas {
+        // Fields with a description wrap their type in a single `allOf`
+        let options = subschemas
+            .all_of
+            .as_ref()
+            .filter(|all_of| all_of.len() == 1)
+            .or(subschemas.any_of.as_ref())
+            .or(subschemas.one_of.as_ref());
+        if let Some(options) = options {
+            let names: Vec<String> = options.iter().map(type_name).collect();
+            return names.join(" or ");
+        }
+    }
+    if let Some(value) = &object.const_value {
+        return format!("`{}`", value_name(value));
+    }
+    if let Some(values) = &object.enum_values {
+        let names: Vec<String> = values
+            .iter()
+            .map(|value| format!("`{}`", value_name(value)))
+            .collect();
+        return names.join(" or ");
+    }
+    match &object.instance_type {
+        Some(SingleOrVec::Single(kind)) => instance_name(object, kind),
+        Some(SingleOrVec::Vec(kinds)) => kinds
+            .iter()
+            .map(|kind| instance_name(object, kind))
+            .collect::<Vec<_>>()
+            .join(" or "),
+        None => "any".to_string(),
+    }
+}
+
+/// Returns a short name for one of the instance types of a schema.
+fn instance_name(schema: &SchemaObject, kind: &InstanceType) -> String {
+    match kind {
+        InstanceType::Null => "null".to_string(),
+        InstanceType::Boolean => "boolean".to_string(),
+        InstanceType::String => "string".to_string(),
+        InstanceType::Integer => schema
+            .format
+            .as_deref()
+            .map(integer_name)
+            .unwrap_or_else(|| "integer".to_string()),
+        InstanceType::Number => match schema.format.as_deref() {
+            Some("float") => "f32".to_string(),
+            _ => "f64".to_string(),
+        },
+        InstanceType::Array => match schema.array.as_ref().and_then(|array| array.items.as_ref()) {
+            Some(SingleOrVec::Single(items)) => format!("array of {}", type_name(items)),
+            Some(SingleOrVec::Vec(items)) => {
+                let names: Vec<String> = items.iter().map(type_name).collect();
+                format!("[{}]", names.join(", "))
+            }
+            None => "array".to_string(),
+        },
+        InstanceType::Object => match schema
+            .object
+            .as_ref()
+            .and_then(|object| object.additional_properties.as_deref())
+        {
+            Some(values @ Schema::Object(_)) => {
+                format!("map of string to {}", type_name(values))
+            }
+            _ => "object".to_string(),
+        },
+    }
+}
+
+/// Returns the Rust name of an integer format, such as `u16` for `uint16`.
+fn integer_name(format: &str) -> String {
+    match format.strip_prefix("uint") {
+        Some(bits) => format!("u{}", bits),
+        None => format.replace("int", "i"),
+    }
+}
+
+/// Returns the definition a `$ref` schema points to.
+fn reference(schema: &Schema) -> Option<&str> {
+    as_object(schema)?
+        .reference
+        .as_deref()?
+        .strip_prefix(DEFINITIONS)
+}
+
+/// Returns the Markdown heading anchor of a type.
+fn anchor(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Formats an enum value without the quotes of strings.
+fn value_name(value: &Value) -> String {
+    value
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Turns rustdoc links such as ``[`FileEntry`]`` into code spans.
+fn strip_doc_links(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("[`") {
+        let Some(end) = rest[start..].find("`]").map(|end| start + end) else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&rest[start + 1..end + 1]);
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Joins the lines of a description.
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Formats a description for a table cell.
+fn cell(text: Option<&str>) -> String {
+    text.map(|text| single_line(&strip_doc_links(text)).replace('|', "\\|"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Message, SessionCreate};
+
+    #[test]
+    fn test_schema_lists_every_message() {
+        let schema = json_schema();
+        let names: Vec<&str> = message_variants(&schema).filter_map(variant_name).collect();
+        assert_eq!(names.len(), message_variants(&schema).count());
+
+        let markdown = markdown();
+        for name in &names {
+            assert!(
+                markdown.contains(&format!("\n### {}\n", name)),
+                "{} is missing from the Markdown reference",
+                name
+            );
+        }
+
+        // Messages encode with the tag the schema documents
+        let message =
+            serde_json::to_value(Message::SessionCreate(SessionCreate::default())).unwrap();
+        assert!(names.contains(&message["type"].as_str().unwrap()));
+    }
+
+    #[test]
+    fn test_fields_in_encoding_order() {
+        let schema = json_schema();
+        let session_create = as_object(&schema.definitions["SessionCreate"]).unwrap();
+        let fields: Vec<&str> = session_create
+            .object
+            .as_ref()
+            .unwrap()
+            .properties
+            .keys()
+            .map(String::as_str)
+            .take(5)
+            .collect();
+        assert_eq!(fields, ["cols", "rows", "shell", "env", "cwd"]);
+    }
+
+    #[test]
+    fn test_strip_doc_links() {
+        assert_eq!(
+            strip_doc_links("Extra information about a [`FileEntry`], see [`A::b`]."),
+            "Extra information about a `FileEntry`, see `A::b`."
+        );
+        assert_eq!(strip_doc_links("`a` [b] [`c"), "`a` [b] [`c");
+    }
+
+    #[test]
+    fn test_markdown_types() {
+        let markdown = markdown();
+        assert!(markdown.contains("| cols | u16 | Yes |"));
+        assert!(markdown.contains("[HostSession](#hostsession)"));
+        assert!(markdown.contains("\n### HostSession\n"));
+    }
+}
@@ -0,0 +1,10 @@
+//! Monotonic clock that also works in browsers.
+//!
+//! `std::time::Instant` panics on `wasm32-unknown-unknown`, where there is no
+//! system clock to read. Browser builds use `web-time`, which reads
+//! `performance.now()` instead.
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) use web_time::Instant;
@@ -0,0 +1,291 @@
+//! Bridge to the local SSH agent for agent forwarding.
+//!
+//! When a session forwards the agent, the daemon announces every connection a
+//! remote process makes to the forwarded socket with an `AgentOpen` message.
+//! Once the user confirms, the frontend opens a matching channel to the local
+//! agent with [`AgentBridge::connect`], passes the payload of each `AgentData`
+//! message to [`AgentBridge::write`], and sends the agent's replies, reported
+//! as [`AgentEvent`]s, back to the daemon.
+//!
+//! The local agent is found through `SSH_AUTH_SOCK` on Unix and the OpenSSH
+//! agent pipe on Windows.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// Environment variable naming the local agent socket.
+pub const SSH_AUTH_SOCK_ENV: &str = "SSH_AUTH_SOCK";
+
+/// Named pipe of the Windows OpenSSH agent.
+#[cfg(windows)]
+const WINDOWS_AGENT_PIPE: &str = r"\\.\pipe\openssh-ssh-agent";
+
+/// Size of the buffer used to read agent replies.
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Errors from the agent bridge.
+#[derive(Debug, Error)]
+pub enum AgentError {
+    /// No local agent is configured.
+    #[error("No SSH agent available ({SSH_AUTH_SOCK_ENV} is not set)")]
+    NotAvailable,
+
+    /// The channel is already open.
+    #[error("Agent channel {1} of session {0} is already open")]
+    AlreadyOpen(String, u32),
+
+    /// The channel is not open.
+    #[error("Agent channel {1} of session {0} is not open")]
+    NotOpen(String, u32),
+
+    /// Connecting to the local agent failed.
+    #[error("Failed to connect to SSH agent: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Result type for agent bridge operations.
+pub type AgentResult<T> = Result<T, AgentError>;
+
+/// Activity on an agent channel, reported to the frontend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum AgentEvent {
+    /// The local agent sent data to relay to the daemon.
+    Data {
+        session_id: String,
+        channel_id: u32,
+        /// Base64-encoded data for JSON serialization.
+        data: String,
+    },
+    /// The local agent closed the channel.
+    Closed { session_id: String, channel_id: u32 },
+}
+
+type ChannelKey = (String, u32);
+type Channels = HashMap<ChannelKey, mpsc::UnboundedSender<Vec<u8>>>;
+
+/// Relays forwarded agent channels to the local SSH agent.
+#[derive(Debug, Clone, Default)]
+pub struct AgentBridge {
+    socket_path: Option<PathBuf>,
+    channels: Arc<Mutex<Channels>>,
+}
+
+impl AgentBridge {
+    /// Creates a bridge to the agent named by `SSH_AUTH_SOCK`.
+    pub fn from_env() -> Self {
+        Self {
+            socket_path: std::env::var_os(SSH_AUTH_SOCK_ENV).map(PathBuf::from),
+            channels: Arc::default(),
+        }
+    }
+
+    /// Creates a bridge to the agent listening on `socket_path`.
+    pub fn with_socket(socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            socket_path: Some(socket_path.into()),
+            channels: Arc::default(),
+        }
+    }
+
+    /// Returns true if a local agent can be reached.
+    pub fn is_available(&self) -> bool {
+        self.socket_path.is_some() || cfg!(windows)
+    }
+
+    /// Opens a channel to the local agent.
+    ///
+    /// Replies from the agent, and the channel closing, are passed to
+    /// `on_event`.
+    pub async fn connect<F>(
+        &self,
+        session_id: &str,
+        channel_id: u32,
+        on_event: F,
+    ) -> AgentResult<()>
+    where
+        F: Fn(AgentEvent) + Send + Sync + 'static,
+    {
+        let key = (session_id.to_string(), channel_id);
+        if self.lock().contains_key(&key) {
+            return Err(AgentError::AlreadyOpen(key.0, key.1));
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        #[cfg(unix)]
+        {
+            let path = self.socket_path.as_ref().ok_or(AgentError::NotAvailable)?;
+            let stream = tokio::net::UnixStream::connect(path).await?;
+            self.lock().insert(key.clone(), tx);
+            tokio::spawn(relay(stream, key, rx, Arc::clone(&self.channels), on_event));
+        }
+
+        #[cfg(windows)]
+        {
+            use tokio::net::windows::named_pipe::ClientOptions;
+            let pipe = self
+                .socket_path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(WINDOWS_AGENT_PIPE));
+            let stream = ClientOptions::new().open(pipe)?;
+            self.lock().insert(key.clone(), tx);
+            tokio::spawn(relay(stream, key, rx, Arc::clone(&self.channels), on_event));
+        }
+
+        Ok(())
+    }
+
+    /// Sends data from the daemon to the local agent.
+    pub fn write(&self, session_id: &str, channel_id: u32, data: Vec<u8>) -> AgentResult<()> {
+        let channels = self.lock();
+        let sender = channels
+            .get(&(session_id.to_string(), channel_id))
+            .ok_or_else(|| AgentError::NotOpen(session_id.to_string(), channel_id))?;
+        sender
+            .send(data)
+            .map_err(|_| AgentError::NotOpen(session_id.to_string(), channel_id))
+    }
+
+    /// Closes a channel, e.g. when the daemon reports that it was closed.
+    pub fn close(&self, session_id: &str, channel_id: u32) {
+        // Dropping the sender ends the relay task
+        self.lock().remove(&(session_id.to_string(), channel_id));
+    }
+
+    /// Closes every channel, e.g. on disconnect.
+    pub fn close_all(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Channels> {
+        self.channels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Relays one channel until the agent or the daemon closes it.
+async fn relay<S, F>(
+    stream: S,
+    key: ChannelKey,
+    mut from_daemon: mpsc::UnboundedReceiver<Vec<u8>>,
+    channels: Arc<Mutex<Channels>>,
+    on_event: F,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: Fn(AgentEvent),
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buf = vec![0u8; READ_BUFFER_SIZE];
+    let mut closed_by_daemon = false;
+
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => on_event(AgentEvent::Data {
+                    session_id: key.0.clone(),
+                    channel_id: key.1,
+                    data: base64::Engine::encode(
+                        &base64::engine::general_purpose::STANDARD,
+                        &buf[..n],
+                    ),
+                }),
+            },
+            data = from_daemon.recv() => match data {
+                Some(data) => {
+                    if writer.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                None => {
+                    closed_by_daemon = true;
+                    break;
+                }
+            },
+        }
+    }
+
+    channels
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&key);
+    if !closed_by_daemon {
+        on_event(AgentEvent::Closed {
+            session_id: key.0,
+            channel_id: key.1,
+        });
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_relays_to_local_agent() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("agent.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        // A fake agent that answers every request with a failure reply
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 5];
+            stream.read_exact(&mut request).await.unwrap();
+            stream.write_all(&[0, 0, 0, 1, 5]).await.unwrap();
+        });
+
+        let bridge = AgentBridge::with_socket(&socket_path);
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        bridge
+            .connect("sess-1", 1, move |event| {
+                let _ = event_tx.send(event);
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            bridge.connect("sess-1", 1, |_| {}).await,
+            Err(AgentError::AlreadyOpen(_, 1))
+        ));
+
+        bridge.write("sess-1", 1, vec![0, 0, 0, 1, 11]).unwrap();
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            AgentEvent::Data {
+                session_id: "sess-1".to_string(),
+                channel_id: 1,
+                data: "AAAAAQU=".to_string(),
+            }
+        );
+
+        // The agent hanging up closes the channel
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            AgentEvent::Closed {
+                session_id: "sess-1".to_string(),
+                channel_id: 1,
+            }
+        );
+        assert!(matches!(
+            bridge.write("sess-1", 1, vec![]),
+            Err(AgentError::NotOpen(_, 1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_connect_without_agent() {
+        let bridge = AgentBridge::default();
+        assert!(!bridge.is_available());
+        assert!(matches!(
+            bridge.connect("sess-1", 1, |_| {}).await,
+            Err(AgentError::NotAvailable)
+        ));
+    }
+}
@@ -7,12 +7,37 @@
 //! - QUIC connection operations (connect, disconnect, send data)
 //! - Device key management (get device keys from keychain)
 //! - Paired device storage (get/store/remove via SQLite)
+//! - Wake-on-LAN for paired devices with a stored MAC address
+//! - Database schema migration status
+//! - Connection history analytics and retention
+//! - Identity backup (encrypted export/import of keys and paired devices)
+//! - Identity profiles (list/create/switch/delete)
+//! - SSH agent forwarding (relay forwarded channels to the local agent)
+//! - Remote host information from the connected daemon
+//! - Local session recordings (start/stop/export, secret redaction)
+//! - `remoshell://` deep links handed over by QR scanners
 //! - Native notifications
 
+use crate::agent::{AgentBridge, AgentError, AgentEvent};
+use crate::deep_link::{self, DeepLink, DeepLinkError};
+use crate::notifications;
 use crate::quic::{ChannelType, ConnectionState, QuicConfig, QuicManager};
-use crate::storage::{Database, DatabaseError, KeychainError, PairedDevice};
+use crate::recording::{
+    self, ActiveRecording, Recorder, RecordingError, RecordingFormat, Redactor,
+    DEFAULT_REDACTION_PATTERNS,
+};
+use crate::storage::{
+    decode_secret_key, BackupError, Database, DatabaseError, DeviceConnectionStats, IdentityBackup,
+    KeychainBackend, KeychainError, KeychainManager, MigrationInfo, PairedDevice, ProfileError,
+    ProfileStore, RecordingEntry, RetentionPolicy, DATABASE_KEY_LEN, DEFAULT_PROFILE,
+};
+use crate::wol::{self, WolError};
 use iroh::NodeAddr;
+use protocol::messages::{Envelope, Message, SystemInfo, SystemInfoRequest};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
 
@@ -66,6 +91,84 @@ impl From<KeychainError> for CommandError {
     }
 }
 
+impl From<BackupError> for CommandError {
+    fn from(e: BackupError) -> Self {
+        let code = match e {
+            BackupError::WeakPassphrase => "WEAK_PASSPHRASE",
+            BackupError::Decryption => "INVALID_PASSPHRASE",
+            _ => "BACKUP_ERROR",
+        };
+        Self {
+            code: code.to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<AgentError> for CommandError {
+    fn from(e: AgentError) -> Self {
+        let code = match e {
+            AgentError::NotAvailable => "AGENT_UNAVAILABLE",
+            _ => "AGENT_ERROR",
+        };
+        Self {
+            code: code.to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<WolError> for CommandError {
+    fn from(e: WolError) -> Self {
+        let code = match e {
+            WolError::InvalidMacAddress(_) => "INVALID_MAC_ADDRESS",
+            WolError::Io(_) => "WOL_ERROR",
+        };
+        Self {
+            code: code.to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<DeepLinkError> for CommandError {
+    fn from(e: DeepLinkError) -> Self {
+        Self {
+            code: "INVALID_DEEP_LINK".to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<RecordingError> for CommandError {
+    fn from(e: RecordingError) -> Self {
+        let code = match e {
+            RecordingError::InvalidPattern { .. } => "INVALID_REDACTION_PATTERN",
+            RecordingError::UnsupportedExport { .. } => "UNSUPPORTED_EXPORT",
+            RecordingError::Io(_) => "IO_ERROR",
+        };
+        Self {
+            code: code.to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<ProfileError> for CommandError {
+    fn from(e: ProfileError) -> Self {
+        let code = match e {
+            ProfileError::InvalidName(_) => "INVALID_PROFILE_NAME",
+            ProfileError::NotFound(_) => "PROFILE_NOT_FOUND",
+            ProfileError::AlreadyExists(_) => "PROFILE_EXISTS",
+            _ => "PROFILE_ERROR",
+        };
+        Self {
+            code: code.to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
 /// Result type for Tauri commands.
 pub type CommandResult<T> = Result<T, CommandError>;
 
@@ -82,6 +185,18 @@ pub struct AppState {
     /// The SQLite database for paired devices (uses std::sync::Mutex because
     /// rusqlite::Connection is Send but not Sync).
     pub database: Arc<Mutex<Option<Database>>>,
+    /// Locations of the identity profiles, set at initialization.
+    pub profiles: Arc<Mutex<Option<ProfileStore>>>,
+    /// Name of the active identity profile.
+    pub active_profile: Arc<Mutex<String>>,
+    /// Whether profile databases are encrypted at rest.
+    pub encrypt_database: AtomicBool,
+    /// The keychain backend selected at initialization.
+    pub keychain: Arc<Mutex<Option<CommandKeychain>>>,
+    /// Bridge to the local SSH agent for forwarded agent channels.
+    pub agent: AgentBridge,
+    /// Session recordings in progress, by recording ID.
+    pub recordings: Arc<Mutex<HashMap<String, ActiveRecording>>>,
 }
 
 impl AppState {
@@ -90,6 +205,12 @@ impl AppState {
         Self {
             quic_manager: Arc::new(RwLock::new(None)),
             database: Arc::new(Mutex::new(None)),
+            profiles: Arc::new(Mutex::new(None)),
+            active_profile: Arc::new(Mutex::new(DEFAULT_PROFILE.to_string())),
+            encrypt_database: AtomicBool::new(false),
+            keychain: Arc::new(Mutex::new(None)),
+            agent: AgentBridge::from_env(),
+            recordings: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -111,6 +232,113 @@ impl AppState {
         *guard = Some(db);
         Ok(())
     }
+
+    /// Initialize the profile store and open the database of a profile.
+    ///
+    /// `database_path` is the database of the default profile; the other
+    /// profiles are stored next to it.
+    pub fn init_profile(&self, database_path: &str, profile: &str) -> CommandResult<()> {
+        self.init_keychain(database_path)?;
+        let store = ProfileStore::new(database_path);
+        self.open_profile(&store, profile)?;
+        *self.profiles.lock().map_err(|_| state_lock_error())? = Some(store);
+        Ok(())
+    }
+
+    /// Open the database of an existing profile and make it the active one.
+    fn open_profile(&self, store: &ProfileStore, profile: &str) -> CommandResult<()> {
+        if !store.exists(profile) {
+            return Err(ProfileError::NotFound(profile.to_string()).into());
+        }
+
+        let db_path = store.database_path(profile)?;
+        let db = self.open_database(&db_path, profile)?;
+        let mut guard = self.database.lock().map_err(|_| CommandError {
+            code: "DATABASE_LOCK_ERROR".to_string(),
+            message: "Failed to acquire database lock".to_string(),
+        })?;
+        *guard = Some(db);
+        *self.active_profile.lock().map_err(|_| state_lock_error())? = profile.to_string();
+        Ok(())
+    }
+
+    /// Open a profile's database, encrypted if encryption is enabled.
+    ///
+    /// The encryption key is stored in the keychain and created on first
+    /// use. An existing unencrypted database is migrated in place.
+    fn open_database(&self, path: &Path, profile: &str) -> CommandResult<Database> {
+        if !self.encrypt_database.load(Ordering::SeqCst) {
+            return Ok(Database::open(path)?);
+        }
+
+        let encoded = self
+            .database_keychain(profile)?
+            .get_or_create_secret_key()?;
+        let key: [u8; DATABASE_KEY_LEN] = decode_secret_key(&encoded)?
+            .try_into()
+            .map_err(|_| KeychainError::EncodingError("Invalid database key length".to_string()))?;
+        Ok(Database::open_encrypted(path, &key)?)
+    }
+
+    /// Get the name of the active profile.
+    pub fn active_profile(&self) -> CommandResult<String> {
+        Ok(self
+            .active_profile
+            .lock()
+            .map_err(|_| state_lock_error())?
+            .clone())
+    }
+
+    /// Select the keychain backend.
+    ///
+    /// The system keychain is used if it responds; otherwise secrets go to an
+    /// encrypted file next to the database (see [`crate::storage::keychain`]).
+    #[cfg(not(test))]
+    fn init_keychain(&self, database_path: &str) -> CommandResult<()> {
+        let dir = Path::new(database_path)
+            .parent()
+            .unwrap_or_else(|| Path::new("."));
+        let keychain = crate::storage::keychain::AutoKeychain::from_env(dir)?;
+        *self.keychain.lock().map_err(|_| state_lock_error())? = Some(keychain);
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn init_keychain(&self, _database_path: &str) -> CommandResult<()> {
+        Ok(())
+    }
+
+    /// Get the keychain backend, the system keychain if none was selected.
+    fn keychain(&self) -> CommandResult<CommandKeychain> {
+        Ok(self
+            .keychain
+            .lock()
+            .map_err(|_| state_lock_error())?
+            .clone()
+            .unwrap_or_default())
+    }
+
+    /// Get the keychain manager for a profile's device key.
+    fn profile_keychain(&self, profile: &str) -> CommandResult<KeychainManager<CommandKeychain>> {
+        Ok(KeychainManager::for_profile(self.keychain()?, profile))
+    }
+
+    /// Get the keychain manager for a profile's database encryption key.
+    fn database_keychain(&self, profile: &str) -> CommandResult<KeychainManager<CommandKeychain>> {
+        Ok(KeychainManager::for_database_key(self.keychain()?, profile))
+    }
+
+    /// Get a copy of the profile store.
+    fn profile_store(&self) -> CommandResult<ProfileStore> {
+        self.profiles
+            .lock()
+            .map_err(|_| state_lock_error())?
+            .clone()
+            .ok_or_else(|| CommandError {
+                code: "NOT_INITIALIZED".to_string(),
+                message: "Profiles not initialized".to_string(),
+            })
+    }
 }
 
 impl Default for AppState {
@@ -119,6 +347,20 @@ impl Default for AppState {
     }
 }
 
+/// Error returned when a state mutex is poisoned.
+fn state_lock_error() -> CommandError {
+    CommandError {
+        code: "STATE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire state lock".to_string(),
+    }
+}
+
+/// The keychain backend used by commands.
+#[cfg(not(test))]
+pub type CommandKeychain = crate::storage::keychain::AutoKeychain;
+#[cfg(test)]
+pub type CommandKeychain = crate::storage::keychain::MockKeychain;
+
 // ============================================================================
 // QUIC Connection Commands
 // ============================================================================
@@ -175,13 +417,20 @@ pub async fn connect_quic(
         None
     };
 
-    // Parse direct addresses if provided
-    let direct_addrs: Vec<std::net::SocketAddr> = request
+    // Parse direct addresses if provided; IPv6 addresses must be bracketed,
+    // e.g. "[2001:db8::1]:51000", and iroh races all of them
+    let direct_addrs = request
         .direct_addresses
         .unwrap_or_default()
         .iter()
-        .filter_map(|addr| addr.parse().ok())
-        .collect();
+        .map(|addr| {
+            addr.parse::<std::net::SocketAddr>()
+                .map_err(|e| CommandError {
+                    code: "INVALID_ADDRESS".to_string(),
+                    message: format!("Invalid direct address {:?}: {}", addr, e),
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
     // Create the node address
     let node_addr = NodeAddr::from_parts(node_id, relay_url, direct_addrs);
@@ -320,52 +569,32 @@ pub struct DeviceKeysResponse {
 
 /// Get or create the device's secret key from the keychain.
 ///
-/// This command retrieves the device's secret key from the system keychain.
-/// If no key exists, a new one is generated and stored.
+/// This command retrieves the active profile's secret key from the system
+/// keychain. If no key exists, a new one is generated and stored.
 #[tauri::command]
-pub fn get_device_keys() -> CommandResult<DeviceKeysResponse> {
-    #[cfg(not(test))]
-    {
-        use crate::storage::keychain::SystemKeychain;
-        use crate::storage::KeychainManager;
-        let manager = KeychainManager::new(SystemKeychain);
-
-        // Check if key exists first
-        let existed = manager.has_secret_key();
+pub fn get_device_keys(state: tauri::State<'_, AppState>) -> CommandResult<DeviceKeysResponse> {
+    let manager = state
+        .inner()
+        .profile_keychain(&state.inner().active_profile()?)?;
 
-        let secret_key = manager.get_or_create_secret_key()?;
+    // Check if key exists first
+    let existed = manager.has_secret_key();
 
-        Ok(DeviceKeysResponse {
-            secret_key,
-            newly_generated: !existed,
-        })
-    }
+    let secret_key = manager.get_or_create_secret_key()?;
 
-    #[cfg(test)]
-    {
-        // In tests, return a mock response
-        Ok(DeviceKeysResponse {
-            secret_key: "dGVzdC1zZWNyZXQta2V5".to_string(),
-            newly_generated: false,
-        })
-    }
+    Ok(DeviceKeysResponse {
+        secret_key,
+        newly_generated: !existed,
+    })
 }
 
-/// Check if device keys exist in the keychain.
+/// Check if the active profile has device keys in the keychain.
 #[tauri::command]
-pub fn has_device_keys() -> CommandResult<bool> {
-    #[cfg(not(test))]
-    {
-        use crate::storage::keychain::SystemKeychain;
-        use crate::storage::KeychainManager;
-        let manager = KeychainManager::new(SystemKeychain);
-        Ok(manager.has_secret_key())
-    }
-
-    #[cfg(test)]
-    {
-        Ok(false)
-    }
+pub fn has_device_keys(state: tauri::State<'_, AppState>) -> CommandResult<bool> {
+    let manager = state
+        .inner()
+        .profile_keychain(&state.inner().active_profile()?)?;
+    Ok(manager.has_secret_key())
 }
 
 // ============================================================================
@@ -431,6 +660,7 @@ pub async fn store_paired_device(
         public_key: request.public_key,
         created_at: now,
         last_seen: None,
+        mac_address: None,
     };
 
     db.add_paired_device(&device)?;
@@ -512,149 +742,1107 @@ pub async fn update_device_last_seen(
 }
 
 // ============================================================================
-// Notification Commands
+// Wake-on-LAN Commands
 // ============================================================================
 
-/// Request payload for showing a notification.
+/// Request payload for storing a device's MAC address.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NotificationRequest {
-    /// The notification title.
-    pub title: String,
-    /// The notification body text.
-    pub body: String,
-    /// Optional icon name or path.
-    pub icon: Option<String>,
+pub struct SetMacAddressRequest {
+    /// The paired device.
+    pub device_id: String,
+    /// MAC address of the device's host, or `None` to clear it.
+    pub mac_address: Option<String>,
 }
 
-/// Response from showing a notification.
+/// Store the MAC address used to wake a paired device's host.
+///
+/// The address is normalized to lowercase, colon-separated hex. Returns
+/// false if the device is not paired.
+#[tauri::command]
+pub async fn set_device_mac_address(
+    state: tauri::State<'_, AppState>,
+    request: SetMacAddressRequest,
+) -> CommandResult<bool> {
+    let mac_address = request
+        .mac_address
+        .as_deref()
+        .map(|text| wol::parse_mac_address(text).map(|mac| wol::format_mac_address(&mac)))
+        .transpose()?;
+
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    let updated = db.update_device_mac_address(&request.device_id, mac_address.as_deref())?;
+    Ok(updated)
+}
+
+/// Request payload for waking a device.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NotificationResponse {
-    /// Whether the notification was shown successfully.
-    pub shown: bool,
+pub struct WakeDeviceRequest {
+    /// The paired device to wake.
+    pub device_id: String,
+    /// Address to send the magic packet to (e.g. `192.168.1.255:9`).
+    /// Defaults to `255.255.255.255:9`.
+    pub broadcast_address: Option<String>,
 }
 
-/// Show a native notification.
+/// Wake a paired device's host by broadcasting a Wake-on-LAN magic packet.
 ///
-/// This command displays a native system notification. On platforms that
-/// support it, notifications may include actions and rich content.
+/// The device must have a stored MAC address and be on the local network.
 #[tauri::command]
-pub async fn show_native_notification(
-    app: tauri::AppHandle,
-    request: NotificationRequest,
-) -> CommandResult<NotificationResponse> {
-    // Get the notification plugin
-    #[cfg(feature = "notification")]
-    {
-        use tauri_plugin_notification::NotificationExt;
-
-        let mut notification = app.notification().builder();
-        notification = notification.title(&request.title).body(&request.body);
-
-        if let Some(icon) = &request.icon {
-            notification = notification.icon(icon);
-        }
+pub async fn wake_device(
+    state: tauri::State<'_, AppState>,
+    request: WakeDeviceRequest,
+) -> CommandResult<()> {
+    let target = match &request.broadcast_address {
+        Some(addr) => addr
+            .parse::<std::net::SocketAddr>()
+            .map_err(|_| CommandError {
+                code: "INVALID_ADDRESS".to_string(),
+                message: format!("Invalid broadcast address: {}", addr),
+            })?,
+        None => wol::DEFAULT_BROADCAST_ADDR,
+    };
 
-        notification.show().map_err(|e| CommandError {
-            code: "NOTIFICATION_ERROR".to_string(),
-            message: format!("Failed to show notification: {}", e),
+    // Release the database lock before sending
+    let mac_address = {
+        let guard = state.inner().database.lock().map_err(|_| CommandError {
+            code: "DATABASE_LOCK_ERROR".to_string(),
+            message: "Failed to acquire database lock".to_string(),
+        })?;
+        let db = guard.as_ref().ok_or_else(|| CommandError {
+            code: "NOT_INITIALIZED".to_string(),
+            message: "Database not initialized".to_string(),
         })?;
 
-        Ok(NotificationResponse { shown: true })
-    }
-
-    // Fallback when notification feature is not enabled
-    #[cfg(not(feature = "notification"))]
-    {
-        // Log that we would show a notification
-        let _ = app; // Suppress unused warning
-        tracing::info!(
-            "Notification requested (feature disabled): {} - {}",
-            request.title,
-            request.body
-        );
+        db.get_paired_device(&request.device_id)?
+            .ok_or_else(|| CommandError {
+                code: "DEVICE_NOT_FOUND".to_string(),
+                message: format!("No paired device {}", request.device_id),
+            })?
+            .mac_address
+            .ok_or_else(|| CommandError {
+                code: "NO_MAC_ADDRESS".to_string(),
+                message: "No MAC address is stored for this device".to_string(),
+            })?
+    };
 
-        Ok(NotificationResponse { shown: false })
-    }
+    let mac = wol::parse_mac_address(&mac_address)?;
+    wol::send_magic_packet(&mac, target).await?;
+    Ok(())
 }
 
 // ============================================================================
-// Initialization Command
+// Database Commands
 // ============================================================================
 
-/// Request payload for initializing the application.
+/// Response with the schema migration status of the database.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InitRequest {
-    /// Path to the database file.
-    pub database_path: String,
-    /// Optional relay URL for QUIC connections.
-    pub relay_url: Option<String>,
+pub struct MigrationStatusResponse {
+    /// The schema version of the open database.
+    pub current_version: i32,
+    /// The schema version this build migrates to.
+    pub latest_version: i32,
+    /// Migrations that have not been applied.
+    pub pending: Vec<MigrationInfo>,
 }
 
-/// Response from initialization.
+/// Get the schema migrations that have not been applied to the database.
+///
+/// Migrations run when the database is opened, so this is normally empty;
+/// it is exposed for diagnostics after a failed or rolled back migration.
+#[tauri::command]
+pub async fn get_pending_migrations(
+    state: tauri::State<'_, AppState>,
+) -> CommandResult<MigrationStatusResponse> {
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    Ok(MigrationStatusResponse {
+        current_version: db.get_schema_version()?,
+        latest_version: crate::storage::migrations::latest_version(),
+        pending: db.pending_migrations()?,
+    })
+}
+
+// ============================================================================
+// Connection History Commands
+// ============================================================================
+
+/// Default analytics window in days.
+const DEFAULT_ANALYTICS_DAYS: u32 = 30;
+
+/// Response with per-device connection statistics.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InitResponse {
-    /// Whether initialization was successful.
-    pub initialized: bool,
-    /// The local node ID for QUIC connections.
-    pub node_id: String,
+pub struct ConnectionAnalyticsResponse {
+    /// Length of the analytics window in days.
+    pub days: u32,
+    /// Unix timestamp of the start of the window.
+    pub since: i64,
+    /// Statistics for each paired device.
+    pub devices: Vec<DeviceConnectionStats>,
 }
 
-/// Initialize the application state.
+/// Get per-device connection statistics for the dashboard.
 ///
-/// This command initializes the database and QUIC manager. It should be
-/// called once when the application starts.
+/// Covers the last `days` days (30 by default).
 #[tauri::command]
-pub async fn initialize_app(
+pub async fn get_connection_analytics(
     state: tauri::State<'_, AppState>,
-    request: InitRequest,
-) -> CommandResult<InitResponse> {
-    // Initialize the database
-    state.inner().init_database(&request.database_path)?;
+    days: Option<u32>,
+) -> CommandResult<ConnectionAnalyticsResponse> {
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
 
-    // Create QUIC configuration
-    let config = if let Some(url) = &request.relay_url {
-        let relay_url = url.parse().map_err(|e| CommandError {
-            code: "INVALID_RELAY_URL".to_string(),
-            message: format!("Invalid relay URL: {}", e),
-        })?;
-        QuicConfig::with_relay(relay_url)
-    } else {
-        QuicConfig::default()
-    };
+    let days = days.unwrap_or(DEFAULT_ANALYTICS_DAYS);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let since = now - i64::from(days) * 24 * 60 * 60;
 
-    // Initialize the QUIC manager
-    state.inner().init_quic(config).await?;
+    Ok(ConnectionAnalyticsResponse {
+        days,
+        since,
+        devices: db.connection_analytics(since)?,
+    })
+}
 
-    // Get the local node ID
-    let guard = state.inner().quic_manager.read().await;
-    let manager = guard.as_ref().ok_or_else(|| CommandError {
-        code: "INIT_FAILED".to_string(),
-        message: "QUIC manager initialization failed".to_string(),
+/// Get the connection history retention policy.
+#[tauri::command]
+pub async fn get_connection_retention(
+    state: tauri::State<'_, AppState>,
+) -> CommandResult<RetentionPolicy> {
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
     })?;
-    let node_id = manager.node_id().to_string();
 
-    Ok(InitResponse {
-        initialized: true,
-        node_id,
-    })
+    Ok(db.retention_policy()?)
+}
+
+/// Set the connection history retention policy.
+///
+/// The policy is applied immediately; returns the number of entries removed.
+#[tauri::command]
+pub async fn set_connection_retention(
+    state: tauri::State<'_, AppState>,
+    policy: RetentionPolicy,
+) -> CommandResult<usize> {
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    Ok(db.set_retention_policy(&policy)?)
 }
 
 // ============================================================================
-// Tests
+// Identity Backup Commands
 // ============================================================================
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_command_error_display() {
-        let error = CommandError {
-            code: "TEST_ERROR".to_string(),
-            message: "Test error message".to_string(),
-        };
-        assert_eq!(format!("{}", error), "TEST_ERROR: Test error message");
-    }
+/// Request payload for exporting the client identity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportIdentityRequest {
+    /// Path of the backup file to write.
+    pub path: String,
+    /// Passphrase used to encrypt the backup.
+    pub passphrase: String,
+}
+
+/// Response from exporting the client identity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportIdentityResponse {
+    /// Path of the written backup file.
+    pub path: String,
+    /// Number of paired devices included in the backup.
+    pub device_count: usize,
+}
+
+/// Request payload for importing a client identity backup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportIdentityRequest {
+    /// Path of the backup file to read.
+    pub path: String,
+    /// Passphrase the backup was encrypted with.
+    pub passphrase: String,
+    /// Replace an existing, different device key in the keychain.
+    #[serde(default)]
+    pub replace_identity: bool,
+}
+
+/// Response from importing a client identity backup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportIdentityResponse {
+    /// Number of paired devices added to the database.
+    pub imported_devices: usize,
+    /// Number of paired devices skipped because they were already present.
+    pub skipped_devices: usize,
+    /// Whether the device key in the keychain was written or replaced.
+    pub identity_restored: bool,
+}
+
+/// Collect the device key and paired devices into a backup.
+fn create_identity_backup<B: KeychainBackend>(
+    keychain: &KeychainManager<B>,
+    db: &Database,
+) -> CommandResult<IdentityBackup> {
+    let secret_key = keychain.get_secret_key()?;
+    let devices = db.list_paired_devices()?;
+    Ok(IdentityBackup::new(secret_key, devices))
+}
+
+/// Write a decrypted backup into the keychain and database.
+///
+/// Paired devices already present in the database are kept as they are. An
+/// existing device key that differs from the backup is only replaced when
+/// `replace_identity` is set, since replacing it invalidates the pairings
+/// made with that key.
+fn restore_identity_backup<B: KeychainBackend>(
+    keychain: &KeychainManager<B>,
+    db: &Database,
+    backup: &IdentityBackup,
+    replace_identity: bool,
+) -> CommandResult<ImportIdentityResponse> {
+    let identity_restored = match keychain.get_secret_key() {
+        Ok(existing) if existing == backup.secret_key => false,
+        Ok(_) if !replace_identity => {
+            return Err(CommandError {
+                code: "IDENTITY_EXISTS".to_string(),
+                message: "A different device key already exists in the keychain".to_string(),
+            });
+        }
+        Ok(_) | Err(KeychainError::NotFound(_)) => {
+            keychain.store_secret_key(&backup.secret_key)?;
+            true
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut imported_devices = 0;
+    let mut skipped_devices = 0;
+    for device in &backup.paired_devices {
+        if db.get_paired_device(&device.id)?.is_some() {
+            skipped_devices += 1;
+        } else {
+            db.add_paired_device(device)?;
+            imported_devices += 1;
+        }
+    }
+
+    Ok(ImportIdentityResponse {
+        imported_devices,
+        skipped_devices,
+        identity_restored,
+    })
+}
+
+/// Export the active profile's device key and paired devices to an
+/// encrypted backup file.
+///
+/// The backup can be restored on another machine with `import_identity`
+/// to keep existing pairings without pairing again.
+#[tauri::command]
+pub async fn export_identity(
+    state: tauri::State<'_, AppState>,
+    request: ExportIdentityRequest,
+) -> CommandResult<ExportIdentityResponse> {
+    let profile = state.inner().active_profile()?;
+    let backup = {
+        let guard = state.inner().database.lock().map_err(|_| CommandError {
+            code: "DATABASE_LOCK_ERROR".to_string(),
+            message: "Failed to acquire database lock".to_string(),
+        })?;
+        let db = guard.as_ref().ok_or_else(|| CommandError {
+            code: "NOT_INITIALIZED".to_string(),
+            message: "Database not initialized".to_string(),
+        })?;
+
+        create_identity_backup(&state.inner().profile_keychain(&profile)?, db)?
+    };
+
+    let bundle = backup.encrypt(&request.passphrase)?;
+    std::fs::write(&request.path, bundle).map_err(|e| CommandError {
+        code: "IO_ERROR".to_string(),
+        message: format!("Failed to write backup file: {}", e),
+    })?;
+
+    Ok(ExportIdentityResponse {
+        path: request.path,
+        device_count: backup.paired_devices.len(),
+    })
+}
+
+/// Restore a device key and paired devices from an encrypted backup file
+/// into the active profile.
+#[tauri::command]
+pub async fn import_identity(
+    state: tauri::State<'_, AppState>,
+    request: ImportIdentityRequest,
+) -> CommandResult<ImportIdentityResponse> {
+    let bundle = std::fs::read(&request.path).map_err(|e| CommandError {
+        code: "IO_ERROR".to_string(),
+        message: format!("Failed to read backup file: {}", e),
+    })?;
+    let backup = IdentityBackup::decrypt(&bundle, &request.passphrase)?;
+    let profile = state.inner().active_profile()?;
+
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    restore_identity_backup(
+        &state.inner().profile_keychain(&profile)?,
+        db,
+        &backup,
+        request.replace_identity,
+    )
+}
+
+// ============================================================================
+// Profile Commands
+// ============================================================================
+
+/// Information about an identity profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileInfo {
+    /// The profile name.
+    pub name: String,
+    /// Whether this is the active profile.
+    pub active: bool,
+    /// Whether the profile has a device key in the keychain.
+    pub has_device_keys: bool,
+}
+
+fn profile_info(state: &AppState, name: &str, active: &str) -> CommandResult<ProfileInfo> {
+    Ok(ProfileInfo {
+        name: name.to_string(),
+        active: name == active,
+        has_device_keys: state.profile_keychain(name)?.has_secret_key(),
+    })
+}
+
+/// List all identity profiles.
+#[tauri::command]
+pub async fn list_profiles(state: tauri::State<'_, AppState>) -> CommandResult<Vec<ProfileInfo>> {
+    let store = state.inner().profile_store()?;
+    let active = state.inner().active_profile()?;
+
+    store
+        .list()?
+        .iter()
+        .map(|name| profile_info(state.inner(), name, &active))
+        .collect()
+}
+
+/// Create a new identity profile.
+///
+/// The profile gets its own database and a newly generated device key. The
+/// active profile is not changed.
+#[tauri::command]
+pub async fn create_profile(
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> CommandResult<ProfileInfo> {
+    let store = state.inner().profile_store()?;
+    let path = store.prepare(&name)?;
+    state.inner().open_database(&path, &name)?;
+    state
+        .inner()
+        .profile_keychain(&name)?
+        .get_or_create_secret_key()?;
+
+    profile_info(state.inner(), &name, &state.inner().active_profile()?)
+}
+
+/// Switch to another identity profile.
+///
+/// Any QUIC connection is closed first, since it was authenticated with the
+/// previous profile's identity.
+#[tauri::command]
+pub async fn switch_profile(
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> CommandResult<ProfileInfo> {
+    let store = state.inner().profile_store()?;
+    if !store.exists(&name) {
+        return Err(ProfileError::NotFound(name).into());
+    }
+
+    if let Some(manager) = state.inner().quic_manager.read().await.as_ref() {
+        manager.disconnect().await?;
+    }
+
+    state.inner().open_profile(&store, &name)?;
+    tracing::info!("Switched to profile {}", name);
+
+    profile_info(state.inner(), &name, &name)
+}
+
+/// Delete an identity profile, its database and its keys.
+///
+/// The default profile and the active profile cannot be deleted.
+#[tauri::command]
+pub async fn delete_profile(
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> CommandResult<bool> {
+    let store = state.inner().profile_store()?;
+    if name == state.inner().active_profile()? {
+        return Err(ProfileError::NotAllowed(
+            name,
+            "the active profile cannot be deleted".to_string(),
+        )
+        .into());
+    }
+
+    store.remove(&name)?;
+    for keychain in [
+        state.inner().profile_keychain(&name)?,
+        state.inner().database_keychain(&name)?,
+    ] {
+        match keychain.delete_secret_key() {
+            Ok(()) | Err(KeychainError::NotFound(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+// ============================================================================
+// SSH Agent Forwarding Commands
+// ============================================================================
+
+/// Name of the event carrying [`AgentEvent`]s to the frontend.
+pub const AGENT_EVENT: &str = "agent_event";
+
+/// Identifies a forwarded agent channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentChannelRequest {
+    /// Session the channel belongs to.
+    pub session_id: String,
+    /// Channel identifier from the daemon's `AgentOpen` message.
+    pub channel_id: u32,
+}
+
+/// Request payload for relaying data to the local agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentWriteRequest {
+    /// Session the channel belongs to.
+    pub session_id: String,
+    /// Channel identifier.
+    pub channel_id: u32,
+    /// The data to relay (base64-encoded).
+    pub data: String,
+}
+
+/// Check whether a local SSH agent is available for forwarding.
+#[tauri::command]
+pub async fn has_ssh_agent(state: tauri::State<'_, AppState>) -> CommandResult<bool> {
+    Ok(state.inner().agent.is_available())
+}
+
+/// Open a channel to the local SSH agent for a forwarded connection.
+///
+/// The frontend calls this after the user confirms an `AgentOpen` request.
+/// Replies from the agent are emitted as `agent_event` events.
+#[tauri::command]
+pub async fn agent_connect(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    request: AgentChannelRequest,
+) -> CommandResult<()> {
+    use tauri::Emitter;
+
+    state
+        .inner()
+        .agent
+        .connect(
+            &request.session_id,
+            request.channel_id,
+            move |event: AgentEvent| {
+                if let Err(e) = app.emit(AGENT_EVENT, event) {
+                    tracing::warn!("Failed to emit agent event: {}", e);
+                }
+            },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Relay data from the daemon to the local SSH agent.
+#[tauri::command]
+pub async fn agent_write(
+    state: tauri::State<'_, AppState>,
+    request: AgentWriteRequest,
+) -> CommandResult<()> {
+    let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &request.data)
+        .map_err(|e| CommandError {
+            code: "INVALID_DATA".to_string(),
+            message: format!("Invalid base64 data: {}", e),
+        })?;
+
+    state
+        .inner()
+        .agent
+        .write(&request.session_id, request.channel_id, data)?;
+    Ok(())
+}
+
+/// Close a forwarded agent channel.
+#[tauri::command]
+pub async fn agent_close(
+    state: tauri::State<'_, AppState>,
+    request: AgentChannelRequest,
+) -> CommandResult<()> {
+    state
+        .inner()
+        .agent
+        .close(&request.session_id, request.channel_id);
+    Ok(())
+}
+
+// ============================================================================
+// Remote Host Commands
+// ============================================================================
+
+/// How long to wait for the daemon to answer a host information request.
+const SYSTEM_INFO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Get host information from the connected daemon.
+///
+/// This command sends a `SystemInfoRequest` on the control channel and waits
+/// for the daemon's `SystemInfo` reply, for showing host health without
+/// opening a shell.
+#[tauri::command]
+pub async fn get_remote_system_info(
+    state: tauri::State<'_, AppState>,
+) -> CommandResult<SystemInfo> {
+    let guard = state.inner().quic_manager.read().await;
+    let manager = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "QUIC manager not initialized".to_string(),
+    })?;
+
+    let request = Envelope::new(0, Message::SystemInfoRequest(SystemInfoRequest {}));
+    let data = request.to_msgpack().map_err(|e| CommandError {
+        code: "PROTOCOL_ERROR".to_string(),
+        message: e.to_string(),
+    })?;
+    manager.send(ChannelType::Control, &data).await?;
+
+    tokio::time::timeout(SYSTEM_INFO_TIMEOUT, recv_system_info(manager))
+        .await
+        .map_err(|_| CommandError {
+            code: "TIMEOUT".to_string(),
+            message: "Daemon did not answer the system information request".to_string(),
+        })?
+}
+
+/// Waits for the reply to a `SystemInfoRequest`, skipping other messages.
+async fn recv_system_info(manager: &QuicManager) -> CommandResult<SystemInfo> {
+    loop {
+        let data = manager.recv(ChannelType::Control).await?;
+        let Ok(envelope) = Envelope::from_msgpack(&data) else {
+            continue;
+        };
+        match envelope.payload {
+            Message::SystemInfo(info) => return Ok(info),
+            Message::Error(e) => {
+                return Err(CommandError {
+                    code: "REMOTE_ERROR".to_string(),
+                    message: e.message,
+                })
+            }
+            _ => continue,
+        }
+    }
+}
+
+// ============================================================================
+// Session Recording Commands
+// ============================================================================
+
+/// Default terminal size for recordings when the frontend does not pass one.
+const DEFAULT_RECORDING_SIZE: (u16, u16) = (80, 24);
+
+/// Request payload for starting a recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartRecordingRequest {
+    /// The session to record.
+    pub session_id: String,
+    /// The recording format.
+    pub format: RecordingFormat,
+    /// Current terminal columns.
+    pub cols: Option<u16>,
+    /// Current terminal rows.
+    pub rows: Option<u16>,
+}
+
+/// Request payload for exporting a recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecordingRequest {
+    /// ID of the recording.
+    pub id: String,
+    /// Path to write the export to.
+    pub path: String,
+    /// Format to export as (defaults to the recording's format).
+    pub format: Option<RecordingFormat>,
+}
+
+/// Response from exporting a recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecordingResponse {
+    /// Path the export was written to.
+    pub path: String,
+    /// Size of the export in bytes.
+    pub size_bytes: u64,
+}
+
+/// Start recording the output of an attached session.
+///
+/// The recording is written under the app data directory and stops when
+/// `stop_recording` is called or the session closes. Output matching the
+/// configured redaction patterns is replaced before it is written.
+#[tauri::command]
+pub async fn start_recording(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    request: StartRecordingRequest,
+) -> CommandResult<RecordingEntry> {
+    use tauri::Manager;
+
+    let events = {
+        let guard = state.inner().quic_manager.read().await;
+        let manager = guard.as_ref().ok_or_else(|| CommandError {
+            code: "NOT_INITIALIZED".to_string(),
+            message: "QUIC manager not initialized".to_string(),
+        })?;
+        manager.subscribe()
+    };
+
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| CommandError {
+            code: "IO_ERROR".to_string(),
+            message: format!("Failed to locate app data directory: {}", e),
+        })?
+        .join("recordings");
+    let id = uuid::Uuid::new_v4().to_string();
+    let path = dir.join(format!("{}.{}", id, request.format.extension()));
+
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    let redactor = match db.redaction_patterns()? {
+        Some(patterns) => Redactor::new(&patterns)?,
+        None => Redactor::with_defaults(),
+    };
+    let size = (
+        request.cols.unwrap_or(DEFAULT_RECORDING_SIZE.0),
+        request.rows.unwrap_or(DEFAULT_RECORDING_SIZE.1),
+    );
+    let recorder = Recorder::create(&path, &request.session_id, request.format, redactor, size)?;
+
+    let entry = RecordingEntry {
+        id: id.clone(),
+        session_id: request.session_id,
+        format: request.format.as_str().to_string(),
+        path: path.to_string_lossy().into_owned(),
+        started_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64,
+        stopped_at: None,
+        size_bytes: 0,
+    };
+    db.add_recording(&entry)?;
+
+    let active = recording::spawn_recorder(
+        id.clone(),
+        recorder,
+        events,
+        Arc::clone(&state.inner().database),
+    );
+    let mut recordings = state
+        .inner()
+        .recordings
+        .lock()
+        .map_err(|_| state_lock_error())?;
+    recordings.retain(|_, recording| !recording.is_finished());
+    recordings.insert(id, active);
+
+    Ok(entry)
+}
+
+/// Stop a recording.
+///
+/// Returns the recording once its file is closed. Stopping a recording that
+/// already stopped on its own returns it unchanged.
+#[tauri::command]
+pub async fn stop_recording(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> CommandResult<RecordingEntry> {
+    let active = state
+        .inner()
+        .recordings
+        .lock()
+        .map_err(|_| state_lock_error())?
+        .remove(&id);
+    if let Some(active) = active {
+        active.stop().await;
+    }
+
+    get_recording_entry(state.inner(), &id)
+}
+
+/// List recordings, newest first.
+#[tauri::command]
+pub async fn list_recordings(
+    state: tauri::State<'_, AppState>,
+) -> CommandResult<Vec<RecordingEntry>> {
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    Ok(db.list_recordings()?)
+}
+
+/// Copy a stopped recording to a path chosen by the user.
+///
+/// asciicast recordings can also be exported as plain text.
+#[tauri::command]
+pub async fn export_recording(
+    state: tauri::State<'_, AppState>,
+    request: ExportRecordingRequest,
+) -> CommandResult<ExportRecordingResponse> {
+    let entry = get_recording_entry(state.inner(), &request.id)?;
+    if entry.stopped_at.is_none() {
+        return Err(CommandError {
+            code: "RECORDING_ACTIVE".to_string(),
+            message: format!("Recording {} has not been stopped", entry.id),
+        });
+    }
+
+    let from = RecordingFormat::from_name(&entry.format).ok_or_else(|| CommandError {
+        code: "DATABASE_ERROR".to_string(),
+        message: format!("Unknown recording format {:?}", entry.format),
+    })?;
+    let size_bytes = recording::export(
+        Path::new(&entry.path),
+        from,
+        Path::new(&request.path),
+        request.format.unwrap_or(from),
+    )?;
+
+    Ok(ExportRecordingResponse {
+        path: request.path,
+        size_bytes,
+    })
+}
+
+/// Delete a stopped recording and its file.
+#[tauri::command]
+pub async fn delete_recording(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> CommandResult<bool> {
+    let entry = match get_recording_entry(state.inner(), &id) {
+        Ok(entry) => entry,
+        Err(e) if e.code == "RECORDING_NOT_FOUND" => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if entry.stopped_at.is_none() {
+        return Err(CommandError {
+            code: "RECORDING_ACTIVE".to_string(),
+            message: format!("Recording {} has not been stopped", entry.id),
+        });
+    }
+
+    match std::fs::remove_file(&entry.path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(RecordingError::Io(e).into()),
+    }
+
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    Ok(db.remove_recording(&id)?)
+}
+
+/// Get the secret patterns redacted from new recordings.
+///
+/// Returns the built-in patterns until others are set.
+#[tauri::command]
+pub async fn get_recording_redactions(
+    state: tauri::State<'_, AppState>,
+) -> CommandResult<Vec<String>> {
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    Ok(db.redaction_patterns()?.unwrap_or_else(|| {
+        DEFAULT_REDACTION_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .collect()
+    }))
+}
+
+/// Set the secret patterns redacted from new recordings.
+///
+/// Patterns are regular expressions; an invalid pattern is rejected and
+/// nothing is saved. Recordings in progress keep their patterns.
+#[tauri::command]
+pub async fn set_recording_redactions(
+    state: tauri::State<'_, AppState>,
+    patterns: Vec<String>,
+) -> CommandResult<()> {
+    Redactor::new(&patterns)?;
+
+    let guard = state.inner().database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    Ok(db.set_redaction_patterns(&patterns)?)
+}
+
+/// Looks up a recording in the index.
+fn get_recording_entry(state: &AppState, id: &str) -> CommandResult<RecordingEntry> {
+    let guard = state.database.lock().map_err(|_| CommandError {
+        code: "DATABASE_LOCK_ERROR".to_string(),
+        message: "Failed to acquire database lock".to_string(),
+    })?;
+    let db = guard.as_ref().ok_or_else(|| CommandError {
+        code: "NOT_INITIALIZED".to_string(),
+        message: "Database not initialized".to_string(),
+    })?;
+
+    db.get_recording(id)?.ok_or_else(|| CommandError {
+        code: "RECORDING_NOT_FOUND".to_string(),
+        message: format!("No recording with ID {}", id),
+    })
+}
+
+// ============================================================================
+// Notification Commands
+// ============================================================================
+
+/// Request payload for showing a notification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationRequest {
+    /// The notification title.
+    pub title: String,
+    /// The notification body text.
+    pub body: String,
+    /// Optional icon name or path.
+    pub icon: Option<String>,
+}
+
+/// Response from showing a notification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationResponse {
+    /// Whether the notification was shown successfully.
+    pub shown: bool,
+}
+
+/// Show a native notification.
+///
+/// This command displays a native system notification. On platforms that
+/// support it, notifications may include actions and rich content.
+#[tauri::command]
+pub async fn show_native_notification(
+    app: tauri::AppHandle,
+    request: NotificationRequest,
+) -> CommandResult<NotificationResponse> {
+    let shown = notifications::show_os_notification(
+        &app,
+        &request.title,
+        &request.body,
+        request.icon.as_deref(),
+    )
+    .map_err(|e| CommandError {
+        code: "NOTIFICATION_ERROR".to_string(),
+        message: format!("Failed to show notification: {}", e),
+    })?;
+
+    Ok(NotificationResponse { shown })
+}
+
+// ============================================================================
+// Deep Link Commands
+// ============================================================================
+
+/// Open a `remoshell://` link.
+///
+/// Used when the frontend receives a link itself, such as a scanned QR code
+/// on mobile. The parsed link is returned and also emitted as a `deep_link`
+/// event, the same as links opened by the OS.
+#[tauri::command]
+pub async fn handle_deep_link(app: tauri::AppHandle, url: String) -> CommandResult<DeepLink> {
+    Ok(deep_link::dispatch(&app, &url)?)
+}
+
+// ============================================================================
+// Initialization Command
+// ============================================================================
+
+/// Request payload for initializing the application.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitRequest {
+    /// Path to the database file.
+    pub database_path: String,
+    /// Optional relay URL for QUIC connections.
+    pub relay_url: Option<String>,
+    /// Identity profile to activate (defaults to the default profile).
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Encrypt the database at rest, migrating an unencrypted database.
+    ///
+    /// Requires a build with the `sqlcipher` feature.
+    #[serde(default)]
+    pub encrypt_database: bool,
+}
+
+/// Response from initialization.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitResponse {
+    /// Whether initialization was successful.
+    pub initialized: bool,
+    /// The local node ID for QUIC connections.
+    pub node_id: String,
+    /// The active identity profile.
+    pub profile: String,
+}
+
+/// Initialize the application state.
+///
+/// This command initializes the database and QUIC manager. It should be
+/// called once when the application starts. Notifications pushed by the
+/// daemon are shown and emitted as `daemon_notification` events from then on.
+#[tauri::command]
+pub async fn initialize_app(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    request: InitRequest,
+) -> CommandResult<InitResponse> {
+    // Initialize the profiles and open the active profile's database
+    let profile = request.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+    state
+        .inner()
+        .encrypt_database
+        .store(request.encrypt_database, Ordering::SeqCst);
+    state
+        .inner()
+        .init_profile(&request.database_path, profile)?;
+
+    // Create QUIC configuration
+    let config = if let Some(url) = &request.relay_url {
+        let relay_url = url.parse().map_err(|e| CommandError {
+            code: "INVALID_RELAY_URL".to_string(),
+            message: format!("Invalid relay URL: {}", e),
+        })?;
+        QuicConfig::with_relay(relay_url)
+    } else {
+        QuicConfig::default()
+    };
+
+    // Initialize the QUIC manager
+    state.inner().init_quic(config).await?;
+
+    // Get the local node ID
+    let guard = state.inner().quic_manager.read().await;
+    let manager = guard.as_ref().ok_or_else(|| CommandError {
+        code: "INIT_FAILED".to_string(),
+        message: "QUIC manager initialization failed".to_string(),
+    })?;
+    let node_id = manager.node_id().to_string();
+    notifications::spawn_listener(app, manager.subscribe());
+
+    Ok(InitResponse {
+        initialized: true,
+        node_id,
+        profile: profile.to_string(),
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_error_display() {
+        let error = CommandError {
+            code: "TEST_ERROR".to_string(),
+            message: "Test error message".to_string(),
+        };
+        assert_eq!(format!("{}", error), "TEST_ERROR: Test error message");
+    }
 
     #[test]
     fn test_connect_request_serialization() {
@@ -698,6 +1886,56 @@ mod tests {
         // Verify state is created with None values
         assert!(state.quic_manager.try_read().is_ok());
         assert!(state.database.lock().is_ok());
+        assert_eq!(state.active_profile().unwrap(), DEFAULT_PROFILE);
+    }
+
+    #[test]
+    fn test_app_state_switch_profile() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("remoshell.db");
+        let state = AppState::new();
+        state
+            .init_profile(db_path.to_str().unwrap(), DEFAULT_PROFILE)
+            .unwrap();
+
+        // Profiles must exist before they can be opened
+        let error = state.init_profile(db_path.to_str().unwrap(), "work");
+        assert_eq!(error.unwrap_err().code, "PROFILE_NOT_FOUND");
+
+        let store = state.profile_store().unwrap();
+        Database::open(store.prepare("work").unwrap()).unwrap();
+        {
+            let guard = state.database.lock().unwrap();
+            let device = PairedDevice {
+                id: "device-1".to_string(),
+                name: "Default Device".to_string(),
+                public_key: "key-1".to_string(),
+                created_at: 0,
+                last_seen: None,
+                mac_address: None,
+            };
+            guard.as_ref().unwrap().add_paired_device(&device).unwrap();
+        }
+
+        // Each profile sees only its own paired devices
+        state.open_profile(&store, "work").unwrap();
+        assert_eq!(state.active_profile().unwrap(), "work");
+        let guard = state.database.lock().unwrap();
+        assert!(guard
+            .as_ref()
+            .unwrap()
+            .list_paired_devices()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_init_request_default_profile() {
+        let request: InitRequest =
+            serde_json::from_str(r#"{"database_path":"/tmp/remoshell.db","relay_url":null}"#)
+                .expect("Failed to deserialize");
+        assert!(request.profile.is_none());
+        assert!(!request.encrypt_database);
     }
 
     #[test]
@@ -736,4 +1974,71 @@ mod tests {
         assert!(json.contains("peer-123"));
         assert!(json.contains("local-456"));
     }
+
+    fn backup_device(id: &str) -> PairedDevice {
+        PairedDevice {
+            id: id.to_string(),
+            name: format!("Device {}", id),
+            public_key: format!("key-{}", id),
+            created_at: 1_700_000_000,
+            last_seen: None,
+            mac_address: None,
+        }
+    }
+
+    #[test]
+    fn test_identity_backup_round_trip() {
+        use crate::storage::keychain::MockKeychain;
+
+        let source_db = Database::open_in_memory().unwrap();
+        source_db.add_paired_device(&backup_device("a")).unwrap();
+        source_db.add_paired_device(&backup_device("b")).unwrap();
+        let source_keychain = KeychainManager::new(MockKeychain::new());
+        let secret_key = source_keychain.get_or_create_secret_key().unwrap();
+
+        let backup = create_identity_backup(&source_keychain, &source_db).unwrap();
+
+        // Restore onto a machine that already knows one of the devices
+        let target_db = Database::open_in_memory().unwrap();
+        target_db.add_paired_device(&backup_device("a")).unwrap();
+        let target_keychain = KeychainManager::new(MockKeychain::new());
+
+        let response =
+            restore_identity_backup(&target_keychain, &target_db, &backup, false).unwrap();
+        assert!(response.identity_restored);
+        assert_eq!(response.imported_devices, 1);
+        assert_eq!(response.skipped_devices, 1);
+        assert_eq!(target_keychain.get_secret_key().unwrap(), secret_key);
+        assert_eq!(target_db.list_paired_devices().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_identity_restore_keeps_different_key() {
+        use crate::storage::keychain::MockKeychain;
+
+        let db = Database::open_in_memory().unwrap();
+        let keychain = KeychainManager::new(MockKeychain::new());
+        let existing = keychain.get_or_create_secret_key().unwrap();
+        let backup = IdentityBackup::new(
+            crate::storage::encode_secret_key(&[1u8; 32]),
+            vec![backup_device("a")],
+        );
+
+        let error = restore_identity_backup(&keychain, &db, &backup, false).unwrap_err();
+        assert_eq!(error.code, "IDENTITY_EXISTS");
+        assert_eq!(keychain.get_secret_key().unwrap(), existing);
+        assert!(db.list_paired_devices().unwrap().is_empty());
+
+        let response = restore_identity_backup(&keychain, &db, &backup, true).unwrap();
+        assert!(response.identity_restored);
+        assert_eq!(keychain.get_secret_key().unwrap(), backup.secret_key);
+    }
+
+    #[test]
+    fn test_import_identity_request_defaults() {
+        let request: ImportIdentityRequest =
+            serde_json::from_str(r#"{"path":"/tmp/backup.json","passphrase":"secret"}"#)
+                .expect("Failed to deserialize");
+        assert!(!request.replace_identity);
+    }
 }
@@ -0,0 +1,357 @@
+//! `remoshell://` deep links.
+//!
+//! Pairing links can be clicked in email or chat, and mobile QR scanners
+//! hand scanned `remoshell://` URIs off to the app. The OS delivers them as
+//! URL strings, which are parsed into a [`DeepLink`] and emitted to the
+//! frontend as `deep_link` events. Links that fail to parse are emitted as
+//! `deep_link_error` events instead, so the user learns why nothing happened.
+//!
+//! Supported links:
+//!
+//! - `remoshell://pair?code=ABCD-1234`: pair using a short code registered on
+//!   the signaling server
+//! - `remoshell://pair?data=<base58>`: pair using the full pairing payload
+//! - `remoshell://connect?device_id=<id>`: connect to a paired device
+//! - `remoshell://connect/<base58>`: legacy QR payload, same as `pair?data=`
+//!
+//! Links carry no authority of their own: pairing still goes through
+//! fingerprint verification and daemon approval, and connecting requires the
+//! device to be paired already.
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use thiserror::Error;
+
+/// URI scheme handled by the app.
+pub const SCHEME: &str = "remoshell";
+
+/// Event emitted to the frontend for each parsed link.
+pub const DEEP_LINK_EVENT: &str = "deep_link";
+
+/// Event emitted to the frontend for links that could not be parsed.
+pub const DEEP_LINK_ERROR_EVENT: &str = "deep_link_error";
+
+/// Longest link accepted, to bound the work done on untrusted input.
+pub const MAX_LINK_LEN: usize = 4096;
+
+/// An action requested through a deep link.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DeepLink {
+    /// Pair using a short code to look up on the signaling server.
+    PairCode {
+        /// The code, uppercased.
+        code: String,
+    },
+    /// Pair using a base58-encoded pairing payload.
+    PairData {
+        /// The base58-encoded payload.
+        data: String,
+    },
+    /// Connect to an already paired device.
+    Connect {
+        /// ID of the paired device.
+        device_id: String,
+    },
+}
+
+/// Errors from parsing a deep link.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DeepLinkError {
+    #[error("link is longer than {MAX_LINK_LEN} bytes")]
+    TooLong,
+
+    #[error("not a {SCHEME}:// link")]
+    UnsupportedScheme,
+
+    #[error("unknown link action {0:?}")]
+    UnknownAction(String),
+
+    #[error("link is missing the {0} parameter")]
+    MissingParameter(&'static str),
+
+    #[error("invalid {0} parameter")]
+    InvalidParameter(&'static str),
+}
+
+impl DeepLink {
+    /// Parses a `remoshell://` link.
+    pub fn parse(link: &str) -> Result<Self, DeepLinkError> {
+        let link = link.trim();
+        if link.len() > MAX_LINK_LEN {
+            return Err(DeepLinkError::TooLong);
+        }
+
+        let rest = strip_scheme(link).ok_or(DeepLinkError::UnsupportedScheme)?;
+        let (target, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let target = target.trim_end_matches('/');
+        let (action, path) = target.split_once('/').unwrap_or((target, ""));
+
+        match action.to_ascii_lowercase().as_str() {
+            "pair" => {
+                if let Some(code) = query_param(query, "code")? {
+                    return Ok(Self::PairCode {
+                        code: validate_code(&code)?,
+                    });
+                }
+                let data =
+                    query_param(query, "data")?.ok_or(DeepLinkError::MissingParameter("code"))?;
+                Ok(Self::PairData {
+                    data: validate_base58(&data, "data")?,
+                })
+            }
+            "connect" if !path.is_empty() => Ok(Self::PairData {
+                data: validate_base58(path, "data")?,
+            }),
+            "connect" => {
+                let device_id = query_param(query, "device_id")?
+                    .ok_or(DeepLinkError::MissingParameter("device_id"))?;
+                Ok(Self::Connect {
+                    device_id: validate_device_id(&device_id)?,
+                })
+            }
+            other => Err(DeepLinkError::UnknownAction(other.to_string())),
+        }
+    }
+}
+
+/// Parses a link and emits the result to the frontend.
+///
+/// Returns the parsed link so that callers can act on it directly.
+pub fn dispatch<R: Runtime>(app: &AppHandle<R>, link: &str) -> Result<DeepLink, DeepLinkError> {
+    use tauri::Emitter;
+
+    match DeepLink::parse(link) {
+        Ok(parsed) => {
+            tracing::info!("Opening deep link: {:?}", parsed);
+            if let Err(e) = app.emit(DEEP_LINK_EVENT, &parsed) {
+                tracing::warn!("Failed to emit deep link event: {}", e);
+            }
+            Ok(parsed)
+        }
+        Err(e) => {
+            tracing::warn!("Ignoring deep link: {}", e);
+            if let Err(emit_err) = app.emit(DEEP_LINK_ERROR_EVENT, e.to_string()) {
+                tracing::warn!("Failed to emit deep link error event: {}", emit_err);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Routes links opened by the OS to [`dispatch`].
+///
+/// Call from the Tauri `setup` hook after adding `tauri_plugin_deep_link`.
+/// Handles both the link the app was launched with and links opened while
+/// it is running. In debug builds on Linux and Windows the scheme is also
+/// registered with the OS, since it is otherwise only registered by the
+/// installer.
+pub fn register<R: Runtime>(app: &tauri::App<R>) -> Result<(), tauri_plugin_deep_link::Error> {
+    use tauri_plugin_deep_link::DeepLinkExt;
+
+    #[cfg(all(debug_assertions, any(target_os = "linux", target_os = "windows")))]
+    app.deep_link().register_all()?;
+
+    let handle = app.handle().clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            let _ = dispatch(&handle, url.as_str());
+        }
+    });
+
+    if let Some(urls) = app.deep_link().get_current()? {
+        for url in urls {
+            let _ = dispatch(app.handle(), url.as_str());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the part of the link after `remoshell://`.
+fn strip_scheme(link: &str) -> Option<&str> {
+    let (scheme, rest) = link.split_once("://")?;
+    scheme.eq_ignore_ascii_case(SCHEME).then_some(rest)
+}
+
+/// Returns the decoded value of a query parameter, if present.
+fn query_param(query: &str, name: &'static str) -> Result<Option<String>, DeepLinkError> {
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if key == name {
+            return percent_decode(value)
+                .map(Some)
+                .ok_or(DeepLinkError::InvalidParameter(name));
+        }
+    }
+    Ok(None)
+}
+
+/// Decodes `%XX` escapes and `+` in a query value.
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value.get(i + 1..i + 3)?;
+                if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return None;
+                }
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Validates a short or numeric pairing code such as `ABCD-1234` or
+/// `482-913-0576`, returning it uppercased.
+fn validate_code(code: &str) -> Result<String, DeepLinkError> {
+    let code = code.trim().to_ascii_uppercase();
+    let valid = code.len() <= 32
+        && code.split('-').count() >= 2
+        && code
+            .split('-')
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_alphanumeric()));
+    if valid {
+        Ok(code)
+    } else {
+        Err(DeepLinkError::InvalidParameter("code"))
+    }
+}
+
+/// Validates a base58-encoded value.
+fn validate_base58(value: &str, name: &'static str) -> Result<String, DeepLinkError> {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    if !value.is_empty() && value.bytes().all(|b| ALPHABET.contains(&b)) {
+        Ok(value.to_string())
+    } else {
+        Err(DeepLinkError::InvalidParameter(name))
+    }
+}
+
+/// Validates a paired device ID.
+fn validate_device_id(device_id: &str) -> Result<String, DeepLinkError> {
+    let valid = !device_id.is_empty()
+        && device_id.len() <= 128
+        && device_id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b':'));
+    if valid {
+        Ok(device_id.to_string())
+    } else {
+        Err(DeepLinkError::InvalidParameter("device_id"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pair_code() {
+        assert_eq!(
+            DeepLink::parse("remoshell://pair?code=axbk-7392").unwrap(),
+            DeepLink::PairCode {
+                code: "AXBK-7392".to_string()
+            }
+        );
+        assert_eq!(
+            DeepLink::parse("REMOSHELL://pair/?foo=1&code=482-913-0576").unwrap(),
+            DeepLink::PairCode {
+                code: "482-913-0576".to_string()
+            }
+        );
+        assert_eq!(
+            DeepLink::parse("remoshell://pair?code=AXBK%2D7392").unwrap(),
+            DeepLink::PairCode {
+                code: "AXBK-7392".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_pair_data() {
+        assert_eq!(
+            DeepLink::parse("remoshell://pair?data=3yZe7d").unwrap(),
+            DeepLink::PairData {
+                data: "3yZe7d".to_string()
+            }
+        );
+        // Legacy QR payloads
+        assert_eq!(
+            DeepLink::parse("remoshell://connect/3yZe7d").unwrap(),
+            DeepLink::PairData {
+                data: "3yZe7d".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_connect() {
+        assert_eq!(
+            DeepLink::parse("remoshell://connect?device_id=a1b2c3d4:e5f67890").unwrap(),
+            DeepLink::Connect {
+                device_id: "a1b2c3d4:e5f67890".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_links() {
+        assert_eq!(
+            DeepLink::parse("https://pair?code=AXBK-7392"),
+            Err(DeepLinkError::UnsupportedScheme)
+        );
+        assert_eq!(
+            DeepLink::parse("remoshell://exec?cmd=ls"),
+            Err(DeepLinkError::UnknownAction("exec".to_string()))
+        );
+        assert_eq!(
+            DeepLink::parse("remoshell://pair"),
+            Err(DeepLinkError::MissingParameter("code"))
+        );
+        assert_eq!(
+            DeepLink::parse("remoshell://connect"),
+            Err(DeepLinkError::MissingParameter("device_id"))
+        );
+        assert_eq!(
+            DeepLink::parse("remoshell://pair?code=<script>"),
+            Err(DeepLinkError::InvalidParameter("code"))
+        );
+        assert_eq!(
+            DeepLink::parse("remoshell://pair?code=AXBK%2"),
+            Err(DeepLinkError::InvalidParameter("code"))
+        );
+        assert_eq!(
+            DeepLink::parse("remoshell://connect/not0base58"),
+            Err(DeepLinkError::InvalidParameter("data"))
+        );
+        assert_eq!(
+            DeepLink::parse("remoshell://connect?device_id=../../etc"),
+            Err(DeepLinkError::InvalidParameter("device_id"))
+        );
+
+        let long = format!("remoshell://pair?data={}", "a".repeat(MAX_LINK_LEN));
+        assert_eq!(DeepLink::parse(&long), Err(DeepLinkError::TooLong));
+    }
+
+    #[test]
+    fn test_deep_link_serialization() {
+        let json = serde_json::to_string(&DeepLink::PairCode {
+            code: "AXBK-7392".to_string(),
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"action":"pair_code","code":"AXBK-7392"}"#);
+    }
+}
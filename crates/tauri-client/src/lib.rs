@@ -58,17 +58,63 @@
 //! - `get_paired_devices`: List all paired devices
 //! - `store_paired_device`: Save a new paired device
 //! - `remove_paired_device`: Remove a paired device
+//! - `set_device_mac_address`, `wake_device`: Wake a paired device's host with
+//!   a Wake-on-LAN magic packet
+//! - `get_pending_migrations`: Report database schema migration status
+//! - `get_connection_analytics`: Per-device uptime and failure statistics
+//! - `get_connection_retention`, `set_connection_retention`: Manage how long
+//!   connection history is kept
+//! - `export_identity`: Write an encrypted backup of keys and paired devices
+//! - `import_identity`: Restore keys and paired devices from a backup
+//! - `list_profiles`, `create_profile`, `switch_profile`, `delete_profile`:
+//!   Manage identity profiles, each with its own keys and paired devices
+//! - `has_ssh_agent`, `agent_connect`, `agent_write`, `agent_close`: Relay
+//!   forwarded SSH agent channels to the local agent
+//! - `get_remote_system_info`: Host name, load, memory and disk usage of the
+//!   connected daemon's host
+//! - `start_recording`, `stop_recording`, `list_recordings`,
+//!   `export_recording`, `delete_recording`: Record attached sessions locally
+//!   as asciicast or plain text
+//! - `get_recording_redactions`, `set_recording_redactions`: Manage the secret
+//!   patterns redacted from recordings
+//! - `handle_deep_link`: Open a `remoshell://` link handed over by the frontend
 //! - `show_native_notification`: Display OS notification
 //!
+//! Notifications pushed by the daemon are shown as OS notifications and
+//! emitted to the frontend as `daemon_notification` events.
+//!
+//! `remoshell://pair` and `remoshell://connect` links opened by the OS are
+//! emitted as `deep_link` events once [`deep_link::register`] is called from
+//! the setup hook:
+//!
+//! ```rust,ignore
+//! tauri::Builder::default()
+//!     .plugin(tauri_plugin_deep_link::init())
+//!     .setup(|app| Ok(tauri_client::deep_link::register(app)?))
+//!     .invoke_handler(generate_handler!())
+//!     .run(tauri::generate_context!())
+//!     .expect("error while running tauri application");
+//! ```
+//!
 //! ## Modules
 //!
+//! - [`agent`]: Local SSH agent bridge for agent forwarding
 //! - [`commands`]: Tauri IPC command handlers
+//! - [`deep_link`]: `remoshell://` link parsing and dispatch
+//! - [`notifications`]: Notifications pushed by the daemon
+//! - [`recording`]: Local session recordings with secret redaction
 //! - [`quic`]: QUIC connection management
 //! - [`storage`]: SQLite database and keychain access
+//! - [`wol`]: Wake-on-LAN magic packets
 
+pub mod agent;
 pub mod commands;
+pub mod deep_link;
+pub mod notifications;
 pub mod quic;
+pub mod recording;
 pub mod storage;
+pub mod wol;
 
 // Re-export protocol for convenience
 pub use protocol;
@@ -155,6 +201,31 @@ macro_rules! generate_handler {
             $crate::commands::store_paired_device,
             $crate::commands::remove_paired_device,
             $crate::commands::update_device_last_seen,
+            $crate::commands::set_device_mac_address,
+            $crate::commands::wake_device,
+            $crate::commands::get_pending_migrations,
+            $crate::commands::get_connection_analytics,
+            $crate::commands::get_connection_retention,
+            $crate::commands::set_connection_retention,
+            $crate::commands::export_identity,
+            $crate::commands::import_identity,
+            $crate::commands::list_profiles,
+            $crate::commands::create_profile,
+            $crate::commands::switch_profile,
+            $crate::commands::delete_profile,
+            $crate::commands::has_ssh_agent,
+            $crate::commands::agent_connect,
+            $crate::commands::agent_write,
+            $crate::commands::agent_close,
+            $crate::commands::get_remote_system_info,
+            $crate::commands::start_recording,
+            $crate::commands::stop_recording,
+            $crate::commands::list_recordings,
+            $crate::commands::export_recording,
+            $crate::commands::delete_recording,
+            $crate::commands::get_recording_redactions,
+            $crate::commands::set_recording_redactions,
+            $crate::commands::handle_deep_link,
             $crate::commands::show_native_notification,
         ]
     };
@@ -175,9 +246,15 @@ macro_rules! generate_handler {
 /// ```
 pub mod command_list {
     pub use crate::commands::{
-        connect_quic, disconnect_quic, get_connection_status, get_device_keys, get_paired_device,
-        get_paired_devices, has_device_keys, initialize_app, remove_paired_device, send_quic_data,
-        show_native_notification, store_paired_device, update_device_last_seen,
+        agent_close, agent_connect, agent_write, connect_quic, create_profile, delete_profile,
+        delete_recording, disconnect_quic, export_identity, export_recording,
+        get_connection_analytics, get_connection_retention, get_connection_status, get_device_keys,
+        get_paired_device, get_paired_devices, get_pending_migrations, get_recording_redactions,
+        get_remote_system_info, handle_deep_link, has_device_keys, has_ssh_agent, import_identity,
+        initialize_app, list_profiles, list_recordings, remove_paired_device, send_quic_data,
+        set_connection_retention, set_device_mac_address, set_recording_redactions,
+        show_native_notification, start_recording, stop_recording, store_paired_device,
+        switch_profile, update_device_last_seen, wake_device,
     };
 }
 
@@ -0,0 +1,154 @@
+//! Notifications pushed by the daemon.
+//!
+//! The daemon sends `Notification` messages on the Control channel without a
+//! preceding request, for example before it shuts down or when a pending
+//! approval expires. A listener started by `initialize_app` picks them out of
+//! the data received by the [`QuicManager`](crate::QuicManager), shows each as
+//! an OS notification and emits it to the frontend as a
+//! [`NOTIFICATION_EVENT`].
+
+use protocol::messages::{Envelope, Message, Notification};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::quic::{ChannelType, ConnectionEvent};
+
+/// Event emitted to the frontend for each notification from the daemon.
+pub const NOTIFICATION_EVENT: &str = "daemon_notification";
+
+/// Extracts a daemon notification from a connection event, if it carries one.
+pub fn notification_from_event(event: &ConnectionEvent) -> Option<Notification> {
+    let ConnectionEvent::DataReceived {
+        channel: ChannelType::Control,
+        data,
+    } = event
+    else {
+        return None;
+    };
+
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, data).ok()?;
+    match Envelope::from_msgpack(&bytes).ok()?.payload {
+        Message::Notification(notification) => Some(notification),
+        _ => None,
+    }
+}
+
+/// Shows an OS notification.
+///
+/// Returns whether it was shown, which is never the case when the
+/// `notification` feature is disabled.
+pub fn show_os_notification(
+    app: &tauri::AppHandle,
+    title: &str,
+    body: &str,
+    icon: Option<&str>,
+) -> Result<bool, String> {
+    #[cfg(feature = "notification")]
+    {
+        use tauri_plugin_notification::NotificationExt;
+
+        let mut notification = app.notification().builder().title(title).body(body);
+        if let Some(icon) = icon {
+            notification = notification.icon(icon);
+        }
+        notification.show().map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    #[cfg(not(feature = "notification"))]
+    {
+        let _ = (app, icon);
+        tracing::info!(
+            "Notification requested (feature disabled): {} - {}",
+            title,
+            body
+        );
+        Ok(false)
+    }
+}
+
+/// Presents daemon notifications from `events` until the manager that sent
+/// them is dropped.
+pub fn spawn_listener(app: tauri::AppHandle, mut events: broadcast::Receiver<ConnectionEvent>) {
+    use tauri::Emitter;
+
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Missed {} connection events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let Some(notification) = notification_from_event(&event) else {
+                continue;
+            };
+
+            if let Err(e) =
+                show_os_notification(&app, &notification.title, &notification.body, None)
+            {
+                tracing::warn!("Failed to show notification: {}", e);
+            }
+            if let Err(e) = app.emit(NOTIFICATION_EVENT, &notification) {
+                tracing::warn!("Failed to emit notification event: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::messages::{NotificationCategory, NotificationLevel, Ping};
+
+    fn data_event(channel: ChannelType, message: Message) -> ConnectionEvent {
+        let bytes = Envelope::new(1, message).to_msgpack().unwrap();
+        ConnectionEvent::DataReceived {
+            channel,
+            data: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes),
+        }
+    }
+
+    #[test]
+    fn test_notification_from_event() {
+        let notification = Notification::new(
+            NotificationLevel::Warning,
+            NotificationCategory::Shutdown,
+            "Daemon stopping",
+            "The daemon is shutting down",
+        );
+
+        let event = data_event(
+            ChannelType::Control,
+            Message::Notification(notification.clone()),
+        );
+        assert_eq!(notification_from_event(&event), Some(notification.clone()));
+
+        // Only the Control channel carries notifications
+        let event = data_event(ChannelType::Files, Message::Notification(notification));
+        assert_eq!(notification_from_event(&event), None);
+    }
+
+    #[test]
+    fn test_notification_from_other_events() {
+        let ping = data_event(
+            ChannelType::Control,
+            Message::Ping(Ping {
+                timestamp: 1,
+                payload: Vec::new(),
+            }),
+        );
+        assert_eq!(notification_from_event(&ping), None);
+
+        let garbage = ConnectionEvent::DataReceived {
+            channel: ChannelType::Control,
+            data: "not base64!".to_string(),
+        };
+        assert_eq!(notification_from_event(&garbage), None);
+        assert_eq!(
+            notification_from_event(&ConnectionEvent::Error("boom".to_string())),
+            None
+        );
+    }
+}
@@ -7,14 +7,16 @@
 //! - Bi-directional streams for data transfer
 //! - Event emission for Tauri frontend integration
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use iroh::endpoint::Connection;
+use iroh::endpoint::{Connection, DirectAddrInfo, RemoteInfo};
 #[cfg(test)]
 use iroh::RelayMode;
 use iroh::{Endpoint, NodeAddr, NodeId, RelayUrl, SecretKey};
+use protocol::dial::{DialRecorder, DialReport, DialStep, DialStepKind, StepOutcome};
 use protocol::error::{ProtocolError, Result};
 use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
 
@@ -66,6 +68,16 @@ impl ChannelType {
             _ => None,
         }
     }
+
+    /// Returns the priority of the channel's send stream, higher first, so
+    /// that an upload filling the link does not delay keystrokes.
+    pub fn priority(&self) -> i32 {
+        match self {
+            ChannelType::Terminal => 2,
+            ChannelType::Control => 1,
+            ChannelType::Files => 0,
+        }
+    }
 }
 
 /// Connection state for the QUIC manager.
@@ -97,6 +109,8 @@ pub enum ConnectionEvent {
     },
     /// Error occurred.
     Error(String),
+    /// Connecting failed; the report lists the paths that were tried.
+    Failed(DialReport),
     /// Peer information available.
     PeerInfo { node_id: String },
 }
@@ -147,34 +161,26 @@ impl QuicConfig {
 /// Internal state for managing bi-directional streams per channel type.
 #[derive(Default)]
 struct StreamChannels {
-    control: Option<StreamPair>,
-    terminal: Option<StreamPair>,
-    files: Option<StreamPair>,
+    control: Option<Arc<StreamPair>>,
+    terminal: Option<Arc<StreamPair>>,
+    files: Option<Arc<StreamPair>>,
 }
 
 impl StreamChannels {
-    #[allow(dead_code)]
-    fn get(&self, channel_type: ChannelType) -> Option<&StreamPair> {
+    fn get(&self, channel_type: ChannelType) -> Option<Arc<StreamPair>> {
         match channel_type {
-            ChannelType::Control => self.control.as_ref(),
-            ChannelType::Terminal => self.terminal.as_ref(),
-            ChannelType::Files => self.files.as_ref(),
-        }
-    }
-
-    fn get_mut(&mut self, channel_type: ChannelType) -> Option<&mut StreamPair> {
-        match channel_type {
-            ChannelType::Control => self.control.as_mut(),
-            ChannelType::Terminal => self.terminal.as_mut(),
-            ChannelType::Files => self.files.as_mut(),
+            ChannelType::Control => self.control.clone(),
+            ChannelType::Terminal => self.terminal.clone(),
+            ChannelType::Files => self.files.clone(),
         }
     }
 
     fn set(&mut self, channel_type: ChannelType, stream: StreamPair) {
+        let stream = Some(Arc::new(stream));
         match channel_type {
-            ChannelType::Control => self.control = Some(stream),
-            ChannelType::Terminal => self.terminal = Some(stream),
-            ChannelType::Files => self.files = Some(stream),
+            ChannelType::Control => self.control = stream,
+            ChannelType::Terminal => self.terminal = stream,
+            ChannelType::Files => self.files = stream,
         }
     }
 
@@ -190,10 +196,35 @@ impl StreamChannels {
     }
 }
 
+/// Messages read from one channel's stream, waiting to be received.
+type ChannelReceiver = Arc<Mutex<mpsc::Receiver<Vec<u8>>>>;
+
 /// A pair of send and receive streams for bi-directional communication.
+///
+/// Each half has its own lock, so a reader waiting for data never holds up
+/// a writer, and a channel waiting on flow control never holds up the
+/// others.
 struct StreamPair {
-    send: iroh::endpoint::SendStream,
-    recv: iroh::endpoint::RecvStream,
+    send: Mutex<iroh::endpoint::SendStream>,
+    recv: Mutex<iroh::endpoint::RecvStream>,
+}
+
+impl StreamPair {
+    /// Pairs the streams of a channel, giving the send stream the channel's
+    /// [priority](ChannelType::priority).
+    fn new(
+        channel_type: ChannelType,
+        send: iroh::endpoint::SendStream,
+        recv: iroh::endpoint::RecvStream,
+    ) -> Result<Self> {
+        send.set_priority(channel_type.priority()).map_err(|e| {
+            ProtocolError::ConnectionClosed(format!("{:?} stream closed: {}", channel_type, e))
+        })?;
+        Ok(Self {
+            send: Mutex::new(send),
+            recv: Mutex::new(recv),
+        })
+    }
 }
 
 /// QUIC connection manager for Tauri client.
@@ -211,8 +242,9 @@ pub struct QuicManager {
     connection: Arc<RwLock<Option<Connection>>>,
     /// Stream channels for different message types.
     streams: Arc<Mutex<StreamChannels>>,
-    /// Receiver for incoming messages from streams.
-    message_rx: Arc<Mutex<HashMap<ChannelType, mpsc::Receiver<Vec<u8>>>>>,
+    /// Receiver for incoming messages from streams, locked per channel so
+    /// that waiting on one channel does not hold up the others.
+    message_rx: Arc<Mutex<HashMap<ChannelType, ChannelReceiver>>>,
     /// Senders for incoming messages (used by stream readers).
     message_tx: Arc<RwLock<HashMap<ChannelType, mpsc::Sender<Vec<u8>>>>>,
     /// Current connection state.
@@ -232,7 +264,10 @@ impl QuicManager {
     ///
     /// This sets up the iroh endpoint and prepares for connections.
     pub async fn new(config: QuicConfig) -> Result<Self> {
-        let builder = Endpoint::builder().alpns(vec![REMOSHELL_ALPN.to_vec()]);
+        // Relay connections go through HTTP_PROXY/HTTPS_PROXY when set
+        let builder = Endpoint::builder()
+            .alpns(vec![REMOSHELL_ALPN.to_vec()])
+            .proxy_from_env();
 
         // Configure relay if provided
         if let Some(ref relay_url) = config.relay_url {
@@ -252,7 +287,8 @@ impl QuicManager {
     pub async fn with_secret_key(config: QuicConfig, secret_key: SecretKey) -> Result<Self> {
         let builder = Endpoint::builder()
             .secret_key(secret_key)
-            .alpns(vec![REMOSHELL_ALPN.to_vec()]);
+            .alpns(vec![REMOSHELL_ALPN.to_vec()])
+            .proxy_from_env();
 
         if let Some(ref relay_url) = config.relay_url {
             tracing::debug!("QUIC endpoint will use relay: {}", relay_url);
@@ -278,7 +314,7 @@ impl QuicManager {
         ] {
             let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
             message_tx.insert(channel_type, tx);
-            message_rx.insert(channel_type, rx);
+            message_rx.insert(channel_type, Arc::new(Mutex::new(rx)));
         }
 
         let (event_tx, _) = broadcast::channel(EVENT_BUFFER_SIZE);
@@ -390,12 +426,15 @@ impl QuicManager {
 
     /// Connects to a peer using their node address.
     ///
-    /// This initiates a QUIC connection with built-in hole punching.
+    /// This initiates a QUIC connection with built-in hole punching. On
+    /// failure, a [`ConnectionEvent::Failed`] carries a dial report of the
+    /// relay, hole punching and direct addresses that were tried.
     pub async fn connect(&self, node_addr: NodeAddr) -> Result<()> {
         // Update state
         self.set_state(ConnectionState::Connecting).await;
 
         let remote_node_id = node_addr.node_id;
+        let recorder = DialRecorder::new(remote_node_id.to_string());
 
         // Store the node address for potential reconnection
         {
@@ -405,23 +444,28 @@ impl QuicManager {
 
         let connection = match tokio::time::timeout(
             self.config.connect_timeout,
-            self.endpoint.connect(node_addr, REMOSHELL_ALPN),
+            self.endpoint.connect(node_addr.clone(), REMOSHELL_ALPN),
         )
         .await
         {
             Ok(Ok(conn)) => conn,
-            Ok(Err(e)) => {
-                self.set_state(ConnectionState::Failed).await;
-                self.emit(ConnectionEvent::Error(format!("connection failed: {}", e)));
-                return Err(ProtocolError::HandshakeFailed(format!(
-                    "failed to connect: {}",
-                    e
-                )));
-            }
-            Err(_) => {
+            failure => {
+                let (message, error) = match failure {
+                    Ok(Err(e)) => (
+                        format!("connection failed: {}", e),
+                        ProtocolError::HandshakeFailed(format!("failed to connect: {}", e)),
+                    ),
+                    _ => (
+                        "connection timed out".to_string(),
+                        ProtocolError::Timeout("connection timed out".into()),
+                    ),
+                };
+                let info = self.endpoint.remote_info(remote_node_id);
+                let report = dial_report(recorder, &node_addr, info.as_ref(), message);
+                tracing::warn!(report = %report, "QUIC dial failed");
                 self.set_state(ConnectionState::Failed).await;
-                self.emit(ConnectionEvent::Error("connection timed out".into()));
-                return Err(ProtocolError::Timeout("connection timed out".into()));
+                self.emit(ConnectionEvent::Failed(report));
+                return Err(error);
             }
         };
 
@@ -511,7 +555,7 @@ impl QuicManager {
             ] {
                 let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
                 message_tx.insert(channel_type, tx);
-                message_rx.insert(channel_type, rx);
+                message_rx.insert(channel_type, Arc::new(Mutex::new(rx)));
             }
         }
 
@@ -548,7 +592,7 @@ impl QuicManager {
                 ProtocolError::TransferFailed(format!("failed to send channel id: {}", e))
             })?;
 
-            streams.set(channel_type, StreamPair { send, recv });
+            streams.set(channel_type, StreamPair::new(channel_type, send, recv)?);
             tracing::debug!("Created {:?} stream", channel_type);
         }
 
@@ -627,16 +671,14 @@ impl QuicManager {
         streams: &Arc<Mutex<StreamChannels>>,
         channel_type: ChannelType,
     ) -> Result<Vec<u8>> {
-        let mut streams = streams.lock().await;
-        let stream_pair = streams.get_mut(channel_type).ok_or_else(|| {
+        let stream_pair = streams.lock().await.get(channel_type).ok_or_else(|| {
             ProtocolError::ConnectionClosed(format!("{:?} stream not available", channel_type))
         })?;
+        let mut recv = stream_pair.recv.lock().await;
 
         // Read 4-byte length prefix
         let mut len_buf = [0u8; 4];
-        stream_pair
-            .recv
-            .read_exact(&mut len_buf)
+        recv.read_exact(&mut len_buf)
             .await
             .map_err(|e| ProtocolError::ConnectionClosed(format!("stream read error: {}", e)))?;
 
@@ -650,9 +692,7 @@ impl QuicManager {
 
         // Read the message data
         let mut data = vec![0u8; len];
-        stream_pair
-            .recv
-            .read_exact(&mut data)
+        recv.read_exact(&mut data)
             .await
             .map_err(|e| ProtocolError::ConnectionClosed(format!("stream read error: {}", e)))?;
 
@@ -672,23 +712,19 @@ impl QuicManager {
             });
         }
 
-        let mut streams = streams.lock().await;
-        let stream_pair = streams.get_mut(channel_type).ok_or_else(|| {
+        let stream_pair = streams.lock().await.get(channel_type).ok_or_else(|| {
             ProtocolError::ConnectionClosed(format!("{:?} stream not available", channel_type))
         })?;
+        let mut send = stream_pair.send.lock().await;
 
         // Write 4-byte length prefix
         let len = data.len() as u32;
-        stream_pair
-            .send
-            .write_all(&len.to_be_bytes())
+        send.write_all(&len.to_be_bytes())
             .await
             .map_err(|e| ProtocolError::TransferFailed(format!("stream write error: {}", e)))?;
 
         // Write the message data
-        stream_pair
-            .send
-            .write_all(data)
+        send.write_all(data)
             .await
             .map_err(|e| ProtocolError::TransferFailed(format!("stream write error: {}", e)))?;
 
@@ -709,11 +745,17 @@ impl QuicManager {
     ///
     /// This reads from the internal message queue populated by the receive loop.
     pub async fn recv(&self, channel_type: ChannelType) -> Result<Vec<u8>> {
-        let mut receivers = self.message_rx.lock().await;
-        let rx = receivers.get_mut(&channel_type).ok_or_else(|| {
-            ProtocolError::ConnectionClosed(format!("{:?} channel not available", channel_type))
-        })?;
+        let rx = self
+            .message_rx
+            .lock()
+            .await
+            .get(&channel_type)
+            .cloned()
+            .ok_or_else(|| {
+                ProtocolError::ConnectionClosed(format!("{:?} channel not available", channel_type))
+            })?;
 
+        let mut rx = rx.lock().await;
         rx.recv()
             .await
             .ok_or_else(|| ProtocolError::ConnectionClosed("channel closed".into()))
@@ -731,6 +773,95 @@ impl QuicManager {
     }
 }
 
+/// Completes the report of a failed dial with the paths iroh tried.
+fn dial_report(
+    mut recorder: DialRecorder,
+    node_addr: &NodeAddr,
+    info: Option<&RemoteInfo>,
+    error: impl std::fmt::Display,
+) -> DialReport {
+    recorder.extend(path_steps(node_addr, info));
+    let elapsed = recorder.elapsed();
+    recorder.push(
+        DialStep::new(DialStepKind::Handshake, StepOutcome::Failed)
+            .timing(Duration::ZERO, elapsed)
+            .detail(error.to_string()),
+    );
+    recorder.fail(error)
+}
+
+/// Describes the relay, direct and hole-punched paths to a peer.
+///
+/// iroh does not report its attempts, so they are read back from what it
+/// learned about the peer: a relay that carried traffic has been alive, and
+/// a direct address that answered a ping has a latency.
+fn path_steps(node_addr: &NodeAddr, info: Option<&RemoteInfo>) -> Vec<DialStep> {
+    let mut steps = Vec::new();
+
+    let relay = info.and_then(|info| info.relay_url.as_ref());
+    let relay_url = node_addr
+        .relay_url
+        .clone()
+        .or_else(|| relay.map(|relay| relay.relay_url.clone()));
+    let relay_alive = relay.filter(|relay| relay.last_alive.is_some() || relay.latency.is_some());
+    steps.push(match (&relay_url, relay_alive) {
+        (None, _) => DialStep::new(DialStepKind::RelayLookup, StepOutcome::Skipped)
+            .detail("no relay URL for the peer"),
+        (Some(url), Some(relay)) => {
+            let step = DialStep::new(DialStepKind::RelayLookup, StepOutcome::Succeeded)
+                .target(url.to_string());
+            match relay.latency {
+                Some(latency) => step.detail(format!("latency {}ms", latency.as_millis())),
+                None => step,
+            }
+        }
+        (Some(url), None) => DialStep::new(DialStepKind::RelayLookup, StepOutcome::Failed)
+            .target(url.to_string())
+            .detail("no answer through the relay"),
+    });
+
+    // Addresses the caller gave, plus any iroh discovered
+    let mut candidates: BTreeMap<SocketAddr, Option<&DirectAddrInfo>> = node_addr
+        .direct_addresses
+        .iter()
+        .map(|addr| (*addr, None))
+        .collect();
+    for addr in info.map_or(&[][..], |info| &info.addrs) {
+        candidates.insert(addr.addr, Some(addr));
+    }
+    let mut direct = false;
+    for (addr, seen) in &candidates {
+        let latency = seen.and_then(|seen| seen.latency);
+        let used = seen.is_some_and(|seen| seen.last_payload.is_some());
+        let step = if latency.is_some() || used {
+            direct = true;
+            let step = DialStep::new(DialStepKind::Direct, StepOutcome::Succeeded);
+            match latency {
+                Some(latency) => step.detail(format!("latency {}ms", latency.as_millis())),
+                None => step,
+            }
+        } else {
+            DialStep::new(DialStepKind::Direct, StepOutcome::Failed).detail("no response")
+        };
+        steps.push(step.target(addr.to_string()));
+    }
+
+    steps.push(if candidates.is_empty() {
+        DialStep::new(DialStepKind::HolePunch, StepOutcome::Skipped)
+            .detail("the peer has no known direct addresses")
+    } else if relay_alive.is_none() {
+        DialStep::new(DialStepKind::HolePunch, StepOutcome::Skipped)
+            .detail("no relay to coordinate through")
+    } else if direct {
+        DialStep::new(DialStepKind::HolePunch, StepOutcome::Succeeded)
+    } else {
+        DialStep::new(DialStepKind::HolePunch, StepOutcome::Failed)
+            .detail("no direct path through NAT")
+    });
+
+    steps
+}
+
 /// Base64 encode data for JSON serialization.
 fn base64_encode(data: &[u8]) -> String {
     use std::io::Write;
@@ -838,6 +969,12 @@ mod tests {
         assert_eq!(ChannelType::from_id(255), None);
     }
 
+    #[tokio::test]
+    async fn test_channel_type_priority() {
+        assert!(ChannelType::Terminal.priority() > ChannelType::Control.priority());
+        assert!(ChannelType::Control.priority() > ChannelType::Files.priority());
+    }
+
     #[tokio::test]
     async fn test_create_manager() {
         let config = QuicConfig::default();
@@ -912,6 +1049,32 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_failed_connect_emits_dial_report() {
+        let manager = QuicManager::new_for_testing().await.unwrap();
+        let mut rx = manager.subscribe();
+
+        // No relay and no direct addresses: nothing to dial
+        let node_id = SecretKey::generate(rand::rngs::OsRng).public();
+        assert!(manager.connect(NodeAddr::new(node_id)).await.is_err());
+        assert_eq!(manager.state().await, ConnectionState::Failed);
+
+        let report = loop {
+            match rx.recv().await.expect("expected event") {
+                ConnectionEvent::Failed(report) => break report,
+                _ => continue,
+            }
+        };
+        assert_eq!(report.target, node_id.to_string());
+        assert!(!report.succeeded());
+        assert_eq!(report.steps[0].kind, DialStepKind::RelayLookup);
+        assert_eq!(report.steps[0].outcome, StepOutcome::Skipped);
+        assert_eq!(
+            report.steps.last().map(|step| step.kind),
+            Some(DialStepKind::Handshake)
+        );
+    }
+
     #[tokio::test]
     async fn test_base64_encode() {
         // Test empty
@@ -1015,7 +1178,9 @@ mod tests {
 
                         let channel_type =
                             ChannelType::from_id(channel_id[0]).expect("unknown channel");
-                        streams.set(channel_type, StreamPair { send, recv });
+                        let stream = StreamPair::new(channel_type, send, recv)
+                            .expect("failed to set stream priority");
+                        streams.set(channel_type, stream);
                     }
                 }
 
@@ -0,0 +1,336 @@
+//! Encrypted backup and restore of the client identity.
+//!
+//! A backup bundles the device secret key from the keychain together with the
+//! paired devices from the database, so a user can move to a new machine
+//! without re-pairing with every daemon.
+//!
+//! The bundle is a JSON envelope. The payload is encrypted with
+//! XChaCha20-Poly1305 under a key derived from the user's passphrase with
+//! Argon2id; the envelope header (version, KDF parameters and salt) is
+//! authenticated as associated data so it cannot be altered undetected.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::database::PairedDevice;
+use super::keychain::{decode_secret_key, encode_secret_key};
+
+/// Current version of the backup format.
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Minimum passphrase length accepted when creating a backup.
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Length of the Argon2 salt in bytes.
+const SALT_LEN: usize = 16;
+
+/// Length of the XChaCha20-Poly1305 nonce in bytes.
+const NONCE_LEN: usize = 24;
+
+/// Length of the derived encryption key in bytes.
+const KEY_LEN: usize = 32;
+
+/// Errors that can occur while creating or opening a backup.
+#[derive(Debug, Error)]
+pub enum BackupError {
+    /// The passphrase is too short.
+    #[error("Passphrase must be at least {MIN_PASSPHRASE_LEN} characters")]
+    WeakPassphrase,
+
+    /// The backup was written by an unsupported format version.
+    #[error("Unsupported backup version: {0}")]
+    UnsupportedVersion(u32),
+
+    /// The backup is malformed.
+    #[error("Invalid backup: {0}")]
+    Invalid(String),
+
+    /// Key derivation failed.
+    #[error("Key derivation failed: {0}")]
+    Kdf(String),
+
+    /// Decryption failed, either because the passphrase is wrong or the
+    /// backup has been modified.
+    #[error("Failed to decrypt backup: wrong passphrase or corrupted file")]
+    Decryption,
+
+    /// Encryption failed.
+    #[error("Failed to encrypt backup")]
+    Encryption,
+
+    /// Serialization error.
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Result type for backup operations.
+pub type BackupResult<T> = Result<T, BackupError>;
+
+/// Argon2id parameters stored in the backup envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB.
+    pub memory_kib: u32,
+    /// Number of iterations.
+    pub iterations: u32,
+    /// Degree of parallelism.
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// 64 MiB, 3 iterations, 1 lane.
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Derives a 32-byte key from a passphrase and salt.
+    pub(super) fn derive_key(&self, passphrase: &str, salt: &[u8]) -> BackupResult<[u8; KEY_LEN]> {
+        let params = Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(KEY_LEN),
+        )
+        .map_err(|e| BackupError::Kdf(e.to_string()))?;
+
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| BackupError::Kdf(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+/// The plaintext contents of an identity backup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityBackup {
+    /// The device secret key (base64-encoded).
+    pub secret_key: String,
+    /// Paired devices at the time of the backup.
+    pub paired_devices: Vec<PairedDevice>,
+    /// Unix timestamp when the backup was created.
+    pub created_at: i64,
+}
+
+/// On-disk envelope of an encrypted backup.
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupEnvelope {
+    version: u32,
+    kdf: KdfParams,
+    /// Base64-encoded Argon2 salt.
+    salt: String,
+    /// Base64-encoded XChaCha20-Poly1305 nonce.
+    nonce: String,
+    /// Base64-encoded ciphertext of the serialized [`IdentityBackup`].
+    ciphertext: String,
+}
+
+impl BackupEnvelope {
+    /// Returns the associated data binding the header to the ciphertext.
+    fn associated_data(&self) -> BackupResult<Vec<u8>> {
+        Ok(serde_json::to_vec(&(self.version, &self.kdf, &self.salt))?)
+    }
+}
+
+impl IdentityBackup {
+    /// Creates a backup of the given secret key and paired devices.
+    pub fn new(secret_key: String, paired_devices: Vec<PairedDevice>) -> Self {
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        Self {
+            secret_key,
+            paired_devices,
+            created_at,
+        }
+    }
+
+    /// Encrypts the backup with a passphrase.
+    ///
+    /// Returns the serialized envelope, ready to be written to a file.
+    pub fn encrypt(&self, passphrase: &str) -> BackupResult<Vec<u8>> {
+        self.encrypt_with(passphrase, KdfParams::default())
+    }
+
+    fn encrypt_with(&self, passphrase: &str, kdf: KdfParams) -> BackupResult<Vec<u8>> {
+        if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+            return Err(BackupError::WeakPassphrase);
+        }
+
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let key = kdf.derive_key(passphrase, &salt)?;
+        let plaintext = serde_json::to_vec(self)?;
+
+        let mut envelope = BackupEnvelope {
+            version: BACKUP_VERSION,
+            kdf,
+            salt: encode_secret_key(&salt),
+            nonce: encode_secret_key(&nonce),
+            ciphertext: String::new(),
+        };
+        let aad = envelope.associated_data()?;
+
+        let ciphertext = XChaCha20Poly1305::new(&key.into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| BackupError::Encryption)?;
+        envelope.ciphertext = encode_secret_key(&ciphertext);
+
+        Ok(serde_json::to_vec_pretty(&envelope)?)
+    }
+
+    /// Decrypts a backup produced by [`IdentityBackup::encrypt`].
+    pub fn decrypt(bundle: &[u8], passphrase: &str) -> BackupResult<Self> {
+        let envelope: BackupEnvelope = serde_json::from_slice(bundle)?;
+        if envelope.version != BACKUP_VERSION {
+            return Err(BackupError::UnsupportedVersion(envelope.version));
+        }
+
+        let salt = decode_field(&envelope.salt, "salt")?;
+        let nonce = decode_field(&envelope.nonce, "nonce")?;
+        let ciphertext = decode_field(&envelope.ciphertext, "ciphertext")?;
+        if nonce.len() != NONCE_LEN {
+            return Err(BackupError::Invalid("invalid nonce length".to_string()));
+        }
+
+        let key = envelope.kdf.derive_key(passphrase, &salt)?;
+        let aad = envelope.associated_data()?;
+
+        let plaintext = XChaCha20Poly1305::new(&key.into())
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| BackupError::Decryption)?;
+
+        let backup: Self = serde_json::from_slice(&plaintext)?;
+        decode_secret_key(&backup.secret_key)
+            .map_err(|e| BackupError::Invalid(format!("secret key: {}", e)))?;
+        Ok(backup)
+    }
+}
+
+/// Decodes a base64 envelope field.
+fn decode_field(value: &str, field: &str) -> BackupResult<Vec<u8>> {
+    decode_secret_key(value).map_err(|e| BackupError::Invalid(format!("{}: {}", field, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap parameters so tests do not spend seconds in Argon2.
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn sample_backup() -> IdentityBackup {
+        IdentityBackup::new(
+            encode_secret_key(&[7u8; 32]),
+            vec![PairedDevice {
+                id: "device-1".to_string(),
+                name: "Laptop".to_string(),
+                public_key: "public-key-1".to_string(),
+                created_at: 1_700_000_000,
+                last_seen: Some(1_700_000_100),
+                mac_address: None,
+            }],
+        )
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let backup = sample_backup();
+        let bundle = backup
+            .encrypt_with("correct horse", TEST_KDF)
+            .expect("Failed to encrypt");
+
+        // Neither the key nor the device list appear in the clear
+        let text = String::from_utf8(bundle.clone()).unwrap();
+        assert!(!text.contains(&backup.secret_key));
+        assert!(!text.contains("Laptop"));
+
+        let restored =
+            IdentityBackup::decrypt(&bundle, "correct horse").expect("Failed to decrypt");
+        assert_eq!(restored.secret_key, backup.secret_key);
+        assert_eq!(restored.created_at, backup.created_at);
+        assert_eq!(restored.paired_devices.len(), 1);
+        assert_eq!(restored.paired_devices[0].id, "device-1");
+        assert_eq!(restored.paired_devices[0].last_seen, Some(1_700_000_100));
+    }
+
+    #[test]
+    fn test_backup_wrong_passphrase() {
+        let bundle = sample_backup()
+            .encrypt_with("correct horse", TEST_KDF)
+            .unwrap();
+        assert!(matches!(
+            IdentityBackup::decrypt(&bundle, "battery staple"),
+            Err(BackupError::Decryption)
+        ));
+    }
+
+    #[test]
+    fn test_backup_header_is_authenticated() {
+        let bundle = sample_backup()
+            .encrypt_with("correct horse", TEST_KDF)
+            .unwrap();
+        let mut envelope: BackupEnvelope = serde_json::from_slice(&bundle).unwrap();
+        envelope.kdf.iterations += 1;
+        let tampered = serde_json::to_vec(&envelope).unwrap();
+
+        assert!(matches!(
+            IdentityBackup::decrypt(&tampered, "correct horse"),
+            Err(BackupError::Decryption)
+        ));
+    }
+
+    #[test]
+    fn test_backup_rejects_short_passphrase() {
+        assert!(matches!(
+            sample_backup().encrypt_with("short", TEST_KDF),
+            Err(BackupError::WeakPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_backup_unsupported_version() {
+        let bundle = sample_backup()
+            .encrypt_with("correct horse", TEST_KDF)
+            .unwrap();
+        let mut envelope: BackupEnvelope = serde_json::from_slice(&bundle).unwrap();
+        envelope.version = BACKUP_VERSION + 1;
+        let bundle = serde_json::to_vec(&envelope).unwrap();
+
+        assert!(matches!(
+            IdentityBackup::decrypt(&bundle, "correct horse"),
+            Err(BackupError::UnsupportedVersion(_))
+        ));
+    }
+}
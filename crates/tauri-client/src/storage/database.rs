@@ -1,15 +1,20 @@
 //! SQLite database implementation for RemoShell Tauri client.
 //!
 //! This module provides the core database functionality including:
-//! - Schema management and migrations
+//! - Schema management and migrations (defined in [`super::migrations`])
 //! - Paired device persistence
-//! - Connection history logging
+//! - Connection history logging, retention and analytics
+//! - The index of local session recordings
 //! - Settings storage
+//! - Optional at-rest encryption with SQLCipher (`sqlcipher` feature)
 
 use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use std::io::Read;
 use std::path::Path;
 use thiserror::Error;
 
+use super::migrations::{self, MigrationInfo};
+
 /// Errors that can occur during database operations.
 #[derive(Debug, Error)]
 pub enum DatabaseError {
@@ -24,6 +29,14 @@ pub enum DatabaseError {
     /// Path error.
     #[error("Invalid database path: {0}")]
     InvalidPath(String),
+
+    /// A stored setting could not be parsed or serialized.
+    #[error("Invalid setting: {0}")]
+    InvalidSetting(String),
+
+    /// Encryption error.
+    #[error("Database encryption error: {0}")]
+    Encryption(String),
 }
 
 /// Result type for database operations.
@@ -42,6 +55,9 @@ pub struct PairedDevice {
     pub created_at: i64,
     /// Unix timestamp when the device was last seen (connected).
     pub last_seen: Option<i64>,
+    /// MAC address of the device's host for Wake-on-LAN, if known.
+    #[serde(default)]
+    pub mac_address: Option<String>,
 }
 
 /// A connection history entry.
@@ -59,6 +75,68 @@ pub struct ConnectionHistoryEntry {
     pub successful: bool,
 }
 
+/// Retention policy for the connection history.
+///
+/// The policy is applied whenever a connection is logged and when the
+/// database is opened. By default only the newest 10,000 entries are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RetentionPolicy {
+    /// Maximum number of entries to keep (oldest are removed first).
+    pub max_rows: Option<u32>,
+    /// Maximum age of entries in days.
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_rows: Some(10_000),
+            max_age_days: None,
+        }
+    }
+}
+
+/// Connection statistics for one paired device over a time window.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeviceConnectionStats {
+    /// Device ID.
+    pub device_id: String,
+    /// Human-readable name of the device.
+    pub device_name: String,
+    /// Number of connection attempts in the window.
+    pub total_connections: u32,
+    /// Number of failed connection attempts in the window.
+    pub failed_connections: u32,
+    /// Fraction of failed attempts (0.0 when there were none).
+    pub failure_rate: f64,
+    /// Total seconds connected within the window, from closed connections.
+    pub uptime_secs: i64,
+    /// Unix timestamp of the most recent connection attempt, if any.
+    pub last_connected_at: Option<i64>,
+}
+
+/// A local recording of a terminal session.
+///
+/// The recording itself is a file under the app data directory; the
+/// database only indexes it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RecordingEntry {
+    /// Unique identifier for the recording.
+    pub id: String,
+    /// Session that was recorded.
+    pub session_id: String,
+    /// File format, `asciicast` or `text`.
+    pub format: String,
+    /// Path of the recording file.
+    pub path: String,
+    /// Unix timestamp when recording started.
+    pub started_at: i64,
+    /// Unix timestamp when recording stopped, if it has.
+    pub stopped_at: Option<i64>,
+    /// Size of the recording file in bytes, once stopped.
+    pub size_bytes: i64,
+}
+
 /// A setting stored in the database.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Setting {
@@ -68,9 +146,21 @@ pub struct Setting {
     pub value: String,
 }
 
+/// Settings key holding the connection history retention policy.
+const RETENTION_SETTING_KEY: &str = "connection_history_retention";
+
+/// Settings key holding the patterns redacted from session recordings.
+const REDACTION_SETTING_KEY: &str = "recording_redaction_patterns";
+
+/// Length of the database encryption key in bytes.
+pub const DATABASE_KEY_LEN: usize = 32;
+
+/// Header at the start of every unencrypted SQLite database file.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
 /// Current schema version.
 #[cfg(test)]
-const CURRENT_SCHEMA_VERSION: i32 = 1;
+const CURRENT_SCHEMA_VERSION: i32 = 3;
 
 /// Database wrapper providing all storage operations.
 pub struct Database {
@@ -84,18 +174,12 @@ impl Database {
     /// If it exists, any pending migrations will be applied.
     pub fn open<P: AsRef<Path>>(path: P) -> StorageResult<Self> {
         let path = path.as_ref();
+        create_parent_dir(path)?;
 
-        // Ensure parent directory exists
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                std::fs::create_dir_all(parent).map_err(|e| {
-                    DatabaseError::InvalidPath(format!(
-                        "Failed to create directory {}: {}",
-                        parent.display(),
-                        e
-                    ))
-                })?;
-            }
+        if read_header(path)?.is_some_and(|header| &header != SQLITE_HEADER) {
+            return Err(DatabaseError::Encryption(
+                "database is encrypted and must be opened with a key".to_string(),
+            ));
         }
 
         let conn = Connection::open(path)?;
@@ -105,10 +189,60 @@ impl Database {
 
         let mut db = Self { conn };
         db.run_migrations()?;
+        db.prune_connection_history()?;
 
         Ok(db)
     }
 
+    /// Open or create an encrypted database at the given path.
+    ///
+    /// Requires SQLite to be built with SQLCipher (the `sqlcipher` feature).
+    /// An existing unencrypted database is encrypted in place first, so
+    /// enabling encryption keeps all stored data.
+    pub fn open_encrypted<P: AsRef<Path>>(
+        path: P,
+        key: &[u8; DATABASE_KEY_LEN],
+    ) -> StorageResult<Self> {
+        if !Self::encryption_available() {
+            return Err(DatabaseError::Encryption(
+                "SQLCipher support is not enabled in this build".to_string(),
+            ));
+        }
+
+        let path = path.as_ref();
+        create_parent_dir(path)?;
+
+        if read_header(path)?.is_some_and(|header| &header == SQLITE_HEADER) {
+            encrypt_in_place(path, key)?;
+        }
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch(&format!("PRAGMA key = \"{}\";", raw_key_literal(key)))?;
+
+        // The key is only checked when the first page is read
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(|_| DatabaseError::Encryption("wrong key or corrupted database".to_string()))?;
+
+        conn.execute("PRAGMA foreign_keys = ON", [])?;
+
+        let mut db = Self { conn };
+        db.run_migrations()?;
+        db.prune_connection_history()?;
+
+        Ok(db)
+    }
+
+    /// Returns true if the linked SQLite library supports encryption.
+    pub fn encryption_available() -> bool {
+        Connection::open_in_memory()
+            .and_then(|conn| {
+                conn.query_row("PRAGMA cipher_version", [], |row| row.get::<_, String>(0))
+            })
+            .is_ok()
+    }
+
     /// Open an in-memory database (useful for testing).
     #[cfg(test)]
     pub fn open_in_memory() -> StorageResult<Self> {
@@ -137,88 +271,30 @@ impl Database {
         Ok(())
     }
 
-    /// Run all pending migrations.
+    /// Validate the schema and apply any pending migrations.
     fn run_migrations(&mut self) -> StorageResult<()> {
-        let current_version = self.get_schema_version()?;
-
-        if current_version < 1 {
-            self.migrate_v1()?;
-        }
-
-        // Future migrations would be added here:
-        // if current_version < 2 {
-        //     self.migrate_v2()?;
-        // }
-
-        Ok(())
+        migrations::migrate(&mut self.conn)
     }
 
-    /// Migration to version 1: Initial schema.
-    fn migrate_v1(&mut self) -> StorageResult<()> {
-        let tx = self.conn.transaction()?;
-
-        // Create paired_devices table
-        tx.execute(
-            r#"
-            CREATE TABLE IF NOT EXISTS paired_devices (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                public_key TEXT NOT NULL,
-                created_at INTEGER NOT NULL,
-                last_seen INTEGER
-            )
-            "#,
-            [],
-        )?;
-
-        // Create connection_history table
-        tx.execute(
-            r#"
-            CREATE TABLE IF NOT EXISTS connection_history (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                device_id TEXT NOT NULL,
-                connected_at INTEGER NOT NULL,
-                disconnected_at INTEGER,
-                successful INTEGER NOT NULL DEFAULT 1,
-                FOREIGN KEY (device_id) REFERENCES paired_devices(id) ON DELETE CASCADE
-            )
-            "#,
-            [],
-        )?;
-
-        // Create index on connection_history for faster queries
-        tx.execute(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_connection_history_device_id
-            ON connection_history(device_id)
-            "#,
-            [],
-        )?;
-
-        tx.execute(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_connection_history_connected_at
-            ON connection_history(connected_at DESC)
-            "#,
-            [],
-        )?;
-
-        // Create settings table
-        tx.execute(
-            r#"
-            CREATE TABLE IF NOT EXISTS settings (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL
-            )
-            "#,
-            [],
-        )?;
+    /// Get the status of every known migration.
+    pub fn migration_status(&self) -> StorageResult<Vec<MigrationInfo>> {
+        migrations::status(&self.conn)
+    }
 
-        // Update schema version
-        tx.execute(&format!("PRAGMA user_version = {}", 1), [])?;
+    /// Get the migrations that have not been applied yet.
+    pub fn pending_migrations(&self) -> StorageResult<Vec<MigrationInfo>> {
+        Ok(self
+            .migration_status()?
+            .into_iter()
+            .filter(|m| m.applied_at.is_none())
+            .collect())
+    }
 
-        tx.commit()?;
-        Ok(())
+    /// Revert migrations until the schema is at the given version.
+    ///
+    /// Data stored in the reverted tables and columns is lost.
+    pub fn rollback_to(&mut self, version: i32) -> StorageResult<()> {
+        migrations::rollback(&mut self.conn, version)
     }
 
     // =========================================================================
@@ -229,15 +305,16 @@ impl Database {
     pub fn add_paired_device(&self, device: &PairedDevice) -> StorageResult<()> {
         self.conn.execute(
             r#"
-            INSERT INTO paired_devices (id, name, public_key, created_at, last_seen)
-            VALUES (?1, ?2, ?3, ?4, ?5)
+            INSERT INTO paired_devices (id, name, public_key, created_at, last_seen, mac_address)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
             "#,
             params![
                 device.id,
                 device.name,
                 device.public_key,
                 device.created_at,
-                device.last_seen
+                device.last_seen,
+                device.mac_address
             ],
         )?;
         Ok(())
@@ -249,7 +326,7 @@ impl Database {
             .conn
             .query_row(
                 r#"
-                SELECT id, name, public_key, created_at, last_seen
+                SELECT id, name, public_key, created_at, last_seen, mac_address
                 FROM paired_devices
                 WHERE id = ?1
                 "#,
@@ -261,6 +338,7 @@ impl Database {
                         public_key: row.get(2)?,
                         created_at: row.get(3)?,
                         last_seen: row.get(4)?,
+                        mac_address: row.get(5)?,
                     })
                 },
             )
@@ -272,7 +350,7 @@ impl Database {
     pub fn list_paired_devices(&self) -> StorageResult<Vec<PairedDevice>> {
         let mut stmt = self.conn.prepare(
             r#"
-            SELECT id, name, public_key, created_at, last_seen
+            SELECT id, name, public_key, created_at, last_seen, mac_address
             FROM paired_devices
             ORDER BY created_at DESC
             "#,
@@ -286,6 +364,7 @@ impl Database {
                     public_key: row.get(2)?,
                     created_at: row.get(3)?,
                     last_seen: row.get(4)?,
+                    mac_address: row.get(5)?,
                 })
             })?
             .collect::<SqliteResult<Vec<_>>>()?;
@@ -312,6 +391,19 @@ impl Database {
         Ok(rows_affected > 0)
     }
 
+    /// Set or clear the MAC address used to wake a device's host.
+    pub fn update_device_mac_address(
+        &self,
+        id: &str,
+        mac_address: Option<&str>,
+    ) -> StorageResult<bool> {
+        let rows_affected = self.conn.execute(
+            "UPDATE paired_devices SET mac_address = ?1 WHERE id = ?2",
+            params![mac_address, id],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
     /// Update a paired device's name.
     pub fn update_device_name(&self, id: &str, name: &str) -> StorageResult<bool> {
         let rows_affected = self.conn.execute(
@@ -340,7 +432,9 @@ impl Database {
             "#,
             params![device_id, connected_at, disconnected_at, successful as i32],
         )?;
-        Ok(self.conn.last_insert_rowid())
+        let id = self.conn.last_insert_rowid();
+        self.prune_connection_history()?;
+        Ok(id)
     }
 
     /// Update a connection's disconnected_at timestamp.
@@ -443,6 +537,207 @@ impl Database {
         Ok(entries)
     }
 
+    /// Get the connection history retention policy.
+    pub fn retention_policy(&self) -> StorageResult<RetentionPolicy> {
+        match self.get_setting(RETENTION_SETTING_KEY)? {
+            Some(value) => serde_json::from_str(&value)
+                .map_err(|e| DatabaseError::InvalidSetting(format!("retention policy: {}", e))),
+            None => Ok(RetentionPolicy::default()),
+        }
+    }
+
+    /// Set the connection history retention policy and apply it.
+    ///
+    /// Returns the number of entries removed.
+    pub fn set_retention_policy(&self, policy: &RetentionPolicy) -> StorageResult<usize> {
+        let value = serde_json::to_string(policy)
+            .map_err(|e| DatabaseError::InvalidSetting(format!("retention policy: {}", e)))?;
+        self.set_setting(RETENTION_SETTING_KEY, &value)?;
+        self.prune_connection_history()
+    }
+
+    /// Remove connection history entries outside the retention policy.
+    ///
+    /// Returns the number of entries removed.
+    pub fn prune_connection_history(&self) -> StorageResult<usize> {
+        let policy = self.retention_policy()?;
+        let mut removed = 0;
+
+        if let Some(days) = policy.max_age_days {
+            let cutoff = unix_now() - i64::from(days) * SECONDS_PER_DAY;
+            removed += self.conn.execute(
+                "DELETE FROM connection_history WHERE connected_at < ?1",
+                params![cutoff],
+            )?;
+        }
+
+        if let Some(max_rows) = policy.max_rows {
+            removed += self.conn.execute(
+                r#"
+                DELETE FROM connection_history WHERE id NOT IN (
+                    SELECT id FROM connection_history
+                    ORDER BY connected_at DESC, id DESC
+                    LIMIT ?1
+                )
+                "#,
+                params![max_rows],
+            )?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Get per-device connection statistics since the given Unix timestamp.
+    ///
+    /// Every paired device is included, even without connections. Uptime
+    /// only counts successful connections that have been closed, clipped to
+    /// the window.
+    pub fn connection_analytics(&self, since: i64) -> StorageResult<Vec<DeviceConnectionStats>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT
+                d.id,
+                d.name,
+                COUNT(h.id),
+                COALESCE(SUM(CASE WHEN h.successful = 0 THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(
+                    CASE WHEN h.successful != 0 AND h.disconnected_at IS NOT NULL
+                    THEN MAX(0, h.disconnected_at - MAX(h.connected_at, ?1))
+                    ELSE 0 END
+                ), 0),
+                MAX(h.connected_at)
+            FROM paired_devices d
+            LEFT JOIN connection_history h
+                ON h.device_id = d.id
+                AND (h.connected_at >= ?1 OR h.disconnected_at >= ?1)
+            GROUP BY d.id, d.name
+            ORDER BY d.name
+            "#,
+        )?;
+
+        let stats = stmt
+            .query_map(params![since], |row| {
+                let total_connections: u32 = row.get(2)?;
+                let failed_connections: u32 = row.get(3)?;
+                let failure_rate = if total_connections == 0 {
+                    0.0
+                } else {
+                    f64::from(failed_connections) / f64::from(total_connections)
+                };
+                Ok(DeviceConnectionStats {
+                    device_id: row.get(0)?,
+                    device_name: row.get(1)?,
+                    total_connections,
+                    failed_connections,
+                    failure_rate,
+                    uptime_secs: row.get(4)?,
+                    last_connected_at: row.get(5)?,
+                })
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(stats)
+    }
+
+    // =========================================================================
+    // Session Recordings
+    // =========================================================================
+
+    /// Add a recording to the index.
+    pub fn add_recording(&self, recording: &RecordingEntry) -> StorageResult<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO recordings
+                (id, session_id, format, path, started_at, stopped_at, size_bytes)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            params![
+                recording.id,
+                recording.session_id,
+                recording.format,
+                recording.path,
+                recording.started_at,
+                recording.stopped_at,
+                recording.size_bytes,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Mark a recording as stopped.
+    pub fn finish_recording(
+        &self,
+        id: &str,
+        stopped_at: i64,
+        size_bytes: i64,
+    ) -> StorageResult<bool> {
+        let rows_affected = self.conn.execute(
+            "UPDATE recordings SET stopped_at = ?1, size_bytes = ?2 WHERE id = ?3",
+            params![stopped_at, size_bytes, id],
+        )?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Get a recording by ID.
+    pub fn get_recording(&self, id: &str) -> StorageResult<Option<RecordingEntry>> {
+        let result = self
+            .conn
+            .query_row(
+                r#"
+                SELECT id, session_id, format, path, started_at, stopped_at, size_bytes
+                FROM recordings
+                WHERE id = ?1
+                "#,
+                params![id],
+                recording_from_row,
+            )
+            .optional()?;
+        Ok(result)
+    }
+
+    /// List all recordings, newest first.
+    pub fn list_recordings(&self) -> StorageResult<Vec<RecordingEntry>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT id, session_id, format, path, started_at, stopped_at, size_bytes
+            FROM recordings
+            ORDER BY started_at DESC, id
+            "#,
+        )?;
+
+        let recordings = stmt
+            .query_map([], recording_from_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(recordings)
+    }
+
+    /// Remove a recording from the index.
+    pub fn remove_recording(&self, id: &str) -> StorageResult<bool> {
+        let rows_affected = self
+            .conn
+            .execute("DELETE FROM recordings WHERE id = ?1", params![id])?;
+        Ok(rows_affected > 0)
+    }
+
+    /// Get the patterns redacted from session recordings, if configured.
+    pub fn redaction_patterns(&self) -> StorageResult<Option<Vec<String>>> {
+        self.get_setting(REDACTION_SETTING_KEY)?
+            .map(|value| {
+                serde_json::from_str(&value).map_err(|e| {
+                    DatabaseError::InvalidSetting(format!("redaction patterns: {}", e))
+                })
+            })
+            .transpose()
+    }
+
+    /// Set the patterns redacted from session recordings.
+    pub fn set_redaction_patterns(&self, patterns: &[String]) -> StorageResult<()> {
+        let value = serde_json::to_string(patterns)
+            .map_err(|e| DatabaseError::InvalidSetting(format!("redaction patterns: {}", e)))?;
+        self.set_setting(REDACTION_SETTING_KEY, &value)
+    }
+
     // =========================================================================
     // Settings
     // =========================================================================
@@ -499,6 +794,112 @@ impl Database {
     }
 }
 
+/// Maps a row of the `recordings` table.
+fn recording_from_row(row: &rusqlite::Row<'_>) -> SqliteResult<RecordingEntry> {
+    Ok(RecordingEntry {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        format: row.get(2)?,
+        path: row.get(3)?,
+        started_at: row.get(4)?,
+        stopped_at: row.get(5)?,
+        size_bytes: row.get(6)?,
+    })
+}
+
+/// Number of seconds in a day.
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Current Unix timestamp in seconds.
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Create the parent directory of a database file if it does not exist.
+fn create_parent_dir(path: &Path) -> StorageResult<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                DatabaseError::InvalidPath(format!(
+                    "Failed to create directory {}: {}",
+                    parent.display(),
+                    e
+                ))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the first 16 bytes of a database file.
+///
+/// Returns `None` if the file does not exist or is empty.
+fn read_header(path: &Path) -> StorageResult<Option<[u8; 16]>> {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(DatabaseError::InvalidPath(format!(
+                "{}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+
+    let mut header = [0u8; 16];
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(Some(header)),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(DatabaseError::InvalidPath(format!(
+            "{}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+/// Format a key as a SQLCipher raw key literal (`x'...'`).
+fn raw_key_literal(key: &[u8; DATABASE_KEY_LEN]) -> String {
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("x'{}'", hex)
+}
+
+/// Encrypt an unencrypted database file in place.
+///
+/// The data is exported into an encrypted copy, which then replaces the
+/// original file.
+fn encrypt_in_place(path: &Path, key: &[u8; DATABASE_KEY_LEN]) -> StorageResult<()> {
+    let encrypted_path = path.with_extension("encrypting");
+    if encrypted_path.exists() {
+        std::fs::remove_file(&encrypted_path).map_err(|e| {
+            DatabaseError::Encryption(format!("Failed to remove stale migration file: {}", e))
+        })?;
+    }
+
+    {
+        let conn = Connection::open(path)?;
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![encrypted_path.to_string_lossy(), raw_key_literal(key)],
+        )?;
+        conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+        // sqlcipher_export does not copy the schema version
+        conn.execute_batch(&format!("PRAGMA encrypted.user_version = {};", version))?;
+        conn.execute("DETACH DATABASE encrypted", [])?;
+    }
+
+    std::fs::rename(&encrypted_path, path).map_err(|e| {
+        DatabaseError::Encryption(format!("Failed to replace unencrypted database: {}", e))
+    })?;
+    tracing::info!("Encrypted database {}", path.display());
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -523,6 +924,7 @@ mod tests {
             public_key: "cascade-key".to_string(),
             created_at: 1000,
             last_seen: None,
+            mac_address: None,
         };
         db.add_paired_device(&device).expect("Failed to add device");
 
@@ -559,6 +961,7 @@ mod tests {
             public_key: "rename-key".to_string(),
             created_at: 1000,
             last_seen: None,
+            mac_address: None,
         };
         db.add_paired_device(&device).expect("Failed to add device");
 
@@ -571,4 +974,182 @@ mod tests {
             .expect("Device should exist");
         assert_eq!(updated.name, "New Name");
     }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn test_open_encrypted_requires_sqlcipher() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let result = Database::open_encrypted(temp_dir.path().join("test.db"), &[1u8; 32]);
+        assert!(matches!(result, Err(DatabaseError::Encryption(_))));
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_encrypt_existing_database() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.db");
+        let key = [7u8; DATABASE_KEY_LEN];
+        let device = PairedDevice {
+            id: "device-1".to_string(),
+            name: "Laptop".to_string(),
+            public_key: "key-1".to_string(),
+            created_at: 0,
+            last_seen: None,
+            mac_address: None,
+        };
+
+        Database::open(&path)
+            .unwrap()
+            .add_paired_device(&device)
+            .unwrap();
+
+        let db = Database::open_encrypted(&path, &key).expect("Failed to migrate database");
+        assert_eq!(db.get_schema_version().unwrap(), CURRENT_SCHEMA_VERSION);
+        assert!(db.get_paired_device("device-1").unwrap().is_some());
+        drop(db);
+
+        // The file is no longer readable without the key
+        assert!(matches!(
+            Database::open(&path),
+            Err(DatabaseError::Encryption(_))
+        ));
+        assert!(matches!(
+            Database::open_encrypted(&path, &[8u8; DATABASE_KEY_LEN]),
+            Err(DatabaseError::Encryption(_))
+        ));
+        assert!(Database::open_encrypted(&path, &key).is_ok());
+    }
+
+    #[test]
+    fn test_no_pending_migrations_after_open() {
+        let mut db = Database::open_in_memory().expect("Failed to create database");
+        assert!(db.pending_migrations().unwrap().is_empty());
+
+        db.rollback_to(0).expect("Failed to roll back");
+        assert_eq!(db.get_schema_version().unwrap(), 0);
+        assert_eq!(
+            db.pending_migrations().unwrap().len(),
+            super::migrations::MIGRATIONS.len()
+        );
+    }
+
+    fn add_test_device(db: &Database, id: &str, name: &str) {
+        db.add_paired_device(&PairedDevice {
+            id: id.to_string(),
+            name: name.to_string(),
+            public_key: format!("key-{}", id),
+            created_at: 0,
+            last_seen: None,
+            mac_address: None,
+        })
+        .expect("Failed to add device");
+    }
+
+    #[test]
+    fn test_retention_max_rows() {
+        let db = Database::open_in_memory().expect("Failed to create database");
+        add_test_device(&db, "device-1", "Laptop");
+        assert_eq!(db.retention_policy().unwrap(), RetentionPolicy::default());
+
+        for i in 0..5 {
+            db.log_connection("device-1", 1000 + i, None, true).unwrap();
+        }
+
+        let policy = RetentionPolicy {
+            max_rows: Some(3),
+            max_age_days: None,
+        };
+        assert_eq!(db.set_retention_policy(&policy).unwrap(), 2);
+        assert_eq!(db.retention_policy().unwrap(), policy);
+
+        // New entries keep the table at the limit, dropping the oldest
+        db.log_connection("device-1", 2000, None, true).unwrap();
+        let history = db.get_all_connection_history(None).unwrap();
+        let times: Vec<i64> = history.iter().map(|e| e.connected_at).collect();
+        assert_eq!(times, vec![2000, 1004, 1003]);
+    }
+
+    #[test]
+    fn test_recording_index() {
+        let db = Database::open_in_memory().expect("Failed to create database");
+        let recording = RecordingEntry {
+            id: "rec-1".to_string(),
+            session_id: "sess-1".to_string(),
+            format: "asciicast".to_string(),
+            path: "/data/recordings/rec-1.cast".to_string(),
+            started_at: 1000,
+            stopped_at: None,
+            size_bytes: 0,
+        };
+        db.add_recording(&recording).unwrap();
+        assert_eq!(db.get_recording("rec-1").unwrap(), Some(recording.clone()));
+
+        assert!(db.finish_recording("rec-1", 1060, 4096).unwrap());
+        let finished = db.get_recording("rec-1").unwrap().unwrap();
+        assert_eq!(finished.stopped_at, Some(1060));
+        assert_eq!(finished.size_bytes, 4096);
+        assert_eq!(db.list_recordings().unwrap(), vec![finished]);
+
+        assert!(db.remove_recording("rec-1").unwrap());
+        assert!(db.list_recordings().unwrap().is_empty());
+
+        assert_eq!(db.redaction_patterns().unwrap(), None);
+        let patterns = vec!["hunter2".to_string()];
+        db.set_redaction_patterns(&patterns).unwrap();
+        assert_eq!(db.redaction_patterns().unwrap(), Some(patterns));
+    }
+
+    #[test]
+    fn test_retention_max_age() {
+        let db = Database::open_in_memory().expect("Failed to create database");
+        add_test_device(&db, "device-1", "Laptop");
+        let now = unix_now();
+
+        db.log_connection("device-1", now - 10 * SECONDS_PER_DAY, None, true)
+            .unwrap();
+        db.log_connection("device-1", now - SECONDS_PER_DAY, None, true)
+            .unwrap();
+
+        let policy = RetentionPolicy {
+            max_rows: None,
+            max_age_days: Some(7),
+        };
+        assert_eq!(db.set_retention_policy(&policy).unwrap(), 1);
+        assert_eq!(db.get_all_connection_history(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_connection_analytics() {
+        let db = Database::open_in_memory().expect("Failed to create database");
+        add_test_device(&db, "device-1", "Laptop");
+        add_test_device(&db, "device-2", "Server");
+
+        // Started before the window: only the part inside counts
+        db.log_connection("device-1", 900, Some(1100), true)
+            .unwrap();
+        db.log_connection("device-1", 1200, Some(1500), true)
+            .unwrap();
+        db.log_connection("device-1", 1600, None, false).unwrap();
+        db.log_connection("device-1", 1700, Some(1800), false)
+            .unwrap();
+        // Entirely before the window
+        db.log_connection("device-2", 100, Some(200), true).unwrap();
+
+        let stats = db.connection_analytics(1000).unwrap();
+        assert_eq!(stats.len(), 2);
+
+        let laptop = &stats[0];
+        assert_eq!(laptop.device_name, "Laptop");
+        assert_eq!(laptop.total_connections, 4);
+        assert_eq!(laptop.failed_connections, 2);
+        assert!((laptop.failure_rate - 0.5).abs() < f64::EPSILON);
+        assert_eq!(laptop.uptime_secs, 100 + 300);
+        assert_eq!(laptop.last_connected_at, Some(1700));
+
+        let server = &stats[1];
+        assert_eq!(server.total_connections, 0);
+        assert_eq!(server.failure_rate, 0.0);
+        assert_eq!(server.uptime_secs, 0);
+        assert_eq!(server.last_connected_at, None);
+    }
 }
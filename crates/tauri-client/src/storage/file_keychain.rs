@@ -0,0 +1,475 @@
+//! Encrypted-file keychain backend.
+//!
+//! Headless Linux machines (CI runners, kiosks, containers) often have no
+//! Secret Service, so the OS keychain is unavailable. [`FileKeychain`] stores
+//! secrets in a JSON file instead, each value encrypted with
+//! XChaCha20-Poly1305 under a master key that is either:
+//!
+//! - derived from a passphrase with Argon2id (the salt is kept in the file), or
+//! - read from a random key file created with owner-only permissions.
+//!
+//! The key-file mode only protects against reading the secrets file on its
+//! own; anyone who can read both files can read the secrets.
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::backup::KdfParams;
+use super::keychain::{
+    decode_secret_key, encode_secret_key, KeychainBackend, KeychainError, KeychainResult,
+};
+
+/// Current version of the keychain file format.
+const FILE_VERSION: u32 = 1;
+
+/// Length of the master key in bytes.
+const MASTER_KEY_LEN: usize = 32;
+
+/// Length of the Argon2 salt in bytes.
+const SALT_LEN: usize = 16;
+
+/// Length of the XChaCha20-Poly1305 nonce in bytes.
+const NONCE_LEN: usize = 24;
+
+/// Name and contents of the entry used to verify the master key.
+const CHECK_ENTRY: &str = "remoshell-keychain-check";
+
+/// An encrypted secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SealedEntry {
+    /// Base64-encoded nonce.
+    nonce: String,
+    /// Base64-encoded ciphertext.
+    ciphertext: String,
+}
+
+/// On-disk contents of the keychain file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeychainFile {
+    version: u32,
+    /// Argon2 parameters, present in passphrase mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfParams>,
+    /// Base64-encoded Argon2 salt, present in passphrase mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    /// Known value encrypted under the master key, so a wrong key is
+    /// rejected when the file is opened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    check: Option<SealedEntry>,
+    /// Encrypted secrets keyed by `service/key`.
+    #[serde(default)]
+    entries: BTreeMap<String, SealedEntry>,
+}
+
+impl KeychainFile {
+    fn new() -> Self {
+        Self {
+            version: FILE_VERSION,
+            kdf: None,
+            salt: None,
+            check: None,
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+struct Inner {
+    path: PathBuf,
+    cipher: XChaCha20Poly1305,
+    /// Serializes read-modify-write cycles on the file.
+    lock: Mutex<()>,
+}
+
+/// Keychain backend storing encrypted secrets in a file.
+///
+/// Cloning is cheap; clones share the same file and master key.
+#[derive(Clone)]
+pub struct FileKeychain {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for FileKeychain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileKeychain")
+            .field("path", &self.inner.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FileKeychain {
+    /// Opens or creates a keychain file protected by a passphrase.
+    ///
+    /// Fails with [`KeychainError::AccessDenied`] if the file already holds
+    /// secrets that the passphrase cannot decrypt.
+    pub fn open_with_passphrase<P: AsRef<Path>>(path: P, passphrase: &str) -> KeychainResult<Self> {
+        Self::open_with_kdf(path.as_ref(), passphrase, KdfParams::default())
+    }
+
+    /// Opens a passphrase-protected file, using `kdf` if it is created.
+    fn open_with_kdf(path: &Path, passphrase: &str, kdf: KdfParams) -> KeychainResult<Self> {
+        let mut file = read_file(path)?.unwrap_or_else(KeychainFile::new);
+
+        let (kdf, salt) = match (file.kdf, &file.salt) {
+            (Some(kdf), Some(salt)) => (kdf, decode_secret_key(salt)?),
+            (None, None) if file.entries.is_empty() => {
+                let mut salt = vec![0u8; SALT_LEN];
+                rand::thread_rng().fill_bytes(&mut salt);
+                file.kdf = Some(kdf);
+                file.salt = Some(encode_secret_key(&salt));
+                (kdf, salt)
+            }
+            _ => {
+                return Err(KeychainError::AccessDenied(
+                    "keychain file is not protected by a passphrase".to_string(),
+                ))
+            }
+        };
+
+        let key = kdf
+            .derive_key(passphrase, &salt)
+            .map_err(|e| KeychainError::EncodingError(e.to_string()))?;
+        Self::from_key(path, &key, file)
+    }
+
+    /// Opens or creates a keychain file protected by a random key file.
+    ///
+    /// The key file is created with owner-only permissions if it does not
+    /// exist.
+    pub fn open_with_key_file<P: AsRef<Path>, K: AsRef<Path>>(
+        path: P,
+        key_path: K,
+    ) -> KeychainResult<Self> {
+        let path = path.as_ref();
+        let file = read_file(path)?.unwrap_or_else(KeychainFile::new);
+        if file.salt.is_some() {
+            return Err(KeychainError::AccessDenied(
+                "keychain file is protected by a passphrase".to_string(),
+            ));
+        }
+
+        let key = load_or_create_key_file(key_path.as_ref())?;
+        Self::from_key(path, &key, file)
+    }
+
+    fn from_key(
+        path: &Path,
+        key: &[u8; MASTER_KEY_LEN],
+        mut file: KeychainFile,
+    ) -> KeychainResult<Self> {
+        let keychain = Self {
+            inner: Arc::new(Inner {
+                path: path.to_path_buf(),
+                cipher: XChaCha20Poly1305::new(key.into()),
+                lock: Mutex::new(()),
+            }),
+        };
+
+        // Check the key up front so a wrong passphrase fails here rather
+        // than on first use
+        match &file.check {
+            Some(check) => {
+                keychain.open_entry(CHECK_ENTRY, check)?;
+            }
+            None => {
+                if let Some((id, entry)) = file.entries.iter().next() {
+                    keychain.open_entry(id, entry)?;
+                }
+                file.check = Some(keychain.seal_entry(CHECK_ENTRY, CHECK_ENTRY)?);
+                write_file(path, &file)?;
+            }
+        }
+
+        Ok(keychain)
+    }
+
+    /// Returns the path of the keychain file.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    fn seal_entry(&self, id: &str, value: &str) -> KeychainResult<SealedEntry> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = self
+            .inner
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: id.as_bytes(),
+                },
+            )
+            .map_err(|_| KeychainError::EncodingError("Failed to encrypt secret".to_string()))?;
+
+        Ok(SealedEntry {
+            nonce: encode_secret_key(&nonce),
+            ciphertext: encode_secret_key(&ciphertext),
+        })
+    }
+
+    fn open_entry(&self, id: &str, entry: &SealedEntry) -> KeychainResult<String> {
+        let nonce = decode_secret_key(&entry.nonce)?;
+        let ciphertext = decode_secret_key(&entry.ciphertext)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(KeychainError::EncodingError(
+                "Invalid nonce length".to_string(),
+            ));
+        }
+
+        let plaintext = self
+            .inner
+            .cipher
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: id.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                KeychainError::AccessDenied("wrong keychain key or corrupted entry".to_string())
+            })?;
+
+        String::from_utf8(plaintext).map_err(|e| KeychainError::EncodingError(e.to_string()))
+    }
+
+    fn lock(&self) -> KeychainResult<std::sync::MutexGuard<'_, ()>> {
+        self.inner
+            .lock
+            .lock()
+            .map_err(|_| KeychainError::PlatformError("Keychain file lock poisoned".to_string()))
+    }
+
+    fn load(&self) -> KeychainResult<KeychainFile> {
+        Ok(read_file(&self.inner.path)?.unwrap_or_else(KeychainFile::new))
+    }
+}
+
+impl KeychainBackend for FileKeychain {
+    fn get_secret(&self, service: &str, key: &str) -> KeychainResult<String> {
+        let _guard = self.lock()?;
+        let id = entry_id(service, key);
+        let file = self.load()?;
+        let entry = file
+            .entries
+            .get(&id)
+            .ok_or_else(|| KeychainError::NotFound(key.to_string()))?;
+        self.open_entry(&id, entry)
+    }
+
+    fn set_secret(&self, service: &str, key: &str, value: &str) -> KeychainResult<()> {
+        let _guard = self.lock()?;
+        let id = entry_id(service, key);
+        let mut file = self.load()?;
+        let entry = self.seal_entry(&id, value)?;
+        file.entries.insert(id, entry);
+        write_file(&self.inner.path, &file)
+    }
+
+    fn delete_secret(&self, service: &str, key: &str) -> KeychainResult<()> {
+        let _guard = self.lock()?;
+        let mut file = self.load()?;
+        if file.entries.remove(&entry_id(service, key)).is_none() {
+            return Err(KeychainError::NotFound(key.to_string()));
+        }
+        write_file(&self.inner.path, &file)
+    }
+}
+
+/// Returns the identifier of an entry, also used as associated data.
+fn entry_id(service: &str, key: &str) -> String {
+    format!("{}/{}", service, key)
+}
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> KeychainError {
+    KeychainError::PlatformError(format!("Failed to {} {}: {}", action, path.display(), e))
+}
+
+/// Reads the keychain file, returning `None` if it does not exist.
+fn read_file(path: &Path) -> KeychainResult<Option<KeychainFile>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io_error("read", path, e)),
+    };
+
+    let file: KeychainFile = serde_json::from_str(&contents)
+        .map_err(|e| KeychainError::EncodingError(format!("Invalid keychain file: {}", e)))?;
+    if file.version != FILE_VERSION {
+        return Err(KeychainError::EncodingError(format!(
+            "Unsupported keychain file version: {}",
+            file.version
+        )));
+    }
+    Ok(Some(file))
+}
+
+/// Writes a file atomically with owner-only permissions.
+fn write_private(path: &Path, contents: &[u8]) -> KeychainResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| io_error("create", parent, e))?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    {
+        use std::io::Write;
+        let mut file = options
+            .open(&temp_path)
+            .map_err(|e| io_error("write", &temp_path, e))?;
+        file.write_all(contents)
+            .map_err(|e| io_error("write", &temp_path, e))?;
+    }
+
+    std::fs::rename(&temp_path, path).map_err(|e| io_error("replace", path, e))
+}
+
+fn write_file(path: &Path, file: &KeychainFile) -> KeychainResult<()> {
+    let contents =
+        serde_json::to_vec_pretty(file).map_err(|e| KeychainError::EncodingError(e.to_string()))?;
+    write_private(path, &contents)
+}
+
+/// Loads the master key from a key file, creating it if needed.
+fn load_or_create_key_file(path: &Path) -> KeychainResult<[u8; MASTER_KEY_LEN]> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => decode_secret_key(contents.trim())?
+            .try_into()
+            .map_err(|_| KeychainError::EncodingError("Invalid keychain key file".to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut key = [0u8; MASTER_KEY_LEN];
+            rand::thread_rng()
+                .try_fill_bytes(&mut key)
+                .map_err(|e| KeychainError::GenerationError(e.to_string()))?;
+            write_private(path, encode_secret_key(&key).as_bytes())?;
+            Ok(key)
+        }
+        Err(e) => Err(io_error("read", path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Cheap parameters so tests do not spend seconds in Argon2.
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_key_file_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("keychain.json");
+        let key_path = temp_dir.path().join("keychain.key");
+
+        let keychain = FileKeychain::open_with_key_file(&path, &key_path).unwrap();
+        keychain
+            .set_secret("remoshell", "device", "secret-value")
+            .unwrap();
+        assert_eq!(
+            keychain.get_secret("remoshell", "device").unwrap(),
+            "secret-value"
+        );
+
+        // The value is not stored in the clear
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("secret-value"));
+
+        // Secrets survive reopening
+        let reopened = FileKeychain::open_with_key_file(&path, &key_path).unwrap();
+        assert_eq!(
+            reopened.get_secret("remoshell", "device").unwrap(),
+            "secret-value"
+        );
+
+        reopened.delete_secret("remoshell", "device").unwrap();
+        assert!(matches!(
+            reopened.get_secret("remoshell", "device"),
+            Err(KeychainError::NotFound(_))
+        ));
+        assert!(matches!(
+            reopened.delete_secret("remoshell", "device"),
+            Err(KeychainError::NotFound(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("keychain.json");
+        let key_path = temp_dir.path().join("keychain.key");
+        let keychain = FileKeychain::open_with_key_file(&path, &key_path).unwrap();
+        keychain.set_secret("remoshell", "device", "value").unwrap();
+
+        for file in [&path, &key_path] {
+            let mode = std::fs::metadata(file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_passphrase_mode() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("keychain.json");
+
+        let keychain = FileKeychain::open_with_kdf(&path, "correct horse", TEST_KDF).unwrap();
+        keychain.set_secret("remoshell", "device", "value").unwrap();
+
+        let reopened = FileKeychain::open_with_kdf(&path, "correct horse", TEST_KDF).unwrap();
+        assert_eq!(reopened.get_secret("remoshell", "device").unwrap(), "value");
+
+        // A wrong passphrase is rejected even when the file holds no secrets
+        reopened.delete_secret("remoshell", "device").unwrap();
+
+        assert!(matches!(
+            FileKeychain::open_with_kdf(&path, "battery staple", TEST_KDF),
+            Err(KeychainError::AccessDenied(_))
+        ));
+        assert!(matches!(
+            FileKeychain::open_with_key_file(&path, temp_dir.path().join("keychain.key")),
+            Err(KeychainError::AccessDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_entries_bound_to_their_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("keychain.json");
+        let keychain =
+            FileKeychain::open_with_key_file(&path, temp_dir.path().join("keychain.key")).unwrap();
+        keychain.set_secret("remoshell", "a", "value-a").unwrap();
+
+        // Moving a ciphertext to another entry name is detected
+        let mut file = read_file(&path).unwrap().unwrap();
+        let entry = file.entries["remoshell/a"].clone();
+        file.entries.insert("remoshell/b".to_string(), entry);
+        write_file(&path, &file).unwrap();
+
+        assert!(matches!(
+            keychain.get_secret("remoshell", "b"),
+            Err(KeychainError::AccessDenied(_))
+        ));
+    }
+}
@@ -6,15 +6,47 @@
 //! - Linux: Secret Service (via D-Bus)
 //! - Android: Android Keystore (when compiled for Android)
 //! - iOS: iOS Keychain (when compiled for iOS)
+//!
+//! Where no system keychain is available, such as on headless Linux without
+//! a Secret Service, secrets can be kept in an encrypted file instead (see
+//! [`FileKeychain`](super::FileKeychain)). The backend is selected with the
+//! `REMOSHELL_KEYCHAIN` environment variable:
+//! - `auto` (default): the system keychain if it responds, otherwise a file
+//! - `system`: always the system keychain
+//! - `file`: always a file
+//!
+//! The file is `keychain.json` next to the database unless
+//! `REMOSHELL_KEYCHAIN_FILE` is set. Its key is derived from
+//! `REMOSHELL_KEYCHAIN_PASSPHRASE` if set, and otherwise read from a random
+//! key file created next to it.
 
+use std::str::FromStr;
 use thiserror::Error;
 
+use super::profile::DEFAULT_PROFILE;
+
 /// The service name used for keychain entries.
 const SERVICE_NAME: &str = "remoshell";
 
 /// The default key name for the device secret.
 const DEFAULT_KEY_NAME: &str = "device_secret_key";
 
+/// The key name for the database encryption key.
+const DATABASE_KEY_NAME: &str = "database_key";
+
+/// Environment variable selecting the keychain backend.
+pub const KEYCHAIN_BACKEND_ENV: &str = "REMOSHELL_KEYCHAIN";
+
+/// Environment variable overriding the location of the keychain file.
+pub const KEYCHAIN_FILE_ENV: &str = "REMOSHELL_KEYCHAIN_FILE";
+
+/// Environment variable holding the passphrase of the keychain file.
+pub const KEYCHAIN_PASSPHRASE_ENV: &str = "REMOSHELL_KEYCHAIN_PASSPHRASE";
+
+/// Default name of the keychain file.
+#[cfg(not(test))]
+const KEYCHAIN_FILE_NAME: &str = "keychain.json";
+
 /// Errors that can occur during keychain operations.
 #[derive(Debug, Error)]
 pub enum KeychainError {
@@ -63,8 +95,26 @@ pub trait KeychainBackend: Send + Sync {
 
 /// Real keychain backend using the system keychain.
 #[cfg(not(test))]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct SystemKeychain;
 
+#[cfg(not(test))]
+impl SystemKeychain {
+    /// Returns true if the system keychain responds.
+    ///
+    /// Looking up an entry that does not exist is enough to reach the
+    /// platform service, so nothing is written.
+    pub fn is_available() -> bool {
+        match SystemKeychain.get_secret(SERVICE_NAME, "availability_probe") {
+            Ok(_) | Err(KeychainError::NotFound(_)) => true,
+            Err(e) => {
+                tracing::debug!("System keychain unavailable: {}", e);
+                false
+            }
+        }
+    }
+}
+
 #[cfg(not(test))]
 impl KeychainBackend for SystemKeychain {
     fn get_secret(&self, service: &str, key: &str) -> KeychainResult<String> {
@@ -127,10 +177,121 @@ impl KeychainBackend for SystemKeychain {
     }
 }
 
+/// Which keychain backend to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeychainMode {
+    /// The system keychain if available, otherwise an encrypted file.
+    #[default]
+    Auto,
+    /// Always the system keychain.
+    System,
+    /// Always an encrypted file.
+    File,
+}
+
+impl KeychainMode {
+    /// Reads the mode from `REMOSHELL_KEYCHAIN`, defaulting to `Auto`.
+    pub fn from_env() -> KeychainResult<Self> {
+        match std::env::var(KEYCHAIN_BACKEND_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::Auto),
+        }
+    }
+}
+
+impl FromStr for KeychainMode {
+    type Err = KeychainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "auto" => Ok(Self::Auto),
+            "system" => Ok(Self::System),
+            "file" => Ok(Self::File),
+            other => Err(KeychainError::PlatformError(format!(
+                "Unknown keychain backend {:?} in {}",
+                other, KEYCHAIN_BACKEND_ENV
+            ))),
+        }
+    }
+}
+
+/// Keychain backend chosen at startup: the system keychain or an encrypted
+/// file.
+#[cfg(not(test))]
+#[derive(Debug, Clone)]
+pub enum AutoKeychain {
+    /// The system keychain.
+    System(SystemKeychain),
+    /// An encrypted file.
+    File(super::FileKeychain),
+}
+
+#[cfg(not(test))]
+impl Default for AutoKeychain {
+    fn default() -> Self {
+        Self::System(SystemKeychain)
+    }
+}
+
+#[cfg(not(test))]
+impl AutoKeychain {
+    /// Selects the backend from the environment.
+    ///
+    /// `default_dir` holds the keychain file unless `REMOSHELL_KEYCHAIN_FILE`
+    /// is set.
+    pub fn from_env(default_dir: &std::path::Path) -> KeychainResult<Self> {
+        let use_file = match KeychainMode::from_env()? {
+            KeychainMode::System => false,
+            KeychainMode::File => true,
+            KeychainMode::Auto => !SystemKeychain::is_available(),
+        };
+        if !use_file {
+            return Ok(Self::System(SystemKeychain));
+        }
+
+        let path = std::env::var_os(KEYCHAIN_FILE_ENV)
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(|| default_dir.join(KEYCHAIN_FILE_NAME));
+        let keychain = match std::env::var(KEYCHAIN_PASSPHRASE_ENV) {
+            Ok(passphrase) => super::FileKeychain::open_with_passphrase(&path, &passphrase)?,
+            Err(_) => super::FileKeychain::open_with_key_file(&path, path.with_extension("key"))?,
+        };
+        tracing::info!("Using file keychain at {}", path.display());
+        Ok(Self::File(keychain))
+    }
+}
+
+#[cfg(not(test))]
+impl KeychainBackend for AutoKeychain {
+    fn get_secret(&self, service: &str, key: &str) -> KeychainResult<String> {
+        match self {
+            Self::System(backend) => backend.get_secret(service, key),
+            Self::File(backend) => backend.get_secret(service, key),
+        }
+    }
+
+    fn set_secret(&self, service: &str, key: &str, value: &str) -> KeychainResult<()> {
+        match self {
+            Self::System(backend) => backend.set_secret(service, key, value),
+            Self::File(backend) => backend.set_secret(service, key, value),
+        }
+    }
+
+    fn delete_secret(&self, service: &str, key: &str) -> KeychainResult<()> {
+        match self {
+            Self::System(backend) => backend.delete_secret(service, key),
+            Self::File(backend) => backend.delete_secret(service, key),
+        }
+    }
+}
+
 /// Mock keychain backend for testing.
+///
+/// Clones share the same storage.
 #[cfg(test)]
+#[derive(Clone)]
 pub struct MockKeychain {
-    storage: std::sync::Mutex<std::collections::HashMap<String, String>>,
+    storage: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, String>>>,
 }
 
 #[cfg(test)]
@@ -138,7 +299,7 @@ impl MockKeychain {
     /// Create a new mock keychain.
     pub fn new() -> Self {
         Self {
-            storage: std::sync::Mutex::new(std::collections::HashMap::new()),
+            storage: Default::default(),
         }
     }
 
@@ -213,6 +374,31 @@ impl<B: KeychainBackend> KeychainManager<B> {
         }
     }
 
+    /// Create a new KeychainManager for the secret key of a profile.
+    ///
+    /// The default profile uses the original key name, so identities created
+    /// before profiles existed are kept.
+    pub fn for_profile(backend: B, profile: &str) -> Self {
+        Self::with_names(
+            backend,
+            SERVICE_NAME,
+            profile_key_name(DEFAULT_KEY_NAME, profile),
+        )
+    }
+
+    /// Create a new KeychainManager for the database encryption key of a
+    /// profile.
+    ///
+    /// The key is generated and stored the same way as the device secret
+    /// key, under a separate entry.
+    pub fn for_database_key(backend: B, profile: &str) -> Self {
+        Self::with_names(
+            backend,
+            SERVICE_NAME,
+            profile_key_name(DATABASE_KEY_NAME, profile),
+        )
+    }
+
     /// Get the secret key from the keychain.
     ///
     /// Returns the secret key as a base64-encoded string, or an error if not found.
@@ -253,6 +439,15 @@ impl<B: KeychainBackend> KeychainManager<B> {
     }
 }
 
+/// Returns the keychain entry name of a key for a profile.
+fn profile_key_name(base: &str, profile: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        base.to_string()
+    } else {
+        format!("{}.{}", base, profile)
+    }
+}
+
 /// Generate a new random secret key.
 ///
 /// Returns a 32-byte random key encoded as base64.
@@ -448,4 +643,32 @@ mod tests {
         let retrieved = manager.get_secret_key().expect("Failed to get key");
         assert_eq!(retrieved, key2, "Should return the overwritten key");
     }
+
+    #[test]
+    fn test_profile_key_names() {
+        let default = KeychainManager::for_profile(MockKeychain::new(), DEFAULT_PROFILE);
+        assert_eq!(default.service, SERVICE_NAME);
+        assert_eq!(default.key_name, DEFAULT_KEY_NAME);
+
+        let work = KeychainManager::for_profile(MockKeychain::new(), "work");
+        assert_eq!(work.key_name, "device_secret_key.work");
+
+        let database = KeychainManager::for_database_key(MockKeychain::new(), "work");
+        assert_eq!(database.key_name, "database_key.work");
+    }
+
+    #[test]
+    fn test_keychain_mode_parse() {
+        assert_eq!("auto".parse::<KeychainMode>().unwrap(), KeychainMode::Auto);
+        assert_eq!("".parse::<KeychainMode>().unwrap(), KeychainMode::Auto);
+        assert_eq!(
+            "System".parse::<KeychainMode>().unwrap(),
+            KeychainMode::System
+        );
+        assert_eq!(
+            " file ".parse::<KeychainMode>().unwrap(),
+            KeychainMode::File
+        );
+        assert!("libsecret".parse::<KeychainMode>().is_err());
+    }
 }
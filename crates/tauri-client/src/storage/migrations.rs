@@ -0,0 +1,405 @@
+//! Versioned schema migrations for the client database.
+//!
+//! Each migration has an `up` script, a `down` script that reverts it, and a
+//! version that is tracked in `PRAGMA user_version`. Applied migrations are
+//! also recorded in the `schema_migrations` table together with a checksum
+//! of their `up` script, so a migration that was edited after release is
+//! detected instead of leaving databases with diverging schemas.
+//!
+//! To change the schema, append a new [`Migration`] to [`MIGRATIONS`]; never
+//! edit or reorder a migration that has been released.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use super::database::{DatabaseError, StorageResult};
+
+/// A single schema migration.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Schema version after the migration is applied.
+    pub version: i32,
+    /// Short descriptive name.
+    pub name: &'static str,
+    /// SQL applying the migration.
+    pub up: &'static str,
+    /// SQL reverting the migration.
+    pub down: &'static str,
+}
+
+impl Migration {
+    /// Returns the hex-encoded SHA-256 checksum of the `up` script.
+    pub fn checksum(&self) -> String {
+        Sha256::digest(self.up.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// Status of a migration in a database.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MigrationInfo {
+    /// Schema version after the migration is applied.
+    pub version: i32,
+    /// Short descriptive name.
+    pub name: String,
+    /// Unix timestamp when the migration was applied, if it was.
+    pub applied_at: Option<i64>,
+}
+
+/// All migrations, in version order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        up: r#"
+        CREATE TABLE IF NOT EXISTS paired_devices (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            public_key TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            last_seen INTEGER
+        );
+
+        CREATE TABLE IF NOT EXISTS connection_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            device_id TEXT NOT NULL,
+            connected_at INTEGER NOT NULL,
+            disconnected_at INTEGER,
+            successful INTEGER NOT NULL DEFAULT 1,
+            FOREIGN KEY (device_id) REFERENCES paired_devices(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_connection_history_device_id
+        ON connection_history(device_id);
+
+        CREATE INDEX IF NOT EXISTS idx_connection_history_connected_at
+        ON connection_history(connected_at DESC);
+
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+    "#,
+        down: r#"
+        DROP TABLE IF EXISTS settings;
+        DROP TABLE IF EXISTS connection_history;
+        DROP TABLE IF EXISTS paired_devices;
+    "#,
+    },
+    Migration {
+        version: 2,
+        name: "paired_device_mac_address",
+        up: r#"
+            ALTER TABLE paired_devices ADD COLUMN mac_address TEXT;
+        "#,
+        down: r#"
+            ALTER TABLE paired_devices DROP COLUMN mac_address;
+        "#,
+    },
+    Migration {
+        version: 3,
+        name: "session_recordings",
+        up: r#"
+            CREATE TABLE IF NOT EXISTS recordings (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                format TEXT NOT NULL,
+                path TEXT NOT NULL,
+                started_at INTEGER NOT NULL,
+                stopped_at INTEGER,
+                size_bytes INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_recordings_started_at
+            ON recordings(started_at DESC);
+        "#,
+        down: r#"
+            DROP TABLE IF EXISTS recordings;
+        "#,
+    },
+];
+
+/// Returns the schema version reached after all migrations.
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Creates the bookkeeping table if needed.
+fn ensure_migrations_table(conn: &Connection) -> StorageResult<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
+fn schema_version(conn: &Connection) -> StorageResult<i32> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Returns the recorded checksum and timestamp of each applied migration.
+fn recorded(conn: &Connection) -> StorageResult<HashMap<i32, (String, i64)>> {
+    let mut stmt = conn.prepare("SELECT version, checksum, applied_at FROM schema_migrations")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+    Ok(rows)
+}
+
+/// Checks the database against the known migrations.
+///
+/// Migrations applied before `schema_migrations` existed are recorded with
+/// their current checksum.
+fn validate(conn: &Connection) -> StorageResult<()> {
+    ensure_migrations_table(conn)?;
+
+    let version = schema_version(conn)?;
+    if version > latest_version() {
+        return Err(DatabaseError::Migration(format!(
+            "database schema version {} is newer than the supported version {}",
+            version,
+            latest_version()
+        )));
+    }
+
+    let recorded = recorded(conn)?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= version) {
+        match recorded.get(&migration.version) {
+            Some((checksum, _)) if *checksum != migration.checksum() => {
+                return Err(DatabaseError::Migration(format!(
+                    "checksum mismatch for migration {} ({})",
+                    migration.version, migration.name
+                )));
+            }
+            Some(_) => {}
+            None => {
+                conn.execute(
+                    "INSERT INTO schema_migrations (version, name, checksum, applied_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        migration.version,
+                        migration.name,
+                        migration.checksum(),
+                        now()
+                    ],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates the database and applies all pending migrations in order.
+///
+/// Each migration runs in its own transaction.
+pub fn migrate(conn: &mut Connection) -> StorageResult<()> {
+    validate(conn)?;
+
+    let version = schema_version(conn)?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.up).map_err(|e| {
+            DatabaseError::Migration(format!(
+                "migration {} ({}) failed: {}",
+                migration.version, migration.name, e
+            ))
+        })?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, checksum, applied_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                migration.version,
+                migration.name,
+                migration.checksum(),
+                now()
+            ],
+        )?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", migration.version))?;
+        tx.commit()?;
+        tracing::info!(
+            "Applied database migration {} ({})",
+            migration.version,
+            migration.name
+        );
+    }
+
+    Ok(())
+}
+
+/// Reverts migrations until the schema is at `target` version.
+///
+/// Each migration is reverted in its own transaction, newest first.
+pub fn rollback(conn: &mut Connection, target: i32) -> StorageResult<()> {
+    if target < 0 {
+        return Err(DatabaseError::Migration(format!(
+            "invalid target version {}",
+            target
+        )));
+    }
+    validate(conn)?;
+
+    let version = schema_version(conn)?;
+    for migration in MIGRATIONS
+        .iter()
+        .rev()
+        .filter(|m| m.version > target && m.version <= version)
+    {
+        let previous = MIGRATIONS
+            .iter()
+            .map(|m| m.version)
+            .filter(|v| *v < migration.version)
+            .max()
+            .unwrap_or(0);
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.down).map_err(|e| {
+            DatabaseError::Migration(format!(
+                "rollback of migration {} ({}) failed: {}",
+                migration.version, migration.name, e
+            ))
+        })?;
+        tx.execute(
+            "DELETE FROM schema_migrations WHERE version = ?1",
+            params![migration.version],
+        )?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", previous))?;
+        tx.commit()?;
+        tracing::info!(
+            "Reverted database migration {} ({})",
+            migration.version,
+            migration.name
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the status of every known migration.
+pub fn status(conn: &Connection) -> StorageResult<Vec<MigrationInfo>> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'",
+            [],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    let recorded = if exists {
+        recorded(conn)?
+    } else {
+        HashMap::new()
+    };
+
+    Ok(MIGRATIONS
+        .iter()
+        .map(|m| MigrationInfo {
+            version: m.version,
+            name: m.name.to_string(),
+            applied_at: recorded.get(&m.version).map(|(_, applied_at)| *applied_at),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_exists(conn: &Connection, name: &str) -> bool {
+        conn.query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [name],
+            |_| Ok(()),
+        )
+        .optional()
+        .unwrap()
+        .is_some()
+    }
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let versions: Vec<i32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert!(versions.windows(2).all(|w| w[0] < w[1]));
+        assert!(versions.iter().all(|v| *v > 0));
+    }
+
+    #[test]
+    fn test_migrate_and_rollback() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(status(&conn)
+            .unwrap()
+            .iter()
+            .all(|m| m.applied_at.is_none()));
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        assert!(table_exists(&conn, "paired_devices"));
+        assert!(status(&conn)
+            .unwrap()
+            .iter()
+            .all(|m| m.applied_at.is_some()));
+
+        rollback(&mut conn, 0).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        assert!(!table_exists(&conn, "paired_devices"));
+        assert!(status(&conn)
+            .unwrap()
+            .iter()
+            .all(|m| m.applied_at.is_none()));
+
+        // Migrating again after a full rollback restores the schema
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn test_adopts_unrecorded_migrations() {
+        // A database created before schema_migrations existed
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0].up).unwrap();
+        conn.execute_batch("PRAGMA user_version = 1").unwrap();
+
+        migrate(&mut conn).unwrap();
+        assert!(status(&conn).unwrap()[0].applied_at.is_some());
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn.execute(
+            "UPDATE schema_migrations SET checksum = 'edited' WHERE version = 1",
+            [],
+        )
+        .unwrap();
+
+        let result = migrate(&mut conn);
+        assert!(matches!(result, Err(DatabaseError::Migration(msg)) if msg.contains("checksum")));
+    }
+
+    #[test]
+    fn test_newer_schema_rejected() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!("PRAGMA user_version = {}", latest_version() + 1))
+            .unwrap();
+
+        let result = migrate(&mut conn);
+        assert!(matches!(result, Err(DatabaseError::Migration(msg)) if msg.contains("newer")));
+    }
+}
@@ -3,21 +3,47 @@
 //! This module provides SQLite-based persistence for:
 //! - Paired devices
 //! - Connection history
+//! - The index of session recordings
 //! - Application settings
 //!
+//! The schema is managed by versioned migrations (see [`migrations`]).
+//!
+//! The database can optionally be encrypted at rest with SQLCipher.
+//!
 //! And secure keychain storage for:
 //! - Device secret keys
+//!
+//! with an encrypted-file fallback where no system keychain is available.
+//!
+//! Both can be exported together as a passphrase-encrypted identity backup.
+//! Each identity profile has its own keychain entry and database.
 
+mod backup;
 mod database;
+mod file_keychain;
 pub mod keychain;
+pub mod migrations;
+mod profile;
+
+pub use backup::{BackupError, BackupResult, IdentityBackup, KdfParams, BACKUP_VERSION};
 
 pub use database::{
-    ConnectionHistoryEntry, Database, DatabaseError, PairedDevice, Setting, StorageResult,
+    ConnectionHistoryEntry, Database, DatabaseError, DeviceConnectionStats, PairedDevice,
+    RecordingEntry, RetentionPolicy, Setting, StorageResult, DATABASE_KEY_LEN,
 };
 
+pub use file_keychain::FileKeychain;
+
 pub use keychain::{
     decode_secret_key, encode_secret_key, KeychainBackend, KeychainError, KeychainManager,
-    KeychainResult,
+    KeychainMode, KeychainResult,
+};
+
+pub use migrations::MigrationInfo;
+
+pub use profile::{
+    validate_profile_name, ProfileError, ProfileResult, ProfileStore, DEFAULT_PROFILE,
+    MAX_PROFILE_NAME_LEN,
 };
 
 #[cfg(test)]
@@ -52,6 +78,7 @@ mod tests {
             public_key: "test-public-key".to_string(),
             created_at: 1234567890,
             last_seen: None,
+            mac_address: None,
         };
 
         db.add_paired_device(&device)
@@ -79,6 +106,7 @@ mod tests {
             public_key: "key-1".to_string(),
             created_at: 1000,
             last_seen: None,
+            mac_address: None,
         };
 
         let device2 = PairedDevice {
@@ -87,6 +115,7 @@ mod tests {
             public_key: "key-2".to_string(),
             created_at: 2000,
             last_seen: Some(3000),
+            mac_address: None,
         };
 
         db.add_paired_device(&device1)
@@ -108,6 +137,7 @@ mod tests {
             public_key: "remove-key".to_string(),
             created_at: 1234567890,
             last_seen: None,
+            mac_address: None,
         };
 
         db.add_paired_device(&device)
@@ -131,6 +161,7 @@ mod tests {
             public_key: "seen-key".to_string(),
             created_at: 1000,
             last_seen: None,
+            mac_address: None,
         };
 
         db.add_paired_device(&device).expect("Failed to add device");
@@ -145,6 +176,42 @@ mod tests {
         assert_eq!(retrieved.last_seen, Some(2000));
     }
 
+    #[test]
+    fn test_update_device_mac_address() {
+        let (db, _temp_dir) = create_test_db();
+
+        let device = PairedDevice {
+            id: "device-wol".to_string(),
+            name: "Wakeable Device".to_string(),
+            public_key: "wol-key".to_string(),
+            created_at: 1000,
+            last_seen: None,
+            mac_address: None,
+        };
+        db.add_paired_device(&device).expect("Failed to add device");
+
+        assert!(db
+            .update_device_mac_address("device-wol", Some("00:11:22:aa:bb:cc"))
+            .expect("Failed to set MAC address"));
+        let retrieved = db
+            .get_paired_device("device-wol")
+            .expect("Failed to get device")
+            .expect("Device should exist");
+        assert_eq!(retrieved.mac_address.as_deref(), Some("00:11:22:aa:bb:cc"));
+
+        db.update_device_mac_address("device-wol", None)
+            .expect("Failed to clear MAC address");
+        let retrieved = db
+            .get_paired_device("device-wol")
+            .expect("Failed to get device")
+            .expect("Device should exist");
+        assert_eq!(retrieved.mac_address, None);
+
+        assert!(!db
+            .update_device_mac_address("missing", Some("00:11:22:aa:bb:cc"))
+            .expect("Failed to update missing device"));
+    }
+
     #[test]
     fn test_connection_history() {
         let (db, _temp_dir) = create_test_db();
@@ -156,6 +223,7 @@ mod tests {
             public_key: "test-key".to_string(),
             created_at: 500,
             last_seen: None,
+            mac_address: None,
         };
         db.add_paired_device(&device).expect("Failed to add device");
 
@@ -263,6 +331,7 @@ mod tests {
                 public_key: "persist-key".to_string(),
                 created_at: 1234567890,
                 last_seen: None,
+                mac_address: None,
             };
             db.add_paired_device(&device).expect("Failed to add device");
             db.set_setting("persistent_setting", "persistent_value")
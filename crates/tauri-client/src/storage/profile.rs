@@ -0,0 +1,228 @@
+//! Client identity profiles.
+//!
+//! A profile pairs a device secret key in the keychain with its own SQLite
+//! database of paired devices, so one client can hold several identities
+//! (for example "work" and "personal") for daemons that trust only one of
+//! them.
+//!
+//! The `default` profile uses the database path passed at initialization and
+//! the original keychain entry, so existing installs keep their identity.
+//! Other profiles are stored as `profiles/<name>.db` next to that database.
+
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Name of the profile used when none is selected.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Directory, next to the default database, holding the other profiles.
+const PROFILES_DIR: &str = "profiles";
+
+/// File extension of profile databases.
+const DATABASE_EXTENSION: &str = "db";
+
+/// Maximum length of a profile name.
+pub const MAX_PROFILE_NAME_LEN: usize = 32;
+
+/// Errors that can occur during profile operations.
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    /// The profile name contains unsupported characters or is too long.
+    #[error("Invalid profile name: {0}")]
+    InvalidName(String),
+
+    /// The profile does not exist.
+    #[error("Profile not found: {0}")]
+    NotFound(String),
+
+    /// A profile with the same name already exists.
+    #[error("Profile already exists: {0}")]
+    AlreadyExists(String),
+
+    /// The operation is not allowed on this profile.
+    #[error("Operation not allowed on profile {0}: {1}")]
+    NotAllowed(String, String),
+
+    /// Filesystem error.
+    #[error("Profile I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Result type for profile operations.
+pub type ProfileResult<T> = Result<T, ProfileError>;
+
+/// Checks that a profile name is 1-32 lowercase letters, digits, `-` or `_`.
+pub fn validate_profile_name(name: &str) -> ProfileResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_PROFILE_NAME_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ProfileError::InvalidName(name.to_string()))
+    }
+}
+
+/// Locates the databases of all profiles.
+#[derive(Debug, Clone)]
+pub struct ProfileStore {
+    default_database: PathBuf,
+}
+
+impl ProfileStore {
+    /// Creates a store rooted at the default profile's database path.
+    pub fn new<P: AsRef<Path>>(default_database: P) -> Self {
+        Self {
+            default_database: default_database.as_ref().to_path_buf(),
+        }
+    }
+
+    fn profiles_dir(&self) -> PathBuf {
+        self.default_database
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(PROFILES_DIR)
+    }
+
+    /// Returns the database path of a profile.
+    pub fn database_path(&self, profile: &str) -> ProfileResult<PathBuf> {
+        if profile == DEFAULT_PROFILE {
+            return Ok(self.default_database.clone());
+        }
+
+        validate_profile_name(profile)?;
+        Ok(self
+            .profiles_dir()
+            .join(profile)
+            .with_extension(DATABASE_EXTENSION))
+    }
+
+    /// Returns true if the profile exists.
+    ///
+    /// The default profile always exists.
+    pub fn exists(&self, profile: &str) -> bool {
+        profile == DEFAULT_PROFILE
+            || self
+                .database_path(profile)
+                .map(|path| path.exists())
+                .unwrap_or(false)
+    }
+
+    /// Lists all profiles, starting with the default profile.
+    pub fn list(&self) -> ProfileResult<Vec<String>> {
+        let mut profiles = Vec::new();
+
+        match std::fs::read_dir(self.profiles_dir()) {
+            Ok(entries) => {
+                for entry in entries {
+                    let path = entry?.path();
+                    if path.extension().and_then(|e| e.to_str()) != Some(DATABASE_EXTENSION) {
+                        continue;
+                    }
+                    if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                        if name != DEFAULT_PROFILE && validate_profile_name(name).is_ok() {
+                            profiles.push(name.to_string());
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        profiles.sort();
+        profiles.insert(0, DEFAULT_PROFILE.to_string());
+        Ok(profiles)
+    }
+
+    /// Returns the database path for a new profile, creating its directory.
+    ///
+    /// Fails if the profile already exists.
+    pub fn prepare(&self, profile: &str) -> ProfileResult<PathBuf> {
+        if self.exists(profile) {
+            return Err(ProfileError::AlreadyExists(profile.to_string()));
+        }
+
+        let path = self.database_path(profile)?;
+        std::fs::create_dir_all(self.profiles_dir())?;
+        Ok(path)
+    }
+
+    /// Deletes a profile's database.
+    ///
+    /// The default profile cannot be deleted.
+    pub fn remove(&self, profile: &str) -> ProfileResult<()> {
+        if profile == DEFAULT_PROFILE {
+            return Err(ProfileError::NotAllowed(
+                profile.to_string(),
+                "the default profile cannot be deleted".to_string(),
+            ));
+        }
+        if !self.exists(profile) {
+            return Err(ProfileError::NotFound(profile.to_string()));
+        }
+
+        std::fs::remove_file(self.database_path(profile)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate_profile_name() {
+        assert!(validate_profile_name("work").is_ok());
+        assert!(validate_profile_name("home-lab_2").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("Work").is_err());
+        assert!(validate_profile_name("../escape").is_err());
+        assert!(validate_profile_name(&"a".repeat(MAX_PROFILE_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_profile_database_paths() {
+        let store = ProfileStore::new("/data/remoshell.db");
+        assert_eq!(
+            store.database_path(DEFAULT_PROFILE).unwrap(),
+            PathBuf::from("/data/remoshell.db")
+        );
+        assert_eq!(
+            store.database_path("work").unwrap(),
+            PathBuf::from("/data/profiles/work.db")
+        );
+        assert!(store.database_path("bad/name").is_err());
+    }
+
+    #[test]
+    fn test_profile_lifecycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ProfileStore::new(temp_dir.path().join("remoshell.db"));
+        assert_eq!(store.list().unwrap(), vec![DEFAULT_PROFILE]);
+
+        let path = store.prepare("work").unwrap();
+        std::fs::write(&path, b"").unwrap();
+        assert!(store.exists("work"));
+        assert!(matches!(
+            store.prepare("work"),
+            Err(ProfileError::AlreadyExists(_))
+        ));
+        assert_eq!(store.list().unwrap(), vec![DEFAULT_PROFILE, "work"]);
+
+        store.remove("work").unwrap();
+        assert!(!store.exists("work"));
+        assert!(matches!(
+            store.remove("work"),
+            Err(ProfileError::NotFound(_))
+        ));
+        assert!(matches!(
+            store.remove(DEFAULT_PROFILE),
+            Err(ProfileError::NotAllowed(_, _))
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+//! Wake-on-LAN for paired daemons on the local network.
+//!
+//! A daemon's host can be woken from sleep or power-off by broadcasting a
+//! magic packet: six `0xFF` bytes followed by its MAC address repeated
+//! sixteen times, sent over UDP. MAC addresses are stored with the paired
+//! device, since the daemon cannot report it while the host is down.
+
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use thiserror::Error;
+use tokio::net::UdpSocket;
+
+/// Address magic packets are sent to by default: the limited broadcast
+/// address on the discard port.
+pub const DEFAULT_BROADCAST_ADDR: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, 9));
+
+/// Length of a magic packet in bytes.
+pub const MAGIC_PACKET_LEN: usize = 6 + 16 * 6;
+
+/// Errors from waking a host.
+#[derive(Debug, Error)]
+pub enum WolError {
+    #[error("invalid MAC address {0:?}")]
+    InvalidMacAddress(String),
+
+    #[error("failed to send magic packet: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Parses a MAC address written as six hex bytes separated by `:` or `-`.
+pub fn parse_mac_address(text: &str) -> Result<[u8; 6], WolError> {
+    let invalid = || WolError::InvalidMacAddress(text.to_string());
+
+    let parts: Vec<&str> = text.trim().split([':', '-']).collect();
+    if parts.len() != 6 {
+        return Err(invalid());
+    }
+    let mut mac = [0u8; 6];
+    for (byte, part) in mac.iter_mut().zip(parts) {
+        if part.len() != 2 {
+            return Err(invalid());
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    Ok(mac)
+}
+
+/// Formats a MAC address as lowercase, colon-separated hex.
+pub fn format_mac_address(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Builds the magic packet that wakes the host with the given MAC address.
+pub fn magic_packet(mac: &[u8; 6]) -> [u8; MAGIC_PACKET_LEN] {
+    let mut packet = [0xFFu8; MAGIC_PACKET_LEN];
+    for chunk in packet[6..].chunks_exact_mut(6) {
+        chunk.copy_from_slice(mac);
+    }
+    packet
+}
+
+/// Broadcasts a magic packet for `mac` to `target`.
+pub async fn send_magic_packet(mac: &[u8; 6], target: SocketAddr) -> Result<(), WolError> {
+    let bind: SocketAddr = if target.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.set_broadcast(true)?;
+    socket.send_to(&magic_packet(mac), target).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mac_address() {
+        let mac = [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc];
+        assert_eq!(parse_mac_address("00:11:22:aa:bb:cc").unwrap(), mac);
+        assert_eq!(parse_mac_address("00-11-22-AA-BB-CC").unwrap(), mac);
+        assert_eq!(format_mac_address(&mac), "00:11:22:aa:bb:cc");
+
+        for invalid in [
+            "",
+            "00:11:22:aa:bb",
+            "00:11:22:aa:bb:cc:dd",
+            "0:11:22:aa:bb:cc",
+            "zz:11:22:aa:bb:cc",
+        ] {
+            assert!(parse_mac_address(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_magic_packet() {
+        let mac = [1, 2, 3, 4, 5, 6];
+        let packet = magic_packet(&mac);
+        assert_eq!(&packet[..6], &[0xFF; 6]);
+        assert!(packet[6..].chunks(6).all(|chunk| chunk == mac));
+    }
+
+    #[tokio::test]
+    async fn test_send_magic_packet() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mac = [1, 2, 3, 4, 5, 6];
+
+        send_magic_packet(&mac, receiver.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; 256];
+        let len = receiver.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], &magic_packet(&mac)[..]);
+    }
+}
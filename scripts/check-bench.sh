@@ -0,0 +1,81 @@
+#!/usr/bin/env bash
+# Benchmark Threshold Check for RemoShell
+# Compares the latest criterion results against bench-thresholds.txt
+#
+# Usage: ./scripts/check-bench.sh [thresholds-file]
+# Run after `cargo bench`; results are read from target/criterion.
+
+set -euo pipefail
+
+# Colors for output
+RED='\033[0;31m'
+GREEN='\033[0;32m'
+YELLOW='\033[0;33m'
+NC='\033[0m' # No Color
+
+# Script configuration
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+PROJECT_ROOT="$(cd "$SCRIPT_DIR/.." && pwd)"
+THRESHOLDS="${1:-$PROJECT_ROOT/bench-thresholds.txt}"
+CRITERION_DIR="${CARGO_TARGET_DIR:-$PROJECT_ROOT/target}/criterion"
+
+if [[ ! -f "$THRESHOLDS" ]]; then
+    echo -e "${RED}Error: Thresholds file not found: $THRESHOLDS${NC}" >&2
+    exit 1
+fi
+
+# Convert a limit to nanoseconds
+to_ns() {
+    local value="$1" unit="$2"
+    case "$unit" in
+        ns) echo "$value" ;;
+        us) awk -v v="$value" 'BEGIN { print v * 1000 }' ;;
+        ms) awk -v v="$value" 'BEGIN { print v * 1000000 }' ;;
+        *)
+            echo -e "${RED}Error: Unknown unit '$unit' in $THRESHOLDS${NC}" >&2
+            exit 1
+            ;;
+    esac
+}
+
+FAILED=0
+MISSING=0
+CHECKED=0
+
+while read -r id value unit _; do
+    # Skip comments and blank lines
+    [[ -z "$id" || "$id" == \#* ]] && continue
+
+    estimates="$CRITERION_DIR/$id/new/estimates.json"
+    if [[ ! -f "$estimates" ]]; then
+        echo -e "${YELLOW}  missing  $id (no results in $CRITERION_DIR)${NC}"
+        MISSING=$((MISSING + 1))
+        continue
+    fi
+
+    mean=$(sed -n 's/.*"mean":{"confidence_interval":{[^}]*},"point_estimate":\([0-9.eE+-]*\).*/\1/p' "$estimates")
+    if [[ -z "$mean" ]]; then
+        echo -e "${RED}Error: Could not read the mean from $estimates${NC}" >&2
+        exit 1
+    fi
+    limit=$(to_ns "$value" "$unit")
+    CHECKED=$((CHECKED + 1))
+
+    if awk -v m="$mean" -v l="$limit" 'BEGIN { exit !(m > l) }'; then
+        printf "${RED}  FAIL     %-40s %12.0f ns > %s %s${NC}\n" "$id" "$mean" "$value" "$unit"
+        FAILED=$((FAILED + 1))
+    else
+        printf "${GREEN}  ok       %-40s %12.0f ns${NC}\n" "$id" "$mean"
+    fi
+done < "$THRESHOLDS"
+
+echo ""
+if [[ $FAILED -gt 0 ]]; then
+    echo -e "${RED}$FAILED of $CHECKED benchmarks exceeded their thresholds${NC}"
+    exit 1
+fi
+if [[ $MISSING -gt 0 ]]; then
+    echo -e "${YELLOW}$MISSING benchmarks have no results; run cargo bench first${NC}"
+    exit 1
+fi
+echo -e "${GREEN}All $CHECKED benchmarks within thresholds${NC}"
@@ -0,0 +1,103 @@
+/**
+ * FleetStore Durable Object
+ *
+ * Reference implementation of a fleet enrollment endpoint. An operator
+ * registers enrollment tokens, each with the devices and permissions new
+ * daemons should start with (in the daemon's provisioning file format).
+ * A daemon configured with one of those tokens posts its device ID and
+ * public key on first start and gets the devices back.
+ */
+
+export interface FleetToken {
+  fleet?: string;
+  prune?: boolean;
+  devices: unknown[];
+  /** Enrollments left; unlimited when absent. */
+  uses_left?: number;
+}
+
+export interface EnrolledDaemon {
+  device_id: string;
+  public_key: string;
+  hostname: string;
+  version: string;
+  fleet?: string;
+  enrolled_at: number;
+}
+
+function json(body: unknown, status = 200): Response {
+  return new Response(JSON.stringify(body), {
+    status,
+    headers: { "Content-Type": "application/json" },
+  });
+}
+
+export class FleetStore implements DurableObject {
+  private state: DurableObjectState;
+
+  constructor(state: DurableObjectState, _env: unknown) {
+    this.state = state;
+  }
+
+  async fetch(request: Request): Promise<Response> {
+    const url = new URL(request.url);
+
+    // PUT /tokens/:token — Register or replace an enrollment token
+    const tokenMatch = url.pathname.match(/^\/tokens\/(.+)$/);
+    if (tokenMatch && request.method === "PUT") {
+      const entry = (await request.json()) as FleetToken;
+      await this.state.storage.put(`token:${tokenMatch[1]}`, JSON.stringify(entry));
+      return json({ ok: true });
+    }
+
+    // DELETE /tokens/:token — Revoke an enrollment token
+    if (tokenMatch && request.method === "DELETE") {
+      const existed = await this.state.storage.delete(`token:${tokenMatch[1]}`);
+      return existed ? json({ ok: true }) : json({ error: "Not found" }, 404);
+    }
+
+    // POST /enroll — Register a daemon and return its initial devices
+    if (url.pathname === "/enroll" && request.method === "POST") {
+      const body = (await request.json()) as {
+        token: string;
+        daemon: Omit<EnrolledDaemon, "fleet" | "enrolled_at">;
+      };
+      const raw = (await this.state.storage.get(`token:${body.token}`)) as
+        | string
+        | undefined;
+      if (!raw) {
+        return json({ error: "Invalid enrollment token" }, 401);
+      }
+      const entry = JSON.parse(raw) as FleetToken;
+      if (entry.uses_left !== undefined) {
+        if (entry.uses_left <= 0) {
+          return json({ error: "Enrollment token used up" }, 401);
+        }
+        entry.uses_left -= 1;
+        await this.state.storage.put(`token:${body.token}`, JSON.stringify(entry));
+      }
+
+      const daemon: EnrolledDaemon = {
+        ...body.daemon,
+        fleet: entry.fleet,
+        enrolled_at: Math.floor(Date.now() / 1000),
+      };
+      await this.state.storage.put(`daemon:${daemon.device_id}`, JSON.stringify(daemon));
+
+      return json({
+        fleet: entry.fleet,
+        prune: entry.prune ?? false,
+        devices: entry.devices,
+      });
+    }
+
+    // GET /daemons — List enrolled daemons
+    if (url.pathname === "/daemons" && request.method === "GET") {
+      const entries = await this.state.storage.list({ prefix: "daemon:" });
+      const daemons = [...entries.values()].map((raw) => JSON.parse(raw as string));
+      return json({ daemons });
+    }
+
+    return new Response("Method not allowed", { status: 405 });
+  }
+}
@@ -5,6 +5,7 @@
 
 import { describe, it, expect, beforeAll, afterAll } from "vitest";
 import { unstable_dev, Unstable_DevWorker } from "wrangler";
+import { webcrypto } from "node:crypto";
 
 interface SignalingMessage {
   type: string;
@@ -104,6 +105,7 @@ describe("Signaling Worker", () => {
         ROOM_TTL_SECONDS: "60",
         RATE_LIMIT_MESSAGES_PER_SECOND: "10",
         PAIRING_TTL_SECONDS: "300",
+        FLEET_ADMIN_TOKEN: "test-admin-token",
       },
     });
   });
@@ -244,6 +246,159 @@ describe("Signaling Worker", () => {
     });
   });
 
+  describe("Fleet Enrollment", () => {
+    const admin = { Authorization: "Bearer test-admin-token" };
+    const device = {
+      device_id: "a1:b2:c3:d4",
+      name: "ops-laptop",
+      public_key: "dGVzdC1wdWJsaWMta2V5",
+      capabilities: ["agent-forwarding"],
+    };
+
+    async function enroll(token: string, deviceId: string): Promise<Response> {
+      return worker.fetch("/fleet/enroll", {
+        method: "POST",
+        headers: { "Content-Type": "application/json", Authorization: `Bearer ${token}` },
+        body: JSON.stringify({
+          device_id: deviceId,
+          public_key: "ZGFlbW9uLWtleQ==",
+          hostname: "build-07",
+          version: "0.1.0",
+        }),
+      });
+    }
+
+    it("should require the admin token to manage enrollment tokens", async () => {
+      const res = await worker.fetch("/fleet/tokens/ci-runners-token-0001", {
+        method: "PUT",
+        headers: { "Content-Type": "application/json", Authorization: "Bearer wrong" },
+        body: JSON.stringify({ devices: [] }),
+      });
+      expect(res.status).toBe(401);
+    });
+
+    it("should enroll a daemon and return its devices", async () => {
+      const put = await worker.fetch("/fleet/tokens/ci-runners-token-0001", {
+        method: "PUT",
+        headers: { "Content-Type": "application/json", ...admin },
+        body: JSON.stringify({ fleet: "ci-runners", devices: [device] }),
+      });
+      expect(put.status).toBe(200);
+
+      const res = await enroll("ci-runners-token-0001", "d1:d2:d3:d4");
+      expect(res.status).toBe(200);
+      const body = (await res.json()) as { fleet: string; prune: boolean; devices: unknown[] };
+      expect(body.fleet).toBe("ci-runners");
+      expect(body.prune).toBe(false);
+      expect(body.devices).toEqual([device]);
+
+      const list = await worker.fetch("/fleet/daemons", { headers: admin });
+      const daemons = (await list.json()) as { daemons: Array<{ device_id: string; hostname: string }> };
+      expect(daemons.daemons).toContainEqual(
+        expect.objectContaining({ device_id: "d1:d2:d3:d4", hostname: "build-07" })
+      );
+    });
+
+    it("should reject unknown and used-up enrollment tokens", async () => {
+      expect((await enroll("no-such-token-00001", "e1:e2")).status).toBe(401);
+      expect((await enroll("", "e1:e2")).status).toBe(401);
+
+      await worker.fetch("/fleet/tokens/single-use-token-0001", {
+        method: "PUT",
+        headers: { "Content-Type": "application/json", ...admin },
+        body: JSON.stringify({ devices: [], uses_left: 1 }),
+      });
+      expect((await enroll("single-use-token-0001", "f1:f2")).status).toBe(200);
+      expect((await enroll("single-use-token-0001", "f3:f4")).status).toBe(401);
+    });
+
+    it("should stop accepting revoked enrollment tokens", async () => {
+      await worker.fetch("/fleet/tokens/revoked-token-00001", {
+        method: "PUT",
+        headers: { "Content-Type": "application/json", ...admin },
+        body: JSON.stringify({ devices: [] }),
+      });
+      const del = await worker.fetch("/fleet/tokens/revoked-token-00001", {
+        method: "DELETE",
+        headers: admin,
+      });
+      expect(del.status).toBe(200);
+      expect((await enroll("revoked-token-00001", "g1:g2")).status).toBe(401);
+    });
+  });
+
+  describe("Mesh Status", () => {
+    const group = "0123456789abcdef0123456789abcdef";
+
+    /** Signs a mesh record the way a daemon does */
+    async function signedRecord(overrides: Record<string, unknown> = {}) {
+      const keys = (await webcrypto.subtle.generateKey({ name: "Ed25519" }, true, [
+        "sign",
+        "verify",
+      ])) as CryptoKeyPair;
+      const publicKey = new Uint8Array(await webcrypto.subtle.exportKey("raw", keys.publicKey));
+      const digest = new Uint8Array(await webcrypto.subtle.digest("SHA-256", publicKey));
+      const hex = [...digest.slice(0, 16)].map((b) => b.toString(16).padStart(2, "0")).join("");
+      const payload = JSON.stringify({
+        group,
+        device_id: hex.match(/.{4}/g)!.join(":"),
+        hostname: "nas",
+        online: true,
+        sessions: 1,
+        version: "0.1.0",
+        timestamp: Math.floor(Date.now() / 1000),
+        ttl_secs: 180,
+        ...overrides,
+      });
+      const signature = new Uint8Array(
+        await webcrypto.subtle.sign({ name: "Ed25519" }, keys.privateKey, new TextEncoder().encode(payload))
+      );
+      return {
+        payload,
+        public_key: Buffer.from(publicKey).toString("base64"),
+        signature: Buffer.from(signature).toString("base64"),
+      };
+    }
+
+    async function publish(record: unknown, to = group): Promise<Response> {
+      return worker.fetch(`/mesh/${to}`, {
+        method: "PUT",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(record),
+      });
+    }
+
+    it("should store and list signed records", async () => {
+      const record = await signedRecord();
+      expect((await publish(record)).status).toBe(200);
+
+      const res = await worker.fetch(`/mesh/${group}`);
+      expect(res.status).toBe(200);
+      expect(res.headers.get("Access-Control-Allow-Origin")).toBe("*");
+      const body = (await res.json()) as { hosts: unknown[] };
+      expect(body.hosts).toContainEqual(record);
+    });
+
+    it("should reject tampered records", async () => {
+      const record = await signedRecord();
+      record.payload = record.payload.replace('"sessions":1', '"sessions":9');
+      expect((await publish(record)).status).toBe(400);
+    });
+
+    it("should reject records for another group or with a stale timestamp", async () => {
+      const record = await signedRecord();
+      expect((await publish(record, "ffffffffffffffffffffffffffffffff")).status).toBe(400);
+
+      const stale = await signedRecord({ timestamp: Math.floor(Date.now() / 1000) - 3600 });
+      expect((await publish(stale)).status).toBe(400);
+    });
+
+    it("should reject malformed group names", async () => {
+      const res = await worker.fetch("/mesh/not-a-group");
+      expect(res.status).toBe(404);
+    });
+  });
+
   describe("HTTP Endpoints", () => {
     it("should return health check on /", async () => {
       const res = await worker.fetch("/");
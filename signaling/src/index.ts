@@ -1,18 +1,26 @@
 /**
  * Cloudflare Worker for WebRTC signaling
- * Routes WebSocket connections to Room Durable Objects
- * and handles pairing code registration/lookup via PairingStore
+ * Routes WebSocket connections to Room Durable Objects,
+ * handles pairing code registration/lookup via PairingStore,
+ * daemon fleet enrollment via FleetStore
+ * and mesh status records via MeshStore
  */
 
 import { Room } from "./room";
 import { PairingStore } from "./pairing";
+import { FleetStore } from "./fleet";
+import { MeshStore } from "./mesh";
 
 export interface Env {
   ROOM: DurableObjectNamespace;
   PAIRING: DurableObjectNamespace;
+  FLEET: DurableObjectNamespace;
+  MESH: DurableObjectNamespace;
   ROOM_TTL_SECONDS: string;
   RATE_LIMIT_MESSAGES_PER_SECOND: string;
   PAIRING_TTL_SECONDS: string;
+  /** Secret for managing enrollment tokens; fleet management is off when unset. */
+  FLEET_ADMIN_TOKEN?: string;
 }
 
 /** CORS headers applied to all responses */
@@ -22,6 +30,43 @@ function corsHeaders(): Record<string, string> {
   };
 }
 
+/** JSON response with CORS headers */
+function jsonResponse(body: unknown, status = 200): Response {
+  return new Response(JSON.stringify(body), {
+    status,
+    headers: { "Content-Type": "application/json", ...corsHeaders() },
+  });
+}
+
+/** Returns the bearer token of a request, if any */
+function bearerToken(request: Request): string | null {
+  const header = request.headers.get("Authorization") || "";
+  return header.startsWith("Bearer ") ? header.slice(7) : null;
+}
+
+/** Compares two strings in time independent of where they differ */
+function constantTimeEqual(a: string, b: string): boolean {
+  if (a.length !== b.length) {
+    return false;
+  }
+  let diff = 0;
+  for (let i = 0; i < a.length; i++) {
+    diff |= a.charCodeAt(i) ^ b.charCodeAt(i);
+  }
+  return diff === 0;
+}
+
+/** Forwards a request to the FleetStore DO */
+async function fleetFetch(env: Env, path: string, init: RequestInit): Promise<Response> {
+  const id = env.FLEET.idFromName("global");
+  const stub = env.FLEET.get(id);
+  const doResponse = await stub.fetch(new Request(`https://fleet${path}`, init));
+  return new Response(await doResponse.text(), {
+    status: doResponse.status,
+    headers: { "Content-Type": "application/json", ...corsHeaders() },
+  });
+}
+
 export default {
   async fetch(request: Request, env: Env, _ctx: ExecutionContext): Promise<Response> {
     const url = new URL(request.url);
@@ -32,8 +77,8 @@ export default {
       return new Response(null, {
         headers: {
           "Access-Control-Allow-Origin": "*",
-          "Access-Control-Allow-Methods": "GET, POST, OPTIONS",
-          "Access-Control-Allow-Headers": "Content-Type, Upgrade, Connection, Sec-WebSocket-Key, Sec-WebSocket-Version, Sec-WebSocket-Protocol",
+          "Access-Control-Allow-Methods": "GET, POST, PUT, DELETE, OPTIONS",
+          "Access-Control-Allow-Headers": "Content-Type, Authorization, Upgrade, Connection, Sec-WebSocket-Key, Sec-WebSocket-Version, Sec-WebSocket-Protocol",
         },
       });
     }
@@ -152,6 +197,124 @@ export default {
       });
     }
 
+    // POST /fleet/enroll — Enroll a daemon with an enrollment token
+    if (path === "/fleet/enroll" && request.method === "POST") {
+      const token = bearerToken(request);
+      if (!token) {
+        return jsonResponse({ error: "Missing enrollment token" }, 401);
+      }
+
+      let body: { device_id?: unknown; public_key?: unknown; hostname?: unknown; version?: unknown };
+      try {
+        body = await request.json();
+      } catch {
+        return jsonResponse({ error: "Invalid JSON body" }, 400);
+      }
+      if (typeof body.device_id !== "string" || typeof body.public_key !== "string") {
+        return jsonResponse({ error: "Invalid body: must contain device_id and public_key" }, 400);
+      }
+
+      return fleetFetch(env, "/enroll", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({
+          token,
+          daemon: {
+            device_id: body.device_id,
+            public_key: body.public_key,
+            hostname: typeof body.hostname === "string" ? body.hostname : "",
+            version: typeof body.version === "string" ? body.version : "",
+          },
+        }),
+      });
+    }
+
+    // Fleet management: PUT/DELETE /fleet/tokens/:token, GET /fleet/daemons
+    const fleetTokenMatch = path.match(/^\/fleet\/tokens\/([A-Za-z0-9_-]{16,128})$/);
+    if (fleetTokenMatch || path === "/fleet/daemons") {
+      const adminToken = env.FLEET_ADMIN_TOKEN;
+      if (!adminToken) {
+        return jsonResponse({ error: "Not found" }, 404);
+      }
+      const token = bearerToken(request);
+      if (!token || !constantTimeEqual(token, adminToken)) {
+        return jsonResponse({ error: "Unauthorized" }, 401);
+      }
+
+      if (path === "/fleet/daemons" && request.method === "GET") {
+        return fleetFetch(env, "/daemons", { method: "GET" });
+      }
+      if (fleetTokenMatch && request.method === "PUT") {
+        let body: { fleet?: unknown; prune?: unknown; devices?: unknown; uses_left?: unknown };
+        try {
+          body = await request.json();
+        } catch {
+          return jsonResponse({ error: "Invalid JSON body" }, 400);
+        }
+        if (!Array.isArray(body.devices)) {
+          return jsonResponse({ error: "Invalid body: devices must be an array" }, 400);
+        }
+        if (body.uses_left !== undefined && (typeof body.uses_left !== "number" || body.uses_left < 0)) {
+          return jsonResponse({ error: "Invalid body: uses_left must be a non-negative number" }, 400);
+        }
+        return fleetFetch(env, `/tokens/${fleetTokenMatch[1]}`, {
+          method: "PUT",
+          headers: { "Content-Type": "application/json" },
+          body: JSON.stringify({
+            fleet: typeof body.fleet === "string" ? body.fleet : undefined,
+            prune: body.prune === true,
+            devices: body.devices,
+            uses_left: body.uses_left,
+          }),
+        });
+      }
+      if (fleetTokenMatch && request.method === "DELETE") {
+        return fleetFetch(env, `/tokens/${fleetTokenMatch[1]}`, { method: "DELETE" });
+      }
+      return jsonResponse({ error: "Method not allowed" }, 405);
+    }
+
+    // Mesh status: PUT /mesh/:group publishes a signed record, GET lists them
+    const meshMatch = path.match(/^\/mesh\/([0-9a-f]{32})$/);
+    if (meshMatch && (request.method === "GET" || request.method === "PUT")) {
+      const group = meshMatch[1];
+      let body: string | undefined;
+      if (request.method === "PUT") {
+        try {
+          const record = (await request.json()) as Record<string, unknown>;
+          if (
+            typeof record.payload !== "string" ||
+            typeof record.public_key !== "string" ||
+            typeof record.signature !== "string" ||
+            record.payload.length > 4096
+          ) {
+            return jsonResponse({ error: "Invalid body: must contain payload, public_key and signature" }, 400);
+          }
+          body = JSON.stringify({
+            payload: record.payload,
+            public_key: record.public_key,
+            signature: record.signature,
+          });
+        } catch {
+          return jsonResponse({ error: "Invalid JSON body" }, 400);
+        }
+      }
+
+      const id = env.MESH.idFromName(group);
+      const stub = env.MESH.get(id);
+      const doResponse = await stub.fetch(
+        new Request(`https://mesh/${group}`, {
+          method: request.method,
+          headers: { "Content-Type": "application/json" },
+          body,
+        })
+      );
+      return new Response(await doResponse.text(), {
+        status: doResponse.status,
+        headers: { "Content-Type": "application/json", ...corsHeaders() },
+      });
+    }
+
     // Room WebSocket endpoint: GET /room/:id
     const roomMatch = path.match(/^\/room\/([a-zA-Z0-9_-]+)$/);
     if (roomMatch && request.method === "GET") {
@@ -199,4 +362,4 @@ export default {
 };
 
 // Export the Durable Object classes
-export { Room, PairingStore };
+export { Room, PairingStore, FleetStore, MeshStore };
@@ -0,0 +1,176 @@
+/**
+ * MeshStore Durable Object
+ *
+ * Holds the presence records of one mesh group: daemons that trust the same
+ * admin device. Each record is signed by the daemon that published it; the
+ * signature, the group and the claimed device ID are checked before a record
+ * is stored. Records expire after their TTL.
+ */
+
+/** A record as published by a daemon */
+export interface SignedMeshRecord {
+  payload: string;
+  public_key: string;
+  signature: string;
+}
+
+/** The signed part of a record */
+interface MeshRecord {
+  group: string;
+  device_id: string;
+  timestamp: number;
+  ttl_secs: number;
+}
+
+interface StoredRecord {
+  record: SignedMeshRecord;
+  expires: number;
+}
+
+/** Largest TTL a record may ask for, in seconds */
+const MAX_TTL_SECONDS = 3 * 3600;
+
+/** Allowed difference between a record's timestamp and the server's clock, in seconds */
+const MAX_CLOCK_SKEW_SECONDS = 300;
+
+function json(body: unknown, status = 200): Response {
+  return new Response(JSON.stringify(body), {
+    status,
+    headers: { "Content-Type": "application/json" },
+  });
+}
+
+function decodeBase64(value: string): Uint8Array {
+  return Uint8Array.from(atob(value), (c) => c.charCodeAt(0));
+}
+
+/** Formats a device ID like the daemon does: 8 groups of 4 hex digits */
+function fingerprint(deviceId: Uint8Array): string {
+  const hex = [...deviceId].map((b) => b.toString(16).padStart(2, "0")).join("");
+  return hex.match(/.{4}/g)!.join(":");
+}
+
+/**
+ * Checks a signed record for `group` and returns its signed part, or an
+ * error message
+ */
+export async function verifyMeshRecord(
+  signed: SignedMeshRecord,
+  group: string,
+  now: number
+): Promise<MeshRecord | string> {
+  let publicKey: Uint8Array;
+  let signature: Uint8Array;
+  let record: MeshRecord;
+  try {
+    publicKey = decodeBase64(signed.public_key);
+    signature = decodeBase64(signed.signature);
+    record = JSON.parse(signed.payload) as MeshRecord;
+  } catch {
+    return "Invalid record encoding";
+  }
+  if (publicKey.length !== 32 || signature.length !== 64) {
+    return "Invalid key or signature length";
+  }
+
+  const key = await crypto.subtle.importKey("raw", publicKey, { name: "Ed25519" }, false, ["verify"]);
+  const valid = await crypto.subtle.verify(
+    { name: "Ed25519" },
+    key,
+    signature,
+    new TextEncoder().encode(signed.payload)
+  );
+  if (!valid) {
+    return "Invalid signature";
+  }
+
+  // The device ID is the first 16 bytes of the SHA-256 of the public key
+  const digest = new Uint8Array(await crypto.subtle.digest("SHA-256", publicKey));
+  if (record.device_id !== fingerprint(digest.slice(0, 16))) {
+    return "Record signed by another key";
+  }
+  if (record.group !== group) {
+    return "Record belongs to another group";
+  }
+  if (typeof record.timestamp !== "number" || Math.abs(now - record.timestamp) > MAX_CLOCK_SKEW_SECONDS) {
+    return "Record timestamp is too far from the current time";
+  }
+  return record;
+}
+
+export class MeshStore implements DurableObject {
+  private state: DurableObjectState;
+
+  constructor(state: DurableObjectState, _env: unknown) {
+    this.state = state;
+  }
+
+  async fetch(request: Request): Promise<Response> {
+    const group = new URL(request.url).pathname.split("/").pop() || "";
+
+    if (request.method === "PUT") {
+      const signed = (await request.json()) as SignedMeshRecord;
+      const now = Math.floor(Date.now() / 1000);
+      const record = await verifyMeshRecord(signed, group, now);
+      if (typeof record === "string") {
+        return json({ error: record }, 400);
+      }
+
+      const key = `host:${record.device_id}`;
+      const previous = (await this.state.storage.get(key)) as string | undefined;
+      if (previous) {
+        // Keep the newest record when updates arrive out of order
+        const stored = JSON.parse(previous) as StoredRecord;
+        const storedRecord = JSON.parse(stored.record.payload) as MeshRecord;
+        if (storedRecord.timestamp > record.timestamp) {
+          return json({ ok: true });
+        }
+      }
+
+      const ttl = Math.min(Math.max(record.ttl_secs, 0), MAX_TTL_SECONDS);
+      const entry: StoredRecord = { record: signed, expires: Date.now() + ttl * 1000 };
+      await this.state.storage.put(key, JSON.stringify(entry));
+      await this.scheduleCleanup(entry.expires + 1000);
+      return json({ ok: true });
+    }
+
+    if (request.method === "GET") {
+      const entries = await this.state.storage.list({ prefix: "host:" });
+      const now = Date.now();
+      const hosts: SignedMeshRecord[] = [];
+      for (const raw of entries.values()) {
+        const entry = JSON.parse(raw as string) as StoredRecord;
+        if (now <= entry.expires) {
+          hosts.push(entry.record);
+        }
+      }
+      return json({ hosts });
+    }
+
+    return new Response("Method not allowed", { status: 405 });
+  }
+
+  private async scheduleCleanup(time: number): Promise<void> {
+    const current = await this.state.storage.getAlarm();
+    if (!current || current > time) {
+      await this.state.storage.setAlarm(time);
+    }
+  }
+
+  async alarm(): Promise<void> {
+    const entries = await this.state.storage.list({ prefix: "host:" });
+    const now = Date.now();
+    let next: number | null = null;
+    for (const [key, raw] of entries) {
+      const entry = JSON.parse(raw as string) as StoredRecord;
+      if (now > entry.expires) {
+        await this.state.storage.delete(key);
+      } else if (next === null || entry.expires < next) {
+        next = entry.expires;
+      }
+    }
+    if (next !== null) {
+      await this.state.storage.setAlarm(next + 1000);
+    }
+  }
+}